@@ -33,7 +33,7 @@
 //! `and rsp, 0xffff0000`.
 
 
-use {Avalue, Constraint, ProgramPoint};
+use {AbstractDomain, Constraint, ProgramPoint};
 
 use panopticon_core::{Operation, Rvalue, il};
 use std::borrow::Cow;
@@ -92,7 +92,7 @@ macro_rules! addrtrack_op {
     }}
 }
 
-impl Avalue for BoundedAddrTrack {
+impl AbstractDomain for BoundedAddrTrack {
     fn abstract_value(v: &Rvalue) -> Self {
         if let &Rvalue::Constant { ref value, ref size } = v {
             BoundedAddrTrack::Offset { region: None, offset: *value, offset_size: *size }
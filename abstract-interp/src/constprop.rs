@@ -0,0 +1,637 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Interprocedural constant propagation.
+//!
+//! Functions are analyzed bottom-up over the call graph. Each function is summarized by the
+//! registers it returns a constant in, the registers it restores before returning and the
+//! registers it overwrites. Calls to summarized functions apply the summary instead of forgetting
+//! everything, so a wrapper returning a fixed value makes an indirect jump or call through the
+//! returned register resolvable in the caller.
+//!
+//! Values are either constants or a register's value at the function's entry plus an offset,
+//! which tracks the stack pointer, spilled registers and pointers passed as arguments. Memory is
+//! only tracked relative to registers' entry values. Constants a callee stores through a pointer
+//! argument are part of its summary. The analysis assumes callees and stores through unknown pointers
+//! leave the caller's stack frame alone. Calls to unknown functions overwrite everything but the
+//! stack pointer and the callee-saved registers of the caller's calling convention.
+
+use ProgramPoint;
+use panopticon_core::{BasicBlock, ControlFlowRef, ControlFlowTarget, Function, Lvalue, Operation, Program, Rvalue, execute};
+use panopticon_core::il::lift;
+use panopticon_graph_algos::{BidirectionalGraphTrait, GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+/// Number of rounds over the call graph before recursive functions are given up on.
+const MAX_ROUNDS: usize = 4;
+
+/// Effect of calling a function on the caller's registers.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct Summary {
+    /// Registers holding the same constant at every return
+    pub returns: BTreeMap<Cow<'static, str>, u64>,
+    /// Registers written, but restored before returning
+    pub preserved: BTreeSet<Cow<'static, str>>,
+    /// Registers overwritten with unknown values
+    pub clobbered: BTreeSet<Cow<'static, str>>,
+    /// Constants written to memory at the entry value of a register plus an offset, like the
+    /// vtable pointer a constructor stores in its object. The values are the size in bits and the
+    /// constant.
+    pub stores: BTreeMap<(Cow<'static, str>, i64), (usize, u64)>,
+    /// True if the function calls unknown code. All registers not in `returns` and `preserved`
+    /// may be overwritten.
+    pub calls_unknown: bool,
+}
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+enum Value {
+    Constant(u64),
+    /// Value of a register at the function's entry plus an offset
+    Entry(Cow<'static, str>, i64),
+    Top,
+}
+
+impl Value {
+    fn add(&self, c: i64) -> Value {
+        match self {
+            &Value::Constant(v) => Value::Constant(v.wrapping_add(c as u64)),
+            &Value::Entry(ref r, o) => Value::Entry(r.clone(), o.wrapping_add(c)),
+            &Value::Top => Value::Top,
+        }
+    }
+}
+
+/// Registers and memory cells at one program point.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+struct Env {
+    /// Registers written. Registers that aren't have their entry value, or are unknown after a
+    /// call to unknown code.
+    registers: BTreeMap<Cow<'static, str>, Value>,
+    /// Memory cells at a register's entry value plus offset, with their size in bits
+    memory: BTreeMap<(Cow<'static, str>, i64), (usize, Value)>,
+    /// True after a call to unknown code
+    havoc: bool,
+}
+
+impl Env {
+    fn get(&self, reg: &Cow<'static, str>) -> Value {
+        match self.registers.get(reg) {
+            Some(v) => v.clone(),
+            None if self.havoc => Value::Top,
+            None => Value::Entry(reg.clone(), 0),
+        }
+    }
+
+    fn join(&self, other: &Env) -> Env {
+        let mut ret = Env { registers: BTreeMap::new(), memory: BTreeMap::new(), havoc: self.havoc || other.havoc };
+        let regs = self.registers.keys().chain(other.registers.keys()).cloned().collect::<BTreeSet<_>>();
+
+        for r in regs {
+            let (a, b) = (self.get(&r), other.get(&r));
+            ret.registers.insert(r, if a == b { a } else { Value::Top });
+        }
+        for (k, v) in self.memory.iter() {
+            if other.memory.get(k) == Some(v) {
+                ret.memory.insert(k.clone(), v.clone());
+            }
+        }
+
+        ret
+    }
+
+    /// Writes `value` to the cell at `base` plus `off`, removing all cells it overlaps.
+    fn store(&mut self, base: Cow<'static, str>, off: i64, bits: usize, value: Value) {
+        let bytes = (bits / 8) as i64;
+
+        self.memory.retain(|&(ref b, o), &mut (sz, _)| *b != base || o + (sz / 8) as i64 <= off || o >= off + bytes);
+        self.memory.insert((base, off), (bits, value));
+    }
+
+    /// Forgets everything except the registers in `keep`, which keep their current value.
+    fn clobber(&mut self, keep: &[&str]) {
+        let kept = keep.iter().map(|r| (Cow::Owned(r.to_string()), self.get(&Cow::Owned(r.to_string())))).collect::<Vec<_>>();
+
+        self.registers.clear();
+        self.registers.extend(kept);
+        self.havoc = true;
+    }
+}
+
+/// Bottom-up interprocedural constant propagation over a `Program`.
+pub struct ConstantPropagation<'a> {
+    program: &'a Program,
+    functions: HashMap<u64, &'a Function>,
+    summaries: HashMap<u64, Summary>,
+}
+
+impl<'a> ConstantPropagation<'a> {
+    /// Prepares the analysis of all functions in `program`.
+    pub fn new(program: &'a Program) -> ConstantPropagation<'a> {
+        let functions = program
+            .functions()
+            .filter(|f| if let Some(&ControlFlowTarget::Resolved(_)) = f.cfg().vertex_label(f.entry_point_ref()) { true } else { false })
+            .map(|f| (f.start(), f))
+            .collect();
+
+        ConstantPropagation { program: program, functions: functions, summaries: HashMap::new() }
+    }
+
+    /// Computes summaries for all functions, callees first.
+    pub fn analyze(&mut self) {
+        let order = self.bottom_up();
+
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+
+            for &start in order.iter() {
+                let func = self.functions[&start];
+                let summary = self.run(func).1.map(|exit| self.summarize(func, &exit));
+
+                match summary {
+                    Some(s) => {
+                        if self.summaries.get(&start) != Some(&s) {
+                            self.summaries.insert(start, s);
+                            changed = true;
+                        }
+                    }
+                    None => {
+                        changed |= self.summaries.remove(&start).is_some();
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Function starts in post order of the call graph.
+    fn bottom_up(&self) -> Vec<u64> {
+        let mut ret = vec![];
+        let mut seen = HashSet::new();
+        let mut roots = self.program.functions().map(|f| f.start()).filter(|s| self.functions.contains_key(s)).collect::<Vec<_>>();
+
+        roots.sort();
+
+        for root in roots {
+            let mut stack = vec![(root, false)];
+
+            while let Some((f, done)) = stack.pop() {
+                if done {
+                    ret.push(f);
+                } else if seen.insert(f) {
+                    stack.push((f, true));
+                    for callee in self.functions[&f].collect_call_addresses() {
+                        if self.functions.contains_key(&callee) && !seen.contains(&callee) {
+                            stack.push((callee, false));
+                        }
+                    }
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Summary of the function starting at `start`. None if it never returns or wasn't analyzed.
+    pub fn summary(&self, start: u64) -> Option<&Summary> {
+        self.summaries.get(&start)
+    }
+
+    /// Constant targets of the unresolved jumps in `func`.
+    pub fn jump_targets(&self, func: &Function) -> Vec<(ControlFlowRef, u64)> {
+        let (states, _) = self.run(func);
+        let cfg = func.cfg();
+        let mut ret = vec![];
+
+        for vx in cfg.vertices() {
+            let target = match cfg.vertex_label(vx) {
+                Some(&ControlFlowTarget::Unresolved(ref rv @ Rvalue::Variable { .. })) => rv,
+                _ => continue,
+            };
+            let mut value = None;
+
+            for e in cfg.in_edges(vx) {
+                let src = cfg.source(e);
+
+                if let (Some(&ControlFlowTarget::Resolved(ref bb)), Some(env)) = (cfg.vertex_label(src), states.get(&src)) {
+                    let mut env = env.clone();
+
+                    if self.block(func, bb, bb.statements().count(), &mut env) {
+                        let v = self.eval(target, &env);
+                        value = Some(match value {
+                            Some(ref old) if *old != v => Value::Top,
+                            _ => v,
+                        });
+                    }
+                }
+            }
+
+            if let Some(Value::Constant(c)) = value {
+                ret.push((vx, c));
+            }
+        }
+
+        ret
+    }
+
+    /// Constant targets of the calls through registers in `func`.
+    pub fn call_targets(&self, func: &Function) -> Vec<(ProgramPoint, u64)> {
+        self.operands(
+            func,
+            &|op| match op {
+                &Operation::Call(ref target @ Rvalue::Variable { .. }) => Some(target),
+                _ => None,
+            },
+        )
+    }
+
+    /// Constant addresses of the loads in `func`.
+    pub fn load_addresses(&self, func: &Function) -> Vec<(ProgramPoint, u64)> {
+        self.operands(
+            func,
+            &|op| match op {
+                &Operation::Load(_, _, _, ref addr) => Some(addr),
+                _ => None,
+            },
+        )
+    }
+
+    /// Evaluates the operand selected by `select` of every statement in `func`. Returns the
+    /// constant ones.
+    fn operands(&self, func: &Function, select: &Fn(&Operation<Rvalue>) -> Option<&Rvalue>) -> Vec<(ProgramPoint, u64)> {
+        let (states, _) = self.run(func);
+        let cfg = func.cfg();
+        let mut ret = vec![];
+
+        for vx in cfg.vertices() {
+            if let (Some(&ControlFlowTarget::Resolved(ref bb)), Some(env)) = (cfg.vertex_label(vx), states.get(&vx)) {
+                for (pos, stmt) in bb.statements().enumerate() {
+                    if let Some(rv) = select(&stmt.op) {
+                        let mut env = env.clone();
+
+                        if self.block(func, bb, pos, &mut env) {
+                            if let Value::Constant(c) = self.eval(rv, &env) {
+                                ret.push((ProgramPoint { address: bb.area.start, position: pos }, c));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Runs `func` to a fixed point. Returns the state at the start of every basic block and the
+    /// join of the states at all returns.
+    fn run(&self, func: &Function) -> (HashMap<ControlFlowRef, Env>, Option<Env>) {
+        let cfg = func.cfg();
+        let mut states = HashMap::new();
+        let mut queue = VecDeque::new();
+        let mut exit: Option<Env> = None;
+
+        states.insert(func.entry_point_ref(), Env::default());
+        queue.push_back(func.entry_point_ref());
+
+        while let Some(vx) = queue.pop_front() {
+            let bb = match cfg.vertex_label(vx) {
+                Some(&ControlFlowTarget::Resolved(ref bb)) => bb,
+                _ => continue,
+            };
+            let mut env = states[&vx].clone();
+
+            if !self.block(func, bb, bb.statements().count(), &mut env) {
+                continue;
+            }
+
+            for e in cfg.out_edges(vx) {
+                let next = cfg.target(e);
+                let new = match states.get(&next) {
+                    Some(old) => old.join(&env),
+                    None => env.clone(),
+                };
+
+                if states.get(&next) != Some(&new) {
+                    states.insert(next, new);
+                    if !queue.contains(&next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        for (&vx, state) in states.iter() {
+            if let (Some(&ControlFlowTarget::Resolved(ref bb)), 0) = (cfg.vertex_label(vx), cfg.out_degree(vx)) {
+                let mut env = state.clone();
+
+                if self.block(func, bb, bb.statements().count(), &mut env) {
+                    exit = Some(match exit {
+                        Some(e) => e.join(&env),
+                        None => env,
+                    });
+                }
+            }
+        }
+
+        (states, exit)
+    }
+
+    /// Executes the first `count` statements of `bb`. Returns false if a callee never returns.
+    fn block(&self, func: &Function, bb: &BasicBlock, count: usize, env: &mut Env) -> bool {
+        for stmt in bb.statements().take(count) {
+            let value = match stmt.op {
+                Operation::Phi(_) => continue,
+                Operation::Call(ref target) => {
+                    if !self.call(func, target, env) {
+                        return false;
+                    }
+                    continue;
+                }
+                Operation::Store(_, _, bits, ref addr, ref val) => {
+                    let val = self.eval(val, env);
+
+                    match self.eval(addr, env) {
+                        Value::Entry(base, off) => env.store(base, off, bits, val),
+                        Value::Constant(_) => {}
+                        Value::Top => {
+                            let sp = self.stack_pointer(func);
+                            env.memory.retain(|&(ref b, _), _| Some(b.as_ref()) == sp);
+                        }
+                    }
+                    continue;
+                }
+                Operation::Load(_, _, bits, ref addr) => {
+                    match self.eval(addr, env) {
+                        Value::Entry(base, off) => {
+                            match env.memory.get(&(base, off)) {
+                                Some(&(sz, ref v)) if sz == bits => v.clone(),
+                                _ => Value::Top,
+                            }
+                        }
+                        _ => Value::Top,
+                    }
+                }
+                Operation::Move(ref a) => self.eval(a, env),
+                Operation::Add(ref a, ref b) => {
+                    match (self.eval(a, env), self.eval(b, env)) {
+                        (Value::Entry(r, o), Value::Constant(c)) | (Value::Constant(c), Value::Entry(r, o)) => {
+                            Value::Entry(r, o).add(sign_extend(c, b.size().unwrap_or(64)))
+                        }
+                        _ => self.fold(&stmt.op, env),
+                    }
+                }
+                Operation::Subtract(ref a, ref b) => {
+                    match (self.eval(a, env), self.eval(b, env)) {
+                        (Value::Entry(r, o), Value::Constant(c)) => Value::Entry(r, o).add(sign_extend(c, b.size().unwrap_or(64)).wrapping_neg()),
+                        _ => self.fold(&stmt.op, env),
+                    }
+                }
+                ref op => self.fold(op, env),
+            };
+
+            if let Lvalue::Variable { ref name, size, .. } = stmt.assignee {
+                let value = match value {
+                    Value::Constant(c) => Value::Constant(mask(c, size)),
+                    v => v,
+                };
+                env.registers.insert(name.clone(), value);
+            }
+        }
+
+        true
+    }
+
+    /// Folds `op` if all its operands are constant.
+    fn fold(&self, op: &Operation<Rvalue>, env: &Env) -> Value {
+        let op = lift(
+            op,
+            &|rv: &Rvalue| match self.eval(rv, env) {
+                Value::Constant(c) => Rvalue::Constant { value: c, size: rv.size().unwrap_or(64) },
+                _ => Rvalue::Undefined,
+            },
+        );
+
+        if op.operands().iter().any(|rv| **rv == Rvalue::Undefined) {
+            return Value::Top;
+        }
+
+        match execute(op) {
+            Rvalue::Constant { value, .. } => Value::Constant(value),
+            _ => Value::Top,
+        }
+    }
+
+    fn eval(&self, rv: &Rvalue, env: &Env) -> Value {
+        match rv {
+            &Rvalue::Constant { value, size } => Value::Constant(mask(value, size)),
+            &Rvalue::Variable { ref name, offset, size, .. } => {
+                match env.get(name) {
+                    Value::Constant(c) if offset < 64 => Value::Constant(mask(c >> offset, size)),
+                    v @ Value::Entry(..) if offset == 0 => v,
+                    _ => Value::Top,
+                }
+            }
+            &Rvalue::Undefined => Value::Top,
+        }
+    }
+
+    /// Applies the summary of the callee. Returns false if it never returns.
+    fn call(&self, func: &Function, target: &Rvalue, env: &mut Env) -> bool {
+        let summary = match self.eval(target, env) {
+            Value::Constant(c) if self.functions.contains_key(&c) => {
+                match self.summaries.get(&c) {
+                    Some(s) => s,
+                    None => return false,
+                }
+            }
+            _ => {
+                let mut keep = func.calling_convention().map(|cc| cc.callee_saved().to_vec()).unwrap_or(vec![]);
+                keep.extend(self.stack_pointer(func));
+                env.clobber(&keep);
+                return true;
+            }
+        };
+
+        let stores = summary
+            .stores
+            .iter()
+            .filter_map(
+                |(&(ref r, off), &(bits, c))| match env.get(r) {
+                    Value::Entry(base, o) => Some((base, o.wrapping_add(off), bits, c)),
+                    _ => None,
+                }
+            )
+            .collect::<Vec<_>>();
+
+        if summary.calls_unknown {
+            let keep = summary.preserved.iter().map(|r| r.as_ref()).chain(self.stack_pointer(func)).collect::<Vec<_>>();
+            env.clobber(&keep);
+        }
+        for r in summary.clobbered.iter() {
+            env.registers.insert(r.clone(), Value::Top);
+        }
+        for (r, &c) in summary.returns.iter() {
+            env.registers.insert(r.clone(), Value::Constant(c));
+        }
+        for (base, off, bits, c) in stores {
+            env.store(base, off, bits, Value::Constant(c));
+        }
+
+        true
+    }
+
+    /// Summarizes `func` given the state at its returns.
+    fn summarize(&self, func: &Function, exit: &Env) -> Summary {
+        let mut ret = Summary { calls_unknown: exit.havoc, ..Summary::default() };
+        let sp = self.stack_pointer(func);
+
+        for (r, v) in exit.registers.iter() {
+            match v {
+                &Value::Constant(c) => {
+                    ret.returns.insert(r.clone(), c);
+                }
+                &Value::Entry(ref q, 0) if q == r => {
+                    ret.preserved.insert(r.clone());
+                }
+                _ if Some(r.as_ref()) == sp => {}
+                _ => {
+                    ret.clobbered.insert(r.clone());
+                }
+            }
+        }
+
+        for (&(ref base, off), &(bits, ref v)) in exit.memory.iter() {
+            if let (&Value::Constant(c), false) = (v, Some(base.as_ref()) == sp) {
+                ret.stores.insert((base.clone(), off), (bits, c));
+            }
+        }
+
+        ret
+    }
+
+    /// Name of the stack pointer register of `func`.
+    fn stack_pointer(&self, func: &Function) -> Option<&'static str> {
+        func.calling_convention().map(|cc| cc.stack_pointer())
+    }
+}
+
+fn mask(value: u64, size: usize) -> u64 {
+    if size >= 64 { value } else { value & ((1u64 << size) - 1) }
+}
+
+fn sign_extend(value: u64, size: usize) -> i64 {
+    if size == 0 || size >= 64 {
+        value as i64
+    } else {
+        let shift = 64 - size;
+        ((value << shift) as i64) >> shift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{CallingConvention, ControlFlowGraph, Endianess, Guard, Mnemonic, Region, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn var(name: &'static str) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: 64, subscript: None }
+    }
+
+    fn block(start: u64, stmts: Vec<Statement>) -> ControlFlowTarget {
+        let mne = Mnemonic::new(start..start + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne]))
+    }
+
+    fn function(blocks: Vec<ControlFlowTarget>) -> Function {
+        let mut cfg = ControlFlowGraph::new();
+        let vxs = blocks.into_iter().map(|b| cfg.add_vertex(b)).collect::<Vec<_>>();
+
+        for w in vxs.windows(2) {
+            cfg.add_edge(Guard::always(), w[0], w[1]);
+        }
+
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), 0x1000), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vxs[0]);
+        func.set_calling_convention(Some(CallingConvention::SysV64));
+        func
+    }
+
+    /*
+     * get_handler: push rbx; rbx = 0; rax = 0x400; pop rbx
+     * main:        rbx = 7; call rbx; call get_handler; jmp rax
+     */
+    #[test]
+    fn wrapper_returns_constant() {
+        let rsp = var("RSP");
+        let getter = function(
+            vec![
+                block(
+                    0x100,
+                    vec![
+                        Statement { op: Operation::Subtract(rsp.clone().into(), Rvalue::new_u64(8)), assignee: rsp.clone() },
+                        Statement {
+                            op: Operation::Store(Cow::Borrowed("RAM"), Endianess::Little, 64, rsp.clone().into(), var("RBX").into()),
+                            assignee: Lvalue::Undefined,
+                        },
+                        Statement { op: Operation::Move(Rvalue::new_u64(0)), assignee: var("RBX") },
+                        Statement { op: Operation::Move(Rvalue::new_u64(0x400)), assignee: var("RAX") },
+                        Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 64, rsp.clone().into()), assignee: var("RBX") },
+                        Statement { op: Operation::Add(rsp.clone().into(), Rvalue::new_u64(8)), assignee: rsp.clone() },
+                    ]
+                ),
+            ]
+        );
+        let main = function(
+            vec![
+                block(
+                    0,
+                    vec![
+                        Statement { op: Operation::Move(Rvalue::new_u64(7)), assignee: var("RBX") },
+                        Statement { op: Operation::Call(var("RBX").into()), assignee: Lvalue::Undefined },
+                        Statement { op: Operation::Call(Rvalue::new_u64(0x100)), assignee: Lvalue::Undefined },
+                    ]
+                ),
+                ControlFlowTarget::Unresolved(var("RAX").into()),
+            ]
+        );
+        let mut prog = Program::new("prog");
+
+        prog.insert(main.clone());
+        prog.insert(getter.clone());
+
+        let mut cp = ConstantPropagation::new(&prog);
+        cp.analyze();
+
+        let summary = cp.summary(0x100).unwrap();
+        assert_eq!(summary.returns.get("RAX"), Some(&0x400));
+        assert!(summary.preserved.contains("RBX"));
+        assert!(!summary.calls_unknown);
+
+        let jumps = cp.jump_targets(&main);
+        assert_eq!(jumps.len(), 1);
+        assert_eq!(jumps[0].1, 0x400);
+
+        let calls = cp.call_targets(&main);
+        assert_eq!(calls, vec![(ProgramPoint { address: 0, position: 1 }, 7)]);
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Indirect call resolution.
+//!
+//! `Program::insert` only knows the targets of calls to constants. Calls through registers and
+//! memory end in a single `Todo` node for the call operand. [`resolve_indirect_calls`] runs the
+//! value-set analysis over the whole program and adds an edge for every possible target of each
+//! such call, e.g. all functions of a table of callbacks. The edges are recorded in
+//! `Program::analyzed_calls` as they are only as precise as the analysis.
+//!
+//! [`resolve_indirect_calls`]: fn.resolve_indirect_calls.html
+
+use {ProgramPoint, Vsa};
+use panopticon_core::{Bound, ControlFlowTarget, Program, Region};
+use panopticon_graph_algos::GraphTrait;
+
+/// Call with a non-constant operand and its possible targets.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct IndirectCall {
+    /// Start of the calling function
+    pub function: u64,
+    /// Call statement
+    pub point: ProgramPoint,
+    /// Possible targets
+    pub targets: Vec<u64>,
+}
+
+/// Resolves the indirect calls in all functions of `program` and adds their targets to the call
+/// graph. `stack_pointer` and `read_only` are passed to `Vsa`. Targets outside of `region` are
+/// ignored.
+pub fn resolve_indirect_calls(program: &mut Program, region: &Region, stack_pointer: &str, read_only: &[Bound]) -> Vec<IndirectCall> {
+    let mut calls = vec![];
+
+    {
+        let mut vsa = read_only.iter().fold(Vsa::new(program, region, stack_pointer), |vsa, b| vsa.read_only(b.clone()));
+        vsa.analyze();
+
+        for func in program.functions() {
+            match func.cfg().vertex_label(func.entry_point_ref()) {
+                Some(&ControlFlowTarget::Resolved(_)) => {}
+                _ => continue,
+            }
+
+            for (point, targets) in vsa.call_targets(func) {
+                let targets = targets.into_iter().filter(|&t| t < region.size()).collect::<Vec<_>>();
+
+                if !targets.is_empty() {
+                    calls.push((func.uuid().clone(), IndirectCall { function: func.start(), point: point, targets: targets }));
+                }
+            }
+        }
+    }
+
+    calls
+        .into_iter()
+        .map(
+            |(uuid, call)| {
+                program.insert_analyzed_calls(&uuid, &call.targets);
+                call
+            }
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, ControlFlowGraph, Function, Guard, Lvalue, Mnemonic, Operation, Rvalue, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn block(start: u64, stmts: Vec<Statement>) -> ControlFlowTarget {
+        let mne = Mnemonic::new(start..start + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne]))
+    }
+
+    /*
+     * main:   if flag { tgt = 0x100 } else { tgt = 0x200 }; call tgt
+     * 0x100:  ret
+     */
+    #[test]
+    fn callback() {
+        let region = Region::undefined("ram".to_owned(), 0x1000);
+        let flag = Lvalue::Variable { name: Cow::Borrowed("flag"), size: 1, subscript: None };
+        let tgt = Lvalue::Variable { name: Cow::Borrowed("tgt"), size: 64, subscript: None };
+        let g = Guard::from_flag(&flag.clone().into()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(block(0, vec![]));
+        let b1 = cfg.add_vertex(block(1, vec![Statement { op: Operation::Move(Rvalue::new_u64(0x100)), assignee: tgt.clone() }]));
+        let b2 = cfg.add_vertex(block(2, vec![Statement { op: Operation::Move(Rvalue::new_u64(0x200)), assignee: tgt.clone() }]));
+        let b3 = cfg.add_vertex(block(3, vec![Statement { op: Operation::Call(tgt.clone().into()), assignee: Lvalue::Undefined }]));
+
+        cfg.add_edge(g.clone(), b0, b1);
+        cfg.add_edge(g.negation(), b0, b2);
+        cfg.add_edge(Guard::always(), b1, b3);
+        cfg.add_edge(Guard::always(), b2, b3);
+
+        let mut main = Function::undefined(0, None, &region, None);
+        *main.cfg_mut() = cfg;
+        main.set_entry_point_ref(b0);
+
+        let mut callee = Function::undefined(0x100, None, &region, None);
+        let vx = callee.cfg_mut().add_vertex(block(0x100, vec![]));
+        callee.set_entry_point_ref(vx);
+
+        let mut prog = Program::new("prog");
+        let caller = main.uuid().clone();
+        let callee_uuid = callee.uuid().clone();
+
+        prog.insert(main);
+        prog.insert(callee);
+
+        let calls = resolve_indirect_calls(&mut prog, &region, "RSP", &[]);
+
+        assert_eq!(calls, vec![IndirectCall { function: 0, point: ProgramPoint { address: 3, position: 0 }, targets: vec![0x100, 0x200] }]);
+        assert!(prog.is_analyzed_call(&caller, &callee_uuid));
+        assert_eq!(prog.analyzed_calls.len(), 2);
+    }
+}
@@ -51,7 +51,10 @@ pub struct ProgramPoint {
 }
 
 /// Abstract Domain. Models both under- and over-approximation.
-pub trait Avalue: Clone + PartialEq + Eq + Hash + Debug + Serialize + for<'a> Deserialize<'a> {
+///
+/// Implement this trait to analyze functions with a new domain using `approximate`. `Kset`,
+/// `Interval`, `Sign` and `Taint` are the domains included.
+pub trait AbstractDomain: Clone + PartialEq + Eq + Hash + Debug + Serialize + for<'a> Deserialize<'a> {
     /// Alpha function. Returns domain element that approximates the concrete value the best
     fn abstract_value(&Rvalue) -> Self;
     /// Alpha function. Returns domain element that approximates the concrete value that fullfil
@@ -63,6 +66,11 @@ pub trait Avalue: Clone + PartialEq + Eq + Hash + Debug + Serialize + for<'a> De
     fn narrow(&self, &Self) -> Self;
     /// Widens `self` with the argument.
     fn widen(&self, other: &Self) -> Self;
+    /// Widens `self` with the argument, stopping growing bounds at the nearest threshold instead
+    /// of going to the extremes right away. Domains without numeric bounds ignore the thresholds.
+    fn widen_with_thresholds(&self, other: &Self, _thresholds: &WideningThresholds) -> Self {
+        self.widen(other)
+    }
     /// Computes the lowest upper bound of self and the argument.
     fn combine(&self, &Self) -> Self;
     /// Returns true if `self` <= `other`.
@@ -73,26 +81,108 @@ pub trait Avalue: Clone + PartialEq + Eq + Hash + Debug + Serialize + for<'a> De
     fn extract(&self, size: usize, offset: usize) -> Self;
 }
 
+/// Integers widening stops at before moving a bound to the extremes.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct WideningThresholds {
+    values: Vec<i64>,
+}
+
+impl WideningThresholds {
+    /// Returns the thresholds `values`. An empty set of thresholds is classic widening.
+    pub fn new<I: IntoIterator<Item = i64>>(values: I) -> WideningThresholds {
+        let mut values = values.into_iter().collect::<Vec<_>>();
+
+        values.sort();
+        values.dedup();
+        WideningThresholds { values: values }
+    }
+
+    /// Returns the constants `func` compares variables with and their neighbours. These are the
+    /// usual loop bounds.
+    pub fn from_function(func: &Function) -> WideningThresholds {
+        let mut values = vec![];
+
+        for op in flag_operations(func).values() {
+            match op {
+                &Operation::Equal(ref a, ref b) |
+                &Operation::LessUnsigned(ref a, ref b) |
+                &Operation::LessSigned(ref a, ref b) |
+                &Operation::LessOrEqualUnsigned(ref a, ref b) |
+                &Operation::LessOrEqualSigned(ref a, ref b) => {
+                    for rv in [a, b].iter() {
+                        if let &&Rvalue::Constant { value, size } = rv {
+                            let value = if size < 64 && size > 0 && value & (1 << (size - 1)) != 0 {
+                                (value | !((1u64 << size) - 1)) as i64
+                            } else {
+                                value as i64
+                            };
+
+                            values.extend(value.checked_sub(1));
+                            values.push(value);
+                            values.extend(value.checked_add(1));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        WideningThresholds::new(values)
+    }
+
+    /// Returns true if there are no thresholds.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Smallest threshold larger than or equal to `value`.
+    pub fn above(&self, value: i64) -> Option<i64> {
+        match self.values.binary_search(&value) {
+            Ok(i) => Some(self.values[i]),
+            Err(i) => self.values.get(i).cloned(),
+        }
+    }
+
+    /// Largest threshold less than or equal to `value`.
+    pub fn below(&self, value: i64) -> Option<i64> {
+        match self.values.binary_search(&value) {
+            Ok(i) => Some(self.values[i]),
+            Err(0) => None,
+            Err(i) => Some(self.values[i - 1]),
+        }
+    }
+}
+
 /// Does an abstract interpretation of `func` using the abstract domain `A`. The function uses a
 /// fixed point iteration and the widening strategy outlined in
 /// Bourdoncle: "Efficient chaotic iteration strategies with widenings".
-pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str>, usize), A>) -> Result<HashMap<Lvalue, A>> {
+pub fn approximate<A: AbstractDomain>(func: &Function, fixed: &HashMap<(Cow<'static, str>, usize), A>) -> Result<HashMap<Lvalue, A>> {
+    approximate_with(func, fixed, &WideningThresholds::default())
+}
+
+/// Like `approximate`, but widening stops at `thresholds` first.
+pub fn approximate_with<A: AbstractDomain>(
+    func: &Function,
+    fixed: &HashMap<(Cow<'static, str>, usize), A>,
+    thresholds: &WideningThresholds,
+) -> Result<HashMap<Lvalue, A>> {
     let wto = weak_topo_order(func.entry_point_ref(), func.cfg());
     let edge_ops = flag_operations(func);
-    fn stabilize<A: Avalue>(
+    fn stabilize<A: AbstractDomain>(
         h: &Vec<Box<HierarchicalOrdering<ControlFlowRef>>>,
         graph: &ControlFlowGraph,
         constr: &HashMap<Lvalue, A>,
         sizes: &HashMap<Cow<'static, str>, usize>,
         ret: &mut HashMap<(Cow<'static, str>, usize), A>,
         fixed: &HashMap<(Cow<'static, str>, usize), A>,
+        thresholds: &WideningThresholds,
     ) -> Result<()> {
         let mut stable = true;
         let mut iter_cnt = 0;
         let head = if let Some(h) = h.first() {
             match &**h {
                 &HierarchicalOrdering::Element(ref vx) => vx.clone(),
-                &HierarchicalOrdering::Component(ref vec) => return stabilize(vec, graph, constr, sizes, ret, fixed),
+                &HierarchicalOrdering::Component(ref vec) => return stabilize(vec, graph, constr, sizes, ret, fixed, thresholds),
             }
         } else {
             return Ok(());
@@ -110,10 +200,11 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
                             sizes,
                             ret,
                             fixed,
+                            thresholds,
                         )?
                     }
                     &HierarchicalOrdering::Component(ref vec) => {
-                        stabilize(&*vec, graph, constr, sizes, ret, fixed)?;
+                        stabilize(&*vec, graph, constr, sizes, ret, fixed, thresholds)?;
                         stable = true;
                     }
                 }
@@ -139,7 +230,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
             iter_cnt += 1;
         }
     }
-    fn execute<A: Avalue>(
+    fn execute<A: AbstractDomain>(
         t: ControlFlowRef,
         do_widen: bool,
         graph: &ControlFlowGraph,
@@ -147,6 +238,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
         sizes: &HashMap<Cow<'static, str>, usize>,
         ret: &mut HashMap<(Cow<'static, str>, usize), A>,
         fixed: &HashMap<(Cow<'static, str>, usize), A>,
+        thresholds: &WideningThresholds,
     ) -> Result<bool> {
         if let Some(&ControlFlowTarget::Resolved(ref bb)) = graph.vertex_label(t) {
             let mut change = false;
@@ -168,7 +260,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
 
                         if let Some(cur) = cur {
                             if do_widen {
-                                let w = cur.widen(&new);
+                                let w = cur.widen_with_thresholds(&new, thresholds);
 
                                 debug!("    widen to {:?}", w);
 
@@ -200,7 +292,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
             Ok(false)
         }
     }
-    fn res<A: Avalue>(
+    fn res<A: AbstractDomain>(
         v: &Rvalue,
         sizes: &HashMap<Cow<'static, str>, usize>,
         env: &HashMap<(Cow<'static, str>, usize), A>,
@@ -312,7 +404,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
 
     match wto {
         HierarchicalOrdering::Component(ref v) => {
-            stabilize(v, &func.cfg(), &constr, &sizes, &mut ret, fixed)?;
+            stabilize(v, &func.cfg(), &constr, &sizes, &mut ret, fixed, thresholds)?;
         }
         HierarchicalOrdering::Element(ref v) => {
             execute(
@@ -323,6 +415,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
                 &sizes,
                 &mut ret,
                 fixed,
+                thresholds,
             )?;
         }
     }
@@ -347,7 +440,7 @@ pub fn approximate<A: Avalue>(func: &Function, fixed: &HashMap<(Cow<'static, str
 
 /// Given a function and an abstract interpretation result this functions returns that variable
 /// names and abstract values that live after the function returns.
-pub fn results<A: Avalue>(func: &Function, vals: &HashMap<Lvalue, A>) -> HashMap<(Cow<'static, str>, usize), A> {
+pub fn results<A: AbstractDomain>(func: &Function, vals: &HashMap<Lvalue, A>) -> HashMap<(Cow<'static, str>, usize), A> {
     let cfg = func.cfg();
     let idom = immediate_dominator(func.entry_point_ref(), cfg);
     let mut ret = HashMap::<(Cow<'static, str>, usize), A>::new();
@@ -452,186 +545,9 @@ mod tests {
     use panopticon_core::{BasicBlock, Bound, ControlFlowGraph, ControlFlowTarget, Function, Guard, Lvalue, Mnemonic, Operation, Region, Rvalue, Statement};
     use panopticon_data_flow::ssa_convertion;
     use panopticon_graph_algos::MutableGraphTrait;
+    use sign::Sign;
     use std::borrow::Cow;
 
-    #[derive(Debug,Clone,PartialEq,Eq,Hash,Serialize,Deserialize)]
-    enum Sign {
-        Join,
-        Positive,
-        Negative,
-        Zero,
-        Meet,
-    }
-
-    impl Avalue for Sign {
-        fn abstract_value(v: &Rvalue) -> Self {
-            match v {
-                &Rvalue::Constant { value: c, .. } if c > 0 => Sign::Positive,
-                &Rvalue::Constant { value: 0, .. } => Sign::Zero,
-                _ => Sign::Join,
-            }
-        }
-
-        fn abstract_constraint(c: &Constraint) -> Self {
-            match c {
-                &Constraint::Equal(Rvalue::Constant { value: 0, .. }) => Sign::Zero,
-                &Constraint::LessUnsigned(Rvalue::Constant { value: 1, .. }) => Sign::Zero,
-                &Constraint::LessOrEqualUnsigned(Rvalue::Constant { value: 0, .. }) => Sign::Zero,
-                &Constraint::LessSigned(Rvalue::Constant { value: 0, .. }) => Sign::Negative,
-                &Constraint::LessOrEqualSigned(Rvalue::Constant { value: v, size: s }) if s <= 64 && v & (1 << (s - 1)) != 0 => Sign::Negative,
-                &Constraint::LessSigned(Rvalue::Constant { value: v, size: s }) if s <= 64 && v & (1 << (s - 1)) != 0 => Sign::Negative,
-                _ => Sign::Join,
-            }
-        }
-
-        fn execute(_: &ProgramPoint, op: &Operation<Self>) -> Self {
-            match op {
-                &Operation::Add(Sign::Positive, Sign::Positive) => Sign::Positive,
-                &Operation::Add(Sign::Positive, Sign::Zero) => Sign::Positive,
-                &Operation::Add(Sign::Zero, Sign::Positive) => Sign::Positive,
-                &Operation::Add(Sign::Negative, Sign::Negative) => Sign::Negative,
-                &Operation::Add(Sign::Negative, Sign::Zero) => Sign::Negative,
-                &Operation::Add(Sign::Zero, Sign::Negative) => Sign::Negative,
-                &Operation::Add(Sign::Positive, Sign::Negative) => Sign::Join,
-                &Operation::Add(Sign::Negative, Sign::Positive) => Sign::Join,
-                &Operation::Add(_, Sign::Join) => Sign::Join,
-                &Operation::Add(Sign::Join, _) => Sign::Join,
-                &Operation::Add(ref a, Sign::Meet) => a.clone(),
-                &Operation::Add(Sign::Meet, ref b) => b.clone(),
-
-                &Operation::Subtract(Sign::Positive, Sign::Positive) => Sign::Join,
-                &Operation::Subtract(Sign::Positive, Sign::Zero) => Sign::Positive,
-                &Operation::Subtract(Sign::Zero, Sign::Positive) => Sign::Negative,
-                &Operation::Subtract(Sign::Negative, Sign::Negative) => Sign::Join,
-                &Operation::Subtract(Sign::Negative, Sign::Zero) => Sign::Negative,
-                &Operation::Subtract(Sign::Zero, Sign::Negative) => Sign::Positive,
-                &Operation::Subtract(Sign::Positive, Sign::Negative) => Sign::Positive,
-                &Operation::Subtract(Sign::Negative, Sign::Positive) => Sign::Negative,
-                &Operation::Subtract(_, Sign::Join) => Sign::Join,
-                &Operation::Subtract(Sign::Join, _) => Sign::Join,
-                &Operation::Subtract(ref a, Sign::Meet) => a.clone(),
-                &Operation::Subtract(Sign::Meet, ref b) => b.clone(),
-
-                &Operation::Multiply(Sign::Positive, Sign::Positive) => Sign::Positive,
-                &Operation::Multiply(Sign::Negative, Sign::Negative) => Sign::Positive,
-                &Operation::Multiply(Sign::Positive, Sign::Negative) => Sign::Negative,
-                &Operation::Multiply(Sign::Negative, Sign::Positive) => Sign::Negative,
-                &Operation::Multiply(_, Sign::Zero) => Sign::Zero,
-                &Operation::Multiply(Sign::Zero, _) => Sign::Zero,
-                &Operation::Multiply(_, Sign::Join) => Sign::Join,
-                &Operation::Multiply(Sign::Join, _) => Sign::Join,
-                &Operation::Multiply(ref a, Sign::Meet) => a.clone(),
-                &Operation::Multiply(Sign::Meet, ref b) => b.clone(),
-
-                &Operation::DivideSigned(Sign::Positive, Sign::Positive) => Sign::Positive,
-                &Operation::DivideSigned(Sign::Negative, Sign::Negative) => Sign::Positive,
-                &Operation::DivideSigned(Sign::Positive, Sign::Negative) => Sign::Negative,
-                &Operation::DivideSigned(Sign::Negative, Sign::Positive) => Sign::Negative,
-                &Operation::DivideSigned(_, Sign::Zero) => Sign::Zero,
-                &Operation::DivideSigned(Sign::Zero, _) => Sign::Zero,
-                &Operation::DivideSigned(_, Sign::Join) => Sign::Join,
-                &Operation::DivideSigned(Sign::Join, _) => Sign::Join,
-                &Operation::DivideSigned(ref a, Sign::Meet) => a.clone(),
-                &Operation::DivideSigned(Sign::Meet, ref b) => b.clone(),
-
-                &Operation::DivideUnsigned(Sign::Positive, Sign::Positive) => Sign::Positive,
-                &Operation::DivideUnsigned(Sign::Negative, Sign::Negative) => Sign::Positive,
-                &Operation::DivideUnsigned(Sign::Positive, Sign::Negative) => Sign::Negative,
-                &Operation::DivideUnsigned(Sign::Negative, Sign::Positive) => Sign::Negative,
-                &Operation::DivideUnsigned(_, Sign::Zero) => Sign::Zero,
-                &Operation::DivideUnsigned(Sign::Zero, _) => Sign::Zero,
-                &Operation::DivideUnsigned(_, Sign::Join) => Sign::Join,
-                &Operation::DivideUnsigned(Sign::Join, _) => Sign::Join,
-                &Operation::DivideUnsigned(ref a, Sign::Meet) => a.clone(),
-                &Operation::DivideUnsigned(Sign::Meet, ref b) => b.clone(),
-
-                &Operation::Modulo(Sign::Positive, Sign::Positive) => Sign::Positive,
-                &Operation::Modulo(Sign::Negative, Sign::Negative) => Sign::Positive,
-                &Operation::Modulo(Sign::Positive, Sign::Negative) => Sign::Negative,
-                &Operation::Modulo(Sign::Negative, Sign::Positive) => Sign::Negative,
-                &Operation::Modulo(_, Sign::Zero) => Sign::Zero,
-                &Operation::Modulo(Sign::Zero, _) => Sign::Zero,
-                &Operation::Modulo(_, Sign::Join) => Sign::Join,
-                &Operation::Modulo(Sign::Join, _) => Sign::Join,
-                &Operation::Modulo(ref a, Sign::Meet) => a.clone(),
-                &Operation::Modulo(Sign::Meet, ref b) => b.clone(),
-
-                &Operation::Move(ref a) => a.clone(),
-                &Operation::ZeroExtend(_, Sign::Negative) => Sign::Join,
-                &Operation::ZeroExtend(_, ref a) => a.clone(),
-                &Operation::SignExtend(_, ref a) => a.clone(),
-
-                &Operation::Phi(ref ops) => {
-                    match ops.len() {
-                        0 => unreachable!("Phi function w/o arguments"),
-                        1 => ops[0].clone(),
-                        _ => ops.iter().fold(Sign::Meet, |acc, x| acc.combine(&x)),
-                    }
-                }
-
-                _ => Sign::Join,
-            }
-        }
-
-        fn narrow(&self, a: &Self) -> Self {
-            match a {
-                &Sign::Meet => Sign::Meet,
-                &Sign::Join => self.clone(),
-                &Sign::Positive | &Sign::Negative | &Sign::Zero => {
-                    match self {
-                        &Sign::Meet => Sign::Meet,
-                        &Sign::Join => a.clone(),
-                        a => if *a == *self { a.clone() } else { Sign::Meet },
-                    }
-                }
-            }
-        }
-
-        fn combine(&self, b: &Self) -> Self {
-            match (self, b) {
-                (x, y) if x == y => x.clone(),
-                (&Sign::Meet, x) => x.clone(),
-                (x, &Sign::Meet) => x.clone(),
-                _ => Sign::Join,
-            }
-        }
-
-        fn widen(&self, b: &Self) -> Self {
-            if *b == *self {
-                self.clone()
-            } else {
-                Sign::Join
-            }
-        }
-
-
-        fn initial() -> Self {
-            Sign::Meet
-        }
-
-        fn more_exact(&self, b: &Self) -> bool {
-            self != b &&
-            match (self, b) {
-                (&Sign::Meet, &Sign::Positive) |
-                (&Sign::Meet, &Sign::Negative) |
-                (&Sign::Meet, &Sign::Join) => false,
-                (&Sign::Positive, &Sign::Join) |
-                (&Sign::Negative, &Sign::Join) => false,
-                _ => true,
-            }
-        }
-
-        fn extract(&self, _: usize, _: usize) -> Self {
-            match self {
-                &Sign::Join => Sign::Join,
-                &Sign::Meet => Sign::Meet,
-                &Sign::Positive => Sign::Positive,
-                &Sign::Negative => Sign::Negative,
-                &Sign::Zero => Sign::Zero,
-            }
-        }
-    }
-
     /*
      * x = 0;
      * n = 1;
@@ -743,7 +659,7 @@ mod tests {
                         .unwrap(),
             ]
         );
-        let bb2 = BasicBlock { area: Bound::new(4, 5), mnemonics: vec![] };
+        let bb2 = BasicBlock { area: Bound::new(4, 5), mnemonics: vec![], mode: None };
         let mut cfg = ControlFlowGraph::new();
 
         let g = Guard::from_flag(&flag.clone().into()).ok().unwrap();
@@ -0,0 +1,295 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Strided interval domain.
+//!
+//! Lifts the strided intervals of the value-set analysis into an [`AbstractDomain`] usable with
+//! `approximate`. Widening moves growing bounds to the nearest of the thresholds passed to
+//! `approximate_with`, and to the extremes only after that.
+//!
+//! [`AbstractDomain`]: ../trait.AbstractDomain.html
+
+use {AbstractDomain, Constraint, ProgramPoint, StridedInterval, WideningThresholds};
+use panopticon_core::{Operation, Rvalue};
+use std::cmp::max;
+use std::fmt;
+use std::i64;
+
+/// Set of integers described by a strided interval.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub enum Interval {
+    /// Lattice meet, no value.
+    Meet,
+    /// Members of the strided interval. Lattice join is the interval of all 64 bit integers.
+    Strided(StridedInterval),
+}
+
+impl Interval {
+    /// Returns the interval of all 64 bit integers.
+    pub fn join() -> Interval {
+        Interval::Strided(StridedInterval::top())
+    }
+
+    /// Maps both strided intervals with `f`, meet if either is the meet.
+    fn binary(a: &Interval, b: &Interval, f: &Fn(&StridedInterval, &StridedInterval) -> StridedInterval) -> Interval {
+        match (a, b) {
+            (&Interval::Strided(ref a), &Interval::Strided(ref b)) => Interval::Strided(f(a, b)),
+            _ => Interval::Meet,
+        }
+    }
+
+    /// Result of comparing all members of `a` with all of `b`.
+    fn compare(a: &Interval, b: &Interval, f: &Fn(i64, i64) -> bool) -> Interval {
+        match (a, b) {
+            (&Interval::Strided(ref a), &Interval::Strided(ref b)) => {
+                match (a.as_constant(), b.as_constant()) {
+                    (Some(a), Some(b)) => Interval::Strided(StridedInterval::constant(if f(a, b) { 1 } else { 0 })),
+                    _ => Interval::Strided(StridedInterval::new(1, 0, 1)),
+                }
+            }
+            _ => Interval::Meet,
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Interval::Meet => write!(f, "Ø"),
+            &Interval::Strided(ref si) => write!(f, "{}", si),
+        }
+    }
+}
+
+/// Returns the constant `value` of `size` bits the way strided intervals store it.
+fn canonical(value: u64, size: usize) -> StridedInterval {
+    StridedInterval::constant(value as i64).wrap(size)
+}
+
+/// Moves `from` towards `threshold` in steps of `stride` until it reaches or passes it.
+fn align(from: i64, threshold: i64, stride: u64) -> Option<i64> {
+    let stride = max(stride, 1);
+
+    if threshold >= from {
+        let distance = (threshold as u64).wrapping_sub(from as u64);
+        let steps = distance / stride + if distance % stride != 0 { 1 } else { 0 };
+        steps.checked_mul(stride).and_then(|d| if d <= i64::MAX as u64 { from.checked_add(d as i64) } else { None })
+    } else {
+        let distance = (from as u64).wrapping_sub(threshold as u64);
+        let steps = distance / stride + if distance % stride != 0 { 1 } else { 0 };
+        steps.checked_mul(stride).and_then(|d| if d <= i64::MAX as u64 { from.checked_sub(d as i64) } else { None })
+    }
+}
+
+impl AbstractDomain for Interval {
+    fn abstract_value(v: &Rvalue) -> Self {
+        match v {
+            &Rvalue::Constant { value, size } => Interval::Strided(canonical(value, size)),
+            _ => Interval::join(),
+        }
+    }
+
+    fn abstract_constraint(c: &Constraint) -> Self {
+        let si = match c {
+            &Constraint::Equal(Rvalue::Constant { value, size }) => Some(canonical(value, size)),
+            &Constraint::LessUnsigned(Rvalue::Constant { value, .. }) if value <= i64::MAX as u64 => {
+                if value == 0 {
+                    return Interval::Meet;
+                }
+                Some(StridedInterval::new(1, 0, value as i64 - 1))
+            }
+            &Constraint::LessOrEqualUnsigned(Rvalue::Constant { value, .. }) if value <= i64::MAX as u64 => Some(StridedInterval::new(1, 0, value as i64)),
+            &Constraint::LessSigned(Rvalue::Constant { value, size }) => {
+                let c = canonical(value, size).lower;
+
+                if c == i64::MIN {
+                    return Interval::Meet;
+                }
+                Some(StridedInterval::new(1, i64::MIN, c - 1))
+            }
+            &Constraint::LessOrEqualSigned(Rvalue::Constant { value, size }) => Some(StridedInterval::new(1, i64::MIN, canonical(value, size).lower)),
+            _ => None,
+        };
+
+        si.map(Interval::Strided).unwrap_or(Interval::join())
+    }
+
+    fn execute(_: &ProgramPoint, op: &Operation<Self>) -> Self {
+        match op {
+            &Operation::Add(ref a, ref b) => Interval::binary(a, b, &|a, b| a.add(b)),
+            &Operation::Subtract(ref a, ref b) => Interval::binary(a, b, &|a, b| a.sub(b)),
+            &Operation::Multiply(ref a, ref b) => Interval::binary(a, b, &|a, b| a.mul(b)),
+            &Operation::DivideUnsigned(ref a, ref b) => Interval::binary(a, b, &|a, b| a.div(b, 64, false)),
+            &Operation::DivideSigned(ref a, ref b) => Interval::binary(a, b, &|a, b| a.div(b, 64, true)),
+            &Operation::Modulo(ref a, ref b) => Interval::binary(a, b, &|a, b| a.rem(b, 64)),
+            &Operation::ShiftLeft(ref a, ref b) => Interval::binary(a, b, &|a, b| a.shl(b)),
+            &Operation::ShiftRightUnsigned(ref a, ref b) => Interval::binary(a, b, &|a, b| a.shr(b, 64, false)),
+            &Operation::ShiftRightSigned(ref a, ref b) => Interval::binary(a, b, &|a, b| a.shr(b, 64, true)),
+            &Operation::And(ref a, ref b) => Interval::binary(a, b, &|a, b| a.and(b)),
+            &Operation::InclusiveOr(ref a, ref b) => Interval::binary(a, b, &|a, b| a.or(b, false)),
+            &Operation::ExclusiveOr(ref a, ref b) => Interval::binary(a, b, &|a, b| a.or(b, true)),
+
+            &Operation::Equal(ref a, ref b) => Interval::compare(a, b, &|a, b| a == b),
+            &Operation::LessUnsigned(ref a, ref b) => Interval::compare(a, b, &|a, b| (a as u64) < (b as u64)),
+            &Operation::LessOrEqualUnsigned(ref a, ref b) => Interval::compare(a, b, &|a, b| (a as u64) <= (b as u64)),
+            &Operation::LessSigned(ref a, ref b) => Interval::compare(a, b, &|a, b| a < b),
+            &Operation::LessOrEqualSigned(ref a, ref b) => Interval::compare(a, b, &|a, b| a <= b),
+
+            &Operation::Move(ref a) => *a,
+            &Operation::SignExtend(_, ref a) => *a,
+            &Operation::ZeroExtend(_, Interval::Strided(ref si)) if si.lower >= 0 => Interval::Strided(*si),
+            &Operation::ZeroExtend(_, Interval::Meet) => Interval::Meet,
+            &Operation::Select(off, ref a, _) => a.extract(64, off),
+
+            &Operation::Phi(ref ops) => {
+                match ops.len() {
+                    0 => unreachable!("Phi function w/o arguments"),
+                    1 => ops[0],
+                    _ => ops.iter().fold(Interval::Meet, |acc, x| acc.combine(x)),
+                }
+            }
+
+            _ => Interval::join(),
+        }
+    }
+
+    fn narrow(&self, a: &Self) -> Self {
+        match (self, a) {
+            (&Interval::Strided(ref si), &Interval::Strided(ref c)) => si.meet_range(c.lower, c.upper).map(Interval::Strided).unwrap_or(Interval::Meet),
+            _ => Interval::Meet,
+        }
+    }
+
+    fn widen(&self, other: &Self) -> Self {
+        self.widen_with_thresholds(other, &WideningThresholds::default())
+    }
+
+    fn widen_with_thresholds(&self, other: &Self, thresholds: &WideningThresholds) -> Self {
+        match (self, other) {
+            (&Interval::Strided(ref a), &Interval::Strided(ref b)) => {
+                let join = a.join(b);
+                let widened = a.widen(b);
+                let lower = if join.lower < a.lower {
+                    thresholds.below(join.lower).and_then(|t| align(join.lower, t, join.stride)).unwrap_or(widened.lower)
+                } else {
+                    widened.lower
+                };
+                let upper = if join.upper > a.upper {
+                    thresholds.above(join.upper).and_then(|t| align(join.upper, t, join.stride)).unwrap_or(widened.upper)
+                } else {
+                    widened.upper
+                };
+
+                Interval::Strided(StridedInterval::new(widened.stride, lower, upper))
+            }
+            (&Interval::Meet, x) | (x, &Interval::Meet) => *x,
+        }
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        match (self, other) {
+            (&Interval::Strided(ref a), &Interval::Strided(ref b)) => Interval::Strided(a.join(b)),
+            (&Interval::Meet, x) | (x, &Interval::Meet) => *x,
+        }
+    }
+
+    fn more_exact(&self, other: &Self) -> bool {
+        self != other && self.combine(other) == *self
+    }
+
+    fn initial() -> Self {
+        Interval::Meet
+    }
+
+    fn extract(&self, size: usize, offset: usize) -> Self {
+        match self {
+            &Interval::Meet => Interval::Meet,
+            &Interval::Strided(ref si) if offset == 0 => Interval::Strided(si.wrap(size)),
+            &Interval::Strided(ref si) => {
+                match si.as_constant() {
+                    Some(c) if offset < 64 => Interval::Strided(canonical((c as u64) >> offset, size)),
+                    _ => Interval::Strided(StridedInterval::full(size)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interpreter::{approximate, approximate_with, results};
+    use panopticon_core::{BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Guard, Lvalue, Mnemonic, Region, Statement};
+    use panopticon_data_flow::ssa_convertion;
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    fn block(start: u64, stmts: Vec<Statement>) -> ControlFlowTarget {
+        let mne = Mnemonic::new(start..start + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne]))
+    }
+
+    /*
+     * i = 0
+     * while(undef) {
+     *   i = (i + 1) & 0xff
+     * }
+     */
+    #[test]
+    fn widening_thresholds() {
+        let i = Lvalue::Variable { name: Cow::Borrowed("i"), size: 32, subscript: None };
+        let f = Lvalue::Variable { name: Cow::Borrowed("f"), size: 1, subscript: None };
+        let mut cfg = ControlFlowGraph::new();
+        let v0 = cfg.add_vertex(block(0, vec![Statement { op: Operation::Move(Rvalue::new_u32(0)), assignee: i.clone() }]));
+        let v1 = cfg.add_vertex(block(1, vec![Statement { op: Operation::LessUnsigned(i.clone().into(), Rvalue::Undefined), assignee: f.clone() }]));
+        let v2 = cfg.add_vertex(
+            block(
+                2,
+                vec![
+                    Statement { op: Operation::Add(i.clone().into(), Rvalue::new_u32(1)), assignee: i.clone() },
+                    Statement { op: Operation::And(i.clone().into(), Rvalue::new_u32(0xff)), assignee: i.clone() },
+                ]
+            )
+        );
+        let v3 = cfg.add_vertex(block(3, vec![Statement { op: Operation::Move(i.clone().into()), assignee: i.clone() }]));
+        let g = Guard::from_flag(&f.clone().into()).ok().unwrap();
+
+        cfg.add_edge(Guard::always(), v0, v1);
+        cfg.add_edge(g.clone(), v1, v2);
+        cfg.add_edge(g.negation(), v1, v3);
+        cfg.add_edge(Guard::always(), v2, v1);
+
+        let mut func = Function::undefined(0, None, &Region::undefined("ram".to_owned(), 100), Some("test".to_owned()));
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(v0);
+        assert!(ssa_convertion(&mut func).is_ok());
+
+        let vals = approximate::<Interval>(&func, &HashMap::new()).ok().unwrap();
+        let res = results::<Interval>(&func, &vals);
+
+        assert_eq!(res[&(Cow::Borrowed("i"), 32)], Interval::Strided(StridedInterval::new(1, 0, i64::MAX)));
+
+        let thresholds = WideningThresholds::new(vec![-1, 255, 0x1000]);
+        let vals = approximate_with::<Interval>(&func, &HashMap::new(), &thresholds).ok().unwrap();
+        let res = results::<Interval>(&func, &vals);
+
+        assert_eq!(res[&(Cow::Borrowed("i"), 32)], Interval::Strided(StridedInterval::new(1, 0, 255)));
+    }
+}
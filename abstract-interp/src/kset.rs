@@ -20,7 +20,7 @@
 //!
 //! TODO
 
-use {Avalue, Constraint, ProgramPoint};
+use {AbstractDomain, Constraint, ProgramPoint};
 
 use panopticon_core::{Operation, Rvalue, execute};
 use std::collections::HashSet;
@@ -73,7 +73,7 @@ impl PartialEq for Kset {
     }
 }
 
-impl Avalue for Kset {
+impl AbstractDomain for Kset {
     fn abstract_value(v: &Rvalue) -> Self {
         if let &Rvalue::Constant { ref value, ref size } = v {
             Kset::Set(
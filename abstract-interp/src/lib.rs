@@ -26,6 +26,10 @@
 //! abstract sign domain. For example multiplying two positive values yields a positive value.
 //! Adding a positive and a negative sign yields an abstract value representing both signs (called
 //! join).
+//!
+//! Domains implement the `AbstractDomain` trait and are plugged into the fixed point iteration of
+//! `approximate` as type parameter. Strided intervals (`Interval`), Ksets, signs and taint are
+//! included, `approximate_with` additionally widens up to a set of thresholds first.
 
 #[macro_use]
 extern crate log;
@@ -44,7 +48,9 @@ extern crate serde;
 #[macro_use] extern crate serde_derive;
 
 mod interpreter;
-pub use interpreter::{Avalue, Constraint, ProgramPoint, approximate, results, lift};
+pub use interpreter::{AbstractDomain, Constraint, ProgramPoint, WideningThresholds, approximate, approximate_with, results, lift};
+/// Former name of `AbstractDomain`.
+pub use interpreter::AbstractDomain as Avalue;
 
 mod bounded_addr_track;
 pub use bounded_addr_track::BoundedAddrTrack;
@@ -54,3 +60,24 @@ pub use kset::Kset;
 
 mod widening;
 pub use widening::Widening;
+
+pub mod interval;
+pub use interval::Interval;
+
+pub mod sign;
+pub use sign::Sign;
+
+pub mod taint;
+pub use taint::Taint;
+
+pub mod vsa;
+pub use vsa::{ALoc, AbsEnv, Base, StridedInterval, ValueSet, Vsa};
+
+pub mod constprop;
+pub use constprop::{ConstantPropagation, Summary};
+
+pub mod indirect;
+pub use indirect::{IndirectCall, resolve_indirect_calls};
+
+pub mod vtable;
+pub use vtable::{VirtualCall, Vtable, devirtualize, find_vtables, recover_classes};
@@ -0,0 +1,210 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Domain of signs.
+//!
+//! Each value is either positive, negative, zero, any of them (join) or none (meet). Constants are
+//! mapped to their sign, arithmetic follows the rule of signs. Only useful for small examples and
+//! as a template for new domains.
+
+use {AbstractDomain, Constraint, ProgramPoint};
+use panopticon_core::{Operation, Rvalue};
+
+/// Sign of an integer.
+#[derive(Debug,Clone,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub enum Sign {
+    /// Lattice join, any sign.
+    Join,
+    /// Larger than zero
+    Positive,
+    /// Less than zero
+    Negative,
+    /// Equal to zero
+    Zero,
+    /// Lattice meet, no value.
+    Meet,
+}
+
+impl AbstractDomain for Sign {
+    fn abstract_value(v: &Rvalue) -> Self {
+        match v {
+            &Rvalue::Constant { value: c, .. } if c > 0 => Sign::Positive,
+            &Rvalue::Constant { value: 0, .. } => Sign::Zero,
+            _ => Sign::Join,
+        }
+    }
+
+    fn abstract_constraint(c: &Constraint) -> Self {
+        match c {
+            &Constraint::Equal(Rvalue::Constant { value: 0, .. }) => Sign::Zero,
+            &Constraint::LessUnsigned(Rvalue::Constant { value: 1, .. }) => Sign::Zero,
+            &Constraint::LessOrEqualUnsigned(Rvalue::Constant { value: 0, .. }) => Sign::Zero,
+            &Constraint::LessSigned(Rvalue::Constant { value: 0, .. }) => Sign::Negative,
+            &Constraint::LessOrEqualSigned(Rvalue::Constant { value: v, size: s }) if s <= 64 && v & (1 << (s - 1)) != 0 => Sign::Negative,
+            &Constraint::LessSigned(Rvalue::Constant { value: v, size: s }) if s <= 64 && v & (1 << (s - 1)) != 0 => Sign::Negative,
+            _ => Sign::Join,
+        }
+    }
+
+    fn execute(_: &ProgramPoint, op: &Operation<Self>) -> Self {
+        match op {
+            &Operation::Add(Sign::Positive, Sign::Positive) => Sign::Positive,
+            &Operation::Add(Sign::Positive, Sign::Zero) => Sign::Positive,
+            &Operation::Add(Sign::Zero, Sign::Positive) => Sign::Positive,
+            &Operation::Add(Sign::Negative, Sign::Negative) => Sign::Negative,
+            &Operation::Add(Sign::Negative, Sign::Zero) => Sign::Negative,
+            &Operation::Add(Sign::Zero, Sign::Negative) => Sign::Negative,
+            &Operation::Add(Sign::Positive, Sign::Negative) => Sign::Join,
+            &Operation::Add(Sign::Negative, Sign::Positive) => Sign::Join,
+            &Operation::Add(_, Sign::Join) => Sign::Join,
+            &Operation::Add(Sign::Join, _) => Sign::Join,
+            &Operation::Add(ref a, Sign::Meet) => a.clone(),
+            &Operation::Add(Sign::Meet, ref b) => b.clone(),
+
+            &Operation::Subtract(Sign::Positive, Sign::Positive) => Sign::Join,
+            &Operation::Subtract(Sign::Positive, Sign::Zero) => Sign::Positive,
+            &Operation::Subtract(Sign::Zero, Sign::Positive) => Sign::Negative,
+            &Operation::Subtract(Sign::Negative, Sign::Negative) => Sign::Join,
+            &Operation::Subtract(Sign::Negative, Sign::Zero) => Sign::Negative,
+            &Operation::Subtract(Sign::Zero, Sign::Negative) => Sign::Positive,
+            &Operation::Subtract(Sign::Positive, Sign::Negative) => Sign::Positive,
+            &Operation::Subtract(Sign::Negative, Sign::Positive) => Sign::Negative,
+            &Operation::Subtract(_, Sign::Join) => Sign::Join,
+            &Operation::Subtract(Sign::Join, _) => Sign::Join,
+            &Operation::Subtract(ref a, Sign::Meet) => a.clone(),
+            &Operation::Subtract(Sign::Meet, ref b) => b.clone(),
+
+            &Operation::Multiply(Sign::Positive, Sign::Positive) => Sign::Positive,
+            &Operation::Multiply(Sign::Negative, Sign::Negative) => Sign::Positive,
+            &Operation::Multiply(Sign::Positive, Sign::Negative) => Sign::Negative,
+            &Operation::Multiply(Sign::Negative, Sign::Positive) => Sign::Negative,
+            &Operation::Multiply(_, Sign::Zero) => Sign::Zero,
+            &Operation::Multiply(Sign::Zero, _) => Sign::Zero,
+            &Operation::Multiply(_, Sign::Join) => Sign::Join,
+            &Operation::Multiply(Sign::Join, _) => Sign::Join,
+            &Operation::Multiply(ref a, Sign::Meet) => a.clone(),
+            &Operation::Multiply(Sign::Meet, ref b) => b.clone(),
+
+            &Operation::DivideSigned(Sign::Positive, Sign::Positive) => Sign::Positive,
+            &Operation::DivideSigned(Sign::Negative, Sign::Negative) => Sign::Positive,
+            &Operation::DivideSigned(Sign::Positive, Sign::Negative) => Sign::Negative,
+            &Operation::DivideSigned(Sign::Negative, Sign::Positive) => Sign::Negative,
+            &Operation::DivideSigned(_, Sign::Zero) => Sign::Zero,
+            &Operation::DivideSigned(Sign::Zero, _) => Sign::Zero,
+            &Operation::DivideSigned(_, Sign::Join) => Sign::Join,
+            &Operation::DivideSigned(Sign::Join, _) => Sign::Join,
+            &Operation::DivideSigned(ref a, Sign::Meet) => a.clone(),
+            &Operation::DivideSigned(Sign::Meet, ref b) => b.clone(),
+
+            &Operation::DivideUnsigned(Sign::Positive, Sign::Positive) => Sign::Positive,
+            &Operation::DivideUnsigned(Sign::Negative, Sign::Negative) => Sign::Positive,
+            &Operation::DivideUnsigned(Sign::Positive, Sign::Negative) => Sign::Negative,
+            &Operation::DivideUnsigned(Sign::Negative, Sign::Positive) => Sign::Negative,
+            &Operation::DivideUnsigned(_, Sign::Zero) => Sign::Zero,
+            &Operation::DivideUnsigned(Sign::Zero, _) => Sign::Zero,
+            &Operation::DivideUnsigned(_, Sign::Join) => Sign::Join,
+            &Operation::DivideUnsigned(Sign::Join, _) => Sign::Join,
+            &Operation::DivideUnsigned(ref a, Sign::Meet) => a.clone(),
+            &Operation::DivideUnsigned(Sign::Meet, ref b) => b.clone(),
+
+            &Operation::Modulo(Sign::Positive, Sign::Positive) => Sign::Positive,
+            &Operation::Modulo(Sign::Negative, Sign::Negative) => Sign::Positive,
+            &Operation::Modulo(Sign::Positive, Sign::Negative) => Sign::Negative,
+            &Operation::Modulo(Sign::Negative, Sign::Positive) => Sign::Negative,
+            &Operation::Modulo(_, Sign::Zero) => Sign::Zero,
+            &Operation::Modulo(Sign::Zero, _) => Sign::Zero,
+            &Operation::Modulo(_, Sign::Join) => Sign::Join,
+            &Operation::Modulo(Sign::Join, _) => Sign::Join,
+            &Operation::Modulo(ref a, Sign::Meet) => a.clone(),
+            &Operation::Modulo(Sign::Meet, ref b) => b.clone(),
+
+            &Operation::Move(ref a) => a.clone(),
+            &Operation::ZeroExtend(_, Sign::Negative) => Sign::Join,
+            &Operation::ZeroExtend(_, ref a) => a.clone(),
+            &Operation::SignExtend(_, ref a) => a.clone(),
+
+            &Operation::Phi(ref ops) => {
+                match ops.len() {
+                    0 => unreachable!("Phi function w/o arguments"),
+                    1 => ops[0].clone(),
+                    _ => ops.iter().fold(Sign::Meet, |acc, x| acc.combine(&x)),
+                }
+            }
+
+            _ => Sign::Join,
+        }
+    }
+
+    fn narrow(&self, a: &Self) -> Self {
+        match a {
+            &Sign::Meet => Sign::Meet,
+            &Sign::Join => self.clone(),
+            &Sign::Positive | &Sign::Negative | &Sign::Zero => {
+                match self {
+                    &Sign::Meet => Sign::Meet,
+                    &Sign::Join => a.clone(),
+                    a => if *a == *self { a.clone() } else { Sign::Meet },
+                }
+            }
+        }
+    }
+
+    fn combine(&self, b: &Self) -> Self {
+        match (self, b) {
+            (x, y) if x == y => x.clone(),
+            (&Sign::Meet, x) => x.clone(),
+            (x, &Sign::Meet) => x.clone(),
+            _ => Sign::Join,
+        }
+    }
+
+    fn widen(&self, b: &Self) -> Self {
+        if *b == *self {
+            self.clone()
+        } else {
+            Sign::Join
+        }
+    }
+
+
+    fn initial() -> Self {
+        Sign::Meet
+    }
+
+    fn more_exact(&self, b: &Self) -> bool {
+        self != b &&
+        match (self, b) {
+            (&Sign::Meet, &Sign::Positive) |
+            (&Sign::Meet, &Sign::Negative) |
+            (&Sign::Meet, &Sign::Join) => false,
+            (&Sign::Positive, &Sign::Join) |
+            (&Sign::Negative, &Sign::Join) => false,
+            _ => true,
+        }
+    }
+
+    fn extract(&self, _: usize, _: usize) -> Self {
+        match self {
+            &Sign::Join => Sign::Join,
+            &Sign::Meet => Sign::Meet,
+            &Sign::Positive => Sign::Positive,
+            &Sign::Negative => Sign::Negative,
+            &Sign::Zero => Sign::Zero,
+        }
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Taint tracking domain.
+//!
+//! Each value carries the set of taint sources it was computed from, as a bit set of up to 64
+//! sources. Sources are introduced by passing tainted values for some variables to `approximate`
+//! as fixed values, everything else starts out clean. Every operation is tainted by all its
+//! operands. Memory is not modeled: a loaded value is tainted by its address only.
+
+use {AbstractDomain, Constraint, ProgramPoint};
+use panopticon_core::{Operation, Rvalue};
+use std::fmt;
+
+/// Taint sources a value depends on.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub enum Taint {
+    /// Lattice meet, no value.
+    Meet,
+    /// Bit set of taint sources, zero for untainted values.
+    Tainted(u64),
+}
+
+impl Taint {
+    /// Returns an untainted value.
+    pub fn clean() -> Taint {
+        Taint::Tainted(0)
+    }
+
+    /// Returns a value tainted by the `source`th source. `source` must be less than 64.
+    pub fn source(source: usize) -> Taint {
+        assert!(source < 64);
+        Taint::Tainted(1 << source)
+    }
+
+    /// Returns true if the value depends on any source.
+    pub fn is_tainted(&self) -> bool {
+        match self {
+            &Taint::Tainted(s) => s != 0,
+            &Taint::Meet => false,
+        }
+    }
+
+    /// Returns true if the value depends on the `source`th source.
+    pub fn is_tainted_by(&self, source: usize) -> bool {
+        match self {
+            &Taint::Tainted(s) => source < 64 && s & (1 << source) != 0,
+            &Taint::Meet => false,
+        }
+    }
+}
+
+impl fmt::Display for Taint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Taint::Meet => write!(f, "Ø"),
+            &Taint::Tainted(0) => write!(f, "clean"),
+            &Taint::Tainted(s) => write!(f, "tainted({:#x})", s),
+        }
+    }
+}
+
+impl AbstractDomain for Taint {
+    fn abstract_value(_: &Rvalue) -> Self {
+        Taint::clean()
+    }
+
+    fn abstract_constraint(_: &Constraint) -> Self {
+        Taint::Tainted(!0)
+    }
+
+    fn execute(_: &ProgramPoint, op: &Operation<Self>) -> Self {
+        match op {
+            &Operation::Phi(ref ops) => ops.iter().fold(Taint::Meet, |acc, x| acc.combine(x)),
+            &Operation::Initialize(..) => Taint::clean(),
+            _ => {
+                op.operands().iter().fold(
+                    Taint::clean(), |acc, x| match (acc, **x) {
+                        (Taint::Tainted(a), Taint::Tainted(b)) => Taint::Tainted(a | b),
+                        _ => Taint::Meet,
+                    }
+                )
+            }
+        }
+    }
+
+    fn narrow(&self, _: &Self) -> Self {
+        *self
+    }
+
+    fn widen(&self, other: &Self) -> Self {
+        self.combine(other)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        match (self, other) {
+            (&Taint::Tainted(a), &Taint::Tainted(b)) => Taint::Tainted(a | b),
+            (&Taint::Meet, x) | (x, &Taint::Meet) => *x,
+        }
+    }
+
+    fn more_exact(&self, other: &Self) -> bool {
+        self != other && self.combine(other) == *self
+    }
+
+    fn initial() -> Self {
+        Taint::Meet
+    }
+
+    fn extract(&self, _: usize, _: usize) -> Self {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interpreter::{approximate, results};
+    use panopticon_core::{BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Lvalue, Mnemonic, Region, Statement};
+    use panopticon_data_flow::ssa_convertion;
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    /*
+     * a = input + 1
+     * b = 5
+     * c = a * b
+     */
+    #[test]
+    fn propagate_taint() {
+        let var = |n: &'static str| Lvalue::Variable { name: Cow::Borrowed(n), size: 32, subscript: None };
+        let stmts = vec![
+            Statement { op: Operation::Add(var("input").into(), Rvalue::new_u32(1)), assignee: var("a") },
+            Statement { op: Operation::Move(Rvalue::new_u32(5)), assignee: var("b") },
+            Statement { op: Operation::Multiply(var("a").into(), var("b").into()), assignee: var("c") },
+        ];
+        let mne = Mnemonic::new(0..1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let v0 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(0, None, &Region::undefined("ram".to_owned(), 100), Some("test".to_owned()));
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(v0);
+        assert!(ssa_convertion(&mut func).is_ok());
+
+        let mut fixed = HashMap::new();
+        fixed.insert((Cow::Borrowed("input"), 0), Taint::source(3));
+
+        let vals = approximate::<Taint>(&func, &fixed).ok().unwrap();
+        let res = results::<Taint>(&func, &vals);
+
+        assert!(res[&(Cow::Borrowed("a"), 32)].is_tainted_by(3));
+        assert_eq!(res[&(Cow::Borrowed("b"), 32)], Taint::clean());
+        assert_eq!(res[&(Cow::Borrowed("c"), 32)], Taint::source(3));
+        assert!(!res[&(Cow::Borrowed("c"), 32)].is_tainted_by(2));
+    }
+}
@@ -0,0 +1,1649 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Value-set analysis.
+//!
+//! Balakrishnan and Reps style value-set analysis (VSA) over RREIL. Every abstract location
+//! ([`ALoc`]) -- a register, a slot in a stack frame or a memory cell at a fixed address -- is
+//! mapped to a [`ValueSet`]: a strided interval per memory region the value may point into.
+//! Plain integers and absolute addresses are offsets into the global region, stack addresses are
+//! offsets relative to the stack pointer at the entry of the function owning the frame.
+//!
+//! [`Vsa`] analyzes all functions of a `Program` together. Callees are analyzed with the join of
+//! the states at all their known call sites, callers continue with the state the callee returns
+//! with. Locations the callee leaves at their value from entry keep the caller's value, which
+//! preserves callee-saved registers. Functions without known callers start with nothing but the
+//! stack pointer known. Call targets are taken from the value set of the call operand, so calls
+//! through resolved function pointers are followed as well.
+//!
+//! The analysis assumes that stores through unknown pointers and calls to unknown functions do
+//! not write into stack frames. They forget all registers and global memory cells.
+//!
+//! Results are kept per basic block and replayed up to the statement asked for.
+//!
+//! ```ignore
+//! let mut vsa = Vsa::new(&program, &region, "RSP").read_only(Bound::new(0x400000, 0x401000));
+//! vsa.analyze();
+//! let value = vsa.value(&function, &ProgramPoint { address: 0x400123, position: 2 }, &operand);
+//! ```
+//!
+//! [`ALoc`]: enum.ALoc.html
+//! [`ValueSet`]: enum.ValueSet.html
+//! [`Vsa`]: struct.Vsa.html
+
+use ProgramPoint;
+use panopticon_core::{BasicBlock, Bound, ControlFlowRef, ControlFlowTarget, Endianess, Function, Guard, Lvalue, Operation, Program, Region, Rvalue, Statement};
+use panopticon_graph_algos::{BidirectionalGraphTrait, GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use panopticon_graph_algos::search::{EdgeKind, depth_first_visit};
+use std::borrow::Cow;
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::i64;
+
+/// Number of times a state is joined before widening kicks in.
+const WIDENING_DELAY: usize = 3;
+
+/// Largest number of addresses a memory access or call is split into.
+const MAX_ADDRESSES: u64 = 256;
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Set of integers `{ lower + i * stride | 0 <= i <= (upper - lower) / stride }`. Integers of less
+/// than 64 bits are kept sign extended, those narrower than a byte zero extended.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub struct StridedInterval {
+    /// Distance between two members. Zero iff the set has a single member.
+    pub stride: u64,
+    /// Smallest member
+    pub lower: i64,
+    /// Largest member
+    pub upper: i64,
+}
+
+impl StridedInterval {
+    /// Returns the members of [lower, upper] congruent to `lower` modulo `stride`.
+    pub fn new(stride: u64, lower: i64, upper: i64) -> StridedInterval {
+        let lower = min(lower, upper);
+
+        if lower == upper {
+            StridedInterval { stride: 0, lower: lower, upper: upper }
+        } else {
+            let stride = max(stride, 1);
+            let span = (upper as u64).wrapping_sub(lower as u64);
+            let upper = (lower as u64).wrapping_add(span / stride * stride) as i64;
+
+            StridedInterval { stride: if lower == upper { 0 } else { stride }, lower: lower, upper: upper }
+        }
+    }
+
+    /// Returns the set containing only `value`.
+    pub fn constant(value: i64) -> StridedInterval {
+        StridedInterval { stride: 0, lower: value, upper: value }
+    }
+
+    /// Returns the set of all 64 bit integers.
+    pub fn top() -> StridedInterval {
+        StridedInterval { stride: 1, lower: i64::MIN, upper: i64::MAX }
+    }
+
+    /// Returns the set of all `bits` wide integers.
+    pub fn full(bits: usize) -> StridedInterval {
+        let (lower, upper) = Self::range(bits);
+        StridedInterval { stride: 1, lower: lower, upper: upper }
+    }
+
+    /// Returns all 64 bit integers congruent to `residue` modulo `stride`, just `residue` if `stride`
+    /// is zero.
+    fn congruent(stride: u64, residue: i64) -> StridedInterval {
+        if stride == 0 {
+            StridedInterval::constant(residue)
+        } else if stride & (stride - 1) == 0 {
+            let lower = (residue as u64).wrapping_sub(i64::MIN as u64) % stride;
+            let upper = (i64::MAX as u64).wrapping_sub(residue as u64) % stride;
+
+            StridedInterval::new(stride, (i64::MIN as u64).wrapping_add(lower) as i64, (i64::MAX as u64).wrapping_sub(upper) as i64)
+        } else {
+            StridedInterval::top()
+        }
+    }
+
+    /// Smallest and largest canonical `bits` wide integer.
+    fn range(bits: usize) -> (i64, i64) {
+        if bits >= 64 {
+            (i64::MIN, i64::MAX)
+        } else if bits < 8 {
+            (0, (1 << bits) - 1)
+        } else {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        }
+    }
+
+    /// Returns true if this is the set of all 64 bit integers.
+    pub fn is_top(&self) -> bool {
+        *self == StridedInterval::top()
+    }
+
+    /// Returns the only member, if there's just one.
+    pub fn as_constant(&self) -> Option<i64> {
+        if self.stride == 0 { Some(self.lower) } else { None }
+    }
+
+    /// Returns true if `value` is a member.
+    pub fn contains(&self, value: i64) -> bool {
+        value >= self.lower && value <= self.upper && (self.stride == 0 || (value as u64).wrapping_sub(self.lower as u64) % self.stride == 0)
+    }
+
+    /// Number of members minus one.
+    fn steps(&self) -> u64 {
+        if self.stride == 0 { 0 } else { (self.upper as u64).wrapping_sub(self.lower as u64) / self.stride }
+    }
+
+    /// Returns all members, if there are at most `limit` of them.
+    pub fn values(&self, limit: u64) -> Option<Vec<i64>> {
+        let steps = self.steps();
+
+        if steps < limit {
+            Some((0..steps + 1).map(|i| (self.lower as u64).wrapping_add(i * self.stride) as i64).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Least upper bound.
+    pub fn join(&self, other: &StridedInterval) -> StridedInterval {
+        let lower = min(self.lower, other.lower);
+        let upper = max(self.upper, other.upper);
+        let distance = (max(self.lower, other.lower) as u64).wrapping_sub(lower as u64);
+
+        StridedInterval::new(gcd(gcd(self.stride, other.stride), distance), lower, upper)
+    }
+
+    /// Joins `other` and moves bounds that grew to the extremes.
+    pub fn widen(&self, other: &StridedInterval) -> StridedInterval {
+        let join = self.join(other);
+        let stride = max(join.stride, 1);
+        let lower = if join.lower < self.lower {
+            let steps = (join.lower as u64).wrapping_sub(i64::MIN as u64) / stride;
+            (join.lower as u64).wrapping_sub(steps * stride) as i64
+        } else {
+            join.lower
+        };
+        let upper = if join.upper > self.upper {
+            let steps = (i64::MAX as u64).wrapping_sub(join.upper as u64) / stride;
+            (join.upper as u64).wrapping_add(steps * stride) as i64
+        } else {
+            join.upper
+        };
+
+        StridedInterval::new(stride, lower, upper)
+    }
+
+    /// Returns the members inside [lower, upper], `None` if there aren't any.
+    pub fn meet_range(&self, lower: i64, upper: i64) -> Option<StridedInterval> {
+        if lower > self.upper || upper < self.lower {
+            return None;
+        }
+
+        let stride = max(self.stride, 1);
+        let first = if lower > self.lower {
+            let distance = (lower as u64).wrapping_sub(self.lower as u64);
+            let steps = distance / stride + if distance % stride != 0 { 1 } else { 0 };
+            (self.lower as u64).wrapping_add(steps * stride) as i64
+        } else {
+            self.lower
+        };
+        let last = if upper < self.upper {
+            let distance = (self.upper as u64).wrapping_sub(upper as u64);
+            let steps = distance / stride + if distance % stride != 0 { 1 } else { 0 };
+            (self.upper as u64).wrapping_sub(steps * stride) as i64
+        } else {
+            self.upper
+        };
+
+        if first <= last && first >= self.lower && last <= self.upper {
+            Some(StridedInterval::new(stride, first, last))
+        } else {
+            None
+        }
+    }
+
+    /// Brings all members into the canonical range of `bits` wide integers.
+    pub fn wrap(&self, bits: usize) -> StridedInterval {
+        if bits >= 64 {
+            return *self;
+        }
+
+        let modulus = 1u64 << bits;
+        let (first, last) = Self::range(bits);
+        let span = (self.upper as u64).wrapping_sub(self.lower as u64);
+        let offset = (self.lower as u64).wrapping_sub(first as u64) & (modulus - 1);
+        let lower = (first as u64).wrapping_add(offset) as i64;
+
+        if span < modulus && span <= (last as u64).wrapping_sub(lower as u64) {
+            StridedInterval::new(self.stride, lower, (lower as u64).wrapping_add(span) as i64)
+        } else {
+            let stride = gcd(self.stride, modulus);
+            let full = StridedInterval::full(bits);
+
+            if stride > 1 {
+                let residue = (lower as u64).wrapping_sub(first as u64) % stride;
+                let lower = (first as u64).wrapping_add(residue) as i64;
+                full.meet_range(lower, last).map(|x| StridedInterval::new(stride, x.lower, x.upper)).unwrap_or(full)
+            } else {
+                full
+            }
+        }
+    }
+
+    /// Returns the members of a `bits` wide integer read as unsigned, if they are in order.
+    fn unsigned(&self, bits: usize) -> Option<StridedInterval> {
+        if self.lower >= 0 {
+            Some(*self)
+        } else if self.upper < 0 && bits < 64 && bits >= 8 {
+            let modulus = 1i64 << bits;
+            Some(StridedInterval::new(self.stride, self.lower + modulus, self.upper + modulus))
+        } else {
+            None
+        }
+    }
+
+    /// Sum of all pairs of members.
+    pub fn add(&self, other: &StridedInterval) -> StridedInterval {
+        let stride = gcd(self.stride, other.stride);
+
+        match (self.lower.checked_add(other.lower), self.upper.checked_add(other.upper)) {
+            (Some(lower), Some(upper)) => StridedInterval::new(stride, lower, upper),
+            _ => StridedInterval::congruent(stride, self.lower.wrapping_add(other.lower)),
+        }
+    }
+
+    /// Negated members.
+    pub fn neg(&self) -> StridedInterval {
+        if self.lower == i64::MIN {
+            StridedInterval::congruent(self.stride, self.lower.wrapping_neg())
+        } else {
+            StridedInterval::new(self.stride, -self.upper, -self.lower)
+        }
+    }
+
+    /// Difference of all pairs of members.
+    pub fn sub(&self, other: &StridedInterval) -> StridedInterval {
+        self.add(&other.neg())
+    }
+
+    /// Product of all pairs of members.
+    pub fn mul(&self, other: &StridedInterval) -> StridedInterval {
+        match (self.as_constant(), other.as_constant()) {
+            (Some(a), Some(b)) => a.checked_mul(b).map(StridedInterval::constant).unwrap_or(StridedInterval::top()),
+            (Some(c), None) | (None, Some(c)) => {
+                let si = if self.as_constant().is_some() { other } else { self };
+                let a = si.lower.checked_mul(c);
+                let b = si.upper.checked_mul(c);
+                let stride = si.stride.checked_mul(c.wrapping_abs() as u64);
+
+                match (a, b, stride) {
+                    (Some(a), Some(b), Some(stride)) => StridedInterval::new(stride, min(a, b), max(a, b)),
+                    _ => StridedInterval::top(),
+                }
+            }
+            (None, None) => {
+                let corners = [
+                    self.lower.checked_mul(other.lower),
+                    self.lower.checked_mul(other.upper),
+                    self.upper.checked_mul(other.lower),
+                    self.upper.checked_mul(other.upper),
+                ];
+
+                if corners.iter().all(|x| x.is_some()) {
+                    let corners = corners.iter().map(|x| x.unwrap()).collect::<Vec<_>>();
+                    StridedInterval::new(1, *corners.iter().min().unwrap(), *corners.iter().max().unwrap())
+                } else {
+                    StridedInterval::top()
+                }
+            }
+        }
+    }
+
+    /// Members shifted left by all members of `other`.
+    pub fn shl(&self, other: &StridedInterval) -> StridedInterval {
+        match other.as_constant() {
+            Some(k) if k >= 0 && k < 63 => self.mul(&StridedInterval::constant(1 << k)),
+            _ => StridedInterval::top(),
+        }
+    }
+
+    /// Members of a `bits` wide integer shifted right by all members of `other`.
+    pub fn shr(&self, other: &StridedInterval, bits: usize, signed: bool) -> StridedInterval {
+        let si = if signed { Some(*self) } else { self.unsigned(bits) };
+
+        match (si, other.as_constant()) {
+            (Some(si), Some(k)) if k >= 0 && k < 64 => {
+                let stride = if si.stride % (1 << k) == 0 { si.stride >> k } else { 1 };
+                StridedInterval::new(stride, si.lower >> k, si.upper >> k)
+            }
+            _ => StridedInterval::full(bits),
+        }
+    }
+
+    /// Quotient of all members of a `bits` wide integer and those of `other`.
+    pub fn div(&self, other: &StridedInterval, bits: usize, signed: bool) -> StridedInterval {
+        let si = if signed { Some(*self) } else { self.unsigned(bits) };
+
+        match (si, other.as_constant()) {
+            (Some(si), Some(c)) if c > 0 => StridedInterval::new(1, si.lower / c, si.upper / c),
+            _ => StridedInterval::full(bits),
+        }
+    }
+
+    /// Remainder of all members of a `bits` wide integer divided by those of `other`.
+    pub fn rem(&self, other: &StridedInterval, bits: usize) -> StridedInterval {
+        match (self.unsigned(bits), other.as_constant()) {
+            (Some(si), Some(c)) if c > 0 && si.upper < c => si,
+            (Some(_), Some(c)) if c > 0 => StridedInterval::new(1, 0, c - 1),
+            _ => StridedInterval::full(bits),
+        }
+    }
+
+    /// Bitwise and of all pairs of members.
+    pub fn and(&self, other: &StridedInterval) -> StridedInterval {
+        match (self.as_constant(), other.as_constant()) {
+            (Some(a), Some(b)) => StridedInterval::constant(a & b),
+            (Some(m), None) | (None, Some(m)) => {
+                let si = if self.as_constant().is_some() { other } else { self };
+
+                if m >= 0 && si.lower >= 0 && si.upper <= m && m & m.wrapping_add(1) == 0 {
+                    *si
+                } else if m >= 0 {
+                    StridedInterval::new(1, 0, if si.lower >= 0 { min(si.upper, m) } else { m })
+                } else {
+                    si.align(m)
+                }
+            }
+            (None, None) if self.lower >= 0 && other.lower >= 0 => StridedInterval::new(1, 0, min(self.upper, other.upper)),
+            (None, None) => StridedInterval::top(),
+        }
+    }
+
+    /// Members and-ed with the negative `mask`, e.g. -16 to align to 16 bytes.
+    fn align(&self, mask: i64) -> StridedInterval {
+        match self.lower.checked_sub(!mask) {
+            Some(lower) => StridedInterval::new(1, lower, self.upper),
+            None => StridedInterval::top(),
+        }
+    }
+
+    /// Bitwise or or exclusive or of all pairs of members.
+    pub fn or(&self, other: &StridedInterval, xor: bool) -> StridedInterval {
+        match (self.as_constant(), other.as_constant()) {
+            (Some(a), Some(b)) => StridedInterval::constant(if xor { a ^ b } else { a | b }),
+            _ if self.lower >= 0 && other.lower >= 0 => {
+                let bits = 64 - max(self.upper, other.upper).leading_zeros();
+                StridedInterval::new(1, 0, ((1u64 << bits) - 1) as i64)
+            }
+            _ => StridedInterval::top(),
+        }
+    }
+}
+
+impl fmt::Display for StridedInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.stride {
+            0 => write!(f, "{}", self.lower),
+            _ if self.is_top() => write!(f, "⫟"),
+            stride => write!(f, "{}[{},{}]", stride, self.lower, self.upper),
+        }
+    }
+}
+
+/// Memory region a value set is an offset into.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub enum Base {
+    /// Integers and absolute addresses
+    Global,
+    /// Stack frame of the function starting at this address. Offset zero is the stack pointer at
+    /// the function's entry.
+    Stack(u64),
+}
+
+/// Abstract location.
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub enum ALoc {
+    /// Register or temporary variable
+    Register(Cow<'static, str>),
+    /// Bytes in a stack frame
+    Stack {
+        /// Start of the function owning the frame
+        frame: u64,
+        /// Offset from the stack pointer at the function's entry
+        offset: i64,
+        /// Size in bytes
+        size: usize,
+    },
+    /// Bytes of memory at a fixed address
+    Global {
+        /// Memory bank
+        bank: Cow<'static, str>,
+        /// Address of the first byte
+        address: u64,
+        /// Size in bytes
+        size: usize,
+    },
+}
+
+/// Strided interval per memory region a value may point into.
+#[derive(Clone,Debug,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub enum ValueSet {
+    /// Any value
+    Top,
+    /// Offsets into each memory region. Empty if there's no value at all.
+    Set(BTreeMap<Base, StridedInterval>),
+}
+
+impl ValueSet {
+    /// Returns the value set containing just the integer `value`.
+    pub fn constant(value: i64) -> ValueSet {
+        ValueSet::new(Base::Global, StridedInterval::constant(value))
+    }
+
+    /// Returns the value set of offsets `si` into `base`.
+    pub fn new(base: Base, si: StridedInterval) -> ValueSet {
+        let mut map = BTreeMap::new();
+        map.insert(base, si);
+        ValueSet::Set(map)
+    }
+
+    /// Returns the value set without any value.
+    pub fn bottom() -> ValueSet {
+        ValueSet::Set(BTreeMap::new())
+    }
+
+    /// Returns true if there's no value at all.
+    pub fn is_bottom(&self) -> bool {
+        match self {
+            &ValueSet::Set(ref map) => map.is_empty(),
+            &ValueSet::Top => false,
+        }
+    }
+
+    /// Returns the integers in this set if it has no pointers.
+    pub fn global(&self) -> Option<StridedInterval> {
+        match self {
+            &ValueSet::Set(ref map) if map.len() == 1 => map.get(&Base::Global).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Returns all integers in this set, read as `bits` wide unsigned values, if there are at
+    /// most `limit` of them and no pointers.
+    pub fn addresses(&self, bits: usize, limit: u64) -> Option<Vec<u64>> {
+        let mask = if bits >= 64 { !0 } else { (1u64 << bits) - 1 };
+        self.global().and_then(|si| si.values(limit)).map(|v| v.into_iter().map(|x| x as u64 & mask).collect())
+    }
+
+    /// Least upper bound.
+    pub fn join(&self, other: &ValueSet) -> ValueSet {
+        self.merge(other, &|a, b| a.join(b))
+    }
+
+    /// Joins `other` and moves bounds that grew to the extremes.
+    pub fn widen(&self, other: &ValueSet) -> ValueSet {
+        self.merge(other, &|a, b| a.widen(b))
+    }
+
+    fn merge(&self, other: &ValueSet, f: &Fn(&StridedInterval, &StridedInterval) -> StridedInterval) -> ValueSet {
+        match (self, other) {
+            (&ValueSet::Set(ref a), &ValueSet::Set(ref b)) => {
+                let mut ret = a.clone();
+
+                for (base, si) in b.iter() {
+                    let new = match a.get(base) {
+                        Some(old) => f(old, si),
+                        None => *si,
+                    };
+                    ret.insert(*base, new);
+                }
+
+                ValueSet::Set(ret)
+            }
+            _ => ValueSet::Top,
+        }
+    }
+
+    /// Applies `f` to the offsets of every region.
+    fn map(&self, f: &Fn(&StridedInterval) -> StridedInterval) -> ValueSet {
+        match self {
+            &ValueSet::Set(ref map) => ValueSet::Set(map.iter().map(|(b, si)| (*b, f(si))).collect()),
+            &ValueSet::Top => ValueSet::Top,
+        }
+    }
+
+    /// Applies `f` to integers, returns the full range of `bits` wide integers if either set
+    /// contains pointers.
+    fn integers(&self, other: &ValueSet, bits: usize, f: &Fn(&StridedInterval, &StridedInterval) -> StridedInterval) -> ValueSet {
+        if self.is_bottom() || other.is_bottom() {
+            return ValueSet::bottom();
+        }
+
+        match (self.global(), other.global()) {
+            (Some(a), Some(b)) => ValueSet::new(Base::Global, f(&a, &b).wrap(bits)),
+            _ => ValueSet::new(Base::Global, StridedInterval::full(bits)),
+        }
+    }
+
+    /// Sum of all pairs of values. Pointers may only be added to integers.
+    pub fn add(&self, other: &ValueSet) -> ValueSet {
+        match (self, other) {
+            (&ValueSet::Set(ref a), &ValueSet::Set(ref b)) => {
+                let mut ret = BTreeMap::new();
+
+                for (base_a, si_a) in a.iter() {
+                    for (base_b, si_b) in b.iter() {
+                        let base = match (*base_a, *base_b) {
+                            (Base::Global, base) | (base, Base::Global) => base,
+                            _ => return ValueSet::Top,
+                        };
+                        let sum = si_a.add(si_b);
+                        let new = ret.get(&base).map(|x: &StridedInterval| x.join(&sum)).unwrap_or(sum);
+                        ret.insert(base, new);
+                    }
+                }
+
+                ValueSet::Set(ret)
+            }
+            _ => ValueSet::Top,
+        }
+    }
+
+    /// Difference of all pairs of values. Subtracting two pointers into the same region yields
+    /// an integer.
+    pub fn sub(&self, other: &ValueSet) -> ValueSet {
+        match (self, other) {
+            (&ValueSet::Set(ref a), &ValueSet::Set(ref b)) => {
+                let mut ret = BTreeMap::new();
+
+                for (base_a, si_a) in a.iter() {
+                    for (base_b, si_b) in b.iter() {
+                        let base = match (*base_a, *base_b) {
+                            (base, Base::Global) => base,
+                            (x, y) if x == y => Base::Global,
+                            _ => return ValueSet::Top,
+                        };
+                        let diff = si_a.sub(si_b);
+                        let new = ret.get(&base).map(|x: &StridedInterval| x.join(&diff)).unwrap_or(diff);
+                        ret.insert(base, new);
+                    }
+                }
+
+                ValueSet::Set(ret)
+            }
+            _ => ValueSet::Top,
+        }
+    }
+
+    /// Bitwise and. Pointers may be aligned by and-ing them with a negative constant.
+    pub fn and(&self, other: &ValueSet, bits: usize) -> ValueSet {
+        let constant = |vs: &ValueSet| vs.global().and_then(|si| si.as_constant());
+
+        if self.is_bottom() || other.is_bottom() {
+            return ValueSet::bottom();
+        }
+
+        match (constant(self), constant(other)) {
+            (_, Some(m)) if m < 0 && self.global().is_none() => self.map(&|si| si.align(m)),
+            (Some(m), _) if m < 0 && other.global().is_none() => other.map(&|si| si.align(m)),
+            (_, Some(m)) | (Some(m), _) if m >= 0 && (self.global().is_none() || other.global().is_none()) => {
+                ValueSet::new(Base::Global, StridedInterval::new(1, 0, m).wrap(bits))
+            }
+            _ => self.integers(other, bits, &|a, b| a.and(b)),
+        }
+    }
+
+    /// Brings all offsets into the canonical range of `bits` wide integers.
+    pub fn wrap(&self, bits: usize) -> ValueSet {
+        self.map(&|si| si.wrap(bits))
+    }
+}
+
+impl fmt::Display for ValueSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ValueSet::Top => write!(f, "⫟"),
+            &ValueSet::Set(ref map) if map.is_empty() => write!(f, "Ø"),
+            &ValueSet::Set(ref map) => {
+                for (i, (base, si)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ∪ ")?;
+                    }
+                    match base {
+                        &Base::Global => write!(f, "{}", si)?,
+                        &Base::Stack(frame) => write!(f, "stack_{:x} + {}", frame, si)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Value sets of all abstract locations at one program point. Locations that are missing have
+/// not been written since the function's entry and are unknown.
+#[derive(Clone,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct AbsEnv {
+    /// Written or known locations
+    pub locations: BTreeMap<ALoc, ValueSet>,
+    /// True if an unknown function was called or memory was written through an unknown pointer.
+    /// Registers and global memory cells may have been changed even if they are missing.
+    pub clobbered: bool,
+}
+
+impl AbsEnv {
+    /// Returns the value set of `loc`.
+    pub fn get(&self, loc: &ALoc) -> ValueSet {
+        self.locations.get(loc).cloned().unwrap_or(ValueSet::Top)
+    }
+
+    /// Sets the value set of `loc`.
+    pub fn set(&mut self, loc: ALoc, value: ValueSet) {
+        self.locations.insert(loc, value);
+    }
+
+    /// Least upper bound.
+    pub fn join(&self, other: &AbsEnv) -> AbsEnv {
+        self.merge(other, &|a, b| a.join(b))
+    }
+
+    /// Joins `other` and moves bounds that grew to the extremes.
+    pub fn widen(&self, other: &AbsEnv) -> AbsEnv {
+        self.merge(other, &|a, b| a.widen(b))
+    }
+
+    fn merge(&self, other: &AbsEnv, f: &Fn(&ValueSet, &ValueSet) -> ValueSet) -> AbsEnv {
+        let mut ret = AbsEnv { locations: BTreeMap::new(), clobbered: self.clobbered || other.clobbered };
+
+        for (loc, a) in self.locations.iter() {
+            ret.locations.insert(loc.clone(), other.locations.get(loc).map(|b| f(a, b)).unwrap_or(ValueSet::Top));
+        }
+        for (loc, _) in other.locations.iter().filter(|&(l, _)| !self.locations.contains_key(l)) {
+            ret.locations.insert(loc.clone(), ValueSet::Top);
+        }
+
+        ret
+    }
+
+    /// Forgets all registers except `keep` and all global memory cells.
+    fn clobber(&mut self, keep: &ALoc) {
+        self.locations = self.locations
+            .iter()
+            .filter(|&(loc, _)| loc == keep || if let &ALoc::Stack { .. } = loc { true } else { false })
+            .map(|(l, v)| (l.clone(), v.clone()))
+            .collect();
+        self.clobbered = true;
+    }
+
+    /// Removes all memory cells in `base` overlapping [offset, offset + size).
+    fn forget(&mut self, bank: &str, base: Base, lower: i64, upper: i64) {
+        let overlaps = |loc: &ALoc| match (loc, base) {
+            (&ALoc::Stack { frame, offset, size }, Base::Stack(f)) => frame == f && offset < upper && offset + size as i64 > lower,
+            (&ALoc::Global { bank: ref b, address, size }, Base::Global) => {
+                b == bank && (address as i64) < upper && (address as i64).wrapping_add(size as i64) > lower
+            }
+            _ => false,
+        };
+        let gone = self.locations.keys().filter(|l| overlaps(l)).cloned().collect::<Vec<_>>();
+
+        for loc in gone {
+            self.locations.insert(loc, ValueSet::Top);
+        }
+    }
+}
+
+/// Interprocedural value-set analysis of a `Program`.
+pub struct Vsa<'a> {
+    program: &'a Program,
+    region: &'a Region,
+    stack_pointer: ALoc,
+    read_only: Vec<Bound>,
+    functions: HashMap<u64, &'a Function>,
+    /// State at the start of each basic block, by function
+    blocks: HashMap<u64, HashMap<ControlFlowRef, AbsEnv>>,
+    /// State each function was analyzed with
+    entries: HashMap<u64, AbsEnv>,
+    /// Join of the states at all returns, by function
+    exits: HashMap<u64, AbsEnv>,
+    /// Join of the states at all call sites, by callee
+    bindings: HashMap<u64, AbsEnv>,
+    /// Functions calling each function
+    callers: HashMap<u64, HashSet<u64>>,
+    /// Number of changes to bindings and exits, by function
+    changes: HashMap<(u64, bool), usize>,
+}
+
+impl<'a> Vsa<'a> {
+    /// Prepares the analysis of all functions in `program`. Memory is read from `region`, the
+    /// stack pointer is the register `stack_pointer`.
+    pub fn new(program: &'a Program, region: &'a Region, stack_pointer: &str) -> Vsa<'a> {
+        let functions = program
+            .functions()
+            .filter(|f| if let Some(&ControlFlowTarget::Resolved(_)) = f.cfg().vertex_label(f.entry_point_ref()) { true } else { false })
+            .map(|f| (f.start(), f))
+            .collect();
+
+        Vsa {
+            program: program,
+            region: region,
+            stack_pointer: ALoc::Register(Cow::Owned(stack_pointer.to_string())),
+            read_only: vec![],
+            functions: functions,
+            blocks: HashMap::new(),
+            entries: HashMap::new(),
+            exits: HashMap::new(),
+            bindings: HashMap::new(),
+            callers: HashMap::new(),
+            changes: HashMap::new(),
+        }
+    }
+
+    /// Marks `area` as never written. Loads from it read the contents of the region.
+    pub fn read_only(mut self, area: Bound) -> Vsa<'a> {
+        self.read_only.push(area);
+        self
+    }
+
+    /// Analyzes all functions until a fixed point is reached.
+    pub fn analyze(&mut self) {
+        let mut queue = self.program.functions().map(|f| f.start()).filter(|s| self.functions.contains_key(s)).collect::<VecDeque<_>>();
+        let mut queued = queue.iter().cloned().collect::<HashSet<_>>();
+
+        while let Some(start) = queue.pop_front() {
+            queued.remove(&start);
+
+            let func = self.functions[&start];
+            let (blocks, exit, calls) = self.analyze_function(func);
+            let mut next = vec![];
+
+            self.blocks.insert(start, blocks);
+
+            for (callee, env) in calls {
+                self.callers.entry(callee).or_insert_with(HashSet::new).insert(start);
+                if self.update(callee, false, env) {
+                    next.push(callee);
+                }
+            }
+            if let Some(exit) = exit {
+                if self.update(start, true, exit) {
+                    next.extend(self.callers.get(&start).into_iter().flat_map(|x| x.iter().cloned()));
+                }
+            }
+
+            for f in next {
+                if queued.insert(f) {
+                    queue.push_back(f);
+                }
+            }
+        }
+    }
+
+    /// Joins `env` into the bindings of `func` or replaces its exit state (`exit` true). Exit
+    /// states are always computed from the latest bindings, they replace the old one until
+    /// widening kicks in. Returns true if the state changed.
+    fn update(&mut self, func: u64, exit: bool, env: AbsEnv) -> bool {
+        let count = self.changes.get(&(func, exit)).cloned().unwrap_or(0);
+        let new = {
+            let map = if exit { &self.exits } else { &self.bindings };
+
+            match map.get(&func) {
+                Some(old) if count >= WIDENING_DELAY => old.widen(&old.join(&env)),
+                Some(_) if exit => env,
+                Some(old) => old.join(&env),
+                None => env,
+            }
+        };
+        let map = if exit { &mut self.exits } else { &mut self.bindings };
+
+        if map.get(&func) != Some(&new) {
+            map.insert(func, new);
+            self.changes.insert((func, exit), count + 1);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Runs `func` to a fixed point. Returns the states at the start of each basic block, the
+    /// state at the function's returns and the states its callees are called with.
+    fn analyze_function(&mut self, func: &Function) -> (HashMap<ControlFlowRef, AbsEnv>, Option<AbsEnv>, Vec<(u64, AbsEnv)>) {
+        let start = func.start();
+        let entry = match self.bindings.get(&start) {
+            Some(env) => env.clone(),
+            None => {
+                let mut env = AbsEnv::default();
+                env.set(self.stack_pointer.clone(), ValueSet::new(Base::Stack(start), StridedInterval::constant(0)));
+                env
+            }
+        };
+        let cfg = func.cfg();
+        let mut heads = HashSet::new();
+        let mut states = HashMap::new();
+        let mut visits = HashMap::<ControlFlowRef, usize>::new();
+
+        depth_first_visit(
+            &mut |_, _| {},
+            &mut |e, kind| if kind == EdgeKind::Backward {
+                heads.insert(cfg.target(*e));
+            },
+            &func.entry_point_ref(),
+            cfg,
+        );
+        let mut queue = VecDeque::new();
+
+        self.entries.insert(start, entry.clone());
+        states.insert(func.entry_point_ref(), entry);
+        queue.push_back(func.entry_point_ref());
+
+        while let Some(vx) = queue.pop_front() {
+            let bb = match cfg.vertex_label(vx) {
+                Some(&ControlFlowTarget::Resolved(ref bb)) => bb,
+                _ => continue,
+            };
+            let mut env = states[&vx].clone();
+
+            if !self.run(start, bb, bb.statements().count(), &mut env, &mut vec![]) {
+                continue;
+            }
+
+            for e in cfg.out_edges(vx) {
+                let next = cfg.target(e);
+                let new = match self.constrain(bb, cfg.edge_label(e), &env) {
+                    Some(new) => new,
+                    None => continue,
+                };
+                let count = visits.get(&next).cloned().unwrap_or(0);
+                let merged = match states.get(&next) {
+                    Some(old) if count >= WIDENING_DELAY && heads.contains(&next) => old.widen(&old.join(&new)),
+                    Some(old) => old.join(&new),
+                    None => new,
+                };
+
+                if states.get(&next) != Some(&merged) {
+                    states.insert(next, merged);
+                    visits.insert(next, count + 1);
+                    if !queue.contains(&next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        let mut exit: Option<AbsEnv> = None;
+        let mut calls = vec![];
+
+        for (&vx, state) in states.iter() {
+            if let Some(&ControlFlowTarget::Resolved(ref bb)) = cfg.vertex_label(vx) {
+                let mut env = state.clone();
+
+                if self.run(start, bb, bb.statements().count(), &mut env, &mut calls) && cfg.out_degree(vx) == 0 {
+                    exit = Some(match exit {
+                        Some(e) => e.join(&env),
+                        None => env,
+                    });
+                }
+            }
+        }
+
+        (states, exit, calls)
+    }
+
+    /// Executes the first `count` statements of `bb`. Returns false if the end isn't reachable.
+    /// Callees are appended to `calls` together with the state they are called with.
+    fn run(&self, frame: u64, bb: &BasicBlock, count: usize, env: &mut AbsEnv, calls: &mut Vec<(u64, AbsEnv)>) -> bool {
+        for stmt in bb.statements().take(count) {
+            if !self.step(frame, stmt, env, calls) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Executes `stmt`. Returns false if the statement never returns.
+    fn step(&self, frame: u64, stmt: &Statement, env: &mut AbsEnv, calls: &mut Vec<(u64, AbsEnv)>) -> bool {
+        let value = match stmt.op {
+            Operation::Call(ref target) => return self.call(frame, target, env, calls),
+            Operation::Store(ref bank, _, bits, ref addr, ref val) => {
+                let addr_bits = addr.size().unwrap_or(64);
+                let addr = self.eval(addr, env);
+                let val = self.eval(val, env);
+                self.store(bank, bits, &addr, addr_bits, val, env);
+                return true;
+            }
+            Operation::Phi(_) => {
+                match stmt.assignee {
+                    Lvalue::Variable { ref name, .. } => env.get(&ALoc::Register(name.clone())),
+                    Lvalue::Undefined => return true,
+                }
+            }
+            ref op => self.execute(op, stmt.assignee.size().unwrap_or(64), env),
+        };
+
+        if let Lvalue::Variable { ref name, size, .. } = stmt.assignee {
+            env.set(ALoc::Register(name.clone()), value.wrap(size));
+        }
+
+        true
+    }
+
+    /// Value set of `rv`.
+    fn eval(&self, rv: &Rvalue, env: &AbsEnv) -> ValueSet {
+        match rv {
+            &Rvalue::Undefined => ValueSet::Top,
+            &Rvalue::Constant { value, size } => ValueSet::constant(value as i64).wrap(size),
+            &Rvalue::Variable { ref name, offset, size, .. } => {
+                let vs = env.get(&ALoc::Register(name.clone()));
+
+                if offset == 0 {
+                    vs.wrap(size)
+                } else {
+                    match vs.global().and_then(|si| si.as_constant()) {
+                        Some(c) if offset < 64 => ValueSet::constant(((c as u64) >> offset) as i64).wrap(size),
+                        _ => ValueSet::new(Base::Global, StridedInterval::full(size)),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Abstract version of all operations except calls, stores and phis.
+    fn execute(&self, op: &Operation<Rvalue>, bits: usize, env: &AbsEnv) -> ValueSet {
+        let size = |rv: &Rvalue| rv.size().unwrap_or(64);
+
+        match op {
+            &Operation::Add(ref a, ref b) => self.eval(a, env).add(&self.eval(b, env)),
+            &Operation::Subtract(ref a, ref b) => self.eval(a, env).sub(&self.eval(b, env)),
+            &Operation::Multiply(ref a, ref b) => self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.mul(b)),
+            &Operation::ShiftLeft(ref a, ref b) => self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.shl(b)),
+            &Operation::ShiftRightUnsigned(ref a, ref b) => {
+                let n = size(a);
+                self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.shr(b, n, false))
+            }
+            &Operation::ShiftRightSigned(ref a, ref b) => {
+                let n = size(a);
+                self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.shr(b, n, true))
+            }
+            &Operation::DivideUnsigned(ref a, ref b) => {
+                let n = size(a);
+                self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.div(b, n, false))
+            }
+            &Operation::DivideSigned(ref a, ref b) => {
+                let n = size(a);
+                self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.div(b, n, true))
+            }
+            &Operation::Modulo(ref a, ref b) => {
+                let n = size(a);
+                self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.rem(b, n))
+            }
+            &Operation::And(ref a, ref b) => self.eval(a, env).and(&self.eval(b, env), bits),
+            &Operation::InclusiveOr(ref a, ref b) => self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.or(b, false)),
+            &Operation::ExclusiveOr(ref a, ref b) => self.eval(a, env).integers(&self.eval(b, env), bits, &|a, b| a.or(b, true)),
+            &Operation::Equal(ref a, ref b) |
+            &Operation::LessOrEqualUnsigned(ref a, ref b) |
+            &Operation::LessOrEqualSigned(ref a, ref b) |
+            &Operation::LessUnsigned(ref a, ref b) |
+            &Operation::LessSigned(ref a, ref b) => self.compare(op, &self.eval(a, env), &self.eval(b, env), size(a)),
+            &Operation::ZeroExtend(_, ref a) => {
+                let n = size(a);
+                let max = if n >= 63 { i64::MAX } else { (1 << n) - 1 };
+                self.eval(a, env).map(&|si| si.unsigned(n).unwrap_or(StridedInterval::new(1, 0, max)))
+            }
+            &Operation::SignExtend(_, ref a) | &Operation::Move(ref a) => self.eval(a, env),
+            &Operation::Load(ref bank, endianess, bits, ref addr) => {
+                let addr_bits = size(addr);
+                self.load(bank, endianess, bits, &self.eval(addr, env), addr_bits, env)
+            }
+            &Operation::Select(..) | &Operation::Initialize(..) | &Operation::Call(_) | &Operation::Store(..) | &Operation::Phi(_) => {
+                ValueSet::new(Base::Global, StridedInterval::full(bits))
+            }
+        }
+    }
+
+    /// Compares `a` and `b`, both `bits` wide, with `op`. Returns 0, 1 or both.
+    fn compare(&self, op: &Operation<Rvalue>, a: &ValueSet, b: &ValueSet, bits: usize) -> ValueSet {
+        let both = ValueSet::new(Base::Global, StridedInterval::new(1, 0, 1));
+        let (a, b) = match (a, b) {
+            (&ValueSet::Set(ref a), &ValueSet::Set(ref b)) if a.len() == 1 && b.len() == 1 && a.keys().next() == b.keys().next() => {
+                (*a.values().next().unwrap(), *b.values().next().unwrap())
+            }
+            _ => return both,
+        };
+        let (a, b) = match op {
+            &Operation::LessUnsigned(_, _) | &Operation::LessOrEqualUnsigned(_, _) => {
+                match (a.unsigned(bits), b.unsigned(bits)) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => return both,
+                }
+            }
+            _ => (a, b),
+        };
+        let (always, never) = match op {
+            &Operation::Equal(_, _) => (a.as_constant().is_some() && a == b, a.upper < b.lower || b.upper < a.lower),
+            &Operation::LessUnsigned(_, _) | &Operation::LessSigned(_, _) => (a.upper < b.lower, a.lower >= b.upper),
+            _ => (a.upper <= b.lower, a.lower > b.upper),
+        };
+
+        if always {
+            ValueSet::constant(1)
+        } else if never {
+            ValueSet::constant(0)
+        } else {
+            both
+        }
+    }
+
+    /// Memory cells `addr` may point to, if there are few enough.
+    fn cells(&self, bank: &Cow<'static, str>, addr: &ValueSet, bytes: usize, addr_bits: usize) -> Option<Vec<ALoc>> {
+        let mask = if addr_bits >= 64 { !0 } else { (1u64 << addr_bits) - 1 };
+        let map = match addr {
+            &ValueSet::Set(ref map) => map,
+            &ValueSet::Top => return None,
+        };
+        let mut ret = vec![];
+
+        for (base, si) in map.iter() {
+            for value in si.values(MAX_ADDRESSES)? {
+                ret.push(
+                    match *base {
+                        Base::Global => ALoc::Global { bank: bank.clone(), address: value as u64 & mask, size: bytes },
+                        Base::Stack(frame) => ALoc::Stack { frame: frame, offset: value, size: bytes },
+                    }
+                );
+            }
+        }
+
+        Some(ret)
+    }
+
+    fn load(&self, bank: &Cow<'static, str>, endianess: Endianess, bits: usize, addr: &ValueSet, addr_bits: usize, env: &AbsEnv) -> ValueSet {
+        let cells = match self.cells(bank, addr, bits / 8, addr_bits) {
+            Some(cells) => cells,
+            None => return ValueSet::new(Base::Global, StridedInterval::full(bits)),
+        };
+        let mut ret = ValueSet::bottom();
+
+        for cell in cells {
+            let value = match env.locations.get(&cell) {
+                Some(v) => v.clone(),
+                None => {
+                    match cell {
+                        ALoc::Global { address, size, .. } => {
+                            self.read(address, size, endianess).map(|v| ValueSet::constant(v).wrap(bits)).unwrap_or(ValueSet::Top)
+                        }
+                        _ => ValueSet::Top,
+                    }
+                }
+            };
+
+            ret = ret.join(&value);
+        }
+
+        ret
+    }
+
+    /// Reads `size` bytes at `address` from read only memory.
+    fn read(&self, address: u64, size: usize, endianess: Endianess) -> Option<i64> {
+        let end = address.checked_add(size as u64)?;
+
+        if !self.read_only.iter().any(|b| b.start <= address && end <= b.end) || size > 8 {
+            return None;
+        }
+
+        self.region.read_uint(address, size, endianess).map(|v| v as i64)
+    }
+
+    fn store(&self, bank: &Cow<'static, str>, bits: usize, addr: &ValueSet, addr_bits: usize, value: ValueSet, env: &mut AbsEnv) {
+        let bytes = bits / 8;
+        let value = value.wrap(bits);
+
+        match self.cells(bank, addr, bytes, addr_bits) {
+            Some(cells) => {
+                let strong = cells.len() == 1;
+
+                for cell in cells {
+                    let old = env.get(&cell);
+                    let (base, offset) = match cell {
+                        ALoc::Stack { frame, offset, .. } => (Base::Stack(frame), offset),
+                        ALoc::Global { address, .. } => (Base::Global, address as i64),
+                        ALoc::Register(_) => unreachable!(),
+                    };
+
+                    env.forget(bank, base, offset, offset.saturating_add(bytes as i64));
+                    env.set(cell, if strong { value.clone() } else { old.join(&value) });
+                }
+            }
+            None => {
+                match addr {
+                    &ValueSet::Set(ref map) => {
+                        for (base, si) in map.iter() {
+                            env.forget(bank, *base, si.lower, si.upper.saturating_add(bytes as i64));
+                        }
+                    }
+                    &ValueSet::Top => {
+                        env.locations.retain(|loc, _| if let &ALoc::Global { .. } = loc { false } else { true });
+                        env.clobbered = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `target`. Returns false if no callee returns.
+    fn call(&self, frame: u64, target: &Rvalue, env: &mut AbsEnv, calls: &mut Vec<(u64, AbsEnv)>) -> bool {
+        let targets = self.eval(target, env).addresses(target.size().unwrap_or(64), MAX_ADDRESSES).unwrap_or(vec![]);
+        let unknown = targets.is_empty() || targets.iter().any(|a| !self.functions.contains_key(a));
+        let mut ret = if unknown {
+            let mut env = env.clone();
+            let sp = self.stack_pointer.clone();
+            env.clobber(&sp);
+            Some(env)
+        } else {
+            None
+        };
+
+        for addr in targets.into_iter().filter(|a| self.functions.contains_key(a)) {
+            calls.push((addr, self.bind(frame, addr, env)));
+
+            if let Some(after) = self.returned(addr, env) {
+                ret = Some(match ret {
+                    Some(r) => r.join(&after),
+                    None => after,
+                });
+            }
+        }
+
+        match ret {
+            Some(r) => {
+                *env = r;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// State at the entry of `callee` when called from `frame` in state `env`.
+    fn bind(&self, frame: u64, callee: u64, env: &AbsEnv) -> AbsEnv {
+        let sp = env.get(&self.stack_pointer);
+        let sp = match sp {
+            ValueSet::Set(ref map) if map.len() == 1 => map.get(&Base::Stack(frame)).and_then(|si| si.as_constant()),
+            _ => None,
+        };
+        let mut ret = AbsEnv::default();
+
+        for (loc, value) in env.locations.iter() {
+            match loc {
+                &ALoc::Stack { frame: f, .. } if f == callee => {}
+                &ALoc::Stack { frame: f, offset, size } if f == frame && callee != frame && sp.map(|sp| offset >= sp).unwrap_or(false) => {
+                    let arg = ALoc::Stack { frame: callee, offset: offset - sp.unwrap(), size: size };
+                    ret.set(arg, value.clone());
+                    ret.set(loc.clone(), value.clone());
+                }
+                _ => ret.set(loc.clone(), value.clone()),
+            }
+        }
+
+        ret.set(self.stack_pointer.clone(), ValueSet::new(Base::Stack(callee), StridedInterval::constant(0)));
+        ret
+    }
+
+    /// State after `callee` returns to a caller in state `env`. None if it never returns.
+    fn returned(&self, callee: u64, env: &AbsEnv) -> Option<AbsEnv> {
+        let exit = self.exits.get(&callee)?;
+        let entry = self.entries.get(&callee);
+        let mut ret = env.clone();
+
+        if exit.clobbered {
+            let sp = self.stack_pointer.clone();
+            ret.clobber(&sp);
+        }
+
+        for (loc, value) in exit.locations.iter() {
+            let unchanged = entry.and_then(|e| e.locations.get(loc)) == Some(value);
+
+            match loc {
+                &ALoc::Stack { frame, .. } if frame == callee => {}
+                _ if *loc == self.stack_pointer => {}
+                _ if unchanged && env.locations.contains_key(loc) => {}
+                _ => ret.set(loc.clone(), value.clone()),
+            }
+        }
+
+        Some(ret)
+    }
+
+    /// Restricts `env` to the states in which `guard` is true. None if there are none.
+    fn constrain(&self, bb: &BasicBlock, guard: Option<&Guard>, env: &AbsEnv) -> Option<AbsEnv> {
+        let (flag, expected) = match guard {
+            Some(&Guard::False) => return None,
+            Some(&Guard::Predicate { flag: Rvalue::Variable { ref name, .. }, expected }) => (name, expected),
+            _ => return Some(env.clone()),
+        };
+        let flag_loc = ALoc::Register(flag.clone());
+        let flag_value = env.get(&flag_loc).global().and_then(|si| si.as_constant());
+
+        if flag_value.is_some() && flag_value != Some(expected as i64) {
+            return None;
+        }
+
+        let mut ret = env.clone();
+        ret.set(flag_loc, ValueSet::constant(expected as i64));
+
+        // Last comparison setting the flag and whether its variable stays unchanged afterwards
+        let stmts = bb.statements().collect::<Vec<_>>();
+        let pos = stmts.iter().rposition(|s| if let Lvalue::Variable { ref name, .. } = s.assignee { name == flag } else { false });
+        let (op, rest) = match pos {
+            Some(p) => (&stmts[p].op, &stmts[p + 1..]),
+            None => return Some(ret),
+        };
+        let (var, bits, c, var_left) = match op {
+            &Operation::Equal(Rvalue::Variable { ref name, size, offset: 0, .. }, Rvalue::Constant { value, .. }) |
+            &Operation::LessUnsigned(Rvalue::Variable { ref name, size, offset: 0, .. }, Rvalue::Constant { value, .. }) |
+            &Operation::LessSigned(Rvalue::Variable { ref name, size, offset: 0, .. }, Rvalue::Constant { value, .. }) |
+            &Operation::LessOrEqualUnsigned(Rvalue::Variable { ref name, size, offset: 0, .. }, Rvalue::Constant { value, .. }) |
+            &Operation::LessOrEqualSigned(Rvalue::Variable { ref name, size, offset: 0, .. }, Rvalue::Constant { value, .. }) => (name, size, value, true),
+            &Operation::Equal(Rvalue::Constant { value, .. }, Rvalue::Variable { ref name, size, offset: 0, .. }) |
+            &Operation::LessUnsigned(Rvalue::Constant { value, .. }, Rvalue::Variable { ref name, size, offset: 0, .. }) |
+            &Operation::LessSigned(Rvalue::Constant { value, .. }, Rvalue::Variable { ref name, size, offset: 0, .. }) |
+            &Operation::LessOrEqualUnsigned(Rvalue::Constant { value, .. }, Rvalue::Variable { ref name, size, offset: 0, .. }) |
+            &Operation::LessOrEqualSigned(Rvalue::Constant { value, .. }, Rvalue::Variable { ref name, size, offset: 0, .. }) => (name, size, value, false),
+            _ => return Some(ret),
+        };
+
+        if rest.iter().any(|s| if let Lvalue::Variable { ref name, .. } = s.assignee { name == var } else { false }) {
+            return Some(ret);
+        }
+
+        let loc = ALoc::Register(var.clone());
+        let si = match env.get(&loc).wrap(bits).global() {
+            Some(si) => si,
+            None => return Some(ret),
+        };
+        let c = StridedInterval::constant(c as i64).wrap(bits).lower;
+        let (min, max) = StridedInterval::range(bits);
+        let unsigned = si.unsigned(bits).is_some() && c >= 0;
+        // Range of `var` if the comparison is true (`expected`) or false
+        let range = match (op, var_left, expected) {
+            (&Operation::Equal(..), _, true) => Some((c, c)),
+            (&Operation::Equal(..), _, false) => None,
+            (&Operation::LessUnsigned(..), true, true) if c >= 0 => Some((0, c - 1)),
+            (&Operation::LessUnsigned(..), true, false) if unsigned => Some((c, max)),
+            (&Operation::LessUnsigned(..), false, true) if unsigned => Some((c.saturating_add(1), max)),
+            (&Operation::LessUnsigned(..), false, false) if c >= 0 => Some((0, c)),
+            (&Operation::LessOrEqualUnsigned(..), true, true) if c >= 0 => Some((0, c)),
+            (&Operation::LessOrEqualUnsigned(..), true, false) if unsigned => Some((c.saturating_add(1), max)),
+            (&Operation::LessOrEqualUnsigned(..), false, true) if unsigned => Some((c, max)),
+            (&Operation::LessOrEqualUnsigned(..), false, false) if c > 0 => Some((0, c - 1)),
+            (&Operation::LessSigned(..), true, true) => Some((min, c.saturating_sub(1))),
+            (&Operation::LessSigned(..), true, false) => Some((c, max)),
+            (&Operation::LessSigned(..), false, true) => Some((c.saturating_add(1), max)),
+            (&Operation::LessSigned(..), false, false) => Some((min, c)),
+            (&Operation::LessOrEqualSigned(..), true, true) => Some((min, c)),
+            (&Operation::LessOrEqualSigned(..), true, false) => Some((c.saturating_add(1), max)),
+            (&Operation::LessOrEqualSigned(..), false, true) => Some((c, max)),
+            (&Operation::LessOrEqualSigned(..), false, false) => Some((min, c.saturating_sub(1))),
+            _ => None,
+        };
+
+        match range {
+            Some((lower, upper)) => {
+                let si = if unsigned && lower >= 0 { si.unsigned(bits).unwrap() } else { si };
+                let new = si.meet_range(lower, upper)?;
+                ret.set(loc, ValueSet::new(Base::Global, new.wrap(bits)));
+                Some(ret)
+            }
+            None => Some(ret),
+        }
+    }
+
+    /// State before `pp` inside `func`. None if the statement isn't reachable.
+    pub fn before(&self, func: &Function, pp: &ProgramPoint) -> Option<AbsEnv> {
+        let vx = func.find_basic_block_by_start(pp.address)?;
+        let mut env = self.blocks.get(&func.start())?.get(&vx)?.clone();
+
+        match func.cfg().vertex_label(vx) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) => {
+                if self.run(func.start(), bb, pp.position, &mut env, &mut vec![]) {
+                    Some(env)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Value set of `rv` before `pp` inside `func`. Empty if the statement isn't reachable.
+    pub fn value(&self, func: &Function, pp: &ProgramPoint, rv: &Rvalue) -> ValueSet {
+        match self.before(func, pp) {
+            Some(env) => self.eval(rv, &env),
+            None => ValueSet::bottom(),
+        }
+    }
+
+    /// State `func` was last analyzed with.
+    pub fn entry(&self, func: &Function) -> Option<&AbsEnv> {
+        self.entries.get(&func.start())
+    }
+
+    /// Join of the states at all returns of `func`. None if it never returns.
+    pub fn exit(&self, func: &Function) -> Option<&AbsEnv> {
+        self.exits.get(&func.start())
+    }
+
+    /// Possible targets of the unresolved jumps in `func`, by jump.
+    pub fn jump_targets(&self, func: &Function) -> HashMap<ControlFlowRef, Vec<u64>> {
+        let cfg = func.cfg();
+        let states = match self.blocks.get(&func.start()) {
+            Some(s) => s,
+            None => return HashMap::new(),
+        };
+        let mut ret = HashMap::new();
+
+        for vx in cfg.vertices() {
+            let target = match cfg.vertex_label(vx) {
+                Some(&ControlFlowTarget::Unresolved(ref rv @ Rvalue::Variable { .. })) => rv,
+                _ => continue,
+            };
+            let mut value = ValueSet::bottom();
+
+            for e in cfg.in_edges(vx) {
+                let src = cfg.source(e);
+
+                if let (Some(&ControlFlowTarget::Resolved(ref bb)), Some(state)) = (cfg.vertex_label(src), states.get(&src)) {
+                    let mut env = state.clone();
+
+                    if self.run(func.start(), bb, bb.statements().count(), &mut env, &mut vec![]) {
+                        if let Some(env) = self.constrain(bb, cfg.edge_label(e), &env) {
+                            value = value.join(&self.eval(target, &env));
+                        }
+                    }
+                }
+            }
+
+            if let Some(targets) = value.addresses(target.size().unwrap_or(64), MAX_ADDRESSES) {
+                if !targets.is_empty() {
+                    ret.insert(vx, targets);
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Possible targets of all calls in `func` with a non-constant operand.
+    pub fn call_targets(&self, func: &Function) -> Vec<(ProgramPoint, Vec<u64>)> {
+        let mut ret = vec![];
+
+        for bb in func.basic_blocks() {
+            for (pos, stmt) in bb.statements().enumerate() {
+                if let Operation::Call(ref target @ Rvalue::Variable { .. }) = stmt.op {
+                    let pp = ProgramPoint { address: bb.area.start, position: pos };
+                    let value = self.value(func, &pp, target);
+
+                    if let Some(targets) = value.addresses(target.size().unwrap_or(64), MAX_ADDRESSES) {
+                        if !targets.is_empty() {
+                            ret.push((pp, targets));
+                        }
+                    }
+                }
+            }
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{ControlFlowGraph, Mnemonic};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn var(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn block(start: u64, stmts: Vec<Statement>) -> BasicBlock {
+        let mne = Mnemonic::new(start..start + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        BasicBlock::from_vec(vec![mne])
+    }
+
+    fn function(region: &Region, blocks: Vec<ControlFlowTarget>, edges: Vec<(usize, usize, Guard)>) -> Function {
+        let mut cfg = ControlFlowGraph::new();
+        let vxs = blocks.into_iter().map(|b| cfg.add_vertex(b)).collect::<Vec<_>>();
+
+        for (from, to, g) in edges {
+            cfg.add_edge(g, vxs[from], vxs[to]);
+        }
+
+        let mut func = Function::undefined(0, None, region, None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vxs[0]);
+        func
+    }
+
+    #[test]
+    fn strided_interval() {
+        let a = StridedInterval::new(4, 0, 10);
+        let b = StridedInterval::constant(2);
+
+        assert_eq!(a, StridedInterval::new(4, 0, 8));
+        assert_eq!(a.add(&b), StridedInterval::new(4, 2, 10));
+        assert_eq!(a.join(&b), StridedInterval::new(2, 0, 8));
+        assert_eq!(a.mul(&StridedInterval::constant(-2)), StridedInterval::new(8, -16, 0));
+        assert_eq!(a.meet_range(1, 7), Some(StridedInterval::new(4, 4, 4)));
+        assert_eq!(a.meet_range(9, 20), None);
+        assert_eq!(a.values(10), Some(vec![0, 4, 8]));
+        assert_eq!(StridedInterval::constant(0xff).wrap(8), StridedInterval::constant(-1));
+        assert_eq!(StridedInterval::new(1, 250, 260).wrap(8), StridedInterval::new(1, -6, 4));
+        assert_eq!(StridedInterval::new(1, 100, 200).wrap(8), StridedInterval::full(8));
+        assert_eq!(StridedInterval::constant(i64::MAX).add(&StridedInterval::constant(1)), StridedInterval::constant(i64::MIN));
+        assert_eq!(a.widen(&StridedInterval::new(4, 0, 12)).upper, i64::MAX - 3);
+        assert_eq!(StridedInterval::new(1, 0x1004, 0x1010).and(&StridedInterval::constant(-16)), StridedInterval::new(1, 0xff5, 0x1010));
+    }
+
+    /*
+     * i = 0
+     * while(i < 40) {
+     *   i += 4
+     * }
+     */
+    #[test]
+    fn loop_bound() {
+        let region = Region::undefined("ram".to_owned(), 0x100);
+        let i = var("i", 32);
+        let flag = var("flag", 1);
+        let g = Guard::from_flag(&flag.clone().into()).ok().unwrap();
+        let func = function(
+            &region,
+            vec![
+                ControlFlowTarget::Resolved(block(0, vec![Statement { op: Operation::Move(Rvalue::new_u32(0)), assignee: i.clone() }])),
+                ControlFlowTarget::Resolved(block(1, vec![Statement { op: Operation::LessUnsigned(i.clone().into(), Rvalue::new_u32(40)), assignee: flag.clone() }])),
+                ControlFlowTarget::Resolved(block(2, vec![Statement { op: Operation::Add(i.clone().into(), Rvalue::new_u32(4)), assignee: i.clone() }])),
+                ControlFlowTarget::Resolved(block(3, vec![])),
+            ],
+            vec![(0, 1, Guard::always()), (1, 2, g.clone()), (1, 3, g.negation()), (2, 1, Guard::always())],
+        );
+        let mut prog = Program::new("prog");
+
+        prog.insert(func.clone());
+
+        let mut vsa = Vsa::new(&prog, &region, "RSP");
+        vsa.analyze();
+
+        let body = vsa.value(&func, &ProgramPoint { address: 2, position: 0 }, &i.clone().into());
+        assert_eq!(body, ValueSet::new(Base::Global, StridedInterval::new(4, 0, 36)));
+
+        let after = vsa.value(&func, &ProgramPoint { address: 3, position: 0 }, &i.clone().into());
+        assert_eq!(after.global().map(|si| si.stride), Some(4));
+        assert!(after.global().map(|si| si.contains(40)).unwrap_or(false));
+    }
+
+    #[test]
+    fn stack_slots() {
+        let region = Region::undefined("ram".to_owned(), 0x100);
+        let sp = var("RSP", 64);
+        let x = var("x", 64);
+        let func = function(
+            &region,
+            vec![
+                ControlFlowTarget::Resolved(
+                    block(
+                        0,
+                        vec![
+                            Statement { op: Operation::Subtract(sp.clone().into(), Rvalue::new_u64(8)), assignee: sp.clone() },
+                            Statement {
+                                op: Operation::Store(Cow::Borrowed("ram"), Endianess::Little, 64, sp.clone().into(), Rvalue::new_u64(42)),
+                                assignee: Lvalue::Undefined,
+                            },
+                            Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Little, 64, sp.clone().into()), assignee: x.clone() },
+                        ]
+                    )
+                ),
+            ],
+            vec![],
+        );
+        let mut prog = Program::new("prog");
+
+        prog.insert(func.clone());
+
+        let mut vsa = Vsa::new(&prog, &region, "RSP");
+        vsa.analyze();
+
+        let exit = vsa.exit(&func).unwrap();
+        assert_eq!(exit.get(&ALoc::Register(Cow::Borrowed("x"))), ValueSet::constant(42));
+        assert_eq!(exit.get(&ALoc::Register(Cow::Borrowed("RSP"))), ValueSet::new(Base::Stack(0), StridedInterval::constant(-8)));
+        assert_eq!(exit.get(&ALoc::Stack { frame: 0, offset: -8, size: 8 }), ValueSet::constant(42));
+    }
+
+    /*
+     * f: RBX = 7; call g; y = RAX + RBX
+     * g: RAX = 5
+     */
+    #[test]
+    fn interprocedural() {
+        let region = Region::undefined("ram".to_owned(), 0x200);
+        let rax = var("RAX", 64);
+        let rbx = var("RBX", 64);
+        let y = var("y", 64);
+        let f = function(
+            &region,
+            vec![
+                ControlFlowTarget::Resolved(
+                    block(
+                        0,
+                        vec![
+                            Statement { op: Operation::Move(Rvalue::new_u64(7)), assignee: rbx.clone() },
+                            Statement { op: Operation::Call(Rvalue::new_u64(0x100)), assignee: Lvalue::Undefined },
+                            Statement { op: Operation::Add(rax.clone().into(), rbx.clone().into()), assignee: y.clone() },
+                        ]
+                    )
+                ),
+            ],
+            vec![],
+        );
+        let g = function(
+            &region,
+            vec![ControlFlowTarget::Resolved(block(0x100, vec![Statement { op: Operation::Move(Rvalue::new_u64(5)), assignee: rax.clone() }]))],
+            vec![],
+        );
+        let mut prog = Program::new("prog");
+
+        prog.insert(f.clone());
+        prog.insert(g.clone());
+
+        let mut vsa = Vsa::new(&prog, &region, "RSP");
+        vsa.analyze();
+
+        let exit = vsa.exit(&f).unwrap();
+        assert_eq!(exit.get(&ALoc::Register(Cow::Borrowed("y"))), ValueSet::constant(12));
+        assert_eq!(exit.get(&ALoc::Register(Cow::Borrowed("RSP"))), ValueSet::new(Base::Stack(0), StridedInterval::constant(0)));
+        assert_eq!(
+            vsa.entry(&g).unwrap().get(&ALoc::Register(Cow::Borrowed("RBX"))),
+            ValueSet::constant(7)
+        );
+    }
+
+    /*
+     * if(x & 0xff < 4) goto *table[x & 0xff]
+     */
+    #[test]
+    fn jump_table() {
+        let mut data = vec![0u8; 0x210];
+        for i in 0..4 {
+            data[0x200 + i * 4] = 0x10 * (i as u8 + 1);
+        }
+        let region = Region::wrap("ram".to_owned(), data);
+        let x = var("x", 32);
+        let idx = var("idx", 32);
+        let addr = var("addr", 32);
+        let tgt = var("tgt", 32);
+        let flag = var("flag", 1);
+        let g = Guard::from_flag(&flag.clone().into()).ok().unwrap();
+        let func = function(
+            &region,
+            vec![
+                ControlFlowTarget::Resolved(
+                    block(
+                        0,
+                        vec![
+                            Statement { op: Operation::And(x.clone().into(), Rvalue::new_u32(0xff)), assignee: idx.clone() },
+                            Statement { op: Operation::LessUnsigned(idx.clone().into(), Rvalue::new_u32(4)), assignee: flag.clone() },
+                        ]
+                    )
+                ),
+                ControlFlowTarget::Resolved(
+                    block(
+                        1,
+                        vec![
+                            Statement { op: Operation::ShiftLeft(idx.clone().into(), Rvalue::new_u32(2)), assignee: addr.clone() },
+                            Statement { op: Operation::Add(addr.clone().into(), Rvalue::new_u32(0x200)), assignee: addr.clone() },
+                            Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Little, 32, addr.clone().into()), assignee: tgt.clone() },
+                        ]
+                    )
+                ),
+                ControlFlowTarget::Unresolved(tgt.clone().into()),
+                ControlFlowTarget::Resolved(block(2, vec![])),
+            ],
+            vec![(0, 1, g.clone()), (0, 3, g.negation()), (1, 2, Guard::always())],
+        );
+        let mut prog = Program::new("prog");
+
+        prog.insert(func.clone());
+
+        let mut vsa = Vsa::new(&prog, &region, "RSP").read_only(Bound::new(0x200, 0x210));
+        vsa.analyze();
+
+        let targets = vsa.jump_targets(&func);
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets.values().next(), Some(&vec![0x10, 0x20, 0x30, 0x40]));
+
+        let mut without = Vsa::new(&prog, &region, "RSP");
+        without.analyze();
+        assert!(without.jump_targets(&func).is_empty());
+    }
+}
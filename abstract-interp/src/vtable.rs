@@ -0,0 +1,288 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! C++ vtables and virtual call resolution.
+//!
+//! Constructors store the address of their class' vtable in the object, so every vtable address
+//! appears as a constant in the code. [`find_vtables`] follows constants pointing into read-only
+//! data sections and reads the array of code pointers starting there. Both the Itanium and the
+//! MSVC ABI put a pointer to the class' RTTI right before the first slot, which is recorded if
+//! it points into a data section.
+//!
+//! [`recover_classes`] adds the classes named by the RTTI records to `Project::type_database`.
+//!
+//! [`devirtualize`] resolves calls through a slot of a known vtable. The vtable pointer is tracked
+//! by interprocedural constant propagation, which follows `this` pointers into constructors
+//! (see the [`constprop`] module). Each resolved call adds an analyzed edge to the call graph.
+//!
+//! [`find_vtables`]: fn.find_vtables.html
+//! [`recover_classes`]: fn.recover_classes.html
+//! [`devirtualize`]: fn.devirtualize.html
+//! [`constprop`]: ../constprop/index.html
+
+use ConstantPropagation;
+use ProgramPoint;
+use panopticon_core::{Bound, Endianess, Lvalue, Operation, Project, Rvalue, Section, TypeDatabase};
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+
+/// Array of virtual function pointers.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Vtable {
+    /// Address of the first slot, the value of an object's vtable pointer
+    pub address: u64,
+    /// Address of the RTTI record of the class, if present
+    pub rtti: Option<u64>,
+    /// Functions in each slot
+    pub entries: Vec<u64>,
+}
+
+/// Call resolved through a vtable slot.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct VirtualCall {
+    /// Start of the calling function
+    pub function: u64,
+    /// Call statement
+    pub point: ProgramPoint,
+    /// Address of the vtable
+    pub vtable: u64,
+    /// Index of the slot read
+    pub slot: usize,
+    /// Called function
+    pub target: u64,
+}
+
+/// Finds all vtables referenced by the code of `proj`. Code pointers are `word` bytes long.
+pub fn find_vtables(proj: &Project, word: usize, endianess: Endianess) -> Vec<Vtable> {
+    let code = |a: u64| proj.sections.iter().any(|s| s.execute && s.area.start <= a && a < s.area.end);
+    let data = |a: u64| proj.sections.iter().any(|s| !s.execute && s.area.start <= a && a < s.area.end);
+    let rodata = proj.sections.iter().filter(|s| s.read && !s.write && !s.execute).collect::<Vec<&Section>>();
+    let mut starts = BTreeSet::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for stmt in func.statements() {
+                for rv in stmt.op.operands() {
+                    if let &Rvalue::Constant { value, .. } = rv {
+                        if value % word as u64 == 0 && rodata.iter().any(|s| s.area.start <= value && value < s.area.end) {
+                            starts.insert(value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut ret = vec![];
+
+    for &start in starts.iter() {
+        let section = rodata.iter().find(|s| s.area.start <= start && start < s.area.end).unwrap();
+        let end = starts.range(start + 1..).next().cloned().unwrap_or(section.area.end).min(section.area.end);
+        let mut entries = vec![];
+        let mut addr = start;
+
+        while addr + word as u64 <= end {
+            match read(proj, &section.area, addr, word, endianess) {
+                Some(w) if code(w) => entries.push(w),
+                _ => break,
+            }
+            addr += word as u64;
+        }
+
+        if !entries.is_empty() {
+            let rtti = start.checked_sub(word as u64).and_then(|a| read(proj, &section.area, a, word, endianess)).and_then(|w| if data(w) { Some(w) } else { None });
+
+            ret.push(Vtable { address: start, rtti: rtti, entries: entries });
+        }
+    }
+
+    ret
+}
+
+/// Resolves calls through the slots of `vtables` in all functions of `proj` and adds the targets
+/// to the call graph as analyzed calls.
+pub fn devirtualize(proj: &mut Project, vtables: &[Vtable], word: usize) -> Vec<VirtualCall> {
+    let slots = vtables
+        .iter()
+        .flat_map(|vt| vt.entries.iter().enumerate().map(move |(i, &e)| (vt.address + (i * word) as u64, (vt.address, i, e))))
+        .collect::<BTreeMap<_, _>>();
+    let mut ret = vec![];
+
+    for prog in proj.code.iter_mut() {
+        let mut calls = vec![];
+
+        {
+            let mut cp = ConstantPropagation::new(prog);
+            cp.analyze();
+
+            for func in prog.functions() {
+                let loads = cp.load_addresses(func).into_iter().collect::<BTreeMap<_, _>>();
+
+                for bb in func.basic_blocks() {
+                    let stmts = bb.statements().collect::<Vec<_>>();
+
+                    for (pos, stmt) in stmts.iter().enumerate() {
+                        let target = match stmt.op {
+                            Operation::Call(Rvalue::Variable { ref name, subscript, .. }) => (name, subscript),
+                            _ => continue,
+                        };
+                        // the last assignment to the call target before the call
+                        let load = stmts[..pos]
+                            .iter()
+                            .rposition(
+                                |s| match s.assignee {
+                                    Lvalue::Variable { ref name, subscript, .. } => (name, subscript) == target,
+                                    Lvalue::Undefined => false,
+                                }
+                            )
+                            .and_then(|p| loads.get(&ProgramPoint { address: bb.area.start, position: p }));
+
+                        if let Some(&(vtable, slot, target)) = load.and_then(|a| slots.get(a)) {
+                            let point = ProgramPoint { address: bb.area.start, position: pos };
+                            calls.push((func.uuid().clone(), VirtualCall { function: func.start(), point: point, vtable: vtable, slot: slot, target: target }));
+                        }
+                    }
+                }
+            }
+        }
+
+        for &(ref caller, ref call) in calls.iter() {
+            prog.insert_analyzed_calls(caller, &[call.target]);
+        }
+
+        ret.extend(calls.into_iter().map(|(_, call)| call));
+    }
+
+    ret
+}
+
+/// Parses the RTTI records of `vtables` and adds the classes to the type database of `proj`.
+/// Returns the number of vtables with a known class.
+pub fn recover_classes(proj: &mut Project, vtables: &[Vtable], word: usize, endianess: Endianess) -> usize {
+    let mut db = mem::replace(&mut proj.type_database, TypeDatabase::new());
+    let mut ret = 0;
+
+    for rtti in vtables.iter().filter_map(|vt| vt.rtti) {
+        if db.add_itanium(proj.region(), rtti, word, endianess) || (endianess == Endianess::Little && db.add_msvc(proj.region(), rtti, word)) {
+            ret += 1;
+        }
+    }
+
+    proj.type_database = db;
+    ret
+}
+
+/// Reads the `word` bytes at `address` inside `area`.
+fn read(proj: &Project, area: &Bound, address: u64, word: usize, endianess: Endianess) -> Option<u64> {
+    if address < area.start || address + word as u64 > area.end || word > 8 {
+        return None;
+    }
+
+    proj.region().read_uint(address, word, endianess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, CallingConvention, ControlFlowGraph, ControlFlowTarget, Function, Layer, Mnemonic, Program, Region, Statement};
+    use panopticon_graph_algos::{AdjacencyMatrixGraphTrait, MutableGraphTrait};
+    use std::borrow::Cow;
+
+    fn var(name: &'static str) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: 64, subscript: None }
+    }
+
+    fn function(start: u64, stmts: Vec<Statement>) -> Function {
+        let mne = Mnemonic::new(start..start + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, &Region::undefined("RAM".to_owned(), 0x1000), None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func.set_calling_convention(Some(CallingConvention::SysV64));
+        func
+    }
+
+    fn section(name: &str, start: u64, end: u64, execute: bool) -> Section {
+        Section { name: name.to_string(), area: Bound::new(start, end), file_size: end - start, file_offset: 0, read: true, write: false, execute: execute }
+    }
+
+    fn words(ws: &[u64]) -> Vec<u8> {
+        ws.iter().flat_map(|w| (0..8).map(move |i| (w >> (i * 8)) as u8)).collect()
+    }
+
+    /*
+     * ctor:   mov [rdi], vtable
+     * main:   lea rdi, [rsp-16]; call ctor; lea rcx, [rsp-16]; mov rax, [rcx]; call [rax+8]
+     * vtable: typeinfo, 0x100, 0x180
+     */
+    #[test]
+    fn virtual_call() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x7f8, 0x810), Layer::wrap(words(&[0x900, 0x100, 0x180]))));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        proj.sections = vec![section(".text", 0, 0x400, true), section(".rodata", 0x7f0, 0x900, false), section(".data.rel.ro", 0x900, 0x920, false)];
+
+        let rsp = var("RSP");
+        let ctor = function(
+            0x200,
+            vec![
+                Statement {
+                    op: Operation::Store(Cow::Borrowed("RAM"), Endianess::Little, 64, var("RDI").into(), Rvalue::new_u64(0x800)),
+                    assignee: Lvalue::Undefined,
+                },
+            ]
+        );
+        let main = function(
+            0,
+            vec![
+                Statement { op: Operation::Subtract(rsp.clone().into(), Rvalue::new_u64(16)), assignee: var("RDI") },
+                Statement { op: Operation::Call(Rvalue::new_u64(0x200)), assignee: Lvalue::Undefined },
+                Statement { op: Operation::Subtract(rsp.clone().into(), Rvalue::new_u64(16)), assignee: var("RCX") },
+                Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 64, var("RCX").into()), assignee: var("RAX") },
+                Statement { op: Operation::Add(var("RAX").into(), Rvalue::new_u64(8)), assignee: var("RAX") },
+                Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 64, var("RAX").into()), assignee: var("RAX") },
+                Statement { op: Operation::Call(var("RAX").into()), assignee: Lvalue::Undefined },
+            ]
+        );
+        let mut prog = Program::new("prog");
+
+        prog.insert(function(0x100, vec![]));
+        prog.insert(function(0x180, vec![]));
+        prog.insert(ctor);
+        prog.insert(main);
+        proj.code.push(prog);
+
+        let vtables = find_vtables(&proj, 8, Endianess::Little);
+        assert_eq!(vtables, vec![Vtable { address: 0x800, rtti: Some(0x900), entries: vec![0x100, 0x180] }]);
+
+        let calls = devirtualize(&mut proj, &vtables, 8);
+        assert_eq!(calls, vec![VirtualCall { function: 0, point: ProgramPoint { address: 0, position: 6 }, vtable: 0x800, slot: 1, target: 0x180 }]);
+
+        let prog = &proj.code[0];
+        let from = prog.find_function_by_entry(0).unwrap();
+        let to = prog.find_function_by_entry(0x180).unwrap();
+        assert!(prog.call_graph.edge(from, to).is_some());
+
+        let caller = prog.functions().find(|f| f.start() == 0).unwrap().uuid().clone();
+        let callee = prog.functions().find(|f| f.start() == 0x180).unwrap().uuid().clone();
+        assert!(prog.is_analyzed_call(&caller, &callee));
+    }
+}
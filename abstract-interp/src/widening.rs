@@ -16,20 +16,20 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use {Avalue, Constraint, ProgramPoint, lift};
+use {AbstractDomain, Constraint, ProgramPoint, WideningThresholds, lift};
 use serde::{Serialize,Deserialize};
 use panopticon_core::{Rvalue, Operation};
 
 /// Mihaila et.al. Widening Point inferring cofibered domain. This domain is parameterized with a
 /// child domain.
 #[derive(Debug,PartialEq,Eq,Clone,Hash,Serialize,Deserialize)]
-#[serde(bound(deserialize = "A: Avalue + Serialize + for<'a> Deserialize<'a>"))]
-pub struct Widening<A: Avalue + Serialize + for<'a> Deserialize<'a>> {
+#[serde(bound(deserialize = "A: AbstractDomain + Serialize + for<'a> Deserialize<'a>"))]
+pub struct Widening<A: AbstractDomain + Serialize + for<'a> Deserialize<'a>> {
     value: A,
     point: Option<ProgramPoint>,
 }
 
-impl<A: Avalue> Avalue for Widening<A> {
+impl<A: AbstractDomain> AbstractDomain for Widening<A> {
     fn abstract_value(v: &Rvalue) -> Self {
         Widening { value: A::abstract_value(v), point: None }
     }
@@ -75,6 +75,10 @@ impl<A: Avalue> Avalue for Widening<A> {
         Widening { value: self.value.widen(&s.value), point: self.point.clone() }
     }
 
+    fn widen_with_thresholds(&self, s: &Self, thresholds: &WideningThresholds) -> Self {
+        Widening { value: self.value.widen_with_thresholds(&s.value, thresholds), point: self.point.clone() }
+    }
+
     fn combine(&self, s: &Self) -> Self {
         Widening {
             value: self.value.combine(&s.value),
@@ -23,7 +23,7 @@ pub enum Amd64 {}
 
 #[derive(Clone,PartialEq,Copy,Debug)]
 pub enum Mode {
-    Real, // Real mode / Virtual 8086 mode
+    Real(u16), // Real mode / Virtual 8086 mode, value of CS
     Protected, // Protected mode / Long compatibility mode
     Long, // Long 64-bit mode
 }
@@ -31,7 +31,7 @@ pub enum Mode {
 impl Mode {
     pub fn alt_bits(&self) -> usize {
         match self {
-            &Mode::Real => 32,
+            &Mode::Real(_) => 32,
             &Mode::Protected => 16,
             &Mode::Long => 16,
         }
@@ -39,7 +39,7 @@ impl Mode {
 
     pub fn bits(&self) -> usize {
         match self {
-            &Mode::Real => 16,
+            &Mode::Real(_) => 16,
             &Mode::Protected => 32,
             &Mode::Long => 64,
         }
@@ -70,13 +70,13 @@ impl Architecture for Amd64 {
         debug!("disass @ {:#x}: {:?}", p, buf);
 
         let ret = ::disassembler::read(*cfg, &buf, p).and_then(
-            |(len, mne, mut jmp)| {
+            |(len, mne, mut jmp, next)| {
                 Ok(
                     Match::<Amd64> {
                         tokens: buf[0..len as usize].to_vec(),
                         mnemonics: vec![mne],
                         jumps: jmp.drain(..).map(|x| (p, x.0, x.1)).collect::<Vec<_>>(),
-                        configuration: cfg.clone(),
+                        configuration: next,
                     }
                 )
             }
@@ -86,4 +86,12 @@ impl Architecture for Amd64 {
 
         ret
     }
+
+    fn mode(cfg: &Self::Configuration, _: u64) -> Option<String> {
+        Some(format!("{}-bit", cfg.bits()))
+    }
+
+    fn flags() -> &'static [&'static str] {
+        &["CF", "PF", "AF", "ZF", "SF", "OF"]
+    }
 }
@@ -17,7 +17,7 @@
  */
 
 use Mode;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
 
 use panopticon_core::{Guard, Lvalue, Mnemonic, Result, Rvalue, Statement};
 use std::cmp;
@@ -669,6 +669,7 @@ pub enum OperandType {
     sd,
     ss,
     /*si,*/
+    t,
     v,
     w,
     x,
@@ -996,6 +997,43 @@ impl Display for Operand {
     }
 }
 
+fn is_real(mode: Mode) -> bool {
+    if let Mode::Real(_) = mode { true } else { false }
+}
+
+/// Whether a 0xc4, 0xc5 or 0x62 byte followed by `next` starts a VEX or EVEX prefix. Outside of
+/// long mode these bytes are LES, LDS and BOUND unless the following byte would be an invalid
+/// register ModR/M for them.
+fn is_vex(mode: Mode, next: Option<&u8>) -> bool {
+    match (mode, next) {
+        (Mode::Long, Some(_)) => true,
+        (Mode::Protected, Some(&b)) => b & 0b11000000 == 0b11000000,
+        _ => false,
+    }
+}
+
+/// VEX and EVEX prefixes encode the mandatory prefix themselves. They may follow segment
+/// overrides and 0x67 but not LOCK, 0x66, 0xf2 or 0xf3.
+fn check_vex_prefixes(prefix: &Prefix, operand_override: bool) -> Result<()> {
+    if prefix.lock || prefix.repe || prefix.repne || operand_override {
+        Err("Invalid instruction: LOCK, 0x66, 0xf2 or 0xf3 prefix before VEX or EVEX".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Target of a near jump to `target`. In real mode the instruction pointer wraps around at the end
+/// of the code segment.
+fn near_target(mode: Mode, target: u64, addrsz: usize) -> Operand {
+    match mode {
+        Mode::Real(cs) => {
+            let base = (cs as u64) << 4;
+            Operand::Immediate(base + (target.wrapping_sub(base) & 0xffff), 32)
+        }
+        _ => Operand::Immediate(target, addrsz),
+    }
+}
+
 fn read_operand(
     spec: &OperandSpec,
     tail: &mut Tail,
@@ -1019,6 +1057,11 @@ fn read_operand(
         (&OperandSpec(AddressingMethod::A, OperandType::v), 16) => Ok(Operand::Immediate(tail.read_u16().ok().unwrap() as u64, 16)),
         (&OperandSpec(AddressingMethod::A, OperandType::v), 32) => Ok(Operand::Immediate(tail.read_u32().ok().unwrap() as u64, 32)),
         (&OperandSpec(AddressingMethod::A, OperandType::v), 64) => Ok(Operand::Immediate(tail.read_u64().ok().unwrap(), 64)),
+        (&OperandSpec(AddressingMethod::A, OperandType::p), opsz) if is_real(mode) => {
+            let off = if opsz == 32 { tail.read_u32()? as u64 } else { tail.read_u16()? as u64 };
+            let seg = tail.read_u16()? as u64;
+            Ok(Operand::Immediate((seg << 4).wrapping_add(off), 32))
+        }
         (&OperandSpec(AddressingMethod::A, OperandType::p), 16) => Ok(Operand::Immediate(tail.read_u32().ok().unwrap() as u64, 32)),
         (&OperandSpec(AddressingMethod::A, OperandType::p), 32) => {
             let imm16 = tail.read_u16().ok().unwrap() as u64;
@@ -1092,6 +1135,14 @@ fn read_operand(
                 64,
             )
         }
+        (&OperandSpec(AddressingMethod::E, OperandType::t), _) => {
+            indirect(
+                read_effective_address(mode, seg, tail, rex, 80, addrsz, addr)?,
+                seg,
+                addrsz,
+                80,
+            )
+        }
 
         // G
         (&OperandSpec(AddressingMethod::G, OperandType::dq), _) => read_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 64),
@@ -1115,7 +1166,7 @@ fn read_operand(
         }
 
         // H
-        (&OperandSpec(AddressingMethod::H, OperandType::x), opsz) if vvvv.is_some() => read_simd_register(vvvv.unwrap(), rex.is_some(), opsz),
+        (&OperandSpec(AddressingMethod::H, OperandType::x), _) if vvvv.is_some() => read_simd_register(vvvv.unwrap(), rex.is_some(), simdsz),
         (&OperandSpec(AddressingMethod::H, OperandType::qq), _) if vvvv.is_some() => read_simd_register(vvvv.unwrap(), rex.is_some(), 256),
         (&OperandSpec(AddressingMethod::H, OperandType::dq), _) if vvvv.is_some() => read_simd_register(vvvv.unwrap(), rex.is_some(), 128),
         (&OperandSpec(AddressingMethod::H, OperandType::ps), _) if vvvv.is_some() => read_simd_register(vvvv.unwrap(), rex.is_some(), simdsz),
@@ -1134,7 +1185,8 @@ fn read_operand(
         (&OperandSpec(AddressingMethod::I, OperandType::v), 64) => Ok(Operand::Immediate(tail.read_u64().ok().unwrap() as u64, 64)),
         (&OperandSpec(AddressingMethod::J, OperandType::b), _) => {
             Ok(
-                Operand::Immediate(
+                near_target(
+                    mode,
                     addr.wrapping_add(((tail.read_u8().ok().unwrap() as i8) as i64) as u64).wrapping_add(1),
                     addrsz,
                 )
@@ -1142,7 +1194,8 @@ fn read_operand(
         }
         (&OperandSpec(AddressingMethod::J, OperandType::z), 16) => {
             Ok(
-                Operand::Immediate(
+                near_target(
+                    mode,
                     addr.wrapping_add(((tail.read_u16().ok().unwrap() as i16) as i64) as u64).wrapping_add(2),
                     addrsz,
                 )
@@ -1150,7 +1203,8 @@ fn read_operand(
         }
         (&OperandSpec(AddressingMethod::J, OperandType::z), _) => {
             Ok(
-                Operand::Immediate(
+                near_target(
+                    mode,
                     addr.wrapping_add(((tail.read_u32().ok().unwrap() as i32) as i64) as u64).wrapping_add(4),
                     addrsz,
                 )
@@ -1171,8 +1225,7 @@ fn read_operand(
         (&OperandSpec(AddressingMethod::M, OperandType::a), 32) => read_effective_address(mode, seg, tail, rex, 64, addrsz, addr),
         (&OperandSpec(AddressingMethod::M, OperandType::a), 16) => read_effective_address(mode, seg, tail, rex, 32, addrsz, addr),
         (&OperandSpec(AddressingMethod::M, OperandType::y), opsz) => read_effective_address(mode, seg, tail, rex, cmp::min(32, opsz), addrsz, addr),
-        (&OperandSpec(AddressingMethod::M, OperandType::x), 32) => read_effective_address(mode, seg, tail, rex, 128, addrsz, addr),
-        (&OperandSpec(AddressingMethod::M, OperandType::x), 64) => read_effective_address(mode, seg, tail, rex, 256, addrsz, addr),
+        (&OperandSpec(AddressingMethod::M, OperandType::x), _) => read_effective_address(mode, seg, tail, rex, simdsz, addrsz, addr),
         (&OperandSpec(AddressingMethod::N, OperandType::q), _) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), 64),
         (&OperandSpec(AddressingMethod::O, OperandType::b), _) if addrsz == 16 => {
             read_memory(
@@ -1266,6 +1319,7 @@ fn read_operand(
                 32,
             )
         }
+        (&OperandSpec(AddressingMethod::R, OperandType::y), opsz) => read_register(tail.modrm(rex)?.2, rex.is_some(), cmp::max(32, opsz)),
         (&OperandSpec(AddressingMethod::R, OperandType::q), _) => {
             read_memory(
                 Operand::Immediate(tail.read_u16().ok().unwrap() as u64, addrsz),
@@ -1278,26 +1332,18 @@ fn read_operand(
         (&OperandSpec(AddressingMethod::U, OperandType::pi), _) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), 64),
         (&OperandSpec(AddressingMethod::U, OperandType::pd), _) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), simdsz),
         (&OperandSpec(AddressingMethod::U, OperandType::q), _) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), 64),
-        (&OperandSpec(AddressingMethod::U, OperandType::x), 32) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), 128),
-        (&OperandSpec(AddressingMethod::U, OperandType::x), 64) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), 256),
+        (&OperandSpec(AddressingMethod::U, OperandType::x), _) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), simdsz),
         (&OperandSpec(AddressingMethod::U, OperandType::dq), _) => read_simd_register(tail.modrm(rex).ok().unwrap().2, rex.is_some(), 128),
         (&OperandSpec(AddressingMethod::V, OperandType::pi), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 64),
         (&OperandSpec(AddressingMethod::V, OperandType::ps), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), simdsz),
         (&OperandSpec(AddressingMethod::V, OperandType::pd), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), simdsz),
         (&OperandSpec(AddressingMethod::V, OperandType::ss), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 128),
-        (&OperandSpec(AddressingMethod::V, OperandType::x), 32) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 128),
-        (&OperandSpec(AddressingMethod::V, OperandType::x), 64) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 256),
+        (&OperandSpec(AddressingMethod::V, OperandType::x), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), simdsz),
         (&OperandSpec(AddressingMethod::V, OperandType::dq), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 128),
         (&OperandSpec(AddressingMethod::V, OperandType::qq), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 256),
-        (&OperandSpec(AddressingMethod::V, OperandType::q), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 64),
+        (&OperandSpec(AddressingMethod::V, OperandType::q), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 128),
         (&OperandSpec(AddressingMethod::V, OperandType::sd), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 128),
-        (&OperandSpec(AddressingMethod::V, OperandType::y), opsz) => {
-            read_simd_register(
-                tail.modrm(rex).ok().unwrap().1,
-                rex.is_some(),
-                cmp::min(32, opsz),
-            )
-        }
+        (&OperandSpec(AddressingMethod::V, OperandType::y), _) => read_simd_register(tail.modrm(rex).ok().unwrap().1, rex.is_some(), 128),
         (&OperandSpec(AddressingMethod::W, OperandType::pd), _) => {
             indirect(
                 read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, simdsz)?,
@@ -1316,7 +1362,7 @@ fn read_operand(
         }
         (&OperandSpec(AddressingMethod::W, OperandType::q), _) => {
             indirect(
-                read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, 64)?,
+                read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, 128)?,
                 seg,
                 addrsz,
                 64,
@@ -1346,28 +1392,21 @@ fn read_operand(
                 256,
             )
         }
-        (&OperandSpec(AddressingMethod::W, OperandType::x), 32) => {
-            indirect(
-                read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, 128)?,
-                seg,
-                addrsz,
-                128,
-            )
-        }
-        (&OperandSpec(AddressingMethod::W, OperandType::x), 64) => {
+        (&OperandSpec(AddressingMethod::W, OperandType::x), _) => {
             indirect(
-                read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, 256)?,
+                read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, simdsz)?,
                 seg,
                 addrsz,
-                256,
+                simdsz,
             )
         }
+        // scalar operands only read the low 64 or 32 bits from memory
         (&OperandSpec(AddressingMethod::W, OperandType::sd), _) => {
             indirect(
                 read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, 128)?,
                 seg,
                 addrsz,
-                128,
+                64,
             )
         }
         (&OperandSpec(AddressingMethod::W, OperandType::ss), _) => {
@@ -1375,7 +1414,7 @@ fn read_operand(
                 read_effective_simd_address(mode, seg, tail, rex, opsz, addrsz, addr, 128)?,
                 seg,
                 addrsz,
-                128,
+                32,
             )
         }
         _ => {
@@ -1415,98 +1454,11 @@ fn read_effective_simd_address(
 ) -> Result<Operand> {
     let (mod_, _reg, rm) = tail.modrm(rex)?;
 
-    match (mod_, rm & 0b111) {
-        // mod = 00
-        (0b00, 0b000) | (0b00, 0b001) | (0b00, 0b010) | (0b00, 0b011) | (0b00, 0b110) | (0b00, 0b111) => read_simd_register(rm, rex.is_some(), addrsz),
-        (0b00, 0b100) => tail.sib(mod_, seg, rex, addrsz),
-        (0b00, 0b101) if mode == Mode::Long => {
-            Ok(
-                Operand::Address(
-                    seg,
-                    Register::None,
-                    Register::None,
-                    0,
-                    (ip + sign_ext_u32(tail.read_u32()?, addrsz), addrsz),
-                )
-            )
-        }
-        (0b00, 0b101) if mode != Mode::Long => {
-            Ok(
-                Operand::Address(
-                    seg,
-                    Register::None,
-                    Register::None,
-                    0,
-                    (sign_ext_u32(tail.read_u32()?, addrsz), addrsz),
-                )
-            )
-        }
-
-        // mod = 01
-        (0b01, 0b000) | (0b01, 0b001) | (0b01, 0b010) | (0b01, 0b011) | (0b01, 0b101) | (0b01, 0b110) | (0b01, 0b111) => {
-            if let Ok(Operand::Register(reg)) = read_register(rm, rex.is_some(), addrsz) {
-                Ok(
-                    Operand::Address(
-                        seg,
-                        reg,
-                        Register::None,
-                        0,
-                        (sign_ext_u8(tail.read_u8()?, addrsz), addrsz),
-                    )
-                )
-            } else {
-                error!("Failed to decode SIB byte");
-                Err("Failed to decode SIB byte".into())
-            }
-        }
-        (0b01, 0b100) => {
-            if let Operand::Address(e, b, i, s, _) = tail.sib(mod_, seg, rex, addrsz)? {
-                let d = sign_ext_u8(tail.read_u8()?, opsz);
-                Ok(Operand::Address(e, b, i, s, (d as u64, addrsz)))
-            } else {
-                error!(
-                    "Internal error: read_sib did not return indirect operand",
-                );
-                Err("Internal error: read_sib did not return indirect operand".into())
-            }
-        }
-
-        // mod = 10
-        (0b10, 0b000) | (0b10, 0b001) | (0b10, 0b010) | (0b10, 0b011) | (0b10, 0b101) | (0b10, 0b110) | (0b10, 0b111) => {
-            if let Ok(Operand::Register(reg)) = read_register(rm, rex.is_some(), addrsz) {
-                Ok(
-                    Operand::Address(
-                        seg,
-                        reg,
-                        Register::None,
-                        0,
-                        (sign_ext_u32(tail.read_u32()?, addrsz), addrsz),
-                    )
-                )
-            } else {
-                error!("Failed to decode SIB byte");
-                Err("Failed to decode SIB byte".into())
-            }
-        }
-        (0b10, 0b100) => {
-            if let Operand::Address(e, b, i, s, _) = tail.sib(mod_, seg, rex, addrsz)? {
-                let d = sign_ext_u32(tail.read_u32()?, addrsz);
-                Ok(Operand::Address(e, b, i, s, (d as u64, 32)))
-            } else {
-                error!(
-                    "Internal error: read_sib did not return indirect operand",
-                );
-                Err("Internal error: read_sib did not return indirect operand".into())
-            }
-        }
-
-        // mod = 11
-        (0b11, _) => read_simd_register(rm, rex.is_some(), simdsz),
-
-        _ => {
-            error!("Invalid mod value: {:b}", mod_);
-            Err("Invalid mod value".into())
-        }
+    // memory operands are addressed with general purpose registers
+    if mod_ == 0b11 {
+        read_simd_register(rm, rex.is_some(), simdsz)
+    } else {
+        read_effective_address(mode, seg, tail, rex, opsz, addrsz, ip)
     }
 }
 
@@ -1519,11 +1471,20 @@ fn read_effective_address(
     addrsz: usize,
     ip: u64,
 ) -> Result<Operand> {
+    if addrsz == 16 {
+        return read_effective_address16(seg, tail, rex, opsz);
+    }
+
     let (mod_, _reg, rm) = tail.modrm(rex)?;
 
     match (mod_, rm & 0b111) {
         // mod = 00
-        (0b00, 0b000) | (0b00, 0b001) | (0b00, 0b010) | (0b00, 0b011) | (0b00, 0b110) | (0b00, 0b111) => read_register(rm, rex.is_some(), addrsz),
+        (0b00, 0b000) | (0b00, 0b001) | (0b00, 0b010) | (0b00, 0b011) | (0b00, 0b110) | (0b00, 0b111) => {
+            match read_register(rm, rex.is_some(), addrsz)? {
+                Operand::Register(base) => Ok(Operand::Address(seg, base, Register::None, 0, (0, 0))),
+                op => Ok(op),
+            }
+        }
         (0b00, 0b100) => tail.sib(mod_, seg, rex, addrsz),
         (0b00, 0b101) if mode == Mode::Long => {
             let imm = sign_ext_u32(tail.read_u32()?, addrsz);
@@ -1623,6 +1584,36 @@ fn read_effective_address(
     }
 }
 
+/// Decodes the 16-bit ModR/M addressing forms (`[BX+SI]`, `[BP+disp8]`, ...).
+fn read_effective_address16(seg: SegmentOverride, tail: &mut Tail, rex: Option<(bool, bool, bool, bool)>, opsz: usize) -> Result<Operand> {
+    let (mod_, _reg, rm) = tail.modrm(rex)?;
+
+    if mod_ == 0b11 {
+        return read_register(rm, rex.is_some(), opsz);
+    }
+
+    let (base, index) = match rm & 0b111 {
+        0b000 => (Register::BX, Register::SI),
+        0b001 => (Register::BX, Register::DI),
+        0b010 => (Register::BP, Register::SI),
+        0b011 => (Register::BP, Register::DI),
+        0b100 => (Register::SI, Register::None),
+        0b101 => (Register::DI, Register::None),
+        0b110 if mod_ == 0b00 => (Register::None, Register::None),
+        0b110 => (Register::BP, Register::None),
+        _ => (Register::BX, Register::None),
+    };
+    let disp = match mod_ {
+        0b00 if base == Register::None => tail.read_u16()? as u64,
+        0b00 => 0,
+        0b01 => sign_ext_u8(tail.read_u8()?, 16),
+        _ => ((tail.read_u16()? as i16) as i64) as u64,
+    };
+    let scale = if index == Register::None { 0 } else { 1 };
+
+    Ok(Operand::Address(seg, base, index, scale, (disp, 16)))
+}
+
 fn indirect(op: Operand, seg: SegmentOverride, addrsz: usize, width: usize) -> Result<Operand> {
     if let Operand::Address(_, _, _, _, _) = op {
         read_memory(op, seg, addrsz, width)
@@ -2326,16 +2317,19 @@ fn select_opcode_ext(grp: isize, opc: usize, modrm: usize, pfx: SimdPrefix, mode
     )
 }
 
-pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+/// Decodes the instruction at the start of `buf`. Returns its length, the mnemonic, the jumps and
+/// the configuration to continue with.
+pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>, Mode)> {
     use tables::*;
 
     let mut i = 0;
     let mut prefix = Prefix::default();
     let mut vexxop_present = false;
     let mut rex_present = false;
+    let mut operand_override = false;
 
     match mode {
-        Mode::Real => {
+        Mode::Real(_) => {
             prefix.address_size = 16;
             prefix.operand_size = 16;
             prefix.simd_size = 128;
@@ -2403,8 +2397,9 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             Some(&0x66) => {
                 match mode {
                     Mode::Long | Mode::Protected => prefix.operand_size = 16,
-                    Mode::Real => prefix.operand_size = 32,
+                    Mode::Real(_) => prefix.operand_size = 32,
                 }
+                operand_override = true;
                 if i == 0 {
                     prefix.simd_prefix = SimdPrefix::Prefix66;
                 }
@@ -2412,7 +2407,7 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             // Group 4: Address size override
             Some(&0x67) => {
                 let new_addr_sz = match mode {
-                    Mode::Real => 32,
+                    Mode::Real(_) => 32,
                     Mode::Protected => 16,
                     Mode::Long => 32,
                 };
@@ -2424,8 +2419,10 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                 }
             }
             // 2 byte VEX
-            Some(&0xc5) if i == 0 && mode == Mode::Long => {
-                let vex = buf[i + 1];
+            Some(&0xc5) if is_vex(mode, buf.get(i + 1)) => {
+                check_vex_prefixes(&prefix, operand_override)?;
+
+                let vex = *buf.get(i + 1).ok_or("Premature buffer end while reading VEX prefix")?;
 
                 prefix.simd_prefix = match vex & 0b00000011 {
                     0 => SimdPrefix::None,
@@ -2440,7 +2437,7 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                     prefix.simd_size = 256
                 }
                 prefix.vvvv = Some((0xFF ^ (vex >> 3)) & 0b1111);
-                prefix.rex_r = vex & 0b1000000 == 0;
+                prefix.rex_r = vex & 0b10000000 == 0 && mode == Mode::Long;
 
                 vexxop_present = true;
                 rex_present = true;
@@ -2450,9 +2447,11 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             }
 
             // 3 byte VEX
-            Some(&0xc4) if i == 0 && mode == Mode::Long => {
-                let vex1 = buf[i + 1];
-                let vex2 = buf[i + 2];
+            Some(&0xc4) if is_vex(mode, buf.get(i + 1)) => {
+                check_vex_prefixes(&prefix, operand_override)?;
+
+                let vex1 = *buf.get(i + 1).ok_or("Premature buffer end while reading VEX prefix")?;
+                let vex2 = *buf.get(i + 2).ok_or("Premature buffer end while reading VEX prefix")?;
 
                 prefix.simd_prefix = match vex2 & 0b00000011 {
                     0 => SimdPrefix::None,
@@ -2473,10 +2472,12 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                 if vex2 & 0b100 != 0 {
                     prefix.simd_size = 256
                 }
-                prefix.rex_r = vex1 & 0b1000000 == 0;
-                prefix.rex_x = vex1 & 0b0100000 == 0;
-                prefix.rex_b = vex1 & 0b0010000 == 0;
-                prefix.rex_w = vex2 & 0b1000000 == 0;
+                // R, X and B are stored inverted, W is not. All but W are ignored outside of
+                // long mode.
+                prefix.rex_r = vex1 & 0b10000000 == 0 && mode == Mode::Long;
+                prefix.rex_x = vex1 & 0b01000000 == 0 && mode == Mode::Long;
+                prefix.rex_b = vex1 & 0b00100000 == 0 && mode == Mode::Long;
+                prefix.rex_w = vex2 & 0b10000000 != 0;
 
                 vexxop_present = true;
                 rex_present = true;
@@ -2486,10 +2487,12 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             }
 
             // EVEX
-            Some(&0x62) if i == 0 && mode == Mode::Long => {
-                let p0 = buf[i + 1];
-                let p1 = buf[i + 2];
-                let p2 = buf[i + 3];
+            Some(&0x62) if is_vex(mode, buf.get(i + 1)) => {
+                check_vex_prefixes(&prefix, operand_override)?;
+
+                let p0 = *buf.get(i + 1).ok_or("Premature buffer end while reading EVEX prefix")?;
+                let p1 = *buf.get(i + 2).ok_or("Premature buffer end while reading EVEX prefix")?;
+                let p2 = *buf.get(i + 3).ok_or("Premature buffer end while reading EVEX prefix")?;
 
                 prefix.simd_prefix = match p1 & 0b00000011 {
                     0 => SimdPrefix::None,
@@ -2507,13 +2510,15 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                 };
 
                 prefix.vvvv = Some((0xFF ^ (p1 >> 3)) & 0b1111);
-                if p2 & 0b100000 != 0 {
-                    prefix.simd_size = 256
+                match (p2 >> 5) & 0b11 {
+                    0b00 => {}
+                    0b01 => prefix.simd_size = 256,
+                    _ => return Err("Unknown instruction: 512 bit EVEX operands are not supported".into()),
                 }
-                prefix.rex_r = p0 & 0b1000000 == 0;
-                prefix.rex_x = p0 & 0b0100000 == 0;
-                prefix.rex_b = p0 & 0b0010000 == 0;
-                prefix.rex_w = p1 & 0b1000000 == 0;
+                prefix.rex_r = p0 & 0b10000000 == 0 && mode == Mode::Long;
+                prefix.rex_x = p0 & 0b01000000 == 0 && mode == Mode::Long;
+                prefix.rex_b = p0 & 0b00100000 == 0 && mode == Mode::Long;
+                prefix.rex_w = p1 & 0b10000000 != 0;
 
                 vexxop_present = true;
                 rex_present = true;
@@ -2622,7 +2627,9 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                         0xb0...0xb7 => true,
                         0x88...0x8f => true,
                         0x98...0x9f => true,
-                        0xa8...0xaf => true,
+                        0xa8...0xad | 0xaf => true,
+                        // F3 selects rdfsbase & co. in group 15
+                        0xae if prefix.simd_prefix != SimdPrefix::PrefixF3 => true,
                         0xb8...0xbf if prefix.simd_prefix != SimdPrefix::PrefixF3 => true,
                         0xc0 | 0xc1 => true,
                         0xc8...0xcf => true,
@@ -2668,9 +2675,10 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             (OpcodeEscape::Escape0F, SimdPrefix::None) => TWOBYTE_TABLE[b].clone(),
             (OpcodeEscape::Escape0F, SimdPrefix::Prefix66) => {
                 prefix.operand_size = match mode {
-                    Mode::Real => 16,
+                    Mode::Real(_) => 16,
                     Mode::Protected => 32,
-                    Mode::Long => 64,
+                    Mode::Long if prefix.rex_w => 64,
+                    Mode::Long => 32,
                 };
                 TWOBYTE_66_TABLE[b].clone()
             }
@@ -2679,9 +2687,10 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             (OpcodeEscape::Escape0F3A, SimdPrefix::None) => THREEBYTE_3A_TABLE[b].clone(),
             (OpcodeEscape::Escape0F3A, SimdPrefix::Prefix66) => {
                 prefix.operand_size = match mode {
-                    Mode::Real => 16,
+                    Mode::Real(_) => 16,
                     Mode::Protected => 32,
-                    Mode::Long => 64,
+                    Mode::Long if prefix.rex_w => 64,
+                    Mode::Long => 32,
                 };
                 THREEBYTE_3A66_TABLE[b].clone()
             }
@@ -2690,9 +2699,10 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
             (OpcodeEscape::Escape0F38, SimdPrefix::None) => THREEBYTE_38_TABLE[b].clone(),
             (OpcodeEscape::Escape0F38, SimdPrefix::Prefix66) => {
                 prefix.operand_size = match mode {
-                    Mode::Real => 16,
+                    Mode::Real(_) => 16,
                     Mode::Protected => 32,
-                    Mode::Long => 64,
+                    Mode::Long if prefix.rex_w => 64,
+                    Mode::Long => 32,
                 };
                 THREEBYTE_3866_TABLE[b].clone()
             }
@@ -2801,7 +2811,7 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                         prefix.simd_size,
                         ip,
                     )
-                        .and_then(|x| to_rreil(x));
+                        .and_then(|x| to_rreil(x, mode));
 
                     match maybe_op {
                         Ok((rv, mut rst, wst)) => {
@@ -2834,9 +2844,12 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                         return Err(e);
                     }
                 };
+                // unary instructions only write their operand back if they modify it
+                let writes_first = ops.len() == 1 && op_stmts.iter().any(|x| Rvalue::from(x.assignee.clone()) == ops[0]);
+
                 stmts.append(&mut op_stmts);
 
-                if ops.len() >= 2 {
+                if ops.len() >= 2 || writes_first {
                     stmts.append(&mut wstmts[0]);
                 }
 
@@ -2848,55 +2861,38 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                     _ => "{u}",
                 };
 
+                // Legacy SSE encodings share the tables with their VEX counterparts. Without a VEX
+                // prefix the second source is the destination and the "v" is dropped.
+                let legacy_h = if prefix.vvvv.is_none() {
+                    opc.operands().iter().position(
+                        |x| match x.0 {
+                            AddressingMethod::H => true,
+                            _ => false,
+                        }
+                    )
+                } else {
+                    None
+                };
+                let s = match legacy_h {
+                    Some(_) if s.starts_with('v') => &s[1..],
+                    _ => s,
+                };
+                let ops = ops.iter()
+                    .enumerate()
+                    .filter(|&(idx, _)| Some(idx) != legacy_h)
+                    .map(|(_, x)| x.clone())
+                    .collect::<Vec<_>>();
+
                 let len = tail.fd.position() + i as u64 + 1;
-                let mne = match ops.len() {
-                    0 => {
-                        Mnemonic::new(
-                            (addr..addr + len),
-                            format!("{}", s),
-                            "".to_string(),
-                            ops.iter(),
-                            stmts.iter(),
-                        )
-                    }
-                    1 => {
-                        Mnemonic::new(
-                            (addr..addr + len),
-                            format!("{}", s),
-                            fmt.to_string(),
-                            ops.iter(),
-                            stmts.iter(),
-                        )
-                    }
-                    2 => {
-                        Mnemonic::new(
-                            (addr..addr + len),
-                            format!("{}", s),
-                            "{u}, {u}".to_string(),
-                            ops.iter(),
-                            stmts.iter(),
-                        )
-                    }
-                    3 => {
-                        Mnemonic::new(
-                            (addr..addr + len),
-                            format!("{}", s),
-                            "{u}, {u}, {u}".to_string(),
-                            ops.iter(),
-                            stmts.iter(),
-                        )
-                    }
-                    4 => {
-                        Mnemonic::new(
-                            (addr..addr + len),
-                            format!("{}", s),
-                            "{u}, {u}, {u}, {u}".to_string(),
-                            ops.iter(),
-                            stmts.iter(),
-                        )
-                    }
+                let fmt = match ops.len() {
+                    0 => "",
+                    1 => fmt,
+                    2 => "{u}, {u}",
+                    3 => "{u}, {u}, {u}",
+                    4 => "{u}, {u}, {u}, {u}",
                     _ => unreachable!(),
-                }?;
+                };
+                let mne = Mnemonic::from_parts(addr..addr + len, format!("{}", s), fmt, ops, stmts)?;
                 let next = match jmp_spec {
                     JumpSpec::DeadEnd => vec![],
                     JumpSpec::FallThru => vec![(Rvalue::Constant { value: addr + len, size: 64 }, Guard::always())],
@@ -2910,16 +2906,25 @@ pub fn read(mode: Mode, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rv
                 };
 
 
+                // far jumps load CS
+                let mode = match (mode, opc.operands().get(0)) {
+                    (Mode::Real(_), Some(&OperandSpec(AddressingMethod::A, OperandType::p))) if s == "jmp" => {
+                        let pos = i + 1 + prefix.operand_size / 8;
+                        Mode::Real(LittleEndian::read_u16(&buf[pos..pos + 2]))
+                    }
+                    _ => mode,
+                };
+
                 debug!("'{:?}' with {} bytes", mne, len as usize);
                 trace!("");
-                Ok((len, mne, next))
+                Ok((len, mne, next, mode))
             }
             e => Err(format!("Internal error: {:?}", e).into()),
         }
     }
 }
 
-fn to_rreil(op: Operand) -> Result<(Rvalue, Vec<Statement>, Vec<Statement>)> {
+fn to_rreil(op: Operand, mode: Mode) -> Result<(Rvalue, Vec<Statement>, Vec<Statement>)> {
     match op {
         Operand::Register(ref name) => {
             Ok(
@@ -2941,7 +2946,8 @@ fn to_rreil(op: Operand) -> Result<(Rvalue, Vec<Statement>, Vec<Statement>)> {
                     index.clone(),
                     scale.clone(),
                     disp.clone(),
-                )
+                ),
+                mode,
             )?;
             let ret = Lvalue::Variable {
                 name: format!("{}", op).into(),
@@ -2966,6 +2972,10 @@ fn to_rreil(op: Operand) -> Result<(Rvalue, Vec<Statement>, Vec<Statement>)> {
                     rstmts.append(&mut rreil!{ load/RAM/le/64 (ret), (tgt); }?);
                     wstmts.append(&mut rreil!{ store/RAM/le/64 (ret), (tgt); }?);
                 }
+                80 => {
+                    rstmts.append(&mut rreil!{ load/RAM/le/80 (ret), (tgt); }?);
+                    wstmts.append(&mut rreil!{ store/RAM/le/80 (ret), (tgt); }?);
+                }
                 128 => {
                     rstmts.append(&mut rreil!{ load/RAM/le/128 (ret), (tgt); }?);
                     wstmts.append(&mut rreil!{ store/RAM/le/128 (ret), (tgt); }?);
@@ -2979,7 +2989,7 @@ fn to_rreil(op: Operand) -> Result<(Rvalue, Vec<Statement>, Vec<Statement>)> {
 
             Ok((ret.into(), rstmts, wstmts))
         }
-        Operand::Address(_, ref base, ref index, ref scale, ref disp) => {
+        Operand::Address(ref seg, ref base, ref index, ref scale, ref disp) => {
             let mut stmts = vec![];
             let mut ret = Rvalue::Undefined;
             let out = format!("{}", op);
@@ -3048,8 +3058,85 @@ fn to_rreil(op: Operand) -> Result<(Rvalue, Vec<Statement>, Vec<Statement>)> {
                 }
             }
 
+            if is_real(mode) {
+                let (linear, mut lin_stmts) = linear_address(seg.clone(), base.clone(), ret, &out)?;
+                stmts.append(&mut lin_stmts);
+                ret = linear;
+            } else if *seg == SegmentOverride::Fs || *seg == SegmentOverride::Gs {
+                let (linear, mut lin_stmts) = segment_base(seg.clone(), ret, &out, mode)?;
+                stmts.append(&mut lin_stmts);
+                ret = linear;
+            }
+
             Ok((ret, stmts, vec![]))
         }
         Operand::Optional => Ok((Rvalue::Undefined, vec![], vec![])),
     }
 }
+
+/// Computes the real mode address `(segment << 4) + offset` of the memory operand `name`.
+fn linear_address(seg: SegmentOverride, base: Register, offset: Rvalue, name: &str) -> Result<(Rvalue, Vec<Statement>)> {
+    let segreg = match seg {
+        SegmentOverride::Cs => Register::CS,
+        SegmentOverride::Ds => Register::DS,
+        SegmentOverride::Es => Register::ES,
+        SegmentOverride::Fs => Register::FS,
+        SegmentOverride::Gs => Register::GS,
+        SegmentOverride::Ss => Register::SS,
+        SegmentOverride::None if base == Register::BP || base == Register::SP => Register::SS,
+        SegmentOverride::None => Register::DS,
+    };
+    let segment = Rvalue::Variable { name: format!("{}", segreg).into(), size: 16, offset: 0, subscript: None };
+    let linear = Lvalue::Variable { name: format!("{}:{}", segreg, name).into(), size: 32, subscript: None };
+    let offset = if offset == Rvalue::Undefined { Rvalue::new_u16(0) } else { offset };
+    let mut stmts = rreil!{
+        zext/32 seg:32, (segment);
+        shl seg:32, seg:32, [4]:32;
+    }?;
+
+    if offset.size() == Some(32) {
+        stmts.append(&mut rreil!{ add (linear), seg:32, (offset); }?);
+    } else {
+        stmts.append(
+            &mut rreil!{
+                zext/32 off:32, (offset);
+                add (linear), seg:32, off:32;
+            }?
+        );
+    }
+
+    Ok((linear.into(), stmts))
+}
+
+/// Adds the base address of FS or GS to the offset of the memory operand `name`. Both are modeled
+/// as the variables `FS_BASE` and `GS_BASE` with the width of a pointer in `mode`. All other
+/// segments have a base address of zero outside of real mode.
+fn segment_base(seg: SegmentOverride, offset: Rvalue, name: &str, mode: Mode) -> Result<(Rvalue, Vec<Statement>)> {
+    let w = mode.bits();
+    let segreg = match seg {
+        SegmentOverride::Fs => "FS",
+        SegmentOverride::Gs => "GS",
+        _ => return Ok((offset, vec![])),
+    };
+    let base = Rvalue::Variable { name: format!("{}_BASE", segreg).into(), size: w, offset: 0, subscript: None };
+    let linear = Lvalue::Variable { name: format!("{}:{}", segreg, name).into(), size: w, subscript: None };
+    let stmts = match offset {
+        Rvalue::Undefined => rreil!{ mov (linear), (base); }?,
+        Rvalue::Constant { value, .. } => {
+            // displacements are sign extended to 64 bits by the decoder
+            let mask = if w < 64 { (1u64 << w) - 1 } else { !0 };
+            let off = Rvalue::Constant { value: value & mask, size: w };
+
+            rreil!{ add (linear), (base), (off); }?
+        }
+        Rvalue::Variable { size, .. } if size == w => rreil!{ add (linear), (base), (offset); }?,
+        Rvalue::Variable { .. } => {
+            rreil!{
+                zext/w off:w, (offset);
+                add (linear), (base), off:w;
+            }?
+        }
+    };
+
+    Ok((linear.into(), stmts))
+}
@@ -32,6 +32,12 @@
 //! All functions in `semantic.rs` follow the same structure. They get the decoded opcode arguments
 //! as input and return a vector of RREIL statements and a `JumpSpec` instance that tells the
 //! disassembler where to continue.
+//!
+//! In real mode, addresses in the `Region` are linear addresses (`(segment << 4) + offset`). The
+//! `Mode::Real` configuration carries the value of CS. Near jumps wrap around inside the 64KB code
+//! segment, far jumps switch to the new code segment. Memory operands are lifted to linear
+//! addresses computed from the segment register (DS or SS unless overridden) and the 16-bit
+//! offset.
 
 #![allow(missing_docs)]
 
@@ -51,3 +57,6 @@ pub use disassembler::{AddressingMethod, JumpSpec, MnemonicSpec, Opcode, Operand
 
 mod architecture;
 pub use architecture::{Amd64, Mode};
+
+pub mod tls;
+pub use tls::{Segment, TlsReference, TlsSlot, tls_references};
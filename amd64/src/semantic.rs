@@ -31,8 +31,17 @@
 //! does not extend values automatically.
 //!
 //! RREIL has no traps, software interrupts of CPU exceptions, this part of the Intel CPUs can be
-//! ignored for now. Also, no paging or segmentation is implemented. Memory addresses are used
-//! as-is.
+//! ignored for now. Also, no paging is implemented and outside of real mode all segments are flat
+//! except FS and GS. Their base addresses are the variables `FS_BASE` and `GS_BASE`, which are
+//! added to every FS/GS-prefixed memory operand. `KERNEL_GS_BASE` holds the value `swapgs`
+//! exchanges with `GS_BASE`.
+//!
+//! SSE and AVX instructions are lifted lane by lane. Each element is computed separately and
+//! inserted into the result vector using `sel`. Legacy encodings pass an undefined first source
+//! operand which is replaced by the destination. Writing a XMM register also writes the lower half
+//! of the YMM register and vice versa. The upper half of a YMM register is never cleared, even by
+//! VEX encoded instructions. RREIL knows no floating point, so the lanes written by floating point
+//! arithmetic are set to undefined while the rest of the vector is preserved.
 //!
 //! When implementing opcodes the instruction set reference in volume 2 of the Intel Software
 //! Developer's Manual should be the primary source of inspiration ;-). Aside from that other
@@ -54,7 +63,8 @@
 use disassembler::{Condition, JumpSpec};
 
 use panopticon_core::{Guard, Lvalue, Result, Rvalue, Statement};
-use std::cmp::max;
+use std::borrow::Cow;
+use std::cmp::{max, min};
 
 /// Sets the adjust flag AF after an addition. Assumes res := a + ?.
 fn set_adj_flag(res: &Lvalue, a: &Rvalue) -> Result<Vec<Statement>> {
@@ -1847,8 +1857,8 @@ pub fn sysret() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
 
-pub fn movapd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movapd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn wrmsr() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -1936,7 +1946,13 @@ pub fn enclu() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
 pub fn swapgs() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let stmts = rreil!{
+        mov t:64, GS_BASE:64;
+        mov GS_BASE:64, KERNEL_GS_BASE:64;
+        mov KERNEL_GS_BASE:64, t:64;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn rdtscp() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -1971,17 +1987,38 @@ pub fn xabort(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn xbegin(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn rdfsbase(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+fn read_segment_base(a: Rvalue, base: &'static str) -> Result<(Vec<Statement>, JumpSpec)> {
+    let sz = match a.size() {
+        Some(sz) => sz,
+        None => return Err("rdfsbase/rdgsbase with undefined operand".into()),
+    };
+    let base = Rvalue::Variable { name: Cow::Borrowed(base), size: sz, offset: 0, subscript: None };
+
+    Ok((write_reg(&a, &base, sz)?, JumpSpec::FallThru))
 }
-pub fn rdgsbase(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+
+fn write_segment_base(a: Rvalue, base: &'static str) -> Result<(Vec<Statement>, JumpSpec)> {
+    let base = Lvalue::Variable { name: Cow::Borrowed(base), size: 64, subscript: None };
+    let stmts = if a.size() == Some(64) {
+        rreil!{ mov (base), (a); }?
+    } else {
+        rreil!{ zext/64 (base), (a); }?
+    };
+
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn wrfsbase(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+
+pub fn rdfsbase(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    read_segment_base(a, "FS_BASE")
 }
-pub fn wrgsbase(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn rdgsbase(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    read_segment_base(a, "GS_BASE")
+}
+pub fn wrfsbase(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    write_segment_base(a, "FS_BASE")
+}
+pub fn wrgsbase(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    write_segment_base(a, "GS_BASE")
 }
 pub fn fxsave() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2035,23 +2072,14 @@ pub fn cvtph2ps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn extracti128(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fist(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
-}
-pub fn fldl2g() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
-}
-pub fn fperm() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
-}
-pub fn fperm1() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fist(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn frndintx() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
-}
-pub fn fsubrp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fsubrp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn gatherdd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2318,6 +2346,132 @@ pub fn verw(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
 
+// Vector helpers
+/// Writes `val` to the vector register or memory operand `dst`. The XMM registers alias the lower
+/// halves of the YMM registers, writing one updates the other.
+fn write_vector(dst: &Rvalue, val: &Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = write_reg(dst, val, 0)?;
+
+    if let &Rvalue::Variable { ref name, .. } = dst {
+        if name.starts_with("XMM") {
+            let ymm = Lvalue::Variable { name: format!("Y{}", &name[1..]).into(), size: 256, subscript: None };
+            stmts.append(&mut rreil!{ sel/0 (ymm), (val); }?);
+        } else if name.starts_with("YMM") {
+            let xmm = Lvalue::Variable { name: format!("X{}", &name[1..]).into(), size: 128, subscript: None };
+            stmts.append(&mut rreil!{ mov (xmm), (val.extract(128, 0)?); }?);
+        }
+    }
+
+    Ok(stmts)
+}
+
+/// Moves the lower `bits` of `b` into `a`, zero extending them to the size of `a`.
+fn move_low(a: Rvalue, b: Rvalue, bits: usize) -> Result<(Vec<Statement>, JumpSpec)> {
+    let sz = a.size().ok_or("vector operand without size")?;
+    let bits = min(bits, min(sz, b.size().unwrap_or(bits)));
+    let low = b.extract(bits, 0)?;
+
+    if bits == sz {
+        Ok((write_vector(&a, &low)?, JumpSpec::FallThru))
+    } else {
+        let val = Lvalue::Variable { name: "vec".into(), size: sz, subscript: None };
+        let mut stmts = rreil!{ zext/sz (val), (low); }?;
+
+        stmts.append(&mut write_vector(&a, &val.into())?);
+        Ok((stmts, JumpSpec::FallThru))
+    }
+}
+
+/// Moves as much of `b` as fits into `a`, zero extending it if `b` is smaller.
+fn move_vector(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let bits = b.size().ok_or("vector operand without size")?;
+    move_low(a, b, bits)
+}
+
+/// MOVSS and MOVSD. Stores write only the scalar, loads from memory clear the rest of the
+/// register and register to register moves merge the scalar into the first source.
+fn move_scalar(a: Rvalue, b: Rvalue, c: Rvalue, bits: usize) -> Result<(Vec<Statement>, JumpSpec)> {
+    if a.size() == Some(bits) {
+        Ok((write_vector(&a, &c.extract(bits, 0)?)?, JumpSpec::FallThru))
+    } else if c.size() == Some(bits) {
+        move_low(a, c, bits)
+    } else {
+        scalar(a, b, c, bits, |r, _, y| rreil!{ mov (r), (y); })
+    }
+}
+
+/// Computes the first `count` lanes of `a` from the `lane` bit wide elements of `b` and `c` using
+/// `op`. The remaining lanes are copied from `b`. Legacy SSE encodings have no separate first
+/// source, for them `b` is undefined and `a` is used instead.
+fn lanewise<F>(a: Rvalue, b: Rvalue, c: Rvalue, lane: usize, count: Option<usize>, op: F) -> Result<(Vec<Statement>, JumpSpec)>
+where
+    F: Fn(&Lvalue, &Rvalue, &Rvalue) -> Result<Vec<Statement>>,
+{
+    let sz = a.size().ok_or("vector operand without size")?;
+    let src = if b == Rvalue::Undefined { a.clone() } else { b };
+    let res = Lvalue::Variable { name: "vec".into(), size: sz, subscript: None };
+    let tmp = Lvalue::Variable { name: "lane".into(), size: lane, subscript: None };
+    let mut stmts = rreil!{ mov (res), (src); }?;
+
+    for i in 0..count.unwrap_or(sz / lane) {
+        let off = i * lane;
+
+        stmts.append(&mut op(&tmp, &src.extract(lane, off)?, &c.extract(lane, off)?)?);
+        stmts.append(&mut rreil!{ sel/off (res), (tmp); }?);
+    }
+
+    stmts.append(&mut write_vector(&a, &res.into())?);
+    Ok((stmts, JumpSpec::FallThru))
+}
+
+/// Packed operation on all lanes.
+fn packed<F>(a: Rvalue, b: Rvalue, c: Rvalue, lane: usize, op: F) -> Result<(Vec<Statement>, JumpSpec)>
+where
+    F: Fn(&Lvalue, &Rvalue, &Rvalue) -> Result<Vec<Statement>>,
+{
+    lanewise(a, b, c, lane, None, op)
+}
+
+/// Scalar operation on the lowest lane.
+fn scalar<F>(a: Rvalue, b: Rvalue, c: Rvalue, lane: usize, op: F) -> Result<(Vec<Statement>, JumpSpec)>
+where
+    F: Fn(&Lvalue, &Rvalue, &Rvalue) -> Result<Vec<Statement>>,
+{
+    lanewise(a, b, c, lane, Some(1), op)
+}
+
+/// PCMPEQ*. Equal lanes are set to all ones, others to zero.
+fn compare_equal(a: Rvalue, b: Rvalue, c: Rvalue, lane: usize) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(
+        a,
+        b,
+        c,
+        lane,
+        |r, x, y| {
+            rreil!{
+                cmpeq eq:1, (x), (y);
+                sext/lane (r), eq:1;
+            }
+        },
+    )
+}
+
+fn and_not(r: &Lvalue, x: &Rvalue, y: &Rvalue) -> Result<Vec<Statement>> {
+    let ones = 0xffffffffffffffffu64;
+
+    rreil!{
+        xor (r), (x), [ones]:64;
+        and (r), (r), (y);
+    }
+}
+
+/// RREIL has no floating point operations. The result lanes are undefined.
+fn float_lane(r: &Lvalue, _: &Rvalue, _: &Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        mov (r), ?;
+    }
+}
+
 // MMX
 pub fn emms() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2331,14 +2485,14 @@ pub fn packssdw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn packuswb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn paddb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn paddb(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpaddb(a, Rvalue::Undefined, b)
 }
-pub fn paddw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn paddw(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpaddw(a, Rvalue::Undefined, b)
 }
-pub fn paddd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn paddd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpaddd(a, Rvalue::Undefined, b)
 }
 pub fn paddsb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2352,20 +2506,20 @@ pub fn paddusb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn paddusw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pand(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pand(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpand(a, Rvalue::Undefined, b)
 }
-pub fn pandn(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pandn(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpandn(a, Rvalue::Undefined, b)
 }
-pub fn pcmpeqb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pcmpeqb(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpcmpeqb(a, Rvalue::Undefined, b)
 }
-pub fn pcmpeqw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pcmpeqw(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpcmpeqw(a, Rvalue::Undefined, b)
 }
-pub fn pcmpeqd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pcmpeqd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpcmpeqd(a, Rvalue::Undefined, b)
 }
 pub fn pcmpgtb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2382,11 +2536,11 @@ pub fn pmadwd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn pmulhw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pmullw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pmullw(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpmullw(a, Rvalue::Undefined, b)
 }
-pub fn por(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn por(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpor(a, Rvalue::Undefined, b)
 }
 pub fn psraw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2412,14 +2566,14 @@ pub fn pslld(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn psllq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn psubb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn psubb(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpsubb(a, Rvalue::Undefined, b)
 }
-pub fn psubw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn psubw(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpsubw(a, Rvalue::Undefined, b)
 }
-pub fn psubd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn psubd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpsubd(a, Rvalue::Undefined, b)
 }
 pub fn psubsb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2451,22 +2605,22 @@ pub fn punpcklwd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn punpcklqdq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pxor(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pxor(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpxor(a, Rvalue::Undefined, b)
 }
 
 // SSE 1
-pub fn addps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn addps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vaddps(a, Rvalue::Undefined, b)
 }
-pub fn addss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn addss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vaddss(a, Rvalue::Undefined, b)
 }
-pub fn andnps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andnps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vandnps(a, Rvalue::Undefined, b)
 }
-pub fn andps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vandps(a, Rvalue::Undefined, b)
 }
 pub fn cmpps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2495,11 +2649,11 @@ pub fn cvttps2pi(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn cvttss2si(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn divps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn divps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vdivps(a, Rvalue::Undefined, b)
 }
-pub fn divss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn divss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vdivss(a, Rvalue::Undefined, b)
 }
 pub fn ldmxcsr() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2507,20 +2661,20 @@ pub fn ldmxcsr() -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn maskmovq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn maxps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn maxps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmaxps(a, Rvalue::Undefined, b)
 }
-pub fn maxss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn maxss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmaxss(a, Rvalue::Undefined, b)
 }
-pub fn minps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn minps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vminps(a, Rvalue::Undefined, b)
 }
-pub fn minss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn minss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vminss(a, Rvalue::Undefined, b)
 }
-pub fn movaps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movaps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn minhps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2531,26 +2685,26 @@ pub fn movlps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn movmskps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn movntps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movntps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn movntq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movntq(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn movss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn movups(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movups(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn mulps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn mulps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmulps(a, Rvalue::Undefined, b)
 }
-pub fn mulss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn mulss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmulss(a, Rvalue::Undefined, b)
 }
-pub fn orps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn orps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vorps(a, Rvalue::Undefined, b)
 }
 pub fn pavgb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2627,20 +2781,20 @@ pub fn sfence() -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn shufps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn sqrtps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn sqrtps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, Rvalue::Undefined, b, 32, float_lane)
 }
-pub fn sqrtss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn sqrtss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vsqrtss(a, Rvalue::Undefined, b)
 }
 pub fn stmxcsr() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn subps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn subps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vsubps(a, Rvalue::Undefined, b)
 }
-pub fn subss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn subss(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vsubss(a, Rvalue::Undefined, b)
 }
 pub fn ucomiss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2651,22 +2805,22 @@ pub fn unpckhps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jump
 pub fn unpcklps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn xorps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn xorps(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vxorps(a, Rvalue::Undefined, b)
 }
 
 // SSE 2
-pub fn addpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn addpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vaddpd(a, Rvalue::Undefined, b)
 }
-pub fn addsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn addsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vaddsd(a, Rvalue::Undefined, b)
 }
-pub fn andnpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andnpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vandnpd(a, Rvalue::Undefined, b)
 }
-pub fn andpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn andpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vandpd(a, Rvalue::Undefined, b)
 }
 pub fn cflush(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2728,11 +2882,11 @@ pub fn cvttps2dq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn cvttsd2si(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn divpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn divpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vdivpd(a, Rvalue::Undefined, b)
 }
-pub fn divsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn divsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vdivsd(a, Rvalue::Undefined, b)
 }
 pub fn lfence() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2740,23 +2894,23 @@ pub fn lfence() -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn maskmovdqu(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn maxpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn maxpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmaxpd(a, Rvalue::Undefined, b)
 }
-pub fn maxsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn maxsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmaxsd(a, Rvalue::Undefined, b)
 }
 pub fn mfence() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn minpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn minpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vminpd(a, Rvalue::Undefined, b)
 }
-pub fn minsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn minsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vminsd(a, Rvalue::Undefined, b)
 }
-pub fn movd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn movdq2q(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2764,11 +2918,11 @@ pub fn movdq2q(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn movdaq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn movdqa(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movdqa(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn movdqu(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movdqu(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn movhpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2782,20 +2936,20 @@ pub fn movlpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn movmskpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn movntdq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movntdq(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn movntdqa(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movntdqa(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn movnti(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movnti(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    mov(a, b)
 }
-pub fn movntpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movntpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn movq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movq(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_low(a, b, 64)
 }
 pub fn movq2dq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2803,17 +2957,17 @@ pub fn movq2dq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn movsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn movupd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn movupd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
-pub fn mulpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn mulpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmulpd(a, Rvalue::Undefined, b)
 }
-pub fn mulsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn mulsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vmulsd(a, Rvalue::Undefined, b)
 }
-pub fn orpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn orpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vorpd(a, Rvalue::Undefined, b)
 }
 pub fn pabsb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2824,8 +2978,8 @@ pub fn pabsw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn pabsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn paddq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn paddq(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpaddq(a, Rvalue::Undefined, b)
 }
 pub fn pause() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2851,8 +3005,8 @@ pub fn psarw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn psrldq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn psubq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn psubq(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpsubq(a, Rvalue::Undefined, b)
 }
 pub fn pusbsw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2872,17 +3026,17 @@ pub fn puncklwd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn shufpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn sqrtpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn sqrtpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, Rvalue::Undefined, b, 64, float_lane)
 }
-pub fn sqrtsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn sqrtsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vsqrtsd(a, Rvalue::Undefined, b)
 }
-pub fn subpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn subpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vsubpd(a, Rvalue::Undefined, b)
 }
-pub fn subsd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn subsd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vsubsd(a, Rvalue::Undefined, b)
 }
 pub fn ucomisd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -2893,8 +3047,8 @@ pub fn unpckhpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn unpcklpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn xorpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn xorpd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vxorpd(a, Rvalue::Undefined, b)
 }
 
 // SSE 4
@@ -3003,8 +3157,8 @@ pub fn pmaxuw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn ptest(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pmulld(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pmulld(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpmulld(a, Rvalue::Undefined, b)
 }
 pub fn pmuldq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3033,8 +3187,8 @@ pub fn packusdw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn pblendvb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn pcmpeqq(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn pcmpeqq(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    vpcmpeqq(a, Rvalue::Undefined, b)
 }
 pub fn phminpushuw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3059,8 +3213,8 @@ pub fn hsubpd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn hsubps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn lddqu(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn lddqu(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn monitor() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3085,8 +3239,8 @@ pub fn palignr(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpS
 pub fn aesdec(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vmovd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmovd(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn aesdeclast(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3117,278 +3271,572 @@ pub fn vzeroupper() -> Result<(Vec<Statement>, JumpSpec)> {
 }
 
 // FPU
+//
+// The x87 register stack is modeled using the registers ST0 to ST7, which always name the
+// registers relative to the top of the stack. Pushing a value moves all registers one slot down and
+// popping moves them up, leaving ST7 undefined. The TOP field of the status word FSW is updated
+// alongside. RREIL knows no floating point, so results of arithmetic, comparisons and conversions
+// from and to other formats are undefined. Moves of 80 bit values and sign manipulations are exact.
+
+/// Stack register ST(`i`).
+fn st(i: usize) -> Lvalue {
+    Lvalue::Variable { name: format!("ST{}", i).into(), size: 80, subscript: None }
+}
+
+/// Adds `delta` modulo 8 to the TOP field of FSW.
+fn fpu_top(delta: u64) -> Result<Vec<Statement>> {
+    rreil!{
+        add top:3, FSW:3/11, [delta]:3;
+        sel/11 FSW:16, top:3;
+    }
+}
+
+/// Pushes `val` onto the register stack.
+fn fpu_push(val: &Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov fpu:80, (val);
+    }?;
+
+    for i in (1..8).rev() {
+        stmts.append(&mut rreil!{ mov (st(i)), (st(i - 1)); }?);
+    }
+
+    stmts.append(&mut rreil!{ mov ST0:80, fpu:80; }?);
+    stmts.append(&mut fpu_top(7)?);
+    Ok(stmts)
+}
+
+/// Removes ST0 from the register stack.
+fn fpu_pop() -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    for i in 0..7 {
+        stmts.append(&mut rreil!{ mov (st(i)), (st(i + 1)); }?);
+    }
+
+    stmts.append(&mut rreil!{ mov ST7:80, ?; }?);
+    stmts.append(&mut fpu_top(1)?);
+    Ok(stmts)
+}
+
+/// Pushes the extended precision constant with exponent `exp` and mantissa `mant`.
+fn fpu_constant(exp: u64, mant: u64) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        zext/80 k:80, [mant]:64;
+        sel/64 k:80, [exp]:16;
+    }?;
+
+    stmts.append(&mut fpu_push(&rreil_rvalue!(k:80))?);
+    Ok(stmts)
+}
+
+/// Sets `dst` to an unknown value.
+fn fpu_undefined(dst: &Rvalue) -> Result<Vec<Statement>> {
+    let lv = Lvalue::from_rvalue(dst.clone()).ok_or("Internal error: FPU result is a constant")?;
+
+    rreil!{
+        mov (lv), ?;
+    }
+}
+
+/// Copies ST0 to `dst`. Stores to 32 and 64 bit memory operands convert the value and are
+/// undefined.
+fn fpu_store(dst: &Rvalue) -> Result<Vec<Statement>> {
+    if dst.size() == Some(80) {
+        let lv = Lvalue::from_rvalue(dst.clone()).ok_or("Internal error: FPU result is a constant")?;
+
+        rreil!{
+            mov (lv), ST0:80;
+        }
+    } else {
+        fpu_undefined(dst)
+    }
+}
+
+/// Sets the condition codes C0, C2 and C3 to unknown values and pops `pops` registers.
+fn fpu_compare(pops: usize) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov c0:1, ?;
+        mov c2:1, ?;
+        mov c3:1, ?;
+        sel/8 FSW:16, c0:1;
+        sel/10 FSW:16, c2:1;
+        sel/14 FSW:16, c3:1;
+    }?;
+
+    for _ in 0..pops {
+        stmts.append(&mut fpu_pop()?);
+    }
+
+    Ok(stmts)
+}
+
+/// FCOMI and friends set ZF, PF and CF, and clear OF, SF and AF.
+fn fpu_compare_flags(pops: usize) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov ZF:1, ?;
+        mov PF:1, ?;
+        mov CF:1, ?;
+        mov OF:1, [0]:1;
+        mov SF:1, [0]:1;
+        mov AF:1, [0]:1;
+    }?;
+
+    for _ in 0..pops {
+        stmts.append(&mut fpu_pop()?);
+    }
+
+    Ok(stmts)
+}
+
+/// Moves `b` into `a` if `cond` is true (or false if `negate` is set). Computed as
+/// a + cond * (b - a) to avoid branching.
+fn fcmov(a: Rvalue, b: Rvalue, cond: Rvalue, negate: bool) -> Result<(Vec<Statement>, JumpSpec)> {
+    let a_lv = Lvalue::from_rvalue(a.clone()).ok_or("Internal error: fcmov with constant")?;
+    let mut stmts = if negate {
+        rreil!{
+            xor c:1, (cond), [1]:1;
+        }?
+    } else {
+        rreil!{
+            mov c:1, (cond);
+        }?
+    };
+
+    stmts.append(
+        &mut rreil!{
+            zext/80 m:80, c:1;
+            sub d:80, (b), (a);
+            mul d:80, d:80, m:80;
+            add (a_lv), (a), d:80;
+        }?
+    );
+
+    Ok((stmts, JumpSpec::FallThru))
+}
+
+/// Resets the FPU. All registers are marked as empty.
+fn fpu_init() -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov FCW:16, [0x37f]:16;
+        mov FSW:16, [0]:16;
+    }?;
+
+    for i in 0..8 {
+        stmts.append(&mut rreil!{ mov (st(i)), ?; }?);
+    }
+
+    Ok(stmts)
+}
+
 pub fn f2xm1() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_undefined(&rreil_rvalue!(ST0:80))?, JumpSpec::FallThru))
 }
 pub fn fabs() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let stmts = rreil!{
+        sel/79 ST0:80, [0]:1;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fadd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fadd(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn faddp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn faddp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fiadd(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fiadd(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
 pub fn fbld(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_push(&Rvalue::Undefined)?, JumpSpec::FallThru))
 }
-pub fn fbstp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fbstp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fchs() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let stmts = rreil!{
+        xor sign:1, ST0:1/79, [1]:1;
+        sel/79 ST0:80, sign:1;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fclex() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let stmts = rreil!{
+        and FSW:16, FSW:16, [0x7f00]:16;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fnclex(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fcmovb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovb(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    fcmov(a, b, rreil_rvalue!(CF:1), false)
 }
-pub fn fcmove(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmove(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    fcmov(a, b, rreil_rvalue!(ZF:1), false)
 }
-pub fn fcmovbe(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovbe(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = rreil!{
+        or be:1, CF:1, ZF:1;
+    }?;
+    let (mut mov, jmp) = fcmov(a, b, rreil_rvalue!(be:1), false)?;
+
+    stmts.append(&mut mov);
+    Ok((stmts, jmp))
 }
-pub fn fcmovu(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovu(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    fcmov(a, b, rreil_rvalue!(PF:1), false)
 }
-pub fn fcmovnb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovnb(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    fcmov(a, b, rreil_rvalue!(CF:1), true)
 }
-pub fn fcmovne(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovne(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    fcmov(a, b, rreil_rvalue!(ZF:1), true)
 }
-pub fn fcmovnbe(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovnbe(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = rreil!{
+        or be:1, CF:1, ZF:1;
+    }?;
+    let (mut mov, jmp) = fcmov(a, b, rreil_rvalue!(be:1), true)?;
+
+    stmts.append(&mut mov);
+    Ok((stmts, jmp))
 }
-pub fn fcmovnu(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fcmovnu(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    fcmov(a, b, rreil_rvalue!(PF:1), true)
 }
 pub fn fcom(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(0)?, JumpSpec::FallThru))
 }
 pub fn fcomp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(1)?, JumpSpec::FallThru))
 }
 pub fn fcompp() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(2)?, JumpSpec::FallThru))
 }
 pub fn fcomi(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare_flags(0)?, JumpSpec::FallThru))
 }
 pub fn fcomip(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare_flags(1)?, JumpSpec::FallThru))
 }
 pub fn fucomi(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare_flags(0)?, JumpSpec::FallThru))
 }
 pub fn fucomip(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare_flags(1)?, JumpSpec::FallThru))
 }
 pub fn fcos() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_undefined(&rreil_rvalue!(ST0:80))?, JumpSpec::FallThru))
 }
 pub fn fdecstp() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = rreil!{
+        mov fpu:80, ST7:80;
+    }?;
+
+    for i in (1..8).rev() {
+        stmts.append(&mut rreil!{ mov (st(i)), (st(i - 1)); }?);
+    }
+
+    stmts.append(&mut rreil!{ mov ST0:80, fpu:80; }?);
+    stmts.append(&mut fpu_top(7)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fdiv(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fdiv(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fdivp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fdivp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fidiv(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fidiv(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fdivr(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fdivr(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fdivrp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fdivrp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fidivr(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fidivr(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn ffree(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn ffree(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
 pub fn ficom(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(0)?, JumpSpec::FallThru))
 }
 pub fn ficomp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(1)?, JumpSpec::FallThru))
 }
 pub fn fild(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_push(&Rvalue::Undefined)?, JumpSpec::FallThru))
 }
 pub fn fincstp() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = rreil!{
+        mov fpu:80, ST0:80;
+    }?;
+
+    for i in 0..7 {
+        stmts.append(&mut rreil!{ mov (st(i)), (st(i + 1)); }?);
+    }
+
+    stmts.append(&mut rreil!{ mov ST7:80, fpu:80; }?);
+    stmts.append(&mut fpu_top(1)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn finit() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_init()?, JumpSpec::FallThru))
 }
 pub fn fninit(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fistp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fistp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fisttp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fisttp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fld(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fld(_: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    // only 80 bit values can be loaded without conversion
+    let val = if b.size() == Some(80) { b } else { Rvalue::Undefined };
+
+    Ok((fpu_push(&val)?, JumpSpec::FallThru))
 }
 pub fn fld1() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x3fff, 0x8000000000000000)?, JumpSpec::FallThru))
 }
 pub fn fldl2t() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x4000, 0xd49a784bcd1b8afe)?, JumpSpec::FallThru))
 }
 pub fn fldl2e() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x3fff, 0xb8aa3b295c17f0bc)?, JumpSpec::FallThru))
 }
 pub fn fldpi() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x4000, 0xc90fdaa22168c235)?, JumpSpec::FallThru))
 }
 pub fn fldlg2() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x3ffd, 0x9a209a84fbcff799)?, JumpSpec::FallThru))
 }
 pub fn fldln2() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x3ffe, 0xb17217f7d1cf79ac)?, JumpSpec::FallThru))
 }
 pub fn fldz() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_constant(0x0, 0x0)?, JumpSpec::FallThru))
 }
-pub fn fldcw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fldcw(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let stmts = rreil!{
+        mov FCW:16, (a);
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fmul(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fmul(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fmulp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fmulp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fimul(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fimul(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
 pub fn fnop() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
 pub fn fpatan() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST1:80))?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fprem() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST0:80))?;
+
+    stmts.append(&mut fpu_compare(0)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fprem1() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST0:80))?;
+
+    stmts.append(&mut fpu_compare(0)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fptan() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST0:80))?;
+
+    stmts.append(&mut fpu_constant(0x3fff, 0x8000000000000000)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn frndint() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_undefined(&rreil_rvalue!(ST0:80))?, JumpSpec::FallThru))
 }
-pub fn frstor(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn frstor(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = rreil!{
+        mov FCW:16, ?;
+        mov FSW:16, ?;
+    }?;
+
+    for i in 0..8 {
+        stmts.append(&mut rreil!{ mov (st(i)), ?; }?);
+    }
+
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fsave(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fsave(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_init()?, JumpSpec::FallThru))
 }
 pub fn fnsave(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
 pub fn fscale() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_undefined(&rreil_rvalue!(ST0:80))?, JumpSpec::FallThru))
 }
 pub fn fsin() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_undefined(&rreil_rvalue!(ST0:80))?, JumpSpec::FallThru))
 }
 pub fn fsincos() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST0:80))?;
+
+    stmts.append(&mut fpu_push(&Rvalue::Undefined)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fsqrt() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_undefined(&rreil_rvalue!(ST0:80))?, JumpSpec::FallThru))
 }
-pub fn fst1(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fst1(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_store(&a)?, JumpSpec::FallThru))
 }
-pub fn fst2(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fst2(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_store(&a)?, JumpSpec::FallThru))
 }
 pub fn fstp(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fstcw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fstcw(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let a_lv = Lvalue::from_rvalue(a).ok_or("Internal error: fstcw with constant")?;
+    let stmts = rreil!{
+        mov (a_lv), FCW:16;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fldenv(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fldenv(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let stmts = rreil!{
+        mov FCW:16, ?;
+        mov FSW:16, ?;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fstenv(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fstenv(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let stmts = rreil!{
+        or FCW:16, FCW:16, [0x3f]:16;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fnstenv(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fstsw1(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fstsw1(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((write_reg(&a, &rreil_rvalue!(FSW:16), 16)?, JumpSpec::FallThru))
 }
-pub fn fstsw2(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fstsw2(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let a_lv = Lvalue::from_rvalue(a).ok_or("Internal error: fstsw with constant")?;
+    let stmts = rreil!{
+        mov (a_lv), FSW:16;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fnstsw(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fsub(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fsub(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fsubp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fsubp(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_undefined(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fisub(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fisub(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fsubr(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fsubr(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
-pub fn fisubr(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fisubr(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    Ok((fpu_undefined(&a)?, JumpSpec::FallThru))
 }
 pub fn ftst() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(0)?, JumpSpec::FallThru))
 }
 pub fn fucom(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(0)?, JumpSpec::FallThru))
 }
 pub fn fucomp(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(1)?, JumpSpec::FallThru))
 }
 pub fn fucompp() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(2)?, JumpSpec::FallThru))
 }
 pub fn fxam() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    Ok((fpu_compare(0)?, JumpSpec::FallThru))
 }
-pub fn fxch(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fxch(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let a_lv = Lvalue::from_rvalue(a.clone()).ok_or("Internal error: fxch with constant")?;
+    let b_lv = Lvalue::from_rvalue(b.clone()).ok_or("Internal error: fxch with constant")?;
+    let stmts = rreil!{
+        mov fpu:80, (a);
+        mov (a_lv), (b);
+        mov (b_lv), fpu:80;
+    }?;
+
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fxtract() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST0:80))?;
+
+    stmts.append(&mut fpu_push(&Rvalue::Undefined)?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fyl2x() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST1:80))?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn fyl2xp1() -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+    let mut stmts = fpu_undefined(&rreil_rvalue!(ST1:80))?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 
 // MPX
@@ -3511,17 +3959,17 @@ pub fn fnmsub231ss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, J
 }
 
 // AVX
-pub fn vaddpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vaddpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, float_lane)
 }
-pub fn vaddps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vaddps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, float_lane)
 }
-pub fn vaddsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vaddsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
-pub fn vaddss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vaddss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
 pub fn vaddsubpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3547,17 +3995,17 @@ pub fn vaesimc(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpS
 pub fn vaeskeygenassist(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vandpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ and (r), (x), (y); })
 }
-pub fn vandps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ and (r), (x), (y); })
 }
-pub fn vandnpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandnpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, and_not)
 }
-pub fn vandnps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vandnps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, and_not)
 }
 pub fn vblendpd(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3634,17 +4082,17 @@ pub fn vcvttsd2si(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn vcvttss2si(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vdivps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vdivps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, float_lane)
 }
-pub fn vdivpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vdivpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, float_lane)
 }
-pub fn vdivss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vdivss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
-pub fn vdivsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vdivsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
 pub fn vdppd(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3670,35 +4118,35 @@ pub fn vhsubps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpS
 pub fn vinsertps(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vlddqu(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vlddqu(a: Rvalue, b: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_vector(a, b)
 }
 pub fn vldmxcsr(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vmaxpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmaxpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, float_lane)
 }
-pub fn vmaxsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmaxsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
-pub fn vmaxps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmaxps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, float_lane)
 }
-pub fn vmaxss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmaxss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
-pub fn vminpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vminpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, float_lane)
 }
-pub fn vminsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vminsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
-pub fn vminps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vminps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, float_lane)
 }
-pub fn vminss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vminss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
 pub fn vmovhpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3712,20 +4160,20 @@ pub fn vmovlpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpS
 pub fn vmovlps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vmovsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmovsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_scalar(a, b, c, 64)
 }
-pub fn vmovss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmovss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    move_scalar(a, b, c, 32)
 }
 pub fn vmpsadbw(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vorpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vorpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ or (r), (x), (y); })
 }
-pub fn vorps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vorps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ or (r), (x), (y); })
 }
 pub fn vpabsb(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3748,17 +4196,17 @@ pub fn vpackusdw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jum
 pub fn vpackuswb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpaddb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpaddb(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 8, |r, x, y| rreil!{ add (r), (x), (y); })
 }
-pub fn vpaddw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpaddw(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 16, |r, x, y| rreil!{ add (r), (x), (y); })
 }
-pub fn vpaddd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpaddd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, |r, x, y| rreil!{ add (r), (x), (y); })
 }
-pub fn vpaddq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpaddq(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ add (r), (x), (y); })
 }
 pub fn vpaddsb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3775,11 +4223,11 @@ pub fn vpaddusw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jump
 pub fn vpalignr(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpand(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpand(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ and (r), (x), (y); })
 }
-pub fn vpandn(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpandn(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, and_not)
 }
 pub fn vpavgb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3796,17 +4244,17 @@ pub fn vpblendw(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<State
 pub fn vpclmulqdq(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpcmpeqb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpcmpeqb(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    compare_equal(a, b, c, 8)
 }
-pub fn vpcmpeqw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpcmpeqw(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    compare_equal(a, b, c, 16)
 }
-pub fn vpcmpeqd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpcmpeqd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    compare_equal(a, b, c, 32)
 }
-pub fn vpcmpeqq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpcmpeqq(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    compare_equal(a, b, c, 64)
 }
 pub fn vpcmpgtb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3904,17 +4352,17 @@ pub fn vpmulhuw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jump
 pub fn vpmulhw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpmulld(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpmulld(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, |r, x, y| rreil!{ mul (r), (x), (y); })
 }
-pub fn vpmullw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpmullw(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 16, |r, x, y| rreil!{ mul (r), (x), (y); })
 }
 pub fn vpmuludq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpor(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpor(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ or (r), (x), (y); })
 }
 pub fn vpsadbw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -3958,17 +4406,17 @@ pub fn vpsrld(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSp
 pub fn vpsrlq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpsubb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpsubb(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 8, |r, x, y| rreil!{ sub (r), (x), (y); })
 }
-pub fn vpsubw(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpsubw(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 16, |r, x, y| rreil!{ sub (r), (x), (y); })
 }
-pub fn vpsubd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpsubd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, |r, x, y| rreil!{ sub (r), (x), (y); })
 }
-pub fn vpsubq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpsubq(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ sub (r), (x), (y); })
 }
 pub fn vpsubsb(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4009,8 +4457,8 @@ pub fn vpuncklqdq(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Ju
 pub fn vpuncklwd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vpxor(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vpxor(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ xor (r), (x), (y); })
 }
 pub fn vrcpps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4033,11 +4481,11 @@ pub fn vrsqrtps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Jump
 pub fn vrsqrtss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vsqrtss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vsqrtss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
-pub fn vsqrtsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vsqrtsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
 pub fn vshufps(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4045,17 +4493,17 @@ pub fn vshufps(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statem
 pub fn vshufpd(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vsubps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vsubps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, float_lane)
 }
-pub fn vsubss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vsubss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
-pub fn vsubpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vsubpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, float_lane)
 }
-pub fn vsubsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vsubsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
 pub fn vunpckhps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4108,17 +4556,17 @@ pub fn vmaskmovps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, Ju
 pub fn vmaskmovpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vmulps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmulps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 32, float_lane)
 }
-pub fn vmulss(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmulss(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 32, float_lane)
 }
-pub fn vmulpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmulpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, float_lane)
 }
-pub fn vmulsd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vmulsd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    scalar(a, b, c, 64, float_lane)
 }
 pub fn vblendd(_: Rvalue, _: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -4186,11 +4634,11 @@ pub fn vtestps(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn vzeroall() -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn vxorps(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vxorps(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ xor (r), (x), (y); })
 }
-pub fn vxorpd(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn vxorpd(a: Rvalue, b: Rvalue, c: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    packed(a, b, c, 64, |r, x, y| rreil!{ xor (r), (x), (y); })
 }
 
 pub fn broadcastf128(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
@@ -4208,11 +4656,17 @@ pub fn broadcastss(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
 pub fn fst(_: Rvalue, _: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
 }
-pub fn fstp1(_: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fstp1(a: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_store(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
-pub fn fstp2(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
-    Ok((vec![], JumpSpec::FallThru))
+pub fn fstp2(a: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
+    let mut stmts = fpu_store(&a)?;
+
+    stmts.append(&mut fpu_pop()?);
+    Ok((stmts, JumpSpec::FallThru))
 }
 pub fn pboradcastw(_: Rvalue, _: Rvalue) -> Result<(Vec<Statement>, JumpSpec)> {
     Ok((vec![], JumpSpec::FallThru))
@@ -111,6 +111,14 @@ macro_rules! opcode {
             OperandSpec(AddressingMethod::$addr1,OperandType::$type1),
             OperandSpec(AddressingMethod::None,OperandType::$type2))
     };
+    ($sem:ident ($mne:expr); $addr1:ident / $type1:ident , $type2:ident) => {
+        Opcode::Binary(
+            MnemonicSpec::Single($mne),
+            OpcodeOption::None,
+            $crate::semantic::$sem,
+            OperandSpec(AddressingMethod::$addr1,OperandType::$type1),
+            OperandSpec(AddressingMethod::None,OperandType::$type2))
+    };
     ($mne:ident; $type1:ident , $type2:ident) => {
         Opcode::Binary(
             MnemonicSpec::Single(stringify!($mne)),
@@ -363,7 +371,7 @@ pub static ONEBYTE_TABLE: [Opcode; 256] = [
     opcode!(xchg; rDIr15, rAX),      // 0x97: xchg
     opcode!(cbw; ),               // 0x98: cbw
     opcode!(cwd; ),               // 0x99: cwd
-    opcode!(call; A/v; Invalid64),           // 0x9a: call
+    opcode!(call; A/p; Invalid64),           // 0x9a: call
     opcode!(wait; ),              // 0x9b: wait
     opcode!(pushfw; ; Default64),            // 0x9c: pushfw
     opcode!(popfw; ; Default64),             // 0x9d: popfw
@@ -4806,7 +4814,7 @@ pub static X87_D9_TABLE: [Opcode; 64] = [
     opcode!(fldl2t; ),
     opcode!(fldl2e; ),
     opcode!(fldpi; ),
-    opcode!(fldl2g; ),
+    opcode!(fldlg2; ),
     opcode!(fldln2; ),
     opcode!(fldz; ),
     unused!(),
@@ -4815,14 +4823,14 @@ pub static X87_D9_TABLE: [Opcode; 64] = [
     opcode!(fptan; ),
     opcode!(fpatan; ),
     opcode!(fxtract; ),
-    opcode!(fperm1; ),
+    opcode!(fprem1; ),
     opcode!(fdecstp; ),
     opcode!(fincstp; ),
-    opcode!(fperm; ),
+    opcode!(fprem; ),
     opcode!(fyl2xp1; ),
     opcode!(fsqrt; ),
     opcode!(fsincos; ),
-    opcode!(frndintx; ),
+    opcode!(frndint; ),
     opcode!(fscale; ),
     opcode!(fsin; ),
     opcode!(fcos; ),
@@ -5231,89 +5239,89 @@ pub static X87_DF_TABLE: [Opcode; 64] = [
 ];
 
 pub static X87_D8_TABLE2: [Opcode; 8] = [
-    opcode!(fadd; E/d, G/d),
-    opcode!(fmul; E/d, G/d),
-    opcode!(fcom; E/d, G/d),
-    opcode!(fcomp; E/d, G/d),
-    opcode!(fsub; E/d, G/d),
-    opcode!(fsubr; E/d, G/d),
-    opcode!(fdiv; E/d, G/d),
-    opcode!(fdivr; E/d, G/d),
+    opcode!(fadd; ST0, E/d),
+    opcode!(fmul; ST0, E/d),
+    opcode!(fcom; ST0, E/d),
+    opcode!(fcomp; ST0, E/d),
+    opcode!(fsub; ST0, E/d),
+    opcode!(fsubr; ST0, E/d),
+    opcode!(fdiv; ST0, E/d),
+    opcode!(fdivr; ST0, E/d),
 ];
 
 pub static X87_D9_TABLE2: [Opcode; 8] = [
-    opcode!(fld; E/d, G/d),
-    unused!(),
-    opcode!(fst2 ("fst"); E/d, G/d),
-    opcode!(fstp2 ("fstp"); E/d, G/d),
-    opcode!(fldenv; E/d, G/d),
-    opcode!(fldcw; E/d, G/d),
-    opcode!(fstenv; E/d, G/d),
-    opcode!(fstcw; E/d, G/d),
+    opcode!(fld; ST0, E/d),
+    unused!(),
+    opcode!(fst2 ("fst"); E/d, ST0),
+    opcode!(fstp2 ("fstp"); E/d, ST0),
+    opcode!(fldenv; M/None),
+    opcode!(fldcw; E/w),
+    opcode!(fstenv; M/None),
+    opcode!(fstcw; E/w),
 ];
 
 pub static X87_DA_TABLE2: [Opcode; 8] = [
-    opcode!(fiadd; E/d, G/d),
-    opcode!(fimul; E/d, G/d),
-    opcode!(ficom; E/d, G/d),
-    opcode!(ficomp; E/d, G/d),
-    opcode!(fisub; E/d, G/d),
-    opcode!(fisubr; E/d, G/d),
-    opcode!(fidiv; E/d, G/d),
-    opcode!(fidivr; E/d, G/d),
+    opcode!(fiadd; ST0, E/d),
+    opcode!(fimul; ST0, E/d),
+    opcode!(ficom; ST0, E/d),
+    opcode!(ficomp; ST0, E/d),
+    opcode!(fisub; ST0, E/d),
+    opcode!(fisubr; ST0, E/d),
+    opcode!(fidiv; ST0, E/d),
+    opcode!(fidivr; ST0, E/d),
 ];
 
 pub static X87_DB_TABLE2: [Opcode; 8] = [
-    opcode!(fild; E/d, G/d),
-    opcode!(fisttp; E/d, G/d),
-    opcode!(fist; E/d, G/d),
-    opcode!(fistp; E/d, G/d),
+    opcode!(fild; ST0, E/d),
+    opcode!(fisttp; E/d, ST0),
+    opcode!(fist; E/d, ST0),
+    opcode!(fistp; E/d, ST0),
     unused!(),
-    opcode!(fld; E/dq, G/dq),
+    opcode!(fld; ST0, E/t),
     unused!(),
-    opcode!(fstp2 ("fstp"); E/dq, G/dq),
+    opcode!(fstp2 ("fstp"); E/t, ST0),
 ];
 
 pub static X87_DC_TABLE2: [Opcode; 8] = [
-    opcode!(fadd; E/dq, G/dq),
-    opcode!(fmul; E/dq, G/dq),
-    opcode!(fcom; E/dq, G/dq),
-    opcode!(fcomp; E/dq, G/dq),
-    opcode!(fsub; E/dq, G/dq),
-    opcode!(fsubr; E/dq, G/dq),
-    opcode!(fdiv; E/dq, G/dq),
-    opcode!(fdivr; E/dq, G/dq),
+    opcode!(fadd; ST0, E/dq),
+    opcode!(fmul; ST0, E/dq),
+    opcode!(fcom; ST0, E/dq),
+    opcode!(fcomp; ST0, E/dq),
+    opcode!(fsub; ST0, E/dq),
+    opcode!(fsubr; ST0, E/dq),
+    opcode!(fdiv; ST0, E/dq),
+    opcode!(fdivr; ST0, E/dq),
 ];
 
 pub static X87_DD_TABLE2: [Opcode; 8] = [
-    opcode!(fld; E/dq, G/dq),
-    opcode!(fisttp; E/d, G/d),
-    opcode!(fst2 ("fst"); E/dq, G/dq),
-    opcode!(fstp2 ("fstp"); E/dq, G/dq),
-    opcode!(frstor; E/dq, G/dq),
-    unused!(),
-    opcode!(fsave; E/dq, G/dq),
-    opcode!(fstsw2 ("fstsw"); E/dq, G/dq),
+    opcode!(fld; ST0, E/dq),
+    opcode!(fisttp; E/dq, ST0),
+    opcode!(fst2 ("fst"); E/dq, ST0),
+    opcode!(fstp2 ("fstp"); E/dq, ST0),
+    opcode!(frstor; M/None),
+    unused!(),
+    opcode!(fsave; M/None),
+    opcode!(fstsw2 ("fstsw"); E/w),
 ];
 
 pub static X87_DE_TABLE2: [Opcode; 8] = [
-    opcode!(fiadd; E/w, G/w),
-    opcode!(fimul; E/w, G/w),
-    opcode!(ficom; E/w, G/w),
-    opcode!(ficomp; E/w, G/w),
-    opcode!(fisub; E/w, G/w),
-    opcode!(fisubr; E/w, G/w),
-    opcode!(fidiv; E/w, G/w),
-    opcode!(fidivr; E/w, G/w),
+    opcode!(fiadd; ST0, E/w),
+    opcode!(fimul; ST0, E/w),
+    opcode!(ficom; ST0, E/w),
+    opcode!(ficomp; ST0, E/w),
+    opcode!(fisub; ST0, E/w),
+    opcode!(fisubr; ST0, E/w),
+    opcode!(fidiv; ST0, E/w),
+    opcode!(fidivr; ST0, E/w),
 ];
 
 pub static X87_DF_TABLE2: [Opcode; 8] = [
-    opcode!(fild; E/w, G/w),
-    opcode!(fisttp; E/w, G/w),
-    opcode!(fist; E/w, G/w),
-    opcode!(fistp; E/w, G/w),
-    opcode!(fbld; E/w, G/w),
-    opcode!(fild; E/dq, G/dq),
-    opcode!(fbstp; E/w, G/w),
-    opcode!(fistp; E/dq, G/dq),
+    opcode!(fild; ST0, E/w),
+    opcode!(fisttp; E/w, ST0),
+    opcode!(fist; E/w, ST0),
+    opcode!(fistp; E/w, ST0),
+    opcode!(fbld; ST0, E/t),
+    opcode!(fild; ST0, E/dq),
+    opcode!(fbstp; E/t, ST0),
+    opcode!(fistp; E/dq, ST0),
 ];
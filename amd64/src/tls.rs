@@ -0,0 +1,170 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! Thread-local storage references.
+//!
+//! The lifter adds `FS_BASE` or `GS_BASE` to every FS/GS-prefixed memory operand. On Linux these
+//! point to the thread control block (`tcbhead_t` in glibc): FS in 64 bit code and GS in 32 bit
+//! code. Fields of the control block live at positive offsets, variables in the static TLS block
+//! right below it. This module finds these accesses in a function and names the slots that are
+//! part of the ABI, most importantly the stack protector canary at `fs:[0x28]`/`gs:[0x14]`.
+
+use Mode;
+use panopticon_core::{Function, Lvalue, Operation, Rvalue, Statement};
+
+/// Segment register used for the access.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Segment {
+    Fs,
+    Gs,
+}
+
+/// Thread-local storage slot.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum TlsSlot {
+    /// Pointer to the thread control block itself (`tcb` and `self`)
+    ThreadPointer,
+    /// Dynamic thread vector
+    Dtv,
+    /// Stack protector canary
+    StackGuard,
+    /// Key used by `PTR_MANGLE`
+    PointerGuard,
+    /// Variable in the static TLS block, the value is its distance below the thread pointer
+    Variable(u64),
+    /// Any other offset from the segment base
+    Unknown(u64),
+}
+
+impl TlsSlot {
+    /// Classifies the access to `seg:[offset]` in code running in `mode`.
+    pub fn new(seg: Segment, offset: u64, mode: Mode) -> TlsSlot {
+        let w = mode.bits();
+        let negative = w < 64 && offset & (1 << (w - 1)) != 0 || w == 64 && offset & (1 << 63) != 0;
+
+        match (mode, seg) {
+            (Mode::Long, Segment::Fs) | (Mode::Protected, Segment::Gs) if negative => {
+                let mask = if w < 64 { (1u64 << w) - 1 } else { !0 };
+                TlsSlot::Variable(offset.wrapping_neg() & mask)
+            }
+            (Mode::Long, Segment::Fs) => {
+                match offset {
+                    0x00 | 0x10 => TlsSlot::ThreadPointer,
+                    0x08 => TlsSlot::Dtv,
+                    0x28 => TlsSlot::StackGuard,
+                    0x30 => TlsSlot::PointerGuard,
+                    _ => TlsSlot::Unknown(offset),
+                }
+            }
+            (Mode::Protected, Segment::Gs) => {
+                match offset {
+                    0x00 | 0x08 => TlsSlot::ThreadPointer,
+                    0x04 => TlsSlot::Dtv,
+                    0x14 => TlsSlot::StackGuard,
+                    0x18 => TlsSlot::PointerGuard,
+                    _ => TlsSlot::Unknown(offset),
+                }
+            }
+            _ => TlsSlot::Unknown(offset),
+        }
+    }
+
+    /// Symbolic name of the slot.
+    pub fn name(&self) -> String {
+        match *self {
+            TlsSlot::ThreadPointer => "__tcb".to_string(),
+            TlsSlot::Dtv => "__dtv".to_string(),
+            TlsSlot::StackGuard => "__stack_chk_guard".to_string(),
+            TlsSlot::PointerGuard => "__pointer_chk_guard".to_string(),
+            TlsSlot::Variable(off) => format!("tls_{:x}", off),
+            TlsSlot::Unknown(off) => format!("tcb_{:x}", off),
+        }
+    }
+}
+
+/// Memory access relative to FS or GS.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct TlsReference {
+    /// Address of the accessing instruction
+    pub address: u64,
+    pub segment: Segment,
+    /// Offset from the segment base, as unsigned pointer-sized value
+    pub offset: u64,
+    pub slot: TlsSlot,
+    /// True if the instruction writes the slot
+    pub write: bool,
+}
+
+/// Returns all accesses with constant offset relative to FS or GS inside `func`.
+pub fn tls_references(func: &Function, mode: Mode) -> Vec<TlsReference> {
+    let mut ret = vec![];
+
+    for bb in func.basic_blocks() {
+        for mne in bb.mnemonics.iter() {
+            for stmt in mne.instructions.iter() {
+                if let Some((seg, offset)) = segment_offset(stmt) {
+                    let write = mne.instructions.iter().any(
+                        |s| match s.op {
+                            Operation::Store(_, _, _, ref addr, _) => Lvalue::from_rvalue(addr.clone()).as_ref() == Some(&stmt.assignee),
+                            _ => false,
+                        }
+                    );
+
+                    ret.push(
+                        TlsReference {
+                            address: mne.area.start,
+                            segment: seg,
+                            offset: offset,
+                            slot: TlsSlot::new(seg, offset, mode),
+                            write: write,
+                        }
+                    );
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Matches the `add linear, FS_BASE, offset` statements emitted by the lifter.
+fn segment_offset(stmt: &Statement) -> Option<(Segment, u64)> {
+    match stmt.op {
+        Operation::Add(Rvalue::Variable { ref name, .. }, Rvalue::Constant { value, .. }) => {
+            match name.as_ref() {
+                "FS_BASE" => Some((Segment::Fs, value)),
+                "GS_BASE" => Some((Segment::Gs, value)),
+                _ => None,
+            }
+        }
+        Operation::Move(Rvalue::Variable { ref name, .. }) => {
+            let linear = match stmt.assignee {
+                Lvalue::Variable { name: ref n, .. } => n,
+                Lvalue::Undefined => return None,
+            };
+
+            match name.as_ref() {
+                "FS_BASE" if linear.starts_with("FS:") => Some((Segment::Fs, 0)),
+                "GS_BASE" if linear.starts_with("GS:") => Some((Segment::Gs, 0)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
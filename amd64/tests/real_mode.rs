@@ -0,0 +1,142 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_amd64;
+
+use panopticon_amd64 as amd64;
+use panopticon_core::{Architecture, Match, Operation, Region, Rvalue};
+
+// boot sector code loaded at 0000:7c00
+fn boot_sector(code: &[u8]) -> Region {
+    let mut buf = vec![0; 0x7c00];
+
+    buf.extend_from_slice(code);
+    buf.resize(0x20000, 0);
+    Region::wrap("ram".to_string(), buf)
+}
+
+fn decode(reg: &Region, addr: u64, cs: u16) -> Match<amd64::Amd64> {
+    amd64::Amd64::decode(reg, addr, &amd64::Mode::Real(cs)).unwrap()
+}
+
+fn targets(m: &Match<amd64::Amd64>) -> Vec<u64> {
+    let mut ret = m.jumps
+        .iter()
+        .filter_map(|&(_, ref tgt, _)| if let &Rvalue::Constant { value, .. } = tgt { Some(value) } else { None })
+        .collect::<Vec<_>>();
+    ret.sort();
+    ret
+}
+
+#[test]
+fn segment_offset_addressing() {
+    let reg = boot_sector(
+        &[
+            0x8a, 0x47, 0x02, // mov al, [bx+2]
+            0x8b, 0x46, 0xfe, // mov ax, [bp-2]
+            0x26, 0x89, 0x04, // mov es:[si], ax
+        ]
+    );
+    let segment_of = |m: &Match<amd64::Amd64>| {
+        m.mnemonics[0]
+            .instructions
+            .iter()
+            .filter_map(
+                |s| match s.op {
+                    Operation::ZeroExtend(32, Rvalue::Variable { ref name, size: 16, .. }) => Some(name.to_string()),
+                    _ => None,
+                }
+            )
+            .next()
+    };
+
+    let m = decode(&reg, 0x7c00, 0);
+    assert_eq!(m.mnemonics[0].area.end, 0x7c03);
+    assert_eq!(segment_of(&m), Some("DS".to_string()));
+
+    let m = decode(&reg, 0x7c03, 0);
+    assert_eq!(m.mnemonics[0].area.end, 0x7c06);
+    assert_eq!(segment_of(&m), Some("SS".to_string()));
+
+    let m = decode(&reg, 0x7c06, 0);
+    assert_eq!(m.mnemonics[0].area.end, 0x7c09);
+    assert_eq!(segment_of(&m), Some("ES".to_string()));
+
+    for m in [decode(&reg, 0x7c00, 0), decode(&reg, 0x7c03, 0), decode(&reg, 0x7c06, 0)].iter() {
+        for s in m.mnemonics[0].instructions.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+}
+
+#[test]
+fn operand_size_prefix() {
+    let reg = boot_sector(
+        &[
+            0xb8, 0x34, 0x12, // mov ax, 0x1234
+            0x66, 0xb8, 0x78, 0x56, 0x34, 0x12, // mov eax, 0x12345678
+        ]
+    );
+
+    assert_eq!(decode(&reg, 0x7c00, 0).mnemonics[0].area.end, 0x7c03);
+    assert_eq!(decode(&reg, 0x7c03, 0).mnemonics[0].area.end, 0x7c09);
+}
+
+#[test]
+fn near_jumps_wrap_around() {
+    let reg = boot_sector(
+        &[
+            0x74, 0x02, // je +2
+            0xeb, 0xfa, // jmp -6
+        ]
+    );
+
+    // CS = 0
+    assert_eq!(targets(&decode(&reg, 0x7c00, 0)), vec![0x7c02, 0x7c04]);
+    assert_eq!(targets(&decode(&reg, 0x7c02, 0)), vec![0x7bfe]);
+
+    // CS = 07c0, jmp -6 at offset 2 wraps around to offset 0xfffe
+    assert_eq!(targets(&decode(&reg, 0x7c02, 0x07c0)), vec![0x7c00 + 0xfffe]);
+}
+
+#[test]
+fn far_jumps() {
+    let reg = boot_sector(
+        &[
+            0xea, 0x05, 0x00, 0xc0, 0x07, // jmp 07c0:0005
+            0x9a, 0x00, 0x01, 0x00, 0x10, // call 1000:0100
+        ]
+    );
+
+    let m = decode(&reg, 0x7c00, 0);
+    assert_eq!(targets(&m), vec![0x7c05]);
+    assert_eq!(m.configuration, amd64::Mode::Real(0x07c0));
+
+    let m = decode(&reg, 0x7c05, 0x07c0);
+    assert_eq!(m.mnemonics[0].area.end, 0x7c0a);
+    assert_eq!(targets(&m), vec![0x7c0a]);
+    assert_eq!(m.configuration, amd64::Mode::Real(0x07c0));
+    assert!(
+        m.mnemonics[0]
+            .instructions
+            .iter()
+            .any(|s| s.op == Operation::Call(Rvalue::Constant { value: 0x10100, size: 32 }))
+    );
+}
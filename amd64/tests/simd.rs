@@ -0,0 +1,159 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_amd64;
+
+use panopticon_amd64 as amd64;
+use panopticon_core::{Architecture, Lvalue, Mnemonic, Operation, Region, Rvalue};
+
+fn decode(code: &[u8], mode: amd64::Mode) -> Mnemonic {
+    let reg = Region::wrap("ram".to_string(), code.to_vec());
+    let mut m = amd64::Amd64::decode(&reg, 0, &mode).unwrap();
+
+    assert_eq!(m.mnemonics.len(), 1);
+    m.mnemonics.remove(0)
+}
+
+fn operands(m: &Mnemonic) -> Vec<String> {
+    m.operands.iter().map(|x| format!("{}", x)).collect()
+}
+
+fn writes(m: &Mnemonic, reg: &str) -> bool {
+    m.instructions.iter().any(
+        |s| match s.assignee {
+            Lvalue::Variable { ref name, .. } => name == reg,
+            _ => false,
+        }
+    )
+}
+
+fn count<F: Fn(&Operation<Rvalue>) -> bool>(m: &Mnemonic, f: F) -> usize {
+    m.instructions.iter().filter(|s| f(&s.op)).count()
+}
+
+#[test]
+fn legacy_encoding() {
+    // paddd xmm0, xmm1
+    let m = decode(&[0x66, 0x0f, 0xfe, 0xc1], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "paddd");
+    assert_eq!(operands(&m), vec!["XMM0:128".to_string(), "XMM1:128".to_string()]);
+    assert_eq!(count(&m, |op| if let &Operation::Add(..) = op { true } else { false }), 4);
+    assert!(writes(&m, "XMM0"));
+    assert!(writes(&m, "YMM0"));
+}
+
+#[test]
+fn vex_encoding() {
+    // vpaddd xmm0, xmm1, xmm2
+    let m = decode(&[0xc5, 0xf1, 0xfe, 0xc2], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "vpaddd");
+    assert_eq!(operands(&m).len(), 3);
+    assert_eq!(count(&m, |op| if let &Operation::Add(..) = op { true } else { false }), 4);
+
+    // vpaddd ymm0, ymm1, ymm2
+    let m = decode(&[0xc5, 0xf5, 0xfe, 0xc2], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "vpaddd");
+    assert_eq!(operands(&m)[0], "YMM0:256");
+    assert_eq!(count(&m, |op| if let &Operation::Add(..) = op { true } else { false }), 8);
+    assert!(writes(&m, "XMM0"));
+
+    // vpxor xmm9, xmm9, xmm9
+    let m = decode(&[0xc4, 0x41, 0x31, 0xef, 0xc9], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "vpxor");
+    assert_eq!(operands(&m), vec!["XMM9:128".to_string(); 3]);
+}
+
+#[test]
+fn vex_in_protected_mode() {
+    // vpaddd xmm0, xmm1, xmm2
+    let m = decode(&[0xc5, 0xf1, 0xfe, 0xc2], amd64::Mode::Protected);
+    assert_eq!(m.opcode, "vpaddd");
+
+    // lds eax, [esi]
+    let m = decode(&[0xc5, 0x06], amd64::Mode::Protected);
+    assert_eq!(m.opcode, "lds");
+}
+
+#[test]
+fn prefixed_vex() {
+    // vpaddd xmm0, xmm1, fs:[eax]
+    let m = decode(&[0x64, 0x67, 0xc5, 0xf1, 0xfe, 0x00], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "vpaddd");
+    assert_eq!(m.area.end, 6);
+
+    // vpxor xmm9, xmm9, xmm9
+    let m = decode(&[0x2e, 0xc4, 0x41, 0x31, 0xef, 0xc9], amd64::Mode::Long);
+    assert_eq!(m.opcode, "vpxor");
+
+    for &pfx in &[0x66, 0xf2, 0xf3, 0xf0] {
+        let reg = Region::wrap("ram".to_string(), vec![pfx, 0xc5, 0xf1, 0xfe, 0xc2]);
+        assert!(amd64::Amd64::decode(&reg, 0, &amd64::Mode::Long).is_err());
+    }
+}
+
+#[test]
+fn scalar_moves() {
+    // movss xmm0, [rax]
+    let m = decode(&[0xf3, 0x0f, 0x10, 0x00], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "movss");
+    assert_eq!(count(&m, |op| if let &Operation::Load(_, _, 32, _) = op { true } else { false }), 1);
+    assert_eq!(count(&m, |op| if let &Operation::ZeroExtend(128, _) = op { true } else { false }), 1);
+
+    // movss xmm0, xmm1
+    let m = decode(&[0xf3, 0x0f, 0x10, 0xc1], amd64::Mode::Long);
+
+    assert_eq!(count(&m, |op| if let &Operation::Select(0, _, _) = op { true } else { false }), 2);
+    assert!(writes(&m, "XMM0"));
+
+    // movsd [rax], xmm0
+    let m = decode(&[0xf2, 0x0f, 0x11, 0x00], amd64::Mode::Long);
+
+    assert_eq!(count(&m, |op| if let &Operation::Store(_, _, 64, _, _) = op { true } else { false }), 1);
+}
+
+#[test]
+fn packed_compare() {
+    // pcmpeqb xmm0, [rax]
+    let m = decode(&[0x66, 0x0f, 0x74, 0x00], amd64::Mode::Long);
+
+    assert_eq!(count(&m, |op| if let &Operation::Equal(..) = op { true } else { false }), 16);
+    assert_eq!(count(&m, |op| if let &Operation::SignExtend(8, _) = op { true } else { false }), 16);
+}
+
+#[test]
+fn float_lanes_are_undefined() {
+    // addps xmm0, xmm1
+    let m = decode(&[0x0f, 0x58, 0xc1], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "addps");
+    assert_eq!(count(&m, |op| if let &Operation::Move(Rvalue::Undefined) = op { true } else { false }), 4);
+
+    // addss xmm0, xmm1
+    let m = decode(&[0xf3, 0x0f, 0x58, 0xc1], amd64::Mode::Long);
+
+    assert_eq!(m.opcode, "addss");
+    assert_eq!(count(&m, |op| if let &Operation::Move(Rvalue::Undefined) = op { true } else { false }), 1);
+}
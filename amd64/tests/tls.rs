@@ -0,0 +1,108 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_amd64;
+
+use panopticon_amd64 as amd64;
+use panopticon_amd64::{Segment, TlsSlot};
+use panopticon_core::{Architecture, Function, Operation, Region, Rvalue};
+
+#[test]
+fn stack_guard_and_tls_variables() {
+    let reg = Region::wrap(
+        "ram".to_string(),
+        vec![
+            0x64, 0x48, 0x8b, 0x04, 0x25, 0x28, 0x00, 0x00, 0x00, // mov rax, fs:[0x28]
+            0x64, 0x48, 0x33, 0x04, 0x25, 0x28, 0x00, 0x00, 0x00, // xor rax, fs:[0x28]
+            0x64, 0x48, 0x8b, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00, // mov rax, fs:[0]
+            0x64, 0x48, 0x89, 0x04, 0x25, 0xf8, 0xff, 0xff, 0xff, // mov fs:[-8], rax
+            0x64, 0x48, 0x8b, 0x00, // mov rax, fs:[rax]
+            0xc3, // ret
+        ],
+    );
+    let func = Function::new::<amd64::Amd64>(0, &reg, None, amd64::Mode::Long).unwrap();
+    let mut refs = amd64::tls_references(&func, amd64::Mode::Long);
+
+    refs.sort_by_key(|r| r.address);
+    assert_eq!(refs.len(), 4);
+    assert!(refs.iter().all(|r| r.segment == Segment::Fs));
+    assert_eq!(
+        refs.iter().map(|r| (r.address, r.slot, r.write)).collect::<Vec<_>>(),
+        vec![
+            (0, TlsSlot::StackGuard, false),
+            (9, TlsSlot::StackGuard, false),
+            (18, TlsSlot::ThreadPointer, false),
+            (27, TlsSlot::Variable(8), true),
+        ]
+    );
+    assert_eq!(refs[0].slot.name(), "__stack_chk_guard");
+    assert_eq!(refs[3].slot.name(), "tls_8");
+}
+
+#[test]
+fn i386_gs_canary() {
+    let reg = Region::wrap(
+        "ram".to_string(),
+        vec![
+            0x65, 0xa1, 0x14, 0x00, 0x00, 0x00, // mov eax, gs:[0x14]
+            0xc3, // ret
+        ],
+    );
+    let func = Function::new::<amd64::Amd64>(0, &reg, None, amd64::Mode::Protected).unwrap();
+    let refs = amd64::tls_references(&func, amd64::Mode::Protected);
+
+    assert_eq!(refs.len(), 1);
+    assert_eq!(refs[0].segment, Segment::Gs);
+    assert_eq!(refs[0].offset, 0x14);
+    assert_eq!(refs[0].slot, TlsSlot::StackGuard);
+}
+
+#[test]
+fn segment_base_variables() {
+    let reg = Region::wrap(
+        "ram".to_string(),
+        vec![
+            0x8b, 0x40, 0x08, // mov eax, [rax+8]
+            0x65, 0x8b, 0x40, 0x08, // mov eax, gs:[rax+8]
+            0xf3, 0x48, 0x0f, 0xae, 0xc0, // rdfsbase rax
+        ],
+    );
+    let reads = |addr: u64, base: &str| {
+        let m = amd64::Amd64::decode(&reg, addr, &amd64::Mode::Long).unwrap();
+
+        m.mnemonics[0].instructions.iter().any(
+            |s| {
+                s.op.operands().iter().any(
+                    |rv| match **rv {
+                        Rvalue::Variable { ref name, .. } => name == base,
+                        _ => false,
+                    }
+                )
+            }
+        )
+    };
+
+    assert!(!reads(0, "GS_BASE"));
+    assert!(reads(3, "GS_BASE"));
+    assert!(reads(7, "FS_BASE"));
+
+    let m = amd64::Amd64::decode(&reg, 3, &amd64::Mode::Long).unwrap();
+    assert!(m.mnemonics[0].instructions.iter().any(|s| if let Operation::Load(..) = s.op { true } else { false }));
+}
@@ -0,0 +1,126 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_amd64;
+
+use panopticon_amd64 as amd64;
+use panopticon_core::{Architecture, Lvalue, Mnemonic, Operation, Region, Rvalue};
+
+fn decode(code: &[u8]) -> Mnemonic {
+    let reg = Region::wrap("ram".to_string(), code.to_vec());
+    let mut m = amd64::Amd64::decode(&reg, 0, &amd64::Mode::Protected).unwrap();
+
+    assert_eq!(m.mnemonics.len(), 1);
+    m.mnemonics.remove(0)
+}
+
+fn rvalue(name: &'static str, size: usize) -> Rvalue {
+    Rvalue::Variable { name: name.into(), subscript: None, offset: 0, size: size }
+}
+
+// operations of all statements writing to `reg`
+fn writes<'a>(m: &'a Mnemonic, reg: &str) -> Vec<&'a Operation<Rvalue>> {
+    m.instructions
+        .iter()
+        .filter(
+            |s| match s.assignee {
+                Lvalue::Variable { ref name, .. } => name == reg,
+                _ => false,
+            }
+        )
+        .map(|s| &s.op)
+        .collect()
+}
+
+fn reads(op: &Operation<Rvalue>, reg: &str) -> bool {
+    op.operands().iter().any(
+        |x| match **x {
+            Rvalue::Variable { ref name, .. } => name == reg,
+            _ => false,
+        }
+    )
+}
+
+#[test]
+fn push_rotates_stack() {
+    // fld st(2)
+    let m = decode(&[0xd9, 0xc2]);
+
+    assert_eq!(m.opcode, "fld");
+    assert!(reads(&m.instructions[0].op, "ST2"));
+    for i in 1..8 {
+        let w = writes(&m, &format!("ST{}", i));
+        assert_eq!(w.len(), 1);
+        assert!(reads(w[0], &format!("ST{}", i - 1)));
+    }
+    assert_eq!(writes(&m, "ST0").len(), 1);
+
+    // TOP is decremented
+    let fsw = writes(&m, "FSW");
+    assert_eq!(fsw.len(), 1);
+    assert_eq!(fsw[0], &Operation::Select(11, rvalue("FSW", 16), rvalue("top", 3)));
+}
+
+#[test]
+fn pop_rotates_stack() {
+    // faddp st(1), st0
+    let m = decode(&[0xde, 0xc1]);
+
+    assert_eq!(m.opcode, "faddp");
+    assert_eq!(writes(&m, "ST1").len(), 2);
+    assert_eq!(writes(&m, "ST7"), vec![&Operation::Move(Rvalue::Undefined)]);
+    assert_eq!(writes(&m, "FSW").len(), 1);
+}
+
+#[test]
+fn exact_moves() {
+    // fld tbyte [eax]
+    let m = decode(&[0xdb, 0x28]);
+    assert!(m.instructions.iter().any(|s| if let Operation::Load(_, _, 80, _) = s.op { true } else { false }));
+    assert!(reads(&writes(&m, "fpu")[0], "UNK PTR [EAX]"));
+
+    // fld dword [eax]
+    let m = decode(&[0xd9, 0x00]);
+    assert_eq!(writes(&m, "fpu"), vec![&Operation::Move(Rvalue::Undefined)]);
+
+    // fxch st(3)
+    let m = decode(&[0xd9, 0xcb]);
+    assert_eq!(writes(&m, "ST0"), vec![&Operation::Move(rvalue("ST3", 80))]);
+    assert_eq!(writes(&m, "ST3"), vec![&Operation::Move(rvalue("fpu", 80))]);
+
+    // fchs
+    let m = decode(&[0xd9, 0xe0]);
+    assert_eq!(writes(&m, "ST0"), vec![&Operation::Select(79, rvalue("ST0", 80), rvalue("sign", 1))]);
+}
+
+#[test]
+fn status_and_control_word() {
+    // fnstsw ax
+    let m = decode(&[0xdf, 0xe0]);
+    assert_eq!(writes(&m, "AX"), vec![&Operation::Move(Rvalue::Variable { name: "val".into(), subscript: None, offset: 0, size: 16 })]);
+
+    // fnstcw [eax]
+    let m = decode(&[0xd9, 0x38]);
+    assert!(m.instructions.iter().any(|s| if let Operation::Store(_, _, 16, _, _) = s.op { true } else { false }));
+
+    // fcom st(1)
+    let m = decode(&[0xd8, 0xd1]);
+    assert_eq!(writes(&m, "FSW").len(), 3);
+}
@@ -0,0 +1,376 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Analysis of a file from start to end.
+//!
+//! [`analyze`] does what a front-end needs to get from a file name to a finished `Project`: it
+//! loads the file, disassembles all functions reachable from the entry points and symbols the
+//! loader found and runs the analysis passes selected in [`Options`].
+//!
+//! Function discovery runs to a fixpoint. After all calls have been followed, constants pointing
+//! into code that no function covers yet are disassembled as new functions, until no new ones
+//! turn up. [`analyze_with_progress`] reports each step to a callback. With a `Strategy` other
+//! than the default, the call targets found by sweeping over the executable sections are added
+//! to the entry points before the first round. If `Options::speculative` is set, the function
+//! starts `gaps::propose` is confident enough about are disassembled once nothing else is left.
+//!
+//! Loaders, architectures and passes of the plugins in `Options::plugins` are used in addition to
+//! the built-in ones, see `plugin`.
+//!
+//! Cancelling `Options::cancel` stops the analysis after the functions being disassembled at the
+//! moment. The project returned contains everything done until then, the remaining passes are
+//! skipped.
+//!
+//! [`analyze`]: fn.analyze.html
+//! [`analyze_with_progress`]: fn.analyze_with_progress.html
+//! [`Options`]: struct.Options.html
+
+use pipeline;
+use plugin::Registry;
+use panopticon_amd64 as amd64;
+use panopticon_arm as arm;
+use panopticon_avr as avr;
+use panopticon_core::{Architecture, CallTarget, CancellationToken, FunctionCache, Limits, Machine, PeripheralMap, Program, Project, RawMapping, Recovery, Result, Rvalue,
+                      SpillCache, Strategy, annotation, gaps, hardening, loader, mmio, pointer, signature, strings, sweep, xref};
+use panopticon_graph_algos::MutableGraphTrait;
+use panopticon_m68k as m68k;
+use panopticon_mcs51 as mcs51;
+use panopticon_mips as mips;
+use panopticon_msp430 as msp430;
+use panopticon_riscv as riscv;
+use panopticon_superh as superh;
+use panopticon_wasm as wasm;
+use std::fmt::Debug;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Analysis run after all functions have been disassembled.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Pass {
+    /// String literals, see `strings::extract`
+    Strings,
+    /// Cross references, see `xref::collect`
+    Xrefs,
+    /// Stack canaries and CET, see `hardening::analyze`
+    Hardening,
+    /// Function prototypes from debug information, see `signature::apply`
+    Signatures,
+    /// Comments showing referenced strings, see `annotation::annotate`
+    Annotations,
+    /// Comments naming the peripheral registers accessed, see `mmio::annotate`. Does nothing
+    /// unless `Options::peripherals` is set.
+    Peripherals,
+}
+
+/// What `analyze` does.
+#[derive(Clone,Debug)]
+pub struct Options {
+    /// Architecture slice of a fat Mach-O file to load
+    pub slice: Option<String>,
+    /// Load the file as a headerless image instead
+    pub raw: Option<RawMapping>,
+    /// Load a position independent file at this address
+    pub base: Option<u64>,
+    /// Disassemble constants pointing into code as functions
+    pub code_pointers: bool,
+    /// Passes to run, in this order
+    pub passes: Vec<Pass>,
+    /// Stops the analysis early
+    pub cancel: CancellationToken,
+    /// Bytes of IL kept in memory during function discovery and between passes, see
+    /// `SpillCache`. Spilled functions read their IL back from disk when it's needed.
+    pub memory_budget: Option<usize>,
+    /// Keep functions without IL until it's read, see `Function::decode_only`. Saves memory when
+    /// the functions are only listed, SSA conversion and calling convention inference are skipped.
+    pub decode_only: bool,
+    /// File the disassembled functions are cached in between runs, see `FunctionCache`
+    pub cache: Option<PathBuf>,
+    /// Memory mapped registers of the device, see `mmio`
+    pub peripherals: Option<PeripheralMap>,
+    /// Where to continue if an instruction can't be decoded
+    pub recovery: Recovery,
+    /// Upper bounds on the work spent on a single function
+    pub limits: Limits,
+    /// How code is found besides following the control flow, see `sweep`
+    pub strategy: Strategy,
+    /// Disassemble function starts proposed by `gaps::propose` with at least this confidence
+    pub speculative: Option<f64>,
+    /// Additional loaders, architectures and passes
+    pub plugins: Arc<Registry>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            slice: None,
+            raw: None,
+            base: None,
+            code_pointers: true,
+            passes: vec![Pass::Strings, Pass::Xrefs, Pass::Hardening, Pass::Signatures, Pass::Annotations, Pass::Peripherals],
+            cancel: CancellationToken::new(),
+            memory_budget: None,
+            decode_only: false,
+            cache: None,
+            peripherals: None,
+            recovery: Recovery::Stop,
+            limits: Limits::default(),
+            strategy: Strategy::Recursive,
+            speculative: None,
+            plugins: Arc::new(Registry::new()),
+        }
+    }
+}
+
+impl Options {
+    /// Loads the file as it is, follows code pointers and runs all passes.
+    pub fn new() -> Options {
+        Options::default()
+    }
+}
+
+/// Step of `analyze_with_progress` just finished.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Progress {
+    /// The file was loaded for the given CPU. Not sent for files disassembled by an architecture
+    /// plugin.
+    Loaded(Machine),
+    /// A round of function discovery ended with `functions` functions disassembled in total
+    Discovered {
+        /// Rounds done, starting at 1
+        round: usize,
+        /// Functions in the project
+        functions: usize,
+    },
+    /// A pass ran
+    Pass(Pass),
+    /// Analysis stopped early because `Options::cancel` was cancelled, sent instead of `Finished`
+    Cancelled,
+    /// Analysis is done
+    Finished,
+}
+
+/// Loads and analyzes the file at `path`.
+pub fn analyze(path: &Path, options: &Options) -> Result<Project> {
+    analyze_with_progress(path, options, &|_| ())
+}
+
+/// Loads and analyzes the file at `path`, calling `progress` after each step.
+pub fn analyze_with_progress(path: &Path, options: &Options, progress: &Fn(Progress)) -> Result<Project> {
+    let plugin = match (&options.raw, &options.slice, options.base) {
+        (&None, &None, None) => options.plugins.loader_for(path)?,
+        _ => None,
+    };
+    let mut proj = match plugin {
+        Some(plugin) => {
+            let (mut proj, arch) = plugin.load(path)?;
+
+            debug!("{} loaded by {}", path.display(), plugin.name());
+            match options.plugins.architecture(&arch) {
+                Some(arch) => arch.disassemble(&mut proj, options)?,
+                None => {
+                    let machine = arch.parse()?;
+
+                    progress(Progress::Loaded(machine));
+                    disassemble(&mut proj, machine, options, progress)?;
+                }
+            }
+            proj
+        }
+        None => {
+            let (mut proj, machine) = match (&options.raw, &options.slice, options.base) {
+                (&Some(ref mapping), _, _) => loader::load_raw(path, mapping)?,
+                (&None, &Some(ref arch), _) => {
+                    match loader::slices(path)?.into_iter().find(|s| s.architecture == *arch) {
+                        Some(slice) => loader::load_slice(path, &slice)?,
+                        None => return Err(format!("{} has no {} slice", path.display(), arch).into()),
+                    }
+                }
+                (&None, &None, Some(base)) => loader::load_at(path, base)?,
+                (&None, &None, None) => loader::load(path)?,
+            };
+
+            progress(Progress::Loaded(machine));
+            disassemble(&mut proj, machine, options, progress)?;
+            proj
+        }
+    };
+
+    // functions spilled during discovery keep their IL in the directory of that cache
+    let mut spill = match options.memory_budget {
+        Some(budget) => Some(SpillCache::new(budget)?),
+        None => None,
+    };
+
+    for &pass in options.passes.iter() {
+        if options.cancel.is_cancelled() {
+            break;
+        }
+
+        run_pass(&mut proj, pass, options);
+        if let Some(ref mut spill) = spill {
+            let n = spill.enforce(&mut proj)?;
+            debug!("{:?}: spilled {} functions", pass, n);
+        }
+        progress(Progress::Pass(pass));
+    }
+
+    for pass in options.plugins.passes() {
+        if options.cancel.is_cancelled() {
+            break;
+        }
+
+        pass.run(&mut proj).map_err(|e| format!("{} pass failed: {}", pass.name(), e))?;
+    }
+
+    progress(if options.cancel.is_cancelled() { Progress::Cancelled } else { Progress::Finished });
+    Ok(proj)
+}
+
+/// Disassembles `proj` with the built-in architecture for `machine`.
+fn disassemble(proj: &mut Project, machine: Machine, options: &Options, progress: &Fn(Progress)) -> Result<()> {
+    match machine {
+        Machine::Avr => discover::<avr::Avr>(proj, Some(machine), avr::Mcu::atmega103(), options, progress)?,
+        Machine::Ia32 => discover::<amd64::Amd64>(proj, Some(machine), amd64::Mode::Protected, options, progress)?,
+        Machine::Amd64 => discover::<amd64::Amd64>(proj, Some(machine), amd64::Mode::Long, options, progress)?,
+        Machine::Arm => {
+            let cpu = arm::Cpu::new(arm::Mode::Arm).with_mapping_symbols(&proj.mapping_symbols);
+            discover::<arm::Arm>(proj, Some(machine), cpu, options, progress)?
+        }
+        Machine::Mips(e) => discover::<mips::Mips>(proj, Some(machine), mips::Cpu::new(mips::Mode::Mips32, e), options, progress)?,
+        Machine::Mips64(e) => discover::<mips::Mips>(proj, Some(machine), mips::Cpu::new(mips::Mode::Mips64, e), options, progress)?,
+        Machine::RiscV32(flags) => {
+            let cpu = riscv::Cpu::new(riscv::Xlen::Rv32, riscv::Extensions::from_elf_flags(flags));
+            discover::<riscv::Riscv>(proj, Some(machine), cpu, options, progress)?
+        }
+        Machine::RiscV64(flags) => {
+            let cpu = riscv::Cpu::new(riscv::Xlen::Rv64, riscv::Extensions::from_elf_flags(flags));
+            discover::<riscv::Riscv>(proj, Some(machine), cpu, options, progress)?
+        }
+        Machine::M68k => discover::<m68k::M68k>(proj, Some(machine), m68k::Model::M68000, options, progress)?,
+        Machine::Mcs51 => discover::<mcs51::Mcs51>(proj, Some(machine), mcs51::Cpu::new(mcs51::Model::I8051), options, progress)?,
+        Machine::Msp430(flags) => discover::<msp430::Msp430>(proj, Some(machine), msp430::Model::from_elf_flags(flags), options, progress)?,
+        Machine::SuperH(e, flags) => {
+            let cpu = superh::Cpu::new(superh::Model::from_elf_flags(flags), e);
+            discover::<superh::SuperH>(proj, Some(machine), cpu, options, progress)?
+        }
+        Machine::Wasm => {
+            let cpu = wasm::Cpu::from_region(proj.region())?;
+            discover::<wasm::Wasm>(proj, Some(machine), cpu, options, progress)?
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `pass` on the already disassembled `proj`.
+pub fn run_pass(proj: &mut Project, pass: Pass, options: &Options) {
+    match pass {
+        Pass::Strings => strings::extract(proj),
+        Pass::Xrefs => xref::collect(proj),
+        Pass::Hardening => hardening::analyze(proj),
+        Pass::Signatures => {
+            signature::apply(proj);
+        }
+        Pass::Annotations => annotation::annotate(proj),
+        Pass::Peripherals => {
+            if let Some(ref map) = options.peripherals {
+                mmio::annotate(proj, map);
+            }
+        }
+    }
+}
+
+/// Disassembles all programs of `proj` until no new functions are found. Speculative function
+/// starts are only disassembled if `machine` is known.
+pub fn discover<A: Architecture + Debug + Sync + 'static>(proj: &mut Project, machine: Option<Machine>, config: A::Configuration, options: &Options, progress: &Fn(Progress)) -> Result<()>
+where
+    A::Configuration: Debug + Sync + 'static,
+{
+    let region = proj.region().clone();
+    let mut round = 0;
+    let mut spill = match options.memory_budget {
+        Some(budget) => Some(SpillCache::new(budget)?),
+        None => None,
+    };
+    // the configuration includes the CPU model and mode, the recovery policy and limits change
+    // what gets disassembled
+    let lifter = format!("panopticon {} {:?} {:?} {:?} {}", env!("CARGO_PKG_VERSION"), config, options.recovery, options.limits, options.decode_only);
+    let mut functions = match options.cache {
+        Some(ref path) if path.exists() => Some(FunctionCache::open(path, &lifter)?),
+        Some(_) => Some(FunctionCache::new(&lifter)),
+        None => None,
+    };
+
+    if options.strategy != Strategy::Recursive && !proj.code.is_empty() {
+        let areas = sweep::code_areas(proj);
+        let decoded = areas.iter().flat_map(|a| sweep::sweep::<A>(&region, &config, options.strategy, a)).collect::<Vec<_>>();
+        let entries = sweep::entry_points(proj, &decoded, &areas);
+
+        debug!("{:?} sweep: {} instructions, {} new entry points", options.strategy, decoded.len(), entries.len());
+        for addr in entries {
+            proj.code[0].call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), None, Uuid::new_v4()));
+        }
+    }
+
+    loop {
+        for prog in proj.code.iter_mut() {
+            let p = mem::replace(prog, Program::new(""));
+            *prog = if options.decode_only {
+                pipeline::analyze_decode_only::<A>(p, region.clone(), config.clone(), options.recovery, options.limits, &options.cancel, functions.as_mut())?
+            } else {
+                pipeline::analyze_with_limits::<A>(p, region.clone(), config.clone(), options.recovery, options.limits, &options.cancel, functions.as_mut())?
+            };
+        }
+
+        round += 1;
+        progress(Progress::Discovered { round: round, functions: proj.code.iter().map(|p| p.functions().count()).sum() });
+
+        if let Some(ref mut spill) = spill {
+            let n = spill.enforce(proj)?;
+            debug!("round {}: spilled {} functions", round, n);
+        }
+
+        let mut new = if !options.code_pointers || proj.code.is_empty() || options.cancel.is_cancelled() {
+            vec![]
+        } else {
+            pointer::classify_operands(proj);
+            pointer::code_pointers(proj)
+        };
+
+        if let (Some(min), Some(machine)) = (options.speculative, machine) {
+            if new.is_empty() && !proj.code.is_empty() && !options.cancel.is_cancelled() {
+                new = gaps::propose(proj, machine).into_iter().filter(|p| p.confidence >= min).map(|p| p.address).collect();
+                debug!("round {}: {} speculative function starts", round, new.len());
+            }
+        }
+
+        if new.is_empty() {
+            if let (Some(ref functions), Some(ref path)) = (functions, options.cache.as_ref()) {
+                functions.save(path)?;
+            }
+            return Ok(());
+        }
+
+        debug!("round {}: {} code pointers", round, new.len());
+        for addr in new {
+            proj.code[0].call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), None, Uuid::new_v4()));
+        }
+    }
+}
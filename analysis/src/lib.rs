@@ -29,7 +29,30 @@ extern crate chashmap;
 extern crate rayon;
 extern crate uuid;
 extern crate parking_lot;
+extern crate libloading;
+extern crate panopticon_amd64;
+extern crate panopticon_arm;
+extern crate panopticon_avr;
+extern crate panopticon_m68k;
+extern crate panopticon_mcs51;
+extern crate panopticon_mips;
+extern crate panopticon_msp430;
+extern crate panopticon_riscv;
+extern crate panopticon_superh;
+extern crate panopticon_wasm;
 
 mod pipeline;
 pub use pipeline::pipeline;
-pub use pipeline::analyze;
+pub use pipeline::{analyze, analyze_cached, analyze_decode_only, analyze_with_limits, analyze_with_recovery, analyze_with_token};
+
+mod reanalysis;
+pub use reanalysis::reanalyze;
+
+pub mod driver;
+pub use driver::{Options, Pass, Progress};
+
+pub mod plugin;
+pub use plugin::Registry;
+
+pub mod server;
+pub use server::Server;
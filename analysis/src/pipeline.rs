@@ -18,8 +18,10 @@
 
 use futures::{Future, Sink, Stream, stream};
 use futures::sync::mpsc;
-use panopticon_core::{Architecture, CallTarget, Error, Function, Program, Result, Region, Rvalue};
-use panopticon_data_flow::ssa_convertion;
+use panopticon_core::{Architecture, CallTarget, CancellationToken, ControlFlowTarget, Error, Function, FunctionCache, Limits, NameSource, Program, Recovery, Result, Region, Rvalue,
+                      calling_convention};
+use panopticon_graph_algos::{GraphTrait, VertexListGraphTrait};
+use panopticon_data_flow::{remove_dead_flags, ssa_convertion};
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::thread;
@@ -34,13 +36,110 @@ pub fn analyze<A: Architecture + Debug + Sync + 'static>(
     config: A::Configuration,
 ) -> Result<Program>
 where
-    A::Configuration: Debug + Sync,
+    A::Configuration: Debug + Sync + 'static,
+{
+    analyze_with_token::<A>(program, region, config, &CancellationToken::new())
+}
+
+/// Like `analyze`, but stops disassembling new functions once `cancel` is cancelled. Functions
+/// not finished by then stay `CallTarget::Todo`s.
+pub fn analyze_with_token<A: Architecture + Debug + Sync + 'static>(
+    program: Program,
+    region: Region,
+    config: A::Configuration,
+    cancel: &CancellationToken,
+) -> Result<Program>
+where
+    A::Configuration: Debug + Sync + 'static,
+{
+    run::<A>(program, region, config, Recovery::Stop, Limits::default(), cancel, None, false)
+}
+
+/// Like `analyze_with_token`, but continues disassembling behind undecodable bytes as `recovery`
+/// says.
+pub fn analyze_with_recovery<A: Architecture + Debug + Sync + 'static>(
+    program: Program,
+    region: Region,
+    config: A::Configuration,
+    recovery: Recovery,
+    cancel: &CancellationToken,
+) -> Result<Program>
+where
+    A::Configuration: Debug + Sync + 'static,
+{
+    run::<A>(program, region, config, recovery, Limits::default(), cancel, None, false)
+}
+
+/// Like `analyze_with_recovery`, but takes functions whose bytes are in `cache` from there
+/// instead of disassembling them again. All functions disassembled are added to `cache`.
+pub fn analyze_cached<A: Architecture + Debug + Sync + 'static>(
+    program: Program,
+    region: Region,
+    config: A::Configuration,
+    recovery: Recovery,
+    cancel: &CancellationToken,
+    cache: &mut FunctionCache,
+) -> Result<Program>
+where
+    A::Configuration: Debug + Sync + 'static,
+{
+    run::<A>(program, region, config, recovery, Limits::default(), cancel, Some(cache), false)
+}
+
+/// Like `analyze_cached`, but the cache is optional and disassembly of a single function stops
+/// early once one of `limits` is hit, see `Function::truncated`.
+pub fn analyze_with_limits<A: Architecture + Debug + Sync + 'static>(
+    program: Program,
+    region: Region,
+    config: A::Configuration,
+    recovery: Recovery,
+    limits: Limits,
+    cancel: &CancellationToken,
+    cache: Option<&mut FunctionCache>,
+) -> Result<Program>
+where
+    A::Configuration: Debug + Sync + 'static,
+{
+    run::<A>(program, region, config, recovery, limits, cancel, cache, false)
+}
+
+/// Like `analyze_with_limits`, but functions are created with `Function::decode_only_with_limits`
+/// and their IL is lifted once it's read. SSA conversion and calling convention inference are
+/// skipped, the functions are meant to be listed.
+pub fn analyze_decode_only<A: Architecture + Debug + Sync + 'static>(
+    program: Program,
+    region: Region,
+    config: A::Configuration,
+    recovery: Recovery,
+    limits: Limits,
+    cancel: &CancellationToken,
+    cache: Option<&mut FunctionCache>,
+) -> Result<Program>
+where
+    A::Configuration: Debug + Sync + 'static,
+{
+    run::<A>(program, region, config, recovery, limits, cancel, cache, true)
+}
+
+fn run<A: Architecture + Debug + Sync + 'static>(
+    program: Program,
+    region: Region,
+    config: A::Configuration,
+    recovery: Recovery,
+    limits: Limits,
+    cancel: &CancellationToken,
+    mut cache: Option<&mut FunctionCache>,
+    decode_only: bool,
+) -> Result<Program>
+where
+    A::Configuration: Debug + Sync + 'static,
 {
     use rayon::prelude::*;
     use chashmap::CHashMap;
 
     struct Init {
         name: Option<String>,
+        source: NameSource,
         entry: u64,
         uuid: Uuid,
     }
@@ -48,6 +147,15 @@ where
     let attempts = CHashMap::<u64, result::Result<(), Error>>::new();
     let targets = CHashMap::<u64, bool>::new();
     let failures = RwLock::new(0);
+
+    // functions disassembled by an earlier run are kept
+    for ct in program.call_graph.vertex_labels() {
+        if let &CallTarget::Concrete(ref f) = ct {
+            if let Some(&ControlFlowTarget::Resolved(ref bb)) = f.cfg().vertex_label(f.entry_point_ref()) {
+                attempts.insert(bb.area.start, Ok(()));
+            }
+        }
+    }
     info!("initializing first wave");
     let functions =
         program
@@ -56,7 +164,9 @@ where
         .filter_map(
             |ct| match ct {
                 &CallTarget::Todo(Rvalue::Constant { value: entry, .. }, ref name, ref uuid) => {
-                    Some(Init { entry, name: name.clone(), uuid: *uuid })
+                    let source = if name.is_some() { program.name_source(uuid) } else { NameSource::Generated };
+
+                    Some(Init { entry, name: name.clone(), source, uuid: *uuid })
                 }
                 _ => None,
             }
@@ -64,18 +174,46 @@ where
 
     // we now lock the program
     let program = Mutex::new(program);
+    let lifted = Mutex::new(Vec::<Uuid>::new());
+    let lift = {
+        let cached = cache.as_ref().map(|c| &**c);
+        let region = &region;
+        let config = &config;
+        let lifted = &lifted;
+
+        move |entry: u64, uuid: &Uuid, name: Option<String>| -> Result<Function> {
+            if let Some(f) = cached.and_then(|c| c.get(entry, uuid, name.clone(), region)) {
+                return Ok(f);
+            }
+
+            if decode_only {
+                let f = Function::decode_only_with_limits::<A>(entry, uuid, region, name, config.clone(), recovery, &limits, cancel)?;
+
+                lifted.lock().push(uuid.clone());
+                return Ok(f);
+            }
+
+            let mut f = Function::with_limits::<A>(entry, uuid, region, name, config.clone(), recovery, &limits, cancel)?;
+
+            remove_dead_flags(&mut f, A::flags());
+            let _ = ssa_convertion(&mut f);
+            let cc = calling_convention::infer(&f);
+            f.set_calling_convention(cc);
+            lifted.lock().push(uuid.clone());
+            Ok(f)
+        }
+    };
 
     info!("begin first wave {}", functions.len());
-    functions.into_par_iter().for_each(| Init { entry, name, uuid }| {
+    functions.into_par_iter().for_each(| Init { entry, name, source, uuid }| {
         let name = &name;
         attempts.upsert(entry,
                         || {
-                            match Function::with_uuid::<A>(entry, &uuid, &region, name.clone(), config.clone()) {
-                                Ok(mut f) => {
+                            match lift(entry, &uuid, name.clone()) {
+                                Ok(f) => {
                                     for address in f.collect_call_addresses() {
                                         targets.upsert(address, || { true }, |_| ());
                                     }
-                                    let _ = ssa_convertion(&mut f);
                                     {
                                         let mut program = program.lock();
                                         let _ = program.insert(f);
@@ -92,7 +230,7 @@ where
                                     let mut program = program.lock();
                                     let f2 = program.find_function_mut(|f| f.start() == entry).unwrap();
                                     info!("New alias ({}) found at {:#x} with canonical name {:?}", &name, entry, &f2.name);
-                                    f2.add_alias(name);
+                                    f2.propose_name(name, source);
                                 },
                                 _ => ()
                             }
@@ -102,17 +240,16 @@ where
     info!("first wave done: success: {} failures: {} targets: {}", attempts.len(), *failures.read(), targets.len());
 
     let mut targets = targets.into_iter().map(|(x, _)| x).collect::<Vec<u64>>();
-    while !targets.is_empty() {
+    while !targets.is_empty() && !cancel.is_cancelled() {
         info!("targets - ({})", targets.len());
         let new_targets = CHashMap::<u64, bool>::new();
         targets.into_par_iter().for_each(| address | {
             attempts.upsert(address, || {
-                match Function::new::<A>(address, &region, None, config.clone()) {
-                    Ok(mut f) => {
+                match lift(address, &Uuid::new_v4(), None) {
+                    Ok(f) => {
                         for address in f.collect_call_addresses() {
                             new_targets.upsert(address, || { true }, |_| ());
                         }
-                        let _ = ssa_convertion(&mut f);
                         {
                             let mut program = program.lock();
                             let _ = program.insert(f);
@@ -129,7 +266,16 @@ where
 
     let mut program = program.into_inner();
     info!("Finished analysis: {} failures {}", attempts.len(), *failures.read());
+
+    if let Some(ref mut cache) = cache {
+        for uuid in lifted.into_inner() {
+            if let Some(f) = program.find_function_by_uuid(&uuid) {
+                cache.insert(f, &region);
+            }
+        }
+    }
     program.update_plt();
+    program.classify();
     Ok(program)
 }
 
@@ -158,7 +304,10 @@ where
                             Ok(mut f) => {
                                 let addresses = f.collect_call_addresses();
                                 targets.extend_from_slice(&addresses);
+                                remove_dead_flags(&mut f, A::flags());
                                 let _ = ssa_convertion(&mut f);
+                                let cc = calling_convention::infer(&f);
+                                f.set_calling_convention(cc);
                                 let tx = tx.clone();
                                 tx.send_all(stream::iter(vec![Ok(f)])).wait().unwrap().0;
                             },
@@ -181,7 +330,10 @@ where
                             Ok(mut f) => {
                                 let addresses = f.collect_call_addresses();
                                 new_targets.extend_from_slice(&addresses);
+                                remove_dead_flags(&mut f, A::flags());
                                 let _ = ssa_convertion(&mut f);
+                                let cc = calling_convention::infer(&f);
+                                f.set_calling_convention(cc);
                                 {
                                     let tx = tx.clone();
                                     tx.send_all(stream::iter(vec![Ok(f)])).wait().unwrap().0;
@@ -0,0 +1,273 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Loaders, architectures and passes contributed by other crates.
+//!
+//! A [`Registry`] holds the plugins known to a front-end. It's passed to the analysis as
+//! `Options::plugins`. When a file is analyzed, the first loader plugin whose `probe` accepts the
+//! file loads it instead of `loader::load`. The loader names the architecture to disassemble
+//! with; architecture plugins of that name take precedence over the built-in CPUs. The passes of
+//! the registry run in the order they were added, after the built-in passes in `Options::passes`.
+//!
+//! Crates linked into the front-end add plugins with [`Registry::add_loader`] and friends. Rust
+//! code implementing `Architecture` can be wrapped with [`Generic`]. Plugins can also be
+//! compiled as `cdylib` and loaded at runtime by [`Registry::load_library`], if they use
+//! [`export_plugin!`] to define their entry point:
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate panopticon_analysis;
+//!
+//! fn register(registry: &mut panopticon_analysis::plugin::Registry) {
+//!     registry.add_pass(Box::new(MyPass));
+//! }
+//!
+//! export_plugin!(register);
+//! ```
+//!
+//! Trait objects have no stable ABI, so libraries must be compiled with the same compiler and
+//! Panopticon version as the front-end loading them. [`API_VERSION`] is checked when loading a
+//! library to catch the most common mismatches.
+//!
+//! [`Registry`]: struct.Registry.html
+//! [`Registry::add_loader`]: struct.Registry.html#method.add_loader
+//! [`Registry::load_library`]: struct.Registry.html#method.load_library
+//! [`Generic`]: struct.Generic.html
+//! [`export_plugin!`]: ../macro.export_plugin.html
+//! [`API_VERSION`]: constant.API_VERSION.html
+
+use driver::{self, Options};
+use libloading::Library;
+use panopticon_core::{Architecture, Project, Result};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Version of the plugin interface. Changes whenever the plugin traits do.
+pub const API_VERSION: u32 = 1;
+
+/// Bytes from the start of a file passed to `LoaderPlugin::probe`.
+pub const PROBE_SIZE: usize = 4096;
+
+/// File format.
+pub trait LoaderPlugin: Send + Sync {
+    /// Unique name, e.g. "intel-hex"
+    fn name(&self) -> &str;
+
+    /// Returns true if `header`, up to `PROBE_SIZE` bytes from the beginning of the file, is in
+    /// a format this plugin understands.
+    fn probe(&self, header: &[u8]) -> bool;
+
+    /// Loads the file at `path`. Returns the project and the architecture to disassemble it
+    /// with: either the name of an architecture plugin or a CPU name `Machine` can parse.
+    fn load(&self, path: &Path) -> Result<(Project, String)>;
+}
+
+/// Instruction set.
+pub trait ArchitecturePlugin: Send + Sync {
+    /// Unique name, e.g. "z80"
+    fn name(&self) -> &str;
+
+    /// Disassembles all functions of `proj`, starting with the ones already in its call graphs.
+    fn disassemble(&self, proj: &mut Project, options: &Options) -> Result<()>;
+}
+
+/// Analysis run after all functions have been disassembled.
+pub trait PassPlugin: Send + Sync {
+    /// Unique name, e.g. "crypto-constants"
+    fn name(&self) -> &str;
+
+    /// Analyzes `proj`.
+    fn run(&self, proj: &mut Project) -> Result<()>;
+}
+
+/// Architecture plugin for an `Architecture` implementation and a CPU configuration.
+pub struct Generic<A: Architecture> {
+    name: String,
+    config: A::Configuration,
+}
+
+impl<A: Architecture> Generic<A> {
+    /// Returns a plugin called `name` disassembling with `config`.
+    pub fn new(name: &str, config: A::Configuration) -> Generic<A> {
+        Generic { name: name.to_string(), config: config }
+    }
+}
+
+impl<A: Architecture + fmt::Debug + Sync + Send + 'static> ArchitecturePlugin for Generic<A>
+where
+    A::Configuration: fmt::Debug + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn disassemble(&self, proj: &mut Project, options: &Options) -> Result<()> {
+        driver::discover::<A>(proj, None, self.config.clone(), options, &|_| ())
+    }
+}
+
+/// Plugins known to a front-end.
+#[derive(Default)]
+pub struct Registry {
+    loaders: Vec<Box<LoaderPlugin>>,
+    architectures: Vec<Box<ArchitecturePlugin>>,
+    passes: Vec<Box<PassPlugin>>,
+    // must outlive the plugins above
+    libraries: Vec<Library>,
+}
+
+impl Registry {
+    /// Returns a registry without plugins.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Adds a loader. Loaders are probed in the order they were added.
+    pub fn add_loader(&mut self, plugin: Box<LoaderPlugin>) {
+        self.loaders.push(plugin);
+    }
+
+    /// Adds an architecture, replacing the one with the same name.
+    pub fn add_architecture(&mut self, plugin: Box<ArchitecturePlugin>) {
+        self.architectures.retain(|a| a.name() != plugin.name());
+        self.architectures.push(plugin);
+    }
+
+    /// Adds a pass to run after the ones added before.
+    pub fn add_pass(&mut self, plugin: Box<PassPlugin>) {
+        self.passes.push(plugin);
+    }
+
+    /// All loaders, in the order they are probed.
+    pub fn loaders(&self) -> &[Box<LoaderPlugin>] {
+        &self.loaders
+    }
+
+    /// All architectures.
+    pub fn architectures(&self) -> &[Box<ArchitecturePlugin>] {
+        &self.architectures
+    }
+
+    /// All passes, in the order they run.
+    pub fn passes(&self) -> &[Box<PassPlugin>] {
+        &self.passes
+    }
+
+    /// Returns the architecture called `name`.
+    pub fn architecture(&self, name: &str) -> Option<&ArchitecturePlugin> {
+        self.architectures.iter().find(|a| a.name() == name).map(|a| &**a)
+    }
+
+    /// Returns the pass called `name`.
+    pub fn pass(&self, name: &str) -> Option<&PassPlugin> {
+        self.passes.iter().find(|p| p.name() == name).map(|p| &**p)
+    }
+
+    /// Returns the first loader accepting the file at `path`.
+    pub fn loader_for(&self, path: &Path) -> Result<Option<&LoaderPlugin>> {
+        if self.loaders.is_empty() {
+            return Ok(None);
+        }
+
+        let mut header = Vec::with_capacity(PROBE_SIZE);
+
+        File::open(path)?.take(PROBE_SIZE as u64).read_to_end(&mut header)?;
+        Ok(self.loaders.iter().find(|l| l.probe(&header)).map(|l| &**l))
+    }
+
+    /// Loads the dynamic library at `path` and adds the plugins it registers. The library must
+    /// define its entry point using `export_plugin!`.
+    pub fn load_library(&mut self, path: &Path) -> Result<()> {
+        let lib = Library::new(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        unsafe {
+            let version = lib.get::<extern "C" fn() -> u32>(b"panopticon_plugin_api_version\0")
+                .map_err(|_| format!("{} is not a Panopticon plugin", path.display()))?;
+
+            if version() != API_VERSION {
+                return Err(format!("{} was built for plugin API version {}, expected {}", path.display(), version(), API_VERSION).into());
+            }
+
+            let register = lib.get::<extern "C" fn(&mut Registry)>(b"panopticon_plugin_register\0")
+                .map_err(|_| format!("{} is not a Panopticon plugin", path.display()))?;
+
+            register(self);
+        }
+
+        info!("loaded plugin {}", path.display());
+        self.libraries.push(lib);
+        Ok(())
+    }
+
+    /// Loads all dynamic libraries in the directory `dir`, see `load_library`. Returns the number
+    /// of libraries loaded.
+    pub fn load_directory(&mut self, dir: &Path) -> Result<usize> {
+        let suffix = if cfg!(target_os = "windows") {
+            "dll"
+        } else if cfg!(target_os = "macos") {
+            "dylib"
+        } else {
+            "so"
+        };
+        let mut paths = vec![];
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().map(|e| e == suffix).unwrap_or(false) {
+                paths.push(path);
+            }
+        }
+
+        paths.sort();
+        for path in paths.iter() {
+            self.load_library(path)?;
+        }
+        Ok(paths.len())
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("loaders", &self.loaders.iter().map(|l| l.name()).collect::<Vec<_>>())
+            .field("architectures", &self.architectures.iter().map(|a| a.name()).collect::<Vec<_>>())
+            .field("passes", &self.passes.iter().map(|p| p.name()).collect::<Vec<_>>())
+            .field("libraries", &self.libraries.len())
+            .finish()
+    }
+}
+
+/// Defines the entry point of a plugin compiled as dynamic library. `$register` is a function
+/// taking a `&mut Registry` to add the plugins to.
+#[macro_export]
+macro_rules! export_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub extern "C" fn panopticon_plugin_api_version() -> u32 {
+            $crate::plugin::API_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn panopticon_plugin_register(registry: &mut $crate::plugin::Registry) {
+            $register(registry)
+        }
+    };
+}
@@ -0,0 +1,131 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Reanalysis after byte patches.
+//!
+//! `Region::patch` remembers the areas it changed. [`reanalyze`] disassembles the functions with
+//! basic blocks overlapping one of them again and puts the new versions into the call graph in
+//! place of the old ones, keeping UUID, name and aliases. The call graph edges and cross
+//! references of these functions are updated, all other functions are left untouched.
+//!
+//! Functions are disassembled from scratch instead of with `Function::cont`, as their old
+//! control flow graph is already in SSA form.
+//!
+//! [`reanalyze`]: fn.reanalyze.html
+
+use panopticon_core::{Alias, Architecture, Bound, ControlFlowTarget, Function, FunctionKind, Limit, NameSource, Project, Signature, calling_convention, xref};
+use panopticon_data_flow::{remove_dead_flags, ssa_convertion};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, MutableGraphTrait};
+use uuid::Uuid;
+
+/// Returns true if a basic block of `func` overlaps `area`.
+// Everything about a function that isn't recovered by disassembling it again
+struct Meta {
+    aliases: Vec<Alias>,
+    name_source: NameSource,
+    kind: FunctionKind,
+    signature: Option<Signature>,
+    truncated: Option<Limit>,
+}
+
+impl Meta {
+    fn of(func: &Function) -> Meta {
+        Meta {
+            aliases: func.alias_entries().to_vec(),
+            name_source: func.name_source(),
+            kind: func.kind().clone(),
+            signature: func.signature().cloned(),
+            truncated: func.truncated(),
+        }
+    }
+
+    fn apply(self, func: &mut Function) {
+        for alias in self.aliases {
+            func.add_alias_from(alias.name, alias.source);
+        }
+        func.set_name_source(self.name_source);
+        func.set_kind(self.kind);
+        func.set_signature(self.signature);
+        func.set_truncated(self.truncated);
+    }
+}
+
+fn overlaps(func: &Function, area: &Bound) -> bool {
+    func.basic_blocks().any(|bb| bb.area.start < area.end && area.start < bb.area.end)
+}
+
+/// Disassembles the functions of `proj` touched by patches to the root region since the last call
+/// again. Returns the UUIDs of the functions replaced.
+pub fn reanalyze<A: Architecture>(proj: &mut Project, config: A::Configuration) -> Vec<Uuid> {
+    let root = proj.data.root;
+    let changes = match proj.data.dependencies.vertex_label_mut(root) {
+        Some(region) => region.take_changes(),
+        None => return vec![],
+    };
+
+    if changes.is_empty() {
+        return vec![];
+    }
+
+    let region = proj.region().clone();
+    let mut ret = vec![];
+
+    for prog in proj.code.iter_mut() {
+        let stale = prog.functions()
+            .filter(|f| changes.iter().any(|c| overlaps(f, c)))
+            .filter_map(
+                |f| match f.cfg().vertex_label(f.entry_point_ref()) {
+                    Some(&ControlFlowTarget::Resolved(ref bb)) => Some((f.uuid().clone(), bb.area.start, f.name.clone(), Meta::of(f))),
+                    _ => None,
+                }
+            )
+            .collect::<Vec<_>>();
+
+        for (uuid, start, name, meta) in stale {
+            let mut func = match Function::with_uuid::<A>(start, &uuid, &region, Some(name), config.clone()) {
+                Ok(func) => func,
+                Err(e) => {
+                    error!("failed to disassemble {} at {:#x} again: {}", uuid, start, e);
+                    continue;
+                }
+            };
+
+            meta.apply(&mut func);
+            remove_dead_flags(&mut func, A::flags());
+            let _ = ssa_convertion(&mut func);
+            let cc = calling_convention::infer(&func);
+            func.set_calling_convention(cc);
+
+            // calls of the old version
+            if let Some(vx) = prog.find_call_target_by_uuid(&uuid) {
+                let edges = prog.call_graph.out_edges(vx).collect::<Vec<_>>();
+
+                for e in edges {
+                    prog.call_graph.remove_edge(e);
+                }
+            }
+            prog.analyzed_calls.retain(|&(ref caller, _)| *caller != uuid);
+            prog.insert(func);
+            ret.push(uuid);
+        }
+    }
+
+    xref::update(proj, &ret);
+    ret
+}
+
@@ -0,0 +1,496 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! JSON-RPC server for front-ends running in other processes.
+//!
+//! [`Server::listen`] accepts WebSocket connections and answers JSON-RPC 2.0 requests sent as
+//! text messages. All connections share one project. Addresses are passed as strings of
+//! hexadecimal numbers like `"0x401000"` or as JSON numbers, results use strings.
+//!
+//! | Method | Parameters | Result |
+//! |--------|------------|--------|
+//! | `open` | `path` | `name` and number of `functions` of the disassembled file |
+//! | `save` | `path` | `null`, writes a session file |
+//! | `functions` | | `uuid`, `name` and `start` of each function |
+//! | `function` | `address` or `uuid` | CFG and IL in the format of `interchange::function` |
+//! | `rename` | `address`, `name`, `suffix` (optional) | new name, see `rename::rename_global` |
+//! | `comment` | `address`, `text` | `null` |
+//! | `subscribe` | | `true`, starts sending `event` notifications |
+//!
+//! After `subscribe`, each `AnalysisEvent` is sent to the connection as a notification with
+//! method `event` and the event as parameters, e.g.
+//! `{"type":"function_finished","uuid":"...","start":"0x401000","size":42}`.
+//!
+//! Errors use the codes of the JSON-RPC specification, and `-32000` if the request failed, for
+//! example because no file has been opened yet.
+//!
+//! [`Server::listen`]: struct.Server.html#method.listen
+
+use driver::{self, Options};
+use panopticon_core::{Collision, Error, Json, Location, Project, Result, event, interchange, rename};
+use panopticon_core::event::AnalysisEvent;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use uuid::Uuid;
+
+/// Largest message accepted from clients.
+const MAX_MESSAGE: usize = 16 << 20;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_FAILED: i64 = -32000;
+
+/// Shared state of all connections.
+#[derive(Clone)]
+pub struct Server {
+    project: Arc<RwLock<Option<Project>>>,
+}
+
+/// JSON-RPC error code and message.
+type RpcError = (i64, String);
+
+fn failed(e: Error) -> RpcError {
+    (REQUEST_FAILED, e.to_string())
+}
+
+fn string(s: &str) -> String {
+    format!("{}", Json::String(s.to_string()))
+}
+
+fn address(a: u64) -> String {
+    format!("\"{:#x}\"", a)
+}
+
+fn param<'a>(params: Option<&'a Json>, key: &str) -> ::std::result::Result<&'a Json, RpcError> {
+    params.and_then(|p| p.get(key)).ok_or_else(|| (INVALID_PARAMS, format!("missing parameter {}", key)))
+}
+
+fn str_param<'a>(params: Option<&'a Json>, key: &str) -> ::std::result::Result<&'a str, RpcError> {
+    param(params, key)?.as_str().ok_or_else(|| (INVALID_PARAMS, format!("{} must be a string", key)))
+}
+
+fn address_param(params: Option<&Json>, key: &str) -> ::std::result::Result<u64, RpcError> {
+    param(params, key)?.as_u64().ok_or_else(|| (INVALID_PARAMS, format!("{} must be an address", key)))
+}
+
+/// Returns `ev` as JSON object.
+fn event_json(ev: &AnalysisEvent) -> String {
+    match ev {
+        &AnalysisEvent::Loaded { ref name, machine, code_bytes } => {
+            format!("{{\"type\":\"loaded\",\"name\":{},\"machine\":{},\"code_bytes\":{}}}", string(name), string(&format!("{:?}", machine)), code_bytes)
+        }
+        &AnalysisEvent::FunctionDiscovered { ref uuid, start } => format!("{{\"type\":\"function_discovered\",\"uuid\":\"{}\",\"start\":{}}}", uuid, address(start)),
+        &AnalysisEvent::FunctionFinished { ref uuid, start, size } => {
+            format!("{{\"type\":\"function_finished\",\"uuid\":\"{}\",\"start\":{},\"size\":{}}}", uuid, address(start), size)
+        }
+        &AnalysisEvent::BytesCovered { ref uuid, bytes } => format!("{{\"type\":\"bytes_covered\",\"uuid\":\"{}\",\"bytes\":{}}}", uuid, bytes),
+        &AnalysisEvent::Error { address: Some(a), ref message } => format!("{{\"type\":\"error\",\"address\":{},\"message\":{}}}", address(a), string(message)),
+        &AnalysisEvent::Error { address: None, ref message } => format!("{{\"type\":\"error\",\"address\":null,\"message\":{}}}", string(message)),
+    }
+}
+
+impl Server {
+    /// Returns a server without a project.
+    pub fn new() -> Server {
+        Server { project: Arc::new(RwLock::new(None)) }
+    }
+
+    /// Returns a server for the already loaded `proj`.
+    pub fn with_project(proj: Project) -> Server {
+        Server { project: Arc::new(RwLock::new(Some(proj))) }
+    }
+
+    /// Runs `f` with the project, failing if no file has been opened.
+    fn read<T, F: FnOnce(&Project) -> ::std::result::Result<T, RpcError>>(&self, f: F) -> ::std::result::Result<T, RpcError> {
+        match self.project.read() {
+            Ok(ref guard) if guard.is_some() => f(guard.as_ref().unwrap()),
+            Ok(_) => Err((REQUEST_FAILED, "no file opened".to_string())),
+            Err(_) => Err((REQUEST_FAILED, "lock poisoned".to_string())),
+        }
+    }
+
+    /// Runs `f` with the project, failing if no file has been opened.
+    fn write<T, F: FnOnce(&mut Project) -> ::std::result::Result<T, RpcError>>(&self, f: F) -> ::std::result::Result<T, RpcError> {
+        match self.project.write() {
+            Ok(ref mut guard) if guard.is_some() => f(guard.as_mut().unwrap()),
+            Ok(_) => Err((REQUEST_FAILED, "no file opened".to_string())),
+            Err(_) => Err((REQUEST_FAILED, "lock poisoned".to_string())),
+        }
+    }
+
+    /// Executes `method`, returning the result as JSON.
+    fn call(&self, method: &str, params: Option<&Json>) -> ::std::result::Result<String, RpcError> {
+        match method {
+            "open" => {
+                let path = str_param(params, "path")?;
+                let proj = driver::analyze(Path::new(path), &Options::new()).map_err(failed)?;
+                let ret = format!("{{\"name\":{},\"functions\":{}}}", string(&proj.name), proj.code.iter().map(|p| p.functions().count()).sum::<usize>());
+
+                *self.project.write().map_err(|_| (REQUEST_FAILED, "lock poisoned".to_string()))? = Some(proj);
+                Ok(ret)
+            }
+            "save" => {
+                let path = str_param(params, "path")?;
+
+                self.read(|proj| proj.snapshot(Path::new(path)).map(|_| "null".to_string()).map_err(failed))
+            }
+            "functions" => {
+                self.read(
+                    |proj| {
+                        let mut funcs = proj.code.iter().flat_map(|p| p.functions()).collect::<Vec<_>>();
+
+                        funcs.sort_by_key(|f| f.start());
+
+                        let funcs = funcs.iter()
+                            .map(|f| format!("{{\"uuid\":\"{}\",\"name\":{},\"start\":{}}}", f.uuid(), string(&f.display_name()), address(f.start())))
+                            .collect::<Vec<_>>();
+
+                        Ok(format!("[{}]", funcs.join(",")))
+                    }
+                )
+            }
+            "function" => {
+                let uuid = match params.and_then(|p| p.get("uuid")).and_then(|u| u.as_str()) {
+                    Some(s) => Some(Uuid::parse_str(s).map_err(|_| (INVALID_PARAMS, "invalid uuid".to_string()))?),
+                    None => None,
+                };
+                let start = if uuid.is_none() { Some(address_param(params, "address")?) } else { None };
+
+                self.read(
+                    |proj| {
+                        let func = proj.code
+                            .iter()
+                            .filter_map(
+                                |p| match (&uuid, start) {
+                                    (&Some(ref uu), _) => p.find_function_by_uuid(uu),
+                                    (&None, Some(a)) => p.find_function_by(|f| f.start() == a),
+                                    (&None, None) => None,
+                                }
+                            )
+                            .next();
+
+                        func.map(interchange::function).ok_or_else(|| (REQUEST_FAILED, "no such function".to_string()))
+                    }
+                )
+            }
+            "rename" => {
+                let addr = address_param(params, "address")?;
+                let name = str_param(params, "name")?;
+                let policy = match params.and_then(|p| p.get("suffix")).and_then(|s| s.as_bool()) {
+                    Some(true) => Collision::Suffix,
+                    _ => Collision::Fail,
+                };
+
+                self.write(|proj| rename::rename_global(proj, addr, name, policy).map(|n| string(&n)).map_err(failed))
+            }
+            "comment" => {
+                let addr = address_param(params, "address")?;
+                let text = str_param(params, "text")?;
+
+                self.write(
+                    |proj| {
+                        proj.annotations.set_comment(Location::Address(addr), text.to_string());
+                        Ok("null".to_string())
+                    }
+                )
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        }
+    }
+
+    /// Answers the JSON-RPC request `request`. Returns None for notifications. `subscribe` is
+    /// only supported by connections made through `listen`.
+    pub fn handle(&self, request: &str) -> Option<String> {
+        let req = match Json::parse(request) {
+            Ok(req) => req,
+            Err(e) => return Some(response("null", Err((PARSE_ERROR, e.to_string())))),
+        };
+        let id = req.get("id").map(|id| format!("{}", id));
+        let ret = match req.get("method").and_then(|m| m.as_str()) {
+            Some(method) => self.call(method, req.get("params")),
+            None => Err((INVALID_REQUEST, "missing method".to_string())),
+        };
+
+        id.map(|id| response(&id, ret))
+    }
+
+    /// Accepts WebSocket connections on `addr` until an error occurs. Each connection is
+    /// handled by its own thread.
+    pub fn listen<A: ToSocketAddrs>(&self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+
+            thread::spawn(
+                move || if let Err(e) = server.connection(stream) {
+                    debug!("connection closed: {}", e);
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    fn connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        handshake(&mut stream)?;
+
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        let mut message = vec![];
+        let mut subscribed = false;
+
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut stream)?;
+
+            match opcode {
+                // continuation, text and binary
+                0 | 1 | 2 => {
+                    if message.len() + payload.len() > MAX_MESSAGE {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+                    }
+                    message.extend(payload);
+                    if !fin {
+                        continue;
+                    }
+                }
+                // close
+                8 => {
+                    let _ = send(&writer, 8, &[]);
+                    return Ok(());
+                }
+                // ping
+                9 => {
+                    send(&writer, 10, &payload)?;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let request = String::from_utf8_lossy(&message).into_owned();
+            message.clear();
+
+            let is_subscribe = Json::parse(&request).ok().map(|r| r.get("method").and_then(|m| m.as_str()) == Some("subscribe")).unwrap_or(false);
+            let answer = if is_subscribe {
+                if !subscribed {
+                    let events = event::subscribe();
+                    let writer = writer.clone();
+
+                    subscribed = true;
+                    thread::spawn(
+                        move || for ev in events.iter() {
+                            let msg = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"event\",\"params\":{}}}", event_json(&ev));
+
+                            if send(&writer, 1, msg.as_bytes()).is_err() {
+                                break;
+                            }
+                        }
+                    );
+                }
+                Json::parse(&request).ok().and_then(|r| r.get("id").map(|id| response(&format!("{}", id), Ok("true".to_string()))))
+            } else {
+                self.handle(&request)
+            };
+
+            if let Some(answer) = answer {
+                send(&writer, 1, answer.as_bytes())?;
+            }
+        }
+    }
+}
+
+fn response(id: &str, result: ::std::result::Result<String, RpcError>) -> String {
+    match result {
+        Ok(r) => format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":{}}}", id, r),
+        Err((code, msg)) => format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"error\":{{\"code\":{},\"message\":{}}}}}", id, code, string(&msg)),
+    }
+}
+
+/// Reads the HTTP upgrade request and accepts it.
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    let mut request = vec![];
+    let mut byte = [0u8];
+
+    while !request.ends_with(b"\r\n\r\n") {
+        if request.len() > 8192 || stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP request"));
+        }
+        request.push(byte[0]);
+    }
+
+    let request = String::from_utf8_lossy(&request).into_owned();
+    let key = request.lines().filter_map(
+        |l| {
+            let mut kv = l.splitn(2, ':');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k.trim().eq_ignore_ascii_case("sec-websocket-key") => Some(v.trim().to_string()),
+                _ => None,
+            }
+        }
+    )
+        .next();
+
+    match key {
+        Some(key) => {
+            let accept = base64(&sha1(format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key).as_bytes()));
+            write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept)
+        }
+        None => {
+            write!(stream, "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket request"))
+        }
+    }
+}
+
+/// Reads a WebSocket frame. Returns the FIN bit, opcode and unmasked payload.
+fn read_frame<R: Read>(r: &mut R) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+
+    r.read_exact(&mut head)?;
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut b = [0u8; 2];
+            r.read_exact(&mut b)?;
+            (b[0] as usize) << 8 | b[1] as usize
+        }
+        127 => {
+            let mut b = [0u8; 8];
+            r.read_exact(&mut b)?;
+            b.iter().fold(0u64, |acc, &x| acc << 8 | x as u64) as usize
+        }
+        l => l as usize,
+    };
+
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+
+    let mut mask = [0u8; 4];
+    let mut payload = vec![0u8; len];
+
+    if head[1] & 0x80 != 0 {
+        r.read_exact(&mut mask)?;
+    }
+    r.read_exact(&mut payload)?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+
+    Ok((head[0] & 0x80 != 0, head[0] & 0x0f, payload))
+}
+
+/// Sends a single, unmasked frame.
+fn send(writer: &Mutex<TcpStream>, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+
+    if payload.len() < 126 {
+        frame.push(payload.len() as u8);
+    } else if payload.len() < 0x10000 {
+        frame.push(126);
+        frame.extend_from_slice(&[(payload.len() >> 8) as u8, payload.len() as u8]);
+    } else {
+        frame.push(127);
+        frame.extend((0..8).rev().map(|i| ((payload.len() as u64) >> (i * 8)) as u8));
+    }
+    frame.extend_from_slice(payload);
+
+    match writer.lock() {
+        Ok(mut w) => w.write_all(&frame),
+        Err(_) => Err(io::Error::new(io::ErrorKind::Other, "lock poisoned")),
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut msg = data.to_vec();
+
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend((0..8).rev().map(|i| ((data.len() as u64 * 8) >> (i * 8)) as u8));
+
+    for chunk in msg.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for i in 0..16 {
+            w[i] = (chunk[4 * i] as u32) << 24 | (chunk[4 * i + 1] as u32) << 16 | (chunk[4 * i + 2] as u32) << 8 | chunk[4 * i + 3] as u32;
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+
+        for i in 0..80 {
+            let (f, k) = match i {
+                0...19 => ((b & c) | (!b & d), 0x5a827999),
+                20...39 => (b ^ c ^ d, 0x6ed9eba1),
+                40...59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[i]);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut ret = [0u8; 20];
+
+    for (i, v) in h.iter().enumerate() {
+        ret[4 * i] = (v >> 24) as u8;
+        ret[4 * i + 1] = (v >> 16) as u8;
+        ret[4 * i + 2] = (v >> 8) as u8;
+        ret[4 * i + 3] = *v as u8;
+    }
+    ret
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut ret = String::new();
+
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                ret.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
@@ -0,0 +1,597 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! ARM (A32) instruction decoder.
+
+use architecture::{Cpu, Mode};
+use byteorder::{ByteOrder, LittleEndian};
+use panopticon_core::{Guard, Mnemonic, Result, Rvalue, Statement};
+use semantic::*;
+
+/// Decodes the ARM instruction at `addr`. Returns its length, the mnemonic and the outgoing
+/// jumps.
+pub fn read(cpu: &Cpu, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    if buf.len() < 4 {
+        return Err("ARM instruction truncated".into());
+    }
+    if addr & 3 != 0 {
+        return Err(format!("unaligned ARM instruction at {:#x}", addr).into());
+    }
+
+    let insn = LittleEndian::read_u32(buf);
+    let cond = insn >> 28;
+    let pc = addr + 8;
+
+    if cond == 0xf {
+        return unconditional(cpu, insn, addr);
+    }
+
+    match (insn >> 25) & 7 {
+        0 => {
+            if insn & 0x0fc0_00f0 == 0x0000_0090 {
+                multiply(insn, addr, cond)
+            } else if insn & 0x0f80_00f0 == 0x0080_0090 {
+                multiply_long(insn, addr, cond)
+            } else if insn & 0x0ff0_00f0 == 0x0120_0010 && (insn >> 8) & 0xfff == 0xfff {
+                branch_exchange(insn, addr, cond, false)
+            } else if insn & 0x0ff0_00f0 == 0x0120_0030 && (insn >> 8) & 0xfff == 0xfff {
+                branch_exchange(insn, addr, cond, true)
+            } else if insn & 0x0fff_0ff0 == 0x016f_0f10 {
+                // RREIL has no count leading zeros operation
+                let rd = (insn >> 12) & 0xf;
+                let rd_lv = reg_lv(rd);
+                let rm = reg(insn & 0xf);
+                let stmts = rreil!{ mov (rd_lv), ?; }?;
+                finish(addr, 4, cond, "clz", "{u}, {u}", vec![reg(rd), rm], stmts, Flow::Next)
+            } else if insn & 0x90 == 0x90 && insn & 0x60 != 0 {
+                extra_load_store(insn, addr, cond)
+            } else if insn & 0x0190_0000 == 0x0100_0000 {
+                Err(format!("unsupported ARM instruction {:#010x}", insn).into())
+            } else {
+                data_processing_reg(insn, addr, cond)
+            }
+        }
+        1 => {
+            if insn & 0x01b0_0000 == 0x0100_0000 {
+                // MOVW/MOVT
+                let rd = (insn >> 12) & 0xf;
+                let imm = ((insn >> 4) & 0xf000) | (insn & 0xfff);
+                move_wide(rd, imm, insn & 0x0040_0000 != 0, addr, 4, cond)
+            } else if insn & 0x01b0_0000 == 0x0120_0000 {
+                Err(format!("unsupported ARM instruction {:#010x}", insn).into())
+            } else {
+                let rot = ((insn >> 8) & 0xf) * 2;
+                let imm = (insn & 0xff).rotate_right(rot);
+                let carry = if rot == 0 { None } else { Some(Rvalue::new_bit((imm >> 31) as usize)) };
+                let operand = ("{u}".to_string(), vec![Rvalue::new_u32(imm)]);
+                data_processing_op(insn, addr, cond, Rvalue::new_u32(imm), carry, operand, vec![])
+            }
+        }
+        2 => {
+            let off = Rvalue::new_u32(insn & 0xfff);
+            load_store(insn, addr, cond, off, None, vec![])
+        }
+        3 if insn & 0x10 == 0 => {
+            let rm = read_reg(insn & 0xf, pc);
+            let (ty, amount) = ((insn >> 5) & 3, (insn >> 7) & 0x1f);
+            let (code, off, _) = shift_imm(rm, ty, amount)?;
+            load_store(insn, addr, cond, off, Some(shifted_reg(insn & 0xf, ty, amount)), code)
+        }
+        4 => load_store_multiple_insn(insn, addr, cond),
+        5 => {
+            let off = sign_extend(insn & 0xff_ffff, 24) << 2;
+            let tgt = Rvalue::new_u32((pc as i64 + off) as u32);
+            if insn & 0x0100_0000 != 0 {
+                let stmts = rreil!{ call (tgt); }?;
+                finish(addr, 4, cond, "bl", "{c:ram}", vec![tgt], stmts, Flow::Next)
+            } else {
+                finish(addr, 4, cond, "b", "{c:ram}", vec![tgt.clone()], vec![], Flow::Jump(tgt))
+            }
+        }
+        7 if insn & 0x0100_0000 != 0 => {
+            let imm = Rvalue::new_u32(insn & 0xff_ffff);
+            let stmts = rreil!{ call (imm); }?;
+            finish(addr, 4, cond, "svc", "{u}", vec![imm], stmts, Flow::Next)
+        }
+        _ => Err(format!("unsupported ARM instruction {:#010x}", insn).into()),
+    }
+}
+
+/// Instructions with condition field `0b1111`. Only `BLX <imm>` is supported.
+fn unconditional(cpu: &Cpu, insn: u32, addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    if insn & 0x0e00_0000 == 0x0a00_0000 {
+        let h = (insn >> 24) & 1;
+        let off = (sign_extend(insn & 0xff_ffff, 24) << 2) | (h << 1) as i64;
+        let tgt_addr = (addr as i64 + 8 + off) as u64 & 0xffff_ffff;
+        let tgt = Rvalue::new_u32(tgt_addr as u32);
+        let stmts = rreil!{ call (tgt); }?;
+
+        cpu.switch_mode(tgt_addr, Mode::Thumb)?;
+        finish(addr, 4, 14, "blx", "{c:ram}", vec![tgt], stmts, Flow::Next)
+    } else {
+        Err(format!("unsupported ARM instruction {:#010x}", insn).into())
+    }
+}
+
+/// `BX` and `BLX` with register operand. Returning via `BX LR` ends the function.
+fn branch_exchange(insn: u32, addr: u64, cond: u32, link: bool) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let rm = insn & 0xf;
+
+    if link {
+        let tgt = reg(rm);
+        let stmts = rreil!{ call (tgt); }?;
+        finish(addr, 4, cond, "blx", "{u}", vec![reg(rm)], stmts, Flow::Next)
+    } else if rm == LR {
+        finish(addr, 4, cond, "bx", "{u}", vec![reg(rm)], vec![], Flow::Return)
+    } else {
+        finish(addr, 4, cond, "bx", "{u}", vec![reg(rm)], vec![], Flow::Jump(reg(rm)))
+    }
+}
+
+/// `MOVW` (`top` = false) and `MOVT`.
+pub fn move_wide(rd: u32, imm: u32, top: bool, addr: u64, len: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let rd_lv = reg_lv(rd);
+
+    if top {
+        let rd_rv = reg(rd);
+        let hi = Rvalue::new_u32(imm << 16);
+        let stmts = rreil!{
+            and lo:32, (rd_rv), [0xffff]:32;
+            or (rd_lv), lo:32, (hi);
+        }?;
+        finish(addr, len, cond, "movt", "{u}, {u}", vec![reg(rd), Rvalue::new_u32(imm)], stmts, Flow::Next)
+    } else {
+        let imm = Rvalue::new_u32(imm);
+        let stmts = rreil!{ mov (rd_lv), (imm); }?;
+        finish(addr, len, cond, "movw", "{u}, {u}", vec![reg(rd), imm], stmts, Flow::Next)
+    }
+}
+
+fn data_processing_reg(insn: u32, addr: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let pc = addr + 8;
+    let rm = read_reg(insn & 0xf, pc);
+    let ty = (insn >> 5) & 3;
+
+    if insn & 0x10 == 0 {
+        let amount = (insn >> 7) & 0x1f;
+        let (code, op2, carry) = shift_imm(rm, ty, amount)?;
+        data_processing_op(insn, addr, cond, op2, carry, shifted_reg(insn & 0xf, ty, amount), code)
+    } else {
+        let rs = (insn >> 8) & 0xf;
+        let (code, op2) = shift_reg(rm, ty, read_reg(rs, pc))?;
+        let operand = (format!("{{u}}, {} {{u}}", shift_name(ty)), vec![reg(insn & 0xf), reg(rs)]);
+        data_processing_op(insn, addr, cond, op2, None, operand, code)
+    }
+}
+
+/// Register `rm` shifted by an immediate as written in the listing, a format string and its
+/// operands.
+fn shifted_reg(rm: u32, ty: u32, amount: u32) -> (String, Vec<Rvalue>) {
+    match (ty, amount) {
+        (0, 0) => ("{u}".to_string(), vec![reg(rm)]),
+        (3, 0) => ("{u}, rrx".to_string(), vec![reg(rm)]),
+        (_, n) => {
+            // LSR and ASR encode a shift by 32 as 0
+            let n = if n == 0 { 32 } else { n };
+            (format!("{{u}}, {} {{u}}", shift_name(ty)), vec![reg(rm), Rvalue::new_u32(n)])
+        }
+    }
+}
+
+fn data_processing_op(insn: u32, addr: u64, cond: u32, op2: Rvalue, carry: Option<Rvalue>, operand: (String, Vec<Rvalue>), mut stmts: Vec<Statement>) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let op = (insn >> 21) & 0xf;
+    let s = insn & 0x0010_0000 != 0;
+    let rn = (insn >> 16) & 0xf;
+    let rd = (insn >> 12) & 0xf;
+    let is_test = op >= 8 && op <= 11;
+    let is_move = op == 13 || op == 15;
+    let rn_rv = read_reg(rn, addr + 8);
+    let dst = if rd == PC { rreil_lvalue!{ target:32 } } else { reg_lv(rd) };
+    let name = format!("{}{}", data_processing_name(op), if s && !is_test { "s" } else { "" });
+    let (op2_fmt, op2_ops) = operand;
+
+    stmts.extend(data_processing(op, s, dst, rn_rv.clone(), op2, carry)?);
+
+    let (fmt, mut ops) = if is_test {
+        (format!("{{u}}, {}", op2_fmt), vec![rn_rv])
+    } else if is_move {
+        (format!("{{u}}, {}", op2_fmt), vec![reg(rd)])
+    } else {
+        (format!("{{u}}, {{u}}, {}", op2_fmt), vec![reg(rd), rn_rv])
+    };
+
+    ops.extend(op2_ops);
+
+    let flow = if rd == PC && !is_test {
+        if op == 13 && insn & 0x0200_0000 == 0 && insn & 0xff0 == 0 && insn & 0xf == LR {
+            Flow::Return
+        } else {
+            Flow::Jump(rreil_rvalue!{ target:32 })
+        }
+    } else {
+        Flow::Next
+    };
+
+    finish(addr, 4, cond, &name, &fmt, ops, stmts, flow)
+}
+
+fn multiply(insn: u32, addr: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let acc = insn & 0x0020_0000 != 0;
+    let s = insn & 0x0010_0000 != 0;
+    let rd = (insn >> 16) & 0xf;
+    let rn = reg((insn >> 12) & 0xf);
+    let rs = reg((insn >> 8) & 0xf);
+    let rm = reg(insn & 0xf);
+    let rd_lv = reg_lv(rd);
+    let mut stmts = rreil!{ mul res:32, (rm), (rs); }?;
+
+    if acc {
+        stmts.extend(rreil!{ add res:32, res:32, (rn); }?);
+    }
+    stmts.extend(rreil!{ mov (rd_lv), res:32; }?);
+    if s {
+        stmts.extend(set_nz()?);
+    }
+
+    let name = format!("{}{}", if acc { "mla" } else { "mul" }, if s { "s" } else { "" });
+    let ops = if acc { vec![reg(rd), rm, rs, rn] } else { vec![reg(rd), rm, rs] };
+    let fmt = if acc { "{u}, {u}, {u}, {u}" } else { "{u}, {u}, {u}" };
+
+    finish(addr, 4, cond, &name, fmt, ops, stmts, Flow::Next)
+}
+
+/// `UMULL`, `UMLAL`, `SMULL` and `SMLAL`.
+fn multiply_long(insn: u32, addr: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let signed = insn & 0x0040_0000 != 0;
+    let acc = insn & 0x0020_0000 != 0;
+    let s = insn & 0x0010_0000 != 0;
+    let rdhi = (insn >> 16) & 0xf;
+    let rdlo = (insn >> 12) & 0xf;
+    let rs = reg((insn >> 8) & 0xf);
+    let rm = reg(insn & 0xf);
+    let (hi_lv, lo_lv) = (reg_lv(rdhi), reg_lv(rdlo));
+    let (hi_rv, lo_rv) = (reg(rdhi), reg(rdlo));
+    let mut stmts = if signed {
+        rreil!{
+            sext/64 a64:64, (rm);
+            sext/64 b64:64, (rs);
+        }?
+    } else {
+        rreil!{
+            zext/64 a64:64, (rm);
+            zext/64 b64:64, (rs);
+        }?
+    };
+
+    stmts.extend(rreil!{ mul r64:64, a64:64, b64:64; }?);
+    if acc {
+        stmts.extend(
+            rreil!{
+                zext/64 acc:64, (hi_rv);
+                shl acc:64, acc:64, [32]:64;
+                zext/64 acclo:64, (lo_rv);
+                or acc:64, acc:64, acclo:64;
+                add r64:64, r64:64, acc:64;
+            }?
+        );
+    }
+    stmts.extend(
+        rreil!{
+            mov (lo_lv), r64:64;
+            shr rhi:64, r64:64, [32]:64;
+            mov (hi_lv), rhi:64;
+        }?
+    );
+    if s {
+        stmts.extend(
+            rreil!{
+                cmplts N:1, r64:64, [0]:64;
+                cmpeq Z:1, r64:64, [0]:64;
+            }?
+        );
+    }
+
+    let name = format!(
+        "{}{}{}",
+        if signed { "s" } else { "u" },
+        if acc { "mlal" } else { "mull" },
+        if s { "s" } else { "" }
+    );
+
+    finish(addr, 4, cond, &name, "{u}, {u}, {u}, {u}", vec![reg(rdlo), reg(rdhi), rm, rs], stmts, Flow::Next)
+}
+
+/// Single register `LDR`, `LDRB`, `STR` and `STRB`, including the unprivileged variants.
+/// `index` is the offset register as written, `None` for immediate offsets.
+fn load_store(insn: u32, addr: u64, cond: u32, off: Rvalue, index: Option<(String, Vec<Rvalue>)>, code: Vec<Statement>) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let byte = insn & 0x0040_0000 != 0;
+    let load = insn & 0x0010_0000 != 0;
+    // post-indexed with W set
+    let user = insn & 0x0120_0000 == 0x0020_0000;
+    let name = format!("{}{}{}", if load { "ldr" } else { "str" }, if byte { "b" } else { "" }, if user { "t" } else { "" });
+
+    transfer(insn, addr, cond, &name, if byte { 8 } else { 32 }, false, off, index, code)
+}
+
+/// `LDRH`, `STRH`, `LDRSB` and `LDRSH`.
+fn extra_load_store(insn: u32, addr: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let load = insn & 0x0010_0000 != 0;
+    let (off, index) = if insn & 0x0040_0000 != 0 {
+        (Rvalue::new_u32(((insn >> 4) & 0xf0) | (insn & 0xf)), None)
+    } else {
+        (read_reg(insn & 0xf, addr + 8), Some(("{u}".to_string(), vec![reg(insn & 0xf)])))
+    };
+    let (name, size, signed) = match ((insn >> 5) & 3, load) {
+        (1, true) => ("ldrh", 16, false),
+        (1, false) => ("strh", 16, false),
+        (2, true) => ("ldrsb", 8, true),
+        (3, true) => ("ldrsh", 16, true),
+        _ => return Err(format!("unsupported ARM instruction {:#010x}", insn).into()),
+    };
+
+    transfer(insn, addr, cond, name, size, signed, off, index, vec![])
+}
+
+fn transfer(insn: u32, addr: u64, cond: u32, name: &str, size: usize, signed: bool, off: Rvalue, index: Option<(String, Vec<Rvalue>)>, mut stmts: Vec<Statement>) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let pre = insn & 0x0100_0000 != 0;
+    let up = insn & 0x0080_0000 != 0;
+    let wb = !pre || insn & 0x0020_0000 != 0;
+    let load = insn & 0x0010_0000 != 0;
+    let rn = (insn >> 16) & 0xf;
+    let rt = (insn >> 12) & 0xf;
+    let base = read_reg(rn, addr + 8);
+    let rn_lv = reg_lv(rn);
+
+    if up {
+        stmts.extend(rreil!{ add offset_addr:32, (base), (off); }?);
+    } else {
+        stmts.extend(rreil!{ sub offset_addr:32, (base), (off); }?);
+    }
+
+    let ea = if pre { rreil_rvalue!{ offset_addr:32 } } else { base.clone() };
+    let literal = match (&base, &off, pre && index.is_none()) {
+        (&Rvalue::Constant { value: b, .. }, &Rvalue::Constant { value: o, .. }, true) => {
+            Some(Rvalue::new_u32(if up { b.wrapping_add(o) } else { b.wrapping_sub(o) } as u32))
+        }
+        _ => None,
+    };
+
+    if load {
+        let dst = if rt == PC { rreil_lvalue!{ target:32 } } else { reg_lv(rt) };
+        stmts.extend(rreil!{ mov address:32, (ea); }?);
+        if wb && rn != PC {
+            stmts.extend(rreil!{ mov (rn_lv), offset_addr:32; }?);
+        }
+        stmts.extend(::semantic::load(dst, rreil_rvalue!{ address:32 }, size, signed)?);
+    } else {
+        stmts.extend(::semantic::store(read_reg(rt, addr + 8), ea, size)?);
+        if wb && rn != PC {
+            stmts.extend(rreil!{ mov (rn_lv), offset_addr:32; }?);
+        }
+    }
+
+    let flow = if load && rt == PC {
+        if rn == SP && !pre && up && off == Rvalue::new_u32(4) {
+            Flow::Return
+        } else {
+            Flow::Jump(rreil_rvalue!{ target:32 })
+        }
+    } else {
+        Flow::Next
+    };
+    // offsets are subtracted if U is clear, registers are prefixed with a minus
+    let index = match (index, &off) {
+        (Some((fmt, ops)), _) => Some((format!("{}{}", if up { "" } else { "-" }, fmt), ops)),
+        (None, &Rvalue::Constant { value: 0, .. }) if up => None,
+        (None, &Rvalue::Constant { value, .. }) => {
+            let value = if up { value as u32 } else { (value as u32).wrapping_neg() };
+            Some(("{s}".to_string(), vec![Rvalue::new_u32(value)]))
+        }
+        (None, _) => Some(("{u}".to_string(), vec![off.clone()])),
+    };
+    let (fmt, ops) = match (literal, index) {
+        (Some(lit), _) => ("{u}, [{p:ram}]".to_string(), vec![reg(rt), lit]),
+        (None, None) => (format!("{{u}}, [{{u}}]{}", if pre && wb { "!" } else { "" }), vec![reg(rt), reg(rn)]),
+        (None, Some((idx_fmt, idx_ops))) => {
+            let fmt = if !pre {
+                format!("{{u}}, [{{u}}], {}", idx_fmt)
+            } else {
+                format!("{{u}}, [{{u}}, {}]{}", idx_fmt, if wb { "!" } else { "" })
+            };
+            let mut ops = vec![reg(rt), reg(rn)];
+
+            ops.extend(idx_ops);
+            (fmt, ops)
+        }
+    };
+
+    finish(addr, 4, cond, name, &fmt, ops, stmts, flow)
+}
+
+/// `LDM`/`STM` in all addressing modes. `PUSH` and `POP` are displayed as such.
+fn load_store_multiple_insn(insn: u32, addr: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let pre = insn & 0x0100_0000 != 0;
+    let up = insn & 0x0080_0000 != 0;
+    let wb = insn & 0x0020_0000 != 0;
+    let load = insn & 0x0010_0000 != 0;
+    let rn = (insn >> 16) & 0xf;
+    let list = register_list(insn & 0xffff);
+
+    if list.is_empty() {
+        return Err(format!("empty register list in {:#010x}", insn).into());
+    }
+
+    let name = if rn == SP && wb && load && !pre && up {
+        "pop".to_string()
+    } else if rn == SP && wb && !load && pre && !up {
+        "push".to_string()
+    } else {
+        format!("{}{}{}", if load { "ldm" } else { "stm" }, if up { "i" } else { "d" }, if pre { "b" } else { "a" })
+    };
+
+    transfer_multiple(&name, load, pre, up, wb, rn, &list, addr, 4, cond)
+}
+
+/// Emits code for a `LDM`/`STM`-like instruction and builds its mnemonic.
+pub fn transfer_multiple(name: &str, load: bool, pre: bool, up: bool, wb: bool, rn: u32, list: &[u32], addr: u64, len: u64, cond: u32) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let base = reg(rn);
+    let rn_lv = reg_lv(rn);
+    let bytes = Rvalue::new_u32(4 * list.len() as u32);
+    let start = match (pre, up) {
+        (false, true) => rreil!{ mov start:32, (base); }?,
+        (true, true) => rreil!{ add start:32, (base), [4]:32; }?,
+        (false, false) => {
+            rreil!{
+                sub start:32, (base), (bytes);
+                add start:32, start:32, [4]:32;
+            }?
+        }
+        (true, false) => rreil!{ sub start:32, (base), (bytes); }?,
+    };
+    let mut stmts = start;
+
+    stmts.extend(load_store_multiple(load, list, rreil_rvalue!{ start:32 })?);
+    if wb && !(load && list.contains(&rn)) {
+        if up {
+            stmts.extend(rreil!{ add (rn_lv), (base), (bytes); }?);
+        } else {
+            stmts.extend(rreil!{ sub (rn_lv), (base), (bytes); }?);
+        }
+    }
+
+    let is_stack_op = name == "pop" || name == "push";
+    let mut fmt = if is_stack_op { String::new() } else { format!("{{u}}{}, ", if wb { "!" } else { "" }) };
+    let mut ops = if is_stack_op { vec![] } else { vec![reg(rn)] };
+
+    fmt.push_str("{{");
+    for (i, r) in list.iter().enumerate() {
+        if i > 0 {
+            fmt.push_str(", ");
+        }
+        fmt.push_str("{u}");
+        ops.push(reg(*r));
+    }
+    fmt.push_str("}");
+
+    let flow = if load && list.contains(&PC) {
+        if rn == SP { Flow::Return } else { Flow::Jump(rreil_rvalue!{ target:32 }) }
+    } else {
+        Flow::Next
+    };
+
+    finish(addr, len, cond, name, &fmt, ops, stmts, flow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Arm;
+    use panopticon_core::golden;
+
+    fn listing(bytes: &[u8]) -> String {
+        golden::render::<Arm>(bytes, 0, &Cpu::new(Mode::Arm))[0].clone()
+    }
+
+    fn decode(insn: u32, addr: u64) -> (u64, Mnemonic, Vec<(Rvalue, Guard)>) {
+        let mut buf = [0u8; 4];
+        LittleEndian::write_u32(&mut buf, insn);
+        read(&Cpu::new(Mode::Arm), &buf, addr).unwrap()
+    }
+
+    #[test]
+    fn decode_data_processing() {
+        // add r0, r1, r2
+        let (len, mne, jmp) = decode(0xe0810002, 0);
+        assert_eq!(len, 4);
+        assert_eq!(mne.opcode, "add");
+        assert_eq!(mne.operands, vec![reg(0), reg(1), reg(2)]);
+        assert_eq!(jmp, vec![(Rvalue::new_u32(4), Guard::always())]);
+
+        // movs r0, #1
+        let (_, mne, _) = decode(0xe3b00001, 0);
+        assert_eq!(mne.opcode, "movs");
+        assert_eq!(mne.operands, vec![reg(0), Rvalue::new_u32(1)]);
+
+        // cmp r3, #0x10
+        let (_, mne, _) = decode(0xe3530010, 0);
+        assert_eq!(mne.opcode, "cmp");
+    }
+
+    #[test]
+    fn conditional_branch() {
+        // bne -8 @ 0x100
+        let (_, mne, jmp) = decode(0x1afffffc, 0x100);
+        assert_eq!(mne.opcode, "bne");
+        assert_eq!(jmp.len(), 2);
+        assert_eq!(jmp[0].0, Rvalue::new_u32(0xf8));
+        assert_eq!(jmp[1].0, Rvalue::new_u32(0x104));
+        assert_eq!(jmp[0].1, jmp[1].1.negation());
+    }
+
+    #[test]
+    fn returns() {
+        // bx lr
+        let (_, mne, jmp) = decode(0xe12fff1e, 0);
+        assert_eq!(mne.opcode, "bx");
+        assert!(jmp.is_empty());
+
+        // pop {r4, pc}
+        let (_, mne, jmp) = decode(0xe8bd8010, 0);
+        assert_eq!(mne.opcode, "pop");
+        assert_eq!(mne.operands, vec![reg(4), reg(15)]);
+        assert!(jmp.is_empty());
+    }
+
+    #[test]
+    fn blx_switches_to_thumb() {
+        let cpu = Cpu::new(Mode::Arm);
+        let mut buf = [0u8; 4];
+
+        // blx #0x100 with H = 1 @ 0x1000
+        LittleEndian::write_u32(&mut buf, 0xfb00003e);
+        let (_, mne, jmp) = read(&cpu, &buf, 0x1000).unwrap();
+        assert_eq!(mne.opcode, "blx");
+        assert_eq!(mne.operands, vec![Rvalue::new_u32(0x1102)]);
+        assert_eq!(jmp, vec![(Rvalue::new_u32(0x1004), Guard::always())]);
+        assert_eq!(cpu.clone().mode_at(0x1102).ok(), Some(Mode::Thumb));
+        assert_eq!(cpu.mode_at(0x1004).ok(), Some(Mode::Arm));
+    }
+
+    #[test]
+    fn pc_relative_load() {
+        // ldr r0, [pc, #4] @ 0x10
+        let (_, mne, _) = decode(0xe59f0004, 0x10);
+        assert_eq!(mne.opcode, "ldr");
+        assert_eq!(mne.operands, vec![reg(0), Rvalue::new_u32(0x1c)]);
+    }
+
+    #[test]
+    fn operands_from_fields() {
+        // post-indexed, offset subtracted
+        assert_eq!(listing(&[0x18, 0x7c, 0x07, 0xe4]), "str r7, [r7], -0xc18");
+        assert_eq!(listing(&[0xe6, 0x51, 0x07, 0x05]), "streq r5, [r7, -0x1e6]");
+        assert_eq!(listing(&[0x04, 0x50, 0xb7, 0xe5]), "ldr r5, [r7, 0x4]!");
+        assert_eq!(listing(&[0x00, 0x50, 0x97, 0xe5]), "ldr r5, [r7]");
+        assert_eq!(listing(&[0xcf, 0x21, 0x84, 0xc7]), "strgt r2, [r4, pc, asr 0x3]");
+        assert_eq!(listing(&[0x02, 0x10, 0x10, 0xe7]), "ldr r1, [r0, -r2]");
+        assert_eq!(listing(&[0xb2, 0x10, 0xd0, 0xe0]), "ldrh r1, [r0], 0x2");
+        assert_eq!(listing(&[0x2c, 0x5d, 0x8c, 0xe1]), "orr r5, r12, r12, lsr 0x1a");
+        assert_eq!(listing(&[0x12, 0x03, 0xa0, 0xe1]), "mov r0, r2, lsl r3");
+        assert_eq!(listing(&[0x61, 0x00, 0xa0, 0xe1]), "mov r0, r1, rrx");
+    }
+}
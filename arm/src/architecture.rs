@@ -0,0 +1,152 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use panopticon_core::{Architecture, MappingSymbol, Match, Region, Result, Rvalue};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+#[derive(Clone,Debug)]
+pub enum Arm {}
+
+/// Instruction set state
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Mode {
+    /// 32-bit ARM instructions
+    Arm,
+    /// 16/32-bit Thumb and Thumb-2 instructions
+    Thumb,
+}
+
+/// CPU configuration.
+///
+/// Decides which instruction set is active at an address. Explicit interworking targets win over
+/// mapping symbols which in turn win over the initial `mode`.
+#[derive(Clone,Debug)]
+pub struct Cpu {
+    /// Instruction set used if nothing else is known about an address
+    pub mode: Mode,
+    mapping: Arc<BTreeMap<u64, MappingSymbol>>,
+    interworking: Arc<RwLock<HashMap<u64, Mode>>>,
+}
+
+impl Cpu {
+    pub fn new(mode: Mode) -> Cpu {
+        Cpu {
+            mode: mode,
+            mapping: Arc::new(BTreeMap::new()),
+            interworking: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Uses the ELF mapping symbols in `syms` to decide the instruction set.
+    pub fn with_mapping_symbols(mut self, syms: &BTreeMap<u64, MappingSymbol>) -> Cpu {
+        self.mapping = Arc::new(syms.clone());
+        self
+    }
+
+    /// Returns the instruction set active at `address`. Fails if a `$d` mapping symbol marks
+    /// `address` as data.
+    pub fn mode_at(&self, address: u64) -> Result<Mode> {
+        if let Some(mode) = self.interworking.read()?.get(&address) {
+            return Ok(*mode);
+        }
+
+        match self.mapping.range(..address + 1).next_back() {
+            Some((_, &MappingSymbol::Arm)) => Ok(Mode::Arm),
+            Some((_, &MappingSymbol::Thumb)) => Ok(Mode::Thumb),
+            Some((_, &MappingSymbol::Data)) => Err(format!("{:#x} is marked as data", address).into()),
+            None => Ok(self.mode),
+        }
+    }
+
+    /// Records that code at `address` is executed in `mode`. Called for interworking branch
+    /// targets.
+    pub fn switch_mode(&self, address: u64, mode: Mode) -> Result<()> {
+        self.interworking.write()?.insert(address, mode);
+        Ok(())
+    }
+
+    /// Records that `address` is reached by a branch or fall through from code executed in
+    /// `mode`. These never change the instruction set, so `address` inherits `mode` unless its
+    /// mode is already known to be different.
+    fn follow(&self, address: u64, mode: Mode) -> Result<()> {
+        if self.mode_at(address).ok() != Some(mode) {
+            self.interworking.write()?.entry(address).or_insert(mode);
+        }
+        Ok(())
+    }
+}
+
+impl Architecture for Arm {
+    type Token = u8;
+    type Configuration = Cpu;
+
+    fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        Ok(vec![])
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        let mode = cfg.mode_at(start)?;
+        let mut buf: Vec<u8> = vec![];
+        let mut i = reg.iter().seek(start);
+
+        while let Some(Some(b)) = i.next() {
+            buf.push(b);
+            if buf.len() == 4 {
+                break;
+            }
+        }
+
+        debug!("disass @ {:#x} ({:?}): {:?}", start, mode, buf);
+
+        let ret = match mode {
+            Mode::Arm => ::a32::read(cfg, &buf, start),
+            Mode::Thumb => ::thumb::read(cfg, &buf, start),
+        };
+        let ret = ret.and_then(
+            |(len, mne, mut jmp)| {
+                for &(ref tgt, _) in jmp.iter() {
+                    if let &Rvalue::Constant { value, .. } = tgt {
+                        cfg.follow(value, mode)?;
+                    }
+                }
+
+                Ok(
+                    Match::<Arm> {
+                        tokens: buf[0..len as usize].to_vec(),
+                        mnemonics: vec![mne],
+                        jumps: jmp.drain(..).map(|x| (start, x.0, x.1)).collect::<Vec<_>>(),
+                        configuration: cfg.clone(),
+                    }
+                )
+            }
+        );
+
+        debug!("    res: {:?}", ret);
+
+        ret
+    }
+
+    fn mode(cfg: &Self::Configuration, address: u64) -> Option<String> {
+        match cfg.mode_at(address) {
+            Ok(Mode::Arm) => Some("arm".to_string()),
+            Ok(Mode::Thumb) => Some("thumb".to_string()),
+            Err(_) => None,
+        }
+    }
+}
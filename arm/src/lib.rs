@@ -0,0 +1,48 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! 32-bit ARM disassembler.
+//!
+//! This disassembler handles the ARM (A32) and Thumb (T16 and the most common T32) instruction
+//! sets of little endian ARMv4T to ARMv7 CPUs.
+//!
+//! Which instruction set is decoded at a given address is decided by the `Cpu` configuration. It
+//! starts out in the mode the loader reported for the entry point and is refined by ELF mapping
+//! symbols (`$a`, `$t` and `$d`). Whenever the decoder sees a `BLX` with an immediate target it
+//! records that the target is in the other instruction set. Branch targets and fall through
+//! successors inherit the instruction set of their origin. All clones of a `Cpu` share this
+//! table, so functions started from an interworking call site are decoded in the right mode.
+//!
+//! Conditionally executed instructions are lifted by computing the condition into a flag and
+//! merging the new and old values of all written registers with it. `IT` blocks are decoded as
+//! `it` mnemonics without side effects, the instructions inside are lifted unconditionally.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+extern crate byteorder;
+
+pub mod semantic;
+mod a32;
+mod thumb;
+
+mod architecture;
+pub use architecture::{Arm, Cpu, Mode};
@@ -0,0 +1,549 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! RREIL code shared by the ARM and Thumb decoders.
+
+use panopticon_core::{Guard, Lvalue, Mnemonic, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+pub static REGISTERS: [&'static str; 16] = ["R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "R8", "R9", "R10", "R11", "R12", "SP", "LR", "PC"];
+pub static CONDITIONS: [&'static str; 16] = ["eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "", ""];
+static FLAGS: [&'static str; 4] = ["N", "Z", "C", "V"];
+
+pub const SP: u32 = 13;
+pub const LR: u32 = 14;
+pub const PC: u32 = 15;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Branches to the given address
+    Jump(Rvalue),
+    /// Returns to the caller
+    Return,
+}
+
+pub fn reg(r: u32) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(REGISTERS[r as usize & 0xf]),
+        subscript: None,
+        offset: 0,
+        size: 32,
+    }
+}
+
+pub fn reg_lv(r: u32) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(REGISTERS[r as usize & 0xf]), subscript: None, size: 32 }
+}
+
+/// Reads register `r`. The program counter is replaced by its value `pc`.
+pub fn read_reg(r: u32, pc: u64) -> Rvalue {
+    if r == PC { Rvalue::new_u32(pc as u32) } else { reg(r) }
+}
+
+pub fn register_list(list: u32) -> Vec<u32> {
+    (0..16).filter(|r| list & (1 << r) != 0).collect()
+}
+
+/// Sign extends the `bits` wide value `v` to 64 bits.
+pub fn sign_extend(v: u32, bits: usize) -> i64 {
+    let shift = 64 - bits;
+    ((v as u64) << shift) as i64 >> shift
+}
+
+/// Computes the condition `cond` into a flag. Returns the code to compute it and the `Guard` that
+/// is true if the condition holds.
+pub fn condition(cond: u32) -> Result<(Vec<Statement>, Guard)> {
+    let flag = |n: &'static str, e: bool| {
+        Guard::Predicate {
+            flag: Rvalue::Variable { name: Cow::Borrowed(n), subscript: None, offset: 0, size: 1 },
+            expected: e,
+        }
+    };
+
+    match cond {
+        0 => Ok((vec![], flag("Z", true))),
+        1 => Ok((vec![], flag("Z", false))),
+        2 => Ok((vec![], flag("C", true))),
+        3 => Ok((vec![], flag("C", false))),
+        4 => Ok((vec![], flag("N", true))),
+        5 => Ok((vec![], flag("N", false))),
+        6 => Ok((vec![], flag("V", true))),
+        7 => Ok((vec![], flag("V", false))),
+        8 | 9 => {
+            let stmts = rreil!{
+                xor nz:1, Z:1, [1]:1;
+                and cond:1, C:1, nz:1;
+            }?;
+            Ok((stmts, flag("cond", cond == 8)))
+        }
+        10 | 11 => {
+            let stmts = rreil!{
+                cmpeq cond:1, N:1, V:1;
+            }?;
+            Ok((stmts, flag("cond", cond == 10)))
+        }
+        12 | 13 => {
+            let stmts = rreil!{
+                cmpeq ge:1, N:1, V:1;
+                xor nz:1, Z:1, [1]:1;
+                and cond:1, ge:1, nz:1;
+            }?;
+            Ok((stmts, flag("cond", cond == 12)))
+        }
+        14 | 15 => Ok((vec![], Guard::always())),
+        _ => Err(format!("invalid condition code {}", cond).into()),
+    }
+}
+
+fn is_architectural(name: &str) -> bool {
+    REGISTERS.iter().chain(FLAGS.iter()).any(|x| *x == name)
+}
+
+/// Makes `stmts` conditional on `guard`. All registers and flags written by `stmts` are restored
+/// to their old values and stores write back the old memory contents if `guard` is false.
+pub fn predicate(guard: &Guard, stmts: Vec<Statement>) -> Result<Vec<Statement>> {
+    let mut ret = match guard {
+        &Guard::True => return Ok(stmts),
+        &Guard::False => return Ok(vec![]),
+        &Guard::Predicate { ref flag, expected: true } => rreil!{ mov taken:1, (flag); }?,
+        &Guard::Predicate { ref flag, expected: false } => rreil!{ xor taken:1, (flag), [1]:1; }?,
+    };
+    let mut written = Vec::<(Cow<'static, str>, usize)>::new();
+
+    for stmt in stmts.iter() {
+        if let Lvalue::Variable { ref name, size, .. } = stmt.assignee {
+            if is_architectural(name) && !written.iter().any(|x| x.0 == *name) {
+                written.push((name.clone(), size));
+            }
+        }
+    }
+
+    for &(ref name, size) in written.iter() {
+        let old = Lvalue::Variable { name: Cow::Owned(format!("{}_old", name)), subscript: None, size: size };
+        let cur = Rvalue::Variable { name: name.clone(), subscript: None, offset: 0, size: size };
+        ret.extend(rreil!{ mov (old), (cur); }?);
+    }
+
+    for stmt in stmts {
+        match stmt.op {
+            Operation::Store(ref bank, ref endianess, sz, ref ptr, ref val) => {
+                let old = Lvalue::Variable { name: Cow::Borrowed("mem_old"), subscript: None, size: sz };
+                let sel = Lvalue::Variable { name: Cow::Borrowed("mem_sel"), subscript: None, size: sz };
+                let mask = Lvalue::Variable { name: Cow::Borrowed("mem_mask"), subscript: None, size: sz };
+                let old_rv = Rvalue::from(old.clone());
+                let sel_rv = Rvalue::from(sel.clone());
+                let mask_rv = Rvalue::from(mask.clone());
+
+                ret.push(Statement { op: Operation::Load(bank.clone(), endianess.clone(), sz, ptr.clone()), assignee: old.clone() });
+                ret.extend(
+                    rreil!{
+                        zext/(sz) (mask), taken:1;
+                        sub (sel), (val), (old_rv);
+                        mul (sel), (sel_rv), (mask_rv);
+                        add (sel), (old_rv), (sel_rv);
+                    }?
+                );
+                ret.push(Statement { op: Operation::Store(bank.clone(), endianess.clone(), sz, ptr.clone(), sel_rv), assignee: Lvalue::Undefined });
+            }
+            _ => ret.push(stmt),
+        }
+    }
+
+    for (name, size) in written {
+        let old = Rvalue::Variable { name: Cow::Owned(format!("{}_old", name)), subscript: None, offset: 0, size: size };
+        let cur = Rvalue::Variable { name: name.clone(), subscript: None, offset: 0, size: size };
+        let cur_lv = Lvalue::Variable { name: name, subscript: None, size: size };
+
+        if size == 1 {
+            ret.extend(
+                rreil!{
+                    xor diff:1, (cur), (old);
+                    and diff:1, diff:1, taken:1;
+                    xor (cur_lv), (old), diff:1;
+                }?
+            );
+        } else {
+            ret.extend(
+                rreil!{
+                    zext/32 mask:32, taken:1;
+                    sub diff:32, (cur), (old);
+                    mul diff:32, diff:32, mask:32;
+                    add (cur_lv), (old), diff:32;
+                }?
+            );
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Builds the mnemonic for an instruction at `addr` that is `len` bytes long and executed if
+/// condition `cond` holds. Returns the mnemonic and its outgoing jumps.
+pub fn finish(addr: u64, len: u64, cond: u32, opcode: &str, fmt: &str, ops: Vec<Rvalue>, stmts: Vec<Statement>, flow: Flow) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let (mut code, guard) = condition(cond)?;
+    let next = Rvalue::new_u32((addr + len) as u32);
+    let opcode = format!("{}{}", opcode, CONDITIONS[cond as usize]);
+    let jumps = match flow {
+        Flow::Next => vec![(next, Guard::always())],
+        Flow::Jump(tgt) => {
+            if guard == Guard::always() {
+                vec![(tgt, guard.clone())]
+            } else {
+                vec![(tgt, guard.clone()), (next, guard.negation())]
+            }
+        }
+        Flow::Return => {
+            if guard == Guard::always() {
+                vec![]
+            } else {
+                vec![(next, guard.negation())]
+            }
+        }
+    };
+
+    code.extend(predicate(&guard, stmts)?);
+
+    let mne = Mnemonic::from_parts(addr..addr + len, opcode, fmt, ops, code)?;
+    Ok((len, mne, jumps))
+}
+
+fn not(name: &'static str, v: &Rvalue) -> Result<(Vec<Statement>, Rvalue)> {
+    let lv = Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: 32 };
+    let stmts = rreil!{ xor (lv), (v), [0xffffffff]:32; }?;
+    Ok((stmts, lv.into()))
+}
+
+/// Computes `a + b + carry` into `res:32`, the carry out into `carry_out:1` and the signed
+/// overflow into `overflow:1`.
+pub fn add_with_carry(a: Rvalue, b: Rvalue, carry: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        zext/64 a64:64, (a);
+        zext/64 b64:64, (b);
+        zext/64 c64:64, (carry);
+        add r64:64, a64:64, b64:64;
+        add r64:64, r64:64, c64:64;
+        mov res:32, r64:64;
+        mov carry_out:1, r64:1/32;
+        xor ov1:32, (a), (b);
+        xor ov1:32, ov1:32, [0xffffffff]:32;
+        xor ov2:32, (a), res:32;
+        and ov1:32, ov1:32, ov2:32;
+        mov overflow:1, ov1:1/31;
+    }
+}
+
+/// Sets the N and Z flags according to `res:32`.
+pub fn set_nz() -> Result<Vec<Statement>> {
+    rreil!{
+        cmplts N:1, res:32, [0]:32;
+        cmpeq Z:1, res:32, [0]:32;
+    }
+}
+
+/// Data processing instruction `op` as encoded in bits 24 to 21 of an ARM data processing
+/// instruction. Compare and test instructions ignore `rd` and always set flags. Logical
+/// instructions set the carry to `shifter_carry` if it is known.
+pub fn data_processing(op: u32, s: bool, rd: Lvalue, rn: Rvalue, op2: Rvalue, shifter_carry: Option<Rvalue>) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+    let zero = Rvalue::new_bit(0);
+    let one = Rvalue::new_bit(1);
+    let carry = rreil_rvalue!{ C:1 };
+    let arith = match op {
+        0 | 8 => {
+            stmts.extend(rreil!{ and res:32, (rn), (op2); }?);
+            false
+        }
+        1 | 9 => {
+            stmts.extend(rreil!{ xor res:32, (rn), (op2); }?);
+            false
+        }
+        2 | 10 | 6 => {
+            let (code, nb) = not("not_b", &op2)?;
+            stmts.extend(code);
+            stmts.extend(add_with_carry(rn, nb, if op == 6 { carry } else { one })?);
+            true
+        }
+        3 | 7 => {
+            let (code, na) = not("not_a", &rn)?;
+            stmts.extend(code);
+            stmts.extend(add_with_carry(na, op2, if op == 7 { carry } else { one })?);
+            true
+        }
+        4 | 11 | 5 => {
+            stmts.extend(add_with_carry(rn, op2, if op == 5 { carry } else { zero })?);
+            true
+        }
+        12 => {
+            stmts.extend(rreil!{ or res:32, (rn), (op2); }?);
+            false
+        }
+        13 => {
+            stmts.extend(rreil!{ mov res:32, (op2); }?);
+            false
+        }
+        14 => {
+            let (code, nb) = not("not_b", &op2)?;
+            stmts.extend(code);
+            stmts.extend(rreil!{ and res:32, (rn), (nb); }?);
+            false
+        }
+        15 => {
+            stmts.extend(rreil!{ xor res:32, (op2), [0xffffffff]:32; }?);
+            false
+        }
+        _ => return Err(format!("invalid data processing opcode {}", op).into()),
+    };
+    let is_test = op >= 8 && op <= 11;
+
+    if !is_test {
+        stmts.extend(rreil!{ mov (rd), res:32; }?);
+    }
+
+    if s || is_test {
+        stmts.extend(set_nz()?);
+
+        if arith {
+            stmts.extend(
+                rreil!{
+                    mov C:1, carry_out:1;
+                    mov V:1, overflow:1;
+                }?
+            );
+        } else if let Some(c) = shifter_carry {
+            stmts.extend(rreil!{ mov C:1, (c); }?);
+        }
+    }
+
+    Ok(stmts)
+}
+
+pub fn data_processing_name(op: u32) -> &'static str {
+    ["and", "eor", "sub", "rsb", "add", "adc", "sbc", "rsc", "tst", "teq", "cmp", "cmn", "orr", "mov", "bic", "mvn"][op as usize & 0xf]
+}
+
+pub fn shift_name(ty: u32) -> &'static str {
+    ["lsl", "lsr", "asr", "ror"][ty as usize & 3]
+}
+
+/// Shifts `rm` by an immediate `amount` as encoded in ARM data processing instructions. Returns
+/// the code, the shifted value and the shifter carry out if any.
+pub fn shift_imm(rm: Rvalue, ty: u32, amount: u32) -> Result<(Vec<Statement>, Rvalue, Option<Rvalue>)> {
+    let sh = rreil_rvalue!{ sh:32 };
+
+    match (ty, amount) {
+        (0, 0) => Ok((vec![], rm, None)),
+        (0, n) => {
+            let c = rm.extract(1, 32 - n as usize)?;
+            let amt = Rvalue::new_u32(n);
+            Ok((rreil!{ shl sh:32, (rm), (amt); }?, sh, Some(c)))
+        }
+        (1, 0) => {
+            let c = rm.extract(1, 31)?;
+            Ok((rreil!{ mov sh:32, [0]:32; }?, sh, Some(c)))
+        }
+        (1, n) => {
+            let c = rm.extract(1, n as usize - 1)?;
+            let amt = Rvalue::new_u32(n);
+            Ok((rreil!{ shr sh:32, (rm), (amt); }?, sh, Some(c)))
+        }
+        (2, n) => {
+            let n = if n == 0 { 32 } else { n };
+            let c = rm.extract(1, n as usize - 1)?;
+            let amt = Rvalue::new_u32(if n == 32 { 31 } else { n });
+            Ok((rreil!{ shrs sh:32, (rm), (amt); }?, sh, Some(c)))
+        }
+        (3, 0) => {
+            let c = rm.extract(1, 0)?;
+            let code = rreil!{
+                shr sh:32, (rm), [1]:32;
+                zext/32 rrx:32, C:1;
+                shl rrx:32, rrx:32, [31]:32;
+                or sh:32, sh:32, rrx:32;
+            }?;
+            Ok((code, sh, Some(c)))
+        }
+        (3, n) => {
+            let c = rm.extract(1, n as usize - 1)?;
+            let amt = Rvalue::new_u32(n);
+            let inv = Rvalue::new_u32(32 - n);
+            let code = rreil!{
+                shr sh:32, (rm), (amt);
+                shl rot:32, (rm), (inv);
+                or sh:32, sh:32, rot:32;
+            }?;
+            Ok((code, sh, Some(c)))
+        }
+        _ => Err(format!("invalid shift type {}", ty).into()),
+    }
+}
+
+/// Shifts `rm` by the lower byte of `rs`. The shifter carry out is not modelled.
+pub fn shift_reg(rm: Rvalue, ty: u32, rs: Rvalue) -> Result<(Vec<Statement>, Rvalue)> {
+    let sh = rreil_rvalue!{ sh:32 };
+    let code = match ty {
+        0 => {
+            rreil!{
+                and amt:32, (rs), [0xff]:32;
+                shl sh:32, (rm), amt:32;
+            }?
+        }
+        1 => {
+            rreil!{
+                and amt:32, (rs), [0xff]:32;
+                shr sh:32, (rm), amt:32;
+            }?
+        }
+        2 => {
+            rreil!{
+                and amt:32, (rs), [0xff]:32;
+                shrs sh:32, (rm), amt:32;
+            }?
+        }
+        3 => {
+            rreil!{
+                and amt:32, (rs), [0x1f]:32;
+                shr sh:32, (rm), amt:32;
+                sub inv:32, [32]:32, amt:32;
+                shl rot:32, (rm), inv:32;
+                or sh:32, sh:32, rot:32;
+            }?
+        }
+        _ => return Err(format!("invalid shift type {}", ty).into()),
+    };
+
+    Ok((code, sh))
+}
+
+/// Loads `size` bits from `addr` into `rt`, zero or sign extending the value to 32 bits.
+pub fn load(rt: Lvalue, addr: Rvalue, size: usize, signed: bool) -> Result<Vec<Statement>> {
+    match (size, signed) {
+        (32, _) => rreil!{ load/ram/le/32 (rt), (addr); },
+        (16, false) => {
+            rreil!{
+                load/ram/le/16 val:16, (addr);
+                zext/32 (rt), val:16;
+            }
+        }
+        (16, true) => {
+            rreil!{
+                load/ram/le/16 val:16, (addr);
+                sext/32 (rt), val:16;
+            }
+        }
+        (8, false) => {
+            rreil!{
+                load/ram/le/8 val:8, (addr);
+                zext/32 (rt), val:8;
+            }
+        }
+        (8, true) => {
+            rreil!{
+                load/ram/le/8 val:8, (addr);
+                sext/32 (rt), val:8;
+            }
+        }
+        _ => Err(format!("invalid load size {}", size).into()),
+    }
+}
+
+/// Stores the lower `size` bits of `rt` at `addr`.
+pub fn store(rt: Rvalue, addr: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    match size {
+        32 => rreil!{ store/ram/le/32 (rt), (addr); },
+        16 => {
+            rreil!{
+                mov val:16, (rt);
+                store/ram/le/16 val:16, (addr);
+            }
+        }
+        8 => {
+            rreil!{
+                mov val:8, (rt);
+                store/ram/le/8 val:8, (addr);
+            }
+        }
+        _ => Err(format!("invalid store size {}", size).into()),
+    }
+}
+
+/// Transfers the registers in `list` from/to the memory starting at `base`. Registers are
+/// transfered in ascending order. Loading the program counter sets `target:32`.
+pub fn load_store_multiple(load: bool, list: &[u32], base: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{ mov ptr:32, (base); }?;
+
+    for &r in list.iter() {
+        if load {
+            let rt = if r == PC { rreil_lvalue!{ target:32 } } else { reg_lv(r) };
+            stmts.extend(rreil!{ load/ram/le/32 (rt), ptr:32; }?);
+        } else {
+            let rt = reg(r);
+            stmts.extend(rreil!{ store/ram/le/32 (rt), ptr:32; }?);
+        }
+        stmts.extend(rreil!{ add ptr:32, ptr:32, [4]:32; }?);
+    }
+
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        for c in 0..15 {
+            let (code, guard) = condition(c).unwrap();
+            assert!(code.iter().all(|s| s.sanity_check().is_ok()));
+            assert!(c == 14 || guard != Guard::always());
+        }
+    }
+
+    #[test]
+    fn predicated_statements_are_sane() {
+        let (code, guard) = condition(12).unwrap();
+        let mut stmts = data_processing(4, true, reg_lv(0), reg(1), Rvalue::new_u32(4), None).unwrap();
+        stmts.extend(store(reg(0), reg(2), 8).unwrap());
+        let stmts = predicate(&guard, stmts).unwrap();
+
+        assert!(!code.is_empty());
+        for s in stmts.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+        assert!(stmts.iter().any(|s| if let Lvalue::Variable { ref name, .. } = s.assignee { name == "R0_old" } else { false }));
+    }
+
+    #[test]
+    fn shifts() {
+        for ty in 0..4 {
+            for amt in 0..32 {
+                let (code, _, _) = shift_imm(reg(3), ty, amt).unwrap();
+                assert!(code.iter().all(|s| s.sanity_check().is_ok()));
+            }
+        }
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(sign_extend(0xff, 8), -1);
+        assert_eq!(sign_extend(0x7f, 8), 127);
+        assert_eq!(sign_extend(0x800000, 24), -0x800000);
+    }
+}
@@ -0,0 +1,500 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Thumb (T16) and Thumb-2 (T32) instruction decoder.
+//!
+//! All 16-bit instructions are decoded. Of the 32-bit Thumb-2 instructions only branches, `PUSH.W`,
+//! `POP.W`, `MOVW`, `MOVT` and the immediate forms of `LDR.W` and `STR.W` are handled.
+
+use a32;
+use architecture::{Cpu, Mode};
+use byteorder::{ByteOrder, LittleEndian};
+use panopticon_core::{Guard, Mnemonic, Result, Rvalue};
+use semantic::*;
+
+/// Decodes the Thumb instruction at `addr`. Returns its length, the mnemonic and the outgoing
+/// jumps.
+pub fn read(cpu: &Cpu, buf: &[u8], addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    if buf.len() < 2 {
+        return Err("Thumb instruction truncated".into());
+    }
+    if addr & 1 != 0 {
+        return Err(format!("unaligned Thumb instruction at {:#x}", addr).into());
+    }
+
+    let hw = LittleEndian::read_u16(buf) as u32;
+
+    if hw >> 11 >= 0b11101 {
+        if buf.len() < 4 {
+            return Err("Thumb-2 instruction truncated".into());
+        }
+        let hw2 = LittleEndian::read_u16(&buf[2..]) as u32;
+        return read_wide(cpu, hw, hw2, addr);
+    }
+
+    let pc = addr + 4;
+    let low = |n: u32| (hw >> n) & 7;
+
+    match hw >> 12 {
+        0 | 1 => {
+            if hw >> 11 == 0b00011 {
+                // ADD/SUB register or 3-bit immediate
+                let op2 = if hw & 0x400 != 0 { Rvalue::new_u32(low(6)) } else { reg(low(6)) };
+                let op = if hw & 0x200 != 0 { 2 } else { 4 };
+                let stmts = data_processing(op, true, reg_lv(low(0)), reg(low(3)), op2.clone(), None)?;
+                let name = format!("{}s", data_processing_name(op));
+                finish(addr, 2, 14, &name, "{u}, {u}, {u}", vec![reg(low(0)), reg(low(3)), op2], stmts, Flow::Next)
+            } else {
+                // LSL/LSR/ASR immediate
+                let ty = (hw >> 11) & 3;
+                let amount = (hw >> 6) & 0x1f;
+                let (mut stmts, op2, carry) = shift_imm(reg(low(3)), ty, amount)?;
+                stmts.extend(data_processing(13, true, reg_lv(low(0)), Rvalue::Undefined, op2, carry)?);
+                let name = format!("{}s", shift_name(ty));
+                finish(addr, 2, 14, &name, "{u}, {u}, {u}", vec![reg(low(0)), reg(low(3)), Rvalue::new_u32(amount)], stmts, Flow::Next)
+            }
+        }
+        2 | 3 => {
+            // MOV/CMP/ADD/SUB 8-bit immediate
+            let rd = low(8);
+            let imm = Rvalue::new_u32(hw & 0xff);
+            let op = [13, 10, 4, 2][((hw >> 11) & 3) as usize];
+            let stmts = data_processing(op, true, reg_lv(rd), reg(rd), imm.clone(), None)?;
+            let name = if op == 10 { "cmp".to_string() } else { format!("{}s", data_processing_name(op)) };
+            finish(addr, 2, 14, &name, "{u}, {u}", vec![reg(rd), imm], stmts, Flow::Next)
+        }
+        4 => {
+            if hw >> 10 == 0b010000 {
+                alu(hw, addr)
+            } else if hw >> 10 == 0b010001 {
+                hi_register(hw, addr)
+            } else {
+                // LDR literal
+                let rt = low(8);
+                let lit = Rvalue::new_u32(((pc & !3) + (hw & 0xff) as u64 * 4) as u32);
+                let stmts = load(reg_lv(rt), lit.clone(), 32, false)?;
+                finish(addr, 2, 14, "ldr", "{u}, [{p:ram}]", vec![reg(rt), lit], stmts, Flow::Next)
+            }
+        }
+        5 => {
+            // load/store register offset
+            let (name, size, signed, is_load) = match (hw >> 9) & 7 {
+                0 => ("str", 32, false, false),
+                1 => ("strh", 16, false, false),
+                2 => ("strb", 8, false, false),
+                3 => ("ldrsb", 8, true, true),
+                4 => ("ldr", 32, false, true),
+                5 => ("ldrh", 16, false, true),
+                6 => ("ldrb", 8, false, true),
+                _ => ("ldrsh", 16, true, true),
+            };
+            let (rt, rn, rm) = (low(0), reg(low(3)), reg(low(6)));
+            let mut stmts = rreil!{ add address:32, (rn), (rm); }?;
+            if is_load {
+                stmts.extend(load(reg_lv(rt), rreil_rvalue!{ address:32 }, size, signed)?);
+            } else {
+                stmts.extend(store(reg(rt), rreil_rvalue!{ address:32 }, size)?);
+            }
+            finish(addr, 2, 14, name, "{u}, [{u}, {u}]", vec![reg(rt), rn, rm], stmts, Flow::Next)
+        }
+        6 | 7 | 8 => {
+            // load/store immediate offset
+            let (name, size) = match hw >> 11 {
+                0b01100 => ("str", 32),
+                0b01101 => ("ldr", 32),
+                0b01110 => ("strb", 8),
+                0b01111 => ("ldrb", 8),
+                0b10000 => ("strh", 16),
+                _ => ("ldrh", 16),
+            };
+            let off = Rvalue::new_u32(((hw >> 6) & 0x1f) * (size as u32 / 8));
+            load_store_imm(name, size, hw & 0x800 != 0, low(0), low(3), off, addr, 2)
+        }
+        9 => {
+            // SP relative load/store
+            let off = Rvalue::new_u32((hw & 0xff) * 4);
+            let name = if hw & 0x800 != 0 { "ldr" } else { "str" };
+            load_store_imm(name, 32, hw & 0x800 != 0, low(8), SP, off, addr, 2)
+        }
+        10 => {
+            // ADR and ADD Rd, SP, #imm
+            let rd = low(8);
+            let rd_lv = reg_lv(rd);
+            let imm = (hw & 0xff) * 4;
+            if hw & 0x800 == 0 {
+                let val = Rvalue::new_u32(((pc & !3) + imm as u64) as u32);
+                let stmts = rreil!{ mov (rd_lv), (val); }?;
+                finish(addr, 2, 14, "adr", "{u}, {p:ram}", vec![reg(rd), val], stmts, Flow::Next)
+            } else {
+                let sp = reg(SP);
+                let imm = Rvalue::new_u32(imm);
+                let stmts = rreil!{ add (rd_lv), (sp), (imm); }?;
+                finish(addr, 2, 14, "add", "{u}, {u}, {u}", vec![reg(rd), reg(SP), imm], stmts, Flow::Next)
+            }
+        }
+        11 => misc(hw, addr),
+        12 => {
+            // LDMIA/STMIA
+            let rn = low(8);
+            let list = register_list(hw & 0xff);
+            let is_load = hw & 0x800 != 0;
+            if list.is_empty() {
+                return Err(format!("empty register list in {:#06x}", hw).into());
+            }
+            let name = if is_load { "ldm" } else { "stm" };
+            // LDM doesn't write back if the base register is loaded
+            let wb = !is_load || !list.contains(&rn);
+            a32::transfer_multiple(name, is_load, false, true, wb, rn, &list, addr, 2, 14)
+        }
+        13 => {
+            let cond = (hw >> 8) & 0xf;
+            match cond {
+                0xe => finish(addr, 2, 14, "udf", "{u}", vec![Rvalue::new_u32(hw & 0xff)], vec![], Flow::Return),
+                0xf => {
+                    let imm = Rvalue::new_u32(hw & 0xff);
+                    let stmts = rreil!{ call (imm); }?;
+                    finish(addr, 2, 14, "svc", "{u}", vec![imm], stmts, Flow::Next)
+                }
+                _ => {
+                    let tgt = Rvalue::new_u32((pc as i64 + (sign_extend(hw & 0xff, 8) << 1)) as u32);
+                    finish(addr, 2, cond, "b", "{c:ram}", vec![tgt.clone()], vec![], Flow::Jump(tgt))
+                }
+            }
+        }
+        14 => {
+            let tgt = Rvalue::new_u32((pc as i64 + (sign_extend(hw & 0x7ff, 11) << 1)) as u32);
+            finish(addr, 2, 14, "b", "{c:ram}", vec![tgt.clone()], vec![], Flow::Jump(tgt))
+        }
+        _ => Err(format!("unsupported Thumb instruction {:#06x}", hw).into()),
+    }
+}
+
+fn load_store_imm(name: &str, size: usize, is_load: bool, rt: u32, rn: u32, off: Rvalue, addr: u64, len: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let base = reg(rn);
+    let mut stmts = rreil!{ add address:32, (base), (off); }?;
+
+    if is_load {
+        let dst = if rt == PC { rreil_lvalue!{ target:32 } } else { reg_lv(rt) };
+        stmts.extend(load(dst, rreil_rvalue!{ address:32 }, size, false)?);
+    } else {
+        stmts.extend(store(reg(rt), rreil_rvalue!{ address:32 }, size)?);
+    }
+
+    let flow = if is_load && rt == PC { Flow::Jump(rreil_rvalue!{ target:32 }) } else { Flow::Next };
+    finish(addr, len, 14, name, "{u}, [{u}, {u}]", vec![reg(rt), base, off], stmts, flow)
+}
+
+/// Data processing instructions with two low registers.
+fn alu(hw: u32, addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let op = (hw >> 6) & 0xf;
+    let rdn = hw & 7;
+    let rm = reg((hw >> 3) & 7);
+    let rd_lv = reg_lv(rdn);
+    let rd = reg(rdn);
+    let ops = vec![reg(rdn), rm.clone()];
+    let (name, stmts) = match op {
+        0 => ("ands", data_processing(0, true, rd_lv, rd, rm, None)?),
+        1 => ("eors", data_processing(1, true, rd_lv, rd, rm, None)?),
+        2 | 3 | 4 | 7 => {
+            let (ty, name) = match op {
+                2 => (0, "lsls"),
+                3 => (1, "lsrs"),
+                4 => (2, "asrs"),
+                _ => (3, "rors"),
+            };
+            let (mut stmts, sh) = shift_reg(rd, ty, rm)?;
+            stmts.extend(data_processing(13, true, rd_lv, Rvalue::Undefined, sh, None)?);
+            (name, stmts)
+        }
+        5 => ("adcs", data_processing(5, true, rd_lv, rd, rm, None)?),
+        6 => ("sbcs", data_processing(6, true, rd_lv, rd, rm, None)?),
+        8 => ("tst", data_processing(8, true, rd_lv, rd, rm, None)?),
+        9 => ("rsbs", data_processing(3, true, rd_lv, rm, Rvalue::new_u32(0), None)?),
+        10 => ("cmp", data_processing(10, true, rd_lv, rd, rm, None)?),
+        11 => ("cmn", data_processing(11, true, rd_lv, rd, rm, None)?),
+        12 => ("orrs", data_processing(12, true, rd_lv, rd, rm, None)?),
+        13 => {
+            let mut stmts = rreil!{ mul res:32, (rd), (rm); }?;
+            stmts.extend(rreil!{ mov (rd_lv), res:32; }?);
+            stmts.extend(set_nz()?);
+            ("muls", stmts)
+        }
+        14 => ("bics", data_processing(14, true, rd_lv, rd, rm, None)?),
+        _ => ("mvns", data_processing(15, true, rd_lv, rd, rm, None)?),
+    };
+
+    finish(addr, 2, 14, name, "{u}, {u}", ops, stmts, Flow::Next)
+}
+
+/// `ADD`, `CMP` and `MOV` with high registers and `BX`/`BLX` register.
+fn hi_register(hw: u32, addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let rd = ((hw >> 4) & 8) | (hw & 7);
+    let rm = (hw >> 3) & 0xf;
+    let pc = addr + 4;
+    let rm_rv = read_reg(rm, pc);
+
+    match (hw >> 8) & 3 {
+        0 | 2 => {
+            let op = if (hw >> 8) & 3 == 0 { 4 } else { 13 };
+            let dst = if rd == PC { rreil_lvalue!{ target:32 } } else { reg_lv(rd) };
+            let stmts = data_processing(op, false, dst, read_reg(rd, pc), rm_rv, None)?;
+            let flow = if rd != PC {
+                Flow::Next
+            } else if op == 13 && rm == LR {
+                Flow::Return
+            } else {
+                Flow::Jump(rreil_rvalue!{ target:32 })
+            };
+            finish(addr, 2, 14, data_processing_name(op), "{u}, {u}", vec![reg(rd), reg(rm)], stmts, flow)
+        }
+        1 => {
+            let stmts = data_processing(10, true, reg_lv(rd), read_reg(rd, pc), rm_rv, None)?;
+            finish(addr, 2, 14, "cmp", "{u}, {u}", vec![reg(rd), reg(rm)], stmts, Flow::Next)
+        }
+        _ => {
+            if hw & 0x80 != 0 {
+                let tgt = reg(rm);
+                let stmts = rreil!{ call (tgt); }?;
+                finish(addr, 2, 14, "blx", "{u}", vec![reg(rm)], stmts, Flow::Next)
+            } else if rm == LR {
+                finish(addr, 2, 14, "bx", "{u}", vec![reg(rm)], vec![], Flow::Return)
+            } else {
+                finish(addr, 2, 14, "bx", "{u}", vec![reg(rm)], vec![], Flow::Jump(rm_rv))
+            }
+        }
+    }
+}
+
+/// Miscellaneous 16-bit instructions.
+fn misc(hw: u32, addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let sp = reg(SP);
+    let sp_lv = reg_lv(SP);
+
+    if hw & 0xff00 == 0xb000 {
+        let imm = Rvalue::new_u32((hw & 0x7f) * 4);
+        if hw & 0x80 == 0 {
+            let stmts = rreil!{ add (sp_lv), (sp), (imm); }?;
+            finish(addr, 2, 14, "add", "{u}, {u}", vec![reg(SP), imm], stmts, Flow::Next)
+        } else {
+            let stmts = rreil!{ sub (sp_lv), (sp), (imm); }?;
+            finish(addr, 2, 14, "sub", "{u}, {u}", vec![reg(SP), imm], stmts, Flow::Next)
+        }
+    } else if hw & 0xf500 == 0xb100 {
+        // CBZ/CBNZ
+        let rn = reg(hw & 7);
+        let off = ((hw >> 3) & 0x1f) << 1 | ((hw >> 9) & 1) << 6;
+        let tgt = Rvalue::new_u32((addr + 4 + off as u64) as u32);
+        let next = Rvalue::new_u32((addr + 2) as u32);
+        let stmts = rreil!{ cmpeq zero:1, (rn), [0]:32; }?;
+        let taken = Guard::Predicate { flag: rreil_rvalue!{ zero:1 }, expected: hw & 0x800 == 0 };
+        let name = if hw & 0x800 == 0 { "cbz" } else { "cbnz" };
+        let mne = Mnemonic::from_parts(addr..addr + 2, name.to_string(), "{u}, {c:ram}", vec![rn, tgt.clone()], stmts)?;
+        Ok((2, mne, vec![(tgt, taken.clone()), (next, taken.negation())]))
+    } else if hw & 0xfe00 == 0xb400 {
+        let mut list = register_list(hw & 0xff);
+        if hw & 0x100 != 0 {
+            list.push(LR);
+        }
+        a32::transfer_multiple("push", false, true, false, true, SP, &list, addr, 2, 14)
+    } else if hw & 0xfe00 == 0xbc00 {
+        let mut list = register_list(hw & 0xff);
+        if hw & 0x100 != 0 {
+            list.push(PC);
+        }
+        a32::transfer_multiple("pop", true, false, true, true, SP, &list, addr, 2, 14)
+    } else if hw & 0xff00 == 0xb200 {
+        // SXTH, SXTB, UXTH and UXTB
+        let rd = reg_lv(hw & 7);
+        let rm = reg((hw >> 3) & 7);
+        let (name, stmts) = match (hw >> 6) & 3 {
+            0 => ("sxth", rreil!{ mov val:16, (rm); sext/32 (rd), val:16; }?),
+            1 => ("sxtb", rreil!{ mov val:8, (rm); sext/32 (rd), val:8; }?),
+            2 => ("uxth", rreil!{ mov val:16, (rm); zext/32 (rd), val:16; }?),
+            _ => ("uxtb", rreil!{ mov val:8, (rm); zext/32 (rd), val:8; }?),
+        };
+        finish(addr, 2, 14, name, "{u}, {u}", vec![reg(hw & 7), rm], stmts, Flow::Next)
+    } else if hw & 0xff00 == 0xbe00 {
+        finish(addr, 2, 14, "bkpt", "{u}", vec![Rvalue::new_u32(hw & 0xff)], vec![], Flow::Next)
+    } else if hw & 0xff00 == 0xbf00 {
+        if hw & 0xf == 0 {
+            finish(addr, 2, 14, "nop", "", vec![], vec![], Flow::Next)
+        } else {
+            finish(addr, 2, 14, "it", "{u}, {u}", vec![Rvalue::new_u32((hw >> 4) & 0xf), Rvalue::new_u32(hw & 0xf)], vec![], Flow::Next)
+        }
+    } else {
+        Err(format!("unsupported Thumb instruction {:#06x}", hw).into())
+    }
+}
+
+/// Decodes a 32-bit Thumb-2 instruction made up of the half words `hw1` and `hw2`.
+fn read_wide(cpu: &Cpu, hw1: u32, hw2: u32, addr: u64) -> Result<(u64, Mnemonic, Vec<(Rvalue, Guard)>)> {
+    let pc = addr + 4;
+
+    if hw1 >> 11 == 0b11110 && hw2 & 0x8000 != 0 {
+        let s = (hw1 >> 10) & 1;
+        let j1 = (hw2 >> 13) & 1;
+        let j2 = (hw2 >> 11) & 1;
+
+        match (hw2 >> 12) & 5 {
+            // B.W T4, BL and BLX
+            1 | 4 | 5 => {
+                let i1 = !(j1 ^ s) & 1;
+                let i2 = !(j2 ^ s) & 1;
+                let imm = (s << 24) | (i1 << 23) | (i2 << 22) | ((hw1 & 0x3ff) << 12) | ((hw2 & 0x7ff) << 1);
+                let off = sign_extend(imm, 25);
+
+                match (hw2 >> 12) & 5 {
+                    1 => {
+                        let tgt = Rvalue::new_u32((pc as i64 + off) as u32);
+                        finish(addr, 4, 14, "b.w", "{c:ram}", vec![tgt.clone()], vec![], Flow::Jump(tgt))
+                    }
+                    5 => {
+                        let tgt = Rvalue::new_u32((pc as i64 + off) as u32);
+                        let stmts = rreil!{ call (tgt); }?;
+                        finish(addr, 4, 14, "bl", "{c:ram}", vec![tgt], stmts, Flow::Next)
+                    }
+                    _ => {
+                        let tgt_addr = ((pc & !3) as i64 + (off & !3)) as u64 & 0xffff_ffff;
+                        let tgt = Rvalue::new_u32(tgt_addr as u32);
+                        let stmts = rreil!{ call (tgt); }?;
+
+                        cpu.switch_mode(tgt_addr, Mode::Arm)?;
+                        finish(addr, 4, 14, "blx", "{c:ram}", vec![tgt], stmts, Flow::Next)
+                    }
+                }
+            }
+            // B.W T3 (conditional)
+            0 if (hw1 >> 7) & 7 != 7 => {
+                let cond = (hw1 >> 6) & 0xf;
+                let imm = (s << 20) | (j2 << 19) | (j1 << 18) | ((hw1 & 0x3f) << 12) | ((hw2 & 0x7ff) << 1);
+                let tgt = Rvalue::new_u32((pc as i64 + sign_extend(imm, 21)) as u32);
+                finish(addr, 4, cond, "b.w", "{c:ram}", vec![tgt.clone()], vec![], Flow::Jump(tgt))
+            }
+            _ => Err(format!("unsupported Thumb-2 instruction {:#06x} {:#06x}", hw1, hw2).into()),
+        }
+    } else if hw1 & 0xfb70 == 0xf240 && hw2 & 0x8000 == 0 {
+        // MOVW/MOVT
+        let imm = ((hw1 & 0xf) << 12) | (((hw1 >> 10) & 1) << 11) | (((hw2 >> 12) & 7) << 8) | (hw2 & 0xff);
+        a32::move_wide((hw2 >> 8) & 0xf, imm, hw1 & 0x80 != 0, addr, 4, 14)
+    } else if hw1 == 0xe92d && hw2 & 0xa000 == 0 {
+        a32::transfer_multiple("push.w", false, true, false, true, SP, &register_list(hw2), addr, 4, 14)
+    } else if hw1 == 0xe8bd && hw2 & 0x2000 == 0 {
+        a32::transfer_multiple("pop.w", true, false, true, true, SP, &register_list(hw2), addr, 4, 14)
+    } else if hw1 & 0xffe0 == 0xf8c0 {
+        // LDR.W/STR.W with 12-bit immediate
+        let is_load = hw1 & 0x10 != 0;
+        let rn = hw1 & 0xf;
+        let rt = (hw2 >> 12) & 0xf;
+        let off = Rvalue::new_u32(hw2 & 0xfff);
+
+        if rn == PC {
+            Err(format!("unsupported Thumb-2 instruction {:#06x} {:#06x}", hw1, hw2).into())
+        } else {
+            load_store_imm(if is_load { "ldr.w" } else { "str.w" }, 32, is_load, rt, rn, off, addr, 4)
+        }
+    } else {
+        Err(format!("unsupported Thumb-2 instruction {:#06x} {:#06x}", hw1, hw2).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Arm;
+    use panopticon_core::golden;
+
+    fn decode(cpu: &Cpu, hws: &[u16], addr: u64) -> (u64, Mnemonic, Vec<(Rvalue, Guard)>) {
+        let mut buf = vec![0u8; hws.len() * 2];
+        for (i, hw) in hws.iter().enumerate() {
+            LittleEndian::write_u16(&mut buf[i * 2..], *hw);
+        }
+        read(cpu, &buf, addr).unwrap()
+    }
+
+    #[test]
+    fn narrow() {
+        let cpu = Cpu::new(Mode::Thumb);
+
+        // movs r0, #42
+        let (len, mne, _) = decode(&cpu, &[0x202a], 0);
+        assert_eq!(len, 2);
+        assert_eq!(mne.opcode, "movs");
+        assert_eq!(mne.operands, vec![reg(0), Rvalue::new_u32(42)]);
+
+        // push {r4, lr}
+        let (_, mne, _) = decode(&cpu, &[0xb510], 0);
+        assert_eq!(mne.opcode, "push");
+        assert_eq!(mne.operands, vec![reg(4), reg(14)]);
+
+        // pop {r4, pc}
+        let (_, mne, jmp) = decode(&cpu, &[0xbd10], 0);
+        assert_eq!(mne.opcode, "pop");
+        assert!(jmp.is_empty());
+
+        // beq +4 @ 0x10
+        let (_, mne, jmp) = decode(&cpu, &[0xd002], 0x10);
+        assert_eq!(mne.opcode, "beq");
+        assert_eq!(jmp[0].0, Rvalue::new_u32(0x18));
+        assert_eq!(jmp[1].0, Rvalue::new_u32(0x12));
+    }
+
+    #[test]
+    fn cbz() {
+        let cpu = Cpu::new(Mode::Thumb);
+
+        // cbz r0, +8 @ 0x20
+        let (_, mne, jmp) = decode(&cpu, &[0xb120], 0x20);
+        assert_eq!(mne.opcode, "cbz");
+        assert_eq!(jmp[0].0, Rvalue::new_u32(0x2c));
+        assert_eq!(jmp[1].0, Rvalue::new_u32(0x22));
+    }
+
+    #[test]
+    fn wide_branches() {
+        let cpu = Cpu::new(Mode::Thumb);
+
+        // bl +0x100 @ 0x1000
+        let (len, mne, jmp) = decode(&cpu, &[0xf000, 0xf880], 0x1000);
+        assert_eq!(len, 4);
+        assert_eq!(mne.opcode, "bl");
+        assert_eq!(mne.operands, vec![Rvalue::new_u32(0x1104)]);
+        assert_eq!(jmp, vec![(Rvalue::new_u32(0x1004), Guard::always())]);
+
+        // blx +0x100 @ 0x1002
+        let (_, mne, _) = decode(&cpu, &[0xf000, 0xe880], 0x1002);
+        assert_eq!(mne.opcode, "blx");
+        assert_eq!(mne.operands, vec![Rvalue::new_u32(0x1104)]);
+        assert_eq!(cpu.mode_at(0x1104).ok(), Some(Mode::Arm));
+    }
+
+    #[test]
+    fn bx_lr_returns() {
+        let (_, mne, jmp) = decode(&Cpu::new(Mode::Thumb), &[0x4770], 0);
+        assert_eq!(mne.opcode, "bx");
+        assert!(jmp.is_empty());
+    }
+
+    #[test]
+    fn ldm_writeback() {
+        let listing = |bytes: &[u8]| golden::render::<Arm>(bytes, 0, &Cpu::new(Mode::Thumb))[0].clone();
+
+        // the base register is loaded instead
+        assert_eq!(listing(&[0x1c, 0xcc]), "ldm r4, {r2, r3, r4}");
+        assert_eq!(listing(&[0x0c, 0xcc]), "ldm r4!, {r2, r3}");
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+extern crate panopticon_core;
+extern crate panopticon_arm;
+extern crate panopticon_graph_algos;
+
+use panopticon_arm::{Arm, Cpu, Mode};
+use panopticon_core::{Function, MappingSymbol, Region};
+use panopticon_graph_algos::{EdgeListGraphTrait, VertexListGraphTrait};
+use std::collections::BTreeMap;
+
+// ARM function at 0x0 calling a Thumb function at 0xc
+fn interworking() -> Region {
+    Region::wrap(
+        "ram".to_string(),
+        vec![
+            0x10, 0x40, 0x2d, 0xe9, // push {r4, lr}
+            0x00, 0x00, 0x00, 0xfa, // blx 0xc
+            0x10, 0x80, 0xbd, 0xe8, // pop {r4, pc}
+            0x01, 0x20, // movs r0, #1
+            0x00, 0x28, // cmp r0, #0
+            0x00, 0xd0, // beq 0x14
+            0x01, 0x30, // adds r0, #1
+            0x70, 0x47, // bx lr
+        ],
+    )
+}
+
+#[test]
+fn arm_function() {
+    let reg = interworking();
+    let func = Function::new::<Arm>(0, &reg, None, Cpu::new(Mode::Arm)).unwrap();
+
+    assert_eq!(func.cfg().num_vertices(), 1);
+    assert_eq!(func.cfg().num_edges(), 0);
+    assert_eq!(func.end(), 0xc);
+    assert_eq!(func.collect_call_addresses(), vec![0xc]);
+    assert_eq!(func.entry_point().mode, Some("arm".to_string()));
+}
+
+#[test]
+fn blx_switches_mode() {
+    let reg = interworking();
+    let cpu = Cpu::new(Mode::Arm);
+
+    Function::new::<Arm>(0, &reg, None, cpu.clone()).unwrap();
+
+    let func = Function::new::<Arm>(0xc, &reg, None, cpu).unwrap();
+    let mut starts = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    starts.sort();
+
+    assert_eq!(starts, vec![0xc, 0x12, 0x14]);
+    assert_eq!(func.cfg().num_edges(), 3);
+    assert_eq!(func.end(), 0x16);
+    assert!(func.basic_blocks().all(|bb| bb.mode == Some("thumb".to_string())));
+}
+
+#[test]
+fn mapping_symbols() {
+    let reg = interworking();
+    let mut syms = BTreeMap::new();
+
+    syms.insert(0x0, MappingSymbol::Arm);
+    syms.insert(0xc, MappingSymbol::Thumb);
+    syms.insert(0x16, MappingSymbol::Data);
+
+    let cpu = Cpu::new(Mode::Arm).with_mapping_symbols(&syms);
+    let func = Function::new::<Arm>(0xc, &reg, None, cpu.clone()).unwrap();
+
+    assert_eq!(func.basic_blocks().count(), 3);
+    assert_eq!(cpu.mode_at(0x4).ok(), Some(Mode::Arm));
+    assert_eq!(cpu.mode_at(0x14).ok(), Some(Mode::Thumb));
+    assert!(cpu.mode_at(0x16).is_err());
+}
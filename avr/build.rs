@@ -0,0 +1,31 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+extern crate panopticon_isa_gen;
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("skip.rs");
+
+    if let Err(e) = panopticon_isa_gen::generate(Path::new("isa/skip.toml"), &out) {
+        panic!("{}", e);
+    }
+    println!("cargo:rerun-if-changed=isa");
+}
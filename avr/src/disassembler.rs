@@ -45,7 +45,7 @@ impl Architecture for Avr {
     }
 }
 
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,Hash)]
 pub struct Mcu {
     pub pc_bits: usize,
     ///< width of the program counter in bits
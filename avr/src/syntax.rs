@@ -23,13 +23,7 @@ use semantic::*;
 use std::sync::Arc;
 
 pub fn disassembler() -> Arc<Disassembler<Avr>> {
-    let skip = new_disassembler!(Avr =>
-        [ "1111 110 sr@..... 0 sb@..." ] = skip("sbrc",false),
-        [ "1111 111 sr@..... 0 sb@..." ] = skip("sbrs",true),
-        [ "000100 cr@. cd@..... cr@...." ] = cpse,
-        [ "1001 1001 sA@..... sb@..." ] = skip("sbic",false),
-        [ "1001 1011 sA@..... sb@..." ] = skip("sbis",true)
-    );
+    let skip = include!(concat!(env!("OUT_DIR"), "/skip.rs"));
 
     let main = new_disassembler!(Avr =>
         [ "000111 R@. D@..... R@...." ] = binary("adc",adc),
@@ -22,7 +22,7 @@ extern crate panopticon_graph_algos;
 extern crate env_logger;
 
 use panopticon_avr::{Avr, Mcu};
-use panopticon_core::{ControlFlowTarget, Function, Region, loader};
+use panopticon_core::{ControlFlowTarget, Function, Region, golden, loader};
 use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, VertexListGraphTrait};
 
 use std::path::Path;
@@ -80,3 +80,8 @@ fn avr_elf() {
     let proj = loader::load(Path::new("../test-data/hello-world")).ok();
     assert!(proj.is_some());
 }
+
+#[test]
+fn avr_golden() {
+    golden::run::<Avr>(Path::new("../test-data/golden/avr.txt"), Mcu::atmega88()).unwrap();
+}
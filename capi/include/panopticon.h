@@ -0,0 +1,85 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*
+ * C interface to Panopticon. Link against libpanopticon_capi.
+ *
+ * Projects own everything reachable from them. Function, basic block,
+ * mnemonic and statement pointers stay valid until the project is freed.
+ * Strings returned as `char *` belong to the caller and must be freed with
+ * panop_string_free(). Functions returning pointers return NULL on failure,
+ * panop_last_error() tells why.
+ */
+
+#ifndef PANOPTICON_H
+#define PANOPTICON_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct PanopProject panop_project;
+typedef struct PanopFunction panop_function;
+typedef struct BasicBlock panop_basic_block;
+typedef struct Mnemonic panop_mnemonic;
+typedef struct Statement panop_statement;
+
+/* Projects */
+panop_project *panop_project_open(const char *path);
+panop_project *panop_project_load(const char *path);
+void panop_project_free(panop_project *proj);
+char *panop_project_name(const panop_project *proj);
+
+/* Errors and strings */
+const char *panop_last_error(void);
+void panop_string_free(char *s);
+
+/* Functions, ordered by address */
+size_t panop_function_count(const panop_project *proj);
+const panop_function *panop_function_get(const panop_project *proj, size_t idx);
+const panop_function *panop_function_find(const panop_project *proj, uint64_t address);
+uint64_t panop_function_start(const panop_function *func);
+char *panop_function_name(const panop_function *func);
+size_t panop_function_block_count(const panop_function *func);
+const panop_basic_block *panop_function_block_get(const panop_function *func, size_t idx);
+
+/* Basic blocks, [start, end) */
+uint64_t panop_block_start(const panop_basic_block *bb);
+uint64_t panop_block_end(const panop_basic_block *bb);
+size_t panop_block_mnemonic_count(const panop_basic_block *bb);
+const panop_mnemonic *panop_block_mnemonic_get(const panop_basic_block *bb, size_t idx);
+
+/* Mnemonics, [start, end) */
+uint64_t panop_mnemonic_start(const panop_mnemonic *mne);
+uint64_t panop_mnemonic_end(const panop_mnemonic *mne);
+char *panop_mnemonic_opcode(const panop_mnemonic *mne);
+char *panop_mnemonic_format(const panop_function *func, const panop_mnemonic *mne);
+size_t panop_mnemonic_statement_count(const panop_mnemonic *mne);
+const panop_statement *panop_mnemonic_statement_get(const panop_mnemonic *mne, size_t idx);
+
+/* IL statements */
+char *panop_statement_format(const panop_statement *stmt);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
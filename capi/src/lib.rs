@@ -0,0 +1,404 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! C interface to Panopticon.
+//!
+//! Exposes loading and disassembling executables, and iterating the functions, basic blocks,
+//! mnemonics and IL statements of the result through a stable C ABI. The declarations are in
+//! `include/panopticon.h`.
+//!
+//! A project returned by [`panop_project_open`] or [`panop_project_load`] owns everything
+//! reachable from it. Function, basic block, mnemonic and statement pointers are borrowed from
+//! the project and are valid until it is freed with [`panop_project_free`]. Strings returned by
+//! `panop_*` functions are owned by the caller and must be freed using [`panop_string_free`].
+//!
+//! Functions returning pointers return NULL on failure. The reason can be retrieved with
+//! [`panop_last_error`]. Functions and basic blocks are ordered by address.
+//!
+//! [`panop_project_open`]: fn.panop_project_open.html
+//! [`panop_project_load`]: fn.panop_project_load.html
+//! [`panop_project_free`]: fn.panop_project_free.html
+//! [`panop_string_free`]: fn.panop_string_free.html
+//! [`panop_last_error`]: fn.panop_last_error.html
+
+#![warn(missing_docs)]
+
+extern crate panopticon_core;
+extern crate panopticon_analysis;
+extern crate panopticon_graph_algos;
+
+use panopticon_analysis::{Options, driver};
+use panopticon_core::{BasicBlock, Function, Mnemonic, Program, Project, Statement, listing};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic;
+use std::path::Path;
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Remembers `msg` as the reason the last call failed.
+fn set_error(msg: String) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(msg.replace('\0', "")).ok());
+}
+
+/// Copies `s` into a string owned by the caller.
+fn to_c(s: &str) -> *mut c_char {
+    match CString::new(s.replace('\0', "")) {
+        Ok(s) => s.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Panopticon session: a loaded executable and the code found in it.
+pub struct PanopProject {
+    project: Project,
+    functions: Vec<PanopFunction>,
+}
+
+/// Function of a `PanopProject`.
+pub struct PanopFunction {
+    project: *const Project,
+    program: *const Program,
+    function: *const Function,
+    blocks: Vec<*const BasicBlock>,
+}
+
+impl PanopProject {
+    fn new(project: Project) -> *mut PanopProject {
+        let mut ret = Box::new(PanopProject { project: project, functions: vec![] });
+        let mut functions = vec![];
+
+        for prog in ret.project.code.iter() {
+            for func in prog.functions() {
+                let mut blocks = func.basic_blocks().collect::<Vec<_>>();
+
+                blocks.sort_by_key(|bb| bb.area.start);
+                functions.push(
+                    PanopFunction {
+                        project: &ret.project,
+                        program: prog,
+                        function: func,
+                        blocks: blocks.into_iter().map(|bb| bb as *const BasicBlock).collect(),
+                    }
+                );
+            }
+        }
+
+        functions.sort_by_key(|f| unsafe { (*f.function).start() });
+        ret.functions = functions;
+        Box::into_raw(ret)
+    }
+}
+
+/// Converts the path `path` and runs `f` with it, catching panics.
+unsafe fn with_path<F: FnOnce(&Path) -> Result<Project, String> + panic::UnwindSafe>(path: *const c_char, f: F) -> *mut PanopProject {
+    if path.is_null() {
+        set_error("path is NULL".to_string());
+        return ptr::null_mut();
+    }
+
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(p) => p.to_string(),
+        Err(_) => {
+            set_error("path is not valid UTF-8".to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    match panic::catch_unwind(move || f(Path::new(&path))) {
+        Ok(Ok(proj)) => PanopProject::new(proj),
+        Ok(Err(e)) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_error("internal error".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Loads the executable at `path` and disassembles it. Returns NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn panop_project_open(path: *const c_char) -> *mut PanopProject {
+    with_path(path, |p| driver::analyze(p, &Options::new()).map_err(|e| e.to_string()))
+}
+
+/// Loads a session saved by Panopticon. Returns NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn panop_project_load(path: *const c_char) -> *mut PanopProject {
+    with_path(path, |p| Project::open(p).map_err(|e| e.to_string()))
+}
+
+/// Frees `proj` and everything borrowed from it.
+#[no_mangle]
+pub unsafe extern "C" fn panop_project_free(proj: *mut PanopProject) {
+    if !proj.is_null() {
+        drop(Box::from_raw(proj));
+    }
+}
+
+/// Returns the name of `proj`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_project_name(proj: *const PanopProject) -> *mut c_char {
+    match proj.as_ref() {
+        Some(p) => to_c(&p.project.name),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the message describing why the last call on this thread failed, or NULL. The string
+/// is owned by the library and valid until the next failing call.
+#[no_mangle]
+pub extern "C" fn panop_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()))
+}
+
+/// Frees a string returned by a `panop_*` function.
+#[no_mangle]
+pub unsafe extern "C" fn panop_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Returns the number of functions in `proj`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_count(proj: *const PanopProject) -> usize {
+    proj.as_ref().map(|p| p.functions.len()).unwrap_or(0)
+}
+
+/// Returns the `idx`th function of `proj`, or NULL if out of range.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_get(proj: *const PanopProject, idx: usize) -> *const PanopFunction {
+    match proj.as_ref().and_then(|p| p.functions.get(idx)) {
+        Some(f) => f,
+        None => ptr::null(),
+    }
+}
+
+/// Returns the function of `proj` starting at `address`, or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_find(proj: *const PanopProject, address: u64) -> *const PanopFunction {
+    match proj.as_ref().and_then(|p| p.functions.iter().find(|f| (*f.function).start() == address)) {
+        Some(f) => f,
+        None => ptr::null(),
+    }
+}
+
+/// Returns the address of the entry point of `func`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_start(func: *const PanopFunction) -> u64 {
+    func.as_ref().map(|f| (*f.function).start()).unwrap_or(0)
+}
+
+/// Returns the name of `func`, demangled if possible.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_name(func: *const PanopFunction) -> *mut c_char {
+    match func.as_ref() {
+        Some(f) => to_c(&(*f.function).display_name()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the number of basic blocks of `func`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_block_count(func: *const PanopFunction) -> usize {
+    func.as_ref().map(|f| f.blocks.len()).unwrap_or(0)
+}
+
+/// Returns the `idx`th basic block of `func`, or NULL if out of range.
+#[no_mangle]
+pub unsafe extern "C" fn panop_function_block_get(func: *const PanopFunction, idx: usize) -> *const BasicBlock {
+    func.as_ref().and_then(|f| f.blocks.get(idx).cloned()).unwrap_or(ptr::null())
+}
+
+/// Returns the address of the first byte of `bb`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_block_start(bb: *const BasicBlock) -> u64 {
+    bb.as_ref().map(|bb| bb.area.start).unwrap_or(0)
+}
+
+/// Returns the address after the last byte of `bb`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_block_end(bb: *const BasicBlock) -> u64 {
+    bb.as_ref().map(|bb| bb.area.end).unwrap_or(0)
+}
+
+/// Returns the number of mnemonics in `bb`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_block_mnemonic_count(bb: *const BasicBlock) -> usize {
+    bb.as_ref().map(|bb| bb.mnemonics.len()).unwrap_or(0)
+}
+
+/// Returns the `idx`th mnemonic of `bb`, or NULL if out of range.
+#[no_mangle]
+pub unsafe extern "C" fn panop_block_mnemonic_get(bb: *const BasicBlock, idx: usize) -> *const Mnemonic {
+    match bb.as_ref().and_then(|bb| bb.mnemonics.get(idx)) {
+        Some(m) => m,
+        None => ptr::null(),
+    }
+}
+
+/// Returns the address of the first byte of `mne`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_mnemonic_start(mne: *const Mnemonic) -> u64 {
+    mne.as_ref().map(|m| m.area.start).unwrap_or(0)
+}
+
+/// Returns the address after the last byte of `mne`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_mnemonic_end(mne: *const Mnemonic) -> u64 {
+    mne.as_ref().map(|m| m.area.end).unwrap_or(0)
+}
+
+/// Returns the opcode of `mne`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_mnemonic_opcode(mne: *const Mnemonic) -> *mut c_char {
+    match mne.as_ref() {
+        Some(m) => to_c(&m.opcode),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns opcode and operands of `mne`, a mnemonic of `func`, with code pointers resolved to
+/// symbol names as in `listing::function`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_mnemonic_format(func: *const PanopFunction, mne: *const Mnemonic) -> *mut c_char {
+    match (func.as_ref(), mne.as_ref()) {
+        (Some(f), Some(m)) => to_c(&listing::instruction(&*f.project, &*f.program, m)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Returns the number of IL statements `mne` is translated into.
+#[no_mangle]
+pub unsafe extern "C" fn panop_mnemonic_statement_count(mne: *const Mnemonic) -> usize {
+    mne.as_ref().map(|m| m.instructions.len()).unwrap_or(0)
+}
+
+/// Returns the `idx`th IL statement of `mne`, or NULL if out of range.
+#[no_mangle]
+pub unsafe extern "C" fn panop_mnemonic_statement_get(mne: *const Mnemonic, idx: usize) -> *const Statement {
+    match mne.as_ref().and_then(|m| m.instructions.get(idx)) {
+        Some(s) => s,
+        None => ptr::null(),
+    }
+}
+
+/// Returns `stmt` in RREIL syntax, e.g. `add RAX:64, RAX:64, 0x1:64`.
+#[no_mangle]
+pub unsafe extern "C" fn panop_statement_format(stmt: *const Statement) -> *mut c_char {
+    match stmt.as_ref() {
+        Some(s) => to_c(&s.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: *mut c_char) -> String {
+        let ret = unsafe { CStr::from_ptr(s).to_str().unwrap().to_string() };
+        unsafe { panop_string_free(s) };
+        ret
+    }
+
+    /*
+     * 0x100: add rax, 1
+     * 0x104: ret
+     */
+    fn project() -> *mut PanopProject {
+        use panopticon_core::{ControlFlowGraph, ControlFlowTarget, Guard, Lvalue, Operation, Region, Rvalue};
+        use panopticon_graph_algos::MutableGraphTrait;
+
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let rax = Lvalue::Variable { name: "RAX".into(), size: 64, subscript: None };
+        let add = Statement { op: Operation::Add(rax.clone().into(), Rvalue::new_u64(1)), assignee: rax.clone() };
+        let ops = vec![rax.into(), Rvalue::new_u64(1)];
+        let mne = Mnemonic::new(0x100..0x104, "add".to_string(), "{u}, {u}".to_string(), ops.iter(), vec![add].iter()).ok().unwrap();
+        let ret = Mnemonic::new(0x104..0x105, "ret".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let b1 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![ret])));
+        let mut func = Function::undefined(0x100, None, proj.region(), Some("main".to_string()));
+        let mut prog = Program::new("prog");
+
+        cfg.add_edge(Guard::True, b0, b1);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        prog.insert(func);
+        proj.code.push(prog);
+        PanopProject::new(proj)
+    }
+
+    #[test]
+    fn iterate() {
+        unsafe {
+            let proj = project();
+
+            assert_eq!(string(panop_project_name(proj)), "test");
+            assert_eq!(panop_function_count(proj), 1);
+            assert!(panop_function_get(proj, 1).is_null());
+
+            let func = panop_function_get(proj, 0);
+
+            assert_eq!(panop_function_find(proj, 0x100), func);
+            assert!(panop_function_find(proj, 0x104).is_null());
+            assert_eq!(panop_function_start(func), 0x100);
+            assert_eq!(string(panop_function_name(func)), "main");
+            assert_eq!(panop_function_block_count(func), 2);
+
+            let bb = panop_function_block_get(func, 0);
+
+            assert_eq!((panop_block_start(bb), panop_block_end(bb)), (0x100, 0x104));
+            assert_eq!(panop_block_start(panop_function_block_get(func, 1)), 0x104);
+            assert_eq!(panop_block_mnemonic_count(bb), 1);
+
+            let mne = panop_block_mnemonic_get(bb, 0);
+
+            assert_eq!((panop_mnemonic_start(mne), panop_mnemonic_end(mne)), (0x100, 0x104));
+            assert_eq!(string(panop_mnemonic_opcode(mne)), "add");
+            assert_eq!(string(panop_mnemonic_format(func, mne)), "add rax, 0x1");
+            assert_eq!(panop_mnemonic_statement_count(mne), 1);
+            assert!(panop_mnemonic_statement_get(mne, 1).is_null());
+            assert_eq!(string(panop_statement_format(panop_mnemonic_statement_get(mne, 0))), "add RAX:64, RAX:64, 0x1:64");
+
+            panop_project_free(proj);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let path = CString::new("../test-data/does-not-exist").unwrap();
+
+        unsafe {
+            assert!(panop_project_open(path.as_ptr()).is_null());
+            assert!(!panop_last_error().is_null());
+            assert!(panop_project_load(ptr::null()).is_null());
+            assert_eq!(CStr::from_ptr(panop_last_error()).to_str().unwrap(), "path is NULL");
+            assert_eq!(panop_function_count(ptr::null()), 0);
+            assert!(panop_function_name(ptr::null()).is_null());
+        }
+    }
+}
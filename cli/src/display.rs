@@ -2,7 +2,7 @@ use std::io::Write;
 use termcolor::WriteColor;
 use termcolor::Color::*;
 
-use panopticon_core::{Function, BasicBlock, Mnemonic, MnemonicFormatToken, Operation, Program, Rvalue, Result, Statement};
+use panopticon_core::{Function, BasicBlock, Mnemonic, MnemonicFormatToken, Operation, Program, Rvalue, Result, Statement, demangle};
 
 macro_rules! color_bold {
     ($fmt:ident, $color:ident, $str:expr) => ({
@@ -21,13 +21,13 @@ macro_rules! color {
 }
 
 /// Prints a sorted-by-start list of the RREIL implementing each mnemonic in a basic block, as well as phi functions and init code
-pub fn print_rreil<W: Write + WriteColor>(fmt: &mut W, bbs: &[&BasicBlock]) -> Result<()> {
+pub fn print_rreil<W: Write + WriteColor>(fmt: &mut W, function: &Function, bbs: &[&BasicBlock]) -> Result<()> {
     color_bold!(fmt, White, "RREIL")?;
     writeln!(fmt, ":")?;
     for bb in bbs {
-        for mnemonic in bb.mnemonics() {
+        for (mnemonic, statements) in function.mnemonics(bb) {
             print_address_and_mnemonic(fmt, mnemonic)?;
-            for statement in &mnemonic.instructions {
+            for statement in statements {
                 print_statement(fmt, statement)?;
             }
         }
@@ -326,7 +326,7 @@ pub fn print_statement<W: Write + WriteColor>(fmt: &mut W, statement: &Statement
 /// Prints the function in a human readable format, using `program`, with colors
 pub fn print_function<W: Write + WriteColor>(fmt: &mut W, function: &Function, bbs: &[&BasicBlock], program: &Program) -> Result<()> {
     write!(fmt, "{:0>8x} <", function.start())?;
-    color_bold!(fmt, Yellow, function.name)?;
+    color_bold!(fmt, Yellow, function.display_name())?;
     writeln!(fmt, ">:")?;
     for bb in bbs {
         print_basic_block(fmt, &bb, program)?;
@@ -371,7 +371,7 @@ pub fn print_mnemonic<W: Write + WriteColor>(fmt: &mut W, mnemonic: &Mnemonic, p
                             color!(fmt, White, format!("{:x}", (val as i64).wrapping_neg()))?;
                         }
                     },
-                    Some(&Rvalue::Variable{ ref name, subscript: Some(ref _subscript),.. }) => {
+                    Some(&Rvalue::Variable{ ref name, .. }) => {
                         color_bold!(fmt, White, &name.to_lowercase())?;
                     },
                     _ => {
@@ -387,12 +387,22 @@ pub fn print_mnemonic<W: Write + WriteColor>(fmt: &mut W, mnemonic: &Mnemonic, p
                                 let res = 1u64 << s;
                                 c % res
                             } else { c };
+                        // Symbol of the relocation applied to this instruction, if any
+                        let symbol = program
+                            .and_then(|p| p.relocation(&mnemonic.area))
+                            .and_then(|r| r.symbol.as_ref())
+                            .map(|s| demangle(s).unwrap_or_else(|| s.clone()));
                         if is_code {
                             if let Some(program) = program {
                                 if let Some(function) = program.find_function_by(|f| { f.start() == val }) {
                                     color!(fmt, Red, format!("{:x}",val))?;
                                     write!(fmt, " <", )?;
-                                    color_bold!(fmt, Yellow, function.name)?;
+                                    color_bold!(fmt, Yellow, function.display_name())?;
+                                    write!(fmt, ">")?;
+                                } else if let Some(ref symbol) = symbol {
+                                    color_bold!(fmt, Magenta, format!("{:x}",val))?;
+                                    write!(fmt, " <", )?;
+                                    color_bold!(fmt, Yellow, symbol)?;
                                     write!(fmt, ">")?;
                                 } else {
                                     color_bold!(fmt, Magenta, format!("{:x}",val))?;
@@ -400,6 +410,11 @@ pub fn print_mnemonic<W: Write + WriteColor>(fmt: &mut W, mnemonic: &Mnemonic, p
                             } else {
                                 write!(fmt, "{}", format!("{:#x}",val))?;
                             }
+                        } else if let Some(ref symbol) = symbol {
+                            write!(fmt, "{}", format!("{:#x}",val))?;
+                            write!(fmt, " <", )?;
+                            color!(fmt, Yellow, symbol)?;
+                            write!(fmt, ">")?;
                         } else {
                             write!(fmt, "{}", format!("{:#x}",val))?;
                         }
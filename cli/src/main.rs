@@ -4,10 +4,9 @@ extern crate structopt_derive;
 #[macro_use]
 extern crate error_chain;
 extern crate panopticon_core;
-extern crate panopticon_amd64;
-extern crate panopticon_avr;
 extern crate panopticon_analysis;
 extern crate panopticon_graph_algos;
+extern crate panopticon_script;
 extern crate futures;
 #[macro_use]
 extern crate log;
@@ -15,12 +14,12 @@ extern crate env_logger;
 extern crate termcolor;
 extern crate atty;
 
-use panopticon_amd64 as amd64;
-use panopticon_analysis::analyze;
-use panopticon_avr as avr;
-use panopticon_core::{Machine, Function, FunctionKind, Program, Result, loader};
+use panopticon_analysis::{Options, Progress, Registry, driver};
+use panopticon_core::{Function, FunctionKind, Program, Project, RawMapping, Result};
+use panopticon_script::Script;
 use std::path::Path;
 use std::result;
+use std::sync::Arc;
 use structopt::StructOpt;
 use std::io::Write;
 use termcolor::{BufferWriter, ColorChoice, WriteColor};
@@ -56,6 +55,24 @@ struct Args {
     /// The specific function address to disassemble
     #[structopt(short = "a", long = "address", help = "Disassemble the function at the given address")]
     address_filter: Option<String>,
+    /// The architecture slice of a fat binary to disassemble
+    #[structopt(long = "slice", help = "Disassemble the given architecture slice of a fat Mach-o binary")]
+    slice: Option<String>,
+    /// Load the binary as a headerless file for this CPU
+    #[structopt(long = "raw", help = "Disassemble a file without headers for the given CPU, e.g. amd64, arm, mipsel or sh4")]
+    raw: Option<String>,
+    /// Load address of a raw file
+    #[structopt(long = "base", help = "Load a raw file at the given hexadecimal address")]
+    base: Option<String>,
+    /// Entry points of a raw file
+    #[structopt(long = "entry", help = "Start disassembling a raw file at the given hexadecimal address")]
+    entry: Vec<String>,
+    /// Automation script
+    #[structopt(long = "script", help = "Run the given Rhai script on the analyzed binary instead of printing functions")]
+    script: Option<String>,
+    /// Plugin libraries
+    #[structopt(long = "plugin", help = "Load loaders, architectures and passes from the given plugin library")]
+    plugins: Vec<String>,
     /// The binary to disassemble
     #[structopt(help = "The binary to disassemble")]
     binary: String,
@@ -80,7 +97,7 @@ impl Filter {
     }
     pub fn is_match(&self, func: &Function) -> bool {
         if let Some(ref name) = self.name {
-            if name == &func.name || func.aliases().contains(name){ return true }
+            if name == &func.name || name == &func.display_name() || func.aliases().contains(name){ return true }
         }
         if let Some(ref addr) = self.addr {
             return *addr == func.start()
@@ -162,16 +179,43 @@ fn print_reverse_deps<W: Write + WriteColor>(mut fmt: W, program: &Program, filt
     Ok(())
 }
 
-fn disassemble(binary: &str) -> Result<Program> {
-    let (mut proj, machine) = loader::load(Path::new(&binary))?;
-    let program = proj.code.pop().unwrap();
-    let reg = proj.region().clone();
-    info!("disassembly thread started");
-    Ok(match machine {
-        Machine::Avr => analyze::<avr::Avr>(program, reg.clone(), avr::Mcu::atmega103()),
-        Machine::Ia32 => analyze::<amd64::Amd64>(program, reg.clone(), amd64::Mode::Protected),
-        Machine::Amd64 => analyze::<amd64::Amd64>(program, reg.clone(), amd64::Mode::Long),
-    }?)
+fn parse_address(addr: &str) -> Result<u64> {
+    let digits = if addr.starts_with("0x") { &addr[2..] } else { addr };
+    u64::from_str_radix(digits, 16).map_err(|_| format!("invalid address: {}", addr).into())
+}
+
+fn raw_mapping(args: &Args) -> Result<Option<RawMapping>> {
+    let machine = match args.raw {
+        Some(ref machine) => machine.parse()?,
+        None => return Ok(None),
+    };
+    let mut mapping = RawMapping::new(machine);
+
+    if let Some(ref base) = args.base {
+        mapping = mapping.base(parse_address(base)?);
+    }
+    for entry in args.entry.iter() {
+        mapping = mapping.entry(parse_address(entry)?);
+    }
+
+    Ok(Some(mapping))
+}
+
+fn disassemble(binary: &str, slice: Option<&str>, raw: Option<&RawMapping>, plugins: Registry, passes: bool, il: bool) -> Result<Project> {
+    // functions that are only listed don't need their IL
+    let options = if passes { Options::new() } else { Options { passes: vec![], decode_only: !il, ..Options::new() } };
+    let options = Options { slice: slice.map(str::to_string), raw: raw.cloned(), plugins: Arc::new(plugins), ..options };
+    let progress = |p: Progress| match p {
+        Progress::Loaded(machine) => info!("disassembling {:?} code", machine),
+        Progress::Discovered { round, functions } => info!("round {}: {} functions", round, functions),
+        Progress::Pass(_) | Progress::Cancelled | Progress::Finished => {}
+    };
+    let mut proj = driver::analyze_with_progress(Path::new(&binary), &options, &progress)?;
+
+    for finding in proj.findings.iter() {
+        println!("Warning: {}", finding);
+    }
+    Ok(proj)
 }
 
 fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: Args) -> Result<()> {
@@ -207,7 +251,7 @@ fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: Args) -> Resul
             }
         }
         if args.dump_il {
-            display::print_rreil(fmt, &bbs)?;
+            display::print_rreil(fmt, &function, &bbs)?;
         }
         writeln!(fmt, "Aliases: {:?}", function.aliases())?;
     }
@@ -216,7 +260,30 @@ fn app_logic(fmt: &mut termcolor::Buffer, program: Program, args: Args) -> Resul
 
 fn run(args: Args) -> Result<()> {
     exists_path_val(&args.binary)?;
-    let program = disassemble(&args.binary)?;
+    let raw = raw_mapping(&args)?;
+    let script = match args.script {
+        Some(ref path) => Some(Script::open(Path::new(path))?),
+        None => None,
+    };
+    let mut plugins = Registry::new();
+    for path in args.plugins.iter() {
+        plugins.load_library(Path::new(path))?;
+    }
+    let mut proj = disassemble(&args.binary, args.slice.as_ref().map(String::as_str), raw.as_ref(), plugins, script.is_some(), args.dump_il)?;
+
+    if let Some(script) = script {
+        let output = script.run(&mut proj)?;
+
+        for line in output.printed {
+            println!("{}", line);
+        }
+        if let Some(value) = output.value {
+            println!("{}", value);
+        }
+        return Ok(());
+    }
+
+    let program = proj.code.pop().unwrap();
     let cc = if args.color || atty::is(atty::Stream::Stdout) { ColorChoice::Auto } else { ColorChoice::Never };
     let writer = BufferWriter::stdout(cc);
     let mut fmt = writer.buffer();
@@ -0,0 +1,294 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! User comments, generated comments and labels.
+//!
+//! Annotations are attached either to an address or to a single IL statement, identified by the
+//! UUID of its function and its position in `Function::statements`. Each location has at most
+//! one comment written by the user, one generated by the analysis and one label. They are stored
+//! in `Project::annotations` and saved together with the project.
+//!
+//! [`annotate`] replaces the generated comments with the contents of the string literals each
+//! mnemonic references. User comments and labels are never touched by the analysis.
+//!
+//! [`annotate`]: fn.annotate.html
+
+use Project;
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::mem;
+use uuid::Uuid;
+
+/// Place an annotation is attached to.
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub enum Location {
+    /// Absolute address in the root region
+    Address(u64),
+    /// IL statement
+    Statement {
+        /// Function containing the statement
+        function: Uuid,
+        /// Position of the statement in `Function::statements`
+        index: usize,
+    },
+}
+
+/// Annotations of a single location.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct Annotation {
+    /// Comment written by the user
+    pub comment: Option<String>,
+    /// Comment generated by the analysis
+    pub auto_comment: Option<String>,
+    /// Name of the location
+    pub label: Option<String>,
+}
+
+impl Annotation {
+    /// True if nothing is set.
+    pub fn is_empty(&self) -> bool {
+        self.comment.is_none() && self.auto_comment.is_none() && self.label.is_none()
+    }
+}
+
+/// All annotations of a project, ordered by location.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct Annotations {
+    entries: BTreeMap<Location, Annotation>,
+}
+
+impl Annotations {
+    /// Returns an empty store.
+    pub fn new() -> Annotations {
+        Annotations::default()
+    }
+
+    /// Returns the annotations of `loc`.
+    pub fn get(&self, loc: &Location) -> Option<&Annotation> {
+        self.entries.get(loc)
+    }
+
+    /// Returns the user comment of `loc`.
+    pub fn comment(&self, loc: &Location) -> Option<&str> {
+        self.get(loc).and_then(|a| a.comment.as_ref()).map(|s| s.as_str())
+    }
+
+    /// Returns the generated comment of `loc`.
+    pub fn auto_comment(&self, loc: &Location) -> Option<&str> {
+        self.get(loc).and_then(|a| a.auto_comment.as_ref()).map(|s| s.as_str())
+    }
+
+    /// Returns the label of `loc`.
+    pub fn label(&self, loc: &Location) -> Option<&str> {
+        self.get(loc).and_then(|a| a.label.as_ref()).map(|s| s.as_str())
+    }
+
+    /// Sets the user comment of `loc`. An empty string removes it. Returns the previous comment.
+    pub fn set_comment(&mut self, loc: Location, comment: String) -> Option<String> {
+        self.update(loc, |a| &mut a.comment, comment)
+    }
+
+    /// Sets the generated comment of `loc`. An empty string removes it. Returns the previous
+    /// comment.
+    pub fn set_auto_comment(&mut self, loc: Location, comment: String) -> Option<String> {
+        self.update(loc, |a| &mut a.auto_comment, comment)
+    }
+
+    /// Sets the label of `loc`. An empty string removes it. Returns the previous label.
+    pub fn set_label(&mut self, loc: Location, label: String) -> Option<String> {
+        self.update(loc, |a| &mut a.label, label)
+    }
+
+    /// Returns the first location labeled `label`.
+    pub fn find_label(&self, label: &str) -> Option<&Location> {
+        self.entries.iter().find(|&(_, a)| a.label.as_ref().map(|l| l == label).unwrap_or(false)).map(|(l, _)| l)
+    }
+
+    /// Removes all annotations of `loc`.
+    pub fn remove(&mut self, loc: &Location) -> Option<Annotation> {
+        self.entries.remove(loc)
+    }
+
+    /// Removes all generated comments.
+    pub fn clear_auto_comments(&mut self) {
+        for a in self.entries.values_mut() {
+            a.auto_comment = None;
+        }
+        self.entries.retain(|_, a| !a.is_empty());
+    }
+
+    /// Iterates over all annotated locations.
+    pub fn iter(&self) -> btree_map::Iter<Location, Annotation> {
+        self.entries.iter()
+    }
+
+    /// Iterates over the annotated addresses in `start..end`.
+    pub fn addresses<'a>(&'a self, start: u64, end: u64) -> Box<Iterator<Item = (u64, &'a Annotation)> + 'a> {
+        if start >= end {
+            return Box::new(None.into_iter());
+        }
+
+        Box::new(
+            self.entries
+                .range(Location::Address(start)..Location::Address(end))
+                .filter_map(
+                    |(l, a)| match l {
+                        &Location::Address(addr) => Some((addr, a)),
+                        _ => None,
+                    }
+                )
+        )
+    }
+
+    /// Iterates over the annotated statements of `function`, by index.
+    pub fn statements<'a>(&'a self, function: &Uuid) -> Box<Iterator<Item = (usize, &'a Annotation)> + 'a> {
+        let function = function.clone();
+        let first = Location::Statement { function: function.clone(), index: 0 };
+
+        Box::new(
+            self.entries
+                .range(first..)
+                .map(
+                    move |(l, a)| match l {
+                        &Location::Statement { function: ref f, index } => (*f == function, index, a),
+                        _ => (false, 0, a),
+                    }
+                )
+                .take_while(|&(same, _, _)| same)
+                .map(|(_, index, a)| (index, a))
+        )
+    }
+
+    /// Replaces every annotated address `a` with `f(a)`.
+    pub fn move_addresses(&mut self, f: &Fn(u64) -> u64) {
+        let entries = mem::replace(&mut self.entries, BTreeMap::new());
+
+        self.entries = entries
+            .into_iter()
+            .map(
+                |(l, a)| match l {
+                    Location::Address(addr) => (Location::Address(f(addr)), a),
+                    l => (l, a),
+                }
+            )
+            .collect();
+    }
+
+    /// Number of annotated locations.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if no location is annotated.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn update<F: Fn(&mut Annotation) -> &mut Option<String>>(&mut self, loc: Location, field: F, value: String) -> Option<String> {
+        let (prev, empty) = {
+            let a = self.entries.entry(loc.clone()).or_insert_with(Annotation::default);
+            let prev = if value.is_empty() { field(a).take() } else { mem::replace(field(a), Some(value)) };
+
+            (prev, a.is_empty())
+        };
+
+        if empty {
+            self.entries.remove(&loc);
+        }
+
+        prev
+    }
+}
+
+/// Replaces the generated comments of `proj` with the string literals referenced by each
+/// mnemonic. `strings::extract` must have been run before.
+pub fn annotate(proj: &mut Project) {
+    let mut comments = BTreeMap::<u64, Vec<String>>::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for r in func.string_refs() {
+                if let Some(s) = proj.strings.get(&r.string) {
+                    comments.entry(r.address).or_insert_with(Vec::new).push(format!("{}", s));
+                }
+            }
+        }
+    }
+
+    proj.annotations.clear_auto_comments();
+    for (addr, strs) in comments {
+        proj.annotations.set_auto_comment(Location::Address(addr), strs.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Bound, Function, Program, Region, StringLiteral, StringRef};
+    use serde_cbor;
+    use strings::Encoding;
+
+    #[test]
+    fn comments_and_labels() {
+        let func = Uuid::new_v4();
+        let stmt = Location::Statement { function: func.clone(), index: 2 };
+        let mut ann = Annotations::new();
+
+        assert_eq!(ann.set_comment(Location::Address(0x100), "entry".to_string()), None);
+        assert_eq!(ann.set_label(Location::Address(0x100), "start".to_string()), None);
+        assert_eq!(ann.set_label(Location::Address(0x200), "loop".to_string()), None);
+        assert_eq!(ann.set_comment(stmt.clone(), "counter".to_string()), None);
+        assert_eq!(ann.set_comment(Location::Address(0x100), "main entry".to_string()), Some("entry".to_string()));
+
+        assert_eq!(ann.comment(&Location::Address(0x100)), Some("main entry"));
+        assert_eq!(ann.label(&Location::Address(0x200)), Some("loop"));
+        assert_eq!(ann.comment(&stmt), Some("counter"));
+        assert_eq!(ann.find_label("loop"), Some(&Location::Address(0x200)));
+        assert_eq!(ann.addresses(0x100, 0x200).map(|(a, _)| a).collect::<Vec<_>>(), vec![0x100]);
+        assert_eq!(ann.statements(&func).map(|(i, _)| i).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(ann.statements(&Uuid::new_v4()).count(), 0);
+
+        let bytes = serde_cbor::to_vec(&ann).unwrap();
+        let ann2: Annotations = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!(ann, ann2);
+
+        assert_eq!(ann.set_label(Location::Address(0x200), "".to_string()), Some("loop".to_string()));
+        assert_eq!(ann.get(&Location::Address(0x200)), None);
+        assert_eq!(ann.len(), 2);
+    }
+
+    #[test]
+    fn string_comments() {
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        let mut prog = Program::new("prog");
+
+        func.set_string_refs(vec![StringRef { address: 0x100, string: 0x800 }]);
+        prog.insert(func);
+        proj.code.push(prog);
+        proj.strings.insert(0x800, StringLiteral { area: Bound::new(0x800, 0x805), encoding: Encoding::Ascii, value: "hello".to_string() });
+        proj.annotations.set_auto_comment(Location::Address(0x300), "stale".to_string());
+        proj.annotations.set_comment(Location::Address(0x100), "greeting".to_string());
+
+        annotate(&mut proj);
+
+        assert_eq!(proj.annotations.auto_comment(&Location::Address(0x100)), Some("\"hello\""));
+        assert_eq!(proj.annotations.comment(&Location::Address(0x100)), Some("greeting"));
+        assert_eq!(proj.annotations.get(&Location::Address(0x300)), None);
+    }
+}
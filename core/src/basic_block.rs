@@ -81,12 +81,16 @@ pub struct BasicBlock {
     pub area: Bound,
     /// List of mnemonics in to order of execution.
     pub mnemonics: Vec<Mnemonic>,
+    /// Instruction set mode the mnemonics were decoded in, e.g. "thumb". None for
+    /// architectures with a single mode.
+    #[serde(default)]
+    pub mode: Option<String>,
 }
 
 impl BasicBlock {
     /// Returns a new, empty basic block.
     pub fn new() -> BasicBlock {
-        BasicBlock { area: Bound::new(0, 0), mnemonics: Vec::new() }
+        BasicBlock { area: Bound::new(0, 0), mnemonics: Vec::new(), mode: None }
     }
 
     /// Moves `ms` into a new basic block. Panics if the mnemonics do not occupy a continuous
@@ -102,7 +106,7 @@ impl BasicBlock {
                     return Some(Bound::new(min(r1.start, r2.start), max(r1.end, r2.end)));
                 }
             );
-        return BasicBlock { area: a.unwrap_or(Bound::new(0, 0)), mnemonics: ms };
+        return BasicBlock { area: a.unwrap_or(Bound::new(0, 0)), mnemonics: ms, mode: None };
     }
 
     /// Calls `f` on all RREIL instructions starting from the last.
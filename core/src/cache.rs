@@ -0,0 +1,324 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Reusing disassembled functions.
+//!
+//! Decoding and lifting is the most expensive part of the analysis. The same code is lifted
+//! again each time a file is opened, and statically linked executables contain many identical
+//! copies of small functions. [`FunctionCache`] remembers disassembled functions by the
+//! [`content_hash`] of their bytes and the lifter that produced them, and hands out copies when
+//! the same bytes turn up again.
+//!
+//! Functions are only reused at a different address if their code doesn't refer to mapped
+//! memory outside of themselves. Otherwise the bytes are the same but the addresses they
+//! reference are not, e.g. with PC-relative calls. Addresses inside the function are moved
+//! along with it.
+//!
+//! The cache can be saved to disk to speed up opening the same file again. The lifter string
+//! should change with every version of the disassembler, entries of other lifters are ignored.
+//!
+//! [`FunctionCache`]: struct.FunctionCache.html
+//! [`content_hash`]: fn.content_hash.html
+
+use {Bound, ControlFlowTarget, Function, Region, Result, Rvalue};
+use byteorder::{ByteOrder, LittleEndian};
+use panopticon_graph_algos::VertexListGraphTrait;
+use serde::{Deserialize, Serialize};
+use serde_cbor::de::Deserializer;
+use serde_cbor::ser::Serializer;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use uuid::Uuid;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// FNV-1a, stable across Rust versions unlike `DefaultHasher`
+fn fnv(mut h: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(FNV_PRIME);
+    }
+    h
+}
+
+/// Hashes `lifter` and the bytes of `areas` moved from `base` to `start`. None if a byte is
+/// undefined.
+fn hash_areas(region: &Region, lifter: &str, areas: &[Bound], base: u64, start: u64) -> Option<u64> {
+    let mut h = fnv(FNV_OFFSET, lifter.as_bytes());
+
+    for area in areas {
+        let len = area.end - area.start;
+        let offset = area.start.wrapping_sub(base);
+        let mut bytes = vec![0u8; 16];
+
+        LittleEndian::write_u64(&mut bytes[0..8], offset);
+        LittleEndian::write_u64(&mut bytes[8..16], len);
+        for cell in region.iter().seek(start.wrapping_add(offset)).take(len as usize) {
+            bytes.push(cell?);
+        }
+        if bytes.len() as u64 != len + 16 {
+            return None;
+        }
+
+        h = fnv(h, &bytes);
+    }
+
+    Some(h)
+}
+
+/// Areas of the mnemonics of `func` in ascending order.
+fn areas(func: &Function) -> Vec<Bound> {
+    let mut ret = func.basic_blocks().flat_map(|bb| bb.mnemonics.iter().map(|m| m.area.clone())).collect::<Vec<_>>();
+
+    ret.sort_by_key(|b| b.start);
+    ret
+}
+
+/// Hash of the bytes of all mnemonics of `func` relative to its start and of `lifter`, the name
+/// and version of the code that disassembled it. None if a byte is undefined.
+pub fn content_hash(func: &Function, region: &Region, lifter: &str) -> Option<u64> {
+    let start = func.start();
+
+    hash_areas(region, lifter, &areas(func), start, start)
+}
+
+/// True if no constant in `func` points to mapped bytes outside of it.
+fn relocatable(func: &Function, region: &Region, start: u64, end: u64) -> bool {
+    let outside = |rv: &Rvalue| match rv {
+        &Rvalue::Constant { value, .. } => {
+            (value < start || value >= end) && value < region.size() && region.iter().seek(value).next().map(|c| c.is_some()).unwrap_or(false)
+        }
+        _ => false,
+    };
+
+    for lb in func.cfg().vertex_labels() {
+        match lb {
+            &ControlFlowTarget::Resolved(ref bb) => {
+                for (mne, stmts) in func.mnemonics(bb) {
+                    if mne.operands.iter().any(&outside) || stmts.iter().any(|s| s.op.operands().into_iter().any(&outside)) {
+                        return false;
+                    }
+                }
+            }
+            &ControlFlowTarget::Unresolved(ref rv) => {
+                if outside(rv) {
+                    return false;
+                }
+            }
+            &ControlFlowTarget::Failed(..) => {}
+        }
+    }
+
+    true
+}
+
+#[derive(Serialize,Deserialize,Debug,Clone)]
+struct Entry {
+    hash: u64,
+    // size and hash of the first mnemonic
+    prefix: (u64, u64),
+    start: u64,
+    areas: Vec<Bound>,
+    relocatable: bool,
+    function: Function,
+}
+
+/// Disassembled functions by content hash, see the module documentation.
+#[derive(Serialize,Deserialize,Debug)]
+pub struct FunctionCache {
+    lifter: String,
+    entries: Vec<Entry>,
+    #[serde(skip)]
+    index: HashMap<(u64, u64), Vec<usize>>,
+}
+
+impl FunctionCache {
+    /// Returns an empty cache for functions disassembled by `lifter`.
+    pub fn new(lifter: &str) -> FunctionCache {
+        FunctionCache { lifter: lifter.to_string(), entries: Vec::new(), index: HashMap::new() }
+    }
+
+    /// Reads a cache written by `save`. Returns an empty cache if it was written for a different
+    /// lifter.
+    pub fn open(path: &Path, lifter: &str) -> Result<FunctionCache> {
+        let fd = BufReader::new(File::open(path)?);
+        let mut cbor = Deserializer::new(fd);
+        let mut ret: FunctionCache = Deserialize::deserialize(&mut cbor)?;
+
+        if ret.lifter != lifter {
+            return Ok(FunctionCache::new(lifter));
+        }
+
+        for (i, e) in ret.entries.iter().enumerate() {
+            ret.index.entry(e.prefix).or_insert_with(Vec::new).push(i);
+        }
+        Ok(ret)
+    }
+
+    /// Writes the cache to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut fd = File::create(path)?;
+        let mut enc = Serializer::new(&mut fd);
+
+        match self.serialize(&mut enc) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("failed to write function cache: {}", e).into()),
+        }
+    }
+
+    /// Name and version of the disassembler the functions come from.
+    pub fn lifter(&self) -> &str {
+        &self.lifter
+    }
+
+    /// Number of functions in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if the cache has no functions.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds `func`, disassembled from `region`. Returns false if it's already in the cache or
+    /// has undefined bytes.
+    pub fn insert(&mut self, func: &Function, region: &Region) -> bool {
+        let start = func.start();
+        let areas = areas(func);
+        let first = match areas.iter().find(|b| b.start == start) {
+            Some(b) => b.end - b.start,
+            None => return false,
+        };
+        let hash = match hash_areas(region, &self.lifter, &areas, start, start) {
+            Some(h) => h,
+            None => return false,
+        };
+        let prefix = match hash_areas(region, "", &[Bound::new(start, start + first)], start, start) {
+            Some(h) => (first, h),
+            None => return false,
+        };
+        let known = self.index
+            .get(&prefix)
+            .map(|v| v.iter().any(|&i| self.entries[i].hash == hash && (self.entries[i].start == start || self.entries[i].relocatable)))
+            .unwrap_or(false);
+
+        if known {
+            return false;
+        }
+
+        let end = areas.iter().map(|b| b.end).max().unwrap_or(start);
+
+        self.index.entry(prefix).or_insert_with(Vec::new).push(self.entries.len());
+        self.entries.push(
+            Entry {
+                hash: hash,
+                prefix: prefix,
+                start: start,
+                areas: areas,
+                relocatable: relocatable(func, region, start, end),
+                function: func.clone(),
+            }
+        );
+        true
+    }
+
+    /// Returns a copy of a cached function with the same bytes as the code at `start` in
+    /// `region`, with UUID `uuid` and `name`.
+    pub fn get(&self, start: u64, uuid: &Uuid, name: Option<String>, region: &Region) -> Option<Function> {
+        let mut prefixes = HashMap::new();
+
+        for (&(len, prefix), entries) in self.index.iter() {
+            let h = *prefixes.entry(len).or_insert_with(|| hash_areas(region, "", &[Bound::new(start, start + len)], start, start));
+
+            if h != Some(prefix) {
+                continue;
+            }
+
+            for &i in entries.iter() {
+                let e = &self.entries[i];
+
+                if (e.start == start || e.relocatable) && hash_areas(region, &self.lifter, &e.areas, e.start, start) == Some(e.hash) {
+                    return Some(e.function.relocated(start, uuid, name));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Mnemonic, OpaqueLayer};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn function(start: u64, len: u64, operand: u64, region: &Region) -> Function {
+        let ops = vec![Rvalue::new_u64(operand)];
+        let mne = Mnemonic::new(start..start + len, "test".to_string(), "{u}".to_string(), ops.iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, region, None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    /*
+     * 0x00: 4 bytes referencing 0x10
+     * 0x08: the same bytes
+     * 0x10: 2 bytes referencing nothing
+     * 0x18: the same bytes
+     */
+    #[test]
+    fn reuse() {
+        let mut bytes = vec![0u8; 0x20];
+        bytes[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        bytes[8..12].copy_from_slice(&[1, 2, 3, 4]);
+        bytes[0x10..0x12].copy_from_slice(&[5, 6]);
+        bytes[0x18..0x1a].copy_from_slice(&[5, 6]);
+        let region = Region::new("RAM".to_string(), OpaqueLayer::wrap(bytes));
+        let mut cache = FunctionCache::new("test-1");
+        let pic = function(0x10, 2, 0x100, &region);
+        let absolute = function(0, 4, 0x10, &region);
+
+        assert!(content_hash(&pic, &region, "test-1").is_some());
+        assert!(content_hash(&pic, &region, "test-1") != content_hash(&pic, &region, "test-2"));
+        assert!(cache.insert(&pic, &region));
+        assert!(!cache.insert(&pic, &region));
+        assert!(cache.insert(&absolute, &region));
+        assert_eq!(cache.len(), 2);
+
+        let uuid = Uuid::new_v4();
+        let copy = cache.get(0x18, &uuid, None, &region).unwrap();
+
+        assert_eq!(copy.start(), 0x18);
+        assert_eq!(copy.uuid(), &uuid);
+        assert_eq!(copy.name, "func_0x18");
+        assert_eq!(copy.entry_point().mnemonics[0].operands, vec![Rvalue::new_u64(0x100)]);
+
+        assert!(cache.get(0, &uuid, None, &region).is_some());
+        assert!(cache.get(8, &uuid, None, &region).is_none());
+        assert!(cache.get(4, &uuid, None, &region).is_none());
+    }
+}
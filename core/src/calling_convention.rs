@@ -0,0 +1,400 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Calling convention detection.
+//!
+//! Infers which of the built-in [`CallingConvention`] models a function follows from the way it
+//! uses registers and the stack:
+//!
+//! - Registers read before they are written on some path from the entry point are inputs.
+//!   Registers that are read at entry and later loaded back from memory are callee-saved (pushed
+//!   and popped) and don't count as inputs.
+//! - Registers written without being restored are clobbered. A Windows x64 function must not
+//!   clobber `RDI` and `RSI`, a System V one may.
+//! - Returns that pop additional bytes off the stack (`ret 8`) mean the callee cleans up its
+//!   arguments.
+//!
+//! x86 sub-registers like `EDI` or `DIL` are treated as the 64 bit register containing them.
+//!
+//! [`CallingConvention`]: enum.CallingConvention.html
+
+use {ControlFlowTarget, Function, Lvalue, Operation, Rvalue, Statement};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Calling convention model.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub enum CallingConvention {
+    /// System V AMD64 ABI used by Linux, the BSDs and macOS.
+    SysV64,
+    /// Microsoft x64 calling convention.
+    Win64,
+    /// 32 bit x86, arguments on the stack, the caller removes them.
+    Cdecl,
+    /// 32 bit x86, arguments on the stack, the callee removes them.
+    Stdcall,
+    /// ARM Procedure Call Standard.
+    Aapcs,
+}
+
+impl CallingConvention {
+    /// All built-in models.
+    pub fn all() -> &'static [CallingConvention] {
+        &[CallingConvention::SysV64, CallingConvention::Win64, CallingConvention::Cdecl, CallingConvention::Stdcall, CallingConvention::Aapcs]
+    }
+
+    /// Registers holding the first arguments, in order. Further arguments are passed on the
+    /// stack.
+    pub fn argument_registers(&self) -> &'static [&'static str] {
+        match *self {
+            CallingConvention::SysV64 => &["RDI", "RSI", "RDX", "RCX", "R8", "R9"],
+            CallingConvention::Win64 => &["RCX", "RDX", "R8", "R9"],
+            CallingConvention::Cdecl | CallingConvention::Stdcall => &[],
+            CallingConvention::Aapcs => &["R0", "R1", "R2", "R3"],
+        }
+    }
+
+    /// Registers holding the return value.
+    pub fn return_registers(&self) -> &'static [&'static str] {
+        match *self {
+            CallingConvention::SysV64 => &["RAX", "RDX"],
+            CallingConvention::Win64 => &["RAX"],
+            CallingConvention::Cdecl | CallingConvention::Stdcall => &["EAX", "EDX"],
+            CallingConvention::Aapcs => &["R0", "R1"],
+        }
+    }
+
+    /// Registers a function must restore before returning.
+    pub fn callee_saved(&self) -> &'static [&'static str] {
+        match *self {
+            CallingConvention::SysV64 => &["RBX", "RBP", "R12", "R13", "R14", "R15"],
+            CallingConvention::Win64 => &["RBX", "RBP", "RDI", "RSI", "R12", "R13", "R14", "R15"],
+            CallingConvention::Cdecl | CallingConvention::Stdcall => &["EBX", "EBP", "ESI", "EDI"],
+            CallingConvention::Aapcs => &["R4", "R5", "R6", "R7", "R8", "R9", "R10", "R11"],
+        }
+    }
+
+    /// Stack pointer register.
+    pub fn stack_pointer(&self) -> &'static str {
+        match *self {
+            CallingConvention::SysV64 | CallingConvention::Win64 => "RSP",
+            CallingConvention::Cdecl | CallingConvention::Stdcall => "ESP",
+            CallingConvention::Aapcs => "SP",
+        }
+    }
+
+    /// True if the callee removes stack arguments before returning.
+    pub fn callee_cleanup(&self) -> bool {
+        *self == CallingConvention::Stdcall
+    }
+}
+
+impl fmt::Display for CallingConvention {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            CallingConvention::SysV64 => "sysv64",
+            CallingConvention::Win64 => "win64",
+            CallingConvention::Cdecl => "cdecl",
+            CallingConvention::Stdcall => "stdcall",
+            CallingConvention::Aapcs => "aapcs",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// x86 general purpose registers and their sub-registers.
+const X86_REGISTERS: &'static [(&'static str, &'static [&'static str])] = &[
+    ("RAX", &["EAX", "AX", "AL", "AH"]),
+    ("RBX", &["EBX", "BX", "BL", "BH"]),
+    ("RCX", &["ECX", "CX", "CL", "CH"]),
+    ("RDX", &["EDX", "DX", "DL", "DH"]),
+    ("RSI", &["ESI", "SI", "SIL"]),
+    ("RDI", &["EDI", "DI", "DIL"]),
+    ("RBP", &["EBP", "BP", "BPL"]),
+    ("RSP", &["ESP", "SP", "SPL"]),
+    ("R8", &["R8D", "R8W", "R8B", "R8L"]),
+    ("R9", &["R9D", "R9W", "R9B", "R9L"]),
+    ("R10", &["R10D", "R10W", "R10B", "R10L"]),
+    ("R11", &["R11D", "R11W", "R11B", "R11L"]),
+    ("R12", &["R12D", "R12W", "R12B", "R12L"]),
+    ("R13", &["R13D", "R13W", "R13B", "R13L"]),
+    ("R14", &["R14D", "R14W", "R14B", "R14L"]),
+    ("R15", &["R15D", "R15W", "R15B", "R15L"]),
+];
+
+/// Registers only 64 bit x86 code has.
+const X86_64_ONLY: &'static [&'static str] = &["SIL", "DIL", "BPL", "SPL", "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15"];
+
+/// Registers that only exist on ARM.
+const ARM_REGISTERS: &'static [&'static str] = &["R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "LR", "PC"];
+
+/// Returns the 64 bit x86 register containing `name`.
+fn x86_register(name: &str) -> Option<&'static str> {
+    X86_REGISTERS.iter().find(|&&(full, subs)| full == name || subs.contains(&name)).map(|&(full, _)| full)
+}
+
+/// Returns the name of the register containing `name`, e.g. `RDI` for `EDI`. Other names are
+/// returned as they are.
+pub fn canonical(name: &str) -> &str {
+    x86_register(name).unwrap_or(name)
+}
+
+/// Returns true if `name` is the stack pointer of one of the calling conventions, or part of it.
+pub fn is_stack_pointer(name: &str) -> bool {
+    let name = canonical(name);
+    CallingConvention::all().iter().any(|cc| canonical(cc.stack_pointer()) == name)
+}
+
+/// Register usage of a function.
+#[derive(Debug,Default)]
+struct Usage {
+    /// Every register name read or written
+    names: HashSet<String>,
+    /// Registers read before written on some path from the entry point
+    exposed: HashSet<String>,
+    /// Registers assigned the result of a memory load
+    loaded: HashSet<String>,
+    /// Registers written
+    written: HashSet<String>,
+    /// Largest number of bytes popped by a return instruction
+    cleanup: u64,
+}
+
+impl Usage {
+    fn new(func: &Function) -> Usage {
+        let cfg = func.cfg();
+        let mut usage = Usage::default();
+        // Registers read before written and registers written, by basic block
+        let mut local = HashMap::new();
+
+        for vx in cfg.vertices() {
+            if let Some(&ControlFlowTarget::Resolved(ref bb)) = cfg.vertex_label(vx) {
+                let mut uses = HashSet::new();
+                let mut defs = HashSet::new();
+
+                for stmt in func.mnemonics(bb).flat_map(|(_, stmts)| stmts.iter()) {
+                    usage.visit(stmt, &mut uses, &mut defs);
+                }
+                for mne in bb.mnemonics() {
+                    if let (true, Some(&Rvalue::Constant { value, .. })) = (mne.opcode.starts_with("ret"), mne.operands.first()) {
+                        usage.cleanup = ::std::cmp::max(usage.cleanup, value);
+                    }
+                }
+
+                local.insert(vx, (uses, defs));
+            }
+        }
+
+        // Backward fixpoint: live(b) = uses(b) ∪ (live(succ) \ defs(b))
+        let mut live = HashMap::<_, HashSet<String>>::new();
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for (&vx, &(ref uses, ref defs)) in local.iter() {
+                let mut new = uses.clone();
+
+                for e in cfg.out_edges(vx) {
+                    if let Some(succ) = live.get(&cfg.target(e)) {
+                        new.extend(succ.iter().filter(|r| !defs.contains(*r)).cloned());
+                    }
+                }
+
+                if live.get(&vx).map(|old| old.len()) != Some(new.len()) {
+                    live.insert(vx, new);
+                    changed = true;
+                }
+            }
+        }
+
+        usage.exposed = live.remove(&func.entry_point_ref()).unwrap_or_default();
+        usage
+    }
+
+    fn visit(&mut self, stmt: &Statement, uses: &mut HashSet<String>, defs: &mut HashSet<String>) {
+        if let Operation::Phi(_) = stmt.op {
+            return;
+        }
+
+        for rv in stmt.op.operands() {
+            if let &Rvalue::Variable { ref name, .. } = rv {
+                let canon = canonical(name);
+
+                self.names.insert(name.to_string());
+                if !defs.contains(canon) {
+                    uses.insert(canon.to_string());
+                }
+            }
+        }
+
+        if let Lvalue::Variable { ref name, .. } = stmt.assignee {
+            let canon = canonical(name).to_string();
+
+            if let Operation::Load(..) = stmt.op {
+                self.loaded.insert(canon.clone());
+            }
+            self.names.insert(name.to_string());
+            self.written.insert(canon.clone());
+            defs.insert(canon);
+        }
+    }
+
+    /// Registers read at entry and restored from memory later.
+    fn saved(&self) -> HashSet<&str> {
+        self.exposed.intersection(&self.loaded).map(|x| x.as_str()).collect()
+    }
+
+    /// Registers read at entry that aren't just saved.
+    fn inputs(&self) -> HashSet<&str> {
+        let saved = self.saved();
+        self.exposed.iter().map(|x| x.as_str()).filter(|x| !saved.contains(x)).collect()
+    }
+
+    /// Registers written and not restored.
+    fn clobbered(&self) -> HashSet<&str> {
+        let saved = self.saved();
+        self.written.iter().map(|x| x.as_str()).filter(|x| !saved.contains(x)).collect()
+    }
+}
+
+/// Infers the calling convention of `func`. Returns None if the function doesn't give enough
+/// evidence to choose between the models of its architecture.
+pub fn infer(func: &Function) -> Option<CallingConvention> {
+    let usage = Usage::new(func);
+    let any = |set: &HashSet<&str>, regs: &[&str]| regs.iter().any(|r| set.contains(r));
+    let inputs = usage.inputs();
+
+    if ARM_REGISTERS.iter().any(|r| usage.names.contains(*r)) {
+        return Some(CallingConvention::Aapcs);
+    }
+    if !usage.names.iter().any(|r| x86_register(r).is_some()) {
+        return None;
+    }
+
+    let is_64 = usage.names.iter().any(|r| X86_64_ONLY.contains(&r.as_str()) || (r.starts_with('R') && r != "RSP" && r != "RIP")) ||
+                any(&inputs, &["RDI", "RSI", "R8", "R9"]);
+
+    if !is_64 {
+        return Some(if usage.cleanup > 0 { CallingConvention::Stdcall } else { CallingConvention::Cdecl });
+    }
+
+    let sysv = any(&inputs, &["RDI", "RSI"]) || any(&usage.clobbered(), &["RDI", "RSI"]);
+    let win = any(&inputs, &["RCX", "RDX", "R8", "R9"]) || any(&usage.saved(), &["RDI", "RSI"]);
+
+    if sysv {
+        Some(CallingConvention::SysV64)
+    } else if win {
+        Some(CallingConvention::Win64)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Endianess, Mnemonic, Region};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn reg(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn push(r: &'static str) -> Vec<Statement> {
+        vec![
+            Statement { op: Operation::Subtract(reg("RSP", 64).into(), Rvalue::new_u64(8)), assignee: reg("RSP", 64) },
+            Statement { op: Operation::Store(Cow::Borrowed("RAM"), Endianess::Little, 64, reg("RSP", 64).into(), reg(r, 64).into()), assignee: Lvalue::Undefined },
+        ]
+    }
+
+    fn pop(r: &'static str) -> Vec<Statement> {
+        vec![
+            Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 64, reg("RSP", 64).into()), assignee: reg(r, 64) },
+            Statement { op: Operation::Add(reg("RSP", 64).into(), Rvalue::new_u64(8)), assignee: reg("RSP", 64) },
+        ]
+    }
+
+    fn function(blocks: Vec<(&'static str, Vec<Rvalue>, Vec<Statement>)>) -> Function {
+        let mnes = blocks
+            .into_iter()
+            .enumerate()
+            .map(|(i, (opcode, ops, stmts))| Mnemonic::new(i as u64..i as u64 + 1, opcode.to_string(), "".to_string(), ops.iter(), stmts.iter()).unwrap())
+            .collect();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(mnes)));
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_string(), 16), None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    #[test]
+    fn sysv() {
+        // mov eax, edi; add eax, esi; ret
+        let func = function(
+            vec![
+                ("mov", vec![], vec![Statement { op: Operation::Move(reg("EDI", 32).into()), assignee: reg("EAX", 32) }]),
+                ("add", vec![], vec![Statement { op: Operation::Add(reg("EAX", 32).into(), reg("ESI", 32).into()), assignee: reg("EAX", 32) }]),
+                ("ret", vec![], vec![]),
+            ]
+        );
+
+        assert_eq!(infer(&func), Some(CallingConvention::SysV64));
+    }
+
+    #[test]
+    fn win64() {
+        // push rdi; mov rdi, rcx; mov rax, rdi; pop rdi; ret
+        let mut stmts = push("RDI");
+        stmts.push(Statement { op: Operation::Move(reg("RCX", 64).into()), assignee: reg("RDI", 64) });
+        stmts.push(Statement { op: Operation::Move(reg("RDI", 64).into()), assignee: reg("RAX", 64) });
+        stmts.extend(pop("RDI"));
+
+        let func = function(vec![("body", vec![], stmts), ("ret", vec![], vec![])]);
+
+        assert_eq!(infer(&func), Some(CallingConvention::Win64));
+    }
+
+    #[test]
+    fn stack_cleanup() {
+        // push ebx; mov eax, ebx; pop ebx; ret 8
+        let mut stmts = push("EBX");
+        stmts.push(Statement { op: Operation::Move(reg("EBX", 32).into()), assignee: reg("EAX", 32) });
+        stmts.extend(pop("EBX"));
+
+        let cdecl = function(vec![("body", vec![], stmts.clone()), ("ret", vec![], vec![])]);
+        let stdcall = function(vec![("body", vec![], stmts), ("ret", vec![Rvalue::new_u16(8)], vec![])]);
+
+        assert_eq!(infer(&cdecl), Some(CallingConvention::Cdecl));
+        assert_eq!(infer(&stdcall), Some(CallingConvention::Stdcall));
+        assert!(CallingConvention::Stdcall.callee_cleanup());
+    }
+
+    #[test]
+    fn aapcs() {
+        let func = function(vec![("add", vec![], vec![Statement { op: Operation::Add(reg("R0", 32).into(), reg("R1", 32).into()), assignee: reg("R0", 32) }])]);
+
+        assert_eq!(infer(&func), Some(CallingConvention::Aapcs));
+        assert_eq!(infer(&function(vec![("nop", vec![], vec![])])), None);
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Aborting analysis from another thread.
+//!
+//! A [`CancellationToken`] is shared between a front-end and the code doing the work. Cancelling
+//! it makes `Function::with_token` and `Function::cont_with_token` fail at the next instruction
+//! they decode, without adding anything to the function. Code driving the analysis checks the
+//! token between functions and passes, so what was already done stays in the project and
+//! everything left over is still a `CallTarget::Todo`.
+//!
+//! [`CancellationToken`]: struct.CancellationToken.html
+
+use Result;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Flag telling long-running analysis to stop. Clones share the flag.
+#[derive(Clone,Debug,Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Returns a token that isn't cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Tells everyone holding a clone of this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst)
+    }
+
+    /// True if `cancel` was called on this token or one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails if the token was cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() { Err("analysis cancelled".into()) } else { Ok(()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_between_clones() {
+        let token = CancellationToken::new();
+        let other = token.clone();
+
+        assert!(token.check().is_ok());
+        other.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.check().is_err());
+        assert!(!CancellationToken::new().is_cancelled());
+    }
+}
@@ -0,0 +1,376 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Execution traces and basic block coverage.
+//!
+//! [`import`] reads a trace recorded while the program ran and counts how often each basic block
+//! was entered. The counts are kept in `Project::coverage`, by [`BasicBlockIndex`]. Three trace
+//! formats are understood:
+//!
+//! - `Addresses`: a text file with one hexadecimal address per line, e.g. from a debugger script
+//!   or an emulator hook. Empty lines and lines starting with `#` are ignored.
+//! - `IntelPt`: instruction traces decoded by `ptxed` or `perf script -F ip,sym`. Lines not
+//!   starting with an address, like `[enabled]`, are ignored.
+//! - `Drcov`: the coverage files written by DynamoRIO's drcov client and compatible tools, with
+//!   binary basic block table. Only modules with the name of the project or one of its images
+//!   are used. Blocks are listed once, so each one counts as entered once.
+//!
+//! A block is entered whenever the trace reaches its first instruction, or reaches another of
+//! its instructions coming from somewhere else. Addresses no basic block starts an instruction at
+//! are counted in `Coverage::unmapped`.
+//!
+//! [`import`]: fn.import.html
+//! [`BasicBlockIndex`]: struct.BasicBlockIndex.html
+
+use {Project, Result, image};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Basic block of a function, identified by the function UUID and the address of the block.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub struct BasicBlockIndex {
+    /// Function containing the block
+    pub function: Uuid,
+    /// First address of the block
+    pub start: u64,
+}
+
+/// Format of an execution trace.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum TraceFormat {
+    /// One address per line
+    Addresses,
+    /// Decoded Intel Processor Trace
+    IntelPt,
+    /// DynamoRIO drcov
+    Drcov,
+}
+
+impl TraceFormat {
+    /// Guesses the format of the trace `bytes`.
+    pub fn detect(bytes: &[u8]) -> TraceFormat {
+        if bytes.starts_with(b"DRCOV VERSION") {
+            TraceFormat::Drcov
+        } else if bytes.split(|&b| b == b'\n').any(|l| l.starts_with(b"[")) {
+            TraceFormat::IntelPt
+        } else {
+            TraceFormat::Addresses
+        }
+    }
+}
+
+/// Number of times each basic block was entered.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct Coverage {
+    /// Times each basic block was entered. Blocks never entered are missing.
+    pub hits: BTreeMap<BasicBlockIndex, u64>,
+    /// Trace entries outside of all basic blocks
+    pub unmapped: u64,
+}
+
+impl Coverage {
+    /// Returns empty coverage.
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// True if no trace has been imported.
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty() && self.unmapped == 0
+    }
+
+    /// Returns how often the basic block `bb` was entered.
+    pub fn hits(&self, bb: &BasicBlockIndex) -> u64 {
+        self.hits.get(bb).cloned().unwrap_or(0)
+    }
+
+    /// Returns the hit count of each block of the function with UUID `function` that was
+    /// entered, by block address.
+    pub fn function(&self, function: &Uuid) -> BTreeMap<u64, u64> {
+        let from = BasicBlockIndex { function: *function, start: 0 };
+
+        self.hits.range(from..).take_while(|&(bb, _)| bb.function == *function).map(|(bb, &n)| (bb.start, n)).collect()
+    }
+
+    /// Adds the counts of `other`.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (bb, &n) in other.hits.iter() {
+            *self.hits.entry(*bb).or_insert(0) += n;
+        }
+        self.unmapped += other.unmapped;
+    }
+}
+
+/// Addresses executed, in order, and the number of bytes executed starting there.
+pub type Trace = Vec<(u64, u64)>;
+
+/// Parses a hexadecimal number with or without `0x` prefix.
+fn hex(s: &str) -> Option<u64> {
+    let digits = if s.starts_with("0x") || s.starts_with("0X") { &s[2..] } else { s };
+
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Parses a trace in the text formats.
+fn parse_text(bytes: &[u8], format: TraceFormat) -> Result<Trace> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut ret = vec![];
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || (format == TraceFormat::IntelPt && line.starts_with('[')) {
+            continue;
+        }
+
+        match hex(line.split(|c: char| c.is_whitespace() || c == ':').next().unwrap_or("")) {
+            Some(a) => ret.push((a, 1)),
+            None if format == TraceFormat::IntelPt => {}
+            None => return Err(format!("line {}: not an address: {}", n + 1, line).into()),
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Module of a drcov file.
+struct Module {
+    base: u64,
+    path: String,
+}
+
+/// Parses a drcov file. Returns the blocks of each module as offset and size.
+fn parse_drcov(bytes: &[u8]) -> Result<(Vec<Module>, Vec<(u16, u64, u64)>)> {
+    let mut pos = 0;
+    let mut line = || -> Result<String> {
+        match bytes[pos..].iter().position(|&b| b == b'\n') {
+            Some(end) => {
+                let ret = String::from_utf8_lossy(&bytes[pos..pos + end]).trim().to_string();
+                pos += end + 1;
+                Ok(ret)
+            }
+            None => Err("truncated drcov file".into()),
+        }
+    };
+    let mut modules = vec![];
+    let mut count = None;
+    let mut columns = vec!["id".to_string(), "base".to_string(), "end".to_string(), "entry".to_string(), "path".to_string()];
+
+    loop {
+        let l = line()?;
+
+        if l.starts_with("Module Table:") {
+            // "Module Table: 5" or "Module Table: version 2, count 5"
+            count = l.rsplit(|c: char| c == ' ' || c == ',').next().and_then(|n| n.parse::<usize>().ok());
+        } else if l.starts_with("Columns:") {
+            columns = l["Columns:".len()..].split(',').map(|c| c.trim().to_string()).collect();
+        } else if let Some(n) = count {
+            if modules.len() == n {
+                if !l.starts_with("BB Table:") {
+                    return Err(format!("expected basic block table, found {}", l).into());
+                }
+                break;
+            }
+
+            let fields = l.splitn(columns.len(), ',').map(str::trim).collect::<Vec<_>>();
+            let field = |name: &str| columns.iter().position(|c| c == name).and_then(|i| fields.get(i).cloned());
+            let base = field("base").or_else(|| field("start")).and_then(hex);
+
+            match (base, field("path")) {
+                (Some(base), Some(path)) => modules.push(Module { base: base, path: path.to_string() }),
+                _ => return Err(format!("invalid module entry: {}", l).into()),
+            }
+        }
+    }
+
+    // struct { u32 start; u16 size; u16 mod_id }
+    let blocks = bytes[pos..]
+        .chunks(8)
+        .filter(|c| c.len() == 8)
+        .map(
+            |c| {
+                let start = c[0] as u64 | (c[1] as u64) << 8 | (c[2] as u64) << 16 | (c[3] as u64) << 24;
+                let size = c[4] as u64 | (c[5] as u64) << 8;
+                let module = c[6] as u16 | (c[7] as u16) << 8;
+
+                (module, start, size)
+            }
+        )
+        .collect();
+
+    Ok((modules, blocks))
+}
+
+/// Maps the blocks of a drcov file onto the addresses of `proj`.
+fn drcov_trace(proj: &Project, bytes: &[u8]) -> Result<Trace> {
+    let (modules, blocks) = parse_drcov(bytes)?;
+    let names = if proj.images.is_empty() { vec![proj.name.clone()] } else { proj.images.iter().map(|i| i.name.clone()).collect() };
+    let mut bases = BTreeMap::new();
+
+    for (id, m) in modules.iter().enumerate() {
+        let file = Path::new(&m.path).file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+
+        if !names.contains(&file) {
+            continue;
+        }
+
+        // modules not loaded at the address they were linked at are relocated to the first page
+        // of the image
+        let area = image::extent(proj, &file)?;
+        let base = if area.start <= m.base && m.base < area.end { m.base } else { area.start & !0xfff };
+
+        bases.insert(id as u16, base);
+    }
+
+    Ok(blocks.into_iter().map(|(m, start, size)| (bases.get(&m).map(|b| b + start), size)).map(|(a, size)| (a.unwrap_or(u64::max_value()), size.max(1))).collect())
+}
+
+/// Counts the basic blocks entered by `trace` and adds them to `Project::coverage`. Returns the
+/// number of trace entries that hit a basic block.
+pub fn record(proj: &mut Project, trace: &[(u64, u64)]) -> usize {
+    // start of each mnemonic -> blocks containing it and whether it's their first mnemonic
+    let mut index = BTreeMap::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for bb in func.basic_blocks() {
+                let idx = BasicBlockIndex { function: *func.uuid(), start: bb.area.start };
+
+                for mne in bb.mnemonics.iter() {
+                    index.entry(mne.area.start).or_insert(vec![]).push((idx, mne.area.start == bb.area.start));
+                }
+            }
+        }
+    }
+
+    let mut previous = HashSet::new();
+    let mut mapped = 0;
+
+    for &(start, len) in trace {
+        let mut current = HashSet::new();
+
+        for (_, blocks) in index.range(start..start.saturating_add(len)) {
+            for &(idx, first) in blocks.iter() {
+                if current.insert(idx) && (first || !previous.contains(&idx)) {
+                    *proj.coverage.hits.entry(idx).or_insert(0) += 1;
+                }
+            }
+        }
+
+        if current.is_empty() {
+            proj.coverage.unmapped += 1;
+        } else {
+            mapped += 1;
+        }
+        previous = current;
+    }
+
+    mapped
+}
+
+/// Reads the trace at `path`. Guesses the format if `format` is None. `proj` is used to map the
+/// modules of drcov files.
+pub fn read(proj: &Project, path: &Path, format: Option<TraceFormat>) -> Result<Trace> {
+    let mut bytes = vec![];
+
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    match format.unwrap_or_else(|| TraceFormat::detect(&bytes)) {
+        TraceFormat::Drcov => drcov_trace(proj, &bytes),
+        f => parse_text(&bytes, f),
+    }
+}
+
+/// Reads the trace at `path` and adds it to `Project::coverage`, see `record`. Guesses the
+/// format if `format` is None.
+pub fn import(proj: &mut Project, path: &Path, format: Option<TraceFormat>) -> Result<usize> {
+    let trace = read(proj, path, format)?;
+
+    Ok(record(proj, &trace))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Guard, Mnemonic, Program, Region};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    /*
+     * 0x100: nop; nop
+     * 0x104: ret
+     */
+    fn project() -> (Project, Uuid) {
+        let mut proj = Project::new("test".to_string(), Region::wrap("RAM".to_string(), vec![0; 0x1000]));
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![Mnemonic::dummy(0x100..0x102), Mnemonic::dummy(0x102..0x104)])));
+        let b1 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![Mnemonic::dummy(0x104..0x105)])));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        let mut prog = Program::new("prog");
+        let uuid = *func.uuid();
+
+        cfg.add_edge(Guard::True, b0, b1);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        prog.insert(func);
+        proj.code.push(prog);
+        (proj, uuid)
+    }
+
+    #[test]
+    fn instruction_trace() {
+        let (mut proj, uuid) = project();
+        let pt = b"[enabled]\n0000000000000100  nop\n0000000000000102  nop\n0000000000000104  ret\n[disabled]\n100\n102\n104\n500 junk\n";
+        let trace = parse_text(pt, TraceFormat::detect(pt)).unwrap();
+
+        assert_eq!(TraceFormat::detect(pt), TraceFormat::IntelPt);
+        assert_eq!(record(&mut proj, &trace), 6);
+        assert_eq!(proj.coverage.hits(&BasicBlockIndex { function: uuid, start: 0x100 }), 2);
+        assert_eq!(proj.coverage.function(&uuid).into_iter().collect::<Vec<_>>(), vec![(0x100, 2), (0x104, 2)]);
+        assert_eq!(proj.coverage.unmapped, 1);
+
+        // entering in the middle of a block counts, too
+        assert_eq!(record(&mut proj, &parse_text(b"# comment\n0x102\n\n", TraceFormat::Addresses).unwrap()), 1);
+        assert_eq!(proj.coverage.hits(&BasicBlockIndex { function: uuid, start: 0x100 }), 3);
+        assert!(parse_text(b"0x100\nret\n", TraceFormat::Addresses).is_err());
+    }
+
+    #[test]
+    fn drcov() {
+        let (mut proj, uuid) = project();
+        let mut file = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 2\n\
+                         Columns: id, base, end, entry, checksum, timestamp, path\n\
+                          0, 0x100, 0x1000, 0x0000000000000000, 0x00000000, 0x00000000, /usr/bin/test\n\
+                          1, 0x7f0000, 0x7f1000, 0x0000000000000000, 0x00000000, 0x00000000, /lib/libc.so.6\n\
+                         BB Table: 2 bbs\n"
+            .to_vec();
+
+        file.extend_from_slice(&[0, 0, 0, 0, 5, 0, 0, 0]);
+        file.extend_from_slice(&[0x10, 0, 0, 0, 4, 0, 1, 0]);
+        assert_eq!(TraceFormat::detect(&file), TraceFormat::Drcov);
+
+        let trace = drcov_trace(&proj, &file).unwrap();
+
+        assert_eq!(trace[0], (0x100, 5));
+        assert_eq!(record(&mut proj, &trace), 1);
+        assert_eq!(proj.coverage.function(&uuid).into_iter().collect::<Vec<_>>(), vec![(0x100, 1), (0x104, 1)]);
+        assert_eq!(proj.coverage.unmapped, 1);
+    }
+}
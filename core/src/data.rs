@@ -0,0 +1,342 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Data definitions.
+//!
+//! The counterpart of disassembly for the non-code parts of an executable. [`define`] declares
+//! a variable of a `DataType` at an address, e.g. an integer, an array, a struct from
+//! `Project::data_types` or a string ([`define_string`]). The variable is stored with the other
+//! global variables in `TypeLibrary::globals`. Data can only be defined in non-executable
+//! sections, or outside of disassembled basic blocks if the loader recorded no sections, and two
+//! definitions can't overlap.
+//!
+//! [`elements`] reads the values of all fields of a definition, using the byte order and pointer
+//! size of the type library. Cross references of a definition are those of all its bytes:
+//! [`xrefs_to`] returns the code reading, writing or taking the address of any of its fields,
+//! [`pointers`] the addresses its pointer fields point to.
+//!
+//! [`define`]: fn.define.html
+//! [`define_string`]: fn.define_string.html
+//! [`elements`]: fn.elements.html
+//! [`xrefs_to`]: fn.xrefs_to.html
+//! [`pointers`]: fn.pointers.html
+
+use {DataType, Declaration, Definition, Project, Result, Xref};
+use strings::Encoding;
+
+/// Longest zero terminated string `define_string` looks for.
+pub const MAX_STRING: u64 = 0x10000;
+
+/// Nesting depth after which `elements` stops descending into fields.
+const MAX_DEPTH: usize = 32;
+
+/// Field of a data definition.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Element {
+    /// First byte of the field
+    pub address: u64,
+    /// Variable name followed by the path to the field, like `config.ports[2]`
+    pub name: String,
+    /// Field type
+    pub ty: DataType,
+    /// Field value, None if its bytes are undefined or its type has no value
+    pub value: Option<String>,
+}
+
+/// Declares a variable `name` of type `ty` at `address`. Fails if `address` isn't data or an
+/// existing definition overlaps.
+pub fn define(proj: &mut Project, address: u64, name: &str, ty: DataType) -> Result<()> {
+    let size = match proj.data_types.size_of(&ty) {
+        Some(s) if s > 0 => s,
+        _ => return Err(format!("size of {} is unknown", ty).into()),
+    };
+    let end = match address.checked_add(size) {
+        Some(end) if end <= proj.region().size() => end,
+        _ => return Err(format!("{:#x}+{:#x} is outside of {}", address, size, proj.region().name()).into()),
+    };
+
+    if proj.sections.is_empty() {
+        let code = proj.code
+            .iter()
+            .flat_map(|p| p.functions())
+            .flat_map(|f| f.basic_blocks())
+            .find(|bb| bb.area.start < end && address < bb.area.end)
+            .map(|bb| bb.area.start);
+
+        if let Some(bb) = code {
+            return Err(format!("{:#x}..{:#x} overlaps the basic block at {:#x}", address, end, bb).into());
+        }
+    } else if !proj.sections.iter().any(|s| !s.execute && s.area.start <= address && end <= s.area.end) {
+        return Err(format!("{:#x}..{:#x} isn't inside a data section", address, end).into());
+    }
+
+    if let Some((start, decl)) = overlapping(proj, address, end) {
+        return Err(format!("{:#x}..{:#x} overlaps {} at {:#x}", address, end, decl.name, start).into());
+    }
+
+    proj.data_types.apply(address, name, ty);
+    Ok(())
+}
+
+/// Declares the string at `address` as a character array named `name`. Uses the literal found
+/// by `strings::scan` if there is one, otherwise `address` must start a zero terminated 8 bit
+/// string. Returns the length of the array.
+pub fn define_string(proj: &mut Project, address: u64, name: &str) -> Result<u64> {
+    let ty = match proj.strings.get(&address) {
+        Some(lit) if lit.encoding == Encoding::Utf16 => DataType::Integer { size: 2, signed: false }.array_of(lit.area.len() / 2 + 1),
+        Some(lit) => DataType::Char.array_of(lit.area.len() + 1),
+        None => {
+            let region = proj.region();
+            let len = (0..MAX_STRING).take_while(|&i| address.checked_add(i).is_some()).find(|&i| region.read_u8(address + i).unwrap_or(0) == 0);
+
+            match len.map(|l| (l, region.read_u8(address + l))) {
+                Some((l, Some(0))) => DataType::Char.array_of(l + 1),
+                _ => return Err(format!("no zero terminated string at {:#x}", address).into()),
+            }
+        }
+    };
+    let len = match ty {
+        DataType::Array { count, .. } => count,
+        _ => 0,
+    };
+
+    define(proj, address, name, ty)?;
+    Ok(len)
+}
+
+/// Removes the definition starting at `address`.
+pub fn undefine(proj: &mut Project, address: u64) -> Option<Declaration> {
+    proj.data_types.globals.remove(&address)
+}
+
+/// Start and size of the definition covering `address`.
+pub fn item(proj: &Project, address: u64) -> Option<(u64, u64, &Declaration)> {
+    let (&start, decl) = proj.data_types.globals.range(..address.saturating_add(1)).next_back()?;
+    let size = proj.data_types.size_of(&decl.ty).unwrap_or(1);
+
+    if address < start.saturating_add(size) { Some((start, size, decl)) } else { None }
+}
+
+/// First definition overlapping `start..end`.
+fn overlapping(proj: &Project, start: u64, end: u64) -> Option<(u64, &Declaration)> {
+    proj.data_types
+        .globals
+        .range(..end)
+        .find(|&(&a, d)| a.saturating_add(proj.data_types.size_of(&d.ty).unwrap_or(1)) > start)
+        .map(|(&a, d)| (a, d))
+}
+
+/// All fields of the definition covering `address`, in memory order.
+pub fn elements(proj: &Project, address: u64) -> Result<Vec<Element>> {
+    let (start, _, decl) = match item(proj, address) {
+        Some(i) => i,
+        None => return Err(format!("no data defined at {:#x}", address).into()),
+    };
+    let mut ret = vec![];
+
+    flatten(proj, &decl.ty, start, decl.name.clone(), 0, &mut ret);
+    Ok(ret)
+}
+
+fn flatten(proj: &Project, ty: &DataType, address: u64, name: String, depth: usize, out: &mut Vec<Element>) {
+    let types = &proj.data_types;
+
+    if depth > MAX_DEPTH {
+        return;
+    }
+
+    match types.resolve(ty) {
+        &DataType::Array { ref element, count } => {
+            let string = proj.strings.get(&address).map(|s| s.to_string());
+
+            if string.is_some() || *types.resolve(element) == DataType::Char {
+                let value = string.or_else(
+                    || {
+                        let bytes = proj.region().read(address, count as usize)?;
+                        let text = bytes.split(|&b| b == 0).next().unwrap_or(&[]);
+
+                        Some(format!("{:?}", String::from_utf8_lossy(text)))
+                    }
+                );
+
+                out.push(Element { address: address, name: name, ty: ty.clone(), value: value });
+            } else if let Some(size) = types.size_of(element) {
+                for i in 0..count {
+                    flatten(proj, element, address + i * size, format!("{}[{}]", name, i), depth + 1, out);
+                }
+            }
+        }
+        &DataType::Named(ref n) => {
+            match types.definitions.get(n) {
+                Some(&Definition::Struct { ref members, .. }) |
+                Some(&Definition::Union { ref members, .. }) => {
+                    for m in members.iter() {
+                        let path = if m.name.is_empty() { name.clone() } else { format!("{}.{}", name, m.name) };
+
+                        flatten(proj, &m.ty, address + m.offset, path, depth + 1, out);
+                    }
+                }
+                Some(&Definition::Enum { size, ref values }) => {
+                    let value = proj.region().read_uint(address, size as usize, types.endianess).map(
+                        |v| {
+                            let v = sign_extend(v, size);
+
+                            match values.iter().find(|e| e.1 == v) {
+                                Some(e) => e.0.clone(),
+                                None => v.to_string(),
+                            }
+                        }
+                    );
+
+                    out.push(Element { address: address, name: name, ty: ty.clone(), value: value });
+                }
+                _ => out.push(Element { address: address, name: name, ty: ty.clone(), value: None }),
+            }
+        }
+        resolved => {
+            let size = types.size_of(resolved).unwrap_or(0);
+            let raw = if size > 0 && size <= 8 { proj.region().read_uint(address, size as usize, types.endianess) } else { None };
+            let value = raw.and_then(
+                |v| match resolved {
+                    &DataType::Bool => Some((v != 0).to_string()),
+                    &DataType::Char => Some(format!("{:?}", v as u8 as char)),
+                    &DataType::Integer { signed: true, .. } => Some(sign_extend(v, size).to_string()),
+                    &DataType::Integer { signed: false, .. } | &DataType::Pointer(_) => Some(format!("{:#x}", v)),
+                    &DataType::Float { size: 4 } => Some(f32::from_bits(v as u32).to_string()),
+                    &DataType::Float { size: 8 } => Some(f64::from_bits(v).to_string()),
+                    _ => None,
+                }
+            );
+
+            out.push(Element { address: address, name: name, ty: ty.clone(), value: value });
+        }
+    }
+}
+
+fn sign_extend(value: u64, size: u64) -> i64 {
+    if size > 0 && size < 8 && value & (1 << (size * 8 - 1)) != 0 {
+        (value as i64).wrapping_sub(1 << (size * 8))
+    } else {
+        value as i64
+    }
+}
+
+/// Code references to any byte of the definition covering `address`.
+pub fn xrefs_to(proj: &Project, address: u64) -> Vec<&Xref> {
+    match item(proj, address) {
+        Some((start, size, _)) => proj.xrefs.range(start, start + size).collect(),
+        None => vec![],
+    }
+}
+
+/// Non-null pointer fields of the definition covering `address` and the addresses they point
+/// to.
+pub fn pointers(proj: &Project, address: u64) -> Vec<(u64, u64)> {
+    let types = &proj.data_types;
+    let elements = elements(proj, address).unwrap_or_default();
+
+    elements.iter()
+        .filter(
+            |e| match types.resolve(&e.ty) {
+                &DataType::Pointer(_) => true,
+                _ => false,
+            }
+        )
+        .filter_map(|e| proj.region().read_uint(e.address, types.pointer_size as usize, types.endianess).map(|p| (e.address, p)))
+        .filter(|&(_, p)| p != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Bound, Region, Section, XrefKind};
+    use strings;
+    use uuid::Uuid;
+
+    fn section(name: &str, start: u64, end: u64, execute: bool) -> Section {
+        Section { name: name.to_string(), area: Bound::new(start, end), file_size: end - start, file_offset: start, read: true, write: !execute, execute: execute }
+    }
+
+    #[test]
+    fn define_data() {
+        let mut bytes = vec![0u8; 0x100];
+        bytes[0x80..0x86].copy_from_slice(b"port\0\0");
+        bytes[0x88..0x8c].copy_from_slice(&[0xfe, 0xff, 0xff, 0xff]);
+        bytes[0x8c..0x8e].copy_from_slice(&[80, 0]);
+        bytes[0x90] = 1;
+        bytes[0x98..0xa0].copy_from_slice(&[0x80, 0, 0, 0, 0, 0, 0, 0]);
+        bytes[0xa0..0xa5].copy_from_slice(b"Wide\0");
+
+        let mut proj = Project::new("test".to_string(), Region::wrap("RAM".to_string(), bytes.clone()));
+        let lits = strings::scan(0, &bytes.iter().map(|&b| Some(b)).collect::<Vec<_>>());
+        proj.strings = lits.into_iter().map(|s| (s.area.start, s)).collect();
+        proj.set_sections(vec![section(".text", 0, 0x80, true), section(".data", 0x80, 0x100, false)]);
+        proj.data_types.define("mode", Definition::Enum { size: 1, values: vec![("OFF".to_string(), 0), ("ON".to_string(), 1)] });
+        proj.data_types
+            .define_struct(
+                "struct config",
+                vec![
+                    ("name".to_string(), DataType::Char.array_of(6)),
+                    ("delta".to_string(), DataType::Integer { size: 4, signed: true }),
+                    ("ports".to_string(), DataType::Integer { size: 2, signed: false }.array_of(2)),
+                    ("mode".to_string(), DataType::Named("mode".to_string())),
+                    ("next".to_string(), DataType::Named("struct config".to_string()).pointer_to()),
+                ]
+            )
+            .unwrap();
+
+        assert!(define(&mut proj, 0x10, "code", DataType::Bool).is_err());
+        assert!(define(&mut proj, 0xfc, "edge", DataType::Integer { size: 8, signed: false }).is_err());
+        assert!(define(&mut proj, 0x80, "void", DataType::Void).is_err());
+        assert!(define(&mut proj, 0x80, "cfg", DataType::Named("struct config".to_string())).is_ok());
+        assert!(define(&mut proj, 0x90, "flag", DataType::Bool).is_err());
+        assert_eq!(define_string(&mut proj, 0xa0, "wide").unwrap(), 5);
+        assert!(define_string(&mut proj, 0xa2, "tail").is_err());
+
+        assert_eq!(item(&proj, 0x9f).map(|x| (x.0, x.1)), Some((0x80, 0x20)));
+        assert_eq!(item(&proj, 0xa5).map(|x| x.0), None);
+
+        let elems = elements(&proj, 0x8d).unwrap();
+        let values = elems.iter().map(|e| (e.name.as_str(), e.value.clone().unwrap_or_default())).collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                ("cfg.name", "\"port\"".to_string()),
+                ("cfg.delta", "-2".to_string()),
+                ("cfg.ports[0]", "0x50".to_string()),
+                ("cfg.ports[1]", "0x0".to_string()),
+                ("cfg.mode", "ON".to_string()),
+                ("cfg.next", "0x80".to_string()),
+            ]
+        );
+        assert_eq!(elems[2].address, 0x8c);
+        assert_eq!(elements(&proj, 0xa0).unwrap()[0].value, Some("\"Wide\"".to_string()));
+        assert_eq!(pointers(&proj, 0x80), vec![(0x98, 0x80)]);
+
+        let func = Uuid::new_v4();
+        for &(target, kind) in [(0x7c, XrefKind::Read), (0x8c, XrefKind::Write), (0x98, XrefKind::Address), (0xa0, XrefKind::Read)].iter() {
+            proj.xrefs.insert(Xref { function: func, address: 0x10, statement: Some(0), target: target, kind: kind });
+        }
+        assert_eq!(xrefs_to(&proj, 0x84).iter().map(|x| x.target).collect::<Vec<_>>(), vec![0x8c, 0x98]);
+
+        assert_eq!(undefine(&mut proj, 0x80).map(|d| d.name), Some("cfg".to_string()));
+        assert!(define(&mut proj, 0x90, "flag", DataType::Bool).is_ok());
+        assert!(xrefs_to(&proj, 0x84).is_empty());
+    }
+}
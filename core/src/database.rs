@@ -0,0 +1,492 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! SQLite backed project database.
+//!
+//! `Project::snapshot` writes the whole project at once and `Project::open` reads it back into
+//! memory. A [`Database`] instead keeps functions, comments and cross references in tables of
+//! their own. Single functions and comments are written in their own transaction while the
+//! analysis is running, and are read back one by one without loading the rest of the project.
+//!
+//! Functions are stored as CBOR. The call graph of each program is stored with `Todo`
+//! placeholders in place of the functions and is put back together by [`Database::load`].
+//!
+//! ```ignore
+//! let mut db = Database::create(Path::new("a.out.db"))?;
+//! db.save(&mut project)?;
+//! for entry in db.functions()? {
+//!     println!("{:#x} {}", entry.start, entry.name);
+//! }
+//! ```
+//!
+//! [`Database`]: struct.Database.html
+//! [`Database::load`]: struct.Database.html#method.load
+
+use {CallGraph, CallTarget, ControlFlowTarget, Function, NameSource, Program, Project, Result, Rvalue, Xref, XrefDatabase, XrefKind};
+use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use rusqlite::{Connection, OptionalExtension};
+use serde_cbor;
+use std::collections::HashMap;
+use std::mem;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Version of the database schema.
+const SCHEMA_VERSION: i64 = 0;
+
+const SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value BLOB NOT NULL);
+    CREATE TABLE IF NOT EXISTS programs (uuid TEXT PRIMARY KEY, name TEXT NOT NULL, data BLOB NOT NULL);
+    CREATE TABLE IF NOT EXISTS functions (
+        uuid TEXT PRIMARY KEY, program TEXT NOT NULL, start INTEGER NOT NULL, name TEXT NOT NULL, data BLOB NOT NULL);
+    CREATE INDEX IF NOT EXISTS functions_start ON functions (start);
+    CREATE TABLE IF NOT EXISTS comments (region TEXT NOT NULL, address INTEGER NOT NULL, text TEXT NOT NULL, PRIMARY KEY (region, address));
+    CREATE TABLE IF NOT EXISTS xrefs (
+        function TEXT NOT NULL, address INTEGER NOT NULL, statement INTEGER, target INTEGER NOT NULL, kind INTEGER NOT NULL);
+    CREATE INDEX IF NOT EXISTS xrefs_target ON xrefs (target);
+    CREATE INDEX IF NOT EXISTS xrefs_function ON xrefs (function);
+";
+
+/// Function stored in a database, without its code.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct FunctionEntry {
+    /// UUID of the function
+    pub uuid: Uuid,
+    /// UUID of the program the function is part of
+    pub program: Uuid,
+    /// Entry point
+    pub start: u64,
+    /// Human-readable name
+    pub name: String,
+}
+
+/// Project stored in a SQLite database.
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Opens the database at `p`, creating it if it doesn't exist.
+    pub fn create(p: &Path) -> Result<Database> {
+        Database::init(Connection::open(p)?)
+    }
+
+    /// Opens an existing database at `p`.
+    pub fn open(p: &Path) -> Result<Database> {
+        if !p.exists() {
+            return Err(format!("failed to open database: {} does not exist", p.display()).into());
+        }
+
+        Database::init(Connection::open(p)?)
+    }
+
+    /// Returns a new database kept in memory.
+    pub fn in_memory() -> Result<Database> {
+        Database::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Database> {
+        conn.execute_batch(SCHEMA)?;
+
+        let version = conn.query_row("SELECT value FROM meta WHERE key = 'version'", params![], |row| row.get::<_, i64>(0)).optional()?;
+
+        match version {
+            Some(SCHEMA_VERSION) => {}
+            Some(_) => return Err("wrong version".into()),
+            None => {
+                conn.execute("INSERT INTO meta (key, value) VALUES ('version', ?1)", params![SCHEMA_VERSION])?;
+            }
+        }
+
+        Ok(Database { conn: conn })
+    }
+
+    /// Replaces the contents of the database with `proj`, in one transaction. `proj` is left
+    /// unchanged.
+    pub fn save(&mut self, proj: &mut Project) -> Result<()> {
+        // everything stored in tables of its own is taken out while the rest is serialized
+        let code = mem::replace(&mut proj.code, vec![]);
+        let comments = mem::replace(&mut proj.comments, HashMap::new());
+        let xrefs = mem::replace(&mut proj.xrefs, XrefDatabase::new());
+        let rest = serde_cbor::to_vec(&*proj);
+
+        proj.code = code;
+        proj.comments = comments;
+        proj.xrefs = xrefs;
+
+        let rest = rest?;
+        let tx = self.conn.transaction()?;
+
+        tx.execute_batch("DELETE FROM programs; DELETE FROM functions; DELETE FROM comments; DELETE FROM xrefs;")?;
+        tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('project', ?1)", params![rest])?;
+
+        for prog in proj.code.iter() {
+            let data = serde_cbor::to_vec(&skeleton(prog))?;
+
+            tx.execute("INSERT INTO programs (uuid, name, data) VALUES (?1, ?2, ?3)", params![prog.uuid.to_string(), prog.name, data])?;
+
+            for func in prog.functions() {
+                insert_function(&tx, &prog.uuid, func)?;
+            }
+        }
+
+        for (&(ref region, address), text) in proj.comments.iter() {
+            tx.execute("INSERT INTO comments (region, address, text) VALUES (?1, ?2, ?3)", params![region, address as i64, text])?;
+        }
+
+        for prog in proj.code.iter() {
+            for func in prog.functions() {
+                for xref in proj.xrefs_from(func.uuid()) {
+                    insert_xref(&tx, xref)?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Reads the whole project.
+    pub fn load(&self) -> Result<Project> {
+        let data = self.conn.query_row("SELECT value FROM meta WHERE key = 'project'", params![], |row| row.get::<_, Vec<u8>>(0)).optional()?;
+        let mut proj: Project = match data {
+            Some(data) => serde_cbor::from_slice(&data)?,
+            None => return Err("database contains no project".into()),
+        };
+        let mut functions = HashMap::<Uuid, (Uuid, Function)>::new();
+
+        for entry in self.functions()? {
+            if let Some(func) = self.function(&entry.uuid)? {
+                functions.insert(entry.uuid, (entry.program, func));
+            }
+        }
+
+        let mut stmt = self.conn.prepare("SELECT data FROM programs ORDER BY rowid")?;
+        let programs = stmt.query_map(params![], |row| row.get::<_, Vec<u8>>(0))?.collect::<::std::result::Result<Vec<_>, _>>()?;
+
+        for data in programs {
+            let mut prog: Program = serde_cbor::from_slice(&data)?;
+            let vxs = prog.call_graph.vertices().collect::<Vec<_>>();
+
+            for vx in vxs {
+                let uuid = match prog.call_graph.vertex_label(vx) {
+                    Some(&CallTarget::Todo(_, _, ref uuid)) => uuid.clone(),
+                    _ => continue,
+                };
+
+                if let Some((_, func)) = functions.remove(&uuid) {
+                    *prog.call_graph.vertex_label_mut(vx).unwrap() = CallTarget::Concrete(func);
+                }
+            }
+
+            // functions saved after the program
+            let new = functions.keys().filter(|u| functions[*u].0 == prog.uuid).cloned().collect::<Vec<_>>();
+
+            for uuid in new {
+                if let Some((_, func)) = functions.remove(&uuid) {
+                    prog.insert(func);
+                }
+            }
+
+            proj.code.push(prog);
+        }
+
+        let mut stmt = self.conn.prepare("SELECT region, address, text FROM comments")?;
+        let comments = stmt.query_map(params![], |row| Ok(((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64), row.get::<_, String>(2)?)))?;
+
+        for c in comments {
+            let (key, text) = c?;
+            proj.comments.insert(key, text);
+        }
+
+        let mut stmt = self.conn.prepare("SELECT function, address, statement, target, kind FROM xrefs ORDER BY rowid")?;
+        let xrefs = stmt.query_map(params![], read_xref)?;
+
+        for x in xrefs {
+            proj.xrefs.insert(x??);
+        }
+
+        Ok(proj)
+    }
+
+    /// Adds `func` to the program with UUID `program`, replacing an older version of it.
+    pub fn save_function(&mut self, program: &Uuid, func: &Function) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        insert_function(&tx, program, func)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Reads the function with UUID `uuid`.
+    pub fn function(&self, uuid: &Uuid) -> Result<Option<Function>> {
+        let row = self.conn
+            .query_row(
+                "SELECT name, data FROM functions WHERE uuid = ?1",
+                params![uuid.to_string()],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            )
+            .optional()?;
+
+        match row {
+            Some((name, data)) => {
+                let mut func: Function = serde_cbor::from_slice(&data)?;
+
+                // `rename_function` only changes the column
+                if name != func.name {
+                    func.propose_name(name, NameSource::User);
+                }
+                Ok(Some(func))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all functions in the database, ordered by entry point.
+    pub fn functions(&self) -> Result<Vec<FunctionEntry>> {
+        let mut stmt = self.conn.prepare("SELECT uuid, program, start, name FROM functions ORDER BY start")?;
+        let rows = stmt.query_map(
+            params![],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?, row.get::<_, String>(3)?)),
+        )?;
+        let mut ret = vec![];
+
+        for row in rows {
+            let (uuid, program, start, name) = row?;
+
+            ret.push(FunctionEntry { uuid: parse_uuid(&uuid)?, program: parse_uuid(&program)?, start: start as u64, name: name });
+        }
+
+        Ok(ret)
+    }
+
+    /// Renames the function with UUID `uuid`.
+    pub fn rename_function(&mut self, uuid: &Uuid, name: &str) -> Result<()> {
+        self.conn.execute("UPDATE functions SET name = ?1 WHERE uuid = ?2", params![name, uuid.to_string()])?;
+        Ok(())
+    }
+
+    /// Sets the comment at `address` in `region`. An empty comment removes it.
+    pub fn set_comment(&mut self, region: &str, address: u64, text: &str) -> Result<()> {
+        if text.is_empty() {
+            self.conn.execute("DELETE FROM comments WHERE region = ?1 AND address = ?2", params![region, address as i64])?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO comments (region, address, text) VALUES (?1, ?2, ?3)",
+                params![region, address as i64, text],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the comment at `address` in `region`.
+    pub fn comment(&self, region: &str, address: u64) -> Result<Option<String>> {
+        Ok(
+            self.conn
+                .query_row(
+                    "SELECT text FROM comments WHERE region = ?1 AND address = ?2",
+                    params![region, address as i64],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+        )
+    }
+
+    /// Replaces the cross references from the function with UUID `function` with `xrefs`.
+    pub fn save_xrefs(&mut self, function: &Uuid, xrefs: &[Xref]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        tx.execute("DELETE FROM xrefs WHERE function = ?1", params![function.to_string()])?;
+        for xref in xrefs {
+            insert_xref(&tx, xref)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns all cross references to `address`.
+    pub fn xrefs_to(&self, address: u64) -> Result<Vec<Xref>> {
+        let mut stmt = self.conn.prepare("SELECT function, address, statement, target, kind FROM xrefs WHERE target = ?1 ORDER BY rowid")?;
+        let rows = stmt.query_map(params![address as i64], read_xref)?;
+        let mut ret = vec![];
+
+        for x in rows {
+            ret.push(x??);
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Returns the entry point of `func`, or the address it will be disassembled from.
+fn start(func: &Function) -> u64 {
+    match func.cfg().vertex_label(func.entry_point_ref()) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => bb.area.start,
+        Some(&ControlFlowTarget::Unresolved(Rvalue::Constant { value, .. })) => value,
+        _ => 0,
+    }
+}
+
+/// Copy of `prog` with `Todo` placeholders in place of its functions.
+fn skeleton(prog: &Program) -> Program {
+    let mut call_graph = CallGraph::new();
+    let mut vxs = HashMap::new();
+
+    for vx in prog.call_graph.vertices() {
+        let ct = match prog.call_graph.vertex_label(vx) {
+            Some(&CallTarget::Concrete(ref func)) => CallTarget::Todo(Rvalue::new_u64(start(func)), Some(func.name.clone()), func.uuid().clone()),
+            Some(&CallTarget::Symbolic(ref name, ref uuid)) => CallTarget::Symbolic(name.clone(), uuid.clone()),
+            Some(&CallTarget::Todo(ref rv, ref name, ref uuid)) => CallTarget::Todo(rv.clone(), name.clone(), uuid.clone()),
+            None => continue,
+        };
+
+        vxs.insert(vx, call_graph.add_vertex(ct));
+    }
+
+    for e in prog.call_graph.edges() {
+        if let (Some(&from), Some(&to)) = (vxs.get(&prog.call_graph.source(e)), vxs.get(&prog.call_graph.target(e))) {
+            call_graph.add_edge((), from, to);
+        }
+    }
+
+    Program {
+        uuid: prog.uuid.clone(),
+        name: prog.name.clone(),
+        call_graph: call_graph,
+        imports: prog.imports.clone(),
+        thunks: prog.thunks.clone(),
+        relocations: prog.relocations.clone(),
+        analyzed_calls: prog.analyzed_calls.clone(),
+        name_sources: prog.name_sources.clone(),
+    }
+}
+
+fn insert_function(conn: &Connection, program: &Uuid, func: &Function) -> Result<()> {
+    let data = serde_cbor::to_vec(func)?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO functions (uuid, program, start, name, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![func.uuid().to_string(), program.to_string(), start(func) as i64, func.name, data],
+    )?;
+    Ok(())
+}
+
+fn insert_xref(conn: &Connection, xref: &Xref) -> Result<()> {
+    let kind = match xref.kind {
+        XrefKind::Read => 0,
+        XrefKind::Write => 1,
+        XrefKind::Call => 2,
+        XrefKind::Jump => 3,
+        XrefKind::Address => 4,
+    };
+
+    conn.execute(
+        "INSERT INTO xrefs (function, address, statement, target, kind) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![xref.function.to_string(), xref.address as i64, xref.statement.map(|s| s as i64), xref.target as i64, kind],
+    )?;
+    Ok(())
+}
+
+fn read_xref(row: &::rusqlite::Row) -> ::rusqlite::Result<Result<Xref>> {
+    let function = row.get::<_, String>(0)?;
+    let address = row.get::<_, i64>(1)? as u64;
+    let statement = row.get::<_, Option<i64>>(2)?.map(|s| s as usize);
+    let target = row.get::<_, i64>(3)? as u64;
+    let kind = match row.get::<_, i64>(4)? {
+        0 => XrefKind::Read,
+        1 => XrefKind::Write,
+        2 => XrefKind::Call,
+        3 => XrefKind::Jump,
+        _ => XrefKind::Address,
+    };
+
+    Ok(parse_uuid(&function).map(|function| Xref { function: function, address: address, statement: statement, target: target, kind: kind }))
+}
+
+fn parse_uuid(s: &str) -> Result<Uuid> {
+    Uuid::parse_str(s).map_err(|e| format!("invalid UUID {}: {:?}", s, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Mnemonic, Region};
+
+    fn function(start: u64, region: &Region) -> Function {
+        let mne = Mnemonic::new(start..start + 4, "ret".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, region, None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let mut prog = Program::new("prog");
+        let func = function(0x100, proj.region());
+        let uuid = func.uuid().clone();
+
+        prog.insert(func);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(0x200), Some("todo".to_string()), Uuid::new_v4()));
+        proj.code.push(prog);
+        proj.comments.insert(("RAM".to_string(), 0x100), "entry".to_string());
+        proj.xrefs.insert(Xref { function: uuid.clone(), address: 0x100, statement: Some(0), target: 0x800, kind: XrefKind::Read });
+
+        let mut db = Database::in_memory().unwrap();
+
+        db.save(&mut proj).unwrap();
+        assert_eq!(proj.code.len(), 1);
+        assert_eq!(proj.comments.len(), 1);
+
+        let entries = db.functions().unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].uuid, uuid);
+        assert_eq!(entries[0].program, proj.code[0].uuid);
+        assert_eq!(entries[0].start, 0x100);
+        assert_eq!(db.xrefs_to(0x800).unwrap(), proj.xrefs_to(0x800).to_vec());
+
+        let mut func = function(0x300, proj.region());
+        func.name = "new".to_string();
+        db.save_function(&proj.code[0].uuid, &func).unwrap();
+        db.rename_function(&uuid, "main").unwrap();
+        db.set_comment("RAM", 0x300, "second").unwrap();
+        db.set_comment("RAM", 0x100, "").unwrap();
+        assert_eq!(db.comment("RAM", 0x300).unwrap(), Some("second".to_string()));
+        assert_eq!(db.function(func.uuid()).unwrap().map(|f| f.name), Some("new".to_string()));
+
+        let loaded = db.load().unwrap();
+        let mut names = loaded.code[0].functions().map(|f| f.name.clone()).collect::<Vec<_>>();
+
+        names.sort();
+
+        assert_eq!(loaded.name, "test");
+        assert_eq!(loaded.code.len(), 1);
+        assert_eq!(names, vec!["main".to_string(), "new".to_string()]);
+        assert_eq!(loaded.code[0].call_graph.num_vertices(), 3);
+        assert_eq!(loaded.comments.get(&("RAM".to_string(), 0x300)), Some(&"second".to_string()));
+        assert_eq!(loaded.comments.len(), 1);
+        assert_eq!(loaded.xrefs.len(), 1);
+        assert_eq!(loaded.region().size(), 0x1000);
+    }
+}
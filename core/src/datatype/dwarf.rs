@@ -0,0 +1,728 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! DWARF type information.
+//!
+//! Reads the debugging information entries of all compilation units in `.debug_info`, DWARF
+//! versions 2 to 5, and converts base, pointer, array, struct, union, enum and typedef entries.
+//! Global variables with a fixed address are applied to it, the prototypes of functions with an
+//! entry point are added to `TypeLibrary::prototypes` and their names to
+//! `TypeLibrary::function_names`. Strings in `.debug_line_str` or the string offset table of
+//! DWARF 5 aren't supported, the entries using them end up anonymous.
+
+use super::{DataType, Declaration, Definition, Member, TypeLibrary, MAX_DEPTH};
+use {Endianess, Result, Signature};
+use std::collections::HashMap;
+
+const DW_TAG_ARRAY_TYPE: u64 = 0x01;
+const DW_TAG_CLASS_TYPE: u64 = 0x02;
+const DW_TAG_ENUMERATION_TYPE: u64 = 0x04;
+const DW_TAG_FORMAL_PARAMETER: u64 = 0x05;
+const DW_TAG_MEMBER: u64 = 0x0d;
+const DW_TAG_POINTER_TYPE: u64 = 0x0f;
+const DW_TAG_REFERENCE_TYPE: u64 = 0x10;
+const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+const DW_TAG_STRUCTURE_TYPE: u64 = 0x13;
+const DW_TAG_SUBROUTINE_TYPE: u64 = 0x15;
+const DW_TAG_TYPEDEF: u64 = 0x16;
+const DW_TAG_UNION_TYPE: u64 = 0x17;
+const DW_TAG_UNSPECIFIED_PARAMETERS: u64 = 0x18;
+const DW_TAG_SUBRANGE_TYPE: u64 = 0x21;
+const DW_TAG_BASE_TYPE: u64 = 0x24;
+const DW_TAG_CONST_TYPE: u64 = 0x26;
+const DW_TAG_ENUMERATOR: u64 = 0x28;
+const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+const DW_TAG_VARIABLE: u64 = 0x34;
+const DW_TAG_VOLATILE_TYPE: u64 = 0x35;
+const DW_TAG_RESTRICT_TYPE: u64 = 0x37;
+const DW_TAG_RVALUE_REFERENCE_TYPE: u64 = 0x42;
+const DW_TAG_ATOMIC_TYPE: u64 = 0x47;
+
+const DW_AT_LOCATION: u64 = 0x02;
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_BYTE_SIZE: u64 = 0x0b;
+const DW_AT_LOW_PC: u64 = 0x11;
+const DW_AT_CONST_VALUE: u64 = 0x1c;
+const DW_AT_UPPER_BOUND: u64 = 0x2f;
+const DW_AT_COUNT: u64 = 0x37;
+const DW_AT_DATA_MEMBER_LOCATION: u64 = 0x38;
+const DW_AT_DECLARATION: u64 = 0x3c;
+const DW_AT_ENCODING: u64 = 0x3e;
+const DW_AT_TYPE: u64 = 0x49;
+
+const DW_ATE_BOOLEAN: u64 = 0x02;
+const DW_ATE_FLOAT: u64 = 0x04;
+const DW_ATE_SIGNED: u64 = 0x05;
+const DW_ATE_SIGNED_CHAR: u64 = 0x06;
+
+const DW_OP_ADDR: u8 = 0x03;
+const DW_OP_PLUS_UCONST: u8 = 0x23;
+
+/// Attribute value.
+#[derive(Clone,Debug,PartialEq)]
+enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    String(String),
+    /// Offset of an entry in `.debug_info`
+    Reference(usize),
+    Block(Vec<u8>),
+    /// Forms not needed to read types
+    Unsupported,
+}
+
+impl Value {
+    fn unsigned(&self) -> Option<u64> {
+        match self {
+            &Value::Unsigned(v) => Some(v),
+            &Value::Signed(v) if v >= 0 => Some(v as u64),
+            _ => None,
+        }
+    }
+}
+
+/// Debugging information entry.
+#[derive(Debug)]
+struct Entry {
+    tag: u64,
+    attributes: Vec<(u64, Value)>,
+    children: Vec<usize>,
+    /// Is a direct child of a compilation unit
+    global: bool,
+    address_size: usize,
+}
+
+impl Entry {
+    fn get(&self, attr: u64) -> Option<&Value> {
+        self.attributes.iter().find(|a| a.0 == attr).map(|a| &a.1)
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self.get(DW_AT_NAME) {
+            Some(&Value::String(ref s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+struct Abbreviation {
+    tag: u64,
+    children: bool,
+    // attribute, form and the value of DW_FORM_implicit_const
+    attributes: Vec<(u64, u64, i64)>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    endianess: Endianess,
+}
+
+impl<'a> Reader<'a> {
+    fn fixed(&mut self, size: usize) -> Option<u64> {
+        let b = self.bytes.get(self.position..self.position + size)?;
+
+        self.position += size;
+        Some(
+            match self.endianess {
+                Endianess::Little => b.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64),
+                Endianess::Big => b.iter().fold(0u64, |acc, &b| acc << 8 | b as u64),
+            }
+        )
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let b = self.bytes.get(self.position..self.position.checked_add(len)?)?;
+
+        self.position += len;
+        Some(b)
+    }
+
+    fn uleb128(&mut self) -> Option<u64> {
+        let mut ret = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.fixed(1)?;
+
+            if shift < 64 {
+                ret |= (b & 0x7f) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                return Some(ret);
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> Option<i64> {
+        let mut ret = 0i64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.fixed(1)?;
+
+            if shift < 64 {
+                ret |= ((b & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    ret |= -1i64 << shift;
+                }
+                return Some(ret);
+            }
+        }
+    }
+
+    fn cstring(&mut self) -> Option<String> {
+        let len = self.bytes.get(self.position..)?.iter().position(|&b| b == 0)?;
+        let s = String::from_utf8_lossy(&self.bytes[self.position..self.position + len]).to_string();
+
+        self.position += len + 1;
+        Some(s)
+    }
+}
+
+/// Format of a compilation unit.
+struct Unit {
+    offset: usize,
+    version: u64,
+    offset_size: usize,
+    address_size: usize,
+}
+
+fn abbreviations(abbrev: &[u8], offset: usize, endianess: Endianess) -> Option<HashMap<u64, Abbreviation>> {
+    let mut rd = Reader { bytes: abbrev, position: offset, endianess: endianess };
+    let mut ret = HashMap::new();
+
+    loop {
+        let code = rd.uleb128()?;
+
+        if code == 0 {
+            return Some(ret);
+        }
+
+        let tag = rd.uleb128()?;
+        let children = rd.fixed(1)? != 0;
+        let mut attributes = vec![];
+
+        loop {
+            let (attr, form) = (rd.uleb128()?, rd.uleb128()?);
+
+            if attr == 0 && form == 0 {
+                break;
+            }
+
+            let implicit = if form == 0x21 { rd.sleb128()? } else { 0 };
+
+            attributes.push((attr, form, implicit));
+        }
+        ret.insert(code, Abbreviation { tag: tag, children: children, attributes: attributes });
+    }
+}
+
+fn value(rd: &mut Reader, form: u64, implicit: i64, unit: &Unit, strings: &[u8]) -> Option<Value> {
+    let offset = unit.offset_size;
+    let block = |rd: &mut Reader, len: u64| rd.bytes(len as usize).map(|b| Value::Block(b.to_vec()));
+
+    Some(
+        match form {
+            0x01 => Value::Unsigned(rd.fixed(unit.address_size)?),
+            0x03 => {
+                let len = rd.fixed(2)?;
+                block(rd, len)?
+            }
+            0x04 => {
+                let len = rd.fixed(4)?;
+                block(rd, len)?
+            }
+            0x09 | 0x18 => {
+                let len = rd.uleb128()?;
+                block(rd, len)?
+            }
+            0x0a => {
+                let len = rd.fixed(1)?;
+                block(rd, len)?
+            }
+            0x05 => Value::Unsigned(rd.fixed(2)?),
+            0x06 => Value::Unsigned(rd.fixed(4)?),
+            0x07 => Value::Unsigned(rd.fixed(8)?),
+            0x0b | 0x0c => Value::Unsigned(rd.fixed(1)?),
+            0x1e => {
+                rd.bytes(16)?;
+                Value::Unsupported
+            }
+            0x08 => Value::String(rd.cstring()?),
+            0x0e => {
+                let off = rd.fixed(offset)? as usize;
+                let mut s = Reader { bytes: strings, position: off, endianess: rd.endianess };
+
+                s.cstring().map(Value::String).unwrap_or(Value::Unsupported)
+            }
+            0x0d => Value::Signed(rd.sleb128()?),
+            0x0f => Value::Unsigned(rd.uleb128()?),
+            0x10 => {
+                let size = if unit.version == 2 { unit.address_size } else { offset };
+                Value::Reference(rd.fixed(size)? as usize)
+            }
+            0x11 => Value::Reference(unit.offset + rd.fixed(1)? as usize),
+            0x12 => Value::Reference(unit.offset + rd.fixed(2)? as usize),
+            0x13 => Value::Reference(unit.offset + rd.fixed(4)? as usize),
+            0x14 => Value::Reference(unit.offset + rd.fixed(8)? as usize),
+            0x15 => Value::Reference(unit.offset + rd.uleb128()? as usize),
+            0x16 => {
+                let form = rd.uleb128()?;
+                return value(rd, form, implicit, unit, strings);
+            }
+            0x17 | 0x1d | 0x1f => {
+                rd.fixed(offset)?;
+                Value::Unsupported
+            }
+            0x19 => Value::Unsigned(1),
+            0x1c => {
+                rd.fixed(4)?;
+                Value::Unsupported
+            }
+            0x20 | 0x24 => {
+                rd.fixed(8)?;
+                Value::Unsupported
+            }
+            0x21 => Value::Signed(implicit),
+            0x1a | 0x1b | 0x22 | 0x23 => {
+                rd.uleb128()?;
+                Value::Unsupported
+            }
+            0x25 | 0x29 => {
+                rd.fixed(1)?;
+                Value::Unsupported
+            }
+            0x26 | 0x2a => {
+                rd.fixed(2)?;
+                Value::Unsupported
+            }
+            0x27 | 0x2b => {
+                rd.fixed(3)?;
+                Value::Unsupported
+            }
+            0x28 | 0x2c => {
+                rd.fixed(4)?;
+                Value::Unsupported
+            }
+            _ => return None,
+        }
+    )
+}
+
+/// Reads all entries of `.debug_info`, by offset.
+fn entries(info: &[u8], abbrev: &[u8], strings: &[u8], endianess: Endianess) -> Result<HashMap<usize, Entry>> {
+    let mut ret = HashMap::new();
+    let mut rd = Reader { bytes: info, position: 0, endianess: endianess };
+
+    while rd.position < info.len() {
+        let start = rd.position;
+        let (length, offset_size) = match rd.fixed(4) {
+            Some(0xffffffff) => (rd.fixed(8).ok_or("truncated unit header")?, 8),
+            Some(l) => (l, 4),
+            None => return Err("truncated unit header".into()),
+        };
+        let end = rd.position.checked_add(length as usize).ok_or("invalid unit length")?;
+        let version = rd.fixed(2).ok_or("truncated unit header")?;
+        let (abbrev_offset, address_size) = match version {
+            2...4 => {
+                let off = rd.fixed(offset_size).ok_or("truncated unit header")?;
+                (off, rd.fixed(1).ok_or("truncated unit header")?)
+            }
+            5 => {
+                let kind = rd.fixed(1).ok_or("truncated unit header")?;
+                let size = rd.fixed(1).ok_or("truncated unit header")?;
+                let off = rd.fixed(offset_size).ok_or("truncated unit header")?;
+
+                match kind {
+                    // type units and split units
+                    2 | 6 => rd.position += 8 + offset_size,
+                    4 | 5 => rd.position += 8,
+                    _ => {}
+                }
+                (off, size)
+            }
+            v => return Err(format!("unsupported DWARF version {}", v).into()),
+        };
+        let unit = Unit { offset: start, version: version, offset_size: offset_size, address_size: address_size as usize };
+        let abbrevs = abbreviations(abbrev, abbrev_offset as usize, endianess).ok_or("invalid abbreviation table")?;
+        // entries with children whose children are being read
+        let mut parents: Vec<usize> = vec![];
+
+        while rd.position < end {
+            let offset = rd.position;
+            let code = rd.uleb128().ok_or("truncated entry")?;
+
+            if code == 0 {
+                parents.pop();
+                continue;
+            }
+
+            let abbr = abbrevs.get(&code).ok_or_else(|| format!("unknown abbreviation {} at {:#x}", code, offset))?;
+            let mut attributes = Vec::with_capacity(abbr.attributes.len());
+
+            for &(attr, form, implicit) in abbr.attributes.iter() {
+                let v = value(&mut rd, form, implicit, &unit, strings).ok_or_else(|| format!("invalid attribute {:#x} of form {:#x} at {:#x}", attr, form, offset))?;
+
+                attributes.push((attr, v));
+            }
+
+            let global = parents.last().and_then(|p| ret.get(p)).map(|p: &Entry| p.tag == DW_TAG_COMPILE_UNIT).unwrap_or(false);
+
+            if let Some(p) = parents.last().and_then(|p| ret.get_mut(p)) {
+                p.children.push(offset);
+            }
+            ret.insert(offset, Entry { tag: abbr.tag, attributes: attributes, children: vec![], global: global, address_size: unit.address_size });
+            if abbr.children {
+                parents.push(offset);
+            }
+        }
+
+        rd.position = end;
+    }
+
+    Ok(ret)
+}
+
+struct Converter<'a> {
+    entries: &'a HashMap<usize, Entry>,
+    names: HashMap<usize, String>,
+}
+
+impl<'a> Converter<'a> {
+    /// Name of the definition for the struct, union or enum at `offset`.
+    fn tag(&mut self, offset: usize, entry: &Entry) -> String {
+        if let Some(name) = self.names.get(&offset) {
+            return name.clone();
+        }
+
+        let keyword = match entry.tag {
+            DW_TAG_UNION_TYPE => "union",
+            DW_TAG_ENUMERATION_TYPE => "enum",
+            _ => "struct",
+        };
+        let name = match entry.name() {
+            Some(n) => format!("{} {}", keyword, n),
+            None => format!("{} __anonymous_{:x}", keyword, offset),
+        };
+
+        self.names.insert(offset, name.clone());
+        name
+    }
+
+    fn type_of(&mut self, entry: &Entry, depth: usize) -> DataType {
+        match entry.get(DW_AT_TYPE) {
+            Some(&Value::Reference(r)) => self.convert(r, depth + 1),
+            _ => DataType::Void,
+        }
+    }
+
+    /// Type of the entry at `offset`.
+    fn convert(&mut self, offset: usize, depth: usize) -> DataType {
+        let entries = self.entries;
+        let entry = match entries.get(&offset) {
+            Some(e) if depth < MAX_DEPTH => e,
+            _ => return DataType::Void,
+        };
+        let size = entry.get(DW_AT_BYTE_SIZE).and_then(|v| v.unsigned()).unwrap_or(0);
+
+        match entry.tag {
+            DW_TAG_BASE_TYPE => {
+                match entry.get(DW_AT_ENCODING).and_then(|v| v.unsigned()) {
+                    Some(DW_ATE_BOOLEAN) => DataType::Bool,
+                    Some(DW_ATE_FLOAT) => DataType::Float { size: size },
+                    Some(DW_ATE_SIGNED_CHAR) if size == 1 && entry.name() == Some("char") => DataType::Char,
+                    Some(DW_ATE_SIGNED) | Some(DW_ATE_SIGNED_CHAR) => DataType::Integer { size: size, signed: true },
+                    _ => DataType::Integer { size: size, signed: false },
+                }
+            }
+            DW_TAG_POINTER_TYPE | DW_TAG_REFERENCE_TYPE | DW_TAG_RVALUE_REFERENCE_TYPE => self.type_of(entry, depth).pointer_to(),
+            DW_TAG_CONST_TYPE | DW_TAG_VOLATILE_TYPE | DW_TAG_RESTRICT_TYPE | DW_TAG_ATOMIC_TYPE => self.type_of(entry, depth),
+            DW_TAG_TYPEDEF => {
+                match entry.name() {
+                    Some(name) => DataType::Named(name.to_string()),
+                    None => self.type_of(entry, depth),
+                }
+            }
+            DW_TAG_ARRAY_TYPE => {
+                let mut ty = self.type_of(entry, depth);
+
+                for sub in entry.children.iter().rev().filter_map(|c| entries.get(c)) {
+                    if sub.tag == DW_TAG_SUBRANGE_TYPE {
+                        let count = sub.get(DW_AT_COUNT)
+                            .and_then(|v| v.unsigned())
+                            .or_else(|| sub.get(DW_AT_UPPER_BOUND).and_then(|v| v.unsigned()).map(|u| u + 1))
+                            .unwrap_or(0);
+
+                        ty = ty.array_of(count);
+                    }
+                }
+                ty
+            }
+            DW_TAG_SUBROUTINE_TYPE => {
+                let args = entry.children
+                    .iter()
+                    .filter_map(|c| entries.get(c))
+                    .filter(|c| c.tag == DW_TAG_FORMAL_PARAMETER)
+                    .map(|c| self.type_of(c, depth))
+                    .collect();
+
+                DataType::Function { ret: Box::new(self.type_of(entry, depth)), args: args }
+            }
+            DW_TAG_STRUCTURE_TYPE | DW_TAG_CLASS_TYPE | DW_TAG_UNION_TYPE | DW_TAG_ENUMERATION_TYPE => DataType::Named(self.tag(offset, entry)),
+            _ => DataType::Void,
+        }
+    }
+
+    /// Definition of the type entry at `offset`, if it defines a named type.
+    fn definition(&mut self, offset: usize) -> Option<(String, Definition)> {
+        let entries = self.entries;
+        let entry = entries.get(&offset)?;
+        let size = entry.get(DW_AT_BYTE_SIZE).and_then(|v| v.unsigned()).unwrap_or(0);
+
+        if entry.get(DW_AT_DECLARATION).is_some() {
+            return None;
+        }
+
+        match entry.tag {
+            DW_TAG_TYPEDEF => Some((entry.name()?.to_string(), Definition::Typedef(self.type_of(entry, 0)))),
+            DW_TAG_STRUCTURE_TYPE | DW_TAG_CLASS_TYPE | DW_TAG_UNION_TYPE => {
+                let mut members = vec![];
+
+                for m in entry.children.iter().filter_map(|c| entries.get(c)).filter(|c| c.tag == DW_TAG_MEMBER) {
+                    let offset = match m.get(DW_AT_DATA_MEMBER_LOCATION) {
+                        Some(&Value::Block(ref b)) if b.first() == Some(&DW_OP_PLUS_UCONST) => {
+                            Reader { bytes: b, position: 1, endianess: Endianess::Little }.uleb128().unwrap_or(0)
+                        }
+                        Some(v) => v.unsigned().unwrap_or(0),
+                        None => 0,
+                    };
+
+                    members.push(Member { name: m.name().unwrap_or("").to_string(), offset: offset, ty: self.type_of(m, 0) });
+                }
+
+                let name = self.tag(offset, entry);
+
+                if entry.tag == DW_TAG_UNION_TYPE {
+                    Some((name, Definition::Union { members: members, size: size }))
+                } else {
+                    Some((name, Definition::Struct { members: members, size: size }))
+                }
+            }
+            DW_TAG_ENUMERATION_TYPE => {
+                let values = entry.children
+                    .iter()
+                    .filter_map(|c| entries.get(c))
+                    .filter(|c| c.tag == DW_TAG_ENUMERATOR)
+                    .map(
+                        |e| {
+                            let v = match e.get(DW_AT_CONST_VALUE) {
+                                Some(&Value::Signed(v)) => v,
+                                Some(&Value::Unsigned(v)) => v as i64,
+                                _ => 0,
+                            };
+                            (e.name().unwrap_or("").to_string(), v)
+                        }
+                    )
+                    .collect();
+
+                Some((self.tag(offset, entry), Definition::Enum { size: size, values: values }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Adds the types and global variables in the DWARF sections to `library`.
+pub fn import(library: &mut TypeLibrary, info: &[u8], abbrev: &[u8], strings: &[u8], endianess: Endianess, base: u64) -> Result<usize> {
+    let entries = entries(info, abbrev, strings, endianess)?;
+    let mut conv = Converter { entries: &entries, names: HashMap::new() };
+    let mut offsets = entries.keys().cloned().collect::<Vec<_>>();
+    let mut added = 0;
+
+    offsets.sort();
+    for &offset in offsets.iter() {
+        if let Some((name, def)) = conv.definition(offset) {
+            library.define(&name, def);
+            added += 1;
+        }
+
+        let entry = &entries[&offset];
+
+        if entry.tag == DW_TAG_VARIABLE && entry.global {
+            if let (Some(name), Some(&Value::Block(ref loc))) = (entry.name(), entry.get(DW_AT_LOCATION)) {
+                if loc.len() == 1 + entry.address_size && loc[0] == DW_OP_ADDR {
+                    let address = Reader { bytes: loc, position: 1, endianess: endianess }.fixed(entry.address_size).unwrap_or(0);
+                    let decl = Declaration { name: name.to_string(), ty: conv.type_of(entry, 0) };
+
+                    library.globals.insert(address.wrapping_add(base), decl);
+                }
+            }
+        }
+
+        if entry.tag == DW_TAG_SUBPROGRAM && entry.get(DW_AT_DECLARATION).is_none() {
+            if let Some(&Value::Unsigned(low_pc)) = entry.get(DW_AT_LOW_PC) {
+                let children = entry.children.iter().filter_map(|c| entries.get(c)).collect::<Vec<_>>();
+                let params = children
+                    .iter()
+                    .filter(|c| c.tag == DW_TAG_FORMAL_PARAMETER)
+                    .enumerate()
+                    .map(|(i, c)| (c.name().map(|n| n.to_string()).unwrap_or(format!("arg{}", i)), conv.type_of(c, 0)))
+                    .collect();
+                let variadic = children.iter().any(|c| c.tag == DW_TAG_UNSPECIFIED_PARAMETERS);
+
+                library.prototypes.insert(low_pc.wrapping_add(base), Signature::new(conv.type_of(entry, 0), params, variadic, None));
+                if let Some(name) = entry.name() {
+                    library.function_names.insert(low_pc.wrapping_add(base), name.to_string());
+                }
+            }
+        }
+    }
+
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn le32(value: usize) -> Vec<u8> {
+        vec![value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]
+    }
+
+    /*
+     * struct node { int value; struct node *next; int data[2]; };
+     * typedef struct node node_t;
+     * struct node *head; // at 0x1000
+     */
+    #[test]
+    fn debug_info() {
+        let abbrev = vec![
+            1, 0x11, 1, 0x03, 0x08, 0, 0,
+            2, 0x24, 0, 0x03, 0x08, 0x0b, 0x0b, 0x3e, 0x0b, 0, 0,
+            3, 0x13, 1, 0x03, 0x0e, 0x0b, 0x0b, 0, 0,
+            4, 0x0d, 0, 0x03, 0x08, 0x49, 0x13, 0x38, 0x0b, 0, 0,
+            5, 0x0f, 0, 0x0b, 0x0b, 0x49, 0x13, 0, 0,
+            6, 0x16, 0, 0x03, 0x08, 0x49, 0x13, 0, 0,
+            7, 0x34, 0, 0x03, 0x08, 0x49, 0x13, 0x02, 0x18, 0, 0,
+            8, 0x01, 1, 0x49, 0x13, 0, 0,
+            9, 0x21, 0, 0x2f, 0x0b, 0, 0,
+            0,
+        ];
+        let strings = b"\0node\0";
+        let mut info = vec![0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 8];
+
+        info.extend_from_slice(b"\x01t.c\0");
+
+        let int = info.len();
+        info.extend_from_slice(b"\x02int\0\x04\x05");
+
+        let array = info.len();
+        info.push(8);
+        info.extend(le32(int));
+        info.extend_from_slice(&[9, 1, 0]);
+
+        let ptr = info.len();
+        let node = ptr + 6;
+        info.extend_from_slice(&[5, 8]);
+        info.extend(le32(node));
+
+        info.push(3);
+        info.extend(le32(1));
+        info.push(0x18);
+        info.extend_from_slice(b"\x04value\0");
+        info.extend(le32(int));
+        info.extend_from_slice(b"\x00\x04next\0");
+        info.extend(le32(ptr));
+        info.extend_from_slice(b"\x08\x04data\0");
+        info.extend(le32(array));
+        info.extend_from_slice(&[0x10, 0]);
+
+        info.extend_from_slice(b"\x06node_t\0");
+        info.extend(le32(node));
+        info.extend_from_slice(b"\x07head\0");
+        info.extend(le32(ptr));
+        info.extend_from_slice(&[9, DW_OP_ADDR, 0, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+
+        let len = info.len() - 4;
+        info[0..4].copy_from_slice(&le32(len));
+
+        let mut lib = TypeLibrary::new(8);
+        let node_ty = DataType::Named("struct node".to_string());
+
+        assert_eq!(lib.import_dwarf(&info, &abbrev, strings, Endianess::Little, 0x400000).ok(), Some(2));
+        assert_eq!(
+            lib.definitions["struct node"],
+            Definition::Struct {
+                members: vec![
+                    Member { name: "value".to_string(), offset: 0, ty: DataType::Integer { size: 4, signed: true } },
+                    Member { name: "next".to_string(), offset: 8, ty: node_ty.clone().pointer_to() },
+                    Member { name: "data".to_string(), offset: 0x10, ty: DataType::Integer { size: 4, signed: true }.array_of(2) },
+                ],
+                size: 0x18,
+            }
+        );
+        assert_eq!(lib.definitions["node_t"], Definition::Typedef(node_ty.clone()));
+        assert_eq!(lib.global(0x401000), Some(("head".to_string(), node_ty.pointer_to())));
+
+        info[4] = 6;
+        assert!(lib.import_dwarf(&info, &abbrev, strings, Endianess::Little, 0).is_err());
+    }
+
+    /*
+     * int add(int a, ...) at 0x1000
+     */
+    #[test]
+    fn prototype() {
+        let abbrev = vec![
+            1, 0x11, 1, 0x03, 0x08, 0, 0,
+            2, 0x24, 0, 0x03, 0x08, 0x0b, 0x0b, 0x3e, 0x0b, 0, 0,
+            3, 0x2e, 1, 0x03, 0x08, 0x11, 0x01, 0x49, 0x13, 0, 0,
+            4, 0x05, 0, 0x03, 0x08, 0x49, 0x13, 0, 0,
+            5, 0x18, 0, 0, 0,
+            0,
+        ];
+        let mut info = vec![0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 8];
+
+        info.extend_from_slice(b"\x01t.c\0");
+
+        let int = info.len();
+        info.extend_from_slice(b"\x02int\0\x04\x05");
+        info.extend_from_slice(b"\x03add\0");
+        info.extend_from_slice(&[0, 0x10, 0, 0, 0, 0, 0, 0]);
+        info.extend(le32(int));
+        info.extend_from_slice(b"\x04a\0");
+        info.extend(le32(int));
+        info.extend_from_slice(&[5, 0, 0]);
+
+        let len = info.len() - 4;
+        info[0..4].copy_from_slice(&le32(len));
+
+        let mut lib = TypeLibrary::new(8);
+        let int = DataType::Integer { size: 4, signed: true };
+
+        assert_eq!(lib.import_dwarf(&info, &abbrev, b"\0", Endianess::Little, 0x400000).ok(), Some(0));
+        assert_eq!(lib.prototypes.get(&0x401000), Some(&Signature::new(int.clone(), vec![("a".to_string(), int)], true, None)));
+        assert_eq!(lib.prototypes[&0x401000].to_string(), "int32_t(int32_t a, ...)");
+        assert_eq!(lib.function_names.get(&0x401000).map(String::as_str), Some("add"));
+    }
+}
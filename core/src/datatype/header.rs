@@ -0,0 +1,819 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! C declarations.
+//!
+//! Understands struct, union and enum definitions and typedefs, including function pointers,
+//! arrays and anonymous members, the builtin types, the integer types of `<stdint.h>` and the
+//! common Windows ones like `DWORD`. `long` is as wide as a pointer. Preprocessor directives,
+//! function prototypes and bodies and variable declarations are skipped. Bit fields occupy their
+//! whole declared type and packing attributes are ignored.
+
+use super::{DataType, Definition, TypeLibrary};
+use Result;
+use std::collections::HashMap;
+
+#[derive(Clone,Debug,PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Str,
+    Punct(char),
+    ShiftLeft,
+    ShiftRight,
+    Ellipsis,
+}
+
+/// Words that can't start a declarator.
+const KEYWORDS: &'static [&'static str] = &[
+    "signed",
+    "unsigned",
+    "short",
+    "long",
+    "int",
+    "char",
+    "void",
+    "_Bool",
+    "bool",
+    "float",
+    "double",
+    "struct",
+    "union",
+    "enum",
+    "typedef",
+];
+
+/// Ignored qualifiers, storage classes and calling conventions.
+const QUALIFIERS: &'static [&'static str] = &[
+    "const",
+    "volatile",
+    "static",
+    "extern",
+    "inline",
+    "register",
+    "restrict",
+    "__restrict",
+    "__restrict__",
+    "__inline",
+    "__inline__",
+    "__extension__",
+    "__const",
+    "__volatile__",
+    "_Noreturn",
+    "__cdecl",
+    "__stdcall",
+    "__fastcall",
+    "__thiscall",
+    "__ptr32",
+    "__ptr64",
+    "WINAPI",
+    "CALLBACK",
+];
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut ret = vec![];
+    let mut i = 0;
+    let mut line_start = true;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\n' {
+            line_start = true;
+            i += 1;
+            continue;
+        } else if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c == '#' && line_start {
+            // directives end at the first newline not escaped by a backslash
+            while i < chars.len() && !(chars[i] == '\n' && chars[i - 1] != '\\') {
+                i += 1;
+            }
+            continue;
+        }
+
+        line_start = false;
+
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            ret.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c.is_digit(10) {
+            let start = i;
+
+            while i < chars.len() && chars[i].is_alphanumeric() {
+                i += 1;
+            }
+
+            let text = chars[start..i].iter().collect::<String>().to_lowercase();
+            let text = text.trim_matches(|c| c == 'u' || c == 'l');
+            let value = if text.starts_with("0x") {
+                u64::from_str_radix(&text[2..], 16)
+            } else if text.len() > 1 && text.starts_with('0') {
+                u64::from_str_radix(&text[1..], 8)
+            } else {
+                text.parse::<u64>()
+            };
+
+            match value {
+                Ok(v) => ret.push(Token::Number(v)),
+                Err(_) => return Err(format!("invalid number {}", text).into()),
+            }
+        } else if c == '"' || c == '\'' {
+            let start = i;
+
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+
+            if c == '"' {
+                ret.push(Token::Str);
+            } else {
+                let lit = chars.get(start + 1..i - 1).unwrap_or(&[]);
+                let value = match (lit.get(0), lit.get(1), lit.len()) {
+                    (Some(&ch), _, 1) => ch as u64,
+                    (Some(&'\\'), Some(&'n'), 2) => 10,
+                    (Some(&'\\'), Some(&'t'), 2) => 9,
+                    (Some(&'\\'), Some(&'r'), 2) => 13,
+                    (Some(&'\\'), Some(&'0'), 2) => 0,
+                    (Some(&'\\'), Some(&ch), 2) => ch as u64,
+                    _ => return Err("invalid character literal".into()),
+                };
+
+                ret.push(Token::Number(value));
+            }
+        } else if c == '<' && chars.get(i + 1) == Some(&'<') {
+            ret.push(Token::ShiftLeft);
+            i += 2;
+        } else if c == '>' && chars.get(i + 1) == Some(&'>') {
+            ret.push(Token::ShiftRight);
+            i += 2;
+        } else if c == '.' && chars.get(i + 1) == Some(&'.') && chars.get(i + 2) == Some(&'.') {
+            ret.push(Token::Ellipsis);
+            i += 3;
+        } else {
+            ret.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Types of `<stdint.h>` and `<windows.h>`.
+fn builtin(name: &str, pointer_size: u64) -> Option<DataType> {
+    let int = |size: u64, signed: bool| Some(DataType::Integer { size: size, signed: signed });
+
+    match name {
+        "int8_t" | "INT8" => int(1, true),
+        "int16_t" | "INT16" | "SHORT" => int(2, true),
+        "int32_t" | "INT32" | "INT" | "LONG" | "BOOL" => int(4, true),
+        "int64_t" | "INT64" | "LONGLONG" => int(8, true),
+        "uint8_t" | "UINT8" | "BYTE" | "UCHAR" => int(1, false),
+        "uint16_t" | "UINT16" | "WORD" | "USHORT" => int(2, false),
+        "uint32_t" | "UINT32" | "DWORD" | "UINT" | "ULONG" => int(4, false),
+        "uint64_t" | "UINT64" | "QWORD" | "DWORD64" | "ULONGLONG" => int(8, false),
+        "size_t" | "uintptr_t" | "ULONG_PTR" | "SIZE_T" => int(pointer_size, false),
+        "ssize_t" | "intptr_t" | "ptrdiff_t" | "LONG_PTR" => int(pointer_size, true),
+        "wchar_t" => int(4, true),
+        "WCHAR" => int(2, false),
+        "CHAR" => Some(DataType::Char),
+        "HANDLE" | "PVOID" | "LPVOID" => Some(DataType::Void.pointer_to()),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    library: &'a mut TypeLibrary,
+    constants: HashMap<String, i64>,
+    anonymous: usize,
+    added: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_ident(&self) -> Option<&str> {
+        match self.peek() {
+            Some(&Token::Ident(ref s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn is(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let ret = self.tokens.get(self.position).cloned();
+
+        self.position += 1;
+        ret
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.next() {
+            Some(Token::Punct(p)) if p == c => Ok(()),
+            Some(t) => Err(format!("expected '{}', found {:?}", c, t).into()),
+            None => Err(format!("expected '{}', found the end of the file", c).into()),
+        }
+    }
+
+    /// Skips a parenthesized token sequence, the current token must be `(`.
+    fn skip_parens(&mut self) {
+        let mut depth = 0;
+
+        while let Some(t) = self.next() {
+            match t {
+                Token::Punct('(') => depth += 1,
+                Token::Punct(')') if depth <= 1 => return,
+                Token::Punct(')') => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Skips qualifiers and attributes.
+    fn qualifiers(&mut self) {
+        loop {
+            match self.peek_ident() {
+                Some(q) if QUALIFIERS.contains(&q) => {}
+                Some("__attribute__") | Some("__declspec") | Some("__asm__") | Some("__asm") | Some("alignas") | Some("_Alignas") => {
+                    self.next();
+                    if self.is('(') {
+                        self.skip_parens();
+                    }
+                    continue;
+                }
+                _ => return,
+            }
+            self.next();
+        }
+    }
+
+    fn import(&mut self) -> Result<()> {
+        while let Some(tok) = self.peek().cloned() {
+            match tok {
+                Token::Punct(';') | Token::Punct('}') => {
+                    self.next();
+                }
+                Token::Ident(ref s) if s == "extern" && self.tokens.get(self.position + 1) == Some(&Token::Str) => {
+                    self.position += 2;
+                    if self.is('{') {
+                        self.next();
+                    }
+                }
+                Token::Ident(ref s) if s == "typedef" => {
+                    self.next();
+
+                    let base = match self.specifier()? {
+                        Some(ty) => ty,
+                        None => return Err("expected a type after typedef".into()),
+                    };
+
+                    loop {
+                        match self.declarator(base.clone())? {
+                            (Some(name), ty) => {
+                                // typedef struct x x;
+                                if ty != DataType::Named(name.clone()) {
+                                    self.library.define(&name, Definition::Typedef(ty));
+                                    self.added += 1;
+                                }
+                            }
+                            (None, _) => return Err("typedef without a name".into()),
+                        }
+                        self.qualifiers();
+                        if self.is(',') {
+                            self.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    self.expect(';')?;
+                }
+                _ => {
+                    self.specifier()?;
+                    self.skip_declaration();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Skips the rest of a variable or function declaration, including the function body.
+    fn skip_declaration(&mut self) {
+        let mut depth = 0;
+
+        while let Some(t) = self.next() {
+            match t {
+                Token::Punct(';') if depth == 0 => return,
+                Token::Punct('(') | Token::Punct('[') | Token::Punct('{') => depth += 1,
+                Token::Punct('}') if depth == 1 => {
+                    if self.is(';') {
+                        self.next();
+                    }
+                    return;
+                }
+                Token::Punct(')') | Token::Punct(']') | Token::Punct('}') => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Parses a type specifier. Returns None if the next token doesn't start one.
+    fn specifier(&mut self) -> Result<Option<DataType>> {
+        let mut signed = None;
+        let mut short = false;
+        let mut long = 0;
+        let mut base: Option<&'static str> = None;
+        let mut named = None;
+
+        loop {
+            self.qualifiers();
+
+            let word = match self.peek_ident() {
+                Some(w) => w.to_string(),
+                None => break,
+            };
+            let seen = signed.is_some() || short || long > 0 || base.is_some() || named.is_some();
+
+            match &*word {
+                "signed" | "__signed__" => signed = Some(true),
+                "unsigned" => signed = Some(false),
+                "short" => short = true,
+                "long" => long += 1,
+                "int" => base = Some("int"),
+                "char" => base = Some("char"),
+                "void" => base = Some("void"),
+                "_Bool" | "bool" => base = Some("bool"),
+                "float" => base = Some("float"),
+                "double" => base = Some("double"),
+                "struct" | "union" | "enum" if !seen => {
+                    self.next();
+                    named = Some(self.tagged(&word)?);
+                    continue;
+                }
+                _ if !seen => {
+                    named = if self.library.definitions.contains_key(&word) {
+                        Some(DataType::Named(word.clone()))
+                    } else {
+                        builtin(&word, self.library.pointer_size).or(Some(DataType::Named(word.clone())))
+                    };
+                }
+                _ => break,
+            }
+            self.next();
+        }
+
+        let ptr = self.library.pointer_size;
+        let int = |size: u64| DataType::Integer { size: size, signed: signed.unwrap_or(true) };
+        let ty = match (named, base) {
+            (Some(ty), _) => ty,
+            (None, Some("void")) => DataType::Void,
+            (None, Some("bool")) => DataType::Bool,
+            (None, Some("float")) => DataType::Float { size: 4 },
+            (None, Some("double")) if long > 0 => DataType::Float { size: if ptr == 8 { 16 } else { 12 } },
+            (None, Some("double")) => DataType::Float { size: 8 },
+            (None, Some("char")) if signed.is_none() => DataType::Char,
+            (None, Some("char")) => int(1),
+            (None, _) if short => int(2),
+            (None, _) if long == 1 => int(if ptr == 8 { 8 } else { 4 }),
+            (None, _) if long > 1 => int(8),
+            (None, Some(_)) => int(4),
+            (None, None) if signed.is_some() => int(4),
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some(ty))
+    }
+
+    /// Parses the rest of a struct, union or enum specifier.
+    fn tagged(&mut self, keyword: &str) -> Result<DataType> {
+        self.qualifiers();
+
+        let tag = match self.peek_ident().map(|s| s.to_string()) {
+            Some(name) => {
+                self.next();
+                format!("{} {}", keyword, name)
+            }
+            None => {
+                loop {
+                    self.anonymous += 1;
+
+                    let tag = format!("{} __anonymous{}", keyword, self.anonymous);
+
+                    if !self.library.definitions.contains_key(&tag) {
+                        break tag;
+                    }
+                }
+            }
+        };
+
+        if keyword == "enum" && self.is(':') {
+            self.next();
+            self.specifier()?;
+        }
+
+        if !self.is('{') {
+            return Ok(DataType::Named(tag));
+        }
+        self.next();
+
+        if keyword == "enum" {
+            let mut values = vec![];
+            let mut next = 0i64;
+
+            while !self.is('}') {
+                let name = match self.next() {
+                    Some(Token::Ident(name)) => name,
+                    t => return Err(format!("expected an enumerator in {}, found {:?}", tag, t).into()),
+                };
+
+                self.qualifiers();
+                if self.is('=') {
+                    self.next();
+                    next = self.expression()?;
+                }
+                self.constants.insert(name.clone(), next);
+                values.push((name, next));
+                next = next.wrapping_add(1);
+
+                if self.is(',') {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+            self.expect('}')?;
+            self.library.define(&tag, Definition::Enum { size: 4, values: values });
+        } else {
+            let mut fields = vec![];
+
+            while !self.is('}') {
+                if self.is(';') {
+                    self.next();
+                    continue;
+                }
+
+                let base = match self.specifier()? {
+                    Some(ty) => ty,
+                    None => return Err(format!("expected a field type in {}, found {:?}", tag, self.peek()).into()),
+                };
+
+                loop {
+                    let (name, ty) = if self.is(':') { (None, base.clone()) } else { self.declarator(base.clone())? };
+
+                    if self.is(':') {
+                        self.next();
+                        self.expression()?;
+                    }
+                    self.qualifiers();
+                    fields.push((name.unwrap_or_default(), ty));
+
+                    if self.is(',') {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(';')?;
+            }
+            self.expect('}')?;
+
+            let def = match self.library.layout(fields, keyword == "union") {
+                Ok(def) => def,
+                Err(e) => return Err(format!("{}: {}", tag, e).into()),
+            };
+
+            self.library.define(&tag, def);
+        }
+
+        self.added += 1;
+        self.qualifiers();
+        Ok(DataType::Named(tag))
+    }
+
+    /// Parses a declarator applied to `base`. Returns the declared name, if any, and its type.
+    fn declarator(&mut self, base: DataType) -> Result<(Option<String>, DataType)> {
+        let mut ty = base;
+
+        self.qualifiers();
+        while self.is('*') {
+            self.next();
+            self.qualifiers();
+            ty = ty.pointer_to();
+        }
+
+        if self.is('(') && self.tokens.get(self.position + 1) == Some(&Token::Punct('*')) {
+            // int (*name)(int): the suffixes after the parentheses apply first
+            let inner = self.position + 1;
+
+            self.skip_parens();
+            ty = self.suffixes(ty)?;
+
+            let end = self.position;
+
+            self.position = inner;
+
+            let ret = self.declarator(ty)?;
+
+            self.expect(')')?;
+            self.position = end;
+            return Ok(ret);
+        }
+
+        let name = match self.peek_ident() {
+            Some(name) if !KEYWORDS.contains(&name) => Some(name.to_string()),
+            _ => None,
+        };
+
+        if name.is_some() {
+            self.next();
+        }
+
+        Ok((name, self.suffixes(ty)?))
+    }
+
+    /// Parses array dimensions and parameter lists following a declarator.
+    fn suffixes(&mut self, base: DataType) -> Result<DataType> {
+        let mut ty = base;
+        let mut dims = vec![];
+
+        loop {
+            if self.is('[') {
+                self.next();
+                if self.is(']') {
+                    dims.push(0);
+                } else {
+                    let count = self.expression()?;
+
+                    if count < 0 {
+                        return Err(format!("negative array size {}", count).into());
+                    }
+                    dims.push(count as u64);
+                }
+                self.expect(']')?;
+            } else if self.is('(') {
+                self.next();
+
+                let mut args = vec![];
+
+                while !self.is(')') {
+                    if self.peek() == Some(&Token::Ellipsis) {
+                        self.next();
+                    } else {
+                        let base = match self.specifier()? {
+                            Some(ty) => ty,
+                            None => return Err(format!("expected a parameter type, found {:?}", self.peek()).into()),
+                        };
+                        let (name, arg) = self.declarator(base)?;
+
+                        if name.is_some() || arg != DataType::Void {
+                            args.push(arg);
+                        }
+                    }
+
+                    if self.is(',') {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(')')?;
+                ty = DataType::Function { ret: Box::new(ty), args: args };
+            } else {
+                break;
+            }
+        }
+
+        for &d in dims.iter().rev() {
+            ty = ty.array_of(d);
+        }
+
+        Ok(ty)
+    }
+
+    /// Evaluates a constant integer expression.
+    fn expression(&mut self) -> Result<i64> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, min: usize) -> Result<i64> {
+        let mut lhs = self.unary()?;
+
+        loop {
+            let prec = match self.peek() {
+                Some(&Token::Punct('|')) => 1,
+                Some(&Token::Punct('^')) => 2,
+                Some(&Token::Punct('&')) => 3,
+                Some(&Token::ShiftLeft) | Some(&Token::ShiftRight) => 4,
+                Some(&Token::Punct('+')) | Some(&Token::Punct('-')) => 5,
+                Some(&Token::Punct('*')) | Some(&Token::Punct('/')) | Some(&Token::Punct('%')) => 6,
+                _ => return Ok(lhs),
+            };
+
+            if prec < min {
+                return Ok(lhs);
+            }
+
+            let op = self.next();
+            let rhs = self.binary(prec + 1)?;
+
+            lhs = match op {
+                Some(Token::Punct('|')) => lhs | rhs,
+                Some(Token::Punct('^')) => lhs ^ rhs,
+                Some(Token::Punct('&')) => lhs & rhs,
+                Some(Token::ShiftLeft) => lhs.wrapping_shl(rhs as u32),
+                Some(Token::ShiftRight) => lhs.wrapping_shr(rhs as u32),
+                Some(Token::Punct('+')) => lhs.wrapping_add(rhs),
+                Some(Token::Punct('-')) => lhs.wrapping_sub(rhs),
+                Some(Token::Punct('*')) => lhs.wrapping_mul(rhs),
+                Some(Token::Punct('/')) if rhs != 0 => lhs.wrapping_div(rhs),
+                Some(Token::Punct('%')) if rhs != 0 => lhs.wrapping_rem(rhs),
+                _ => return Err("division by zero".into()),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n as i64),
+            Some(Token::Punct('-')) => self.unary().map(|v| v.wrapping_neg()),
+            Some(Token::Punct('+')) => self.unary(),
+            Some(Token::Punct('~')) => self.unary().map(|v| !v),
+            Some(Token::Punct('(')) => {
+                let start = self.position;
+
+                // casts like (uint32_t)1
+                if let Some(Token::Ident(_)) = self.peek().cloned() {
+                    if self.tokens.get(self.position + 1) == Some(&Token::Punct(')')) && !self.constants.contains_key(self.peek_ident().unwrap_or("")) {
+                        self.position += 2;
+                        return self.unary();
+                    }
+                }
+
+                self.position = start;
+
+                let ret = self.expression()?;
+
+                self.expect(')')?;
+                Ok(ret)
+            }
+            Some(Token::Ident(ref name)) if name == "sizeof" => {
+                self.expect('(')?;
+
+                let ty = match self.specifier()? {
+                    Some(ty) => self.declarator(ty)?.1,
+                    None => return Err("expected a type in sizeof".into()),
+                };
+
+                self.expect(')')?;
+                match self.library.size_of(&ty) {
+                    Some(sz) => Ok(sz as i64),
+                    None => Err(format!("size of {} is unknown", ty).into()),
+                }
+            }
+            Some(Token::Ident(name)) => {
+                match self.constants.get(&name) {
+                    Some(&v) => Ok(v),
+                    None => Err(format!("unknown constant {}", name).into()),
+                }
+            }
+            t => Err(format!("expected a constant, found {:?}", t).into()),
+        }
+    }
+}
+
+/// Adds the definitions in `src` to `library`, see the module documentation.
+pub fn import(library: &mut TypeLibrary, src: &str) -> Result<usize> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        position: 0,
+        library: library,
+        constants: HashMap::new(),
+        anonymous: 0,
+        added: 0,
+    };
+
+    parser.import()?;
+    Ok(parser.added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declarations() {
+        let src = r#"
+            #include <stdint.h>
+            #define MAX_ITEMS \
+                16
+            /* game state */
+            enum state { IDLE, RUNNING = 1 << 2, DEAD };
+            typedef struct vec { float x, y; } vec_t;
+            struct player {
+                char name[MAX_NAME];
+            };
+            extern "C" {
+            typedef struct player {
+                uint8_t alive : 1;
+                vec_t pos;
+                const char *name;
+                union { int32_t hp; float shield; };
+                int (*on_hit)(struct player *, int);
+                enum state state;
+                unsigned long flags;
+                struct player *next;
+                short items[DEAD][2];
+            } player_t;
+            }
+            int update(player_t *p) { if (p) { return 1; } return 0; }
+            static const int table[] = { 1, 2, 3 };
+        "#;
+        let mut lib = TypeLibrary::new(8);
+
+        // MAX_NAME is undefined
+        assert!(lib.import_header(src).is_err());
+
+        let src = src.replace("MAX_NAME", "(uint32_t)8");
+        let mut lib = TypeLibrary::new(8);
+
+        assert_eq!(lib.import_header(&src).ok(), Some(7));
+        assert_eq!(lib.definitions["enum state"], Definition::Enum { size: 4, values: vec![("IDLE".to_string(), 0), ("RUNNING".to_string(), 4), ("DEAD".to_string(), 5)] });
+        assert_eq!(lib.definitions["vec_t"], Definition::Typedef(DataType::Named("struct vec".to_string())));
+        assert_eq!(lib.size_of(&DataType::Named("vec_t".to_string())), Some(8));
+
+        let player = DataType::Named("player_t".to_string());
+        let members = match lib.definitions["struct player"] {
+            Definition::Struct { ref members, size } => {
+                assert_eq!(size, 0x58);
+                members.iter().map(|m| (m.name.clone(), m.offset, m.ty.to_string())).collect::<Vec<_>>()
+            }
+            ref d => panic!("{:?}", d),
+        };
+
+        assert_eq!(
+            members,
+            vec![
+                ("alive".to_string(), 0, "uint8_t".to_string()),
+                ("pos".to_string(), 4, "vec_t".to_string()),
+                ("name".to_string(), 0x10, "char *".to_string()),
+                ("".to_string(), 0x18, "union __anonymous1".to_string()),
+                ("on_hit".to_string(), 0x20, "int32_t(struct player *, int32_t) *".to_string()),
+                ("state".to_string(), 0x28, "enum state".to_string()),
+                ("flags".to_string(), 0x30, "uint64_t".to_string()),
+                ("next".to_string(), 0x38, "struct player *".to_string()),
+                ("items".to_string(), 0x40, "int16_t[5][2]".to_string()),
+            ]
+        );
+        assert_eq!(lib.member(&player, 0x8).map(|p| p.0), Some(".pos.y".to_string()));
+        assert_eq!(lib.member(&player, 0x18).map(|p| p.0), Some(".hp".to_string()));
+        assert_eq!(lib.member(&player, 0x4e).map(|p| p.0), Some(".items[3][1]".to_string()));
+        assert_eq!(lib.member(&player, 0x1), None);
+
+        // 32 bit targets
+        let mut lib = TypeLibrary::new(4);
+
+        assert_eq!(lib.import_header("struct s { char c; long l; void *p; double d; };").ok(), Some(1));
+        assert_eq!(lib.size_of(&DataType::Named("struct s".to_string())), Some(24));
+        assert!(lib.import_header("struct t { struct u value; };").is_err());
+    }
+}
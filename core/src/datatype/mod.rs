@@ -0,0 +1,688 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! User defined data types.
+//!
+//! A [`TypeLibrary`] holds C-like struct, union, enum and typedef definitions, built with
+//! [`TypeLibrary::define`] or imported from C headers ([`TypeLibrary::import_header`]) and DWARF
+//! debug information ([`TypeLibrary::import_dwarf`]). The ELF loader imports the
+//! `.debug_info` section of unstripped files.
+//!
+//! Types are applied to global variables by address, to stack slots by function, frame register
+//! and offset and to registers holding a pointer at the start of a function, e.g. arguments.
+//! [`typed_operands`] uses them to name memory operands: `[rbp-0x18]` becomes `local.count`,
+//! and after `mov rax, [rbp-0x8]` the operand `[rax+0x1c]` becomes `player->health` if the slot
+//! at `rbp-0x8` is a `struct player *`. Values are followed through moves, additions and loads
+//! within a basic block. `listing::function` shows these names instead of the operands.
+//!
+//! Struct tags, union tags and enum tags are named `struct x`, `union x` and `enum x`, typedefs
+//! by their plain name.
+//!
+//! [`TypeLibrary`]: struct.TypeLibrary.html
+//! [`TypeLibrary::define`]: struct.TypeLibrary.html#method.define
+//! [`TypeLibrary::import_header`]: struct.TypeLibrary.html#method.import_header
+//! [`TypeLibrary::import_dwarf`]: struct.TypeLibrary.html#method.import_dwarf
+//! [`typed_operands`]: fn.typed_operands.html
+
+use {Endianess, Function, Lvalue, Operation, Project, Result, Rvalue, Signature};
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+
+mod dwarf;
+mod header;
+
+/// Maximal number of typedefs followed before giving up.
+const MAX_DEPTH: usize = 32;
+
+/// A C type.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum DataType {
+    /// `void`
+    Void,
+    /// `_Bool`
+    Bool,
+    /// `char`
+    Char,
+    /// Integer of `size` bytes
+    Integer {
+        /// Size in bytes
+        size: u64,
+        /// Two's complement
+        signed: bool,
+    },
+    /// IEEE 754 floating point number of `size` bytes
+    Float {
+        /// Size in bytes
+        size: u64,
+    },
+    /// Pointer to a value of the type
+    Pointer(Box<DataType>),
+    /// Fixed size array. Zero `count` for flexible array members.
+    Array {
+        /// Element type
+        element: Box<DataType>,
+        /// Number of elements
+        count: u64,
+    },
+    /// Function type
+    Function {
+        /// Return type
+        ret: Box<DataType>,
+        /// Parameter types
+        args: Vec<DataType>,
+    },
+    /// Reference to a `Definition` of the library
+    Named(String),
+}
+
+impl DataType {
+    /// Returns a pointer to `self`.
+    pub fn pointer_to(self) -> DataType {
+        DataType::Pointer(Box::new(self))
+    }
+
+    /// Returns an array of `count` elements of `self`.
+    pub fn array_of(self, count: u64) -> DataType {
+        DataType::Array { element: Box::new(self), count: count }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &DataType::Void => f.write_str("void"),
+            &DataType::Bool => f.write_str("_Bool"),
+            &DataType::Char => f.write_str("char"),
+            &DataType::Integer { size, signed: true } => write!(f, "int{}_t", size * 8),
+            &DataType::Integer { size, signed: false } => write!(f, "uint{}_t", size * 8),
+            &DataType::Float { size: 4 } => f.write_str("float"),
+            &DataType::Float { size: 8 } => f.write_str("double"),
+            &DataType::Float { size } => write!(f, "float{}", size * 8),
+            &DataType::Pointer(ref ty) => write!(f, "{} *", ty),
+            &DataType::Array { .. } => {
+                let mut ty = self;
+                let mut dims = String::new();
+
+                while let &DataType::Array { ref element, count } = ty {
+                    dims.push_str(&format!("[{}]", count));
+                    ty = element;
+                }
+                write!(f, "{}{}", ty, dims)
+            }
+            &DataType::Function { ref ret, ref args } => {
+                write!(f, "{}({})", ret, args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "))
+            }
+            &DataType::Named(ref name) => f.write_str(name),
+        }
+    }
+}
+
+/// Field of a struct or union.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Member {
+    /// Field name, empty for anonymous structs and unions
+    pub name: String,
+    /// Offset from the start of the struct in bytes
+    pub offset: u64,
+    /// Field type
+    pub ty: DataType,
+}
+
+/// Named type.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Definition {
+    /// Structure
+    Struct {
+        /// Fields ordered by offset
+        members: Vec<Member>,
+        /// Size in bytes, including padding
+        size: u64,
+    },
+    /// Union. All members start at offset 0.
+    Union {
+        /// Fields
+        members: Vec<Member>,
+        /// Size in bytes, including padding
+        size: u64,
+    },
+    /// Enumeration
+    Enum {
+        /// Size in bytes
+        size: u64,
+        /// Enumerators and their values
+        values: Vec<(String, i64)>,
+    },
+    /// Alias of another type
+    Typedef(DataType),
+}
+
+/// A variable with a type.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Declaration {
+    /// Variable name
+    pub name: String,
+    /// Variable type
+    pub ty: DataType,
+}
+
+/// Type definitions of a project and the places they are applied to.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct TypeLibrary {
+    /// Size of pointers in bytes
+    pub pointer_size: u64,
+    /// Byte order of values in memory
+    pub endianess: Endianess,
+    /// Definitions by name
+    pub definitions: BTreeMap<String, Definition>,
+    /// Global variables, by address
+    pub globals: BTreeMap<u64, Declaration>,
+    /// Stack slots by function UUID, frame register and offset from that register
+    pub stack: BTreeMap<(Uuid, String, i64), Declaration>,
+    /// Values of registers at the entry of a function, by function UUID and register name
+    pub registers: BTreeMap<(Uuid, String), Declaration>,
+    /// Prototypes of functions, by entry point. See `signature::apply`.
+    #[serde(default)]
+    pub prototypes: BTreeMap<u64, Signature>,
+    /// Names of functions from debug information, by entry point
+    #[serde(default)]
+    pub function_names: BTreeMap<u64, String>,
+}
+
+impl Default for TypeLibrary {
+    fn default() -> TypeLibrary {
+        TypeLibrary::new(8)
+    }
+}
+
+impl TypeLibrary {
+    /// Returns an empty library for a little endian target with `pointer_size` byte pointers.
+    pub fn new(pointer_size: u64) -> TypeLibrary {
+        TypeLibrary {
+            pointer_size: pointer_size,
+            endianess: Endianess::Little,
+            definitions: BTreeMap::new(),
+            globals: BTreeMap::new(),
+            stack: BTreeMap::new(),
+            registers: BTreeMap::new(),
+            prototypes: BTreeMap::new(),
+            function_names: BTreeMap::new(),
+        }
+    }
+
+    /// True if no type has been applied to anything.
+    pub fn is_empty(&self) -> bool {
+        self.globals.is_empty() && self.stack.is_empty() && self.registers.is_empty()
+    }
+
+    /// Adds `def` named `name`, replacing the previous definition. Returns the replaced one.
+    pub fn define(&mut self, name: &str, def: Definition) -> Option<Definition> {
+        self.definitions.insert(name.to_string(), def)
+    }
+
+    /// Defines `name` as a struct with `fields`, in this order and naturally aligned.
+    pub fn define_struct(&mut self, name: &str, fields: Vec<(String, DataType)>) -> Result<()> {
+        let def = self.layout(fields, false)?;
+
+        self.define(name, def);
+        Ok(())
+    }
+
+    /// Defines `name` as a union of `fields`.
+    pub fn define_union(&mut self, name: &str, fields: Vec<(String, DataType)>) -> Result<()> {
+        let def = self.layout(fields, true)?;
+
+        self.define(name, def);
+        Ok(())
+    }
+
+    /// Lays out `fields` like a C compiler without packing attributes would.
+    fn layout(&self, fields: Vec<(String, DataType)>, union: bool) -> Result<Definition> {
+        let mut members = Vec::with_capacity(fields.len());
+        let mut offset = 0;
+        let mut size = 0;
+        let mut align = 1;
+
+        for (name, ty) in fields {
+            let sz = match (self.size_of(&ty), &ty) {
+                (Some(sz), _) => sz,
+                (None, &DataType::Array { count: 0, .. }) => 0,
+                (None, _) => return Err(format!("size of {} is unknown", ty).into()),
+            };
+            let al = self.align_of(&ty).unwrap_or(1);
+
+            align = cmp::max(align, al);
+            if union {
+                size = cmp::max(size, sz);
+                members.push(Member { name: name, offset: 0, ty: ty });
+            } else {
+                offset = align_up(offset, al);
+                members.push(Member { name: name, offset: offset, ty: ty });
+                offset += sz;
+                size = offset;
+            }
+        }
+
+        let size = align_up(size, align);
+
+        if union {
+            Ok(Definition::Union { members: members, size: size })
+        } else {
+            Ok(Definition::Struct { members: members, size: size })
+        }
+    }
+
+    /// Follows typedefs until `ty` isn't a typedef anymore.
+    pub fn resolve<'a>(&'a self, ty: &'a DataType) -> &'a DataType {
+        let mut ty = ty;
+
+        for _ in 0..MAX_DEPTH {
+            match ty {
+                &DataType::Named(ref name) => {
+                    match self.definitions.get(name) {
+                        Some(&Definition::Typedef(ref t)) => ty = t,
+                        _ => return ty,
+                    }
+                }
+                _ => return ty,
+            }
+        }
+
+        ty
+    }
+
+    /// Size of a value of type `ty` in bytes. None for `void`, functions and undefined names.
+    pub fn size_of(&self, ty: &DataType) -> Option<u64> {
+        match self.resolve(ty) {
+            &DataType::Void | &DataType::Function { .. } => None,
+            &DataType::Bool | &DataType::Char => Some(1),
+            &DataType::Integer { size, .. } | &DataType::Float { size } => Some(size),
+            &DataType::Pointer(_) => Some(self.pointer_size),
+            &DataType::Array { ref element, count } => self.size_of(element).map(|s| s * count),
+            &DataType::Named(ref name) => {
+                match self.definitions.get(name) {
+                    Some(&Definition::Struct { size, .. }) |
+                    Some(&Definition::Union { size, .. }) |
+                    Some(&Definition::Enum { size, .. }) => Some(size),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Alignment of `ty` in bytes.
+    pub fn align_of(&self, ty: &DataType) -> Option<u64> {
+        self.align(ty, 0)
+    }
+
+    fn align(&self, ty: &DataType, depth: usize) -> Option<u64> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        match self.resolve(ty) {
+            &DataType::Array { ref element, .. } => self.align(element, depth + 1),
+            &DataType::Named(ref name) => {
+                match self.definitions.get(name) {
+                    Some(&Definition::Struct { ref members, .. }) |
+                    Some(&Definition::Union { ref members, .. }) => {
+                        Some(members.iter().filter_map(|m| self.align(&m.ty, depth + 1)).max().unwrap_or(1))
+                    }
+                    Some(&Definition::Enum { size, .. }) => Some(size),
+                    _ => None,
+                }
+            }
+            ty => self.size_of(ty),
+        }
+    }
+
+    /// Declares a global variable `name` of type `ty` at `address`.
+    pub fn apply(&mut self, address: u64, name: &str, ty: DataType) {
+        self.globals.insert(address, Declaration { name: name.to_string(), ty: ty });
+    }
+
+    /// Declares a variable `name` of type `ty` at `offset` from the `register` in the stack frame
+    /// of the function `function`. `register` is the IL name of the stack or frame pointer, e.g.
+    /// `RBP` or `RSP`, and `offset` the displacement used with it in memory operands.
+    pub fn apply_stack(&mut self, function: &Uuid, register: &str, offset: i64, name: &str, ty: DataType) {
+        self.stack.insert((*function, register.to_string(), offset), Declaration { name: name.to_string(), ty: ty });
+    }
+
+    /// Declares that `register` holds the variable `name` of type `ty` when `function` is entered.
+    pub fn apply_register(&mut self, function: &Uuid, register: &str, name: &str, ty: DataType) {
+        self.registers.insert((*function, register.to_string()), Declaration { name: name.to_string(), ty: ty });
+    }
+
+    /// Path to the field at `offset` bytes into a `ty`, like `.pos.x` or `[3]`, and its type.
+    /// Empty if `offset` is 0 and `ty` is no aggregate.
+    pub fn member(&self, ty: &DataType, offset: u64) -> Option<(String, DataType)> {
+        self.path(ty, offset, 0)
+    }
+
+    fn path(&self, ty: &DataType, offset: u64, depth: usize) -> Option<(String, DataType)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        match self.resolve(ty) {
+            &DataType::Array { ref element, count } => {
+                let size = self.size_of(element)?;
+
+                if size == 0 || (count > 0 && offset >= size * count) {
+                    return None;
+                }
+
+                let (rest, leaf) = self.path(element, offset % size, depth + 1)?;
+
+                Some((format!("[{}]{}", offset / size, rest), leaf))
+            }
+            &DataType::Named(ref name) => {
+                let (members, union) = match self.definitions.get(name) {
+                    Some(&Definition::Struct { ref members, .. }) => (members, false),
+                    Some(&Definition::Union { ref members, .. }) => (members, true),
+                    _ if offset == 0 => return Some((String::new(), ty.clone())),
+                    _ => return None,
+                };
+                let mut candidates = members.iter().filter(
+                    |m| {
+                        m.offset <= offset &&
+                        match self.size_of(&m.ty) {
+                            Some(sz) => offset < m.offset + sz,
+                            None => offset == m.offset,
+                        }
+                    }
+                );
+                let found = if union {
+                    candidates.filter_map(|m| self.path(&m.ty, offset - m.offset, depth + 1).map(|p| (m, p))).next()
+                } else {
+                    candidates.next().and_then(|m| self.path(&m.ty, offset - m.offset, depth + 1).map(|p| (m, p)))
+                };
+
+                found.map(
+                    |(m, (rest, leaf))| if m.name.is_empty() {
+                        (rest, leaf)
+                    } else {
+                        (format!(".{}{}", m.name, rest), leaf)
+                    }
+                )
+            }
+            _ if offset == 0 => Some((String::new(), ty.clone())),
+            _ => None,
+        }
+    }
+
+    /// Name of the global variable or field at `address`, like `config.flags`.
+    pub fn global(&self, address: u64) -> Option<(String, DataType)> {
+        let (&start, decl) = self.globals.range(..address.saturating_add(1)).next_back()?;
+        let (path, leaf) = self.member(&decl.ty, address - start)?;
+
+        Some((format!("{}{}", decl.name, path), leaf))
+    }
+
+    /// Expression accessing the value `offset` bytes after where `expr`, a `*ty`, points to.
+    fn dereference(&self, expr: &str, ty: &DataType, offset: i64) -> Option<(String, DataType)> {
+        let size = self.size_of(ty).unwrap_or(0) as i64;
+
+        if offset >= 0 && (size == 0 || offset < size) {
+            let (path, leaf) = self.member(ty, offset as u64)?;
+
+            if path.is_empty() {
+                Some((format!("*{}", expr), leaf))
+            } else if path.starts_with('.') {
+                Some((format!("{}->{}", expr, &path[1..]), leaf))
+            } else {
+                Some((format!("(*{}){}", expr, path), leaf))
+            }
+        } else if size > 0 {
+            let index = if offset < 0 { (offset - size + 1) / size } else { offset / size };
+            let (path, leaf) = self.member(ty, (offset - index * size) as u64)?;
+
+            Some((format!("{}[{}]{}", expr, index, path), leaf))
+        } else {
+            None
+        }
+    }
+
+    /// Variable at `address` in the stack frame of `function`.
+    fn stack_slot(&self, function: &Uuid, register: &str, offset: i64) -> Option<(String, DataType)> {
+        let from = (*function, register.to_string(), i64::min_value());
+        let to = (*function, register.to_string(), offset.saturating_add(1));
+        let (&(_, _, start), decl) = self.stack.range(from..to).next_back()?;
+        let (path, leaf) = self.member(&decl.ty, (offset - start) as u64)?;
+
+        Some((format!("{}{}", decl.name, path), leaf))
+    }
+
+    /// Imports the struct, union, enum and typedef definitions in the C source `src`. Returns
+    /// the number of definitions added. See `header` for what is understood.
+    pub fn import_header(&mut self, src: &str) -> Result<usize> {
+        header::import(self, src)
+    }
+
+    /// Imports types and global variables from the DWARF sections `.debug_info`, `.debug_abbrev`
+    /// and `.debug_str`. Addresses are moved by `base`. Returns the number of definitions added.
+    pub fn import_dwarf(&mut self, info: &[u8], abbrev: &[u8], strings: &[u8], endianess: Endianess, base: u64) -> Result<usize> {
+        dwarf::import(self, info, abbrev, strings, endianess, base)
+    }
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    if align <= 1 { value } else { (value + align - 1) / align * align }
+}
+
+/// Symbolic value of an IL variable.
+#[derive(Clone,Debug)]
+enum Value {
+    Unknown,
+    Constant(u64),
+    /// Value a register had when the basic block was entered, plus an offset
+    Register(Cow<'static, str>, i64),
+    /// Typed variable plus an offset
+    Typed(String, DataType, i64),
+}
+
+impl Value {
+    fn add(self, offset: i64) -> Value {
+        match self {
+            Value::Unknown => Value::Unknown,
+            Value::Constant(c) => Value::Constant(c.wrapping_add(offset as u64)),
+            Value::Register(r, o) => Value::Register(r, o.wrapping_add(offset)),
+            Value::Typed(e, t, o) => Value::Typed(e, t, o.wrapping_add(offset)),
+        }
+    }
+}
+
+/// Sign extends the `size` bit constant `value`.
+fn signed(value: u64, size: usize) -> i64 {
+    if size > 0 && size < 64 && value & (1 << (size - 1)) != 0 {
+        (value as i64).wrapping_sub(1 << size)
+    } else {
+        value as i64
+    }
+}
+
+/// Names the memory operands of `func` that access typed variables. Returns the expressions by
+/// mnemonic address and IL name of the operand.
+pub fn typed_operands(proj: &Project, func: &Function) -> HashMap<(u64, Cow<'static, str>), String> {
+    let types = &proj.data_types;
+    let mut ret = HashMap::new();
+
+    if types.is_empty() {
+        return ret;
+    }
+
+    let eval = |env: &HashMap<Cow<'static, str>, Value>, rv: &Rvalue| match rv {
+        &Rvalue::Constant { value, .. } => Value::Constant(value),
+        &Rvalue::Variable { ref name, .. } => {
+            match env.get(name) {
+                Some(v) => v.clone(),
+                None => {
+                    match types.registers.get(&(*func.uuid(), name.to_string())) {
+                        Some(decl) => Value::Typed(decl.name.clone(), decl.ty.clone(), 0),
+                        None => Value::Register(name.clone(), 0),
+                    }
+                }
+            }
+        }
+        &Rvalue::Undefined => Value::Unknown,
+    };
+    let access = |value: Value| match value {
+        Value::Constant(a) => types.global(a),
+        Value::Register(ref r, o) => types.stack_slot(func.uuid(), r, o),
+        Value::Typed(ref expr, ref ty, o) => {
+            match types.resolve(ty) {
+                &DataType::Pointer(ref ty) => types.dereference(expr, ty, o),
+                _ => None,
+            }
+        }
+        Value::Unknown => None,
+    };
+
+    // offsets of stack slots are relative to the current value of the frame register
+    let frame = types.stack.keys().filter(|k| k.0 == *func.uuid()).map(|k| &*k.1).collect::<HashSet<&str>>();
+
+    for bb in func.basic_blocks() {
+        // registers are only tracked within a basic block, except for the arguments
+        let mut env = HashMap::new();
+
+        for (mne, stmts) in func.mnemonics(bb) {
+            for stmt in stmts.iter() {
+                let value = match stmt.op {
+                    Operation::Move(ref a) | Operation::ZeroExtend(_, ref a) | Operation::SignExtend(_, ref a) => eval(&env, a),
+                    Operation::Add(ref a, Rvalue::Constant { value, size }) => eval(&env, a).add(signed(value, size)),
+                    Operation::Add(Rvalue::Constant { value, size }, ref b) => eval(&env, b).add(signed(value, size)),
+                    Operation::Subtract(ref a, Rvalue::Constant { value, size }) => eval(&env, a).add(signed(value, size).wrapping_neg()),
+                    Operation::Load(_, _, _, ref addr) => {
+                        match access(eval(&env, addr)) {
+                            Some((expr, ty)) => {
+                                if let Lvalue::Variable { ref name, .. } = stmt.assignee {
+                                    ret.insert((mne.area.start, name.clone()), expr.clone());
+                                }
+                                Value::Typed(expr, ty, 0)
+                            }
+                            None => Value::Unknown,
+                        }
+                    }
+                    Operation::Store(_, _, _, ref addr, ref val) => {
+                        if let (Some((expr, _)), &Rvalue::Variable { ref name, .. }) = (access(eval(&env, addr)), val) {
+                            ret.insert((mne.area.start, name.clone()), expr);
+                        }
+                        Value::Unknown
+                    }
+                    _ => Value::Unknown,
+                };
+
+                match stmt.assignee {
+                    Lvalue::Variable { ref name, .. } if frame.contains(&**name) => {}
+                    Lvalue::Variable { ref name, .. } => {
+                        env.insert(name.clone(), value);
+                    }
+                    Lvalue::Undefined => {}
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Mnemonic, Program, Region, Statement, listing};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn var(name: &str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Owned(name.to_string()), size: size, subscript: None }
+    }
+
+    /// `opcode dst, [base+disp]` or `opcode [base+disp], src`, lifted like the AMD64 disassembler
+    fn memory(start: u64, opcode: &str, reg: &str, base: &str, disp: i64, size: usize, load: bool) -> Mnemonic {
+        let addr = var(&format!("[{}{:+}]", base, disp), 64);
+        let op = var(&format!("PTR [{}{:+}]", base, disp), size);
+        let reg = var(reg, size);
+        let ram = Cow::Borrowed("RAM");
+        let mut stmts = vec![Statement { op: Operation::Add(var(base, 64).into(), Rvalue::new_u64(disp as u64)), assignee: addr.clone() }];
+
+        if load {
+            stmts.push(Statement { op: Operation::Load(ram, Endianess::Little, size, addr.into()), assignee: op.clone() });
+            stmts.push(Statement { op: Operation::Move(op.clone().into()), assignee: reg.clone() });
+        } else {
+            stmts.push(Statement { op: Operation::Move(reg.clone().into()), assignee: op.clone() });
+            stmts.push(Statement { op: Operation::Store(ram, Endianess::Little, size, addr.into(), op.clone().into()), assignee: Lvalue::Undefined });
+        }
+
+        let ops = if load { vec![reg.into(), op.into()] } else { vec![op.into(), reg.into()] };
+
+        Mnemonic::new(start..start + 4, opcode.to_string(), "{u}, {u}".to_string(), ops.iter(), stmts.iter()).ok().unwrap()
+    }
+
+    #[test]
+    fn typed_listing() {
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let mnes = vec![
+            memory(0x100, "mov", "RAX", "RBP", -8, 64, true),
+            memory(0x104, "mov", "EDX", "RAX", 0x1c, 32, true),
+            memory(0x108, "mov", "RCX", "RDI", 0x20, 64, false),
+            memory(0x10c, "mov", "EDX", "RBP", -0x18, 32, true),
+            memory(0x110, "mov", "RSI", "RAX", 0x20, 64, true),
+            memory(0x114, "mov", "ESI", "RSI", 0x10, 32, false),
+            memory(0x118, "mov", "EAX", "RCX", 0, 32, true),
+        ];
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(mnes)));
+        let mut func = Function::undefined(0x100, None, proj.region(), Some("update".to_string()));
+        let mut prog = Program::new("prog");
+        let uuid = *func.uuid();
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+
+        let player = DataType::Named("struct player".to_string());
+        let lib = &mut proj.data_types;
+
+        lib.import_header("struct item { char id[16]; int count; }; struct player { char name[28]; int health; struct item *items; };").unwrap();
+        lib.apply_stack(&uuid, "RBP", -8, "player", player.clone().pointer_to());
+        lib.apply_stack(&uuid, "RBP", -0x28, "local", DataType::Named("struct item".to_string()));
+        lib.apply_register(&uuid, "RDI", "other", player.pointer_to());
+
+        let typed = typed_operands(&proj, &func);
+
+        prog.insert(func);
+
+        let listing = listing::program(&proj, &prog);
+        let lines = listing.lines().map(|l| l.split('\t').last().unwrap_or("")).collect::<Vec<_>>();
+
+        assert_eq!(typed.len(), 6);
+        assert_eq!(
+            lines,
+            vec![
+                "0000000000000100 <update>:",
+                "mov rax, player",
+                "mov edx, player->health",
+                "mov other->items, rcx",
+                "mov edx, local.count",
+                "mov rsi, player->items",
+                "mov player->items->count, esi",
+                "mov eax, ptr [rcx+0]",
+            ]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Decompressed views of compressed data.
+//!
+//! Firmware containers often carry their code compressed. Once the compressed blob has been
+//! found, [`map`] decompresses it and places the result at a virtual address of the root region,
+//! where the disassembler and all analyses see it like any other code. The decompressed bytes
+//! are a layer named `DECOMPRESSED_LAYER`, so they can be switched off and told apart from the
+//! file with `Region::provenance`. They are also added to `Project::data` as a `Region`
+//! overlapping the compressed bytes.
+//!
+//! zlib, gzip and raw DEFLATE streams, LZMA (`.lzma`) and XZ files as well as LZ4 frames and
+//! blocks are understood. [`Codec::detect`] guesses the format from the first bytes.
+//!
+//! [`map`]: fn.map.html
+//! [`Codec::detect`]: enum.Codec.html#method.detect
+
+use {Bound, Layer, OpaqueLayer, Project, Region, Result};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use lz4_flex;
+use lzma_rs;
+use panopticon_graph_algos::MutableGraphTrait;
+use region::{DECOMPRESSED_LAYER, RegionRef};
+use std::fmt;
+use std::io::{BufReader, Read};
+
+/// Compression format.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Codec {
+    /// DEFLATE with zlib header (RFC 1950)
+    Zlib,
+    /// DEFLATE with gzip header (RFC 1952)
+    Gzip,
+    /// DEFLATE without header (RFC 1951)
+    Deflate,
+    /// LZMA with the 13 byte header of `.lzma` files
+    Lzma,
+    /// XZ container
+    Xz,
+    /// LZ4 frame
+    Lz4,
+    /// Single LZ4 block, decompressing to the given number of bytes
+    Lz4Block(usize),
+}
+
+impl Codec {
+    /// Guesses the format of `bytes` from their magic number. Raw DEFLATE streams and LZ4 blocks
+    /// have none and are never returned.
+    pub fn detect(bytes: &[u8]) -> Option<Codec> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Codec::Xz)
+        } else if bytes.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+            Some(Codec::Lz4)
+        } else if bytes.len() >= 13 && bytes[0] == 0x5d && bytes[1] == 0 && bytes[2] == 0 {
+            // properties lc=3, lp=0, pb=2 and a dictionary size of at most 16 MiB
+            Some(Codec::Lzma)
+        } else if bytes.len() >= 2 && bytes[0] & 0x0f == 8 && bytes[0] >> 4 <= 7 && ((bytes[0] as u16) << 8 | bytes[1] as u16) % 31 == 0 {
+            Some(Codec::Zlib)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Codec::Zlib => f.write_str("zlib"),
+            Codec::Gzip => f.write_str("gzip"),
+            Codec::Deflate => f.write_str("deflate"),
+            Codec::Lzma => f.write_str("lzma"),
+            Codec::Xz => f.write_str("xz"),
+            Codec::Lz4 | Codec::Lz4Block(_) => f.write_str("lz4"),
+        }
+    }
+}
+
+/// Decompresses `bytes`. Data after the end of the compressed stream is ignored.
+pub fn decompress(bytes: &[u8], codec: Codec) -> Result<Vec<u8>> {
+    let mut ret = vec![];
+
+    match codec {
+        Codec::Zlib => {
+            ZlibDecoder::new(bytes).read_to_end(&mut ret)?;
+        }
+        Codec::Gzip => {
+            GzDecoder::new(bytes)?.read_to_end(&mut ret)?;
+        }
+        Codec::Deflate => {
+            DeflateDecoder::new(bytes).read_to_end(&mut ret)?;
+        }
+        Codec::Lzma => lzma_rs::lzma_decompress(&mut BufReader::new(bytes), &mut ret)?,
+        Codec::Xz => lzma_rs::xz_decompress(&mut BufReader::new(bytes), &mut ret)?,
+        Codec::Lz4 => {
+            lz4_flex::frame::FrameDecoder::new(bytes).read_to_end(&mut ret)?;
+        }
+        Codec::Lz4Block(len) => ret = lz4_flex::block::decompress(bytes, len)?,
+    }
+
+    Ok(ret)
+}
+
+/// Decompresses the bytes inside `area` of the root region of `proj` and maps them at `address`.
+/// Returns the overlay `Region` holding the decompressed bytes. Fails if `area` isn't completely
+/// defined or the result doesn't fit into the root region at `address`.
+pub fn map(proj: &mut Project, area: Bound, codec: Codec, address: u64) -> Result<RegionRef> {
+    let bytes = match proj.region().read(area.start, (area.end - area.start) as usize) {
+        Some(b) => b,
+        None => return Err(format!("{:#x}..{:#x} is not completely defined", area.start, area.end).into()),
+    };
+    let data = OpaqueLayer::wrap(decompress(&bytes, codec)?);
+    let mapped = Bound::new(address, address + data.len());
+    let root = proj.data.root;
+    let covered = match proj.data.dependencies.vertex_label_mut(root) {
+        Some(r) => r.cover_named(DECOMPRESSED_LAYER, mapped.clone(), Layer::Opaque(data.clone())),
+        None => false,
+    };
+
+    if !covered {
+        return Err(format!("{} bytes decompressed from {:#x} don't fit at {:#x}", data.len(), area.start, address).into());
+    }
+
+    let name = format!("{}@{:#x}", codec, area.start);
+    let overlay = proj.data.dependencies.add_vertex(Region::new(name, data));
+
+    proj.data.dependencies.add_edge(area, root, overlay);
+    Ok(overlay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use panopticon_graph_algos::GraphTrait;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    fn sample() -> Vec<u8> {
+        (0..0x300u32).map(|i| (i % 7) as u8).collect()
+    }
+
+    #[test]
+    fn codecs() {
+        let data = sample();
+        let mut zlib = ZlibEncoder::new(vec![], Compression::Default);
+        let mut lzma = vec![];
+        let mut lz4 = lz4_flex::frame::FrameEncoder::new(vec![]);
+
+        zlib.write_all(&data).unwrap();
+        lzma_rs::lzma_compress(&mut BufReader::new(&data[..]), &mut lzma).unwrap();
+        lz4.write_all(&data).unwrap();
+
+        let zlib = zlib.finish().unwrap();
+        let lz4 = lz4.finish().unwrap();
+        let block = lz4_flex::block::compress(&data);
+
+        assert_eq!(Codec::detect(&zlib), Some(Codec::Zlib));
+        assert_eq!(Codec::detect(&lzma), Some(Codec::Lzma));
+        assert_eq!(Codec::detect(&lz4), Some(Codec::Lz4));
+        assert_eq!(Codec::detect(&data), None);
+        assert_eq!(decompress(&zlib, Codec::Zlib).unwrap(), data);
+        assert_eq!(decompress(&lzma, Codec::Lzma).unwrap(), data);
+        assert_eq!(decompress(&lz4, Codec::Lz4).unwrap(), data);
+        assert_eq!(decompress(&block, Codec::Lz4Block(data.len())).unwrap(), data);
+        assert!(decompress(&data, Codec::Xz).is_err());
+    }
+
+    #[test]
+    fn map_blob() {
+        let data = sample();
+        let mut zlib = ZlibEncoder::new(vec![], Compression::Default);
+
+        zlib.write_all(&data).unwrap();
+
+        let mut blob = vec![0xaa; 0x10];
+        let zlib = zlib.finish().unwrap();
+        let area = Bound::new(0x10, 0x10 + zlib.len() as u64);
+
+        blob.extend(zlib);
+
+        let mut reg = Region::undefined("RAM".to_string(), 0x10000);
+        assert!(reg.cover(Bound::new(0, blob.len() as u64), Layer::wrap(blob)));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        let overlay = map(&mut proj, area.clone(), Codec::Zlib, 0x8000).unwrap();
+
+        assert_eq!(proj.region().read(0x8000, data.len()), Some(data.clone()));
+        assert_eq!(proj.region().provenance(0x8001), Some(DECOMPRESSED_LAYER.to_string()));
+        assert_eq!(proj.data.dependencies.vertex_label(overlay).map(|r| r.name().clone()), Some("zlib@0x10".to_string()));
+        assert_eq!(proj.data.projection().iter().filter(|&&(_, r)| r == overlay).count(), 1);
+        assert!(map(&mut proj, area.clone(), Codec::Zlib, 0xff00).is_err());
+        assert!(map(&mut proj, Bound::new(0x100, 0x200), Codec::Zlib, 0).is_err());
+    }
+}
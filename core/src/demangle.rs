@@ -0,0 +1,2452 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Symbol demangling.
+//!
+//! C++ and Rust compilers encode the scope, template arguments and parameter types of functions
+//! in their symbol names. [`demangle`](fn.demangle.html) turns such a name back into the
+//! declaration it came from. Three schemes are understood:
+//!
+//! - Itanium C++ (`_Z...`), used by GCC and Clang everywhere except Windows. Rust's legacy scheme
+//!   is a subset of it, ending in a hash that is dropped.
+//! - Microsoft Visual C++ (`?...`).
+//! - Rust v0 (`_R...`).
+//!
+//! The output follows `c++filt`, `llvm-undname` and `rustc-demangle` respectively. Constructs
+//! that are rare in binaries, like expressions in template arguments, aren't supported. Names
+//! using them are left mangled.
+//!
+//! Functions keep the symbol name as found by the loader, `Function::display_name` demangles it.
+
+use std::mem;
+
+/// Maximal nesting of names and types. Deeper symbols are rejected instead of overflowing the
+/// stack.
+const MAX_DEPTH: usize = 256;
+
+/// Demangles the Itanium C++, MSVC or Rust symbol `name`. Returns `None` if `name` isn't mangled
+/// or uses a construct that isn't supported.
+pub fn demangle(name: &str) -> Option<String> {
+    // Mach-o prefixes all symbols with an underscore
+    let name = if name.starts_with("__Z") || name.starts_with("__R") { &name[1..] } else { name };
+
+    if name.starts_with('?') {
+        return Msvc::new(name).symbol();
+    }
+
+    // ELF imports carry the version of the symbol, e.g. `@GLIBCXX_3.4`
+    let (name, version) = match name.find('@') {
+        Some(i) => (&name[..i], &name[i..]),
+        None => (name, ""),
+    };
+    let ret = if name.starts_with("_R") {
+        Rust::new(&name[2..]).symbol()
+    } else if name.starts_with("_Z") {
+        rust_legacy(name).or_else(|| Itanium::new(&name[2..]).symbol())
+    } else {
+        None
+    };
+
+    ret.map(|s| s + version)
+}
+
+/// Itanium C++ type. Kept as a tree because pointers to functions and arrays are printed around
+/// the inner type.
+#[derive(Clone,Debug)]
+enum Node {
+    Name(String),
+    Qualified(Box<Node>, String),
+    Pointer(Box<Node>, &'static str),
+    /// Return type, parameters and reference qualifier
+    Function(Box<Node>, Vec<Node>, &'static str),
+    Array(Box<Node>, String),
+    /// Class and member type
+    MemberPointer(String, Box<Node>),
+    /// Template parameter. Only used in the substitution table, resolved when referenced.
+    Param(usize),
+    /// Template argument pack
+    Pack(Vec<Node>),
+    /// Pattern repeated for each element of the packs it references
+    Expansion(Box<Node>),
+}
+
+impl Node {
+    fn left(&self) -> String {
+        match self {
+            &Node::Name(ref n) => n.clone(),
+            &Node::Qualified(ref t, ref q) => {
+                match **t {
+                    Node::Function(..) => t.left(),
+                    _ => format!("{} {}", t.left(), q),
+                }
+            }
+            &Node::Pointer(ref t, op) => {
+                match t.unqualified() {
+                    &Node::Function(..) => format!("{}({}", t.left(), op),
+                    &Node::Array(..) => format!("{} ({}", t.left(), op),
+                    _ => format!("{}{}", t.left(), op),
+                }
+            }
+            &Node::Function(ref ret, _, _) => format!("{} ", ret),
+            &Node::Array(ref t, _) => t.left(),
+            &Node::MemberPointer(ref class, ref t) => {
+                match t.unqualified() {
+                    &Node::Function(..) => format!("{}({}::*", t.left(), class),
+                    _ => format!("{} {}::*", t.left(), class),
+                }
+            }
+            &Node::Param(i) => format!("T{}", i),
+            &Node::Pack(ref elems) => join(elems),
+            &Node::Expansion(ref t) => {
+                match t.pack_size() {
+                    Some(n) => (0..n).map(|i| t.pack_element(i).to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(", "),
+                    None => t.left(),
+                }
+            }
+        }
+    }
+
+    fn right(&self) -> String {
+        match self {
+            &Node::Name(_) => String::new(),
+            &Node::Qualified(ref t, ref q) => {
+                match **t {
+                    Node::Function(..) => format!("{} {}", t.right(), q),
+                    _ => t.right(),
+                }
+            }
+            &Node::Pointer(ref t, _) |
+            &Node::MemberPointer(_, ref t) => {
+                match t.unqualified() {
+                    &Node::Function(..) | &Node::Array(..) => format!("){}", t.right()),
+                    _ => t.right(),
+                }
+            }
+            &Node::Function(_, ref params, refq) => format!("({}){}", join(params), refq),
+            &Node::Array(ref t, ref dim) => {
+                let inner = t.right();
+                let inner = if inner.starts_with(' ') { &inner[1..] } else { &inner[..] };
+
+                format!(" [{}]{}", dim, inner)
+            }
+            &Node::Param(_) |
+            &Node::Pack(_) => String::new(),
+            &Node::Expansion(ref t) => {
+                match t.pack_size() {
+                    Some(_) => String::new(),
+                    None => format!("{}...", t.right()),
+                }
+            }
+        }
+    }
+
+    fn unqualified(&self) -> &Node {
+        match self {
+            &Node::Qualified(ref t, _) => t.unqualified(),
+            t => t,
+        }
+    }
+
+    /// Number of elements of the first pack referenced by this type.
+    fn pack_size(&self) -> Option<usize> {
+        match self {
+            &Node::Pack(ref elems) => Some(elems.len()),
+            &Node::Name(_) |
+            &Node::Param(_) |
+            &Node::Expansion(_) => None,
+            &Node::Qualified(ref t, _) |
+            &Node::Pointer(ref t, _) |
+            &Node::Array(ref t, _) |
+            &Node::MemberPointer(_, ref t) => t.pack_size(),
+            &Node::Function(ref ret, ref params, _) => ret.pack_size().or_else(|| params.iter().filter_map(|p| p.pack_size()).next()),
+        }
+    }
+
+    /// This type with all packs replaced by their `i`th element.
+    fn pack_element(&self, i: usize) -> Node {
+        match self {
+            &Node::Pack(ref elems) => elems.get(i).cloned().unwrap_or_else(|| Node::Name(String::new())),
+            &Node::Name(_) |
+            &Node::Param(_) |
+            &Node::Expansion(_) => self.clone(),
+            &Node::Qualified(ref t, ref q) => Node::Qualified(Box::new(t.pack_element(i)), q.clone()),
+            &Node::Pointer(ref t, op) => pointer(t.pack_element(i), op),
+            &Node::Array(ref t, ref dim) => Node::Array(Box::new(t.pack_element(i)), dim.clone()),
+            &Node::MemberPointer(ref class, ref t) => Node::MemberPointer(class.clone(), Box::new(t.pack_element(i))),
+            &Node::Function(ref ret, ref params, refq) => {
+                Node::Function(Box::new(ret.pack_element(i)), params.iter().map(|p| p.pack_element(i)).collect(), refq)
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Display for Node {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}{}", self.left(), self.right())
+    }
+}
+
+/// `t` with cv-qualifiers `qualifiers`. Qualifiers already present aren't repeated.
+fn qualified(t: Node, qualifiers: String) -> Node {
+    match t {
+        Node::Qualified(inner, q) => {
+            let mut all = q.split(' ').chain(qualifiers.split(' ')).collect::<Vec<_>>();
+
+            all.sort_by_key(|q| ["const", "volatile", "restrict"].iter().position(|x| x == q));
+            all.dedup();
+            Node::Qualified(inner, all.join(" "))
+        }
+        t => Node::Qualified(Box::new(t), qualifiers),
+    }
+}
+
+/// Pointer or reference to `t`. References to references collapse.
+fn pointer(t: Node, op: &'static str) -> Node {
+    match t {
+        Node::Pointer(inner, inner_op) if op != "*" && inner_op != "*" => {
+            if op == "&&" && inner_op == "&&" {
+                Node::Pointer(inner, "&&")
+            } else {
+                Node::Pointer(inner, "&")
+            }
+        }
+        t => Node::Pointer(Box::new(t), op),
+    }
+}
+
+fn join(nodes: &[Node]) -> String {
+    // Empty packs vanish
+    nodes.iter().map(|n| n.to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(", ")
+}
+
+/// Properties of an Itanium C++ name relevant to the function type following it.
+#[derive(Clone,Debug,Default)]
+struct NameInfo {
+    /// The name ends in template arguments, functions have their return type encoded then
+    template: bool,
+    /// Constructors, destructors and conversion operators have no return type
+    special: bool,
+    /// Qualifiers of member functions
+    qualifiers: String,
+}
+
+/// Parser for Itanium C++ names, without the `_Z` prefix.
+struct Itanium<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+    /// Substitution candidates, referenced by `S_` and `S<n>_`
+    subs: Vec<Node>,
+    /// Arguments of the template being encoded, referenced by `T_` and `T<n>_`
+    template_args: Vec<Node>,
+    /// Nesting of types, template arguments inside them aren't referenced by `T_`
+    in_type: usize,
+}
+
+impl<'a> Itanium<'a> {
+    fn new(s: &'a str) -> Itanium<'a> {
+        Itanium { s: s.as_bytes(), pos: 0, depth: 0, subs: vec![], template_args: vec![], in_type: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, off: usize) -> Option<u8> {
+        self.s.get(self.pos + off).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let ret = self.peek();
+        self.pos += 1;
+        ret
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.eat(c) { Some(()) } else { None }
+    }
+
+    fn enter(&mut self) -> Option<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH { None } else { Some(()) }
+    }
+
+    fn leave<T>(&mut self, ret: Option<T>) -> Option<T> {
+        self.depth -= 1;
+        ret
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+
+        while self.peek().map(|c| c.is_ascii_digit()) == Some(true) {
+            self.pos += 1;
+        }
+        ::std::str::from_utf8(&self.s[start..self.pos]).ok()?.parse().ok()
+    }
+
+    /// Number that may be negative, like call offsets.
+    fn signed_number(&mut self) -> Option<String> {
+        let neg = self.eat(b'n');
+        let n = self.number()?;
+
+        Some(if neg { format!("-{}", n) } else { n.to_string() })
+    }
+
+    fn at_end(&self) -> bool {
+        match self.peek() {
+            None | Some(b'E') | Some(b'.') => true,
+            _ => false,
+        }
+    }
+
+    fn symbol(mut self) -> Option<String> {
+        let mut ret = self.encoding(true)?;
+
+        // Clones made by GCC's optimizer, e.g. `.constprop.0`
+        while self.peek() == Some(b'.') && self.peek_at(1).map(|c| c.is_ascii_lowercase() || c == b'_') == Some(true) {
+            let start = self.pos;
+
+            self.pos += 1;
+            while self.peek().map(|c| c.is_ascii_lowercase() || c == b'_') == Some(true) {
+                self.pos += 1;
+            }
+            while self.peek() == Some(b'.') && self.peek_at(1).map(|c| c.is_ascii_digit()) == Some(true) {
+                self.pos += 1;
+                self.number()?;
+            }
+            ret = format!("{} [clone {}]", ret, String::from_utf8_lossy(&self.s[start..self.pos]));
+        }
+
+        if self.pos == self.s.len() { Some(ret) } else { None }
+    }
+
+    /// Parses a function or variable. The return type of template functions is omitted unless
+    /// `return_type` is set.
+    fn encoding(&mut self, return_type: bool) -> Option<String> {
+        match (self.peek()?, self.peek_at(1)) {
+            (b'T', _) | (b'G', _) => return self.special_name(),
+            _ => {}
+        }
+
+        let (name, info) = self.name()?;
+
+        if self.at_end() {
+            return Some(name);
+        }
+
+        let ret = if info.template && !info.special { Some(self.type_()?) } else { None };
+        let params = self.parameters()?;
+
+        Some(
+            match ret {
+                Some(ref ret) if return_type => format!("{} {}({}){}", ret, name, params, info.qualifiers),
+                _ => format!("{}({}){}", name, params, info.qualifiers),
+            }
+        )
+    }
+
+    fn parameters(&mut self) -> Option<String> {
+        let mut params = vec![];
+
+        if self.peek() == Some(b'v') && (self.peek_at(1).is_none() || self.peek_at(1) == Some(b'E') || self.peek_at(1) == Some(b'.')) {
+            self.pos += 1;
+            return Some(String::new());
+        }
+        while !self.at_end() {
+            params.push(self.type_()?);
+        }
+        Some(join(&params))
+    }
+
+    fn special_name(&mut self) -> Option<String> {
+        let kind = (self.next()?, self.next()?);
+
+        match kind {
+            (b'T', b'V') => Some(format!("vtable for {}", self.type_()?)),
+            (b'T', b'T') => Some(format!("VTT for {}", self.type_()?)),
+            (b'T', b'I') => Some(format!("typeinfo for {}", self.type_()?)),
+            (b'T', b'S') => Some(format!("typeinfo name for {}", self.type_()?)),
+            (b'T', b'h') => {
+                self.signed_number()?;
+                self.expect(b'_')?;
+                Some(format!("non-virtual thunk to {}", self.encoding(true)?))
+            }
+            (b'T', b'v') => {
+                self.signed_number()?;
+                self.expect(b'_')?;
+                self.signed_number()?;
+                self.expect(b'_')?;
+                Some(format!("virtual thunk to {}", self.encoding(true)?))
+            }
+            (b'T', b'c') => {
+                self.call_offset()?;
+                self.call_offset()?;
+                Some(format!("covariant return thunk to {}", self.encoding(true)?))
+            }
+            (b'T', b'C') => {
+                let derived = self.type_()?;
+
+                self.number()?;
+                self.expect(b'_')?;
+                Some(format!("construction vtable for {}-in-{}", self.type_()?, derived))
+            }
+            (b'T', b'H') => Some(format!("TLS init function for {}", self.name()?.0)),
+            (b'T', b'W') => Some(format!("TLS wrapper function for {}", self.name()?.0)),
+            (b'G', b'V') => Some(format!("guard variable for {}", self.name()?.0)),
+            (b'G', b'R') => {
+                let name = self.name()?.0;
+                let mut seq = 0;
+
+                while self.peek().map(|c| c.is_ascii_alphanumeric()) == Some(true) {
+                    seq += 1;
+                    self.pos += 1;
+                }
+                self.eat(b'_');
+                Some(format!("reference temporary #{} for {}", seq, name))
+            }
+            (b'G', b'A') => Some(format!("hidden alias for {}", self.encoding(true)?)),
+            (b'G', b'T') => {
+                match self.next()? {
+                    b'n' => Some(format!("non-transaction clone for {}", self.encoding(true)?)),
+                    b't' => Some(format!("transaction clone for {}", self.encoding(true)?)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn call_offset(&mut self) -> Option<()> {
+        match self.next()? {
+            b'h' => {
+                self.signed_number()?;
+                self.expect(b'_')
+            }
+            b'v' => {
+                self.signed_number()?;
+                self.expect(b'_')?;
+                self.signed_number()?;
+                self.expect(b'_')
+            }
+            _ => None,
+        }
+    }
+
+    fn name(&mut self) -> Option<(String, NameInfo)> {
+        self.enter()?;
+
+        let ret = match self.peek()? {
+            b'N' => self.nested_name(),
+            b'Z' => self.local_name(),
+            b'S' if self.peek_at(1) == Some(b't') => {
+                self.pos += 2;
+                let (name, special) = self.unqualified_name(None)?;
+                self.maybe_template(format!("std::{}", name), special)
+            }
+            b'S' => {
+                let name = self.substitution()?.to_string();
+
+                if self.peek() == Some(b'I') {
+                    let args = self.template_args()?;
+                    Some((name + &args, NameInfo { template: true, ..NameInfo::default() }))
+                } else {
+                    Some((name, NameInfo::default()))
+                }
+            }
+            _ => {
+                let (name, special) = self.unqualified_name(None)?;
+                self.maybe_template(name, special)
+            }
+        };
+
+        self.leave(ret)
+    }
+
+    fn maybe_template(&mut self, name: String, special: bool) -> Option<(String, NameInfo)> {
+        if self.peek() == Some(b'I') {
+            self.subs.push(Node::Name(name.clone()));
+
+            let args = self.template_args()?;
+            Some((append_args(name, &args), NameInfo { template: true, special: special, qualifiers: String::new() }))
+        } else {
+            Some((name, NameInfo { template: false, special: special, qualifiers: String::new() }))
+        }
+    }
+
+    fn nested_name(&mut self) -> Option<(String, NameInfo)> {
+        self.expect(b'N')?;
+
+        let mut info = NameInfo::default();
+        let qualifiers = self.cv_qualifiers();
+        let refq = if self.eat(b'R') {
+            " &"
+        } else if self.eat(b'O') {
+            " &&"
+        } else {
+            ""
+        };
+        let mut prefix: Option<String> = None;
+
+        info.qualifiers = format!("{}{}", qualifiers.iter().map(|q| format!(" {}", q)).collect::<String>(), refq);
+
+        loop {
+            match self.peek()? {
+                b'E' => {
+                    self.pos += 1;
+                    break;
+                }
+                b'I' => {
+                    let args = self.template_args()?;
+                    prefix = Some(append_args(prefix?, &args));
+                    info.template = true;
+                }
+                b'S' if prefix.is_none() => {
+                    // Substitutions are candidates already
+                    if self.peek_at(1) == Some(b't') {
+                        self.pos += 2;
+                        prefix = Some("std".to_string());
+                    } else {
+                        prefix = Some(self.substitution()?.to_string());
+                    }
+                    continue;
+                }
+                b'T' if prefix.is_none() => {
+                    let idx = self.template_param()?;
+                    prefix = Some(self.resolve(Node::Param(idx))?.to_string());
+                }
+                _ => {
+                    let (name, special) = self.unqualified_name(prefix.as_ref().map(|p| p.as_str()))?;
+
+                    info.template = false;
+                    info.special = special;
+                    prefix = Some(
+                        match prefix {
+                            Some(p) => format!("{}::{}", p, name),
+                            None => name,
+                        }
+                    );
+                }
+            }
+
+            // All prefixes are substitution candidates, the complete name isn't
+            if self.peek() != Some(b'E') {
+                self.subs.push(Node::Name(prefix.clone()?));
+            }
+        }
+
+        Some((prefix?, info))
+    }
+
+    fn local_name(&mut self) -> Option<(String, NameInfo)> {
+        self.expect(b'Z')?;
+
+        // Template parameters of the function refer to its own arguments
+        let saved = (mem::replace(&mut self.template_args, vec![]), mem::replace(&mut self.in_type, 0));
+        let function = self.encoding(false);
+
+        self.in_type = saved.1;
+        if self.in_type > 0 {
+            self.template_args = saved.0;
+        }
+
+        let function = function?;
+
+        self.expect(b'E')?;
+        if self.eat(b's') {
+            self.discriminator();
+            return Some((format!("{}::string literal", function), NameInfo::default()));
+        }
+
+        let (name, info) = self.name()?;
+
+        self.discriminator();
+        Some((format!("{}::{}", function, name), info))
+    }
+
+    fn discriminator(&mut self) {
+        if self.peek() == Some(b'_') {
+            self.pos += 1;
+            if self.eat(b'_') {
+                self.number();
+                self.eat(b'_');
+            } else {
+                self.number();
+            }
+        }
+    }
+
+    /// Parses a name inside the scope `scope`. Returns whether it's a constructor, destructor or
+    /// conversion operator.
+    fn unqualified_name(&mut self, scope: Option<&str>) -> Option<(String, bool)> {
+        let (mut name, special) = match self.peek()? {
+            b'0'...b'9' => (self.source_name()?, false),
+            b'L' => {
+                // Internal linkage
+                self.pos += 1;
+                let name = self.source_name()?;
+                self.discriminator();
+                (name, false)
+            }
+            b'C' => {
+                self.pos += 1;
+                if self.eat(b'I') {
+                    self.next()?;
+                    self.type_()?;
+                } else {
+                    self.next()?;
+                }
+                (base_name(scope?), true)
+            }
+            b'D' if self.peek_at(1).map(|c| c >= b'0' && c <= b'5') == Some(true) => {
+                self.pos += 2;
+                (format!("~{}", base_name(scope?)), true)
+            }
+            b'U' => (self.unnamed_type()?, false),
+            b'a'...b'z' => self.operator_name()?,
+            _ => return None,
+        };
+
+        // ABI tags
+        while self.eat(b'B') {
+            name = format!("{}[abi:{}]", name, self.source_name()?);
+        }
+
+        Some((name, special))
+    }
+
+    fn source_name(&mut self) -> Option<String> {
+        let len = self.number()?;
+        let name = self.pos.checked_add(len).and_then(|end| self.s.get(self.pos..end))?;
+
+        self.pos += len;
+        if name.starts_with(b"_GLOBAL__N") {
+            Some("(anonymous namespace)".to_string())
+        } else {
+            Some(String::from_utf8_lossy(name).to_string())
+        }
+    }
+
+    fn unnamed_type(&mut self) -> Option<String> {
+        self.expect(b'U')?;
+
+        let ret = match self.next()? {
+            b't' => "{unnamed type#".to_string(),
+            b'l' => {
+                self.in_type += 1;
+                let params = self.parameters_until_e();
+                self.in_type -= 1;
+                format!("{{lambda({})#", params?)
+            }
+            _ => return None,
+        };
+        let n = if self.peek() == Some(b'_') { 1 } else { self.number()? + 2 };
+
+        self.expect(b'_')?;
+        Some(format!("{}{}}}", ret, n))
+    }
+
+    fn parameters_until_e(&mut self) -> Option<String> {
+        let mut params = vec![];
+
+        if self.peek() == Some(b'v') && self.peek_at(1) == Some(b'E') {
+            self.pos += 2;
+            return Some(String::new());
+        }
+        while !self.eat(b'E') {
+            params.push(self.type_()?);
+        }
+        Some(join(&params))
+    }
+
+    fn operator_name(&mut self) -> Option<(String, bool)> {
+        let code = (self.next()?, self.next()?);
+        let op = match code {
+            (b'n', b'w') => " new",
+            (b'n', b'a') => " new[]",
+            (b'd', b'l') => " delete",
+            (b'd', b'a') => " delete[]",
+            (b'p', b's') | (b'p', b'l') => "+",
+            (b'n', b'g') | (b'm', b'i') => "-",
+            (b'a', b'd') | (b'a', b'n') => "&",
+            (b'd', b'e') | (b'm', b'l') => "*",
+            (b'c', b'o') => "~",
+            (b'd', b'v') => "/",
+            (b'r', b'm') => "%",
+            (b'o', b'r') => "|",
+            (b'e', b'o') => "^",
+            (b'a', b'S') => "=",
+            (b'p', b'L') => "+=",
+            (b'm', b'I') => "-=",
+            (b'm', b'L') => "*=",
+            (b'd', b'V') => "/=",
+            (b'r', b'M') => "%=",
+            (b'a', b'N') => "&=",
+            (b'o', b'R') => "|=",
+            (b'e', b'O') => "^=",
+            (b'l', b's') => "<<",
+            (b'r', b's') => ">>",
+            (b'l', b'S') => "<<=",
+            (b'r', b'S') => ">>=",
+            (b'e', b'q') => "==",
+            (b'n', b'e') => "!=",
+            (b'l', b't') => "<",
+            (b'g', b't') => ">",
+            (b'l', b'e') => "<=",
+            (b'g', b'e') => ">=",
+            (b's', b's') => "<=>",
+            (b'n', b't') => "!",
+            (b'a', b'a') => "&&",
+            (b'o', b'o') => "||",
+            (b'p', b'p') => "++",
+            (b'm', b'm') => "--",
+            (b'c', b'm') => ",",
+            (b'p', b'm') => "->*",
+            (b'p', b't') => "->",
+            (b'c', b'l') => "()",
+            (b'i', b'x') => "[]",
+            (b'q', b'u') => "?",
+            (b'c', b'v') => {
+                self.in_type += 1;
+                let ty = self.type_();
+                self.in_type -= 1;
+                return Some((format!("operator {}", ty?), true));
+            }
+            (b'l', b'i') => return Some((format!("operator\"\" {}", self.source_name()?), false)),
+            (b'v', c) if c.is_ascii_digit() => return Some((format!("operator {}", self.source_name()?), false)),
+            _ => return None,
+        };
+
+        Some((format!("operator{}", op), false))
+    }
+
+    fn cv_qualifiers(&mut self) -> Vec<&'static str> {
+        let mut ret = vec![];
+
+        if self.eat(b'r') {
+            ret.push("restrict");
+        }
+        if self.eat(b'V') {
+            ret.push("volatile");
+        }
+        if self.eat(b'K') {
+            ret.push("const");
+        }
+        // Printed as `const volatile`
+        ret.reverse();
+        ret
+    }
+
+    fn substitution(&mut self) -> Option<Node> {
+        self.expect(b'S')?;
+
+        let name = match self.next()? {
+            b'_' => return self.subs.get(0).cloned().and_then(|n| self.resolve(n)),
+            b'a' => "std::allocator",
+            b'b' => "std::basic_string",
+            b's' => "std::basic_string<char, std::char_traits<char>, std::allocator<char> >",
+            b'i' => "std::basic_istream<char, std::char_traits<char> >",
+            b'o' => "std::basic_ostream<char, std::char_traits<char> >",
+            b'd' => "std::basic_iostream<char, std::char_traits<char> >",
+            c if c.is_ascii_digit() || c.is_ascii_uppercase() => {
+                let mut seq = 0usize;
+                let mut c = c;
+
+                loop {
+                    let digit = if c.is_ascii_digit() { c - b'0' } else { c - b'A' + 10 } as usize;
+
+                    seq = seq.checked_mul(36)?.checked_add(digit)?;
+                    c = self.next()?;
+                    if c == b'_' {
+                        break;
+                    }
+                    if !c.is_ascii_digit() && !c.is_ascii_uppercase() {
+                        return None;
+                    }
+                }
+                // GCC refers to the template parameter, not its value at the time it was added
+                return self.subs.get(seq + 1).cloned().and_then(|n| self.resolve(n));
+            }
+            _ => return None,
+        };
+
+        Some(Node::Name(name.to_string()))
+    }
+
+    fn template_param(&mut self) -> Option<usize> {
+        self.expect(b'T')?;
+
+        if self.eat(b'_') {
+            Some(0)
+        } else {
+            let n = self.number()?;
+            self.expect(b'_')?;
+            Some(n + 1)
+        }
+    }
+
+    fn resolve(&self, node: Node) -> Option<Node> {
+        match node {
+            Node::Param(idx) => self.template_args.get(idx).cloned(),
+            node => Some(node),
+        }
+    }
+
+    fn template_args(&mut self) -> Option<String> {
+        self.expect(b'I')?;
+
+        let mut args = vec![];
+
+        self.in_type += 1;
+        while !self.eat(b'E') {
+            match self.template_arg() {
+                Some(arg) => args.push(arg),
+                None => {
+                    self.in_type -= 1;
+                    return None;
+                }
+            }
+        }
+        self.in_type -= 1;
+
+        let mut ret = join(&args);
+
+        if ret.ends_with('>') {
+            ret.push(' ');
+        }
+        if self.in_type == 0 {
+            self.template_args = args;
+        }
+        Some(format!("<{}>", ret))
+    }
+
+    fn template_arg(&mut self) -> Option<Node> {
+        match self.peek()? {
+            b'L' => self.literal(),
+            b'J' => {
+                self.pos += 1;
+
+                let mut args = vec![];
+                while !self.eat(b'E') {
+                    args.push(self.template_arg()?);
+                }
+                Some(Node::Pack(args))
+            }
+            // Expressions aren't supported
+            b'X' => None,
+            _ => self.type_(),
+        }
+    }
+
+    fn literal(&mut self) -> Option<Node> {
+        self.expect(b'L')?;
+
+        if self.peek() == Some(b'_') && self.peek_at(1) == Some(b'Z') {
+            self.pos += 2;
+            let ret = self.encoding(true)?;
+            self.expect(b'E')?;
+            return Some(Node::Name(ret));
+        }
+
+        let ty = self.peek()?;
+        let ty_name = self.type_()?;
+        let neg = self.eat(b'n');
+        let start = self.pos;
+
+        while self.peek()? != b'E' {
+            self.pos += 1;
+        }
+
+        let value = String::from_utf8_lossy(&self.s[start..self.pos]).to_string();
+        let value = if neg { format!("-{}", value) } else { value };
+
+        self.pos += 1;
+        Some(
+            Node::Name(
+                match ty {
+                    b'b' if value == "0" => "false".to_string(),
+                    b'b' if value == "1" => "true".to_string(),
+                    b'i' => value,
+                    b'j' => format!("{}u", value),
+                    b'l' => format!("{}l", value),
+                    b'm' => format!("{}ul", value),
+                    b'x' => format!("{}ll", value),
+                    b'y' => format!("{}ull", value),
+                    _ => format!("({}){}", ty_name, value),
+                }
+            )
+        )
+    }
+
+    fn type_(&mut self) -> Option<Node> {
+        self.enter()?;
+        self.in_type += 1;
+
+        let ret = self.type_inner();
+
+        self.in_type -= 1;
+        self.leave(ret)
+    }
+
+    fn type_inner(&mut self) -> Option<Node> {
+        let builtin = match self.peek()? {
+            b'v' => "void",
+            b'w' => "wchar_t",
+            b'b' => "bool",
+            b'c' => "char",
+            b'a' => "signed char",
+            b'h' => "unsigned char",
+            b's' => "short",
+            b't' => "unsigned short",
+            b'i' => "int",
+            b'j' => "unsigned int",
+            b'l' => "long",
+            b'm' => "unsigned long",
+            b'x' => "long long",
+            b'y' => "unsigned long long",
+            b'n' => "__int128",
+            b'o' => "unsigned __int128",
+            b'f' => "float",
+            b'd' => "double",
+            b'e' => "long double",
+            b'g' => "__float128",
+            b'z' => "...",
+            b'D' => {
+                match self.peek_at(1)? {
+                    b'd' => "decimal64",
+                    b'e' => "decimal128",
+                    b'f' => "decimal32",
+                    b'h' => "half",
+                    b'i' => "char32_t",
+                    b's' => "char16_t",
+                    b'u' => "char8_t",
+                    b'a' => "auto",
+                    b'c' => "decltype(auto)",
+                    b'n' => "decltype(nullptr)",
+                    b'p' => {
+                        self.pos += 2;
+                        let ret = Node::Expansion(Box::new(self.type_()?));
+                        self.subs.push(ret.clone());
+                        return Some(ret);
+                    }
+                    _ => return None,
+                }
+            }
+            _ => "",
+        };
+
+        if !builtin.is_empty() {
+            self.pos += if self.peek() == Some(b'D') { 2 } else { 1 };
+            return Some(Node::Name(builtin.to_string()));
+        }
+
+        let ret = match self.peek()? {
+            b'r' | b'V' | b'K' => {
+                let qualifiers = self.cv_qualifiers().join(" ");
+
+                // Qualified function types are a single substitution candidate
+                if self.peek() == Some(b'F') {
+                    Node::Qualified(Box::new(self.function_type()?), qualifiers)
+                } else {
+                    qualified(self.type_()?, qualifiers)
+                }
+            }
+            b'P' => {
+                self.pos += 1;
+                Node::Pointer(Box::new(self.type_()?), "*")
+            }
+            b'R' => {
+                self.pos += 1;
+                pointer(self.type_()?, "&")
+            }
+            b'O' => {
+                self.pos += 1;
+                pointer(self.type_()?, "&&")
+            }
+            b'C' => {
+                self.pos += 1;
+                Node::Name(format!("{} _Complex", self.type_()?))
+            }
+            b'G' => {
+                self.pos += 1;
+                Node::Name(format!("{} _Imaginary", self.type_()?))
+            }
+            b'F' => self.function_type()?,
+            b'A' => {
+                self.pos += 1;
+
+                let start = self.pos;
+                while self.peek()?.is_ascii_digit() {
+                    self.pos += 1;
+                }
+
+                let dim = String::from_utf8_lossy(&self.s[start..self.pos]).to_string();
+                self.expect(b'_')?;
+                Node::Array(Box::new(self.type_()?), dim)
+            }
+            b'M' => {
+                self.pos += 1;
+
+                let class = self.type_()?.to_string();
+                Node::MemberPointer(class, Box::new(self.type_()?))
+            }
+            b'T' => {
+                let idx = self.template_param()?;
+                let param = self.resolve(Node::Param(idx))?;
+
+                self.subs.push(Node::Param(idx));
+                if self.peek() == Some(b'I') {
+                    Node::Name(format!("{}{}", param, self.template_args()?))
+                } else {
+                    return Some(param);
+                }
+            }
+            b'S' if self.peek_at(1) != Some(b't') => {
+                let sub = self.substitution()?;
+
+                if self.peek() == Some(b'I') {
+                    Node::Name(format!("{}{}", sub, self.template_args()?))
+                } else {
+                    // Already a candidate
+                    return Some(sub);
+                }
+            }
+            b'u' => {
+                self.pos += 1;
+                Node::Name(self.source_name()?)
+            }
+            _ => Node::Name(self.name()?.0),
+        };
+
+        self.subs.push(ret.clone());
+        Some(ret)
+    }
+
+    fn function_type(&mut self) -> Option<Node> {
+        self.expect(b'F')?;
+        self.eat(b'Y');
+
+        let ret = self.type_()?;
+        let mut params = vec![];
+        let mut refq = "";
+
+        loop {
+            match (self.peek()?, self.peek_at(1)) {
+                (b'E', _) => {
+                    self.pos += 1;
+                    break;
+                }
+                (b'R', Some(b'E')) => {
+                    self.pos += 1;
+                    refq = " &";
+                }
+                (b'O', Some(b'E')) => {
+                    self.pos += 1;
+                    refq = " &&";
+                }
+                (b'v', Some(b'E')) => self.pos += 1,
+                _ => params.push(self.type_()?),
+            }
+        }
+
+        Some(Node::Function(Box::new(ret), params, refq))
+    }
+}
+
+/// Appends template arguments to `name`, separated from `operator<` and `operator<<`.
+fn append_args(name: String, args: &str) -> String {
+    if name.ends_with('<') { format!("{} {}", name, args) } else { name + args }
+}
+
+/// Name of the class `scope`, which is also the name of its constructors.
+fn base_name(scope: &str) -> String {
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = scope.as_bytes();
+
+    for (i, &c) in bytes.iter().enumerate() {
+        match c {
+            b'<' => depth += 1,
+            b'>' => depth -= 1,
+            b':' if depth == 0 && i > 0 && bytes[i - 1] == b':' => start = i + 1,
+            _ => {}
+        }
+    }
+
+    let name = &scope[start..];
+    let name = match name.find("[abi:") {
+        Some(i) => &name[..i],
+        None => name,
+    };
+    match name.find('<') {
+        Some(i) if !name.starts_with("operator") => name[..i].to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// Demangles Rust's legacy symbols. They are Itanium C++ nested names ending in a hash, without
+/// a function type.
+fn rust_legacy(name: &str) -> Option<String> {
+    let bytes = name.as_bytes();
+    let mut pos = 3;
+    let mut parts = vec![];
+
+    if !name.starts_with("_ZN") {
+        return None;
+    }
+
+    while bytes.get(pos)? != &b'E' {
+        let start = pos;
+
+        while bytes.get(pos)?.is_ascii_digit() {
+            pos += 1;
+        }
+
+        let len = name[start..pos].parse::<usize>().ok()?;
+        parts.push(pos.checked_add(len).and_then(|end| name.get(pos..end))?);
+        pos += len;
+    }
+
+    // Optimized clones and LTO add a suffix
+    let rest = &name[pos + 1..];
+    if !rest.is_empty() && !rest.starts_with('.') {
+        return None;
+    }
+
+    let hash = parts.pop()?;
+    if hash.len() != 17 || !hash.starts_with('h') || !hash[1..].bytes().all(|c| c.is_ascii_hexdigit()) || parts.is_empty() {
+        return None;
+    }
+
+    Some(parts.iter().map(|p| rust_unescape(p)).collect::<Vec<_>>().join("::"))
+}
+
+fn rust_unescape(part: &str) -> String {
+    let part = if part.starts_with("_$") { &part[1..] } else { part };
+    let mut ret = String::new();
+    let mut rest = part;
+
+    while !rest.is_empty() {
+        if rest.starts_with("..") {
+            ret.push_str("::");
+            rest = &rest[2..];
+        } else if rest.starts_with('$') {
+            let end = match rest[1..].find('$') {
+                Some(end) => end + 1,
+                None => {
+                    ret.push_str(rest);
+                    break;
+                }
+            };
+            let escape = &rest[1..end];
+            let c = match escape {
+                "SP" => Some('@'),
+                "BP" => Some('*'),
+                "RF" => Some('&'),
+                "LT" => Some('<'),
+                "GT" => Some('>'),
+                "LP" => Some('('),
+                "RP" => Some(')'),
+                "C" => Some(','),
+                _ if escape.starts_with('u') => u32::from_str_radix(&escape[1..], 16).ok().and_then(::std::char::from_u32),
+                _ => None,
+            };
+
+            match c {
+                Some(c) => ret.push(c),
+                None => ret.push_str(&rest[..end + 1]),
+            }
+            rest = &rest[end + 1..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            ret.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    ret
+}
+
+/// Parser for Rust v0 names, without the `_R` prefix.
+struct Rust<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+    /// Number of lifetimes bound by enclosing `for<...>` binders
+    bound_lifetimes: u64,
+    out: String,
+}
+
+impl<'a> Rust<'a> {
+    fn new(s: &'a str) -> Rust<'a> {
+        Rust { s: s.as_bytes(), pos: 0, depth: 0, bound_lifetimes: 0, out: String::new() }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let ret = self.peek();
+        self.pos += 1;
+        ret
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn symbol(mut self) -> Option<String> {
+        // Only version 0 has no version number
+        if self.peek()?.is_ascii_digit() {
+            return None;
+        }
+
+        self.path(true)?;
+
+        // The instantiating crate isn't printed
+        if self.peek().map(|c| c.is_ascii_uppercase()) == Some(true) {
+            let len = self.out.len();
+            self.path(false)?;
+            self.out.truncate(len);
+        }
+
+        // Vendor specific suffixes start with `.` or `$`
+        match self.peek() {
+            None | Some(b'.') | Some(b'$') => Some(self.out),
+            _ => None,
+        }
+    }
+
+    fn base62(&mut self) -> Option<u64> {
+        if self.eat(b'_') {
+            return Some(0);
+        }
+
+        let mut ret = 0u64;
+        loop {
+            let c = self.next()?;
+            let digit = match c {
+                b'0'...b'9' => c - b'0',
+                b'a'...b'z' => c - b'a' + 10,
+                b'A'...b'Z' => c - b'A' + 36,
+                b'_' => return ret.checked_add(1),
+                _ => return None,
+            };
+
+            ret = ret.checked_mul(62)?.checked_add(digit as u64)?;
+        }
+    }
+
+    fn opt_base62(&mut self, tag: u8) -> Option<u64> {
+        if self.eat(tag) { self.base62()?.checked_add(1) } else { Some(0) }
+    }
+
+    fn decimal(&mut self) -> Option<usize> {
+        let start = self.pos;
+
+        while self.peek().map(|c| c.is_ascii_digit()) == Some(true) {
+            self.pos += 1;
+        }
+        ::std::str::from_utf8(&self.s[start..self.pos]).ok()?.parse().ok()
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let punycode = self.eat(b'u');
+        let len = self.decimal()?;
+
+        self.eat(b'_');
+
+        let bytes = self.pos.checked_add(len).and_then(|end| self.s.get(self.pos..end))?;
+        let ident = String::from_utf8(bytes.to_vec()).ok()?;
+
+        self.pos += len;
+        if punycode {
+            Some(punycode_decode(&ident).unwrap_or_else(|| format!("punycode{{{}}}", ident)))
+        } else {
+            Some(ident)
+        }
+    }
+
+    fn backref<F: FnOnce(&mut Self) -> Option<()>>(&mut self, f: F) -> Option<()> {
+        let start = self.pos - 1;
+        let target = self.base62()? as usize;
+
+        if target >= start {
+            return None;
+        }
+
+        let saved = mem::replace(&mut self.pos, target);
+        let ret = f(self);
+
+        self.pos = saved;
+        ret
+    }
+
+    fn path(&mut self, in_value: bool) -> Option<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+
+        let ret = self.path_inner(in_value);
+
+        self.depth -= 1;
+        ret
+    }
+
+    fn path_inner(&mut self, in_value: bool) -> Option<()> {
+        match self.next()? {
+            b'C' => {
+                self.opt_base62(b's')?;
+                let name = self.ident()?;
+                self.out.push_str(&name);
+            }
+            b'M' => {
+                self.opt_base62(b's')?;
+                self.skip_path()?;
+                self.out.push('<');
+                self.type_()?;
+                self.out.push('>');
+            }
+            b'X' => {
+                self.opt_base62(b's')?;
+                self.skip_path()?;
+                self.out.push('<');
+                self.type_()?;
+                self.out.push_str(" as ");
+                self.path(false)?;
+                self.out.push('>');
+            }
+            b'Y' => {
+                self.out.push('<');
+                self.type_()?;
+                self.out.push_str(" as ");
+                self.path(false)?;
+                self.out.push('>');
+            }
+            b'N' => {
+                let ns = self.next()?;
+
+                self.path(in_value)?;
+
+                let dis = self.opt_base62(b's')?;
+                let name = self.ident()?;
+
+                if ns.is_ascii_uppercase() {
+                    let kind = match ns {
+                        b'C' => "closure".to_string(),
+                        b'S' => "shim".to_string(),
+                        c => (c as char).to_string(),
+                    };
+
+                    self.out.push_str("::{");
+                    self.out.push_str(&kind);
+                    if !name.is_empty() {
+                        self.out.push(':');
+                        self.out.push_str(&name);
+                    }
+                    self.out.push_str(&format!("#{}}}", dis));
+                } else if !name.is_empty() {
+                    self.out.push_str("::");
+                    self.out.push_str(&name);
+                }
+            }
+            b'I' => {
+                self.path(in_value)?;
+                if in_value {
+                    self.out.push_str("::");
+                }
+                self.out.push('<');
+
+                let mut first = true;
+                while !self.eat(b'E') {
+                    if !first {
+                        self.out.push_str(", ");
+                    }
+                    first = false;
+                    self.generic_arg()?;
+                }
+                self.out.push('>');
+            }
+            b'B' => return self.backref(|p| p.path(in_value)),
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    fn skip_path(&mut self) -> Option<()> {
+        let len = self.out.len();
+
+        self.path(false)?;
+        self.out.truncate(len);
+        Some(())
+    }
+
+    fn generic_arg(&mut self) -> Option<()> {
+        if self.eat(b'L') {
+            let lt = self.base62()?;
+            self.lifetime(lt)
+        } else if self.eat(b'K') {
+            self.const_()
+        } else {
+            self.type_()
+        }
+    }
+
+    fn lifetime(&mut self, lt: u64) -> Option<()> {
+        if lt == 0 {
+            self.out.push_str("'_");
+        } else {
+            let depth = self.bound_lifetimes.checked_sub(lt)?;
+
+            if depth < 26 {
+                self.out.push('\'');
+                self.out.push((b'a' + depth as u8) as char);
+            } else {
+                self.out.push_str(&format!("'_{}", depth));
+            }
+        }
+
+        Some(())
+    }
+
+    fn binder(&mut self) -> Option<u64> {
+        let count = self.opt_base62(b'G')?;
+
+        if count > 0 {
+            self.out.push_str("for<");
+            for i in 0..count {
+                if i > 0 {
+                    self.out.push_str(", ");
+                }
+                self.bound_lifetimes += 1;
+                self.lifetime(1)?;
+            }
+            self.out.push_str("> ");
+        }
+
+        Some(count)
+    }
+
+    fn type_(&mut self) -> Option<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+
+        let ret = self.type_inner();
+
+        self.depth -= 1;
+        ret
+    }
+
+    fn type_inner(&mut self) -> Option<()> {
+        let basic = match self.peek()? {
+            b'a' => "i8",
+            b'b' => "bool",
+            b'c' => "char",
+            b'd' => "f64",
+            b'e' => "str",
+            b'f' => "f32",
+            b'h' => "u8",
+            b'i' => "isize",
+            b'j' => "usize",
+            b'l' => "i32",
+            b'm' => "u32",
+            b'n' => "i128",
+            b'o' => "u128",
+            b's' => "i16",
+            b't' => "u16",
+            b'u' => "()",
+            b'v' => "...",
+            b'x' => "i64",
+            b'y' => "u64",
+            b'z' => "!",
+            b'p' => "_",
+            _ => "",
+        };
+
+        if !basic.is_empty() {
+            self.pos += 1;
+            self.out.push_str(basic);
+            return Some(());
+        }
+
+        match self.next()? {
+            c @ b'R' | c @ b'Q' => {
+                self.out.push('&');
+                if self.eat(b'L') {
+                    let lt = self.base62()?;
+
+                    if lt != 0 {
+                        self.lifetime(lt)?;
+                        self.out.push(' ');
+                    }
+                }
+                if c == b'Q' {
+                    self.out.push_str("mut ");
+                }
+                self.type_()?;
+            }
+            b'P' => {
+                self.out.push_str("*const ");
+                self.type_()?;
+            }
+            b'O' => {
+                self.out.push_str("*mut ");
+                self.type_()?;
+            }
+            b'A' => {
+                self.out.push('[');
+                self.type_()?;
+                self.out.push_str("; ");
+                self.const_()?;
+                self.out.push(']');
+            }
+            b'S' => {
+                self.out.push('[');
+                self.type_()?;
+                self.out.push(']');
+            }
+            b'T' => {
+                let mut count = 0;
+
+                self.out.push('(');
+                while !self.eat(b'E') {
+                    if count > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.type_()?;
+                    count += 1;
+                }
+                if count == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            b'F' => {
+                let bound = self.binder()?;
+
+                if self.eat(b'U') {
+                    self.out.push_str("unsafe ");
+                }
+                if self.eat(b'K') {
+                    let abi = if self.eat(b'C') { "C".to_string() } else { self.ident()?.replace('_', "-") };
+                    self.out.push_str(&format!("extern \"{}\" ", abi));
+                }
+
+                self.out.push_str("fn(");
+
+                let mut first = true;
+                while !self.eat(b'E') {
+                    if !first {
+                        self.out.push_str(", ");
+                    }
+                    first = false;
+                    self.type_()?;
+                }
+                self.out.push(')');
+
+                if self.eat(b'u') {
+                    // Unit return type is omitted
+                } else {
+                    self.out.push_str(" -> ");
+                    self.type_()?;
+                }
+                self.bound_lifetimes -= bound;
+            }
+            b'D' => {
+                self.out.push_str("dyn ");
+
+                let bound = self.binder()?;
+                let mut first = true;
+
+                while !self.eat(b'E') {
+                    if !first {
+                        self.out.push_str(" + ");
+                    }
+                    first = false;
+                    self.dyn_trait()?;
+                }
+
+                self.bound_lifetimes -= bound;
+                if !self.eat(b'L') {
+                    return None;
+                }
+
+                let lt = self.base62()?;
+                if lt != 0 {
+                    self.out.push_str(" + ");
+                    self.lifetime(lt)?;
+                }
+            }
+            b'B' => return self.backref(|p| p.type_()),
+            _ => {
+                self.pos -= 1;
+                self.path(false)?;
+            }
+        }
+
+        Some(())
+    }
+
+    fn dyn_trait(&mut self) -> Option<()> {
+        self.path(false)?;
+
+        // Associated type bindings go into the trait's generic arguments
+        while self.eat(b'p') {
+            if self.out.ends_with('>') {
+                self.out.pop();
+                self.out.push_str(", ");
+            } else {
+                self.out.push('<');
+            }
+
+            let name = self.ident()?;
+            self.out.push_str(&name);
+            self.out.push_str(" = ");
+            self.type_()?;
+            self.out.push('>');
+        }
+
+        Some(())
+    }
+
+    fn const_(&mut self) -> Option<()> {
+        if self.eat(b'B') {
+            return self.backref(|p| p.const_());
+        }
+        if self.eat(b'p') {
+            self.out.push('_');
+            return Some(());
+        }
+
+        let ty = self.next()?;
+        let neg = self.eat(b'n');
+        let start = self.pos;
+
+        while self.peek()? != b'_' {
+            if !self.peek()?.is_ascii_hexdigit() {
+                return None;
+            }
+            self.pos += 1;
+        }
+
+        let hex = ::std::str::from_utf8(&self.s[start..self.pos]).ok()?.to_string();
+        self.pos += 1;
+
+        let value = if hex.is_empty() { Some(0) } else { u64::from_str_radix(&hex, 16).ok() };
+        let text = match (ty, value) {
+            (b'b', Some(0)) => "false".to_string(),
+            (b'b', Some(1)) => "true".to_string(),
+            (b'c', Some(v)) => format!("{:?}", ::std::char::from_u32(v as u32)?),
+            (b'a', Some(v)) | (b's', Some(v)) | (b'l', Some(v)) | (b'x', Some(v)) | (b'n', Some(v)) | (b'i', Some(v)) => {
+                if neg { format!("-{}", v) } else { v.to_string() }
+            }
+            (b'h', Some(v)) | (b't', Some(v)) | (b'm', Some(v)) | (b'y', Some(v)) | (b'o', Some(v)) | (b'j', Some(v)) => v.to_string(),
+            (b'a', None) | (b's', None) | (b'l', None) | (b'x', None) | (b'n', None) | (b'i', None) | (b'h', None) | (b't', None) |
+            (b'm', None) | (b'y', None) | (b'o', None) | (b'j', None) => format!("{}0x{}", if neg { "-" } else { "" }, hex),
+            _ => return None,
+        };
+
+        self.out.push_str(&text);
+        Some(())
+    }
+}
+
+/// Decodes a Punycode encoded identifier of a Rust v0 name, which uses `_` as delimiter.
+fn punycode_decode(ident: &str) -> Option<String> {
+    let (mut out, encoded) = match ident.rfind('_') {
+        Some(i) => (ident[..i].chars().collect::<Vec<_>>(), &ident[i + 1..]),
+        None => (vec![], ident),
+    };
+    let (base, t_min, t_max, skew, damp) = (36u32, 1u32, 26u32, 38u32, 700u32);
+    let (mut n, mut i, mut bias) = (0x80u32, 0u32, 72u32);
+    let mut chars = encoded.bytes().peekable();
+
+    while chars.peek().is_some() {
+        let old_i = i;
+        let mut w = 1u32;
+        let mut k = base;
+
+        loop {
+            let c = chars.next()?;
+            let digit = match c {
+                b'a'...b'z' => c - b'a',
+                b'0'...b'9' => c - b'0' + 26,
+                _ => return None,
+            } as u32;
+
+            i = i.checked_add(digit.checked_mul(w)?)?;
+
+            let t = if k <= bias {
+                t_min
+            } else if k >= bias + t_max {
+                t_max
+            } else {
+                k - bias
+            };
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(base - t)?;
+            k += base;
+        }
+
+        let len = out.len() as u32 + 1;
+        let mut delta = if old_i == 0 { (i - old_i) / damp } else { (i - old_i) / 2 };
+
+        delta += delta / len;
+        k = 0;
+        while delta > ((base - t_min) * t_max) / 2 {
+            delta /= base - t_min;
+            k += base;
+        }
+        bias = k + ((base - t_min + 1) * delta) / (delta + skew);
+
+        n = n.checked_add(i / len)?;
+        i %= len;
+        out.insert(i as usize, ::std::char::from_u32(n)?);
+        i += 1;
+    }
+
+    Some(out.into_iter().collect())
+}
+
+/// Parser for names mangled by Microsoft Visual C++.
+struct Msvc<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+    /// Names referenced by the digits 0 to 9
+    names: Vec<String>,
+    /// Parameter types referenced by the digits 0 to 9
+    params: Vec<String>,
+}
+
+impl<'a> Msvc<'a> {
+    fn new(s: &'a str) -> Msvc<'a> {
+        Msvc::new_at(s.as_bytes(), 0, 0)
+    }
+
+    fn new_at(s: &'a [u8], pos: usize, depth: usize) -> Msvc<'a> {
+        Msvc { s: s, pos: pos, depth: depth, names: vec![], params: vec![] }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let ret = self.peek();
+        self.pos += 1;
+        ret
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.s[self.pos..].starts_with(prefix)
+    }
+
+    fn enter(&mut self) -> Option<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH { None } else { Some(()) }
+    }
+
+    fn leave<T>(&mut self, ret: Option<T>) -> Option<T> {
+        self.depth -= 1;
+        ret
+    }
+
+    fn symbol(mut self) -> Option<String> {
+        let ret = self.declaration()?;
+
+        if self.pos == self.s.len() { Some(ret) } else { None }
+    }
+
+    fn declaration(&mut self) -> Option<String> {
+        self.enter()?;
+        let ret = self.declaration_inner();
+        self.leave(ret)
+    }
+
+    fn declaration_inner(&mut self) -> Option<String> {
+        if !self.eat(b'?') {
+            return None;
+        }
+        if self.starts_with(b"?_C@") {
+            return Some("`string'".to_string());
+        }
+
+        let (first, special) = self.first_name()?;
+        let scope = self.scope()?;
+        let class = scope.last().cloned().unwrap_or_default();
+        let first = match special {
+            Some(Special::Constructor) => class,
+            Some(Special::Destructor) => format!("~{}", class),
+            _ => first,
+        };
+        let mut name = scope;
+
+        name.push(first);
+
+        let name = name.join("::");
+
+        self.type_of(&name, special == Some(Special::Conversion))
+    }
+
+    /// Parses the name being declared. Returns whether it's special.
+    fn first_name(&mut self) -> Option<(String, Option<Special>)> {
+        if !self.eat(b'?') {
+            return Some((self.simple_name()?, None));
+        }
+        if self.eat(b'$') {
+            return Some((self.template_name()?, None));
+        }
+
+        let op = match self.next()? {
+            b'0' => return Some((String::new(), Some(Special::Constructor))),
+            b'1' => return Some((String::new(), Some(Special::Destructor))),
+            b'B' => return Some((String::new(), Some(Special::Conversion))),
+            b'2' => "operator new",
+            b'3' => "operator delete",
+            b'4' => "operator=",
+            b'5' => "operator>>",
+            b'6' => "operator<<",
+            b'7' => "operator!",
+            b'8' => "operator==",
+            b'9' => "operator!=",
+            b'A' => "operator[]",
+            b'C' => "operator->",
+            b'D' => "operator*",
+            b'E' => "operator++",
+            b'F' => "operator--",
+            b'G' => "operator-",
+            b'H' => "operator+",
+            b'I' => "operator&",
+            b'J' => "operator->*",
+            b'K' => "operator/",
+            b'L' => "operator%",
+            b'M' => "operator<",
+            b'N' => "operator<=",
+            b'O' => "operator>",
+            b'P' => "operator>=",
+            b'Q' => "operator,",
+            b'R' => "operator()",
+            b'S' => "operator~",
+            b'T' => "operator^",
+            b'U' => "operator|",
+            b'V' => "operator&&",
+            b'W' => "operator||",
+            b'X' => "operator*=",
+            b'Y' => "operator+=",
+            b'Z' => "operator-=",
+            b'_' => {
+                match self.next()? {
+                    b'0' => "operator/=",
+                    b'1' => "operator%=",
+                    b'2' => "operator>>=",
+                    b'3' => "operator<<=",
+                    b'4' => "operator&=",
+                    b'5' => "operator|=",
+                    b'6' => "operator^=",
+                    b'7' => "`vftable'",
+                    b'8' => "`vbtable'",
+                    b'9' => "`vcall'",
+                    b'B' => "`local static guard'",
+                    b'D' => "`vbase dtor'",
+                    b'E' => "`vector deleting dtor'",
+                    b'F' => "`default ctor closure'",
+                    b'G' => "`scalar deleting dtor'",
+                    b'H' => "`vector ctor iterator'",
+                    b'I' => "`vector dtor iterator'",
+                    b'U' => "operator new[]",
+                    b'V' => "operator delete[]",
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        Some((op.to_string(), Some(Special::Operator)))
+    }
+
+    fn simple_name(&mut self) -> Option<String> {
+        let start = self.pos;
+
+        while self.peek()? != b'@' {
+            self.pos += 1;
+        }
+
+        let name = String::from_utf8_lossy(&self.s[start..self.pos]).to_string();
+
+        self.pos += 1;
+        self.remember(name.clone());
+        Some(name)
+    }
+
+    fn remember(&mut self, name: String) {
+        if self.names.len() < 10 && !self.names.contains(&name) {
+            self.names.push(name);
+        }
+    }
+
+    fn template_name(&mut self) -> Option<String> {
+        let names = mem::replace(&mut self.names, vec![]);
+        let params = mem::replace(&mut self.params, vec![]);
+        let ret = self.template_name_inner();
+
+        self.names = names;
+        self.params = params;
+
+        let ret = ret?;
+        self.remember(ret.clone());
+        Some(ret)
+    }
+
+    fn template_name_inner(&mut self) -> Option<String> {
+        let name = self.simple_name()?;
+        let mut args = vec![];
+
+        while !self.eat(b'@') {
+            if self.eat(b'$') {
+                match self.next()? {
+                    b'0' => args.push(self.number()?.to_string()),
+                    // Empty parameter packs
+                    b'$' if self.eat(b'V') || self.eat(b'Z') => {}
+                    b'$' => {
+                        self.pos -= 2;
+                        args.push(self.parameter()?);
+                    }
+                    _ => return None,
+                }
+            } else {
+                args.push(self.parameter()?);
+            }
+        }
+
+        Some(format!("{}<{}>", name, args.join(",")))
+    }
+
+    /// Parses the scope of a name, innermost last.
+    fn scope(&mut self) -> Option<Vec<String>> {
+        let mut ret = vec![];
+
+        while !self.eat(b'@') {
+            let name = match self.peek()? {
+                c @ b'0'...b'9' => {
+                    self.pos += 1;
+                    self.names.get((c - b'0') as usize)?.clone()
+                }
+                b'?' if self.starts_with(b"?$") => {
+                    self.pos += 2;
+                    self.template_name()?
+                }
+                b'?' if self.starts_with(b"?A") => {
+                    while self.next()? != b'@' {}
+                    "`anonymous namespace'".to_string()
+                }
+                b'?' if self.starts_with(b"??") => {
+                    // Function containing a local static
+                    let mut inner = Msvc::new_at(self.s, self.pos + 1, self.depth);
+                    let ret = inner.declaration()?;
+
+                    self.pos = inner.pos;
+                    format!("`{}'", ret)
+                }
+                b'?' => {
+                    // Block containing a local static
+                    self.pos += 1;
+                    format!("`{}'", self.number()?)
+                }
+                _ => self.simple_name()?,
+            };
+
+            ret.push(name);
+        }
+
+        ret.reverse();
+        Some(ret)
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let neg = self.eat(b'?');
+        let c = self.next()?;
+        let value = if c.is_ascii_digit() {
+            (c - b'0') as i64 + 1
+        } else {
+            let mut value = 0i64;
+            let mut c = c;
+
+            while c != b'@' {
+                if c < b'A' || c > b'P' {
+                    return None;
+                }
+                value = value.checked_mul(16)?.checked_add((c - b'A') as i64)?;
+                c = self.next()?;
+            }
+            value
+        };
+
+        Some(if neg { -value } else { value })
+    }
+
+    fn type_of(&mut self, name: &str, conversion: bool) -> Option<String> {
+        let c = self.next()?;
+
+        match c {
+            b'0'...b'4' => {
+                let access = match c {
+                    b'0' => "private: static ",
+                    b'1' => "protected: static ",
+                    b'2' => "public: static ",
+                    _ => "",
+                };
+                let pointer = match self.peek() {
+                    Some(b'P') | Some(b'Q') | Some(b'R') | Some(b'S') | Some(b'A') | Some(b'B') => true,
+                    _ => false,
+                };
+                let ty = self.type_()?;
+                let cv = self.storage_class()?;
+                let cv = if pointer { "" } else { cv };
+                let sep = if cv.is_empty() && (ty.ends_with('*') || ty.ends_with('&')) { "" } else { " " };
+
+                Some(format!("{}{}{}{}{}", access, ty, cv, sep, name))
+            }
+            b'6' | b'7' => {
+                let cv = self.storage_class()?;
+                let cv = if cv.starts_with(' ') { &cv[1..] } else { cv };
+
+                // Base class whose table this is
+                if !self.eat(b'@') {
+                    let base = self.scope()?.join("::");
+
+                    self.eat(b'@');
+                    return Some(format!("{} {}{{for `{}'}}", cv, name, base)).map(|s| s.trim().to_string());
+                }
+                Some(format!("{} {}", cv, name).trim().to_string())
+            }
+            b'A'...b'Z' => self.function(c, name, conversion),
+            _ => None,
+        }
+    }
+
+    fn storage_class(&mut self) -> Option<&'static str> {
+        match self.next()? {
+            b'A' => Some(""),
+            b'B' => Some(" const"),
+            b'C' => Some(" volatile"),
+            b'D' => Some(" const volatile"),
+            _ => None,
+        }
+    }
+
+    fn function(&mut self, c: u8, name: &str, conversion: bool) -> Option<String> {
+        let (access, member) = match c {
+            b'A' | b'B' => ("private: ", true),
+            b'C' | b'D' => ("private: static ", false),
+            b'E' | b'F' => ("private: virtual ", true),
+            b'G' | b'H' => ("private: virtual ", true),
+            b'I' | b'J' => ("protected: ", true),
+            b'K' | b'L' => ("protected: static ", false),
+            b'M' | b'N' => ("protected: virtual ", true),
+            b'O' | b'P' => ("protected: virtual ", true),
+            b'Q' | b'R' => ("public: ", true),
+            b'S' | b'T' => ("public: static ", false),
+            b'U' | b'V' => ("public: virtual ", true),
+            b'W' | b'X' => ("public: virtual ", true),
+            _ => ("", false),
+        };
+
+        // Thunks adjust `this` first
+        if let b'G' | b'H' | b'O' | b'P' | b'W' | b'X' = c {
+            self.number()?;
+        }
+
+        let mut qualifiers = "";
+        if member {
+            self.eat(b'E');
+            qualifiers = match self.next()? {
+                b'A' => "",
+                b'B' => " const",
+                b'C' => " volatile",
+                b'D' => " const volatile",
+                _ => return None,
+            };
+        }
+
+        let cc = self.calling_convention()?;
+        let ret = if self.eat(b'@') {
+            None
+        } else {
+            if self.eat(b'?') {
+                self.storage_class()?;
+            }
+            Some(self.type_()?)
+        };
+        let params = self.parameters()?;
+
+        self.eat(b'Z');
+
+        Some(
+            match ret {
+                Some(ref ret) if conversion => format!("{}{} {} {}operator {}({}){}", access, ret, cc, name, ret, params, qualifiers),
+                Some(ret) => format!("{}{} {} {}({}){}", access, ret, cc, name, params, qualifiers),
+                None => format!("{}{} {}({}){}", access, cc, name, params, qualifiers),
+            }
+        )
+    }
+
+    fn calling_convention(&mut self) -> Option<&'static str> {
+        match self.next()? {
+            b'A' | b'B' => Some("__cdecl"),
+            b'C' | b'D' => Some("__pascal"),
+            b'E' | b'F' => Some("__thiscall"),
+            b'G' | b'H' => Some("__stdcall"),
+            b'I' | b'J' => Some("__fastcall"),
+            b'M' | b'N' => Some("__clrcall"),
+            b'O' | b'P' => Some("__eabi"),
+            b'Q' => Some("__vectorcall"),
+            _ => None,
+        }
+    }
+
+    fn parameters(&mut self) -> Option<String> {
+        if self.eat(b'X') {
+            return Some("void".to_string());
+        }
+
+        let mut ret = vec![];
+        loop {
+            if self.eat(b'@') {
+                break;
+            }
+            if self.eat(b'Z') {
+                ret.push("...".to_string());
+                break;
+            }
+            ret.push(self.parameter()?);
+        }
+
+        Some(ret.join(", "))
+    }
+
+    /// Type of a parameter or template argument, which may be a back reference.
+    fn parameter(&mut self) -> Option<String> {
+        if let Some(c @ b'0'...b'9') = self.peek() {
+            self.pos += 1;
+            return self.params.get((c - b'0') as usize).cloned();
+        }
+
+        let start = self.pos;
+        let ty = self.type_()?;
+
+        // Only types encoded with more than one character are remembered
+        if self.pos - start > 1 && self.params.len() < 10 {
+            self.params.push(ty.clone());
+        }
+        Some(ty)
+    }
+
+    fn type_(&mut self) -> Option<String> {
+        self.enter()?;
+        let ret = self.type_inner();
+        self.leave(ret)
+    }
+
+    fn type_inner(&mut self) -> Option<String> {
+        let c = self.next()?;
+        let ret = match c {
+            b'C' => "signed char",
+            b'D' => "char",
+            b'E' => "unsigned char",
+            b'F' => "short",
+            b'G' => "unsigned short",
+            b'H' => "int",
+            b'I' => "unsigned int",
+            b'J' => "long",
+            b'K' => "unsigned long",
+            b'M' => "float",
+            b'N' => "double",
+            b'O' => "long double",
+            b'X' => "void",
+            b'_' => {
+                match self.next()? {
+                    b'D' => "__int8",
+                    b'E' => "unsigned __int8",
+                    b'F' => "__int16",
+                    b'G' => "unsigned __int16",
+                    b'H' => "__int32",
+                    b'I' => "unsigned __int32",
+                    b'J' => "__int64",
+                    b'K' => "unsigned __int64",
+                    b'L' => "__int128",
+                    b'M' => "unsigned __int128",
+                    b'N' => "bool",
+                    b'Q' => "char8_t",
+                    b'S' => "char16_t",
+                    b'U' => "char32_t",
+                    b'W' => "wchar_t",
+                    _ => return None,
+                }
+            }
+            b'T' => return Some(format!("union {}", self.type_name()?)),
+            b'U' => return Some(format!("struct {}", self.type_name()?)),
+            b'V' => return Some(format!("class {}", self.type_name()?)),
+            b'W' => {
+                self.next()?;
+                return Some(format!("enum {}", self.type_name()?));
+            }
+            b'P' => return self.pointer(c, "*"),
+            b'Q' => return self.pointer(c, "*"),
+            b'R' => return self.pointer(c, "*"),
+            b'S' => return self.pointer(c, "*"),
+            b'A' => return self.pointer(c, "&"),
+            b'B' => return self.pointer(c, "&"),
+            b'$' => {
+                if self.eat(b'$') {
+                    match self.next()? {
+                        b'Q' => return self.pointer(b'P', "&&"),
+                        b'T' => "std::nullptr_t",
+                        _ => return None,
+                    }
+                } else {
+                    return None;
+                }
+            }
+            b'?' => {
+                // Qualified type passed by value
+                let cv = self.storage_class()?;
+                return Some(format!("{}{}", self.type_()?, cv));
+            }
+            _ => return None,
+        };
+
+        Some(ret.to_string())
+    }
+
+    /// Qualified name of a class, struct, union or enum.
+    fn type_name(&mut self) -> Option<String> {
+        let first = if self.starts_with(b"?$") {
+            self.pos += 2;
+            self.template_name()?
+        } else if let Some(c @ b'0'...b'9') = self.peek() {
+            self.pos += 1;
+            self.names.get((c - b'0') as usize)?.clone()
+        } else {
+            self.simple_name()?
+        };
+        let mut scope = self.scope()?;
+
+        scope.push(first);
+        Some(scope.join("::"))
+    }
+
+    fn pointer(&mut self, kind: u8, op: &str) -> Option<String> {
+        let own = match kind {
+            b'Q' => " const",
+            b'R' | b'B' => " volatile",
+            b'S' => " const volatile",
+            _ => "",
+        };
+
+        // __ptr64, __restrict and __unaligned
+        while self.eat(b'E') || self.eat(b'I') || self.eat(b'F') {}
+
+        if self.eat(b'6') {
+            let cc = self.calling_convention()?;
+            let ret = if self.eat(b'@') { "void".to_string() } else { self.type_()? };
+            let params = self.parameters()?;
+
+            self.eat(b'Z');
+            return Some(format!("{} ({} {}{})({})", ret, cc, op, own, params));
+        }
+
+        if self.eat(b'8') {
+            let class = self.type_name()?;
+
+            self.eat(b'E');
+
+            let this = self.storage_class()?;
+            let cc = self.calling_convention()?;
+            let ret = if self.eat(b'@') { "void".to_string() } else { self.type_()? };
+            let params = self.parameters()?;
+
+            self.eat(b'Z');
+            return Some(format!("{} ({} {}::{}{})({}){}", ret, cc, class, op, own, params, this));
+        }
+
+        // Pointers carry their own qualifiers
+        let cv = match (self.storage_class()?, self.peek()) {
+            (_, Some(b'P')) | (_, Some(b'Q')) | (_, Some(b'R')) | (_, Some(b'S')) => "",
+            (cv, _) => cv,
+        };
+
+        if self.eat(b'Y') {
+            let dims = self.number()?;
+            let dims = (0..dims).map(|_| self.number().map(|d| format!("[{}]", d))).collect::<Option<Vec<_>>>()?;
+            let ty = self.type_()?;
+
+            return Some(format!("{}{} ({}{}){}", ty, cv, op, own, dims.concat()));
+        }
+
+        let ty = self.type_()?;
+        let sep = if cv.is_empty() && ty.ends_with('*') { "" } else { " " };
+
+        Some(format!("{}{}{}{}{}", ty, cv, sep, op, own.trim()))
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Special {
+    Constructor,
+    Destructor,
+    Conversion,
+    Operator,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(cases: &[(&str, &str)]) {
+        for &(mangled, expected) in cases.iter() {
+            assert_eq!(demangle(mangled).as_ref().map(|s| s.as_str()), Some(expected), "{}", mangled);
+        }
+    }
+
+    #[test]
+    fn itanium() {
+        check(
+            &[
+                ("_Z3foov", "foo()"),
+                ("_Z3fooic", "foo(int, char)"),
+                ("_ZN3foo3barEPKc", "foo::bar(char const*)"),
+                ("_ZNK3Foo3getEv", "Foo::get() const"),
+                ("_ZN3FooC2ERKS_", "Foo::Foo(Foo const&)"),
+                ("_ZN3FooD0Ev", "Foo::~Foo()"),
+                ("_ZN1N1TIiiE2mfES0_IddE", "N::T<int, int>::mf(N::T<double, double>)"),
+                ("_Z1fIiEvT_", "void f<int>(int)"),
+                ("_ZNSt6vectorIiSaIiEE9push_backERKi", "std::vector<int, std::allocator<int> >::push_back(int const&)"),
+                ("_ZNKSt5ctypeIcE8do_widenEc", "std::ctype<char>::do_widen(char) const"),
+                ("_Z1fPFviE", "f(void (*)(int))"),
+                ("_Z1fPA3_i", "f(int (*) [3])"),
+                ("_Z1fM1AFviE", "f(void (A::*)(int))"),
+                ("_ZlsRSoRK3Foo", "operator<<(std::basic_ostream<char, std::char_traits<char> >&, Foo const&)"),
+                ("_ZN12_GLOBAL__N_13fooEv", "(anonymous namespace)::foo()"),
+                ("_ZZ4mainE5count", "main::count"),
+                ("_ZTV3Foo", "vtable for Foo"),
+                ("_ZTI3Foo", "typeinfo for Foo"),
+                ("_ZThn8_N3Foo3barEv", "non-virtual thunk to Foo::bar()"),
+                ("_ZGVZ4mainE1x", "guard variable for main::x"),
+                ("_ZL6helperi", "helper(int)"),
+                ("_Z3fooi.constprop.0", "foo(int) [clone .constprop.0]"),
+                ("_ZNSt7__cxx1112basic_stringIcSt11char_traitsIcESaIcEEC1EPKcRKS3_", "std::__cxx11::basic_string<char, std::char_traits<char>, std::allocator<char> >::basic_string(char const*, std::allocator<char> const&)"),
+                ("_ZN5Outer5InnerC2ERKS0_", "Outer::Inner::Inner(Outer::Inner const&)"),
+                ("_Z1fILi3EEvv", "void f<3>()"),
+                ("_ZN3FoocviEv", "Foo::operator int()"),
+                ("__ZN3foo3barEv", "foo::bar()"),
+                ("_ZN4llvm10make_errorINS_8RawErrorEJRA32_KcEEENS_5ErrorEDpOT0_", "llvm::Error llvm::make_error<llvm::RawError, char const (&) [32]>(char const (&) [32])"),
+                ("_ZZN1A1fIiEEvvENKUlRKiE_clES2_", "A::f<int>()::{lambda(int const&)#1}::operator()(int const&) const"),
+                ("_ZNSt8functionIFbRKiEED2Ev", "std::function<bool (int const&)>::~function()"),
+                ("_ZNSt8ios_base4InitC1Ev@GLIBCXX_3.4", "std::ios_base::Init::Init()@GLIBCXX_3.4"),
+            ]
+        );
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("_Z"), None);
+        assert_eq!(demangle("_ZN3foo"), None);
+    }
+
+    #[test]
+    fn msvc() {
+        check(
+            &[
+                ("?foo@@YAHH@Z", "int __cdecl foo(int)"),
+                ("?f@@YAXPBD@Z", "void __cdecl f(char const *)"),
+                ("?bar@Foo@@QAEHH@Z", "public: int __thiscall Foo::bar(int)"),
+                ("?get@Foo@@QBEHXZ", "public: int __thiscall Foo::get(void) const"),
+                ("??0Foo@@QAE@XZ", "public: __thiscall Foo::Foo(void)"),
+                ("??1Foo@@UAE@XZ", "public: virtual __thiscall Foo::~Foo(void)"),
+                ("??_7Foo@@6B@", "const Foo::`vftable'"),
+                ("?x@@3HA", "int x"),
+                ("?s@Foo@@2PAHA", "public: static int *Foo::s"),
+                ("?f@@YAXV?$vector@H@std@@@Z", "void __cdecl f(class std::vector<int>)"),
+                ("?f@@YAXPAUA@@0@Z", "void __cdecl f(struct A *, struct A *)"),
+                ("?f@@YAXP6AHH@Z@Z", "void __cdecl f(int (__cdecl *)(int))"),
+                ("?f@N@@YAXAEAVC@1@@Z", "void __cdecl N::f(class N::C &)"),
+                ("??4Foo@@QEAAAEAV0@AEBV0@@Z", "public: class Foo & __cdecl Foo::operator=(class Foo const &)"),
+                ("??0?$A@H@@QAE@XZ", "public: __thiscall A<int>::A<int>(void)"),
+                ("?x@?1??f@@YAXXZ@4HA", "int `void __cdecl f(void)'::`2'::x"),
+                ("?f@@YAXP8Foo@@AEXH@Z@Z", "void __cdecl f(void (__thiscall Foo::*)(int))"),
+                ("?f@@YAXAAY02H@Z", "void __cdecl f(int (&)[3])"),
+                ("?x@@3QBHB", "int const *const x"),
+            ]
+        );
+        assert_eq!(demangle("?"), None);
+    }
+
+    #[test]
+    fn rust() {
+        check(
+            &[
+                ("_RNvCs1234_7mycrate3foo", "mycrate::foo"),
+                ("_RNvNtCs1234_7mycrate3bar3baz", "mycrate::bar::baz"),
+                ("_RNvMNtCs1234_7mycrate3barNtB2_3Foo3new", "<mycrate::bar::Foo>::new"),
+                ("_RNvXCs1234_7mycrateNtB2_3FooNtNtCs5678_4core3fmt5Debug3fmt", "<mycrate::Foo as core::fmt::Debug>::fmt"),
+                ("_RINvCs1234_7mycrate3fooRlEB2_", "mycrate::foo::<&i32>"),
+                ("_RNCNvCs1234_7mycrate4main0", "mycrate::main::{closure#0}"),
+                ("_RINvCs1234_7mycrate3fooTlhEEB2_", "mycrate::foo::<(i32, u8)>"),
+                ("_RINvCs1234_7mycrate3fooKj7b_EB2_", "mycrate::foo::<123>"),
+                ("_RINvCs1234_7mycrate3fooFG_RL0_hEuEB2_", "mycrate::foo::<for<'a> fn(&'a u8)>"),
+                ("_RINvCs1234_7mycrate3fooDG_INtNtNtCs1_4core3ops8function2FnTRL0_hEEp6OutputuEL_EB2_", "mycrate::foo::<dyn for<'a> core::ops::function::Fn<(&'a u8,), Output = ()>>"),
+                ("_RNvCs1234_7mycrate3foo.llvm.123", "mycrate::foo"),
+                ("_RNvCs1234_7mycrateu8gdel_5qa", "mycrate::gödel"),
+                ("_ZN7mycrate3foo17h0123456789abcdefE", "mycrate::foo"),
+                ("_ZN4core3ptr13drop_in_place17h0123456789abcdefE", "core::ptr::drop_in_place"),
+                ("_ZN49_$LT$mycrate..Foo$u20$as$u20$core..fmt..Debug$GT$3fmt17h0123456789abcdefE", "<mycrate::Foo as core::fmt::Debug>::fmt"),
+            ]
+        );
+        assert_eq!(demangle("_RNvB_3foo"), None);
+        assert_eq!(demangle("_ZN18446744073709551615fooE"), None);
+        assert_eq!(demangle("_RNvCs1234_18446744073709551615foo"), None);
+    }
+}
@@ -51,8 +51,8 @@
 //! ``10001001`` matches the byte ``0x89``, the pattern ``11.100.0`` matches ``0xd0``
 //! (``11010000``), ``0xd2`` (``11010010``), ``0xf0`` (``11110000``) and ``0xf2`` (``11110010``). Pattern must
 //! have one pattern character for each bit in the token. Patters allow named groups
-//! of bits so called capture groups. These start with a character except ``0``, ``1``,
-//! ``.`` and `` `` (space), followed by a ``@``, followed by a pattern. The capture group
+//! of bits so called capture groups. These start with a letter, optionally followed by
+//! letters, digits and ``_``, followed by a ``@``, followed by a pattern. The capture group
 //! extend until the next space character or the end of the pattern string. The
 //! pattern ``10 a@110 011`` has the capture group named `a` that is always equal to
 //! ``0x6`` (``110``). The pattern ``001 a@.....`` matches all tokens larger than or equal to
@@ -60,6 +60,16 @@
 //! paired with a decode function in the disassembler the function receives the
 //! contents of capture groups a an argument.
 //!
+//! Token Widths
+//! ------------
+//!
+//! A pattern may be shorter than `Architecture::Token` as long as it covers whole bytes. It then
+//! reads only that many bytes, so a disassembler with 16 bit tokens can match an 8 bit prefix
+//! followed by a 16 bit opcode, and one with 32 bit tokens can match 16 bit compressed
+//! instructions. Tokens are read little endian, the short token ends up in the least
+//! significant bits. `State::length` is the number of bytes matched so far, use it instead of
+//! `tokens.len()` to compute the size of an instruction if patterns have different widths.
+//!
 //! Example
 //! -------
 //!
@@ -82,6 +92,39 @@
 //! );
 //! # }
 //! ```
+//!
+//! Overlapping Patterns
+//! --------------------
+//!
+//! If more than one pattern matches, the one with the highest priority wins. Patterns have
+//! priority 0 unless one is given after the pattern list, like `[ "0000 1111" ] : 1 = ...`.
+//! Among patterns of the same priority the one reading the most bytes wins, then the one with
+//! the most fixed bits and finally the one added first. Overlapping patterns that are equal in
+//! priority, length and fixed bits are ambiguous. `Disassembler::conflicts` lists these pairs
+//! and `Disassembler::check` fails with a message naming the first one, for example in a test of
+//! the architecture crate. Sub-disassemblers are only compared by identity.
+//!
+//! Decode Cache
+//! ------------
+//!
+//! `Disassembler::next_match` remembers its last [`DECODE_CACHE_SIZE`] results, keyed by
+//! address and the hash of the configuration, so passes going over the same code again don't
+//! repeat the token matching. A cached result is only used if the bytes the longest pattern could read are still
+//! the same. `Disassembler::cache_stats` returns hit and miss counts,
+//! `Disassembler::set_cache_capacity` changes the size or turns the cache off.
+//!
+//! Tracing
+//! -------
+//!
+//! To find out why an instruction decodes wrongly, call `Disassembler::set_tracing`. Each
+//! `next_match` then bypasses the cache and records a [`Trace`]: the tokens every candidate
+//! pattern matched, the sub-disassemblers it went through, the mnemonics and jumps its semantic
+//! action emitted and whether the action accepted the match. `Disassembler::take_traces` returns
+//! the traces recorded so far. Semantic actions can add their own messages with `State::note`
+//! instead of printing them. Tracing only needs to be enabled on the outermost disassembler.
+//!
+//! [`DECODE_CACHE_SIZE`]: constant.DECODE_CACHE_SIZE.html
+//! [`Trace`]: struct.Trace.html
 
 #![macro_use]
 
@@ -92,11 +135,17 @@ use num::traits::{NumCast, One, Zero};
 use panopticon_graph_algos::{AdjacencyList, EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::AdjacencyListVertexDescriptor;
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 use std::mem::size_of;
 use std::ops::{BitAnd, BitOr, Not, Shl, Shr};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Number of results a `Disassembler` caches unless told otherwise.
+pub const DECODE_CACHE_SIZE: usize = 4096;
 
 /// CPU architecture and instruction set.
 pub trait Architecture: Clone {
@@ -115,6 +164,18 @@ pub trait Architecture: Clone {
 
     /// Start to disassemble a single Opcode inside a given region at a given address.
     fn decode(&Region, u64, &Self::Configuration) -> Result<Match<Self>>;
+
+    /// Name of the instruction set mode `cfg` selects at `address`, e.g. "thumb". Basic blocks
+    /// remember the mode they were decoded in. Architectures with only one mode return None.
+    fn mode(_: &Self::Configuration, _: u64) -> Option<String> {
+        None
+    }
+
+    /// Names of the status flag variables the lifted code sets as a side effect of arithmetic,
+    /// like `CF` and `ZF` on x86. Assignments to them are removed if no instruction reads them.
+    fn flags() -> &'static [&'static str] {
+        &[]
+    }
 }
 
 /// Result of a single disassembly operation.
@@ -127,7 +188,7 @@ pub struct Match<A: Architecture> {
     /// Jumps/branches originating from the recovered mnemonics
     pub jumps: Vec<(u64, Rvalue, Guard)>,
 
-    /// New CPU state
+    /// New CPU state. Successors of the mnemonics are decoded with it.
     pub configuration: A::Configuration,
 }
 
@@ -155,6 +216,8 @@ pub struct State<A: Architecture> {
     pub address: u64,
     /// Matched tokens
     pub tokens: Vec<A::Token>,
+    /// Number of bytes read for `tokens`
+    pub length: usize,
     /// Extracted capture groups
     pub groups: Vec<(String, u64)>,
 
@@ -169,6 +232,9 @@ pub struct State<A: Architecture> {
 
     /// Current CPU state
     pub configuration: A::Configuration,
+
+    /// Decoding steps taken so far, None unless the disassembler is tracing
+    pub trace: Option<Vec<TraceEvent>>,
 }
 
 impl<A: Architecture> State<A> {
@@ -177,12 +243,30 @@ impl<A: Architecture> State<A> {
         State {
             address: a,
             tokens: vec![],
+            length: 0,
             groups: vec![],
             mnemonics: Vec::new(),
             jumps: Vec::new(),
             mnemonic_origin: a,
             jump_origin: a,
             configuration: c,
+            trace: None,
+        }
+    }
+
+    /// True if the disassembler records a `Trace` for this match.
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Adds `msg` to the trace. Does nothing if the disassembler isn't tracing.
+    pub fn note(&mut self, msg: &str) {
+        self.record(TraceEvent::Note(msg.to_string()));
+    }
+
+    fn record(&mut self, ev: TraceEvent) {
+        if let Some(ref mut t) = self.trace {
+            t.push(ev);
         }
     }
 
@@ -226,17 +310,10 @@ impl<A: Architecture> State<A> {
         F: Fn(&mut A::Configuration) -> Result<(Vec<Rvalue>, Vec<Statement>)>,
     {
         let (ops, stmts) = f(&mut self.configuration)?;
+        let ev = TraceEvent::Mnemonic { address: self.mnemonic_origin, opcode: n.to_string() };
 
-        self.mnemonics
-            .push(
-                Mnemonic::new(
-                    self.mnemonic_origin..(self.mnemonic_origin + (len as u64)),
-                    n.to_string(),
-                    fmt.to_string(),
-                    ops.iter(),
-                    stmts.iter(),
-                )?
-            );
+        self.record(ev);
+        self.mnemonics.push(Mnemonic::from_parts(self.mnemonic_origin..(self.mnemonic_origin + (len as u64)), n.to_string(), fmt, ops, stmts)?);
         self.jump_origin = self.mnemonic_origin;
         self.mnemonic_origin += len as u64;
 
@@ -257,6 +334,7 @@ impl<A: Architecture> State<A> {
 
     /// Append a jump/branch from `origin` to `v`, guarded by `g`.
     pub fn jump_from(&mut self, origin: u64, v: Rvalue, g: Guard) -> Result<()> {
+        self.record(TraceEvent::Jump { origin: origin, target: v.clone(), guard: g.clone() });
         self.jumps.push((origin, v, g));
         Ok(())
     }
@@ -273,6 +351,8 @@ pub enum Rule<A: Architecture> {
         pattern: A::Token,
         /// Pair of capture group name and bit mask
         capture_group: Vec<(String, A::Token)>,
+        /// Number of bytes read, at most the size of `A::Token`
+        width: usize,
     },
     /// Matches one of the sub-disassemblers' rules
     Sub(Arc<Disassembler<A>>),
@@ -281,14 +361,189 @@ pub enum Rule<A: Architecture> {
 impl<A: Architecture> PartialEq for Rule<A> {
     fn eq(&self, other: &Rule<A>) -> bool {
         match (self, other) {
-            (&Rule::Terminal { mask: ref ma, pattern: ref pa, capture_group: ref ca },
-             &Rule::Terminal { mask: ref mb, pattern: ref pb, capture_group: ref cb }) => ma == mb && pa == pb && ca == cb,
+            (&Rule::Terminal { mask: ref ma, pattern: ref pa, capture_group: ref ca, width: wa },
+             &Rule::Terminal { mask: ref mb, pattern: ref pb, capture_group: ref cb, width: wb }) => ma == mb && pa == pb && ca == cb && wa == wb,
             (&Rule::Sub(ref a), &Rule::Sub(ref b)) => a.as_ref() as *const Disassembler<A> as usize == b.as_ref() as *const Disassembler<A> as usize,
             _ => false,
         }
     }
 }
 
+/// Counters of the decode cache of a `Disassembler`.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub struct DecodeCacheStats {
+    /// Calls of `next_match` answered from the cache
+    pub hits: u64,
+    /// Calls of `next_match` that had to match tokens
+    pub misses: u64,
+    /// Results dropped to make room for new ones
+    pub evictions: u64,
+    /// Results cached at the moment
+    pub entries: usize,
+    /// Maximal number of results cached
+    pub capacity: usize,
+}
+
+struct CachedMatch<A: Architecture> {
+    cells: Vec<Option<u8>>,
+    state: Option<State<A>>,
+    used: u64,
+}
+
+struct DecodeCache<A: Architecture> {
+    // by address and hash of the configuration
+    entries: HashMap<(u64, u64), CachedMatch<A>>,
+    stats: DecodeCacheStats,
+    clock: u64,
+    // bytes read by the longest pattern, computed on first use
+    depth: Option<usize>,
+}
+
+impl<A: Architecture> DecodeCache<A> {
+    fn new(capacity: usize) -> DecodeCache<A> {
+        DecodeCache {
+            entries: HashMap::new(),
+            stats: DecodeCacheStats { capacity: capacity, ..DecodeCacheStats::default() },
+            clock: 0,
+            depth: None,
+        }
+    }
+
+    fn insert(&mut self, key: (u64, u64), cells: Vec<Option<u8>>, state: Option<State<A>>) {
+        if self.stats.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() >= self.stats.capacity && !self.entries.contains_key(&key) {
+            // drop the least recently used half at once to keep insertion cheap
+            let mut used = self.entries.values().map(|e| e.used).collect::<Vec<_>>();
+
+            used.sort();
+
+            let cutoff = used[(used.len() - 1) / 2];
+            let before = self.entries.len();
+
+            self.entries.retain(|_, e| e.used > cutoff);
+            self.stats.evictions += (before - self.entries.len()) as u64;
+        }
+
+        self.clock += 1;
+        self.entries.insert(key, CachedMatch { cells: cells, state: state, used: self.clock });
+        self.stats.entries = self.entries.len();
+    }
+}
+
+/// Single step of decoding a token sequence, see `Trace`.
+#[derive(Clone,Debug,PartialEq)]
+pub enum TraceEvent {
+    /// A token pattern matched `token`, `width` bytes long
+    Token {
+        /// Bits the pattern fixes, `.` for the others
+        pattern: String,
+        /// Token read
+        token: u64,
+        /// Number of bytes read
+        width: usize,
+    },
+    /// Matching continued in a sub-disassembler
+    Sub,
+    /// Semantic action of a complete pattern ran
+    Action {
+        /// Priority of the pattern
+        priority: i32,
+        /// Return value of the action
+        accepted: bool,
+    },
+    /// The default action ran because no pattern matched
+    Default {
+        /// Return value of the action
+        accepted: bool,
+    },
+    /// The semantic action appended a mnemonic
+    Mnemonic {
+        /// Start of the mnemonic
+        address: u64,
+        /// Opcode
+        opcode: String,
+    },
+    /// The semantic action appended a jump
+    Jump {
+        /// Address the jump starts at
+        origin: u64,
+        /// Jump target
+        target: Rvalue,
+        /// Condition
+        guard: Guard,
+    },
+    /// Message added by the semantic action with `State::note`
+    Note(String),
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &TraceEvent::Token { ref pattern, token, width } => write!(f, "token 0x{:0w$x} matches \"{}\"", token, pattern, w = width * 2),
+            &TraceEvent::Sub => write!(f, "enter sub-disassembler"),
+            &TraceEvent::Action { priority, accepted } => write!(f, "action (priority {}) {}", priority, if accepted { "accepted" } else { "rejected" }),
+            &TraceEvent::Default { accepted } => write!(f, "default action {}", if accepted { "accepted" } else { "rejected" }),
+            &TraceEvent::Mnemonic { address, ref opcode } => write!(f, "mnemonic {} at 0x{:x}", opcode, address),
+            &TraceEvent::Jump { origin, ref target, ref guard } => write!(f, "jump from 0x{:x} to {} if {}", origin, target, guard),
+            &TraceEvent::Note(ref msg) => write!(f, "note: {}", msg),
+        }
+    }
+}
+
+/// Record of a single `Disassembler::next_match` call made while tracing.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Trace {
+    /// Start of the token sequence
+    pub address: u64,
+    /// Steps leading to each semantic action that ran, accepted or not, in the order they ran
+    pub candidates: Vec<Vec<TraceEvent>>,
+    /// Steps of the match returned, None if nothing matched
+    pub result: Option<Vec<TraceEvent>>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "decode at 0x{:x}: {} candidates", self.address, self.candidates.len())?;
+        for (i, cand) in self.candidates.iter().enumerate() {
+            writeln!(f, "  candidate {}", i)?;
+            for ev in cand.iter() {
+                writeln!(f, "    {}", ev)?;
+            }
+        }
+        match self.result {
+            Some(ref res) => {
+                writeln!(f, "  result")?;
+                for ev in res.iter() {
+                    writeln!(f, "    {}", ev)?;
+                }
+                Ok(())
+            }
+            None => writeln!(f, "  no match"),
+        }
+    }
+}
+
+/// Two patterns of a `Disassembler` that match the same tokens with nothing deciding between
+/// them, see `Disassembler::conflicts`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Conflict {
+    /// First pattern, one string per token
+    pub first: Vec<String>,
+    /// Second pattern
+    pub second: Vec<String>,
+    /// Priority of both
+    pub priority: i32,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "patterns [{}] and [{}] overlap and both have priority {}", self.first.join(", "), self.second.join(", "), self.priority)
+    }
+}
+
 /// Ready made disassembler for simple instruction sets.
 ///
 /// Disassembler instances are creates using the `new_disassembler!` macro. The resulting
@@ -296,8 +551,12 @@ impl<A: Architecture> PartialEq for Rule<A> {
 pub struct Disassembler<A: Architecture> {
     graph: AdjacencyList<(), Rule<A>>,
     start: AdjacencyListVertexDescriptor,
-    end: HashMap<AdjacencyListVertexDescriptor, Arc<Action<A>>>,
+    // semantic actions and their priority, highest first
+    end: HashMap<AdjacencyListVertexDescriptor, Vec<(Arc<Action<A>>, i32)>>,
     default: Option<Action<A>>,
+    cache: Mutex<DecodeCache<A>>,
+    tracing: AtomicBool,
+    traces: Mutex<Vec<Trace>>,
 }
 
 impl<A: Architecture> Disassembler<A> {
@@ -307,7 +566,75 @@ impl<A: Architecture> Disassembler<A> {
         let mut g = AdjacencyList::new();
         let s = g.add_vertex(());
 
-        Disassembler { graph: g, start: s, end: HashMap::new(), default: None }
+        Disassembler {
+            graph: g,
+            start: s,
+            end: HashMap::new(),
+            default: None,
+            cache: Mutex::new(DecodeCache::new(DECODE_CACHE_SIZE)),
+            tracing: AtomicBool::new(false),
+            traces: Mutex::new(vec![]),
+        }
+    }
+
+    /// Starts or stops recording a `Trace` for each call of `next_match`. The cache is not used
+    /// while tracing.
+    pub fn set_tracing(&self, on: bool) {
+        self.tracing.store(on, Ordering::SeqCst);
+    }
+
+    /// True if `set_tracing` turned tracing on.
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.load(Ordering::SeqCst)
+    }
+
+    /// Returns and forgets the traces recorded so far, oldest first.
+    pub fn take_traces(&self) -> Vec<Trace> {
+        self.traces.lock().map(|mut t| t.drain(..).collect()).unwrap_or_default()
+    }
+
+    /// Hit and miss counts of the decode cache.
+    pub fn cache_stats(&self) -> DecodeCacheStats {
+        self.cache.lock().map(|c| c.stats).unwrap_or_default()
+    }
+
+    /// Empties the decode cache and sets the number of results it holds to `capacity`. Zero turns
+    /// caching off.
+    pub fn set_cache_capacity(&self, capacity: usize) {
+        if let Ok(mut c) = self.cache.lock() {
+            *c = DecodeCache::new(capacity);
+        }
+    }
+
+    /// Forgets all cached results, keeping the counters.
+    pub fn clear_cache(&self) {
+        if let Ok(mut c) = self.cache.lock() {
+            c.entries.clear();
+            c.stats.entries = 0;
+        }
+    }
+
+    /// Number of tokens the longest pattern reads.
+    fn max_tokens(&self) -> usize {
+        self.longest(self.start)
+    }
+
+    fn longest(&self, v: AdjacencyListVertexDescriptor) -> usize {
+        self.graph
+            .out_edges(v)
+            .map(
+                |e| {
+                    let len = match self.graph.edge_label(e) {
+                        Some(&Rule::Terminal { .. }) => 1,
+                        Some(&Rule::Sub(ref sub)) => sub.max_tokens(),
+                        None => 0,
+                    };
+
+                    len + self.longest(self.graph.target(e))
+                }
+            )
+            .max()
+            .unwrap_or(0)
     }
     /// Converts to a dot file; useful for debugging
     pub fn to_dot(&self) {
@@ -345,6 +672,12 @@ impl<A: Architecture> Disassembler<A> {
     /// Adds the matching rule and associated semantic action.
     /// Panics if a is empty.
     pub fn add(&mut self, a: &Vec<Rule<A>>, b: Arc<Action<A>>) {
+        self.add_with_priority(a, b, 0)
+    }
+
+    /// Adds the matching rule and associated semantic action. Rules with higher `priority` are
+    /// preferred if more than one matches. Panics if a is empty.
+    pub fn add_with_priority(&mut self, a: &Vec<Rule<A>>, b: Arc<Action<A>>, priority: i32) {
         assert!(!a.is_empty());
 
         let mut v = self.start;
@@ -368,7 +701,129 @@ impl<A: Architecture> Disassembler<A> {
             }
         }
 
-        self.end.insert(v, b);
+        let acts = self.end.entry(v).or_insert_with(Vec::new);
+        let pos = acts.iter().position(|&(_, p)| p < priority).unwrap_or(acts.len());
+
+        acts.insert(pos, (b, priority));
+        if let Ok(c) = self.cache.get_mut() {
+            c.entries.clear();
+            c.depth = None;
+        }
+    }
+
+    /// Pairs of overlapping patterns that have the same priority, length and number of fixed
+    /// bits. Empty if every token sequence has a single best match.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut paths = vec![];
+        let mut groups = HashMap::<(usize, i32, u32), Vec<usize>>::new();
+        let mut ret = vec![];
+
+        self.paths(self.start, &mut vec![], &mut paths);
+        for (i, &(ref rules, prio)) in paths.iter().enumerate() {
+            let bits = rules.iter().map(|r| Self::fixed_bits(r)).sum::<u32>();
+            let bytes = rules.iter().map(|r| Self::width(r)).sum::<usize>();
+            groups.entry((bytes, prio, bits)).or_insert_with(Vec::new).push(i);
+        }
+
+        let mut keys = groups.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            let idx = &groups[&key];
+
+            for (n, &i) in idx.iter().enumerate() {
+                for &j in idx[n + 1..].iter() {
+                    let (ref a, prio) = paths[i];
+                    let (ref b, _) = paths[j];
+
+                    if a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| Self::overlap(x, y)) {
+                        ret.push(
+                            Conflict {
+                                first: a.iter().map(|r| Self::describe(r)).collect(),
+                                second: b.iter().map(|r| Self::describe(r)).collect(),
+                                priority: prio,
+                            }
+                        );
+                    }
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Fails with a description of the first ambiguous pair of patterns, see `conflicts`.
+    pub fn check(&self) -> Result<()> {
+        match self.conflicts().first() {
+            Some(c) => Err(format!("ambiguous disassembler: {}", c).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// All rule sequences from `v` to an accepting vertex, once per semantic action.
+    fn paths<'a>(&'a self, v: AdjacencyListVertexDescriptor, prefix: &mut Vec<&'a Rule<A>>, ret: &mut Vec<(Vec<&'a Rule<A>>, i32)>) {
+        if let Some(acts) = self.end.get(&v) {
+            ret.extend(acts.iter().map(|&(_, p)| (prefix.clone(), p)));
+        }
+
+        for e in self.graph.out_edges(v) {
+            if let Some(r) = self.graph.edge_label(e) {
+                prefix.push(r);
+                self.paths(self.graph.target(e), prefix, ret);
+                prefix.pop();
+            }
+        }
+    }
+
+    /// Bytes read by a terminal, zero for sub-disassemblers.
+    fn width(r: &Rule<A>) -> usize {
+        match r {
+            &Rule::Terminal { width, .. } => width,
+            &Rule::Sub(_) => 0,
+        }
+    }
+
+    fn fixed_bits(r: &Rule<A>) -> u32 {
+        match r {
+            &Rule::Terminal { ref mask, .. } => <u64 as NumCast>::from(mask.clone()).map(|m| m.count_ones()).unwrap_or(0),
+            &Rule::Sub(_) => 0,
+        }
+    }
+
+    /// True if a token exists that both rules match. Different sub-disassemblers are assumed to
+    /// be disjoint.
+    fn overlap(a: &Rule<A>, b: &Rule<A>) -> bool {
+        match (a, b) {
+            (&Rule::Terminal { mask: ref ma, pattern: ref pa, .. }, &Rule::Terminal { mask: ref mb, pattern: ref pb, .. }) => {
+                pa.clone() & mb.clone() == pb.clone() & ma.clone()
+            }
+            (&Rule::Sub(_), &Rule::Sub(_)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Renders a rule in token pattern syntax.
+    fn describe(r: &Rule<A>) -> String {
+        match r {
+            &Rule::Terminal { ref mask, ref pattern, width, .. } => {
+                (0..width * 8)
+                    .rev()
+                    .map(
+                        |bit| {
+                            let b = A::Token::one() << bit;
+
+                            if mask.clone() & b.clone() == A::Token::zero() {
+                                '.'
+                            } else if pattern.clone() & b == A::Token::zero() {
+                                '0'
+                            } else {
+                                '1'
+                            }
+                        }
+                    )
+                    .collect()
+            }
+            &Rule::Sub(_) => "<sub>".to_string(),
+        }
     }
 
     /// Sets the default semantic action. This action will be called for each token that failed to
@@ -378,55 +833,139 @@ impl<A: Architecture> Disassembler<A> {
     }
 
     /// Trys to match the token sequence `i`. If successful, the state after the semantic function
-    /// was called is returned and None otherwise.
+    /// was called is returned and None otherwise. Results are cached, see the module
+    /// documentation.
     pub fn next_match<Iter>(&self, i: &mut Iter, offset: u64, cfg: A::Configuration) -> Option<State<A>>
+    where
+        Iter: Iterator<Item = Option<u8>> + Clone,
+        A::Configuration: Clone + Debug + Hash,
+        A: Debug,
+    {
+        if self.is_tracing() {
+            return self.match_tokens(i, offset, cfg);
+        }
+
+        let depth = match self.cache.lock() {
+            Ok(ref mut c) if c.stats.capacity > 0 => {
+                if c.depth.is_none() {
+                    c.depth = Some(::std::cmp::max(self.max_tokens(), 1) * size_of::<A::Token>());
+                }
+                c.depth
+            }
+            _ => None,
+        };
+        let depth = match depth {
+            Some(d) => d,
+            None => return self.match_tokens(i, offset, cfg),
+        };
+        let key = {
+            let mut h = DefaultHasher::new();
+            cfg.hash(&mut h);
+            (offset, h.finish())
+        };
+        let cells = i.clone().take(depth).collect::<Vec<_>>();
+
+        if let Ok(mut c) = self.cache.lock() {
+            let clock = c.clock + 1;
+            let hit = match c.entries.get_mut(&key) {
+                Some(ref mut e) if e.cells == cells => {
+                    e.used = clock;
+                    Some(e.state.clone())
+                }
+                _ => None,
+            };
+
+            if let Some(state) = hit {
+                c.clock = clock;
+                c.stats.hits += 1;
+                return state;
+            }
+            c.stats.misses += 1;
+        }
+
+        let ret = self.match_tokens(i, offset, cfg);
+
+        if let Ok(mut c) = self.cache.lock() {
+            c.insert(key, cells, ret.clone());
+        }
+
+        ret
+    }
+
+    fn match_tokens<Iter>(&self, i: &mut Iter, offset: u64, cfg: A::Configuration) -> Option<State<A>>
     where
         Iter: Iterator<Item = Option<u8>> + Clone,
         A::Configuration: Clone + Debug,
         A: Debug,
     {
-        let mut matches = self.find(i.clone(), &State::<A>::new(offset, cfg.clone()));
-        let l = matches.len();
+        let tracing = self.is_tracing();
+        let mut initial = State::<A>::new(offset, cfg.clone());
+        let mut candidates = vec![];
+
+        if tracing {
+            initial.trace = Some(vec![]);
+        }
 
-        match l {
+        let mut matches = self.find(i.clone(), &initial, &mut candidates);
+        let l = matches.len();
+        let ret = match l {
             0 => {
+                let mut ret = None;
+
                 if let Some(ref def) = self.default {
-                    let mut state = State::<A>::new(offset, cfg);
+                    let mut state = initial;
                     let mut iter = i.clone();
-                    if let Some(tok) = Self::read_token(&mut iter) {
+                    if let Some(tok) = Self::read_token(&mut iter, size_of::<A::Token>()) {
                         state.tokens.push(tok);
+                        state.length = size_of::<A::Token>();
 
-                        if def(&mut state) {
-                            return Some(state);
+                        let accepted = def(&mut state);
+
+                        state.record(TraceEvent::Default { accepted: accepted });
+                        if accepted {
+                            ret = Some(state);
+                        } else if let Some(t) = state.trace {
+                            candidates.push(t);
                         }
                     }
                 }
 
-                None
+                ret
             }
-            1 => Some(matches[0].clone().1),
+            1 => Some(matches[0].clone().2),
             _ => {
-                // return longest match
-                matches.sort_by(|b, a| a.1.tokens.len().cmp(&b.1.tokens.len()));
-                Some(matches[0].clone().1)
+                // highest priority, then longest match, then most fixed bits, then the first added
+                matches.sort_by(|b, a| (a.0, a.2.length, a.1).cmp(&(b.0, b.2.length, b.1)));
+                Some(matches[0].clone().2)
+            }
+        };
+
+        if tracing {
+            let trace = Trace { address: offset, candidates: candidates, result: ret.as_ref().and_then(|st| st.trace.clone()) };
+
+            if let Ok(mut t) = self.traces.lock() {
+                t.push(trace);
             }
         }
+
+        ret
     }
 
-    fn read_token<Iter>(i: &mut Iter) -> Option<A::Token>
+    /// Reads a `width` bytes long token.
+    fn read_token<Iter>(i: &mut Iter, width: usize) -> Option<A::Token>
     where
         Iter: Iterator<Item = Option<u8>>,
     {
         let mut tok = A::Token::zero();
         // XXX: Hardcoded to little endian for AVR. Make configurable in Architecture trait
         let cells = {
-            let mut x = i.take(size_of::<A::Token>()).collect::<Vec<_>>();
+            let mut x = i.take(width).collect::<Vec<_>>();
             x.reverse();
             x
         };
         let mut j = cells.iter();
 
-        for _ in 0..size_of::<A::Token>() {
+        for _ in 0..width {
             if tok != A::Token::zero() {
                 tok = tok << 8;
             }
@@ -440,37 +979,45 @@ impl<A: Architecture> Disassembler<A> {
         Some(tok)
     }
 
-    fn find<Iter>(&self, i: Iter, initial_state: &State<A>) -> Vec<(Vec<&()>, State<A>, Iter)>
+    /// Returns priority, number of fixed bits, final state and remaining tokens of each match. If
+    /// `initial_state` is tracing the trace of every semantic action run is added to `candidates`.
+    fn find<Iter>(&self, i: Iter, initial_state: &State<A>, candidates: &mut Vec<Vec<TraceEvent>>) -> Vec<(i32, u32, State<A>, Iter)>
     where
         Iter: Iterator<Item = Option<u8>> + Clone,
         A::Configuration: Clone,
     {
-        let mut states = Vec::<(Vec<&()>, State<A>, AdjacencyListVertexDescriptor, Iter)>::new();
+        let mut states = Vec::<(u32, State<A>, AdjacencyListVertexDescriptor, Iter)>::new();
         let mut ret = vec![];
 
-        states.push((vec![], initial_state.clone(), self.start, i.clone()));
+        states.push((0, initial_state.clone(), self.start, i.clone()));
         while !states.is_empty() {
-            for &(ref pats, ref state, ref v, ref iter) in states.iter() {
-                if let Some(act) = self.end.get(v) {
+            for &(bits, ref state, ref v, ref iter) in states.iter() {
+                for &(ref act, prio) in self.end.get(v).map(|a| &a[..]).unwrap_or(&[]) {
                     let mut st = state.clone();
-                    if act(&mut st) {
-                        ret.push((pats.clone(), st, iter.clone()));
+                    let accepted = act(&mut st);
+
+                    st.record(TraceEvent::Action { priority: prio, accepted: accepted });
+                    if let Some(ref t) = st.trace {
+                        candidates.push(t.clone());
+                    }
+                    if accepted {
+                        ret.push((prio, bits, st, iter.clone()));
+                        break;
                     }
                 }
             }
 
-            let mut new_states = Vec::<(Vec<&()>, State<A>, AdjacencyListVertexDescriptor, Iter)>::new();
+            let mut new_states = Vec::<(u32, State<A>, AdjacencyListVertexDescriptor, Iter)>::new();
 
 
-            for &(ref pats, ref state, ref vx, ref iter) in states.iter() {
-                if let Some(a) = self.graph.vertex_label(*vx) {
+            for &(bits, ref state, ref vx, ref iter) in states.iter() {
+                if self.graph.vertex_label(*vx).is_some() {
                     for e in self.graph.out_edges(*vx) {
                         match self.graph.edge_label(e) {
-                            Some(&Rule::Terminal { ref mask, ref pattern, capture_group: ref capture }) => {
+                            Some(&Rule::Terminal { ref mask, ref pattern, capture_group: ref capture, width }) => {
                                 let mut i = iter.clone();
-                                if let Some(tok) = Self::read_token(&mut i) {
+                                if let Some(tok) = Self::read_token(&mut i, width) {
                                     if mask.clone() & tok.clone() == *pattern {
-                                        let mut p = pats.clone();
                                         let mut st = state.clone();
 
                                         // capture group
@@ -481,8 +1028,8 @@ impl<A: Architecture> Disassembler<A> {
                                                 0u64
                                             };
 
-                                            for rbit in 0..(size_of::<A::Token>() * 8) {
-                                                let bit = (size_of::<A::Token>() * 8) - rbit - 1;
+                                            for rbit in 0..(width * 8) {
+                                                let bit = (width * 8) - rbit - 1;
                                                 let bit_mask = if bit > 0 {
                                                     A::Token::one() << bit
                                                 } else {
@@ -507,17 +1054,30 @@ impl<A: Architecture> Disassembler<A> {
                                             }
                                         }
 
-                                        p.push(a);
+                                        if st.is_tracing() {
+                                            let ev = TraceEvent::Token {
+                                                pattern: Self::describe(self.graph.edge_label(e).unwrap()),
+                                                token: <u64 as NumCast>::from(tok.clone()).unwrap_or(0),
+                                                width: width,
+                                            };
+
+                                            st.record(ev);
+                                        }
                                         st.tokens.push(tok);
-                                        new_states.push((p, st, self.graph.target(e), i));
+                                        st.length += width;
+                                        new_states.push((bits + Self::fixed_bits(self.graph.edge_label(e).unwrap()), st, self.graph.target(e), i));
                                     }
                                 }
                             }
                             Some(&Rule::Sub(ref sub)) => {
                                 let i = iter.clone();
-                                let mut v = sub.find(i.clone(), state);
+                                let mut st = state.clone();
+
+                                st.record(TraceEvent::Sub);
 
-                                new_states.extend(v.drain(..).map(|(a, b, i)| (a, b, self.graph.target(e), i.clone())));
+                                let mut v = sub.find(i.clone(), &st, candidates);
+
+                                new_states.extend(v.drain(..).map(|(_, b, st, i)| (bits + b, st, self.graph.target(e), i.clone())));
                             }
                             None => {}
                         };
@@ -538,12 +1098,20 @@ impl<A: Architecture> Debug for Disassembler<A> {
     }
 }
 
+// like sub-disassemblers, disassemblers used as configuration are told apart by identity
+impl<A: Architecture> Hash for Disassembler<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self as *const Disassembler<A> as usize).hash(state);
+    }
+}
+
 impl<A: Architecture> Into<Rule<A>> for usize {
     fn into(self) -> Rule<A> {
         Rule::Terminal {
             mask: !A::Token::zero(),
             pattern: <A::Token as NumCast>::from(self).unwrap(),
             capture_group: vec![],
+            width: size_of::<A::Token>(),
         }
     }
 }
@@ -587,6 +1155,9 @@ impl<'a, A: Architecture> Into<Rule<A>> for &'a str {
                     read_pat = false;
                     cur_group = "".to_string();
                 }
+                '0'...'9' | '_' if !read_pat && cur_group != "" => {
+                    cur_group.push(c);
+                }
                 '.' => {
                     if bit <= 0 {
                         panic!("too long bit pattern: '{}'", self);
@@ -639,23 +1210,29 @@ impl<'a, A: Architecture> Into<Rule<A>> for &'a str {
             }
         }
 
-        if bit != 0 {
+        // patterns shorter than a token read fewer bytes and match the least significant bits
+        let len = size_of::<A::Token>() * 8 - bit as usize;
+
+        if len == 0 || len % 8 != 0 {
             panic!("Pattern syntax error: invalid pattern length in '{}'", self);
         }
 
+        let shift = bit as usize;
+
         Rule::Terminal {
-            pattern: pat,
-            mask: mask,
+            pattern: if shift > 0 { pat >> shift } else { pat },
+            mask: if shift > 0 { mask >> shift } else { mask },
             capture_group: groups
                 .iter()
                 .filter_map(
                     |x| if *x.1 != A::Token::zero() {
-                        Some((x.0.clone(), x.1.clone()))
+                        Some((x.0.clone(), if shift > 0 { x.1.clone() >> shift } else { x.1.clone() }))
                     } else {
                         None
                     }
                 )
                 .collect(),
+            width: len / 8,
         }
     }
 }
@@ -715,7 +1292,7 @@ macro_rules! opt {
 
 #[macro_export]
 macro_rules! new_disassembler {
-    ($ty:ty => $( [ $( $t:expr ),+ ] = $f:expr),+) => {
+    ($ty:ty => $( [ $( $t:expr ),+ ] $( : $prio:tt )* = $f:expr),+) => {
         {
             let mut dis = $crate::disassembler::Disassembler::<$ty>::new();
             $({
@@ -727,14 +1304,14 @@ macro_rules! new_disassembler {
                 let fuc: $crate::disassembler::Action<$ty> = a;
 
                 for r in gen.rules {
-                    dis.add(&r,::std::sync::Arc::new(fuc));
+                    dis.add_with_priority(&r,::std::sync::Arc::new(fuc),0 $( + $prio )*);
                 }
             })+
 
             ::std::sync::Arc::<$crate::disassembler::Disassembler<$ty>>::new(dis)
         }
     };
-    ($ty:ty => $( [ $( $t:expr ),+ ] = $f:expr),+, _ = $def:expr) => {
+    ($ty:ty => $( [ $( $t:expr ),+ ] $( : $prio:tt )* = $f:expr),+, _ = $def:expr) => {
         {
            let mut dis = $crate::disassembler::Disassembler::<$ty>::new();
             $({
@@ -746,7 +1323,7 @@ macro_rules! new_disassembler {
                 let fuc: $crate::disassembler::Action<$ty> = a;
 
                 for r in gen.rules {
-                    dis.add(&r,::std::sync::Arc::new(fuc));
+                    dis.add_with_priority(&r,::std::sync::Arc::new(fuc),0 $( + $prio )*);
                 }
             })+
 
@@ -842,6 +1419,146 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decode_cache() {
+        let main = new_disassembler!(TestArchShort =>
+            [ 1 ] = &|_| { true },
+            [ 1, 2 ] = &|_| { true }
+        );
+        let src = OpaqueLayer::wrap(vec![1, 2, 1, 3]);
+        let patched = OpaqueLayer::wrap(vec![1, 3, 1, 3]);
+
+        assert_eq!(main.next_match(&mut src.iter(), 0, ()).map(|s| s.tokens), Some(vec![1, 2]));
+        assert_eq!(main.next_match(&mut src.iter(), 0, ()).map(|s| s.tokens), Some(vec![1, 2]));
+        assert_eq!(main.next_match(&mut patched.iter(), 0, ()).map(|s| s.tokens), Some(vec![1]));
+        assert!(main.next_match(&mut src.iter().seek(3), 3, ()).is_none());
+        assert!(main.next_match(&mut src.iter().seek(3), 3, ()).is_none());
+
+        let stats = main.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries, stats.capacity), (2, 3, 2, DECODE_CACHE_SIZE));
+
+        main.set_cache_capacity(2);
+        for a in 0..4 {
+            main.next_match(&mut src.iter().seek(a), a, ());
+        }
+        assert_eq!((main.cache_stats().evictions, main.cache_stats().entries), (2, 2));
+
+        main.set_cache_capacity(0);
+        main.next_match(&mut src.iter(), 0, ());
+        assert_eq!(main.cache_stats(), DecodeCacheStats::default());
+    }
+
+    #[test]
+    fn tracing() {
+        let sub = new_disassembler!(TestArchShort =>
+            [ 2 ] = &|st: &mut State<TestArchShort>| {
+                st.note("two");
+                true
+            }
+        );
+        let main = new_disassembler!(TestArchShort =>
+            [ 1, sub ] = &|st: &mut State<TestArchShort>| {
+                st.mnemonic(2, "a", "", vec![], &|_| Ok(vec![])).unwrap();
+                true
+            },
+            [ 1 ] = &|_| { false }
+        );
+        let src = OpaqueLayer::wrap(vec![1, 2, 3]);
+
+        assert!(main.next_match(&mut src.iter(), 0, ()).unwrap().trace.is_none());
+        assert!(main.take_traces().is_empty());
+
+        main.set_tracing(true);
+        assert!(main.next_match(&mut src.iter(), 0, ()).is_some());
+        assert!(main.next_match(&mut src.iter().seek(2), 2, ()).is_none());
+        main.set_tracing(false);
+
+        let traces = main.take_traces();
+        let one = TraceEvent::Token { pattern: "00000001".to_string(), token: 1, width: 1 };
+        let two = TraceEvent::Token { pattern: "00000010".to_string(), token: 2, width: 1 };
+        let result = vec![
+            one.clone(),
+            TraceEvent::Sub,
+            two.clone(),
+            TraceEvent::Note("two".to_string()),
+            TraceEvent::Action { priority: 0, accepted: true },
+            TraceEvent::Mnemonic { address: 0, opcode: "a".to_string() },
+            TraceEvent::Action { priority: 0, accepted: true },
+        ];
+
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0].address, 0);
+        assert_eq!(traces[0].candidates.len(), 3);
+        assert_eq!(traces[0].candidates[0], vec![one, TraceEvent::Action { priority: 0, accepted: false }]);
+        assert_eq!(traces[0].result, Some(result));
+        assert_eq!(traces[1], Trace { address: 2, candidates: vec![], result: None });
+        assert!(format!("{}", traces[0]).contains("token 0x01 matches \"00000001\""));
+        assert!(main.take_traces().is_empty());
+    }
+
+    #[test]
+    fn priorities() {
+        let ambiguous = new_disassembler!(TestArchShort =>
+            [ "0000 ..11" ] = |st: &mut State<TestArchShort>| { st.groups.push(("a".to_string(), 1)); true },
+            [ "0000 11.." ] = |st: &mut State<TestArchShort>| { st.groups.push(("b".to_string(), 1)); true },
+            [ "0000 1111", 1 ] = &|_| true
+        );
+        let conflicts = ambiguous.conflicts();
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].first, vec!["0000..11".to_string()]);
+        assert_eq!(conflicts[0].second, vec!["000011..".to_string()]);
+        assert!(ambiguous.check().unwrap_err().to_string().contains("[0000..11] and [000011..]"));
+
+        let src = OpaqueLayer::wrap(vec![0x0f, 0x0f, 0x00]);
+        assert!(ambiguous.next_match(&mut src.iter().seek(1), 1, ()).unwrap().has_group("a"));
+
+        let resolved = new_disassembler!(TestArchShort =>
+            [ "0000 ..11" ] = |st: &mut State<TestArchShort>| { st.groups.push(("a".to_string(), 1)); true },
+            [ "0000 11.." ] : 1 = |st: &mut State<TestArchShort>| { st.groups.push(("b".to_string(), 1)); true },
+            [ "0000 1111" ] : (-1) = |st: &mut State<TestArchShort>| { st.groups.push(("c".to_string(), 1)); true },
+            [ "0000 1111", 1 ] = &|_| true
+        );
+
+        assert!(resolved.check().is_ok());
+        assert!(resolved.next_match(&mut src.iter().seek(1), 1, ()).unwrap().has_group("b"));
+        assert_eq!(resolved.next_match(&mut src.iter(), 0, ()).unwrap().tokens.len(), 1);
+
+        // priority beats length
+        let res = resolved.next_match(&mut OpaqueLayer::wrap(vec![0x0f, 0x01]).iter(), 0, ()).unwrap();
+        assert!(res.has_group("b") && res.tokens.len() == 1);
+    }
+
+    /*
+     * 8 bit prefix 0xf0 followed by a 16 bit opcode, 16 bit opcodes without prefix
+     */
+    #[test]
+    fn mixed_widths() {
+        let dec = new_disassembler!(TestArchWide =>
+            [ "1111 0000", "0001 a@.... 0000 0000" ] = |st: &mut State<TestArchWide>| {
+                let len = st.length;
+                st.mnemonic(len, "lock op", "", vec![], &|_| Ok(vec![])).is_ok()
+            },
+            [ "0001 a@.... 0000 0000" ] = |st: &mut State<TestArchWide>| {
+                let len = st.length;
+                st.mnemonic(len, "op", "", vec![], &|_| Ok(vec![])).is_ok()
+            }
+        );
+        let prefixed = dec.next_match(&mut OpaqueLayer::wrap(vec![0xf0, 0x00, 0x13]).iter(), 0, ()).unwrap();
+
+        assert_eq!(prefixed.length, 3);
+        assert_eq!(prefixed.tokens, vec![0xf0, 0x1300]);
+        assert_eq!(prefixed.get_group("a"), 3);
+        assert_eq!(prefixed.mnemonics[0].area, Bound::new(0, 3));
+
+        let plain = dec.next_match(&mut OpaqueLayer::wrap(vec![0x00, 0x15]).iter(), 0, ()).unwrap();
+
+        assert_eq!(plain.length, 2);
+        assert_eq!(plain.get_group("a"), 5);
+        assert!(dec.next_match(&mut OpaqueLayer::wrap(vec![0xf0]).iter(), 0, ()).is_none());
+        assert!(dec.conflicts().is_empty());
+    }
+
     #[test]
     fn decode_macro() {
         let lock_prfx = new_disassembler!(TestArchShort =>
@@ -1089,6 +1806,21 @@ mod tests {
         new_disassembler!(TestArchShort => [ "a111111" ] = &|_| { true });
     }
 
+    #[test]
+    fn group_names_with_digits() {
+        let dec = new_disassembler!(TestArchShort =>
+            [ "rs1@.. 0 imm_2@....." ] = |st: &mut State<TestArchShort>| {
+                let (rs1, imm) = (st.get_group("rs1"), st.get_group("imm_2"));
+                st.mnemonic(1, if rs1 == 2 && imm == 0x15 { "ok" } else { "bad" }, "", vec![], &|_| Ok(vec![])).unwrap();
+                true
+            }
+        );
+        let reg = Region::new("".to_string(), OpaqueLayer::wrap(vec![0x95]));
+        let st = dec.next_match(&mut reg.iter().seek(0), 0, ()).unwrap();
+
+        assert_eq!(st.mnemonics[0].opcode, "ok");
+    }
+
     #[test]
     fn wide_token() {
         let def = OpaqueLayer::wrap(vec![0x11, 0x22, 0x33, 0x44, 0x55, 0x44]);
@@ -0,0 +1,469 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Exception handling tables.
+//!
+//! ELF files compiled with unwinding support have an `.eh_frame` section with one Frame
+//! Description Entry (FDE) for every function, even if the symbol table was stripped. C++
+//! functions with `try` blocks or destructors to run point to a Language Specific Data Area
+//! (LSDA) in `.gcc_except_table`, which maps the call sites that may throw to the landing pads
+//! handling the exception.
+//!
+//! The ELF loader adds the start of every FDE as a function and stores the parsed tables in
+//! `Project::exception_tables`. Landing pads are only reachable by unwinding, so disassembling a
+//! function misses them. [`add_exception_edges`] connects the call sites to their landing pads,
+//! continuing the disassembly with `Function::cont` decodes them.
+//!
+//! [`add_exception_edges`]: fn.add_exception_edges.html
+
+use {Bound, ControlFlowTarget, Endianess, Function, Guard, Region, Rvalue};
+use panopticon_graph_algos::{AdjacencyMatrixGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use std::collections::HashMap;
+
+/// Maximal number of bytes read for a single LSDA.
+const MAX_LSDA_SIZE: usize = 0x10000;
+
+/// `DW_EH_PE_omit`
+const DW_EH_PE_OMIT: u8 = 0xff;
+
+/// Range of instructions that may throw.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct CallSite {
+    /// Instructions covered
+    pub area: Bound,
+    /// Code handling exceptions thrown in `area`. `None` if they are passed to the caller.
+    pub landing_pad: Option<u64>,
+    /// Index into the action table plus one, zero for cleanup only
+    pub action: u64,
+}
+
+/// Unwinding information of a single function.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Fde {
+    /// Code covered, starting at the function's entry point
+    pub function: Bound,
+    /// Address of the LSDA, if any
+    pub lsda: Option<u64>,
+    /// Call sites listed in the LSDA
+    pub call_sites: Vec<CallSite>,
+}
+
+/// Parts of a Common Information Entry needed to read its FDEs.
+#[derive(Clone,Copy,Debug)]
+struct Cie {
+    augmented: bool,
+    fde_encoding: u8,
+    lsda_encoding: u8,
+}
+
+/// Reads DWARF encoded values from a byte string starting at `address`.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    address: u64,
+    position: usize,
+    word: usize,
+    endianess: Endianess,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], address: u64, word: usize, endianess: Endianess) -> Reader<'a> {
+        Reader { bytes: bytes, address: address, position: 0, word: word, endianess: endianess }
+    }
+
+    fn pc(&self) -> u64 {
+        self.address + self.position as u64
+    }
+
+    fn fixed(&mut self, size: usize) -> Option<u64> {
+        let b = self.bytes.get(self.position..self.position + size)?;
+        let value = match self.endianess {
+            Endianess::Little => b.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64),
+            Endianess::Big => b.iter().fold(0u64, |acc, &b| acc << 8 | b as u64),
+        };
+
+        self.position += size;
+        Some(value)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.fixed(1).map(|b| b as u8)
+    }
+
+    fn uleb128(&mut self) -> Option<u64> {
+        let mut ret = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.u8()?;
+
+            if shift < 64 {
+                ret |= ((b & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                return Some(ret);
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> Option<i64> {
+        let mut ret = 0i64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.u8()?;
+
+            if shift < 64 {
+                ret |= ((b & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    ret |= -1i64 << shift;
+                }
+                return Some(ret);
+            }
+        }
+    }
+
+    fn cstring(&mut self) -> Option<String> {
+        let len = self.bytes.get(self.position..)?.iter().position(|&b| b == 0)?;
+        let s = String::from_utf8_lossy(&self.bytes[self.position..self.position + len]).to_string();
+
+        self.position += len + 1;
+        Some(s)
+    }
+
+    /// Reads a pointer encoded as `encoding`. Returns `Some(None)` if the value can't be computed,
+    /// e.g. because it's relative to the GOT.
+    fn pointer(&mut self, encoding: u8, function: u64, region: &Region) -> Option<Option<u64>> {
+        let pc = self.pc();
+        let value = match encoding & 0x0f {
+            0x00 => self.fixed(self.word)?,
+            0x01 => self.uleb128()?,
+            0x02 => self.fixed(2)?,
+            0x03 => self.fixed(4)?,
+            0x04 => self.fixed(8)?,
+            0x09 => self.sleb128()? as u64,
+            0x0a => self.fixed(2)? as u16 as i16 as i64 as u64,
+            0x0b => self.fixed(4)? as u32 as i32 as i64 as u64,
+            0x0c => self.fixed(8)?,
+            _ => return None,
+        };
+        let value = match encoding & 0x70 {
+            0x00 => value,
+            0x10 => pc.wrapping_add(value),
+            0x40 => function.wrapping_add(value),
+            _ => return Some(None),
+        };
+        let value = if self.word == 4 { value & 0xffff_ffff } else { value };
+
+        if encoding & 0x80 != 0 {
+            let bytes = region.iter().seek(value).take(self.word).collect::<Option<Vec<u8>>>();
+
+            Some(bytes.and_then(|b| Reader::new(&b, value, self.word, self.endianess).fixed(self.word)))
+        } else {
+            Some(Some(value))
+        }
+    }
+}
+
+/// Parses the `.eh_frame` section covering `area` of `region`. Pointers are `word` bytes long.
+pub fn parse_eh_frame(region: &Region, area: &Bound, word: usize, endianess: Endianess) -> Vec<Fde> {
+    let bytes = region.iter().cut(&(area.start..area.end)).map(|c| c.unwrap_or(0)).collect::<Vec<u8>>();
+    let mut rd = Reader::new(&bytes, area.start, word, endianess);
+    let mut cies = HashMap::<usize, Cie>::new();
+    let mut ret = vec![];
+
+    loop {
+        let start = rd.position;
+        let (length, id_size) = match rd.fixed(4) {
+            Some(0) | None => break,
+            Some(0xffff_ffff) => {
+                match rd.fixed(8) {
+                    Some(l) => (l as usize, 8),
+                    None => break,
+                }
+            }
+            Some(l) => (l as usize, 4),
+        };
+        let id_pos = rd.position;
+        let next = id_pos + length;
+
+        match rd.fixed(id_size) {
+            Some(0) => {
+                if let Some(cie) = parse_cie(&mut rd, region) {
+                    cies.insert(start, cie);
+                }
+            }
+            Some(ptr) => {
+                let cie = id_pos.checked_sub(ptr as usize).and_then(|off| cies.get(&off)).cloned();
+
+                if let Some(fde) = cie.and_then(|cie| parse_fde(&mut rd, &cie, region)) {
+                    ret.push(fde);
+                }
+            }
+            None => break,
+        }
+
+        if next > bytes.len() {
+            break;
+        }
+        rd.position = next;
+    }
+
+    ret
+}
+
+fn parse_cie(rd: &mut Reader, region: &Region) -> Option<Cie> {
+    let version = rd.u8()?;
+    let augmentation = rd.cstring()?;
+    let mut ret = Cie { augmented: augmentation.starts_with('z'), fde_encoding: 0, lsda_encoding: DW_EH_PE_OMIT };
+
+    if augmentation.contains("eh") {
+        rd.fixed(rd.word)?;
+    }
+
+    rd.uleb128()?;
+    rd.sleb128()?;
+    if version == 1 {
+        rd.u8()?;
+    } else {
+        rd.uleb128()?;
+    }
+
+    if ret.augmented {
+        rd.uleb128()?;
+
+        for c in augmentation.chars().skip(1) {
+            match c {
+                'L' => ret.lsda_encoding = rd.u8()?,
+                'R' => ret.fde_encoding = rd.u8()?,
+                'P' => {
+                    let enc = rd.u8()?;
+                    rd.pointer(enc & 0x7f, 0, region)?;
+                }
+                'S' | 'B' => {}
+                _ => break,
+            }
+        }
+    }
+
+    Some(ret)
+}
+
+fn parse_fde(rd: &mut Reader, cie: &Cie, region: &Region) -> Option<Fde> {
+    let start = rd.pointer(cie.fde_encoding, 0, region)??;
+    let len = rd.pointer(cie.fde_encoding & 0x0f, 0, region)??;
+    let mut lsda = None;
+
+    if cie.augmented {
+        let aug_len = rd.uleb128()? as usize;
+        let aug_end = rd.position + aug_len;
+
+        if cie.lsda_encoding != DW_EH_PE_OMIT && aug_len > 0 {
+            lsda = rd.pointer(cie.lsda_encoding, start, region)?.and_then(|l| if l == 0 { None } else { Some(l) });
+        }
+        rd.position = aug_end;
+    }
+
+    let call_sites = lsda.map(|l| parse_lsda(region, l, start, rd.word, rd.endianess)).unwrap_or_default();
+
+    Some(Fde { function: Bound::new(start, start + len), lsda: lsda, call_sites: call_sites })
+}
+
+/// Reads the call site table of the LSDA at `address` for the function starting at `function`.
+fn parse_lsda(region: &Region, address: u64, function: u64, word: usize, endianess: Endianess) -> Vec<CallSite> {
+    let bytes = region.iter().seek(address).take(MAX_LSDA_SIZE).take_while(|c| c.is_some()).map(|c| c.unwrap()).collect::<Vec<u8>>();
+    let mut rd = Reader::new(&bytes, address, word, endianess);
+    let mut ret = vec![];
+
+    let header = (|| {
+        let lp_encoding = rd.u8()?;
+        let lp_start = if lp_encoding != DW_EH_PE_OMIT { rd.pointer(lp_encoding, function, region)?? } else { function };
+
+        if rd.u8()? != DW_EH_PE_OMIT {
+            rd.uleb128()?;
+        }
+
+        let cs_encoding = rd.u8()?;
+        let cs_len = rd.uleb128()? as usize;
+
+        Some((lp_start, cs_encoding, rd.position + cs_len))
+    })();
+    let (lp_start, cs_encoding, end) = match header {
+        Some(h) => h,
+        None => return ret,
+    };
+
+    while rd.position < end {
+        let site = (|| {
+            let start = rd.pointer(cs_encoding & 0x0f, 0, region)??;
+            let len = rd.pointer(cs_encoding & 0x0f, 0, region)??;
+            let pad = rd.pointer(cs_encoding & 0x0f, 0, region)??;
+            let action = rd.uleb128()?;
+
+            Some(
+                CallSite {
+                    area: Bound::new(function + start, function + start + len),
+                    landing_pad: if pad == 0 { None } else { Some(lp_start + pad) },
+                    action: action,
+                }
+            )
+        })();
+
+        match site {
+            Some(s) => ret.push(s),
+            None => break,
+        }
+    }
+
+    ret
+}
+
+/// Adds edges from the basic blocks of `func` calling into code that may throw to the landing
+/// pads handling the exception. Returns true if edges to code not yet disassembled were added.
+/// Continuing disassembly at the function's start decodes the landing pads.
+pub fn add_exception_edges(func: &mut Function, fde: &Fde) -> bool {
+    let mut ret = false;
+
+    for site in fde.call_sites.iter() {
+        let pad = match site.landing_pad {
+            Some(pad) => pad,
+            None => continue,
+        };
+        let cfg = func.cfg_mut();
+        let mut from = None;
+        let mut to = None;
+
+        for vx in cfg.vertices() {
+            match cfg.vertex_label(vx) {
+                Some(&ControlFlowTarget::Resolved(ref bb)) => {
+                    if bb.mnemonics.iter().any(|m| site.area.start <= m.area.start && m.area.start < site.area.end) {
+                        from = Some(vx);
+                    }
+                    if bb.area.start == pad {
+                        to = Some(vx);
+                    }
+                }
+                Some(&ControlFlowTarget::Unresolved(Rvalue::Constant { value, .. })) if value == pad => {
+                    to = Some(vx);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(from) = from {
+            let to = match to {
+                Some(to) => to,
+                None => {
+                    ret = true;
+                    cfg.add_vertex(ControlFlowTarget::Unresolved(Rvalue::new_u64(pad)))
+                }
+            };
+
+            if cfg.edge(from, to).is_none() {
+                cfg.add_edge(Guard::always(), from, to);
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Mnemonic};
+    use panopticon_graph_algos::EdgeListGraphTrait;
+
+    fn put(buf: &mut Vec<u8>, addr: usize, bytes: &[u8]) {
+        buf[addr..addr + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn le32(v: u32) -> [u8; 4] {
+        [v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]
+    }
+
+    fn region() -> Region {
+        let mut buf = vec![0u8; 0x4000];
+
+        // CIE: version 1, "zLR", code align 1, data align -8, return address in 16, absolute
+        // LSDA pointers, PC relative FDE pointers
+        put(&mut buf, 0x2000, &le32(16));
+        put(&mut buf, 0x2004, &le32(0));
+        put(&mut buf, 0x2008, &[1, b'z', b'L', b'R', 0, 1, 0x78, 16, 2, 0x00, 0x1b]);
+        // FDE for 0x1000..0x1040 with its LSDA at 0x3000
+        put(&mut buf, 0x2014, &le32(24));
+        put(&mut buf, 0x2018, &le32(0x18));
+        put(&mut buf, 0x201c, &le32((0x1000i64 - 0x201c) as i32 as u32));
+        put(&mut buf, 0x2020, &le32(0x40));
+        put(&mut buf, 0x2024, &[8, 0, 0x30, 0, 0, 0, 0, 0, 0]);
+        // LSDA: no LPStart and type table, ULEB128 call site table
+        put(&mut buf, 0x3000, &[0xff, 0xff, 0x01, 8, 0x10, 5, 0x30, 0, 0x20, 5, 0, 0]);
+
+        Region::wrap("RAM".to_string(), buf)
+    }
+
+    fn expected() -> Fde {
+        Fde {
+            function: Bound::new(0x1000, 0x1040),
+            lsda: Some(0x3000),
+            call_sites: vec![
+                CallSite { area: Bound::new(0x1010, 0x1015), landing_pad: Some(0x1030), action: 0 },
+                CallSite { area: Bound::new(0x1020, 0x1025), landing_pad: None, action: 0 },
+            ],
+        }
+    }
+
+    #[test]
+    fn eh_frame() {
+        let fdes = parse_eh_frame(&region(), &Bound::new(0x2000, 0x2034), 8, Endianess::Little);
+
+        assert_eq!(fdes, vec![expected()]);
+    }
+
+    #[test]
+    fn exception_edges() {
+        let mnes = vec![
+            Mnemonic::new(0x1000..0x1010, "nop".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap(),
+            Mnemonic::new(0x1010..0x1015, "call".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap(),
+        ];
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(mnes)));
+        let mut func = Function::undefined(0x1000, None, &region(), None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+
+        assert!(add_exception_edges(&mut func, &expected()));
+        assert!(!add_exception_edges(&mut func, &expected()));
+
+        let cfg = func.cfg();
+        assert_eq!(cfg.num_vertices(), 2);
+        assert_eq!(cfg.num_edges(), 1);
+        assert!(
+            cfg.vertex_labels().any(
+                |lb| match lb {
+                    &ControlFlowTarget::Unresolved(Rvalue::Constant { value: 0x1030, .. }) => true,
+                    _ => false,
+                }
+            )
+        );
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Progress of loading and disassembly.
+//!
+//! The loaders and `Function` report what they are doing as [`AnalysisEvent`]s. Front-ends call
+//! [`subscribe`] to receive them on a channel and use them to show progress bars: `Loaded` tells
+//! how many bytes of code there are, `BytesCovered` how many of them were disassembled so far.
+//!
+//! Events are sent to all subscribers from whatever thread does the work. Nothing is recorded if
+//! nobody subscribed. Dropping the `Receiver` unsubscribes.
+//!
+//! ```
+//! use panopticon_core::event::{self, AnalysisEvent};
+//!
+//! let events = event::subscribe();
+//! // ...
+//! for ev in events.try_iter() {
+//!     if let AnalysisEvent::Error { address, message } = ev {
+//!         println!("{:?}: {}", address, message);
+//!     }
+//! }
+//! ```
+//!
+//! [`AnalysisEvent`]: enum.AnalysisEvent.html
+//! [`subscribe`]: fn.subscribe.html
+
+use Machine;
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use uuid::Uuid;
+
+/// Something the loaders or the disassembler did.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum AnalysisEvent {
+    /// A file was loaded
+    Loaded {
+        /// File name
+        name: String,
+        /// CPU the code is for
+        machine: Machine,
+        /// Size of all executable sections, zero if the file has none
+        code_bytes: u64,
+    },
+    /// Disassembly of a new function started
+    FunctionDiscovered {
+        /// Function UUID
+        uuid: Uuid,
+        /// Entry point
+        start: u64,
+    },
+    /// Disassembly of a function ended, either the first time or after continuing it
+    FunctionFinished {
+        /// Function UUID
+        uuid: Uuid,
+        /// Entry point
+        start: u64,
+        /// Bytes of code in the function
+        size: usize,
+    },
+    /// Instructions decoded since the last `BytesCovered` event of the same function
+    BytesCovered {
+        /// Function UUID
+        uuid: Uuid,
+        /// Size of the new instructions
+        bytes: usize,
+    },
+    /// Loading a file or disassembling an instruction failed
+    Error {
+        /// Address of the failed instruction, None for loader errors
+        address: Option<u64>,
+        /// What went wrong
+        message: String,
+    },
+}
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<Vec<Sender<AnalysisEvent>>> = Mutex::new(Vec::new());
+}
+
+// avoids taking the lock for every event when nobody listens
+static NUM_SUBSCRIBERS: AtomicUsize = AtomicUsize::new(0);
+
+fn subscribers() -> MutexGuard<'static, Vec<Sender<AnalysisEvent>>> {
+    match SUBSCRIBERS.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Returns a channel receiving all events from now on.
+pub fn subscribe() -> Receiver<AnalysisEvent> {
+    let (tx, rx) = channel();
+    let mut subs = subscribers();
+
+    subs.push(tx);
+    NUM_SUBSCRIBERS.store(subs.len(), Ordering::SeqCst);
+    rx
+}
+
+/// Sends `event` to all subscribers.
+pub fn emit(event: AnalysisEvent) {
+    if NUM_SUBSCRIBERS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+
+    let mut subs = subscribers();
+
+    subs.retain(|tx| tx.send(event.clone()).is_ok());
+    NUM_SUBSCRIBERS.store(subs.len(), Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribe_and_drop() {
+        let uuid = Uuid::new_v4();
+        let rx1 = subscribe();
+        let rx2 = subscribe();
+        let ev = AnalysisEvent::BytesCovered { uuid: uuid, bytes: 4 };
+
+        emit(ev.clone());
+        drop(rx2);
+        emit(AnalysisEvent::FunctionFinished { uuid: uuid, start: 0, size: 4 });
+
+        // other tests emit events too
+        let mine = rx1.try_iter()
+            .filter(
+                |e| match e {
+                    &AnalysisEvent::BytesCovered { uuid: u, .. } |
+                    &AnalysisEvent::FunctionFinished { uuid: u, .. } => u == uuid,
+                    _ => false,
+                }
+            )
+            .collect::<Vec<_>>();
+
+        assert_eq!(mine, vec![ev, AnalysisEvent::FunctionFinished { uuid: uuid, start: 0, size: 4 }]);
+    }
+}
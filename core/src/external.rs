@@ -0,0 +1,452 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Import of function starts, names and comments found by other tools.
+//!
+//! Three formats are read into an [`ExternalDatabase`]:
+//!
+//! - Ghidra's XML export (`File > Export Program > XML`). `FUNCTION`, `SYMBOL` and `COMMENT`
+//!   elements are used, everything else is skipped.
+//! - Ghidra's SARIF export. Results whose `ruleId` names functions, symbols or comments are used,
+//!   their address is taken from the first physical location, names and comments from
+//!   `properties.additionalProperties` or the result message.
+//! - radare2 project scripts. The commands `af+`, `afn`, `f`, `CC` and `CCu` are used.
+//!
+//! [`merge`] copies the result into a `Project`. Functions already disassembled are renamed,
+//! unknown function starts are added as `Todo`s for the next run of the analysis. Comments and
+//! labels end up in `Project::annotations`. Only XML and JSON needed for these files is
+//! understood, not the whole of either.
+//!
+//! [`ExternalDatabase`]: struct.ExternalDatabase.html
+//! [`merge`]: fn.merge.html
+
+use {CallTarget, ControlFlowTarget, Function, Location, NameSource, Program, Project, Result, Rvalue};
+use json::Json;
+use panopticon_graph_algos::{GraphTrait, MutableGraphTrait};
+use std::collections::BTreeMap;
+use std::str;
+use uuid::Uuid;
+
+/// Function starts, names and comments exported by another tool.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct ExternalDatabase {
+    /// Image base of the program in the other tool, if known
+    pub image_base: Option<u64>,
+    /// Function starts and names
+    pub functions: BTreeMap<u64, Option<String>>,
+    /// Comments by address. Several comments at one address are separated by newlines.
+    pub comments: BTreeMap<u64, String>,
+    /// Names of addresses other than function starts
+    pub labels: BTreeMap<u64, String>,
+}
+
+impl ExternalDatabase {
+    /// Returns an empty database.
+    pub fn new() -> ExternalDatabase {
+        ExternalDatabase::default()
+    }
+
+    /// Moves all addresses by the difference between `base` and `image_base`.
+    pub fn rebase(&mut self, base: u64) {
+        let delta = match self.image_base {
+            Some(old) => base.wrapping_sub(old),
+            None => return,
+        };
+
+        self.functions = moved(&self.functions, delta);
+        self.comments = moved(&self.comments, delta);
+        self.labels = moved(&self.labels, delta);
+        self.image_base = Some(base);
+    }
+
+    fn add_comment(&mut self, address: u64, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        let c = self.comments.entry(address).or_insert_with(String::new);
+
+        if !c.is_empty() {
+            c.push('\n');
+        }
+        c.push_str(&text);
+    }
+}
+
+fn moved<V: Clone>(m: &BTreeMap<u64, V>, delta: u64) -> BTreeMap<u64, V> {
+    m.iter().map(|(k, v)| (k.wrapping_add(delta), v.clone())).collect()
+}
+
+/// Reads a Ghidra XML export.
+pub fn ghidra_xml(xml: &str) -> Result<ExternalDatabase> {
+    let mut ret = ExternalDatabase::new();
+    let mut pos = 0;
+
+    while let Some(off) = xml[pos..].find('<') {
+        let start = pos + off;
+        let end = match xml[start..].find('>') {
+            Some(e) => start + e,
+            None => return Err("unterminated XML tag".into()),
+        };
+        let tag = &xml[start + 1..end];
+
+        pos = end + 1;
+
+        if tag.starts_with('/') || tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        let (name, attrs) = xml_tag(tag)?;
+        let attr = |k: &str| attrs.iter().find(|&&(ref n, _)| n == k).map(|&(_, ref v)| v.clone());
+
+        match name {
+            "PROGRAM" => {
+                ret.image_base = attr("IMAGE_BASE").and_then(|a| parse_address(&a));
+            }
+            "FUNCTION" => {
+                if let Some(addr) = attr("ENTRY_POINT").and_then(|a| parse_address(&a)) {
+                    ret.functions.insert(addr, attr("NAME"));
+                }
+            }
+            "SYMBOL" => {
+                if let (Some(addr), Some(name)) = (attr("ADDRESS").and_then(|a| parse_address(&a)), attr("NAME")) {
+                    ret.labels.insert(addr, name);
+                }
+            }
+            "COMMENT" if !tag.ends_with('/') => {
+                let close = match xml[pos..].find("</COMMENT>") {
+                    Some(c) => pos + c,
+                    None => return Err("unterminated COMMENT element".into()),
+                };
+
+                if let Some(addr) = attr("ADDRESS").and_then(|a| parse_address(&a)) {
+                    ret.add_comment(addr, xml_unescape(xml[pos..close].trim()));
+                }
+                pos = close + "</COMMENT>".len();
+            }
+            _ => {}
+        }
+    }
+
+    // Ghidra lists function names as symbols too
+    let functions = ret.functions.clone();
+    ret.labels.retain(|a, n| functions.get(a).map(|f| f.as_ref() != Some(n)).unwrap_or(true));
+    Ok(ret)
+}
+
+/// Splits the inside of a start tag into element name and attributes.
+fn xml_tag(tag: &str) -> Result<(&str, Vec<(String, String)>)> {
+    let tag = if tag.ends_with('/') { &tag[..tag.len() - 1] } else { tag };
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut rest = tag[name_end..].trim();
+    let mut attrs = vec![];
+
+    while !rest.is_empty() {
+        let eq = match rest.find('=') {
+            Some(e) => e,
+            None => return Err(format!("malformed XML attribute in <{}>", tag).into()),
+        };
+        let key = rest[..eq].trim().to_string();
+        let value = rest[eq + 1..].trim();
+        let quote = match value.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => return Err(format!("unquoted XML attribute in <{}>", tag).into()),
+        };
+        let close = match value[1..].find(quote) {
+            Some(c) => c + 1,
+            None => return Err(format!("unterminated XML attribute in <{}>", tag).into()),
+        };
+
+        attrs.push((key, xml_unescape(&value[1..close])));
+        rest = value[close + 1..].trim();
+    }
+
+    Ok((&tag[..name_end], attrs))
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Parses Ghidra addresses like `00401000`, `ram:00401000` or `0x401000`, which are hexadecimal.
+fn parse_address(s: &str) -> Option<u64> {
+    let s = s.rsplit(':').next().unwrap_or(s).trim();
+    let s = if s.starts_with("0x") || s.starts_with("0X") { &s[2..] } else { s };
+
+    u64::from_str_radix(s, 16).ok()
+}
+
+/// Reads a Ghidra SARIF export.
+pub fn ghidra_sarif(sarif: &str) -> Result<ExternalDatabase> {
+    let doc = Json::parse(sarif)?;
+    let mut ret = ExternalDatabase::new();
+    let results = doc.get("runs").and_then(|r| r.index(0)).and_then(|r| r.get("results")).and_then(|r| r.as_array());
+
+    for res in results.unwrap_or(&[]) {
+        let rule = res.get("ruleId").and_then(|r| r.as_str()).unwrap_or("").to_uppercase();
+        let loc = res.get("locations").and_then(|l| l.index(0)).and_then(|l| l.get("physicalLocation")).and_then(|l| l.get("address"));
+        let addr = match loc.and_then(|l| l.get("absoluteAddress")) {
+            Some(&Json::Number(n)) if n >= 0.0 => n as u64,
+            Some(&Json::String(ref s)) => {
+                match parse_address(s) {
+                    Some(a) => a,
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        let props = res.get("properties").and_then(|p| p.get("additionalProperties"));
+        let prop = |k: &str| props.and_then(|p| p.get(k)).and_then(|v| v.as_str()).map(|s| s.to_string());
+        let message = res.get("message").and_then(|m| m.get("text")).and_then(|t| t.as_str()).map(|s| s.to_string());
+
+        if rule.contains("FUNCTION") {
+            ret.functions.insert(addr, prop("name").or(message));
+        } else if rule.contains("COMMENT") {
+            if let Some(text) = prop("comment").or(prop("value")).or(message) {
+                ret.add_comment(addr, text);
+            }
+        } else if rule.contains("SYMBOL") || rule.contains("LABEL") {
+            if let Some(name) = prop("name").or(message) {
+                ret.labels.insert(addr, name);
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Reads a radare2 project script.
+pub fn radare2_script(script: &str) -> Result<ExternalDatabase> {
+    let mut ret = ExternalDatabase::new();
+
+    for line in script.lines() {
+        let line = line.trim();
+        let line = if line.len() >= 2 && line.starts_with('"') && line.ends_with('"') { &line[1..line.len() - 1] } else { line };
+        let (cmd, at) = match line.rfind(" @ ").or(line.rfind(" @")) {
+            Some(p) => (line[..p].trim(), parse_number(line[p..].trim_matches(|c| c == ' ' || c == '@'))),
+            None => (line, None),
+        };
+        let (verb, args) = match cmd.find(' ') {
+            Some(p) => (&cmd[..p], cmd[p + 1..].trim()),
+            None => (cmd, ""),
+        };
+        let words = args.split_whitespace().collect::<Vec<_>>();
+
+        match verb {
+            // af+ <addr> <name> [type] [diff]
+            "af+" if words.len() >= 2 => {
+                if let Some(addr) = parse_number(words[0]).or(at) {
+                    ret.functions.insert(addr, Some(words[1].to_string()));
+                }
+            }
+            // afn <name> [addr]
+            "afn" if words.len() >= 1 => {
+                if let Some(addr) = words.get(1).and_then(|w| parse_number(w)).or(at) {
+                    ret.functions.insert(addr, Some(words[0].to_string()));
+                }
+            }
+            // f <name> [size] [addr]
+            "f" if words.len() >= 1 => {
+                if let Some(addr) = words.get(2).and_then(|w| parse_number(w)).or(at) {
+                    ret.labels.insert(addr, words[0].to_string());
+                }
+            }
+            "CC" | "CCu" => {
+                if let Some(addr) = at {
+                    let text = if args.starts_with("base64:") { base64(&args[7..]).unwrap_or_default() } else { args.to_string() };
+                    ret.add_comment(addr, text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ret)
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    if s.starts_with("0x") || s.starts_with("0X") { u64::from_str_radix(&s[2..], 16).ok() } else { s.parse::<u64>().ok() }
+}
+
+fn base64(s: &str) -> Option<String> {
+    let mut bits = 0u32;
+    let mut n = 0;
+    let mut out = vec![];
+
+    for c in s.trim().bytes().take_while(|&c| c != b'=') {
+        let v = match c {
+            b'A'...b'Z' => c - b'A',
+            b'a'...b'z' => c - b'a' + 26,
+            b'0'...b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        bits = bits << 6 | v as u32;
+        n += 6;
+        if n >= 8 {
+            n -= 8;
+            out.push((bits >> n) as u8);
+        }
+    }
+
+    String::from_utf8(out).ok()
+}
+
+/// Returns true if `func` starts at `address`.
+fn starts_at(func: &Function, address: u64) -> bool {
+    match func.cfg().vertex_label(func.entry_point_ref()) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => bb.area.start == address,
+        _ => false,
+    }
+}
+
+/// Copies `db` into `proj`. Functions are renamed or added as `Todo`s to the first program,
+/// comments and labels are added to the annotations, replacing existing ones. Returns the number
+/// of function starts not known before.
+pub fn merge(proj: &mut Project, db: &ExternalDatabase) -> usize {
+    let mut added = 0;
+
+    for (&addr, name) in db.functions.iter() {
+        let mut found = false;
+
+        for prog in proj.code.iter_mut() {
+            if let Some(func) = prog.find_function_mut(|f| starts_at(f, addr)) {
+                if let Some(ref name) = *name {
+                    func.propose_name(name.clone(), NameSource::User);
+                }
+                found = true;
+                break;
+            }
+
+            for ct in prog.call_graph.vertex_labels_mut() {
+                if let &mut CallTarget::Todo(Rvalue::Constant { value, .. }, ref mut todo_name, ref uuid) = ct {
+                    if value == addr {
+                        if name.is_some() {
+                            *todo_name = name.clone();
+                            prog.name_sources.insert(uuid.clone(), NameSource::User);
+                        }
+                        found = true;
+                    }
+                }
+            }
+            if found {
+                break;
+            }
+        }
+
+        if !found {
+            if proj.code.is_empty() {
+                let prog = Program::new(&proj.name);
+                proj.code.push(prog);
+            }
+
+            match *name {
+                Some(ref name) => {
+                    proj.code[0].add_todo(addr, name.clone(), NameSource::User);
+                }
+                None => {
+                    proj.code[0].call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), None, Uuid::new_v4()));
+                }
+            }
+            added += 1;
+        }
+    }
+
+    for (&addr, text) in db.comments.iter() {
+        proj.annotations.set_comment(Location::Address(addr), text.clone());
+    }
+    for (&addr, name) in db.labels.iter() {
+        proj.annotations.set_label(Location::Address(addr), name.clone());
+    }
+
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Region;
+    use panopticon_graph_algos::VertexListGraphTrait;
+
+    #[test]
+    fn ghidra() {
+        let xml = r#"<?xml version="1.0" standalone="yes"?>
+<PROGRAM NAME="test.exe" EXE_FORMAT="Portable Executable (PE)" IMAGE_BASE="00400000">
+    <SYMBOL_TABLE>
+        <SYMBOL ADDRESS="00401000" NAME="main" NAMESPACE="" TYPE="global" SOURCE_TYPE="USER_DEFINED" PRIMARY="y" />
+        <SYMBOL ADDRESS="00402000" NAME="s_hello" NAMESPACE="" TYPE="global" SOURCE_TYPE="ANALYSIS" PRIMARY="y" />
+    </SYMBOL_TABLE>
+    <COMMENTS>
+        <COMMENT ADDRESS="ram:00401004" TYPE="end-of-line">a &lt; b</COMMENT>
+    </COMMENTS>
+    <FUNCTIONS>
+        <FUNCTION ENTRY_POINT="00401000" NAME="main" LIBRARY_FUNCTION="n">
+            <ADDRESS_RANGE START="00401000" END="00401010" />
+        </FUNCTION>
+    </FUNCTIONS>
+</PROGRAM>"#;
+        let mut db = ghidra_xml(xml).unwrap();
+
+        assert_eq!(db.image_base, Some(0x400000));
+        assert_eq!(db.functions.get(&0x401000), Some(&Some("main".to_string())));
+        assert_eq!(db.comments.get(&0x401004).map(|s| s.as_str()), Some("a < b"));
+        assert_eq!(db.labels.len(), 1);
+
+        db.rebase(0x10000);
+        assert_eq!(db.labels.get(&0x12000).map(|s| s.as_str()), Some("s_hello"));
+
+        let sarif = r#"{"version": "2.1.0", "runs": [{"results": [
+            {"ruleId": "FUNCTIONS", "message": {"text": "Function"},
+             "locations": [{"physicalLocation": {"address": {"absoluteAddress": 4198400}}}],
+             "properties": {"additionalProperties": {"name": "main"}}},
+            {"ruleId": "COMMENTS", "message": {"text": "loop!"},
+             "locations": [{"physicalLocation": {"address": {"absoluteAddress": "00401004"}}}]}
+        ]}]}"#;
+        let db = ghidra_sarif(sarif).unwrap();
+
+        assert_eq!(db.functions.get(&0x401000), Some(&Some("main".to_string())));
+        assert_eq!(db.comments.get(&0x401004).map(|s| s.as_str()), Some("loop!"));
+        assert!(ghidra_sarif("{\"runs\": [").is_err());
+    }
+
+    #[test]
+    fn radare2_and_merge() {
+        let script = "e asm.arch = x86
+\"af+ 0x1000 main\"
+afn helper 0x1100
+f str.hello 6 0x2000
+\"CC check length @ 0x1004\"
+CCu base64:c2Vjb25k @ 0x1004
+";
+        let db = radare2_script(script).unwrap();
+
+        assert_eq!(db.functions.len(), 2);
+        assert_eq!(db.labels.get(&0x2000).map(|s| s.as_str()), Some("str.hello"));
+        assert_eq!(db.comments.get(&0x1004).map(|s| s.as_str()), Some("check length\nsecond"));
+
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x3000));
+
+        assert_eq!(merge(&mut proj, &db), 2);
+        assert_eq!(merge(&mut proj, &db), 0);
+        assert_eq!(proj.code[0].call_graph.num_vertices(), 2);
+        assert_eq!(proj.annotations.comment(&Location::Address(0x1004)), Some("check length\nsecond"));
+        assert_eq!(proj.annotations.label(&Location::Address(0x2000)), Some("str.hello"));
+    }
+}
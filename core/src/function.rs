@@ -25,16 +25,25 @@
 //! Functions have the concept of unresolved basic blocks. These are inserted into the graph if a
 //! indirect branch could not be resolved. If disassembly failes for example because an unknown
 //! instruction was found, an error node is inserted into the graph to allow displaying a message
-//! on the front-end.
+//! on the front-end. Depending on the `Recovery` policy disassembly continues behind the error
+//! node, so a single bad byte doesn't cut off the rest of the function.
 
 
-use {Architecture, BasicBlock, Guard, Mnemonic, Operation, Region, Result, Rvalue, Statement};
+use {AnalysisEvent, Architecture, BasicBlock, Bound, CallingConvention, CancellationToken, Guard, Mnemonic, Operation, Region, Result, Rvalue, Signature, Statement, StringRef, Syscall, demangle};
+use event;
+use syscall;
 
-use panopticon_graph_algos::{AdjacencyList, EdgeListGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use panopticon_graph_algos::{AdjacencyList, EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListEdgeDescriptor, AdjacencyListVertexDescriptor, VertexLabelIterator};
 use panopticon_graph_algos::search::{TraversalOrder, TreeIterator};
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::Error as SerError;
+use std::result;
 use uuid::Uuid;
 
 /// An iterator over every BasicBlock in a Function
@@ -83,7 +92,8 @@ pub type ControlFlowRef = AdjacencyListVertexDescriptor;
 pub type ControlFlowEdge = AdjacencyListEdgeDescriptor;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-/// The kind of function this is, to distinguish plt stubs from regular functions.
+/// The kind of function this is, to distinguish plt stubs, thunks and library code from regular
+/// functions.
 pub enum FunctionKind {
     /// A regular function
     Regular,
@@ -93,15 +103,215 @@ pub enum FunctionKind {
         name: String,
         /// The address of this stub in the PLT table
         plt_address: u64
+    },
+    /// A function that only jumps to another one
+    Thunk {
+        /// Entry point of the function jumped to
+        target: u64,
+    },
+    /// A function implemented in another image, for example one the loader found in an import
+    /// table and mapped into the address space
+    Import {
+        /// Imported symbol
+        name: String,
+        /// Library the symbol is imported from, if known
+        library: Option<String>,
+    },
+    /// A function identified as part of a known library, e.g. statically linked libc code
+    Library {
+        /// Name of the library
+        library: String,
+    },
+    /// Code added by an analysis instead of found in the binary, like outlined helpers
+    Synthetic,
+    /// Startup code and helpers emitted by the compiler, e.g. `_start` or `__stack_chk_fail`
+    Runtime,
+}
+
+impl FunctionKind {
+    /// Returns true if calling the function ends up in code elsewhere. Stubs, thunks and imports
+    /// are usually collapsed into their target when displaying calls.
+    pub fn is_forwarder(&self) -> bool {
+        match self {
+            &FunctionKind::Stub { .. } | &FunctionKind::Thunk { .. } | &FunctionKind::Import { .. } => true,
+            &FunctionKind::Regular | &FunctionKind::Library { .. } | &FunctionKind::Synthetic | &FunctionKind::Runtime => false,
+        }
+    }
+
+    /// Returns true if the function wasn't written by the author of the binary but comes from a
+    /// library or the compiler.
+    pub fn is_library(&self) -> bool {
+        match self {
+            &FunctionKind::Library { .. } | &FunctionKind::Runtime | &FunctionKind::Import { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Where a function name came from. Sources are ordered by how much they are trusted: when two
+/// sources name the same function, the greater one becomes the primary name, see
+/// `Function::propose_name`.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub enum NameSource {
+    /// Made up by panopticon, like `func_0x1000`
+    Generated,
+    /// Not recorded, e.g. for projects saved before sources were tracked
+    Unknown,
+    /// Demangled form of another name
+    Demangler,
+    /// Export table
+    Export,
+    /// Import table or PLT
+    Import,
+    /// Symbol table
+    Symbol,
+    /// DWARF or PDB debug information
+    Debug,
+    /// Renamed by the user
+    User,
+}
+
+impl Default for NameSource {
+    fn default() -> NameSource {
+        NameSource::Unknown
+    }
+}
+
+/// An additional name of a function and where it came from
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Alias {
+    /// The name
+    pub name: String,
+    /// Where it came from
+    pub source: NameSource,
+}
+
+// Aliases used to be plain strings.
+fn aliases<'de, D: Deserializer<'de>>(deserializer: D) -> result::Result<Vec<Alias>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Entry {
+        Alias(Alias),
+        Name(String),
+    }
+
+    let entries = Vec::<Entry>::deserialize(deserializer)?;
+    Ok(
+        entries
+            .into_iter()
+            .map(|e| match e {
+                Entry::Alias(a) => a,
+                Entry::Name(name) => Alias { name: name, source: NameSource::Unknown },
+            })
+            .collect()
+    )
+}
+
+/// What to do if an instruction can't be decoded. In every case a
+/// `ControlFlowTarget::Failed` node is added for the undecodable bytes.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Recovery {
+    /// End the basic block with the error node
+    Stop,
+    /// Try again `step` bytes further, until `limit` bytes in a row were skipped
+    Skip {
+        /// Bytes skipped per attempt
+        step: u64,
+        /// Maximal number of bytes skipped before giving up
+        limit: u64,
+    },
+    /// Try again at the next multiple of `alignment`, until `limit` bytes in a row were skipped
+    Align {
+        /// Instruction alignment, for example 4 for 32 bit RISC instructions
+        alignment: u64,
+        /// Maximal number of bytes skipped before giving up
+        limit: u64,
+    },
+}
+
+impl Default for Recovery {
+    fn default() -> Recovery {
+        Recovery::Stop
+    }
+}
+
+/// Upper bounds on the work spent disassembling a single function. Anti-disassembly tricks and
+/// data misclassified as code can otherwise produce huge, nonsensical functions. Once a limit is
+/// hit disassembly stops, the remaining jump targets stay unresolved and the function is marked
+/// as truncated. The default has no limits.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub struct Limits {
+    /// Maximal number of basic blocks
+    pub basic_blocks: Option<usize>,
+    /// Maximal number of IL statements
+    pub statements: Option<usize>,
+    /// Maximal time spent decoding
+    pub time: Option<Duration>,
+}
+
+/// The limit that truncated a function, see `Limits`.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Limit {
+    /// Too many basic blocks
+    BasicBlocks,
+    /// Too many IL statements
+    Statements,
+    /// Decoding took too long
+    Time,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Limit::BasicBlocks => f.write_str("basic block limit"),
+            Limit::Statements => f.write_str("statement limit"),
+            Limit::Time => f.write_str("time limit"),
+        }
+    }
+}
+
+impl Limits {
+    /// Returns the first limit exceeded by `blocks` basic blocks and `statements` IL statements
+    /// decoded since `started`.
+    fn exceeded(&self, blocks: usize, statements: usize, started: Instant) -> Option<Limit> {
+        if self.basic_blocks.map(|l| blocks > l).unwrap_or(false) {
+            Some(Limit::BasicBlocks)
+        } else if self.statements.map(|l| statements > l).unwrap_or(false) {
+            Some(Limit::Statements)
+        } else if self.time.map(|l| started.elapsed() > l).unwrap_or(false) {
+            Some(Limit::Time)
+        } else {
+            None
+        }
+    }
+}
+
+impl Recovery {
+    /// Address to continue at after failing to decode `address`, `skipped` bytes after the last
+    /// instruction that could be decoded.
+    fn resume(&self, address: u64, skipped: u64) -> Option<u64> {
+        let (next, limit) = match *self {
+            Recovery::Stop => return None,
+            Recovery::Skip { step, limit } if step > 0 => (address.checked_add(step)?, limit),
+            Recovery::Align { alignment, limit } if alignment > 0 => ((address / alignment).checked_add(1)?.checked_mul(alignment)?, limit),
+            _ => return None,
+        };
+
+        if skipped + (next - address) <= limit { Some(next) } else { None }
     }
 }
 
 /// A set of basic blocks connected by conditional jumps
 #[derive(Serialize,Deserialize,Debug,Clone)]
 pub struct Function {
-    /// Display name of the function.
+    /// Name of the function as found in the binary. C++ and Rust names are mangled, see
+    /// `display_name`.
     pub name: String,
-    aliases: Vec<String>,
+    /// Where `name` came from
+    #[serde(default)]
+    name_source: NameSource,
+    #[serde(deserialize_with = "aliases")]
+    aliases: Vec<Alias>,
     /// Unique, immutable identifier for this function.
     uuid: Uuid,
     /// Graph of basic blocks and jumps
@@ -114,6 +324,91 @@ pub struct Function {
     size: usize,
     /// What kind of function is this
     kind: FunctionKind,
+    /// Calling convention, if known
+    #[serde(default)]
+    calling_convention: Option<CallingConvention>,
+    /// String literals referenced by the function's code
+    #[serde(default)]
+    string_refs: Vec<StringRef>,
+    /// IL dropped by `decode_only` or `defer`, produced again when it's first read
+    #[serde(default, serialize_with = "serialize_deferred", deserialize_with = "deserialize_deferred")]
+    deferred: Option<Deferred>,
+    /// Limit that stopped disassembly early, if any
+    #[serde(default)]
+    truncated: Option<Limit>,
+    /// Prototype, if known
+    #[serde(default)]
+    signature: Option<Signature>,
+}
+
+/// Statements of the mnemonics of a function by start address. Mnemonics starting at the same
+/// address are in basic block order.
+pub type DeferredIl = BTreeMap<u64, Vec<Vec<Statement>>>;
+
+/// Produces the statements `Function::defer` dropped.
+pub type IlSource = Arc<Fn() -> Result<DeferredIl> + Send + Sync>;
+
+/// IL of a function that is produced the first time it's read.
+#[derive(Clone)]
+struct Deferred {
+    /// `None` for IL read from a saved project, which can't be unloaded
+    source: Option<IlSource>,
+    il: OnceLock<result::Result<DeferredIl, String>>,
+    /// False if the dropped statements contain no calls, see `call_statements`
+    calls: bool,
+}
+
+impl Deferred {
+    fn get(&self) -> result::Result<&DeferredIl, String> {
+        let il = self.il.get_or_init(
+            || match self.source {
+                Some(ref source) => source().map_err(|e| e.to_string()),
+                None => Err("the IL was unloaded".to_string()),
+            }
+        );
+
+        match il {
+            &Ok(ref il) => Ok(il),
+            &Err(ref e) => Err(e.clone()),
+        }
+    }
+}
+
+impl fmt::Debug for Deferred {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Deferred {{ loaded: {} }}", self.il.get().is_some())
+    }
+}
+
+// Deferred IL is saved like the IL of all other mnemonics, producing it if necessary.
+fn serialize_deferred<S: Serializer>(deferred: &Option<Deferred>, serializer: S) -> result::Result<S::Ok, S::Error> {
+    match deferred {
+        &Some(ref d) => Some(d.get().map_err(S::Error::custom)?).serialize(serializer),
+        &None => None::<DeferredIl>.serialize(serializer),
+    }
+}
+
+fn deserialize_deferred<'de, D: Deserializer<'de>>(deserializer: D) -> result::Result<Option<Deferred>, D::Error> {
+    let il = Option::<DeferredIl>::deserialize(deserializer)?;
+
+    Ok(
+        il.map(
+            |il| {
+                let calls = il.values().flat_map(|v| v.iter()).flat_map(|v| v.iter()).any(is_call);
+                let cell = OnceLock::new();
+
+                let _ = cell.set(Ok(il));
+                Deferred { source: None, il: cell, calls: calls }
+            }
+        )
+    )
+}
+
+fn is_call(stmt: &Statement) -> bool {
+    match stmt.op {
+        Operation::Call(_) => true,
+        _ => false,
+    }
 }
 
 #[derive(Clone,PartialEq,Eq,Debug)]
@@ -129,6 +424,7 @@ impl Function {
         let entry_point = ControlFlowTarget::Unresolved(Rvalue::new_u64(start));
         let entry_point = cflow_graph.add_vertex(entry_point);
         Function {
+            name_source: if name.is_some() { NameSource::Unknown } else { NameSource::Generated },
             name: name.unwrap_or(format!("func_{:#x}", start)),
             aliases: Vec::new(),
             uuid: uuid.unwrap_or(Uuid::new_v4()),
@@ -137,58 +433,111 @@ impl Function {
             region: region.name().clone(),
             size: 0,
             kind: FunctionKind::Regular,
+            calling_convention: None,
+            string_refs: Vec::new(),
+            deferred: None,
+            truncated: None,
+            signature: None,
         }
     }
     // this private method is where the meat of making a function is;
     // almost all perf gains for function disassembly will be in here, and related functions like, assemble_cflow_graph, etc.
-    fn disassemble<A: Architecture>(start: u64, cflow_graph: &mut ControlFlowGraph, size: &mut usize, name: &str, uuid: &Uuid, region: &Region, init: A::Configuration) -> Result<ControlFlowRef> {
-        let (mut mnemonics, mut by_source, mut by_destination) = Self::index_cflow_graph(cflow_graph, start);
-
+    fn disassemble<A: Architecture>(
+        start: u64,
+        cflow_graph: &mut ControlFlowGraph,
+        size: &mut usize,
+        name: &str,
+        uuid: &Uuid,
+        region: &Region,
+        init: A::Configuration,
+        recovery: Recovery,
+        limits: &Limits,
+        truncated: &mut Option<Limit>,
+        mut states: Option<&mut HashMap<u64, (u64, A::Configuration)>>,
+        cancel: &CancellationToken,
+    ) -> Result<ControlFlowRef> {
+        let (mut mnemonics, mut by_source, mut by_destination, mut modes) = Self::index_cflow_graph(cflow_graph, start);
+
+        // CPU state each address is decoded with. Jump targets inherit the state the jumping
+        // instruction left the CPU in.
         let mut todo = cflow_graph.vertex_labels().filter_map(|lb| {
             if let &ControlFlowTarget::Unresolved(Rvalue::Constant{ value,.. }) = lb {
-                Some(value)
+                Some((value, init.clone()))
             } else {
                 None
             }
-        }).collect::<HashSet<u64>>();
-
-        todo.insert(start);
-
-        while let Some(addr) = todo.iter().next().cloned() {
-            let maybe_mnes = mnemonics.iter().find(|x| *x.0 >= addr).map(|x| x.1.clone());
+        }).collect::<HashMap<u64, A::Configuration>>();
+
+        todo.insert(start, init);
+
+        let mut decoded = 0;
+        // bytes skipped to get to an address after decoding failed
+        let mut skipped = HashMap::<u64, u64>::new();
+        let started = Instant::now();
+        // starts of basic blocks found by this call, besides the ones already in `cflow_graph`
+        let known = cflow_graph.vertex_labels().filter(|lb| if let &ControlFlowTarget::Resolved(_) = *lb { true } else { false }).count();
+        let mut leaders = HashSet::<u64>::new();
+        let mut statements = mnemonics.values().flat_map(|ms| ms.iter()).map(|m| match m {
+            &MnemonicOrError::Mnemonic(ref mne) => mne.instructions.len(),
+            &MnemonicOrError::Error(..) => 0,
+        }).sum::<usize>();
+
+        while let Some(addr) = todo.keys().next().cloned() {
+            cancel.check()?;
+
+            if let Some(limit) = limits.exceeded(known.max(1) + leaders.len(), statements, started) {
+                let msg = format!("function ({}) {} truncated at the {}, {} jump targets left", name, uuid, limit, todo.len());
+
+                warn!("{}", msg);
+                event::emit(AnalysisEvent::Error { address: Some(addr), message: msg });
+                *truncated = Some(limit);
+                break;
+            }
 
-            assert!(todo.remove(&addr));
+            // area of the mnemonic or position of the error at or after `addr`
+            let next = mnemonics.iter().find(|x| *x.0 >= addr).and_then(|x| x.1.first()).map(
+                |m| match m {
+                    &MnemonicOrError::Mnemonic(ref mne) => Ok((mne.area.start, mne.area.end)),
+                    &MnemonicOrError::Error(pos, _) => Err(pos),
+                }
+            );
+            let cfg = todo.remove(&addr).unwrap();
 
-            if let Some(mnes) = maybe_mnes {
-                if !mnes.is_empty() {
-                    match mnes.first() {
-                        Some(&MnemonicOrError::Mnemonic(ref mne)) => {
-                            if mne.area.start < addr && mne.area.end > addr {
-                                mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Jump inside instruction".into()));
-                                continue;
-                            } else if mne.area.start == addr {
-                                *size += mne.size();
-                                continue;
-                            }
-                        }
-                        Some(&MnemonicOrError::Error(ref pos, _)) => {
-                            if *pos == addr {
-                                continue;
-                            }
-                        }
-                        None => unreachable!(),
-                    }
+            match next {
+                Some(Ok((start, end))) if start < addr && end > addr => {
+                    mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Jump inside instruction".into()));
+                    continue;
+                }
+                Some(Ok((start, end))) if start == addr => {
+                    *size += (end - start) as usize;
+                    continue;
                 }
+                Some(Err(pos)) if pos == addr => continue,
+                _ => {}
             }
 
-            let maybe_match = A::decode(region, addr, &init);
+            let maybe_match = A::decode(region, addr, &cfg);
+            let mut failed = false;
 
             match maybe_match {
                 Ok(match_st) => {
+                    // a jump starts a new basic block unless it's the only one of its instruction
+                    // and falls through
+                    for &(origin, ref tgt, _) in match_st.jumps.iter() {
+                        if let &Rvalue::Constant { value, .. } = tgt {
+                            let alone = match_st.jumps.iter().filter(|j| j.0 == origin).count() == 1;
+                            let falls_through = match_st.mnemonics.iter().any(|m| m.area.start == origin && m.area.end == value);
+
+                            if !alone || !falls_through {
+                                leaders.insert(value);
+                            }
+                        }
+                    }
+
                     if match_st.mnemonics.is_empty() {
-                        mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Unrecognized instruction".into()));
+                        failed = true;
                     } else {
-                        for mne in match_st.mnemonics {
+                        for mut mne in match_st.mnemonics {
                             debug!(
                                 "{:x}: {} ({:?})",
                                 mne.area.start,
@@ -196,6 +545,19 @@ impl Function {
                                 match_st.tokens
                             );
                             *size += mne.size();
+                            decoded += mne.size();
+                            statements += mne.instructions.len();
+                            if let Some(mode) = A::mode(&cfg, mne.area.start) {
+                                modes.insert(mne.area.start, mode);
+                            }
+                            // decode-only functions keep the IL of calls, it's needed to find
+                            // other functions
+                            if let Some(ref mut states) = states {
+                                if !mne.instructions.iter().any(is_call) {
+                                    mne.instructions = Vec::new();
+                                    states.insert(mne.area.start, (addr, cfg.clone()));
+                                }
+                            }
                             mnemonics.entry(mne.area.start).or_insert(Vec::new()).push(MnemonicOrError::Mnemonic(mne));
                         }
                     }
@@ -206,7 +568,7 @@ impl Function {
                             Rvalue::Constant { value: ref c, .. } => {
                                 by_source.entry(origin).or_insert(Vec::new()).push((tgt.clone(), gu.clone()));
                                 by_destination.entry(*c).or_insert(Vec::new()).push((Rvalue::new_u64(origin), gu.clone()));
-                                todo.insert(*c);
+                                todo.entry(*c).or_insert(match_st.configuration.clone());
                             }
                             _ => {
                                 by_source.entry(origin).or_insert(Vec::new()).push((tgt, gu.clone()));
@@ -216,12 +578,28 @@ impl Function {
                 }
                 Err(e) => {
                     error!("failed to disassemble: {}", e);
-                    mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Unrecognized instruction".into()));
+                    event::emit(AnalysisEvent::Error { address: Some(addr), message: format!("{}", e) });
+                    failed = true;
+                }
+            }
+
+            if failed {
+                let skip = skipped.get(&addr).cloned().unwrap_or(0);
+
+                mnemonics.entry(addr).or_insert(Vec::new()).push(MnemonicOrError::Error(addr, "Unrecognized instruction".into()));
+
+                // continue behind the error node
+                if let Some(next) = recovery.resume(addr, skip) {
+                    debug!("skipping from {:#x} to {:#x}", addr, next);
+                    by_source.entry(addr).or_insert(Vec::new()).push((Rvalue::new_u64(next), Guard::always()));
+                    by_destination.entry(next).or_insert(Vec::new()).push((Rvalue::new_u64(addr), Guard::always()));
+                    skipped.entry(next).or_insert(skip + next - addr);
+                    todo.entry(next).or_insert(cfg);
                 }
             }
         }
 
-        let cfg = Self::assemble_cflow_graph(mnemonics, by_source, by_destination, start);
+        let cfg = Self::assemble_cflow_graph(mnemonics, by_source, by_destination, modes, start, cancel)?;
         let ep = cfg
             .vertices()
             .find(
@@ -231,41 +609,50 @@ impl Function {
                 }
             );
 
+        event::emit(AnalysisEvent::BytesCovered { uuid: uuid.clone(), bytes: decoded });
+
         match ep {
             Some(entry_point) => {
                 *cflow_graph = cfg;
+                event::emit(AnalysisEvent::FunctionFinished { uuid: uuid.clone(), start: start, size: *size });
                 Ok(entry_point)
             },
             None => {
-                Err(format!("function ({}) {} has no entry point", name, uuid).into())
+                let msg = format!("function ({}) {} has no entry point", name, uuid);
+
+                event::emit(AnalysisEvent::Error { address: Some(start), message: msg.clone() });
+                Err(msg.into())
             }
         }
     }
     /// Continue disassembling from `start`, at `region`, with CPU `configuration`, using the functions current, internal control flow graph.
     pub fn cont<A: Architecture>(&mut self, start: u64, region: &Region, configuration: A::Configuration) -> Result<()> {
-        self.entry_point = Self::disassemble::<A>(start, &mut self.cflow_graph, &mut self.size, &self.name, &self.uuid, region, configuration)?;
+        self.cont_with_token::<A>(start, region, configuration, &CancellationToken::new())
+    }
+
+    /// Like `cont`, but fails without changing the function once `cancel` is cancelled.
+    pub fn cont_with_token<A: Architecture>(&mut self, start: u64, region: &Region, configuration: A::Configuration, cancel: &CancellationToken) -> Result<()> {
+        self.cont_with_limits::<A>(start, region, configuration, &Limits::default(), cancel)
+    }
+
+    /// Like `cont_with_token`, but stops early once one of `limits` is hit. Afterwards the function
+    /// is marked as truncated if and only if this call was stopped, see `truncated`.
+    pub fn cont_with_limits<A: Architecture>(&mut self, start: u64, region: &Region, configuration: A::Configuration, limits: &Limits, cancel: &CancellationToken) -> Result<()> {
+        let mut size = self.size;
+        let mut truncated = None;
+
+        self.lift()?;
+        self.entry_point = Self::disassemble::<A>(
+            start, &mut self.cflow_graph, &mut size, &self.name, &self.uuid, region, configuration, Recovery::Stop, limits, &mut truncated, None, cancel,
+        )?;
+        self.size = size;
+        self.truncated = truncated;
         Ok(())
     }
 
     /// Create and start disassembling a new function with `name`, inside memory `region`, starting at entry point `start`, with a random UUID.
     pub fn new<A: Architecture>(start: u64, region: &Region, name: Option<String>, init: A::Configuration) -> Result<Function> {
-        let mut cflow_graph = AdjacencyList::new();
-        let entry_point = ControlFlowTarget::Unresolved(Rvalue::new_u64(start));
-        cflow_graph.add_vertex(entry_point);
-        let mut size = 0;
-        let name = name.unwrap_or(format!("func_{:#x}", start));
-        let uuid = Uuid::new_v4();
-        let entry_point = Self::disassemble::<A>(start, &mut cflow_graph, &mut size, &name, &uuid, region, init)?;
-        Ok(Function {
-            name,
-            aliases: Vec::new(),
-            uuid,
-            cflow_graph,
-            entry_point,
-            region: region.name().clone(),
-            size,
-            kind: FunctionKind::Regular,
-        })
+        Function::with_uuid::<A>(start, &Uuid::new_v4(), region, name, init)
     }
 
     /// Returns the start address of the first basic block in this function
@@ -294,9 +681,246 @@ impl Function {
 
     /// New function starting at `start`, with name `name`, inside memory region `region` and UUID `uuid`.
     pub fn with_uuid<A: Architecture>(start: u64, uuid: &Uuid, region: &Region, name: Option<String>, init: A::Configuration) -> Result<Function> {
-        let mut f = Function::new::<A>(start, region, name, init)?;
-        f.uuid = uuid.clone();
-        Ok(f)
+        Function::with_token::<A>(start, uuid, region, name, init, &CancellationToken::new())
+    }
+
+    /// Like `with_uuid`, but fails once `cancel` is cancelled.
+    pub fn with_token<A: Architecture>(start: u64, uuid: &Uuid, region: &Region, name: Option<String>, init: A::Configuration, cancel: &CancellationToken) -> Result<Function> {
+        Function::with_recovery::<A>(start, uuid, region, name, init, Recovery::Stop, cancel)
+    }
+
+    /// Like `with_token`, but continues after undecodable bytes as `recovery` says.
+    pub fn with_recovery<A: Architecture>(
+        start: u64,
+        uuid: &Uuid,
+        region: &Region,
+        name: Option<String>,
+        init: A::Configuration,
+        recovery: Recovery,
+        cancel: &CancellationToken,
+    ) -> Result<Function> {
+        Function::with_limits::<A>(start, uuid, region, name, init, recovery, &Limits::default(), cancel)
+    }
+
+    /// Like `with_recovery`, but stops early once one of `limits` is hit. The function is marked
+    /// as truncated in this case, see `truncated`.
+    pub fn with_limits<A: Architecture>(
+        start: u64,
+        uuid: &Uuid,
+        region: &Region,
+        name: Option<String>,
+        init: A::Configuration,
+        recovery: Recovery,
+        limits: &Limits,
+        cancel: &CancellationToken,
+    ) -> Result<Function> {
+        Function::build::<A>(start, uuid, region, name, init, recovery, limits, None, cancel)
+    }
+
+    fn build<A: Architecture>(
+        start: u64,
+        uuid: &Uuid,
+        region: &Region,
+        name: Option<String>,
+        init: A::Configuration,
+        recovery: Recovery,
+        limits: &Limits,
+        states: Option<&mut HashMap<u64, (u64, A::Configuration)>>,
+        cancel: &CancellationToken,
+    ) -> Result<Function> {
+        let mut cflow_graph = AdjacencyList::new();
+        let entry_point = ControlFlowTarget::Unresolved(Rvalue::new_u64(start));
+        cflow_graph.add_vertex(entry_point);
+        let mut size = 0;
+        let name_source = if name.is_some() { NameSource::Unknown } else { NameSource::Generated };
+        let name = name.unwrap_or(format!("func_{:#x}", start));
+        let uuid = uuid.clone();
+        event::emit(AnalysisEvent::FunctionDiscovered { uuid: uuid.clone(), start: start });
+        let mut truncated = None;
+        let entry_point = Self::disassemble::<A>(start, &mut cflow_graph, &mut size, &name, &uuid, region, init, recovery, limits, &mut truncated, states, cancel)?;
+        Ok(Function {
+            name,
+            name_source,
+            aliases: Vec::new(),
+            uuid,
+            cflow_graph,
+            entry_point,
+            region: region.name().clone(),
+            size,
+            kind: FunctionKind::Regular,
+            calling_convention: None,
+            string_refs: Vec::new(),
+            deferred: None,
+            truncated,
+            signature: None,
+        })
+    }
+
+    /// Like `new`, but only records mnemonics and control flow. The IL of each mnemonic is
+    /// dropped after decoding and generated again from a copy of `region` the first time it's
+    /// read, e.g. by `statements`. This saves memory for functions that are only listed, decoding
+    /// itself still runs the semantic functions once. The IL of calls is kept, so
+    /// `collect_calls` doesn't need to lift the function.
+    pub fn decode_only<A: Architecture + 'static>(start: u64, region: &Region, name: Option<String>, init: A::Configuration) -> Result<Function>
+    where
+        A::Configuration: 'static,
+    {
+        Function::decode_only_with_limits::<A>(start, &Uuid::new_v4(), region, name, init, Recovery::Stop, &Limits::default(), &CancellationToken::new())
+    }
+
+    /// Like `with_limits`, but the IL is dropped like in `decode_only`.
+    pub fn decode_only_with_limits<A: Architecture + 'static>(
+        start: u64,
+        uuid: &Uuid,
+        region: &Region,
+        name: Option<String>,
+        init: A::Configuration,
+        recovery: Recovery,
+        limits: &Limits,
+        cancel: &CancellationToken,
+    ) -> Result<Function>
+    where
+        A::Configuration: 'static,
+    {
+        let mut states = HashMap::new();
+        let mut ret = Function::build::<A>(start, uuid, region, name, init, recovery, limits, Some(&mut states), cancel)?;
+
+        if states.is_empty() {
+            return Ok(ret);
+        }
+
+        // mnemonics dropped, by the address decoding started at
+        let mut origins = HashMap::<u64, (A::Configuration, Vec<u64>)>::new();
+
+        for (address, (origin, cfg)) in states.into_iter() {
+            origins.entry(origin).or_insert((cfg, vec![])).1.push(address);
+        }
+
+        let region = region.clone();
+        let origins = Mutex::new(origins);
+        let source = move || -> Result<DeferredIl> {
+            let origins = match origins.lock() {
+                Ok(origins) => origins,
+                Err(_) => return Err("lifter state poisoned".into()),
+            };
+            let mut ret = DeferredIl::new();
+
+            for (&origin, &(ref cfg, ref addresses)) in origins.iter() {
+                let mnemonics = A::decode(&region, origin, cfg)?.mnemonics;
+
+                for &address in addresses.iter() {
+                    let il = mnemonics.iter().filter(|mne| mne.area.start == address).map(|mne| mne.instructions.clone()).collect::<Vec<_>>();
+
+                    if il.is_empty() {
+                        return Err(format!("decoding {:#x} again yields no mnemonic at {:#x}", origin, address).into());
+                    }
+                    ret.insert(address, il);
+                }
+            }
+
+            Ok(ret)
+        };
+
+        ret.deferred = Some(Deferred { source: Some(Arc::new(source)), il: OnceLock::new(), calls: false });
+        Ok(ret)
+    }
+
+    /// Returns the limit that stopped disassembly of this function early, if any. Truncated
+    /// functions have unresolved jump targets where decoding stopped.
+    pub fn truncated(&self) -> Option<Limit> {
+        self.truncated
+    }
+
+    /// Marks the function as truncated by `limit`, e.g. when a rebuilt copy replaces a function
+    /// that hit a limit before.
+    pub fn set_truncated(&mut self, limit: Option<Limit>) {
+        self.truncated = limit;
+    }
+
+    /// Returns true if the IL of every mnemonic is stored in the mnemonics, i.e. the function
+    /// wasn't created by `decode_only` or its IL was deferred and `lift` was called since.
+    pub fn is_lifted(&self) -> bool {
+        self.deferred.is_none()
+    }
+
+    /// Puts the IL dropped by `decode_only` or `defer` back into the mnemonics, producing it if
+    /// needed.
+    pub fn lift(&mut self) -> Result<()> {
+        let il = match self.deferred {
+            Some(ref d) => d.get().map_err(|e| format!("can't lift {}: {}", self.name, e))?.clone(),
+            None => return Ok(()),
+        };
+
+        self.deferred = None;
+        self.restore(il);
+        Ok(())
+    }
+
+    fn restore(&mut self, mut il: DeferredIl) {
+        for lb in self.cflow_graph.vertex_labels_mut() {
+            if let &mut ControlFlowTarget::Resolved(ref mut bb) = lb {
+                for mne in bb.mnemonics.iter_mut().filter(|mne| mne.instructions.is_empty()) {
+                    if let Some(stmts) = il.get_mut(&mne.area.start) {
+                        if !stmts.is_empty() {
+                            mne.instructions = stmts.remove(0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Deferred IL must be back in the mnemonics before they are changed.
+    fn lift_or_panic(&mut self) {
+        if let Err(e) = self.lift() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Drops the IL of all mnemonics, `source` produces it again the first time it's read. The
+    /// statements of each address returned by `source` must be in the order `basic_blocks`
+    /// visits the mnemonics starting there. Used to keep the IL of large projects on disk, see
+    /// `SpillCache`.
+    pub fn defer(&mut self, source: IlSource) -> Result<()> {
+        self.lift()?;
+
+        for lb in self.cflow_graph.vertex_labels_mut() {
+            if let &mut ControlFlowTarget::Resolved(ref mut bb) = lb {
+                for mne in bb.mnemonics.iter_mut() {
+                    mne.instructions = Vec::new();
+                }
+            }
+        }
+
+        self.deferred = Some(Deferred { source: Some(source), il: OnceLock::new(), calls: true });
+        Ok(())
+    }
+
+    /// Frees the deferred IL produced since the last call, it's produced again when read next.
+    /// Returns false if the function has no deferred IL that can be produced again.
+    pub fn unload(&mut self) -> bool {
+        match self.deferred {
+            Some(ref mut d) if d.source.is_some() => {
+                d.il = OnceLock::new();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of statements held in memory. Deferred IL that wasn't read yet isn't counted.
+    pub fn resident_statements(&self) -> usize {
+        let deferred = match self.deferred {
+            Some(ref d) => {
+                match d.il.get() {
+                    Some(&Ok(ref il)) => il.values().flat_map(|v| v.iter()).map(|v| v.len()).sum(),
+                    _ => 0,
+                }
+            }
+            None => 0,
+        };
+
+        self.basic_blocks().flat_map(|bb| bb.statements()).count() + deferred
     }
 
     /// Returns the UUID of this function
@@ -314,31 +938,215 @@ impl Function {
         &self.cflow_graph
     }
 
-    /// Adds `alias` to this functions known aliases
+    /// Adds `alias` to this functions known aliases, with an unknown source
     pub fn add_alias(&mut self, alias: String) {
-        self.aliases.push(alias)
+        self.add_alias_from(alias, NameSource::Unknown)
+    }
+
+    /// Adds `alias`, found in `source`, to this functions known aliases. Adding a known alias
+    /// again keeps the more trusted source.
+    pub fn add_alias_from(&mut self, alias: String, source: NameSource) {
+        if let Some(a) = self.aliases.iter_mut().find(|a| a.name == alias) {
+            a.source = ::std::cmp::max(a.source, source);
+            return;
+        }
+        self.aliases.push(Alias { name: alias, source: source })
+    }
+
+    /// Removes `alias` from this functions known aliases
+    pub fn remove_alias(&mut self, alias: &str) {
+        self.aliases.retain(|a| a.name != alias)
+    }
+
+    /// Returns where the functions name came from
+    pub fn name_source(&self) -> NameSource {
+        self.name_source
+    }
+
+    /// Records that the functions name came from `source`
+    pub fn set_name_source(&mut self, source: NameSource) {
+        self.name_source = source;
+    }
+
+    /// Adds `name` found in `source`. If `source` is trusted more than the source of the current
+    /// name, or is `NameSource::User`, `name` becomes the primary name and the current one an
+    /// alias. Otherwise `name` is added as alias. Returns true if the primary name changed.
+    pub fn propose_name(&mut self, name: String, source: NameSource) -> bool {
+        if name == self.name {
+            self.name_source = ::std::cmp::max(self.name_source, source);
+            false
+        } else if source > self.name_source || source == NameSource::User {
+            let (old, old_source) = (::std::mem::replace(&mut self.name, name.clone()), self.name_source);
+
+            self.remove_alias(&name);
+            self.name_source = source;
+            if old_source != NameSource::Generated {
+                self.add_alias_from(old, old_source);
+            }
+            true
+        } else {
+            self.add_alias_from(name, source);
+            false
+        }
     }
 
     /// Sets this function's plt stub entry at `plt_address`, as `name`. **Note** This will alter the function's kind from `Regular` to `Stub`, and will also change move its canonical name into aliases.
     pub fn set_plt(&mut self, name: &str, plt_address: u64) {
-        let old_name = self.name.clone();
-        self.aliases.push(old_name);
+        let (old_name, old_source) = (self.name.clone(), self.name_source);
+        self.aliases.push(Alias { name: old_name, source: old_source });
         self.name = format!("{}@plt", name);
+        self.name_source = NameSource::Import;
         self.kind = FunctionKind::Stub { name: name.to_string(), plt_address };
     }
 
+    /// Returns the demangled name of this function, or its name if it isn't mangled.
+    pub fn display_name(&self) -> String {
+        demangle(&self.name).unwrap_or_else(|| self.name.clone())
+    }
+
     /// Returns this functions FunctionKind
     pub fn kind(&self) -> &FunctionKind {
         &self.kind
     }
 
+    /// Changes this functions FunctionKind. Loaders and analyses use this to mark imports, library
+    /// code and thunks, see `Program::classify`.
+    pub fn set_kind(&mut self, kind: FunctionKind) {
+        self.kind = kind;
+    }
+
+    /// Returns the address jumped to if the function consists of a single unconditional jump and
+    /// nothing else.
+    pub fn thunk_target(&self) -> Option<u64> {
+        let entry = self.entry_point_ref();
+        let mne = match self.cflow_graph.vertex_label(entry) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) if bb.mnemonics.len() == 1 => &bb.mnemonics[0],
+            _ => return None,
+        };
+        let mut out = self.cflow_graph.out_edges(entry);
+
+        match (out.next(), out.next()) {
+            (Some(e), None) if mne.instructions.is_empty() && self.cflow_graph.edge_label(e) == Some(&Guard::True) => {
+                let target = match self.cflow_graph.vertex_label(self.cflow_graph.target(e)) {
+                    Some(&ControlFlowTarget::Resolved(ref bb)) => bb.area.start,
+                    Some(&ControlFlowTarget::Unresolved(Rvalue::Constant { value, .. })) => value,
+                    _ => return None,
+                };
+
+                if target != mne.area.end { Some(target) } else { None }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns this functions calling convention, if known
+    pub fn calling_convention(&self) -> Option<CallingConvention> {
+        self.calling_convention
+    }
+
+    /// Sets this functions calling convention. See the
+    /// [`calling_convention`](../calling_convention/index.html) module for inferring it.
+    pub fn set_calling_convention(&mut self, cc: Option<CallingConvention>) {
+        self.calling_convention = cc;
+    }
+
+    /// Returns the prototype of this function, if known
+    pub fn signature(&self) -> Option<&Signature> {
+        self.signature.as_ref()
+    }
+
+    /// Sets the prototype of this function, e.g. from debug information or user input. See
+    /// `signature::arguments` for annotating the calls of the function.
+    pub fn set_signature(&mut self, sig: Option<Signature>) {
+        self.signature = sig;
+    }
+
+    /// Returns the string literals referenced by this function, ordered by the referencing
+    /// mnemonic. Filled by [`strings::extract`](../strings/fn.extract.html).
+    pub fn string_refs(&self) -> &[StringRef] {
+        self.string_refs.as_slice()
+    }
+
+    /// Sets the string literals referenced by this function
+    pub fn set_string_refs(&mut self, refs: Vec<StringRef>) {
+        self.string_refs = refs;
+    }
+
+    /// Replaces every address `a` stored in the function, including IL constants, with `f(a)`.
+    /// Used when the image containing the function is moved.
+    pub fn move_addresses(&mut self, f: &Fn(u64) -> u64) {
+        // deferred IL is produced for the old addresses
+        self.lift_or_panic();
+
+        let mv = |rv: &mut Rvalue| if let &mut Rvalue::Constant { ref mut value, .. } = rv {
+            *value = f(*value);
+        };
+
+        for vx in self.cflow_graph.vertices().collect::<Vec<_>>() {
+            match self.cflow_graph.vertex_label_mut(vx) {
+                Some(&mut ControlFlowTarget::Resolved(ref mut bb)) => {
+                    bb.area = Bound::new(f(bb.area.start), f(bb.area.start) + (bb.area.end - bb.area.start));
+
+                    for mne in bb.mnemonics.iter_mut() {
+                        mne.area = Bound::new(f(mne.area.start), f(mne.area.start) + (mne.area.end - mne.area.start));
+                        for op in mne.operands.iter_mut() {
+                            mv(op);
+                        }
+                        for stmt in mne.instructions.iter_mut() {
+                            for op in stmt.op.operands_mut() {
+                                mv(op);
+                            }
+                        }
+                    }
+                }
+                Some(&mut ControlFlowTarget::Unresolved(ref mut rv)) => mv(rv),
+                Some(&mut ControlFlowTarget::Failed(ref mut addr, _)) => *addr = f(*addr),
+                None => {}
+            }
+        }
+
+        for r in self.string_refs.iter_mut() {
+            r.address = f(r.address);
+            r.string = f(r.string);
+        }
+        match self.kind {
+            FunctionKind::Stub { ref mut plt_address, .. } => *plt_address = f(*plt_address),
+            FunctionKind::Thunk { ref mut target } => *target = f(*target),
+            _ => {}
+        }
+    }
+
+    /// Returns a copy of this function starting at `start` instead, with UUID `uuid` and `name`.
+    /// Addresses inside the function are moved along, all others are kept. Used to reuse the
+    /// code of functions whose bytes are repeated elsewhere, see `cache`.
+    pub fn relocated(&self, start: u64, uuid: &Uuid, name: Option<String>) -> Function {
+        let (old, end) = (self.start(), self.end());
+        let mut ret = self.clone();
+
+        ret.uuid = uuid.clone();
+        ret.name_source = if name.is_some() { NameSource::Unknown } else { NameSource::Generated };
+        ret.name = name.unwrap_or(format!("func_{:#x}", start));
+        ret.aliases = Vec::new();
+        ret.kind = FunctionKind::Regular;
+        if start != old {
+            ret.move_addresses(&|a| if a >= old && a < end { a - old + start } else { a });
+        }
+        ret
+    }
+
     /// Returns this functions known name aliases (names pointing to the same start address)
-    pub fn aliases(&self) -> &[String] {
+    pub fn aliases(&self) -> Vec<String> {
+        self.aliases.iter().map(|a| a.name.clone()).collect()
+    }
+
+    /// Returns this functions known name aliases together with their sources
+    pub fn alias_entries(&self) -> &[Alias] {
         self.aliases.as_slice()
     }
 
     /// Returns a mutable reference to this functions control flow graph; **WARNING** this can cause instability if the entry point is not correctly updated
     pub fn cfg_mut(&mut self) -> &mut ControlFlowGraph {
+        self.lift_or_panic();
         &mut self.cflow_graph
     }
 
@@ -364,6 +1172,7 @@ impl Function {
 
     /// Returns a mutable reference to the BasicBlock entry point of this function.
     pub fn entry_point_mut(&mut self) -> &mut BasicBlock {
+        self.lift_or_panic();
         match self.cflow_graph.vertex_label_mut(self.entry_point).unwrap() {
             &mut ControlFlowTarget::Resolved(ref mut bb) => bb,
             _ => panic!("Function {} has an unresolved entry point - this is a bug!", self.name) // can't dump cfg here because borrowed mutable ;)
@@ -372,12 +1181,10 @@ impl Function {
 
     /// Whether this function is a leaf function or not (no outgoing calls)
     pub fn is_leaf(&self) -> bool {
-        for bb in self.basic_blocks() {
-            for statement in bb.statements() {
-                match statement {
-                    &Statement { op: Operation::Call(_), .. } => return false,
-                    _ => ()
-                }
+        for statement in self.call_statements() {
+            match statement {
+                &Statement { op: Operation::Call(_), .. } => return false,
+                _ => ()
             }
         }
         true
@@ -386,10 +1193,11 @@ impl Function {
     fn index_cflow_graph(
         g: &ControlFlowGraph,
         entry: u64,
-    ) -> (BTreeMap<u64, Vec<MnemonicOrError>>, HashMap<u64, Vec<(Rvalue, Guard)>>, HashMap<u64, Vec<(Rvalue, Guard)>>) {
+    ) -> (BTreeMap<u64, Vec<MnemonicOrError>>, HashMap<u64, Vec<(Rvalue, Guard)>>, HashMap<u64, Vec<(Rvalue, Guard)>>, HashMap<u64, String>) {
         let mut mnemonics = BTreeMap::new();
         let mut by_source = HashMap::<u64, Vec<(Rvalue, Guard)>>::new();
         let mut by_destination = HashMap::<u64, Vec<(Rvalue, Guard)>>::new();
+        let mut modes = HashMap::<u64, String>::new();
 
         by_destination.insert(entry, vec![(Rvalue::Undefined, Guard::always())]);
 
@@ -400,6 +1208,9 @@ impl Function {
 
                     for mne in &bb.mnemonics {
                         mnemonics.entry(mne.area.start).or_insert(Vec::new()).push(MnemonicOrError::Mnemonic(mne.clone()));
+                        if let Some(ref mode) = bb.mode {
+                            modes.insert(mne.area.start, mode.clone());
+                        }
 
                         if let Some(prev) = prev_mne {
                             by_source.entry(prev).or_insert(Vec::new()).push((Rvalue::new_u64(mne.area.start), Guard::always()));
@@ -486,15 +1297,17 @@ impl Function {
             }
         }
 
-        (mnemonics, by_source, by_destination)
+        (mnemonics, by_source, by_destination, modes)
     }
 
     fn assemble_cflow_graph(
         mut mnemonics: BTreeMap<u64, Vec<MnemonicOrError>>,
         by_source: HashMap<u64, Vec<(Rvalue, Guard)>>,
         by_destination: HashMap<u64, Vec<(Rvalue, Guard)>>,
+        modes: HashMap<u64, String>,
         start: u64,
-    ) -> ControlFlowGraph {
+        cancel: &CancellationToken,
+    ) -> Result<ControlFlowGraph> {
         let mut ret = ControlFlowGraph::new();
         let mut bblock = Vec::<Mnemonic>::new();
 
@@ -535,10 +1348,15 @@ impl Function {
                     // or the entry point does not point here
                     new_bb |= mne.area.start == start;
 
+                    // or the instruction set changes
+                    new_bb |= modes.get(&last_mne.area.start) != modes.get(&mne.area.start);
+
                     if new_bb {
-                        let bb = BasicBlock::from_vec(bblock.clone());
+                        // hand the mnemonics over instead of copying them and their IL
+                        let len = bblock.len();
+                        let mut bb = BasicBlock::from_vec(::std::mem::replace(&mut bblock, Vec::with_capacity(len)));
+                        bb.mode = modes.get(&bb.area.start).cloned();
 
-                        bblock.clear();
                         ret.add_vertex(ControlFlowTarget::Resolved(bb));
                     }
                 }
@@ -558,11 +1376,15 @@ impl Function {
 
         // last basic block
         if !bblock.is_empty() {
-            ret.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(bblock)));
+            let mut bb = BasicBlock::from_vec(bblock);
+            bb.mode = modes.get(&bb.area.start).cloned();
+            ret.add_vertex(ControlFlowTarget::Resolved(bb));
         }
 
         // connect basic blocks
         for (src_off, tgts) in by_source.iter() {
+            cancel.check()?;
+
             for &(ref tgt, ref gu) in tgts {
 
                 let from_bb = ret.vertices()
@@ -623,7 +1445,7 @@ impl Function {
             }
         }
 
-        ret
+        Ok(ret)
     }
 
     /// Returns an iterator over this functions `BasicBlock`s
@@ -634,12 +1456,10 @@ impl Function {
     /// Returns the address of every function this function calls
     pub fn collect_call_addresses(&self) -> Vec<u64> {
         let mut ret = Vec::new();
-        for bb in self.basic_blocks() {
-            for statement in bb.statements() {
-                match statement {
-                    &Statement { op: Operation::Call(Rvalue::Constant{ value, .. }), .. } => ret.push(value),
-                    _ => ()
-                }
+        for statement in self.call_statements() {
+            match statement {
+                &Statement { op: Operation::Call(Rvalue::Constant{ value, .. }), .. } => ret.push(value),
+                _ => ()
             }
         }
         debug!("collected calls: {:?}", ret);
@@ -649,12 +1469,10 @@ impl Function {
     /// Returns all call targets.
     pub fn collect_calls(&self) -> Vec<Rvalue> {
         let mut ret = Vec::new();
-        for bb in self.basic_blocks() {
-            for statement in bb.statements() {
-                match statement {
-                    &Statement { op: Operation::Call(ref t), .. } => ret.push(t.clone()),
-                    _ => ()
-                }
+        for statement in self.call_statements() {
+            match statement {
+                &Statement { op: Operation::Call(ref t), .. } => ret.push(t.clone()),
+                _ => ()
             }
         }
         debug!("collected calls: {:?}", ret);
@@ -688,9 +1506,59 @@ impl Function {
                 .collect()
     }
 
-    /// Return a boxed iterator over every statement in this function
+    /// Return a boxed iterator over every statement in this function. IL dropped by
+    /// `decode_only` or `defer` is produced the first time and kept until `unload` is called.
+    /// Panics if that fails, call `lift` first to handle the error.
     pub fn statements<'b>(&'b self) -> Box<Iterator<Item=&'b Statement> + 'b> {
-        Box::new(self.basic_blocks().map(|bb| bb.statements()).flat_map(|ss| ss))
+        Box::new(self.basic_blocks().flat_map(move |bb| self.mnemonics(bb)).flat_map(|(_, stmts)| stmts.iter()))
+    }
+
+    /// Returns the mnemonics of `bb`, a basic block of this function, together with their
+    /// statements. Produces deferred IL like `statements`.
+    pub fn mnemonics<'b>(&'b self, bb: &'b BasicBlock) -> Box<Iterator<Item=(&'b Mnemonic, &'b [Statement])> + 'b> {
+        let il = match self.deferred {
+            Some(ref d) => {
+                match d.get() {
+                    Ok(il) => il,
+                    Err(e) => panic!("can't lift {}: {}", self.name, e),
+                }
+            }
+            None => return Box::new(bb.mnemonics.iter().map(|mne| (mne, mne.instructions.as_slice()))),
+        };
+        // mnemonics without statements at the previous address, their IL is taken in order
+        let mut seen = (None, 0);
+
+        Box::new(
+            bb.mnemonics.iter().map(
+                move |mne| {
+                    if !mne.instructions.is_empty() {
+                        return (mne, mne.instructions.as_slice());
+                    }
+
+                    let n = if seen.0 == Some(mne.area.start) { seen.1 } else { 0 };
+
+                    seen = (Some(mne.area.start), n + 1);
+                    match il.get(&mne.area.start).and_then(|v| v.get(n)) {
+                        Some(stmts) => (mne, stmts.as_slice()),
+                        None => (mne, mne.instructions.as_slice()),
+                    }
+                }
+            )
+        )
+    }
+
+    // Statements that may be calls. Doesn't produce deferred IL if it has none.
+    fn call_statements<'b>(&'b self) -> Box<Iterator<Item=&'b Statement> + 'b> {
+        match self.deferred {
+            Some(ref d) if !d.calls => Box::new(self.basic_blocks().flat_map(|bb| bb.statements())),
+            _ => self.statements(),
+        }
+    }
+
+    /// Returns a boxed iterator over every Linux system call in this function. See the
+    /// [`syscall`](../syscall/index.html) module for how numbers are resolved.
+    pub fn syscalls<'b>(&'b self) -> Box<Iterator<Item=Syscall<'b>> + 'b> {
+        Box::new(self.basic_blocks().flat_map(move |bb| syscall::recognize_in(self.mnemonics(bb).collect())))
     }
 
     /// Returns the functions basic block graph in graphivz's DOT format. Useful for debugging.
@@ -791,6 +1659,84 @@ mod tests {
         }
     }
 
+    // Decodes one byte instructions until `0xff` switches to two byte instructions.
+    #[derive(Clone,Debug)]
+    enum TestArchModal {}
+    impl Architecture for TestArchModal {
+        type Token = u8;
+        type Configuration = bool;
+
+        fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+            unimplemented!()
+        }
+
+        fn decode(reg: &Region, addr: u64, wide: &Self::Configuration) -> Result<Match<Self>> {
+            let len = if *wide { 2 } else { 1 };
+            let tokens = reg.iter().seek(addr).take(len).map(|x| x.unwrap_or(0)).collect::<Vec<_>>();
+            let (opcode, next) = match (tokens[0], *wide) {
+                (0x01, _) => ("ret", None),
+                (0xff, false) => ("switch", Some(true)),
+                (_, w) => ("nop", Some(w)),
+            };
+            let mne = Mnemonic::new(addr..addr + len as u64, opcode.to_string(), "".to_string(), vec![].iter(), vec![].iter())?;
+            let jumps = if next.is_some() { vec![(addr, Rvalue::new_u64(addr + len as u64), Guard::always())] } else { vec![] };
+
+            Ok(Match { tokens: tokens, mnemonics: vec![mne], jumps: jumps, configuration: next.unwrap_or(*wide) })
+        }
+
+        fn mode(wide: &Self::Configuration, _: u64) -> Option<String> {
+            Some(if *wide { "wide" } else { "narrow" }.to_string())
+        }
+    }
+
+    #[test]
+    fn display_name() {
+        let reg = Region::undefined("ram".to_owned(), 100);
+        let f = Function::undefined(10, None, &reg, Some("_ZN3foo3barEPKc".to_owned()));
+        let g = Function::undefined(20, None, &reg, Some("main".to_owned()));
+
+        assert_eq!(f.name, "_ZN3foo3barEPKc");
+        assert_eq!(f.display_name(), "foo::bar(char const*)");
+        assert_eq!(g.display_name(), "main");
+    }
+
+    #[test]
+    fn name_sources() {
+        let reg = Region::undefined("ram".to_owned(), 100);
+        let mut f = Function::undefined(10, None, &reg, None);
+
+        assert_eq!(f.name_source(), NameSource::Generated);
+        assert!(f.propose_name("foo".to_string(), NameSource::Export));
+        assert!(f.aliases().is_empty());
+        assert!(f.propose_name("_Z3foov".to_string(), NameSource::Debug));
+        assert!(!f.propose_name("bar".to_string(), NameSource::Symbol));
+        assert!(!f.propose_name("foo".to_string(), NameSource::Symbol));
+        assert_eq!(f.name, "_Z3foov");
+        assert_eq!(
+            f.alias_entries(),
+            &[Alias { name: "foo".to_string(), source: NameSource::Symbol }, Alias { name: "bar".to_string(), source: NameSource::Symbol }]
+        );
+
+        // aliases without sources
+        #[derive(Serialize)]
+        struct Old {
+            aliases: Vec<String>,
+        }
+        #[derive(Deserialize)]
+        struct New {
+            #[serde(deserialize_with = "aliases")]
+            aliases: Vec<Alias>,
+        }
+
+        let old = ::serde_cbor::to_vec(&Old { aliases: vec!["foo".to_string()] }).unwrap();
+        let new = ::serde_cbor::from_slice::<New>(&old).unwrap();
+        assert_eq!(new.aliases, vec![Alias { name: "foo".to_string(), source: NameSource::Unknown }]);
+
+        let g = ::serde_cbor::from_slice::<Function>(&::serde_cbor::to_vec(&f).unwrap()).unwrap();
+        assert_eq!(g.alias_entries(), f.alias_entries());
+        assert_eq!(g.name_source(), NameSource::Debug);
+    }
+
     #[test]
     fn new() {
         let f = Function::undefined(100, None, &Region::undefined("ram".to_owned(), 100), Some("test".to_owned()));
@@ -838,13 +1784,13 @@ mod tests {
         cfg.add_edge(Guard::always(), vx1, vx2);
         cfg.add_edge(Guard::always(), vx2, vx0);
 
-        let (mnes, src, dest) = Function::index_cflow_graph(&cfg, 0);
+        let (mnes, src, dest, modes) = Function::index_cflow_graph(&cfg, 0);
 
         assert_eq!(mnes.len(), 9);
         assert_eq!(src.values().fold(0, |acc, x| acc + x.len()), 10);
         assert_eq!(dest.values().fold(0, |acc, x| acc + x.len()), 11); // because index_cflow_graph adds the start/entry value
 
-        let cfg_re = Function::assemble_cflow_graph(mnes, src, dest, 0);
+        let cfg_re = Function::assemble_cflow_graph(mnes, src, dest, modes, 0, &CancellationToken::new()).unwrap();
 
         assert_eq!(cfg_re.num_vertices(), 3);
         assert_eq!(cfg_re.num_edges(), 4);
@@ -900,13 +1846,13 @@ mod tests {
         cfg.add_edge(Guard::always(), vx3, vx0);
         cfg.add_edge(Guard::always(), vx4, vx3);
 
-        let (mnes, src, dest) = Function::index_cflow_graph(&cfg, 0);
+        let (mnes, src, dest, modes) = Function::index_cflow_graph(&cfg, 0);
 
         assert_eq!(mnes.len(), 2);
         assert_eq!(src.values().fold(0, |acc, x| acc + x.len()), 3);
         assert_eq!(dest.values().fold(0, |acc, x| acc + x.len()), 4); // because index_cflow_graph automatically adds the functions start entry
 
-        let cfg_re = Function::assemble_cflow_graph(mnes, src, dest, 0);
+        let cfg_re = Function::assemble_cflow_graph(mnes, src, dest, modes, 0, &CancellationToken::new()).unwrap();
 
         assert_eq!(cfg_re.num_vertices(), 4);
         assert_eq!(cfg_re.num_edges(), 3);
@@ -958,6 +1904,22 @@ mod tests {
         assert_eq!(func.name, "func_0x0".to_string());
     }
 
+    #[test]
+    fn cancelled() {
+        let main = new_disassembler!(TestArchShort =>
+            [ 0 ] = |st: &mut State<TestArchShort>| {
+                st.mnemonic(1,"A","",vec!(),&|_| { Ok(vec![]) }).unwrap();
+                true
+            }
+        );
+        let data = OpaqueLayer::wrap(vec![0]);
+        let reg = Region::new("".to_string(), data);
+        let cancel = CancellationToken::new();
+
+        cancel.cancel();
+        assert!(Function::with_token::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main, &cancel).is_err());
+    }
+
     #[test]
     fn continuous() {
         let main = new_disassembler!(TestArchShort =>
@@ -1040,6 +2002,47 @@ mod tests {
         assert!(func.cflow_graph.edge(bb_vx.unwrap(), ures_vx.unwrap()).is_some());
     }
 
+    /*
+     * 0: nop
+     * 1: ???
+     * 2: ???
+     * 3: nop
+     * 4: ret
+     */
+    #[test]
+    fn recovery() {
+        let main = new_disassembler!(TestArchShort =>
+            [ 0 ] = |st: &mut State<TestArchShort>| {
+                let next = st.address + 1;
+                st.mnemonic(1,"nop","",vec!(),&|_| { Ok(vec![]) }).unwrap();
+                st.jump(Rvalue::new_u64(next),Guard::always()).unwrap();
+                true
+            },
+            [ 1 ] = |st: &mut State<TestArchShort>| {
+                st.mnemonic(1,"ret","",vec!(),&|_| { Ok(vec![]) }).unwrap();
+                true
+            }
+        );
+        let reg = Region::new("".to_string(), OpaqueLayer::wrap(vec![0, 9, 9, 0, 1]));
+        let disassemble = |recovery| {
+            let func = Function::with_recovery::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main.clone(), recovery, &CancellationToken::new()).unwrap();
+            let mut bbs = func.basic_blocks().map(|bb| bb.area.clone()).collect::<Vec<_>>();
+            let mut failed = func.cflow_graph
+                .vertex_labels()
+                .filter_map(|lb| if let &ControlFlowTarget::Failed(pos, _) = lb { Some(pos) } else { None })
+                .collect::<Vec<_>>();
+
+            bbs.sort_by_key(|b| b.start);
+            failed.sort();
+            (bbs, failed, func.cflow_graph.num_edges())
+        };
+
+        assert_eq!(disassemble(Recovery::Stop), (vec![Bound::new(0, 1)], vec![1], 1));
+        assert_eq!(disassemble(Recovery::Skip { step: 1, limit: 4 }), (vec![Bound::new(0, 1), Bound::new(3, 5)], vec![1, 2], 3));
+        assert_eq!(disassemble(Recovery::Skip { step: 1, limit: 1 }), (vec![Bound::new(0, 1)], vec![1, 2], 2));
+        assert_eq!(disassemble(Recovery::Align { alignment: 2, limit: 4 }), (vec![Bound::new(0, 1), Bound::new(4, 5)], vec![1, 2], 3));
+    }
+
     #[test]
     fn branch() {
         let main = new_disassembler!(TestArchShort =>
@@ -1437,4 +2440,106 @@ mod tests {
         assert!(func.cflow_graph.edge(bb1_vx.unwrap(), bb2_vx.unwrap()).is_some());
         assert!(func.cflow_graph.edge(bb2_vx.unwrap(), bb01_vx.unwrap()).is_some());
     }
+
+    #[test]
+    fn lift_on_demand() {
+        let main = new_disassembler!(TestArchShort =>
+            [ 0 ] = |st: &mut State<TestArchShort>| {
+                let next = st.address + 1;
+                st.mnemonic(1,"A","",vec!(),&|_| { rreil!{ add a:32, b:32, c:32; } }).unwrap();
+                st.jump(Rvalue::new_u64(next),Guard::always()).unwrap();
+                true
+            },
+            [ 1 ] = |st: &mut State<TestArchShort>| {
+                st.mnemonic(1,"B","",vec!(),&|_| { rreil!{ sub a:32, b:32, c:32; mov d:32, a:32; } }).unwrap();
+                true
+            }
+        );
+        let data = OpaqueLayer::wrap(vec![0, 1]);
+        let reg = Region::new("".to_string(), data);
+        let eager = Function::new::<TestArchShort>(0, &reg, None, main.clone()).unwrap();
+        let mut func = Function::decode_only::<TestArchShort>(0, &reg, None, main.clone()).unwrap();
+
+        assert_eq!(func.basic_blocks().flat_map(|bb| bb.mnemonics.iter()).count(), 2);
+        assert!(!func.is_lifted());
+        assert_eq!(func.resident_statements(), 0);
+        assert_eq!(func.statements().collect::<Vec<_>>(), eager.statements().collect::<Vec<_>>());
+        assert_eq!(func.resident_statements(), 3);
+
+        let saved = ::serde_cbor::from_slice::<Function>(&::serde_cbor::to_vec(&func).unwrap()).unwrap();
+        assert_eq!(saved.statements().collect::<Vec<_>>(), eager.statements().collect::<Vec<_>>());
+
+        assert!(func.unload());
+        assert_eq!(func.resident_statements(), 0);
+        func.lift().unwrap();
+        assert!(func.is_lifted());
+        assert!(!func.unload());
+        assert_eq!(func.statements().collect::<Vec<_>>(), eager.statements().collect::<Vec<_>>());
+
+        let mut moved = Function::decode_only::<TestArchShort>(0, &reg, None, main).unwrap();
+        moved.move_addresses(&|a| a + 0x100);
+        assert!(moved.is_lifted());
+        assert_eq!(moved.statements().count(), 3);
+    }
+
+    #[test]
+    fn limits() {
+        let main = new_disassembler!(TestArchShort =>
+            [ 0 ] = |st: &mut State<TestArchShort>| {
+                let next = st.address + 2;
+                st.mnemonic(1,"jmp","",vec!(),&|_| { rreil!{ add a:32, b:32, c:32; } }).unwrap();
+                st.jump(Rvalue::new_u64(next),Guard::always()).unwrap();
+                true
+            },
+            [ 1 ] = |st: &mut State<TestArchShort>| {
+                st.mnemonic(1,"ret","",vec!(),&|_| { Ok(vec![]) }).unwrap();
+                true
+            }
+        );
+        let data = OpaqueLayer::wrap(vec![0, 9, 0, 9, 0, 9, 1]);
+        let reg = Region::new("".to_string(), data);
+        let cancel = CancellationToken::new();
+        let blocks = |f: &Function| f.basic_blocks().count();
+        let unlimited = Function::with_limits::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main.clone(), Recovery::Stop, &Limits::default(), &cancel).unwrap();
+
+        assert_eq!(unlimited.truncated(), None);
+        assert_eq!(blocks(&unlimited), 4);
+
+        let limits = Limits { basic_blocks: Some(2), ..Limits::default() };
+        let func = Function::with_limits::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main.clone(), Recovery::Stop, &limits, &cancel).unwrap();
+
+        assert_eq!(func.truncated(), Some(Limit::BasicBlocks));
+        assert_eq!(blocks(&func), 2);
+        assert!(func.cfg().vertex_labels().any(|lb| if let &ControlFlowTarget::Unresolved(Rvalue::Constant { value: 4, .. }) = lb { true } else { false }));
+
+        let limits = Limits { statements: Some(0), ..Limits::default() };
+        let mut func = Function::with_limits::<TestArchShort>(0, &Uuid::new_v4(), &reg, None, main.clone(), Recovery::Stop, &limits, &cancel).unwrap();
+
+        assert_eq!(func.truncated(), Some(Limit::Statements));
+        assert_eq!(blocks(&func), 1);
+
+        func.cont::<TestArchShort>(0, &reg, main).unwrap();
+        assert_eq!(func.truncated(), None);
+        assert_eq!(blocks(&func), 4);
+    }
+
+    #[test]
+    fn mode_switch() {
+        let data = OpaqueLayer::wrap(vec![0x00, 0xff, 0x00, 0x00, 0x01, 0x00]);
+        let reg = Region::new("".to_string(), data);
+        let func = Function::new::<TestArchModal>(0, &reg, None, false).unwrap();
+        let mut bbs = func.basic_blocks()
+            .map(|bb| (bb.area.clone(), bb.mode.clone(), bb.mnemonics.iter().map(|m| m.opcode.clone()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        bbs.sort_by_key(|x| x.0.start);
+        assert_eq!(
+            bbs,
+            vec![
+                (Bound::new(0, 2), Some("narrow".to_string()), vec!["nop".to_string(), "switch".to_string()]),
+                (Bound::new(2, 6), Some("wide".to_string()), vec!["nop".to_string(), "ret".to_string()]),
+            ]
+        );
+        assert_eq!(func.cflow_graph.num_edges(), 1);
+    }
 }
@@ -0,0 +1,277 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Gap analysis and speculative function discovery.
+//!
+//! Functions only reached through indirect calls are missed by the recursive disassembly. The
+//! executable bytes no function covers, the [`gaps`], are where they hide. [`propose`] looks for
+//! evidence of function starts in them and returns [`Proposal`]s with a confidence between 0
+//! and 1:
+//!
+//! - The bytes match a prologue typical for the architecture, like `push rbp; mov rbp, rsp` on
+//!   AMD64 or `addiu sp, sp, -n` on MIPS.
+//! - Known code calls the address, or uses it as a code pointer.
+//! - It is the first byte after the padding following a function.
+//!
+//! Each kind of [`Evidence`] has a weight, the confidence is the chance that not all of them are
+//! wrong. Proposals aren't disassembled until [`accept`]ed, by the user or by the analysis
+//! driver if their confidence is high enough.
+//!
+//! [`gaps`]: fn.gaps.html
+//! [`propose`]: fn.propose.html
+//! [`Proposal`]: struct.Proposal.html
+//! [`Evidence`]: enum.Evidence.html
+//! [`accept`]: fn.accept.html
+
+use {Bound, CallTarget, Endianess, Machine, Operation, PointerKind, Project, Rvalue, Statement};
+use panopticon_graph_algos::MutableGraphTrait;
+use search::Pattern;
+use std::collections::{BTreeMap, HashMap};
+use sweep;
+use uuid::Uuid;
+
+/// Bytes compilers pad between functions with.
+const PADDING: &'static [u8] = &[0x00, 0x90, 0xcc];
+
+/// Reason to believe a function starts at an address.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Evidence {
+    /// Matches the named prologue
+    Prologue(&'static str),
+    /// Called or used as code pointer this many times
+    Referenced(usize),
+    /// First byte of a gap after padding
+    GapStart,
+}
+
+impl Evidence {
+    /// Probability that the evidence is right.
+    pub fn weight(&self) -> f64 {
+        match *self {
+            Evidence::Prologue(_) => 0.6,
+            Evidence::Referenced(n) => 1. - 0.5f64.powi(n as i32),
+            Evidence::GapStart => 0.2,
+        }
+    }
+}
+
+/// Possible function entry point.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Proposal {
+    /// Address of the first instruction
+    pub address: u64,
+    /// Between 0 and 1
+    pub confidence: f64,
+    /// Why a function is thought to start here
+    pub evidence: Vec<Evidence>,
+}
+
+/// Function prologues of `machine` and their names.
+pub fn prologues(machine: Machine) -> Vec<(&'static str, Pattern)> {
+    let pats: Vec<(&'static str, &'static str)> = match machine {
+        Machine::Amd64 => vec![("push rbp; mov rbp, rsp", "55 48 89 e5"), ("endbr64", "f3 0f 1e fa"), ("sub rsp, imm8", "48 83 ec ??")],
+        Machine::Ia32 => vec![("push ebp; mov ebp, esp", "55 89 e5"), ("push ebp; mov ebp, esp", "55 8b ec"), ("endbr32", "f3 0f 1e fb")],
+        Machine::Arm => vec![("push {..., lr}", "?? ?? 2d e9")],
+        Machine::Mips(Endianess::Big) | Machine::Mips64(Endianess::Big) => vec![("addiu sp, sp, -n", "27 bd ff ??")],
+        Machine::Mips(Endianess::Little) | Machine::Mips64(Endianess::Little) => vec![("addiu sp, sp, -n", "?? ff bd 27")],
+        Machine::RiscV32(_) | Machine::RiscV64(_) => vec![("addi sp, sp, -n", "13 01 01 ??")],
+        Machine::Avr => vec![("push r28; push r29", "cf 93 df 93")],
+        Machine::M68k => vec![("link a6, #-n", "4e 56 ?? ??")],
+        Machine::SuperH(Endianess::Big, _) => vec![("mov.l r14, @-r15", "2f e6")],
+        Machine::SuperH(Endianess::Little, _) => vec![("mov.l r14, @-r15", "e6 2f")],
+        Machine::Mcs51 | Machine::Msp430(_) | Machine::Wasm => vec![],
+    };
+
+    pats.into_iter().filter_map(|(n, p)| Pattern::parse(p).ok().map(|p| (n, p))).collect()
+}
+
+/// Alignment of instructions on `machine`.
+fn alignment(machine: Machine) -> u64 {
+    match machine {
+        Machine::Arm | Machine::Mips(_) | Machine::Mips64(_) => 4,
+        Machine::Avr | Machine::M68k | Machine::Msp430(_) | Machine::SuperH(..) | Machine::RiscV32(_) | Machine::RiscV64(_) => 2,
+        Machine::Amd64 | Machine::Ia32 | Machine::Mcs51 | Machine::Wasm => 1,
+    }
+}
+
+/// Parts of the executable sections of `proj` not covered by a basic block, sorted.
+pub fn gaps(proj: &Project) -> Vec<Bound> {
+    let mut covered = vec![];
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            covered.extend(func.basic_blocks().map(|bb| bb.area.clone()));
+        }
+    }
+    covered.sort_by_key(|b| (b.start, b.end));
+
+    let mut ret = vec![];
+
+    for area in sweep::code_areas(proj) {
+        let mut pos = area.start;
+
+        for b in covered.iter().filter(|b| b.end > area.start && b.start < area.end) {
+            if b.start > pos {
+                ret.push(Bound::new(pos, b.start));
+            }
+            pos = ::std::cmp::max(pos, b.end);
+        }
+        if pos < area.end {
+            ret.push(Bound::new(pos, area.end));
+        }
+    }
+
+    ret
+}
+
+/// Constant call targets and code pointers used by the functions of `proj`, with the number of
+/// references.
+fn references(proj: &Project) -> HashMap<u64, usize> {
+    let mut ret = HashMap::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for bb in func.basic_blocks() {
+                for (mne, stmts) in func.mnemonics(bb) {
+                    for stmt in stmts.iter() {
+                        if let &Statement { op: Operation::Call(Rvalue::Constant { value, .. }), .. } = stmt {
+                            *ret.entry(value).or_insert(0) += 1;
+                        }
+                    }
+                    for (op, kind) in mne.operands.iter().zip(mne.pointers.iter()) {
+                        if let (&Rvalue::Constant { value, .. }, &Some(PointerKind::Code)) = (op, kind) {
+                            *ret.entry(value).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Proposes function entry points inside the [`gaps`](fn.gaps.html) of `proj`, which was
+/// compiled for `machine`. Most likely first.
+pub fn propose(proj: &Project, machine: Machine) -> Vec<Proposal> {
+    let prologues = prologues(machine);
+    let align = alignment(machine);
+    let refs = references(proj);
+    let known = sweep::function_starts(proj);
+    let mut evidence = BTreeMap::<u64, Vec<Evidence>>::new();
+
+    for gap in gaps(proj) {
+        let cells = proj.region().iter().seek(gap.start).take((gap.end - gap.start) as usize).collect::<Vec<_>>();
+        let first = cells.iter().position(|c| c.map(|b| !PADDING.contains(&b)).unwrap_or(true));
+
+        if let Some(first) = first {
+            let addr = gap.start + first as u64;
+
+            if first > 0 && cells[first].is_some() && addr % align == 0 {
+                evidence.entry(addr).or_insert_with(Vec::new).push(Evidence::GapStart);
+            }
+        }
+
+        for (i, _) in cells.iter().enumerate().filter(|&(i, _)| (gap.start + i as u64) % align == 0) {
+            for &(name, ref pat) in prologues.iter() {
+                if pat.matches(&cells[i..]) {
+                    evidence.entry(gap.start + i as u64).or_insert_with(Vec::new).push(Evidence::Prologue(name));
+                    break;
+                }
+            }
+        }
+
+        for (&addr, &n) in refs.iter().filter(|&(a, _)| gap.start <= *a && *a < gap.end) {
+            evidence.entry(addr).or_insert_with(Vec::new).push(Evidence::Referenced(n));
+        }
+    }
+
+    let mut ret = evidence
+        .into_iter()
+        .filter(|&(a, _)| !known.contains(&a))
+        .map(
+            |(a, ev)| {
+                let doubt = ev.iter().fold(1., |acc, e| acc * (1. - e.weight()));
+                Proposal { address: a, confidence: 1. - doubt, evidence: ev }
+            }
+        )
+        .collect::<Vec<_>>();
+
+    ret.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(::std::cmp::Ordering::Equal).then(a.address.cmp(&b.address)));
+    ret
+}
+
+/// Adds a function starting at `address` to the first program of `proj`. It's disassembled the
+/// next time the program is analyzed. Returns false if `proj` has no program.
+pub fn accept(proj: &mut Project, address: u64) -> bool {
+    match proj.code.first_mut() {
+        Some(prog) => {
+            prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(address), None, Uuid::new_v4()));
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Lvalue, Mnemonic, OpaqueLayer, Program, Region};
+
+    /*
+     * 0x00: nop; nop; lea rax, [0x10]
+     * 0x04: int3 padding
+     * 0x08: push rbp; mov rbp, rsp
+     * 0x10: endbr64
+     */
+    #[test]
+    fn proposals() {
+        let mut bytes = vec![0x90, 0x90, 0x48, 0x8d, 0xcc, 0xcc, 0xcc, 0xcc, 0x55, 0x48, 0x89, 0xe5, 0x90, 0xc3, 0x00, 0x00];
+        bytes.extend_from_slice(&[0xf3, 0x0f, 0x1e, 0xfa, 0xc3, 0x00, 0x00, 0x00]);
+
+        let reg = Region::new("RAM".to_string(), OpaqueLayer::wrap(bytes));
+        let lea = Statement { op: Operation::Move(Rvalue::new_u64(0x10)), assignee: Lvalue::Variable { name: "rax".into(), size: 64, subscript: None } };
+        let mut mne = Mnemonic::new(0..4, "lea".to_string(), "{u}".to_string(), vec![Rvalue::new_u64(0x10)].iter(), vec![lea].iter()).ok().unwrap();
+        mne.pointers = vec![Some(PointerKind::Code)];
+
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(0, None, &reg, None);
+        let mut prog = Program::new("prog");
+        let mut proj = Project::new("test".to_string(), reg.clone());
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        prog.insert(func);
+        proj.code.push(prog);
+
+        assert_eq!(gaps(&proj), vec![Bound::new(4, 0x18)]);
+
+        let props = propose(&proj, Machine::Amd64);
+
+        assert_eq!(props.iter().map(|p| p.address).collect::<Vec<_>>(), vec![0x10, 0x08]);
+        assert_eq!(props[0].evidence, vec![Evidence::Prologue("endbr64"), Evidence::Referenced(1)]);
+        assert!((props[0].confidence - 0.8).abs() < 1e-9);
+        assert_eq!(props[1].evidence, vec![Evidence::GapStart, Evidence::Prologue("push rbp; mov rbp, rsp")]);
+        assert!((props[1].confidence - 0.68).abs() < 1e-9);
+        assert_eq!(propose(&proj, Machine::Mcs51).iter().map(|p| p.address).collect::<Vec<_>>(), vec![0x10, 0x08]);
+
+        assert!(accept(&mut proj, 0x10));
+        assert_eq!(propose(&proj, Machine::Amd64).iter().map(|p| p.address).collect::<Vec<_>>(), vec![0x08]);
+    }
+}
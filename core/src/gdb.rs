@@ -0,0 +1,698 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Client for the GDB remote serial protocol.
+//!
+//! [`Client`] talks to `gdbserver`, QEMU's gdbstub, OpenOCD and other stubs implementing the
+//! protocol. It reads registers, memory, the memory map and the loaded libraries of the stopped
+//! target. Register names and sizes come from the target description, for stubs that don't
+//! send one the register layout of GDB for AMD64, IA-32 and ARM is used.
+//!
+//! [`Client::snapshot`] collects everything needed to line a project up with the live process.
+//! [`rebase`] moves the images of the project to the addresses they were loaded at, after that
+//! [`locate`] maps runtime addresses to functions. [`annotate`] leaves the register values as
+//! comment at the current instruction, [`import_memory`] copies memory of the process into the
+//! project. [`Snapshot::seeds`] returns the register values in the form `approximate` of the
+//! abstract interpretation crate takes as fixed values.
+//!
+//! [`Client`]: struct.Client.html
+//! [`Client::snapshot`]: struct.Client.html#method.snapshot
+//! [`Snapshot::seeds`]: struct.Snapshot.html#method.seeds
+//! [`rebase`]: fn.rebase.html
+//! [`locate`]: fn.locate.html
+//! [`annotate`]: fn.annotate.html
+//! [`import_memory`]: fn.import_memory.html
+
+use {Bound, Endianess, Layer, Location, Machine, Project, Result, Rvalue, image};
+use panopticon_graph_algos::MutableGraphTrait;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+/// Bytes requested with a single `m` packet.
+const MEMORY_CHUNK: usize = 0x800;
+
+/// Bytes requested with a single `qXfer` packet.
+const XFER_CHUNK: usize = 0xf00;
+
+/// Register of the target.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct RegisterValue {
+    /// Name used by GDB, e.g. `rax`
+    pub name: String,
+    /// Size in bits
+    pub size: usize,
+    /// Contents, None if the stub doesn't know it or it's larger than 64 bits
+    pub value: Option<u64>,
+}
+
+/// Part of the address space of the target.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct MemoryArea {
+    /// Addresses covered
+    pub area: Bound,
+    /// `ram`, `rom` or `flash`
+    pub kind: String,
+}
+
+/// Shared library loaded by the target.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Library {
+    /// Path of the file
+    pub name: String,
+    /// Difference between the addresses in the file and in memory. For libraries linked at
+    /// address zero this is the base address.
+    pub base: u64,
+}
+
+/// State of the stopped target.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct Snapshot {
+    /// All registers, in the order of the target description
+    pub registers: Vec<RegisterValue>,
+    /// Memory map, empty if the stub doesn't report one
+    pub memory_map: Vec<MemoryArea>,
+    /// Loaded shared libraries
+    pub libraries: Vec<Library>,
+    /// Offset the main executable was relocated by, None if the stub doesn't say
+    pub text_offset: Option<u64>,
+}
+
+impl Snapshot {
+    /// Returns the value of the program counter.
+    pub fn pc(&self) -> Option<u64> {
+        self.register("pc").or_else(|| self.register("rip")).or_else(|| self.register("eip"))
+    }
+
+    /// Returns the value of the register named `name`, ignoring case.
+    pub fn register(&self, name: &str) -> Option<u64> {
+        self.registers.iter().find(|r| r.name.eq_ignore_ascii_case(name)).and_then(|r| r.value)
+    }
+
+    /// Register values as constants, keyed by upper case name and size in bits like the IL
+    /// variables of the disassemblers.
+    pub fn seeds(&self) -> HashMap<(Cow<'static, str>, usize), Rvalue> {
+        self.registers
+            .iter()
+            .filter_map(|r| r.value.map(|v| ((Cow::Owned(r.name.to_uppercase()), r.size), Rvalue::Constant { value: v, size: r.size })))
+            .collect()
+    }
+}
+
+/// Connection to a GDB stub.
+pub struct Client<S: Read + Write> {
+    stream: S,
+    endianess: Endianess,
+    // name, size in bits and register number
+    layout: Option<Vec<(String, usize, usize)>>,
+}
+
+impl Client<TcpStream> {
+    /// Connects to the stub listening at `addr` and debugging a `machine` target.
+    pub fn connect<A: ToSocketAddrs>(addr: A, machine: Machine) -> Result<Client<TcpStream>> {
+        let stream = TcpStream::connect(addr)?;
+
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        Ok(Client::new(stream, machine))
+    }
+}
+
+impl<S: Read + Write> Client<S> {
+    /// Returns a client talking to a stub over `stream`.
+    pub fn new(stream: S, machine: Machine) -> Client<S> {
+        Client { stream: stream, endianess: machine.endianess(), layout: default_layout(machine) }
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        let mut b = [0u8];
+
+        match self.stream.read(&mut b)? {
+            0 => Err("gdb stub closed the connection".into()),
+            _ => Ok(b[0]),
+        }
+    }
+
+    /// Sends `packet` and returns the response. Empty responses mean the stub doesn't support
+    /// the request.
+    pub fn request(&mut self, packet: &str) -> Result<Vec<u8>> {
+        let checksum = packet.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+        let frame = format!("${}#{:02x}", packet, checksum);
+
+        for _ in 0..3 {
+            self.stream.write_all(frame.as_bytes())?;
+            self.stream.flush()?;
+
+            match self.read_byte()? {
+                b'+' => return self.response(),
+                b'-' => continue,
+                b => return Err(format!("gdb stub sent {:?} instead of an acknowledgement", b as char).into()),
+            }
+        }
+
+        Err(format!("gdb stub rejected {}", packet).into())
+    }
+
+    fn response(&mut self) -> Result<Vec<u8>> {
+        loop {
+            while self.read_byte()? != b'$' {}
+
+            let mut data = vec![];
+            let mut sum = 0u8;
+
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    b => {
+                        sum = sum.wrapping_add(b);
+                        data.push(b);
+                    }
+                }
+            }
+
+            let hi = self.read_byte()?;
+            let lo = self.read_byte()?;
+
+            if hex_digit(hi).map(|h| h << 4 | hex_digit(lo).unwrap_or(0)) == Some(sum) {
+                self.stream.write_all(b"+")?;
+                return Ok(decode(&data));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    /// Reads the object `object` named `annex` using `qXfer`. Returns None if not supported.
+    fn xfer(&mut self, object: &str, annex: &str) -> Result<Option<String>> {
+        let mut ret = vec![];
+
+        loop {
+            let resp = self.request(&format!("qXfer:{}:read:{}:{:x},{:x}", object, annex, ret.len(), XFER_CHUNK))?;
+
+            match resp.first() {
+                Some(&b'm') => ret.extend_from_slice(&resp[1..]),
+                Some(&b'l') => {
+                    ret.extend_from_slice(&resp[1..]);
+                    return Ok(Some(String::from_utf8_lossy(&ret).into_owned()));
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    /// Reads the register layout from the target description, including the files it includes.
+    fn describe(&mut self) -> Result<()> {
+        let mut layout = vec![];
+        let mut pending = vec!["target.xml".to_string()];
+        let mut num = 0;
+
+        // includes are expanded in place
+        while let Some(file) = pending.pop() {
+            let xml = match self.xfer("features", &file)? {
+                Some(xml) => xml,
+                None if file == "target.xml" => return Ok(()),
+                None => return Err(format!("gdb stub failed to send {}", file).into()),
+            };
+            let mut includes = vec![];
+
+            for tag in tags(&xml) {
+                if tag.starts_with("xi:include") {
+                    if let Some(href) = attribute(tag, "href") {
+                        includes.push(href);
+                    }
+                } else if tag.starts_with("reg ") {
+                    if !includes.is_empty() {
+                        break;
+                    }
+
+                    let name = attribute(tag, "name").unwrap_or_default();
+                    let size = attribute(tag, "bitsize").and_then(|s| s.parse().ok()).unwrap_or(0);
+
+                    num = attribute(tag, "regnum").and_then(|s| s.parse().ok()).unwrap_or(num);
+                    layout.push((name, size, num));
+                    num += 1;
+                }
+            }
+
+            pending.extend(includes.into_iter().rev());
+        }
+
+        layout.sort_by_key(|&(_, _, n)| n);
+        self.layout = Some(layout);
+        Ok(())
+    }
+
+    /// Reads all registers.
+    pub fn registers(&mut self) -> Result<Vec<RegisterValue>> {
+        self.describe()?;
+
+        let layout = match self.layout {
+            Some(ref l) => l.clone(),
+            None => return Err("gdb stub sent no target description".into()),
+        };
+        let resp = self.request("g")?;
+
+        if resp.first() == Some(&b'E') {
+            return Err(format!("gdb stub failed to read registers: {}", String::from_utf8_lossy(&resp)).into());
+        }
+
+        let mut pos = 0;
+        let mut ret = vec![];
+
+        for (name, size, _) in layout {
+            let digits = size / 4;
+
+            if pos + digits > resp.len() {
+                break;
+            }
+
+            let bytes = hex_bytes(&resp[pos..pos + digits]);
+            let value = match bytes {
+                Some(ref b) if size <= 64 => {
+                    let it: Box<Iterator<Item = &u8>> = match self.endianess {
+                        Endianess::Little => Box::new(b.iter().rev()),
+                        Endianess::Big => Box::new(b.iter()),
+                    };
+                    Some(it.fold(0u64, |acc, &x| acc << 8 | x as u64))
+                }
+                _ => None,
+            };
+
+            ret.push(RegisterValue { name: name, size: size, value: value });
+            pos += digits;
+        }
+
+        Ok(ret)
+    }
+
+    /// Reads `len` bytes starting at `address`. Stops early at unreadable memory.
+    pub fn read_memory(&mut self, address: u64, len: usize) -> Result<Vec<u8>> {
+        let mut ret = Vec::with_capacity(len);
+
+        while ret.len() < len {
+            let n = (len - ret.len()).min(MEMORY_CHUNK);
+            let resp = self.request(&format!("m{:x},{:x}", address + ret.len() as u64, n))?;
+
+            match hex_bytes(&resp) {
+                Some(ref bytes) if !bytes.is_empty() && resp.first() != Some(&b'E') => {
+                    ret.extend_from_slice(bytes);
+                    if bytes.len() < n {
+                        break;
+                    }
+                }
+                _ if ret.is_empty() => return Err(format!("gdb stub failed to read memory at {:#x}", address).into()),
+                _ => break,
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns the memory map. Empty if the stub doesn't send one.
+    pub fn memory_map(&mut self) -> Result<Vec<MemoryArea>> {
+        let xml = match self.xfer("memory-map", "")? {
+            Some(xml) => xml,
+            None => return Ok(vec![]),
+        };
+
+        Ok(
+            tags(&xml)
+                .filter(|t| t.starts_with("memory "))
+                .filter_map(
+                    |t| {
+                        let start = attribute(t, "start").and_then(|s| number(&s))?;
+                        let len = attribute(t, "length").and_then(|s| number(&s))?;
+
+                        Some(MemoryArea { area: Bound::new(start, start.saturating_add(len)), kind: attribute(t, "type").unwrap_or_default() })
+                    }
+                )
+                .collect()
+        )
+    }
+
+    /// Returns the loaded shared libraries. Empty if the stub doesn't report them.
+    pub fn libraries(&mut self) -> Result<Vec<Library>> {
+        if let Some(xml) = self.xfer("libraries-svr4", "")? {
+            return Ok(
+                tags(&xml)
+                    .filter(|t| t.starts_with("library "))
+                    .filter_map(|t| Some(Library { name: attribute(t, "name")?, base: attribute(t, "l_addr").and_then(|s| number(&s))? }))
+                    .collect()
+            );
+        }
+
+        let xml = match self.xfer("libraries", "")? {
+            Some(xml) => xml,
+            None => return Ok(vec![]),
+        };
+        let mut ret = vec![];
+        let mut name = None;
+
+        // <library name=".."><segment address=".."/></library>
+        for tag in tags(&xml) {
+            if tag.starts_with("library ") {
+                name = attribute(tag, "name");
+            } else if tag.starts_with("segment ") || tag.starts_with("section ") {
+                if let (Some(n), Some(a)) = (name.take(), attribute(tag, "address").and_then(|s| number(&s))) {
+                    ret.push(Library { name: n, base: a });
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns the offset the executable was relocated by, as reported by `qOffsets`.
+    pub fn text_offset(&mut self) -> Result<Option<u64>> {
+        let resp = String::from_utf8_lossy(&self.request("qOffsets")?).into_owned();
+
+        Ok(
+            resp.split(';')
+                .filter_map(
+                    |kv| {
+                        let mut kv = kv.splitn(2, '=');
+                        match (kv.next(), kv.next()) {
+                            (Some("Text"), Some(v)) | (Some("TextSeg"), Some(v)) => u64::from_str_radix(v, 16).ok(),
+                            _ => None,
+                        }
+                    }
+                )
+                .next()
+        )
+    }
+
+    /// Reads registers, memory map, libraries and relocation of the target.
+    pub fn snapshot(&mut self) -> Result<Snapshot> {
+        Ok(
+            Snapshot {
+                registers: self.registers()?,
+                memory_map: self.memory_map()?,
+                libraries: self.libraries()?,
+                text_offset: self.text_offset()?,
+            }
+        )
+    }
+}
+
+/// Register layout of GDB for stubs without target description.
+fn default_layout(machine: Machine) -> Option<Vec<(String, usize, usize)>> {
+    let regs: Vec<(&str, usize)> = match machine {
+        Machine::Amd64 => {
+            let mut regs = ["rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "rip"]
+                .iter()
+                .map(|&r| (r, 64))
+                .collect::<Vec<_>>();
+            regs.extend(["eflags", "cs", "ss", "ds", "es", "fs", "gs"].iter().map(|&r| (r, 32)));
+            regs
+        }
+        Machine::Ia32 => ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "eip", "eflags", "cs", "ss", "ds", "es", "fs", "gs"].iter().map(|&r| (r, 32)).collect(),
+        Machine::Arm => ["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr", "pc"].iter().map(|&r| (r, 32)).collect(),
+        _ => return None,
+    };
+
+    Some(regs.into_iter().enumerate().map(|(i, (n, s))| (n.to_string(), s, i)).collect())
+}
+
+/// Removes escapes and run-length encoding from a packet.
+fn decode(data: &[u8]) -> Vec<u8> {
+    let mut ret: Vec<u8> = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        match data[i] {
+            b'}' if i + 1 < data.len() => {
+                ret.push(data[i + 1] ^ 0x20);
+                i += 2;
+            }
+            b'*' if i + 1 < data.len() && !ret.is_empty() => {
+                let last = ret[ret.len() - 1];
+                let n = (data[i + 1] as usize).saturating_sub(29);
+
+                ret.extend((0..n).map(|_| last));
+                i += 2;
+            }
+            b => {
+                ret.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    ret
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Decodes hex pairs. None if a byte is unknown (`xx`) or not hex.
+fn hex_bytes(hex: &[u8]) -> Option<Vec<u8>> {
+    hex.chunks(2)
+        .map(
+            |c| match (c.get(0).and_then(|&b| hex_digit(b)), c.get(1).and_then(|&b| hex_digit(b))) {
+                (Some(hi), Some(lo)) => Some(hi << 4 | lo),
+                _ => None,
+            }
+        )
+        .collect()
+}
+
+fn number(s: &str) -> Option<u64> {
+    if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Contents of all XML tags, without the angle brackets.
+fn tags<'a>(xml: &'a str) -> Box<Iterator<Item = &'a str> + 'a> {
+    Box::new(
+        xml.split('<').skip(1).filter_map(|t| t.split('>').next()).map(
+            |t| {
+                let t = t.trim();
+                if t.ends_with('/') { t[..t.len() - 1].trim() } else { t }
+            }
+        )
+    )
+}
+
+/// Value of the attribute `name` of `tag`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().last();
+
+        rest = &rest[pos + name.len()..];
+        if before.map(|c| c.is_whitespace()).unwrap_or(false) && rest.starts_with('=') {
+            let quote = rest[1..].chars().next()?;
+            let value = &rest[2..];
+
+            return value.find(quote).map(|end| value[..end].to_string());
+        }
+    }
+
+    None
+}
+
+/// Moves the images of `proj` to the addresses the target loaded them at. The main executable
+/// is moved by `Snapshot::text_offset`, libraries of the same file name by their load address.
+/// Returns the number of images moved.
+pub fn rebase(proj: &mut Project, snapshot: &Snapshot) -> Result<usize> {
+    let mut moves = vec![];
+    let main = proj.images.first().map(|i| i.name.clone()).unwrap_or_else(|| proj.name.clone());
+
+    if let Some(offset) = snapshot.text_offset {
+        if offset != 0 {
+            let area = image::extent(proj, &main)?;
+            moves.push((main.clone(), area.start.wrapping_add(offset)));
+        }
+    }
+
+    for lib in snapshot.libraries.iter() {
+        let file = Path::new(&lib.name).file_name().map(|f| f.to_string_lossy().into_owned());
+
+        if let Some(img) = proj.images.iter().find(|i| Some(&i.name) == file.as_ref() && i.name != main) {
+            if lib.base != 0 && lib.base != img.base {
+                moves.push((img.name.clone(), lib.base));
+            }
+        }
+    }
+
+    for &(ref name, base) in moves.iter() {
+        image::rebase(proj, name, base)?;
+    }
+
+    Ok(moves.len())
+}
+
+/// Returns `address` as function name plus offset, e.g. `main+0x12`, or the name of the import
+/// at `address`.
+pub fn locate(proj: &Project, address: u64) -> Option<String> {
+    for prog in proj.code.iter() {
+        if let Some(f) = prog.find_function_by(|f| f.contains(address)) {
+            return Some(
+                if f.start() == address {
+                    f.display_name()
+                } else {
+                    format!("{}+{:#x}", f.display_name(), address.wrapping_sub(f.start()))
+                }
+            );
+        }
+        if let Some(name) = prog.imports.get(&address) {
+            return Some(name.clone());
+        }
+    }
+
+    proj.imports.get(&address).cloned()
+}
+
+/// Adds the register values of `snapshot` as comment at the program counter. Values pointing
+/// into functions are followed by the function. Returns the address commented, None if the
+/// program counter is unknown.
+pub fn annotate(proj: &mut Project, snapshot: &Snapshot) -> Option<u64> {
+    let pc = snapshot.pc()?;
+    let regs = snapshot.registers
+        .iter()
+        .filter_map(
+            |r| {
+                let v = r.value?;
+
+                Some(
+                    match locate(proj, v) {
+                        Some(sym) => format!("{}={:#x} <{}>", r.name, v, sym),
+                        None => format!("{}={:#x}", r.name, v),
+                    }
+                )
+            }
+        )
+        .collect::<Vec<_>>();
+
+    proj.annotations.set_auto_comment(Location::Address(pc), format!("live: {}", regs.join(", ")));
+    Some(pc)
+}
+
+/// Copies the memory of the target at `area` into the root region of `proj`, replacing the
+/// contents of the file. Returns the number of bytes copied.
+pub fn import_memory<S: Read + Write>(proj: &mut Project, client: &mut Client<S>, area: Bound) -> Result<usize> {
+    let bytes = client.read_memory(area.start, (area.end - area.start) as usize)?;
+    let len = bytes.len();
+    let root = proj.data.root;
+
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let ok = match proj.data.dependencies.vertex_label_mut(root) {
+        Some(region) => region.cover(Bound::new(area.start, area.start + len as u64), Layer::wrap(bytes)),
+        None => false,
+    };
+
+    if ok { Ok(len) } else { Err(format!("{:?} is outside the project", area).into()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Function, Program, Region};
+    use std::io::{self, Cursor};
+
+    /// Stub replaying canned responses.
+    struct Replay {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Replay {
+        fn new(responses: &[&str]) -> Replay {
+            let mut input = vec![];
+
+            for r in responses {
+                let sum = r.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+                input.extend(format!("+${}#{:02x}", r, sum).into_bytes());
+            }
+            Replay { input: Cursor::new(input), output: vec![] }
+        }
+    }
+
+    impl Read for Replay {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Replay {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn packets() {
+        assert_eq!(decode(b"0* "), b"0000".to_vec());
+        assert_eq!(decode(b"}]x"), b"}x".to_vec());
+        assert_eq!(attribute("reg name=\"rip\" bitsize='64' type=\"code_ptr\"", "bitsize"), Some("64".to_string()));
+        assert_eq!(attribute("reg regname=\"x\" name=\"pc\"", "name"), Some("pc".to_string()));
+
+        let mut client = Client::new(Replay::new(&["OK"]), Machine::Amd64);
+
+        assert_eq!(client.request("vCont?").unwrap(), b"OK".to_vec());
+        assert_eq!(client.stream.output, b"$vCont?#49+".to_vec());
+    }
+
+    #[test]
+    fn live_project() {
+        let target = "l<target><architecture>i386:x86-64</architecture><xi:include href=\"core.xml\"/></target>";
+        let core = "l<feature><reg name=\"rax\" bitsize=\"64\"/><reg name=\"rip\" bitsize=\"64\" regnum=\"1\"/><reg name=\"xmm0\" bitsize=\"128\"/></feature>";
+        let regs = "100000000000000005010*(ff";
+        let client = Replay::new(&[target, core, regs, "", "l<library-list-svr4 version=\"1.0\"/>", "Text=1000;Data=1000;Bss=1000", "c3c3"]);
+        let mut client = Client::new(client, Machine::Amd64);
+        let snap = client.snapshot().unwrap();
+
+        assert_eq!(
+            snap.registers,
+            vec![
+                RegisterValue { name: "rax".to_string(), size: 64, value: Some(0x10) },
+                RegisterValue { name: "rip".to_string(), size: 64, value: Some(0x105) },
+            ]
+        );
+        assert_eq!(snap.pc(), Some(0x105));
+        assert_eq!(snap.text_offset, Some(0x1000));
+        assert_eq!(snap.seeds().get(&(Cow::Borrowed("RIP"), 64)), Some(&Rvalue::new_u64(0x105)));
+
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x2000));
+        let mut prog = Program::new("prog");
+        let func = Function::undefined(0x100, None, proj.region(), Some("main".to_string()));
+
+        prog.insert(func);
+        proj.code.push(prog);
+
+        assert_eq!(import_memory(&mut proj, &mut client, Bound::new(0x100, 0x102)).unwrap(), 2);
+        let mut bytes = proj.region().iter().seek(0x100);
+        assert_eq!((bytes.next(), bytes.next()), (Some(Some(0xc3)), Some(Some(0xc3))));
+        assert_eq!(annotate(&mut proj, &snap), Some(0x105));
+        assert_eq!(proj.annotations.auto_comment(&Location::Address(0x105)), Some("live: rax=0x10, rip=0x105"));
+    }
+}
@@ -0,0 +1,335 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Golden file tests for disassemblers.
+//!
+//! A golden file lists byte sequences together with what the disassembler is expected to make
+//! of them. [`run`] decodes each one with an `Architecture` and fails with a diff of every case
+//! whose output changed, so an architecture crate needs a single test function for any number of
+//! encodings:
+//!
+//! ```ignore
+//! #[test]
+//! fn golden() {
+//!     golden::run::<Avr>(Path::new("tests/golden/avr.txt"), Mcu::atmega88()).unwrap();
+//! }
+//! ```
+//!
+//! Cases are separated by blank lines. The first line of a case holds the bytes in hex,
+//! optionally followed by `@` and the address they are decoded at. The expected output follows,
+//! indented by two spaces: each mnemonic with its operands and, indented further, its IL, then
+//! the jumps. Lines starting with `#` are comments.
+//!
+//! ```text
+//! # pop r16
+//! 0f 91 @ 0x100
+//!   pop r16
+//!     ...
+//!   -> 0x102 if true
+//! ```
+//!
+//! Writing the expected output by hand is tedious. With the environment variable
+//! [`BLESS_VARIABLE`] set, `run` replaces it with the actual output instead of comparing. A
+//! new case only needs the line with its bytes.
+//!
+//! [`run`]: fn.run.html
+//! [`BLESS_VARIABLE`]: constant.BLESS_VARIABLE.html
+
+use {Architecture, Bound, Layer, Mnemonic, MnemonicFormatToken, Region, Result, Rvalue};
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// If set, `run` rewrites golden files instead of checking them.
+pub const BLESS_VARIABLE: &'static str = "PANOPTICON_BLESS";
+
+/// Single byte sequence of a golden file.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Case {
+    /// Comment lines before the case, without `#`
+    pub comments: Vec<String>,
+    /// Address the bytes are decoded at
+    pub address: u64,
+    /// Machine code
+    pub bytes: Vec<u8>,
+    /// Output lines without the first two spaces of indentation
+    pub expected: Vec<String>,
+}
+
+/// Case whose output differs from the golden file.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Mismatch {
+    /// Case as written in the golden file
+    pub case: Case,
+    /// Output of the disassembler
+    pub actual: Vec<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} @ {:#x}:", hex(&self.case.bytes), self.case.address)?;
+        for l in self.case.expected.iter().filter(|l| !self.actual.contains(l)) {
+            writeln!(f, "- {}", l)?;
+        }
+        for l in self.actual.iter().filter(|l| !self.case.expected.contains(l)) {
+            writeln!(f, "+ {}", l)?;
+        }
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// Parses the cases of a golden file.
+pub fn parse(text: &str) -> Result<Vec<Case>> {
+    let mut ret = vec![];
+    let mut comments = vec![];
+    let mut case: Option<Case> = None;
+
+    for (no, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            ret.extend(case.take());
+        } else if trimmed.starts_with('#') {
+            comments.push(trimmed[1..].trim().to_string());
+        } else if line.starts_with(' ') {
+            match case {
+                Some(ref mut c) => c.expected.push(if line.starts_with("  ") { line[2..].trim_end_matches(' ') } else { trimmed }.to_string()),
+                None => return Err(format!("line {}: output without bytes", no + 1).into()),
+            }
+        } else {
+            ret.extend(case.take());
+
+            let mut parts = trimmed.splitn(2, '@');
+            let bytes = parts
+                .next()
+                .unwrap_or("")
+                .split_whitespace()
+                .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("line {}: invalid byte {}", no + 1, b)))
+                .collect::<::std::result::Result<Vec<_>, _>>()?;
+            let address = match parts.next().map(|a| a.trim()) {
+                Some(a) if a.starts_with("0x") => u64::from_str_radix(&a[2..], 16).map_err(|_| format!("line {}: invalid address {}", no + 1, a))?,
+                Some(a) => a.parse::<u64>().map_err(|_| format!("line {}: invalid address {}", no + 1, a))?,
+                None => 0,
+            };
+
+            if bytes.is_empty() {
+                return Err(format!("line {}: case without bytes", no + 1).into());
+            }
+
+            case = Some(Case { comments: comments.drain(..).collect(), address: address, bytes: bytes, expected: vec![] });
+        }
+    }
+
+    ret.extend(case.take());
+    Ok(ret)
+}
+
+/// Writes `cases` in golden file syntax.
+pub fn print(cases: &[Case]) -> String {
+    let mut ret = String::new();
+
+    for (i, c) in cases.iter().enumerate() {
+        if i > 0 {
+            ret.push('\n');
+        }
+        for l in c.comments.iter() {
+            ret.push_str(&format!("# {}\n", l));
+        }
+        if c.address == 0 {
+            ret.push_str(&format!("{}\n", hex(&c.bytes)));
+        } else {
+            ret.push_str(&format!("{} @ {:#x}\n", hex(&c.bytes), c.address));
+        }
+        for l in c.expected.iter() {
+            ret.push_str(&format!("  {}\n", l));
+        }
+    }
+
+    ret
+}
+
+/// Renders the operands of a mnemonic as its format string says.
+fn operands(mne: &Mnemonic) -> String {
+    let mut ops = mne.operands.iter();
+    let mut ret = String::new();
+
+    for tok in mne.format_string.iter() {
+        let (signed, op) = match tok {
+            &MnemonicFormatToken::Literal(c) => {
+                ret.push(c);
+                continue;
+            }
+            &MnemonicFormatToken::Variable { has_sign } => (has_sign, ops.next()),
+            &MnemonicFormatToken::Pointer { .. } => (false, ops.next()),
+        };
+
+        match op {
+            Some(&Rvalue::Constant { value, size }) => {
+                let value = if size > 0 && size < 64 { value & ((1u64 << size) - 1) } else { value };
+                let sign = if size > 0 && size < 64 { 1u64 << (size - 1) } else { 1u64 << 63 };
+
+                if signed && value & sign != 0 {
+                    ret.push_str(&format!("-{:#x}", (sign << 1).wrapping_sub(value)));
+                } else {
+                    ret.push_str(&format!("{:#x}", value));
+                }
+            }
+            Some(&Rvalue::Variable { ref name, .. }) => ret.push_str(&name.to_lowercase()),
+            Some(&Rvalue::Undefined) | None => ret.push('?'),
+        }
+    }
+
+    ret
+}
+
+/// Decodes `bytes` at `address` with `A` and renders the result as lines of a golden file.
+pub fn render<A: Architecture>(bytes: &[u8], address: u64, cfg: &A::Configuration) -> Vec<String> {
+    let mut reg = Region::undefined("golden".to_string(), address + bytes.len() as u64);
+
+    reg.cover(Bound::new(address, address + bytes.len() as u64), Layer::wrap(bytes.to_vec()));
+
+    match A::decode(&reg, address, cfg) {
+        Ok(m) => {
+            let mut ret = vec![];
+
+            for mne in m.mnemonics.iter() {
+                let ops = operands(mne);
+
+                ret.push(if ops.is_empty() { mne.opcode.clone() } else { format!("{} {}", mne.opcode, ops) });
+                ret.extend(mne.instructions.iter().map(|s| format!("  {}", s)));
+            }
+            for &(_, ref tgt, ref guard) in m.jumps.iter() {
+                match tgt {
+                    &Rvalue::Constant { value, .. } => ret.push(format!("-> {:#x} if {}", value, guard)),
+                    tgt => ret.push(format!("-> {} if {}", tgt, guard)),
+                }
+            }
+
+            ret
+        }
+        Err(e) => vec![format!("error: {}", e)],
+    }
+}
+
+/// Decodes all cases in `text` and returns those whose output changed.
+pub fn check<A: Architecture>(text: &str, cfg: &A::Configuration) -> Result<Vec<Mismatch>> {
+    Ok(
+        parse(text)?
+            .into_iter()
+            .filter_map(
+                |c| {
+                    let actual = render::<A>(&c.bytes, c.address, cfg);
+                    if actual != c.expected { Some(Mismatch { case: c, actual: actual }) } else { None }
+                }
+            )
+            .collect()
+    )
+}
+
+/// Replaces the expected output of all cases in `text` with the actual one.
+pub fn bless<A: Architecture>(text: &str, cfg: &A::Configuration) -> Result<String> {
+    let mut cases = parse(text)?;
+
+    for c in cases.iter_mut() {
+        c.expected = render::<A>(&c.bytes, c.address, cfg);
+    }
+
+    Ok(print(&cases))
+}
+
+/// Checks the golden file at `path`, or rewrites it if `BLESS_VARIABLE` is set. Fails with the
+/// differences of all mismatched cases.
+pub fn run<A: Architecture>(path: &Path, cfg: A::Configuration) -> Result<()> {
+    let mut text = String::new();
+
+    File::open(path)?.read_to_string(&mut text)?;
+
+    if env::var_os(BLESS_VARIABLE).is_some() {
+        let blessed = bless::<A>(&text, &cfg)?;
+        File::create(path)?.write_all(blessed.as_bytes())?;
+        return Ok(());
+    }
+
+    let mismatches = check::<A>(&text, &cfg)?;
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        let diff = mismatches.iter().map(|m| m.to_string()).collect::<Vec<_>>().join("\n");
+        Err(format!("{} of {} cases in {} differ:\n{}", mismatches.len(), parse(&text)?.len(), path.display(), diff).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bless, check, parse};
+    use {Architecture, Guard, Lvalue, Match, Mnemonic, Operation, Region, Result, Rvalue, Statement};
+
+    /// 0x90: nop, 0x6a x: push x
+    #[derive(Clone,Debug)]
+    enum TestArch {}
+    impl Architecture for TestArch {
+        type Token = u8;
+        type Configuration = ();
+
+        fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+            unimplemented!()
+        }
+
+        fn decode(reg: &Region, addr: u64, _: &Self::Configuration) -> Result<Match<Self>> {
+            let bytes = reg.iter().seek(addr).take(2).map(|x| x.unwrap_or(0)).collect::<Vec<_>>();
+            let (mne, len) = match bytes[0] {
+                0x90 => (Mnemonic::new(addr..addr + 1, "nop".to_string(), "".to_string(), vec![].iter(), vec![].iter())?, 1),
+                0x6a => {
+                    let op = Rvalue::new_u8(bytes[1]);
+                    let stmt = Statement { op: Operation::Move(op.clone()), assignee: Lvalue::Variable { name: "t".into(), size: 8, subscript: None } };
+                    (Mnemonic::new(addr..addr + 2, "push".to_string(), "{s}".to_string(), vec![op].iter(), vec![stmt].iter())?, 2)
+                }
+                _ => return Err("unknown opcode".into()),
+            };
+
+            Ok(Match { tokens: bytes, mnemonics: vec![mne], jumps: vec![(addr, Rvalue::new_u64(addr + len), Guard::always())], configuration: () })
+        }
+    }
+
+    #[test]
+    fn bless_and_check() {
+        let text = "# no operation\n90\n\n6a ff @ 0x10\n\n6a 05\n  push 0x4\n\nff\n";
+        let blessed = bless::<TestArch>(text, &()).unwrap();
+
+        assert_eq!(
+            blessed,
+            "# no operation\n90\n  nop\n  -> 0x1 if true\n\n6a ff @ 0x10\n  push -0x1\n    mov t:8, 0xff:8\n  -> 0x12 if true\n\n6a 05\n  push 0x5\n    mov t:8, 0x5:8\n  -> 0x2 if true\n\nff\n  error: unknown opcode\n"
+        );
+        assert!(check::<TestArch>(&blessed, &()).unwrap().is_empty());
+
+        let mismatches = check::<TestArch>(text, &()).unwrap();
+
+        assert_eq!(mismatches.len(), 4);
+        assert_eq!(mismatches[2].case.expected, vec!["push 0x4".to_string()]);
+        assert_eq!(format!("{}", mismatches[2]), "6a 05 @ 0x0:\n- push 0x4\n+ push 0x5\n+   mov t:8, 0x5:8\n+ -> 0x2 if true\n");
+        assert!(parse("  nop\n").is_err());
+        assert!(parse("6x\n").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Exploit mitigations used by an executable.
+//!
+//! The loaders fill in the properties found in the file headers: a non-executable stack
+//! (`PT_GNU_STACK`, `IMAGE_DLLCHARACTERISTICS_NX_COMPAT`), position independence (`ET_DYN`,
+//! `IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE`), ELF RELRO and Windows Control Flow Guard.
+//!
+//! [`analyze`] adds what only the code shows. Functions protected by stack canaries call the
+//! failure handler, `__stack_chk_fail` with GCC and Clang, `__security_check_cookie` with MSVC,
+//! either directly, through an import slot or through a PLT stub. Functions compiled with
+//! Intel CET branch tracking start with an `endbr32`/`endbr64` instruction.
+//!
+//! [`analyze`]: fn.analyze.html
+
+use {CallTarget, ControlFlowTarget, Function, Project, Rvalue};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Functions called when a stack canary was overwritten.
+pub const CANARY_FAILURE_HANDLERS: &'static [&'static str] = &["__stack_chk_fail", "__stack_chk_fail_local", "__security_check_cookie"];
+
+/// ELF relocation read-only protection.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Relro {
+    /// No `PT_GNU_RELRO` segment
+    None,
+    /// Relocated data is read-only after loading, the GOT entries of lazily bound functions are
+    /// not
+    Partial,
+    /// All imports are bound at load time, the whole GOT is read-only
+    Full,
+}
+
+/// Mitigations of an executable.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct HardeningReport {
+    /// Stack and heap are not executable. None if the file format doesn't say.
+    pub nx: Option<bool>,
+    /// Code can be loaded at a random address. None if the file format doesn't say.
+    pub pie: Option<bool>,
+    /// ELF only
+    pub relro: Option<Relro>,
+    /// Windows Control Flow Guard checks indirect calls
+    pub control_flow_guard: bool,
+    /// Starts of the functions calling a stack canary failure handler
+    pub canary_functions: Vec<u64>,
+    /// Starts of the functions beginning with an `endbr32` or `endbr64` instruction
+    pub endbr_functions: Vec<u64>,
+}
+
+impl HardeningReport {
+    /// Returns an empty report.
+    pub fn new() -> HardeningReport {
+        HardeningReport::default()
+    }
+
+    /// True if any function is protected by a stack canary.
+    pub fn stack_canary(&self) -> bool {
+        !self.canary_functions.is_empty()
+    }
+
+    /// True if indirect branches are checked by Control Flow Guard or CET.
+    pub fn cfi(&self) -> bool {
+        self.control_flow_guard || !self.endbr_functions.is_empty()
+    }
+}
+
+impl fmt::Display for HardeningReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flag = |b: Option<bool>| match b {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "n/a",
+        };
+
+        write!(f, "NX: {}, PIE: {}, ", flag(self.nx), flag(self.pie))?;
+        match self.relro {
+            Some(Relro::Full) => write!(f, "RELRO: full, ")?,
+            Some(Relro::Partial) => write!(f, "RELRO: partial, ")?,
+            Some(Relro::None) => write!(f, "RELRO: no, ")?,
+            None => write!(f, "RELRO: n/a, ")?,
+        }
+        write!(
+            f,
+            "canary: {} ({} functions), CFI: {} ({} endbr)",
+            flag(Some(self.stack_canary())),
+            self.canary_functions.len(),
+            flag(Some(self.cfi())),
+            self.endbr_functions.len()
+        )
+    }
+}
+
+/// Returns true if the symbol `name` is a stack canary failure handler. Symbol versions and
+/// fastcall decorations are ignored.
+fn is_canary_handler(name: &str) -> bool {
+    match name.split('@').find(|s| !s.is_empty()) {
+        Some(name) => CANARY_FAILURE_HANDLERS.contains(&name),
+        None => false,
+    }
+}
+
+/// Returns true if the entry point of `func` has been disassembled.
+fn is_resolved(func: &Function) -> bool {
+    match func.cfg().vertex_label(func.entry_point_ref()) {
+        Some(&ControlFlowTarget::Resolved(_)) => true,
+        _ => false,
+    }
+}
+
+/// Finds the functions of `proj` protected by stack canaries and CET and adds them to
+/// `Project::hardening`. The properties set by the loader are kept.
+pub fn analyze(proj: &mut Project) {
+    let mut handlers = proj.imports.iter().filter(|&(_, n)| is_canary_handler(n)).map(|(&a, _)| a).collect::<HashSet<u64>>();
+    let mut canary = vec![];
+    let mut endbr = vec![];
+
+    for prog in proj.code.iter() {
+        handlers.extend(prog.functions().filter(|f| is_canary_handler(&f.name) && is_resolved(f)).map(|f| f.start()));
+        handlers.extend(prog.imports.iter().filter(|&(_, n)| is_canary_handler(n)).map(|(&a, _)| a));
+    }
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            if !is_resolved(func) || is_canary_handler(&func.name) {
+                continue;
+            }
+
+            let calls = func.statements().any(
+                |s| s.op.operands().iter().any(
+                    |rv| match *rv {
+                        &Rvalue::Constant { value, .. } => handlers.contains(&value),
+                        _ => false,
+                    }
+                )
+            );
+            let symbolic = prog.find_call_target_by_uuid(func.uuid())
+                .map(
+                    |vx| {
+                        prog.call_graph.out_edges(vx).any(
+                            |e| match prog.call_graph.vertex_label(prog.call_graph.target(e)) {
+                                Some(&CallTarget::Symbolic(ref name, _)) => is_canary_handler(name),
+                                _ => false,
+                            }
+                        )
+                    }
+                )
+                .unwrap_or(false);
+
+            if calls || symbolic {
+                canary.push(func.start());
+            }
+
+            let start = proj.region().iter().seek(func.start()).take(4).collect::<Vec<_>>();
+
+            if start == [Some(0xf3), Some(0x0f), Some(0x1e), Some(0xfa)] || start == [Some(0xf3), Some(0x0f), Some(0x1e), Some(0xfb)] {
+                endbr.push(func.start());
+            }
+        }
+    }
+
+    canary.sort();
+    canary.dedup();
+    endbr.sort();
+    endbr.dedup();
+    proj.hardening.canary_functions = canary;
+    proj.hardening.endbr_functions = endbr;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, Bound, ControlFlowGraph, Endianess, Layer, Lvalue, Mnemonic, Operation, Program, Region, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn function(start: u64, stmts: Vec<Statement>, region: &Region) -> Function {
+        let mne = Mnemonic::new(start..start + 4, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, region, None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    /*
+     * 0x100: endbr64; call [0x800]
+     * 0x200: ret
+     */
+    #[test]
+    fn canary_and_endbr() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x100, 0x104), Layer::wrap(vec![0xf3, 0x0f, 0x1e, 0xfa])));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        let t = Lvalue::Variable { name: Cow::Borrowed("t"), size: 64, subscript: None };
+        let protected = function(
+            0x100,
+            vec![
+                Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 64, Rvalue::new_u64(0x800)), assignee: t.clone() },
+                Statement { op: Operation::Call(t.clone().into()), assignee: Lvalue::Undefined },
+            ],
+            proj.region()
+        );
+        let plain = function(0x200, vec![], proj.region());
+        let mut prog = Program::new("prog");
+
+        prog.insert(protected);
+        prog.insert(plain);
+        proj.code.push(prog);
+        proj.imports.insert(0x800, "__stack_chk_fail@GLIBC_2.4".to_string());
+        proj.hardening.nx = Some(true);
+
+        analyze(&mut proj);
+
+        assert_eq!(proj.hardening.canary_functions, vec![0x100]);
+        assert_eq!(proj.hardening.endbr_functions, vec![0x100]);
+        assert_eq!(proj.hardening.nx, Some(true));
+        assert!(proj.hardening.stack_canary() && proj.hardening.cfi());
+        assert_eq!(format!("{}", proj.hardening), "NX: yes, PIE: n/a, RELRO: n/a, canary: yes (1 functions), CFI: yes (1 endbr)");
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Architecture identification.
+//!
+//! Firmware images and memory dumps often come without a header naming the CPU they are written
+//! for. [`identify`] trial-decodes samples of a `Region` with a set of candidate architectures and
+//! ranks them by how much the result resembles real code.
+//!
+//! Each candidate is a [`Probe`]: an `Architecture` together with its configuration. The
+//! architecture crates depend on core, so the front end assembles the list of probes.
+//!
+//! ```ignore
+//! let probes = vec![
+//!     Probe::new::<amd64::Amd64>(Machine::Amd64, amd64::Mode::Long),
+//!     Probe::new::<avr::Avr>(Machine::Avr, avr::Mcu::atmega103()),
+//! ];
+//! let ranking = identify(&region, &probes);
+//! ```
+//!
+//! Starting at up to [`SAMPLES`] evenly spaced offsets each probe decodes a run of at most
+//! [`RUN_LENGTH`] instructions, stopping at the first one it can't decode. Samples starting with a
+//! single repeated byte value (padding, erased flash) are skipped. The statistics of all runs are
+//! combined into a score between 0 and 1:
+//!
+//! - The fraction of decode attempts that succeeded.
+//! - How concentrated the opcode distribution is. Code uses a handful of opcodes most of the
+//!   time, data decoded as instructions spreads over the whole opcode space.
+//! - Branch density. Compiled code has a branch, call or return every 3 to 30 instructions.
+//! - The fraction of constant branch and call targets that point into the region.
+//!
+//! [`identify`]: fn.identify.html
+//! [`Probe`]: struct.Probe.html
+//! [`SAMPLES`]: constant.SAMPLES.html
+//! [`RUN_LENGTH`]: constant.RUN_LENGTH.html
+
+use {Architecture, Machine, Match, Operation, Region, Rvalue, Statement};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Maximal number of sample offsets per region.
+pub const SAMPLES: usize = 64;
+
+/// Maximal number of instructions decoded per sample.
+pub const RUN_LENGTH: usize = 32;
+
+/// Number of most frequent opcodes used to measure concentration.
+const TOP_OPCODES: usize = 8;
+
+/// Trial decoding statistics of a single architecture.
+#[derive(Clone,Debug,Default)]
+pub struct Statistics {
+    /// Samples decoded
+    pub samples: usize,
+    /// Successfully decoded instructions
+    pub instructions: usize,
+    /// Runs that ended in a decoding error
+    pub failures: usize,
+    /// Instructions that branch, call or return
+    pub branches: usize,
+    /// Constant branch and call targets
+    pub targets: usize,
+    /// Constant branch and call targets inside the region
+    pub targets_inside: usize,
+    /// Number of occurrences of each opcode
+    pub opcodes: HashMap<String, usize>,
+}
+
+impl Statistics {
+    /// Fraction of decode attempts that succeeded.
+    pub fn validity(&self) -> f64 {
+        if self.instructions + self.failures == 0 {
+            0.
+        } else {
+            self.instructions as f64 / (self.instructions + self.failures) as f64
+        }
+    }
+
+    /// Fraction of instructions using one of the most frequent opcodes.
+    pub fn concentration(&self) -> f64 {
+        let mut counts = self.opcodes.values().cloned().collect::<Vec<_>>();
+
+        if self.instructions == 0 {
+            return 0.;
+        }
+
+        counts.sort_by(|a, b| b.cmp(a));
+        counts.iter().take(TOP_OPCODES).sum::<usize>() as f64 / self.instructions as f64
+    }
+
+    /// Fraction of instructions that change control flow.
+    pub fn branch_density(&self) -> f64 {
+        if self.instructions == 0 { 0. } else { self.branches as f64 / self.instructions as f64 }
+    }
+
+    /// Fraction of constant targets inside the region, 0.5 if there are none.
+    pub fn target_plausibility(&self) -> f64 {
+        if self.targets == 0 { 0.5 } else { self.targets_inside as f64 / self.targets as f64 }
+    }
+
+    /// Combined score between 0 (not code) and 1.
+    pub fn score(&self) -> f64 {
+        let density = self.branch_density();
+        let density = if density < 0.03 {
+            density / 0.03
+        } else if density > 0.3 {
+            (1. - (density - 0.3) / 0.3).max(0.)
+        } else {
+            1.
+        };
+
+        self.validity() * (0.4 * self.concentration() + 0.3 * density + 0.3 * self.target_plausibility())
+    }
+}
+
+/// Candidate architecture for [`identify`](fn.identify.html).
+pub struct Probe {
+    /// Architecture and configuration tested
+    pub machine: Machine,
+    trial: Box<Fn(&Region, &[u64]) -> Statistics>,
+}
+
+impl Probe {
+    /// Creates a probe decoding with architecture `A` and configuration `cfg`. The `machine` is
+    /// returned in the ranking.
+    pub fn new<A: Architecture + 'static>(machine: Machine, cfg: A::Configuration) -> Probe
+    where
+        A::Configuration: 'static,
+    {
+        Probe { machine: machine, trial: Box::new(move |reg, offsets| trial::<A>(reg, &cfg, offsets)) }
+    }
+}
+
+/// Result of [`identify`](fn.identify.html) for a single probe.
+#[derive(Clone,Debug)]
+pub struct Candidate {
+    /// Probed architecture
+    pub machine: Machine,
+    /// Score between 0 (not code) and 1
+    pub score: f64,
+    /// Statistics the score was computed from
+    pub statistics: Statistics,
+}
+
+/// Scores all `probes` against `reg`. The result is sorted, most likely architecture first.
+pub fn identify(reg: &Region, probes: &[Probe]) -> Vec<Candidate> {
+    let offsets = sample_offsets(reg);
+    let mut ret = probes
+        .iter()
+        .map(
+            |p| {
+                let stats = (p.trial)(reg, &offsets);
+                Candidate { machine: p.machine, score: stats.score(), statistics: stats }
+            }
+        )
+        .collect::<Vec<_>>();
+
+    ret.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    ret
+}
+
+/// Decodes runs of instructions starting at each of `offsets` and collects statistics.
+pub fn trial<A: Architecture>(reg: &Region, cfg: &A::Configuration, offsets: &[u64]) -> Statistics {
+    let mut ret = Statistics::default();
+
+    for &start in offsets.iter() {
+        let mut addr = start;
+        let mut cfg = cfg.clone();
+
+        ret.samples += 1;
+
+        for _ in 0..RUN_LENGTH {
+            if addr >= reg.size() {
+                break;
+            }
+
+            match A::decode(reg, addr, &cfg) {
+                Ok(m) => {
+                    let next = match m.mnemonics.iter().map(|m| m.area.end).max() {
+                        Some(end) => end,
+                        None => addr + (m.tokens.len() * size_of::<A::Token>()) as u64,
+                    };
+
+                    if next <= addr {
+                        break;
+                    }
+
+                    record(&mut ret, &m, next, reg.size());
+                    addr = next;
+                    cfg = m.configuration;
+                }
+                Err(_) => {
+                    ret.failures += 1;
+                    break;
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+fn record<A: Architecture>(stats: &mut Statistics, m: &Match<A>, next: u64, size: u64) {
+    let mut branch = m.jumps.is_empty();
+    let mut targets = vec![];
+
+    for &(_, ref tgt, _) in m.jumps.iter() {
+        match tgt {
+            &Rvalue::Constant { value, .. } if value == next => {}
+            &Rvalue::Constant { value, .. } => {
+                targets.push(value);
+                branch = true;
+            }
+            _ => branch = true,
+        }
+    }
+
+    for mne in m.mnemonics.iter() {
+        *stats.opcodes.entry(mne.opcode.clone()).or_insert(0) += 1;
+        stats.instructions += 1;
+
+        for stmt in mne.instructions.iter() {
+            match stmt {
+                &Statement { op: Operation::Call(Rvalue::Constant { value, .. }), .. } => {
+                    targets.push(value);
+                    branch = true;
+                }
+                &Statement { op: Operation::Call(_), .. } => branch = true,
+                _ => {}
+            }
+        }
+    }
+
+    if branch {
+        stats.branches += 1;
+    }
+    stats.targets += targets.len();
+    stats.targets_inside += targets.iter().filter(|&&t| t < size).count();
+}
+
+// Evenly spaced, 4 byte aligned offsets that don't start with padding.
+fn sample_offsets(reg: &Region) -> Vec<u64> {
+    let size = reg.size();
+    let mut ret = vec![];
+
+    for k in 0..SAMPLES as u64 {
+        let off = (k * size / SAMPLES as u64) & !3;
+
+        if ret.last() == Some(&off) {
+            continue;
+        }
+
+        let bytes = reg.iter().seek(off).take(16).collect::<Vec<_>>();
+        let padding = bytes.iter().all(|b| b.is_none() || *b == bytes[0]);
+
+        if !bytes.is_empty() && !padding {
+            ret.push(off);
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Guard, Mnemonic, Result};
+
+    // Single byte instructions whose upper nibble must match the configuration. `x0` is a return,
+    // `xf` a jump to the absolute address in the next byte.
+    #[derive(Clone,Debug)]
+    enum TestArchNibble {}
+    impl Architecture for TestArchNibble {
+        type Token = u8;
+        type Configuration = u8;
+
+        fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+            unimplemented!()
+        }
+
+        fn decode(reg: &Region, addr: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+            let tokens = reg.iter().seek(addr).take(2).map(|x| x.unwrap_or(0)).collect::<Vec<_>>();
+
+            if tokens[0] >> 4 != *cfg {
+                return Err("invalid opcode".into());
+            }
+
+            let (len, jumps) = match tokens[0] & 0xf {
+                0 => (1, vec![]),
+                0xf => (2, vec![(addr, Rvalue::new_u64(tokens[1] as u64), Guard::always())]),
+                _ => (1, vec![(addr, Rvalue::new_u64(addr + 1), Guard::always())]),
+            };
+            let opcode = format!("op{:x}", tokens[0] & 0xf);
+            let mne = Mnemonic::new(addr..addr + len, opcode, "".to_string(), vec![].iter(), vec![].iter())?;
+
+            Ok(Match { tokens: tokens[0..len as usize].to_vec(), mnemonics: vec![mne], jumps: jumps, configuration: *cfg })
+        }
+    }
+
+    #[test]
+    fn ranking() {
+        let mut code = vec![];
+
+        for i in 0..64u8 {
+            code.extend_from_slice(&[0x11, 0x12, 0x11, 0x13, 0x11, 0x12, 0x1f, i * 4, 0x11, 0x2a, 0x11, 0x10]);
+        }
+
+        let reg = Region::wrap("ram".to_string(), code);
+        let probes = vec![
+            Probe::new::<TestArchNibble>(Machine::Avr, 2),
+            Probe::new::<TestArchNibble>(Machine::Mcs51, 1),
+        ];
+        let ranking = identify(&reg, &probes);
+
+        assert_eq!(ranking.len(), 2);
+        match ranking[0].machine {
+            Machine::Mcs51 => {}
+            m => panic!("wrong architecture {:?}", m),
+        }
+        assert!(ranking[0].score > 0.5);
+        assert!(ranking[1].score < ranking[0].score);
+        assert_eq!(ranking[0].statistics.targets, ranking[0].statistics.targets_inside);
+        assert!(ranking[0].statistics.failures > 0);
+    }
+
+    #[test]
+    fn padding() {
+        let reg = Region::wrap("flash".to_string(), vec![0xff; 1024]);
+        let ranking = identify(&reg, &[Probe::new::<TestArchNibble>(Machine::Avr, 0xf)]);
+
+        assert_eq!(ranking[0].statistics.samples, 0);
+        assert_eq!(ranking[0].score, 0.);
+    }
+}
@@ -337,7 +337,7 @@ impl Display for Lvalue {
 }
 
 /// Branch condition
-#[derive(Clone,PartialEq,Eq,Debug,Serialize,Deserialize)]
+#[derive(Clone,PartialEq,Eq,Debug,Serialize,Deserialize,Hash)]
 pub enum Guard {
     /// Guard is constant true
     True,
@@ -0,0 +1,479 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Projects with more than one executable image.
+//!
+//! A project can hold a main executable together with the shared libraries it loads, each at its
+//! own base address. Libraries are loaded with `loader::load_at` into a project of their own and
+//! added with [`add_library`], which copies their memory into the root region and their
+//! functions into the call graph of the first program. Having a single call graph means that
+//! calls into a library are ordinary call graph edges.
+//!
+//! [`resolve_imports`] adds these edges. Calls of imported symbols and PLT stubs get an edge to
+//! the function of the same name in another image, ignoring symbol versions. Which image an
+//! address belongs to is recorded in `Project::images`.
+//!
+//! [`rebase`] moves one image to a new base address, e.g. to match the addresses of a process
+//! randomized by ASLR. Every stored address inside the image is moved, including IL constants.
+//! Constants that only happen to have the value of such an address are moved, too.
+//!
+//! [`add_library`]: fn.add_library.html
+//! [`resolve_imports`]: fn.resolve_imports.html
+//! [`rebase`]: fn.rebase.html
+
+use {Bound, CallTarget, FunctionKind, Program, Project, Result, Rvalue, xref};
+use panopticon_graph_algos::{AdjacencyMatrixGraphTrait, BidirectionalGraphTrait, EdgeListGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use std::collections::HashMap;
+
+/// Executable or library loaded into a project.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Image {
+    /// File name
+    pub name: String,
+    /// Lowest address of the image
+    pub base: u64,
+    /// Addresses covered by the image
+    pub area: Bound,
+}
+
+/// Addresses covered by the sections of `proj`, or by the defined parts of its root region if
+/// the loader recorded no sections.
+fn area(proj: &Project) -> Option<Bound> {
+    let bounds = if proj.sections.is_empty() {
+        proj.region().flatten().into_iter().filter(|&(_, l)| !l.is_undefined()).map(|(b, _)| b).collect::<Vec<_>>()
+    } else {
+        proj.sections.iter().map(|s| s.area.clone()).collect::<Vec<_>>()
+    };
+    let start = bounds.iter().map(|b| b.start).min();
+    let end = bounds.iter().map(|b| b.end).max();
+
+    match (start, end) {
+        (Some(start), Some(end)) => Some(Bound::new(start, end)),
+        _ => None,
+    }
+}
+
+fn copy(ct: &CallTarget) -> CallTarget {
+    match ct {
+        &CallTarget::Concrete(ref f) => CallTarget::Concrete(f.clone()),
+        &CallTarget::Symbolic(ref name, ref uuid) => CallTarget::Symbolic(name.clone(), uuid.clone()),
+        &CallTarget::Todo(ref rv, ref name, ref uuid) => CallTarget::Todo(rv.clone(), name.clone(), uuid.clone()),
+    }
+}
+
+/// Adds the library `lib`, loaded at a base address not used by `proj`, to `proj`. The first
+/// call also registers the image of `proj` itself. Fails if the images overlap.
+pub fn add_library(proj: &mut Project, lib: Project) -> Result<()> {
+    let lib_area = match area(&lib) {
+        Some(a) => a,
+        None => return Err(format!("{} maps no memory", lib.name).into()),
+    };
+
+    if proj.images.is_empty() {
+        if let Some(a) = area(proj) {
+            let name = proj.name.clone();
+            proj.images.push(Image { name: name, base: a.start, area: a });
+        }
+    }
+
+    if let Some(other) = proj.images.iter().find(|i| i.area.start < lib_area.end && lib_area.start < i.area.end) {
+        return Err(format!("{} at {:?} overlaps {} at {:?}", lib.name, lib_area, other.name, other.area).into());
+    }
+
+    let root = proj.data.root;
+    {
+        let region = match proj.data.dependencies.vertex_label_mut(root) {
+            Some(r) => r,
+            None => return Err("project has no root region".into()),
+        };
+
+        for (i, &(ref bound, ref layer)) in lib.region().stack().iter().enumerate() {
+            if i == 0 && layer.is_undefined() {
+                continue;
+            }
+            if !region.cover(bound.clone(), layer.clone()) {
+                return Err(format!("{} doesn't fit into {}", lib.name, region.name()).into());
+            }
+        }
+        for section in lib.region().sections() {
+            region.add_section(section.clone());
+        }
+    }
+
+    if proj.code.is_empty() {
+        let name = proj.name.clone();
+        proj.code.push(Program::new(&name));
+    }
+
+    {
+        let prog = &mut proj.code[0];
+
+        for other in lib.code.iter() {
+            let mut map = HashMap::new();
+
+            for vx in other.call_graph.vertices() {
+                if let Some(ct) = other.call_graph.vertex_label(vx) {
+                    map.insert(vx, prog.call_graph.add_vertex(copy(ct)));
+                }
+            }
+            for e in other.call_graph.edges() {
+                if let (Some(&from), Some(&to)) = (map.get(&other.call_graph.source(e)), map.get(&other.call_graph.target(e))) {
+                    prog.call_graph.add_edge((), from, to);
+                }
+            }
+
+            prog.imports.extend(other.imports.iter().map(|(&a, n)| (a, n.clone())));
+            prog.thunks.extend(other.thunks.iter().map(|(&a, &s)| (a, s)));
+            prog.relocations.extend(other.relocations.iter().map(|(&a, r)| (a, r.clone())));
+            prog.analyzed_calls.extend(other.analyzed_calls.iter().cloned());
+        }
+    }
+
+    proj.imports.extend(lib.imports.iter().map(|(&a, n)| (a, n.clone())));
+    proj.relocations.extend(lib.relocations.iter().map(|(&a, r)| (a, r.clone())));
+    proj.mapping_symbols.extend(lib.mapping_symbols.iter().map(|(&a, &m)| (a, m)));
+    proj.sections.extend(lib.sections.iter().cloned());
+    proj.exception_tables.extend(lib.exception_tables.iter().cloned());
+    for (&(ref region, addr), c) in lib.comments.iter() {
+        proj.comments.entry((region.clone(), addr)).or_insert(c.clone());
+    }
+    proj.images.push(Image { name: lib.name.clone(), base: lib_area.start, area: lib_area });
+
+    Ok(())
+}
+
+/// Symbol name without version, e.g. `printf` for `printf@GLIBC_2.2.5`.
+fn unversioned(name: &str) -> &str {
+    name.split('@').next().unwrap_or(name)
+}
+
+/// Adds call graph edges from imported symbols and PLT stubs to their implementation in another
+/// image of `proj`. Returns the number of edges added.
+pub fn resolve_imports(proj: &mut Project) -> usize {
+    let images = proj.images.clone();
+    let image_of = |addr: u64| images.iter().position(|i| i.area.start <= addr && addr < i.area.end);
+    let mut ret = 0;
+
+    for prog in proj.code.iter_mut() {
+        let mut exports = HashMap::<String, Vec<_>>::new();
+        let mut links = vec![];
+
+        for vx in prog.call_graph.vertices() {
+            let (name, addr) = match prog.call_graph.vertex_label(vx) {
+                Some(&CallTarget::Concrete(ref f)) if !f.kind().is_forwarder() => {
+                    match f.basic_blocks().map(|bb| bb.area.start).min() {
+                        Some(start) => (f.name.clone(), start),
+                        None => continue,
+                    }
+                }
+                Some(&CallTarget::Todo(Rvalue::Constant { value, .. }, Some(ref name), _)) => (name.clone(), value),
+                _ => continue,
+            };
+
+            if let Some(img) = image_of(addr) {
+                exports.entry(unversioned(&name).to_string()).or_insert_with(Vec::new).push((img, vx));
+            }
+        }
+
+        for vx in prog.call_graph.vertices() {
+            match prog.call_graph.vertex_label(vx) {
+                // calls of the imported symbol become calls of the implementation
+                Some(&CallTarget::Symbolic(ref name, _)) => {
+                    for e in prog.call_graph.in_edges(vx) {
+                        let caller = prog.call_graph.source(e);
+                        let img = match prog.call_graph.vertex_label(caller) {
+                            Some(&CallTarget::Concrete(ref f)) => f.basic_blocks().next().and_then(|bb| image_of(bb.area.start)),
+                            _ => None,
+                        };
+
+                        if let Some(&(_, target)) = exports.get(unversioned(name)).and_then(|v| v.iter().find(|&&(i, _)| Some(i) != img)) {
+                            links.push((caller, target));
+                        }
+                    }
+                }
+                Some(&CallTarget::Concrete(ref f)) => {
+                    let name = match f.kind() {
+                        &FunctionKind::Stub { ref name, .. } | &FunctionKind::Import { ref name, .. } => Some(name),
+                        _ => None,
+                    };
+
+                    if let Some(name) = name {
+                        let img = f.basic_blocks().next().and_then(|bb| image_of(bb.area.start));
+
+                        if let Some(&(_, target)) = exports.get(unversioned(name)).and_then(|v| v.iter().find(|&&(i, _)| Some(i) != img)) {
+                            links.push((vx, target));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (from, to) in links {
+            if prog.call_graph.edge(from, to).is_none() {
+                prog.call_graph.add_edge((), from, to);
+                ret += 1;
+            }
+        }
+    }
+
+    ret
+}
+
+/// Returns the addresses covered by the image named `image`. Projects with a single image use
+/// the project name.
+pub fn extent(proj: &Project, image: &str) -> Result<Bound> {
+    match proj.images.iter().find(|i| i.name == image) {
+        Some(i) => Ok(i.area.clone()),
+        None if proj.images.is_empty() && proj.name == image => {
+            match area(proj) {
+                Some(a) => Ok(a),
+                None => Err(format!("{} maps no memory", image).into()),
+            }
+        }
+        None => Err(format!("no image named {}", image).into()),
+    }
+}
+
+/// Moves the image named `image` to `new_base`. Projects with a single image use the project
+/// name. Fails if the moved image would overlap another one.
+pub fn rebase(proj: &mut Project, image: &str, new_base: u64) -> Result<()> {
+    let area = extent(proj, image)?;
+    let moved = Bound::new(new_base, new_base.wrapping_add(area.end - area.start));
+
+    if moved.end < moved.start {
+        return Err(format!("{} doesn't fit at {:#x}", image, new_base).into());
+    }
+    if let Some(other) = proj.images.iter().find(|i| i.name != image && i.area.start < moved.end && moved.start < i.area.end) {
+        return Err(format!("{} at {:?} would overlap {} at {:?}", image, moved, other.name, other.area).into());
+    }
+
+    let root = proj.data.root;
+    let ok = match proj.data.dependencies.vertex_label_mut(root) {
+        Some(region) => region.move_area(&area, new_base),
+        None => false,
+    };
+
+    if !ok {
+        return Err(format!("{} doesn't fit at {:#x}", image, new_base).into());
+    }
+
+    let f = |a: u64| if area.start <= a && a < area.end { a - area.start + new_base } else { a };
+    let mv = |b: &Bound| if area.start <= b.start && b.start < area.end { Bound::new(f(b.start), f(b.start) + (b.end - b.start)) } else { b.clone() };
+
+    for prog in proj.code.iter_mut() {
+        for ct in prog.call_graph.vertex_labels_mut() {
+            match ct {
+                &mut CallTarget::Concrete(ref mut func) => func.move_addresses(&f),
+                &mut CallTarget::Todo(Rvalue::Constant { ref mut value, .. }, _, _) => *value = f(*value),
+                _ => {}
+            }
+        }
+
+        prog.imports = prog.imports.drain().map(|(a, n)| (f(a), n)).collect();
+        prog.thunks = prog.thunks.drain().map(|(a, s)| (f(a), f(s))).collect();
+        prog.relocations = ::std::mem::replace(&mut prog.relocations, Default::default())
+            .into_iter()
+            .map(
+                |(a, mut r)| {
+                    r.target = r.target.map(&f);
+                    (f(a), r)
+                }
+            )
+            .collect();
+    }
+
+    proj.comments = proj.comments.drain().map(|((r, a), c)| ((r, f(a)), c)).collect();
+    proj.imports = proj.imports.drain().map(|(a, n)| (f(a), n)).collect();
+    proj.mapping_symbols = ::std::mem::replace(&mut proj.mapping_symbols, Default::default()).into_iter().map(|(a, m)| (f(a), m)).collect();
+    proj.relocations = ::std::mem::replace(&mut proj.relocations, Default::default())
+        .into_iter()
+        .map(
+            |(a, mut r)| {
+                r.target = r.target.map(&f);
+                (f(a), r)
+            }
+        )
+        .collect();
+    for s in proj.sections.iter_mut() {
+        s.area = mv(&s.area);
+    }
+    proj.strings = ::std::mem::replace(&mut proj.strings, Default::default())
+        .into_iter()
+        .map(
+            |(a, mut s)| {
+                s.area = mv(&s.area);
+                (f(a), s)
+            }
+        )
+        .collect();
+    proj.type_database.classes = ::std::mem::replace(&mut proj.type_database.classes, Default::default())
+        .into_iter()
+        .map(
+            |(a, mut c)| {
+                for b in c.bases.iter_mut() {
+                    b.class = f(b.class);
+                }
+                (f(a), c)
+            }
+        )
+        .collect();
+    for fde in proj.exception_tables.iter_mut() {
+        fde.function = mv(&fde.function);
+        fde.lsda = fde.lsda.map(&f);
+        for cs in fde.call_sites.iter_mut() {
+            cs.area = mv(&cs.area);
+            cs.landing_pad = cs.landing_pad.map(&f);
+        }
+    }
+    for a in proj.hardening.canary_functions.iter_mut().chain(proj.hardening.endbr_functions.iter_mut()) {
+        *a = f(*a);
+    }
+    for p in proj.patches.iter_mut() {
+        p.address = f(p.address);
+    }
+    proj.annotations.move_addresses(&f);
+    if let Some(img) = proj.images.iter_mut().find(|i| i.name == image) {
+        img.base = new_base;
+        img.area = moved;
+    }
+    if !proj.xrefs.is_empty() {
+        xref::collect(proj);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Endianess, Function, Layer, Lvalue, Mnemonic, Operation, Region, Statement};
+    use std::borrow::Cow;
+    use panopticon_graph_algos::IncidenceGraphTrait;
+    use uuid::Uuid;
+
+    fn function(start: u64, name: &str, region: &Region) -> Function {
+        let mne = Mnemonic::new(start..start + 1, "ret".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, region, Some(name.to_string()));
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    fn project(name: &str, base: u64) -> Project {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+
+        assert!(reg.cover(Bound::new(base, base + 0x100), Layer::wrap(vec![0xc3; 0x100])));
+        Project::new(name.to_string(), reg)
+    }
+
+    #[test]
+    fn link_libraries() {
+        let mut exe = project("a.out", 0x1000);
+        let mut prog = Program::new("prog0");
+        let mut stub = function(0x1010, "puts@plt", exe.region());
+        let main = function(0x1000, "main", exe.region());
+
+        stub.set_plt("puts", 0x1080);
+        prog.insert(stub);
+        let main_uuid = main.uuid().clone();
+        let main_vx = {
+            prog.insert(main);
+            prog.find_call_target_by_uuid(&main_uuid).unwrap()
+        };
+        let import = prog.call_graph.add_vertex(CallTarget::Symbolic("exit@GLIBC_2.2.5".to_string(), Uuid::new_v4()));
+        prog.call_graph.add_edge((), main_vx, import);
+        exe.code.push(prog);
+
+        let mut libc = project("libc.so.6", 0x7000_0000);
+        let mut prog = Program::new("prog0");
+        let puts = function(0x7000_0010, "puts", libc.region());
+        let puts_uuid = puts.uuid().clone();
+
+        prog.insert(puts);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(0x7000_0020), Some("exit".to_string()), Uuid::new_v4()));
+        libc.code.push(prog);
+
+        assert!(add_library(&mut exe, libc).is_ok());
+        assert!(add_library(&mut exe, project("overlap.so", 0x1080)).is_err());
+        assert_eq!(exe.images.len(), 2);
+        assert_eq!(exe.image(0x7000_0010).map(|i| i.name.as_str()), Some("libc.so.6"));
+        assert_eq!(exe.region().iter().seek(0x7000_0000).next(), Some(Some(0xc3)));
+
+        assert_eq!(resolve_imports(&mut exe), 2);
+        assert_eq!(resolve_imports(&mut exe), 0);
+
+        let prog = &exe.code[0];
+        let stub = prog.find_function_by_entry(0x1010).unwrap();
+        let puts = prog.find_call_target_by_uuid(&puts_uuid).unwrap();
+
+        assert!(prog.call_graph.edge(stub, puts).is_some());
+        assert_eq!(prog.call_graph.out_degree(main_vx), 2);
+    }
+
+    #[test]
+    fn rebase_image() {
+        let mut proj = project("a.out", 0x1000);
+        let mut prog = Program::new("prog0");
+        let mut func = function(0x1000, "main", proj.region());
+        let load = Statement {
+            op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 32, Rvalue::new_u64(0x1080)),
+            assignee: Lvalue::Variable { name: Cow::Borrowed("a"), size: 32, subscript: None },
+        };
+
+        func.cfg_mut().vertex_labels_mut().next().map(
+            |ct| if let &mut ControlFlowTarget::Resolved(ref mut bb) = ct {
+                bb.mnemonics[0].instructions.push(load);
+            }
+        );
+        prog.insert(func);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(0x1040), None, Uuid::new_v4()));
+        proj.code.push(prog);
+        proj.imports.insert(0x1080, "puts".to_string());
+        proj.annotations.set_label(::Location::Address(0x1040), "loop".to_string());
+
+        assert!(proj.rebase("b.out", 0x5000).is_err());
+        assert!(proj.rebase("a.out", 0x5000).is_ok());
+
+        let prog = &proj.code[0];
+        assert!(prog.find_function_by_entry(0x5000).is_some());
+        assert!(
+            prog.functions().next().unwrap().statements().any(
+                |s| match s.op {
+                    Operation::Load(_, _, _, Rvalue::Constant { value: 0x5080, .. }) => true,
+                    _ => false,
+                }
+            )
+        );
+        assert!(
+            prog.call_graph.vertex_labels().any(
+                |ct| match ct {
+                    &CallTarget::Todo(Rvalue::Constant { value: 0x5040, .. }, _, _) => true,
+                    _ => false,
+                }
+            )
+        );
+        assert_eq!(proj.imports.get(&0x5080).map(|s| s.as_str()), Some("puts"));
+        assert_eq!(proj.annotations.label(&::Location::Address(0x5040)), Some("loop"));
+        assert_eq!(proj.region().iter().seek(0x5000).next(), Some(Some(0xc3)));
+        assert_eq!(proj.region().iter().seek(0x1000).next(), Some(None));
+    }
+}
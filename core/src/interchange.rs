@@ -0,0 +1,350 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Export of the analysis results as JSON.
+//!
+//! [`to_json`] writes everything other tools need to consume an analysis without linking this
+//! crate: functions with their basic blocks, mnemonics and IL, the call graph, cross references,
+//! imports, labels and comments. The format is versioned by [`VERSION`], which is increased
+//! whenever a field changes meaning or is removed. Adding fields doesn't change the version, so
+//! readers should ignore keys they don't know.
+//!
+//! Addresses are strings of hexadecimal numbers starting with `0x`, because JSON numbers can't
+//! represent all 64 bit values. IL statements and operands are in the textual form printed by
+//! panopticon. The top level object looks like this:
+//!
+//! ```text
+//! {
+//!   "format": "panopticon", "version": 1, "name": "<project name>",
+//!   "programs": [{
+//!     "uuid": "...", "name": "...",
+//!     "functions": [{
+//!       "uuid": "...", "name": "...", "aliases": ["..."], "entry": "0x...",
+//!       "kind": "regular" | "stub" | "thunk" | "import" | "library" | "synthetic" | "runtime",
+//!       "plt_address": "0x...",  // stubs only
+//!       "target": "0x...",  // thunks only
+//!       "import": "...", "library": "..." | null,  // imports only, library functions have "library"
+//!       "blocks": [{
+//!         "start": "0x...", "end": "0x...",
+//!         "mnemonics": [{"start": "0x...", "end": "0x...", "opcode": "...", "operands": ["..."], "il": ["..."]}]
+//!       }],
+//!       "unresolved": [{"id": 3, "target": "..."}],  // indirect jumps, IL value of the target
+//!       "edges": [{"from": "0x...", "to": "0x..." | 3, "guard": "..."}],  // block start or unresolved id
+//!       "name_source": "generated" | "unknown" | "demangler" | "export" | "import" | "symbol" | "debug" | "user",
+//!       "alias_sources": ["..."]  // same order as "aliases"
+//!     }],
+//!     "calls": [{"from": "<uuid>", "to": "<uuid>"}],
+//!     "symbolic": [{"uuid": "...", "name": "..."}],  // imported functions
+//!     "todo": [{"uuid": "...", "address": "0x...", "name": "..." | null}]  // not disassembled yet
+//!   }],
+//!   "imports": [{"address": "0x...", "name": "..."}],
+//!   "xrefs": [{"function": "<uuid>", "address": "0x...", "statement": 1 | null, "target": "0x...",
+//!              "kind": "read" | "write" | "call" | "jump" | "address"}],
+//!   "labels": [{"address": "0x...", "name": "..."}],
+//!   "comments": [{"address": "0x...", "text": "...", "generated": false}]
+//! }
+//! ```
+//!
+//! [`to_json`]: fn.to_json.html
+//! [`VERSION`]: constant.VERSION.html
+
+use {CallTarget, ControlFlowTarget, Function, FunctionKind, Location, NameSource, Program, Project, Result, Rvalue, XrefKind};
+use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, VertexListGraphTrait};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+/// Version of the format written by `to_json`.
+pub const VERSION: u32 = 1;
+
+/// Returns `s` as a JSON string literal.
+fn string(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(ret, "\\u{:04x}", c as u32);
+            }
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+fn address(a: u64) -> String {
+    format!("\"{:#x}\"", a)
+}
+
+fn list<I: Iterator<Item = String>>(items: I) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+/// Returns `func` as JSON object, in the format of the elements of `programs[].functions`.
+pub fn function(func: &Function) -> String {
+    let cfg = func.cfg();
+    let mut unresolved = BTreeMap::new();
+    let node = |vx| match cfg.vertex_label(vx) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => Some(address(bb.area.start)),
+        Some(_) => Some(format!("{}", vx.0)),
+        None => None,
+    };
+    let mut blocks = func.basic_blocks().collect::<Vec<_>>();
+
+    blocks.sort_by_key(|bb| bb.area.start);
+    for vx in cfg.vertices() {
+        match cfg.vertex_label(vx) {
+            Some(&ControlFlowTarget::Unresolved(ref rv)) => {
+                unresolved.insert(vx.0, format!("{}", rv));
+            }
+            Some(&ControlFlowTarget::Failed(addr, ref msg)) => {
+                unresolved.insert(vx.0, format!("failed at {:#x}: {}", addr, msg));
+            }
+            _ => {}
+        }
+    }
+
+    let entry = match cfg.vertex_label(func.entry_point_ref()) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => address(bb.area.start),
+        _ => "null".to_string(),
+    };
+    let kind = match func.kind() {
+        &FunctionKind::Regular => "\"kind\":\"regular\"".to_string(),
+        &FunctionKind::Stub { plt_address, .. } => format!("\"kind\":\"stub\",\"plt_address\":{}", address(plt_address)),
+        &FunctionKind::Thunk { target } => format!("\"kind\":\"thunk\",\"target\":{}", address(target)),
+        &FunctionKind::Import { ref name, ref library } => {
+            let library = library.as_ref().map(|l| string(l)).unwrap_or("null".to_string());
+            format!("\"kind\":\"import\",\"import\":{},\"library\":{}", string(name), library)
+        }
+        &FunctionKind::Library { ref library } => format!("\"kind\":\"library\",\"library\":{}", string(library)),
+        &FunctionKind::Synthetic => "\"kind\":\"synthetic\"".to_string(),
+        &FunctionKind::Runtime => "\"kind\":\"runtime\"".to_string(),
+    };
+    let blocks = list(
+        blocks.iter().map(
+            |bb| {
+                let mnes = list(
+                    func.mnemonics(bb).map(
+                        |(mne, stmts)| {
+                            format!(
+                                "{{\"start\":{},\"end\":{},\"opcode\":{},\"operands\":{},\"il\":{}}}",
+                                address(mne.area.start),
+                                address(mne.area.end),
+                                string(&mne.opcode),
+                                list(mne.operands.iter().map(|o| string(&format!("{}", o)))),
+                                list(stmts.iter().map(|s| string(&format!("{}", s))))
+                            )
+                        }
+                    )
+                );
+
+                format!("{{\"start\":{},\"end\":{},\"mnemonics\":{}}}", address(bb.area.start), address(bb.area.end), mnes)
+            }
+        )
+    );
+    let edges = list(
+        cfg.edges().filter_map(
+            |e| match (node(cfg.source(e)), node(cfg.target(e))) {
+                (Some(from), Some(to)) => {
+                    let guard = cfg.edge_label(e).map(|g| format!("{}", g)).unwrap_or_default();
+                    Some(format!("{{\"from\":{},\"to\":{},\"guard\":{}}}", from, to, string(&guard)))
+                }
+                _ => None,
+            }
+        )
+    );
+
+    format!(
+        "{{\"uuid\":{},\"name\":{},\"aliases\":{},\"entry\":{},{},\"blocks\":{},\"unresolved\":{},\"edges\":{},\"name_source\":{},\"alias_sources\":{}}}",
+        string(&func.uuid().to_string()),
+        string(&func.name),
+        list(func.aliases().iter().map(|a| string(a))),
+        entry,
+        kind,
+        blocks,
+        list(unresolved.into_iter().map(|(id, t)| format!("{{\"id\":{},\"target\":{}}}", id, string(&t)))),
+        edges,
+        string(source(func.name_source())),
+        list(func.alias_entries().iter().map(|a| string(source(a.source))))
+    )
+}
+
+fn source(s: NameSource) -> &'static str {
+    match s {
+        NameSource::Generated => "generated",
+        NameSource::Unknown => "unknown",
+        NameSource::Demangler => "demangler",
+        NameSource::Export => "export",
+        NameSource::Import => "import",
+        NameSource::Symbol => "symbol",
+        NameSource::Debug => "debug",
+        NameSource::User => "user",
+    }
+}
+
+fn program(prog: &Program) -> String {
+    let cg = &prog.call_graph;
+    let mut functions = vec![];
+    let mut symbolic = vec![];
+    let mut todo = vec![];
+
+    for ct in cg.vertex_labels() {
+        match ct {
+            &CallTarget::Concrete(ref f) => functions.push(function(f)),
+            &CallTarget::Symbolic(ref name, ref uuid) => symbolic.push(format!("{{\"uuid\":{},\"name\":{}}}", string(&uuid.to_string()), string(name))),
+            &CallTarget::Todo(ref rv, ref name, ref uuid) => {
+                let addr = match rv {
+                    &Rvalue::Constant { value, .. } => address(value),
+                    _ => "null".to_string(),
+                };
+                let name = name.as_ref().map(|n| string(n)).unwrap_or("null".to_string());
+
+                todo.push(format!("{{\"uuid\":{},\"address\":{},\"name\":{}}}", string(&uuid.to_string()), addr, name));
+            }
+        }
+    }
+
+    let calls = list(
+        cg.edges().filter_map(
+            |e| match (cg.vertex_label(cg.source(e)), cg.vertex_label(cg.target(e))) {
+                (Some(from), Some(to)) => Some(format!("{{\"from\":{},\"to\":{}}}", string(&from.uuid().to_string()), string(&to.uuid().to_string()))),
+                _ => None,
+            }
+        )
+    );
+
+    format!(
+        "{{\"uuid\":{},\"name\":{},\"functions\":[{}],\"calls\":{},\"symbolic\":[{}],\"todo\":[{}]}}",
+        string(&prog.uuid.to_string()),
+        string(&prog.name),
+        functions.join(","),
+        calls,
+        symbolic.join(","),
+        todo.join(",")
+    )
+}
+
+/// Returns the analysis results of `proj` as JSON, in the format described in the module
+/// documentation.
+pub fn to_json(proj: &Project) -> String {
+    let mut imports = proj.imports.iter().collect::<Vec<_>>();
+    let mut xrefs = proj.xrefs.iter().collect::<Vec<_>>();
+    let mut labels = vec![];
+    let mut comments = vec![];
+
+    imports.sort();
+    xrefs.sort_by_key(|x| (x.target, x.address));
+
+    for (loc, ann) in proj.annotations.iter() {
+        if let &Location::Address(addr) = loc {
+            if let Some(ref l) = ann.label {
+                labels.push(format!("{{\"address\":{},\"name\":{}}}", address(addr), string(l)));
+            }
+            if let Some(ref c) = ann.comment {
+                comments.push(format!("{{\"address\":{},\"text\":{},\"generated\":false}}", address(addr), string(c)));
+            }
+            if let Some(ref c) = ann.auto_comment {
+                comments.push(format!("{{\"address\":{},\"text\":{},\"generated\":true}}", address(addr), string(c)));
+            }
+        }
+    }
+
+    let xrefs = list(
+        xrefs.into_iter().map(
+            |x| {
+                let kind = match x.kind {
+                    XrefKind::Read => "read",
+                    XrefKind::Write => "write",
+                    XrefKind::Call => "call",
+                    XrefKind::Jump => "jump",
+                    XrefKind::Address => "address",
+                };
+
+                format!(
+                    "{{\"function\":{},\"address\":{},\"statement\":{},\"target\":{},\"kind\":\"{}\"}}",
+                    string(&x.function.to_string()),
+                    address(x.address),
+                    x.statement.map(|s| s.to_string()).unwrap_or("null".to_string()),
+                    address(x.target),
+                    kind
+                )
+            }
+        )
+    );
+
+    format!(
+        "{{\"format\":\"panopticon\",\"version\":{},\"name\":{},\"programs\":{},\"imports\":{},\"xrefs\":{},\"labels\":[{}],\"comments\":[{}]}}",
+        VERSION,
+        string(&proj.name),
+        list(proj.code.iter().map(program)),
+        list(imports.into_iter().map(|(&a, n)| format!("{{\"address\":{},\"name\":{}}}", address(a), string(n)))),
+        xrefs,
+        labels.join(","),
+        comments.join(",")
+    )
+}
+
+/// Writes `to_json(proj)` to `w`.
+pub fn write_json<W: Write>(proj: &Project, w: &mut W) -> Result<()> {
+    w.write_all(to_json(proj).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Mnemonic, Region};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    #[test]
+    fn export() {
+        let mut proj = Project::new("te\"st".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let mne = Mnemonic::new(0x100..0x102, "jmp".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let bb = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let ind = cfg.add_vertex(ControlFlowTarget::Unresolved(Rvalue::Undefined));
+        let mut func = Function::undefined(0x100, None, proj.region(), Some("main".to_string()));
+        let mut prog = Program::new("prog");
+
+        cfg.add_edge(::Guard::True, bb, ind);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(bb);
+        prog.insert(func);
+        proj.code.push(prog);
+        proj.imports.insert(0x800, "printf".to_string());
+        proj.annotations.set_label(Location::Address(0x100), "start".to_string());
+
+        let json = to_json(&proj);
+
+        assert!(json.starts_with("{\"format\":\"panopticon\",\"version\":1,\"name\":\"te\\\"st\""));
+        assert!(json.contains("\"name\":\"main\",\"aliases\":[],\"entry\":\"0x100\",\"kind\":\"regular\""));
+        assert!(json.contains("\"opcode\":\"jmp\""));
+        assert!(json.contains(&format!("\"unresolved\":[{{\"id\":{},\"target\":\"?\"}}]", ind.0)));
+        assert!(json.contains(&format!("\"edges\":[{{\"from\":\"0x100\",\"to\":{},\"guard\":\"true\"}}]", ind.0)));
+        assert!(json.contains("\"imports\":[{\"address\":\"0x800\",\"name\":\"printf\"}]"));
+        assert!(json.contains("\"labels\":[{\"address\":\"0x100\",\"name\":\"start\"}]"));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+    }
+}
@@ -0,0 +1,292 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Minimal JSON reader and writer.
+//!
+//! Enough to read SARIF files in `external` and JSON-RPC requests, and to write answers. Numbers
+//! are `f64`, so addresses are better passed as strings of hexadecimal numbers.
+
+use Result;
+use std::fmt;
+use std::u64;
+
+/// JSON value.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Json {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// Any number
+    Number(f64),
+    /// String, unescaped
+    String(String),
+    /// Array
+    Array(Vec<Json>),
+    /// Object, keys in the order they were written
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses the JSON document `s`.
+    pub fn parse(s: &str) -> Result<Json> {
+        let mut chars = s.chars().peekable();
+        let ret = Json::value(&mut chars)?;
+
+        Json::skip_whitespace(&mut chars);
+        if chars.peek().is_some() { Err("trailing characters after JSON value".into()) } else { Ok(ret) }
+    }
+
+    /// Value of `key` if this is an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            &Json::Object(ref o) => o.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v),
+            _ => None,
+        }
+    }
+
+    /// `i`th element if this is an array.
+    pub fn index(&self, i: usize) -> Option<&Json> {
+        self.as_array().and_then(|a| a.get(i))
+    }
+
+    /// Elements if this is an array.
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            &Json::Array(ref a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Contents if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            &Json::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Value if this is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            &Json::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Value if this is a non-negative integer or a string of a hexadecimal number starting with
+    /// `0x`, the way addresses are written by `interchange`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            &Json::Number(n) if n >= 0.0 && n.fract() == 0.0 && n <= u64::MAX as f64 => Some(n as u64),
+            &Json::String(ref s) if s.starts_with("0x") => u64::from_str_radix(&s[2..], 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn skip_whitespace<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>) {
+        while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            chars.next();
+        }
+    }
+
+    fn value<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>) -> Result<Json> {
+        Json::skip_whitespace(chars);
+
+        match chars.peek().cloned() {
+            Some('{') => {
+                let mut ret = vec![];
+
+                chars.next();
+                loop {
+                    Json::skip_whitespace(chars);
+                    match chars.peek().cloned() {
+                        Some('}') if ret.is_empty() => {
+                            chars.next();
+                            return Ok(Json::Object(ret));
+                        }
+                        _ => {}
+                    }
+
+                    let key = match Json::value(chars)? {
+                        Json::String(s) => s,
+                        _ => return Err("JSON object key is not a string".into()),
+                    };
+
+                    Json::skip_whitespace(chars);
+                    if chars.next() != Some(':') {
+                        return Err("expected ':' in JSON object".into());
+                    }
+                    ret.push((key, Json::value(chars)?));
+                    Json::skip_whitespace(chars);
+                    match chars.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Json::Object(ret)),
+                        _ => return Err("expected ',' or '}' in JSON object".into()),
+                    }
+                }
+            }
+            Some('[') => {
+                let mut ret = vec![];
+
+                chars.next();
+                loop {
+                    Json::skip_whitespace(chars);
+                    match chars.peek().cloned() {
+                        Some(']') if ret.is_empty() => {
+                            chars.next();
+                            return Ok(Json::Array(ret));
+                        }
+                        _ => {}
+                    }
+
+                    ret.push(Json::value(chars)?);
+                    Json::skip_whitespace(chars);
+                    match chars.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Json::Array(ret)),
+                        _ => return Err("expected ',' or ']' in JSON array".into()),
+                    }
+                }
+            }
+            Some('"') => {
+                let mut ret = String::new();
+
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => return Ok(Json::String(ret)),
+                        Some('\\') => {
+                            match chars.next() {
+                                Some('n') => ret.push('\n'),
+                                Some('t') => ret.push('\t'),
+                                Some('r') => ret.push('\r'),
+                                Some('b') => ret.push('\u{8}'),
+                                Some('f') => ret.push('\u{c}'),
+                                Some('u') => {
+                                    let hex = chars.by_ref().take(4).collect::<String>();
+                                    let c = u32::from_str_radix(&hex, 16).ok().and_then(::std::char::from_u32);
+
+                                    ret.push(c.unwrap_or('\u{fffd}'));
+                                }
+                                Some(c) => ret.push(c),
+                                None => return Err("unterminated JSON string".into()),
+                            }
+                        }
+                        Some(c) => ret.push(c),
+                        None => return Err("unterminated JSON string".into()),
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_digit(10) => {
+                let mut num = String::new();
+
+                while chars.peek().map(|&c| c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' || c.is_digit(10)).unwrap_or(false) {
+                    num.push(chars.next().unwrap());
+                }
+
+                num.parse::<f64>().map(Json::Number).map_err(|_| format!("invalid JSON number {}", num).into())
+            }
+            Some(_) => {
+                let mut word = String::new();
+
+                while chars.peek().map(|c| c.is_alphabetic()).unwrap_or(false) {
+                    word.push(chars.next().unwrap());
+                }
+
+                match word.as_str() {
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    "null" => Ok(Json::Null),
+                    _ => Err(format!("unexpected JSON token {}", word).into()),
+                }
+            }
+            None => Err("unexpected end of JSON".into()),
+        }
+    }
+}
+
+/// Writes `s` as a JSON string literal.
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Json::Null => f.write_str("null"),
+            &Json::Bool(b) => write!(f, "{}", b),
+            &Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            &Json::Number(_) => f.write_str("null"),
+            &Json::String(ref s) => write_string(f, s),
+            &Json::Array(ref a) => {
+                f.write_str("[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                f.write_str("]")
+            }
+            &Json::Object(ref o) => {
+                f.write_str("{")?;
+                for (i, &(ref k, ref v)) in o.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let s = "{\"a\":[1,2.5,-3],\"b\":\"x\\\"y\\n\",\"c\":null,\"d\":true,\"e\":{}}";
+        let doc = Json::parse(s).ok().unwrap();
+
+        assert_eq!(format!("{}", doc), s);
+        assert_eq!(doc.get("a").and_then(|a| a.index(0)).and_then(|n| n.as_u64()), Some(1));
+        assert_eq!(doc.get("a").and_then(|a| a.index(1)).and_then(|n| n.as_u64()), None);
+        assert_eq!(doc.get("b").and_then(|b| b.as_str()), Some("x\"y\n"));
+        assert_eq!(doc.get("d").and_then(|d| d.as_bool()), Some(true));
+        assert_eq!(Json::String("0x401000".to_string()).as_u64(), Some(0x401000));
+        assert!(Json::parse("[1,").is_err());
+        assert!(Json::parse("{} x").is_err());
+    }
+}
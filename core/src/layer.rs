@@ -41,7 +41,8 @@
 
 
 use Result;
-use std::collections::HashMap;
+use region::PAGE_SIZE;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::Read;
 use std::ops::Range;
@@ -76,6 +77,15 @@ pub enum LayerIter<'a> {
         /// Starting point
         pos: u64,
     },
+    /// Pages of cells replacing the ones below, see `Region::write`.
+    Paged {
+        /// Pages by number, `None` for cells not written
+        pages: &'a BTreeMap<u64, Arc<Vec<Cell>>>,
+        /// Cells below the pages
+        mapped: Box<LayerIter<'a>>,
+        /// Starting point
+        pos: u64,
+    },
     /// Concatenation of two layers
     Concat {
         /// First layer
@@ -115,6 +125,16 @@ impl<'a> Iterator for LayerIter<'a> {
                     None
                 }
             }
+            LayerIter::Paged { pages: ref pg, mapped: ref mut i, pos: ref mut p } => {
+                if let Some(covered) = i.next() {
+                    let written = pg.get(&(*p / PAGE_SIZE)).and_then(|c| c.get((*p % PAGE_SIZE) as usize).cloned()).and_then(|c| c);
+
+                    *p += 1;
+                    Some(written.or(covered))
+                } else {
+                    None
+                }
+            }
             LayerIter::Concat { car: ref mut a, cdr: ref mut b } => {
                 if let Some(aa) = a.next() {
                     Some(aa)
@@ -158,6 +178,7 @@ impl<'a> LayerIter<'a> {
             LayerIter::Defined(None) => LayerIter::Defined(None),
             LayerIter::Defined(Some(ref buf)) => LayerIter::Defined(Some(&buf[r.start as usize..real_end as usize])),
             LayerIter::Sparse { map: ref m, mapped: ref i, pos: ref p, .. } => LayerIter::Sparse { map: m, mapped: Box::new(i.cut(r)), pos: p + r.start },
+            LayerIter::Paged { pages: ref pg, mapped: ref i, pos: ref p } => LayerIter::Paged { pages: pg, mapped: Box::new(i.cut(r)), pos: p + r.start },
             LayerIter::Concat { car: ref a, cdr: ref b } => {
                 if r.start < a.len() && real_end <= a.len() {
                     a.cut(r)
@@ -194,6 +215,7 @@ impl<'a> LayerIter<'a> {
             LayerIter::Defined(None) => 0,
             LayerIter::Defined(Some(ref r)) => r.len() as u64,
             LayerIter::Sparse { mapped: ref m, .. } => m.len(),
+            LayerIter::Paged { mapped: ref m, .. } => m.len(),
             LayerIter::Concat { car: ref a, cdr: ref b } => a.len() + b.len(),
         }
     }
@@ -16,7 +16,7 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use errors::Error;
+use Error;
 
 use panopticon_graph_algos::{AdjacencyList, BidirectionalGraphTrait, EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait,
                              VertexListGraphTrait};
@@ -28,9 +28,9 @@ use std::cmp::{Ordering, max, min};
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
-use sugiyama::order::{crossings, initial_ordering, optimize_ordering_once};
+use layout::order::{crossings, initial_ordering, optimize_ordering_once};
 
-use sugiyama::rank::{add_virtual_vertices, compute_ranking, ensure_single_entry, normalize_rank, remove_cycles, remove_loops, remove_parallel_edges};
+use layout::rank::{add_virtual_vertices, compute_ranking, ensure_single_entry, normalize_rank, remove_cycles, remove_loops, remove_parallel_edges};
 
 fn partial_max(a: f32, b: f32) -> f32 {
     if a < b { b } else { a }
@@ -40,33 +40,59 @@ fn partial_min(a: f32, b: f32) -> f32 {
     if a > b { b } else { a }
 }
 
+/// Intermediate state of a layout. Each `linear_layout_*` step consumes one state and returns
+/// the next.
 #[derive(Clone)]
 pub enum LinearLayout {
+    /// Input graph turned into a DAG with a single entry
     Cooked {
+        /// Working graph, vertices are labeled with the input vertex, edges with the input edge index
         graph: AdjacencyList<usize, usize>,
+        /// Input vertex to working graph vertex
         rev: HashMap<usize, AdjacencyListVertexDescriptor>,
+        /// Entry vertex
         head: AdjacencyListVertexDescriptor,
+        /// Edges reversed to break cycles
         revd_edge_labels: HashSet<usize>,
+        /// Parallel edges removed from the graph
         revd_parallel_edges: Vec<(usize, AdjacencyListVertexDescriptor, AdjacencyListVertexDescriptor)>,
     },
+    /// Vertices assigned to ranks, long edges split by virtual vertices
     Ranked {
+        /// Working graph, vertices are labeled with the input vertex, edges with the input edge index
         graph: AdjacencyList<usize, usize>,
+        /// Input vertex to working graph vertex
         rev: HashMap<usize, AdjacencyListVertexDescriptor>,
+        /// Entry vertex
         head: AdjacencyListVertexDescriptor,
+        /// Label of the first virtual vertex
         virt_start: usize,
+        /// Rank of each vertex
         rank: HashMap<AdjacencyListVertexDescriptor, isize>,
+        /// Edges reversed to break cycles
         revd_edge_labels: HashSet<usize>,
     },
+    /// Crossings inside each rank are being minimized
     Ordering {
+        /// Number of calls to `linear_layout_order` left
         iterations_left: usize,
+        /// Working graph, vertices are labeled with the input vertex, edges with the input edge index
         graph: AdjacencyList<usize, usize>,
+        /// Input vertex to working graph vertex
         rev: HashMap<usize, AdjacencyListVertexDescriptor>,
+        /// Entry vertex
         head: AdjacencyListVertexDescriptor,
+        /// Label of the first virtual vertex
         virt_start: usize,
+        /// Edges reversed to break cycles
         revd_edge_labels: HashSet<usize>,
+        /// Edges between each pair of adjacent ranks
         bipartite: HashMap<(usize, usize), Vec<(AdjacencyListEdgeDescriptor, AdjacencyListEdgeDescriptor)>>,
+        /// Vertices of each rank from left to right
         order: Vec<Vec<AdjacencyListVertexDescriptor>>,
+        /// Rank of each vertex
         rank: HashMap<AdjacencyListVertexDescriptor, isize>,
+        /// Number of edge crossings of `order`
         xings: usize,
     },
 }
@@ -98,6 +124,8 @@ pub fn linear_layout(
         block_spacing,
     )
 }
+/// Starts the layout of the graph with `vertices` and `edges`. `entry` is placed at the top, if
+/// it's None a new entry vertex is added. Fails if the graph is empty or not connected.
 pub fn linear_layout_start(vertices: &Vec<usize>, edges: &Vec<(usize, usize)>, entry: Option<usize>) -> Result<LinearLayout, Error> {
     let mut graph = AdjacencyList::<usize, usize>::new();
     let mut rev = HashMap::<usize, AdjacencyListVertexDescriptor>::new();
@@ -141,6 +169,7 @@ pub fn linear_layout_start(vertices: &Vec<usize>, edges: &Vec<(usize, usize)>, e
     )
 }
 
+/// Assigns vertices of a `Cooked` layout to ranks.
 pub fn linear_layout_rank(layout: LinearLayout) -> Result<LinearLayout, Error> {
     if let LinearLayout::Cooked { mut graph, rev, head, revd_parallel_edges, revd_edge_labels } = layout {
         // Desc -> Rank
@@ -246,6 +275,7 @@ pub fn linear_layout_rank(layout: LinearLayout) -> Result<LinearLayout, Error> {
     }
 }
 
+/// Computes the initial order of the vertices in each rank of a `Ranked` layout.
 pub fn linear_layout_initial_order(layout: LinearLayout) -> Result<LinearLayout, Error> {
     if let LinearLayout::Ranked { graph, rev, head, virt_start, rank, revd_edge_labels } = layout {
         // logical intra-rank ordering
@@ -276,6 +306,7 @@ pub fn linear_layout_initial_order(layout: LinearLayout) -> Result<LinearLayout,
     }
 }
 
+/// Does one round of crossing minimization on an `Ordering` layout.
 pub fn linear_layout_order(mut layout: LinearLayout) -> Result<LinearLayout, Error> {
     match layout {
         LinearLayout::Ordering { iterations_left: 0, .. } => {}
@@ -324,6 +355,9 @@ pub fn linear_layout_structural(vertices: &Vec<usize>, edges: &Vec<(usize, usize
     Ok(ordered)
 }
 
+/// Computes the center of each vertex and the segments of each edge of an `Ordering` layout.
+/// `dims` are the width and height of the vertices. Edges are returned as list of line segments
+/// `(x1, y1, x2, y2)`, the position of the arrow tail and the position of the arrow head.
 pub fn linear_layout_placement(
     vertices: &Vec<usize>,
     edges: &Vec<(usize, usize)>,
@@ -1303,7 +1337,7 @@ pub fn compute_x_coordinates(
         )
         .collect::<Vec<_>>();
 
-    let final_position = HashMap::from_iter(
+    let mut final_position = HashMap::from_iter(
         graph
             .vertices()
             .map(
@@ -1320,5 +1354,18 @@ pub fn compute_x_coordinates(
             )
     );
 
+    // balancing can move neighbors closer than allowed, push them apart again
+    for (r, word) in order.iter().enumerate() {
+        for i in 1..word.len() {
+            let (a, b) = (word[i - 1], word[i]);
+            let width = |vx| dims.get(&vx).map(|x| x.0).unwrap_or(0.);
+            let min_x = final_position[&a] + (width(a) + width(b)) / 2. + node_spacing(r);
+
+            if final_position[&b] < min_x {
+                final_position.insert(b, min_x);
+            }
+        }
+    }
+
     final_position
 }
@@ -16,12 +16,158 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+//! Hierarchical layout of control flow graphs.
+//!
+//! Implements the layered graph drawing algorithm by Sugiyama et.al. Cycles are broken by
+//! reversing back edges, vertices are assigned to ranks and long edges are split by virtual
+//! vertices. Crossings are minimized using the weighted median heuristic and the vertices are
+//! placed using Brandes & Köpf's algorithm. Edges are routed as orthogonal-ish polylines with
+//! separate ports for each incoming and outgoing edge.
+//!
+//! [`function`] lays out the control flow graph of a function. The individual steps are exposed
+//! as `linear_layout_*` functions, so front-ends can run the crossing minimization in the
+//! background and abort it. The layout only depends on the graph and the dimensions of the basic
+//! blocks, so all front-ends draw the same picture.
+//!
+//! [`function`]: fn.function.html
+
+use {ControlFlowEdge, ControlFlowRef, Function, Result};
+use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, VertexListGraphTrait};
+use panopticon_graph_algos::adjacency_list::AdjacencyListVertexDescriptor;
+use std::collections::HashMap;
+use std::f32;
+
 mod order;
 mod linear;
 mod rank;
 
 pub use self::linear::{LinearLayout, linear_layout_initial_order, linear_layout_order, linear_layout_placement, linear_layout_rank, linear_layout_start};
 
+/// Distances used by [`function`].
+///
+/// [`function`]: fn.function.html
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Spacing {
+    /// Horizontal space between basic blocks
+    pub node: f32,
+    /// Vertical space between ranks
+    pub rank: f32,
+    /// Horizontal space between edges entering or leaving the same basic block
+    pub port: f32,
+    /// Width of edges from a basic block to itself
+    pub loops: f32,
+    /// Length of the vertical part of an edge at its basic blocks
+    pub entry: f32,
+    /// Space between a basic block and its edges
+    pub block: f32,
+}
+
+impl Default for Spacing {
+    fn default() -> Spacing {
+        Spacing { node: 40., rank: 20., port: 50., loops: 30., entry: 30., block: 8. }
+    }
+}
+
+/// Route of a jump.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Route {
+    /// Line segments `(x1, y1, x2, y2)`
+    pub segments: Vec<(f32, f32, f32, f32)>,
+    /// Arrow tail
+    pub start: (f32, f32),
+    /// Arrow head
+    pub end: (f32, f32),
+}
+
+/// Layout of a control flow graph. The top left corner of the bounding box is `(0, 0)`.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Layout {
+    /// Center of each basic block
+    pub nodes: HashMap<ControlFlowRef, (f32, f32)>,
+    /// Route of each jump
+    pub edges: HashMap<ControlFlowEdge, Route>,
+    /// Width of the bounding box
+    pub width: f32,
+    /// Height of the bounding box
+    pub height: f32,
+}
+
+/// Lays out the control flow graph of `func`. `dims` are the width and height of each basic
+/// block, missing blocks are assumed to be points. Fails if the graph is empty or not connected.
+pub fn function(func: &Function, dims: &HashMap<ControlFlowRef, (f32, f32)>, spacing: &Spacing) -> Result<Layout> {
+    let cfg = func.cfg();
+    let vertices = cfg.vertices().map(|vx| vx.0).collect::<Vec<_>>();
+    let edge_refs = cfg.edges().collect::<Vec<_>>();
+    let edges = edge_refs.iter().map(|&e| (cfg.source(e).0, cfg.target(e).0)).collect::<Vec<_>>();
+    let dims = dims.iter().map(|(vx, &wh)| (vx.0, wh)).collect::<HashMap<_, _>>();
+    let mut layout = linear_layout_start(&vertices, &edges, Some(func.entry_point_ref().0))?;
+
+    layout = linear_layout_rank(layout)?;
+    layout = linear_layout_initial_order(layout)?;
+    loop {
+        match layout {
+            LinearLayout::Ordering { iterations_left: 0, .. } => break,
+            _ => layout = linear_layout_order(layout)?,
+        }
+    }
+
+    let (nodes, routes) = linear_layout_placement(
+        &vertices,
+        &edges,
+        &layout,
+        &dims,
+        spacing.node,
+        spacing.rank,
+        spacing.port,
+        spacing.loops,
+        spacing.entry,
+        spacing.block,
+    )?;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+    let mut extend = |x: f32, y: f32| {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    };
+
+    for (n, &(x, y)) in nodes.iter() {
+        let (w, h) = *dims.get(n).unwrap_or(&(0., 0.));
+
+        extend(x - w / 2., y - h / 2.);
+        extend(x + w / 2., y + h / 2.);
+    }
+    for &(ref segs, _, _) in routes.values() {
+        for &(x1, y1, x2, y2) in segs.iter() {
+            extend(x1, y1);
+            extend(x2, y2);
+        }
+    }
+
+    let shift = |(x, y): (f32, f32)| (x - min_x, y - min_y);
+
+    Ok(
+        Layout {
+            nodes: nodes.into_iter().map(|(n, pos)| (AdjacencyListVertexDescriptor(n), shift(pos))).collect(),
+            edges: routes
+                .into_iter()
+                .map(
+                    |(idx, (segs, start, end))| {
+                        let route = Route {
+                            segments: segs.into_iter().map(|(x1, y1, x2, y2)| (x1 - min_x, y1 - min_y, x2 - min_x, y2 - min_y)).collect(),
+                            start: shift(start),
+                            end: shift(end),
+                        };
+                        (edge_refs[idx], route)
+                    }
+                )
+                .collect(),
+            width: max_x - min_x,
+            height: max_y - min_y,
+        }
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use panopticon_graph_algos::{AdjacencyList, EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
@@ -29,8 +175,8 @@ mod tests {
     use std::{f32, isize, usize};
     use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
-    use sugiyama::linear::{compute_x_coordinates, linear_layout};
-    use sugiyama::rank::{add_virtual_vertices, ensure_single_entry, remove_cycles, remove_loops};
+    use layout::linear::{compute_x_coordinates, linear_layout};
+    use layout::rank::{add_virtual_vertices, ensure_single_entry, remove_cycles, remove_loops};
 
     #[test]
     fn test_remove_loops() {
@@ -608,4 +754,42 @@ mod tests {
             virt_start,
         );
     }
+    /*
+     * b0 -> b1 -> b3
+     *  \--> b2 --/
+     */
+    #[test]
+    fn diamond() {
+        use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Guard, Region};
+        use super::{Spacing, function};
+
+        let mut cfg = ControlFlowGraph::new();
+        let b = (0..4).map(|_| cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![])))).collect::<Vec<_>>();
+        let mut func = ::Function::undefined(0, None, &Region::undefined("RAM".to_string(), 0x10), None);
+
+        cfg.add_edge(Guard::True, b[0], b[1]);
+        cfg.add_edge(Guard::True, b[0], b[2]);
+        cfg.add_edge(Guard::True, b[1], b[3]);
+        cfg.add_edge(Guard::True, b[2], b[3]);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b[0]);
+
+        let dims = HashMap::from_iter(b.iter().map(|&vx| (vx, (100f32, 50f32))));
+        let layout = function(&func, &dims, &Spacing::default()).ok().unwrap();
+        let pos = b.iter().map(|vx| layout.nodes[vx]).collect::<Vec<_>>();
+
+        assert_eq!(layout.nodes.len(), 4);
+        assert_eq!(layout.edges.len(), 4);
+        assert!(pos[0].1 < pos[1].1 && pos[1].1 == pos[2].1 && pos[2].1 < pos[3].1);
+        assert!(pos[1].0 != pos[2].0);
+        assert!((pos[1].0 - pos[2].0).abs() >= 100.);
+        assert!(pos[0].0 - 50. >= 0. && pos[0].1 - 25. >= 0.);
+        for route in layout.edges.values() {
+            assert!(!route.segments.is_empty());
+            assert!(route.start.1 < route.end.1);
+            for &(x1, y1, x2, y2) in route.segments.iter() {
+                assert!(x1 >= 0. && y1 >= 0. && x2 <= layout.width && y2 <= layout.height);
+            }
+        }
+    }
 }
@@ -17,12 +17,13 @@
  */
 
 
-use panopticon_graph_algos::{AdjacencyList, BidirectionalGraphTrait, EdgeListGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use panopticon_graph_algos::{AdjacencyList, BidirectionalGraphTrait, EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
 
 use panopticon_graph_algos::adjacency_list::AdjacencyListVertexDescriptor;
 
 use panopticon_graph_algos::search::{EdgeKind, VertexEvent, depth_first_visit};
 use std::{isize, usize};
+use std::cmp::max;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
@@ -168,48 +169,30 @@ pub fn remove_parallel_edges(graph: &mut AdjacencyList<usize, usize>) -> Vec<(us
     to_rm.iter().map(|&(_, a, b, c)| (a, b, c)).collect::<Vec<_>>()
 }
 
-/// Computes the ranks for all vertices.
+/// Computes the ranks for all vertices. `graph` must be acyclic. Every vertex is placed on the
+/// lowest rank below all its predecessors (longest path layering).
 pub fn compute_ranking(graph: &AdjacencyList<usize, usize>) -> HashMap<AdjacencyListVertexDescriptor, isize> {
-    use cassowary::{Solver, Variable};
-    use cassowary::WeightedRelation::*;
-    use cassowary::strength::{REQUIRED, WEAK};
-
-    let mut vx2var = HashMap::new();
-    let mut solver = Solver::new();
-
-    for vx in graph.vertices() {
-        let var = Variable::new();
-
-        // First rank is 0
-        let _ = solver.add_constraint(var | GE(REQUIRED) | 0.0);
-
-        // Minimize ranks
-        let _ = solver.add_constraint(var | EQ(WEAK) | 0.0);
-
-        vx2var.insert(vx, var);
-    }
-
-    for e in graph.edges() {
-        let from_vx = vx2var[&graph.source(e)];
-        let to_vx = vx2var[&graph.target(e)];
-
-        // Adjacent vertices are at least on rank apart
-        let _ = solver.add_constraint(to_vx - from_vx | GE(REQUIRED) | 1.0);
+    let mut ret = HashMap::<AdjacencyListVertexDescriptor, isize>::new();
+    let mut in_deg = HashMap::<AdjacencyListVertexDescriptor, usize>::from_iter(graph.vertices().map(|vx| (vx, graph.in_degree(vx))));
+    let mut queue = graph.vertices().filter(|vx| in_deg[vx] == 0).collect::<Vec<_>>();
+
+    while let Some(vx) = queue.pop() {
+        let r = *ret.entry(vx).or_insert(0);
+
+        for e in graph.out_edges(vx) {
+            let t = graph.target(e);
+            let deg = in_deg.get_mut(&t).unwrap();
+            let rank = ret.entry(t).or_insert(0);
+
+            *rank = max(*rank, r + 1);
+            *deg -= 1;
+            if *deg == 0 {
+                queue.push(t);
+            }
+        }
     }
 
-    solver.update_variables();
-
-    HashMap::from_iter(
-        vx2var
-            .iter()
-            .map(
-                |(&vx, &var)| {
-                    let rank = solver.value_for(var);
-
-                    (vx, rank.unwrap_or(0.0) as isize)
-                }
-            )
-    )
+    ret
 }
 
 pub fn add_virtual_vertices(rank: &mut HashMap<AdjacencyListVertexDescriptor, isize>, graph: &mut AdjacencyList<usize, usize>) -> (usize, usize) {
@@ -77,6 +77,8 @@
 
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate lazy_static;
 
 extern crate num;
 extern crate flate2;
@@ -88,13 +90,19 @@ extern crate quickcheck;
 extern crate serde;
 #[macro_use] extern crate serde_derive;
 extern crate serde_cbor;
+extern crate tempdir;
+extern crate lzma_rs;
+extern crate lz4_flex;
+#[cfg(feature = "sqlite")]
+#[macro_use]
+extern crate rusqlite;
 
 #[cfg(test)]
 extern crate env_logger;
 
 // core
 pub mod disassembler;
-pub use disassembler::{Architecture, Disassembler, Match, State};
+pub use disassembler::{Architecture, DecodeCacheStats, Disassembler, Match, State, Trace, TraceEvent};
 
 #[macro_use]
 pub mod il;
@@ -106,7 +114,7 @@ pub mod basic_block;
 pub use basic_block::BasicBlock;
 
 pub mod function;
-pub use function::{ControlFlowEdge, ControlFlowGraph, ControlFlowRef, ControlFlowTarget, Function, FunctionKind};
+pub use function::{Alias, ControlFlowEdge, ControlFlowGraph, ControlFlowRef, ControlFlowTarget, DeferredIl, Function, FunctionKind, IlSource, Limit, Limits, NameSource, Recovery};
 
 pub mod program;
 pub use program::{CallGraph, CallGraphRef, CallTarget, Program};
@@ -115,7 +123,7 @@ pub mod project;
 pub use project::Project;
 
 pub mod region;
-pub use region::{Region, World};
+pub use region::{LayerInfo, Region, Snapshot, Words, World};
 
 pub mod layer;
 pub use layer::{Layer, LayerIter, OpaqueLayer};
@@ -123,6 +131,131 @@ pub use layer::{Layer, LayerIter, OpaqueLayer};
 pub mod result;
 pub use result::{Error, Result};
 
+pub mod syscall;
+pub use syscall::{Syscall, SyscallAbi};
+
+pub mod calling_convention;
+pub use calling_convention::CallingConvention;
+
+pub mod identify;
+pub use identify::{Candidate, Probe, identify};
+
 // file formats
 pub mod loader;
-pub use loader::{Machine, load};
+pub use loader::{Machine, MappingSymbol, RawMapping, RawSegment, Relocation, load, load_at, load_raw};
+
+pub mod wasm;
+
+pub mod pdb;
+pub use pdb::Pdb;
+
+pub mod uefi;
+
+pub mod demangle;
+pub use demangle::demangle;
+
+// analyses
+pub mod packer;
+pub use packer::{Anomaly, Finding, Section};
+
+pub mod strings;
+pub use strings::{StringLiteral, StringRef};
+
+pub mod rtti;
+pub use rtti::{BaseClass, Class, TypeDatabase};
+
+pub mod eh;
+pub use eh::{CallSite, Fde};
+
+pub mod xref;
+pub use xref::{Xref, XrefDatabase, XrefKind};
+
+pub mod hardening;
+pub use hardening::{HardeningReport, Relro};
+
+pub mod pointer;
+pub use pointer::PointerKind;
+
+pub mod annotation;
+pub use annotation::{Annotation, Annotations, Location};
+
+pub mod patch;
+pub use patch::Patch;
+
+pub mod external;
+pub use external::ExternalDatabase;
+
+pub mod interchange;
+
+pub mod image;
+pub use image::Image;
+
+pub mod gdb;
+
+pub mod coverage;
+pub use coverage::{BasicBlockIndex, Coverage, TraceFormat};
+
+pub mod replay;
+pub use replay::{Position, Replay};
+
+pub mod datatype;
+pub use datatype::{DataType, Declaration, Definition, Member, TypeLibrary};
+
+pub mod signature;
+pub use signature::{Argument, Parameter, Signature, Storage};
+
+pub mod data;
+pub use data::Element;
+
+pub mod rename;
+pub use rename::Collision;
+
+pub mod search;
+pub use search::{Hit, Pattern};
+
+pub mod event;
+pub use event::AnalysisEvent;
+
+pub mod cancel;
+pub use cancel::CancellationToken;
+
+pub mod spill;
+pub use spill::SpillCache;
+
+pub mod cache;
+pub use cache::FunctionCache;
+
+pub mod mmio;
+pub use mmio::{Field, Peripheral, PeripheralMap, Register};
+
+pub mod decompress;
+pub use decompress::Codec;
+
+pub mod sweep;
+pub use sweep::{Decoded, Strategy};
+
+pub mod gaps;
+pub use gaps::{Evidence, Proposal};
+
+pub mod golden;
+
+pub mod syntax;
+
+pub mod listing;
+
+pub mod terminal;
+
+pub mod svg;
+
+pub mod report;
+
+pub mod layout;
+pub use layout::{Layout, Route, Spacing};
+
+pub mod json;
+pub use json::Json;
+
+#[cfg(feature = "sqlite")]
+pub mod database;
+#[cfg(feature = "sqlite")]
+pub use database::{Database, FunctionEntry};
@@ -0,0 +1,269 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Linear text listings in the style of `objdump -d`.
+//!
+//! [`function`] prints each mnemonic of a function on its own line, in address order: address,
+//! raw bytes, opcode and operands formatted according to the mnemonic's format string. Code
+//! pointers to known functions and imports are followed by the symbol name in angle brackets,
+//! like objdump does, relocated operands by the relocation's symbol. Mnemonics referenced from
+//! elsewhere end with a comment listing the references found by `xref::collect`. Memory operands
+//! accessing variables with a type from `Project::data_types` are replaced by the variable, like
+//! `player->health`, see `datatype::typed_operands`.
+//!
+//! The output contains no colors and only depends on the project, so it can be diffed between
+//! two versions of a binary or of Panopticon. [`program`] prints all functions of a program.
+//! Mnemonics are printed as the disassembler describes them, the `_with` variants like
+//! [`function_with`] take a `syntax::Syntax` profile, e.g. AT&T for x86, and formatting options.
+//!
+//! [`function`]: fn.function.html
+//! [`function_with`]: fn.function_with.html
+//! [`program`]: fn.program.html
+
+use {Function, Mnemonic, MnemonicFormatToken, Program, Project, Rvalue, XrefKind, datatype, demangle};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem;
+use syntax::{self, Native, Operand, Options, Syntax};
+
+/// Number of bytes shown per line. Longer mnemonics continue on the next line.
+pub const BYTES_PER_LINE: usize = 7;
+
+/// Line of a function listing.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Line {
+    /// Address of the first byte on the line
+    pub address: u64,
+    /// Bytes as hex digits, `??` if undefined
+    pub bytes: Vec<String>,
+    /// Opcode and operands, empty if the line continues the bytes of the previous one
+    pub text: String,
+    /// Cross references, empty if there are none
+    pub comment: String,
+}
+
+/// Name of the function or import at `address`.
+fn symbol(proj: &Project, prog: &Program, address: u64) -> Option<String> {
+    prog.find_function_by(|f| f.start() == address)
+        .map(|f| f.display_name())
+        .or_else(|| prog.imports.get(&address).cloned())
+        .or_else(|| proj.imports.get(&address).cloned())
+}
+
+/// Operands of `mne` in the order of its format string. Variables in `typed` are replaced by the
+/// expression they map to.
+fn operands(proj: &Project, prog: &Program, mne: &Mnemonic, typed: &HashMap<(u64, Cow<'static, str>), String>) -> Vec<Operand> {
+    let mut ops = mne.operands.iter();
+    let mut ret = vec![];
+    // symbol of the relocation applied to this mnemonic, if any
+    let reloc = prog.relocation(&mne.area).and_then(|r| r.symbol.as_ref()).map(|s| demangle(s).unwrap_or_else(|| s.clone()));
+
+    for tok in mne.format_string.iter() {
+        let (signed, code, pointer) = match tok {
+            &MnemonicFormatToken::Literal(_) => continue,
+            &MnemonicFormatToken::Variable { has_sign } => (has_sign, false, false),
+            &MnemonicFormatToken::Pointer { is_code, .. } => (false, is_code, true),
+        };
+
+        let op = match ops.next() {
+            Some(&Rvalue::Constant { value, size }) => {
+                let name = if code { symbol(proj, prog, value) } else { None };
+                let symbol = name.or_else(|| if pointer { reloc.clone() } else { None });
+
+                Operand::Immediate { value: value, size: size, signed: signed, code: code, symbol: symbol }
+            }
+            Some(&Rvalue::Variable { ref name, .. }) => {
+                match typed.get(&(mne.area.start, name.clone())) {
+                    Some(expr) => Operand::Expression(expr.clone()),
+                    None => Operand::variable(name),
+                }
+            }
+            Some(&Rvalue::Undefined) | None => Operand::Undefined,
+        };
+
+        ret.push(op);
+    }
+
+    ret
+}
+
+/// Returns the opcode of `mne` followed by its operands, as printed by [`function`].
+///
+/// [`function`]: fn.function.html
+pub fn instruction(proj: &Project, prog: &Program, mne: &Mnemonic) -> String {
+    instruction_with(proj, prog, mne, &Native, &Options::default())
+}
+
+/// Returns the opcode of `mne` followed by its operands in `syntax`.
+pub fn instruction_with(proj: &Project, prog: &Program, mne: &Mnemonic, syntax: &Syntax, options: &Options) -> String {
+    syntax::render(syntax, mne, &operands(proj, prog, mne, &HashMap::new()), options)
+}
+
+/// Comment listing the references to `address`, like `xrefs: 0x100 (call)`. Empty if there are
+/// none.
+pub fn comment(proj: &Project, address: u64) -> String {
+    let mut refs = proj.xrefs_to(address)
+        .iter()
+        .map(
+            |x| {
+                let kind = match x.kind {
+                    XrefKind::Read => "read",
+                    XrefKind::Write => "write",
+                    XrefKind::Call => "call",
+                    XrefKind::Jump => "jump",
+                    XrefKind::Address => "address",
+                };
+                (x.address, kind)
+            }
+        )
+        .collect::<Vec<_>>();
+
+    refs.sort();
+    refs.dedup();
+
+    if refs.is_empty() {
+        String::new()
+    } else {
+        format!("xrefs: {}", refs.iter().map(|&(a, k)| format!("{:#x} ({})", a, k)).collect::<Vec<_>>().join(", "))
+    }
+}
+
+/// Lines of `func` in `syntax`, in address order. Mnemonics longer than `BYTES_PER_LINE` bytes
+/// continue on lines without text.
+pub fn lines(proj: &Project, prog: &Program, func: &Function, syntax: &Syntax, options: &Options) -> Vec<Line> {
+    let mut mnes = func.basic_blocks().flat_map(|bb| bb.mnemonics.iter()).collect::<Vec<_>>();
+    let typed = datatype::typed_operands(proj, func);
+    let mut ret = vec![];
+
+    mnes.sort_by_key(|m| (m.area.start, m.area.end));
+    mnes.dedup_by_key(|m| (m.area.start, m.area.end));
+
+    for mne in mnes {
+        let bytes = proj.region()
+            .iter()
+            .seek(mne.area.start)
+            .take(mne.area.len() as usize)
+            .map(|b| b.map(|b| format!("{:02x}", b)).unwrap_or("??".to_string()))
+            .collect::<Vec<_>>();
+        let mut text = syntax::render(syntax, mne, &operands(proj, prog, mne, &typed), options);
+        let mut comment = if mne.area.start == func.start() { String::new() } else { self::comment(proj, mne.area.start) };
+
+        for (i, c) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            ret.push(
+                Line {
+                    address: mne.area.start + (i * BYTES_PER_LINE) as u64,
+                    bytes: c.to_vec(),
+                    text: mem::replace(&mut text, String::new()),
+                    comment: mem::replace(&mut comment, String::new()),
+                }
+            );
+        }
+    }
+
+    ret
+}
+
+/// Returns the listing of `func`, a function of `prog`, one mnemonic per line.
+pub fn function(proj: &Project, prog: &Program, func: &Function) -> String {
+    function_with(proj, prog, func, &Native, &Options::default())
+}
+
+/// Returns the listing of `func` with mnemonics printed in `syntax`.
+pub fn function_with(proj: &Project, prog: &Program, func: &Function, syntax: &Syntax, options: &Options) -> String {
+    let refs = comment(proj, func.start());
+    let mut ret = format!("{:016x} <{}>:{}{}\n", func.start(), func.display_name(), if refs.is_empty() { "" } else { "\t; " }, refs);
+
+    for line in lines(proj, prog, func, syntax, options) {
+        let bytes = line.bytes.join(" ");
+
+        if line.text.is_empty() {
+            let _ = writeln!(ret, "{:8x}:\t{}", line.address, bytes);
+        } else {
+            let sep = if line.comment.is_empty() { "" } else { "\t; " };
+            let _ = writeln!(ret, "{:8x}:\t{:<w$}\t{}{}{}", line.address, bytes, line.text, sep, line.comment, w = BYTES_PER_LINE * 3 - 1);
+        }
+    }
+
+    ret
+}
+
+/// Returns the listings of all functions in `prog` ordered by address, separated by empty lines.
+pub fn program(proj: &Project, prog: &Program) -> String {
+    program_with(proj, prog, &Native, &Options::default())
+}
+
+/// Returns the listings of all functions in `prog` with mnemonics printed in `syntax`.
+pub fn program_with(proj: &Project, prog: &Program, syntax: &Syntax, options: &Options) -> String {
+    let mut funcs = prog.functions().collect::<Vec<_>>();
+
+    funcs.sort_by_key(|f| f.start());
+    funcs.iter().map(|f| function_with(proj, prog, f, syntax, options)).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, Bound, ControlFlowGraph, ControlFlowTarget, Layer, Region, Xref};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn function(start: u64, mnes: Vec<Mnemonic>, name: &str, region: &Region) -> Function {
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(mnes)));
+        let mut func = Function::undefined(start, None, region, Some(name.to_string()));
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    /*
+     * 0x100: call 0x200; movabs rax, 0x1122334455667788
+     * 0x200: ret
+     */
+    #[test]
+    fn objdump_style() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        let code = vec![0xe8, 0xfb, 0x00, 0x00, 0x00, 0x48, 0xb8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11];
+
+        assert!(reg.cover(Bound::new(0x100, 0x10f), Layer::wrap(code)));
+        assert!(reg.cover(Bound::new(0x200, 0x201), Layer::wrap(vec![0xc3])));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        let call = Mnemonic::new(0x100..0x105, "call".to_string(), "{c:RAM}".to_string(), vec![Rvalue::new_u64(0x200)].iter(), vec![].iter()).ok().unwrap();
+        let rax = Rvalue::Variable { name: "RAX".into(), subscript: None, offset: 0, size: 64 };
+        let mov = Mnemonic::new(0x105..0x10f, "movabs".to_string(), "{u}, {u}".to_string(), vec![rax, Rvalue::new_u64(0x1122334455667788)].iter(), vec![].iter())
+            .ok()
+            .unwrap();
+        let ret = Mnemonic::new(0x200..0x201, "ret".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let caller = function(0x100, vec![call, mov], "main", proj.region());
+        let callee = function(0x200, vec![ret], "callee", proj.region());
+        let mut prog = Program::new("prog");
+
+        proj.xrefs.insert(Xref { function: caller.uuid().clone(), address: 0x100, statement: None, target: 0x200, kind: XrefKind::Call });
+        prog.insert(caller);
+        prog.insert(callee);
+        proj.code.push(prog);
+
+        assert_eq!(
+            program(&proj, &proj.code[0]),
+            "0000000000000100 <main>:\n     100:\te8 fb 00 00 00      \tcall 0x200 <callee>\n     105:\t48 b8 88 77 66 55 44\tmovabs rax, 0x1122334455667788\n     10c:\t33 22 11\n\n\
+             0000000000000200 <callee>:\t; xrefs: 0x100 (call)\n     200:\tc3                  \tret\n"
+        );
+    }
+}
@@ -16,21 +16,61 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-//! Loader for 32 and 64-bit ELF, PE, and Mach-o files.
+//! Loader for 32 and 64-bit ELF, PE, and Mach-o files as well as WebAssembly modules.
+//!
+//! Fat Mach-o binaries and Windows ARM64X images contain code for more than one architecture.
+//! [`load`](fn.load.html) refuses them, [`slices`](fn.slices.html) lists their architecture slices
+//! and [`load_slice`](fn.load_slice.html) creates a `Project` from a slice of a fat Mach-o
+//! binary. There is no ARM64 disassembler, the two views of an ARM64X image are listed but
+//! can't be loaded.
+//!
+//! Relocations of shared objects and PE images are applied to the loaded memory. The relocated
+//! words are written into a separate layer on top of the file contents and listed in
+//! `Project::relocations`. [`load_at`](fn.load_at.html) loads position independent files at
+//! another address than their preferred one.
+//!
+//! Static libraries (`ar` archives of ELF or COFF object files) are loaded into a single address
+//! space. Each member is placed at its own base, gets a `Region` overlaying that part of the
+//! address space and its own `Program`. References between members are resolved.
+//!
+//! Relocatable object files are laid out section after section at a synthetic base address, by
+//! default `0x1000`, and linked there. References to undefined symbols point to a slot after the
+//! sections, which is listed as an import. Each relocated word is recorded with the symbol it
+//! refers to in `Program::relocations`, so operands can be shown by name. Use `load_at` to link
+//! the object to another address.
+//!
+//! UEFI firmware images are searched for firmware volumes, see the [`uefi`](../uefi/index.html)
+//! module. Every PE32 or TE module found gets an address space and a `Program` of its own, named
+//! after the module. Well known protocol GUIDs inside UEFI images are marked with comments.
+//!
+//! Android boot images are loaded as their kernel. Methods compiled ahead of time in OAT files,
+//! ELF shared objects produced by Android's runtime, are found by walking the method headers in
+//! front of their code and added as functions. The dex bytecode in the files stays data.
+//!
+//! Functions are named by their symbols as is, C++ and Rust names stay mangled. Mangled data
+//! symbols and exports, like vtables, get a comment with their demangled name.
+//!
+//! Memory dumps and firmware images without a header are loaded with
+//! [`load_raw`](fn.load_raw.html). The [`RawMapping`](struct.RawMapping.html) passed along says
+//! which CPU the file is for, where its parts are mapped and where code starts.
 
 
-use {Bound, CallTarget, Layer, Program, Project, Region, Result, Rvalue};
-use goblin::{self, Hint, archive, elf, mach, pe};
+use {AnalysisEvent, Bound, CallTarget, Endianess, HardeningReport, Layer, NameSource, Pdb, Program, Project, Region, Relro, Result, Rvalue, Section, TypeDatabase, demangle, eh, packer, uefi,
+     event, region, wasm};
+use goblin::{self, Hint, elf, mach, pe};
 use goblin::elf::program_header;
 
 use panopticon_graph_algos::MutableGraphTrait;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{Cursor, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use uuid::Uuid;
 
 /// CPU the binary file is intended for.
-#[derive(Clone,Copy,Debug)]
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
 pub enum Machine {
     /// 8-bit AVR
     Avr,
@@ -38,6 +78,187 @@ pub enum Machine {
     Amd64,
     /// Intel x86
     Ia32,
+    /// 32-bit ARM, possibly mixed with Thumb code
+    Arm,
+    /// 32-bit MIPS
+    Mips(Endianess),
+    /// 64-bit MIPS
+    Mips64(Endianess),
+    /// RV32 RISC-V. Carries the ELF header flags describing the ABI and compressed instruction use
+    RiscV32(u32),
+    /// RV64 RISC-V. Carries the ELF header flags describing the ABI and compressed instruction use
+    RiscV64(u32),
+    /// Motorola 68000
+    M68k,
+    /// Intel 8051
+    Mcs51,
+    /// TI MSP430. Carries the ELF header flags, which hold the CPU model
+    Msp430(u32),
+    /// SuperH. Carries the byte order and the ELF header flags, which hold the CPU model
+    SuperH(Endianess, u32),
+    /// WebAssembly module
+    Wasm,
+}
+
+impl Machine {
+    /// Returns the same CPU running with byte order `endianess`. Machines with a fixed byte order
+    /// are returned unchanged.
+    pub fn with_endianess(self, endianess: Endianess) -> Machine {
+        match self {
+            Machine::Mips(_) => Machine::Mips(endianess),
+            Machine::Mips64(_) => Machine::Mips64(endianess),
+            Machine::SuperH(_, flags) => Machine::SuperH(endianess, flags),
+            m => m,
+        }
+    }
+
+    /// Size of a code or data pointer in bytes.
+    pub fn pointer_size(&self) -> usize {
+        match *self {
+            Machine::Avr | Machine::Mcs51 | Machine::Msp430(_) => 2,
+            Machine::Amd64 | Machine::Mips64(_) | Machine::RiscV64(_) => 8,
+            Machine::Ia32 | Machine::Arm | Machine::Mips(_) | Machine::RiscV32(_) | Machine::M68k | Machine::SuperH(..) | Machine::Wasm => 4,
+        }
+    }
+
+    /// Byte order of words in memory.
+    pub fn endianess(&self) -> Endianess {
+        match *self {
+            Machine::Mips(e) | Machine::Mips64(e) | Machine::SuperH(e, _) => e,
+            Machine::M68k => Endianess::Big,
+            _ => Endianess::Little,
+        }
+    }
+}
+
+impl FromStr for Machine {
+    type Err = ::Error;
+
+    /// Parses CPU names like `amd64`, `mipsel` or `sh4`. Machines configured by ELF header flags
+    /// get the flags of a typical file.
+    fn from_str(s: &str) -> Result<Machine> {
+        match s {
+            "avr" => Ok(Machine::Avr),
+            "amd64" | "x86_64" => Ok(Machine::Amd64),
+            "ia32" | "x86" | "i386" => Ok(Machine::Ia32),
+            "arm" => Ok(Machine::Arm),
+            "mips" => Ok(Machine::Mips(Endianess::Big)),
+            "mipsel" => Ok(Machine::Mips(Endianess::Little)),
+            "mips64" => Ok(Machine::Mips64(Endianess::Big)),
+            "mips64el" => Ok(Machine::Mips64(Endianess::Little)),
+            // RVC and the double precision float ABI
+            "riscv32" => Ok(Machine::RiscV32(0x5)),
+            "riscv64" => Ok(Machine::RiscV64(0x5)),
+            "m68k" => Ok(Machine::M68k),
+            "mcs51" | "8051" => Ok(Machine::Mcs51),
+            "msp430" => Ok(Machine::Msp430(0)),
+            // E_MSP430_MACH_MSP430X
+            "msp430x" => Ok(Machine::Msp430(45)),
+            // EF_SH2
+            "sh2" => Ok(Machine::SuperH(Endianess::Big, 2)),
+            "sh4" => Ok(Machine::SuperH(Endianess::Little, 0)),
+            "wasm" => Ok(Machine::Wasm),
+            _ => Err(format!("Unknown machine: {}", s).into()),
+        }
+    }
+}
+
+/// Part of a raw file mapped into memory.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct RawSegment {
+    /// File offset of the first byte
+    pub offset: u64,
+    /// Number of bytes mapped, `None` maps everything up to the end of the file
+    pub size: Option<u64>,
+    /// Address of the first byte
+    pub address: u64,
+}
+
+/// Memory layout of a file without headers, for [`load_raw`](fn.load_raw.html).
+///
+/// ```ignore
+/// // Boot ROM at 0xbfc00000 with its vector table in the first 0x400 bytes
+/// let mapping = RawMapping::new(Machine::Mips(Endianess::Big))
+///     .segment(0, Some(0x400), 0xbfc0_0000)
+///     .segment(0x400, None, 0xbfc0_0400)
+///     .entry(0xbfc0_0000);
+/// let (proj, machine) = load_raw(Path::new("bootrom.bin"), &mapping)?;
+/// ```
+#[derive(Clone,Debug)]
+pub struct RawMapping {
+    /// CPU the file is for
+    pub machine: Machine,
+    /// Mapped parts of the file. The whole file is mapped at address 0 if this is empty
+    pub segments: Vec<RawSegment>,
+    /// Addresses code starts at. Defaults to the address of the first segment
+    pub entry_points: Vec<u64>,
+}
+
+impl RawMapping {
+    /// Maps the whole file at address 0.
+    pub fn new(machine: Machine) -> RawMapping {
+        RawMapping { machine: machine, segments: vec![], entry_points: vec![] }
+    }
+
+    /// Maps the whole file at `address`, replacing all segments added before.
+    pub fn base(mut self, address: u64) -> RawMapping {
+        self.segments = vec![RawSegment { offset: 0, size: None, address: address }];
+        self
+    }
+
+    /// Maps `size` bytes starting at file offset `offset` to `address`.
+    pub fn segment(mut self, offset: u64, size: Option<u64>, address: u64) -> RawMapping {
+        self.segments.push(RawSegment { offset: offset, size: size, address: address });
+        self
+    }
+
+    /// Adds an entry point at `address`.
+    pub fn entry(mut self, address: u64) -> RawMapping {
+        self.entry_points.push(address);
+        self
+    }
+
+    /// Overrides the byte order of the machine.
+    pub fn endianess(mut self, endianess: Endianess) -> RawMapping {
+        self.machine = self.machine.with_endianess(endianess);
+        self
+    }
+}
+
+/// Instruction set hint derived from ARM ELF mapping symbols (`$a`, `$t` and `$d`).
+///
+/// A mapping symbol marks the start of a sequence of ARM code, Thumb code or literal data. The
+/// sequence extends to the next mapping symbol.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum MappingSymbol {
+    /// `$a`: ARM instructions follow
+    Arm,
+    /// `$t`: Thumb instructions follow
+    Thumb,
+    /// `$d`: Literal pool or other data follows
+    Data,
+}
+
+impl MappingSymbol {
+    /// Returns the mapping symbol kind `name` denotes, if any. Mapping symbols may carry a suffix,
+    /// e.g. `$t.42`.
+    pub fn from_name(name: &str) -> Option<MappingSymbol> {
+        match name.split('.').next() {
+            Some("$a") => Some(MappingSymbol::Arm),
+            Some("$t") => Some(MappingSymbol::Thumb),
+            Some("$d") => Some(MappingSymbol::Data),
+            _ => None,
+        }
+    }
+}
+
+/// Pointer sized word the loader relocated. Keyed by its address in `Project::relocations`.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Relocation {
+    /// Address the word points to after relocation, `None` if it depends on an import
+    pub target: Option<u64>,
+    /// Imported or exported symbol the word is bound to
+    pub symbol: Option<String>,
 }
 
 /// Parses a non-fat Mach-o binary from `bytes` at `offset` and creates a `Project` from it. Returns the `Project` instance and
@@ -116,14 +337,7 @@ pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project,
     for export in binary.exports()? {
         if export.offset != 0 {
             debug!("adding: {:?}", &export);
-            prog.call_graph
-                .add_vertex(
-                    CallTarget::Todo(
-                        Rvalue::new_u64(export.offset as u64 + base),
-                        Some(export.name),
-                        Uuid::new_v4(),
-                    )
-                );
+            prog.add_todo(export.offset as u64 + base, export.name, NameSource::Export);
         }
     }
 
@@ -141,15 +355,25 @@ pub fn load_mach(bytes: &[u8], offset: usize, name: String) -> Result<(Project,
 }
 
 /// Parses an ELF 32/64-bit binary from `bytes` and creates a `Project` from it. Returns the `Project` instance and
-/// the CPU its intended for.
-fn load_elf(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
-    use std::collections::HashSet;
+/// the CPU its intended for. All addresses are moved by `base`, which must be 0 unless the file is
+/// position independent.
+fn load_elf(bytes: &[u8], name: String, base: u64) -> Result<(Project, Machine)> {
+    use std::collections::{BTreeMap, HashSet};
+
+    // not known to goblin yet
+    const EM_RISCV: u16 = 243;
 
     let mut cursor = Cursor::new(&bytes);
     let binary = elf::Elf::parse(&bytes)?;
     debug!("elf: {:#?}", &binary);
 
-    let entry = binary.entry;
+    let raw = match ElfFile::parse(bytes) {
+        Some(raw) => raw,
+        None => return Err("Invalid ELF header".into()),
+    };
+    let relocs = raw.relocations();
+    let versions = raw.versions(binary.dynsyms.len());
+
     let (machine, mut reg) = match binary.header.e_machine {
         elf::header::EM_X86_64 => {
             let reg = Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF);
@@ -163,23 +387,92 @@ fn load_elf(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
             let reg = Region::undefined("Flash".to_string(), 0x2_0000);
             (Machine::Avr, reg)
         }
+        elf::header::EM_ARM => {
+            let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+            (Machine::Arm, reg)
+        }
+        elf::header::EM_MIPS => {
+            let endianess = if binary.little_endian { Endianess::Little } else { Endianess::Big };
+
+            if binary.is_64 {
+                let reg = Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF);
+                (Machine::Mips64(endianess), reg)
+            } else {
+                let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+                (Machine::Mips(endianess), reg)
+            }
+        }
+        EM_RISCV => {
+            let flags = binary.header.e_flags;
+
+            if binary.is_64 {
+                let reg = Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF);
+                (Machine::RiscV64(flags), reg)
+            } else {
+                let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+                (Machine::RiscV32(flags), reg)
+            }
+        }
+        elf::header::EM_68K => {
+            let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+            (Machine::M68k, reg)
+        }
+        elf::header::EM_8051 => {
+            let reg = Region::undefined("code".to_string(), 0x1_0000);
+            (Machine::Mcs51, reg)
+        }
+        elf::header::EM_MSP430 => {
+            let reg = Region::undefined("RAM".to_string(), 0x10_0000);
+            (Machine::Msp430(binary.header.e_flags), reg)
+        }
+        elf::header::EM_SH => {
+            let endianess = if binary.little_endian { Endianess::Little } else { Endianess::Big };
+            let reg = Region::undefined("RAM".to_string(), 0x1_0000_0000);
+            (Machine::SuperH(endianess, binary.header.e_flags), reg)
+        }
         machine => return Err(format!("Unsupported machine: {}", machine).into()),
     };
 
+    // ARM code addresses have bit 0 set if they point to Thumb code
+    let is_arm = if let Machine::Arm = machine { true } else { false };
+    let mut mapping_symbols = BTreeMap::<u64, MappingSymbol>::new();
+    let entry = if is_arm {
+        let entry = (binary.entry + base) & !1;
+        let mode = if binary.entry & 1 == 1 { MappingSymbol::Thumb } else { MappingSymbol::Arm };
+        mapping_symbols.insert(entry, mode);
+        entry
+    } else {
+        binary.entry + base
+    };
+
+    let mut sections = vec![];
+
     for ph in &binary.program_headers {
         if ph.p_type == program_header::PT_LOAD {
+            sections.push(
+                Section {
+                    name: format!("LOAD{}", sections.len()),
+                    area: Bound::new(ph.p_vaddr + base, ph.p_vaddr + base + ph.p_memsz),
+                    file_size: ph.p_filesz,
+                    file_offset: ph.p_offset,
+                    read: ph.p_flags & PF_R != 0,
+                    write: ph.p_flags & PF_W != 0,
+                    execute: ph.p_flags & PF_X != 0,
+                }
+            );
+
             let mut buf = vec![0u8; ph.p_filesz as usize];
 
             debug!(
                 "Load ELF {} bytes segment to {:#x}",
                 ph.p_filesz,
-                ph.p_vaddr
+                ph.p_vaddr + base
             );
 
             if cursor.seek(SeekFrom::Start(ph.p_offset)).ok() == Some(ph.p_offset) {
                 cursor.read_exact(&mut buf)?;
                 reg.cover(
-                    Bound::new(ph.p_vaddr, ph.p_vaddr + ph.p_filesz),
+                    Bound::new(ph.p_vaddr + base, ph.p_vaddr + base + ph.p_filesz),
                     Layer::wrap(buf),
                 );
             } else {
@@ -188,6 +481,27 @@ fn load_elf(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
         }
     }
 
+    // Dynamic symbols for relocations, imports are named with their version
+    let symbols = binary.dynsyms
+        .iter()
+        .enumerate()
+        .map(
+            |(i, sym)| {
+                let name = &binary.dynstrtab[sym.st_name];
+
+                match versions.get(i) {
+                    Some(&Some(ref version)) if sym.is_import() => (None, format!("{}{}", name, version)),
+                    _ if sym.is_import() => (None, name.to_string()),
+                    _ => (Some(sym.st_value), name.to_string()),
+                }
+            }
+        )
+        .collect::<Vec<_>>();
+    let (patches, relocations) = raw.apply_relocations(&relocs, base, &symbols);
+
+    debug!("{} relocated words", patches.len());
+    cover_patches(&mut reg, &patches)?;
+
     let name = if let &Some(ref soname) = &binary.soname {
         soname.to_string()
     } else {
@@ -199,48 +513,83 @@ fn load_elf(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
     let mut prog = Program::new("prog0");
     let mut proj = Project::new(name.clone(), reg);
 
+    proj.relocations.extend(relocations);
+
+    add_data_spaces(&mut proj, machine);
+
     prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry as u64), Some(name), Uuid::new_v4()));
 
     let add_sym = |prog: &mut Program, sym: &elf::Sym, name: &str| {
         let name = name.to_string();
-        let addr = sym.st_value;
+        let addr = if is_arm { (sym.st_value + base) & !1 } else { sym.st_value + base };
         debug!("Symbol: {} @ 0x{:x}: {:?}", name, addr, sym);
         if sym.is_function() {
             if sym.is_import() {
                 prog.call_graph.add_vertex(CallTarget::Symbolic(name, Uuid::new_v4()));
             } else {
-                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(name), Uuid::new_v4()));
+                prog.add_todo(addr, name, NameSource::Symbol);
             }
+        } else if sym.st_info & 0xf == STT_GNU_IFUNC && !sym.is_import() {
+            // The symbol's value is the resolver returning the implementation
+            prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(format!("{}.resolver", name)), Uuid::new_v4()));
         }
     };
 
-    let resolve_import_address = |proj: &mut Project, relocs: &[elf::Reloc], name: &str| {
+    // `display` is `name` with its version, if any
+    let resolve_import_address = |proj: &mut Project, relocs: &[elf::Reloc], name: &str, display: &str| {
         for reloc in relocs {
             let pltsym = &binary.dynsyms[reloc.r_sym];
             let pltname = &binary.dynstrtab[pltsym.st_name];
             if pltname == name {
-                debug!("Import match {}: {:#x} {:?}", name, reloc.r_offset, pltsym);
-                proj.imports.insert(reloc.r_offset as u64, name.to_string());
+                debug!("Import match {}: {:#x} {:?}", display, reloc.r_offset, pltsym);
+                proj.imports.insert(reloc.r_offset as u64 + base, display.to_string());
                 return true;
             }
         }
         false
     };
 
+    // records ARM/Thumb hints, returns true if `sym` is a mapping symbol and not a real one
+    let add_mapping_symbol = |hints: &mut BTreeMap<u64, MappingSymbol>, sym: &elf::Sym, name: &str| -> bool {
+        if !is_arm {
+            return false;
+        }
+
+        if let Some(ms) = MappingSymbol::from_name(name) {
+            hints.insert(sym.st_value + base, ms);
+            true
+        } else {
+            if sym.is_function() && !sym.is_import() {
+                let mode = if sym.st_value & 1 == 1 { MappingSymbol::Thumb } else { MappingSymbol::Arm };
+                hints.entry((sym.st_value + base) & !1).or_insert(mode);
+            }
+            false
+        }
+    };
+
     let mut seen_syms = HashSet::<u64>::new();
 
     // add dynamic symbol information (non-strippable)
-    for sym in &binary.dynsyms {
+    for (i, sym) in binary.dynsyms.iter().enumerate() {
         let name = &binary.dynstrtab[sym.st_name];
 
-        add_sym(&mut prog, sym, name);
-        seen_syms.insert(sym.st_value);
+        if add_mapping_symbol(&mut mapping_symbols, sym, name) {
+            continue;
+        }
 
-        let name = &binary.dynstrtab[sym.st_name];
-        if !resolve_import_address(&mut proj, &binary.pltrelocs, name) {
+        // Imports are bound to a specific version, e.g. `memcpy@GLIBC_2.14`
+        let display = match versions.get(i) {
+            Some(&Some(ref version)) if sym.is_import() => format!("{}{}", name, version),
+            _ => name.to_string(),
+        };
+
+        add_sym(&mut prog, sym, &display);
+        seen_syms.insert(sym.st_value + base);
+
+        if !resolve_import_address(&mut proj, &binary.pltrelocs, name, &display) {
             if sym.is_function() {
-                if !resolve_import_address(&mut proj, &binary.dynrelas, name) {
-                    resolve_import_address(&mut proj, &binary.dynrels, name);
+                if !resolve_import_address(&mut proj, &binary.dynrelas, name, &display) {
+                    resolve_import_address(&mut proj, &binary.dynrels, name, &display);
                 }
             }
         }
@@ -250,24 +599,228 @@ fn load_elf(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
     // add strippable symbol information
     for sym in &binary.syms {
         let name = &binary.strtab[sym.st_name];
-        if !seen_syms.contains(&sym.st_value) {
+        if add_mapping_symbol(&mut mapping_symbols, sym, &name) {
+            continue;
+        }
+        if !seen_syms.contains(&(sym.st_value + base)) {
             add_sym(&mut prog, sym, &name);
         }
-        seen_syms.insert(sym.st_value);
+        seen_syms.insert(sym.st_value + base);
     }
+
     prog.imports = proj.imports.clone();
+
+    // IFUNC resolvers of statically linked files and their GOT slots. The functions aren't
+    // imported, the slots only name the PLT stubs jumping through them.
+    for (slot, resolver) in raw.ifunc_resolvers(&relocs) {
+        let name = binary.syms
+            .iter()
+            .map(|s| (s, &binary.strtab[s.st_name]))
+            .chain(binary.dynsyms.iter().map(|s| (s, &binary.dynstrtab[s.st_name])))
+            .find(|&(s, _)| s.st_value == resolver && s.st_info & 0xf == STT_GNU_IFUNC)
+            .map(|(_, n)| n.to_string())
+            .unwrap_or_else(|| format!("ifunc_{:x}", resolver));
+
+        let (slot, resolver) = (slot + base, resolver + base);
+
+        debug!("IFUNC {} resolved by {:#x} into {:#x}", name, resolver, slot);
+        prog.imports.entry(slot).or_insert(name.clone());
+        if !seen_syms.contains(&resolver) {
+            prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(resolver), Some(format!("{}.resolver", name)), Uuid::new_v4()));
+            seen_syms.insert(resolver);
+        }
+    }
+
+    // Vtables, typeinfo and other C++ data
+    let region = proj.region().name().clone();
+    let data_syms = binary.dynsyms
+        .iter()
+        .map(|s| (s, &binary.dynstrtab[s.st_name]))
+        .chain(binary.syms.iter().map(|s| (s, &binary.strtab[s.st_name])));
+    let endianess = if raw.little_endian { Endianess::Little } else { Endianess::Big };
+    let mut types = TypeDatabase::new();
+    for (sym, name) in data_syms {
+        if sym.st_info & 0xf == STT_OBJECT && !sym.is_import() {
+            if let Some(decl) = demangle(name) {
+                proj.comments.entry((region.clone(), sym.st_value + base)).or_insert(decl);
+            }
+            if name.starts_with("_ZTI") {
+                types.add_itanium(proj.region(), sym.st_value + base, raw.pointer_size(), endianess);
+            }
+        }
+    }
+    proj.type_database = types;
+
+    // Every function compiled with unwinding support has an FDE, even in stripped files
+    let eh_frame = (0..raw.sections.len()).find(|&i| raw.section(i).map(|s| s.name == ".eh_frame").unwrap_or(false)).map(|i| raw.sections[i]);
+    if let Some((_, addr, _, size)) = eh_frame {
+        let area = Bound::new(addr + base, addr + base + size);
+        let fdes = eh::parse_eh_frame(proj.region(), &area, raw.pointer_size(), endianess);
+
+        debug!("{} FDEs in .eh_frame", fdes.len());
+        for fde in fdes.iter() {
+            let start = if is_arm { fde.function.start & !1 } else { fde.function.start };
+
+            if start != 0 && seen_syms.insert(start) {
+                prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(start), None, Uuid::new_v4()));
+            }
+        }
+        proj.exception_tables = fdes;
+    }
+
+    // Types and global variables of unstripped files. Compressed sections aren't supported
+    let debug_section = |name: &str| {
+        (0..raw.sections.len())
+            .filter_map(|i| raw.section(i))
+            .find(|s| s.name == name && s.flags & SHF_COMPRESSED == 0)
+            .and_then(|s| bytes.get(s.offset as usize..(s.offset + s.size) as usize))
+    };
+    if let (Some(info), Some(abbrev)) = (debug_section(".debug_info"), debug_section(".debug_abbrev")) {
+        proj.data_types.pointer_size = raw.pointer_size() as u64;
+        proj.data_types.endianess = endianess;
+        match proj.data_types.import_dwarf(info, abbrev, debug_section(".debug_str").unwrap_or(&[]), endianess, base) {
+            Ok(n) => debug!("{} types in .debug_info", n),
+            Err(e) => warn!("failed to read .debug_info: {}", e),
+        }
+        for (&addr, name) in proj.data_types.function_names.iter() {
+            prog.propose_name(addr, name.clone(), NameSource::Debug);
+        }
+    }
+
+    // Constructors and destructors
+    for (addr, name) in raw.initializers(&relocs) {
+        let addr = addr + base;
+        let addr = if is_arm {
+            let mode = if addr & 1 == 1 { MappingSymbol::Thumb } else { MappingSymbol::Arm };
+            mapping_symbols.entry(addr & !1).or_insert(mode);
+            addr & !1
+        } else {
+            addr
+        };
+
+        debug!("initializer {} at {:#x}", name, addr);
+        proj.comments.entry((region.clone(), addr)).or_insert(name.clone());
+        if !seen_syms.contains(&addr) {
+            prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some(name), Uuid::new_v4()));
+            seen_syms.insert(addr);
+        }
+    }
+
+    // OAT files hold the methods of an Android app or framework ART compiled ahead of time. The
+    // dex files in `oatdata` are bytecode and stay data
+    let oat_symbol = |name: &str| binary.dynsyms.iter().find(|s| &binary.dynstrtab[s.st_name] == name).map(|s| (s.st_value, s.st_size));
+    if let (Some((data, _)), Some((exec, exec_size))) = (oat_symbol("oatdata"), oat_symbol("oatexec")) {
+        let header = raw.offset(data).and_then(|off| bytes.get(off..));
+        let code = raw.offset(exec).and_then(|off| bytes.get(off..off + exec_size as usize));
+
+        if let (Some(header), Some(code)) = (header, code) {
+            proj.comments.insert((region.clone(), data + base), "OAT header".to_string());
+
+            for (addr, thumb) in oat_methods(header, code, exec + base) {
+                if is_arm {
+                    mapping_symbols.insert(addr, if thumb { MappingSymbol::Thumb } else { MappingSymbol::Arm });
+                }
+                if seen_syms.insert(addr) {
+                    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), None, Uuid::new_v4()));
+                }
+            }
+        }
+    }
+
     proj.comments.insert(("base".to_string(), entry), "main".to_string());
+    proj.mapping_symbols = mapping_symbols;
     proj.code.push(prog);
+    proj.set_sections(sections);
+    proj.findings = packer::scan(&proj);
+    proj.hardening = raw.hardening();
 
     Ok((proj, machine))
 }
 
-/// Parses a PE32/PE32+ file from `bytes` and create a project from it.
-fn load_pe(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+/// Magic at the start of an OAT header.
+const OAT_MAGIC: &'static [u8] = b"oat\n";
+
+/// Sizes of the header in front of each compiled method in the OAT versions that end it with the
+/// size of the method's code.
+const OAT_METHOD_HEADER_SIZES: [usize; 5] = [24, 28, 8, 16, 20];
+
+/// Finds the methods ART compiled ahead of time into an OAT file. `header` starts at the OAT
+/// header (`oatdata`), `code` is the compiled code (`oatexec`) loaded at `addr`. Returns the start
+/// of each method and whether it's Thumb code.
+fn oat_methods(header: &[u8], code: &[u8], addr: u64) -> Vec<(u64, bool)> {
+    if header.get(0..4) != Some(OAT_MAGIC) {
+        return vec![];
+    }
+
+    // Code alignment of the instruction set: kArm, kArm64, kThumb2, kX86, kX86_64, kMips and
+    // kMips64
+    let (alignment, thumb) = match le_u32(header, 12) {
+        Some(1) => (8, false),
+        Some(3) => (8, true),
+        Some(2) | Some(4...7) => (16, false),
+        isa => {
+            debug!("unknown OAT instruction set {:?}", isa);
+            return vec![];
+        }
+    };
+
+    // Methods follow each other, each one preceded by its header. Its layout changed between
+    // versions, so all header sizes are tried. Those that walk the code to its end are candidates,
+    // the one finding the most methods wins
+    let mut ret = vec![];
+
+    'sizes: for &size in OAT_METHOD_HEADER_SIZES.iter() {
+        let mut methods = vec![];
+        let mut pos = 0;
+
+        while code.len() - pos >= alignment {
+            let start = (pos + size + alignment - 1) / alignment * alignment;
+            // The top bit flags methods that need to be deoptimized
+            let len = le_u32(code, start - 4).unwrap_or(0) as usize & 0x7fff_ffff;
+
+            if len == 0 || start + len > code.len() {
+                continue 'sizes;
+            }
+
+            methods.push((addr + start as u64, thumb));
+            pos = start + len;
+        }
+
+        debug!("{} OAT methods with {} byte headers", methods.len(), size);
+        if methods.len() > ret.len() {
+            ret = methods;
+        }
+    }
+
+    ret
+}
+
+/// Parses a PE32/PE32+ file from `bytes` and create a project from it. Exports and the import
+/// thunks are added as functions, the thunks become stubs of their import after disassembly. The
+/// image is loaded at `base` or its preferred image base.
+fn load_pe(bytes: &[u8], name: String, base: Option<u64>) -> Result<(Project, Machine)> {
     let pe = pe::PE::parse(&bytes)?;
     debug!("pe: {:#?}", &pe);
-    let image_base = pe.image_base as u64;
-    let mut ram = Region::undefined("RAM".to_string(), 0x100000000);
+    let hdr = match PeHeader::parse(bytes) {
+        Some(hdr) => hdr,
+        None => return Err("Invalid PE header".into()),
+    };
+    let (machine, ram_size) = match hdr.machine {
+        0x14c => (Machine::Ia32, 0x1_0000_0000),
+        0x8664 => (Machine::Amd64, 0xFFFF_FFFF_FFFF_FFFF),
+        machine => return Err(format!("Unsupported machine ({:#x})", machine).into()),
+    };
+    let preferred = pe.image_base as u64;
+    let image_base = base.unwrap_or(preferred);
+    // Added to all absolute addresses in the file
+    let delta = image_base.wrapping_sub(preferred);
+    let mut ram = Region::undefined("RAM".to_string(), ram_size);
+
+    if delta != 0 && hdr.relocs_stripped() {
+        return Err(format!("{} has no relocations and can only be loaded at {:#x}", name, preferred).into());
+    }
+    let mut sections = vec![];
+
     for section in &pe.sections {
         let name = String::from_utf8_lossy(&section.name);
         debug!("section: {}", name);
@@ -302,12 +855,59 @@ fn load_pe(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
             debug!("bad cover");
             return Err(format!("Cannot cover bound: {:?}", Bound::new(begin, end)).into());
         }
+
+        // IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE and IMAGE_SCN_MEM_EXECUTE
+        let flags = section.characteristics;
+        sections.push(
+            Section {
+                name: name.trim_matches('\0').to_string(),
+                area: Bound::new(begin, begin + (section.virtual_size as u64).max(size)),
+                file_size: section.size_of_raw_data as u64,
+                file_offset: section.pointer_to_raw_data as u64,
+                read: flags & 0x4000_0000 != 0,
+                write: flags & 0x8000_0000 != 0,
+                execute: flags & 0x2000_0000 != 0,
+            }
+        );
+    }
+
+    let mut patches = vec![];
+    let mut relocations = vec![];
+
+    for (rva, width) in hdr.base_relocations() {
+        let value = match hdr.offset(rva) {
+            Some(off) if width == 8 => le_u64(bytes, off),
+            Some(off) => le_u32(bytes, off).map(|x| x as u64),
+            None => None,
+        };
+        let addr = image_base + rva as u64;
+
+        if let Some(value) = value {
+            let value = value.wrapping_add(delta);
+            let mut word = vec![0u8; width];
+
+            if width == 8 {
+                LittleEndian::write_u64(&mut word, value);
+                relocations.push((addr, Relocation { target: Some(value), symbol: None }));
+            } else {
+                LittleEndian::write_u32(&mut word, value as u32);
+                relocations.push((addr, Relocation { target: Some(value & 0xffff_ffff), symbol: None }));
+            }
+            if delta != 0 {
+                patches.push((addr, word));
+            }
+        }
     }
-    let entry = (pe.image_base + pe.entry) as u64;
+
+    debug!("{} base relocations", relocations.len());
+    cover_patches(&mut ram, &patches)?;
+
+    let entry = image_base + pe.entry as u64;
     debug!("entry: {:#x}", entry);
     let mut prog = Program::new("prog0");
     let mut proj = Project::new(name.to_string(), ram);
 
+    proj.relocations.extend(relocations);
     prog.call_graph
         .add_vertex(
             CallTarget::Todo(
@@ -317,59 +917,3046 @@ fn load_pe(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
             )
         );
 
-    for export in pe.exports {
+    for export in hdr.exports() {
+        let address = export.rva as u64 + image_base;
+
+        if let Some(ref forward) = export.forward {
+            let name = demangle(&export.name).unwrap_or_else(|| export.name.clone());
+
+            debug!("export {} forwarded to {}", export.name, forward);
+            proj.comments.insert(("RAM".to_string(), address), format!("{} forwarded to {}", name, forward));
+            continue;
+        }
+        if !hdr.executable(export.rva) {
+            debug!("data export: {:?}", &export);
+            if let Some(decl) = demangle(&export.name) {
+                proj.comments.insert(("RAM".to_string(), address), decl);
+            }
+            continue;
+        }
+
+        if DLL_ENTRY_POINTS.contains(&export.name.as_str()) {
+            proj.comments.insert(("RAM".to_string(), address), format!("{} is called by the system", export.name));
+        }
+
         debug!("adding export: {:?}", &export);
-        prog.call_graph
-            .add_vertex(
-                CallTarget::Todo(
-                    Rvalue::new_u64(export.rva as u64 + image_base),
-                    Some(export.name.to_string()),
-                    Uuid::new_v4(),
-                )
-            );
+        prog.add_todo(address, export.name, NameSource::Export);
     }
 
-    for import in pe.imports {
-        debug!(
-            "adding import: {:?} @ {:#x}",
-            &import,
-            import.rva + pe.image_base
-        );
-        prog.call_graph.add_vertex(CallTarget::Symbolic(import.name.into_owned(), Uuid::new_v4()));
+    let imports = hdr.imports();
+
+    for import in imports.iter() {
+        let slot = import.slot.wrapping_add(delta);
+
+        debug!("adding import: {}!{} @ {:#x}", import.dll, import.name, slot);
+        proj.imports.insert(slot, import.name.clone());
+        proj.relocations.insert(slot, Relocation { target: None, symbol: Some(import.name.clone()) });
+        prog.call_graph.add_vertex(CallTarget::Symbolic(import.name.clone(), Uuid::new_v4()));
+    }
+
+    for (thunk, slot) in hdr.thunks(&imports) {
+        let (thunk, slot) = (thunk.wrapping_add(delta), slot.wrapping_add(delta));
+
+        debug!("import thunk at {:#x} for {}", thunk, proj.imports[&slot]);
+        prog.thunks.insert(thunk, slot);
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(thunk), None, Uuid::new_v4()));
+    }
+
+    // TLS callbacks run before the entry point
+    for (i, callback) in hdr.tls_callbacks().into_iter().enumerate() {
+        let callback = callback.wrapping_add(delta);
+
+        debug!("TLS callback at {:#x}", callback);
+        proj.comments.insert(("RAM".to_string(), callback), "TLS callback".to_string());
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(callback), Some(format!("tls_callback_{}", i)), Uuid::new_v4()));
+    }
+
+    if hdr.efi() {
+        if let Ok(image) = EfiImage::parse(bytes) {
+            for (addr, protocol) in image.protocols() {
+                proj.comments.insert(("RAM".to_string(), addr.wrapping_add(delta)), protocol.to_string());
+            }
+        }
     }
 
+    prog.imports = proj.imports.clone();
     proj.comments.insert(("base".to_string(), entry), "main".to_string());
     proj.code.push(prog);
-    Ok((proj, Machine::Ia32))
+    proj.set_sections(sections);
+    proj.findings = packer::scan(&proj);
+    proj.hardening = hdr.hardening();
+    Ok((proj, machine))
 }
 
-/// Load an ELF or PE file from disk and creates a `Project` from it. Returns the `Project` instance and
-/// the CPU its intended for.
-pub fn load(path: &Path) -> Result<(Project, Machine)> {
-    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
-    let mut fd = File::open(path)?;
-    let peek = goblin::peek(&mut fd)?;
-    if let Hint::Unknown(magic) = peek {
-        Err(format!("Tried to load an unknown file. Magic: {}", magic).into())
-    } else {
-        let mut bytes = Vec::new();
-        fd.read_to_end(&mut bytes)?;
-        match peek {
-            Hint::Elf(_) => load_elf(&bytes, name),
-            Hint::PE => load_pe(&bytes, name),
-            Hint::Mach(_) => load_mach(&bytes, 0, name),
-            Hint::MachFat(_) => Err("Cannot directly load a fat mach-o binary (e.g., which one do I load?)".into()),
-            Hint::Archive => {
-                let archive = archive::Archive::parse(&bytes)?;
-                debug!("archive: {:#?}", &archive);
-                Err("Tried to load an archive, unsupported format".into())
-            }
-            _ => {
-                println!(
-                    "Loader branch hit wildcard, should be unreachable (a new variant must have been added but code was not updated)",
-                );
-                unreachable!()
+/// Adds the data memory of Harvard architectures to `proj`.
+fn add_data_spaces(proj: &mut Project, machine: Machine) {
+    if let Machine::Mcs51 = machine {
+        proj.data.add_space(Region::undefined("idata".to_string(), 0x100));
+        proj.data.add_space(Region::undefined("sfr".to_string(), 0x100));
+        proj.data.add_space(Region::undefined("xdata".to_string(), 0x1_0000));
+    }
+}
+
+/// Empty code memory of `machine`.
+fn address_space(machine: Machine) -> Result<Region> {
+    Ok(
+        match machine {
+            Machine::Avr => Region::undefined("Flash".to_string(), 0x2_0000),
+            Machine::Mcs51 => Region::undefined("code".to_string(), 0x1_0000),
+            Machine::Msp430(_) => Region::undefined("RAM".to_string(), 0x10_0000),
+            Machine::Amd64 | Machine::Mips64(_) | Machine::RiscV64(_) => Region::undefined("RAM".to_string(), 0xFFFF_FFFF_FFFF_FFFF),
+            Machine::Ia32 | Machine::Arm | Machine::Mips(_) | Machine::RiscV32(_) | Machine::M68k | Machine::SuperH(..) => {
+                Region::undefined("RAM".to_string(), 0x1_0000_0000)
             }
+            Machine::Wasm => return Err("WebAssembly modules have no flat address space".into()),
+        }
+    )
+}
+
+/// Maps the parts of `bytes` listed in `mapping` into the code memory of its machine.
+fn load_raw_bytes(bytes: &[u8], name: String, mapping: &RawMapping) -> Result<(Project, Machine)> {
+    let mut reg = address_space(mapping.machine)?;
+    let default = [RawSegment { offset: 0, size: None, address: 0 }];
+    let segments = if mapping.segments.is_empty() { &default[..] } else { &mapping.segments[..] };
+
+    for seg in segments.iter() {
+        let start = seg.offset as usize;
+        let end = match seg.size {
+            Some(size) => start.saturating_add(size as usize),
+            None => bytes.len(),
+        };
+        let data = match bytes.get(start..end) {
+            Some(data) if !data.is_empty() => data,
+            _ => return Err(format!("Segment {:#x}..{:#x} is outside of {}", start, end, name).into()),
+        };
+        let bound = Bound::new(seg.address, seg.address.saturating_add(data.len() as u64));
+
+        debug!("map {:#x}..{:#x} to {:?}", start, end, bound);
+        if !reg.cover(bound.clone(), Layer::wrap(data.to_vec())) {
+            return Err(format!("Cannot cover bound: {:?}", bound).into());
+        }
+    }
+
+    let entry_points = if mapping.entry_points.is_empty() { vec![segments[0].address] } else { mapping.entry_points.clone() };
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name.clone(), reg);
+
+    add_data_spaces(&mut proj, mapping.machine);
+    for (i, &entry) in entry_points.iter().enumerate() {
+        let name = if i == 0 { name.clone() } else { format!("entry_{}", i) };
+
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry), Some(name), Uuid::new_v4()));
+    }
+    proj.comments.insert(("base".to_string(), entry_points[0]), "main".to_string());
+    proj.code.push(prog);
+
+    Ok((proj, mapping.machine))
+}
+
+/// Alignment of archive members in the address space.
+const MEMBER_ALIGNMENT: u64 = 0x1000;
+
+/// Returns the name and contents of all members of the `ar` archive `bytes`, except for the symbol
+/// index and the long name table.
+fn ar_members(bytes: &[u8]) -> Result<Vec<(String, &[u8])>> {
+    if bytes.get(0..8) != Some(b"!<arch>\n") {
+        return Err("Invalid archive magic".into());
+    }
+
+    let field = |hdr: &[u8], range: ::std::ops::Range<usize>| String::from_utf8_lossy(&hdr[range]).trim().to_string();
+    let mut long_names: &[u8] = &[];
+    let mut off = 8;
+    let mut ret = vec![];
+
+    while off + 60 <= bytes.len() {
+        let hdr = &bytes[off..off + 60];
+
+        if &hdr[58..60] != b"`\n" {
+            return Err(format!("Invalid archive member header at {:#x}", off).into());
+        }
+
+        let size = field(hdr, 48..58).parse::<usize>().map_err(|_| format!("Invalid size of archive member at {:#x}", off))?;
+        let data = match bytes.get(off + 60..off + 60 + size) {
+            Some(data) => data,
+            None => return Err(format!("Archive member at {:#x} is truncated", off).into()),
+        };
+        let raw_name = field(hdr, 0..16);
+
+        off += 60 + size + (size & 1);
+
+        // GNU and System V use "/" for the symbol index, "//" for the long names, BSD
+        // "__.SYMDEF". BSD stores long names in front of the member's contents
+        let (name, data) = if raw_name == "/" || raw_name == "/SYM64/" || raw_name.starts_with("__.SYMDEF") {
+            continue;
+        } else if raw_name == "//" {
+            long_names = data;
+            continue;
+        } else if raw_name.starts_with("#1/") {
+            let len = raw_name[3..].parse::<usize>().unwrap_or(0).min(data.len());
+            let name = String::from_utf8_lossy(&data[0..len]).trim_matches('\0').to_string();
+
+            (name, &data[len..])
+        } else if raw_name.starts_with('/') {
+            let start = raw_name[1..].parse::<usize>().unwrap_or(long_names.len()).min(long_names.len());
+            let name = long_names[start..].split(|&b| b == b'\n').next().unwrap_or(&[]);
+
+            (String::from_utf8_lossy(name).trim_matches('/').to_string(), data)
+        } else {
+            (raw_name.trim_matches('/').to_string(), data)
+        };
+
+        ret.push((name, data));
+    }
+
+    Ok(ret)
+}
+
+/// Loads all object files in the `ar` archive `bytes`. Members are placed one after another
+/// into the same address space and linked against each other.
+fn load_archive(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let mut objects = vec![];
+
+    for (member, data) in ar_members(bytes)? {
+        match Object::parse(data) {
+            Ok(obj) => objects.push((member, obj)),
+            Err(e) => debug!("skipping archive member {}: {}", member, e),
+        }
+    }
+
+    let machine = match objects.first() {
+        Some(&(_, ref obj)) => obj.machine,
+        None => return Err(format!("{} contains no object files", name).into()),
+    };
+    let mut members = vec![];
+    let mut globals = HashMap::new();
+    let mut base = MEMBER_ALIGNMENT;
+
+    for (member, obj) in objects {
+        if obj.machine != machine {
+            debug!("skipping archive member {} for {:?}", member, obj.machine);
+            continue;
+        }
+
+        // The first definition of a symbol wins, like with a linker searching the archive
+        for (sym, &addr) in obj.globals.iter() {
+            globals.entry(sym.clone()).or_insert(base + addr);
         }
+
+        let end = base + obj.size.max(1);
+
+        members.push((member, base, obj));
+        base = (end + MEMBER_ALIGNMENT - 1) & !(MEMBER_ALIGNMENT - 1);
+    }
+
+    let mut reg = address_space(machine)?;
+    let mut overlays = vec![];
+
+    for &(ref member, base, ref obj) in members.iter() {
+        let mut overlay = Region::undefined(member.clone(), obj.size.max(1));
+        let (patches, records) = obj.link(base, &|sym| globals.get(sym).cloned());
+        let local = patches.iter().map(|&(addr, ref word)| (addr - base, word.clone())).collect::<Vec<_>>();
+
+        obj.map(&mut reg, base)?;
+        cover_patches(&mut reg, &patches)?;
+        obj.map(&mut overlay, 0)?;
+        cover_patches(&mut overlay, &local)?;
+        overlays.push((overlay, records));
+    }
+
+    let root_name = reg.name().clone();
+    let mut proj = Project::new(name, reg);
+
+    add_data_spaces(&mut proj, machine);
+
+    for ((member, base, obj), (overlay, records)) in members.into_iter().zip(overlays.into_iter()) {
+        let mut prog = Program::new(&member);
+        let root = proj.data.root;
+        let vx = proj.data.dependencies.add_vertex(overlay);
+
+        proj.data.dependencies.add_edge(Bound::new(base, base + obj.size.max(1)), root, vx);
+        proj.comments.insert((root_name.clone(), base), member.clone());
+
+        for &(addr, ref func) in obj.functions.iter() {
+            prog.add_todo(base + addr, func.clone(), NameSource::Symbol);
+        }
+        for (sym, &slot) in obj.externs.iter().filter(|&(sym, _)| !globals.contains_key(sym)) {
+            debug!("adding import: {} @ {:#x}", sym, base + slot);
+            prog.imports.insert(base + slot, sym.clone());
+            prog.call_graph.add_vertex(CallTarget::Symbolic(sym.clone(), Uuid::new_v4()));
+        }
+
+        proj.imports.extend(prog.imports.iter().map(|(&a, s)| (a, s.clone())));
+        proj.relocations.extend(records.iter().cloned());
+        prog.relocations.extend(records);
+        proj.code.push(prog);
+    }
+
+    Ok((proj, machine))
+}
+
+/// Default address of relocatable object files.
+const OBJECT_BASE: u64 = 0x1000;
+
+/// Lays out the sections of the relocatable object file `obj` starting at `base` and applies its
+/// relocations. Undefined symbols are imports pointing to a slot after the sections.
+fn load_object(obj: &Object, name: String, base: u64) -> Result<(Project, Machine)> {
+    let mut reg = address_space(obj.machine)?;
+    let (patches, records) = obj.link(base, &|_| None);
+
+    obj.map(&mut reg, base)?;
+    cover_patches(&mut reg, &patches)?;
+
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name, reg);
+
+    add_data_spaces(&mut proj, obj.machine);
+
+    for &(addr, ref func) in obj.functions.iter() {
+        prog.add_todo(base + addr, func.clone(), NameSource::Symbol);
+    }
+    for (sym, &slot) in obj.externs.iter() {
+        debug!("adding import: {} @ {:#x}", sym, base + slot);
+        proj.imports.insert(base + slot, sym.clone());
+        prog.call_graph.add_vertex(CallTarget::Symbolic(sym.clone(), Uuid::new_v4()));
+    }
+
+    proj.relocations.extend(records.iter().cloned());
+    prog.relocations.extend(records);
+    prog.imports = proj.imports.clone();
+    proj.code.push(prog);
+
+    Ok((proj, obj.machine))
+}
+
+/// Loads a TE image, the stripped down PE format of UEFI PEI modules.
+fn load_te(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let image = EfiImage::parse(bytes)?;
+    let mut reg = address_space(image.machine)?;
+
+    image.map(&mut reg)?;
+
+    let entry = image.base + image.entry;
+    let root_name = reg.name().clone();
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name.clone(), reg);
+
+    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(entry), Some(name), Uuid::new_v4()));
+    for (addr, protocol) in image.protocols() {
+        proj.comments.insert((root_name.clone(), addr), protocol.to_string());
+    }
+    proj.comments.insert(("base".to_string(), entry), "main".to_string());
+    proj.code.push(prog);
+
+    Ok((proj, image.machine))
+}
+
+/// Loads the modules in the UEFI firmware volumes of `bytes`. The firmware file itself is the
+/// root region. Each module gets an address space named like its `Program`, with its image mapped
+/// at its preferred base.
+fn load_firmware(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let mut images = vec![];
+
+    for module in uefi::modules(bytes) {
+        match EfiImage::parse(module.image) {
+            Ok(image) => images.push((module, image)),
+            Err(e) => debug!("skipping module {}: {}", module.guid, e),
+        }
+    }
+
+    // PEI modules are often 32 bit while DXE drivers are 64 bit. Only the most common machine is
+    // loaded
+    let count = |machine: Machine| images.iter().filter(|&&(_, ref i)| i.machine == machine).count();
+    let machine = match images.iter().map(|&(_, ref i)| i.machine).max_by_key(|&m| count(m)) {
+        Some(machine) => machine,
+        None => return Err(format!("{} contains no UEFI modules", name).into()),
+    };
+    let root_name = "Firmware".to_string();
+    let mut proj = Project::new(name, Region::wrap(root_name.clone(), bytes.to_vec()));
+    let mut names = HashSet::new();
+
+    for (module, image) in images {
+        if image.machine != machine {
+            debug!("skipping module {} for {:?}", module.guid, image.machine);
+            continue;
+        }
+
+        let guid = module.guid.hyphenated().to_string();
+        let mod_name = match module.name {
+            Some(ref n) if names.insert(n.clone()) => n.clone(),
+            Some(ref n) => format!("{} {}", n, guid),
+            None => guid.clone(),
+        };
+        let mut reg = Region::undefined(mod_name.clone(), image.base + image.size.max(1));
+        let mut prog = Program::new(&mod_name);
+
+        image.map(&mut reg)?;
+        proj.data.add_space(reg);
+        proj.comments.insert((root_name.clone(), module.offset as u64), format!("{} ({})", mod_name, guid));
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(image.base + image.entry), Some(mod_name.clone()), Uuid::new_v4()));
+
+        for (addr, protocol) in image.protocols() {
+            proj.comments.insert((mod_name.clone(), addr), protocol.to_string());
+        }
+        proj.code.push(prog);
+    }
+
+    Ok((proj, machine))
+}
+
+/// Magic at the start of Android boot images.
+const ANDROID_BOOT_MAGIC: &'static [u8] = b"ANDROID!";
+
+/// Kernel load address of boot images that don't name one, the default of `mkbootimg`.
+const ANDROID_KERNEL_ADDR: u64 = 0x1000_8000;
+
+/// Loads the kernel of the Android boot image `bytes`. The ramdisk and the second stage loader
+/// aren't mapped, they're noted in comments instead. Only ARM `zImage` and x86 `bzImage` kernels
+/// are supported.
+fn load_android_boot(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let field = |off: usize| le_u32(bytes, off).ok_or_else(|| ::Error::from("Boot image header is truncated"));
+    let version = field(40)?;
+    // Version 3 and later dropped the load addresses and use 4 KiB pages
+    let (kernel_size, ramdisk_size, addr, page, cmdline) = if version >= 3 {
+        (field(8)? as usize, field(12)? as usize, ANDROID_KERNEL_ADDR, 4096, 44..44 + 1536)
+    } else {
+        (field(8)? as usize, field(16)? as usize, field(12)? as u64, field(36)? as usize, 64..64 + 512)
+    };
+
+    if page == 0 {
+        return Err("Boot image has a page size of 0".into());
+    }
+
+    let kernel = match bytes.get(page..page + kernel_size) {
+        Some(kernel) => kernel,
+        None => return Err(format!("Kernel of {} is truncated", name).into()),
+    };
+    let ramdisk = page + (kernel_size + page - 1) / page * page;
+    let cmdline = bytes.get(cmdline).map(|c| String::from_utf8_lossy(c.split(|&b| b == 0).next().unwrap_or(&[])).to_string()).unwrap_or_default();
+
+    // ARM zImages have a magic number at 0x24, bzImages the setup header at 0x1f1. The protected
+    // mode part of the latter follows the real mode setup sectors
+    let (machine, addr, kernel) = if le_u32(kernel, 0x24) == Some(0x016f_2818) {
+        (Machine::Arm, addr, kernel)
+    } else if kernel.get(0x202..0x206) == Some(b"HdrS") {
+        let setup = match kernel[0x1f1] {
+            0 => 4,
+            n => n as usize,
+        };
+        let code32 = le_u32(kernel, 0x214).unwrap_or(0x10_0000) as u64;
+
+        match kernel.get((setup + 1) * 512..) {
+            Some(kernel) => (Machine::Ia32, code32, kernel),
+            None => return Err(format!("Kernel of {} is truncated", name).into()),
+        }
+    } else if kernel.get(0x38..0x3c) == Some(b"ARMd") {
+        return Err("AArch64 kernels are not supported".into());
+    } else {
+        return Err(format!("Unknown kernel format in {}", name).into());
+    };
+    let mut reg = address_space(machine)?;
+    let bound = Bound::new(addr, addr + kernel.len() as u64);
+
+    if !reg.cover(bound.clone(), Layer::wrap(kernel.to_vec())) {
+        return Err(format!("Cannot cover bound: {:?}", bound).into());
+    }
+
+    let root_name = reg.name().clone();
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name, reg);
+
+    debug!("kernel at {:#x}, ramdisk of {} bytes at file offset {:#x}", addr, ramdisk_size, ramdisk);
+    prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(addr), Some("kernel".to_string()), Uuid::new_v4()));
+    if !cmdline.is_empty() {
+        proj.comments.insert((root_name.clone(), addr), format!("cmdline: {}", cmdline));
+    }
+    if ramdisk_size > 0 {
+        proj.comments.insert((root_name, addr + kernel.len() as u64), format!("ramdisk of {} bytes at file offset {:#x}", ramdisk_size, ramdisk));
+    }
+    proj.comments.insert(("base".to_string(), addr), "main".to_string());
+    proj.code.push(prog);
+
+    Ok((proj, machine))
+}
+
+/// Parses a WebAssembly module from `bytes`. The module file is mapped as is, functions start at
+/// the first instruction of their body.
+fn load_wasm(bytes: &[u8], name: String) -> Result<(Project, Machine)> {
+    let module = wasm::Module::parse(bytes)?;
+    debug!("wasm: {:#?}", &module);
+
+    let reg = Region::wrap("Module".to_string(), bytes.to_vec());
+    let mut prog = Program::new("prog0");
+    let mut proj = Project::new(name, reg);
+    let imported = module.imported_functions();
+
+    for index in 0..imported {
+        if let Some(name) = module.function_name(index) {
+            debug!("adding import: {}", name);
+            prog.call_graph.add_vertex(CallTarget::Symbolic(name, Uuid::new_v4()));
+        }
+    }
+
+    for (i, body) in module.bodies.iter().enumerate() {
+        let index = i as u32 + imported;
+
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(body.start), module.function_name(index), Uuid::new_v4()));
+        if module.start == Some(index) {
+            proj.comments.insert(("Module".to_string(), body.start), "main".to_string());
+        }
+    }
+
+    proj.code.push(prog);
+    Ok((proj, Machine::Wasm))
+}
+
+/// Architecture slice of a multi-architecture container, returned by [`slices`](fn.slices.html).
+#[derive(Clone,Debug)]
+pub struct Slice {
+    /// Architecture name as used by Apple's and Microsoft's tools, e.g. `x86_64` or `arm64ec`
+    pub architecture: String,
+    /// CPU of the slice, `None` if unsupported
+    pub machine: Option<Machine>,
+    /// Offset of the slice in the file
+    pub offset: u64,
+    /// Size of the slice in bytes
+    pub size: u64,
+    container: Container,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Container {
+    /// Slice is a complete Mach-o file embedded in a fat binary
+    MachFat,
+    /// Slice is one of the two views of a Windows ARM64X image. Both span the whole file
+    Arm64x,
+}
+
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+// Java class files start with 0xcafebabe too. There the next word is the class file version,
+// which is always larger than any sane number of fat slices.
+const MAX_FAT_ARCHS: u32 = 30;
+
+/// Returns the architecture name and the `Machine` of a Mach-o CPU type.
+fn fat_architecture(cputype: u32, cpusubtype: u32) -> (String, Option<Machine>) {
+    match (cputype, cpusubtype & 0xff) {
+        (0x0000_0007, _) => ("i386".to_string(), Some(Machine::Ia32)),
+        (0x0100_0007, 8) => ("x86_64h".to_string(), Some(Machine::Amd64)),
+        (0x0100_0007, _) => ("x86_64".to_string(), Some(Machine::Amd64)),
+        (0x0000_000c, _) => ("arm".to_string(), None),
+        (0x0100_000c, 2) => ("arm64e".to_string(), None),
+        (0x0100_000c, _) => ("arm64".to_string(), None),
+        (0x0200_000c, _) => ("arm64_32".to_string(), None),
+        (0x0000_0012, _) => ("ppc".to_string(), None),
+        (0x0100_0012, _) => ("ppc64".to_string(), None),
+        (cputype, _) => (format!("cputype {:#x}", cputype), None),
+    }
+}
+
+/// Parses the header of a 32 or 64-bit fat Mach-o binary. Returns `None` if `bytes` isn't one.
+fn fat_slices(bytes: &[u8]) -> Result<Option<Vec<Slice>>> {
+    let mut cur = Cursor::new(bytes);
+    let magic = match cur.read_u32::<BigEndian>() {
+        Ok(m) => m,
+        Err(_) => return Ok(None),
+    };
+    let nfat_arch = match cur.read_u32::<BigEndian>() {
+        Ok(n) if n <= MAX_FAT_ARCHS && (magic == FAT_MAGIC || magic == FAT_MAGIC_64) => n,
+        _ => return Ok(None),
+    };
+    let mut ret = Vec::with_capacity(nfat_arch as usize);
+
+    for _ in 0..nfat_arch {
+        let cputype = cur.read_u32::<BigEndian>()?;
+        let cpusubtype = cur.read_u32::<BigEndian>()?;
+        let (offset, size) = if magic == FAT_MAGIC_64 {
+            let offset = cur.read_u64::<BigEndian>()?;
+            let size = cur.read_u64::<BigEndian>()?;
+            // align and reserved
+            cur.read_u64::<BigEndian>()?;
+            (offset, size)
+        } else {
+            let offset = cur.read_u32::<BigEndian>()? as u64;
+            let size = cur.read_u32::<BigEndian>()? as u64;
+            // align
+            cur.read_u32::<BigEndian>()?;
+            (offset, size)
+        };
+        let (architecture, machine) = fat_architecture(cputype, cpusubtype);
+
+        if offset.checked_add(size).map(|end| end > bytes.len() as u64).unwrap_or(true) {
+            return Err(format!("Fat Mach-o slice {} ({:#x}+{:#x}) is outside the file", architecture, offset, size).into());
+        }
+
+        ret.push(
+            Slice {
+                architecture: architecture,
+                machine: machine,
+                offset: offset,
+                size: size,
+                container: Container::MachFat,
+            }
+        );
+    }
+
+    Ok(Some(ret))
+}
+
+fn le_u16(bytes: &[u8], off: usize) -> Option<u16> {
+    bytes.get(off..off + 2).map(LittleEndian::read_u16)
+}
+
+fn le_u32(bytes: &[u8], off: usize) -> Option<u32> {
+    bytes.get(off..off + 4).map(LittleEndian::read_u32)
+}
+
+fn le_u64(bytes: &[u8], off: usize) -> Option<u64> {
+    bytes.get(off..off + 8).map(LittleEndian::read_u64)
+}
+
+/// PE headers, read directly for the parts goblin doesn't expose.
+struct PeHeader<'a> {
+    bytes: &'a [u8],
+    machine: u16,
+    pe32plus: bool,
+    /// File offset of the optional header
+    opt: usize,
+    /// File offset of the section table
+    sections: usize,
+    num_sections: usize,
+}
+
+impl<'a> PeHeader<'a> {
+    fn parse(bytes: &'a [u8]) -> Option<PeHeader<'a>> {
+        if bytes.get(0..2) != Some(b"MZ") {
+            return None;
+        }
+
+        let pe = le_u32(bytes, 0x3c)? as usize;
+        if bytes.get(pe..pe + 4) != Some(b"PE\0\0") {
+            return None;
+        }
+
+        let opt = pe + 24;
+        let pe32plus = match le_u16(bytes, opt)? {
+            0x10b => false,
+            0x20b => true,
+            _ => return None,
+        };
+
+        Some(
+            PeHeader {
+                bytes: bytes,
+                machine: le_u16(bytes, pe + 4)?,
+                pe32plus: pe32plus,
+                opt: opt,
+                sections: opt + le_u16(bytes, pe + 20)? as usize,
+                num_sections: le_u16(bytes, pe + 6)? as usize,
+            }
+        )
+    }
+
+    fn image_base(&self) -> Option<u64> {
+        if self.pe32plus { le_u64(self.bytes, self.opt + 24) } else { le_u32(self.bytes, self.opt + 28).map(|x| x as u64) }
+    }
+
+    /// Mitigations enabled in the `DllCharacteristics` field.
+    fn hardening(&self) -> HardeningReport {
+        const DYNAMIC_BASE: u16 = 0x40;
+        const NX_COMPAT: u16 = 0x100;
+        const GUARD_CF: u16 = 0x4000;
+
+        match le_u16(self.bytes, self.opt + 70) {
+            Some(flags) => {
+                HardeningReport {
+                    nx: Some(flags & NX_COMPAT != 0),
+                    pie: Some(flags & DYNAMIC_BASE != 0),
+                    control_flow_guard: flags & GUARD_CF != 0,
+                    ..HardeningReport::new()
+                }
+            }
+            None => HardeningReport::new(),
+        }
+    }
+
+    /// RVA and size of data directory `index`.
+    fn data_directory(&self, index: usize) -> Option<(u32, u32)> {
+        let (count, dirs) = if self.pe32plus { (self.opt + 108, self.opt + 112) } else { (self.opt + 92, self.opt + 96) };
+
+        if index >= le_u32(self.bytes, count)? as usize {
+            return None;
+        }
+
+        Some((le_u32(self.bytes, dirs + index * 8)?, le_u32(self.bytes, dirs + index * 8 + 4)?))
+    }
+
+    /// File offset of `rva`, if it's inside a section.
+    fn offset(&self, rva: u32) -> Option<usize> {
+        (0..self.num_sections)
+            .map(|i| self.sections + i * 40)
+            .filter_map(
+                |sec| {
+                    let vsize = le_u32(self.bytes, sec + 8)?;
+                    let vaddr = le_u32(self.bytes, sec + 12)?;
+                    let raw = le_u32(self.bytes, sec + 20)?;
+
+                    if rva >= vaddr && rva < vaddr.saturating_add(vsize) {
+                        Some((rva - vaddr + raw) as usize)
+                    } else {
+                        None
+                    }
+                }
+            )
+            .next()
+    }
+}
+
+/// PE32, PE32+ or TE image of a UEFI module.
+struct EfiImage {
+    machine: Machine,
+    /// Preferred load address
+    base: u64,
+    /// RVA of the entry point
+    entry: u64,
+    /// Size in memory
+    size: u64,
+    /// RVA, size in memory and initialized contents of each section
+    sections: Vec<(u64, u64, Vec<u8>)>,
+}
+
+impl EfiImage {
+    fn parse(bytes: &[u8]) -> Result<EfiImage> {
+        // Section table, number of sections and the difference between the file offsets in the
+        // table and those in `bytes`
+        let (machine, base, entry, table, count, shift) = if let Some(hdr) = PeHeader::parse(bytes) {
+            (hdr.machine, hdr.image_base().unwrap_or(0), le_u32(bytes, hdr.opt + 16).unwrap_or(0), hdr.sections, hdr.num_sections, 0)
+        } else if bytes.get(0..2) == Some(uefi::TE_SIGNATURE) && bytes.len() >= 40 {
+            // The 40 byte TE header replaces the first `stripped` bytes of the original PE file
+            let stripped = le_u16(bytes, 6).unwrap_or(0) as i64;
+
+            (le_u16(bytes, 2).unwrap_or(0), le_u64(bytes, 16).unwrap_or(0), le_u32(bytes, 8).unwrap_or(0), 40, bytes[4] as usize, 40 - stripped)
+        } else {
+            return Err("Neither a PE nor a TE image".into());
+        };
+        let machine = match machine {
+            0x14c => Machine::Ia32,
+            0x8664 => Machine::Amd64,
+            0x1c0 | 0x1c2 | 0x1c4 => Machine::Arm,
+            machine => return Err(format!("Unsupported machine ({:#x})", machine).into()),
+        };
+        let mut sections = vec![];
+
+        for sec in (0..count).map(|i| table + i * 40) {
+            let (vsize, rva, raw_size, raw) = match (le_u32(bytes, sec + 8), le_u32(bytes, sec + 12), le_u32(bytes, sec + 16), le_u32(bytes, sec + 20)) {
+                (Some(v), Some(r), Some(s), Some(p)) => (v as u64, r as u64, s as u64, p as i64),
+                _ => return Err("Section table is truncated".into()),
+            };
+            let start = (raw + shift).max(0) as usize;
+            let len = if vsize == 0 { raw_size } else { raw_size.min(vsize) } as usize;
+            let data = match bytes.get(start..start + len) {
+                Some(data) => data.to_vec(),
+                None => return Err(format!("Section at RVA {:#x} is truncated", rva).into()),
+            };
+
+            sections.push((rva, vsize.max(raw_size), data));
+        }
+
+        let size = sections.iter().map(|&(rva, size, _)| rva + size).max().unwrap_or(0);
+
+        Ok(EfiImage { machine: machine, base: base, entry: entry as u64, size: size, sections: sections })
+    }
+
+    /// Covers `reg` with the initialized parts of all sections.
+    fn map(&self, reg: &mut Region) -> Result<()> {
+        for &(rva, _, ref data) in self.sections.iter().filter(|s| !s.2.is_empty()) {
+            let bound = Bound::new(self.base + rva, self.base + rva + data.len() as u64);
+
+            if !reg.cover(bound.clone(), Layer::wrap(data.clone())) {
+                return Err(format!("Cannot cover bound: {:?}", bound).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Addresses and names of the well known protocol GUIDs in the image.
+    fn protocols(&self) -> Vec<(u64, &'static str)> {
+        let mut ret = vec![];
+
+        for &(rva, _, ref data) in self.sections.iter() {
+            ret.extend(uefi::protocol_references(data).into_iter().map(|(off, name)| (self.base + rva + off as u64, name)));
+        }
+
+        ret
+    }
+}
+
+/// Checks whether `bytes` is a Windows ARM64X image. These are ARM64 PE32+ files whose load
+/// configuration points to CHPE metadata describing a second, ARM64EC view of the same image.
+/// Returns the native and the EC view as slices, `None` if `bytes` is any other file.
+fn arm64x_slices(bytes: &[u8]) -> Option<Vec<Slice>> {
+    let pe = PeHeader::parse(bytes)?;
+
+    if pe.machine != 0xaa64 || !pe.pe32plus {
+        return None;
+    }
+
+    let (load_config_rva, _) = pe.data_directory(10)?;
+    let load_config = pe.offset(load_config_rva)?;
+
+    // CHPEMetadataPointer of IMAGE_LOAD_CONFIG_DIRECTORY64
+    if le_u32(bytes, load_config)? < 0xd0 || le_u64(bytes, load_config + 0xc8)? == 0 {
+        return None;
+    }
+
+    let slice = |arch: &str| {
+        Slice {
+            architecture: arch.to_string(),
+            machine: None,
+            offset: 0,
+            size: bytes.len() as u64,
+            container: Container::Arm64x,
+        }
+    };
+
+    Some(vec![slice("arm64"), slice("arm64ec")])
+}
+
+/// Exports DLLs provide for the system to call, in addition to the entry point.
+const DLL_ENTRY_POINTS: &'static [&'static str] = &[
+    "DllMain",
+    "DllRegisterServer",
+    "DllUnregisterServer",
+    "DllGetClassObject",
+    "DllCanUnloadNow",
+    "DllInstall",
+    "ServiceMain",
+];
+
+/// Upper bound on the length of a TLS callback list, in case it isn't terminated.
+const MAX_TLS_CALLBACKS: usize = 256;
+
+/// Function imported by a PE file.
+#[derive(Clone,Debug,PartialEq,Eq)]
+struct PeImport {
+    dll: String,
+    /// Import name, `<dll>#<ordinal>` for imports by ordinal
+    name: String,
+    /// Virtual address of the import address table slot
+    slot: u64,
+}
+
+/// Symbol exported by a PE file.
+#[derive(Clone,Debug,PartialEq,Eq)]
+struct PeExport {
+    /// Export name, `<dll>#<ordinal>` for exports without one
+    name: String,
+    rva: u32,
+    /// Target of a forwarder export, e.g. `NTDLL.RtlAllocateHeap`
+    forward: Option<String>,
+}
+
+impl<'a> PeHeader<'a> {
+    fn pointer_size(&self) -> usize {
+        if self.pe32plus { 8 } else { 4 }
+    }
+
+    fn string(&self, rva: u32) -> Option<String> {
+        let off = self.offset(rva)?;
+        let bytes = self.bytes.get(off..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+
+        Some(String::from_utf8_lossy(&bytes[0..len]).to_string())
+    }
+
+    /// Reads the import name table at `names` of `dll`. The matching import address table
+    /// starts at `iat`.
+    fn import_names(&self, dll: &str, names: u32, iat: u32, image_base: u64) -> Vec<PeImport> {
+        let ptr = self.pointer_size();
+        let ordinal_flag = 1u64 << (ptr * 8 - 1);
+        let mut ret = vec![];
+
+        for i in 0.. {
+            let entry = match self.offset(names + (i * ptr) as u32) {
+                Some(off) if ptr == 8 => le_u64(self.bytes, off),
+                Some(off) => le_u32(self.bytes, off).map(|x| x as u64),
+                None => None,
+            };
+            let name = match entry {
+                None | Some(0) => break,
+                Some(e) if e & ordinal_flag != 0 => format!("{}#{}", dll, e & 0xffff),
+                // Skips the hint
+                Some(e) => {
+                    match self.string(e as u32 + 2) {
+                        Some(name) => name,
+                        None => break,
+                    }
+                }
+            };
+
+            ret.push(PeImport { dll: dll.to_string(), name: name, slot: image_base + iat as u64 + (i * ptr) as u64 });
+        }
+
+        ret
+    }
+
+    /// Imports from the import directory and the delay load import directory.
+    fn imports(&self) -> Vec<PeImport> {
+        let image_base = self.image_base().unwrap_or(0);
+        let mut ret = vec![];
+
+        if let Some(dir) = self.data_directory(1).and_then(|(rva, _)| self.offset(rva)) {
+            for desc in (0..).map(|i| dir + i * 20) {
+                let ilt = le_u32(self.bytes, desc).unwrap_or(0);
+                let name = le_u32(self.bytes, desc + 12).unwrap_or(0);
+                let iat = le_u32(self.bytes, desc + 16).unwrap_or(0);
+
+                if name == 0 || iat == 0 {
+                    break;
+                }
+
+                let dll = self.string(name).unwrap_or_default();
+                // Bound imports without a lookup table only have names in the IAT
+                let names = if ilt != 0 { ilt } else { iat };
+                ret.extend(self.import_names(&dll, names, iat, image_base));
+            }
+        }
+
+        if let Some(dir) = self.data_directory(13).and_then(|(rva, _)| self.offset(rva)) {
+            for desc in (0..).map(|i| dir + i * 32) {
+                let attrs = le_u32(self.bytes, desc).unwrap_or(0);
+                let fields = [4, 12, 16].iter().map(|&o| le_u32(self.bytes, desc + o).unwrap_or(0)).collect::<Vec<_>>();
+
+                if fields.iter().any(|&f| f == 0) {
+                    break;
+                }
+
+                // Descriptors of old linkers hold virtual addresses instead of RVAs
+                let fields = if attrs & 1 == 0 {
+                    fields.iter().map(|&f| (f as u64).wrapping_sub(image_base) as u32).collect()
+                } else {
+                    fields
+                };
+                let dll = self.string(fields[0]).unwrap_or_default();
+
+                ret.extend(self.import_names(&dll, fields[2], fields[1], image_base));
+            }
+        }
+
+        ret
+    }
+
+    /// Named and ordinal-only exports. Forwarders point into the export directory.
+    fn exports(&self) -> Vec<PeExport> {
+        let (dir_rva, dir_size) = match self.data_directory(0) {
+            Some((rva, size)) if rva != 0 => (rva, size),
+            _ => return vec![],
+        };
+        let dir = match self.offset(dir_rva) {
+            Some(dir) => dir,
+            None => return vec![],
+        };
+        let field = |o: usize| le_u32(self.bytes, dir + o).unwrap_or(0);
+        let dll = self.string(field(12)).unwrap_or_default();
+        let (base, num_funcs, num_names) = (field(16), field(20), field(24));
+        let (funcs, names, ordinals) = (field(28), field(32), field(36));
+        let mut named = HashMap::new();
+        let mut ret = vec![];
+
+        for i in 0..num_names {
+            let name = self.offset(names + i * 4).and_then(|o| le_u32(self.bytes, o)).and_then(|rva| self.string(rva));
+            let idx = self.offset(ordinals + i * 2).and_then(|o| le_u16(self.bytes, o));
+
+            if let (Some(name), Some(idx)) = (name, idx) {
+                named.insert(idx as u32, name);
+            }
+        }
+
+        for i in 0..num_funcs {
+            let rva = match self.offset(funcs + i * 4).and_then(|o| le_u32(self.bytes, o)) {
+                Some(0) | None => continue,
+                Some(rva) => rva,
+            };
+            let forward = if rva >= dir_rva && rva < dir_rva + dir_size { self.string(rva) } else { None };
+            let name = named.remove(&i).unwrap_or_else(|| format!("{}#{}", dll, base + i));
+
+            ret.push(PeExport { name: name, rva: rva, forward: forward });
+        }
+
+        ret
+    }
+
+    /// Virtual addresses of the callbacks listed in the TLS directory.
+    fn tls_callbacks(&self) -> Vec<u64> {
+        let image_base = self.image_base().unwrap_or(0);
+        let ptr = self.pointer_size();
+        let read = |va: u64| -> Option<u64> {
+            let off = self.offset(va.checked_sub(image_base)? as u32)?;
+            if ptr == 8 { le_u64(self.bytes, off) } else { le_u32(self.bytes, off).map(|x| x as u64) }
+        };
+        let dir = match self.data_directory(9) {
+            Some((rva, _)) if rva != 0 => rva as u64 + image_base,
+            _ => return vec![],
+        };
+        // AddressOfCallBacks follows the start and end of the template and the index address
+        let mut next = match read(dir + 3 * ptr as u64) {
+            Some(va) if va != 0 => va,
+            _ => return vec![],
+        };
+        let mut ret = vec![];
+
+        while let Some(cb) = read(next) {
+            if cb == 0 || ret.len() >= MAX_TLS_CALLBACKS {
+                break;
+            }
+            ret.push(cb);
+            next += ptr as u64;
+        }
+
+        ret
+    }
+
+    /// RVA and size of each word listed in the base relocation directory.
+    fn base_relocations(&self) -> Vec<(u32, usize)> {
+        let (mut block, end) = match self.data_directory(5) {
+            Some((rva, size)) if rva != 0 => (rva, rva.saturating_add(size)),
+            _ => return vec![],
+        };
+        let mut ret = vec![];
+
+        // Blocks of a page RVA, the block size and 16 bit entries of type and page offset
+        while block < end {
+            let (page, size) = match self.offset(block).map(|off| (le_u32(self.bytes, off), le_u32(self.bytes, off + 4))) {
+                Some((Some(page), Some(size))) if size >= 8 => (page, size),
+                _ => break,
+            };
+
+            for entry in (0..(size - 8) / 2).map(|i| 8 + i * 2) {
+                let entry = match self.offset(block + entry).and_then(|off| le_u16(self.bytes, off)) {
+                    Some(e) => e,
+                    None => break,
+                };
+
+                match entry >> 12 {
+                    // IMAGE_REL_BASED_HIGHLOW
+                    3 => ret.push((page + (entry & 0xfff) as u32, 4)),
+                    // IMAGE_REL_BASED_DIR64
+                    10 => ret.push((page + (entry & 0xfff) as u32, 8)),
+                    _ => {}
+                }
+            }
+
+            block = block.saturating_add(size);
+        }
+
+        ret
+    }
+
+    /// Checks whether the image is a UEFI application or driver.
+    fn efi(&self) -> bool {
+        // IMAGE_SUBSYSTEM_EFI_APPLICATION to IMAGE_SUBSYSTEM_EFI_ROM
+        le_u16(self.bytes, self.opt + 68).map(|s| s >= 10 && s <= 13).unwrap_or(false)
+    }
+
+    /// Checks whether the image can only be loaded at its preferred base.
+    fn relocs_stripped(&self) -> bool {
+        // IMAGE_FILE_RELOCS_STRIPPED in the file header characteristics
+        le_u16(self.bytes, self.opt - 2).map(|c| c & 1 != 0).unwrap_or(false)
+    }
+
+    /// Checks whether `rva` is inside an executable section.
+    fn executable(&self, rva: u32) -> bool {
+        (0..self.num_sections).map(|i| self.sections + i * 40).any(
+            |sec| {
+                let vaddr = le_u32(self.bytes, sec + 12).unwrap_or(0);
+                let vsize = le_u32(self.bytes, sec + 8).unwrap_or(0);
+                // IMAGE_SCN_MEM_EXECUTE
+                let flags = le_u32(self.bytes, sec + 36).unwrap_or(0);
+
+                flags & 0x2000_0000 != 0 && rva >= vaddr && rva < vaddr.saturating_add(vsize)
+            }
+        )
+    }
+
+    /// Finds `jmp [slot]` import thunks in executable sections. Returns the thunk addresses and
+    /// the slots they jump through.
+    fn thunks(&self, imports: &[PeImport]) -> Vec<(u64, u64)> {
+        let image_base = self.image_base().unwrap_or(0);
+        let slots = imports.iter().map(|i| i.slot).collect::<HashSet<_>>();
+        let mut ret = vec![];
+
+        for sec in (0..self.num_sections).map(|i| self.sections + i * 40) {
+            let rva = le_u32(self.bytes, sec + 12).unwrap_or(0);
+            let size = le_u32(self.bytes, sec + 16).unwrap_or(0) as usize;
+            let raw = le_u32(self.bytes, sec + 20).unwrap_or(0) as usize;
+            let data = match self.bytes.get(raw..raw + size) {
+                Some(data) if self.executable(rva) => data,
+                _ => continue,
+            };
+
+            for (i, w) in data.windows(6).enumerate() {
+                if w[0] != 0xff || w[1] != 0x25 {
+                    continue;
+                }
+
+                let addr = image_base + rva as u64 + i as u64;
+                let disp = LittleEndian::read_u32(&w[2..6]);
+                // Absolute on x86, relative to the next instruction on x86-64
+                let slot = if self.machine == 0x8664 { (addr + 6).wrapping_add(disp as i32 as i64 as u64) } else { disp as u64 };
+
+                if slots.contains(&slot) {
+                    ret.push((addr, slot));
+                }
+            }
+        }
+
+        ret
+    }
+}
+
+/// Returns the GUID and path of the PDB file named in the CodeView debug directory entry of a PE
+/// file.
+fn codeview(bytes: &[u8]) -> Option<([u8; 16], String)> {
+    let pe = PeHeader::parse(bytes)?;
+    let (rva, size) = pe.data_directory(6)?;
+    let dir = pe.offset(rva)?;
+
+    for entry in (0..size as usize / 28).map(|i| dir + i * 28) {
+        // IMAGE_DEBUG_TYPE_CODEVIEW
+        if le_u32(bytes, entry + 12)? != 2 {
+            continue;
+        }
+
+        let rec = le_u32(bytes, entry + 24)? as usize;
+        let len = le_u32(bytes, entry + 16)? as usize;
+        let rec = bytes.get(rec..rec + len)?;
+
+        if rec.len() > 24 && &rec[0..4] == b"RSDS" {
+            let mut guid = [0u8; 16];
+            let path = rec[24..].split(|&b| b == 0).next().unwrap_or(&[]);
+
+            guid.copy_from_slice(&rec[4..20]);
+            return Some((guid, String::from_utf8_lossy(path).to_string()));
+        }
+    }
+
+    None
+}
+
+/// Looks for the PDB of the PE file `bytes` loaded from `path`. Tries the path recorded in the
+/// binary and a file with the same name next to the binary. PDBs whose GUID doesn't match are
+/// ignored.
+fn find_pdb(path: &Path, bytes: &[u8]) -> Option<(Pdb, u64)> {
+    let (guid, pdb_path) = codeview(bytes)?;
+    let image_base = PeHeader::parse(bytes)?.image_base()?;
+    let file_name = pdb_path.rsplit(|c| c == '\\' || c == '/').next().unwrap_or("").to_string();
+    let mut candidates = vec![PathBuf::from(&pdb_path)];
+
+    if let Some(dir) = path.parent() {
+        candidates.push(dir.join(&file_name));
+    }
+    candidates.push(path.with_extension("pdb"));
+
+    for cand in candidates.iter().filter(|p| p.is_file()) {
+        match Pdb::open(cand) {
+            Ok(ref pdb) if pdb.guid != guid => debug!("{} belongs to a different binary", cand.display()),
+            Ok(pdb) => return Some((pdb, image_base)),
+            Err(e) => debug!("failed to read {}: {}", cand.display(), e),
+        }
+    }
+
+    None
+}
+
+// ELF constants goblin doesn't export
+const ET_REL: u16 = 1;
+const ET_DYN: u16 = 3;
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+const PT_GNU_RELRO: u32 = 0x6474_e552;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+const SHT_INIT_ARRAY: u32 = 14;
+const SHT_FINI_ARRAY: u32 = 15;
+const SHT_PREINIT_ARRAY: u32 = 16;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_STRTAB: u64 = 5;
+const DT_INIT: u64 = 12;
+const DT_FINI: u64 = 13;
+const DT_REL: u64 = 17;
+const DT_RELSZ: u64 = 18;
+const DT_PLTREL: u64 = 20;
+const DT_JMPREL: u64 = 23;
+const DT_PLTRELSZ: u64 = 2;
+const DT_INIT_ARRAY: u64 = 25;
+const DT_FINI_ARRAY: u64 = 26;
+const DT_INIT_ARRAYSZ: u64 = 27;
+const DT_FINI_ARRAYSZ: u64 = 28;
+const DT_PREINIT_ARRAY: u64 = 32;
+const DT_PREINIT_ARRAYSZ: u64 = 33;
+const DT_BIND_NOW: u64 = 24;
+const DT_FLAGS: u64 = 30;
+const DT_FLAGS_1: u64 = 0x6fff_fffb;
+const DF_BIND_NOW: u64 = 0x8;
+const DF_1_NOW: u64 = 0x1;
+const DT_VERSYM: u64 = 0x6fff_fff0;
+const DT_VERDEF: u64 = 0x6fff_fffc;
+const DT_VERDEFNUM: u64 = 0x6fff_fffd;
+const DT_VERNEED: u64 = 0x6fff_fffe;
+const DT_VERNEEDNUM: u64 = 0x6fff_ffff;
+const STT_OBJECT: u8 = 1;
+const STT_GNU_IFUNC: u8 = 10;
+
+/// What an ELF relocation type computes, independent of the machine.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum ElfRelocClass {
+    /// Load base plus addend
+    Relative,
+    /// Symbol value plus addend
+    Absolute,
+    /// Symbol value, i.e. GOT and PLT slots
+    Symbol,
+    /// Return value of the resolver at load base plus addend
+    IRelative,
+}
+
+/// Relocation entry of an ELF file.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+struct ElfReloc {
+    offset: u64,
+    sym: u64,
+    kind: u32,
+    /// Explicit addend of `RELA` entries, `REL` entries use the contents of the relocated word
+    addend: Option<i64>,
+}
+
+/// ELF headers and dynamic section, read directly for the parts goblin doesn't expose: symbol
+/// versions, initializer arrays and relocations of statically linked files.
+struct ElfFile<'a> {
+    bytes: &'a [u8],
+    is_64: bool,
+    little_endian: bool,
+    /// `e_type`
+    kind: u16,
+    machine: u16,
+    flags: u32,
+    /// Virtual address, file offset and file size of each `PT_LOAD` segment
+    segments: Vec<(u64, u64, u64)>,
+    /// Type, address, file offset and size of each section
+    sections: Vec<(u32, u64, u64, u64)>,
+    /// File offset and entry size of the section header table and the index of the section name
+    /// table
+    section_table: (usize, usize, usize),
+    dynamic: Vec<(u64, u64)>,
+    /// Flags of the `PT_GNU_STACK` segment
+    stack_flags: Option<u32>,
+    /// Has a `PT_GNU_RELRO` segment
+    relro: bool,
+}
+
+impl<'a> ElfFile<'a> {
+    fn parse(bytes: &'a [u8]) -> Option<ElfFile<'a>> {
+        if bytes.get(0..4) != Some(b"\x7fELF") {
+            return None;
+        }
+
+        let mut ret = ElfFile {
+            bytes: bytes,
+            is_64: *bytes.get(4)? == 2,
+            little_endian: *bytes.get(5)? == 1,
+            kind: 0,
+            machine: 0,
+            flags: 0,
+            segments: vec![],
+            sections: vec![],
+            section_table: (0, 0, 0),
+            dynamic: vec![],
+            stack_flags: None,
+            relro: false,
+        };
+        let (phoff, shoff, sizes) = if ret.is_64 { (ret.word(32, 8)?, ret.word(40, 8)?, 54) } else { (ret.word(28, 4)?, ret.word(32, 4)?, 42) };
+        let phentsize = ret.word(sizes, 2)? as usize;
+        let phnum = ret.word(sizes + 2, 2)? as usize;
+        let shentsize = ret.word(sizes + 4, 2)? as usize;
+        let shnum = ret.word(sizes + 6, 2)? as usize;
+        let ptr = ret.pointer_size();
+        let mut dynamic = None;
+
+        ret.kind = ret.word(16, 2)? as u16;
+        ret.machine = ret.word(18, 2)? as u16;
+        ret.flags = ret.word(if ret.is_64 { 48 } else { 36 }, 4)? as u32;
+        ret.section_table = (shoff as usize, shentsize, ret.word(sizes + 8, 2)? as usize);
+
+        for ph in (0..phnum).map(|i| phoff as usize + i * phentsize) {
+            let kind = ret.word(ph, 4)? as u32;
+            let (offset, vaddr, filesz) = if ret.is_64 {
+                (ret.word(ph + 8, 8)?, ret.word(ph + 16, 8)?, ret.word(ph + 32, 8)?)
+            } else {
+                (ret.word(ph + 4, 4)?, ret.word(ph + 8, 4)?, ret.word(ph + 16, 4)?)
+            };
+
+            match kind {
+                PT_LOAD => ret.segments.push((vaddr, offset, filesz)),
+                PT_DYNAMIC => dynamic = Some((offset, filesz)),
+                PT_GNU_STACK => ret.stack_flags = Some(ret.word(if ret.is_64 { ph + 4 } else { ph + 24 }, 4)? as u32),
+                PT_GNU_RELRO => ret.relro = true,
+                _ => {}
+            }
+        }
+
+        for sh in (0..shnum).map(|i| shoff as usize + i * shentsize) {
+            let kind = ret.word(sh + 4, 4)? as u32;
+            let (addr, offset, size) = if ret.is_64 {
+                (ret.word(sh + 16, 8)?, ret.word(sh + 24, 8)?, ret.word(sh + 32, 8)?)
+            } else {
+                (ret.word(sh + 12, 4)?, ret.word(sh + 16, 4)?, ret.word(sh + 20, 4)?)
+            };
+
+            ret.sections.push((kind, addr, offset, size));
+        }
+
+        if let Some((offset, size)) = dynamic {
+            for entry in (0..size as usize / (2 * ptr)).map(|i| offset as usize + i * 2 * ptr) {
+                let tag = ret.word(entry, ptr)?;
+
+                if tag == 0 {
+                    break;
+                }
+                ret.dynamic.push((tag, ret.word(entry + ptr, ptr)?));
+            }
+        }
+
+        Some(ret)
+    }
+
+    fn pointer_size(&self) -> usize {
+        if self.is_64 { 8 } else { 4 }
+    }
+
+    /// Reads a `size` byte word at file offset `off`.
+    fn word(&self, off: usize, size: usize) -> Option<u64> {
+        let b = self.bytes.get(off..off + size)?;
+
+        Some(
+            match (size, self.little_endian) {
+                (1, _) => b[0] as u64,
+                (2, true) => LittleEndian::read_u16(b) as u64,
+                (2, false) => BigEndian::read_u16(b) as u64,
+                (4, true) => LittleEndian::read_u32(b) as u64,
+                (4, false) => BigEndian::read_u32(b) as u64,
+                (_, true) => LittleEndian::read_u64(b),
+                (_, false) => BigEndian::read_u64(b),
+            }
+        )
+    }
+
+    fn dynamic(&self, tag: u64) -> Option<u64> {
+        self.dynamic.iter().find(|&&(t, _)| t == tag).map(|&(_, v)| v)
+    }
+
+    /// Mitigations set in the program headers and the dynamic section. Without a `PT_GNU_STACK`
+    /// segment the stack is executable.
+    fn hardening(&self) -> HardeningReport {
+        let now = self.dynamic(DT_BIND_NOW).is_some() || self.dynamic(DT_FLAGS).map(|f| f & DF_BIND_NOW != 0).unwrap_or(false) ||
+                  self.dynamic(DT_FLAGS_1).map(|f| f & DF_1_NOW != 0).unwrap_or(false);
+        let relro = match (self.relro, now) {
+            (false, _) => Relro::None,
+            (true, false) => Relro::Partial,
+            (true, true) => Relro::Full,
+        };
+
+        HardeningReport {
+            nx: Some(self.stack_flags.map(|f| f & PF_X == 0).unwrap_or(false)),
+            pie: Some(self.kind == ET_DYN),
+            relro: Some(relro),
+            ..HardeningReport::new()
+        }
+    }
+
+    /// File offset of virtual address `vaddr`.
+    fn offset(&self, vaddr: u64) -> Option<usize> {
+        self.segments
+            .iter()
+            .find(|&&(start, _, size)| vaddr >= start && vaddr < start + size)
+            .map(|&(start, off, _)| (vaddr - start + off) as usize)
+    }
+
+    /// Pointer sized word at virtual address `vaddr`.
+    fn pointer(&self, vaddr: u64) -> Option<u64> {
+        self.word(self.offset(vaddr)?, self.pointer_size())
+    }
+
+    /// String at offset `off` of the dynamic string table.
+    fn string(&self, off: u64) -> Option<String> {
+        let start = self.offset(self.dynamic(DT_STRTAB)? + off)?;
+        let bytes = self.bytes.get(start..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+
+        Some(String::from_utf8_lossy(&bytes[0..len]).to_string())
+    }
+
+    /// Returns the version suffix of the first `count` dynamic symbols, e.g. `@GLIBC_2.2.5` for
+    /// imports and hidden definitions and `@@VERS_1` for default definitions.
+    fn versions(&self, count: usize) -> Vec<Option<String>> {
+        // Version names by index and whether they are needed from another object
+        let mut names = HashMap::<u64, (String, bool)>::new();
+
+        // Version needs: file, then a list of version names with their index
+        if let (Some(mut need), Some(num)) = (self.dynamic(DT_VERNEED).and_then(|v| self.offset(v)), self.dynamic(DT_VERNEEDNUM)) {
+            for _ in 0..num {
+                let cnt = self.word(need + 2, 2).unwrap_or(0);
+                let mut aux = need + self.word(need + 8, 4).unwrap_or(0) as usize;
+
+                for _ in 0..cnt {
+                    if let (Some(idx), Some(name)) = (self.word(aux + 6, 2), self.word(aux + 8, 4).and_then(|n| self.string(n))) {
+                        names.insert(idx & 0x7fff, (name, true));
+                    }
+                    aux += self.word(aux + 12, 4).unwrap_or(0) as usize;
+                }
+
+                match self.word(need + 12, 4) {
+                    Some(0) | None => break,
+                    Some(next) => need += next as usize,
+                }
+            }
+        }
+
+        // Version definitions: index and name of each version this object provides
+        if let (Some(mut def), Some(num)) = (self.dynamic(DT_VERDEF).and_then(|v| self.offset(v)), self.dynamic(DT_VERDEFNUM)) {
+            for _ in 0..num {
+                let flags = self.word(def + 2, 2).unwrap_or(0);
+                let idx = self.word(def + 4, 2).unwrap_or(0);
+                let aux = def + self.word(def + 12, 4).unwrap_or(0) as usize;
+
+                // VER_FLG_BASE names the file itself
+                if flags & 1 == 0 {
+                    if let Some(name) = self.word(aux, 4).and_then(|n| self.string(n)) {
+                        names.insert(idx, (name, false));
+                    }
+                }
+
+                match self.word(def + 16, 4) {
+                    Some(0) | None => break,
+                    Some(next) => def += next as usize,
+                }
+            }
+        }
+
+        let versym = self.dynamic(DT_VERSYM).and_then(|v| self.offset(v));
+
+        (0..count)
+            .map(
+                |i| {
+                    let v = self.word(versym? + i * 2, 2)?;
+                    let &(ref name, needed) = names.get(&(v & 0x7fff))?;
+
+                    // Bit 15 hides a definition, it isn't the default version then
+                    Some(format!("{}{}", if needed || v & 0x8000 != 0 { "@" } else { "@@" }, name))
+                }
+            )
+            .collect()
+    }
+
+    /// All relocations, from the relocation sections or, without section headers, from the
+    /// tables named in the dynamic section.
+    fn relocations(&self) -> Vec<ElfReloc> {
+        let mut tables = self.sections
+            .iter()
+            .filter(|s| s.0 == SHT_RELA || s.0 == SHT_REL)
+            .map(|&(kind, _, offset, size)| (offset as usize, size as usize, kind == SHT_RELA))
+            .collect::<Vec<_>>();
+
+        if self.sections.is_empty() {
+            let plt_rela = self.dynamic(DT_PLTREL) == Some(DT_RELA);
+            let dyn_tables = [(DT_RELA, DT_RELASZ, true), (DT_REL, DT_RELSZ, false), (DT_JMPREL, DT_PLTRELSZ, plt_rela)];
+
+            for &(tag, size, rela) in dyn_tables.iter() {
+                if let (Some(off), Some(size)) = (self.dynamic(tag).and_then(|v| self.offset(v)), self.dynamic(size)) {
+                    tables.push((off, size as usize, rela));
+                }
+            }
+        }
+
+        tables.into_iter().flat_map(|(off, size, rela)| self.relocation_table(off, size, rela)).collect()
+    }
+
+    /// Entries of the `REL` or `RELA` table of `size` bytes at file offset `off`.
+    fn relocation_table(&self, off: usize, size: usize, rela: bool) -> Vec<ElfReloc> {
+        let ptr = self.pointer_size();
+        let entsize = if rela { 3 * ptr } else { 2 * ptr };
+        let mut ret = vec![];
+
+        for ent in (0..size / entsize).map(|i| off + i * entsize) {
+            let (offset, info) = match (self.word(ent, ptr), self.word(ent + ptr, ptr)) {
+                (Some(o), Some(i)) => (o, i),
+                _ => break,
+            };
+            let (sym, kind) = if self.is_64 { (info >> 32, info as u32) } else { (info >> 8, info as u32 & 0xff) };
+            let addend = if rela {
+                match self.word(ent + 2 * ptr, ptr) {
+                    Some(a) if self.is_64 => Some(a as i64),
+                    Some(a) => Some(a as u32 as i32 as i64),
+                    None => break,
+                }
+            } else {
+                None
+            };
+
+            ret.push(ElfReloc { offset: offset, sym: sym, kind: kind, addend: addend });
+        }
+
+        ret
+    }
+
+    /// Relocation types of `R_*_RELATIVE` and `R_*_IRELATIVE` for this machine.
+    fn relative_types(&self) -> (Option<u32>, Option<u32>) {
+        match self.machine {
+            elf::header::EM_X86_64 => (Some(8), Some(37)),
+            elf::header::EM_386 => (Some(8), Some(42)),
+            elf::header::EM_ARM => (Some(23), Some(160)),
+            // EM_RISCV
+            243 => (Some(3), Some(58)),
+            _ => (None, None),
+        }
+    }
+
+    /// Maps the relocation type `kind` of this machine to what it computes. Returns `None` for types
+    /// that don't fill a pointer sized word.
+    fn relocation_class(&self, kind: u32) -> Option<ElfRelocClass> {
+        let (relative, irelative) = self.relative_types();
+
+        if Some(kind) == relative {
+            return Some(ElfRelocClass::Relative);
+        }
+        if Some(kind) == irelative {
+            return Some(ElfRelocClass::IRelative);
+        }
+
+        match (self.machine, kind) {
+            (elf::header::EM_X86_64, 1) | (elf::header::EM_386, 1) | (elf::header::EM_ARM, 2) => Some(ElfRelocClass::Absolute),
+            (243, 1) if !self.is_64 => Some(ElfRelocClass::Absolute),
+            (243, 2) if self.is_64 => Some(ElfRelocClass::Absolute),
+            (elf::header::EM_X86_64, 6...7) | (elf::header::EM_386, 6...7) | (elf::header::EM_ARM, 21...22) | (243, 5) => Some(ElfRelocClass::Symbol),
+            _ => None,
+        }
+    }
+
+    /// Encodes `value` as a pointer sized word in the byte order of the file.
+    fn encode(&self, value: u64) -> Vec<u8> {
+        let mut ret = vec![0u8; self.pointer_size()];
+
+        match (self.is_64, self.little_endian) {
+            (true, true) => LittleEndian::write_u64(&mut ret, value),
+            (true, false) => BigEndian::write_u64(&mut ret, value),
+            (false, true) => LittleEndian::write_u32(&mut ret, value as u32),
+            (false, false) => BigEndian::write_u32(&mut ret, value as u32),
+        }
+
+        ret
+    }
+
+    /// Applies `relocs` for a load address of `base`. `symbols` are the value and name of each
+    /// dynamic symbol, the value is `None` for imports. Returns the relocated words by address and
+    /// what each of them points to. Relocations of imports are recorded but leave the word
+    /// untouched, like all relocations of object files.
+    fn apply_relocations(&self, relocs: &[ElfReloc], base: u64, symbols: &[(Option<u64>, String)]) -> (Vec<(u64, Vec<u8>)>, Vec<(u64, Relocation)>) {
+        let mask = if self.is_64 { !0 } else { 0xffff_ffff };
+        let mut patches = vec![];
+        let mut records = vec![];
+
+        if self.kind == ET_REL {
+            return (patches, records);
+        }
+
+        for r in relocs.iter() {
+            let class = match self.relocation_class(r.kind) {
+                Some(class) => class,
+                None => continue,
+            };
+            // REL entries keep the addend in the relocated word
+            let addend = match r.addend {
+                Some(a) => a as u64,
+                None => self.pointer(r.offset).unwrap_or(0),
+            };
+            let symbol = if r.sym == 0 { None } else { symbols.get(r.sym as usize) };
+            let addr = r.offset + base;
+
+            let (value, name) = match (class, symbol) {
+                (ElfRelocClass::Relative, _) => (Some(base.wrapping_add(addend)), None),
+                (ElfRelocClass::IRelative, _) => {
+                    records.push((addr, Relocation { target: Some((base.wrapping_add(addend)) & mask), symbol: None }));
+                    continue;
+                }
+                (ElfRelocClass::Absolute, None) => (Some(addend), None),
+                (ElfRelocClass::Absolute, Some(&(Some(v), ref n))) => (Some(base.wrapping_add(v).wrapping_add(addend)), Some(n.clone())),
+                (ElfRelocClass::Symbol, Some(&(Some(v), ref n))) => (Some(base.wrapping_add(v)), Some(n.clone())),
+                (_, Some(&(None, ref n))) => (None, Some(n.clone())),
+                (ElfRelocClass::Symbol, None) => continue,
+            };
+
+            if let Some(value) = value {
+                patches.push((addr, self.encode(value)));
+            }
+            records.push((addr, Relocation { target: value.map(|v| v & mask), symbol: name }));
+        }
+
+        (patches, records)
+    }
+
+    /// Value of the pointer at `vaddr` after applying relative relocations.
+    fn relocated_pointer(&self, vaddr: u64, relocs: &[ElfReloc]) -> Option<u64> {
+        let (relative, irelative) = self.relative_types();
+        let reloc = relocs.iter().find(|r| r.offset == vaddr && (Some(r.kind) == relative || Some(r.kind) == irelative));
+
+        match reloc {
+            Some(&ElfReloc { addend: Some(a), .. }) => Some(a as u64),
+            _ => self.pointer(vaddr),
+        }
+    }
+
+    /// Returns the addresses of `DT_INIT`, `DT_FINI` and the entries of the initializer and
+    /// finalizer arrays, together with a name for each.
+    fn initializers(&self, relocs: &[ElfReloc]) -> Vec<(u64, String)> {
+        let ptr = self.pointer_size() as u64;
+        let mut ret = vec![];
+        let mut arrays = vec![];
+
+        if let Some(init) = self.dynamic(DT_INIT) {
+            ret.push((init, "_init".to_string()));
+        }
+        if let Some(fini) = self.dynamic(DT_FINI) {
+            ret.push((fini, "_fini".to_string()));
+        }
+
+        let tags = [
+            (DT_PREINIT_ARRAY, DT_PREINIT_ARRAYSZ, SHT_PREINIT_ARRAY, "preinit_array"),
+            (DT_INIT_ARRAY, DT_INIT_ARRAYSZ, SHT_INIT_ARRAY, "init_array"),
+            (DT_FINI_ARRAY, DT_FINI_ARRAYSZ, SHT_FINI_ARRAY, "fini_array"),
+        ];
+
+        for &(tag, size, kind, name) in tags.iter() {
+            if let (Some(start), Some(size)) = (self.dynamic(tag), self.dynamic(size)) {
+                arrays.push((start, size, name));
+            } else if let Some(&(_, addr, _, size)) = self.sections.iter().find(|s| s.0 == kind) {
+                arrays.push((addr, size, name));
+            }
+        }
+
+        for (start, size, name) in arrays {
+            for i in 0..size / ptr {
+                match self.relocated_pointer(start + i * ptr, relocs) {
+                    // Unused slots are 0 or -1
+                    Some(0) | None => {}
+                    Some(f) if f == !0 || (ptr == 4 && f == 0xffff_ffff) => {}
+                    Some(f) => ret.push((f, format!("{}_{}", name, i))),
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Resolvers of `R_*_IRELATIVE` relocations and the GOT slots they fill.
+    fn ifunc_resolvers(&self, relocs: &[ElfReloc]) -> Vec<(u64, u64)> {
+        let (_, irelative) = self.relative_types();
+
+        relocs
+            .iter()
+            .filter(|r| Some(r.kind) == irelative)
+            .filter_map(
+                |r| match r.addend {
+                    Some(a) => Some((r.offset, a as u64)),
+                    None => self.pointer(r.offset).map(|p| (r.offset, p)),
+                }
+            )
+            .collect()
+    }
+}
+
+/// Section header of an ELF file.
+#[derive(Clone,Debug)]
+struct ElfSection {
+    name: String,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+}
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 2;
+const SHF_COMPRESSED: u64 = 0x800;
+const STT_FUNC: u8 = 2;
+
+impl<'a> ElfFile<'a> {
+    /// CPU this file is for.
+    fn cpu(&self) -> Option<Machine> {
+        let endianess = if self.little_endian { Endianess::Little } else { Endianess::Big };
+
+        match (self.machine, self.is_64) {
+            (elf::header::EM_X86_64, _) => Some(Machine::Amd64),
+            (elf::header::EM_386, _) => Some(Machine::Ia32),
+            (elf::header::EM_AVR, _) => Some(Machine::Avr),
+            (elf::header::EM_ARM, _) => Some(Machine::Arm),
+            (elf::header::EM_MIPS, false) => Some(Machine::Mips(endianess)),
+            (elf::header::EM_MIPS, true) => Some(Machine::Mips64(endianess)),
+            (243, false) => Some(Machine::RiscV32(self.flags)),
+            (243, true) => Some(Machine::RiscV64(self.flags)),
+            (elf::header::EM_68K, _) => Some(Machine::M68k),
+            (elf::header::EM_8051, _) => Some(Machine::Mcs51),
+            (elf::header::EM_MSP430, _) => Some(Machine::Msp430(self.flags)),
+            (elf::header::EM_SH, _) => Some(Machine::SuperH(endianess, self.flags)),
+            _ => None,
+        }
+    }
+
+    /// Header of section `index`.
+    fn section(&self, index: usize) -> Option<ElfSection> {
+        let (shoff, shentsize, shstrndx) = self.section_table;
+        let sh = shoff + index * shentsize;
+        let (flags, offset, size, link, info, align) = if self.is_64 {
+            (self.word(sh + 8, 8)?, self.word(sh + 24, 8)?, self.word(sh + 32, 8)?, self.word(sh + 40, 4)?, self.word(sh + 44, 4)?, self.word(sh + 48, 8)?)
+        } else {
+            (self.word(sh + 8, 4)?, self.word(sh + 16, 4)?, self.word(sh + 20, 4)?, self.word(sh + 24, 4)?, self.word(sh + 28, 4)?, self.word(sh + 32, 4)?)
+        };
+        let name = self.word(sh, 4)
+            .and_then(|n| Some(n as usize + self.sections.get(shstrndx)?.2 as usize))
+            .and_then(|n| self.bytes.get(n..))
+            .map(|b| String::from_utf8_lossy(b.split(|&c| c == 0).next().unwrap_or(&[])).to_string())
+            .unwrap_or_default();
+
+        Some(
+            ElfSection {
+                name: name,
+                kind: self.word(sh + 4, 4)? as u32,
+                flags: flags,
+                offset: offset,
+                size: size,
+                link: link as u32,
+                info: info as u32,
+                align: align,
+            }
+        )
+    }
+
+    /// Maps the relocation type `kind` of an object file to what it computes.
+    fn object_relocation_kind(&self, kind: u32) -> Option<ObjectRelocKind> {
+        match (self.machine, kind) {
+            // R_X86_64_64, R_X86_64_PC32, R_X86_64_PLT32, R_X86_64_32 and R_X86_64_32S
+            (elf::header::EM_X86_64, 1) => Some(ObjectRelocKind::Absolute(8)),
+            (elf::header::EM_X86_64, 2) | (elf::header::EM_X86_64, 4) => Some(ObjectRelocKind::Relative(0)),
+            (elf::header::EM_X86_64, 10...11) => Some(ObjectRelocKind::Absolute(4)),
+            // R_386_32, R_386_PC32 and R_386_PLT32
+            (elf::header::EM_386, 1) => Some(ObjectRelocKind::Absolute(4)),
+            (elf::header::EM_386, 2) | (elf::header::EM_386, 4) => Some(ObjectRelocKind::Relative(0)),
+            // R_ARM_PC24, R_ARM_ABS32, R_ARM_CALL and R_ARM_JUMP24
+            (elf::header::EM_ARM, 1) | (elf::header::EM_ARM, 28...29) => Some(ObjectRelocKind::ArmBranch),
+            (elf::header::EM_ARM, 2) => Some(ObjectRelocKind::Absolute(4)),
+            // R_MIPS_32
+            (elf::header::EM_MIPS, 2) => Some(ObjectRelocKind::Absolute(4)),
+            // R_RISCV_32 and R_RISCV_64
+            (243, 1) => Some(ObjectRelocKind::Absolute(4)),
+            (243, 2) => Some(ObjectRelocKind::Absolute(8)),
+            _ => None,
+        }
+    }
+
+    /// Name, value, type and section index of all entries in the symbol table.
+    fn symbols(&self) -> Vec<(String, u64, u8, u16)> {
+        let (strtab, symtab) = match (0..self.sections.len()).filter_map(|i| self.section(i)).find(|s| s.kind == SHT_SYMTAB) {
+            Some(symtab) => (self.section(symtab.link as usize).map(|s| s.offset as usize).unwrap_or(0), symtab),
+            None => return vec![],
+        };
+        let entsize = if self.is_64 { 24 } else { 16 };
+        let mut ret = vec![];
+
+        for ent in (0..symtab.size as usize / entsize).map(|i| symtab.offset as usize + i * entsize) {
+            let (value, info, shndx) = if self.is_64 {
+                (self.word(ent + 8, 8), self.word(ent + 4, 1), self.word(ent + 6, 2))
+            } else {
+                (self.word(ent + 4, 4), self.word(ent + 12, 1), self.word(ent + 14, 2))
+            };
+            let name = self.word(ent, 4)
+                .and_then(|n| self.bytes.get(strtab + n as usize..))
+                .map(|b| String::from_utf8_lossy(b.split(|&c| c == 0).next().unwrap_or(&[])).to_string());
+
+            if let (Some(name), Some(value), Some(info), Some(shndx)) = (name, value, info, shndx) {
+                ret.push((name, value, info as u8, shndx as u16));
+            }
+        }
+
+        ret
+    }
+}
+
+/// Section of a relocatable object file.
+#[derive(Clone,Debug)]
+struct ObjectSection {
+    name: String,
+    /// Offset from the start of the object's memory
+    address: u64,
+    /// Contents, `None` for uninitialized data
+    data: Option<Vec<u8>>,
+}
+
+/// How a relocation of an object file computes the word it writes.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum ObjectRelocKind {
+    /// Symbol plus addend, `n` bytes wide
+    Absolute(usize),
+    /// 32 bit offset of symbol plus addend from the address of the relocated word plus `n`
+    Relative(u64),
+    /// 24 bit word offset of ARM `B` and `BL`
+    ArmBranch,
+}
+
+/// Relocation of an object file.
+#[derive(Clone,Debug,PartialEq,Eq)]
+struct ObjectReloc {
+    /// Offset of the relocated word from the start of the object's memory
+    offset: u64,
+    kind: ObjectRelocKind,
+    /// Name of the symbol, or of the section for section relative relocations
+    symbol: String,
+    /// Offset of the symbol from the start of the object's memory, `None` if it's external
+    target: Option<u64>,
+    addend: i64,
+}
+
+/// ELF or COFF relocatable object file. The sections are laid out one after another, starting at
+/// address 0, followed by a pointer sized slot for each external symbol.
+#[derive(Clone,Debug)]
+struct Object {
+    machine: Machine,
+    little_endian: bool,
+    /// Size of the laid out sections and external symbol slots
+    size: u64,
+    sections: Vec<ObjectSection>,
+    /// Address and name of each function
+    functions: Vec<(u64, String)>,
+    /// Address of each global symbol, by name
+    globals: HashMap<String, u64>,
+    relocations: Vec<ObjectReloc>,
+    /// Address of the slot of each external symbol, by name
+    externs: BTreeMap<String, u64>,
+}
+
+impl Object {
+    fn parse(bytes: &[u8]) -> Result<Object> {
+        let mut ret = if bytes.get(0..4) == Some(b"\x7fELF") { Object::parse_elf(bytes)? } else { Object::parse_coff(bytes)? };
+        let externs = ret.relocations.iter().filter(|r| r.target.is_none()).map(|r| r.symbol.clone()).collect::<HashSet<_>>();
+        let mut externs = externs.into_iter().collect::<Vec<_>>();
+
+        externs.sort();
+        for name in externs {
+            let slot = ret.push("extern".to_string(), 8, 8, None);
+            ret.externs.insert(name, slot);
+        }
+
+        Ok(ret)
+    }
+
+    fn new(machine: Machine, little_endian: bool) -> Object {
+        Object {
+            machine: machine,
+            little_endian: little_endian,
+            size: 0,
+            sections: vec![],
+            functions: vec![],
+            globals: HashMap::new(),
+            relocations: vec![],
+            externs: BTreeMap::new(),
+        }
+    }
+
+    /// Appends a section of `size` bytes aligned to `align` and returns its address.
+    fn push(&mut self, name: String, size: u64, align: u64, data: Option<Vec<u8>>) -> u64 {
+        let align = align.max(1);
+        let address = (self.size + align - 1) / align * align;
+
+        self.sections.push(ObjectSection { name: name, address: address, data: data });
+        self.size = address + size;
+        address
+    }
+
+    /// Covers `reg` with the contents of all sections, placed at `base`.
+    fn map(&self, reg: &mut Region, base: u64) -> Result<()> {
+        for sec in self.sections.iter() {
+            if let Some(ref data) = sec.data {
+                let bound = Bound::new(base + sec.address, base + sec.address + data.len() as u64);
+
+                debug!("map {} to {:?}", sec.name, bound);
+                if !reg.cover(bound.clone(), Layer::wrap(data.clone())) {
+                    return Err(format!("Cannot cover bound: {:?}", bound).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads `size` bytes at `address` as a number.
+    fn read(&self, address: u64, size: usize) -> Option<u64> {
+        let sec = self.sections.iter().find(|s| s.address <= address && s.data.as_ref().map(|d| address - s.address < d.len() as u64) == Some(true))?;
+        let off = (address - sec.address) as usize;
+        let bytes = sec.data.as_ref()?.get(off..off + size)?;
+
+        Some(bytes.iter().enumerate().fold(0, |acc, (i, &b)| if self.little_endian { acc | (b as u64) << (8 * i) } else { acc << 8 | b as u64 }))
+    }
+
+    /// Writes the `size` least significant bytes of `value` in the object's byte order.
+    fn encode(&self, value: u64, size: usize) -> Vec<u8> {
+        (0..size).map(|i| if self.little_endian { (value >> (8 * i)) as u8 } else { (value >> (8 * (size - 1 - i))) as u8 }).collect()
+    }
+
+    /// Addend of `REL` relocations and COFF relocations, stored in the relocated word.
+    fn implicit_addend(&self, address: u64, kind: ObjectRelocKind) -> i64 {
+        match kind {
+            ObjectRelocKind::Absolute(8) => self.read(address, 8).unwrap_or(0) as i64,
+            ObjectRelocKind::Absolute(n) => self.read(address, n).unwrap_or(0) as u32 as i32 as i64,
+            ObjectRelocKind::Relative(_) => self.read(address, 4).unwrap_or(0) as u32 as i32 as i64,
+            ObjectRelocKind::ArmBranch => ((self.read(address, 4).unwrap_or(0) << 8) as u32 as i32 as i64 >> 8) << 2,
+        }
+    }
+
+    /// Applies the relocations for a load address of `base`. External symbols are looked up with
+    /// `resolve` and point to their slot if it doesn't know them. Returns the relocated words and
+    /// what each of them points to.
+    fn link(&self, base: u64, resolve: &Fn(&str) -> Option<u64>) -> (Vec<(u64, Vec<u8>)>, Vec<(u64, Relocation)>) {
+        let mut patches = vec![];
+        let mut records = vec![];
+
+        for r in self.relocations.iter() {
+            let sym = match r.target {
+                Some(t) => base + t,
+                None => resolve(&r.symbol).unwrap_or_else(|| base + self.externs.get(&r.symbol).cloned().unwrap_or(0)),
+            };
+            let addr = base + r.offset;
+            let (word, target) = match r.kind {
+                ObjectRelocKind::Absolute(n) => {
+                    let value = sym.wrapping_add(r.addend as u64);
+                    (self.encode(value, n), value)
+                }
+                // Assumes the relocated word ends the instruction
+                ObjectRelocKind::Relative(n) => {
+                    let value = sym.wrapping_add(r.addend as u64).wrapping_sub(addr + n);
+                    (self.encode(value, 4), sym.wrapping_add(r.addend as u64).wrapping_add(4u64.saturating_sub(n)))
+                }
+                ObjectRelocKind::ArmBranch => {
+                    let insn = self.read(r.offset, 4).unwrap_or(0);
+                    let value = sym.wrapping_add(r.addend as u64).wrapping_sub(addr) >> 2;
+                    (self.encode(insn & 0xff00_0000 | value & 0xff_ffff, 4), sym)
+                }
+            };
+
+            patches.push((addr, word));
+            records.push((addr, Relocation { target: Some(target), symbol: Some(r.symbol.clone()) }));
+        }
+
+        (patches, records)
+    }
+
+    fn parse_elf(bytes: &[u8]) -> Result<Object> {
+        const SHN_ABS: u16 = 0xfff1;
+        const STB_LOCAL: u8 = 0;
+        const STT_SECTION: u8 = 3;
+
+        let elf = match ElfFile::parse(bytes) {
+            Some(elf) => elf,
+            None => return Err("Invalid ELF header".into()),
+        };
+
+        if elf.kind != ET_REL {
+            return Err("Not a relocatable ELF file".into());
+        }
+
+        let machine = match elf.cpu() {
+            Some(m) => m,
+            None => return Err(format!("Unsupported machine: {}", elf.machine).into()),
+        };
+        let mut ret = Object::new(machine, elf.little_endian);
+        let mut addresses = HashMap::new();
+
+        for (idx, sec) in (0..elf.sections.len()).filter_map(|i| elf.section(i).map(|s| (i, s))) {
+            if sec.flags & SHF_ALLOC == 0 || sec.size == 0 {
+                continue;
+            }
+
+            let data = if sec.kind == SHT_NOBITS {
+                None
+            } else {
+                match bytes.get(sec.offset as usize..(sec.offset + sec.size) as usize) {
+                    Some(data) => Some(data.to_vec()),
+                    None => return Err(format!("Section {} is truncated", sec.name).into()),
+                }
+            };
+
+            addresses.insert(idx as u16, (ret.push(sec.name.clone(), sec.size, sec.align, data), sec.name));
+        }
+
+        // Name and address of all symbols, by index. External symbols have no address
+        let mut symbols = vec![];
+        for (name, value, info, shndx) in elf.symbols() {
+            let address = match (shndx, addresses.get(&shndx)) {
+                (_, Some(&(address, _))) if info & 0xf == STT_SECTION => Some(address),
+                (_, Some(&(address, _))) => Some(address + value),
+                (SHN_ABS, _) => Some(value),
+                _ => None,
+            };
+
+            if let (false, Some(address)) = (name.is_empty(), address) {
+                // Bit 0 of ARM function symbols selects Thumb mode
+                let address = if machine == Machine::Arm && info & 0xf == STT_FUNC { address & !1 } else { address };
+
+                if info & 0xf == STT_FUNC {
+                    ret.functions.push((address, name.clone()));
+                }
+                if info >> 4 != STB_LOCAL {
+                    ret.globals.insert(name.clone(), address);
+                }
+            }
+
+            match addresses.get(&shndx) {
+                Some(&(_, ref section)) if info & 0xf == STT_SECTION => symbols.push((section.clone(), address)),
+                _ => symbols.push((name, address)),
+            }
+        }
+
+        // Relocation sections name the section they apply to in `sh_info`
+        for sec in (0..elf.sections.len()).filter_map(|i| elf.section(i)) {
+            let base = match addresses.get(&(sec.info as u16)) {
+                Some(&(address, _)) if sec.kind == SHT_RELA || sec.kind == SHT_REL => address,
+                _ => continue,
+            };
+
+            for r in elf.relocation_table(sec.offset as usize, sec.size as usize, sec.kind == SHT_RELA) {
+                let kind = match elf.object_relocation_kind(r.kind) {
+                    Some(kind) => kind,
+                    None => {
+                        debug!("unsupported relocation type {} at {:#x}", r.kind, base + r.offset);
+                        continue;
+                    }
+                };
+                let (symbol, target) = symbols.get(r.sym as usize).cloned().unwrap_or_default();
+                let addend = match r.addend {
+                    Some(a) => a,
+                    None => ret.implicit_addend(base + r.offset, kind),
+                };
+
+                ret.relocations.push(ObjectReloc { offset: base + r.offset, kind: kind, symbol: symbol, target: target, addend: addend });
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn parse_coff(bytes: &[u8]) -> Result<Object> {
+        // IMAGE_SCN_CNT_CODE, IMAGE_SCN_CNT_INITIALIZED_DATA and IMAGE_SCN_CNT_UNINITIALIZED_DATA
+        const CONTENTS: u32 = 0x20 | 0x40 | 0x80;
+        // IMAGE_SCN_LNK_INFO, IMAGE_SCN_LNK_REMOVE and IMAGE_SCN_MEM_DISCARDABLE
+        const DISCARDED: u32 = 0x200 | 0x800 | 0x0200_0000;
+        // IMAGE_SYM_CLASS_EXTERNAL
+        const EXTERNAL: u8 = 2;
+
+        let machine = match le_u16(bytes, 0) {
+            Some(0x14c) => Machine::Ia32,
+            Some(0x8664) => Machine::Amd64,
+            // Short import objects of MSVC import libraries start with 0, 0xffff
+            Some(0) if le_u16(bytes, 2) == Some(0xffff) => return Err("Import object".into()),
+            _ => return Err("Not a COFF object file".into()),
+        };
+        let field = |off: usize| le_u32(bytes, off).ok_or_else(|| ::Error::from("COFF header is truncated"));
+        let num_sections = le_u16(bytes, 2).unwrap_or(0) as usize;
+        let (symtab, num_symbols) = (field(8)? as usize, field(12)? as usize);
+        let sections = 20 + le_u16(bytes, 16).unwrap_or(0) as usize;
+        let strtab = symtab + num_symbols * 18;
+        let mut ret = Object::new(machine, true);
+        let mut addresses = HashMap::new();
+
+        for (idx, sec) in (0..num_sections).map(|i| (i + 1, sections + i * 40)) {
+            let name = bytes.get(sec..sec + 8).ok_or_else(|| ::Error::from("Section table is truncated"))?;
+            let name = String::from_utf8_lossy(name.split(|&c| c == 0).next().unwrap_or(&[])).to_string();
+            let (size, offset, flags) = (field(sec + 16)? as u64, field(sec + 20)? as usize, field(sec + 36)?);
+            // IMAGE_SCN_ALIGN_*: 1 << (n - 1) bytes
+            let align = match (flags >> 20) & 0xf {
+                0 => 16,
+                n => 1 << (n - 1),
+            };
+
+            if flags & CONTENTS == 0 || flags & DISCARDED != 0 || size == 0 {
+                continue;
+            }
+
+            let data = if flags & 0x80 != 0 {
+                None
+            } else {
+                match bytes.get(offset..offset + size as usize) {
+                    Some(data) => Some(data.to_vec()),
+                    None => return Err(format!("Section {} is truncated", name).into()),
+                }
+            };
+
+            addresses.insert(idx as i16, (ret.push(name, size, align, data), sec));
+        }
+
+        // Name and address of all symbols, by index. Auxiliary records are `None`
+        let mut symbols = vec![];
+        while symbols.len() < num_symbols {
+            let sym = symtab + symbols.len() * 18;
+            let entry = bytes.get(sym..sym + 18).ok_or_else(|| ::Error::from("Symbol table is truncated"))?;
+            let name = if LittleEndian::read_u32(&entry[0..4]) == 0 {
+                let start = strtab + LittleEndian::read_u32(&entry[4..8]) as usize;
+                bytes.get(start..).and_then(|b| b.split(|&c| c == 0).next()).unwrap_or(&[])
+            } else {
+                entry[0..8].split(|&c| c == 0).next().unwrap_or(&[])
+            };
+            let name = String::from_utf8_lossy(name).to_string();
+            let value = LittleEndian::read_u32(&entry[8..12]) as u64;
+            let section = LittleEndian::read_i16(&entry[12..14]);
+            // Complex type IMAGE_SYM_DTYPE_FUNCTION
+            let function = (LittleEndian::read_u16(&entry[14..16]) >> 4) & 3 == 2;
+            let address = match section {
+                -1 => Some(value),
+                _ => addresses.get(&section).map(|&(address, _)| address + value),
+            };
+
+            if let Some(address) = address {
+                if function {
+                    ret.functions.push((address, name.clone()));
+                }
+                if entry[16] == EXTERNAL {
+                    ret.globals.insert(name.clone(), address);
+                }
+            }
+            symbols.push(Some((name, address)));
+            // Skips the auxiliary records
+            for _ in 0..entry[17] {
+                symbols.push(None);
+            }
+        }
+
+        // Relocations of each section: address, symbol index and type
+        for &(base, sec) in addresses.values() {
+            let (relocs, count) = (field(sec + 24)? as usize, le_u16(bytes, sec + 32).unwrap_or(0) as usize);
+
+            for r in (0..count).map(|i| relocs + i * 10) {
+                let (offset, sym, kind) = match (le_u32(bytes, r), le_u32(bytes, r + 4), le_u16(bytes, r + 8)) {
+                    (Some(o), Some(s), Some(k)) => (o as u64, s as usize, k),
+                    _ => return Err("Relocation table is truncated".into()),
+                };
+                let kind = match (machine, kind) {
+                    // IMAGE_REL_AMD64_ADDR64, _ADDR32 and _ADDR32NB
+                    (Machine::Amd64, 1) => ObjectRelocKind::Absolute(8),
+                    (Machine::Amd64, 2...3) => ObjectRelocKind::Absolute(4),
+                    // IMAGE_REL_AMD64_REL32 to _REL32_5 are relative to the end of the word plus
+                    // 0 to 5 bytes
+                    (Machine::Amd64, 4...9) => ObjectRelocKind::Relative(kind as u64),
+                    // IMAGE_REL_I386_DIR32, _DIR32NB and _REL32
+                    (Machine::Ia32, 6...7) => ObjectRelocKind::Absolute(4),
+                    (Machine::Ia32, 20) => ObjectRelocKind::Relative(4),
+                    _ => {
+                        debug!("unsupported relocation type {} at {:#x}", kind, base + offset);
+                        continue;
+                    }
+                };
+                let (symbol, target) = symbols.get(sym).cloned().and_then(|s| s).unwrap_or_default();
+                let addend = ret.implicit_addend(base + offset, kind);
+
+                ret.relocations.push(ObjectReloc { offset: base + offset, kind: kind, symbol: symbol, target: target, addend: addend });
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Covers `reg` with a writable layer holding the relocated words in `patches`.
+fn cover_patches(reg: &mut Region, patches: &[(u64, Vec<u8>)]) -> Result<()> {
+    let start = patches.iter().map(|&(a, _)| a).min();
+    let end = patches.iter().map(|&(a, ref w)| a + w.len() as u64).max();
+
+    if let (Some(start), Some(end)) = (start, end) {
+        let mut layer = Layer::writable();
+
+        for &(addr, ref word) in patches.iter() {
+            for (i, &b) in word.iter().enumerate() {
+                layer.write(addr - start + i as u64, Some(b));
+            }
+        }
+
+        if !reg.cover_named(region::RELOCATION_LAYER, Bound::new(start, end), layer) {
+            return Err(format!("Cannot cover relocations at {:#x}..{:#x}", start, end).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Emits an `AnalysisEvent` telling whether loading `path` worked.
+fn report(path: &Path, ret: Result<(Project, Machine)>) -> Result<(Project, Machine)> {
+    match ret {
+        Ok((mut proj, machine)) => {
+            let code = proj.sections.iter().filter(|s| s.execute).map(|s| s.area.end - s.area.start).sum();
+
+            proj.data_types.pointer_size = machine.pointer_size() as u64;
+            proj.data_types.endianess = machine.endianess();
+            event::emit(AnalysisEvent::Loaded { name: proj.name.clone(), machine: machine, code_bytes: code });
+            Ok((proj, machine))
+        }
+        Err(e) => {
+            event::emit(AnalysisEvent::Error { address: None, message: format!("failed to load {}: {}", path.display(), e) });
+            Err(e)
+        }
+    }
+}
+
+fn slice_list(slices: &[Slice]) -> String {
+    slices.iter().map(|s| s.architecture.clone()).collect::<Vec<_>>().join(", ")
+}
+
+/// Lists the architecture slices of a multi-architecture container, i.e. a fat Mach-o binary or a
+/// Windows ARM64X image. Returns an empty list for all other files, which can be opened with
+/// [`load`](fn.load.html) directly.
+pub fn slices(path: &Path) -> Result<Vec<Slice>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    if let Some(slices) = fat_slices(&bytes)? {
+        Ok(slices)
+    } else {
+        Ok(arm64x_slices(&bytes).unwrap_or_default())
+    }
+}
+
+/// Loads a single architecture `slice` of the multi-architecture container at `path`. The slice
+/// must have been returned by [`slices`](fn.slices.html) for the same file. Fails for the slices
+/// of ARM64X images.
+pub fn load_slice(path: &Path, slice: &Slice) -> Result<(Project, Machine)> {
+    report(path, read_slice(path, slice))
+}
+
+fn read_slice(path: &Path, slice: &Slice) -> Result<(Project, Machine)> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+
+    match (slice.container, slice.machine) {
+        (Container::MachFat, Some(_)) => {
+            let mut fd = File::open(path)?;
+            let mut bytes = vec![0u8; slice.size as usize];
+
+            fd.seek(SeekFrom::Start(slice.offset))?;
+            fd.read_exact(&mut bytes)?;
+            load_mach(&bytes, 0, name)
+        }
+        (Container::Arm64x, _) => Err(format!("{} is an ARM64X image, loading its {} view is not supported", name, slice.architecture).into()),
+        (Container::MachFat, None) => Err(format!("Unsupported machine: {} slice of {}", slice.architecture, name).into()),
+    }
+}
+
+/// Loads the position independent ELF or PE file at `path` like [`load`](fn.load.html), but at
+/// address `base` instead of its preferred one. ELF files must be shared objects, PIE executables
+/// or relocatable objects, PE images must have base relocations. COFF object files are linked to
+/// `base` too.
+pub fn load_at(path: &Path, base: u64) -> Result<(Project, Machine)> {
+    report(path, read_at(path, base))
+}
+
+fn read_at(path: &Path, base: u64) -> Result<(Project, Machine)> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    if let Some(elf) = ElfFile::parse(&bytes) {
+        match elf.kind {
+            ET_DYN => load_elf(&bytes, name, base),
+            ET_REL => load_object(&Object::parse(&bytes)?, name, base),
+            _ => Err(format!("{} isn't position independent", name).into()),
+        }
+    } else if PeHeader::parse(&bytes).is_some() && arm64x_slices(&bytes).is_none() {
+        let (mut proj, machine) = load_pe(&bytes, name, Some(base))?;
+
+        if let Some((pdb, _)) = find_pdb(path, &bytes) {
+            pdb.apply(&mut proj, base);
+        }
+        Ok((proj, machine))
+    } else if let Ok(obj) = Object::parse(&bytes) {
+        load_object(&obj, name, base)
+    } else {
+        Err(format!("{} can't be loaded at {:#x}, only ELF, PE and COFF files can be rebased", name, base).into())
+    }
+}
+
+/// Loads a file without headers, like a memory dump or a firmware image, as described by `mapping`.
+pub fn load_raw(path: &Path, mapping: &RawMapping) -> Result<(Project, Machine)> {
+    report(path, read_raw(path, mapping))
+}
+
+fn read_raw(path: &Path, mapping: &RawMapping) -> Result<(Project, Machine)> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+    let mut bytes = Vec::new();
+
+    File::open(path)?.read_to_end(&mut bytes)?;
+    load_raw_bytes(&bytes, name, mapping)
+}
+
+/// Load an ELF, PE, TE, Mach-o or WebAssembly file, a static library, an object file, a UEFI
+/// firmware image or an Android boot image from disk and creates a `Project` from it. Returns the `Project` instance and the CPU its intended for.
+pub fn load(path: &Path) -> Result<(Project, Machine)> {
+    report(path, read_file(path))
+}
+
+fn read_file(path: &Path) -> Result<(Project, Machine)> {
+    let name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or("(encoding error)".to_string());
+    let mut fd = File::open(path)?;
+    let mut magic = [0u8; 4];
+
+    if fd.read_exact(&mut magic).is_ok() && magic == wasm::MAGIC {
+        let mut bytes = magic.to_vec();
+        fd.read_to_end(&mut bytes)?;
+        return load_wasm(&bytes, name);
+    }
+    fd.seek(SeekFrom::Start(0))?;
+
+    let peek = goblin::peek(&mut fd)?;
+    if let Hint::Unknown(magic) = peek {
+        let mut bytes = Vec::new();
+        fd.read_to_end(&mut bytes)?;
+
+        // COFF object files start with the machine type, there's no magic number
+        if let Ok(obj) = Object::parse(&bytes) {
+            load_object(&obj, name, OBJECT_BASE)
+        } else if bytes.get(0..8) == Some(ANDROID_BOOT_MAGIC) {
+            load_android_boot(&bytes, name)
+        } else if bytes.get(0..2) == Some(uefi::TE_SIGNATURE) {
+            load_te(&bytes, name)
+        } else if !uefi::modules(&bytes).is_empty() {
+            load_firmware(&bytes, name)
+        } else {
+            Err(format!("Tried to load an unknown file. Magic: {}", magic).into())
+        }
+    } else {
+        let mut bytes = Vec::new();
+        fd.read_to_end(&mut bytes)?;
+        match peek {
+            Hint::Elf(_) if ElfFile::parse(&bytes).map(|elf| elf.kind) == Some(ET_REL) => {
+                load_object(&Object::parse(&bytes)?, name, OBJECT_BASE)
+            }
+            Hint::Elf(_) => load_elf(&bytes, name, 0),
+            Hint::PE => {
+                match arm64x_slices(&bytes) {
+                    Some(slices) => Err(format!("{} is an ARM64X image with the views {}, which is not supported", name, slice_list(&slices)).into()),
+                    None => {
+                        let (mut proj, machine) = load_pe(&bytes, name, None)?;
+
+                        if let Some((pdb, image_base)) = find_pdb(path, &bytes) {
+                            pdb.apply(&mut proj, image_base);
+                        }
+                        Ok((proj, machine))
+                    }
+                }
+            }
+            Hint::Mach(_) => load_mach(&bytes, 0, name),
+            Hint::MachFat(_) => {
+                match fat_slices(&bytes)? {
+                    Some(slices) => Err(format!("{} is a fat Mach-o binary with the slices {}, select one with load_slice", name, slice_list(&slices)).into()),
+                    None => Err("Tried to load an unknown file. Magic: 0xcafebabe".into()),
+                }
+            }
+            Hint::Archive => load_archive(&bytes, name),
+            _ => {
+                println!(
+                    "Loader branch hit wildcard, should be unreachable (a new variant must have been added but code was not updated)",
+                );
+                unreachable!()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use panopticon_graph_algos::{GraphTrait, VertexListGraphTrait};
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    fn fat(magic: u32, archs: &[(u32, u32, u64, u64)], len: usize) -> Vec<u8> {
+        let mut ret = vec![];
+
+        ret.write_u32::<BigEndian>(magic).unwrap();
+        ret.write_u32::<BigEndian>(archs.len() as u32).unwrap();
+        for &(cputype, cpusubtype, offset, size) in archs.iter() {
+            ret.write_u32::<BigEndian>(cputype).unwrap();
+            ret.write_u32::<BigEndian>(cpusubtype).unwrap();
+            if magic == FAT_MAGIC_64 {
+                ret.write_u64::<BigEndian>(offset).unwrap();
+                ret.write_u64::<BigEndian>(size).unwrap();
+                ret.write_u64::<BigEndian>(12).unwrap();
+            } else {
+                ret.write_u32::<BigEndian>(offset as u32).unwrap();
+                ret.write_u32::<BigEndian>(size as u32).unwrap();
+                ret.write_u32::<BigEndian>(12).unwrap();
+            }
+        }
+        ret.resize(len, 0);
+        ret
+    }
+
+    #[test]
+    fn fat_mach() {
+        let bytes = fat(FAT_MAGIC, &[(0x0100_0007, 3, 0x1000, 0x100), (0x0100_000c, 2, 0x2000, 0x80)], 0x2080);
+        let slices = fat_slices(&bytes).unwrap().unwrap();
+
+        assert_eq!(slices.len(), 2);
+        assert_eq!(slices[0].architecture, "x86_64");
+        assert!(match slices[0].machine {
+                    Some(Machine::Amd64) => true,
+                    _ => false,
+                });
+        assert_eq!((slices[0].offset, slices[0].size), (0x1000, 0x100));
+        assert_eq!(slices[1].architecture, "arm64e");
+        assert!(slices[1].machine.is_none());
+        assert_eq!(slice_list(&slices), "x86_64, arm64e");
+
+        let bytes = fat(FAT_MAGIC_64, &[(7, 3, 0x1_0000, 0x10)], 0x1_0010);
+        let slices = fat_slices(&bytes).unwrap().unwrap();
+
+        assert_eq!(slices[0].architecture, "i386");
+        assert_eq!(slices[0].offset, 0x1_0000);
+    }
+
+    #[test]
+    fn fat_mach_invalid() {
+        let java = [0xca, 0xfe, 0xba, 0xbe, 0x00, 0x00, 0x00, 0x34, 0x00, 0x1d];
+        assert!(fat_slices(&java).unwrap().is_none());
+        assert!(fat_slices(b"\x7fELF").unwrap().is_none());
+
+        let truncated = fat(FAT_MAGIC, &[(7, 3, 0x1000, 0x100)], 0x1080);
+        assert!(fat_slices(&truncated).is_err());
+    }
+
+    // Minimal ARM64 PE32+ image with a single section holding the load configuration.
+    fn pe(machine: u16, chpe: u64) -> Vec<u8> {
+        let mut ret = vec![0u8; 0x400];
+        let opt = 0x80 + 24;
+        let sec = opt + 240;
+
+        ret[0..2].copy_from_slice(b"MZ");
+        LittleEndian::write_u32(&mut ret[0x3c..], 0x80);
+        ret[0x80..0x84].copy_from_slice(b"PE\0\0");
+        LittleEndian::write_u16(&mut ret[0x84..], machine);
+        LittleEndian::write_u16(&mut ret[0x86..], 1);
+        LittleEndian::write_u16(&mut ret[0x94..], 240);
+        LittleEndian::write_u16(&mut ret[opt..], 0x20b);
+        LittleEndian::write_u32(&mut ret[opt + 108..], 16);
+        LittleEndian::write_u32(&mut ret[opt + 112 + 80..], 0x1010);
+        LittleEndian::write_u32(&mut ret[opt + 112 + 84..], 0x140);
+        LittleEndian::write_u32(&mut ret[sec + 8..], 0x200);
+        LittleEndian::write_u32(&mut ret[sec + 12..], 0x1000);
+        LittleEndian::write_u32(&mut ret[sec + 16..], 0x200);
+        LittleEndian::write_u32(&mut ret[sec + 20..], 0x200);
+        LittleEndian::write_u32(&mut ret[0x210..], 0x140);
+        LittleEndian::write_u64(&mut ret[0x210 + 0xc8..], chpe);
+        ret
+    }
+
+    #[test]
+    fn arm64x() {
+        let slices = arm64x_slices(&pe(0xaa64, 0x1_4000_3000)).unwrap();
+
+        assert_eq!(slice_list(&slices), "arm64, arm64ec");
+        assert_eq!(slices[1].size, 0x400);
+        assert!(slices.iter().all(|s| s.machine.is_none()));
+
+        assert!(arm64x_slices(&pe(0xaa64, 0)).is_none());
+        assert!(arm64x_slices(&pe(0x8664, 0x1_4000_3000)).is_none());
+        assert!(arm64x_slices(&pe(0xaa64, 0x1_4000_3000)[0..0x100]).is_none());
+
+        let dir = TempDir::new("panopticon-arm64x").unwrap();
+        let path = dir.path().join("arm64x.dll");
+
+        File::create(&path).unwrap().write_all(&pe(0xaa64, 0x1_4000_3000)).unwrap();
+        for slice in slices.iter() {
+            let msg = read_slice(&path, slice).err().unwrap().to_string();
+            assert!(msg.contains("not supported"), "{}", msg);
+        }
+    }
+
+    fn read(path: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn pe_imports() {
+        let bytes = read("../test-data/test.exe");
+        let pe = PeHeader::parse(&bytes).unwrap();
+        let imports = pe.imports();
+        let encode = imports.iter().find(|i| i.name == "EncodePointer").unwrap();
+
+        assert_eq!(encode.dll, "KERNEL32.dll");
+        assert_eq!(encode.slot, 0x42_0050);
+        assert!(imports.iter().any(|i| i.dll == "MSVCR120D.dll" && i.name == "_initterm"));
+        assert!(pe.thunks(&imports).contains(&(0x41_8986, 0x42_0050)));
+        assert!(pe.exports().is_empty());
+    }
+
+    #[test]
+    fn pe_exports() {
+        let bytes = read("../test-data/libbeef.dll");
+        let pe = PeHeader::parse(&bytes).unwrap();
+        let exports = pe.exports();
+        let maximum = exports.iter().find(|e| e.name == "beef_maximum").unwrap();
+
+        assert_eq!(exports.len(), 10);
+        assert_eq!(maximum.rva, 0x1110);
+        assert!(maximum.forward.is_none());
+        assert!(pe.executable(maximum.rva));
+        assert!(!pe.executable(exports.iter().find(|e| e.name == "kdeadbeef").unwrap().rva));
+        assert!(pe.imports().iter().any(|i| i.name == "malloc" && i.slot == 0x61a4_8090));
+    }
+
+    // PE32 image with a single executable section mapped at the same RVA as its file offset.
+    fn pe32(dirs: &[(usize, u32, u32)], contents: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut ret = vec![0u8; 0x1000];
+        let opt = 0x80 + 24;
+        let sec = opt + 224;
+
+        ret[0..2].copy_from_slice(b"MZ");
+        LittleEndian::write_u32(&mut ret[0x3c..], 0x80);
+        ret[0x80..0x84].copy_from_slice(b"PE\0\0");
+        LittleEndian::write_u16(&mut ret[0x84..], 0x14c);
+        LittleEndian::write_u16(&mut ret[0x86..], 1);
+        LittleEndian::write_u16(&mut ret[0x94..], 224);
+        LittleEndian::write_u16(&mut ret[opt..], 0x10b);
+        LittleEndian::write_u32(&mut ret[opt + 28..], 0x40_0000);
+        LittleEndian::write_u32(&mut ret[opt + 92..], 16);
+        for &(idx, rva, size) in dirs.iter() {
+            LittleEndian::write_u32(&mut ret[opt + 96 + idx * 8..], rva);
+            LittleEndian::write_u32(&mut ret[opt + 100 + idx * 8..], size);
+        }
+        for &(off, val) in [(8, 0xe00), (12, 0x200), (16, 0xe00), (20, 0x200), (36, 0x6000_0020)].iter() {
+            LittleEndian::write_u32(&mut ret[sec + off..], val);
+        }
+        for &(off, bytes) in contents.iter() {
+            ret[off..off + bytes.len()].copy_from_slice(bytes);
+        }
+        ret
+    }
+
+    fn words(ws: &[u32]) -> Vec<u8> {
+        let mut ret = vec![0u8; ws.len() * 4];
+        LittleEndian::write_u32_into(ws, &mut ret);
+        ret
+    }
+
+    #[test]
+    fn pe_forwarders_and_delay_imports() {
+        // Export directory at 0x300: two functions, the second forwarded, only the first named
+        let exports = words(&[0, 0, 0, 0x380, 1, 2, 1, 0x340, 0x350, 0x358]);
+        let functions = words(&[0x600, 0x390]);
+        // Delay load descriptor at 0x400 with a name table at 0x440 and an IAT at 0x460
+        let delay = words(&[1, 0x480, 0x490, 0x460, 0x440, 0, 0, 0]);
+        let names = words(&[0x4a0, 0x8000_0007, 0]);
+        let bytes = pe32(
+            &[(0, 0x300, 0x100), (13, 0x400, 0x40)],
+            &[
+                (0x300, &exports),
+                (0x340, &functions),
+                (0x350, &words(&[0x3a0])),
+                (0x358, &[0, 0]),
+                (0x380, b"lib.dll\0"),
+                (0x390, b"NTDLL.RtlFree\0"),
+                (0x3a0, b"Frob\0"),
+                (0x400, &delay),
+                (0x440, &names),
+                (0x480, b"ws2_32.dll\0"),
+                (0x4a0, b"\0\0WSAStartup\0"),
+                (0x600, &[0xff, 0x25, 0x64, 0x04, 0x40, 0x00]),
+            ],
+        );
+        let pe = PeHeader::parse(&bytes).unwrap();
+
+        assert_eq!(
+            pe.exports(),
+            vec![
+                PeExport { name: "Frob".to_string(), rva: 0x600, forward: None },
+                PeExport { name: "lib.dll#2".to_string(), rva: 0x390, forward: Some("NTDLL.RtlFree".to_string()) },
+            ]
+        );
+
+        let imports = pe.imports();
+        assert_eq!(
+            imports,
+            vec![
+                PeImport { dll: "ws2_32.dll".to_string(), name: "WSAStartup".to_string(), slot: 0x40_0460 },
+                PeImport { dll: "ws2_32.dll".to_string(), name: "ws2_32.dll#7".to_string(), slot: 0x40_0464 },
+            ]
+        );
+        assert_eq!(pe.thunks(&imports), vec![(0x40_0600, 0x40_0464)]);
+    }
+
+    #[test]
+    fn pe_tls_callbacks() {
+        // TLS directory at 0x500, callback list at 0x540
+        let tls = words(&[0x40_0800, 0x40_0810, 0x40_0820, 0x40_0540, 0, 0]);
+        let bytes = pe32(&[(9, 0x500, 24)], &[(0x500, &tls), (0x540, &words(&[0x40_0600, 0x40_0700, 0]))]);
+        let pe = PeHeader::parse(&bytes).unwrap();
+
+        assert_eq!(pe.tls_callbacks(), vec![0x40_0600, 0x40_0700]);
+        assert!(PeHeader::parse(&pe32(&[], &[])).unwrap().tls_callbacks().is_empty());
+    }
+
+    #[test]
+    fn elf_versions_and_initializers() {
+        let bytes = read("../test-data/libfoo.so");
+        let elf = ElfFile::parse(&bytes).unwrap();
+        let relocs = elf.relocations();
+        let versions = elf.versions(8);
+
+        assert_eq!(versions[2], Some("@GLIBC_2.2.5".to_string()));
+        assert_eq!(versions[0], None);
+        assert_eq!(
+            elf.initializers(&relocs),
+            vec![
+                (0x628, "_init".to_string()),
+                (0x83c, "_fini".to_string()),
+                (0x780, "init_array_0".to_string()),
+                (0x740, "fini_array_0".to_string()),
+            ]
+        );
+        assert!(elf.ifunc_resolvers(&relocs).is_empty());
+    }
+
+    #[test]
+    fn elf_static_ifuncs() {
+        let bytes = read("../test-data/static");
+        let elf = ElfFile::parse(&bytes).unwrap();
+        let relocs = elf.relocations();
+        let resolvers = elf.ifunc_resolvers(&relocs);
+
+        assert_eq!(resolvers.len(), 10);
+        assert!(resolvers.contains(&(0x6b_0060, 0x41_3d50)));
+        assert!(elf.initializers(&relocs).contains(&(0x40_0980, "init_array_0".to_string())));
+        assert!(elf.versions(4).iter().all(|v| v.is_none()));
+        assert!(ElfFile::parse(b"\x7fELF").is_none());
+    }
+
+    #[test]
+    fn hardening_flags() {
+        let libfoo = ElfFile::parse(&read("../test-data/libfoo.so")).unwrap().hardening();
+        let hello = ElfFile::parse(&read("../test-data/hello-world")).unwrap().hardening();
+        let exe = read("../test-data/test.exe");
+        let pe = PeHeader::parse(&exe).unwrap().hardening();
+
+        assert_eq!((libfoo.nx, libfoo.pie, libfoo.relro), (Some(true), Some(true), Some(Relro::Partial)));
+        assert_eq!((hello.nx, hello.pie, hello.relro), (Some(false), Some(false), Some(Relro::None)));
+        assert_eq!((pe.nx, pe.pie, pe.relro, pe.control_flow_guard), (Some(true), Some(true), None, false));
+    }
+
+    #[test]
+    fn elf_relocations_at_base() {
+        let bytes = read("../test-data/libfoo.so");
+        let elf = ElfFile::parse(&bytes).unwrap();
+        let relocs = elf.relocations();
+        let mut symbols = vec![(None, String::new()); 17];
+
+        symbols[2] = (None, "puts@GLIBC_2.2.5".to_string());
+        symbols[9] = (Some(0x7cc), "bar".to_string());
+
+        let (patches, records) = elf.apply_relocations(&relocs, 0x1_0000, &symbols);
+        let records = records.into_iter().collect::<HashMap<_, _>>();
+
+        assert_eq!(elf.kind, ET_DYN);
+        assert!(patches.contains(&(0x21_0e00, vec![0x80, 0x07, 0x01, 0, 0, 0, 0, 0])));
+        assert!(patches.contains(&(0x21_1020, vec![0xcc, 0x07, 0x01, 0, 0, 0, 0, 0])));
+        assert!(!patches.iter().any(|&(a, _)| a == 0x21_1018));
+        assert_eq!(records[&0x21_0e00], Relocation { target: Some(0x1_0780), symbol: None });
+        assert_eq!(records[&0x21_1020], Relocation { target: Some(0x1_07cc), symbol: Some("bar".to_string()) });
+        assert_eq!(records[&0x21_1018], Relocation { target: None, symbol: Some("puts@GLIBC_2.2.5".to_string()) });
+        assert_eq!(records.len(), 13);
+    }
+
+    #[test]
+    fn pe_base_relocations() {
+        // One block for the page at 0x600 with a HIGHLOW entry at 0x610 and padding
+        let block = words(&[0x600, 12, 0x0000_3010]);
+        let bytes = pe32(&[(5, 0x700, 12)], &[(0x610, &words(&[0x40_0800])), (0x700, &block)]);
+        let pe = PeHeader::parse(&bytes).unwrap();
+
+        assert_eq!(pe.base_relocations(), vec![(0x610, 4)]);
+        assert!(!pe.relocs_stripped());
+        assert!(PeHeader::parse(&pe32(&[], &[])).unwrap().base_relocations().is_empty());
+    }
+
+    #[test]
+    fn relocation_layer() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+
+        assert!(reg.cover(Bound::new(0x100, 0x110), Layer::wrap(vec![0xaa; 0x10])));
+        cover_patches(&mut reg, &[(0x104, vec![1, 2]), (0x10a, vec![3])]).unwrap();
+
+        let bytes = Iterator::take(reg.iter().seek(0x102), 10).collect::<Vec<_>>();
+        assert_eq!(bytes, vec![Some(0xaa), Some(0xaa), Some(1), Some(2), Some(0xaa), Some(0xaa), Some(0xaa), Some(0xaa), Some(3), Some(0xaa)]);
+    }
+
+    #[test]
+    fn raw_segments() {
+        let bytes = (0..0x20u8).collect::<Vec<_>>();
+        let mapping = RawMapping::new("mips".parse().unwrap())
+            .endianess(Endianess::Little)
+            .segment(0, Some(0x10), 0xbfc0_0000)
+            .segment(0x10, None, 0x8000_0000)
+            .entry(0xbfc0_0000)
+            .entry(0x8000_0004);
+        let (proj, machine) = load_raw_bytes(&bytes, "rom.bin".to_string(), &mapping).unwrap();
+        let reg = proj.region();
+
+        match machine {
+            Machine::Mips(Endianess::Little) => {}
+            m => panic!("wrong machine {:?}", m),
+        }
+        assert_eq!(reg.iter().seek(0xbfc0_000f).next(), Some(Some(0x0f)));
+        assert_eq!(reg.iter().seek(0xbfc0_0010).next(), Some(None));
+        assert_eq!(reg.iter().seek(0x8000_0000).next(), Some(Some(0x10)));
+
+        let mut entries = proj.code[0]
+            .call_graph
+            .vertices()
+            .filter_map(|v| match proj.code[0].call_graph.vertex_label(v) {
+                Some(&CallTarget::Todo(Rvalue::Constant { value, .. }, Some(ref name), _)) => Some((value, name.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(entries, vec![(0x8000_0004, "entry_1".to_string()), (0xbfc0_0000, "rom.bin".to_string())]);
+    }
+
+    #[test]
+    fn raw_defaults_and_errors() {
+        let bytes = vec![0x90; 0x10];
+        let (proj, _) = load_raw_bytes(&bytes, "dump".to_string(), &RawMapping::new(Machine::Mcs51)).unwrap();
+
+        assert_eq!(proj.region().name(), "code");
+        assert_eq!(proj.data.dependencies.num_vertices(), 4);
+        assert_eq!(proj.comments.get(&("base".to_string(), 0)), Some(&"main".to_string()));
+        assert!(load_raw_bytes(&bytes, "dump".to_string(), &RawMapping::new(Machine::Avr).segment(0x20, None, 0)).is_err());
+        assert!(load_raw_bytes(&bytes, "dump".to_string(), &RawMapping::new(Machine::Avr).base(0x1_fff8)).is_err());
+        assert!(load_raw_bytes(&bytes, "dump".to_string(), &RawMapping::new(Machine::Wasm)).is_err());
+        assert!("vax".parse::<Machine>().is_err());
+    }
+
+    #[test]
+    fn archive_members() {
+        let bytes = read("../test-data/libbar.a");
+        let members = ar_members(&bytes).unwrap();
+
+        assert_eq!(members.iter().map(|m| m.0.as_str()).collect::<Vec<_>>(), vec!["bar.o", "baz.o"]);
+        assert_eq!(&members[0].1[0..4], b"\x7fELF");
+
+        // BSD long name and GNU long name table
+        let mut bsd = b"!<arch>\n".to_vec();
+        bsd.extend_from_slice(b"#1/12           0           0     0     644     16        `\nlong_name.o\0abcd");
+        bsd.extend_from_slice(b"//                                              8         `\nfoo.obj/");
+        bsd.extend_from_slice(b"/0              0           0     0     644     1         `\nx\n");
+        let members = ar_members(&bsd).unwrap();
+
+        assert_eq!(members, vec![("long_name.o".to_string(), &b"abcd"[..]), ("foo.obj".to_string(), &b"x"[..])]);
+        assert!(ar_members(b"!<arch>\nfoo").unwrap().is_empty());
+        assert!(ar_members(b"!<bogus>").is_err());
+    }
+
+    #[test]
+    fn load_static_library() {
+        let bytes = read("../test-data/libbar.a");
+        let (proj, machine) = load_archive(&bytes, "libbar.a".to_string()).unwrap();
+        let functions = |prog: &Program| {
+            let mut ret = prog.call_graph
+                .vertices()
+                .filter_map(|v| match prog.call_graph.vertex_label(v) {
+                    Some(&CallTarget::Todo(Rvalue::Constant { value, .. }, Some(ref name), _)) => Some((name.clone(), value)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            ret.sort();
+            ret
+        };
+
+        assert_eq!(machine, Machine::Amd64);
+        assert_eq!(proj.code.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["bar.o", "baz.o"]);
+        assert_eq!(
+            functions(&proj.code[0]),
+            vec![("bar".to_string(), 0x1004), ("bar2".to_string(), 0x100e), ("helper".to_string(), 0x1000)]
+        );
+        assert_eq!(functions(&proj.code[1]), vec![("baz".to_string(), 0x2000)]);
+        assert_eq!(proj.comments.get(&("RAM".to_string(), 0x2000)), Some(&"baz.o".to_string()));
+        assert_eq!(proj.data.space("RAM").unwrap().iter().seek(0x1004).next(), Some(Some(0x8d)));
+
+        let overlays = proj.data.projection().into_iter().map(|(b, r)| (b, proj.data.dependencies.vertex_label(r).unwrap().name().clone())).collect::<Vec<_>>();
+        assert!(overlays.contains(&(Bound::new(0, 0x2c), "bar.o".to_string())), "{:?}", overlays);
+
+        // baz() calls bar() in the other member and puts(), which isn't in the archive
+        let ram = proj.data.space("RAM").unwrap();
+        assert_eq!(Iterator::take(ram.iter().seek(0x2014), 4).collect::<Vec<_>>(), vec![Some(0xec), Some(0xef), Some(0xff), Some(0xff)]);
+        assert_eq!(Iterator::take(ram.iter().seek(0x2005), 4).collect::<Vec<_>>(), vec![Some(0x1d), Some(0x20), Some(0), Some(0)]);
+        assert_eq!(
+            proj.code[1].relocation(&Bound::new(0x2013, 0x2018)),
+            Some(&Relocation { target: Some(0x1004), symbol: Some("bar".to_string()) })
+        );
+        assert_eq!(proj.code[1].imports.get(&0x2030), Some(&"puts".to_string()));
+        assert_eq!(proj.code[1].imports.len(), 1);
+    }
+
+    #[test]
+    fn elf_object() {
+        let bytes = read("../test-data/libbar.a");
+        let members = ar_members(&bytes).unwrap();
+        let obj = Object::parse(members[1].1).unwrap();
+
+        assert_eq!(obj.size, 0x38);
+        assert_eq!(obj.externs.iter().map(|(s, &a)| (s.as_str(), a)).collect::<Vec<_>>(), vec![("bar", 0x28), ("puts", 0x30)]);
+        assert_eq!(obj.relocations.len(), 3);
+        assert_eq!(obj.relocations[0].symbol, ".rodata.str1.1");
+        assert_eq!(obj.relocations[0].target, Some(0x1d));
+        assert_eq!(obj.relocations[2].kind, ObjectRelocKind::Relative(0));
+        assert_eq!(obj.relocations[2].addend, -4);
+
+        let (proj, _) = load_object(&obj, "baz.o".to_string(), OBJECT_BASE).unwrap();
+        let ram = proj.data.space("RAM").unwrap();
+
+        assert_eq!(Iterator::take(ram.iter().seek(0x1014), 4).collect::<Vec<_>>(), vec![Some(0x10), Some(0), Some(0), Some(0)]);
+        assert_eq!(proj.imports.get(&0x1028), Some(&"bar".to_string()));
+        assert_eq!(proj.imports.get(&0x1030), Some(&"puts".to_string()));
+        assert_eq!(
+            proj.code[0].relocation(&Bound::new(0x1004, 0x1009)),
+            Some(&Relocation { target: Some(0x101d), symbol: Some(".rodata.str1.1".to_string()) })
+        );
+        assert_eq!(proj.code[0].relocation(&Bound::new(0x1018, 0x101c)), None);
+
+        // Linked again at another base
+        let (proj, _) = load_object(&obj, "baz.o".to_string(), 0x40_0000).unwrap();
+        let ram = proj.data.space("RAM").unwrap();
+
+        assert_eq!(Iterator::take(ram.iter().seek(0x40_0005), 4).collect::<Vec<_>>(), vec![Some(0x1d), Some(0), Some(0x40), Some(0)]);
+        assert_eq!(proj.relocations.get(&0x40_000a).and_then(|r| r.target), Some(0x40_0030));
+    }
+
+    #[test]
+    fn coff_object() {
+        let mut obj = vec![0u8; 0x100];
+        let symbols = words(&[0, 4, 0x10, 0x0020_0001, 0]);
+
+        LittleEndian::write_u16(&mut obj[0..], 0x8664);
+        LittleEndian::write_u16(&mut obj[2..], 2);
+        LittleEndian::write_u32(&mut obj[8..], 0x80);
+        LittleEndian::write_u32(&mut obj[12..], 1);
+        obj[20..28].copy_from_slice(b".text\0\0\0");
+        LittleEndian::write_u32(&mut obj[36..], 0x20);
+        LittleEndian::write_u32(&mut obj[40..], 0x60);
+        LittleEndian::write_u32(&mut obj[56..], 0x6050_0020);
+        obj[60..68].copy_from_slice(b".debug$S");
+        LittleEndian::write_u32(&mut obj[76..], 0x10);
+        LittleEndian::write_u32(&mut obj[96..], 0x4210_0040);
+        obj[0x80..0x80 + 18].copy_from_slice(&symbols[0..18]);
+        obj[0x92..0x96].copy_from_slice(&words(&[11]));
+        obj[0x96..0x9b].copy_from_slice(b"frob\0");
+
+        let obj = Object::parse(&obj).unwrap();
+
+        assert_eq!(obj.machine, Machine::Amd64);
+        assert_eq!(obj.sections.len(), 1);
+        assert_eq!(obj.size, 0x20);
+        assert_eq!(obj.functions, vec![(0x10, "frob".to_string())]);
+        assert!(Object::parse(&[0, 0, 0xff, 0xff, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn coff_relocations() {
+        let mut obj = vec![0u8; 0x100];
+
+        LittleEndian::write_u16(&mut obj[0..], 0x8664);
+        LittleEndian::write_u16(&mut obj[2..], 1);
+        LittleEndian::write_u32(&mut obj[8..], 0x80);
+        LittleEndian::write_u32(&mut obj[12..], 2);
+        obj[20..28].copy_from_slice(b".text\0\0\0");
+        LittleEndian::write_u32(&mut obj[36..], 0x10);
+        LittleEndian::write_u32(&mut obj[40..], 0x60);
+        LittleEndian::write_u32(&mut obj[44..], 0x70);
+        LittleEndian::write_u16(&mut obj[52..], 1);
+        LittleEndian::write_u32(&mut obj[56..], 0x6050_0020);
+        // call puts; ret
+        obj[0x60..0x66].copy_from_slice(&[0xe8, 0, 0, 0, 0, 0xc3]);
+        // IMAGE_REL_AMD64_REL32 at 1 against symbol 1
+        obj[0x70..0x78].copy_from_slice(&words(&[1, 1]));
+        LittleEndian::write_u16(&mut obj[0x78..], 4);
+        obj[0x80..0x88].copy_from_slice(b"main\0\0\0\0");
+        LittleEndian::write_u16(&mut obj[0x8c..], 1);
+        LittleEndian::write_u16(&mut obj[0x8e..], 0x20);
+        obj[0x90] = 2;
+        obj[0x92..0x9a].copy_from_slice(b"puts\0\0\0\0");
+        LittleEndian::write_u16(&mut obj[0xa0..], 0x20);
+        obj[0xa2] = 2;
+        obj[0xa4..0xa8].copy_from_slice(&words(&[4]));
+
+        let obj = Object::parse(&obj).unwrap();
+
+        assert_eq!(obj.size, 0x18);
+        assert_eq!(obj.globals.get("main"), Some(&0));
+        assert_eq!(obj.relocations.len(), 1);
+        assert_eq!(obj.relocations[0].kind, ObjectRelocKind::Relative(4));
+
+        let (proj, machine) = load_object(&obj, "main.obj".to_string(), 0x1000).unwrap();
+        let ram = proj.data.space("RAM").unwrap();
+
+        assert_eq!(machine, Machine::Amd64);
+        assert_eq!(Iterator::take(ram.iter().seek(0x1001), 4).collect::<Vec<_>>(), vec![Some(0xb), Some(0), Some(0), Some(0)]);
+        assert_eq!(proj.imports.get(&0x1010), Some(&"puts".to_string()));
+        assert_eq!(
+            proj.code[0].relocation(&Bound::new(0x1000, 0x1005)),
+            Some(&Relocation { target: Some(0x1010), symbol: Some("puts".to_string()) })
+        );
+    }
+
+    // TE image of a module linked at 0x10000 with a single section at RVA 0x280, holding a `ret`
+    // and the loaded image protocol GUID
+    fn te_image() -> Vec<u8> {
+        let mut te = vec![0u8; 0x90];
+
+        te[0..2].copy_from_slice(b"VZ");
+        LittleEndian::write_u16(&mut te[2..], 0x8664);
+        te[4] = 1;
+        te[5] = 11;
+        LittleEndian::write_u16(&mut te[6..], 0x1c0);
+        LittleEndian::write_u32(&mut te[8..], 0x280);
+        LittleEndian::write_u64(&mut te[16..], 0x1_0000);
+        te[40..45].copy_from_slice(b".text");
+        te[48..64].copy_from_slice(&words(&[0x40, 0x280, 0x40, 0x1e8]));
+        te[0x50] = 0xc3;
+        te[0x60..0x70].copy_from_slice(&words(&[0x5b1b31a1, 0x11d29562, 0xa0003f8e, 0x3b7269c9]));
+        te
+    }
+
+    #[test]
+    fn te() {
+        let (proj, machine) = load_te(&te_image(), "foo.te".to_string()).unwrap();
+
+        assert_eq!(machine, Machine::Amd64);
+        assert_eq!(proj.region().iter().seek(0x1_0280).next(), Some(Some(0xc3)));
+        assert_eq!(proj.comments.get(&("RAM".to_string(), 0x1_0290)), Some(&"gEfiLoadedImageProtocolGuid".to_string()));
+        assert_eq!(proj.comments.get(&("base".to_string(), 0x1_0280)), Some(&"main".to_string()));
+    }
+
+    #[test]
+    fn firmware_volume() {
+        let te = te_image();
+        let mut fv = vec![0u8; 0x48];
+        // File with a user interface section naming it "Foo" and a TE section
+        let mut file = vec![7u8; 16];
+
+        file.extend_from_slice(&[0, 0, 7, 0, 0, 0, 0, 0xf8]);
+        file.extend_from_slice(&[12, 0, 0, 0x15, b'F', 0, b'o', 0, b'o', 0, 0, 0]);
+        file.extend_from_slice(&[te.len() as u8 + 4, 0, 0, 0x12]);
+        file.extend_from_slice(&te);
+        let len = file.len() as u16;
+        LittleEndian::write_u16(&mut file[20..], len);
+        LittleEndian::write_u64(&mut fv[32..], 0x200);
+        fv[40..44].copy_from_slice(b"_FVH");
+        LittleEndian::write_u16(&mut fv[48..], 0x48);
+        fv.extend(file);
+        fv.resize(0x200, 0xff);
+
+        let (proj, machine) = load_firmware(&fv, "flash.bin".to_string()).unwrap();
+        let module = proj.data.space("Foo").unwrap();
+
+        assert_eq!(machine, Machine::Amd64);
+        assert_eq!(proj.region().name(), "Firmware");
+        assert_eq!(proj.code.len(), 1);
+        assert_eq!(proj.code[0].name, "Foo");
+        assert_eq!(module.iter().seek(0x1_0280).next(), Some(Some(0xc3)));
+        assert_eq!(proj.comments.get(&("Foo".to_string(), 0x1_0290)), Some(&"gEfiLoadedImageProtocolGuid".to_string()));
+        assert_eq!(
+            proj.comments.get(&("Firmware".to_string(), 0x48 + 24 + 12 + 4)),
+            Some(&"Foo (07070707-0707-0707-0707-070707070707)".to_string())
+        );
+        assert!(load_firmware(&[0xff; 0x100], "empty.bin".to_string()).is_err());
+    }
+
+    fn boot_image(version: u32, kernel: &[u8]) -> Vec<u8> {
+        let mut ret = vec![0u8; 0x800];
+
+        ret[0..8].copy_from_slice(ANDROID_BOOT_MAGIC);
+        ret[8..40].copy_from_slice(&words(&[kernel.len() as u32, 0x8000_8000, 0x100, 0x8100_0000, 0, 0, 0x8000_0100, 0x800]));
+        LittleEndian::write_u32(&mut ret[40..], version);
+        ret[64..77].copy_from_slice(b"console=ttyS0");
+        ret.extend_from_slice(kernel);
+        ret.resize(0x1000 + 0x100, 0);
+        ret
+    }
+
+    #[test]
+    fn android_boot_image() {
+        let mut zimage = vec![0u8; 0x40];
+
+        zimage[0..4].copy_from_slice(&[0x00, 0x00, 0xa0, 0xe1]);
+        LittleEndian::write_u32(&mut zimage[0x24..], 0x016f_2818);
+
+        let (proj, machine) = load_android_boot(&boot_image(0, &zimage), "boot.img".to_string()).unwrap();
+
+        assert_eq!(machine, Machine::Arm);
+        assert_eq!(proj.region().iter().seek(0x8000_8002).next(), Some(Some(0xa0)));
+        assert_eq!(proj.comments.get(&("RAM".to_string(), 0x8000_8000)), Some(&"cmdline: console=ttyS0".to_string()));
+        assert_eq!(proj.comments.get(&("base".to_string(), 0x8000_8000)), Some(&"main".to_string()));
+
+        // bzImage with two setup sectors
+        let mut bzimage = vec![0u8; 0x620];
+
+        bzimage[0x1f1] = 2;
+        bzimage[0x202..0x206].copy_from_slice(b"HdrS");
+        LittleEndian::write_u32(&mut bzimage[0x214..], 0x10_0000);
+        bzimage[0x600] = 0xfc;
+
+        let (proj, machine) = load_android_boot(&boot_image(2, &bzimage), "boot.img".to_string()).unwrap();
+
+        assert_eq!(machine, Machine::Ia32);
+        assert_eq!(proj.region().iter().seek(0x10_0000).next(), Some(Some(0xfc)));
+
+        let mut arm64 = vec![0u8; 0x40];
+        arm64[0x38..0x3c].copy_from_slice(b"ARMd");
+        assert!(load_android_boot(&boot_image(0, &arm64), "boot.img".to_string()).is_err());
+    }
+
+    #[test]
+    fn oat_method_headers() {
+        let mut header = b"oat\n138\0".to_vec();
+        header.extend_from_slice(&words(&[0, 5]));
+
+        // Two x86-64 methods of 0x10 and 0x12 bytes behind 24 byte headers
+        let mut code = vec![0u8; 0x80];
+        LittleEndian::write_u32(&mut code[0x1c..], 0x10);
+        LittleEndian::write_u32(&mut code[0x4c..], 0x8000_0012);
+
+        assert_eq!(oat_methods(&header, &code[0..0x62], 0x1000), vec![(0x1020, false), (0x1050, false)]);
+        assert_eq!(oat_methods(&header, &code[0..0x6f], 0x1000), vec![(0x1020, false), (0x1050, false)]);
+        assert_eq!(oat_methods(&header, &code, 0x1000), vec![]);
+        assert_eq!(oat_methods(b"dex\n035\0", &code, 0x1000), vec![]);
+
+        // Thumb-2 methods behind 8 byte headers
+        header[12] = 3;
+        let mut code = vec![0u8; 0x28];
+        LittleEndian::write_u32(&mut code[4..], 0x10);
+        LittleEndian::write_u32(&mut code[0x1c..], 4);
+
+        assert_eq!(oat_methods(&header, &code, 0), vec![(8, true), (0x20, true)]);
     }
 }
@@ -0,0 +1,630 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Memory mapped peripheral registers.
+//!
+//! Firmware talks to its hardware through registers at fixed addresses. A [`PeripheralMap`]
+//! describes where these registers are and what their bits mean. It is read either from a CMSIS
+//! SVD file with [`svd`] or from a small TOML file with [`toml`]:
+//!
+//! ```toml
+//! # one table per peripheral
+//! [UART0]
+//! base = 0x4000c000
+//! size = 0x1000        # optional, defaults to the end of the last register
+//! DR = 0x00            # register offsets
+//! FR = 0x18
+//! FR.TXFF = 5          # single bit field
+//! CR.BAUD = "15:8"     # field spanning bits 8 to 15, most significant bit first
+//! ```
+//!
+//! Registers are 32 bits wide in the TOML format. Of SVD, peripherals (including `derivedFrom`),
+//! registers and fields are used. Clusters, register arrays and enumerated values are skipped.
+//!
+//! [`annotate`] adds the name of the register accessed to the generated comment of each load and
+//! store whose address is known. Addresses are found by following constants through moves,
+//! additions and loads from the literal pool inside each basic block. Stores of known values
+//! list the fields set.
+//!
+//! [`PeripheralMap`]: struct.PeripheralMap.html
+//! [`svd`]: fn.svd.html
+//! [`toml`]: fn.toml.html
+//! [`annotate`]: fn.annotate.html
+
+use {Location, Lvalue, Operation, Project, Result, Rvalue};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+/// Range of bits inside a register.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Field {
+    /// Name of the field
+    pub name: String,
+    /// What the field does
+    pub description: Option<String>,
+    /// Least significant bit
+    pub offset: u32,
+    /// Number of bits
+    pub width: u32,
+}
+
+impl Field {
+    /// Extracts the field from the register value `value`.
+    pub fn value(&self, value: u64) -> u64 {
+        let v = value.checked_shr(self.offset).unwrap_or(0);
+
+        if self.width >= 64 { v } else { v & ((1 << self.width) - 1) }
+    }
+}
+
+/// Single register of a peripheral.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Register {
+    /// Name of the register
+    pub name: String,
+    /// What the register does
+    pub description: Option<String>,
+    /// Offset from the base address of the peripheral
+    pub offset: u64,
+    /// Size in bits
+    pub size: usize,
+    /// Bit fields, may be empty
+    pub fields: Vec<Field>,
+}
+
+/// Block of registers belonging to one piece of hardware.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Peripheral {
+    /// Name of the peripheral
+    pub name: String,
+    /// What the peripheral does
+    pub description: Option<String>,
+    /// Address of the first register
+    pub base: u64,
+    /// Size of the address range in bytes
+    pub size: u64,
+    /// Registers by offset
+    pub registers: Vec<Register>,
+}
+
+impl Peripheral {
+    fn new(name: String) -> Peripheral {
+        Peripheral { name: name, description: None, base: 0, size: 0, registers: vec![] }
+    }
+
+    /// Sets `size` to the end of the last register if it's unknown.
+    fn fix_size(&mut self) {
+        if self.size == 0 {
+            self.size = self.registers.iter().map(|r| r.offset + (r.size as u64 + 7) / 8).max().unwrap_or(0);
+        }
+    }
+}
+
+/// Memory mapped peripherals of a device.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct PeripheralMap {
+    /// All peripherals
+    pub peripherals: Vec<Peripheral>,
+}
+
+impl PeripheralMap {
+    /// Returns a map without any peripherals.
+    pub fn new() -> PeripheralMap {
+        PeripheralMap::default()
+    }
+
+    /// Returns the peripheral whose address range includes `address`.
+    pub fn peripheral(&self, address: u64) -> Option<&Peripheral> {
+        self.peripherals.iter().find(|p| p.base <= address && address - p.base < p.size)
+    }
+
+    /// Returns the peripheral and the register at `address`.
+    pub fn register(&self, address: u64) -> Option<(&Peripheral, &Register)> {
+        self.peripheral(address).and_then(
+            |p| {
+                let off = address - p.base;
+
+                p.registers.iter().find(|r| r.offset <= off && off < r.offset + (r.size as u64 + 7) / 8).map(|r| (p, r))
+            }
+        )
+    }
+
+    /// Describes an access to `address`, like `UART0.CR` or `UART0+0x40` if the register is
+    /// unknown. If `value` is written, the non-zero fields are listed as well, as in
+    /// `UART0.CR = UARTEN|BAUD=0x1a`. Returns None for addresses outside all peripherals.
+    pub fn describe(&self, address: u64, value: Option<u64>) -> Option<String> {
+        let p = match self.peripheral(address) {
+            Some(p) => p,
+            None => return None,
+        };
+        let r = match self.register(address) {
+            Some((_, r)) => r,
+            None => return Some(format!("{}+{:#x}", p.name, address - p.base)),
+        };
+        let fields = match value {
+            Some(v) if !r.fields.is_empty() => {
+                let set = r.fields
+                    .iter()
+                    .filter(|f| f.value(v) != 0)
+                    .map(|f| if f.width == 1 { f.name.clone() } else { format!("{}={:#x}", f.name, f.value(v)) })
+                    .collect::<Vec<_>>();
+
+                if set.is_empty() { " = 0".to_string() } else { format!(" = {}", set.join("|")) }
+            }
+            Some(v) => format!(" = {:#x}", v),
+            None => "".to_string(),
+        };
+
+        Some(format!("{}.{}{}", p.name, r.name, fields))
+    }
+}
+
+/// Parses numbers in SVD and TOML files: decimal, hexadecimal with `0x` and binary with `#` or
+/// `0b`.
+fn parse_number(s: &str) -> Option<u64> {
+    let s = s.trim().replace('_', "");
+
+    if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else if s.starts_with("0b") {
+        u64::from_str_radix(&s[2..], 2).ok()
+    } else if s.starts_with('#') {
+        u64::from_str_radix(&s[1..], 2).ok()
+    } else {
+        s.parse::<u64>().ok()
+    }
+}
+
+/// Parses bit ranges like `15:8` or `[15:8]` into offset and width.
+fn parse_bits(s: &str) -> Option<(u32, u32)> {
+    let s = s.trim().trim_matches(|c| c == '[' || c == ']');
+    let mut parts = s.splitn(2, ':');
+    let msb = parts.next().and_then(parse_number);
+    let lsb = match parts.next() {
+        Some(l) => parse_number(l),
+        None => msb,
+    };
+
+    match (msb, lsb) {
+        (Some(m), Some(l)) if l <= m && m < 64 => Some((l as u32, (m - l + 1) as u32)),
+        _ => None,
+    }
+}
+
+/// Reads a CMSIS System View Description.
+pub fn svd(xml: &str) -> Result<PeripheralMap> {
+    let mut ret = PeripheralMap::new();
+    let mut derived = vec![];
+    let mut path: Vec<String> = vec![];
+    let mut periph: Option<Peripheral> = None;
+    let mut reg: Option<Register> = None;
+    let mut field: Option<Field> = None;
+    let mut block = (0, 0);
+    let mut lsb = None;
+    let mut pos = 0;
+
+    while let Some(off) = xml[pos..].find('<') {
+        let start = pos + off;
+        let text = xml[pos..start].split_whitespace().collect::<Vec<_>>().join(" ");
+
+        if xml[start..].starts_with("<!--") {
+            pos = match xml[start..].find("-->") {
+                Some(e) => start + e + 3,
+                None => return Err("unterminated XML comment".into()),
+            };
+            continue;
+        }
+
+        let end = match xml[start..].find('>') {
+            Some(e) => start + e,
+            None => return Err("unterminated XML tag".into()),
+        };
+        let tag = &xml[start + 1..end];
+
+        pos = end + 1;
+
+        if !text.is_empty() && path.len() >= 2 {
+            let text = text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&");
+            let elem = &path[path.len() - 1];
+            let parent = &path[path.len() - 2];
+
+            match (&parent[..], &elem[..]) {
+                ("field", "name") => field.as_mut().map(|f| f.name = text).unwrap_or(()),
+                ("field", "description") => field.as_mut().map(|f| f.description = Some(text)).unwrap_or(()),
+                ("field", "bitOffset") | ("field", "lsb") => {
+                    if let (Some(f), Some(n)) = (field.as_mut(), parse_number(&text)) {
+                        f.width = (f.width + f.offset).saturating_sub(n as u32).max(1);
+                        f.offset = n as u32;
+                        lsb = Some(n as u32);
+                    }
+                }
+                ("field", "msb") => {
+                    if let (Some(f), Some(n)) = (field.as_mut(), parse_number(&text)) {
+                        f.width = (n as u32 + 1).saturating_sub(lsb.unwrap_or(f.offset)).max(1);
+                    }
+                }
+                ("field", "bitWidth") => field.as_mut().map(|f| f.width = parse_number(&text).unwrap_or(1) as u32).unwrap_or(()),
+                ("field", "bitRange") => {
+                    if let (Some(f), Some((o, w))) = (field.as_mut(), parse_bits(&text)) {
+                        f.offset = o;
+                        f.width = w;
+                    }
+                }
+                ("register", "name") => reg.as_mut().map(|r| r.name = text).unwrap_or(()),
+                ("register", "description") => reg.as_mut().map(|r| r.description = Some(text)).unwrap_or(()),
+                ("register", "addressOffset") => reg.as_mut().map(|r| r.offset = parse_number(&text).unwrap_or(0)).unwrap_or(()),
+                ("register", "size") => reg.as_mut().map(|r| r.size = parse_number(&text).unwrap_or(32) as usize).unwrap_or(()),
+                ("peripheral", "name") => periph.as_mut().map(|p| p.name = text).unwrap_or(()),
+                ("peripheral", "description") => periph.as_mut().map(|p| p.description = Some(text)).unwrap_or(()),
+                ("peripheral", "baseAddress") => periph.as_mut().map(|p| p.base = parse_number(&text).unwrap_or(0)).unwrap_or(()),
+                ("addressBlock", "offset") => block.0 = parse_number(&text).unwrap_or(0),
+                ("addressBlock", "size") => block.1 = parse_number(&text).unwrap_or(0),
+                _ => {}
+            }
+        }
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if tag.starts_with('/') {
+            match tag[1..].trim() {
+                "field" => {
+                    if let (Some(r), Some(f)) = (reg.as_mut(), field.take()) {
+                        r.fields.push(f);
+                    }
+                }
+                "register" => {
+                    if let (Some(p), Some(r)) = (periph.as_mut(), reg.take()) {
+                        p.registers.push(r);
+                    }
+                }
+                "addressBlock" => {
+                    if let Some(p) = periph.as_mut() {
+                        p.size = p.size.max(block.0 + block.1);
+                    }
+                    block = (0, 0);
+                }
+                "peripheral" => {
+                    if let Some(mut p) = periph.take() {
+                        p.fix_size();
+                        ret.peripherals.push(p);
+                    }
+                }
+                _ => {}
+            }
+            path.pop();
+            continue;
+        }
+
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+
+        match name {
+            "peripheral" => {
+                let attr = " derivedFrom=\"";
+
+                periph = Some(Peripheral::new("".to_string()));
+                if let Some(a) = tag.find(attr) {
+                    let from = &tag[a + attr.len()..];
+                    derived.push((ret.peripherals.len(), from[..from.find('"').unwrap_or(from.len())].to_string()));
+                }
+            }
+            "register" => reg = Some(Register { name: "".to_string(), description: None, offset: 0, size: 32, fields: vec![] }),
+            "field" => {
+                field = Some(Field { name: "".to_string(), description: None, offset: 0, width: 1 });
+                lsb = None;
+            }
+            _ => {}
+        }
+
+        if !tag.ends_with('/') {
+            path.push(name.to_string());
+        }
+    }
+
+    for (idx, from) in derived {
+        if let Some(src) = ret.peripherals.iter().find(|p| p.name == from).cloned() {
+            let p = &mut ret.peripherals[idx];
+
+            if p.registers.is_empty() {
+                p.registers = src.registers;
+            }
+            if p.size == 0 {
+                p.size = src.size;
+            }
+            if p.description.is_none() {
+                p.description = src.description;
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Reads a peripheral map in the TOML format described in the module documentation.
+pub fn toml(text: &str) -> Result<PeripheralMap> {
+    let mut ret = PeripheralMap::new();
+
+    for (no, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(c) if !line[..c].contains('"') => &line[..c],
+            _ => line,
+        };
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().trim_matches('"');
+
+            ret.peripherals.push(Peripheral::new(name.to_string()));
+            continue;
+        }
+
+        let eq = match line.find('=') {
+            Some(e) => e,
+            None => return Err(format!("line {}: expected key = value", no + 1).into()),
+        };
+        let key = line[..eq].trim();
+        let value = line[eq + 1..].trim().trim_matches('"');
+        let p = match ret.peripherals.last_mut() {
+            Some(p) => p,
+            None => return Err(format!("line {}: register outside of a peripheral", no + 1).into()),
+        };
+        let mut parts = key.splitn(2, '.');
+        let reg = parts.next().unwrap_or("");
+
+        match (reg, parts.next()) {
+            ("base", None) => {
+                p.base = parse_number(value).ok_or_else(|| format!("line {}: invalid base address", no + 1))?;
+            }
+            ("size", None) => {
+                p.size = parse_number(value).ok_or_else(|| format!("line {}: invalid size", no + 1))?;
+            }
+            (reg, None) => {
+                let offset = parse_number(value).ok_or_else(|| format!("line {}: invalid offset of {}", no + 1, reg))?;
+
+                match p.registers.iter().position(|r| r.name == reg) {
+                    Some(i) => p.registers[i].offset = offset,
+                    None => p.registers.push(Register { name: reg.to_string(), description: None, offset: offset, size: 32, fields: vec![] }),
+                }
+            }
+            (reg, Some(field)) => {
+                let (offset, width) = parse_bits(value).ok_or_else(|| format!("line {}: invalid bits of {}.{}", no + 1, reg, field))?;
+                let f = Field { name: field.to_string(), description: None, offset: offset, width: width };
+
+                match p.registers.iter().position(|r| r.name == reg) {
+                    Some(i) => p.registers[i].fields.push(f),
+                    None => return Err(format!("line {}: field of unknown register {}", no + 1, reg).into()),
+                }
+            }
+        }
+    }
+
+    for p in ret.peripherals.iter_mut() {
+        p.fix_size();
+    }
+
+    Ok(ret)
+}
+
+/// Adds the peripheral registers accessed by the functions of `proj` to the generated comments.
+/// Existing comments are kept. Returns the number of accesses found.
+pub fn annotate(proj: &mut Project, map: &PeripheralMap) -> usize {
+    let mut comments = BTreeMap::<u64, Vec<String>>::new();
+    let mut ret = 0;
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for bb in func.basic_blocks() {
+                let mut known = HashMap::<(Cow<'static, str>, Option<usize>), u64>::new();
+
+                for (mne, stmts) in func.mnemonics(bb) {
+                    for stmt in stmts.iter() {
+                        let value = {
+                            let eval = |rv: &Rvalue| match rv {
+                                &Rvalue::Constant { value, .. } => Some(value),
+                                &Rvalue::Variable { ref name, subscript, .. } => known.get(&(name.clone(), subscript)).cloned(),
+                                &Rvalue::Undefined => None,
+                            };
+
+                            match stmt.op {
+                                Operation::Move(ref a) | Operation::ZeroExtend(_, ref a) => eval(a),
+                                Operation::Add(ref a, ref b) => eval(a).and_then(|a| eval(b).map(|b| a.wrapping_add(b))),
+                                Operation::Subtract(ref a, ref b) => eval(a).and_then(|a| eval(b).map(|b| a.wrapping_sub(b))),
+                                Operation::InclusiveOr(ref a, ref b) => eval(a).and_then(|a| eval(b).map(|b| a | b)),
+                                Operation::And(ref a, ref b) => eval(a).and_then(|a| eval(b).map(|b| a & b)),
+                                Operation::ShiftLeft(ref a, ref b) => eval(a).and_then(|a| eval(b).map(|b| a.checked_shl(b as u32).unwrap_or(0))),
+                                Operation::Load(_, endianess, bits, ref addr) => {
+                                    match eval(addr) {
+                                        Some(a) if map.peripheral(a).is_some() => {
+                                            comments.entry(mne.area.start).or_insert_with(Vec::new).extend(map.describe(a, None));
+                                            ret += 1;
+                                            None
+                                        }
+                                        Some(a) if bits % 8 == 0 => proj.region().read_uint(a, bits / 8, endianess),
+                                        Some(_) => None,
+                                        None => None,
+                                    }
+                                }
+                                Operation::Store(_, _, _, ref addr, ref val) => {
+                                    if let Some(a) = eval(addr) {
+                                        if map.peripheral(a).is_some() {
+                                            comments.entry(mne.area.start).or_insert_with(Vec::new).extend(map.describe(a, eval(val)));
+                                            ret += 1;
+                                        }
+                                    }
+                                    None
+                                }
+                                _ => None,
+                            }
+                        };
+
+                        if let Lvalue::Variable { ref name, size, subscript } = stmt.assignee {
+                            match value {
+                                Some(v) => {
+                                    let v = if size < 64 { v & ((1 << size) - 1) } else { v };
+                                    known.insert((name.clone(), subscript), v);
+                                }
+                                None => {
+                                    known.remove(&(name.clone(), subscript));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for (addr, mut strs) in comments {
+        let loc = Location::Address(addr);
+
+        strs.dedup();
+        let mut text = proj.annotations.auto_comment(&loc).map(|s| s.to_string()).unwrap_or_default();
+        for s in strs {
+            if !text.split(", ").any(|t| t == s) {
+                if !text.is_empty() {
+                    text.push_str(", ");
+                }
+                text.push_str(&s);
+            }
+        }
+        proj.annotations.set_auto_comment(loc, text);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, Bound, ControlFlowGraph, ControlFlowTarget, Endianess, Function, Layer, Mnemonic, Program, Region, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    const SVD: &'static str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device>
+  <peripherals>
+    <peripheral>
+      <name>UART0</name>
+      <baseAddress>0x4000C000</baseAddress>
+      <addressBlock><offset>0</offset><size>0x1000</size><usage>registers</usage></addressBlock>
+      <registers>
+        <register>
+          <name>CR</name>
+          <description>Control &amp; status</description>
+          <addressOffset>0x30</addressOffset>
+          <size>32</size>
+          <fields>
+            <field><name>UARTEN</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth></field>
+            <!-- <field><name>IGNORED</name></field> -->
+            <field><name>BAUD</name><bitRange>[15:8]</bitRange>
+              <enumeratedValues><enumeratedValue><name>FAST</name><value>1</value></enumeratedValue></enumeratedValues>
+            </field>
+            <field><name>TXE</name><lsb>16</lsb><msb>16</msb></field>
+          </fields>
+        </register>
+      </registers>
+    </peripheral>
+    <peripheral derivedFrom="UART0">
+      <name>UART1</name>
+      <baseAddress>0x4000D000</baseAddress>
+    </peripheral>
+  </peripherals>
+</device>"#;
+
+    #[test]
+    fn svd_and_toml() {
+        let map = svd(SVD).unwrap();
+
+        assert_eq!(map.peripherals.len(), 2);
+        assert_eq!(map.peripherals[0].size, 0x1000);
+        assert_eq!(map.peripherals[0].registers[0].description, Some("Control & status".to_string()));
+        assert_eq!(map.peripherals[0].registers[0].fields.iter().map(|f| (f.offset, f.width)).collect::<Vec<_>>(), vec![(0, 1), (8, 8), (16, 1)]);
+        assert_eq!(map.peripherals[1].registers, map.peripherals[0].registers);
+        assert_eq!(map.describe(0x4000d030, Some(0x11a01)), Some("UART1.CR = UARTEN|BAUD=0x1a|TXE".to_string()));
+        assert_eq!(map.describe(0x4000c040, None), Some("UART0+0x40".to_string()));
+        assert_eq!(map.describe(0x4000e000, None), None);
+
+        let map2 = toml(
+            "# test\n[UART0]\nbase = 0x4000C000\nsize = 0x1000\nCR = 0x30 # control\nCR.UARTEN = 0\nCR.BAUD = \"15:8\"\nCR.TXE = 16\n\n[UART1]\nbase = 0x4000D000\n"
+        )
+            .unwrap();
+
+        assert_eq!((map2.peripherals[0].base, map2.peripherals[0].size), (0x4000c000, 0x1000));
+        assert_eq!(map2.peripherals[0].registers[0].fields, map.peripherals[0].registers[0].fields);
+        assert_eq!(map2.peripherals[1].size, 0);
+        assert!(toml("CR = 0x30").is_err());
+        assert!(toml("[UART0]\nCR.TXE = 16").is_err());
+    }
+
+    /*
+     * 0x100: ldr r0, [0x200]
+     * 0x104: str r1, [r0, #0x30]   ; r1 = 0x101
+     * 0x108: ldr r2, [r0, #0x34]
+     */
+    #[test]
+    fn annotate_accesses() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x200, 0x204), Layer::wrap(vec![0x00, 0xc0, 0x00, 0x40])));
+
+        let var = |n: &'static str| Lvalue::Variable { name: Cow::Borrowed(n), size: 32, subscript: None };
+        let mne = |start: u64, stmts: Vec<Statement>| Mnemonic::new(start..start + 4, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let bb = BasicBlock::from_vec(
+            vec![
+                mne(0x100, vec![Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 32, Rvalue::new_u32(0x200)), assignee: var("r0") }]),
+                mne(
+                    0x104,
+                    vec![
+                        Statement { op: Operation::Move(Rvalue::new_u32(0x101)), assignee: var("r1") },
+                        Statement { op: Operation::Add(var("r0").into(), Rvalue::new_u32(0x30)), assignee: var("t") },
+                        Statement { op: Operation::Store(Cow::Borrowed("RAM"), Endianess::Little, 32, var("t").into(), var("r1").into()), assignee: Lvalue::Undefined },
+                    ]
+                ),
+                mne(
+                    0x108,
+                    vec![
+                        Statement { op: Operation::Add(var("r0").into(), Rvalue::new_u32(0x34)), assignee: var("t") },
+                        Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 32, var("t").into()), assignee: var("r2") },
+                    ]
+                ),
+            ]
+        );
+        let mut proj = Project::new("test".to_string(), reg);
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(bb));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        let mut prog = Program::new("prog");
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        prog.insert(func);
+        proj.code.push(prog);
+        proj.annotations.set_auto_comment(Location::Address(0x104), "\"hello\"".to_string());
+
+        let map = toml("[UART0]\nbase = 0x4000C000\nCR = 0x30\nCR.UARTEN = 0\nCR.BAUD = \"15:8\"\nDR = 0x34").unwrap();
+
+        assert_eq!(annotate(&mut proj, &map), 2);
+        assert_eq!(annotate(&mut proj, &map), 2);
+        assert_eq!(proj.annotations.auto_comment(&Location::Address(0x100)), None);
+        assert_eq!(proj.annotations.auto_comment(&Location::Address(0x104)), Some("\"hello\", UART0.CR = UARTEN|BAUD=0x1"));
+        assert_eq!(proj.annotations.auto_comment(&Location::Address(0x108)), Some("UART0.DR"));
+    }
+}
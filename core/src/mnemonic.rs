@@ -30,6 +30,7 @@
 //! value. Other formattings are `{d:<region>}` for data pointer into <region> and `{s}` for
 //! signed values.
 
+use PointerKind;
 use Result;
 
 use Rvalue;
@@ -149,6 +150,10 @@ pub struct Mnemonic {
     pub instructions: Vec<Statement>,
     /// Describes how the operands need to be printed
     pub format_string: Vec<MnemonicFormatToken>,
+    /// What each operand points to, in the order of `operands`. Empty until
+    /// `pointer::classify_operands` ran.
+    #[serde(default)]
+    pub pointers: Vec<Option<PointerKind>>,
 }
 
 impl Mnemonic {
@@ -165,6 +170,22 @@ impl Mnemonic {
                 operands: ops.cloned().collect(),
                 instructions: instr.cloned().collect(),
                 format_string: MnemonicFormatToken::parse(fmt.chars())?,
+                pointers: vec![],
+            }
+        )
+    }
+
+    /// Create a new mnemonic `code` from operands and statements the caller doesn't need
+    /// anymore. Same as `new` but moves the vectors instead of copying them.
+    pub fn from_parts(a: Range<u64>, code: String, fmt: &str, ops: Vec<Rvalue>, instr: Vec<Statement>) -> Result<Mnemonic> {
+        Ok(
+            Mnemonic {
+                area: Bound::new(a.start, a.end),
+                opcode: code,
+                operands: ops,
+                instructions: instr,
+                format_string: MnemonicFormatToken::parse(fmt.chars())?,
+                pointers: vec![],
             }
         )
     }
@@ -183,6 +204,7 @@ impl Mnemonic {
             operands: vec![],
             instructions: vec![],
             format_string: vec![],
+            pointers: vec![],
         }
     }
 }
@@ -299,5 +321,9 @@ mod tests {
         assert_eq!(mne1.opcode, "op1");
         assert_eq!(mne1.operands, ops1);
         assert_eq!(mne1.instructions, i1);
+
+        let mne2 = Mnemonic::from_parts(0..10, "op1".to_string(), "{s} nog", ops1, i1).ok().unwrap();
+
+        assert_eq!(mne2, mne1);
     }
 }
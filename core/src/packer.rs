@@ -0,0 +1,365 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Packer detection and section anomalies.
+//!
+//! Packed executables carry their real code compressed or encrypted and unpack it at run time.
+//! Disassembling them shows little more than the unpacking stub. [`scan`] looks at the sections
+//! recorded by the loader and returns a [`Finding`] for everything that hints at a packer:
+//!
+//! - Section names used by well known packers, like `UPX0` or `.aspack`.
+//! - The `UPX!` magic near the start of a section.
+//! - Unpacking stubs at the entry point.
+//! - Sections whose contents have a Shannon entropy above [`ENTROPY_THRESHOLD`] bits per byte.
+//!   Compressed and encrypted data comes close to 8, code rarely exceeds 6.5.
+//! - Sections that are both writable and executable, or executable without any contents in the
+//!   file.
+//! - An entry point outside of all executable sections.
+//!
+//! The ELF and PE loaders run the scan and store the result in `Project::findings`.
+//!
+//! [`scan`]: fn.scan.html
+//! [`Finding`]: struct.Finding.html
+//! [`ENTROPY_THRESHOLD`]: constant.ENTROPY_THRESHOLD.html
+
+use {Bound, Project};
+use std::fmt;
+
+/// Minimal entropy in bits per byte of sections reported as compressed or encrypted.
+pub const ENTROPY_THRESHOLD: f64 = 7.2;
+
+/// Minimal number of bytes in a section to compute its entropy.
+pub const ENTROPY_MIN_SIZE: usize = 512;
+
+/// Number of bytes at the start of each section searched for packer magic.
+const MAGIC_WINDOW: u64 = 0x400;
+
+/// Section of an executable file as seen by the loader.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Section {
+    /// Section name, `LOAD<n>` for ELF segments
+    pub name: String,
+    /// Addresses covered in memory
+    pub area: Bound,
+    /// Number of bytes read from the file, the rest is zero filled
+    pub file_size: u64,
+    /// Position of the bytes read in the file
+    #[serde(default)]
+    pub file_offset: u64,
+    /// Readable at run time
+    pub read: bool,
+    /// Writable at run time
+    pub write: bool,
+    /// Executable at run time
+    pub execute: bool,
+}
+
+/// Kind of anomaly found.
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum Anomaly {
+    /// Section names, magic or entry point code of the named packer
+    Packer(String),
+    /// Section contents look compressed or encrypted. Entropy in bits per byte.
+    HighEntropy(f64),
+    /// Section is both writable and executable
+    WritableExecutable,
+    /// Executable section that has no contents in the file and is filled at run time
+    UninitializedExecutable,
+    /// Entry point is not inside an executable section
+    EntryOutsideCode,
+}
+
+/// Single anomaly found by `scan`.
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct Finding {
+    /// What was found
+    pub anomaly: Anomaly,
+    /// Name of the section concerned
+    pub section: Option<String>,
+    /// Address the finding refers to
+    pub address: Option<u64>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.anomaly {
+            Anomaly::Packer(ref packer) => write!(f, "packed with {}", packer)?,
+            Anomaly::HighEntropy(entropy) => write!(f, "compressed or encrypted data ({:.2} bits per byte)", entropy)?,
+            Anomaly::WritableExecutable => write!(f, "writable and executable")?,
+            Anomaly::UninitializedExecutable => write!(f, "executable but empty in the file")?,
+            Anomaly::EntryOutsideCode => write!(f, "entry point outside of executable sections")?,
+        }
+        if let Some(ref section) = self.section {
+            write!(f, " in section {}", section)?;
+        }
+        if let Some(address) = self.address {
+            write!(f, " at {:#x}", address)?;
+        }
+        Ok(())
+    }
+}
+
+/// Section names of packers and protectors.
+const SECTION_NAMES: &'static [(&'static str, &'static str)] = &[
+    ("UPX0", "UPX"),
+    ("UPX1", "UPX"),
+    ("UPX2", "UPX"),
+    (".aspack", "ASPack"),
+    (".adata", "ASPack"),
+    (".MPRESS1", "MPRESS"),
+    (".MPRESS2", "MPRESS"),
+    (".petite", "Petite"),
+    (".nsp0", "NsPack"),
+    (".nsp1", "NsPack"),
+    (".nsp2", "NsPack"),
+    ("PEC2", "PECompact"),
+    ("PEC2TO", "PECompact"),
+    ("pec1", "PECompact"),
+    ("pec2", "PECompact"),
+    (".packed", "RLPack"),
+    (".RLPack", "RLPack"),
+    ("FSG!", "FSG"),
+    ("MEW", "MEW"),
+    (".themida", "Themida"),
+    (".winlice", "WinLicense"),
+    (".vmp0", "VMProtect"),
+    (".vmp1", "VMProtect"),
+    (".vmp2", "VMProtect"),
+    (".enigma1", "Enigma"),
+    (".enigma2", "Enigma"),
+    (".yP", "Y0da Protector"),
+];
+
+/// Unpacking stubs at the entry point. Bytes in hex, `??` matches any byte.
+const ENTRY_STUBS: &'static [(&'static str, &'static str)] = &[
+    // pushad; mov esi, imm32; lea edi, [esi + disp32]
+    ("UPX", "60 BE ?? ?? ?? ?? 8D BE"),
+    // push rbx; push rsi; push rdi; push rbp; lea rsi, [rip + disp32]; lea rdi, [rsi + disp32]
+    ("UPX", "53 56 57 55 48 8D 35 ?? ?? ?? ?? 48 8D BE"),
+    // pushad; call $+8; jmp ...
+    ("ASPack", "60 E8 03 00 00 00 E9 EB"),
+    // pushad; call $+5; pop eax; add eax, imm32
+    ("MPRESS", "60 E8 00 00 00 00 58 05"),
+    // mov eax, imm32; push eax; push dword fs:[0]
+    ("PECompact", "B8 ?? ?? ?? ?? 50 64 FF 35 00 00 00 00"),
+    // mov eax, imm32; pushf; pushad; push eax
+    ("Petite", "B8 ?? ?? ?? ?? 66 9C 60 50"),
+];
+
+/// Looks for packers and suspicious sections in `proj`.
+pub fn scan(proj: &Project) -> Vec<Finding> {
+    let mut ret = vec![];
+    let region = proj.region();
+    let packer = |ret: &mut Vec<Finding>, name: &str, section: Option<&String>, address: Option<u64>| {
+        let known = ret.iter().any(|f| f.anomaly == Anomaly::Packer(name.to_string()));
+
+        if !known {
+            ret.push(Finding { anomaly: Anomaly::Packer(name.to_string()), section: section.cloned(), address: address });
+        }
+    };
+
+    for sec in proj.sections.iter() {
+        if let Some(&(_, name)) = SECTION_NAMES.iter().find(|&&(n, _)| n == sec.name) {
+            packer(&mut ret, name, Some(&sec.name), Some(sec.area.start));
+        }
+
+        let head = contents(proj, &Bound::new(sec.area.start, sec.area.end.min(sec.area.start + MAGIC_WINDOW)));
+        if head.windows(4).any(|w| w == b"UPX!") {
+            packer(&mut ret, "UPX", Some(&sec.name), Some(sec.area.start));
+        }
+    }
+
+    let entry = proj.comments.iter().find(|&(&(ref reg, _), c)| reg == "base" && c == "main").map(|(&(_, addr), _)| addr);
+    if let Some(entry) = entry {
+        let code = region.iter().seek(entry).take(32).take_while(|c| c.is_some()).map(|c| c.unwrap()).collect::<Vec<u8>>();
+        let section = proj.sections.iter().find(|s| s.area.start <= entry && entry < s.area.end);
+
+        for &(name, stub) in ENTRY_STUBS.iter() {
+            if matches(stub, &code) {
+                packer(&mut ret, name, section.map(|s| &s.name), Some(entry));
+            }
+        }
+
+        match section {
+            Some(sec) if sec.execute => {}
+            Some(sec) => {
+                ret.push(Finding { anomaly: Anomaly::EntryOutsideCode, section: Some(sec.name.clone()), address: Some(entry) });
+            }
+            None if !proj.sections.is_empty() => {
+                ret.push(Finding { anomaly: Anomaly::EntryOutsideCode, section: None, address: Some(entry) });
+            }
+            None => {}
+        }
+    }
+
+    for sec in proj.sections.iter() {
+        let finding = |anomaly| Finding { anomaly: anomaly, section: Some(sec.name.clone()), address: Some(sec.area.start) };
+
+        if sec.write && sec.execute {
+            ret.push(finding(Anomaly::WritableExecutable));
+        }
+        if sec.execute && sec.file_size == 0 {
+            ret.push(finding(Anomaly::UninitializedExecutable));
+        }
+
+        let bytes = contents(proj, &sec.area);
+        if bytes.len() >= ENTROPY_MIN_SIZE {
+            let entropy = entropy(&bytes);
+
+            if entropy > ENTROPY_THRESHOLD {
+                ret.push(finding(Anomaly::HighEntropy(entropy)));
+            }
+        }
+    }
+
+    ret
+}
+
+/// Shannon entropy of `bytes` in bits per byte.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+
+    for &b in bytes {
+        counts[b as usize] += 1;
+    }
+
+    let len = bytes.len() as f64;
+    counts.iter().filter(|&&c| c > 0).map(|&c| c as f64 / len).map(|p| -p * p.log2()).sum()
+}
+
+/// Defined bytes of the root region inside `area`.
+fn contents(proj: &Project, area: &Bound) -> Vec<u8> {
+    proj.region().iter().cut(&(area.start..area.end)).filter_map(|c| c).collect()
+}
+
+/// Matches `bytes` against the hex `pattern`.
+fn matches(pattern: &str, bytes: &[u8]) -> bool {
+    let pattern = pattern.split(' ').collect::<Vec<_>>();
+
+    pattern.len() <= bytes.len() &&
+    pattern.iter().zip(bytes.iter()).all(|(p, &b)| *p == "??" || u8::from_str_radix(p, 16).ok() == Some(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Layer, Region};
+
+    fn section(name: &str, start: u64, end: u64, write: bool, execute: bool) -> Section {
+        Section {
+            name: name.to_string(),
+            area: Bound::new(start, end),
+            file_size: end - start,
+            file_offset: 0,
+            read: true,
+            write: write,
+            execute: execute,
+        }
+    }
+
+    fn project(contents: Vec<(u64, Vec<u8>)>, sections: Vec<Section>, entry: u64) -> Project {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1_0000);
+
+        for (addr, bytes) in contents {
+            let len = bytes.len() as u64;
+            assert!(reg.cover(Bound::new(addr, addr + len), Layer::wrap(bytes)));
+        }
+
+        let mut proj = Project::new("test".to_string(), reg);
+        proj.sections = sections;
+        proj.comments.insert(("base".to_string(), entry), "main".to_string());
+        proj
+    }
+
+    #[test]
+    fn upx() {
+        let mut stub = vec![0x60, 0xbe, 0x00, 0x10, 0x40, 0x00, 0x8d, 0xbe, 0x00, 0xf0, 0xff, 0xff];
+        stub.resize(0x100, 0x90);
+        let mut proj = project(
+            vec![(0x2000, stub)],
+            vec![section("UPX0", 0x1000, 0x2000, true, true), section("UPX1", 0x2000, 0x2100, true, true)],
+            0x2000,
+        );
+        proj.sections[0].file_size = 0;
+
+        let findings = scan(&proj);
+        let packers = findings.iter().filter(|f| f.anomaly == Anomaly::Packer("UPX".to_string())).count();
+
+        assert_eq!(packers, 1);
+        assert_eq!(findings.iter().filter(|f| f.anomaly == Anomaly::WritableExecutable).count(), 2);
+        assert!(findings.iter().any(|f| f.anomaly == Anomaly::UninitializedExecutable && f.section == Some("UPX0".to_string())));
+        assert_eq!(format!("{}", findings[0]), "packed with UPX in section UPX0 at 0x1000");
+    }
+
+    #[test]
+    fn entry_stub() {
+        let stub = vec![0x60, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x58, 0x05, 0x12, 0x34, 0x00, 0x00];
+        let proj = project(vec![(0x1000, stub)], vec![section(".text", 0x1000, 0x100c, false, true)], 0x1000);
+
+        assert_eq!(
+            scan(&proj),
+            vec![Finding { anomaly: Anomaly::Packer("MPRESS".to_string()), section: Some(".text".to_string()), address: Some(0x1000) }]
+        );
+    }
+
+    #[test]
+    fn high_entropy() {
+        // xorshift output looks random
+        let mut x = 0x1234_5678u32;
+        let random = (0..4096)
+            .map(
+                |_| {
+                    x ^= x << 13;
+                    x ^= x >> 17;
+                    x ^= x << 5;
+                    x as u8
+                }
+            )
+            .collect::<Vec<u8>>();
+        let code = (0..4096).map(|i| [0x55, 0x48, 0x89, 0xe5, 0xc3][i % 5]).collect::<Vec<u8>>();
+        let proj = project(
+            vec![(0x1000, code), (0x2000, random)],
+            vec![section(".text", 0x1000, 0x2000, false, true), section(".data", 0x2000, 0x3000, true, false)],
+            0x1000,
+        );
+        let findings = scan(&proj);
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].section, Some(".data".to_string()));
+        match findings[0].anomaly {
+            Anomaly::HighEntropy(e) => assert!(e > 7.9),
+            ref a => panic!("unexpected {:?}", a),
+        }
+    }
+
+    #[test]
+    fn entry_outside_code() {
+        let proj = project(vec![(0x1000, vec![0xc3; 16])], vec![section(".data", 0x1000, 0x1010, true, false)], 0x1004);
+
+        assert_eq!(
+            scan(&proj),
+            vec![Finding { anomaly: Anomaly::EntryOutsideCode, section: Some(".data".to_string()), address: Some(0x1004) }]
+        );
+    }
+
+    #[test]
+    fn entropy_bounds() {
+        assert_eq!(entropy(&[0u8; 64]), 0.);
+        assert_eq!(entropy(&(0..256).map(|x| x as u8).collect::<Vec<_>>()), 8.);
+    }
+}
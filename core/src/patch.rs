@@ -0,0 +1,309 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Binary patches.
+//!
+//! [`apply`] overwrites bytes of the root region and records the change as a named [`Patch`] in
+//! `Project::patches`, together with the bytes replaced. [`apply_instructions`] does the same
+//! after checking that the new bytes decode to complete instructions of the given architecture.
+//! Functions touched by patches are updated by `panopticon_analysis::reanalyze`.
+//!
+//! Patches are written back with [`export_file`], which copies the original executable with the
+//! patched bytes and updates the PE checksum if the file has one. ELF files have no checksum. A
+//! patch that other tools can apply is written by [`export_ips`]. Both need the file offsets of
+//! the sections recorded by the loader; projects without sections are assumed to map the file
+//! at address 0.
+//!
+//! [`apply`]: fn.apply.html
+//! [`apply_instructions`]: fn.apply_instructions.html
+//! [`export_file`]: fn.export_file.html
+//! [`export_ips`]: fn.export_ips.html
+//! [`Patch`]: struct.Patch.html
+
+use {Architecture, Mnemonic, Project, Result};
+use panopticon_graph_algos::MutableGraphTrait;
+use std::collections::BTreeMap;
+
+/// Largest file offset IPS patches can address.
+pub const IPS_MAX_OFFSET: u64 = 0xff_ffff;
+
+/// Change to the bytes of the root region.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Patch {
+    /// Name given by the user
+    pub name: String,
+    /// First address overwritten
+    pub address: u64,
+    /// Bytes before the patch
+    pub original: Vec<Option<u8>>,
+    /// Bytes after the patch
+    pub bytes: Vec<u8>,
+    /// Opcodes the new bytes decode to, if applied with `apply_instructions`
+    pub opcodes: Vec<String>,
+}
+
+/// Overwrites the bytes starting at `address` with `bytes` and records the patch as `name`.
+pub fn apply(proj: &mut Project, name: &str, address: u64, bytes: Vec<u8>) -> Result<()> {
+    record(proj, name, address, bytes, vec![])
+}
+
+/// Overwrites the bytes starting at `address` with the machine code in `bytes` and records the
+/// patch as `name`. Fails without changing anything if the code doesn't decode or its last
+/// instruction doesn't end with the patch. Returns the new mnemonics.
+pub fn apply_instructions<A: Architecture>(proj: &mut Project, name: &str, address: u64, bytes: Vec<u8>, config: A::Configuration) -> Result<Vec<Mnemonic>> {
+    let end = address + bytes.len() as u64;
+    let mut mnemonics = vec![];
+    let mut region = proj.region().clone();
+
+    if !region.patch(address, bytes.clone()) {
+        return Err(format!("patch {} doesn't fit at {:#x}", name, address).into());
+    }
+
+    let mut addr = address;
+    let mut config = config;
+
+    while addr < end {
+        let m = A::decode(&region, addr, &config)?;
+        let next = m.mnemonics.iter().map(|mne| mne.area.end).max().unwrap_or(addr);
+
+        if next <= addr {
+            return Err(format!("no instruction at {:#x}", addr).into());
+        }
+
+        mnemonics.extend(m.mnemonics.into_iter());
+        config = m.configuration;
+        addr = next;
+    }
+
+    if addr != end {
+        return Err(format!("patch {} ends inside the instruction at {:#x}", name, mnemonics.last().map(|m| m.area.start).unwrap_or(address)).into());
+    }
+
+    let opcodes = mnemonics.iter().map(|m| m.opcode.clone()).collect();
+    record(proj, name, address, bytes, opcodes)?;
+    Ok(mnemonics)
+}
+
+fn record(proj: &mut Project, name: &str, address: u64, bytes: Vec<u8>, opcodes: Vec<String>) -> Result<()> {
+    let original = proj.region().iter().seek(address).take(bytes.len()).collect::<Vec<_>>();
+    let root = proj.data.root;
+    let ok = match proj.data.dependencies.vertex_label_mut(root) {
+        Some(region) => region.patch(address, bytes.clone()),
+        None => false,
+    };
+
+    if !ok {
+        return Err(format!("patch {} doesn't fit at {:#x}", name, address).into());
+    }
+
+    proj.patches.push(Patch { name: name.to_string(), address: address, original: original, bytes: bytes, opcodes: opcodes });
+    Ok(())
+}
+
+/// Returns all bytes changed by patches, by address, with their values before the first and
+/// after the last patch. Bytes patched back to their original value are left out.
+pub fn diff(proj: &Project) -> BTreeMap<u64, (Option<u8>, u8)> {
+    let mut ret = BTreeMap::new();
+
+    for patch in proj.patches.iter() {
+        for (i, (&old, &new)) in patch.original.iter().zip(patch.bytes.iter()).enumerate() {
+            ret.entry(patch.address + i as u64).or_insert((old, new)).1 = new;
+        }
+    }
+
+    ret.into_iter().filter(|&(_, (old, new))| old != Some(new)).collect()
+}
+
+/// Returns the position of the byte at `address` in the file `proj` was loaded from.
+pub fn file_offset(proj: &Project, address: u64) -> Option<u64> {
+    if proj.sections.is_empty() {
+        return Some(address);
+    }
+
+    proj.sections
+        .iter()
+        .find(|s| s.area.start <= address && address - s.area.start < s.file_size)
+        .map(|s| s.file_offset + address - s.area.start)
+}
+
+/// Returns a copy of `original`, the file `proj` was loaded from, with all patches applied.
+pub fn export_file(proj: &Project, original: &[u8]) -> Result<Vec<u8>> {
+    let mut ret = original.to_vec();
+
+    for (addr, (_, byte)) in diff(proj) {
+        match file_offset(proj, addr) {
+            Some(off) if off < ret.len() as u64 => ret[off as usize] = byte,
+            _ => return Err(format!("patched byte at {:#x} isn't read from the file", addr).into()),
+        }
+    }
+
+    if let Some(off) = pe_checksum_offset(&ret) {
+        if ret[off..off + 4] != [0, 0, 0, 0] {
+            let sum = pe_checksum(&ret, off);
+
+            for i in 0..4 {
+                ret[off + i] = (sum >> (8 * i)) as u8;
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Returns the patches of `proj` in IPS format, relative to the file it was loaded from.
+pub fn export_ips(proj: &Project) -> Result<Vec<u8>> {
+    let mut records = Vec::<(u64, Vec<u8>)>::new();
+
+    for (addr, (_, byte)) in diff(proj) {
+        let off = match file_offset(proj, addr) {
+            Some(off) if off <= IPS_MAX_OFFSET => off,
+            Some(off) => return Err(format!("file offset {:#x} is too large for IPS", off).into()),
+            None => return Err(format!("patched byte at {:#x} isn't read from the file", addr).into()),
+        };
+
+        match records.last_mut() {
+            Some(&mut (start, ref mut data)) if start + data.len() as u64 == off && data.len() < 0xffff => {
+                data.push(byte);
+                continue;
+            }
+            _ => {}
+        }
+
+        // an offset of 0x454f46 would be read as the "EOF" marker
+        if off == 0x45_4f46 {
+            let prev = if addr > 0 { proj.region().iter().seek(addr - 1).next().and_then(|b| b) } else { None };
+
+            match prev {
+                Some(b) => records.push((off - 1, vec![b, byte])),
+                _ => return Err("can't patch the byte at file offset 0x454f46".into()),
+            }
+        } else {
+            records.push((off, vec![byte]));
+        }
+    }
+
+    let mut ret = b"PATCH".to_vec();
+
+    for (off, data) in records {
+        ret.extend_from_slice(&[(off >> 16) as u8, (off >> 8) as u8, off as u8]);
+        ret.extend_from_slice(&[(data.len() >> 8) as u8, data.len() as u8]);
+        ret.extend(data);
+    }
+
+    ret.extend_from_slice(b"EOF");
+    Ok(ret)
+}
+
+/// Position of the `CheckSum` field of a PE file.
+fn pe_checksum_offset(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 0x40 || &bytes[0..2] != b"MZ" {
+        return None;
+    }
+
+    let pe = bytes[0x3c] as usize | (bytes[0x3d] as usize) << 8 | (bytes[0x3e] as usize) << 16 | (bytes[0x3f] as usize) << 24;
+
+    // PE signature, COFF header, then the checksum at offset 64 of the optional header
+    if pe.checked_add(24 + 64 + 4).map(|e| e <= bytes.len()).unwrap_or(false) && &bytes[pe..pe + 4] == b"PE\0\0" {
+        Some(pe + 24 + 64)
+    } else {
+        None
+    }
+}
+
+/// PE image checksum of `bytes`, skipping the checksum itself at `field`.
+fn pe_checksum(bytes: &[u8], field: usize) -> u32 {
+    let mut sum = 0u64;
+
+    for (i, word) in bytes.chunks(2).enumerate() {
+        if i * 2 == field || i * 2 == field + 2 {
+            continue;
+        }
+
+        sum += word[0] as u64 | (*word.get(1).unwrap_or(&0) as u64) << 8;
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    sum = (sum & 0xffff) + (sum >> 16);
+    (sum as u32).wrapping_add(bytes.len() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Bound, Region, Section};
+
+    fn project() -> Project {
+        let mut proj = Project::new("test".to_string(), Region::wrap("RAM".to_string(), vec![0x90; 0x2000]));
+
+        proj.sections.push(
+            Section {
+                name: ".text".to_string(),
+                area: Bound::new(0x1000, 0x1100),
+                file_size: 0x80,
+                file_offset: 0x400,
+                read: true,
+                write: false,
+                execute: true,
+            }
+        );
+        proj
+    }
+
+    #[test]
+    fn apply_and_export() {
+        let mut proj = project();
+
+        assert!(apply(&mut proj, "ret early", 0x1010, vec![0xc3, 0x90]).is_ok());
+        assert!(apply(&mut proj, "int3", 0x1012, vec![0xcc]).is_ok());
+        assert!(apply(&mut proj, "too far", 0x1ffe, vec![0, 0, 0]).is_err());
+
+        assert_eq!(proj.patches.len(), 2);
+        assert_eq!(proj.patches[0].original, vec![Some(0x90), Some(0x90)]);
+        assert_eq!(proj.region().iter().seek(0x1010).take(3).collect::<Vec<_>>(), vec![Some(0xc3), Some(0x90), Some(0xcc)]);
+        assert_eq!(proj.region().changes().len(), 2);
+
+        let d = diff(&proj);
+        assert_eq!(d.keys().cloned().collect::<Vec<_>>(), vec![0x1010, 0x1012]);
+
+        assert_eq!(file_offset(&proj, 0x1010), Some(0x410));
+        assert_eq!(file_offset(&proj, 0x1090), None);
+
+        let file = export_file(&proj, &vec![0x90; 0x500]).unwrap();
+        assert_eq!(&file[0x40f..0x414], &[0x90, 0xc3, 0x90, 0xcc, 0x90]);
+
+        let ips = export_ips(&proj).unwrap();
+        assert_eq!(ips, b"PATCH\x00\x04\x10\x00\x01\xc3\x00\x04\x12\x00\x01\xccEOF".to_vec());
+
+        assert!(apply(&mut proj, "bss", 0x10a0, vec![1]).is_ok());
+        assert!(export_file(&proj, &vec![0x90; 0x500]).is_err());
+    }
+
+    #[test]
+    fn checksum() {
+        let mut file = vec![0u8; 0x200];
+
+        file[0..2].copy_from_slice(b"MZ");
+        file[0x3c] = 0x80;
+        file[0x80..0x84].copy_from_slice(b"PE\0\0");
+        file[0x80 + 24 + 64] = 1;
+
+        assert_eq!(pe_checksum_offset(&file), Some(0x80 + 24 + 64));
+        // "MZ" + "PE" + 0x80 + length
+        assert_eq!(pe_checksum(&file, 0x80 + 24 + 64), 0x5a4d + 0x4550 + 0x80 + 0x200);
+    }
+}
@@ -0,0 +1,1078 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Program database (PDB) reader.
+//!
+//! Microsoft's toolchain stores symbols and types of PE binaries in a separate `.pdb` file. The
+//! file is a MSF container, a simple file system of numbered streams. This module reads the
+//! streams needed to name functions and global variables and to recover the layout of structures:
+//!
+//! - The PDB information stream (1) holds the GUID matching the CodeView record of the binary.
+//! - The type stream (2) holds type records. Structures, classes, unions, enums, pointers, arrays
+//!   and procedure types are decoded into [`Type`](enum.Type.html), everything else is skipped.
+//! - The debug information stream (3) names the global symbol stream, the per-module symbol
+//!   streams and the stream holding a copy of the binary's section headers, which is needed to
+//!   convert the `segment:offset` addresses of symbols into RVAs.
+//!
+//! The loader looks for a PDB next to the binary when opening a PE file and uses
+//! [`Pdb::apply`](struct.Pdb.html#method.apply) to name the functions found and give them the
+//! signatures of their procedure types.
+
+use {ControlFlowTarget, DataType, NameSource, Project, Result, Signature};
+use byteorder::{ByteOrder, LittleEndian};
+use panopticon_graph_algos::GraphTrait;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Magic at the start of every MSF 7.0 container.
+pub const MAGIC: &'static [u8] = b"Microsoft C/C++ MSF 7.00\r\n\x1aDS\0\0\0";
+
+const PDB_STREAM: usize = 1;
+const TPI_STREAM: usize = 2;
+const DBI_STREAM: usize = 3;
+
+// Type references followed by `Pdb::data_type` before giving up on cyclic records
+const MAX_DEPTH: usize = 32;
+
+// Symbol record kinds
+const S_LDATA32: u16 = 0x110c;
+const S_GDATA32: u16 = 0x110d;
+const S_PUB32: u16 = 0x110e;
+const S_LPROC32: u16 = 0x110f;
+const S_GPROC32: u16 = 0x1110;
+const S_LPROC32_ID: u16 = 0x1146;
+const S_GPROC32_ID: u16 = 0x1147;
+
+// Type record kinds
+const LF_MODIFIER: u16 = 0x1001;
+const LF_POINTER: u16 = 0x1002;
+const LF_PROCEDURE: u16 = 0x1008;
+const LF_MFUNCTION: u16 = 0x1009;
+const LF_ARGLIST: u16 = 0x1201;
+const LF_FIELDLIST: u16 = 0x1203;
+const LF_ENUMERATE: u16 = 0x1502;
+const LF_ARRAY: u16 = 0x1503;
+const LF_CLASS: u16 = 0x1504;
+const LF_STRUCTURE: u16 = 0x1505;
+const LF_UNION: u16 = 0x1506;
+const LF_ENUM: u16 = 0x1507;
+const LF_MEMBER: u16 = 0x150d;
+
+// Forward declaration bit of the structure property field
+const FORWARD_REF: u16 = 0x80;
+
+/// Kind of a [`Symbol`](struct.Symbol.html).
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum SymbolKind {
+    /// Function entry point
+    Function,
+    /// Global or static variable
+    Data,
+}
+
+/// Named address from the PDB.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Symbol {
+    /// Undecorated name for functions and variables with debug information, the decorated linker
+    /// name for public symbols.
+    pub name: String,
+    /// Address relative to the image base
+    pub rva: u64,
+    /// Size in bytes, if known
+    pub size: Option<u64>,
+    /// Function or variable
+    pub kind: SymbolKind,
+    /// Index of the symbol's type in `Pdb::types`, if known
+    pub type_index: Option<u32>,
+}
+
+/// Aggregate type kinds.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Aggregate {
+    /// `struct`
+    Struct,
+    /// `class`
+    Class,
+    /// `union`
+    Union,
+}
+
+/// Member of a structure, class or union.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Field {
+    /// Member name
+    pub name: String,
+    /// Offset from the start of the aggregate in bytes
+    pub offset: u64,
+    /// Type index of the member
+    pub type_index: u32,
+}
+
+/// Decoded type record. Types refer to each other by type index. Indices below 0x1000 denote
+/// built-in types like `int`, see [`Pdb::type_name`](struct.Pdb.html#method.type_name).
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Type {
+    /// Pointer to a type
+    Pointer(u32),
+    /// `const` and/or `volatile` qualified type
+    Modifier(u32),
+    /// Fixed size array
+    Array {
+        /// Element type index
+        element: u32,
+        /// Size of the whole array in bytes
+        size: u64,
+    },
+    /// Function type
+    Procedure {
+        /// Return type index
+        ret: u32,
+        /// Parameter type indices
+        args: Vec<u32>,
+    },
+    /// Structure, class or union definition
+    Aggregate {
+        /// Keyword
+        kind: Aggregate,
+        /// Type name
+        name: String,
+        /// Size in bytes
+        size: u64,
+        /// Data members
+        fields: Vec<Field>,
+    },
+    /// Enumeration
+    Enum {
+        /// Type name
+        name: String,
+        /// Underlying integer type index
+        underlying: u32,
+        /// Enumerators and their values
+        values: Vec<(String, i64)>,
+    },
+}
+
+/// Symbols and types of a PE binary, read from its PDB file.
+#[derive(Clone,Debug)]
+pub struct Pdb {
+    /// GUID identifying the binary the PDB belongs to
+    pub guid: [u8; 16],
+    /// Number of times the PDB was written to
+    pub age: u32,
+    /// Functions, variables and public symbols, sorted by RVA
+    pub symbols: Vec<Symbol>,
+    /// Decoded type records, by type index
+    pub types: BTreeMap<u32, Type>,
+}
+
+// Bounds checked little endian reader.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes: bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err("PDB record truncated".into());
+        }
+
+        let ret = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(ret)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    fn cstr(&mut self) -> Result<String> {
+        let len = match self.bytes.get(self.pos..).and_then(|b| b.iter().position(|&b| b == 0)) {
+            Some(len) => len,
+            None => return Err("unterminated string in PDB".into()),
+        };
+        let ret = String::from_utf8_lossy(self.take(len)?).to_string();
+
+        self.pos += 1;
+        Ok(ret)
+    }
+
+    // Variable length integer used for sizes, offsets and enum values.
+    fn numeric(&mut self) -> Result<i64> {
+        let leaf = self.u16()?;
+
+        match leaf {
+            0...0x7fff => Ok(leaf as i64),
+            0x8000 => Ok(self.u8()? as i8 as i64),
+            0x8001 => Ok(self.u16()? as i16 as i64),
+            0x8002 => Ok(self.u16()? as i64),
+            0x8003 => Ok(self.u32()? as i32 as i64),
+            0x8004 => Ok(self.u32()? as i64),
+            0x8009 | 0x800a => Ok(self.u64()? as i64),
+            _ => Err(format!("unsupported numeric leaf {:#x} in PDB", leaf).into()),
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+}
+
+// Reads all streams of a MSF container.
+fn streams(bytes: &[u8]) -> Result<Vec<Vec<u8>>> {
+    if !bytes.starts_with(MAGIC) {
+        return Err("not a MSF 7.0 file".into());
+    }
+
+    let mut hdr = Reader::new(bytes);
+    hdr.pos = MAGIC.len();
+    let block_size = hdr.u32()? as usize;
+    let _free_block_map = hdr.u32()?;
+    let num_blocks = hdr.u32()? as usize;
+    let dir_size = hdr.u32()? as usize;
+    let _ = hdr.u32()?;
+    let block_map = hdr.u32()? as usize;
+
+    match block_size {
+        512 | 1024 | 2048 | 4096 => {}
+        _ => return Err(format!("invalid MSF block size {}", block_size).into()),
+    }
+    if num_blocks * block_size > bytes.len() {
+        return Err("MSF file truncated".into());
+    }
+
+    let block = |idx: usize| -> Result<&[u8]> {
+        if idx >= num_blocks {
+            Err(format!("MSF block {} out of range", idx).into())
+        } else {
+            Ok(&bytes[idx * block_size..(idx + 1) * block_size])
+        }
+    };
+    let read = |blocks: &[usize], size: usize| -> Result<Vec<u8>> {
+        let mut ret = Vec::with_capacity(size);
+
+        for &b in blocks.iter() {
+            ret.extend_from_slice(block(b)?);
+        }
+        if ret.len() < size {
+            return Err("MSF stream truncated".into());
+        }
+        ret.truncate(size);
+        Ok(ret)
+    };
+    let num_blocks_of = |size: usize| (size + block_size - 1) / block_size;
+
+    let dir_blocks = {
+        let mut rd = Reader::new(block(block_map)?);
+        (0..num_blocks_of(dir_size)).map(|_| rd.u32().map(|x| x as usize)).collect::<Result<Vec<_>>>()?
+    };
+    let dir = read(&dir_blocks, dir_size)?;
+    let mut rd = Reader::new(&dir);
+    let num_streams = rd.u32()? as usize;
+    let sizes = (0..num_streams).map(|_| rd.u32()).collect::<Result<Vec<_>>>()?;
+    let mut ret = Vec::with_capacity(num_streams);
+
+    for &size in sizes.iter() {
+        // Deleted streams have size 0xffffffff
+        let size = if size == 0xffff_ffff { 0 } else { size as usize };
+        let blocks = (0..num_blocks_of(size)).map(|_| rd.u32().map(|x| x as usize)).collect::<Result<Vec<_>>>()?;
+
+        ret.push(read(&blocks, size)?);
+    }
+
+    Ok(ret)
+}
+
+// Record stream of length prefixed records. Calls `f` with the kind and the contents of each.
+fn records<F: FnMut(u16, &mut Reader) -> Result<()>>(bytes: &[u8], mut f: F) -> Result<()> {
+    let mut rd = Reader::new(bytes);
+
+    while rd.pos + 4 <= bytes.len() {
+        let len = rd.u16()? as usize;
+        if len < 2 {
+            break;
+        }
+        let rec = rd.take(len)?;
+        let mut rec = Reader::new(rec);
+        let kind = rec.u16()?;
+
+        f(kind, &mut rec)?;
+    }
+
+    Ok(())
+}
+
+// Type records as stored before resolving argument and field lists.
+enum Raw {
+    Done(Type),
+    Procedure(u32, u32),
+    Aggregate(Aggregate, String, u64, u32),
+    Enum(String, u32, u32),
+    Arguments(Vec<u32>),
+    Fields(Vec<Field>, Vec<(String, i64)>),
+}
+
+fn parse_type(kind: u16, rd: &mut Reader) -> Result<Option<Raw>> {
+    Ok(
+        Some(
+            match kind {
+                LF_MODIFIER => Raw::Done(Type::Modifier(rd.u32()?)),
+                LF_POINTER => Raw::Done(Type::Pointer(rd.u32()?)),
+                LF_ARRAY => {
+                    let element = rd.u32()?;
+                    let _index = rd.u32()?;
+                    let size = rd.numeric()? as u64;
+
+                    Raw::Done(Type::Array { element: element, size: size })
+                }
+                LF_PROCEDURE => {
+                    let ret = rd.u32()?;
+                    let _conv = rd.u16()?;
+                    let _count = rd.u16()?;
+
+                    Raw::Procedure(ret, rd.u32()?)
+                }
+                LF_MFUNCTION => {
+                    let ret = rd.u32()?;
+                    let _class = rd.u32()?;
+                    let _this = rd.u32()?;
+                    let _conv = rd.u16()?;
+                    let _count = rd.u16()?;
+
+                    Raw::Procedure(ret, rd.u32()?)
+                }
+                LF_ARGLIST => {
+                    let count = rd.u32()?;
+                    Raw::Arguments((0..count).map(|_| rd.u32()).collect::<Result<Vec<_>>>()?)
+                }
+                LF_CLASS | LF_STRUCTURE | LF_UNION => {
+                    let _count = rd.u16()?;
+                    let property = rd.u16()?;
+                    let fields = rd.u32()?;
+                    let agg = match kind {
+                        LF_CLASS => Aggregate::Class,
+                        LF_STRUCTURE => Aggregate::Struct,
+                        _ => Aggregate::Union,
+                    };
+
+                    if kind != LF_UNION {
+                        let _derived = rd.u32()?;
+                        let _vshape = rd.u32()?;
+                    }
+
+                    let size = rd.numeric()? as u64;
+                    let name = rd.cstr()?;
+                    let fields = if property & FORWARD_REF != 0 { 0 } else { fields };
+
+                    Raw::Aggregate(agg, name, size, fields)
+                }
+                LF_ENUM => {
+                    let _count = rd.u16()?;
+                    let _property = rd.u16()?;
+                    let underlying = rd.u32()?;
+                    let fields = rd.u32()?;
+
+                    Raw::Enum(rd.cstr()?, underlying, fields)
+                }
+                LF_FIELDLIST => {
+                    let (fields, values) = parse_fields(rd)?;
+                    Raw::Fields(fields, values)
+                }
+                _ => return Ok(None),
+            }
+        )
+    )
+}
+
+// Members and enumerators of a field list. Stops at the first unsupported member kind, their
+// length isn't known.
+fn parse_fields(rd: &mut Reader) -> Result<(Vec<Field>, Vec<(String, i64)>)> {
+    let mut fields = vec![];
+    let mut values = vec![];
+
+    while !rd.at_end() {
+        // Members are padded with 0xf1, 0xf2, ... to a multiple of four bytes
+        if rd.bytes[rd.pos] >= 0xf0 {
+            rd.pos += 1;
+            continue;
+        }
+
+        match rd.u16()? {
+            LF_MEMBER => {
+                let _attr = rd.u16()?;
+                let ty = rd.u32()?;
+                let offset = rd.numeric()? as u64;
+
+                fields.push(Field { name: rd.cstr()?, offset: offset, type_index: ty });
+            }
+            LF_ENUMERATE => {
+                let _attr = rd.u16()?;
+                let value = rd.numeric()?;
+
+                values.push((rd.cstr()?, value));
+            }
+            _ => break,
+        }
+    }
+
+    Ok((fields, values))
+}
+
+fn parse_types(tpi: &[u8]) -> Result<BTreeMap<u32, Type>> {
+    if tpi.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    let mut hdr = Reader::new(tpi);
+    let _version = hdr.u32()?;
+    let header_size = hdr.u32()? as usize;
+    let mut index = hdr.u32()?;
+    let _end = hdr.u32()?;
+    let record_bytes = hdr.u32()? as usize;
+    let mut raw = HashMap::new();
+
+    if header_size + record_bytes > tpi.len() {
+        return Err("PDB type stream truncated".into());
+    }
+
+    records(
+        &tpi[header_size..header_size + record_bytes], |kind, rd| {
+            if let Some(r) = parse_type(kind, rd)? {
+                raw.insert(index, r);
+            }
+            index += 1;
+            Ok(())
+        }
+    )?;
+
+    let mut ret = BTreeMap::new();
+
+    for (&idx, r) in raw.iter() {
+        let ty = match r {
+            &Raw::Done(ref ty) => ty.clone(),
+            &Raw::Procedure(ret, args) => {
+                let args = match raw.get(&args) {
+                    Some(&Raw::Arguments(ref a)) => a.clone(),
+                    _ => vec![],
+                };
+
+                Type::Procedure { ret: ret, args: args }
+            }
+            &Raw::Aggregate(kind, ref name, size, fields) => {
+                let fields = match raw.get(&fields) {
+                    Some(&Raw::Fields(ref f, _)) => f.clone(),
+                    _ => vec![],
+                };
+
+                Type::Aggregate { kind: kind, name: name.clone(), size: size, fields: fields }
+            }
+            &Raw::Enum(ref name, underlying, fields) => {
+                let values = match raw.get(&fields) {
+                    Some(&Raw::Fields(_, ref v)) => v.clone(),
+                    _ => vec![],
+                };
+
+                Type::Enum { name: name.clone(), underlying: underlying, values: values }
+            }
+            &Raw::Arguments(_) | &Raw::Fields(_, _) => continue,
+        };
+
+        ret.insert(idx, ty);
+    }
+
+    Ok(ret)
+}
+
+// Name of a built-in type index.
+fn primitive_name(index: u32) -> String {
+    let base = match index & 0xff {
+        0x03 => "void",
+        0x10 => "signed char",
+        0x20 => "unsigned char",
+        0x68 => "int8_t",
+        0x69 => "uint8_t",
+        0x70 => "char",
+        0x71 => "wchar_t",
+        0x7a => "char16_t",
+        0x7b => "char32_t",
+        0x7c => "char8_t",
+        0x11 | 0x72 => "short",
+        0x21 | 0x73 => "unsigned short",
+        0x74 => "int",
+        0x75 => "unsigned int",
+        0x12 => "long",
+        0x22 => "unsigned long",
+        0x13 | 0x76 => "__int64",
+        0x23 | 0x77 => "unsigned __int64",
+        0x30 => "bool",
+        0x40 => "float",
+        0x41 => "double",
+        0x42 => "long double",
+        _ => return format!("<builtin {:#x}>", index),
+    };
+
+    // Bits 8 to 11 select a pointer to the base type
+    if index & 0xf00 != 0 { format!("{}*", base) } else { base.to_string() }
+}
+
+// Converts the primitive type `index` into a `DataType`.
+fn primitive_type(index: u32) -> DataType {
+    let base = match index & 0xff {
+        0x03 => DataType::Void,
+        0x70 => DataType::Char,
+        0x30 => DataType::Bool,
+        0x10 | 0x68 => DataType::Integer { size: 1, signed: true },
+        0x20 | 0x69 | 0x7c => DataType::Integer { size: 1, signed: false },
+        0x11 | 0x72 => DataType::Integer { size: 2, signed: true },
+        0x21 | 0x73 | 0x71 | 0x7a => DataType::Integer { size: 2, signed: false },
+        0x12 | 0x74 => DataType::Integer { size: 4, signed: true },
+        0x22 | 0x75 | 0x7b => DataType::Integer { size: 4, signed: false },
+        0x13 | 0x76 => DataType::Integer { size: 8, signed: true },
+        0x23 | 0x77 => DataType::Integer { size: 8, signed: false },
+        0x40 => DataType::Float { size: 4 },
+        0x41 | 0x42 => DataType::Float { size: 8 },
+        _ => DataType::Named(primitive_name(index)),
+    };
+
+    if index & 0xf00 != 0 { base.pointer_to() } else { base }
+}
+
+// Section headers copied from the binary. Returns the virtual address of each.
+fn section_addresses(bytes: &[u8]) -> Result<Vec<u64>> {
+    let mut rd = Reader::new(bytes);
+    let mut ret = vec![];
+
+    while rd.pos + 40 <= bytes.len() {
+        let hdr = rd.take(40)?;
+        ret.push(LittleEndian::read_u32(&hdr[12..16]) as u64);
+    }
+
+    Ok(ret)
+}
+
+// Converts a 1-based segment number and offset into a RVA.
+fn rva(sections: &[u64], segment: u16, offset: u32) -> Option<u64> {
+    if segment == 0 {
+        None
+    } else {
+        sections.get(segment as usize - 1).map(|va| va + offset as u64)
+    }
+}
+
+fn parse_symbols(bytes: &[u8], sections: &[u64], ret: &mut Vec<Symbol>) -> Result<()> {
+    records(
+        bytes, |kind, rd| {
+            match kind {
+                S_PUB32 => {
+                    let flags = rd.u32()?;
+                    let offset = rd.u32()?;
+                    let segment = rd.u16()?;
+                    let name = rd.cstr()?;
+                    // Code or function flag
+                    let kind = if flags & 3 != 0 { SymbolKind::Function } else { SymbolKind::Data };
+
+                    if let Some(rva) = rva(sections, segment, offset) {
+                        ret.push(Symbol { name: name, rva: rva, size: None, kind: kind, type_index: None });
+                    }
+                }
+                S_GDATA32 | S_LDATA32 => {
+                    let ty = rd.u32()?;
+                    let offset = rd.u32()?;
+                    let segment = rd.u16()?;
+                    let name = rd.cstr()?;
+
+                    if let Some(rva) = rva(sections, segment, offset) {
+                        ret.push(Symbol { name: name, rva: rva, size: None, kind: SymbolKind::Data, type_index: Some(ty) });
+                    }
+                }
+                S_GPROC32 | S_LPROC32 | S_GPROC32_ID | S_LPROC32_ID => {
+                    let _parent = rd.u32()?;
+                    let _end = rd.u32()?;
+                    let _next = rd.u32()?;
+                    let len = rd.u32()?;
+                    let _dbg_start = rd.u32()?;
+                    let _dbg_end = rd.u32()?;
+                    let ty = rd.u32()?;
+                    let offset = rd.u32()?;
+                    let segment = rd.u16()?;
+                    let _flags = rd.u8()?;
+                    let name = rd.cstr()?;
+                    // The *_ID variants refer to the IPI stream, which isn't read
+                    let ty = if kind == S_GPROC32 || kind == S_LPROC32 { Some(ty) } else { None };
+
+                    if let Some(rva) = rva(sections, segment, offset) {
+                        ret.push(Symbol { name: name, rva: rva, size: Some(len as u64), kind: SymbolKind::Function, type_index: ty });
+                    }
+                }
+                _ => {}
+            }
+
+            Ok(())
+        }
+    )
+}
+
+impl Pdb {
+    /// Reads the PDB file at `path`.
+    pub fn open(path: &Path) -> Result<Pdb> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Pdb::parse(&bytes)
+    }
+
+    /// Parses the contents of a PDB file.
+    pub fn parse(bytes: &[u8]) -> Result<Pdb> {
+        let streams = streams(bytes)?;
+        let stream = |idx: usize| -> Result<&[u8]> {
+            match streams.get(idx) {
+                Some(s) => Ok(s),
+                None => Err(format!("PDB stream {} missing", idx).into()),
+            }
+        };
+
+        let mut info = Reader::new(stream(PDB_STREAM)?);
+        let _version = info.u32()?;
+        let _signature = info.u32()?;
+        let age = info.u32()?;
+        let mut guid = [0u8; 16];
+        guid.copy_from_slice(info.take(16)?);
+
+        let types = parse_types(stream(TPI_STREAM)?)?;
+
+        // DBI header
+        let dbi = stream(DBI_STREAM)?;
+        let mut hdr = Reader::new(dbi);
+        hdr.pos = 12;
+        let _globals = hdr.u16()?;
+        let _build = hdr.u16()?;
+        let _publics = hdr.u16()?;
+        let _dll_version = hdr.u16()?;
+        let symbol_records = hdr.u16()? as usize;
+        let _dll_build = hdr.u16()?;
+        let substreams = (0..5).map(|_| hdr.u32().map(|x| x as usize)).collect::<Result<Vec<_>>>()?;
+        let _mfc_index = hdr.u32()?;
+        let dbg_header = hdr.u32()? as usize;
+        let ec_size = hdr.u32()? as usize;
+        let mod_info = substreams[0];
+        let header_size = 64;
+
+        // Optional debug header follows all other substreams. Its 6th entry is the stream with
+        // the section headers.
+        let dbg_start = header_size + substreams.iter().sum::<usize>() + ec_size;
+        let sections = if dbg_header >= 12 && dbg_start + 12 <= dbi.len() {
+            match LittleEndian::read_u16(&dbi[dbg_start + 10..]) {
+                0xffff => vec![],
+                idx => section_addresses(stream(idx as usize)?)?,
+            }
+        } else {
+            vec![]
+        };
+
+        let mut symbols = vec![];
+
+        // Module symbols: functions and statics with their undecorated names
+        let mut modules = Reader::new(dbi.get(header_size..header_size + mod_info).unwrap_or(&[]));
+        while modules.pos + 64 <= modules.bytes.len() {
+            modules.take(34)?;
+            let sym_stream = modules.u16()? as usize;
+            let sym_size = modules.u32()? as usize;
+            modules.take(24)?;
+            let _module = modules.cstr()?;
+            let _object = modules.cstr()?;
+            modules.pos = (modules.pos + 3) & !3;
+
+            if sym_stream != 0xffff {
+                // Skips the signature
+                if let Some(syms) = stream(sym_stream)?.get(4..sym_size) {
+                    parse_symbols(syms, &sections, &mut symbols)?;
+                }
+            }
+        }
+
+        // Public and global symbols
+        if symbol_records != 0xffff {
+            parse_symbols(stream(symbol_records)?, &sections, &mut symbols)?;
+        }
+
+        symbols.sort_by_key(|s| s.rva);
+
+        Ok(Pdb { guid: guid, age: age, symbols: symbols, types: types })
+    }
+
+    /// Renders the type with index `index` as C declaration specifier, e.g. `struct foo*`.
+    pub fn type_name(&self, index: u32) -> String {
+        if index < 0x1000 {
+            return primitive_name(index);
+        }
+
+        match self.types.get(&index) {
+            Some(&Type::Pointer(t)) => format!("{}*", self.type_name(t)),
+            Some(&Type::Modifier(t)) => format!("const {}", self.type_name(t)),
+            Some(&Type::Array { element, .. }) => format!("{}[]", self.type_name(element)),
+            Some(&Type::Procedure { ret, ref args }) => {
+                let args = args.iter().map(|&a| self.type_name(a)).collect::<Vec<_>>();
+                format!("{} (*)({})", self.type_name(ret), args.join(", "))
+            }
+            Some(&Type::Aggregate { kind: Aggregate::Struct, ref name, .. }) => format!("struct {}", name),
+            Some(&Type::Aggregate { kind: Aggregate::Class, ref name, .. }) => format!("class {}", name),
+            Some(&Type::Aggregate { kind: Aggregate::Union, ref name, .. }) => format!("union {}", name),
+            Some(&Type::Enum { ref name, .. }) => format!("enum {}", name),
+            None => format!("<type {:#x}>", index),
+        }
+    }
+
+    /// Converts type `index` into a `DataType`. Structures, unions and enums become references
+    /// by name, arrays have an unknown number of elements.
+    pub fn data_type(&self, index: u32) -> DataType {
+        self.data_type_at(index, 0)
+    }
+
+    fn data_type_at(&self, index: u32, depth: usize) -> DataType {
+        if index < 0x1000 {
+            return primitive_type(index);
+        }
+        if depth > MAX_DEPTH {
+            return DataType::Named(self.type_name(index));
+        }
+
+        match self.types.get(&index) {
+            Some(&Type::Pointer(t)) => self.data_type_at(t, depth + 1).pointer_to(),
+            Some(&Type::Modifier(t)) => self.data_type_at(t, depth + 1),
+            Some(&Type::Array { element, .. }) => DataType::Array { element: Box::new(self.data_type_at(element, depth + 1)), count: 0 },
+            Some(&Type::Procedure { ret, ref args }) => {
+                DataType::Function {
+                    ret: Box::new(self.data_type_at(ret, depth + 1)),
+                    args: args.iter().map(|&a| self.data_type_at(a, depth + 1)).collect(),
+                }
+            }
+            Some(&Type::Aggregate { ref name, .. }) | Some(&Type::Enum { ref name, .. }) => DataType::Named(name.clone()),
+            None => DataType::Named(self.type_name(index)),
+        }
+    }
+
+    /// Returns the function symbols, one per address. Symbols with debug information are
+    /// preferred over public symbols because they carry the undecorated name.
+    pub fn functions(&self) -> Vec<&Symbol> {
+        let mut ret: BTreeMap<u64, &Symbol> = BTreeMap::new();
+
+        for sym in self.symbols.iter().filter(|s| s.kind == SymbolKind::Function) {
+            let better = match ret.get(&sym.rva) {
+                Some(prev) => prev.type_index.is_none() && prev.size.is_none() && sym.size.is_some(),
+                None => true,
+            };
+
+            if better {
+                ret.insert(sym.rva, sym);
+            }
+        }
+
+        ret.into_iter().map(|(_, s)| s).collect()
+    }
+
+    /// Names the functions of `proj` whose address matches a function symbol and adds the
+    /// remaining function symbols as new entry points. Functions with a procedure type get its
+    /// signature, the signatures are also added to `TypeLibrary::prototypes` for functions
+    /// disassembled later. `image_base` is the address the binary is loaded at. Type records are
+    /// copied into `Project::types`.
+    pub fn apply(&self, proj: &mut Project, image_base: u64) {
+        let functions = self.functions().into_iter().map(|s| (s.rva + image_base, s)).collect::<HashMap<_, _>>();
+        let signatures = functions
+            .iter()
+            .filter_map(|(&addr, sym)| sym.type_index.and_then(|ty| Signature::from_type(&self.data_type(ty), None)).map(|sig| (addr, sig)))
+            .collect::<HashMap<_, _>>();
+
+        for prog in proj.code.iter_mut() {
+            for (&addr, sym) in functions.iter() {
+                debug!("pdb: naming {:#x} {}", addr, sym.name);
+                prog.propose_name(addr, sym.name.clone(), NameSource::Debug);
+            }
+
+            for func in prog.functions_mut() {
+                let start = match func.cfg().vertex_label(func.entry_point_ref()) {
+                    Some(&ControlFlowTarget::Resolved(ref bb)) => bb.area.start,
+                    _ => continue,
+                };
+
+                if let Some(sig) = signatures.get(&start) {
+                    let mut sig = sig.clone();
+
+                    sig.set_calling_convention(func.calling_convention());
+                    func.set_signature(Some(sig));
+                }
+            }
+        }
+        proj.data_types.prototypes.extend(signatures);
+
+        for (&idx, ty) in self.types.iter() {
+            proj.types.insert(idx, ty.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, CallTarget, Function, Mnemonic, Program, Region, Rvalue};
+    use byteorder::WriteBytesExt;
+    use panopticon_graph_algos::{MutableGraphTrait, VertexListGraphTrait};
+    use uuid::Uuid;
+
+    const BLOCK: usize = 512;
+
+    fn msf(streams: &[Vec<u8>]) -> Vec<u8> {
+        let blocks = streams.iter().map(|s| (s.len() + BLOCK - 1) / BLOCK).collect::<Vec<_>>();
+        let dir_size = 4 + 8 * streams.len() + 4 * blocks.iter().sum::<usize>();
+        let dir_blocks = (dir_size + BLOCK - 1) / BLOCK;
+        let mut next = 4 + dir_blocks;
+        let mut dir = vec![];
+        let mut data = vec![];
+
+        dir.write_u32::<LittleEndian>(streams.len() as u32).unwrap();
+        for s in streams.iter() {
+            dir.write_u32::<LittleEndian>(s.len() as u32).unwrap();
+        }
+        for (s, &n) in streams.iter().zip(blocks.iter()) {
+            for _ in 0..n {
+                dir.write_u32::<LittleEndian>(next as u32).unwrap();
+                next += 1;
+            }
+            data.extend_from_slice(s);
+            data.resize(next.saturating_sub(4 + dir_blocks) * BLOCK, 0);
+        }
+
+        let mut ret = MAGIC.to_vec();
+        ret.write_u32::<LittleEndian>(BLOCK as u32).unwrap();
+        ret.write_u32::<LittleEndian>(1).unwrap();
+        ret.write_u32::<LittleEndian>(next as u32).unwrap();
+        ret.write_u32::<LittleEndian>(dir_size as u32).unwrap();
+        ret.write_u32::<LittleEndian>(0).unwrap();
+        ret.write_u32::<LittleEndian>(3).unwrap();
+        ret.resize(3 * BLOCK, 0);
+        for b in 0..dir_blocks {
+            ret.write_u32::<LittleEndian>(4 + b as u32).unwrap();
+        }
+        ret.resize(4 * BLOCK, 0);
+        dir.resize(dir_blocks * BLOCK, 0);
+        ret.extend(dir);
+        ret.extend(data);
+        ret
+    }
+
+    // Length prefixed record, padded to four bytes.
+    fn record(kind: u16, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![];
+
+        body.write_u16::<LittleEndian>(kind).unwrap();
+        body.extend_from_slice(data);
+        while (body.len() + 2) % 4 != 0 {
+            body.push(0xf0 | (4 - (body.len() + 2) % 4) as u8);
+        }
+
+        let mut ret = vec![];
+        ret.write_u16::<LittleEndian>(body.len() as u16).unwrap();
+        ret.extend(body);
+        ret
+    }
+
+    fn le(words: &[(u32, usize)]) -> Vec<u8> {
+        let mut ret = vec![];
+
+        for &(w, len) in words.iter() {
+            match len {
+                1 => ret.push(w as u8),
+                2 => ret.write_u16::<LittleEndian>(w as u16).unwrap(),
+                _ => ret.write_u32::<LittleEndian>(w).unwrap(),
+            }
+        }
+        ret
+    }
+
+    fn cstr(s: &str) -> Vec<u8> {
+        let mut ret = s.as_bytes().to_vec();
+        ret.push(0);
+        ret
+    }
+
+    fn proc32(offset: u32, len: u32, name: &str) -> Vec<u8> {
+        let mut data = le(&[(0, 4), (0, 4), (0, 4), (len, 4), (0, 4), (0, 4), (0x1001, 4), (offset, 4), (1, 2), (0, 1)]);
+        data.extend(cstr(name));
+        record(S_GPROC32, &data)
+    }
+
+    fn pdb() -> Vec<u8> {
+        let info = le(&[(20000404, 4), (0, 4), (1, 4), (0x11223344, 4), (0x55667788, 4), (0x99aabbcc, 4), (0xddeeff00, 4)]);
+
+        let mut fields = le(&[(LF_MEMBER as u32, 2), (3, 2), (0x74, 4), (0, 2)]);
+        fields.extend(cstr("x"));
+        fields.push(0xf1);
+        fields.extend(le(&[(LF_MEMBER as u32, 2), (3, 2), (0x1003, 4), (8, 2)]));
+        fields.extend(cstr("next"));
+        let mut node = le(&[(2, 2), (0, 2), (0x1002, 4), (0, 4), (0, 4), (16, 2)]);
+        node.extend(cstr("node"));
+        let types = [
+            record(LF_ARGLIST, &le(&[(2, 4), (0x74, 4), (0x1003, 4)])),
+            record(LF_PROCEDURE, &le(&[(0x74, 4), (0, 2), (2, 2), (0x1000, 4)])),
+            record(LF_FIELDLIST, &fields),
+            record(LF_POINTER, &le(&[(0x1004, 4), (0x1000c, 4)])),
+            record(LF_STRUCTURE, &node),
+        ]
+                .concat();
+        let mut tpi = le(&[(20040203, 4), (56, 4), (0x1000, 4), (0x1005, 4), (types.len() as u32, 4)]);
+        tpi.resize(56, 0);
+        tpi.extend(types);
+
+        let mut module = le(&[(0, 4); 8]);
+        module.extend(le(&[(0, 2), (6, 2)]));
+        module.resize(64, 0);
+        let mut module_syms = le(&[(4, 4)]);
+        module_syms.extend(proc32(0x10, 0x20, "f"));
+        module_syms.extend(proc32(0x40, 0x8, "g"));
+        LittleEndian::write_u32(&mut module[36..40], module_syms.len() as u32);
+        module.extend(cstr("a.obj"));
+        module.extend(cstr("a.obj"));
+        while module.len() % 4 != 0 {
+            module.push(0);
+        }
+
+        let dbg = le(&[(0xffff, 2), (0xffff, 2), (0xffff, 2), (0xffff, 2), (0xffff, 2), (7, 2)]);
+        let mut dbi = le(&[(0xffff_ffff, 4), (19990903, 4), (1, 4), (0xffff, 2), (0, 2), (0xffff, 2), (0, 2), (5, 2), (0, 2)]);
+        dbi.extend(le(&[(module.len() as u32, 4), (0, 4), (0, 4), (0, 4), (0, 4), (0, 4), (dbg.len() as u32, 4), (0, 4)]));
+        dbi.extend(le(&[(0, 2), (0x8664, 2), (0, 4)]));
+        dbi.extend(module);
+        dbi.extend(dbg);
+
+        let mut publics = record(S_PUB32, &[le(&[(2, 4), (0x10, 4), (1, 2)]), cstr("?f@@YAHH@Z")].concat());
+        publics.extend(record(S_PUB32, &[le(&[(2, 4), (0x80, 4), (1, 2)]), cstr("h")].concat()));
+        publics.extend(record(S_GDATA32, &[le(&[(0x74, 4), (4, 4), (2, 2)]), cstr("counter")].concat()));
+
+        let mut sections = vec![0u8; 80];
+        LittleEndian::write_u32(&mut sections[12..16], 0x1000);
+        LittleEndian::write_u32(&mut sections[52..56], 0x3000);
+
+        msf(&[vec![], info, tpi, dbi, vec![], publics, module_syms, sections])
+    }
+
+    #[test]
+    fn symbols() {
+        let pdb = Pdb::parse(&pdb()).unwrap();
+
+        assert_eq!(pdb.age, 1);
+        assert_eq!(&pdb.guid[0..4], &[0x44, 0x33, 0x22, 0x11]);
+
+        let funcs = pdb.functions().iter().map(|s| (s.name.clone(), s.rva)).collect::<Vec<_>>();
+        assert_eq!(funcs, vec![("f".to_string(), 0x1010), ("g".to_string(), 0x1040), ("h".to_string(), 0x1080)]);
+
+        let counter = pdb.symbols.iter().find(|s| s.name == "counter").unwrap();
+        assert_eq!(counter.rva, 0x3004);
+        assert_eq!(counter.kind, SymbolKind::Data);
+        assert_eq!(pdb.functions()[0].size, Some(0x20));
+    }
+
+    #[test]
+    fn types() {
+        let pdb = Pdb::parse(&pdb()).unwrap();
+
+        assert_eq!(pdb.type_name(0x1001), "int (*)(int, struct node*)");
+        assert_eq!(pdb.type_name(0x603), "void*");
+        match pdb.types.get(&0x1004) {
+            Some(&Type::Aggregate { kind: Aggregate::Struct, ref name, size, ref fields }) => {
+                assert_eq!(name, "node");
+                assert_eq!(size, 16);
+                assert_eq!(fields.len(), 2);
+                assert_eq!(fields[1], Field { name: "next".to_string(), offset: 8, type_index: 0x1003 });
+            }
+            t => panic!("wrong type {:?}", t),
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let pdb = Pdb::parse(&pdb()).unwrap();
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1_0000_0000));
+        let mut prog = Program::new("prog0");
+
+        let uu = Uuid::new_v4();
+
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(0x40_1010), None, uu.clone()));
+        proj.code.push(prog);
+        pdb.apply(&mut proj, 0x40_0000);
+        assert_eq!(proj.code[0].name_source(&uu), NameSource::Debug);
+
+        let mut names = proj.code[0]
+            .call_graph
+            .vertex_labels()
+            .filter_map(
+                |ct| match ct {
+                    &CallTarget::Todo(Rvalue::Constant { value, .. }, Some(ref name), _) => Some((value, name.clone())),
+                    _ => None,
+                }
+            )
+            .collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, vec![(0x40_1010, "f".to_string()), (0x40_1040, "g".to_string()), (0x40_1080, "h".to_string())]);
+        assert_eq!(proj.types.len(), pdb.types.len());
+    }
+
+    #[test]
+    fn signatures() {
+        let pdb = Pdb::parse(&pdb()).unwrap();
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1_0000_0000));
+        let mut prog = Program::new("prog0");
+        let mut func = Function::undefined(0x40_1040, None, proj.region(), None);
+        let vx = func.cfg_mut().add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![Mnemonic::dummy(0x40_1040..0x40_1048)])));
+
+        func.set_entry_point_ref(vx);
+        prog.insert(func);
+        proj.code.push(prog);
+        pdb.apply(&mut proj, 0x40_0000);
+
+        let g = proj.code[0].functions().next().unwrap();
+        assert_eq!(g.name, "g");
+        assert_eq!(g.signature().map(|s| s.to_string()), Some("int32_t(int32_t arg0, node * arg1)".to_string()));
+        assert!(proj.data_types.prototypes.contains_key(&0x40_1010));
+        assert!(!proj.data_types.prototypes.contains_key(&0x40_1080));
+        assert_eq!(pdb.data_type(0x603), DataType::Void.pointer_to());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Pdb::parse(b"MZ").is_err());
+        let mut bytes = pdb();
+        bytes.truncate(5 * BLOCK);
+        assert!(Pdb::parse(&bytes).is_err());
+    }
+}
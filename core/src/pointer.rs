@@ -0,0 +1,223 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Classification of constant operands.
+//!
+//! [`classify_operands`] decides for every constant operand of every mnemonic whether it's an
+//! address and what it points to, and stores the result in `Mnemonic::pointers`. Front-ends use
+//! this to render references, [`code_pointers`] returns the code addresses that aren't known
+//! functions yet, most referenced first, as candidates for disassembly.
+//!
+//! Permissions are taken from the sections the loader recorded. Without sections, constants
+//! pointing into mapped bytes of the root region are code if they're inside a disassembled basic
+//! block and writable data otherwise.
+//!
+//! [`classify_operands`]: fn.classify_operands.html
+//! [`code_pointers`]: fn.code_pointers.html
+
+use {Bound, CallTarget, ControlFlowTarget, Project, Rvalue};
+use panopticon_graph_algos::{GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use std::collections::{HashMap, HashSet};
+
+/// What an address points to.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub enum PointerKind {
+    /// Executable memory
+    Code,
+    /// Memory that can't be written at run time
+    ReadOnlyData,
+    /// Memory that can be written at run time
+    WritableData,
+}
+
+/// Returns what `address` points to in `proj`, None if it's not mapped. `code` are the areas of
+/// all disassembled basic blocks.
+fn classify(proj: &Project, code: &[Bound], address: u64) -> Option<PointerKind> {
+    if proj.sections.is_empty() {
+        let region = proj.region();
+
+        if address >= region.size() || region.iter().seek(address).next().map(|c| c.is_none()).unwrap_or(true) {
+            None
+        } else if code.iter().any(|b| b.start <= address && address < b.end) {
+            Some(PointerKind::Code)
+        } else {
+            Some(PointerKind::WritableData)
+        }
+    } else {
+        let secs = proj.sections.iter().filter(|s| s.area.start <= address && address < s.area.end).collect::<Vec<_>>();
+
+        if secs.is_empty() {
+            None
+        } else if secs.iter().any(|s| s.execute) {
+            Some(PointerKind::Code)
+        } else if secs.iter().any(|s| s.write) {
+            Some(PointerKind::WritableData)
+        } else {
+            Some(PointerKind::ReadOnlyData)
+        }
+    }
+}
+
+/// Classifies the operands of all mnemonics in `proj` and stores the result in
+/// `Mnemonic::pointers`.
+pub fn classify_operands(proj: &mut Project) {
+    let code = proj.code.iter().flat_map(|p| p.functions()).flat_map(|f| f.basic_blocks()).map(|bb| bb.area.clone()).collect::<Vec<_>>();
+    let mut kinds = HashMap::<u64, Option<PointerKind>>::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for bb in func.basic_blocks() {
+                for mne in bb.mnemonics.iter() {
+                    for op in mne.operands.iter() {
+                        if let &Rvalue::Constant { value, .. } = op {
+                            if !kinds.contains_key(&value) {
+                                kinds.insert(value, classify(proj, &code, value));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for prog in proj.code.iter_mut() {
+        for func in prog.functions_mut() {
+            let cfg = func.cfg_mut();
+            let vxs = cfg.vertices().collect::<Vec<_>>();
+
+            for vx in vxs {
+                if let Some(&mut ControlFlowTarget::Resolved(ref mut bb)) = cfg.vertex_label_mut(vx) {
+                    for mne in bb.mnemonics.iter_mut() {
+                        mne.pointers = mne.operands
+                            .iter()
+                            .map(
+                                |op| match op {
+                                    &Rvalue::Constant { value, .. } => kinds.get(&value).cloned().unwrap_or(None),
+                                    _ => None,
+                                }
+                            )
+                            .collect();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the addresses operands point to as code that aren't the start of a known function,
+/// most referenced first. `classify_operands` must have run before.
+pub fn code_pointers(proj: &Project) -> Vec<u64> {
+    let mut starts = HashSet::<u64>::new();
+
+    for prog in proj.code.iter() {
+        for vx in prog.call_graph.vertices() {
+            match prog.call_graph.vertex_label(vx) {
+                Some(&CallTarget::Concrete(ref func)) => {
+                    if let Some(&ControlFlowTarget::Resolved(ref bb)) = func.cfg().vertex_label(func.entry_point_ref()) {
+                        starts.insert(bb.area.start);
+                    }
+                }
+                Some(&CallTarget::Todo(Rvalue::Constant { value, .. }, _, _)) => {
+                    starts.insert(value);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut counts = HashMap::<u64, usize>::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for bb in func.basic_blocks() {
+                for mne in bb.mnemonics.iter() {
+                    for (op, kind) in mne.operands.iter().zip(mne.pointers.iter()) {
+                        if let (&Rvalue::Constant { value, .. }, &Some(PointerKind::Code)) = (op, kind) {
+                            if !starts.contains(&value) {
+                                *counts.entry(value).or_insert(0) += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut ret = counts.into_iter().collect::<Vec<_>>();
+
+    ret.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ret.into_iter().map(|(a, _)| a).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Function, Layer, Mnemonic, Program, Region, Section};
+    use std::borrow::Cow;
+
+    fn section(name: &str, start: u64, end: u64, write: bool, execute: bool) -> Section {
+        Section {
+            name: name.to_string(),
+            area: Bound::new(start, end),
+            file_size: end - start,
+            file_offset: 0,
+            read: true,
+            write: write,
+            execute: execute,
+        }
+    }
+
+    #[test]
+    fn classify_constants() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x100, 0x300), Layer::wrap(vec![0; 0x200])));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        let rax = Rvalue::Variable { name: Cow::Borrowed("RAX"), size: 64, offset: 0, subscript: None };
+        let mne = |start: u64, ops: Vec<Rvalue>| Mnemonic::new(start..start + 4, "test".to_string(), "".to_string(), ops.iter(), vec![].iter()).ok().unwrap();
+        let mne0 = mne(0x100, vec![Rvalue::new_u64(0x110), Rvalue::new_u64(0x210), Rvalue::new_u64(0x290), Rvalue::new_u64(5), rax]);
+        let mne1 = mne(0x104, vec![Rvalue::new_u64(0x110), Rvalue::new_u64(0x180), Rvalue::new_u64(0x100)]);
+
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne0, mne1])));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        let mut prog = Program::new("prog");
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        prog.insert(func);
+        proj.code.push(prog);
+        proj.sections = vec![section(".text", 0x100, 0x200, false, true), section(".rodata", 0x200, 0x280, false, false), section(".data", 0x280, 0x300, true, false)];
+
+        classify_operands(&mut proj);
+
+        let pointers = |proj: &Project, idx: usize| proj.code[0].functions().next().unwrap().entry_point().mnemonics[idx].pointers.clone();
+        let code = Some(PointerKind::Code);
+        let data = Some(PointerKind::WritableData);
+
+        assert_eq!(pointers(&proj, 0), vec![code, Some(PointerKind::ReadOnlyData), data, None, None]);
+        assert_eq!(pointers(&proj, 1), vec![code, code, code]);
+        assert_eq!(code_pointers(&proj), vec![0x110, 0x180]);
+
+        proj.sections.clear();
+        classify_operands(&mut proj);
+
+        assert_eq!(pointers(&proj, 0), vec![data, data, data, None, None]);
+        assert_eq!(pointers(&proj, 1), vec![data, data, code]);
+    }
+}
@@ -29,7 +29,7 @@
 //! error node.
 
 
-use {Function, Statement, Operation, Rvalue};
+use {Bound, ControlFlowTarget, Function, FunctionKind, NameSource, Relocation, Statement, Operation, Rvalue, demangle};
 use panopticon_graph_algos::{AdjacencyList, AdjacencyMatrixGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListVertexDescriptor, VertexLabelIterator, VertexLabelMutIterator};
 use uuid::Uuid;
@@ -126,6 +126,22 @@ pub struct Program {
     pub call_graph: CallGraph,
     /// Symbolic References (Imports)
     pub imports: ::std::collections::HashMap<u64, String>,
+    /// Import thunks found by the loader. Maps the thunk's start address to the import table slot
+    /// it jumps through
+    #[serde(default)]
+    pub thunks: ::std::collections::HashMap<u64, u64>,
+    /// Relocated words of the program's code and data, by address. Used to show the symbol an
+    /// operand refers to
+    #[serde(default)]
+    pub relocations: ::std::collections::BTreeMap<u64, Relocation>,
+    /// Call graph edges found by an analysis instead of a constant call target, by the UUIDs of
+    /// the caller and callee. They may be wrong.
+    #[serde(default)]
+    pub analyzed_calls: ::std::collections::HashSet<(Uuid, Uuid)>,
+    /// Where the names of `Todo`s come from, by UUID. Names without an entry are
+    /// `NameSource::Unknown`. See `Program::add_todo`.
+    #[serde(default)]
+    pub name_sources: ::std::collections::HashMap<Uuid, NameSource>,
 }
 
 impl<'a> IntoIterator for &'a Program {
@@ -144,9 +160,68 @@ impl Program {
             name: n.to_string(),
             call_graph: CallGraph::new(),
             imports: ::std::collections::HashMap::new(),
+            thunks: ::std::collections::HashMap::new(),
+            relocations: ::std::collections::BTreeMap::new(),
+            analyzed_calls: ::std::collections::HashSet::new(),
+            name_sources: ::std::collections::HashMap::new(),
         }
     }
 
+    /// Adds a `Todo` for the function at `address` named `name`, a name found in `source`.
+    /// Returns the UUID of the new `Todo`.
+    pub fn add_todo(&mut self, address: u64, name: String, source: NameSource) -> Uuid {
+        let uu = Uuid::new_v4();
+
+        self.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(address), Some(name), uu.clone()));
+        self.name_sources.insert(uu.clone(), source);
+        uu
+    }
+
+    /// Names the function or `Todo` starting at `address` `name`, a name found in `source`. Adds a
+    /// new `Todo` if there is neither. A name from a less trusted source than the current one
+    /// becomes an alias of a function and is dropped for a `Todo`, see `Function::propose_name`.
+    pub fn propose_name(&mut self, address: u64, name: String, source: NameSource) {
+        let vertices = self.call_graph.vertices().collect::<Vec<_>>();
+
+        for vx in vertices {
+            match self.call_graph.vertex_label_mut(vx) {
+                Some(&mut CallTarget::Concrete(ref mut func)) => {
+                    let starts = match func.cfg().vertex_label(func.entry_point_ref()) {
+                        Some(&ControlFlowTarget::Resolved(ref bb)) => bb.area.start == address,
+                        _ => false,
+                    };
+
+                    if starts {
+                        func.propose_name(name, source);
+                        return;
+                    }
+                }
+                Some(&mut CallTarget::Todo(Rvalue::Constant { value, .. }, ref mut todo_name, ref uuid)) if value == address => {
+                    let current = self.name_sources.get(uuid).cloned().unwrap_or(NameSource::Unknown);
+
+                    if todo_name.is_none() || source > current {
+                        *todo_name = Some(name);
+                        self.name_sources.insert(uuid.clone(), source);
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        self.add_todo(address, name, source);
+    }
+
+    /// Returns where the name of the `Todo` with UUID `uu` comes from.
+    pub fn name_source(&self, uu: &Uuid) -> NameSource {
+        self.name_sources.get(uu).cloned().unwrap_or(NameSource::Unknown)
+    }
+
+    /// Returns the first relocation of a word inside `area`, e.g. of a mnemonic.
+    pub fn relocation(&self, area: &Bound) -> Option<&Relocation> {
+        self.relocations.range(area.start..area.end).next().map(|(_, r)| r)
+    }
+
     /// Returns a function if it matches the condition in the `filter` closure.
     pub fn find_function_by<'a, F: (Fn(&Function) -> bool)>(&'a self, filter: F) -> Option<&'a Function> {
         for ct in self.call_graph.vertex_labels() {
@@ -206,10 +281,22 @@ impl Program {
     }
 
     /// Puts `function` into the call graph, returning the UUIDs of all _new_ `Todo`s
-    /// that are called by `function`
-    pub fn insert(&mut self, function: Function) -> Vec<Uuid> {
+    /// that are called by `function`. A `Todo` replaced by `function` passes its name on with
+    /// the source it was recorded with, mangled names get their demangled form as alias.
+    pub fn insert(&mut self, mut function: Function) -> Vec<Uuid> {
         let maybe_vx = self.call_graph.vertices().find(|ct| self.call_graph.vertex_label(*ct).unwrap().uuid() == function.uuid());
 
+        if let Some(source) = self.name_sources.remove(function.uuid()) {
+            if let Some(&CallTarget::Todo(_, Some(ref name), _)) = maybe_vx.and_then(|vx| self.call_graph.vertex_label(vx)) {
+                function.propose_name(name.clone(), source);
+            }
+        }
+        if let Some(demangled) = demangle(&function.name) {
+            if demangled != function.name {
+                function.add_alias_from(demangled, NameSource::Demangler);
+            }
+        }
+
         let calls = function.collect_calls();
         let new_vx = if let Some(vx) = maybe_vx {
             *self.call_graph.vertex_label_mut(vx).unwrap() = CallTarget::Concrete(function);
@@ -262,6 +349,52 @@ impl Program {
         todos
     }
 
+    /// Adds call graph edges from the function with UUID `caller` to the functions starting at
+    /// `targets`, resolved by an analysis. New edges are recorded in `analyzed_calls`. Returns the
+    /// UUIDs of the _new_ `Todo`s.
+    pub fn insert_analyzed_calls(&mut self, caller: &Uuid, targets: &[u64]) -> Vec<Uuid> {
+        let from = match self.find_call_target_by_uuid(caller) {
+            Some(vx) => vx,
+            None => return vec![],
+        };
+        let mut todos = Vec::new();
+
+        for &target in targets {
+            let existing = self.call_graph
+                .vertices()
+                .find(
+                    |&vx| match self.call_graph.vertex_label(vx) {
+                        Some(&CallTarget::Concrete(ref function)) => function.start() == target,
+                        Some(&CallTarget::Todo(Rvalue::Constant { value, .. }, _, _)) => value == target,
+                        _ => false,
+                    }
+                );
+            let to = match existing {
+                Some(vx) => vx,
+                None => {
+                    let uu = Uuid::new_v4();
+                    todos.push(uu);
+                    self.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(target), None, uu))
+                }
+            };
+
+            if self.call_graph.edge(from, to) == None {
+                let callee = self.call_graph.vertex_label(to).unwrap().uuid().clone();
+
+                self.call_graph.add_edge((), from, to);
+                self.analyzed_calls.insert((caller.clone(), callee));
+            }
+        }
+
+        todos
+    }
+
+    /// Returns true if the call from `caller` to `callee` was found by an analysis and isn't
+    /// ground truth.
+    pub fn is_analyzed_call(&self, caller: &Uuid, callee: &Uuid) -> bool {
+        self.analyzed_calls.contains(&(caller.clone(), callee.clone()))
+    }
+
     /// Returns the function, todo item or symbolic reference with UUID `uu`.
     pub fn find_call_target_by_uuid<'a>(&'a self, uu: &Uuid) -> Option<CallGraphRef> {
         for vx in self.call_graph.vertices() {
@@ -286,19 +419,51 @@ impl Program {
     pub fn functions_mut(&mut self) -> FunctionMutIterator {
         FunctionMutIterator::new(&mut self.call_graph)
     }
+    /// Marks regular functions that only jump to another function as `FunctionKind::Thunk` and
+    /// functions named like compiler runtime code as `FunctionKind::Runtime`. Returns the number
+    /// of functions changed.
+    pub fn classify(&mut self) -> usize {
+        // undefined functions have no entry point
+        let start = |f: &Function| match f.cfg().vertex_label(f.entry_point_ref()) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) => Some(bb.area.start),
+            _ => None,
+        };
+        let entries = self.functions().filter_map(&start).collect::<::std::collections::HashSet<_>>();
+        let mut ret = 0;
+
+        for f in self.functions_mut() {
+            if let &FunctionKind::Regular = f.kind() {
+                let kind = match f.thunk_target() {
+                    Some(target) if entries.contains(&target) && Some(target) != start(f) => FunctionKind::Thunk { target: target },
+                    _ if is_runtime(&f.name) => FunctionKind::Runtime,
+                    _ => continue,
+                };
+
+                f.set_kind(kind);
+                ret += 1;
+            }
+        }
+
+        ret
+    }
+
     /// Calls [Function::set_plt](../function/struct.Function.html#method.set_plt) on all matching functions
     pub fn update_plt(&mut self) {
         for ct in self.call_graph.vertex_labels_mut() {
             match ct {
                 &mut CallTarget::Concrete(ref mut function) => {
-                    let address = {
+                    let address = if let Some(&slot) = self.thunks.get(&function.start()) {
+                        Some(slot)
+                    } else {
                         let mut last = None;
                         let mut count = 0;
-                        for statement in function.statements() {
+                        // SSA conversion adds the undefined initial value of the loaded variable,
+                        // functions created by `Function::decode_only` aren't converted
+                        for statement in function.statements().filter(|s| match s.op { Operation::Move(Rvalue::Undefined) => false, _ => true }) {
                             count += 1;
                             last = Some(statement);
                         }
-                        if count == 2 {
+                        if count == 1 {
                             if let Some( &Statement { op: Operation::Load(_, _, _, Rvalue::Constant { value, .. }), .. }) = last {
                                 Some(value)
                             } else {
@@ -323,10 +488,36 @@ impl Program {
     }
 }
 
+/// Names of startup code and helpers inserted by GCC, Clang and MSVC.
+const RUNTIME: &[&str] = &[
+    "_start",
+    "_init",
+    "_fini",
+    "__libc_csu_init",
+    "__libc_csu_fini",
+    "frame_dummy",
+    "register_tm_clones",
+    "deregister_tm_clones",
+    "__do_global_dtors_aux",
+    "__do_global_ctors_aux",
+    "__stack_chk_fail",
+    "__stack_chk_fail_local",
+    "mainCRTStartup",
+    "WinMainCRTStartup",
+    "_DllMainCRTStartup",
+    "__security_check_cookie",
+    "__security_init_cookie",
+    "__report_gsfailure",
+];
+
+fn is_runtime(name: &str) -> bool {
+    RUNTIME.contains(&name) || name.starts_with("__x86.get_pc_thunk.") || name.starts_with("_RTC_")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use {BasicBlock, ControlFlowTarget, Function, Lvalue, Mnemonic, Operation, Region, Rvalue, Statement};
+    use {BasicBlock, ControlFlowTarget, Function, FunctionKind, Guard, Lvalue, Mnemonic, Operation, Region, Rvalue, Statement};
     use panopticon_graph_algos::{AdjacencyMatrixGraphTrait, EdgeListGraphTrait, GraphTrait, MutableGraphTrait, VertexListGraphTrait};
     use uuid::Uuid;
 
@@ -354,6 +545,64 @@ mod tests {
         assert_eq!(prog.find_function_by_entry(2), None);
     }
 
+    #[test]
+    fn classify() {
+        let reg = Region::undefined("ram".to_owned(), 0x1000);
+        let mut prog = Program::new("prog_test");
+        let func = |start: u64, name: &str| {
+            let mut f = Function::undefined(start, None, &reg, Some(name.to_owned()));
+            let bb = BasicBlock::from_vec(vec![Mnemonic::dummy(start..start + 5)]);
+            let vx = f.cfg_mut().add_vertex(ControlFlowTarget::Resolved(bb));
+            f.set_entry_point_ref(vx);
+            f
+        };
+        let mut thunk = func(0, "j_main");
+        let tgt = thunk.cfg_mut().add_vertex(ControlFlowTarget::Unresolved(Rvalue::new_u64(0x100)));
+        let ep = thunk.entry_point_ref();
+        thunk.cfg_mut().add_edge(Guard::always(), ep, tgt);
+
+        prog.call_graph.add_vertex(CallTarget::Concrete(thunk));
+        prog.call_graph.add_vertex(CallTarget::Concrete(func(0x100, "main")));
+        prog.call_graph.add_vertex(CallTarget::Concrete(func(0x200, "_start")));
+
+        assert_eq!(prog.classify(), 2);
+        assert_eq!(prog.classify(), 0);
+
+        let kinds = prog.functions().map(|f| (f.name.clone(), f.kind().clone())).collect::<Vec<_>>();
+        for (name, kind) in kinds {
+            match (name.as_str(), kind) {
+                ("j_main", FunctionKind::Thunk { target: 0x100 }) => {}
+                ("main", FunctionKind::Regular) => {}
+                ("_start", FunctionKind::Runtime) => {}
+                (n, k) => panic!("{} is {:?}", n, k),
+            }
+        }
+    }
+
+    #[test]
+    fn name_sources() {
+        let reg = Region::undefined("ram".to_owned(), 0x1000);
+        let mut prog = Program::new("prog_test");
+        let uu = prog.add_todo(0x100, "_ZN3foo3barEv".to_string(), NameSource::Symbol);
+
+        prog.propose_name(0x100, "sub_100".to_string(), NameSource::Unknown);
+        prog.propose_name(0x200, "baz".to_string(), NameSource::Debug);
+        assert_eq!(prog.name_source(&uu), NameSource::Symbol);
+
+        let mut func = Function::undefined(0x100, Some(uu.clone()), &reg, Some("_ZN3foo3barEv".to_owned()));
+        let bb = BasicBlock::from_vec(vec![Mnemonic::dummy(0x100..0x105)]);
+        let vx = func.cfg_mut().add_vertex(ControlFlowTarget::Resolved(bb));
+        func.set_entry_point_ref(vx);
+        prog.insert(func);
+        prog.propose_name(0x100, "bar".to_string(), NameSource::Debug);
+
+        let func = prog.find_function_by_uuid(&uu).unwrap();
+        assert_eq!(func.name, "bar");
+        assert_eq!(func.name_source(), NameSource::Debug);
+        assert_eq!(func.alias_entries().iter().map(|a| a.source).collect::<Vec<_>>(), vec![NameSource::Demangler, NameSource::Symbol]);
+        assert_eq!(prog.call_graph.vertex_labels().filter(|ct| ct.uuid() != &uu).count(), 1);
+    }
+
     #[test]
     fn insert_replaces_todo() {
         let uu = Uuid::new_v4();
@@ -430,4 +679,25 @@ mod tests {
         assert_eq!(prog.call_graph.num_edges(), 1);
         assert_eq!(prog.call_graph.num_vertices(), 2);
     }
+
+    #[test]
+    fn analyzed_calls() {
+        let mut prog = Program::new("prog_test");
+        let mut func = Function::undefined(0, None, &Region::undefined("ram".to_owned(), 100), Some("test".to_owned()));
+        let i1 = vec![Statement { op: Operation::Call(Rvalue::new_u64(12)), assignee: Lvalue::Undefined }];
+        let mne1 = Mnemonic::new(0..1, "call".to_string(), "".to_string(), vec![].iter(), i1.iter()).ok().unwrap();
+        let vx = func.cfg_mut().add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne1])));
+        func.set_entry_point_ref(vx);
+        let caller = func.uuid().clone();
+
+        let todos = prog.insert(func);
+        assert_eq!(todos.len(), 1);
+
+        let new = prog.insert_analyzed_calls(&caller, &[12, 40, 40]);
+        assert_eq!(new.len(), 1);
+        assert_eq!(prog.call_graph.num_edges(), 2);
+        assert!(!prog.is_analyzed_call(&caller, &todos[0]));
+        assert!(prog.is_analyzed_call(&caller, &new[0]));
+        assert_eq!(prog.insert_analyzed_calls(&Uuid::new_v4(), &[40]), vec![]);
+    }
 }
@@ -21,8 +21,11 @@
 //! Projects are a set of `Program`s, associated memory `Region`s and comments.
 
 
-use {CallGraphRef, Function, Program, Region, Result, World};
-use panopticon_graph_algos::GraphTrait;
+use {Annotations, CallGraphRef, Coverage, Fde, Finding, Function, HardeningReport, Image, MappingSymbol, Patch, Program, Region, Relocation, Result, Section, StringLiteral,
+     TypeDatabase, TypeLibrary, World, Xref, XrefDatabase};
+use image;
+use pdb::Type;
+use panopticon_graph_algos::{GraphTrait, MutableGraphTrait};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use flate2::Compression;
 use flate2::read::ZlibDecoder;
@@ -30,7 +33,7 @@ use flate2::write::ZlibEncoder;
 use serde_cbor::de::Deserializer;
 use serde_cbor::ser::Serializer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
@@ -50,6 +53,51 @@ pub struct Project {
     pub comments: HashMap<(String, u64), String>,
     /// Symbolic References (Imports)
     pub imports: HashMap<u64, String>,
+    /// ARM/Thumb instruction set hints recovered by the loader
+    #[serde(default)]
+    pub mapping_symbols: BTreeMap<u64, MappingSymbol>,
+    /// Types recovered from debug information, by type index
+    #[serde(default)]
+    pub types: BTreeMap<u32, Type>,
+    /// Words relocated by the loader, by address
+    #[serde(default)]
+    pub relocations: BTreeMap<u64, Relocation>,
+    /// Sections of the executable file, with their permissions
+    #[serde(default)]
+    pub sections: Vec<Section>,
+    /// Packers and suspicious sections found after loading
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+    /// String literals in data sections, by start address
+    #[serde(default)]
+    pub strings: BTreeMap<u64, StringLiteral>,
+    /// C++ classes recovered from RTTI
+    #[serde(default)]
+    pub type_database: TypeDatabase,
+    /// Unwinding information and exception handlers of each function
+    #[serde(default)]
+    pub exception_tables: Vec<Fde>,
+    /// Cross references from code to code and data
+    #[serde(default)]
+    pub xrefs: XrefDatabase,
+    /// Exploit mitigations of the executable
+    #[serde(default)]
+    pub hardening: HardeningReport,
+    /// Comments and labels, see `annotation`
+    #[serde(default)]
+    pub annotations: Annotations,
+    /// Changes to the root region, see `patch`
+    #[serde(default)]
+    pub patches: Vec<Patch>,
+    /// Executable and shared libraries, empty if only one image was loaded. See `image`.
+    #[serde(default)]
+    pub images: Vec<Image>,
+    /// Basic blocks entered by imported execution traces, see `coverage`
+    #[serde(default)]
+    pub coverage: Coverage,
+    /// User defined types and the variables they are applied to, see `datatype`
+    #[serde(default)]
+    pub data_types: TypeLibrary,
 }
 
 impl Project {
@@ -61,6 +109,21 @@ impl Project {
             data: World::new(r),
             comments: HashMap::new(),
             imports: HashMap::new(),
+            mapping_symbols: BTreeMap::new(),
+            types: BTreeMap::new(),
+            relocations: BTreeMap::new(),
+            sections: Vec::new(),
+            findings: Vec::new(),
+            strings: BTreeMap::new(),
+            type_database: TypeDatabase::new(),
+            exception_tables: Vec::new(),
+            xrefs: XrefDatabase::new(),
+            hardening: HardeningReport::new(),
+            annotations: Annotations::new(),
+            patches: Vec::new(),
+            images: Vec::new(),
+            coverage: Coverage::new(),
+            data_types: TypeLibrary::default(),
         }
     }
 
@@ -70,6 +133,26 @@ impl Project {
         self.data.dependencies.vertex_label(self.data.root).unwrap()
     }
 
+    /// Sets the sections of the executable, both in `Project::sections` and the root `Region`.
+    pub fn set_sections(&mut self, sections: Vec<Section>) {
+        let root = self.data.root;
+
+        if let Some(region) = self.data.dependencies.vertex_label_mut(root) {
+            region.set_sections(sections.clone());
+        }
+        self.sections = sections;
+    }
+
+    /// Returns the image `address` belongs to, if the project has more than one.
+    pub fn image(&self, address: u64) -> Option<&Image> {
+        self.images.iter().find(|i| i.area.start <= address && address < i.area.end)
+    }
+
+    /// Moves the image named `image` to `new_base`, see `image::rebase`.
+    pub fn rebase(&mut self, image: &str, new_base: u64) -> Result<()> {
+        image::rebase(self, image, new_base)
+    }
+
     /// Reads a serialized project from disk.
     pub fn open(p: &Path) -> Result<Project> {
         let mut fd = match File::open(p) {
@@ -148,6 +231,16 @@ impl Project {
         None
     }
 
+    /// Returns all references to `address` found by `xref::collect`
+    pub fn xrefs_to(&self, address: u64) -> &[Xref] {
+        self.xrefs.to(address)
+    }
+
+    /// Returns all references from the function with UUID `func` found by `xref::collect`
+    pub fn xrefs_from(&self, func: &Uuid) -> &[Xref] {
+        self.xrefs.from(func)
+    }
+
     /// Serializes the project into the file at `p`. The format looks like this:
     /// [u8;10] magic = "PANOPTICON"
     /// u32     version = 0
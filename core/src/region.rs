@@ -48,15 +48,59 @@
 //! let undefined_region = Region::undefined("undef".to_string(),4096);
 //! ```
 //! This region is named "undef" and is just 4k of undefined cells
+//!
+//! Loaders record the sections or segments of the file mapped into a region, with their
+//! permissions and position in the file. Analyses ask [`Region::section`] and the
+//! `is_readable`, `is_writable`, `is_executable` and `file_offset` methods what an address is.
+//!
+//! The layers of a region are named. The file contents mapped by the loader are
+//! [`ORIGINAL_LAYER`], relocated words [`RELOCATION_LAYER`], bytes changed with
+//! `Region::patch` [`PATCH_LAYER`] and data unpacked by `decompress::map` `DECOMPRESSED_LAYER`. Named layers can be switched off to see the bytes without
+//! them, `Region::provenance` tells which layer a byte comes from and `Region::diff` lists all
+//! bytes that differ from the original file.
+//!
+//! Emulators and speculative analyses change memory with `Region::write` instead. Written
+//! bytes are kept in [`PAGE_SIZE`] byte pages on top of the layers, cells that weren't written
+//! still show the bytes below. [`Region::snapshot`] only copies references to these pages, a
+//! page is copied again when it's written after the snapshot was taken, so rolling back with
+//! `Region::restore` is cheap no matter how large the region is.
+//!
+//! [`Region::section`]: struct.Region.html#method.section
+//! [`ORIGINAL_LAYER`]: constant.ORIGINAL_LAYER.html
+//! [`RELOCATION_LAYER`]: constant.RELOCATION_LAYER.html
+//! [`PATCH_LAYER`]: constant.PATCH_LAYER.html
+//! [`PAGE_SIZE`]: constant.PAGE_SIZE.html
+//! [`Region::snapshot`]: struct.Region.html#method.snapshot
 
 
-use {Bound, Layer, LayerIter, OpaqueLayer, Result};
-use panopticon_graph_algos::{AdjacencyList, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use {Bound, Endianess, Layer, LayerIter, Machine, OpaqueLayer, Result, Section};
+use layer::Cell;
+use panopticon_graph_algos::{AdjacencyList, BidirectionalGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListEdgeDescriptor, AdjacencyListVertexDescriptor};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 
+/// Name of the layers holding the contents of the file.
+pub const ORIGINAL_LAYER: &'static str = "original";
+/// Name of the layers holding words changed by relocations.
+pub const RELOCATION_LAYER: &'static str = "relocations";
+/// Name of the layers added by `Region::patch`.
+pub const PATCH_LAYER: &'static str = "patches";
+/// Name of the layers added by `decompress::map`.
+pub const DECOMPRESSED_LAYER: &'static str = "decompressed";
+/// Granularity in which `Region::write` copies cells.
+pub const PAGE_SIZE: u64 = 0x1000;
+
+/// Name of a `Layer` in a `Region` and whether it's applied.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct LayerInfo {
+    /// Where the layer comes from, e.g. `PATCH_LAYER`
+    pub name: String,
+    /// False if the layer is switched off
+    pub enabled: bool,
+}
+
 /// A continuous sequcence of `Cell`s
 ///
 /// `Region`s are a stack of [`Layer`](../layer/index.html) inside a single address space. The
@@ -67,6 +111,24 @@ pub struct Region {
     stack: Vec<(Bound, Layer)>,
     name: String,
     size: u64,
+    #[serde(default)]
+    changes: Vec<Bound>,
+    #[serde(default)]
+    sections: Vec<Section>,
+    // one per entry in `stack`, layers without one are original and enabled
+    #[serde(default)]
+    layers: Vec<LayerInfo>,
+    // cells changed by `write` grouped into pages by page number, `None` if not written
+    #[serde(default)]
+    pages: BTreeMap<u64, Arc<Vec<Cell>>>,
+}
+
+/// Contents of a `Region` at one point in time, returned by `Region::snapshot`.
+#[derive(Clone,Debug)]
+pub struct Snapshot {
+    pages: BTreeMap<u64, Arc<Vec<Cell>>>,
+    stack: usize,
+    layers: Vec<LayerInfo>,
 }
 
 /// Graph that models overlapping regions.
@@ -82,6 +144,11 @@ pub type RegionRef = AdjacencyListVertexDescriptor;
 /// will not yield `Cell`s from the overlapping `Region`. For example, a compressed file inside a `Region`
 /// would be overlapped with a new, larger `Region` that holds the result after decompression. A `Program`
 /// inside the overlapped `Region` would still see only the compressed version.
+///
+/// Architectures with more than one address space, like the 8051 with its separate code, internal
+/// and external data memory, add a `Region` per space that is not connected to the others. RREIL
+/// `load` and `store` statements name the space they access, `World::space` maps that name back
+/// to the `Region`.
 #[derive(Clone,Serialize,Deserialize,Debug)]
 pub struct World {
     ///< Graph of all `Region`s with edges pointing from the overlapping to the overlapped `Region`.
@@ -111,7 +178,15 @@ impl Region {
     pub fn new(name: String, root: OpaqueLayer) -> Region {
         let l = root.len();
         let b = Layer::Opaque(root);
-        Region { stack: vec![(Bound::new(0, l), b)], name: name, size: l }
+        Region {
+            stack: vec![(Bound::new(0, l), b)],
+            name: name,
+            size: l,
+            changes: vec![],
+            sections: vec![],
+            layers: vec![],
+            pages: BTreeMap::new(),
+        }
     }
 
     /// Applies `layer` to the cells inside `area`.
@@ -120,6 +195,11 @@ impl Region {
     /// `false` if `area` is outside of `0..self.size()` of not compatible with `layer`, `true`
     /// otherwise.
     pub fn cover(&mut self, b: Bound, l: Layer) -> bool {
+        self.cover_named(ORIGINAL_LAYER, b, l)
+    }
+
+    /// Like `cover`, but names the layer `name`.
+    pub fn cover_named(&mut self, name: &str, b: Bound, l: Layer) -> bool {
         if b.end <= self.stack[0].0.end {
             if let Some(o) = l.as_opaque() {
                 if b.end - b.start > o.len() {
@@ -127,20 +207,284 @@ impl Region {
                 }
             }
 
+            while self.layers.len() < self.stack.len() {
+                self.layers.push(LayerInfo { name: ORIGINAL_LAYER.to_string(), enabled: true });
+            }
             self.stack.push((b, l));
+            self.layers.push(LayerInfo { name: name.to_string(), enabled: true });
             true
         } else {
             false
         }
     }
 
+    /// Name and state of the `i`th layer of `stack`.
+    pub fn layer_info(&self, i: usize) -> LayerInfo {
+        self.layers.get(i).cloned().unwrap_or(LayerInfo { name: ORIGINAL_LAYER.to_string(), enabled: true })
+    }
+
+    /// Names of all layers, bottom to top, without duplicates.
+    pub fn layer_names(&self) -> Vec<String> {
+        let mut ret = vec![];
+
+        for i in 0..self.stack.len() {
+            let name = self.layer_info(i).name;
+
+            if !ret.contains(&name) {
+                ret.push(name);
+            }
+        }
+        ret
+    }
+
+    /// Switches all layers named `name` on or off. The lowest layer, which defines the size of
+    /// the `Region`, is always on. Returns the number of layers changed.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> usize {
+        let mut ret = 0;
+
+        for i in 1..self.stack.len() {
+            let mut info = self.layer_info(i);
+
+            if info.name == name && info.enabled != enabled {
+                info.enabled = enabled;
+                while self.layers.len() <= i {
+                    self.layers.push(LayerInfo { name: ORIGINAL_LAYER.to_string(), enabled: true });
+                }
+                self.layers[i] = info;
+                ret += 1;
+            }
+        }
+        ret
+    }
+
+    /// Returns the name of the topmost enabled layer covering `address`, the one the byte there
+    /// comes from. None if `address` is outside the `Region`.
+    pub fn provenance(&self, address: u64) -> Option<String> {
+        if address >= self.size {
+            return None;
+        }
+
+        for i in (0..self.stack.len()).rev() {
+            let info = self.layer_info(i);
+            let area = &self.stack[i].0;
+
+            if (i == 0 || info.enabled) && area.start <= address && address < area.end {
+                return Some(info.name);
+            }
+        }
+        None
+    }
+
+    /// Iterator over all `Cell`s of the file, without any layers except `ORIGINAL_LAYER` ones.
+    pub fn original(&self) -> LayerIter {
+        self.iter_layers(&|info| info.name == ORIGINAL_LAYER)
+    }
+
+    /// Returns every address whose contents differ from `original`, with the original and the
+    /// current value.
+    pub fn diff(&self) -> Vec<(u64, Option<u8>, Option<u8>)> {
+        let mut areas = (1..self.stack.len())
+            .filter(
+                |&i| {
+                    let info = self.layer_info(i);
+                    info.enabled && info.name != ORIGINAL_LAYER
+                }
+            )
+            .map(|i| self.stack[i].0.clone())
+            .chain(self.pages.iter().map(|(&p, c)| Bound::new(p * PAGE_SIZE, p * PAGE_SIZE + c.len() as u64)))
+            .collect::<Vec<_>>();
+        let mut ret = vec![];
+        let mut done = 0;
+
+        areas.sort_by_key(|b| b.start);
+        for area in areas {
+            let start = ::std::cmp::max(area.start, done);
+
+            if start >= area.end {
+                continue;
+            }
+
+            let len = (area.end - start) as usize;
+            let old = self.original().seek(start).take(len);
+            let new = self.iter().seek(start).take(len);
+
+            for (i, (o, n)) in old.zip(new).enumerate() {
+                if o != n {
+                    ret.push((start + i as u64, o, n));
+                }
+            }
+            done = area.end;
+        }
+
+        ret
+    }
+
+    /// Overwrites the cells starting at `start` with `bytes` and remembers the area changed until
+    /// `take_changes` is called. Returns `false` if the bytes don't fit into the `Region`.
+    pub fn patch(&mut self, start: u64, bytes: Vec<u8>) -> bool {
+        let area = Bound::new(start, start + bytes.len() as u64);
+
+        if bytes.is_empty() || !self.cover_named(PATCH_LAYER, area.clone(), Layer::wrap(bytes)) {
+            return false;
+        }
+
+        self.changes.push(area);
+        true
+    }
+
+    /// Overwrites the cells starting at `address` with `bytes` without adding a layer. Only the
+    /// written cells are stored, all others still show the layers below. The pages written are
+    /// copied first if they are shared with a `Snapshot`. Returns `false` if the
+    /// bytes don't fit into the `Region`.
+    pub fn write(&mut self, address: u64, bytes: &[u8]) -> bool {
+        if address.checked_add(bytes.len() as u64).map(|e| e > self.size).unwrap_or(true) {
+            return false;
+        }
+
+        for (i, &b) in bytes.iter().enumerate() {
+            let addr = address + i as u64;
+            let page = addr / PAGE_SIZE;
+
+            if !self.pages.contains_key(&page) {
+                let len = (page * PAGE_SIZE + PAGE_SIZE).min(self.size) - page * PAGE_SIZE;
+
+                self.pages.insert(page, Arc::new(vec![None; len as usize]));
+            }
+            if let Some(p) = self.pages.get_mut(&page) {
+                Arc::make_mut(p)[(addr % PAGE_SIZE) as usize] = Some(b);
+            }
+        }
+
+        true
+    }
+
+    /// Forgets all bytes changed by `write`.
+    pub fn discard_writes(&mut self) {
+        self.pages.clear();
+    }
+
+    /// Remembers the current contents. Pages written so far are shared between the `Region`
+    /// and the snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { pages: self.pages.clone(), stack: self.stack.len(), layers: (0..self.stack.len()).map(|i| self.layer_info(i)).collect() }
+    }
+
+    /// Rolls back to `snapshot`, which must have been taken from this `Region`. Writes and layers
+    /// added since are removed, layers switched on or off are switched back. Returns `false`
+    /// without changing anything if layers were removed from the `Region` in the meantime.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> bool {
+        if snapshot.stack > self.stack.len() {
+            return false;
+        }
+
+        self.stack.truncate(snapshot.stack);
+        self.layers = snapshot.layers.clone();
+        self.pages = snapshot.pages.clone();
+        true
+    }
+
+    /// Areas patched since the last call to `take_changes`.
+    pub fn changes(&self) -> &[Bound] {
+        &self.changes
+    }
+
+    /// Returns the areas patched since the last call and forgets them.
+    pub fn take_changes(&mut self) -> Vec<Bound> {
+        ::std::mem::replace(&mut self.changes, vec![])
+    }
+
+    /// Moves all layers inside `area` so that `area` starts at `to`. Layers overlapping `area`
+    /// only partially are left alone. Returns `false` without changing anything if the moved
+    /// area doesn't fit into the `Region` or bytes were changed with `write`.
+    pub fn move_area(&mut self, area: &Bound, to: u64) -> bool {
+        let len = area.end - area.start;
+
+        if to.checked_add(len).map(|e| e > self.size).unwrap_or(true) || !self.pages.is_empty() {
+            return false;
+        }
+
+        let mv = |b: &Bound| Bound::new(b.start - area.start + to, b.end - area.start + to);
+
+        for &mut (ref mut b, _) in self.stack.iter_mut().skip(1) {
+            if area.start <= b.start && b.end <= area.end {
+                *b = mv(b);
+            }
+        }
+        for b in self.changes.iter_mut() {
+            if area.start <= b.start && b.end <= area.end {
+                *b = mv(b);
+            }
+        }
+        for s in self.sections.iter_mut() {
+            if area.start <= s.area.start && s.area.end <= area.end {
+                s.area = mv(&s.area);
+            }
+        }
+        true
+    }
+
+    /// Sections or segments of the file mapped into the `Region`, empty if the loader knows none.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Replaces the sections of the `Region`.
+    pub fn set_sections(&mut self, sections: Vec<Section>) {
+        self.sections = sections;
+    }
+
+    /// Adds a section to the `Region`.
+    pub fn add_section(&mut self, section: Section) {
+        self.sections.push(section);
+    }
+
+    /// Returns the section `address` is in. If sections overlap, the one added last wins.
+    pub fn section(&self, address: u64) -> Option<&Section> {
+        self.sections.iter().rev().find(|s| s.area.start <= address && address < s.area.end)
+    }
+
+    /// True if `address` can be read at run time. None if it isn't in any section.
+    pub fn is_readable(&self, address: u64) -> Option<bool> {
+        self.section(address).map(|s| s.read)
+    }
+
+    /// True if `address` can be written at run time. None if it isn't in any section.
+    pub fn is_writable(&self, address: u64) -> Option<bool> {
+        self.section(address).map(|s| s.write)
+    }
+
+    /// True if `address` can be executed. None if it isn't in any section.
+    pub fn is_executable(&self, address: u64) -> Option<bool> {
+        self.section(address).map(|s| s.execute)
+    }
+
+    /// Position of the byte at `address` in the file the `Region` was loaded from. None if it
+    /// isn't in any section or was zero filled by the loader.
+    pub fn file_offset(&self, address: u64) -> Option<u64> {
+        self.section(address).and_then(|s| if address - s.area.start < s.file_size { Some(s.file_offset + address - s.area.start) } else { None })
+    }
+
     /// Iterator over all `Cell`s, starting at 0.
     pub fn iter(&self) -> LayerIter {
+        let ret = self.iter_layers(&|info| info.enabled);
+
+        if self.pages.is_empty() {
+            ret
+        } else {
+            LayerIter::Paged { pages: &self.pages, mapped: Box::new(ret), pos: 0 }
+        }
+    }
+
+    fn iter_layers(&self, keep: &Fn(&LayerInfo) -> bool) -> LayerIter {
         let mut ret = self.stack[0].1.as_opaque().unwrap().iter();
 
-        for s in self.stack.iter().skip(1) {
+        for (i, s) in self.stack.iter().enumerate().skip(1) {
             let &(ref area, ref layer) = s;
 
+            if !keep(&self.layer_info(i)) {
+                continue;
+            }
+
             let src = ret.cut(&(area.start..area.end));
             assert_eq!(src.len(), area.end - area.start);
 
@@ -209,13 +553,102 @@ impl Region {
     /// Vector of all uncovered parts.
     pub fn flatten(&self) -> Vec<(Bound, &Layer)> {
         let mut ret = Vec::new();
-        for x in self.stack.iter() {
-            ret = Self::add((x.0.clone(), &x.1), ret);
+        for (i, x) in self.stack.iter().enumerate() {
+            if i == 0 || self.layer_info(i).enabled {
+                ret = Self::add((x.0.clone(), &x.1), ret);
+            }
         }
         ret.sort_by(|a, b| a.0.start.cmp(&b.0.start));
         ret
     }
 
+    /// Returns the `len` bytes starting at `address`. None if any of them is undefined or outside
+    /// the region.
+    pub fn read(&self, address: u64, len: usize) -> Option<Vec<u8>> {
+        match address.checked_add(len as u64) {
+            Some(end) if end <= self.size => self.iter().seek(address).take(len).collect(),
+            _ => None,
+        }
+    }
+
+    /// Reads a `size` byte unsigned integer at `address`. `size` can be at most 8.
+    pub fn read_uint(&self, address: u64, size: usize, endianess: Endianess) -> Option<u64> {
+        if size > 8 {
+            return None;
+        }
+
+        self.read(address, size).map(|b| assemble(&b, endianess))
+    }
+
+    /// Reads the byte at `address`.
+    pub fn read_u8(&self, address: u64) -> Option<u8> {
+        self.read_uint(address, 1, Endianess::Little).map(|x| x as u8)
+    }
+
+    /// Reads a 16 bit unsigned integer at `address`.
+    pub fn read_u16(&self, address: u64, endianess: Endianess) -> Option<u16> {
+        self.read_uint(address, 2, endianess).map(|x| x as u16)
+    }
+
+    /// Reads a 32 bit unsigned integer at `address`.
+    pub fn read_u32(&self, address: u64, endianess: Endianess) -> Option<u32> {
+        self.read_uint(address, 4, endianess).map(|x| x as u32)
+    }
+
+    /// Reads a 64 bit unsigned integer at `address`.
+    pub fn read_u64(&self, address: u64, endianess: Endianess) -> Option<u64> {
+        self.read_uint(address, 8, endianess)
+    }
+
+    /// Reads the byte at `address` as two's complement.
+    pub fn read_i8(&self, address: u64) -> Option<i8> {
+        self.read_u8(address).map(|x| x as i8)
+    }
+
+    /// Reads a 16 bit two's complement integer at `address`.
+    pub fn read_i16(&self, address: u64, endianess: Endianess) -> Option<i16> {
+        self.read_u16(address, endianess).map(|x| x as i16)
+    }
+
+    /// Reads a 32 bit two's complement integer at `address`.
+    pub fn read_i32(&self, address: u64, endianess: Endianess) -> Option<i32> {
+        self.read_u32(address, endianess).map(|x| x as i32)
+    }
+
+    /// Reads a 64 bit two's complement integer at `address`.
+    pub fn read_i64(&self, address: u64, endianess: Endianess) -> Option<i64> {
+        self.read_u64(address, endianess).map(|x| x as i64)
+    }
+
+    /// Reads an IEEE 754 single precision float at `address`.
+    pub fn read_f32(&self, address: u64, endianess: Endianess) -> Option<f32> {
+        self.read_u32(address, endianess).map(f32::from_bits)
+    }
+
+    /// Reads an IEEE 754 double precision float at `address`.
+    pub fn read_f64(&self, address: u64, endianess: Endianess) -> Option<f64> {
+        self.read_u64(address, endianess).map(f64::from_bits)
+    }
+
+    /// Reads a pointer of `machine` at `address`, using its pointer size and byte order.
+    pub fn read_pointer(&self, address: u64, machine: Machine) -> Option<u64> {
+        self.read_uint(address, machine.pointer_size(), machine.endianess())
+    }
+
+    /// Iterates over the `size` byte words inside `area`, see `Words`. A partial word at the end
+    /// is skipped.
+    pub fn words(&self, area: Bound, size: usize, endianess: Endianess) -> Words {
+        let end = area.end.min(self.size);
+        let start = area.start.min(end);
+
+        Words { bytes: self.iter().seek(start), address: start, end: end, size: size, endianess: endianess }
+    }
+
+    /// Iterates over the pointers of `machine` inside `area`.
+    pub fn pointers(&self, area: Bound, machine: Machine) -> Words {
+        self.words(area, machine.pointer_size(), machine.endianess())
+    }
+
     /// Stack of all `Layer` and covered area.
     pub fn stack(&self) -> &Vec<(Bound, Layer)> {
         &self.stack
@@ -232,6 +665,40 @@ impl Region {
     }
 }
 
+/// Combines `bytes` into an integer.
+fn assemble(bytes: &[u8], endianess: Endianess) -> u64 {
+    match endianess {
+        Endianess::Little => bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64),
+        Endianess::Big => bytes.iter().fold(0, |acc, &b| acc << 8 | b as u64),
+    }
+}
+
+/// Iterator over consecutive words of a `Region`, returned by `Region::words`. Yields the address
+/// of each word and its value, None if one of its bytes is undefined.
+pub struct Words<'a> {
+    bytes: LayerIter<'a>,
+    address: u64,
+    end: u64,
+    size: usize,
+    endianess: Endianess,
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = (u64, Option<u64>);
+
+    fn next(&mut self) -> Option<(u64, Option<u64>)> {
+        if self.size == 0 || self.size > 8 || self.end - self.address < self.size as u64 {
+            return None;
+        }
+
+        let addr = self.address;
+        let bytes = (&mut self.bytes).take(self.size).collect::<Option<Vec<u8>>>();
+
+        self.address += self.size as u64;
+        Some((addr, bytes.map(|b| assemble(&b, self.endianess))))
+    }
+}
+
 impl World {
     /// Creates a new `World` with a single `Region` `reg`
     pub fn new(reg: Region) -> World {
@@ -241,6 +708,33 @@ impl World {
         World { dependencies: g, root: b }
     }
 
+    /// Adds `reg` as a new address space that neither overlaps nor is overlapped by other
+    /// `Region`s.
+    pub fn add_space(&mut self, reg: Region) -> RegionRef {
+        self.dependencies.add_vertex(reg)
+    }
+
+    /// All address spaces, i.e. `Region`s that do not overlap another one. The root `Region` is
+    /// always the first.
+    pub fn spaces(&self) -> Vec<RegionRef> {
+        let mut ret = self.dependencies
+            .vertices()
+            .filter(|&v| v != self.root && self.dependencies.in_degree(v) == 0)
+            .collect::<Vec<_>>();
+
+        ret.sort();
+        ret.insert(0, self.root);
+        ret
+    }
+
+    /// Returns the address space named `name`.
+    pub fn space(&self, name: &str) -> Option<&Region> {
+        self.spaces()
+            .into_iter()
+            .filter_map(|v| self.dependencies.vertex_label(v))
+            .find(|r| r.name() == name)
+    }
+
     /// Vector of all `Region` in `self` and their uncovered area
     pub fn projection(&self) -> Vec<(Bound, RegionRef)> {
         let mut ret = Vec::<(Bound, RegionRef)>::new();
@@ -329,6 +823,19 @@ mod tests {
         assert_eq!(proj, expect);
     }
 
+    #[test]
+    fn address_spaces() {
+        let (r1, _, _, mut regs) = fixture();
+        let r4 = regs.add_space(Region::undefined("xdata".to_string(), 0x10000));
+        let r5 = regs.add_space(Region::undefined("idata".to_string(), 0x100));
+
+        assert_eq!(regs.spaces(), vec![r1, r4, r5]);
+        assert_eq!(regs.space("xdata").map(|r| r.size()), Some(0x10000));
+        assert_eq!(regs.space("base").map(|r| r.size()), Some(128));
+        assert!(regs.space("zlib").is_none());
+        assert_eq!(regs.projection().len(), 4);
+    }
+
     #[test]
     fn read_undefined() {
         let r1 = Region::undefined("test".to_string(), 128);
@@ -368,4 +875,136 @@ mod tests {
         assert_eq!(proj[5].0, Bound::new(134, 140));
         assert_eq!(proj[5].1.as_opaque().unwrap().iter().len(), 140);
     }
+
+    #[test]
+    fn patch() {
+        let mut st = Region::wrap("".to_string(), vec![1, 2, 3, 4, 5, 6]);
+
+        assert!(st.patch(2, vec![0x90, 0x90]));
+        assert!(!st.patch(5, vec![0x90, 0x90]));
+        assert!(!st.patch(0, vec![]));
+        assert_eq!(st.iter().collect::<Vec<_>>(), vec![Some(1), Some(2), Some(0x90), Some(0x90), Some(5), Some(6)]);
+        assert_eq!(st.changes(), &[Bound::new(2, 4)]);
+        assert_eq!(st.take_changes(), vec![Bound::new(2, 4)]);
+        assert!(st.changes().is_empty());
+    }
+
+    #[test]
+    fn sections() {
+        let mut st = Region::undefined("RAM".to_string(), 0x1000);
+        let text = Section {
+            name: ".text".to_string(),
+            area: Bound::new(0x100, 0x200),
+            file_size: 0x100,
+            file_offset: 0x400,
+            read: true,
+            write: false,
+            execute: true,
+        };
+        let bss = Section { name: ".bss".to_string(), area: Bound::new(0x200, 0x300), file_size: 0, file_offset: 0, read: true, write: true, execute: false };
+
+        st.set_sections(vec![text]);
+        st.add_section(bss);
+
+        assert_eq!(st.section(0x180).map(|s| s.name.as_str()), Some(".text"));
+        assert_eq!(st.is_executable(0x180), Some(true));
+        assert_eq!(st.is_writable(0x280), Some(true));
+        assert_eq!(st.is_readable(0x300), None);
+        assert_eq!(st.file_offset(0x110), Some(0x410));
+        assert_eq!(st.file_offset(0x210), None);
+
+        assert!(st.move_area(&Bound::new(0, 0x300), 0x800));
+        assert_eq!(st.section(0x910).map(|s| s.name.as_str()), Some(".text"));
+        assert_eq!(st.file_offset(0x910), Some(0x410));
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut st = Region::wrap("RAM".to_string(), vec![0; 0x2000]);
+        let empty = st.snapshot();
+
+        assert!(st.write(0xffe, &[1, 2, 3, 4]));
+        assert!(!st.write(0x1fff, &[1, 2]));
+        assert_eq!(st.read(0xffd, 6), Some(vec![0, 1, 2, 3, 4, 0]));
+        assert_eq!(st.original().seek(0xffe).next(), Some(Some(0)));
+        assert_eq!(st.diff().len(), 4);
+
+        let snap = st.snapshot();
+        assert!(st.write(0x1000, &[5]));
+        assert!(st.patch(0x10, vec![6]));
+        assert_eq!(st.read(0xffe, 4), Some(vec![1, 2, 5, 4]));
+        assert!(!st.move_area(&Bound::new(0, 0x100), 0x100));
+
+        assert!(st.restore(&snap));
+        assert_eq!(st.read(0xffe, 4), Some(vec![1, 2, 3, 4]));
+        assert_eq!(st.read_u8(0x10), Some(0));
+        assert_eq!(st.layer_names(), vec![ORIGINAL_LAYER.to_string()]);
+
+        assert!(st.restore(&empty));
+        assert_eq!(st.read(0xffe, 4), Some(vec![0, 0, 0, 0]));
+        assert!(st.write(0, &[7]));
+        st.discard_writes();
+        assert_eq!(st.read_u8(0), Some(0));
+
+        assert!(st.write(0x20, &[8]));
+        assert!(st.patch(0x21, vec![9]));
+        assert_eq!(st.read(0x20, 2), Some(vec![8, 9]));
+        assert_eq!(st.set_enabled(PATCH_LAYER, false), 1);
+        assert_eq!(st.read(0x20, 2), Some(vec![8, 0]));
+        assert!(st.patch(0x20, vec![10]));
+        assert_eq!(st.read_u8(0x20), Some(8));
+        assert_eq!(st.set_enabled(PATCH_LAYER, true), 1);
+        assert_eq!(st.read(0x20, 2), Some(vec![8, 9]));
+    }
+
+    #[test]
+    fn typed_reads() {
+        let mut st = Region::undefined("RAM".to_string(), 0x20);
+        assert!(st.cover(Bound::new(0, 8), Layer::wrap(vec![0x01, 0x02, 0x03, 0x84, 0x00, 0x00, 0x80, 0x3f])));
+
+        assert_eq!(st.read(6, 2), Some(vec![0x80, 0x3f]));
+        assert_eq!(st.read(6, 3), None);
+        assert_eq!(st.read(0x1f, 2), None);
+        assert_eq!(st.read_u8(3), Some(0x84));
+        assert_eq!(st.read_i8(3), Some(-0x7c));
+        assert_eq!(st.read_u16(0, Endianess::Little), Some(0x0201));
+        assert_eq!(st.read_u16(0, Endianess::Big), Some(0x0102));
+        assert_eq!(st.read_u32(0, Endianess::Little), Some(0x8403_0201));
+        assert_eq!(st.read_i32(0, Endianess::Big), Some(0x0102_0384));
+        assert_eq!(st.read_i16(2, Endianess::Little), Some(-0x7bfd));
+        assert_eq!(st.read_f32(4, Endianess::Little), Some(1.0));
+        assert_eq!(st.read_u64(0, Endianess::Little), Some(0x3f80_0000_8403_0201));
+        assert_eq!(st.read_pointer(4, Machine::Ia32), Some(0x3f80_0000));
+        assert_eq!(st.read_pointer(6, Machine::Mcs51), Some(0x3f80));
+        assert_eq!(st.read_pointer(6, Machine::Amd64), None);
+
+        let words = st.words(Bound::new(4, 0x100), 2, Endianess::Big).collect::<Vec<_>>();
+        assert_eq!(&words[0..3], &[(4, Some(0)), (6, Some(0x803f)), (8, None)]);
+        assert_eq!(words.len(), 14);
+        assert_eq!(st.pointers(Bound::new(0, 7), Machine::M68k).collect::<Vec<_>>(), vec![(0, Some(0x0102_0384))]);
+    }
+
+    #[test]
+    fn layer_provenance() {
+        let mut st = Region::undefined("RAM".to_string(), 8);
+
+        assert!(st.cover(Bound::new(0, 6), Layer::wrap(vec![1, 2, 3, 4, 5, 6])));
+        assert!(st.cover_named(RELOCATION_LAYER, Bound::new(0, 2), Layer::wrap(vec![0x10, 0x20])));
+        assert!(st.patch(1, vec![0x90, 0x90]));
+
+        assert_eq!(st.layer_names(), vec![ORIGINAL_LAYER.to_string(), RELOCATION_LAYER.to_string(), PATCH_LAYER.to_string()]);
+        assert_eq!(st.iter().take(4).collect::<Vec<_>>(), vec![Some(0x10), Some(0x90), Some(0x90), Some(4)]);
+        assert_eq!(st.provenance(0), Some(RELOCATION_LAYER.to_string()));
+        assert_eq!(st.provenance(2), Some(PATCH_LAYER.to_string()));
+        assert_eq!(st.provenance(5), Some(ORIGINAL_LAYER.to_string()));
+        assert_eq!(st.provenance(8), None);
+        assert_eq!(st.diff(), vec![(0, Some(1), Some(0x10)), (1, Some(2), Some(0x90)), (2, Some(3), Some(0x90))]);
+
+        assert_eq!(st.set_enabled(PATCH_LAYER, false), 1);
+        assert_eq!(st.set_enabled(PATCH_LAYER, false), 0);
+        assert_eq!(st.iter().take(4).collect::<Vec<_>>(), vec![Some(0x10), Some(0x20), Some(3), Some(4)]);
+        assert_eq!(st.provenance(2), Some(ORIGINAL_LAYER.to_string()));
+        assert_eq!(st.diff(), vec![(0, Some(1), Some(0x10)), (1, Some(2), Some(0x20))]);
+        assert_eq!(st.original().take(2).collect::<Vec<_>>(), vec![Some(1), Some(2)]);
+    }
 }
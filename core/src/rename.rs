@@ -0,0 +1,311 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Renaming of functions, imports and global variables.
+//!
+//! A name is stored in several places: the call graph node, the aliases of a function, the
+//! import tables, the symbols of relocations used to print operands and the labels in
+//! `Project::annotations`. [`rename_function`] and [`rename_global`] update all of them, so
+//! every view and export shows the new name.
+//!
+//! Names are unique in a project. If the new name is already taken, [`Collision`] decides
+//! whether renaming fails or a numeric suffix is appended, like `main_1`.
+//!
+//! [`rename_function`]: fn.rename_function.html
+//! [`rename_global`]: fn.rename_global.html
+//! [`Collision`]: enum.Collision.html
+
+use {CallTarget, ControlFlowTarget, Function, FunctionKind, Location, NameSource, Project, Result, Rvalue};
+use panopticon_graph_algos::{GraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// What to do if a new name is already used.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Collision {
+    /// Fail with an error
+    Fail,
+    /// Append `_1`, `_2`, ... until the name is unique
+    Suffix,
+}
+
+/// All names used in `proj`, except those of the call graph node `except`.
+fn names(proj: &Project, except: Option<&Uuid>) -> HashSet<String> {
+    let mut ret = HashSet::new();
+
+    for prog in proj.code.iter() {
+        for ct in prog.call_graph.vertex_labels() {
+            if Some(ct.uuid()) == except {
+                continue;
+            }
+
+            match ct {
+                &CallTarget::Concrete(ref f) => {
+                    ret.insert(f.name.clone());
+                    ret.extend(f.aliases().iter().cloned());
+                }
+                &CallTarget::Symbolic(ref name, _) => {
+                    ret.insert(name.clone());
+                }
+                &CallTarget::Todo(_, Some(ref name), _) => {
+                    ret.insert(name.clone());
+                }
+                &CallTarget::Todo(_, None, _) => {}
+            }
+        }
+    }
+
+    ret.extend(proj.annotations.iter().filter_map(|(_, a)| a.label.clone()));
+    ret
+}
+
+/// Returns `name` if it isn't in `used`, otherwise applies `policy`.
+fn unique(name: &str, used: &HashSet<String>, policy: Collision) -> Result<String> {
+    if name.is_empty() {
+        return Err("names can't be empty".into());
+    }
+    if !used.contains(name) {
+        return Ok(name.to_string());
+    }
+
+    match policy {
+        Collision::Fail => Err(format!("{} is already used", name).into()),
+        Collision::Suffix => Ok((1..).map(|i| format!("{}_{}", name, i)).find(|n| !used.contains(n)).unwrap()),
+    }
+}
+
+/// Returns true if `name` is used by a function, import or label of `proj`.
+pub fn is_used(proj: &Project, name: &str) -> bool {
+    names(proj, None).contains(name)
+}
+
+fn entry(func: &Function) -> Option<u64> {
+    match func.cfg().vertex_label(func.entry_point_ref()) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => Some(bb.area.start),
+        _ => None,
+    }
+}
+
+/// Renames the function, import or not yet disassembled function with UUID `uuid` to `name`.
+/// Returns the name given, which differs from `name` if a suffix was added.
+///
+/// Renamed functions keep their old name as alias, unless it was made up by panopticon. Renaming
+/// an import renames the PLT stubs and import table entries bound to it as well.
+pub fn rename_function(proj: &mut Project, uuid: &Uuid, name: &str, policy: Collision) -> Result<String> {
+    let new = unique(name, &names(proj, Some(uuid)), policy)?;
+    let mut old = None;
+    let mut start = None;
+    let mut import = false;
+
+    for prog in proj.code.iter_mut() {
+        for ct in prog.call_graph.vertex_labels_mut() {
+            if ct.uuid() != uuid {
+                continue;
+            }
+
+            match ct {
+                &mut CallTarget::Concrete(ref mut f) => {
+                    let source = f.name_source();
+                    let prev = ::std::mem::replace(&mut f.name, new.clone());
+
+                    start = entry(f);
+                    f.remove_alias(&new);
+                    f.set_name_source(NameSource::User);
+                    if Some(prev.clone()) != start.map(|s| format!("func_{:#x}", s)) && !f.aliases().contains(&prev) && prev != new {
+                        f.add_alias_from(prev.clone(), source);
+                    }
+                    old = Some(prev);
+                }
+                &mut CallTarget::Symbolic(ref mut n, _) => {
+                    old = Some(::std::mem::replace(n, new.clone()));
+                    import = true;
+                }
+                &mut CallTarget::Todo(ref rv, ref mut n, _) => {
+                    old = ::std::mem::replace(n, Some(new.clone()));
+                    if let &Rvalue::Constant { value, .. } = rv {
+                        start = Some(value);
+                    }
+                }
+            }
+        }
+    }
+
+    let old = match old {
+        Some(old) => old,
+        None if start.is_some() => String::new(),
+        None => return Err(format!("no function with UUID {}", uuid).into()),
+    };
+
+    if import {
+        for prog in proj.code.iter_mut() {
+            for f in prog.functions_mut() {
+                let plt = match f.kind() {
+                    &FunctionKind::Stub { ref name, plt_address } if *name == old => Some(plt_address),
+                    _ => None,
+                };
+
+                if let Some(plt) = plt {
+                    let aliases = f.aliases().to_vec();
+
+                    f.set_plt(&new, plt);
+                    // set_plt moves the old stub name into the aliases
+                    f.remove_alias(&format!("{}@plt", old));
+                    if aliases.len() + 1 == f.aliases().len() {
+                        f.remove_alias(&format!("{}@plt", new));
+                    }
+                    f.set_name_source(NameSource::User);
+                }
+            }
+            for n in prog.imports.values_mut().filter(|n| **n == old) {
+                *n = new.clone();
+            }
+        }
+        for n in proj.imports.values_mut().filter(|n| **n == old) {
+            *n = new.clone();
+        }
+    }
+
+    if !old.is_empty() {
+        for prog in proj.code.iter_mut() {
+            for r in prog.relocations.values_mut() {
+                if r.symbol.as_ref() == Some(&old) && (import || r.target == start) {
+                    r.symbol = Some(new.clone());
+                }
+            }
+        }
+        for r in proj.relocations.values_mut() {
+            if r.symbol.as_ref() == Some(&old) && (import || r.target == start) {
+                r.symbol = Some(new.clone());
+            }
+        }
+    }
+
+    if let Some(start) = start {
+        if proj.annotations.label(&Location::Address(start)).is_some() {
+            proj.annotations.set_label(Location::Address(start), new.clone());
+        }
+    }
+
+    Ok(new)
+}
+
+/// Names the global variable or code location at `address` `name`. If a function starts there,
+/// the function is renamed instead. Returns the name given.
+pub fn rename_global(proj: &mut Project, address: u64, name: &str, policy: Collision) -> Result<String> {
+    let func = proj.code
+        .iter()
+        .filter_map(|p| p.functions().find(|f| entry(f) == Some(address)).map(|f| f.uuid().clone()))
+        .next();
+
+    if let Some(uuid) = func {
+        return rename_function(proj, &uuid, name, policy);
+    }
+
+    let loc = Location::Address(address);
+    let mut used = names(proj, None);
+
+    if let Some(l) = proj.annotations.label(&loc) {
+        used.remove(l);
+    }
+
+    let new = unique(name, &used, policy)?;
+    let old = proj.annotations.set_label(loc, new.clone());
+
+    if let Some(old) = old {
+        for prog in proj.code.iter_mut() {
+            for r in prog.relocations.values_mut() {
+                if r.symbol.as_ref() == Some(&old) && r.target == Some(address) {
+                    r.symbol = Some(new.clone());
+                }
+            }
+        }
+        for r in proj.relocations.values_mut() {
+            if r.symbol.as_ref() == Some(&old) && r.target == Some(address) {
+                r.symbol = Some(new.clone());
+            }
+        }
+    }
+
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Mnemonic, Program, Region, Relocation};
+
+    fn function(start: u64, name: Option<&str>, region: &Region) -> Function {
+        let mne = Mnemonic::new(start..start + 1, "ret".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, region, name.map(|n| n.to_string()));
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    #[test]
+    fn rename() {
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let mut prog = Program::new("prog");
+        let main = function(0x100, None, proj.region());
+        let helper = function(0x200, Some("helper"), proj.region());
+        let mut stub = function(0x300, Some("stub"), proj.region());
+        let (main_uuid, helper_uuid) = (main.uuid().clone(), helper.uuid().clone());
+        let puts = Uuid::new_v4();
+
+        stub.set_plt("puts", 0x800);
+        prog.insert(main);
+        prog.insert(helper);
+        prog.insert(stub);
+        prog.call_graph.add_vertex(CallTarget::Symbolic("puts".to_string(), puts.clone()));
+        proj.code.push(prog);
+        proj.imports.insert(0x800, "puts".to_string());
+        proj.relocations.insert(0x800, Relocation { target: None, symbol: Some("puts".to_string()) });
+        proj.relocations.insert(0x900, Relocation { target: Some(0x200), symbol: Some("helper".to_string()) });
+
+        assert_eq!(rename_function(&mut proj, &main_uuid, "main", Collision::Fail).ok(), Some("main".to_string()));
+        assert!(proj.find_function_by_uuid(&main_uuid).unwrap().aliases().is_empty());
+        assert!(rename_function(&mut proj, &helper_uuid, "main", Collision::Fail).is_err());
+        assert_eq!(rename_function(&mut proj, &helper_uuid, "main", Collision::Suffix).ok(), Some("main_1".to_string()));
+        assert_eq!(proj.find_function_by_uuid(&helper_uuid).unwrap().aliases(), &["helper".to_string()]);
+        assert_eq!(proj.find_function_by_uuid(&helper_uuid).unwrap().name_source(), NameSource::User);
+        assert_eq!(proj.relocations[&0x900].symbol, Some("main_1".to_string()));
+        assert_eq!(rename_function(&mut proj, &main_uuid, "main", Collision::Fail).ok(), Some("main".to_string()));
+
+        assert!(rename_function(&mut proj, &puts, "my_puts", Collision::Fail).is_ok());
+        assert_eq!(proj.imports[&0x800], "my_puts");
+        assert_eq!(proj.relocations[&0x800].symbol, Some("my_puts".to_string()));
+        assert!(
+            proj.code[0].functions().any(
+                |f| match f.kind() {
+                    &FunctionKind::Stub { ref name, plt_address: 0x800 } => name == "my_puts" && f.name == "my_puts@plt",
+                    _ => false,
+                }
+            )
+        );
+
+        assert_eq!(rename_global(&mut proj, 0x700, "counter", Collision::Fail).ok(), Some("counter".to_string()));
+        assert_eq!(rename_global(&mut proj, 0x700, "counter", Collision::Fail).ok(), Some("counter".to_string()));
+        assert_eq!(rename_global(&mut proj, 0x710, "counter", Collision::Suffix).ok(), Some("counter_1".to_string()));
+        assert_eq!(rename_global(&mut proj, 0x100, "start", Collision::Fail).ok(), Some("start".to_string()));
+        assert_eq!(proj.find_function_by_uuid(&main_uuid).unwrap().aliases(), &["main".to_string()]);
+        assert!(is_used(&proj, "start") && !is_used(&proj, "helper2"));
+    }
+}
@@ -0,0 +1,358 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Replays execution traces on the IL.
+//!
+//! [`Replay::new`] walks a trace imported with `coverage::read` over the disassembled mnemonics
+//! and evaluates their RREIL statements one after another, starting with the register values of
+//! a `gdb::Snapshot` or none at all. Every value assigned and every byte stored is recorded, so
+//! the state of the program can be looked up at any point of the trace afterwards:
+//!
+//! ```ignore
+//! let trace = coverage::read(&proj, Path::new("trace.txt"), None)?;
+//! let replay = Replay::new(&proj, &trace, &snapshot.seeds());
+//!
+//! // RSI before the 3rd statement of the 2nd execution of the mnemonic at 0x401000
+//! let step = replay.steps_at(0x401000)[1];
+//! let rsi = replay.value(Position { step: step, statement: 2 }, "RSI");
+//! ```
+//!
+//! Values depending on something outside the project, like memory not mapped by any region or
+//! the result of a call into an untraced library, are `Rvalue::Undefined`. So is everything
+//! computed from them. Statements are evaluated with `il::execute`, loads read the bytes stored
+//! earlier in the trace and fall back to `Project::region`.
+//!
+//! [`Replay::new`]: struct.Replay.html#method.new
+
+use {Endianess, Lvalue, Mnemonic, Operation, Project, Rvalue, execute};
+use il::lift;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+/// A point in a replayed trace: the statement with index `statement` of the mnemonic executed in
+/// step `step`.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct Position {
+    /// Index of the mnemonic in the trace
+    pub step: usize,
+    /// Index of the statement in `Mnemonic::instructions`
+    pub statement: usize,
+}
+
+/// Variable values and memory contents along a trace.
+#[derive(Clone,Debug)]
+pub struct Replay {
+    steps: Vec<u64>,
+    initial: HashMap<Cow<'static, str>, Rvalue>,
+    variables: HashMap<Cow<'static, str>, Vec<(Position, Rvalue)>>,
+    memory: BTreeMap<u64, Vec<(Position, Option<u8>)>>,
+    /// Number of trace entries that matched no mnemonic
+    pub unmapped: usize,
+}
+
+impl Replay {
+    /// Executes the mnemonics of `proj` in the order of `trace`. Variables in `seeds` start with
+    /// the given value, all others are undefined.
+    pub fn new(proj: &Project, trace: &[(u64, u64)], seeds: &HashMap<(Cow<'static, str>, usize), Rvalue>) -> Replay {
+        let mut mnemonics = BTreeMap::<u64, &Mnemonic>::new();
+
+        for prog in proj.code.iter() {
+            for func in prog.functions() {
+                for bb in func.basic_blocks() {
+                    for mne in bb.mnemonics.iter() {
+                        mnemonics.entry(mne.area.start).or_insert(mne);
+                    }
+                }
+            }
+        }
+
+        let mut replay = Replay {
+            steps: vec![],
+            initial: seeds.iter().map(|(&(ref name, _), val)| (name.clone(), val.clone())).collect(),
+            variables: HashMap::new(),
+            memory: BTreeMap::new(),
+            unmapped: 0,
+        };
+        let mut env = replay.initial.clone();
+        let mut stores = HashMap::<u64, Option<u8>>::new();
+
+        for &(start, len) in trace {
+            let mut found = false;
+
+            for (_, mne) in mnemonics.range(start..start.saturating_add(len.max(1))) {
+                let step = replay.steps.len();
+
+                found = true;
+                replay.steps.push(mne.area.start);
+
+                for (idx, stmt) in mne.instructions.iter().enumerate() {
+                    let pos = Position { step: step, statement: idx };
+                    let op = lift(&stmt.op, &|rv| read_variable(&env, rv));
+                    let load = |address: u64| match stores.get(&address) {
+                        Some(&b) => b,
+                        None => proj.region().read_u8(address),
+                    };
+                    let value = match op {
+                        Operation::Load(_, endianess, size, Rvalue::Constant { value: address, .. }) => {
+                            let bytes = (0..(size as u64 + 7) / 8).map(|i| load(address.wrapping_add(i))).collect::<Option<Vec<u8>>>();
+
+                            match bytes {
+                                Some(bytes) => constant(assemble(&bytes, endianess), size),
+                                None => Rvalue::Undefined,
+                            }
+                        }
+                        Operation::Store(_, endianess, size, Rvalue::Constant { value: address, .. }, ref val) => {
+                            let bytes = (size + 7) / 8;
+
+                            for i in 0..bytes {
+                                let shift = match endianess {
+                                    Endianess::Little => i * 8,
+                                    Endianess::Big => (bytes - i - 1) * 8,
+                                };
+                                let byte = match val {
+                                    &Rvalue::Constant { value, .. } if shift < 64 => Some((value >> shift) as u8),
+                                    _ => None,
+                                };
+                                let address = address.wrapping_add(i as u64);
+
+                                stores.insert(address, byte);
+                                replay.memory.entry(address).or_insert(vec![]).push((pos, byte));
+                            }
+                            continue;
+                        }
+                        op => {
+                            match execute(op) {
+                                Rvalue::Constant { value, size } => constant(value, size),
+                                _ => Rvalue::Undefined,
+                            }
+                        }
+                    };
+
+                    if let Lvalue::Variable { ref name, size, .. } = stmt.assignee {
+                        let value = match value {
+                            Rvalue::Constant { value, .. } => constant(value, size),
+                            v => v,
+                        };
+
+                        env.insert(name.clone(), value.clone());
+                        replay.variables.entry(name.clone()).or_insert(vec![]).push((pos, value));
+                    }
+                }
+            }
+
+            if !found {
+                replay.unmapped += 1;
+            }
+        }
+
+        replay
+    }
+
+    /// Number of mnemonics executed.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// True if no mnemonic was executed.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Address of the mnemonic executed in step `step`.
+    pub fn address(&self, step: usize) -> Option<u64> {
+        self.steps.get(step).cloned()
+    }
+
+    /// Steps executing the mnemonic starting at `address`, in order.
+    pub fn steps_at(&self, address: u64) -> Vec<usize> {
+        self.steps.iter().enumerate().filter(|&(_, &a)| a == address).map(|(i, _)| i).collect()
+    }
+
+    /// Value of the variable `name` right before the statement at `pos` is executed. None if it
+    /// was never assigned and isn't one of the seeds.
+    pub fn value(&self, pos: Position, name: &str) -> Option<&Rvalue> {
+        let history = self.variables.get(name).map(|h| &h[..]).unwrap_or(&[]);
+
+        match history.binary_search_by(|&(p, _)| p.cmp(&pos)) {
+            Ok(0) | Err(0) => self.initial.get(name),
+            Ok(i) | Err(i) => Some(&history[i - 1].1),
+        }
+    }
+
+    /// Value assigned by the statement at `pos`, if it has a variable as assignee.
+    pub fn assigned(&self, pos: Position) -> Option<(&str, &Rvalue)> {
+        for (name, history) in self.variables.iter() {
+            if let Ok(i) = history.binary_search_by(|&(p, _)| p.cmp(&pos)) {
+                return Some((&*name, &history[i].1));
+            }
+        }
+
+        None
+    }
+
+    /// Byte at `address` right before the statement at `pos` is executed. None if the trace
+    /// didn't store anything there up to this point, `Project::region` has the original contents
+    /// then. `Some(None)` if the byte was overwritten with an unknown value.
+    pub fn memory(&self, pos: Position, address: u64) -> Option<Option<u8>> {
+        let history = match self.memory.get(&address) {
+            Some(h) => h,
+            None => return None,
+        };
+
+        match history.binary_search_by(|&(p, _)| p.cmp(&pos)) {
+            Ok(0) | Err(0) => None,
+            Ok(i) | Err(i) => Some(history[i - 1].1),
+        }
+    }
+
+    /// Positions where the variable `name` was assigned, with the new value.
+    pub fn history(&self, name: &str) -> &[(Position, Rvalue)] {
+        self.variables.get(name).map(|h| &h[..]).unwrap_or(&[])
+    }
+}
+
+/// Replaces variables with their current value.
+fn read_variable(env: &HashMap<Cow<'static, str>, Rvalue>, rv: &Rvalue) -> Rvalue {
+    match rv {
+        &Rvalue::Variable { ref name, offset, size, .. } => {
+            match env.get(name) {
+                Some(&Rvalue::Constant { value, .. }) if offset < 64 => constant(value >> offset, size),
+                _ => Rvalue::Undefined,
+            }
+        }
+        rv => rv.clone(),
+    }
+}
+
+/// Constant `value` truncated to `size` bits.
+fn constant(value: u64, size: usize) -> Rvalue {
+    let mask = if size < 64 { (1u64 << size) - 1 } else { u64::max_value() };
+
+    Rvalue::Constant { value: value & mask, size: size }
+}
+
+fn assemble(bytes: &[u8], endianess: Endianess) -> u64 {
+    match endianess {
+        Endianess::Little => bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64),
+        Endianess::Big => bytes.iter().fold(0, |acc, &b| acc << 8 | b as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Guard, Program, Region, Statement};
+    use gdb::{RegisterValue, Snapshot};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn var(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn mnemonic(start: u64, stmts: Vec<Statement>) -> Mnemonic {
+        Mnemonic::new(start..start + 2, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap()
+    }
+
+    /*
+     * 0x100: rsi = rsi + [0x10]
+     * 0x102: [0x20] = rsi; rdi = rdi - 1
+     * 0x104: jnz 0x100
+     */
+    fn project() -> Project {
+        let mut proj = Project::new("test".to_string(), Region::wrap("RAM".to_string(), vec![3; 0x200]));
+        let rsi = var("RSI", 64);
+        let rdi = var("RDI", 64);
+        let tmp = var("tmp", 64);
+        let ram = Cow::Borrowed("RAM");
+        let add = mnemonic(
+            0x100,
+            vec![
+                Statement { op: Operation::Load(ram.clone(), Endianess::Little, 64, Rvalue::new_u64(0x10)), assignee: tmp.clone() },
+                Statement { op: Operation::Add(rsi.clone().into(), tmp.clone().into()), assignee: rsi.clone() },
+            ]
+        );
+        let store = mnemonic(
+            0x102,
+            vec![
+                Statement { op: Operation::Store(ram.clone(), Endianess::Little, 16, Rvalue::new_u64(0x20), rsi.clone().into()), assignee: Lvalue::Undefined },
+                Statement { op: Operation::Subtract(rdi.clone().into(), Rvalue::new_u64(1)), assignee: rdi.clone() },
+                Statement { op: Operation::Equal(rdi.clone().into(), Rvalue::new_u64(0)), assignee: var("ZF", 1) },
+            ]
+        );
+        let jmp = mnemonic(0x104, vec![]);
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![add, store, jmp])));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        let mut prog = Program::new("prog");
+
+        cfg.add_edge(Guard::True, b0, b0);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        prog.insert(func);
+        proj.code.push(prog);
+        proj
+    }
+
+    #[test]
+    fn time_travel() {
+        let proj = project();
+        let snapshot = Snapshot {
+            registers: vec![
+                RegisterValue { name: "rsi".to_string(), size: 64, value: Some(1) },
+                RegisterValue { name: "rdi".to_string(), size: 64, value: Some(2) },
+            ],
+            memory_map: vec![],
+            libraries: vec![],
+            text_offset: None,
+        };
+        let trace = vec![(0x100, 6), (0x100, 1), (0x102, 1), (0x104, 1), (0x300, 1)];
+        let replay = Replay::new(&proj, &trace, &snapshot.seeds());
+        let mem = 0x0303030303030303;
+
+        assert_eq!(replay.len(), 6);
+        assert_eq!(replay.unmapped, 1);
+        assert_eq!(replay.steps_at(0x100), vec![0, 3]);
+        assert_eq!(replay.address(4), Some(0x102));
+
+        // RSI at the second statement of the second execution of 0x100
+        let second = Position { step: 3, statement: 1 };
+        assert_eq!(replay.value(Position { step: 0, statement: 0 }, "RSI"), Some(&Rvalue::new_u64(1)));
+        assert_eq!(replay.value(second, "RSI"), Some(&Rvalue::new_u64(1 + mem)));
+        assert_eq!(replay.value(second, "tmp"), Some(&Rvalue::new_u64(mem)));
+        assert_eq!(replay.assigned(second), Some(("RSI", &Rvalue::new_u64(1 + 2 * mem))));
+        assert_eq!(replay.value(Position { step: 5, statement: 0 }, "ZF"), Some(&Rvalue::new_bit(1)));
+        assert_eq!(replay.value(second, "RAX"), None);
+        assert_eq!(replay.history("RDI").len(), 2);
+
+        assert_eq!(replay.memory(Position { step: 1, statement: 0 }, 0x20), None);
+        assert_eq!(replay.memory(Position { step: 1, statement: 1 }, 0x20), Some(Some(0x04)));
+        assert_eq!(replay.memory(Position { step: 6, statement: 0 }, 0x21), Some(Some(0x06)));
+        assert_eq!(replay.memory(Position { step: 6, statement: 0 }, 0x22), None);
+    }
+
+    #[test]
+    fn unknown_values() {
+        let proj = project();
+        let replay = Replay::new(&proj, &[(0x100, 4)], &HashMap::new());
+        let end = Position { step: 2, statement: 0 };
+
+        assert_eq!(replay.value(end, "RSI"), Some(&Rvalue::Undefined));
+        assert_eq!(replay.value(end, "tmp"), Some(&Rvalue::new_u64(0x0303030303030303)));
+        assert_eq!(replay.memory(end, 0x20), Some(None));
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Self-contained HTML reports.
+//!
+//! [`html`] renders a project as a single HTML file without scripts or external resources, so
+//! analysis results can be shared with people who don't have Panopticon installed. The report
+//! starts with the list of functions of each program, followed by a section per function with
+//! its control flow graph drawn as inline SVG and its listing. Calls link to the section of
+//! the callee and references to string literals link to the list of strings at the end, which
+//! in turn links back to the instructions using them.
+//!
+//! Listings are formatted by `listing::instruction` and graphs are drawn by `svg::graph`, so
+//! they look the same as in the other front-ends. If execution traces have been imported, basic
+//! blocks are shaded from yellow to red by how often they were entered.
+//!
+//! [`html`]: fn.html.html
+
+use {Function, Program, Project, XrefKind, listing, svg};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const STYLE: &'static str = "body { font-family: sans-serif; margin: 2em; }\n\
+pre { font-family: monospace; font-size: 12px; background: #f6f6f6; padding: 1em; overflow-x: auto; }\n\
+a { color: #0645ad; text-decoration: none; }\n\
+.ref { color: #666; }\n";
+
+/// Escapes `s` for use in HTML text and attribute values.
+pub fn escape(s: &str) -> String {
+    svg::escape(s)
+}
+
+/// Returns the control flow graph of `func` as SVG, drawn by `svg::graph`.
+pub fn cfg_svg(proj: &Project, prog: &Program, func: &Function) -> String {
+    match svg::graph(proj, prog, func) {
+        Ok(svg) => svg,
+        Err(e) => format!("<p class=\"ref\">No graph: {}</p>\n", escape(&e.to_string())),
+    }
+}
+
+/// Listing of `func` with links to callees and string literals.
+fn function_listing(proj: &Project, prog: &Program, func: &Function) -> String {
+    let mut mnes = func.basic_blocks().flat_map(|bb| bb.mnemonics.iter()).collect::<Vec<_>>();
+    let mut refs = HashMap::new();
+    let mut ret = String::new();
+
+    for x in proj.xrefs_from(func.uuid()) {
+        refs.entry(x.address).or_insert(vec![]).push(x);
+    }
+
+    mnes.sort_by_key(|m| (m.area.start, m.area.end));
+    mnes.dedup_by_key(|m| (m.area.start, m.area.end));
+
+    for mne in mnes {
+        let mut links = vec![];
+
+        for x in refs.get(&mne.area.start).map(|v| &v[..]).unwrap_or(&[]) {
+            match x.kind {
+                XrefKind::Call | XrefKind::Jump => {
+                    if let Some(f) = prog.find_function_by(|f| f.start() == x.target) {
+                        links.push(format!("<a href=\"#f_{:x}\">{}</a>", x.target, escape(&f.display_name())));
+                    }
+                }
+                XrefKind::Read | XrefKind::Address => {
+                    if let Some(s) = proj.strings.get(&x.target) {
+                        links.push(format!("<a href=\"#s_{:x}\">{}</a>", x.target, escape(&format!("{}", s))));
+                    }
+                }
+                XrefKind::Write => {}
+            }
+        }
+
+        links.dedup();
+
+        let _ = write!(ret, "<span id=\"a_{:x}\">{:8x}:  {}</span>", mne.area.start, mne.area.start, escape(&listing::instruction(proj, prog, mne)));
+        if !links.is_empty() {
+            let _ = write!(ret, "  <span class=\"ref\">; {}</span>", links.join(", "));
+        }
+        ret.push('\n');
+    }
+
+    ret
+}
+
+/// Returns a self-contained HTML report of `proj`.
+pub fn html(proj: &Project) -> String {
+    let mut ret = String::new();
+    let title = escape(&proj.name);
+
+    let _ = writeln!(ret, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>", title, STYLE);
+    let _ = writeln!(ret, "<h1>{}</h1>", title);
+
+    for prog in proj.code.iter() {
+        let mut funcs = prog.functions().collect::<Vec<_>>();
+
+        funcs.sort_by_key(|f| f.start());
+
+        let _ = writeln!(ret, "<h2>{}</h2>\n<ul>", escape(&prog.name));
+        for f in funcs.iter() {
+            let _ = writeln!(ret, "<li><a href=\"#f_{:x}\">{:#x} {}</a></li>", f.start(), f.start(), escape(&f.display_name()));
+        }
+        let _ = writeln!(ret, "</ul>");
+
+        for f in funcs.iter() {
+            let _ = writeln!(ret, "<h3 id=\"f_{:x}\">{}</h3>", f.start(), escape(&f.display_name()));
+            ret.push_str(&cfg_svg(proj, prog, f));
+            let _ = writeln!(ret, "<pre>{}</pre>", function_listing(proj, prog, f));
+        }
+    }
+
+    if !proj.strings.is_empty() {
+        let _ = writeln!(ret, "<h2>Strings</h2>\n<ul>");
+        for (&address, s) in proj.strings.iter() {
+            let mut users = proj.xrefs_to(address).iter().map(|x| x.address).collect::<Vec<_>>();
+
+            users.sort();
+            users.dedup();
+
+            let links = users.iter().map(|a| format!("<a href=\"#a_{:x}\">{:#x}</a>", a, a)).collect::<Vec<_>>();
+            let _ = writeln!(ret, "<li id=\"s_{:x}\"><code>{:#x} {}</code> <span class=\"ref\">{}</span></li>", address, address, escape(&format!("{}", s)), links.join(", "));
+        }
+        let _ = writeln!(ret, "</ul>");
+    }
+
+    ret.push_str("</body>\n</html>\n");
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, BasicBlockIndex, Bound, ControlFlowGraph, ControlFlowTarget, Guard, Mnemonic, Region, Rvalue, StringLiteral, Xref};
+    use strings::Encoding;
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn mnemonic(address: u64, opcode: &str, fmt: &str, ops: Vec<Rvalue>) -> Mnemonic {
+        Mnemonic::new(address..address + 1, opcode.to_string(), fmt.to_string(), ops.iter(), vec![].iter()).ok().unwrap()
+    }
+
+    /*
+     * 0x100: lea 0x400; call 0x200
+     * 0x102: ret
+     * 0x200: ret
+     */
+    #[test]
+    fn report() {
+        let mut proj = Project::new("a<b>".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(
+            ControlFlowTarget::Resolved(
+                BasicBlock::from_vec(vec![mnemonic(0x100, "lea", "{u}", vec![Rvalue::new_u64(0x400)]), mnemonic(0x101, "call", "{c:RAM}", vec![Rvalue::new_u64(0x200)])]),
+            )
+        );
+        let b1 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mnemonic(0x102, "ret", "", vec![])])));
+        let mut caller = Function::undefined(0x100, None, proj.region(), Some("main".to_string()));
+        let mut callee = Function::undefined(0x200, None, proj.region(), Some("callee".to_string()));
+        let mut cfg2 = ControlFlowGraph::new();
+        let c0 = cfg2.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mnemonic(0x200, "ret", "", vec![])])));
+        let mut prog = Program::new("prog");
+
+        cfg.add_edge(Guard::True, b0, b1);
+        *caller.cfg_mut() = cfg;
+        caller.set_entry_point_ref(b0);
+        *callee.cfg_mut() = cfg2;
+        callee.set_entry_point_ref(c0);
+
+        proj.xrefs.insert(Xref { function: caller.uuid().clone(), address: 0x100, statement: None, target: 0x400, kind: XrefKind::Address });
+        proj.xrefs.insert(Xref { function: caller.uuid().clone(), address: 0x101, statement: None, target: 0x200, kind: XrefKind::Call });
+        proj.strings.insert(0x400, StringLiteral { area: Bound::new(0x400, 0x405), encoding: Encoding::Ascii, value: "hi<>\n".to_string() });
+        proj.coverage.hits.insert(BasicBlockIndex { function: caller.uuid().clone(), start: 0x100 }, 3);
+        prog.insert(caller);
+        prog.insert(callee);
+        proj.code.push(prog);
+
+        let html = html(&proj);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>a&lt;b&gt;</title>"));
+        assert!(html.contains("<li><a href=\"#f_100\">0x100 main</a></li>"));
+        assert!(html.contains("<h3 id=\"f_200\">callee</h3>"));
+        assert!(html.contains("<span id=\"a_101\">     101:  call 0x200 &lt;callee&gt;</span>  <span class=\"ref\">; <a href=\"#f_200\">callee</a></span>"));
+        assert!(html.contains("; <a href=\"#s_400\">&quot;hi&lt;&gt;\\n&quot;</a>"));
+        assert!(html.contains("<li id=\"s_400\"><code>0x400 &quot;hi&lt;&gt;\\n&quot;</code> <span class=\"ref\"><a href=\"#a_100\">0x100</a></span></li>"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert_eq!(html.matches("<rect").count(), 3);
+        assert_eq!(html.matches("<polyline").count(), 1);
+        assert_eq!(html.matches("style=\"fill: #ff3c1e\"").count(), 1);
+        assert!(html.contains("<text x=\"15\" y=\"38\">101: call 0x200 &lt;callee&gt;</text>"));
+    }
+}
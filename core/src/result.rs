@@ -22,6 +22,10 @@
 
 
 use goblin;
+use lz4_flex;
+use lzma_rs;
+#[cfg(feature = "sqlite")]
+use rusqlite;
 
 use std::borrow::Cow;
 use std::convert::From;
@@ -89,8 +93,27 @@ impl From<goblin::error::Error> for Error {
     }
 }
 
+impl From<lzma_rs::error::Error> for Error {
+    fn from(e: lzma_rs::error::Error) -> Error {
+        Error(Cow::Owned(format!("LZMA error: {}", e)))
+    }
+}
+
+impl From<lz4_flex::block::DecompressError> for Error {
+    fn from(e: lz4_flex::block::DecompressError) -> Error {
+        Error(Cow::Owned(format!("LZ4 error: {}", e)))
+    }
+}
+
 impl From<serde_cbor::Error> for Error {
     fn from(e: serde_cbor::Error) -> Error {
         Error(Cow::Owned(format!("Serde error: {}", e)))
     }
 }
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Error {
+        Error(Cow::Owned(format!("SQLite error: {}", e)))
+    }
+}
@@ -0,0 +1,369 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! C++ run-time type information.
+//!
+//! Polymorphic classes have a RTTI record naming the class and listing its base classes. The
+//! records survive stripping because `dynamic_cast` and exception handling need them.
+//!
+//! The Itanium ABI, used by GCC and Clang, uses `std::type_info` objects: a vtable pointer, a
+//! pointer to the mangled type name and, depending on the vtable, nothing (no bases), a single
+//! base or a list of bases with their offsets. The kind of record is guessed from its contents.
+//!
+//! MSVC points the slot before each vtable to a Complete Object Locator, which refers to the
+//! class' type descriptor holding the decorated name (`.?AVFoo@@`) and a hierarchy descriptor
+//! listing all base classes. 64 bit binaries store image relative offsets instead of pointers.
+//!
+//! Classes recovered by [`TypeDatabase::add_itanium`] and [`TypeDatabase::add_msvc`] are stored in
+//! `Project::type_database`, keyed by the address of their `type_info` object or type descriptor.
+//!
+//! [`TypeDatabase::add_itanium`]: struct.TypeDatabase.html#method.add_itanium
+//! [`TypeDatabase::add_msvc`]: struct.TypeDatabase.html#method.add_msvc
+
+use {Endianess, Region, demangle};
+use std::collections::BTreeMap;
+
+/// Maximal length of a type name.
+const MAX_NAME_LENGTH: usize = 1024;
+
+/// Maximal number of base classes of a single class.
+const MAX_BASES: u64 = 256;
+
+/// Base class of a C++ class.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct BaseClass {
+    /// Key of the base class in the `TypeDatabase`
+    pub class: u64,
+    /// Offset of the base class' subobject. Virtual bases have the offset of their offset in the
+    /// vtable.
+    pub offset: i64,
+    /// Inherited virtually
+    pub virtual_: bool,
+}
+
+/// C++ class recovered from RTTI.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Class {
+    /// Demangled name
+    pub name: String,
+    /// Direct base classes, in declaration order
+    pub bases: Vec<BaseClass>,
+}
+
+/// C++ classes of a project.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct TypeDatabase {
+    /// Classes, keyed by the address of their RTTI record
+    pub classes: BTreeMap<u64, Class>,
+}
+
+impl TypeDatabase {
+    /// Returns an empty database.
+    pub fn new() -> TypeDatabase {
+        TypeDatabase::default()
+    }
+
+    /// Returns the key of the class named `name`.
+    pub fn find_by_name(&self, name: &str) -> Option<u64> {
+        self.classes.iter().find(|&(_, c)| c.name == name).map(|(&k, _)| k)
+    }
+
+    /// Returns the keys of all classes directly derived from `class`.
+    pub fn derived(&self, class: u64) -> Vec<u64> {
+        self.classes.iter().filter(|&(_, c)| c.bases.iter().any(|b| b.class == class)).map(|(&k, _)| k).collect()
+    }
+
+    /// Parses the Itanium `type_info` object at `address` and all its bases. Pointers are `word`
+    /// bytes long. Returns false if there is no `type_info` for a class at `address`.
+    pub fn add_itanium(&mut self, region: &Region, address: u64, word: usize, endianess: Endianess) -> bool {
+        let mem = Memory { region: region, word: word, endianess: endianess };
+
+        self.itanium(&mem, address, 0)
+    }
+
+    fn itanium(&mut self, mem: &Memory, address: u64, depth: usize) -> bool {
+        if self.classes.contains_key(&address) {
+            return true;
+        }
+        if depth > 32 {
+            return false;
+        }
+
+        let name = match itanium_name(mem, address) {
+            Some(name) => name,
+            None => return false,
+        };
+        let w = mem.word as u64;
+        let mut bases = vec![];
+
+        // __si_class_type_info: single public non-virtual base at offset zero
+        match mem.pointer(address + 2 * w) {
+            Some(base) if itanium_name(mem, base).is_some() => {
+                if self.itanium(mem, base, depth + 1) {
+                    bases.push(BaseClass { class: base, offset: 0, virtual_: false });
+                }
+            }
+            _ => {
+                // __vmi_class_type_info: flags, base count and (base, offset << 8 | flags) pairs
+                let flags = mem.read(address + 2 * w, 4);
+                let count = mem.read(address + 2 * w + 4, 4);
+
+                if let (Some(0...3), Some(count @ 1...MAX_BASES)) = (flags, count) {
+                    let vmi = (0..count)
+                        .map(|i| address + 2 * w + 8 + i * 2 * w)
+                        .map(|p| (mem.pointer(p), mem.pointer(p + w)))
+                        .collect::<Vec<_>>();
+
+                    if vmi.iter().all(|&(b, o)| b.and_then(|b| itanium_name(mem, b)).is_some() && o.is_some()) {
+                        for (base, off) in vmi.into_iter().map(|(b, o)| (b.unwrap(), o.unwrap())) {
+                            let off = if w == 4 { off as u32 as i32 as i64 } else { off as i64 };
+
+                            if self.itanium(mem, base, depth + 1) {
+                                bases.push(BaseClass { class: base, offset: off >> 8, virtual_: off & 1 != 0 });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.classes.insert(address, Class { name: name, bases: bases });
+        true
+    }
+
+    /// Parses the MSVC Complete Object Locator at `address`, which is stored in the slot before
+    /// a vtable. Returns false if there is none.
+    pub fn add_msvc(&mut self, region: &Region, address: u64, word: usize) -> bool {
+        let mem = Memory { region: region, word: word, endianess: Endianess::Little };
+
+        // Signature 1 locators use offsets relative to the image base, the locator knows its own.
+        let image_base = match mem.read(address, 4) {
+            Some(0) => None,
+            Some(1) => {
+                match mem.read(address + 20, 4) {
+                    Some(me) if me <= address => Some(address - me),
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        };
+        let ptr = |a: u64| mem.read(a, 4).map(|p| p + image_base.unwrap_or(0));
+        let (td, chd) = match (ptr(address + 12), ptr(address + 16)) {
+            (Some(td), Some(chd)) => (td, chd),
+            _ => return false,
+        };
+        let name = match msvc_name(&mem, td) {
+            Some(name) => name,
+            None => return false,
+        };
+        let count = match mem.read(chd + 8, 4) {
+            Some(c @ 1...MAX_BASES) => c,
+            _ => return false,
+        };
+        let array = match ptr(chd + 12) {
+            Some(a) => a,
+            None => return false,
+        };
+        // Base class descriptors: type descriptor, number of bases it contains, member, vbtable
+        // and vbtable entry displacements and attributes. The first one is the class itself,
+        // followed by each direct base and the bases it contains.
+        let descriptors = (0..count)
+            .map(
+                |i| {
+                    let bcd = ptr(array + i * 4)?;
+                    let td = ptr(bcd)?;
+                    let contained = mem.read(bcd + 4, 4)?;
+                    let mdisp = mem.read(bcd + 8, 4)? as u32 as i32 as i64;
+                    let pdisp = mem.read(bcd + 12, 4)? as u32 as i32;
+                    let vdisp = mem.read(bcd + 16, 4)? as u32 as i32 as i64;
+
+                    Some((td, contained, if pdisp == -1 { (mdisp, false) } else { (vdisp, true) }))
+                }
+            )
+            .collect::<Option<Vec<_>>>();
+        let descriptors = match descriptors {
+            Some(d) => d,
+            None => return false,
+        };
+        let mut bases = vec![];
+        let mut i = 1;
+
+        while i < descriptors.len() {
+            let (base, contained, (offset, virtual_)) = descriptors[i];
+
+            if let Some(base_name) = msvc_name(&mem, base) {
+                self.classes.entry(base).or_insert(Class { name: base_name, bases: vec![] });
+                bases.push(BaseClass { class: base, offset: offset, virtual_: virtual_ });
+            }
+            i += 1 + contained as usize;
+        }
+
+        self.classes.insert(td, Class { name: name, bases: bases });
+        true
+    }
+}
+
+/// Reads words from a region.
+struct Memory<'a> {
+    region: &'a Region,
+    word: usize,
+    endianess: Endianess,
+}
+
+impl<'a> Memory<'a> {
+    fn read(&self, address: u64, size: usize) -> Option<u64> {
+        self.region.read_uint(address, size, self.endianess)
+    }
+
+    fn pointer(&self, address: u64) -> Option<u64> {
+        self.read(address, self.word)
+    }
+
+    fn cstring(&self, address: u64) -> Option<String> {
+        let bytes = self.region
+            .iter()
+            .seek(address)
+            .take(MAX_NAME_LENGTH)
+            .take_while(|c| c.is_some() && *c != Some(0))
+            .map(|c| c.unwrap())
+            .collect::<Vec<u8>>();
+
+        if bytes.is_empty() || bytes.len() == MAX_NAME_LENGTH || bytes.iter().any(|&b| b < 0x20 || b > 0x7e) {
+            None
+        } else {
+            String::from_utf8(bytes).ok()
+        }
+    }
+}
+
+/// Demangled name of the class of the `type_info` object at `address`.
+fn itanium_name(mem: &Memory, address: u64) -> Option<String> {
+    let mangled = mem.cstring(mem.pointer(address + mem.word as u64)?)?;
+    let decl = demangle(&format!("_ZTS{}", mangled))?;
+
+    if decl.starts_with("typeinfo name for ") { Some(decl["typeinfo name for ".len()..].to_string()) } else { None }
+}
+
+/// Name of the class of the MSVC type descriptor at `address`. The decorated name follows the
+/// vtable pointer and a reserved pointer.
+fn msvc_name(mem: &Memory, address: u64) -> Option<String> {
+    let decorated = mem.cstring(address + 2 * mem.word as u64)?;
+    let name = if decorated.starts_with(".?AV") || decorated.starts_with(".?AU") {
+        &decorated[4..]
+    } else {
+        return None;
+    };
+
+    if !name.ends_with("@@") || name.contains('?') {
+        // Templates and other complex names are kept decorated
+        return Some(decorated.clone());
+    }
+
+    let mut parts = name[..name.len() - 2].split('@').collect::<Vec<_>>();
+
+    parts.reverse();
+    Some(parts.join("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(buf: &mut Vec<u8>, addr: u64, bytes: &[u8]) {
+        buf[addr as usize..addr as usize + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn le(v: u64, size: usize) -> Vec<u8> {
+        (0..size).map(|i| (v >> (i * 8)) as u8).collect()
+    }
+
+    #[test]
+    fn itanium() {
+        let mut buf = vec![0u8; 0x1000];
+
+        // A: __class_type_info, B: __si_class_type_info derived from A, C:
+        // __vmi_class_type_info derived from B and virtually from A
+        put(&mut buf, 0x100, &le(0x800, 8));
+        put(&mut buf, 0x108, &le(0x200, 8));
+        put(&mut buf, 0x110, &le(0x810, 8));
+        put(&mut buf, 0x118, &le(0x210, 8));
+        put(&mut buf, 0x120, &le(0x100, 8));
+        put(&mut buf, 0x130, &le(0x820, 8));
+        put(&mut buf, 0x138, &le(0x220, 8));
+        put(&mut buf, 0x140, &le(2, 4));
+        put(&mut buf, 0x144, &le(2, 4));
+        put(&mut buf, 0x148, &le(0x110, 8));
+        put(&mut buf, 0x150, &le(0x2, 8));
+        put(&mut buf, 0x158, &le(0x100, 8));
+        put(&mut buf, 0x160, &le((-24i64 << 8) as u64 | 3, 8));
+        put(&mut buf, 0x200, b"1A\0");
+        put(&mut buf, 0x210, b"N2ns1BE\0");
+        put(&mut buf, 0x220, b"1C\0");
+
+        let region = Region::wrap("RAM".to_string(), buf);
+        let mut db = TypeDatabase::new();
+
+        assert!(db.add_itanium(&region, 0x130, 8, Endianess::Little));
+        assert!(!db.add_itanium(&region, 0x200, 8, Endianess::Little));
+
+        assert_eq!(db.classes.len(), 3);
+        assert_eq!(db.classes[&0x100], Class { name: "A".to_string(), bases: vec![] });
+        assert_eq!(db.classes[&0x110], Class { name: "ns::B".to_string(), bases: vec![BaseClass { class: 0x100, offset: 0, virtual_: false }] });
+        assert_eq!(
+            db.classes[&0x130].bases,
+            vec![BaseClass { class: 0x110, offset: 0, virtual_: false }, BaseClass { class: 0x100, offset: -24, virtual_: true }]
+        );
+        assert_eq!(db.find_by_name("C"), Some(0x130));
+        assert_eq!(db.derived(0x100), vec![0x110, 0x130]);
+    }
+
+    #[test]
+    fn msvc() {
+        let mut buf = vec![0u8; 0x1000];
+        let base = 0x400u64;
+
+        // Complete Object Locator with image relative offsets
+        put(&mut buf, 0x500, &le(1, 4));
+        put(&mut buf, 0x50c, &le(0x200, 4));
+        put(&mut buf, 0x510, &le(0x400, 4));
+        put(&mut buf, 0x514, &le(0x500 - base, 4));
+        // type descriptors
+        put(&mut buf, 0x610, b".?AVDerived@app@@\0");
+        put(&mut buf, 0x710, b".?AUBase@@\0");
+        // class hierarchy descriptor and base class array
+        put(&mut buf, 0x808, &le(2, 4));
+        put(&mut buf, 0x80c, &le(0x480, 4));
+        put(&mut buf, 0x880, &le(0x500, 4));
+        put(&mut buf, 0x884, &le(0x520, 4));
+        put(&mut buf, 0x900, &le(0x200, 4));
+        put(&mut buf, 0x904, &le(1, 4));
+        put(&mut buf, 0x90c, &le(0xffff_ffff, 4));
+        put(&mut buf, 0x920, &le(0x300, 4));
+        put(&mut buf, 0x928, &le(8, 4));
+        put(&mut buf, 0x92c, &le(0xffff_ffff, 4));
+
+        let region = Region::wrap("RAM".to_string(), buf);
+        let mut db = TypeDatabase::new();
+
+        assert!(db.add_msvc(&region, 0x500, 8));
+        assert!(!db.add_msvc(&region, 0x600, 8));
+
+        assert_eq!(db.classes[&0x600], Class { name: "app::Derived".to_string(), bases: vec![BaseClass { class: 0x700, offset: 8, virtual_: false }] });
+        assert_eq!(db.classes[&0x700].name, "Base");
+    }
+}
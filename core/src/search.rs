@@ -0,0 +1,278 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Search for byte patterns, strings and immediate values.
+//!
+//! [`bytes`] finds a [`Pattern`] in the defined parts of all regions of a project. Patterns are
+//! written in hex with `??` for bytes that match anything, like `48 8b ?? ??`, or built from
+//! ASCII and UTF-16LE strings. [`immediates`] finds mnemonics using a constant as operand or in
+//! their IL.
+//!
+//! Both return iterators, so the first results are available before the whole project has been
+//! searched. Each [`Hit`] carries the function containing it, if any.
+//!
+//! [`bytes`]: fn.bytes.html
+//! [`immediates`]: fn.immediates.html
+//! [`Pattern`]: struct.Pattern.html
+//! [`Hit`]: struct.Hit.html
+
+use {Bound, Function, Project, Region, Result, Rvalue};
+use panopticon_graph_algos::{GraphTrait, VertexListGraphTrait};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Byte string with wildcards.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Pattern {
+    /// Bytes to match, `None` matches any byte
+    pub bytes: Vec<Option<u8>>,
+}
+
+impl Pattern {
+    /// Parses a pattern of space separated hex bytes and `??` wildcards.
+    pub fn parse(s: &str) -> Result<Pattern> {
+        let mut bytes = vec![];
+
+        for tok in s.split_whitespace() {
+            if tok == "??" || tok == "?" {
+                bytes.push(None);
+            } else {
+                match u8::from_str_radix(tok, 16) {
+                    Ok(b) if tok.len() <= 2 => bytes.push(Some(b)),
+                    _ => return Err(format!("invalid byte {} in pattern", tok).into()),
+                }
+            }
+        }
+
+        if bytes.is_empty() {
+            Err("empty pattern".into())
+        } else {
+            Ok(Pattern { bytes: bytes })
+        }
+    }
+
+    /// Pattern matching the ASCII or UTF-8 encoding of `s`.
+    pub fn ascii(s: &str) -> Pattern {
+        Pattern { bytes: s.bytes().map(Some).collect() }
+    }
+
+    /// Pattern matching the UTF-16LE encoding of `s`.
+    pub fn utf16(s: &str) -> Pattern {
+        Pattern { bytes: s.encode_utf16().flat_map(|c| vec![Some(c as u8), Some((c >> 8) as u8)]).collect() }
+    }
+
+    /// Returns true if the pattern matches the start of `cells`.
+    pub fn matches(&self, cells: &[Option<u8>]) -> bool {
+        self.bytes.len() <= cells.len() &&
+        self.bytes.iter().zip(cells.iter()).all(
+            |(p, c)| match (p, c) {
+                (&None, &Some(_)) => true,
+                (&Some(p), &Some(c)) => p == c,
+                _ => false,
+            }
+        )
+    }
+}
+
+/// Search result.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Hit {
+    /// Name of the region searched
+    pub region: String,
+    /// Address of the match, or of the mnemonic using an immediate
+    pub address: u64,
+    /// Function containing `address`
+    pub function: Option<Uuid>,
+}
+
+/// Basic block areas of all functions in `proj`, sorted.
+fn code_index(proj: &Project) -> Vec<(Bound, Uuid)> {
+    let mut ret = vec![];
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            ret.extend(func.basic_blocks().map(|bb| (bb.area.clone(), func.uuid().clone())));
+        }
+    }
+
+    ret.sort_by_key(|&(ref b, _)| (b.start, b.end));
+    ret
+}
+
+fn containing(index: &[(Bound, Uuid)], address: u64) -> Option<Uuid> {
+    let pos = match index.binary_search_by_key(&address, |&(ref b, _)| b.start) {
+        Ok(p) => p + 1,
+        Err(p) => p,
+    };
+
+    index[..pos].iter().rev().take(16).find(|&&(ref b, _)| b.start <= address && address < b.end).map(|&(_, ref u)| u.clone())
+}
+
+/// Parts of `region` not covered by undefined layers, adjacent parts merged.
+fn defined_areas(region: &Region) -> Vec<Bound> {
+    let mut ret: Vec<Bound> = vec![];
+
+    for (b, l) in region.flatten() {
+        if l.is_undefined() || b.start == b.end {
+            continue;
+        }
+
+        match ret.last_mut() {
+            Some(last) if last.end == b.start => {
+                last.end = b.end;
+                continue;
+            }
+            _ => {}
+        }
+        ret.push(b);
+    }
+
+    ret
+}
+
+/// Finds all occurrences of `pattern` in the regions of `proj`, in address order per region.
+/// Matches in the root region are attributed to the function containing their first byte.
+pub fn bytes<'a>(proj: &'a Project, pattern: &'a Pattern) -> Box<Iterator<Item = Hit> + 'a> {
+    let index = Arc::new(code_index(proj));
+    let root = proj.data.root;
+    let regions = proj.data.dependencies.vertices().collect::<Vec<_>>();
+
+    Box::new(
+        regions
+            .into_iter()
+            .filter_map(move |vx| proj.data.dependencies.vertex_label(vx).map(|r| (vx == root, r)))
+            .flat_map(move |(is_root, region)| defined_areas(region).into_iter().map(move |area| (is_root, region, area)))
+            .flat_map(
+                move |(is_root, region, area)| {
+                    let cells = region.iter().cut(&(area.start..area.end)).collect::<Vec<_>>();
+                    let index = index.clone();
+                    let len = pattern.bytes.len();
+                    let last = if cells.len() >= len { cells.len() - len + 1 } else { 0 };
+
+                    (0..last)
+                        .filter(|&i| pattern.matches(&cells[i..]))
+                        .map(
+                            |i| {
+                                let addr = area.start + i as u64;
+
+                                Hit {
+                                    region: region.name().clone(),
+                                    address: addr,
+                                    function: if is_root { containing(&index, addr) } else { None },
+                                }
+                            }
+                        )
+                        .collect::<Vec<_>>()
+                }
+            )
+    )
+}
+
+/// Returns true if `rv` is the constant `value`.
+fn is_immediate(rv: &Rvalue, value: u64) -> bool {
+    match rv {
+        &Rvalue::Constant { value: v, .. } => v == value,
+        _ => false,
+    }
+}
+
+fn mnemonics_using<'a>(func: &'a Function, value: u64) -> Box<Iterator<Item = u64> + 'a> {
+    Box::new(
+        func.basic_blocks()
+            .flat_map(move |bb| func.mnemonics(bb))
+            .filter(
+                move |&(mne, stmts)| {
+                    mne.operands.iter().any(|rv| is_immediate(rv, value)) ||
+                    stmts.iter().any(|s| s.op.operands().into_iter().any(|rv| is_immediate(rv, value)))
+                }
+            )
+            .map(|(mne, _)| mne.area.start)
+    )
+}
+
+/// Finds all mnemonics in `proj` that have `value` as operand or as constant in their IL.
+pub fn immediates<'a>(proj: &'a Project, value: u64) -> Box<Iterator<Item = Hit> + 'a> {
+    let region = proj.region().name().clone();
+
+    Box::new(
+        proj.code
+            .iter()
+            .flat_map(|prog| prog.functions())
+            .flat_map(
+                move |func| {
+                    let region = region.clone();
+
+                    mnemonics_using(func, value).map(move |addr| Hit { region: region.clone(), address: addr, function: Some(func.uuid().clone()) })
+                }
+            )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Layer, Mnemonic, Program};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    #[test]
+    fn patterns() {
+        assert_eq!(Pattern::parse("48 8B ?? ??").ok(), Some(Pattern { bytes: vec![Some(0x48), Some(0x8b), None, None] }));
+        assert!(Pattern::parse("48 8BC").is_err());
+        assert!(Pattern::parse("").is_err());
+        assert_eq!(Pattern::utf16("Hi").bytes, vec![Some(b'H'), Some(0), Some(b'i'), Some(0)]);
+        assert!(!Pattern::parse("??").unwrap().matches(&[None]));
+    }
+
+    #[test]
+    fn search() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1_0000);
+
+        assert!(reg.cover(Bound::new(0x100, 0x106), Layer::wrap(vec![0x48, 0x8b, 0x05, 0xef, 0xbe, 0xad])));
+        assert!(reg.cover(Bound::new(0x106, 0x108), Layer::wrap(vec![0xde, 0x48])));
+        assert!(reg.cover(Bound::new(0x800, 0x806), Layer::wrap(b"H\0i\0!\0".to_vec())));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        let ops = vec![Rvalue::new_u32(0xdeadbeef)];
+        let mne = Mnemonic::new(0x100..0x107, "mov".to_string(), "{u}".to_string(), ops.iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        let mut prog = Program::new("prog");
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        let uuid = func.uuid().clone();
+        prog.insert(func);
+        proj.code.push(prog);
+
+        let pat = Pattern::parse("48 8b ?? ef").unwrap();
+        let hits = bytes(&proj, &pat).collect::<Vec<_>>();
+        assert_eq!(hits, vec![Hit { region: "RAM".to_string(), address: 0x100, function: Some(uuid.clone()) }]);
+
+        let pat = Pattern::parse("de 48").unwrap();
+        assert_eq!(bytes(&proj, &pat).map(|h| h.address).collect::<Vec<_>>(), vec![0x106]);
+
+        let pat = Pattern::utf16("Hi!");
+        let hits = bytes(&proj, &pat).collect::<Vec<_>>();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].address, hits[0].function.clone()), (0x800, None));
+
+        assert_eq!(immediates(&proj, 0xdeadbeef).map(|h| (h.address, h.function)).collect::<Vec<_>>(), vec![(0x100, Some(uuid))]);
+        assert_eq!(immediates(&proj, 0x1234).count(), 0);
+    }
+}
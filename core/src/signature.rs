@@ -0,0 +1,351 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Function signatures.
+//!
+//! A [`Signature`] is the prototype of a function: return type, parameters and whether it takes
+//! a variable number of arguments. When the calling convention is known each parameter is
+//! assigned the register or stack slot it is passed in, following the [`CallingConvention`]
+//! model. Signatures are stored on the function with `Function::set_signature`, either built by
+//! the user with [`Signature::new`], from a C function type with [`Signature::from_type`] or
+//! taken from the DWARF prototypes the type library imported, see [`apply`].
+//!
+//! [`arguments`] annotates call sites: for every call of a function with a signature it finds the
+//! IL statement in the same basic block that last sets up each parameter before the call. That
+//! is the last write to the parameter register, or the last store to the parameter's stack slot.
+//! Stack slots are found by following the stack pointer through moves, additions and
+//! subtractions of constants, which covers pushes as well as `mov [rsp+8], rax`.
+//!
+//! [`Signature`]: struct.Signature.html
+//! [`Signature::new`]: struct.Signature.html#method.new
+//! [`Signature::from_type`]: struct.Signature.html#method.from_type
+//! [`CallingConvention`]: ../calling_convention/enum.CallingConvention.html
+//! [`apply`]: fn.apply.html
+//! [`arguments`]: fn.arguments.html
+
+use {CallingConvention, ControlFlowTarget, DataType, Function, Operation, Program, Project, Rvalue, Lvalue};
+use calling_convention;
+use panopticon_graph_algos::GraphTrait;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Where a parameter is passed.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Storage {
+    /// In a register
+    Register(String),
+    /// On the stack, at this offset from the stack pointer at the call instruction
+    Stack(i64),
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Storage::Register(ref r) => f.write_str(r),
+            &Storage::Stack(o) => write!(f, "[sp+{:#x}]", o),
+        }
+    }
+}
+
+/// Function parameter.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Parameter {
+    /// Parameter name
+    pub name: String,
+    /// Parameter type
+    pub ty: DataType,
+    /// Register or stack slot, None if the calling convention is unknown
+    pub storage: Option<Storage>,
+}
+
+/// Function prototype.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Signature {
+    /// Return type
+    pub ret: DataType,
+    /// Parameters in declaration order
+    pub parameters: Vec<Parameter>,
+    /// Takes more arguments than `parameters`, like `printf`
+    pub variadic: bool,
+    /// Calling convention the parameter storage follows
+    pub calling_convention: Option<CallingConvention>,
+}
+
+impl Signature {
+    /// Returns a signature with parameters `params`. If `cc` is given the parameters are assigned
+    /// registers and stack slots, one register or slot per parameter.
+    pub fn new(ret: DataType, params: Vec<(String, DataType)>, variadic: bool, cc: Option<CallingConvention>) -> Signature {
+        let parameters = params.into_iter().map(|(name, ty)| Parameter { name: name, ty: ty, storage: None }).collect();
+        let mut ret = Signature { ret: ret, parameters: parameters, variadic: variadic, calling_convention: None };
+
+        ret.set_calling_convention(cc);
+        ret
+    }
+
+    /// Returns the signature of the C function type `ty`, with parameters named `arg0`, `arg1`
+    /// and so on. None if `ty` isn't a function type.
+    pub fn from_type(ty: &DataType, cc: Option<CallingConvention>) -> Option<Signature> {
+        match ty {
+            &DataType::Function { ref ret, ref args } => {
+                let params = args.iter().enumerate().map(|(i, a)| (format!("arg{}", i), a.clone())).collect();
+                Some(Signature::new((**ret).clone(), params, false, cc))
+            }
+            _ => None,
+        }
+    }
+
+    /// Changes the calling convention and assigns the parameters new storage.
+    pub fn set_calling_convention(&mut self, cc: Option<CallingConvention>) {
+        self.calling_convention = cc;
+
+        match cc {
+            Some(cc) => {
+                let regs = cc.argument_registers();
+                let (first, slot) = match cc {
+                    CallingConvention::SysV64 => (0, 8),
+                    // callers reserve 32 bytes of shadow space for the register arguments
+                    CallingConvention::Win64 => (0x20, 8),
+                    CallingConvention::Cdecl | CallingConvention::Stdcall | CallingConvention::Aapcs => (0, 4),
+                };
+
+                for (i, p) in self.parameters.iter_mut().enumerate() {
+                    p.storage = Some(
+                        match regs.get(i) {
+                            Some(r) => Storage::Register(r.to_string()),
+                            None => Storage::Stack(first + slot * (i - regs.len()) as i64),
+                        }
+                    );
+                }
+            }
+            None => {
+                for p in self.parameters.iter_mut() {
+                    p.storage = None;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut params = self.parameters.iter().map(|p| format!("{} {}", p.ty, p.name)).collect::<Vec<_>>();
+
+        if self.variadic {
+            params.push("...".to_string());
+        }
+        write!(f, "{}({})", self.ret, params.join(", "))
+    }
+}
+
+/// Statement setting up an argument of a call.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Argument {
+    /// Address of the call instruction
+    pub call: u64,
+    /// Address of the instruction setting up the argument
+    pub address: u64,
+    /// Index of the statement in the IL of that instruction
+    pub statement: usize,
+    /// Name of the parameter
+    pub parameter: String,
+}
+
+/// Sign extends the `size` bit constant `value`.
+fn signed(value: u64, size: usize) -> i64 {
+    if size > 0 && size < 64 && value & (1 << (size - 1)) != 0 {
+        (value as i64).wrapping_sub(1 << size)
+    } else {
+        value as i64
+    }
+}
+
+/// Entry point of `func`, None for undefined functions.
+fn entry(func: &Function) -> Option<u64> {
+    match func.cfg().vertex_label(func.entry_point_ref()) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => Some(bb.area.start),
+        _ => None,
+    }
+}
+
+/// Finds the statements of `func` setting up the arguments of calls to functions of `prog`
+/// whose signature assigns storage to their parameters. Parameters set up outside the calling
+/// basic block are skipped.
+pub fn arguments(prog: &Program, func: &Function) -> Vec<Argument> {
+    let signatures = prog.functions()
+        .filter_map(|f| match (entry(f), f.signature()) {
+            (Some(e), Some(sig)) if sig.calling_convention.is_some() => Some((e, sig)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let mut ret = vec![];
+
+    if signatures.is_empty() {
+        return ret;
+    }
+
+    for bb in func.basic_blocks() {
+        // offsets from the stack pointer at the start of the basic block
+        let mut offsets = HashMap::<Cow<'static, str>, Option<i64>>::new();
+        // last write of each register and last store to each stack slot
+        let mut registers = HashMap::<String, (u64, usize)>::new();
+        let mut slots = HashMap::<i64, (u64, usize)>::new();
+
+        for (mne, stmts) in func.mnemonics(bb) {
+            for (idx, stmt) in stmts.iter().enumerate() {
+                let eval = |offsets: &HashMap<Cow<'static, str>, Option<i64>>, rv: &Rvalue| match rv {
+                    &Rvalue::Variable { ref name, .. } => {
+                        match offsets.get(name) {
+                            Some(&o) => o,
+                            None if calling_convention::is_stack_pointer(name) => Some(0),
+                            None => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let value = match stmt.op {
+                    Operation::Move(ref a) => eval(&offsets, a),
+                    Operation::Add(ref a, Rvalue::Constant { value, size }) | Operation::Add(Rvalue::Constant { value, size }, ref a) => {
+                        eval(&offsets, a).map(|o| o.wrapping_add(signed(value, size)))
+                    }
+                    Operation::Subtract(ref a, Rvalue::Constant { value, size }) => eval(&offsets, a).map(|o| o.wrapping_sub(signed(value, size))),
+                    Operation::Store(_, _, _, ref addr, _) => {
+                        if let Some(o) = eval(&offsets, addr) {
+                            slots.insert(o, (mne.area.start, idx));
+                        }
+                        None
+                    }
+                    Operation::Call(Rvalue::Constant { value, .. }) => {
+                        if let Some(sig) = signatures.get(&value) {
+                            let sp = offsets.iter().find(|&(n, _)| calling_convention::is_stack_pointer(n)).map(|(_, &o)| o).unwrap_or(Some(0));
+
+                            for p in sig.parameters.iter() {
+                                let setup = match p.storage {
+                                    Some(Storage::Register(ref r)) => registers.get(calling_convention::canonical(r)),
+                                    Some(Storage::Stack(o)) => sp.and_then(|sp| slots.get(&(sp + o))),
+                                    None => None,
+                                };
+
+                                if let Some(&(address, statement)) = setup {
+                                    ret.push(Argument { call: mne.area.start, address: address, statement: statement, parameter: p.name.clone() });
+                                }
+                            }
+                        }
+                        // the callee clobbers argument registers and may reuse its stack arguments
+                        registers.clear();
+                        slots.clear();
+                        None
+                    }
+                    _ => None,
+                };
+
+                if let Lvalue::Variable { ref name, .. } = stmt.assignee {
+                    offsets.insert(name.clone(), value);
+                    registers.insert(calling_convention::canonical(name).to_string(), (mne.area.start, idx));
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Sets the signature of every function of `proj` that has a prototype in the type library and
+/// no signature yet. Parameters are assigned storage according to the functions calling
+/// convention. Returns the number of signatures set.
+pub fn apply(proj: &mut Project) -> usize {
+    let prototypes = &proj.data_types.prototypes;
+    let mut ret = 0;
+
+    if prototypes.is_empty() {
+        return 0;
+    }
+
+    for prog in proj.code.iter_mut() {
+        for f in prog.functions_mut() {
+            if f.signature().is_some() {
+                continue;
+            }
+            if let Some(sig) = entry(f).and_then(|e| prototypes.get(&e)) {
+                let mut sig = sig.clone();
+
+                sig.set_calling_convention(f.calling_convention());
+                f.set_signature(Some(sig));
+                ret += 1;
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, CallTarget, Mnemonic, Region, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn function(start: u64, name: &str, code: Vec<(&str, Vec<Statement>)>) -> Function {
+        let mut f = Function::undefined(start, None, &Region::undefined("ram".to_owned(), 0x1000), Some(name.to_owned()));
+        let mnes = code.into_iter()
+            .enumerate()
+            .map(|(i, (op, stmts))| Mnemonic::new(start + i as u64..start + i as u64 + 1, op.to_string(), "".to_string(), vec![].iter(), stmts.iter()).unwrap())
+            .collect();
+        let vx = f.cfg_mut().add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(mnes)));
+
+        f.set_entry_point_ref(vx);
+        f
+    }
+
+    #[test]
+    fn call_arguments() {
+        let int = DataType::Integer { size: 4, signed: true };
+        let mut cdecl = function(0x100, "cdecl", vec![("ret", vec![])]);
+        let mut sysv = function(0x200, "sysv", vec![("ret", vec![])]);
+        let caller = function(
+            0,
+            "caller",
+            vec![
+                ("push", rreil!{ sub stack:32, ESP:32, [4]:32; store/RAM/le/32 [2]:32, stack:32; mov ESP:32, stack:32; }.unwrap()),
+                ("push", rreil!{ sub stack:32, ESP:32, [4]:32; store/RAM/le/32 [1]:32, stack:32; mov ESP:32, stack:32; }.unwrap()),
+                ("mov", rreil!{ mov EDI:32, [3]:32; }.unwrap()),
+                ("call", rreil!{ call [0x100]:32; }.unwrap()),
+                ("mov", rreil!{ mov ESI:32, [4]:32; }.unwrap()),
+                ("call", rreil!{ call [0x200]:32; }.unwrap()),
+            ],
+        );
+        let mut prog = Program::new("prog");
+
+        cdecl.set_signature(Some(Signature::new(int.clone(), vec![("a".to_string(), int.clone()), ("b".to_string(), int.clone())], false, Some(CallingConvention::Cdecl))));
+        sysv.set_signature(Some(Signature::new(DataType::Void, vec![("x".to_string(), int.clone()), ("y".to_string(), int)], false, Some(CallingConvention::SysV64))));
+        assert_eq!(cdecl.signature().unwrap().parameters[1].storage, Some(Storage::Stack(4)));
+        assert_eq!(sysv.signature().unwrap().to_string(), "void(int32_t x, int32_t y)");
+
+        prog.call_graph.add_vertex(CallTarget::Concrete(cdecl));
+        prog.call_graph.add_vertex(CallTarget::Concrete(sysv));
+
+        assert_eq!(
+            arguments(&prog, &caller),
+            vec![
+                Argument { call: 3, address: 1, statement: 1, parameter: "a".to_string() },
+                Argument { call: 3, address: 0, statement: 1, parameter: "b".to_string() },
+                Argument { call: 5, address: 4, statement: 0, parameter: "y".to_string() },
+            ]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Keeping the IL of huge projects on disk.
+//!
+//! The RREIL statements of a function take up many times the memory of its machine code. Large
+//! firmware images and statically linked executables have more of it than fits into memory.
+//! [`SpillCache`] writes the statements of functions to a temporary directory and removes them
+//! from the `Mnemonic`s. Everything else, the control flow graph, the mnemonics and their
+//! operands, stays in memory.
+//!
+//! [`SpillCache::enforce`] spills the functions used least recently until the IL left in memory
+//! is below the budget. Spilled functions read their statements back from disk the first time
+//! `Function::statements` or `Function::mnemonics` is called, the next call to `enforce` drops
+//! them again. Functions created by `Function::decode_only` are not written to disk, their IL is
+//! lifted again instead.
+//!
+//! [`SpillCache`]: struct.SpillCache.html
+//! [`SpillCache::enforce`]: struct.SpillCache.html#method.enforce
+
+use {DeferredIl, Function, Project, Result, Statement};
+use serde::{Deserialize, Serialize};
+use serde_cbor::de::Deserializer;
+use serde_cbor::ser::Serializer;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::mem;
+use std::sync::Arc;
+use tempdir::TempDir;
+use uuid::Uuid;
+
+/// Estimated memory used by the IL of `func` in bytes. Statements that are only on disk aren't
+/// counted.
+pub fn il_size(func: &Function) -> usize {
+    func.resident_statements() * mem::size_of::<Statement>()
+}
+
+/// On-disk cache for the IL of functions, see the module documentation.
+#[derive(Debug)]
+pub struct SpillCache {
+    // shared with the spilled functions, they read from it after the cache is dropped
+    dir: Arc<TempDir>,
+    budget: usize,
+    // bytes of IL left in memory right after each function was spilled
+    spilled: HashMap<Uuid, usize>,
+    last_use: HashMap<Uuid, u64>,
+    clock: u64,
+}
+
+impl SpillCache {
+    /// Creates a cache in a new temporary directory keeping at most `budget` bytes of IL in
+    /// memory. The directory is removed once the cache and all functions spilled to it are
+    /// dropped.
+    pub fn new(budget: usize) -> Result<SpillCache> {
+        Ok(
+            SpillCache {
+                dir: Arc::new(TempDir::new("panopticon-spill")?),
+                budget: budget,
+                spilled: HashMap::new(),
+                last_use: HashMap::new(),
+                clock: 0,
+            }
+        )
+    }
+
+    /// Bytes of IL kept in memory.
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// True if the statements of `func` were spilled and weren't read since.
+    pub fn is_spilled(&self, func: &Function) -> bool {
+        match self.spilled.get(func.uuid()) {
+            Some(&rest) => !func.is_lifted() && il_size(func) <= rest,
+            None => false,
+        }
+    }
+
+    fn touch(&mut self, uuid: &Uuid) {
+        self.clock += 1;
+        self.last_use.insert(uuid.clone(), self.clock);
+    }
+
+    /// Writes the statements of `func` to disk and removes them from its mnemonics. Returns the
+    /// estimated number of bytes freed.
+    pub fn spill(&mut self, func: &mut Function) -> Result<usize> {
+        let size = il_size(func);
+
+        // already on disk or lifted on demand
+        if func.unload() {
+            let rest = il_size(func);
+
+            self.spilled.insert(func.uuid().clone(), rest);
+            return Ok(size - rest);
+        }
+
+        func.lift()?;
+
+        let mut il = DeferredIl::new();
+
+        for bb in func.basic_blocks() {
+            for mne in bb.mnemonics.iter() {
+                il.entry(mne.area.start).or_insert_with(Vec::new).push(mne.instructions.clone());
+            }
+        }
+
+        let name = func.uuid().to_string();
+
+        {
+            let mut fd = File::create(self.dir.path().join(&name))?;
+            let mut enc = Serializer::new(&mut fd);
+
+            if let Err(e) = il.serialize(&mut enc) {
+                return Err(format!("failed to spill {}: {}", func.name, e).into());
+            }
+        }
+
+        let dir = self.dir.clone();
+        let source = move || -> Result<DeferredIl> {
+            let fd = BufReader::new(File::open(dir.path().join(&name))?);
+            let mut cbor = Deserializer::new(fd);
+
+            Ok(Deserialize::deserialize(&mut cbor)?)
+        };
+
+        func.defer(Arc::new(source))?;
+        self.spilled.insert(func.uuid().clone(), 0);
+        Ok(size)
+    }
+
+    /// Puts the statements of `func` back into its mnemonics if they were spilled and marks the
+    /// function as used. Reading the statements of a spilled function doesn't need this.
+    pub fn reload(&mut self, func: &mut Function) -> Result<()> {
+        self.touch(func.uuid());
+        func.lift()?;
+        self.spilled.remove(func.uuid());
+        Ok(())
+    }
+
+    /// Spills the functions of `proj` used least recently until the IL in memory fits the budget.
+    /// Spilled functions whose statements were read since count as used. Returns the number of
+    /// functions spilled.
+    pub fn enforce(&mut self, proj: &mut Project) -> Result<usize> {
+        let mut resident = vec![];
+        let mut total = 0;
+
+        for prog in proj.code.iter() {
+            for func in prog.functions() {
+                let size = il_size(func);
+
+                if size == 0 {
+                    continue;
+                }
+                if self.spilled.get(func.uuid()).map(|&rest| size > rest).unwrap_or(false) {
+                    self.spilled.remove(func.uuid());
+                    self.touch(func.uuid());
+                }
+                total += size;
+                resident.push((self.last_use.get(func.uuid()).cloned().unwrap_or(0), size, func.uuid().clone()));
+            }
+        }
+
+        if total <= self.budget {
+            return Ok(0);
+        }
+
+        // oldest first, larger ones first among functions never used
+        resident.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        let mut ret = 0;
+
+        for (_, _, uuid) in resident {
+            if total <= self.budget {
+                break;
+            }
+            if let Some(func) = proj.find_function_by_uuid_mut(&uuid) {
+                total -= self.spill(func)?;
+                ret += 1;
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Lvalue, Mnemonic, Operation, Program, Region, Rvalue};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn function(start: u64, stmts: Vec<Statement>, region: &Region) -> Function {
+        let mne = Mnemonic::new(start..start + 4, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(start, None, region, None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    #[test]
+    fn spill_and_reload() {
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let a = Lvalue::Variable { name: Cow::Borrowed("a"), size: 32, subscript: None };
+        let stmts = vec![
+            Statement { op: Operation::Move(Rvalue::new_u32(1)), assignee: a.clone() },
+            Statement { op: Operation::Add(a.clone().into(), Rvalue::new_u32(2)), assignee: a.clone() },
+        ];
+        let func = function(0x100, stmts.clone(), proj.region());
+        let uuid = func.uuid().clone();
+        let mut prog = Program::new("prog");
+
+        prog.insert(func);
+        proj.code.push(prog);
+
+        let mut cache = SpillCache::new(2 * mem::size_of::<Statement>()).unwrap();
+
+        assert_eq!(cache.enforce(&mut proj).unwrap(), 0);
+        cache.budget = 0;
+        assert_eq!(cache.enforce(&mut proj).unwrap(), 1);
+        assert!(cache.is_spilled(proj.find_function_by_uuid(&uuid).unwrap()));
+        assert_eq!(il_size(proj.find_function_by_uuid(&uuid).unwrap()), 0);
+
+        // read back from disk
+        assert_eq!(proj.find_function_by_uuid(&uuid).unwrap().statements().cloned().collect::<Vec<_>>(), stmts);
+        assert!(!cache.is_spilled(proj.find_function_by_uuid(&uuid).unwrap()));
+
+        assert_eq!(cache.enforce(&mut proj).unwrap(), 1);
+        assert!(cache.is_spilled(proj.find_function_by_uuid(&uuid).unwrap()));
+
+        {
+            let func = proj.find_function_by_uuid_mut(&uuid).unwrap();
+
+            cache.reload(func).unwrap();
+            assert!(func.is_lifted());
+            assert!(!cache.is_spilled(func));
+        }
+
+        // spilled functions outlive the cache
+        cache.enforce(&mut proj).unwrap();
+        drop(cache);
+        assert_eq!(proj.find_function_by_uuid(&uuid).unwrap().statements().cloned().collect::<Vec<_>>(), stmts);
+    }
+}
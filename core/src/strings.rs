@@ -0,0 +1,286 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! String literals and their cross references.
+//!
+//! [`extract`] scans the data sections of a project for runs of at least [`MIN_LENGTH`] printable
+//! characters encoded as ASCII, UTF-8 or UTF-16LE and stores them in `Project::strings`. If the
+//! loader recorded no sections the whole root region is scanned. Afterwards every IL constant
+//! equal to the start of a string is recorded as a reference from its mnemonic, which is returned
+//! by `Function::string_refs`.
+//!
+//! Strings referenced only by an address in the middle of them, like shared suffixes, are not
+//! linked.
+//!
+//! [`extract`]: fn.extract.html
+//! [`MIN_LENGTH`]: constant.MIN_LENGTH.html
+
+use {Bound, Project, Rvalue};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str;
+
+/// Minimal number of characters of a string.
+pub const MIN_LENGTH: usize = 4;
+
+/// Character encoding of a string literal.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Encoding {
+    /// 7 bit ASCII
+    Ascii,
+    /// UTF-8 with at least one multi byte character
+    Utf8,
+    /// Little endian UTF-16 without surrogates
+    Utf16,
+}
+
+/// String literal found in memory.
+#[derive(Clone,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub struct StringLiteral {
+    /// Bytes covered, without the terminating zero
+    pub area: Bound,
+    /// How the bytes are encoded
+    pub encoding: Encoding,
+    /// Decoded contents
+    pub value: String,
+}
+
+impl fmt::Display for StringLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.encoding {
+            Encoding::Utf16 => write!(f, "L{:?}", self.value),
+            _ => write!(f, "{:?}", self.value),
+        }
+    }
+}
+
+/// Reference from a mnemonic to a string literal.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct StringRef {
+    /// Address of the referencing mnemonic
+    pub address: u64,
+    /// Start of the string literal in `Project::strings`
+    pub string: u64,
+}
+
+/// Fills the string table of `proj` and links all functions to the strings they reference.
+pub fn extract(proj: &mut Project) {
+    let areas = {
+        let data = proj.sections.iter().filter(|s| s.read && !s.execute).map(|s| s.area.clone()).collect::<Vec<_>>();
+
+        if proj.sections.is_empty() { vec![Bound::new(0, proj.region().size())] } else { data }
+    };
+    let mut strings = BTreeMap::new();
+
+    for area in areas {
+        let bytes = proj.region().iter().cut(&(area.start..area.end)).collect::<Vec<_>>();
+
+        for s in scan(area.start, &bytes) {
+            strings.insert(s.area.start, s);
+        }
+    }
+
+    for prog in proj.code.iter_mut() {
+        for func in prog.functions_mut() {
+            let mut refs = vec![];
+
+            for bb in func.basic_blocks() {
+                for (mne, stmts) in func.mnemonics(bb) {
+                    for stmt in stmts.iter() {
+                        for rv in stmt.op.operands() {
+                            if let &Rvalue::Constant { value, .. } = rv {
+                                if strings.contains_key(&value) {
+                                    refs.push(StringRef { address: mne.area.start, string: value });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            refs.sort();
+            refs.dedup();
+            func.set_string_refs(refs);
+        }
+    }
+
+    proj.strings = strings;
+}
+
+/// Returns all strings in `bytes`, which start at address `base`. Undefined bytes end strings.
+pub fn scan(base: u64, bytes: &[Option<u8>]) -> Vec<StringLiteral> {
+    let mut ret = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some((value, len)) = utf16(&bytes[i..]) {
+            ret.push(
+                StringLiteral {
+                    area: Bound::new(base + i as u64, base + (i + len) as u64),
+                    encoding: Encoding::Utf16,
+                    value: value,
+                }
+            );
+            i += len;
+            continue;
+        }
+
+        let (value, chars, len) = utf8(&bytes[i..]);
+
+        if chars >= MIN_LENGTH {
+            ret.push(
+                StringLiteral {
+                    area: Bound::new(base + i as u64, base + (i + len) as u64),
+                    encoding: if chars == len { Encoding::Ascii } else { Encoding::Utf8 },
+                    value: value,
+                }
+            );
+        }
+
+        i += if len > 0 { len } else { 1 };
+    }
+
+    ret
+}
+
+fn printable(c: char) -> bool {
+    !c.is_control() || c == '\t' || c == '\n' || c == '\r'
+}
+
+/// Decodes the longest prefix of printable UTF-8 characters. Returns it together with its length
+/// in characters and bytes.
+fn utf8(bytes: &[Option<u8>]) -> (String, usize, usize) {
+    let mut value = String::new();
+    let mut chars = 0;
+    let mut len = 0;
+
+    while let Some(Some(b)) = bytes.get(len).cloned() {
+        let width = match b {
+            0x00...0x7f => 1,
+            0xc2...0xdf => 2,
+            0xe0...0xef => 3,
+            0xf0...0xf4 => 4,
+            _ => break,
+        };
+        let seq = bytes[len..].iter().take(width).filter_map(|&b| b).collect::<Vec<u8>>();
+
+        match str::from_utf8(&seq).ok().and_then(|s| s.chars().next()) {
+            Some(c) if seq.len() == width && printable(c) => {
+                value.push(c);
+                chars += 1;
+                len += width;
+            }
+            _ => break,
+        }
+    }
+
+    (value, chars, len)
+}
+
+/// Decodes a UTF-16LE string of at least `MIN_LENGTH` printable characters, all of them in the
+/// Basic Multilingual Plane and most of them in Latin-1. Returns it together with its length in
+/// bytes.
+fn utf16(bytes: &[Option<u8>]) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut len = 0;
+    let mut latin = 0;
+
+    while let (Some(&Some(lo)), Some(&Some(hi))) = (bytes.get(len), bytes.get(len + 1)) {
+        match ::std::char::from_u32((hi as u32) << 8 | lo as u32) {
+            Some(c) if printable(c) => value.push(c),
+            _ => break,
+        }
+
+        if hi == 0 {
+            latin += 1;
+        }
+        len += 2;
+    }
+
+    // Pairs of ASCII characters decode to valid CJK characters, so nearly all characters must be
+    // Latin-1, starting with the first.
+    if len / 2 >= MIN_LENGTH && bytes[1] == Some(0) && latin * 4 >= len / 2 * 3 { Some((value, len)) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, ControlFlowTarget, Function, Layer, Lvalue, Mnemonic, Operation, Program, Region, Section, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn bytes(b: &[u8]) -> Vec<Option<u8>> {
+        b.iter().map(|&b| Some(b)).collect()
+    }
+
+    #[test]
+    fn encodings() {
+        let mut data = b"\x01Hello\0ab\0\xc3\xa4pfel\0".to_vec();
+        data.extend(b"W\0i\0d\0e\0\0\0\xff");
+
+        let strs = scan(0x100, &bytes(&data));
+
+        assert_eq!(strs.len(), 3);
+        assert_eq!(strs[0], StringLiteral { area: Bound::new(0x101, 0x106), encoding: Encoding::Ascii, value: "Hello".to_string() });
+        assert_eq!(strs[1], StringLiteral { area: Bound::new(0x10a, 0x110), encoding: Encoding::Utf8, value: "äpfel".to_string() });
+        assert_eq!(strs[2], StringLiteral { area: Bound::new(0x111, 0x119), encoding: Encoding::Utf16, value: "Wide".to_string() });
+        assert_eq!(format!("{}", strs[2]), "L\"Wide\"");
+
+        let mut gap = bytes(b"abcdef");
+        gap[2] = None;
+        assert!(scan(0, &gap).iter().all(|s| s.value == "cdef"));
+    }
+
+    #[test]
+    fn references() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x800, 0x810), Layer::wrap(b"usage: %s\0\0\0\0\0\0\0".to_vec())));
+        assert!(reg.cover(Bound::new(0x100, 0x108), Layer::wrap(b"notadata".to_vec())));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        proj.sections = vec![
+            Section { name: ".text".to_string(), area: Bound::new(0x100, 0x200), file_size: 0x100, file_offset: 0, read: true, write: false, execute: true },
+            Section { name: ".rodata".to_string(), area: Bound::new(0x800, 0x900), file_size: 0x100, file_offset: 0, read: true, write: false, execute: false },
+        ];
+
+        let stmts = vec![
+            Statement {
+                op: Operation::Move(Rvalue::new_u64(0x800)),
+                assignee: Lvalue::Variable { name: Cow::Borrowed("RDI"), size: 64, subscript: None },
+            },
+        ];
+        let mne = Mnemonic::new(0x100..0x105, "mov".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+
+        let mut prog = Program::new("prog");
+        prog.insert(func);
+        proj.code.push(prog);
+
+        extract(&mut proj);
+
+        assert_eq!(proj.strings.keys().cloned().collect::<Vec<_>>(), vec![0x800]);
+        assert_eq!(proj.strings[&0x800].value, "usage: %s");
+
+        let func = proj.code[0].functions().next().unwrap();
+        assert_eq!(func.string_refs(), &[StringRef { address: 0x100, string: 0x800 }]);
+    }
+}
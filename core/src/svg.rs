@@ -0,0 +1,241 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! SVG drawings of control flow graphs.
+//!
+//! [`graph`] lays out the control flow graph of a function with `layout::function` and draws
+//! it as a `<svg>` element, for embedding into HTML. [`document`] returns the same drawing as
+//! a standalone SVG file for documentation or reports. Neither needs a GUI or external
+//! stylesheets, the colors are part of the drawing.
+//!
+//! Basic blocks show their mnemonics formatted by `listing::instruction`, the entry block is
+//! marked by an arrow pointing at it. Conditional jumps are green, the fall through edge of a
+//! conditional branch red, with the guard written next to the start of the edge. Blocks are
+//! shaded by how often they were entered if execution traces have been imported.
+//!
+//! [`graph`]: fn.graph.html
+//! [`document`]: fn.document.html
+
+use {ControlFlowRef, ControlFlowTarget, Function, Guard, Program, Project, Result, Spacing, layout, listing};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Width of a character, in pixels.
+pub const CHAR_WIDTH: usize = 7;
+/// Height of a line of text, in pixels.
+pub const LINE_HEIGHT: usize = 14;
+/// Space around the graph, in pixels.
+pub const MARGIN: f32 = 10.;
+
+const STYLE: &'static str = ".cfg text { font-family: monospace; font-size: 12px; }\n\
+.cfg rect { fill: #fff; stroke: #333; }\n\
+.cfg rect.entry { stroke-width: 2; }\n\
+.cfg polyline { fill: none; stroke: #333; marker-end: url(#arrow); }\n\
+.cfg polyline.branch { stroke: #2a2; }\n\
+.cfg polyline.fallthrough { stroke: #c22; }\n\
+.cfg polygon { fill: #333; }\n\
+.cfg text.guard { font-size: 10px; fill: #666; }\n";
+
+/// Escapes `s` for use in XML text and attribute values.
+pub fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            c => ret.push(c),
+        }
+    }
+
+    ret
+}
+
+/// Color of a basic block entered `hits` times, if the hottest one was entered `max` times.
+/// Scaled logarithmically from light yellow to red.
+fn heat(hits: u64, max: u64) -> String {
+    let t = if max > 1 { (hits as f64).ln() / (max as f64).ln() } else { 1. };
+    let green = 240. - 180. * t.max(0.).min(1.);
+
+    format!("#ff{:02x}{:02x}", green as u8, (green / 2.) as u8)
+}
+
+/// Lines of text shown for basic block `vx`.
+fn block_text(proj: &Project, prog: &Program, func: &Function, vx: ControlFlowRef) -> Vec<String> {
+    match func.cfg().vertex_label(vx) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => bb.mnemonics.iter().map(|m| format!("{:x}: {}", m.area.start, listing::instruction(proj, prog, m))).collect(),
+        Some(&ControlFlowTarget::Unresolved(ref rv)) => vec![format!("jump {}", rv)],
+        Some(&ControlFlowTarget::Failed(address, ref msg)) => vec![format!("{:x}: {}", address, msg)],
+        None => vec![],
+    }
+}
+
+/// Class of a jump from `from` to `to`: jumps out of blocks with more than one successor are
+/// either the fall through edge to the next block or the branch.
+fn kind(func: &Function, from: ControlFlowRef, to: ControlFlowRef) -> &'static str {
+    let cfg = func.cfg();
+    let end = match cfg.vertex_label(from) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => bb.area.end,
+        _ => return "jump",
+    };
+
+    if cfg.out_degree(from) < 2 {
+        "jump"
+    } else {
+        match cfg.vertex_label(to) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) if bb.area.start == end => "fallthrough",
+            _ => "branch",
+        }
+    }
+}
+
+/// Returns the control flow graph of `func`, a function of `prog`, as `<svg>` element.
+pub fn graph(proj: &Project, prog: &Program, func: &Function) -> Result<String> {
+    let cfg = func.cfg();
+    let text = cfg.vertices().map(|vx| (vx, block_text(proj, prog, func, vx))).collect::<HashMap<_, _>>();
+    let dims = text.iter()
+        .map(
+            |(&vx, lines)| {
+                let w = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) * CHAR_WIDTH + 10;
+                let h = lines.len() * LINE_HEIGHT + 8;
+                (vx, (w as f32, h as f32))
+            }
+        )
+        .collect::<HashMap<_, _>>();
+    let placement = layout::function(func, &dims, &Spacing::default())?;
+    let mut body = String::new();
+    let mut vxs = placement.nodes.keys().cloned().collect::<Vec<_>>();
+    let hits = proj.coverage.function(func.uuid());
+    let max = hits.values().cloned().max().unwrap_or(0);
+    let entry = func.entry_point_ref();
+
+    vxs.sort();
+    for vx in vxs {
+        let (cx, cy) = placement.nodes[&vx];
+        let (w, h) = dims[&vx];
+        let (x, y) = (MARGIN + cx - w / 2., MARGIN + cy - h / 2.);
+
+        let fill = match cfg.vertex_label(vx) {
+            Some(&ControlFlowTarget::Resolved(ref bb)) if hits.contains_key(&bb.area.start) => format!(" style=\"fill: {}\"", heat(hits[&bb.area.start], max)),
+            _ => String::new(),
+        };
+        let class = if vx == entry { " class=\"entry\"" } else { "" };
+
+        let _ = writeln!(body, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}{}/>", x, y, w, h, class, fill);
+        for (i, l) in text[&vx].iter().enumerate() {
+            let _ = writeln!(body, "<text x=\"{}\" y=\"{}\">{}</text>", x + 5., y + ((i + 1) * LINE_HEIGHT) as f32, escape(l));
+        }
+        if vx == entry {
+            let (ax, ay) = (MARGIN + cx, y);
+            let _ = writeln!(body, "<polygon class=\"entry\" points=\"{},{} {},{} {},{}\"/>", ax - 5., ay - MARGIN, ax + 5., ay - MARGIN, ax, ay);
+        }
+    }
+
+    let mut edges = placement.edges.iter().collect::<Vec<_>>();
+
+    edges.sort_by_key(|&(e, _)| *e);
+    for (&e, route) in edges {
+        let kind = kind(func, cfg.source(e), cfg.target(e));
+        let mut points = route.segments.iter().map(|&(x, y, _, _)| format!("{},{}", MARGIN + x, MARGIN + y)).collect::<Vec<_>>();
+
+        if let Some(&(_, _, x, y)) = route.segments.last() {
+            points.push(format!("{},{}", MARGIN + x, MARGIN + y));
+        }
+
+        let class = if kind == "jump" { String::new() } else { format!(" class=\"{}\"", kind) };
+        let _ = writeln!(body, "<polyline{} points=\"{}\"/>", class, points.join(" "));
+
+        match cfg.edge_label(e) {
+            Some(&Guard::True) | None => {}
+            Some(guard) => {
+                let (x, y) = route.start;
+                let _ = writeln!(body, "<text class=\"guard\" x=\"{}\" y=\"{}\">{}</text>", MARGIN + x + 3., MARGIN + y + 10., escape(&guard.to_string()));
+            }
+        }
+    }
+
+    Ok(
+        format!(
+            "<svg class=\"cfg\" xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n\
+             <style>\n{}</style>\n\
+             <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\">\
+             <path d=\"M 0 0 L 10 5 L 0 10 z\"/></marker></defs>\n{}</svg>\n",
+            placement.width + 2. * MARGIN,
+            placement.height + 2. * MARGIN,
+            STYLE,
+            body
+        )
+    )
+}
+
+/// Returns the control flow graph of `func` as standalone SVG file.
+pub fn document(proj: &Project, prog: &Program, func: &Function) -> Result<String> {
+    Ok(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", graph(proj, prog, func)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, ControlFlowGraph, Mnemonic, Region, Rvalue};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn block(address: u64, opcode: &str) -> ControlFlowTarget {
+        let mne = Mnemonic::new(address..address + 2, opcode.to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne]))
+    }
+
+    /*
+     * 0x100: je 0x110
+     * 0x102: ret
+     * 0x110: ret
+     */
+    #[test]
+    fn conditional() {
+        let proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let zf = Rvalue::Variable { name: "ZF".into(), subscript: None, offset: 0, size: 1 };
+        let guard = Guard::from_flag(&zf).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(block(0x100, "je"));
+        let b1 = cfg.add_vertex(block(0x102, "ret"));
+        let b2 = cfg.add_vertex(block(0x110, "ret"));
+        let mut func = Function::undefined(0x100, None, proj.region(), Some("f<1>".to_string()));
+        let prog = Program::new("prog");
+
+        cfg.add_edge(guard.negation(), b0, b1);
+        cfg.add_edge(guard, b0, b2);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+
+        let svg = document(&proj, &prog, &func).unwrap();
+
+        assert!(svg.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg class=\"cfg\" xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert_eq!(svg.matches("class=\"entry\"").count(), 2);
+        assert_eq!(svg.matches("<polygon class=\"entry\"").count(), 1);
+        assert_eq!(svg.matches("<polyline class=\"fallthrough\"").count(), 1);
+        assert_eq!(svg.matches("<polyline class=\"branch\"").count(), 1);
+        assert!(svg.contains(">ZF</text>"));
+        assert!(svg.contains(">¬ZF</text>"));
+        assert!(svg.contains(">100: je</text>"));
+    }
+}
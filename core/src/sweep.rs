@@ -0,0 +1,278 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Linear sweep and superset disassembly.
+//!
+//! `Function` follows the control flow from known entry points, so code only reached through
+//! jump tables, callbacks or virtual calls is never seen. The two other [`Strategy`]s decode
+//! executable bytes without following jumps.
+//!
+//! A linear sweep decodes one instruction after another from the start of each code area,
+//! skipping a token whenever decoding fails. It recovers most code of a compiler generated
+//! binary but desynchronizes on data embedded in the code. Superset disassembly decodes an
+//! instruction at every token offset, which yields every instruction the CPU could possibly
+//! execute, including those overlapping each other. This is what a search for return oriented
+//! programming gadgets needs.
+//!
+//! Both produce a list of [`Decoded`] instructions. [`entry_points`] turns their call targets
+//! into candidate function starts for the function discovery.
+//!
+//! [`Strategy`]: enum.Strategy.html
+//! [`Decoded`]: struct.Decoded.html
+//! [`entry_points`]: fn.entry_points.html
+
+use {Architecture, Bound, CallTarget, ControlFlowTarget, Guard, Mnemonic, Operation, Project, Region, Rvalue, Statement};
+use panopticon_graph_algos::{GraphTrait, VertexListGraphTrait};
+use std::collections::BTreeSet;
+use std::mem::size_of;
+
+/// How code is found.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Strategy {
+    /// Follow the control flow from known entry points only
+    Recursive,
+    /// Additionally decode the code areas front to back, see `linear`
+    Linear,
+    /// Additionally decode at every offset of the code areas, see `superset`
+    Superset,
+}
+
+impl Default for Strategy {
+    fn default() -> Strategy {
+        Strategy::Recursive
+    }
+}
+
+/// Instruction decoded by a sweep.
+#[derive(Clone,Debug)]
+pub struct Decoded {
+    /// Address of the first byte
+    pub address: u64,
+    /// Number of bytes decoded
+    pub length: u64,
+    /// Mnemonics found at `address`
+    pub mnemonics: Vec<Mnemonic>,
+    /// Jumps/branches of the mnemonics, including the fall through
+    pub jumps: Vec<(u64, Rvalue, Guard)>,
+}
+
+impl Decoded {
+    /// Constant targets of call instructions.
+    pub fn calls(&self) -> Vec<u64> {
+        self.mnemonics
+            .iter()
+            .flat_map(|m| m.instructions.iter())
+            .filter_map(
+                |s| match s {
+                    &Statement { op: Operation::Call(Rvalue::Constant { value, .. }), .. } => Some(value),
+                    _ => None,
+                }
+            )
+            .collect()
+    }
+
+    /// Constant jump targets other than the next instruction.
+    pub fn branches(&self) -> Vec<u64> {
+        let next = self.address + self.length;
+
+        self.jumps
+            .iter()
+            .filter_map(
+                |&(_, ref tgt, _)| match tgt {
+                    &Rvalue::Constant { value, .. } if value != next => Some(value),
+                    _ => None,
+                }
+            )
+            .collect()
+    }
+}
+
+/// Decodes a single instruction at `address`. Returns it and the configuration it leaves the
+/// CPU in.
+fn decode<A: Architecture>(reg: &Region, cfg: &A::Configuration, address: u64) -> Option<(Decoded, A::Configuration)> {
+    let m = A::decode(reg, address, cfg).ok()?;
+    let end = match m.mnemonics.iter().map(|m| m.area.end).max() {
+        Some(end) => end,
+        None => address + (m.tokens.len() * size_of::<A::Token>()) as u64,
+    };
+
+    if m.mnemonics.is_empty() || end <= address {
+        return None;
+    }
+
+    let d = Decoded { address: address, length: end - address, mnemonics: m.mnemonics, jumps: m.jumps };
+    Some((d, m.configuration))
+}
+
+/// Decodes `area` front to back. The configuration is carried from one instruction to the next,
+/// undecodable tokens are skipped.
+pub fn linear<A: Architecture>(reg: &Region, cfg: &A::Configuration, area: &Bound) -> Vec<Decoded> {
+    let step = size_of::<A::Token>() as u64;
+    let mut cfg = cfg.clone();
+    let mut addr = area.start;
+    let mut ret = vec![];
+
+    while addr < area.end && addr < reg.size() {
+        match decode::<A>(reg, &cfg, addr) {
+            Some((d, next)) => {
+                addr += d.length;
+                cfg = next;
+                ret.push(d);
+            }
+            None => addr += step,
+        }
+    }
+
+    ret
+}
+
+/// Decodes an instruction at every token offset of `area`, all with configuration `cfg`.
+pub fn superset<A: Architecture>(reg: &Region, cfg: &A::Configuration, area: &Bound) -> Vec<Decoded> {
+    let step = size_of::<A::Token>() as u64;
+    let mut addr = area.start;
+    let mut ret = vec![];
+
+    while addr < area.end && addr < reg.size() {
+        if let Some((d, _)) = decode::<A>(reg, cfg, addr) {
+            ret.push(d);
+        }
+        addr += step;
+    }
+
+    ret
+}
+
+/// Decodes `area` with `strategy`. Empty for `Strategy::Recursive`.
+pub fn sweep<A: Architecture>(reg: &Region, cfg: &A::Configuration, strategy: Strategy, area: &Bound) -> Vec<Decoded> {
+    match strategy {
+        Strategy::Recursive => vec![],
+        Strategy::Linear => linear::<A>(reg, cfg, area),
+        Strategy::Superset => superset::<A>(reg, cfg, area),
+    }
+}
+
+/// Executable sections of `proj`, or the whole root region if the loader found none.
+pub fn code_areas(proj: &Project) -> Vec<Bound> {
+    let ret = proj.sections.iter().filter(|s| s.execute).map(|s| s.area.clone()).collect::<Vec<_>>();
+
+    if ret.is_empty() { vec![Bound::new(0, proj.region().size())] } else { ret }
+}
+
+/// Entry points of the functions of `proj`, disassembled or not.
+pub fn function_starts(proj: &Project) -> BTreeSet<u64> {
+    let mut ret = BTreeSet::new();
+
+    for prog in proj.code.iter() {
+        for vx in prog.call_graph.vertices() {
+            match prog.call_graph.vertex_label(vx) {
+                Some(&CallTarget::Concrete(ref func)) => {
+                    if let Some(&ControlFlowTarget::Resolved(ref bb)) = func.cfg().vertex_label(func.entry_point_ref()) {
+                        ret.insert(bb.area.start);
+                    }
+                }
+                Some(&CallTarget::Todo(Rvalue::Constant { value, .. }, _, _)) => {
+                    ret.insert(value);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    ret
+}
+
+/// Call targets in `decoded` that lie inside `areas` and aren't the start of a function of
+/// `proj` yet, in ascending order.
+pub fn entry_points(proj: &Project, decoded: &[Decoded], areas: &[Bound]) -> Vec<u64> {
+    let known = function_starts(proj);
+
+    decoded
+        .iter()
+        .flat_map(|d| d.calls())
+        .filter(|a| !known.contains(a) && areas.iter().any(|b| b.start <= *a && *a < b.end))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Lvalue, Match, OpaqueLayer, Program, Result};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    /// 0x90: nop, 0x05: push, 0x01: ret, 0xe8 a: call a
+    #[derive(Clone,Debug)]
+    enum TestArch {}
+    impl Architecture for TestArch {
+        type Token = u8;
+        type Configuration = ();
+
+        fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+            unimplemented!()
+        }
+
+        fn decode(reg: &Region, addr: u64, _: &Self::Configuration) -> Result<Match<Self>> {
+            let bytes = reg.iter().seek(addr).take(2).map(|x| x.unwrap_or(0)).collect::<Vec<_>>();
+            let (opcode, len, stmts) = match bytes[0] {
+                0x90 => ("nop", 1, vec![]),
+                0x05 => ("push", 1, vec![]),
+                0x01 => ("ret", 1, vec![]),
+                0xe8 => ("call", 2, vec![Statement { op: Operation::Call(Rvalue::new_u64(bytes[1] as u64)), assignee: Lvalue::Undefined }]),
+                _ => return Err("unknown opcode".into()),
+            };
+            let mne = Mnemonic::new(addr..addr + len, opcode.to_string(), "".to_string(), vec![].iter(), stmts.iter())?;
+            let jumps = if opcode == "ret" { vec![] } else { vec![(addr, Rvalue::new_u64(addr + len), Guard::always())] };
+
+            Ok(Match { tokens: bytes[0..len as usize].to_vec(), mnemonics: vec![mne], jumps: jumps, configuration: () })
+        }
+    }
+
+    /*
+     * 0: nop
+     * 1: call 5
+     * 3: ret
+     * 4: ???
+     * 5: nop
+     * 6: ret
+     */
+    #[test]
+    fn strategies() {
+        let reg = Region::new("RAM".to_string(), OpaqueLayer::wrap(vec![0x90, 0xe8, 0x05, 0x01, 0xff, 0x90, 0x01]));
+        let area = Bound::new(0, 7);
+        let addresses = |ds: Vec<Decoded>| ds.iter().map(|d| d.address).collect::<Vec<_>>();
+
+        assert_eq!(addresses(linear::<TestArch>(&reg, &(), &area)), vec![0, 1, 3, 5, 6]);
+        assert_eq!(addresses(superset::<TestArch>(&reg, &(), &area)), vec![0, 1, 2, 3, 5, 6]);
+        assert!(sweep::<TestArch>(&reg, &(), Strategy::Recursive, &area).is_empty());
+
+        let mut proj = Project::new("test".to_string(), reg.clone());
+        let decoded = linear::<TestArch>(&reg, &(), &area);
+
+        assert_eq!(decoded[1].calls(), vec![5]);
+        assert!(decoded[1].branches().is_empty());
+        assert_eq!(code_areas(&proj), vec![area.clone()]);
+        assert_eq!(entry_points(&proj, &decoded, &[area.clone()]), vec![5]);
+        assert!(entry_points(&proj, &decoded, &[Bound::new(0, 4)]).is_empty());
+
+        let mut prog = Program::new("prog");
+        prog.call_graph.add_vertex(CallTarget::Todo(Rvalue::new_u64(5), None, ::uuid::Uuid::new_v4()));
+        proj.code.push(prog);
+        assert!(entry_points(&proj, &decoded, &[area]).is_empty());
+    }
+}
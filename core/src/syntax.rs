@@ -0,0 +1,475 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Assembler syntax profiles.
+//!
+//! Disassemblers describe how a mnemonic is printed with a format string of literals and operand
+//! tokens (see `MnemonicFormatToken`). How the opcode, registers, memory operands and constants
+//! end up in the text is decided by a [`Syntax`]. [`Native`] prints everything like the
+//! disassembler wrote it. [`Intel`] and [`Att`] match the two flavours of `objdump -d` for x86,
+//! [`Unified`] and [`Divided`] the two assembler syntaxes for ARM, e.g. `ldrbeq` vs. `ldreqb`.
+//! New profiles implement `Syntax` and override what differs from the default methods.
+//!
+//! [`Options`] control the parts of the output independent of the syntax: radix of constants,
+//! upper case opcodes and registers and whether code pointers are followed by the symbol they
+//! point to. [`profile`] returns a profile by name for front ends letting users choose.
+//!
+//! [`Syntax`]: trait.Syntax.html
+//! [`Native`]: struct.Native.html
+//! [`Intel`]: struct.Intel.html
+//! [`Att`]: struct.Att.html
+//! [`Unified`]: struct.Unified.html
+//! [`Divided`]: struct.Divided.html
+//! [`Options`]: struct.Options.html
+//! [`profile`]: fn.profile.html
+
+use {Mnemonic, MnemonicFormatToken};
+
+/// Names accepted by `profile`.
+pub const PROFILES: [&'static str; 5] = ["native", "intel", "att", "unified", "divided"];
+
+/// Radix constants are printed in.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Radix {
+    /// `0x10`, negative values of signed operands as `-0x10`
+    Hexadecimal,
+    /// `16`, negative values of signed operands as `-16`
+    Decimal,
+}
+
+/// Formatting choices independent of the syntax.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Options {
+    /// Radix of constants. Addresses of code pointers are always hexadecimal.
+    pub radix: Radix,
+    /// Print opcodes and registers in upper case
+    pub uppercase: bool,
+    /// Follow code pointers with the name of the function or import they point to
+    pub symbols: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options { radix: Radix::Hexadecimal, uppercase: false, symbols: true }
+    }
+}
+
+impl Options {
+    /// Opcode or register `name` in the configured case. Numbers like `0x8` stay in lower case.
+    pub fn case(&self, name: &str) -> String {
+        let mut ret = String::with_capacity(name.len());
+        let mut number = false;
+
+        for c in name.chars() {
+            if !c.is_alphanumeric() {
+                number = false;
+            } else if c.is_digit(10) && !ret.chars().last().map(|p| p.is_alphanumeric()).unwrap_or(false) {
+                number = true;
+            }
+
+            if self.uppercase && !number {
+                ret.extend(c.to_uppercase());
+            } else {
+                ret.extend(c.to_lowercase());
+            }
+        }
+
+        ret
+    }
+}
+
+/// Operand of a mnemonic, in the order of the operand tokens of its format string.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Operand {
+    /// Register, named as in the IL
+    Register(String),
+    /// Memory operand named as in the IL, like `DWORD PTR [RBP-0x8]`
+    Memory(String),
+    /// Constant
+    Immediate {
+        /// Value
+        value: u64,
+        /// Size in bits
+        size: usize,
+        /// Printed as signed number
+        signed: bool,
+        /// Address of code
+        code: bool,
+        /// Name of the function, import or relocation `value` refers to
+        symbol: Option<String>,
+    },
+    /// Variable replaced by an expression like `player->health`
+    Expression(String),
+    /// Operand missing or undefined
+    Undefined,
+}
+
+impl Operand {
+    /// Classifies an IL variable `name` used as an operand.
+    pub fn variable(name: &str) -> Operand {
+        if name.contains('[') { Operand::Memory(name.to_string()) } else { Operand::Register(name.to_string()) }
+    }
+}
+
+/// Assembler syntax.
+pub trait Syntax {
+    /// Opcode of `mne` with `operands`.
+    fn opcode(&self, mne: &Mnemonic, _: &[Operand], options: &Options) -> String {
+        options.case(&mne.opcode)
+    }
+
+    /// Text of a single operand.
+    fn operand(&self, op: &Operand, options: &Options) -> String {
+        match op {
+            &Operand::Register(ref name) => options.case(name),
+            &Operand::Memory(ref name) => name.to_lowercase(),
+            &Operand::Immediate { .. } => immediate(op, options),
+            &Operand::Expression(ref expr) => expr.clone(),
+            &Operand::Undefined => "?".to_string(),
+        }
+    }
+
+    /// Operand list of `mne`, given the text of each of its operands.
+    fn operands(&self, mne: &Mnemonic, operands: &[String]) -> String {
+        substitute(mne, operands)
+    }
+}
+
+/// Prints mnemonics as their disassembler describes them, in lower case.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Native;
+
+impl Syntax for Native {}
+
+/// Intel syntax as printed by `objdump -M intel`: `mov DWORD PTR [rbp-0x4],edi`.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Intel;
+
+impl Syntax for Intel {
+    fn operand(&self, op: &Operand, options: &Options) -> String {
+        match op {
+            &Operand::Memory(ref name) => {
+                match name.find(" PTR ") {
+                    Some(p) => format!("{}{}", &name[..p + 5], options.case(&name[p + 5..])),
+                    None => options.case(name),
+                }
+            }
+            _ => Native.operand(op, options),
+        }
+    }
+
+    fn operands(&self, mne: &Mnemonic, operands: &[String]) -> String {
+        substitute(mne, operands).replace(", ", ",")
+    }
+}
+
+/// AT&T syntax as printed by `objdump`: `movl $0x0,-0x4(%rbp)`. Source operands come first,
+/// registers are prefixed with `%` and constants with `$`. Opcodes get a size suffix if no
+/// register operand implies the size.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Att;
+
+impl Syntax for Att {
+    fn opcode(&self, mne: &Mnemonic, operands: &[Operand], options: &Options) -> String {
+        let register = operands.iter().any(
+            |o| match o {
+                &Operand::Register(_) => true,
+                _ => false,
+            }
+        );
+        let suffix = operands.iter()
+            .filter_map(
+                |o| match o {
+                    &Operand::Memory(ref name) if !register => name.split(" PTR ").next(),
+                    _ => None,
+                }
+            )
+            .filter_map(
+                |size| match size {
+                    "BYTE" => Some("b"),
+                    "WORD" => Some("w"),
+                    "DWORD" => Some("l"),
+                    "QWORD" => Some("q"),
+                    _ => None,
+                }
+            )
+            .next()
+            .unwrap_or("");
+
+        options.case(&format!("{}{}", mne.opcode, suffix))
+    }
+
+    fn operand(&self, op: &Operand, options: &Options) -> String {
+        match op {
+            &Operand::Register(ref name) => format!("%{}", options.case(name)),
+            &Operand::Memory(ref name) => att_memory(name, options),
+            &Operand::Immediate { code: false, .. } => format!("${}", immediate(op, options)),
+            _ => Native.operand(op, options),
+        }
+    }
+
+    fn operands(&self, mne: &Mnemonic, operands: &[String]) -> String {
+        if is_list(mne) {
+            operands.iter().rev().cloned().collect::<Vec<_>>().join(",")
+        } else {
+            substitute(mne, operands)
+        }
+    }
+}
+
+/// ARM unified assembler language, condition codes follow the opcode suffix: `ldrbeq`. This is
+/// how the ARM disassembler names mnemonics.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Unified;
+
+impl Syntax for Unified {}
+
+/// Pre-UAL ARM syntax, condition codes precede the opcode suffix: `ldreqb`, `addeqs`.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Divided;
+
+/// ARM condition codes, see `panopticon_arm::CONDITIONS`.
+const CONDITIONS: [&'static str; 14] = ["eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le"];
+
+/// Opcode suffixes placed after the condition code in divided syntax. Longer ones first.
+const SUFFIXES: [&'static str; 9] = ["sb", "sh", "ia", "ib", "da", "db", "s", "b", "h"];
+
+/// Opcodes taking one of `SUFFIXES`.
+const STEMS: [&'static str; 22] = [
+    "and",
+    "eor",
+    "sub",
+    "rsb",
+    "add",
+    "adc",
+    "sbc",
+    "rsc",
+    "orr",
+    "mov",
+    "bic",
+    "mvn",
+    "mul",
+    "mla",
+    "umull",
+    "umlal",
+    "smull",
+    "smlal",
+    "ldr",
+    "str",
+    "ldm",
+    "stm",
+];
+
+impl Syntax for Divided {
+    fn opcode(&self, mne: &Mnemonic, _: &[Operand], options: &Options) -> String {
+        let opcode = &mne.opcode;
+
+        for cond in CONDITIONS.iter() {
+            if !opcode.ends_with(cond) {
+                continue;
+            }
+
+            let stem = &opcode[..opcode.len() - cond.len()];
+
+            for suffix in SUFFIXES.iter() {
+                if stem.ends_with(suffix) && STEMS.contains(&&stem[..stem.len() - suffix.len()]) {
+                    return options.case(&format!("{}{}{}", &stem[..stem.len() - suffix.len()], cond, suffix));
+                }
+            }
+        }
+
+        options.case(opcode)
+    }
+}
+
+/// Returns the profile called `name`, one of `PROFILES`.
+pub fn profile(name: &str) -> Option<Box<Syntax>> {
+    match name {
+        "native" => Some(Box::new(Native)),
+        "intel" => Some(Box::new(Intel)),
+        "att" => Some(Box::new(Att)),
+        "unified" => Some(Box::new(Unified)),
+        "divided" => Some(Box::new(Divided)),
+        _ => None,
+    }
+}
+
+/// Opcode and operands of `mne` in `syntax`.
+pub fn render(syntax: &Syntax, mne: &Mnemonic, operands: &[Operand], options: &Options) -> String {
+    let opcode = syntax.opcode(mne, operands, options);
+    let texts = operands.iter().map(|o| syntax.operand(o, options)).collect::<Vec<_>>();
+    let ops = syntax.operands(mne, &texts);
+
+    if ops.is_empty() { opcode } else { format!("{} {}", opcode, ops) }
+}
+
+/// Constant operand `op` in the radix of `options`, followed by its symbol.
+pub fn immediate(op: &Operand, options: &Options) -> String {
+    let (value, size, signed, code, symbol) = match op {
+        &Operand::Immediate { value, size, signed, code, ref symbol } => (value, size, signed, code, symbol),
+        _ => return String::new(),
+    };
+    let value = if size > 0 && size < 64 { value & ((1u64 << size) - 1) } else { value };
+    let sign = if size > 0 && size < 64 { 1u64 << (size - 1) } else { 1u64 << 63 };
+    let (neg, abs) = if signed && value & sign != 0 { ("-", (sign << 1).wrapping_sub(value)) } else { ("", value) };
+    let mut ret = match options.radix {
+        Radix::Decimal if !code => format!("{}{}", neg, abs),
+        _ => format!("{}{:#x}", neg, abs),
+    };
+
+    if let (true, &Some(ref name)) = (options.symbols, symbol) {
+        ret.push_str(&format!(" <{}>", name));
+    }
+
+    ret
+}
+
+/// Format string of `mne` with the operand tokens replaced by `operands`.
+pub fn substitute(mne: &Mnemonic, operands: &[String]) -> String {
+    let mut ops = operands.iter();
+    let mut ret = String::new();
+
+    for tok in mne.format_string.iter() {
+        match tok {
+            &MnemonicFormatToken::Literal(c) => ret.push(c),
+            _ => ret.push_str(ops.next().map(|s| s.as_str()).unwrap_or("?")),
+        }
+    }
+
+    ret
+}
+
+/// True if the format string of `mne` is a plain comma separated operand list.
+fn is_list(mne: &Mnemonic) -> bool {
+    mne.format_string.iter().all(
+        |t| match t {
+            &MnemonicFormatToken::Literal(c) => c == ',' || c == ' ',
+            _ => true,
+        }
+    )
+}
+
+/// Converts an Intel memory operand like `DWORD PTR FS:[RBP+RAX*4-0x8]` to `%fs:-0x8(%rbp,%rax,4)`.
+fn att_memory(name: &str, options: &Options) -> String {
+    let addr = name.split(" PTR ").last().unwrap_or(name);
+    let (segment, inner) = match (addr.find('['), addr.rfind(']')) {
+        (Some(s), Some(e)) if s < e => (addr[..s].split(':').next().unwrap_or(""), &addr[s + 1..e]),
+        _ => return options.case(name),
+    };
+    let mut base = None;
+    let mut index = None;
+    let mut disp = String::new();
+    let mut terms = vec![];
+    let mut start = 0;
+
+    for (i, c) in inner.char_indices() {
+        if (c == '+' || c == '-') && i > 0 {
+            terms.push(&inner[start..i]);
+            start = i;
+        }
+    }
+    terms.push(&inner[start..]);
+
+    for term in terms {
+        let t = if term.starts_with('+') { &term[1..] } else { term };
+
+        if t.starts_with('-') || t.starts_with(|c: char| c.is_digit(10)) {
+            disp = t.to_string();
+        } else if let Some(p) = t.find('*') {
+            index = Some((&t[..p], &t[p + 1..]));
+        } else if base.is_none() {
+            base = Some(t);
+        } else {
+            index = Some((t, "1"));
+        }
+    }
+
+    let mut ret = if segment.is_empty() { String::new() } else { format!("%{}:", options.case(segment)) };
+
+    ret.push_str(&disp);
+    if base.is_some() || index.is_some() {
+        ret.push('(');
+        if let Some(b) = base {
+            ret.push_str(&format!("%{}", options.case(b)));
+        }
+        if let Some((i, s)) = index {
+            ret.push_str(&format!(",%{},{}", options.case(i), s));
+        }
+        ret.push(')');
+    } else if disp.is_empty() {
+        ret.push_str("0x0");
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rvalue;
+
+    fn mnemonic(opcode: &str, fmt: &str, ops: Vec<Rvalue>) -> Mnemonic {
+        Mnemonic::new(0..4, opcode.to_string(), fmt.to_string(), ops.iter(), vec![].iter()).ok().unwrap()
+    }
+
+    fn var(name: &str) -> Operand {
+        Operand::variable(name)
+    }
+
+    fn imm(value: u64, signed: bool) -> Operand {
+        Operand::Immediate { value: value, size: 32, signed: signed, code: false, symbol: None }
+    }
+
+    #[test]
+    fn x86_profiles() {
+        let opts = Options::default();
+        let store = mnemonic("mov", "{u}, {s}", vec![]);
+        let ops = vec![var("DWORD PTR FS:[RBP+RAX*4-0x8]"), imm(0xfffffff0, true)];
+        let load = mnemonic("mov", "{u}, {u}", vec![]);
+        let lea = vec![var("RAX"), var("[RIP+0x200]")];
+        let call = mnemonic("call", "{c:RAM}", vec![]);
+        let target = vec![Operand::Immediate { value: 0x200, size: 64, signed: false, code: true, symbol: Some("main".to_string()) }];
+
+        assert_eq!(render(&Native, &store, &ops, &opts), "mov dword ptr fs:[rbp+rax*4-0x8], -0x10");
+        assert_eq!(render(&Intel, &store, &ops, &opts), "mov DWORD PTR fs:[rbp+rax*4-0x8],-0x10");
+        assert_eq!(render(&Att, &store, &ops, &opts), "movl $-0x10,%fs:-0x8(%rbp,%rax,4)");
+        assert_eq!(render(&Att, &load, &lea, &opts), "mov 0x200(%rip),%rax");
+        assert_eq!(render(&Att, &call, &target, &opts), "call 0x200 <main>");
+        assert_eq!(render(&Att, &load, &[var("RBX"), var("QWORD PTR [RSI+RCX]")], &opts), "mov (%rsi,%rcx,1),%rbx");
+
+        let opts = Options { radix: Radix::Decimal, uppercase: true, symbols: false };
+        assert_eq!(render(&Intel, &store, &ops, &opts), "MOV DWORD PTR FS:[RBP+RAX*4-0x8],-16");
+        assert_eq!(render(&Att, &call, &target, &opts), "CALL 0x200");
+    }
+
+    #[test]
+    fn arm_profiles() {
+        let opts = Options::default();
+        let ops = vec![var("R0"), var("R1")];
+
+        for &(unified, divided) in [("ldrbeq", "ldreqb"), ("addseq", "addeqs"), ("ldmiane", "ldmneia"), ("ldrsbgt", "ldrgtsb"), ("teq", "teq"), ("lsls", "lsls"), ("movls", "movls"), ("adcs", "adcs"), ("smulls", "smulls")].iter() {
+            let mne = mnemonic(unified, "{u}, {u}", vec![]);
+
+            assert_eq!(render(&Unified, &mne, &ops, &opts), format!("{} r0, r1", unified));
+            assert_eq!(render(&Divided, &mne, &ops, &opts), format!("{} r0, r1", divided));
+        }
+
+        assert!(PROFILES.iter().all(|p| profile(p).is_some()));
+        assert!(profile("gas").is_none());
+    }
+}
@@ -0,0 +1,344 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! Linux system call recognition.
+//!
+//! Architecture crates lift `syscall` and `int 0x80` to mnemonics without semantics. This module
+//! finds these mnemonics, tries to prove the system call number constant and pairs the call with
+//! the argument registers of the Linux ABI.
+//!
+//! Resolution is block-local: only the statements preceding the system call inside the same basic
+//! block are considered. Numbers set in a predecessor block are reported as unknown.
+
+use {BasicBlock, Lvalue, Mnemonic, Operation, Rvalue, Statement, execute};
+use std::borrow::Cow;
+
+/// Linux system call convention.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum SyscallAbi {
+    /// `syscall` instruction of x86-64. Number in `RAX`, arguments in `RDI`, `RSI`, `RDX`, `R10`,
+    /// `R8` and `R9`.
+    Amd64,
+    /// `int 0x80` of i386. Number in `EAX`, arguments in `EBX`, `ECX`, `EDX`, `ESI`, `EDI` and
+    /// `EBP`. 64 bit code uses this convention with `int 0x80`, too.
+    I386,
+}
+
+impl SyscallAbi {
+    /// Returns the ABI used by the system call instruction `mne` or None if `mne` isn't one.
+    pub fn from_mnemonic(mne: &Mnemonic) -> Option<SyscallAbi> {
+        match (mne.opcode.as_str(), mne.operands.first()) {
+            ("syscall", _) => Some(SyscallAbi::Amd64),
+            ("int", Some(&Rvalue::Constant { value: 0x80, .. })) => Some(SyscallAbi::I386),
+            _ => None,
+        }
+    }
+
+    /// Register width in bits.
+    pub fn width(&self) -> usize {
+        match *self {
+            SyscallAbi::Amd64 => 64,
+            SyscallAbi::I386 => 32,
+        }
+    }
+
+    /// Registers holding the system call number, in order of preference.
+    fn number_registers(&self) -> &'static [&'static str] {
+        match *self {
+            SyscallAbi::Amd64 => &["RAX", "EAX"],
+            SyscallAbi::I386 => &["EAX", "RAX"],
+        }
+    }
+
+    /// Argument registers, in order.
+    pub fn argument_registers(&self) -> &'static [&'static str; 6] {
+        match *self {
+            SyscallAbi::Amd64 => &["RDI", "RSI", "RDX", "R10", "R8", "R9"],
+            SyscallAbi::I386 => &["EBX", "ECX", "EDX", "ESI", "EDI", "EBP"],
+        }
+    }
+
+    /// Name and number of arguments of system call `nr`.
+    pub fn lookup(&self, nr: u64) -> Option<(&'static str, usize)> {
+        match *self {
+            SyscallAbi::Amd64 => X86_64.get(nr as usize).cloned(),
+            SyscallAbi::I386 => {
+                I386.binary_search_by_key(&nr, |&(n, _, _)| n)
+                    .ok()
+                    .map(|i| (I386[i].1, I386[i].2))
+            }
+        }
+    }
+}
+
+/// A system call inside a function.
+#[derive(Clone,Debug)]
+pub struct Syscall<'a> {
+    /// Address of the system call instruction
+    pub address: u64,
+    /// Calling convention
+    pub abi: SyscallAbi,
+    /// System call number, if the code before the call proves it constant
+    pub number: Option<u64>,
+    /// The `syscall` or `int` mnemonic
+    pub mnemonic: &'a Mnemonic,
+}
+
+impl<'a> Syscall<'a> {
+    /// Name of the system call, e.g. "write". None if the number is unknown.
+    pub fn name(&self) -> Option<&'static str> {
+        self.number.and_then(|nr| self.abi.lookup(nr)).map(|(name, _)| name)
+    }
+
+    /// Registers holding the arguments of the system call. If the number is unknown all six
+    /// argument registers are returned.
+    pub fn arguments(&self) -> Vec<Rvalue> {
+        let argc = self.number.and_then(|nr| self.abi.lookup(nr)).map(|(_, argc)| argc).unwrap_or(6);
+
+        self.abi
+            .argument_registers()
+            .iter()
+            .take(argc)
+            .map(
+                |&reg| {
+                    Rvalue::Variable {
+                        name: Cow::Borrowed(reg),
+                        subscript: None,
+                        offset: 0,
+                        size: self.abi.width(),
+                    }
+                }
+            )
+            .collect()
+    }
+}
+
+/// Returns all system calls inside `bb`.
+pub fn recognize(bb: &BasicBlock) -> Vec<Syscall> {
+    recognize_in(bb.mnemonics.iter().map(|mne| (mne, mne.instructions.as_slice())).collect())
+}
+
+/// Like `recognize`, but takes the mnemonics of a basic block together with their statements,
+/// see `Function::mnemonics`.
+pub fn recognize_in<'a>(mnemonics: Vec<(&'a Mnemonic, &'a [Statement])>) -> Vec<Syscall<'a>> {
+    let mut ret = vec![];
+
+    for (idx, &(mne, _)) in mnemonics.iter().enumerate() {
+        if let Some(abi) = SyscallAbi::from_mnemonic(mne) {
+            let prev = mnemonics[..idx].iter().flat_map(|&(_, stmts)| stmts.iter()).collect::<Vec<_>>();
+            let number = abi.number_registers().iter().filter_map(|reg| constant(&prev, reg, 0, abi.width())).next();
+
+            ret.push(
+                Syscall {
+                    address: mne.area.start,
+                    abi: abi,
+                    number: number,
+                    mnemonic: mne,
+                }
+            );
+        }
+    }
+
+    ret
+}
+
+/// Evaluates bits `offset` to `offset + size` of `name` after executing `stmts`.
+fn constant(stmts: &[&Statement], name: &str, offset: usize, size: usize) -> Option<u64> {
+    let pos = stmts.iter().rposition(
+        |stmt| match stmt.assignee {
+            Lvalue::Variable { name: ref n, .. } => n == name,
+            Lvalue::Undefined => false,
+        }
+    )?;
+    let value = evaluate(&stmts[..pos], &stmts[pos].op, stmts[pos].assignee.size()?)?;
+    let mask = if size < 64 { (1u64 << size) - 1 } else { !0 };
+
+    if offset < 64 { Some((value >> offset) & mask) } else { None }
+}
+
+fn evaluate(stmts: &[&Statement], op: &Operation<Rvalue>, size: usize) -> Option<u64> {
+    match *op {
+        Operation::ExclusiveOr(ref a, ref b) |
+        Operation::Subtract(ref a, ref b) if a == b && *a != Rvalue::Undefined => return Some(0),
+        Operation::Load(..) | Operation::Store(..) | Operation::Call(_) | Operation::Phi(_) | Operation::Initialize(..) => return None,
+        _ => {}
+    }
+
+    let mut op = op.clone();
+
+    for rv in op.operands_mut() {
+        let value = match *rv {
+            Rvalue::Constant { .. } => continue,
+            Rvalue::Variable { ref name, offset, size, .. } => constant(stmts, name, offset, size)?,
+            Rvalue::Undefined => return None,
+        };
+        let sz = rv.size()?;
+
+        *rv = Rvalue::Constant { value: value, size: sz };
+    }
+
+    match execute(op) {
+        Rvalue::Constant { value, .. } => {
+            let mask = if size < 64 { (1u64 << size) - 1 } else { !0 };
+            Some(value & mask)
+        }
+        _ => None,
+    }
+}
+
+/// x86-64 system calls, indexed by number.
+static X86_64: [(&'static str, usize); 333] = [
+    ("read", 3), ("write", 3), ("open", 3), ("close", 1), ("stat", 2), ("fstat", 2), ("lstat", 2), ("poll", 3),
+    ("lseek", 3), ("mmap", 6), ("mprotect", 3), ("munmap", 2), ("brk", 1), ("rt_sigaction", 4), ("rt_sigprocmask", 4), ("rt_sigreturn", 0),
+    ("ioctl", 3), ("pread64", 4), ("pwrite64", 4), ("readv", 3), ("writev", 3), ("access", 2), ("pipe", 1), ("select", 5),
+    ("sched_yield", 0), ("mremap", 5), ("msync", 3), ("mincore", 3), ("madvise", 3), ("shmget", 3), ("shmat", 3), ("shmctl", 3),
+    ("dup", 1), ("dup2", 2), ("pause", 0), ("nanosleep", 2), ("getitimer", 2), ("alarm", 1), ("setitimer", 3), ("getpid", 0),
+    ("sendfile", 4), ("socket", 3), ("connect", 3), ("accept", 3), ("sendto", 6), ("recvfrom", 6), ("sendmsg", 3), ("recvmsg", 3),
+    ("shutdown", 2), ("bind", 3), ("listen", 2), ("getsockname", 3), ("getpeername", 3), ("socketpair", 4), ("setsockopt", 5), ("getsockopt", 5),
+    ("clone", 5), ("fork", 0), ("vfork", 0), ("execve", 3), ("exit", 1), ("wait4", 4), ("kill", 2), ("uname", 1),
+    ("semget", 3), ("semop", 3), ("semctl", 4), ("shmdt", 1), ("msgget", 2), ("msgsnd", 4), ("msgrcv", 5), ("msgctl", 3),
+    ("fcntl", 3), ("flock", 2), ("fsync", 1), ("fdatasync", 1), ("truncate", 2), ("ftruncate", 2), ("getdents", 3), ("getcwd", 2),
+    ("chdir", 1), ("fchdir", 1), ("rename", 2), ("mkdir", 2), ("rmdir", 1), ("creat", 2), ("link", 2), ("unlink", 1),
+    ("symlink", 2), ("readlink", 3), ("chmod", 2), ("fchmod", 2), ("chown", 3), ("fchown", 3), ("lchown", 3), ("umask", 1),
+    ("gettimeofday", 2), ("getrlimit", 2), ("getrusage", 2), ("sysinfo", 1), ("times", 1), ("ptrace", 4), ("getuid", 0), ("syslog", 3),
+    ("getgid", 0), ("setuid", 1), ("setgid", 1), ("geteuid", 0), ("getegid", 0), ("setpgid", 2), ("getppid", 0), ("getpgrp", 0),
+    ("setsid", 0), ("setreuid", 2), ("setregid", 2), ("getgroups", 2), ("setgroups", 2), ("setresuid", 3), ("getresuid", 3), ("setresgid", 3),
+    ("getresgid", 3), ("getpgid", 1), ("setfsuid", 1), ("setfsgid", 1), ("getsid", 1), ("capget", 2), ("capset", 2), ("rt_sigpending", 2),
+    ("rt_sigtimedwait", 4), ("rt_sigqueueinfo", 3), ("rt_sigsuspend", 2), ("sigaltstack", 2), ("utime", 2), ("mknod", 3), ("uselib", 1), ("personality", 1),
+    ("ustat", 2), ("statfs", 2), ("fstatfs", 2), ("sysfs", 3), ("getpriority", 2), ("setpriority", 3), ("sched_setparam", 2), ("sched_getparam", 2),
+    ("sched_setscheduler", 3), ("sched_getscheduler", 1), ("sched_get_priority_max", 1), ("sched_get_priority_min", 1), ("sched_rr_get_interval", 2), ("mlock", 2), ("munlock", 2), ("mlockall", 1),
+    ("munlockall", 0), ("vhangup", 0), ("modify_ldt", 3), ("pivot_root", 2), ("_sysctl", 1), ("prctl", 5), ("arch_prctl", 2), ("adjtimex", 1),
+    ("setrlimit", 2), ("chroot", 1), ("sync", 0), ("acct", 1), ("settimeofday", 2), ("mount", 5), ("umount2", 2), ("swapon", 2),
+    ("swapoff", 1), ("reboot", 4), ("sethostname", 2), ("setdomainname", 2), ("iopl", 1), ("ioperm", 3), ("create_module", 2), ("init_module", 3),
+    ("delete_module", 2), ("get_kernel_syms", 1), ("query_module", 5), ("quotactl", 4), ("nfsservctl", 3), ("getpmsg", 5), ("putpmsg", 5), ("afs_syscall", 0),
+    ("tuxcall", 0), ("security", 0), ("gettid", 0), ("readahead", 3), ("setxattr", 5), ("lsetxattr", 5), ("fsetxattr", 5), ("getxattr", 4),
+    ("lgetxattr", 4), ("fgetxattr", 4), ("listxattr", 3), ("llistxattr", 3), ("flistxattr", 3), ("removexattr", 2), ("lremovexattr", 2), ("fremovexattr", 2),
+    ("tkill", 2), ("time", 1), ("futex", 6), ("sched_setaffinity", 3), ("sched_getaffinity", 3), ("set_thread_area", 1), ("io_setup", 2), ("io_destroy", 1),
+    ("io_getevents", 5), ("io_submit", 3), ("io_cancel", 3), ("get_thread_area", 1), ("lookup_dcookie", 3), ("epoll_create", 1), ("epoll_ctl_old", 4), ("epoll_wait_old", 4),
+    ("remap_file_pages", 5), ("getdents64", 3), ("set_tid_address", 1), ("restart_syscall", 0), ("semtimedop", 4), ("fadvise64", 4), ("timer_create", 3), ("timer_settime", 4),
+    ("timer_gettime", 2), ("timer_getoverrun", 1), ("timer_delete", 1), ("clock_settime", 2), ("clock_gettime", 2), ("clock_getres", 2), ("clock_nanosleep", 4), ("exit_group", 1),
+    ("epoll_wait", 4), ("epoll_ctl", 4), ("tgkill", 3), ("utimes", 2), ("vserver", 0), ("mbind", 6), ("set_mempolicy", 3), ("get_mempolicy", 5),
+    ("mq_open", 4), ("mq_unlink", 1), ("mq_timedsend", 5), ("mq_timedreceive", 5), ("mq_notify", 2), ("mq_getsetattr", 3), ("kexec_load", 4), ("waitid", 5),
+    ("add_key", 5), ("request_key", 4), ("keyctl", 5), ("ioprio_set", 3), ("ioprio_get", 2), ("inotify_init", 0), ("inotify_add_watch", 3), ("inotify_rm_watch", 2),
+    ("migrate_pages", 4), ("openat", 4), ("mkdirat", 3), ("mknodat", 4), ("fchownat", 5), ("futimesat", 3), ("newfstatat", 4), ("unlinkat", 3),
+    ("renameat", 4), ("linkat", 5), ("symlinkat", 3), ("readlinkat", 4), ("fchmodat", 3), ("faccessat", 3), ("pselect6", 6), ("ppoll", 5),
+    ("unshare", 1), ("set_robust_list", 2), ("get_robust_list", 3), ("splice", 6), ("tee", 4), ("sync_file_range", 4), ("vmsplice", 4), ("move_pages", 6),
+    ("utimensat", 4), ("epoll_pwait", 6), ("signalfd", 3), ("timerfd_create", 2), ("eventfd", 1), ("fallocate", 4), ("timerfd_settime", 4), ("timerfd_gettime", 2),
+    ("accept4", 4), ("signalfd4", 4), ("eventfd2", 2), ("epoll_create1", 1), ("dup3", 3), ("pipe2", 2), ("inotify_init1", 1), ("preadv", 5),
+    ("pwritev", 5), ("rt_tgsigqueueinfo", 4), ("perf_event_open", 5), ("recvmmsg", 5), ("fanotify_init", 2), ("fanotify_mark", 5), ("prlimit64", 4), ("name_to_handle_at", 5),
+    ("open_by_handle_at", 3), ("clock_adjtime", 2), ("syncfs", 1), ("sendmmsg", 4), ("setns", 2), ("getcpu", 3), ("process_vm_readv", 6), ("process_vm_writev", 6),
+    ("kcmp", 5), ("finit_module", 3), ("sched_setattr", 3), ("sched_getattr", 4), ("renameat2", 5), ("seccomp", 3), ("getrandom", 3), ("memfd_create", 2),
+    ("kexec_file_load", 5), ("bpf", 3), ("execveat", 5), ("userfaultfd", 1), ("membarrier", 2), ("mlock2", 3), ("copy_file_range", 6), ("preadv2", 6),
+    ("pwritev2", 6), ("pkey_mprotect", 4), ("pkey_alloc", 2), ("pkey_free", 1), ("statx", 5),
+];
+
+/// Commonly used i386 system calls, sorted by number.
+static I386: [(u64, &'static str, usize); 120] = [
+    (1, "exit", 1), (2, "fork", 0), (3, "read", 3), (4, "write", 3), (5, "open", 3), (6, "close", 1), (7, "waitpid", 3), (8, "creat", 2),
+    (9, "link", 2), (10, "unlink", 1), (11, "execve", 3), (12, "chdir", 1), (13, "time", 1), (14, "mknod", 3), (15, "chmod", 2), (16, "lchown", 3),
+    (19, "lseek", 3), (20, "getpid", 0), (21, "mount", 5), (22, "umount", 1), (23, "setuid", 1), (24, "getuid", 0), (25, "stime", 1), (26, "ptrace", 4),
+    (27, "alarm", 1), (29, "pause", 0), (30, "utime", 2), (33, "access", 2), (34, "nice", 1), (36, "sync", 0), (37, "kill", 2), (38, "rename", 2),
+    (39, "mkdir", 2), (40, "rmdir", 1), (41, "dup", 1), (42, "pipe", 1), (43, "times", 1), (45, "brk", 1), (46, "setgid", 1), (47, "getgid", 0),
+    (48, "signal", 2), (49, "geteuid", 0), (50, "getegid", 0), (51, "acct", 1), (52, "umount2", 2), (54, "ioctl", 3), (55, "fcntl", 3), (57, "setpgid", 2),
+    (60, "umask", 1), (61, "chroot", 1), (62, "ustat", 2), (63, "dup2", 2), (64, "getppid", 0), (65, "getpgrp", 0), (66, "setsid", 0), (67, "sigaction", 3),
+    (68, "sgetmask", 0), (69, "ssetmask", 1), (70, "setreuid", 2), (71, "setregid", 2), (74, "sethostname", 2), (75, "setrlimit", 2), (76, "getrlimit", 2), (77, "getrusage", 2),
+    (78, "gettimeofday", 2), (79, "settimeofday", 2), (83, "symlink", 2), (85, "readlink", 3), (88, "reboot", 4), (90, "mmap", 1), (91, "munmap", 2), (94, "fchmod", 2),
+    (95, "fchown", 3), (96, "getpriority", 2), (97, "setpriority", 3), (102, "socketcall", 2), (106, "stat", 2), (107, "lstat", 2), (108, "fstat", 2), (114, "wait4", 4),
+    (118, "fsync", 1), (119, "sigreturn", 0), (120, "clone", 5), (122, "uname", 1), (125, "mprotect", 3), (140, "_llseek", 5), (141, "getdents", 3), (142, "_newselect", 5),
+    (143, "flock", 2), (145, "readv", 3), (146, "writev", 3), (158, "sched_yield", 0), (162, "nanosleep", 2), (163, "mremap", 5), (168, "poll", 3), (172, "prctl", 5),
+    (173, "rt_sigreturn", 0), (174, "rt_sigaction", 4), (175, "rt_sigprocmask", 4), (180, "pread64", 4), (181, "pwrite64", 4), (183, "getcwd", 2), (187, "sendfile", 4), (190, "vfork", 0),
+    (192, "mmap2", 6), (195, "stat64", 2), (197, "fstat64", 2), (199, "getuid32", 0), (220, "getdents64", 3), (221, "fcntl64", 3), (224, "gettid", 0), (240, "futex", 6),
+    (243, "set_thread_area", 1), (252, "exit_group", 1), (258, "set_tid_address", 1), (265, "clock_gettime", 2), (270, "tgkill", 3), (295, "openat", 4), (355, "getrandom", 3), (356, "memfd_create", 2),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Result, Statement};
+
+    fn mnemonic(addr: u64, opcode: &str, ops: Vec<Rvalue>, stmts: Result<Vec<Statement>>) -> Mnemonic {
+        let stmts = stmts.unwrap();
+        Mnemonic::new(addr..addr + 2, opcode.to_string(), "".to_string(), ops.iter(), stmts.iter()).unwrap()
+    }
+
+    #[test]
+    fn amd64_constant_number() {
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "mov", vec![], rreil!{ mov EAX:32, [60]:32; zext/64 RAX:64, EAX:32; }),
+                mnemonic(2, "xor", vec![], rreil!{ xor EDI:32, EDI:32, EDI:32; zext/64 RDI:64, EDI:32; }),
+                mnemonic(4, "syscall", vec![], Ok(vec![])),
+            ]
+        );
+        let calls = recognize(&bb);
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].address, 4);
+        assert_eq!(calls[0].abi, SyscallAbi::Amd64);
+        assert_eq!(calls[0].number, Some(60));
+        assert_eq!(calls[0].name(), Some("exit"));
+        assert_eq!(calls[0].arguments(), vec![rreil_rvalue!{ RDI:64 }]);
+    }
+
+    #[test]
+    fn amd64_zeroed_number() {
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "xor", vec![], rreil!{ xor EAX:32, EAX:32, EAX:32; zext/64 RAX:64, EAX:32; }),
+                mnemonic(2, "syscall", vec![], Ok(vec![])),
+            ]
+        );
+        let calls = recognize(&bb);
+
+        assert_eq!(calls[0].name(), Some("read"));
+        assert_eq!(calls[0].arguments().len(), 3);
+    }
+
+    #[test]
+    fn amd64_unknown_number() {
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "mov", vec![], rreil!{ load/ram/le/64 RAX:64, RSP:64; }),
+                mnemonic(2, "syscall", vec![], Ok(vec![])),
+            ]
+        );
+        let calls = recognize(&bb);
+
+        assert_eq!(calls[0].number, None);
+        assert_eq!(calls[0].name(), None);
+        assert_eq!(calls[0].arguments().len(), 6);
+    }
+
+    #[test]
+    fn i386_int80() {
+        let bb = BasicBlock::from_vec(
+            vec![
+                mnemonic(0, "mov", vec![], rreil!{ mov EAX:32, [2]:32; add EAX:32, EAX:32, [2]:32; }),
+                mnemonic(2, "int", vec![Rvalue::new_u8(3)], Ok(vec![])),
+                mnemonic(4, "int", vec![Rvalue::new_u8(0x80)], Ok(vec![])),
+            ]
+        );
+        let calls = recognize(&bb);
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].abi, SyscallAbi::I386);
+        assert_eq!(calls[0].name(), Some("write"));
+        assert_eq!(calls[0].arguments(), vec![rreil_rvalue!{ EBX:32 }, rreil_rvalue!{ ECX:32 }, rreil_rvalue!{ EDX:32 }]);
+    }
+}
@@ -0,0 +1,342 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Listings and IL for terminals.
+//!
+//! A [`Printer`] prints the same listing as `listing::function`, highlighted with ANSI escape
+//! sequences: addresses, raw bytes, opcodes, registers, constants, symbols and comments each get
+//! the color of their [`Theme`] entry. [`Printer::statements`] prints the IL of a function the
+//! same way. `Theme::plain` disables colors, e.g. if the output isn't a terminal.
+//!
+//! If `Printer::width` is set lines longer than that many columns are wrapped at the last space
+//! that fits, continuation lines are indented to the start of the opcode. Escape sequences don't
+//! count towards the width.
+//!
+//! Highlighting of operands is done by wrapping the `syntax::Syntax` used to print them, so it
+//! works with any syntax profile.
+//!
+//! [`Printer`]: struct.Printer.html
+//! [`Printer::statements`]: struct.Printer.html#method.statements
+//! [`Theme`]: struct.Theme.html
+
+use {Function, Mnemonic, Program, Project, Statement};
+use listing::{self, BYTES_PER_LINE};
+use std::fmt::Write;
+use syntax::{Native, Operand, Options, Syntax};
+
+/// Colors of the parts of a listing, as SGR parameters like `"1;34"`. Empty strings leave the
+/// text as is.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Theme {
+    /// Addresses at the start of each line
+    pub address: &'static str,
+    /// Raw bytes
+    pub bytes: &'static str,
+    /// Opcodes and IL operations
+    pub mnemonic: &'static str,
+    /// Registers, memory operands and IL variables
+    pub register: &'static str,
+    /// Constants
+    pub immediate: &'static str,
+    /// Names of functions, imports and typed variables
+    pub symbol: &'static str,
+    /// Cross reference comments
+    pub comment: &'static str,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme { address: "90", bytes: "2", mnemonic: "1;34", register: "33", immediate: "36", symbol: "32", comment: "3;90" }
+    }
+}
+
+impl Theme {
+    /// Theme without any colors.
+    pub fn plain() -> Theme {
+        Theme { address: "", bytes: "", mnemonic: "", register: "", immediate: "", symbol: "", comment: "" }
+    }
+
+    /// `text` in color `sgr`.
+    pub fn paint(&self, sgr: &str, text: &str) -> String {
+        if sgr.is_empty() || text.is_empty() { text.to_string() } else { format!("\x1b[{}m{}\x1b[0m", sgr, text) }
+    }
+}
+
+/// Wraps a syntax profile, coloring opcodes and operands.
+struct Highlight<'a> {
+    syntax: &'a Syntax,
+    theme: &'a Theme,
+}
+
+impl<'a> Syntax for Highlight<'a> {
+    fn opcode(&self, mne: &Mnemonic, operands: &[Operand], options: &Options) -> String {
+        self.theme.paint(self.theme.mnemonic, &self.syntax.opcode(mne, operands, options))
+    }
+
+    fn operand(&self, op: &Operand, options: &Options) -> String {
+        let theme = self.theme;
+
+        match op {
+            &Operand::Register(_) |
+            &Operand::Memory(_) => theme.paint(theme.register, &self.syntax.operand(op, options)),
+            &Operand::Expression(_) => theme.paint(theme.symbol, &self.syntax.operand(op, options)),
+            &Operand::Immediate { value, size, signed, code, ref symbol } => {
+                let bare = Operand::Immediate { value: value, size: size, signed: signed, code: code, symbol: None };
+                let mut ret = theme.paint(theme.immediate, &self.syntax.operand(&bare, options));
+
+                if let (true, &Some(ref name)) = (options.symbols, symbol) {
+                    ret = format!("{} {}", ret, theme.paint(theme.symbol, &format!("<{}>", name)));
+                }
+
+                ret
+            }
+            &Operand::Undefined => self.syntax.operand(op, options),
+        }
+    }
+
+    fn operands(&self, mne: &Mnemonic, operands: &[String]) -> String {
+        self.syntax.operands(mne, operands)
+    }
+}
+
+/// Prints highlighted listings.
+pub struct Printer<'a> {
+    /// Syntax of mnemonics
+    pub syntax: &'a Syntax,
+    /// Formatting of mnemonics
+    pub options: Options,
+    /// Colors
+    pub theme: Theme,
+    /// Maximal line length in columns, None to never wrap
+    pub width: Option<usize>,
+}
+
+impl Default for Printer<'static> {
+    fn default() -> Printer<'static> {
+        Printer::new(&Native)
+    }
+}
+
+impl<'a> Printer<'a> {
+    /// Printer using `syntax` and the default theme, not wrapping lines.
+    pub fn new(syntax: &'a Syntax) -> Printer<'a> {
+        Printer { syntax: syntax, options: Options::default(), theme: Theme::default(), width: None }
+    }
+
+    /// Returns the listing of `func`, a function of `prog`.
+    pub fn function(&self, proj: &Project, prog: &Program, func: &Function) -> String {
+        let theme = &self.theme;
+        let highlight = Highlight { syntax: self.syntax, theme: theme };
+        let refs = listing::comment(proj, func.start());
+        let mut ret = format!(
+            "{} {}:",
+            theme.paint(theme.address, &format!("{:016x}", func.start())),
+            theme.paint(theme.symbol, &format!("<{}>", func.display_name()))
+        );
+
+        if !refs.is_empty() {
+            ret = format!("{}  {}", ret, theme.paint(theme.comment, &format!("; {}", refs)));
+        }
+        ret = self.wrap(&ret, 0);
+        ret.push('\n');
+
+        for line in listing::lines(proj, prog, func, &highlight, &self.options) {
+            let address = theme.paint(theme.address, &format!("{:8x}:", line.address));
+            let bytes = line.bytes.join(" ");
+
+            if line.text.is_empty() {
+                let _ = writeln!(ret, "{} {}", address, theme.paint(theme.bytes, &bytes));
+            } else {
+                let bytes = format!("{:<w$}", bytes, w = BYTES_PER_LINE * 3 - 1);
+                let mut text = format!("{} {}  {}", address, theme.paint(theme.bytes, &bytes), line.text);
+
+                if !line.comment.is_empty() {
+                    text = format!("{}  {}", text, theme.paint(theme.comment, &format!("; {}", line.comment)));
+                }
+
+                let _ = writeln!(ret, "{}", self.wrap(&text, 8 + 2 + BYTES_PER_LINE * 3 - 1 + 2));
+            }
+        }
+
+        ret
+    }
+
+    /// Returns the listings of all functions in `prog` ordered by address, separated by empty
+    /// lines.
+    pub fn program(&self, proj: &Project, prog: &Program) -> String {
+        let mut funcs = prog.functions().collect::<Vec<_>>();
+
+        funcs.sort_by_key(|f| f.start());
+        funcs.iter().map(|f| self.function(proj, prog, f)).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Returns the IL of `func`, grouped by mnemonic in address order.
+    pub fn statements(&self, func: &Function) -> String {
+        let theme = &self.theme;
+        let mut mnes = func.basic_blocks().flat_map(|bb| bb.mnemonics.iter()).collect::<Vec<_>>();
+        let mut ret = String::new();
+
+        mnes.sort_by_key(|m| (m.area.start, m.area.end));
+        mnes.dedup_by_key(|m| (m.area.start, m.area.end));
+
+        for mne in mnes {
+            let _ = writeln!(
+                ret,
+                "{} {}",
+                theme.paint(theme.address, &format!("{:8x}:", mne.area.start)),
+                theme.paint(theme.comment, &format!("; {}", mne.opcode))
+            );
+
+            for stmt in mne.instructions.iter() {
+                let _ = writeln!(ret, "{}", self.wrap(&format!("          {}", self.statement(stmt)), 10));
+            }
+        }
+
+        ret
+    }
+
+    /// Returns `stmt` highlighted.
+    pub fn statement(&self, stmt: &Statement) -> String {
+        let theme = &self.theme;
+        let text = stmt.to_string();
+        let (op, args) = match text.find(' ') {
+            Some(p) => (&text[..p], &text[p + 1..]),
+            None => (&text[..], ""),
+        };
+        let args = args.split(", ")
+            .filter(|a| !a.is_empty())
+            .map(
+                |a| if a.starts_with("0x") {
+                    theme.paint(theme.immediate, a)
+                } else if a == "?" {
+                    a.to_string()
+                } else {
+                    theme.paint(theme.register, a)
+                }
+            )
+            .collect::<Vec<_>>();
+
+        if args.is_empty() { theme.paint(theme.mnemonic, op) } else { format!("{} {}", theme.paint(theme.mnemonic, op), args.join(", ")) }
+    }
+
+    /// Breaks `line` at spaces so no line is longer than `width` columns. Continuation lines are
+    /// indented by `indent` spaces.
+    pub fn wrap(&self, line: &str, indent: usize) -> String {
+        let width = match self.width {
+            Some(w) if w > indent => w,
+            _ => return line.to_string(),
+        };
+        let mut ret = String::with_capacity(line.len());
+        let mut column = 0;
+        // position in `ret` and column of the last space on the current line
+        let mut space = None;
+        let mut escape = false;
+
+        for c in line.chars() {
+            if escape {
+                escape = c != 'm';
+                ret.push(c);
+                continue;
+            } else if c == '\x1b' {
+                escape = true;
+                ret.push(c);
+                continue;
+            }
+
+            if column >= width {
+                if let Some((pos, col)) = space.take() {
+                    let rest = ret.split_off(pos + 1);
+
+                    ret.pop();
+                    ret.push('\n');
+                    ret.push_str(&" ".repeat(indent));
+                    ret.push_str(&rest);
+                    column = indent + column - col - 1;
+                }
+            }
+
+            if c == ' ' && column > indent {
+                space = Some((ret.len(), column));
+            }
+
+            ret.push(c);
+            column += 1;
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, Bound, ControlFlowGraph, ControlFlowTarget, Layer, Lvalue, Operation, Region, Rvalue, Xref, XrefKind};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use syntax::Att;
+    use uuid::Uuid;
+
+    /*
+     * 0x100: call 0x100; mov rax, 0x10
+     */
+    #[test]
+    fn highlighting() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x100, 0x10a), Layer::wrap(vec![0xe8, 0xfb, 0xff, 0xff, 0xff, 0xb8, 0x10, 0x00, 0x00, 0x00])));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        let rax = Rvalue::Variable { name: "RAX".into(), subscript: None, offset: 0, size: 64 };
+        let stmt = Statement { op: Operation::Move(Rvalue::new_u64(0x10)), assignee: Lvalue::Variable { name: "RAX".into(), subscript: None, size: 64 } };
+        let call = Mnemonic::new(0x100..0x105, "call".to_string(), "{c:RAM}".to_string(), vec![Rvalue::new_u64(0x100)].iter(), vec![].iter()).ok().unwrap();
+        let mov = Mnemonic::new(0x105..0x10a, "mov".to_string(), "{u}, {u}".to_string(), vec![rax, Rvalue::new_u64(0x10)].iter(), vec![stmt].iter())
+            .ok()
+            .unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![call, mov])));
+        let mut func = Function::undefined(0x100, None, proj.region(), Some("main".to_string()));
+        let mut prog = Program::new("prog");
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        for i in 0..4 {
+            proj.xrefs.insert(Xref { function: Uuid::new_v4(), address: 0x1000 + i * 0x100, statement: None, target: 0x100, kind: XrefKind::Call });
+        }
+        prog.insert(func);
+
+        let func = prog.functions().next().unwrap();
+        let mut printer = Printer::new(&Att);
+        let colored = printer.function(&proj, &prog, func);
+
+        assert!(colored.contains("\x1b[1;34mcall\x1b[0m \x1b[36m0x100\x1b[0m \x1b[32m<main>\x1b[0m"));
+        assert!(colored.contains("\x1b[1;34mmov\x1b[0m \x1b[36m$0x10\x1b[0m,\x1b[33m%rax\x1b[0m"));
+        assert_eq!(
+            printer.statement(&func.statements().next().unwrap()),
+            "\x1b[1;34mmov\x1b[0m \x1b[33mRAX:64\x1b[0m, \x1b[36m0x10:64\x1b[0m"
+        );
+
+        printer.theme = Theme::plain();
+        printer.width = Some(60);
+        assert_eq!(
+            printer.function(&proj, &prog, func),
+            "0000000000000100 <main>:  ; xrefs: 0x1000 (call), 0x1100\n(call), 0x1200 (call), 0x1300 (call)\n     \
+             100: e8 fb ff ff ff        call 0x100 <main>\n     105: b8 10 00 00 00        mov $0x10,%rax\n"
+        );
+        assert_eq!(printer.statements(func), "     100: ; call\n     105: ; mov\n          mov RAX:64, 0x10:64\n");
+        printer.width = Some(33);
+        assert_eq!(printer.wrap("     100: aa bb  add r0, r1, r2, r3, r4", 16), "     100: aa bb  add r0, r1, r2,\n                r3, r4");
+    }
+}
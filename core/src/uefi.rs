@@ -0,0 +1,352 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Parser for UEFI firmware volumes.
+//!
+//! A firmware volume is a flash file system (FFS). Its files are named by GUIDs and consist of
+//! sections, one of which holds the PE32 or TE image of a driver or application. Another one
+//! usually holds its human readable name. Sections can be nested inside compression, GUID defined
+//! and firmware volume image sections. Only uncompressed ones are searched, modules inside LZMA or
+//! Tiano compressed sections are skipped.
+//!
+//! [`modules`](fn.modules.html) searches a flash image for volumes and returns all modules found.
+//! [`protocol_references`](fn.protocol_references.html) finds well known protocol GUIDs in a
+//! module. Drivers pass these to the boot services to locate and install protocols, so the
+//! location of a GUID hints at what the code using it does.
+
+use byteorder::{ByteOrder, LittleEndian};
+use uuid::Uuid;
+
+/// Signature of a firmware volume header, at offset 40.
+pub const FV_SIGNATURE: &'static [u8] = b"_FVH";
+
+/// Signature of a TE image header.
+pub const TE_SIGNATURE: &'static [u8] = b"VZ";
+
+/// FFS file type of padding files.
+const FILE_PAD: u8 = 0xf0;
+/// FFS file type of files without sections.
+const FILE_RAW: u8 = 0x01;
+/// FFS file attribute of files with a 64 bit size.
+const FFS_ATTRIB_LARGE_FILE: u8 = 0x01;
+
+// Section types
+const SECTION_COMPRESSION: u8 = 0x01;
+const SECTION_GUID_DEFINED: u8 = 0x02;
+const SECTION_PE32: u8 = 0x10;
+const SECTION_TE: u8 = 0x12;
+const SECTION_USER_INTERFACE: u8 = 0x15;
+const SECTION_FIRMWARE_VOLUME_IMAGE: u8 = 0x17;
+
+/// GUID defined section attribute of sections that need decoding, e.g. decompression.
+const GUIDED_PROCESSING_REQUIRED: u16 = 0x01;
+
+/// Well known protocol, PPI and variable GUIDs.
+const PROTOCOLS: &'static [(&'static str, &'static str)] = &[
+    ("5b1b31a1-9562-11d2-8e3f-00a0c969723b", "gEfiLoadedImageProtocolGuid"),
+    ("387477c1-69c7-11d2-8e39-00a0c969723b", "gEfiSimpleTextInProtocolGuid"),
+    ("387477c2-69c7-11d2-8e39-00a0c969723b", "gEfiSimpleTextOutProtocolGuid"),
+    ("09576e91-6d3f-11d2-8e39-00a0c969723b", "gEfiDevicePathProtocolGuid"),
+    ("964e5b21-6459-11d2-8e39-00a0c969723b", "gEfiBlockIoProtocolGuid"),
+    ("ce345171-ba0b-11d2-8e4f-00a0c969723b", "gEfiDiskIoProtocolGuid"),
+    ("964e5b22-6459-11d2-8e39-00a0c969723b", "gEfiSimpleFileSystemProtocolGuid"),
+    ("9042a9de-23dc-4a38-96fb-7aded080516a", "gEfiGraphicsOutputProtocolGuid"),
+    ("4cf5b200-68b8-4ca5-9eec-b23e3f50029a", "gEfiPciIoProtocolGuid"),
+    ("2b2f68d6-0cd2-44cf-8e8b-bba20b1b5b75", "gEfiUsbIoProtocolGuid"),
+    ("18a031ab-b443-4d1a-a5c0-0c09261e9f71", "gEfiDriverBindingProtocolGuid"),
+    ("6a7a5cff-e8d9-4f70-bada-75ab3025ce14", "gEfiComponentName2ProtocolGuid"),
+    ("220e73b6-6bdb-4413-8405-b974b108619a", "gEfiFirmwareVolume2ProtocolGuid"),
+    ("1e5668e2-8481-11d4-bcf1-0080c73c8881", "gEfiVariableArchProtocolGuid"),
+    ("8be4df61-93ca-11d2-aa0d-00e098032b8c", "gEfiGlobalVariableGuid"),
+    ("f4ccbfb7-f6e0-47fd-9dd4-10a8f150c191", "gEfiSmmBase2ProtocolGuid"),
+    ("18a3c6dc-5eea-48c8-a1c1-b53389f98999", "gEfiSmmSwDispatch2ProtocolGuid"),
+    ("2ab86ef5-ecb5-4134-b556-3854ca1fe1b4", "gEfiPeiReadOnlyVariable2PpiGuid"),
+];
+
+/// Module found in a firmware volume.
+#[derive(Clone,Debug)]
+pub struct Module<'a> {
+    /// Name of the FFS file holding the module
+    pub guid: Uuid,
+    /// FFS file type, e.g. 7 for DXE drivers
+    pub kind: u8,
+    /// Name from the user interface section
+    pub name: Option<String>,
+    /// Offset of the image in the firmware file
+    pub offset: usize,
+    /// PE32 or TE image
+    pub image: &'a [u8],
+}
+
+/// Reads a GUID stored in the mixed endian EFI format.
+pub fn guid(bytes: &[u8]) -> Option<Uuid> {
+    let bytes = bytes.get(0..16)?;
+    let (d1, d2, d3) = (LittleEndian::read_u32(&bytes[0..4]), LittleEndian::read_u16(&bytes[4..6]), LittleEndian::read_u16(&bytes[6..8]));
+
+    Uuid::from_fields(d1, d2, d3, &bytes[8..16]).ok()
+}
+
+/// Name of the well known protocol `guid`.
+pub fn protocol_name(guid: &Uuid) -> Option<&'static str> {
+    let guid = guid.hyphenated().to_string();
+
+    PROTOCOLS.iter().find(|&&(g, _)| g == guid).map(|&(_, name)| name)
+}
+
+/// Offsets and names of all well known protocol GUIDs in `bytes`. GUIDs are expected to be 4 byte
+/// aligned.
+pub fn protocol_references(bytes: &[u8]) -> Vec<(usize, &'static str)> {
+    (0..bytes.len().saturating_sub(15) / 4)
+        .map(|i| i * 4)
+        .filter_map(|off| protocol_name(&guid(&bytes[off..])?).map(|name| (off, name)))
+        .collect()
+}
+
+/// Returns all modules in the firmware volumes in `bytes`. Volumes are searched at 16 byte
+/// aligned offsets, so `bytes` can be a whole flash image.
+pub fn modules<'a>(bytes: &'a [u8]) -> Vec<Module<'a>> {
+    let mut ret = vec![];
+    let mut off = 0;
+
+    while off + 56 <= bytes.len() {
+        match volume(bytes, off, bytes.len(), &mut ret) {
+            Some(end) => off = (end + 15) & !15,
+            None => off += 16,
+        }
+    }
+
+    ret
+}
+
+/// Parses the volume at `start`, which must end before `end`, and returns its end.
+fn volume<'a>(bytes: &'a [u8], start: usize, end: usize, modules: &mut Vec<Module<'a>>) -> Option<usize> {
+    if bytes.get(start + 40..start + 44) != Some(FV_SIGNATURE) {
+        return None;
+    }
+
+    let length = LittleEndian::read_u64(bytes.get(start + 32..start + 40)?) as usize;
+    let header = LittleEndian::read_u16(bytes.get(start + 48..start + 50)?) as usize;
+    let ext = LittleEndian::read_u16(bytes.get(start + 52..start + 54)?) as usize;
+    let vol_end = start.checked_add(length)?;
+
+    if vol_end > end || header < 56 || header > length {
+        debug!("invalid firmware volume at {:#x}", start);
+        return None;
+    }
+
+    // The extended header holds the volume's name and follows the header
+    let mut off = if ext != 0 {
+        let size = LittleEndian::read_u32(bytes.get(start + ext + 16..start + ext + 20)?) as usize;
+        (start + ext + size + 7) & !7
+    } else {
+        start + header
+    };
+
+    debug!("firmware volume at {:#x}..{:#x}", start, vol_end);
+
+    while off + 24 <= vol_end {
+        let hdr = &bytes[off..off + 24];
+
+        // Erased flash
+        if hdr.iter().all(|&b| b == 0xff) {
+            break;
+        }
+
+        let (size, hdr_size) = if hdr[19] & FFS_ATTRIB_LARGE_FILE != 0 {
+            (LittleEndian::read_u64(bytes.get(off + 24..off + 32)?) as usize, 32)
+        } else {
+            (LittleEndian::read_u32(&[hdr[20], hdr[21], hdr[22], 0]) as usize, 24)
+        };
+
+        if size < hdr_size || off + size > vol_end {
+            debug!("invalid FFS file at {:#x}", off);
+            break;
+        }
+
+        let kind = hdr[18];
+
+        if kind != FILE_PAD && kind != FILE_RAW {
+            let mut file = Module { guid: guid(hdr)?, kind: kind, name: None, offset: 0, image: &[] };
+            let mut images = vec![];
+
+            sections(bytes, off + hdr_size, off + size, &mut file.name, &mut images, modules);
+            for (offset, image) in images {
+                modules.push(Module { offset: offset, image: image, ..file.clone() });
+            }
+        }
+
+        off = (off + size + 7) & !7;
+    }
+
+    Some(vol_end)
+}
+
+/// Parses the sections between `start` and `end`. Images are appended to `images`, modules in
+/// nested volumes to `modules`.
+fn sections<'a>(
+    bytes: &'a [u8],
+    start: usize,
+    end: usize,
+    name: &mut Option<String>,
+    images: &mut Vec<(usize, &'a [u8])>,
+    modules: &mut Vec<Module<'a>>,
+) {
+    let mut off = start;
+
+    while off + 4 <= end {
+        let (size, hdr_size) = match LittleEndian::read_u32(&[bytes[off], bytes[off + 1], bytes[off + 2], 0]) {
+            0xff_ffff => (bytes.get(off + 4..off + 8).map(|b| LittleEndian::read_u32(b) as usize).unwrap_or(0), 8),
+            size => (size as usize, 4),
+        };
+
+        if size < hdr_size || off + size > end {
+            debug!("invalid section at {:#x}", off);
+            return;
+        }
+
+        let data = off + hdr_size;
+
+        match bytes[off + 3] {
+            SECTION_PE32 | SECTION_TE => images.push((data, &bytes[data..off + size])),
+            SECTION_USER_INTERFACE => {
+                let chars = bytes[data..off + size]
+                    .chunks(2)
+                    .map(|c| c[0] as u16 | (*c.get(1).unwrap_or(&0) as u16) << 8)
+                    .take_while(|&c| c != 0)
+                    .collect::<Vec<_>>();
+
+                *name = Some(String::from_utf16_lossy(&chars));
+            }
+            // Compression type 0 means not compressed
+            SECTION_COMPRESSION if bytes.get(data + 4) == Some(&0) => sections(bytes, data + 5, off + size, name, images, modules),
+            SECTION_COMPRESSION => debug!("skipping compressed section at {:#x}", off),
+            SECTION_GUID_DEFINED if data + 20 <= off + size => {
+                let data_offset = LittleEndian::read_u16(&bytes[data + 16..data + 18]) as usize;
+                let attributes = LittleEndian::read_u16(&bytes[data + 18..data + 20]);
+
+                if attributes & GUIDED_PROCESSING_REQUIRED == 0 && data_offset <= size {
+                    sections(bytes, off + data_offset, off + size, name, images, modules);
+                } else {
+                    debug!("skipping encoded section {:?} at {:#x}", guid(&bytes[data..]), off);
+                }
+            }
+            SECTION_FIRMWARE_VOLUME_IMAGE => {
+                volume(bytes, data, off + size, modules);
+            }
+            _ => {}
+        }
+
+        off = (off + size + 3) & !3;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loaded image protocol GUID in EFI byte order
+    const LOADED_IMAGE: [u8; 16] = [0xa1, 0x31, 0x1b, 0x5b, 0x62, 0x95, 0xd2, 0x11, 0x8e, 0x3f, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b];
+
+    fn section(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut ret = vec![0u8; 4];
+
+        LittleEndian::write_u32(&mut ret, data.len() as u32 + 4);
+        ret[3] = kind;
+        ret.extend_from_slice(data);
+        while ret.len() % 4 != 0 {
+            ret.push(0);
+        }
+        ret
+    }
+
+    fn file(kind: u8, name: u8, content: &[u8]) -> Vec<u8> {
+        let mut ret = vec![name; 16];
+
+        ret.extend_from_slice(&[0, 0, kind, 0, 0, 0, 0, 0xf8]);
+        LittleEndian::write_u16(&mut ret[20..], content.len() as u16 + 24);
+        ret.extend_from_slice(content);
+        while ret.len() % 8 != 0 {
+            ret.push(0xff);
+        }
+        ret
+    }
+
+    fn volume(files: &[Vec<u8>], length: usize) -> Vec<u8> {
+        let mut ret = vec![0u8; 0x48];
+
+        LittleEndian::write_u64(&mut ret[32..], length as u64);
+        ret[40..44].copy_from_slice(FV_SIGNATURE);
+        LittleEndian::write_u16(&mut ret[48..], 0x48);
+        for f in files.iter() {
+            ret.extend_from_slice(f);
+        }
+        ret.resize(length, 0xff);
+        ret
+    }
+
+    #[test]
+    fn parse_guid() {
+        let g = guid(&LOADED_IMAGE).unwrap();
+
+        assert_eq!(g.hyphenated().to_string(), "5b1b31a1-9562-11d2-8e3f-00a0c969723b");
+        assert_eq!(protocol_name(&g), Some("gEfiLoadedImageProtocolGuid"));
+
+        let mut data = vec![0u8; 0x20];
+        data[8..24].copy_from_slice(&LOADED_IMAGE);
+        assert_eq!(protocol_references(&data), vec![(8, "gEfiLoadedImageProtocolGuid")]);
+    }
+
+    #[test]
+    fn firmware_volume() {
+        let ui = section(SECTION_USER_INTERFACE, &[b'F', 0, b'o', 0, b'o', 0, 0, 0]);
+        let te = section(SECTION_TE, b"VZ\x64\x86");
+        let mut encap = vec![0, 0, 0, 0, 0];
+        encap.extend(section(SECTION_PE32, b"MZ"));
+        let compressed = section(SECTION_COMPRESSION, &encap);
+        let mut lzma = vec![0u8; 20];
+        lzma[16] = 24;
+        lzma[18] = 1;
+        lzma.extend(section(SECTION_PE32, b"MZ"));
+        let lzma = section(SECTION_GUID_DEFINED, &lzma);
+        let inner = volume(&[file(7, 3, &section(SECTION_TE, b"VZ"))], 0x80);
+        let nested = section(SECTION_FIRMWARE_VOLUME_IMAGE, &inner);
+        let files = [
+            file(7, 1, &[ui, te].concat()),
+            file(FILE_PAD, 0xff, &[0; 8]),
+            file(6, 2, &[compressed, lzma].concat()),
+            file(0x0b, 4, &nested),
+        ];
+        let mut flash = vec![0u8; 0x100];
+
+        flash.extend(volume(&files, 0x300));
+
+        let mods = modules(&flash);
+
+        assert_eq!(mods.len(), 3);
+        assert_eq!(mods[0].name, Some("Foo".to_string()));
+        assert_eq!(mods[0].kind, 7);
+        assert_eq!(mods[0].image, b"VZ\x64\x86");
+        assert_eq!(&flash[mods[0].offset..mods[0].offset + 4], b"VZ\x64\x86");
+        assert_eq!(mods[0].guid, guid(&[1; 16]).unwrap());
+        assert_eq!(mods[1].name, None);
+        assert_eq!(mods[1].image, b"MZ");
+        assert_eq!(mods[1].kind, 6);
+        assert_eq!(mods[2].image, b"VZ");
+        assert_eq!(mods[2].guid, guid(&[3; 16]).unwrap());
+    }
+}
@@ -0,0 +1,561 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Parser for the WebAssembly binary module format.
+//!
+//! Only the parts of a module needed for disassembly are parsed: function signatures, imports,
+//! exports, globals and the location of function bodies. Addresses are offsets into the module
+//! file. Element and data segments as well as all custom sections except `name` are skipped.
+
+use Result;
+
+/// Magic number at the start of every module.
+pub const MAGIC: &'static [u8] = b"\0asm";
+
+/// Value types
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum ValueType {
+    /// 32-bit integer
+    I32,
+    /// 64-bit integer
+    I64,
+    /// Single precision float
+    F32,
+    /// Double precision float
+    F64,
+    /// 128-bit SIMD vector
+    V128,
+    /// Function reference
+    FuncRef,
+    /// Host reference
+    ExternRef,
+}
+
+impl ValueType {
+    /// Decodes a value type byte.
+    pub fn from_byte(b: u8) -> Result<ValueType> {
+        match b {
+            0x7f => Ok(ValueType::I32),
+            0x7e => Ok(ValueType::I64),
+            0x7d => Ok(ValueType::F32),
+            0x7c => Ok(ValueType::F64),
+            0x7b => Ok(ValueType::V128),
+            0x70 => Ok(ValueType::FuncRef),
+            0x6f => Ok(ValueType::ExternRef),
+            _ => Err(format!("unknown WebAssembly value type {:#x}", b).into()),
+        }
+    }
+
+    /// Size in bits.
+    pub fn size(&self) -> usize {
+        match self {
+            &ValueType::I32 | &ValueType::F32 => 32,
+            &ValueType::V128 => 128,
+            _ => 64,
+        }
+    }
+}
+
+/// Function signature
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct FuncType {
+    /// Parameter types
+    pub params: Vec<ValueType>,
+    /// Result types
+    pub results: Vec<ValueType>,
+}
+
+/// Kind of an imported or exported definition
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum ExternalKind {
+    /// Function. Imports carry the index of its signature, exports the function index
+    Function(u32),
+    /// Table
+    Table(u32),
+    /// Linear memory
+    Memory(u32),
+    /// Global variable
+    Global(u32),
+}
+
+/// Imported definition
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct Import {
+    /// Module name
+    pub module: String,
+    /// Field name
+    pub name: String,
+    /// What is imported
+    pub kind: ExternalKind,
+    /// Offset of the import entry
+    pub offset: u64,
+}
+
+/// Exported definition
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct Export {
+    /// Export name
+    pub name: String,
+    /// What is exported
+    pub kind: ExternalKind,
+}
+
+/// Body of a function defined inside the module.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct Body {
+    /// Index of the function signature
+    pub type_index: u32,
+    /// Types of the locals declared in the body. Does not include the parameters
+    pub locals: Vec<ValueType>,
+    /// Offset of the first instruction
+    pub start: u64,
+    /// Offset right behind the final `end` instruction
+    pub end: u64,
+}
+
+/// Parsed module.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct Module {
+    /// Type section
+    pub types: Vec<FuncType>,
+    /// Import section
+    pub imports: Vec<Import>,
+    /// Export section
+    pub exports: Vec<Export>,
+    /// Types of the globals defined in the module
+    pub globals: Vec<ValueType>,
+    /// Bodies of all functions defined in the module, in function index order
+    pub bodies: Vec<Body>,
+    /// Start function
+    pub start: Option<u32>,
+    /// Function names from the `name` custom section
+    pub names: Vec<(u32, String)>,
+}
+
+/// Cursor over a byte slice that decodes the primitive encodings of the binary format.
+#[derive(Clone,Debug)]
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    /// Current position
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Starts reading at `pos`.
+    pub fn new(bytes: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { bytes: bytes, pos: pos }
+    }
+
+    /// True if all bytes have been read.
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    /// Reads a single byte.
+    pub fn byte(&mut self) -> Result<u8> {
+        match self.bytes.get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                Ok(b)
+            }
+            None => Err(format!("WebAssembly module truncated at {:#x}", self.pos).into()),
+        }
+    }
+
+    /// Reads `len` bytes.
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err(format!("WebAssembly module truncated at {:#x}", self.bytes.len()).into());
+        }
+
+        let ret = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(ret)
+    }
+
+    /// Reads an unsigned LEB128 number of at most `bits` bits.
+    pub fn unsigned(&mut self, bits: usize) -> Result<u64> {
+        let mut ret = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.byte()?;
+
+            if shift >= bits {
+                return Err(format!("LEB128 number too long at {:#x}", self.pos - 1).into());
+            }
+
+            ret |= ((b & 0x7f) as u64) << shift;
+            shift += 7;
+
+            if b & 0x80 == 0 {
+                return Ok(ret);
+            }
+        }
+    }
+
+    /// Reads a signed LEB128 number of at most `bits` bits and sign extends it to 64 bits.
+    pub fn signed(&mut self, bits: usize) -> Result<u64> {
+        let mut ret = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let b = self.byte()?;
+
+            if shift >= bits {
+                return Err(format!("LEB128 number too long at {:#x}", self.pos - 1).into());
+            }
+
+            ret |= ((b & 0x7f) as u64) << shift;
+            shift += 7;
+
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    ret |= !0 << shift;
+                }
+                return Ok(ret);
+            }
+        }
+    }
+
+    /// Reads a `u32` in unsigned LEB128 encoding.
+    pub fn u32(&mut self) -> Result<u32> {
+        self.unsigned(32).map(|x| x as u32)
+    }
+
+    /// Reads a length prefixed UTF-8 string.
+    pub fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+
+        Ok(String::from_utf8_lossy(bytes).to_string())
+    }
+
+    /// Reads a value type.
+    pub fn value_type(&mut self) -> Result<ValueType> {
+        ValueType::from_byte(self.byte()?)
+    }
+
+    /// Reads table or memory limits.
+    fn limits(&mut self) -> Result<()> {
+        match self.byte()? {
+            0 => {
+                self.u32()?;
+            }
+            1 => {
+                self.u32()?;
+                self.u32()?;
+            }
+            b => return Err(format!("invalid WebAssembly limits {:#x}", b).into()),
+        }
+        Ok(())
+    }
+
+    /// Skips a constant expression.
+    fn init_expr(&mut self) -> Result<()> {
+        loop {
+            match self.byte()? {
+                0x0b => return Ok(()),
+                0x41 | 0x23 | 0xd2 => {
+                    self.signed(32)?;
+                }
+                0x42 => {
+                    self.signed(64)?;
+                }
+                0x43 => {
+                    self.bytes(4)?;
+                }
+                0x44 => {
+                    self.bytes(8)?;
+                }
+                0xd0 => {
+                    self.byte()?;
+                }
+                b => return Err(format!("unsupported opcode {:#x} in WebAssembly constant expression", b).into()),
+            }
+        }
+    }
+}
+
+impl Module {
+    /// Parses the module in `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Module> {
+        let mut module = Module {
+            types: vec![],
+            imports: vec![],
+            exports: vec![],
+            globals: vec![],
+            bodies: vec![],
+            start: None,
+            names: vec![],
+        };
+        let mut functions = vec![];
+        let mut rd = Reader::new(bytes, 0);
+
+        if rd.bytes(4)? != MAGIC {
+            return Err("not a WebAssembly module".into());
+        }
+        if rd.bytes(4)? != &[1, 0, 0, 0] {
+            return Err("unsupported WebAssembly version".into());
+        }
+
+        while !rd.is_empty() {
+            let id = rd.byte()?;
+            let len = rd.u32()? as usize;
+            let end = rd.pos + len;
+
+            if end > bytes.len() {
+                return Err(format!("WebAssembly section {} truncated", id).into());
+            }
+
+            let mut sec = Reader::new(&bytes[..end], rd.pos);
+
+            debug!("section {} @ {:#x}: {} bytes", id, rd.pos, len);
+
+            match id {
+                0 => {
+                    if sec.name()? == "name" {
+                        module.names = Self::parse_names(&mut sec)?;
+                    }
+                }
+                1 => {
+                    for _ in 0..sec.u32()? {
+                        if sec.byte()? != 0x60 {
+                            return Err("invalid WebAssembly function type".into());
+                        }
+
+                        let params = (0..sec.u32()?).map(|_| sec.value_type()).collect::<Result<Vec<_>>>()?;
+                        let results = (0..sec.u32()?).map(|_| sec.value_type()).collect::<Result<Vec<_>>>()?;
+
+                        module.types.push(FuncType { params: params, results: results });
+                    }
+                }
+                2 => {
+                    for _ in 0..sec.u32()? {
+                        let offset = sec.pos as u64;
+                        let modname = sec.name()?;
+                        let name = sec.name()?;
+                        let kind = match sec.byte()? {
+                            0 => ExternalKind::Function(sec.u32()?),
+                            1 => {
+                                sec.byte()?;
+                                sec.limits()?;
+                                ExternalKind::Table(0)
+                            }
+                            2 => {
+                                sec.limits()?;
+                                ExternalKind::Memory(0)
+                            }
+                            3 => {
+                                sec.value_type()?;
+                                sec.byte()?;
+                                ExternalKind::Global(0)
+                            }
+                            b => return Err(format!("unknown WebAssembly import kind {:#x}", b).into()),
+                        };
+
+                        module.imports.push(Import { module: modname, name: name, kind: kind, offset: offset });
+                    }
+                }
+                3 => {
+                    for _ in 0..sec.u32()? {
+                        functions.push(sec.u32()?);
+                    }
+                }
+                6 => {
+                    for _ in 0..sec.u32()? {
+                        let ty = sec.value_type()?;
+
+                        sec.byte()?;
+                        sec.init_expr()?;
+                        module.globals.push(ty);
+                    }
+                }
+                7 => {
+                    for _ in 0..sec.u32()? {
+                        let name = sec.name()?;
+                        let kind = sec.byte()?;
+                        let index = sec.u32()?;
+                        let kind = match kind {
+                            0 => ExternalKind::Function(index),
+                            1 => ExternalKind::Table(index),
+                            2 => ExternalKind::Memory(index),
+                            3 => ExternalKind::Global(index),
+                            b => return Err(format!("unknown WebAssembly export kind {:#x}", b).into()),
+                        };
+
+                        module.exports.push(Export { name: name, kind: kind });
+                    }
+                }
+                8 => {
+                    module.start = Some(sec.u32()?);
+                }
+                10 => {
+                    let count = sec.u32()? as usize;
+
+                    if count != functions.len() {
+                        return Err("WebAssembly function and code section disagree".into());
+                    }
+
+                    for &type_index in functions.iter() {
+                        let size = sec.u32()? as usize;
+                        let end = sec.pos + size;
+                        let mut locals = vec![];
+
+                        for _ in 0..sec.u32()? {
+                            let n = sec.u32()?;
+                            let ty = sec.value_type()?;
+
+                            if locals.len() + n as usize > 50000 {
+                                return Err("too many locals in WebAssembly function".into());
+                            }
+                            locals.extend((0..n).map(|_| ty));
+                        }
+
+                        module.bodies.push(
+                            Body {
+                                type_index: type_index,
+                                locals: locals,
+                                start: sec.pos as u64,
+                                end: end as u64,
+                            }
+                        );
+                        sec.pos = end;
+                    }
+                }
+                _ => {}
+            }
+
+            rd.pos = end;
+        }
+
+        Ok(module)
+    }
+
+    /// Reads the function names subsection of a `name` section.
+    fn parse_names(sec: &mut Reader) -> Result<Vec<(u32, String)>> {
+        let mut ret = vec![];
+
+        while !sec.is_empty() {
+            let id = sec.byte()?;
+            let len = sec.u32()? as usize;
+            let end = sec.pos + len;
+
+            if id == 1 {
+                for _ in 0..sec.u32()? {
+                    let index = sec.u32()?;
+                    let name = sec.name()?;
+
+                    ret.push((index, name));
+                }
+            }
+            sec.pos = end;
+        }
+
+        Ok(ret)
+    }
+
+    /// Number of imported functions. Defined functions are numbered after them.
+    pub fn imported_functions(&self) -> u32 {
+        self.imports
+            .iter()
+            .filter(|i| if let ExternalKind::Function(_) = i.kind { true } else { false })
+            .count() as u32
+    }
+
+    /// Returns the import of function `index` if it's not defined in the module.
+    pub fn function_import(&self, index: u32) -> Option<&Import> {
+        self.imports.iter().filter(|i| if let ExternalKind::Function(_) = i.kind { true } else { false }).nth(index as usize)
+    }
+
+    /// Returns the body of function `index` if it's defined in the module.
+    pub fn body(&self, index: u32) -> Option<&Body> {
+        index.checked_sub(self.imported_functions()).and_then(|i| self.bodies.get(i as usize))
+    }
+
+    /// Signature of function `index`.
+    pub fn function_type(&self, index: u32) -> Option<&FuncType> {
+        let ty = match self.function_import(index) {
+            Some(&Import { kind: ExternalKind::Function(ty), .. }) => Some(ty),
+            _ => self.body(index).map(|b| b.type_index),
+        };
+
+        ty.and_then(|ty| self.types.get(ty as usize))
+    }
+
+    /// Name of function `index`. Exports take precedence over the `name` section, imports are
+    /// named `module.field`.
+    pub fn function_name(&self, index: u32) -> Option<String> {
+        if let Some(import) = self.function_import(index) {
+            return Some(format!("{}.{}", import.module, import.name));
+        }
+
+        for export in self.exports.iter() {
+            if export.kind == ExternalKind::Function(index) {
+                return Some(export.name.clone());
+            }
+        }
+
+        self.names.iter().find(|&&(i, _)| i == index).map(|&(_, ref n)| n.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128() {
+        let bytes = [0xe5, 0x8e, 0x26, 0x7f, 0x80, 0x7f, 0xff, 0xff, 0xff, 0xff, 0x1f];
+        let mut rd = Reader::new(&bytes, 0);
+
+        assert_eq!(rd.unsigned(32).unwrap(), 624485);
+        assert_eq!(rd.signed(32).unwrap(), (-1i64) as u64);
+        assert_eq!(rd.signed(32).unwrap(), (-128i64) as u64);
+        assert!(rd.unsigned(32).is_ok());
+        assert!(rd.is_empty());
+        assert!(Reader::new(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], 0).unsigned(32).is_err());
+    }
+
+    #[test]
+    fn module() {
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // type: (i32) -> i32, () -> ()
+            0x01, 0x09, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00,
+            // import: env.exit (type 1)
+            0x02, 0x0c, 0x01, 0x03, b'e', b'n', b'v', 0x04, b'e', b'x', b'i', b't', 0x00, 0x01,
+            // function: type 0
+            0x03, 0x02, 0x01, 0x00,
+            // export: "inc" -> function 1
+            0x07, 0x07, 0x01, 0x03, b'i', b'n', b'c', 0x00, 0x01,
+            // code: (local i64) local.get 0 i32.const 1 i32.add end
+            0x0a, 0x0b, 0x01, 0x09, 0x01, 0x01, 0x7e, 0x20, 0x00, 0x41, 0x01, 0x6a, 0x0b,
+        ];
+        let module = Module::parse(&bytes).unwrap();
+
+        assert_eq!(module.types.len(), 2);
+        assert_eq!(module.imported_functions(), 1);
+        assert_eq!(module.function_name(0), Some("env.exit".to_string()));
+        assert_eq!(module.function_name(1), Some("inc".to_string()));
+        assert_eq!(module.function_type(1).map(|t| t.params.clone()), Some(vec![ValueType::I32]));
+        assert_eq!(module.bodies, vec![Body { type_index: 0, locals: vec![ValueType::I64], start: 0x35, end: 0x3b }]);
+        assert!(module.body(0).is_none());
+        assert!(Module::parse(&bytes[0..20]).is_err());
+    }
+}
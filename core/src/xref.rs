@@ -0,0 +1,330 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Cross references.
+//!
+//! [`collect`] walks the IL of every function in a project and records each absolute address it
+//! references together with the statement doing so. Constant addresses of `Load`, `Store` and
+//! `Call` operations are reads, writes and calls. Jumps are taken from the control flow graph,
+//! falling through to the next basic block is not a jump. All other constants are recorded as
+//! taken addresses if they point into a section of the executable, or into the mapped part of
+//! the root region if the loader recorded no sections.
+//!
+//! The result is stored in `Project::xrefs` and queried with `Project::xrefs_to` and
+//! `Project::xrefs_from`.
+//!
+//! [`collect`]: fn.collect.html
+
+use {ControlFlowTarget, Function, Operation, Project, Rvalue};
+use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait};
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// How an address is referenced.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+pub enum XrefKind {
+    /// Memory read
+    Read,
+    /// Memory write
+    Write,
+    /// Function call
+    Call,
+    /// Jump or branch
+    Jump,
+    /// Address used as a value, e.g. a pointer passed as argument
+    Address,
+}
+
+/// Reference to an address from code.
+#[derive(Clone,Debug,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub struct Xref {
+    /// Function containing the reference
+    pub function: Uuid,
+    /// Address of the mnemonic referencing
+    pub address: u64,
+    /// Index of the statement inside the mnemonic. Jumps have none.
+    pub statement: Option<usize>,
+    /// Address referenced
+    pub target: u64,
+    /// Kind of reference
+    pub kind: XrefKind,
+}
+
+/// Cross references of a project, indexed by target and by function.
+#[derive(Clone,Debug,PartialEq,Eq,Default,Serialize,Deserialize)]
+pub struct XrefDatabase {
+    to: BTreeMap<u64, Vec<Xref>>,
+    from: HashMap<Uuid, Vec<Xref>>,
+}
+
+impl XrefDatabase {
+    /// Returns an empty database.
+    pub fn new() -> XrefDatabase {
+        XrefDatabase::default()
+    }
+
+    /// Adds `xref` to the database.
+    pub fn insert(&mut self, xref: Xref) {
+        self.to.entry(xref.target).or_insert_with(Vec::new).push(xref.clone());
+        self.from.entry(xref.function.clone()).or_insert_with(Vec::new).push(xref);
+    }
+
+    /// Removes all references from the function with UUID `function`.
+    pub fn remove_from(&mut self, function: &Uuid) {
+        for x in self.from.remove(function).unwrap_or_default() {
+            let empty = match self.to.get_mut(&x.target) {
+                Some(xrefs) => {
+                    xrefs.retain(|y| y.function != *function);
+                    xrefs.is_empty()
+                }
+                None => false,
+            };
+
+            if empty {
+                self.to.remove(&x.target);
+            }
+        }
+    }
+
+    /// All references to `address`.
+    pub fn to(&self, address: u64) -> &[Xref] {
+        self.to.get(&address).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// All references to addresses between `start` and `end`, excluding `end`, ordered by target.
+    pub fn range<'a>(&'a self, start: u64, end: u64) -> Box<Iterator<Item = &'a Xref> + 'a> {
+        Box::new(self.to.range(start..cmp::max(start, end)).flat_map(|(_, v)| v.iter()))
+    }
+
+    /// All references from the function with UUID `function`, in address order.
+    pub fn from(&self, function: &Uuid) -> &[Xref] {
+        self.from.get(function).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// All references, ordered by target.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = &'a Xref> + 'a> {
+        Box::new(self.to.values().flat_map(|v| v.iter()))
+    }
+
+    /// Number of references.
+    pub fn len(&self) -> usize {
+        self.to.values().map(|v| v.len()).sum()
+    }
+
+    /// Returns true if there are no references.
+    pub fn is_empty(&self) -> bool {
+        self.to.is_empty()
+    }
+}
+
+/// Collects the cross references of all functions in `proj` and stores them in `Project::xrefs`.
+pub fn collect(proj: &mut Project) {
+    let mut db = XrefDatabase::new();
+
+    for prog in proj.code.iter() {
+        for func in prog.functions() {
+            for x in references(proj, func) {
+                db.insert(x);
+            }
+        }
+    }
+
+    proj.xrefs = db;
+}
+
+/// Replaces the cross references of the functions with UUIDs in `functions` with ones collected
+/// from their current code. References of other functions are kept.
+pub fn update(proj: &mut Project, functions: &[Uuid]) {
+    let mut xrefs = vec![];
+
+    for prog in proj.code.iter() {
+        for func in prog.functions().filter(|f| functions.contains(f.uuid())) {
+            xrefs.extend(references(proj, func));
+        }
+    }
+
+    for uuid in functions {
+        proj.xrefs.remove_from(uuid);
+    }
+    for x in xrefs {
+        proj.xrefs.insert(x);
+    }
+}
+
+/// Sorted references from `func`, a function of `proj`.
+fn references(proj: &Project, func: &Function) -> Vec<Xref> {
+    let region = proj.region();
+    let mapped = |a: u64| if proj.sections.is_empty() {
+        a < region.size() && region.iter().seek(a).next().map(|c| c.is_some()).unwrap_or(false)
+    } else {
+        proj.sections.iter().any(|s| s.area.start <= a && a < s.area.end)
+    };
+    let mut xrefs = function(func, &mapped);
+
+    xrefs.sort_by_key(|x| (x.address, x.statement, x.target));
+    xrefs.dedup();
+    xrefs
+}
+
+/// References from `func`. Plain constants are only recorded if `mapped` returns true for them.
+fn function(func: &Function, mapped: &Fn(u64) -> bool) -> Vec<Xref> {
+    let mut ret = vec![];
+    let uuid = func.uuid();
+
+    for bb in func.basic_blocks() {
+        for (mne, stmts) in func.mnemonics(bb) {
+            for (idx, stmt) in stmts.iter().enumerate() {
+                let mut xref = |target: u64, kind: XrefKind| {
+                    ret.push(Xref { function: uuid.clone(), address: mne.area.start, statement: Some(idx), target: target, kind: kind });
+                };
+                let access = match stmt.op {
+                    Operation::Load(_, _, _, Rvalue::Constant { value, .. }) => Some((value, XrefKind::Read)),
+                    Operation::Store(_, _, _, Rvalue::Constant { value, .. }, _) => Some((value, XrefKind::Write)),
+                    Operation::Call(Rvalue::Constant { value, .. }) => Some((value, XrefKind::Call)),
+                    _ => None,
+                };
+
+                if let Some((value, kind)) = access {
+                    xref(value, kind);
+                }
+
+                for (pos, rv) in stmt.op.operands().into_iter().enumerate() {
+                    let is_access = match (&stmt.op, pos) {
+                        (&Operation::Load(..), 0) | (&Operation::Store(..), 0) | (&Operation::Call(_), 0) => true,
+                        _ => false,
+                    };
+
+                    if let &Rvalue::Constant { value, .. } = rv {
+                        if !is_access && mapped(value) {
+                            xref(value, XrefKind::Address);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let cfg = func.cfg();
+
+    for e in cfg.edges() {
+        let (bb, to) = match (cfg.vertex_label(cfg.source(e)), cfg.vertex_label(cfg.target(e))) {
+            (Some(&ControlFlowTarget::Resolved(ref bb)), Some(to)) => (bb, to),
+            _ => continue,
+        };
+        let target = match to {
+            &ControlFlowTarget::Resolved(ref to) => to.area.start,
+            &ControlFlowTarget::Unresolved(Rvalue::Constant { value, .. }) => value,
+            _ => continue,
+        };
+
+        if target != bb.area.end {
+            if let Some(last) = bb.mnemonics.last() {
+                ret.push(Xref { function: uuid.clone(), address: last.area.start, statement: None, target: target, kind: XrefKind::Jump });
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {BasicBlock, Bound, ControlFlowGraph, Endianess, Guard, Layer, Lvalue, Mnemonic, Program, Region, Section, Statement};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn mnemonic(start: u64, stmts: Vec<Statement>) -> Mnemonic {
+        Mnemonic::new(start..start + 4, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap()
+    }
+
+    /*
+     * 0x100: mov [0x800], 0x810
+     * 0x104: call 0x200
+     * 0x108: jmp 0x110
+     * 0x110: mov rax, [0x808]
+     */
+    #[test]
+    fn collect_xrefs() {
+        let mut reg = Region::undefined("RAM".to_string(), 0x1000);
+        assert!(reg.cover(Bound::new(0x800, 0x820), Layer::wrap(vec![0; 0x20])));
+
+        let mut proj = Project::new("test".to_string(), reg);
+        proj.sections = vec![
+            Section { name: ".text".to_string(), area: Bound::new(0x100, 0x300), file_size: 0x200, file_offset: 0, read: true, write: false, execute: true },
+            Section { name: ".data".to_string(), area: Bound::new(0x800, 0x820), file_size: 0x20, file_offset: 0, read: true, write: true, execute: false },
+        ];
+
+        let rax = Lvalue::Variable { name: Cow::Borrowed("RAX"), size: 64, subscript: None };
+        let ram = Cow::Borrowed("RAM");
+        let b0 = BasicBlock::from_vec(
+            vec![
+                mnemonic(
+                    0x100,
+                    vec![
+                        Statement {
+                            op: Operation::Store(ram.clone(), Endianess::Little, 64, Rvalue::new_u64(0x800), Rvalue::new_u64(0x810)),
+                            assignee: Lvalue::Undefined,
+                        },
+                    ]
+                ),
+                mnemonic(0x104, vec![Statement { op: Operation::Call(Rvalue::new_u64(0x200)), assignee: Lvalue::Undefined }]),
+                mnemonic(0x108, vec![Statement { op: Operation::Move(Rvalue::new_u64(42)), assignee: rax.clone() }]),
+            ]
+        );
+        let b1 = BasicBlock::from_vec(
+            vec![mnemonic(0x110, vec![Statement { op: Operation::Load(ram.clone(), Endianess::Little, 64, Rvalue::new_u64(0x808)), assignee: rax.clone() }])]
+        );
+        let mut cfg = ControlFlowGraph::new();
+        let v0 = cfg.add_vertex(ControlFlowTarget::Resolved(b0));
+        let v1 = cfg.add_vertex(ControlFlowTarget::Resolved(b1));
+        cfg.add_edge(Guard::always(), v0, v1);
+
+        let mut func = Function::undefined(0x100, None, proj.region(), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(v0);
+
+        let uuid = func.uuid().clone();
+        let xref = |address: u64, statement: Option<usize>, target: u64, kind: XrefKind| {
+            Xref { function: uuid.clone(), address: address, statement: statement, target: target, kind: kind }
+        };
+        let mut prog = Program::new("prog");
+        prog.insert(func);
+        proj.code.push(prog);
+
+        collect(&mut proj);
+
+        assert_eq!(proj.xrefs_to(0x800), &[xref(0x100, Some(0), 0x800, XrefKind::Write)]);
+        assert_eq!(proj.xrefs_to(0x810), &[xref(0x100, Some(0), 0x810, XrefKind::Address)]);
+        assert_eq!(proj.xrefs_to(0x200), &[xref(0x104, Some(0), 0x200, XrefKind::Call)]);
+        assert_eq!(proj.xrefs_to(0x110), &[xref(0x108, None, 0x110, XrefKind::Jump)]);
+        assert_eq!(proj.xrefs_to(0x808), &[xref(0x110, Some(0), 0x808, XrefKind::Read)]);
+        assert!(proj.xrefs_to(42).is_empty());
+        assert_eq!(proj.xrefs_from(&uuid).len(), 5);
+        assert_eq!(proj.xrefs.len(), 5);
+
+        proj.xrefs.remove_from(&uuid);
+        assert!(proj.xrefs.is_empty());
+        assert!(proj.xrefs_to(0x800).is_empty());
+
+        update(&mut proj, &[uuid.clone()]);
+        assert_eq!(proj.xrefs.len(), 5);
+        assert_eq!(proj.xrefs_to(0x808), &[xref(0x110, Some(0), 0x808, XrefKind::Read)]);
+    }
+}
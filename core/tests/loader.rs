@@ -17,6 +17,7 @@
  */
 
 extern crate panopticon_core;
+extern crate panopticon_graph_algos;
 
 use panopticon_core::loader;
 use std::path::Path;
@@ -51,6 +52,38 @@ fn elf_load_dynamic() {
     }
 }
 
+#[test]
+fn ar_load_static_library() {
+    match loader::load(Path::new("../test-data/libbar.a")) {
+        Ok((proj, _)) => {
+            println!("{:?}", &proj);
+            assert_eq!(proj.name, "libbar.a");
+            assert_eq!(proj.code.len(), 2);
+            assert_eq!(proj.code[1].name, "baz.o");
+        }
+        Err(error) => {
+            println!("{:?}", error);
+            assert!(false);
+        }
+    }
+}
+
+#[test]
+fn elf_load_object() {
+    match loader::load(Path::new("../test-data/baz.o")) {
+        Ok((proj, _)) => {
+            println!("{:?}", &proj);
+            assert_eq!(proj.imports.len(), 2);
+            assert_eq!(proj.relocations.len(), 3);
+            assert_eq!(proj.code[0].relocations.len(), 3);
+        }
+        Err(error) => {
+            println!("{:?}", error);
+            assert!(false);
+        }
+    }
+}
+
 #[test]
 fn mach_load_lib() {
     match loader::load(Path::new("../test-data/libbeef.dylib")) {
@@ -101,15 +134,14 @@ fn mach_load_bytes() {
     }
 }
 
-// TODO: add imports into the PE loader
-
 #[test]
 fn load_pe32() {
     let project = loader::load(Path::new("../test-data/test.exe"));
     match project {
         Ok((proj, _)) => {
             println!("{:?}", proj);
-            assert_eq!(proj.imports.len(), 0);
+            assert_eq!(proj.imports.get(&0x42_0050).map(String::as_str), Some("EncodePointer"));
+            assert_eq!(proj.code[0].thunks.get(&0x41_8986), Some(&0x42_0050));
         }
         Err(error) => {
             println!("{:?}", error);
@@ -124,7 +156,8 @@ fn load_pe32_dll() {
     match project {
         Ok((proj, _)) => {
             println!("{:?}", proj);
-            assert_eq!(proj.imports.len(), 0);
+            assert_eq!(proj.imports.len(), 12);
+            assert_eq!(proj.imports.get(&0x61a4_8090).map(String::as_str), Some("malloc"));
         }
         Err(error) => {
             println!("{:?}", error);
@@ -132,3 +165,24 @@ fn load_pe32_dll() {
         }
     }
 }
+
+#[test]
+fn name_sources() {
+    use panopticon_core::{CallTarget, NameSource, Program};
+    use panopticon_graph_algos::VertexListGraphTrait;
+
+    fn source(prog: &Program, name: &str) -> Option<NameSource> {
+        prog.call_graph.vertex_labels().filter_map(
+            |ct| match ct {
+                &CallTarget::Todo(_, Some(ref n), ref uuid) if n == name => Some(prog.name_source(uuid)),
+                _ => None,
+            }
+        ).next()
+    }
+
+    let (proj, _) = loader::load(Path::new("../test-data/libfoo.so")).unwrap();
+    assert_eq!(source(&proj.code[0], "foo"), Some(NameSource::Symbol));
+
+    let (proj, _) = loader::load(Path::new("../test-data/libbeef.dll")).unwrap();
+    assert_eq!(source(&proj.code[0], "beef_maximum"), Some(NameSource::Export));
+}
@@ -0,0 +1,839 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Pseudo-C output.
+//!
+//! [`decompile`] prints a function as C-like source code. The result is meant to be read, not
+//! compiled.
+//!
+//! Inside each basic block the statements computing a value used exactly once are folded into
+//! the expression using it, as long as none of the variables they read is changed in between
+//! and no store or call separates a load from its use. Assignments to variables that are dead
+//! afterwards are removed, which takes care of most flag computations. Conditions of branches
+//! are built from the comparison setting the flag and negated comparisons are flipped.
+//!
+//! Arguments are the variables live at the entry point. If the calling convention is known only
+//! its argument registers are considered and the first return register is returned if the
+//! function writes it. All other variables written become locals. Types come from
+//! [`infer_types`].
+//!
+//! Control flow is recovered by [`structure`], jumps it can't express become `goto`s.
+//!
+//! [`decompile`]: fn.decompile.html
+//! [`infer_types`]: fn.infer_types.html
+//! [`structure`]: fn.structure.html
+
+use liveness::{liveness, liveness_sets};
+use panopticon_core::{ControlFlowRef, ControlFlowTarget, Function, Guard, Lvalue, Operation, Program, Rvalue, Statement};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use structure::{Condition, Node, goto_targets, structure};
+use types::{Type, Types, infer_types};
+
+type Name = (Cow<'static, str>, Option<usize>);
+
+/// Pseudo-C expression.
+#[derive(Clone,Debug,PartialEq,Eq)]
+enum Expression {
+    Variable(String),
+    Constant(u64, usize),
+    Not(Box<Expression>),
+    Cast(String, Box<Expression>),
+    /// Type of the value read and address
+    Load(String, Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+    Apply(&'static str, Vec<Expression>),
+    Undefined,
+}
+
+impl Expression {
+    fn binary(op: &'static str, a: Expression, b: Expression) -> Expression {
+        Expression::Binary(op, Box::new(a), Box::new(b))
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            &Expression::Not(_) | &Expression::Cast(..) | &Expression::Load(..) => 14,
+            &Expression::Binary(op, ..) => {
+                match op {
+                    "*" | "/" | "%" => 13,
+                    "+" | "-" => 12,
+                    "<<" | ">>" => 11,
+                    "<" | "<=" | ">" | ">=" => 10,
+                    "==" | "!=" => 9,
+                    "&" => 8,
+                    "^" => 7,
+                    "|" => 6,
+                    "&&" => 5,
+                    _ => 4,
+                }
+            }
+            _ => 15,
+        }
+    }
+
+    /// Writes the expression, in parenthesis if it binds weaker than `min`.
+    fn write(&self, f: &mut fmt::Formatter, min: u8) -> fmt::Result {
+        let prec = self.precedence();
+
+        if prec < min {
+            write!(f, "(")?;
+            self.write(f, 0)?;
+            return write!(f, ")");
+        }
+
+        match self {
+            &Expression::Variable(ref name) => write!(f, "{}", name),
+            &Expression::Constant(value, _) if value < 10 => write!(f, "{}", value),
+            &Expression::Constant(value, _) => write!(f, "0x{:x}", value),
+            &Expression::Not(ref e) => {
+                write!(f, "!")?;
+                e.write(f, prec)
+            }
+            &Expression::Cast(ref ty, ref e) => {
+                write!(f, "({})", ty)?;
+                e.write(f, prec)
+            }
+            &Expression::Load(ref ty, ref e) => {
+                write!(f, "*({}*)", ty)?;
+                e.write(f, prec)
+            }
+            &Expression::Binary(op, ref a, ref b) => {
+                a.write(f, prec)?;
+                write!(f, " {} ", op)?;
+                b.write(f, prec + 1)
+            }
+            &Expression::Apply(func, ref args) => {
+                write!(f, "{}(", func)?;
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    a.write(f, 0)?;
+                }
+                write!(f, ")")
+            }
+            &Expression::Undefined => write!(f, "undefined"),
+        }
+    }
+
+    /// Removes double negations, negates comparisons instead of their result and replaces
+    /// comparisons of one bit values with constants as well as additions of negative constants.
+    fn simplify(self) -> Expression {
+        match self {
+            Expression::Not(e) => {
+                match e.simplify() {
+                    Expression::Not(e) => *e,
+                    Expression::Binary(op, a, b) if negation(op).is_some() => Expression::Binary(negation(op).unwrap(), a, b),
+                    Expression::Constant(value, 1) => Expression::Constant(value ^ 1, 1),
+                    e => Expression::Not(Box::new(e)),
+                }
+            }
+            Expression::Binary(op, a, b) => {
+                match (op, a.simplify(), b.simplify()) {
+                    ("==", a, Expression::Constant(1, 1)) | ("!=", a, Expression::Constant(0, 1)) => a,
+                    ("==", a, Expression::Constant(0, 1)) | ("!=", a, Expression::Constant(1, 1)) | ("^", a, Expression::Constant(1, 1)) => {
+                        Expression::Not(Box::new(a)).simplify()
+                    }
+                    ("+", a, Expression::Constant(value, size)) if is_negative(value, size) => Expression::binary("-", a, Expression::Constant(negate(value, size), size)),
+                    ("-", a, Expression::Constant(value, size)) if is_negative(value, size) => Expression::binary("+", a, Expression::Constant(negate(value, size), size)),
+                    (op, a, b) => Expression::binary(op, a, b),
+                }
+            }
+            Expression::Cast(ty, e) => Expression::Cast(ty, Box::new(e.simplify())),
+            Expression::Load(ty, e) => Expression::Load(ty, Box::new(e.simplify())),
+            Expression::Apply(func, args) => Expression::Apply(func, args.into_iter().map(|a| a.simplify()).collect()),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+fn negation(op: &str) -> Option<&'static str> {
+    match op {
+        "==" => Some("!="),
+        "!=" => Some("=="),
+        "<" => Some(">="),
+        "<=" => Some(">"),
+        ">" => Some("<="),
+        ">=" => Some("<"),
+        _ => None,
+    }
+}
+
+fn is_negative(value: u64, size: usize) -> bool {
+    size > 1 && size <= 64 && (value >> (size - 1)) & 1 == 1
+}
+
+fn negate(value: u64, size: usize) -> u64 {
+    let v = (!value).wrapping_add(1);
+
+    if size >= 64 { v } else { v & ((1 << size) - 1) }
+}
+
+/// C name of an integer `bits` wide.
+fn integer(bits: usize, signed: bool) -> String {
+    let bits = match bits {
+        0...8 => 8,
+        9...16 => 16,
+        17...32 => 32,
+        33...64 => 64,
+        b => b,
+    };
+
+    format!("{}int{}_t", if signed { "" } else { "u" }, bits)
+}
+
+/// C name of `ty`.
+fn c_type(ty: &Type) -> String {
+    match ty {
+        &Type::Unknown(0) => "void".to_string(),
+        &Type::Unknown(1) | &Type::Bool => "bool".to_string(),
+        &Type::Unknown(bits) => integer(bits, false),
+        &Type::Integer { bits: 1, .. } => "bool".to_string(),
+        &Type::Integer { bits, signed } => integer(bits, signed == Some(true)),
+        &Type::Pointer(ref ty) => format!("{}*", c_type(ty)),
+        &Type::Struct(_) | &Type::Code => "void".to_string(),
+    }
+}
+
+/// Turns a RREIL variable name into a C identifier.
+fn identifier(name: &str, subscript: Option<usize>) -> String {
+    let mut ret = name.chars().map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' }).collect::<String>();
+
+    if ret.chars().next().map(|c| c.is_digit(10)).unwrap_or(true) {
+        ret.insert(0, '_');
+    }
+    if let Some(s) = subscript {
+        ret = format!("{}_{}", ret, s);
+    }
+    ret
+}
+
+fn name_of(lv: &Lvalue) -> Option<Name> {
+    match lv {
+        &Lvalue::Variable { ref name, subscript, .. } => Some((name.clone(), subscript)),
+        &Lvalue::Undefined => None,
+    }
+}
+
+fn reads(stmt: &Statement) -> Vec<Name> {
+    stmt.op
+        .operands()
+        .into_iter()
+        .filter_map(
+            |rv| match rv {
+                &Rvalue::Variable { ref name, subscript, .. } => Some((name.clone(), subscript)),
+                _ => None,
+            }
+        )
+        .collect()
+}
+
+/// Statements without side effects.
+fn is_pure(stmt: &Statement) -> bool {
+    match stmt.op {
+        Operation::Call(_) | Operation::Store(..) => false,
+        _ => true,
+    }
+}
+
+/// Statements that can be removed if their result isn't used.
+fn is_removable(stmt: &Statement) -> bool {
+    match stmt.op {
+        Operation::Call(_) | Operation::Store(..) | Operation::Load(..) => false,
+        _ => true,
+    }
+}
+
+/// Flags read by the guards of the jumps leaving `vx`.
+fn guard_flags(func: &Function, vx: ControlFlowRef) -> Vec<Name> {
+    let cfg = func.cfg();
+
+    cfg.out_edges(vx)
+        .filter_map(
+            |e| match cfg.edge_label(e) {
+                Some(&Guard::Predicate { flag: Rvalue::Variable { ref name, subscript, .. }, .. }) => Some((name.clone(), subscript)),
+                _ => None,
+            }
+        )
+        .collect()
+}
+
+/// Converts RREIL into pseudo-C.
+struct Context<'a> {
+    types: Types,
+    symbols: &'a HashMap<u64, String>,
+    /// Variables assigned outside of folded expressions
+    assigned: BTreeMap<String, String>,
+}
+
+impl<'a> Context<'a> {
+    fn type_of(&self, name: &Name, bits: usize) -> String {
+        match self.types.variables.get(name) {
+            Some(ty) => c_type(ty),
+            None => integer(bits, false),
+        }
+    }
+
+    fn operand(&self, rv: &Rvalue, values: &HashMap<Name, Expression>) -> Expression {
+        match rv {
+            &Rvalue::Variable { ref name, subscript, offset, size } => {
+                let key = (name.clone(), subscript);
+                let e = values.get(&key).cloned().unwrap_or_else(|| Expression::Variable(identifier(name, subscript)));
+
+                if offset > 0 {
+                    Expression::Cast(integer(size, false), Box::new(Expression::binary(">>", e, Expression::Constant(offset as u64, 8))))
+                } else {
+                    e
+                }
+            }
+            &Rvalue::Constant { value, size } => Expression::Constant(value, size),
+            &Rvalue::Undefined => Expression::Undefined,
+        }
+    }
+
+    fn signed(&self, rv: &Rvalue, values: &HashMap<Name, Expression>) -> Expression {
+        Expression::Cast(integer(rv.size().unwrap_or(0), true), Box::new(self.operand(rv, values)))
+    }
+
+    fn call_target(&self, rv: &Rvalue, values: &HashMap<Name, Expression>) -> Expression {
+        match rv {
+            &Rvalue::Constant { value, .. } => {
+                let name = self.symbols.get(&value).map(|s| identifier(s, None)).unwrap_or_else(|| format!("sub_{:x}", value));
+                Expression::Variable(name)
+            }
+            rv => Expression::Cast("code".to_string(), Box::new(self.operand(rv, values))),
+        }
+    }
+
+    /// Value computed by `op`.
+    fn expression(&self, op: &Operation<Rvalue>, values: &HashMap<Name, Expression>) -> Expression {
+        let bin = |o, a: &Rvalue, b: &Rvalue| Expression::binary(o, self.operand(a, values), self.operand(b, values));
+        let signed = |o, a: &Rvalue, b: &Rvalue| Expression::binary(o, self.signed(a, values), self.signed(b, values));
+
+        match op {
+            &Operation::Add(ref a, ref b) => bin("+", a, b),
+            &Operation::Subtract(ref a, ref b) => bin("-", a, b),
+            &Operation::Multiply(ref a, ref b) => bin("*", a, b),
+            &Operation::DivideUnsigned(ref a, ref b) => bin("/", a, b),
+            &Operation::DivideSigned(ref a, ref b) => signed("/", a, b),
+            &Operation::ShiftLeft(ref a, ref b) => bin("<<", a, b),
+            &Operation::ShiftRightUnsigned(ref a, ref b) => bin(">>", a, b),
+            &Operation::ShiftRightSigned(ref a, ref b) => Expression::binary(">>", self.signed(a, values), self.operand(b, values)),
+            &Operation::Modulo(ref a, ref b) => bin("%", a, b),
+            &Operation::And(ref a, ref b) => bin("&", a, b),
+            &Operation::InclusiveOr(ref a, ref b) => bin("|", a, b),
+            &Operation::ExclusiveOr(ref a, ref b) => bin("^", a, b),
+            &Operation::Equal(ref a, ref b) => bin("==", a, b),
+            &Operation::LessOrEqualUnsigned(ref a, ref b) => bin("<=", a, b),
+            &Operation::LessOrEqualSigned(ref a, ref b) => signed("<=", a, b),
+            &Operation::LessUnsigned(ref a, ref b) => bin("<", a, b),
+            &Operation::LessSigned(ref a, ref b) => signed("<", a, b),
+            &Operation::ZeroExtend(bits, ref a) => Expression::Cast(integer(bits, false), Box::new(self.operand(a, values))),
+            &Operation::SignExtend(bits, ref a) => Expression::Cast(integer(bits, true), Box::new(self.signed(a, values))),
+            &Operation::Move(ref a) => self.operand(a, values),
+            &Operation::Call(ref a) => Expression::Apply("call", vec![self.call_target(a, values)]),
+            &Operation::Initialize(ref name, _) => Expression::Apply("initial", vec![Expression::Variable(identifier(name, None))]),
+            &Operation::Select(off, ref a, ref b) => Expression::Apply("select", vec![Expression::Constant(off as u64, 8), self.operand(a, values), self.operand(b, values)]),
+            &Operation::Load(_, _, bits, ref addr) => Expression::Load(integer(bits, false), Box::new(self.operand(addr, values))),
+            &Operation::Store(_, _, bits, ref addr, _) => Expression::Load(integer(bits, false), Box::new(self.operand(addr, values))),
+            &Operation::Phi(ref ops) => Expression::Apply("phi", ops.iter().map(|a| self.operand(a, values)).collect()),
+        }
+    }
+
+    /// Returns the lines of `stmts` and the values of the flags read by the jumps leaving the
+    /// basic block.
+    fn block(&mut self, stmts: &[&Statement], flags: &[Name], liveout: &HashSet<Cow<'static, str>>) -> (Vec<String>, HashMap<Name, Expression>) {
+        let n = stmts.len();
+        let mut dead = vec![false; n];
+        let mut live = liveout.clone();
+
+        live.extend(flags.iter().map(|f| f.0.clone()));
+        for (i, stmt) in stmts.iter().enumerate().rev() {
+            let def = name_of(&stmt.assignee);
+
+            if is_removable(stmt) && def.as_ref().map(|d| !live.contains(&d.0)).unwrap_or(true) {
+                dead[i] = true;
+                continue;
+            }
+            if let Some(d) = def {
+                live.remove(&d.0);
+            }
+            live.extend(reads(stmt).into_iter().map(|r| r.0));
+        }
+
+        // statement `i` is folded into statement `into[i]`, `n` stands for the guards
+        let mut into = vec![None; n];
+        let mut folded = vec![Vec::<usize>::new(); n + 1];
+        let mut expanded = vec![HashSet::new(); n];
+        let mut loads = vec![false; n];
+
+        for i in 0..n {
+            if dead[i] {
+                continue;
+            }
+
+            let stmt = stmts[i];
+
+            expanded[i] = reads(stmt).into_iter().map(|r| r.0).collect::<HashSet<_>>();
+            loads[i] = if let Operation::Load(..) = stmt.op { true } else { false };
+            for &k in folded[i].iter() {
+                let e = expanded[k].clone();
+                expanded[i].extend(e);
+                loads[i] |= loads[k];
+            }
+
+            let def = match (name_of(&stmt.assignee), &stmt.op) {
+                (_, &Operation::Phi(_)) => continue,
+                (Some(def), _) if is_pure(stmt) => def,
+                _ => continue,
+            };
+            let mut uses = vec![];
+            let mut redefined = false;
+
+            for j in (i + 1)..n {
+                if dead[j] {
+                    continue;
+                }
+                uses.extend(reads(stmts[j]).into_iter().filter(|r| *r == def).map(|_| j));
+                if name_of(&stmts[j].assignee).map(|d| d.0 == def.0).unwrap_or(false) {
+                    redefined = true;
+                    break;
+                }
+            }
+            if !redefined {
+                if liveout.contains(&def.0) {
+                    continue;
+                }
+                if flags.contains(&def) {
+                    uses.push(n);
+                }
+            }
+            if uses.len() != 1 {
+                continue;
+            }
+
+            let j = uses[0];
+            let clobbered = ((i + 1)..j).any(
+                |k| {
+                    !dead[k] &&
+                    (name_of(&stmts[k].assignee).map(|d| expanded[i].contains(&d.0)).unwrap_or(false) || loads[i] && !is_pure(stmts[k]))
+                }
+            );
+
+            if !clobbered {
+                into[i] = Some(j);
+                folded[j].push(i);
+            }
+        }
+
+        let mut lines = vec![];
+        let mut values = HashMap::<usize, Expression>::new();
+        let inputs = |folded: &Vec<usize>, values: &HashMap<usize, Expression>| {
+            folded.iter().filter_map(|k| name_of(&stmts[*k].assignee).map(|d| (d, values[k].clone()))).collect::<HashMap<_, _>>()
+        };
+
+        for i in 0..n {
+            if dead[i] {
+                continue;
+            }
+
+            let stmt = stmts[i];
+            let inp = inputs(&folded[i], &values);
+            let line = match stmt.op {
+                Operation::Store(_, _, bits, ref addr, ref val) => {
+                    let addr = self.operand(addr, &inp).simplify();
+                    let val = self.operand(val, &inp).simplify();
+                    format!("{} = {};", Expression::Load(integer(bits, false), Box::new(addr)), val)
+                }
+                Operation::Call(ref a) => format!("{}();", self.call_target(a, &inp).simplify()),
+                ref op => {
+                    let e = self.expression(op, &inp).simplify();
+
+                    if into[i].is_some() {
+                        values.insert(i, e);
+                        continue;
+                    }
+
+                    match stmt.assignee {
+                        Lvalue::Variable { ref name, subscript, size } => {
+                            let id = identifier(name, subscript);
+                            let ty = self.type_of(&(name.clone(), subscript), size);
+
+                            self.assigned.insert(id.clone(), ty);
+                            format!("{} = {};", id, e)
+                        }
+                        Lvalue::Undefined => format!("{};", e),
+                    }
+                }
+            };
+
+            lines.push(line);
+        }
+
+        (lines, inputs(&folded[n], &values))
+    }
+
+    fn condition(&self, guard: &Guard, values: &HashMap<Name, Expression>) -> Expression {
+        match guard {
+            &Guard::True => Expression::Constant(1, 1),
+            &Guard::False => Expression::Constant(0, 1),
+            &Guard::Predicate { ref flag, expected: true } => self.operand(flag, values).simplify(),
+            &Guard::Predicate { ref flag, expected: false } => Expression::Not(Box::new(self.operand(flag, values))).simplify(),
+        }
+    }
+}
+
+/// Variables read after the function returns or jumps to unknown code: the return register and
+/// everything respectively. Returns the ones live at the end of each basic block.
+fn exit_liveness(func: &Function, varkill: &HashMap<ControlFlowRef, HashSet<Cow<'static, str>>>, ret: Option<&'static str>) -> HashMap<ControlFlowRef, HashSet<Cow<'static, str>>> {
+    let cfg = func.cfg();
+    let all = func.statements().filter_map(|s| name_of(&s.assignee).map(|n| n.0)).collect::<HashSet<_>>();
+    let mut out = HashMap::<ControlFlowRef, HashSet<Cow<'static, str>>>::new();
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+        for vx in cfg.vertices() {
+            let mut live = HashSet::new();
+
+            if cfg.out_degree(vx) == 0 {
+                live.extend(ret.map(Cow::Borrowed));
+            }
+            for e in cfg.out_edges(vx) {
+                let tgt = cfg.target(e);
+
+                match cfg.vertex_label(tgt) {
+                    Some(&ControlFlowTarget::Resolved(_)) => {
+                        let empty = HashSet::new();
+                        let kill = varkill.get(&tgt).unwrap_or(&empty);
+
+                        live.extend(out.get(&tgt).unwrap_or(&empty).difference(kill).cloned());
+                    }
+                    _ => live.extend(all.iter().cloned()),
+                }
+            }
+
+            if out.get(&vx).map(|o| o.len() != live.len()).unwrap_or(true) {
+                out.insert(vx, live);
+                changed = true;
+            }
+        }
+    }
+
+    out
+}
+
+fn label(addr: u64) -> String {
+    format!("L_{:x}", addr)
+}
+
+/// Returns `func` as pseudo-C. Calls to constant addresses are named after `symbols` or
+/// `sub_<address>` if the address isn't in there. `func` must not be in SSA form.
+pub fn decompile(func: &Function, symbols: &HashMap<u64, String>) -> String {
+    let cfg = func.cfg();
+    let entry = func.entry_point_ref();
+    let liveout = liveness(func);
+    let (varkill, uevar) = liveness_sets(func);
+    let mut ctx = Context { types: infer_types(func), symbols: symbols, assigned: BTreeMap::new() };
+
+    // arguments
+    let mut livein = uevar.get(&entry).cloned().unwrap_or_default();
+
+    if let (Some(out), Some(kill)) = (liveout.get(&entry), varkill.get(&entry)) {
+        livein.extend(out.difference(kill).cloned());
+    }
+
+    let cc = func.calling_convention();
+    let ret_reg = cc.and_then(|cc| cc.return_registers().first().cloned());
+    let mut live = exit_liveness(func, &varkill, ret_reg);
+
+    for (vx, l) in liveout.iter() {
+        live.entry(*vx).or_insert_with(HashSet::new).extend(l.iter().cloned());
+    }
+
+    let args = match cc {
+        Some(cc) => cc.argument_registers().iter().filter(|r| livein.contains(**r)).map(|r| Cow::Borrowed(*r)).collect::<Vec<_>>(),
+        None => {
+            let mut args = livein.into_iter().filter(|v| !["RSP", "ESP", "SP"].contains(&&**v)).collect::<Vec<_>>();
+            args.sort();
+            args
+        }
+    };
+
+    let widths = func.statements()
+        .flat_map(|s| s.op.operands().into_iter().cloned().chain(::std::iter::once(s.assignee.clone().into())).collect::<Vec<Rvalue>>())
+        .filter_map(
+            |rv| match rv {
+                Rvalue::Variable { name, subscript: None, offset: 0, size } => Some((name, size)),
+                _ => None,
+            }
+        )
+        .collect::<HashMap<_, _>>();
+    let params = args.iter().map(|a| format!("{} {}", ctx.type_of(&(a.clone(), None), widths.get(a).cloned().unwrap_or(0)), identifier(a, None))).collect::<Vec<_>>();
+
+    let empty = HashSet::new();
+    let blocks = cfg.vertices()
+        .filter_map(
+            |vx| match cfg.vertex_label(vx) {
+                Some(&ControlFlowTarget::Resolved(ref bb)) => {
+                    let stmts = bb.statements().collect::<Vec<_>>();
+                    let flags = guard_flags(func, vx);
+
+                    Some((vx, ctx.block(&stmts, &flags, live.get(&vx).unwrap_or(&empty))))
+                }
+                _ => None,
+            }
+        )
+        .collect::<HashMap<_, _>>();
+    let nodes = structure(func);
+    let ret = ret_reg.and_then(|r| ctx.assigned.get(r).cloned().map(|ty| (r, ty)));
+    let mut out = format!(
+        "{} {}({})\n{{\n",
+        ret.as_ref().map(|r| r.1.clone()).unwrap_or("void".to_string()),
+        identifier(&func.name, None),
+        params.join(", ")
+    );
+    let locals = ctx.assigned.iter().filter(|&(v, _)| !args.iter().any(|a| identifier(a, None) == **v)).collect::<Vec<_>>();
+
+    for &(v, ty) in locals.iter() {
+        out.push_str(&format!("    {} {};\n", ty, v));
+    }
+    if !locals.is_empty() {
+        out.push('\n');
+    }
+
+    let printer = Printer {
+        ctx: &ctx,
+        func: func,
+        blocks: blocks,
+        labels: goto_targets(&nodes),
+        ret: ret.map(|r| r.0),
+    };
+
+    printer.nodes(&nodes, 1, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+/// Prints structured control flow.
+struct Printer<'a> {
+    ctx: &'a Context<'a>,
+    func: &'a Function,
+    /// Lines and flag values of each basic block
+    blocks: HashMap<ControlFlowRef, (Vec<String>, HashMap<Name, Expression>)>,
+    labels: HashSet<ControlFlowRef>,
+    ret: Option<&'static str>,
+}
+
+impl<'a> Printer<'a> {
+    fn condition(&self, cond: &Condition) -> Expression {
+        let empty = HashMap::new();
+        let values = self.blocks.get(&cond.block).map(|b| &b.1).unwrap_or(&empty);
+
+        self.ctx.condition(&cond.guard, values)
+    }
+
+    fn lines(&self, vx: ControlFlowRef) -> &[String] {
+        self.blocks.get(&vx).map(|b| &b.0[..]).unwrap_or(&[])
+    }
+
+    fn label(&self, vx: ControlFlowRef, pad: &str, out: &mut String) {
+        if self.labels.contains(&vx) {
+            if let Some(&ControlFlowTarget::Resolved(ref bb)) = self.func.cfg().vertex_label(vx) {
+                out.push_str(&format!("{}{}:\n", &pad[4..], label(bb.area.start)));
+            }
+        }
+    }
+
+    fn nodes(&self, nodes: &[Node], indent: usize, out: &mut String) {
+        let cfg = self.func.cfg();
+        let pad = " ".repeat(indent * 4);
+
+        for node in nodes {
+            match node {
+                &Node::Block(vx) => {
+                    self.label(vx, &pad, out);
+                    for l in self.lines(vx) {
+                        out.push_str(&format!("{}{}\n", pad, l));
+                    }
+                    if cfg.out_degree(vx) == 0 {
+                        match self.ret {
+                            Some(r) => out.push_str(&format!("{}return {};\n", pad, r)),
+                            None => out.push_str(&format!("{}return;\n", pad)),
+                        }
+                    }
+                }
+                &Node::If { ref condition, ref then, ref otherwise } => {
+                    if then.is_empty() && otherwise.is_empty() {
+                        continue;
+                    }
+                    out.push_str(&format!("{}if ({}) {{\n", pad, self.condition(condition)));
+                    self.nodes(then, indent + 1, out);
+                    if !otherwise.is_empty() {
+                        out.push_str(&format!("{}}} else {{\n", pad));
+                        self.nodes(otherwise, indent + 1, out);
+                    }
+                    out.push_str(&format!("{}}}\n", pad));
+                }
+                &Node::Switch { block, ref cases } => {
+                    // guards of the jumps leaving a basic block cover all cases
+                    for (i, &(ref guard, ref arm)) in cases.iter().enumerate() {
+                        let cond = self.condition(&Condition { block: block, guard: guard.clone() });
+
+                        if i == 0 {
+                            out.push_str(&format!("{}if ({}) {{\n", pad, cond));
+                        } else if i + 1 == cases.len() {
+                            out.push_str(&format!("{}}} else {{\n", pad));
+                        } else {
+                            out.push_str(&format!("{}}} else if ({}) {{\n", pad, cond));
+                        }
+                        self.nodes(arm, indent + 1, out);
+                    }
+                    out.push_str(&format!("{}}}\n", pad));
+                }
+                &Node::While { ref condition, ref body } => {
+                    let header = self.lines(condition.block);
+
+                    self.label(condition.block, &pad, out);
+                    if header.is_empty() {
+                        out.push_str(&format!("{}while ({}) {{\n", pad, self.condition(condition)));
+                    } else {
+                        out.push_str(&format!("{}while (1) {{\n", pad));
+                        for l in header {
+                            out.push_str(&format!("{}    {}\n", pad, l));
+                        }
+                        out.push_str(&format!("{}    if ({}) break;\n", pad, self.condition(&condition.negation())));
+                    }
+                    self.nodes(body, indent + 1, out);
+                    out.push_str(&format!("{}}}\n", pad));
+                }
+                &Node::DoWhile { ref body, ref condition } => {
+                    out.push_str(&format!("{}do {{\n", pad));
+                    self.nodes(body, indent + 1, out);
+                    out.push_str(&format!("{}}} while ({});\n", pad, self.condition(condition)));
+                }
+                &Node::Loop(ref body) => {
+                    out.push_str(&format!("{}while (1) {{\n", pad));
+                    self.nodes(body, indent + 1, out);
+                    out.push_str(&format!("{}}}\n", pad));
+                }
+                &Node::Break => out.push_str(&format!("{}break;\n", pad)),
+                &Node::Continue => out.push_str(&format!("{}continue;\n", pad)),
+                &Node::Goto(vx) => {
+                    match cfg.vertex_label(vx) {
+                        Some(&ControlFlowTarget::Resolved(ref bb)) => out.push_str(&format!("{}goto {};\n", pad, label(bb.area.start))),
+                        Some(&ControlFlowTarget::Unresolved(ref rv)) => out.push_str(&format!("{}goto *{};\n", pad, self.ctx.operand(rv, &HashMap::new()).simplify())),
+                        Some(&ControlFlowTarget::Failed(addr, ref msg)) => out.push_str(&format!("{}/* failed to disassemble 0x{:x}: {} */\n", pad, addr, msg)),
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns all functions of `prog` as pseudo-C, ordered by address. Calls are named after the
+/// functions and imports of `prog`.
+pub fn decompile_program(prog: &Program) -> String {
+    let mut symbols = prog.imports.clone();
+    let mut funcs = prog.functions().collect::<Vec<_>>();
+
+    symbols.extend(funcs.iter().map(|f| (f.start(), f.name.clone())));
+    funcs.sort_by_key(|f| f.start());
+    funcs.iter().map(|f| decompile(f, &symbols)).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, CallingConvention, ControlFlowGraph, Endianess, Mnemonic, Region};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn var(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn block(start: u64, stmts: Vec<Statement>) -> ControlFlowTarget {
+        let mne = Mnemonic::new(start..start + 4, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne]))
+    }
+
+    /*
+     * 0x0: t = *rdi + 1; cf = t < *rdi; zf = t == 0; rax = t; if zf goto 0x8
+     * 0x4: call 0x100
+     * 0x8: ret
+     */
+    #[test]
+    fn pseudo_c() {
+        let (rdi, rax, a, t, cf, zf) = (var("RDI", 64), var("RAX", 64), var("a", 64), var("t", 64), var("CF", 1), var("ZF", 1));
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(
+            block(
+                0,
+                vec![
+                    Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, 64, rdi.clone().into()), assignee: a.clone() },
+                    Statement { op: Operation::Add(a.clone().into(), Rvalue::new_u64(1)), assignee: t.clone() },
+                    Statement { op: Operation::LessUnsigned(t.clone().into(), a.clone().into()), assignee: cf.clone() },
+                    Statement { op: Operation::Equal(t.clone().into(), Rvalue::new_u64(0)), assignee: zf.clone() },
+                    Statement { op: Operation::Move(t.clone().into()), assignee: rax.clone() },
+                ]
+            )
+        );
+        let b1 = cfg.add_vertex(block(4, vec![Statement { op: Operation::Call(Rvalue::new_u64(0x100)), assignee: Lvalue::Undefined }]));
+        let b2 = cfg.add_vertex(block(8, vec![]));
+        let zf = Guard::from_flag(&zf.into()).ok().unwrap();
+
+        cfg.add_edge(zf.negation(), b0, b1);
+        cfg.add_edge(zf, b0, b2);
+        cfg.add_edge(Guard::True, b1, b2);
+
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), 0x10), Some("test".to_string()));
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        func.set_calling_convention(Some(CallingConvention::SysV64));
+
+        let mut symbols = HashMap::new();
+        symbols.insert(0x100, "puts@plt".to_string());
+
+        assert_eq!(
+            decompile(&func, &symbols),
+            "uint64_t test(uint64_t* RDI)\n{\n    uint64_t RAX;\n    uint64_t t;\n\n    t = *(uint64_t*)RDI + 1;\n    RAX = t;\n    if (t != 0) {\n        puts_plt();\n    }\n    return RAX;\n}\n"
+        );
+    }
+
+    #[test]
+    fn simplify() {
+        let x = Expression::Variable("x".to_string());
+        let lt = Expression::binary("<", x.clone(), Expression::Constant(5, 32));
+
+        assert_eq!(format!("{}", Expression::Not(Box::new(lt.clone())).simplify()), "x >= 5");
+        assert_eq!(format!("{}", Expression::binary("==", lt.clone(), Expression::Constant(0, 1)).simplify()), "x >= 5");
+        assert_eq!(format!("{}", Expression::binary("+", x.clone(), Expression::Constant(0xfffffff8, 32)).simplify()), "x - 8");
+        assert_eq!(format!("{}", Expression::binary("*", Expression::binary("+", x.clone(), x.clone()), x.clone())), "(x + x) * x");
+        assert_eq!(format!("{}", Expression::binary("-", x.clone(), Expression::binary("-", x.clone(), x.clone()))), "x - (x - x)");
+    }
+}
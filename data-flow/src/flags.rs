@@ -0,0 +1,216 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Removal of dead flag computations.
+//!
+//! Lifters compute every status flag an instruction sets, even though almost all of them are
+//! overwritten by the next arithmetic instruction before anything reads them. On AMD64 this is
+//! often more than half of all statements. [`remove_dead_flags`] deletes assignments to flags
+//! that are dead afterwards, together with the temporaries only used to compute them.
+//!
+//! A temporary is a variable every mnemonic writes before reading, so its value never leaves the
+//! mnemonic. Flags are assumed to be live at indirect jumps and jumps to code outside the
+//! function, and dead at returns.
+//!
+//! [`remove_dead_flags`]: fn.remove_dead_flags.html
+
+use liveness;
+use panopticon_core::{ControlFlowTarget, Function, Guard, Lvalue, Operation, Rvalue};
+use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, MutableGraphTrait, VertexListGraphTrait};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// Removes assignments to the variables in `flags` that are never read, as well as computations
+/// of temporaries used only by them. `func` must not be in SSA form. Returns the number of
+/// statements removed.
+pub fn remove_dead_flags(func: &mut Function, flags: &[&str]) -> usize {
+    if flags.is_empty() {
+        return 0;
+    }
+
+    let temps = temporaries(func);
+    let mut liveout = liveness(func);
+
+    // guards are read after the last statement, unknown successors may read any flag
+    for (vx, live) in liveout.iter_mut() {
+        let cfg = func.cfg();
+
+        for e in cfg.out_edges(*vx) {
+            if let Some(&Guard::Predicate { flag: Rvalue::Variable { ref name, .. }, .. }) = cfg.edge_label(e) {
+                live.insert(name.clone());
+            }
+
+            match cfg.vertex_label(cfg.target(e)) {
+                Some(&ControlFlowTarget::Resolved(_)) => {}
+                _ => live.extend(flags.iter().map(|f| Cow::Owned(f.to_string()))),
+            }
+        }
+    }
+
+    let mut ret = 0;
+    let vxs = func.cfg().vertices().collect::<Vec<_>>();
+
+    for vx in vxs {
+        let mut live = match liveout.remove(&vx) {
+            Some(l) => l,
+            None => continue,
+        };
+        let bb = match func.cfg_mut().vertex_label_mut(vx) {
+            Some(&mut ControlFlowTarget::Resolved(ref mut bb)) => bb,
+            _ => continue,
+        };
+
+        for mne in bb.mnemonics.iter_mut().rev() {
+            let before = mne.instructions.len();
+            let mut keep = vec![];
+
+            for stmt in mne.instructions.drain(..).rev() {
+                let removable = match (&stmt.assignee, &stmt.op) {
+                    (_, &Operation::Call(_)) |
+                    (_, &Operation::Load(..)) |
+                    (_, &Operation::Store(..)) => false,
+                    (&Lvalue::Variable { ref name, .. }, _) => {
+                        (flags.contains(&&**name) || temps.contains(name)) && !live.contains(name)
+                    }
+                    (&Lvalue::Undefined, _) => false,
+                };
+
+                if removable {
+                    continue;
+                }
+
+                if let Lvalue::Variable { ref name, .. } = stmt.assignee {
+                    live.remove(name);
+                }
+                for rv in stmt.op.operands() {
+                    if let &Rvalue::Variable { ref name, .. } = rv {
+                        live.insert(name.clone());
+                    }
+                }
+
+                keep.push(stmt);
+            }
+
+            keep.reverse();
+            ret += before - keep.len();
+            mne.instructions = keep;
+        }
+    }
+
+    ret
+}
+
+/// Variables whose value never leaves a mnemonic.
+fn temporaries(func: &Function) -> HashSet<Cow<'static, str>> {
+    let mut written = HashSet::new();
+    let mut exposed = HashSet::new();
+
+    for bb in func.basic_blocks() {
+        for mne in bb.mnemonics.iter() {
+            let mut defined = HashSet::new();
+
+            for stmt in mne.instructions.iter() {
+                for rv in stmt.op.operands() {
+                    if let &Rvalue::Variable { ref name, .. } = rv {
+                        if !defined.contains(name) {
+                            exposed.insert(name.clone());
+                        }
+                    }
+                }
+                if let Lvalue::Variable { ref name, .. } = stmt.assignee {
+                    defined.insert(name.clone());
+                    written.insert(name.clone());
+                }
+            }
+        }
+    }
+
+    let cfg = func.cfg();
+
+    for e in cfg.edges() {
+        if let Some(&Guard::Predicate { flag: Rvalue::Variable { ref name, .. }, .. }) = cfg.edge_label(e) {
+            exposed.insert(name.clone());
+        }
+    }
+
+    written.difference(&exposed).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, ControlFlowGraph, Mnemonic, Region, Statement};
+
+    fn var(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn nop(start: u64) -> Mnemonic {
+        Mnemonic::new(start..start + 1, "nop".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap()
+    }
+
+    /// a = a + b, setting CF and ZF
+    fn add(start: u64, a: &Lvalue, b: &Lvalue) -> Mnemonic {
+        let res = var("res", 32);
+        let cf = var("cf1", 1);
+        let stmts = vec![
+            Statement { op: Operation::Add(a.clone().into(), b.clone().into()), assignee: res.clone() },
+            Statement { op: Operation::LessUnsigned(res.clone().into(), a.clone().into()), assignee: cf.clone() },
+            Statement { op: Operation::Move(cf.clone().into()), assignee: var("CF", 1) },
+            Statement { op: Operation::Equal(res.clone().into(), Rvalue::new_u32(0)), assignee: var("ZF", 1) },
+            Statement { op: Operation::Move(res.clone().into()), assignee: a.clone() },
+        ];
+
+        Mnemonic::new(start..start + 2, "add".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap()
+    }
+
+    /*
+     * add eax, ebx; add eax, ecx; jz 0x10
+     */
+    #[test]
+    fn dead_flags() {
+        let eax = var("EAX", 32);
+        let bb = BasicBlock::from_vec(vec![add(0, &eax, &var("EBX", 32)), add(2, &eax, &var("ECX", 32))]);
+        let zf = Guard::from_flag(&var("ZF", 1).into()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(ControlFlowTarget::Resolved(bb));
+        let b1 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![nop(4)])));
+        let b2 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![nop(0x10)])));
+
+        cfg.add_edge(zf.negation(), b0, b1);
+        cfg.add_edge(zf, b0, b2);
+
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), 0x20), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+
+        assert_eq!(remove_dead_flags(&mut func, &["CF", "ZF"]), 5);
+
+        let names = func.statements()
+            .filter_map(
+                |s| match s.assignee {
+                    Lvalue::Variable { ref name, .. } => Some(name.to_string()),
+                    Lvalue::Undefined => None,
+                }
+            )
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["res", "EAX", "res", "ZF", "EAX"]);
+        assert_eq!(remove_dead_flags(&mut func, &[]), 0);
+    }
+}
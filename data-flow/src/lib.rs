@@ -19,7 +19,8 @@
 //! Collection of data flow algorithms.
 //!
 //! This module contains algorithms to convert RREIL code into SSA form. Aside from SSA form this
-//! module implements functions to compute liveness sets and basic reverse data flow information.
+//! module implements functions to compute liveness sets and basic reverse data flow information
+//! as well as a simple type recovery pass and a pseudo-C decompiler.
 
 extern crate panopticon_core;
 extern crate panopticon_graph_algos;
@@ -29,3 +30,18 @@ pub use liveness::{liveness, liveness_sets};
 
 mod ssa;
 pub use ssa::{flag_operations, ssa_convertion, type_check};
+
+mod flags;
+pub use flags::remove_dead_flags;
+
+mod loops;
+pub use loops::{InductionVariable, Loop, loops};
+
+mod types;
+pub use types::{Type, Types, infer_types};
+
+mod structure;
+pub use structure::{Condition, Node, goto_targets, structure};
+
+mod decompile;
+pub use decompile::{decompile, decompile_program};
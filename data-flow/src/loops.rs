@@ -0,0 +1,465 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Natural loops, induction variables and trip counts.
+//!
+//! A back edge is a jump to a basic block dominating its source. The blocks reaching the source
+//! without passing the target form a natural loop, loops sharing a header are merged.
+//!
+//! The function must be in SSA form. A basic induction variable is a Phi function in the loop
+//! header with a single operand defined outside the loop, its initial value, and all other
+//! operands computed inside the loop by adding the same constant step to the Phi's result.
+//!
+//! If the only exit of a loop is guarded by a comparison of an induction variable with a
+//! constant and its initial value is known the number of iterations is computed. It is the
+//! number of times a back edge is taken, so a loop testing its condition after the body runs the
+//! body once more.
+
+use panopticon_core::{ControlFlowRef, ControlFlowTarget, Function, Guard, Lvalue, Operation, Rvalue, Statement};
+use panopticon_graph_algos::{BidirectionalGraphTrait, EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use panopticon_graph_algos::dominator::dominators;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+
+/// Basic induction variable.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct InductionVariable {
+    /// Name of the variable
+    pub name: Cow<'static, str>,
+    /// SSA subscript of the Phi function in the loop header
+    pub subscript: Option<usize>,
+    /// Width in bits
+    pub bits: usize,
+    /// Value before the first iteration, if constant
+    pub initial: Option<u64>,
+    /// Value added in each iteration
+    pub step: i64,
+}
+
+/// Natural loop of a function.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Loop {
+    /// Basic block all back edges jump to
+    pub header: ControlFlowRef,
+    /// All basic blocks of the loop including the header, sorted
+    pub body: Vec<ControlFlowRef>,
+    /// Basic induction variables
+    pub induction_variables: Vec<InductionVariable>,
+    /// Number of times a back edge is taken, if known
+    pub trip_count: Option<u64>,
+}
+
+type Name = (Cow<'static, str>, Option<usize>);
+
+/// Comparison deciding whether the loop continues, with the induction variable on the left.
+#[derive(Clone,Copy,Debug)]
+enum Condition {
+    Less(i64),
+    Greater(i64),
+    Equal(i64),
+    NotEqual(i64),
+}
+
+/// Finds the natural loops of `func`, which needs to be in SSA form.
+pub fn loops(func: &Function) -> Vec<Loop> {
+    let cfg = func.cfg();
+    let dom = dominators(func.entry_point_ref(), cfg);
+    let mut bodies = HashMap::<ControlFlowRef, BTreeSet<ControlFlowRef>>::new();
+
+    for e in cfg.edges() {
+        let (from, to) = (cfg.source(e), cfg.target(e));
+
+        if dom.get(&from).map(|d| d.contains(&to)).unwrap_or(false) {
+            let body = bodies.entry(to).or_insert_with(BTreeSet::new);
+            let mut todo = vec![from];
+
+            body.insert(to);
+            while let Some(vx) = todo.pop() {
+                if body.insert(vx) {
+                    todo.extend(cfg.in_edges(vx).map(|e| cfg.source(e)));
+                }
+            }
+        }
+    }
+
+    let defs = definitions(func);
+    let mut ret = bodies
+        .into_iter()
+        .map(
+            |(header, body)| {
+                let ivs = induction_variables(func, header, &body, &defs);
+                let trip_count = trip_count(func, &body, &ivs, &defs);
+
+                Loop {
+                    header: header,
+                    body: body.into_iter().collect(),
+                    induction_variables: ivs.into_iter().map(|(iv, _)| iv).collect(),
+                    trip_count: trip_count,
+                }
+            }
+        )
+        .collect::<Vec<_>>();
+
+    ret.sort_by_key(|l| l.header);
+    ret
+}
+
+/// Defining statement and basic block of every SSA variable.
+fn definitions(func: &Function) -> HashMap<Name, (ControlFlowRef, &Statement)> {
+    let cfg = func.cfg();
+    let mut ret = HashMap::new();
+
+    for vx in cfg.vertices() {
+        if let Some(&ControlFlowTarget::Resolved(ref bb)) = cfg.vertex_label(vx) {
+            for stmt in bb.statements() {
+                if let Lvalue::Variable { ref name, subscript, .. } = stmt.assignee {
+                    ret.insert((name.clone(), subscript), (vx, stmt));
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Follows copies of `rv`.
+fn resolve<'a>(mut rv: &'a Rvalue, defs: &HashMap<Name, (ControlFlowRef, &'a Statement)>) -> &'a Rvalue {
+    for _ in 0..16 {
+        let def = match rv {
+            &Rvalue::Variable { ref name, subscript, offset: 0, .. } => defs.get(&(name.clone(), subscript)),
+            _ => None,
+        };
+
+        match def {
+            Some(&(_, &Statement { op: Operation::Move(ref a), .. })) => rv = a,
+            _ => break,
+        }
+    }
+
+    rv
+}
+
+fn name_of(rv: &Rvalue) -> Option<Name> {
+    match rv {
+        &Rvalue::Variable { ref name, subscript, offset: 0, .. } => Some((name.clone(), subscript)),
+        _ => None,
+    }
+}
+
+/// Basic induction variables of the loop, with the names of the variables holding the value
+/// after the increment.
+fn induction_variables(func: &Function, header: ControlFlowRef, body: &BTreeSet<ControlFlowRef>, defs: &HashMap<Name, (ControlFlowRef, &Statement)>)
+                       -> Vec<(InductionVariable, Vec<Name>)> {
+    let bb = match func.cfg().vertex_label(header) {
+        Some(&ControlFlowTarget::Resolved(ref bb)) => bb,
+        _ => return vec![],
+    };
+    let mut ret = vec![];
+
+    for stmt in bb.statements() {
+        let (ops, name, subscript, bits) = match stmt {
+            &Statement { op: Operation::Phi(ref ops), assignee: Lvalue::Variable { ref name, subscript, size } } => {
+                (ops, name, subscript, size)
+            }
+            _ => continue,
+        };
+        let phi = (name.clone(), subscript);
+        let mut initial = vec![];
+        let mut steps = vec![];
+        let mut next = vec![];
+
+        for op in ops.iter() {
+            let n = match name_of(op) {
+                Some(n) => n,
+                None => {
+                    initial.push(op);
+                    continue;
+                }
+            };
+
+            match defs.get(&n) {
+                Some(&(vx, _)) if body.contains(&vx) => {
+                    let step = match defs.get(&name_of(resolve(op, defs)).unwrap_or(n.clone())) {
+                        Some(&(_, &Statement { op: Operation::Add(ref a, ref b), ref assignee })) => {
+                            match (name_of(resolve(a, defs)), name_of(resolve(b, defs)), resolve(a, defs), resolve(b, defs)) {
+                                (Some(ref a), _, _, &Rvalue::Constant { value, .. }) if *a == phi => Some((signed(value, bits), assignee)),
+                                (_, Some(ref b), &Rvalue::Constant { value, .. }, _) if *b == phi => Some((signed(value, bits), assignee)),
+                                _ => None,
+                            }
+                        }
+                        Some(&(_, &Statement { op: Operation::Subtract(ref a, ref b), ref assignee })) => {
+                            match (name_of(resolve(a, defs)), resolve(b, defs)) {
+                                (Some(ref a), &Rvalue::Constant { value, .. }) if *a == phi => Some((signed(value, bits).wrapping_neg(), assignee)),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    };
+
+                    match step {
+                        Some((step, &Lvalue::Variable { ref name, subscript, .. })) => {
+                            steps.push(step);
+                            next.push((name.clone(), subscript));
+                            next.push(n);
+                        }
+                        _ => {
+                            steps.clear();
+                            break;
+                        }
+                    }
+                }
+                _ => initial.push(op),
+            }
+        }
+
+        if steps.is_empty() || initial.len() != 1 || steps.iter().any(|&s| s != steps[0]) || steps[0] == 0 {
+            continue;
+        }
+
+        let initial = match resolve(initial[0], defs) {
+            &Rvalue::Constant { value, .. } => Some(value),
+            _ => None,
+        };
+        let iv = InductionVariable { name: name.clone(), subscript: subscript, bits: bits, initial: initial, step: steps[0] };
+
+        ret.push((iv, next));
+    }
+
+    ret
+}
+
+/// Sign extends the `bits` wide `value`.
+fn signed(value: u64, bits: usize) -> i64 {
+    if bits == 0 || bits >= 64 {
+        value as i64
+    } else {
+        let shift = 64 - bits;
+        ((value << shift) as i64) >> shift
+    }
+}
+
+/// Number of iterations of a loop with a single exit guarded by a comparison of an induction
+/// variable.
+fn trip_count(func: &Function, body: &BTreeSet<ControlFlowRef>, ivs: &[(InductionVariable, Vec<Name>)], defs: &HashMap<Name, (ControlFlowRef, &Statement)>)
+              -> Option<u64> {
+    let cfg = func.cfg();
+    let exits = body.iter().flat_map(|&vx| cfg.out_edges(vx)).filter(|&e| !body.contains(&cfg.target(e))).collect::<Vec<_>>();
+
+    if exits.len() != 1 {
+        return None;
+    }
+
+    let (flag, expected) = match cfg.edge_label(exits[0]) {
+        Some(&Guard::Predicate { ref flag, expected }) => (name_of(resolve(flag, defs))?, expected),
+        _ => return None,
+    };
+    // comparison kind: None for equality, otherwise whether it includes equality and is signed
+    let (cmp, a, b) = match defs.get(&flag) {
+        Some(&(_, &Statement { op: Operation::LessUnsigned(ref a, ref b), .. })) => (Some((false, false)), resolve(a, defs), resolve(b, defs)),
+        Some(&(_, &Statement { op: Operation::LessSigned(ref a, ref b), .. })) => (Some((false, true)), resolve(a, defs), resolve(b, defs)),
+        Some(&(_, &Statement { op: Operation::LessOrEqualUnsigned(ref a, ref b), .. })) => (Some((true, false)), resolve(a, defs), resolve(b, defs)),
+        Some(&(_, &Statement { op: Operation::LessOrEqualSigned(ref a, ref b), .. })) => (Some((true, true)), resolve(a, defs), resolve(b, defs)),
+        Some(&(_, &Statement { op: Operation::Equal(ref a, ref b), .. })) => {
+            let (a, b) = (resolve(a, defs), resolve(b, defs));
+
+            // x - c == 0
+            match (b, name_of(a).and_then(|n| defs.get(&n))) {
+                (&Rvalue::Constant { value: 0, .. }, Some(&(_, &Statement { op: Operation::Subtract(ref x, ref c), .. }))) => {
+                    (None, resolve(x, defs), resolve(c, defs))
+                }
+                _ => (None, a, b),
+            }
+        }
+        _ => return None,
+    };
+    // induction variable compared and the number of steps added to its initial value at the
+    // test in the first iteration
+    let find = |rv: &Rvalue| -> Option<(&InductionVariable, u64)> {
+        let n = name_of(rv)?;
+
+        ivs.iter()
+            .filter_map(
+                |&(ref iv, ref next)| if n == (iv.name.clone(), iv.subscript) {
+                    Some((iv, 0))
+                } else if next.contains(&n) {
+                    Some((iv, 1))
+                } else {
+                    None
+                }
+            )
+            .next()
+    };
+    let (iv, off, c, left) = match (find(a), find(b), a, b) {
+        (Some((iv, off)), None, _, &Rvalue::Constant { value, .. }) => (iv, off, value, true),
+        (None, Some((iv, off)), &Rvalue::Constant { value, .. }, _) => (iv, off, value, false),
+        _ => return None,
+    };
+    let signedness = match cmp {
+        Some((_, s)) => s,
+        None => false,
+    };
+    let value = |v: u64| -> Option<i64> {
+        if signedness {
+            Some(signed(v, iv.bits))
+        } else if iv.bits < 64 {
+            Some((v & ((1u64 << iv.bits) - 1)) as i64)
+        } else if v <= i64::max_value() as u64 {
+            Some(v as i64)
+        } else {
+            None
+        }
+    };
+    let (min, max) = match (signedness, iv.bits) {
+        (_, 0) | (_, 64...128) => (if signedness { i64::min_value() } else { 0 }, i64::max_value()),
+        (true, b) => (-(1i64 << (b - 1)), (1i64 << (b - 1)) - 1),
+        (false, b) => (0, (1i64 << b) - 1),
+    };
+    let c = value(c)?;
+    let step = iv.step;
+    let start = value(iv.initial?)?.checked_add(step.checked_mul(off as i64)?)?;
+
+    // comparison result with the induction variable on the left
+    let cond = match (cmp, left) {
+        (Some((false, _)), true) => Condition::Less(c),
+        (Some((true, _)), true) => Condition::Less(c.checked_add(1)?),
+        (Some((false, _)), false) => Condition::Greater(c),
+        (Some((true, _)), false) => Condition::Greater(c.checked_sub(1)?),
+        (None, _) => Condition::Equal(c),
+    };
+    // the exit is taken if the comparison equals `expected`
+    let cont = if expected {
+        match cond {
+            Condition::Less(c) => Condition::Greater(c.checked_sub(1)?),
+            Condition::Greater(c) => Condition::Less(c.checked_add(1)?),
+            Condition::Equal(c) => Condition::NotEqual(c),
+            Condition::NotEqual(c) => Condition::Equal(c),
+        }
+    } else {
+        cond
+    };
+    let n = match cont {
+        Condition::Less(c) if start >= c => 0,
+        Condition::Less(c) if step > 0 => (c.checked_sub(start)? + step - 1) / step,
+        Condition::Greater(c) if start <= c => 0,
+        Condition::Greater(c) if step < 0 => (start.checked_sub(c)? - step - 1) / -step,
+        Condition::Equal(c) => if start == c { 1 } else { 0 },
+        Condition::NotEqual(c) => {
+            let d = c.checked_sub(start)?;
+
+            if d % step == 0 && d / step >= 0 { d / step } else { return None; }
+        }
+        _ => return None,
+    };
+    let last = start.checked_add(n.checked_mul(step)?)?;
+
+    if min <= start && start <= max && min <= last && last <= max { Some(n as u64) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, ControlFlowGraph, Mnemonic, Region};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use ssa_convertion;
+
+    fn var(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn block(start: u64, stmts: Vec<Statement>) -> ControlFlowTarget {
+        let mne = Mnemonic::new(start..start + 1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne]))
+    }
+
+    /*
+     * i = 0; while i < 10 { i += 1 }
+     */
+    #[test]
+    fn while_loop() {
+        let i = var("i", 32);
+        let f = var("f", 1);
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(block(0, vec![Statement { op: Operation::Move(Rvalue::new_u32(0)), assignee: i.clone() }]));
+        let b1 = cfg.add_vertex(block(1, vec![Statement { op: Operation::LessUnsigned(i.clone().into(), Rvalue::new_u32(10)), assignee: f.clone() }]));
+        let b2 = cfg.add_vertex(block(2, vec![Statement { op: Operation::Add(i.clone().into(), Rvalue::new_u32(1)), assignee: i.clone() }]));
+        let b3 = cfg.add_vertex(block(3, vec![]));
+        let g = Guard::from_flag(&f.clone().into()).ok().unwrap();
+
+        cfg.add_edge(Guard::always(), b0, b1);
+        cfg.add_edge(g.clone(), b1, b2);
+        cfg.add_edge(g.negation(), b1, b3);
+        cfg.add_edge(Guard::always(), b2, b1);
+
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), 4), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        assert!(ssa_convertion(&mut func).is_ok());
+
+        let ls = loops(&func);
+
+        assert_eq!(ls.len(), 1);
+        assert_eq!(ls[0].header, b1);
+        assert_eq!(ls[0].body, vec![b1, b2]);
+        assert_eq!(ls[0].induction_variables.len(), 1);
+        assert_eq!(ls[0].induction_variables[0].name, "i");
+        assert_eq!(ls[0].induction_variables[0].initial, Some(0));
+        assert_eq!(ls[0].induction_variables[0].step, 1);
+        assert_eq!(ls[0].trip_count, Some(10));
+    }
+
+    /*
+     * n = 8; do { n -= 1 } while n != 0
+     */
+    #[test]
+    fn count_down() {
+        let n = var("n", 64);
+        let t = var("t", 64);
+        let z = var("z", 1);
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(block(0, vec![Statement { op: Operation::Move(Rvalue::new_u64(8)), assignee: n.clone() }]));
+        let b1 = cfg.add_vertex(
+            block(
+                1,
+                vec![
+                    Statement { op: Operation::Subtract(n.clone().into(), Rvalue::new_u64(1)), assignee: t.clone() },
+                    Statement { op: Operation::Move(t.clone().into()), assignee: n.clone() },
+                ]
+            )
+        );
+        let b2 = cfg.add_vertex(block(2, vec![Statement { op: Operation::Equal(n.clone().into(), Rvalue::new_u64(0)), assignee: z.clone() }]));
+        let b3 = cfg.add_vertex(block(3, vec![]));
+        let g = Guard::from_flag(&z.clone().into()).ok().unwrap();
+
+        cfg.add_edge(Guard::always(), b0, b1);
+        cfg.add_edge(Guard::always(), b1, b2);
+        cfg.add_edge(g.negation(), b2, b1);
+        cfg.add_edge(g, b2, b3);
+
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), 4), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        assert!(ssa_convertion(&mut func).is_ok());
+
+        let ls = loops(&func);
+
+        assert_eq!(ls.len(), 1);
+        assert_eq!(ls[0].body, vec![b1, b2]);
+        assert_eq!(ls[0].induction_variables[0].step, -1);
+        assert_eq!(ls[0].trip_count, Some(7));
+    }
+}
@@ -0,0 +1,487 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Structured control flow.
+//!
+//! [`structure`] turns the control flow graph of a function into a tree of [`Node`]s: basic
+//! blocks, if/else, switches and loops. It doesn't depend on how a consumer prints basic blocks
+//! or conditions, the decompiler and graph front-ends can use it alike.
+//!
+//! Natural loops become `While` loops if the header decides whether to leave the loop,
+//! `DoWhile` loops if the last basic block does and endless `Loop`s left by `Break` otherwise. A
+//! conditional jump becomes an `If` or `Switch` whose arms end at the jump's immediate
+//! post-dominator, where the code following the branch continues. Edges that don't fit, because
+//! they jump into a loop, into an arm of another branch or out of a loop somewhere else than its
+//! follow block, become `Goto`s. Every basic block is part of the tree exactly once and every
+//! jump is either implied by the tree or an explicit `Goto`.
+//!
+//! [`structure`]: fn.structure.html
+//! [`Node`]: enum.Node.html
+
+use loops::loops;
+use panopticon_core::{ControlFlowRef, ControlFlowTarget, Function, Guard};
+use panopticon_graph_algos::{GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Guard of a jump, evaluated after the statements of the basic block it leaves.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Condition {
+    /// Basic block computing the condition
+    pub block: ControlFlowRef,
+    /// Condition
+    pub guard: Guard,
+}
+
+impl Condition {
+    /// Returns the condition being true iff this one is false.
+    pub fn negation(&self) -> Condition {
+        Condition { block: self.block, guard: self.guard.negation() }
+    }
+}
+
+/// Structured control flow.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Node {
+    /// Statements of a basic block. Blocks without outgoing jumps return from the function.
+    Block(ControlFlowRef),
+    /// Two way branch
+    If {
+        /// Decides between the arms
+        condition: Condition,
+        /// Executed if `condition` is true
+        then: Vec<Node>,
+        /// Executed if `condition` is false, may be empty
+        otherwise: Vec<Node>,
+    },
+    /// Multi way branch. The first arm whose guard is true is executed.
+    Switch {
+        /// Basic block computing the guards
+        block: ControlFlowRef,
+        /// Guards and arms
+        cases: Vec<(Guard, Vec<Node>)>,
+    },
+    /// Loop testing its condition at the start. The statements of `condition.block` are executed
+    /// before each test.
+    While {
+        /// Loop continues while this is true
+        condition: Condition,
+        /// Executed if `condition` is true
+        body: Vec<Node>,
+    },
+    /// Loop testing its condition after the body.
+    DoWhile {
+        /// Executed at least once
+        body: Vec<Node>,
+        /// Loop continues while this is true. Computed by the last block of `body`.
+        condition: Condition,
+    },
+    /// Loop only left by `Break`, `Goto` or returning.
+    Loop(Vec<Node>),
+    /// Leaves the innermost loop
+    Break,
+    /// Starts the next iteration of the innermost loop
+    Continue,
+    /// Jump to a basic block elsewhere in the tree, an unresolved jump target or a disassembly
+    /// error
+    Goto(ControlFlowRef),
+}
+
+/// Returns the basic blocks `nodes` jump to using `Goto`.
+pub fn goto_targets(nodes: &[Node]) -> HashSet<ControlFlowRef> {
+    let mut ret = HashSet::new();
+
+    for n in nodes {
+        match n {
+            &Node::Goto(vx) => {
+                ret.insert(vx);
+            }
+            &Node::If { ref then, ref otherwise, .. } => {
+                ret.extend(goto_targets(then));
+                ret.extend(goto_targets(otherwise));
+            }
+            &Node::Switch { ref cases, .. } => {
+                for &(_, ref arm) in cases.iter() {
+                    ret.extend(goto_targets(arm));
+                }
+            }
+            &Node::While { ref body, .. } | &Node::DoWhile { ref body, .. } | &Node::Loop(ref body) => ret.extend(goto_targets(body)),
+            &Node::Block(_) | &Node::Break | &Node::Continue => {}
+        }
+    }
+
+    ret
+}
+
+/// Returns the immediate post-dominator of each basic block, if it has one. Blocks without
+/// outgoing jumps are exits.
+fn post_dominators(func: &Function) -> HashMap<ControlFlowRef, ControlFlowRef> {
+    let cfg = func.cfg();
+    let all = cfg.vertices().collect::<BTreeSet<_>>();
+    let mut pdom = all.iter().map(|&vx| (vx, if cfg.out_degree(vx) == 0 { Some(vx).into_iter().collect() } else { all.clone() })).collect::<HashMap<_, BTreeSet<_>>>();
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+        for &vx in all.iter() {
+            if cfg.out_degree(vx) == 0 {
+                continue;
+            }
+
+            let mut new = cfg.out_edges(vx)
+                .map(|e| pdom[&cfg.target(e)].clone())
+                .fold(None, |acc: Option<BTreeSet<_>>, s| Some(acc.map(|a| a.intersection(&s).cloned().collect()).unwrap_or(s)))
+                .unwrap_or_default();
+
+            new.insert(vx);
+            if new != pdom[&vx] {
+                pdom.insert(vx, new);
+                changed = true;
+            }
+        }
+    }
+
+    // the strict post-dominator post-dominated by all others
+    let mut ret = HashMap::new();
+
+    for (&vx, set) in pdom.iter() {
+        if set.len() == all.len() && all.len() > 1 && cfg.out_degree(vx) > 0 {
+            // never reaches an exit
+            continue;
+        }
+        if let Some(&p) = set.iter().find(|&&p| p != vx && pdom[&p].len() + 1 == set.len()) {
+            ret.insert(vx, p);
+        }
+    }
+
+    ret
+}
+
+struct LoopContext {
+    header: ControlFlowRef,
+    body: BTreeSet<ControlFlowRef>,
+    follow: Option<ControlFlowRef>,
+}
+
+struct Walker<'a> {
+    func: &'a Function,
+    ipdom: HashMap<ControlFlowRef, ControlFlowRef>,
+    loops: HashMap<ControlFlowRef, BTreeSet<ControlFlowRef>>,
+    done: HashSet<ControlFlowRef>,
+}
+
+impl<'a> Walker<'a> {
+    fn is_resolved(&self, vx: ControlFlowRef) -> bool {
+        match self.func.cfg().vertex_label(vx) {
+            Some(&ControlFlowTarget::Resolved(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Structures the code starting at `start` until `stop`. If `enter` is true `start` is the
+    /// header of the loop `lp` and starts its body.
+    fn region(&mut self, start: ControlFlowRef, stop: Option<ControlFlowRef>, lp: Option<&LoopContext>, enter: bool) -> Vec<Node> {
+        let cfg = self.func.cfg();
+        let mut seq = vec![];
+        let mut cur = Some(start);
+        let mut enter = enter;
+
+        while let Some(vx) = cur {
+            if Some(vx) == stop {
+                break;
+            }
+            if let Some(lp) = lp {
+                if vx == lp.header && !enter {
+                    seq.push(Node::Continue);
+                    break;
+                }
+                if Some(vx) == lp.follow {
+                    seq.push(Node::Break);
+                    break;
+                }
+                if !lp.body.contains(&vx) {
+                    seq.push(Node::Goto(vx));
+                    break;
+                }
+            }
+            if !self.is_resolved(vx) || self.done.contains(&vx) {
+                seq.push(Node::Goto(vx));
+                break;
+            }
+            if self.loops.contains_key(&vx) && !enter {
+                let (node, follow) = self.looping(vx);
+
+                seq.push(node);
+                cur = follow;
+                continue;
+            }
+
+            enter = false;
+            self.done.insert(vx);
+            seq.push(Node::Block(vx));
+
+            let edges = cfg.out_edges(vx).collect::<Vec<_>>();
+
+            match edges.len() {
+                0 => break,
+                1 => cur = Some(cfg.target(edges[0])),
+                _ => {
+                    // arms end where the code after the branch continues, unless that is
+                    // outside of the loop
+                    let merge = match (self.ipdom.get(&vx).cloned(), lp) {
+                        (Some(m), Some(lp)) if !lp.body.contains(&m) && Some(m) != lp.follow => None,
+                        (m, _) => m,
+                    };
+
+                    if edges.len() == 2 {
+                        let mut cond = Condition { block: vx, guard: cfg.edge_label(edges[0]).cloned().unwrap_or(Guard::True) };
+                        let mut then = self.region(cfg.target(edges[0]), merge, lp, false);
+                        let mut otherwise = self.region(cfg.target(edges[1]), merge, lp, false);
+
+                        if then.is_empty() {
+                            ::std::mem::swap(&mut then, &mut otherwise);
+                            cond = cond.negation();
+                        }
+                        seq.push(Node::If { condition: cond, then: then, otherwise: otherwise });
+                    } else {
+                        let cases = edges.iter()
+                            .map(|&e| (cfg.edge_label(e).cloned().unwrap_or(Guard::True), self.region(cfg.target(e), merge, lp, false)))
+                            .collect();
+
+                        seq.push(Node::Switch { block: vx, cases: cases });
+                    }
+
+                    cur = merge;
+                }
+            }
+        }
+
+        seq
+    }
+
+    /// Structures the loop with header `header`. Returns the loop and the block following it.
+    fn looping(&mut self, header: ControlFlowRef) -> (Node, Option<ControlFlowRef>) {
+        let cfg = self.func.cfg();
+        let body = self.loops[&header].clone();
+        let mut exits = HashMap::<ControlFlowRef, usize>::new();
+
+        for &vx in body.iter() {
+            for e in cfg.out_edges(vx) {
+                let tgt = cfg.target(e);
+
+                if !body.contains(&tgt) && self.is_resolved(tgt) {
+                    *exits.entry(tgt).or_insert(0) += 1;
+                }
+            }
+        }
+
+        // prefer leaving from the header, then the most used exit
+        let follow = cfg.out_edges(header)
+            .map(|e| cfg.target(e))
+            .find(|t| exits.contains_key(t))
+            .or_else(|| exits.iter().max_by_key(|&(&t, &n)| (n, ::std::cmp::Reverse(t))).map(|(&t, _)| t));
+        let lp = LoopContext { header: header, body: body, follow: follow };
+        let mut nodes = self.region(header, None, Some(&lp), true);
+
+        if nodes.last() == Some(&Node::Continue) {
+            nodes.pop();
+        }
+
+        let node = match (nodes.get(0).cloned(), nodes.get(1).cloned(), nodes.len()) {
+            // header: if (c) { body; continue } break
+            (Some(Node::Block(h)), Some(Node::If { ref condition, ref then, ref otherwise }), 3) if h == header && otherwise.is_empty() &&
+                                                                                                    then.last() == Some(&Node::Continue) &&
+                                                                                                    nodes[2] == Node::Break => {
+                let mut body = then.clone();
+
+                body.pop();
+                Node::While { condition: condition.clone(), body: body }
+            }
+            // header: if (c) { break } body
+            (Some(Node::Block(h)), Some(Node::If { ref condition, ref then, ref otherwise }), _) if h == header && otherwise.is_empty() &&
+                                                                                                    then == &vec![Node::Break] => {
+                Node::While { condition: condition.negation(), body: nodes[2..].to_vec() }
+            }
+            _ => {
+                let n = nodes.len();
+
+                match (n, nodes.get(n.wrapping_sub(2)).cloned(), nodes.last().cloned()) {
+                    // body; if (c) { continue } break
+                    (_, Some(Node::If { ref condition, ref then, ref otherwise }), Some(Node::Break)) if then == &vec![Node::Continue] &&
+                                                                                                       otherwise.is_empty() => {
+                        Node::DoWhile { body: nodes[..n - 2].to_vec(), condition: condition.clone() }
+                    }
+                    // body; if (c) { break }
+                    (_, _, Some(Node::If { ref condition, ref then, ref otherwise })) if then == &vec![Node::Break] && otherwise.is_empty() => {
+                        Node::DoWhile { body: nodes[..n - 1].to_vec(), condition: condition.negation() }
+                    }
+                    _ => Node::Loop(nodes),
+                }
+            }
+        };
+
+        (node, follow)
+    }
+}
+
+/// Recovers structured control flow of `func`. Basic blocks only reachable by `Goto`s are
+/// appended at the end.
+pub fn structure(func: &Function) -> Vec<Node> {
+    let mut walker = Walker {
+        func: func,
+        ipdom: post_dominators(func),
+        loops: loops(func).into_iter().map(|l| (l.header, l.body.into_iter().collect())).collect(),
+        done: HashSet::new(),
+    };
+    let mut ret = walker.region(func.entry_point_ref(), None, None, false);
+
+    loop {
+        let mut todo = goto_targets(&ret).into_iter().filter(|vx| !walker.done.contains(vx) && walker.is_resolved(*vx)).collect::<Vec<_>>();
+
+        if todo.is_empty() {
+            break;
+        }
+
+        todo.sort();
+
+        let tail = walker.region(todo[0], None, None, false);
+        ret.extend(tail);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, ControlFlowGraph, Lvalue, Mnemonic, Region, Rvalue};
+    use panopticon_graph_algos::MutableGraphTrait;
+    use std::borrow::Cow;
+
+    fn flag(name: &'static str) -> Guard {
+        let rv: Rvalue = Lvalue::Variable { name: Cow::Borrowed(name), size: 1, subscript: None }.into();
+        Guard::from_flag(&rv).ok().unwrap()
+    }
+
+    /// Function with one basic block per address and a jump for each edge.
+    fn function(blocks: u64, edges: &[(u64, Guard, u64)]) -> (Function, Vec<ControlFlowRef>) {
+        let mut cfg = ControlFlowGraph::new();
+        let vxs = (0..blocks)
+            .map(
+                |a| {
+                    let mne = Mnemonic::new(a..a + 1, "nop".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+                    cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])))
+                }
+            )
+            .collect::<Vec<_>>();
+
+        for &(from, ref g, to) in edges.iter() {
+            cfg.add_edge(g.clone(), vxs[from as usize], vxs[to as usize]);
+        }
+
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), blocks), None);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vxs[0]);
+        (func, vxs)
+    }
+
+    /*
+     * b0; while (g) { b2; if (h) { b3 } else { b4 } } b5
+     */
+    #[test]
+    fn while_if_else() {
+        let (g, h) = (flag("g"), flag("h"));
+        let (func, b) = function(
+            6,
+            &[
+                (0, Guard::True, 1),
+                (1, g.clone(), 2),
+                (1, g.negation(), 5),
+                (2, h.clone(), 3),
+                (2, h.negation(), 4),
+                (3, Guard::True, 1),
+                (4, Guard::True, 1),
+            ]
+        );
+
+        assert_eq!(
+            structure(&func),
+            vec![
+                Node::Block(b[0]),
+                Node::While {
+                    condition: Condition { block: b[1], guard: g },
+                    body: vec![
+                        Node::Block(b[2]),
+                        Node::If { condition: Condition { block: b[2], guard: h }, then: vec![Node::Block(b[3])], otherwise: vec![Node::Block(b[4])] },
+                    ],
+                },
+                Node::Block(b[5]),
+            ]
+        );
+    }
+
+    /*
+     * b0; do { b1; b2 } while (g); b3
+     */
+    #[test]
+    fn do_while() {
+        let g = flag("g");
+        let (func, b) = function(4, &[(0, Guard::True, 1), (1, Guard::True, 2), (2, g.clone(), 1), (2, g.negation(), 3)]);
+
+        assert_eq!(
+            structure(&func),
+            vec![
+                Node::Block(b[0]),
+                Node::DoWhile { body: vec![Node::Block(b[1]), Node::Block(b[2])], condition: Condition { block: b[2], guard: g } },
+                Node::Block(b[3]),
+            ]
+        );
+    }
+
+    /*
+     * b0; if (g) { b1; if (h) { b2 } } else { goto b2 } b3
+     */
+    #[test]
+    fn goto_fallback() {
+        let (g, h) = (flag("g"), flag("h"));
+        let (func, b) = function(
+            4,
+            &[
+                (0, g.clone(), 1),
+                (0, g.negation(), 2),
+                (1, h.clone(), 2),
+                (1, h.negation(), 3),
+                (2, Guard::True, 3),
+            ]
+        );
+        let nodes = structure(&func);
+
+        assert_eq!(
+            nodes,
+            vec![
+                Node::Block(b[0]),
+                Node::If {
+                    condition: Condition { block: b[0], guard: g },
+                    then: vec![Node::Block(b[1]), Node::If { condition: Condition { block: b[1], guard: h }, then: vec![Node::Block(b[2])], otherwise: vec![] }],
+                    otherwise: vec![Node::Goto(b[2])],
+                },
+                Node::Block(b[3]),
+            ]
+        );
+        assert_eq!(goto_targets(&nodes), vec![b[2]].into_iter().collect());
+    }
+}
@@ -0,0 +1,644 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Constraint based type recovery.
+//!
+//! Every SSA variable and every stack frame slot gets a type variable. Statements add equality
+//! constraints between them, which are solved by unification:
+//!
+//! - Moves and Phi functions give the assignee the type of their operands.
+//! - Arithmetic other than addition and subtraction, as well as extensions, needs integers. Signed
+//!   and unsigned operations decide the signedness.
+//! - Memory accesses need a pointer. The loaded or stored value is the type of the field at the
+//!   offset the address adds to its base variable. Accesses at different offsets from the same
+//!   base make the pointed-to type a struct.
+//! - Accesses relative to the stack pointer at entry are frame slots instead.
+//! - Call targets are code pointers.
+//!
+//! Pointers win over integers when both are required, as address arithmetic uses integer
+//! operations.
+
+use panopticon_core::{Function, Lvalue, Operation, Rvalue, Statement};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// Recovered type.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Type {
+    /// No constraint, `bits` wide.
+    Unknown(usize),
+    /// Result of a comparison
+    Bool,
+    /// Integer. Signedness is None if unknown.
+    Integer {
+        /// Width in bits
+        bits: usize,
+        /// True for signed integers
+        signed: Option<bool>,
+    },
+    /// Pointer to a value. Recursive types point to `Unknown(0)` at the point of recursion.
+    Pointer(Box<Type>),
+    /// Fields accessed by offset
+    Struct(BTreeMap<i64, Type>),
+    /// Pointer to code
+    Code,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Type::Unknown(bits) => write!(f, "u{}?", bits),
+            &Type::Bool => write!(f, "bool"),
+            &Type::Integer { bits, signed: Some(true) } => write!(f, "i{}", bits),
+            &Type::Integer { bits, .. } => write!(f, "u{}", bits),
+            &Type::Pointer(ref ty) => write!(f, "*{}", ty),
+            &Type::Struct(ref fields) => {
+                write!(f, "{{")?;
+                for (i, (off, ty)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", off, ty)?;
+                }
+                write!(f, "}}")
+            }
+            &Type::Code => write!(f, "code*"),
+        }
+    }
+}
+
+/// Types of a function's variables and stack frame.
+#[derive(Clone,Debug,Default)]
+pub struct Types {
+    /// SSA variables by name and subscript
+    pub variables: HashMap<(Cow<'static, str>, Option<usize>), Type>,
+    /// Stack frame slots by offset from the stack pointer at the function's entry
+    pub frame: BTreeMap<i64, Type>,
+}
+
+#[derive(Clone,Debug)]
+enum Shape {
+    Unknown,
+    Bool,
+    Integer(Option<bool>),
+    /// Pointer to a record node
+    Pointer(usize),
+    Code,
+    /// Fields by offset
+    Record(BTreeMap<i64, usize>),
+}
+
+/// Union-find over type variables.
+#[derive(Default)]
+struct Solver {
+    parent: Vec<usize>,
+    shape: Vec<Shape>,
+    bits: Vec<usize>,
+}
+
+impl Solver {
+    fn fresh(&mut self, shape: Shape, bits: usize) -> usize {
+        self.parent.push(self.parent.len());
+        self.shape.push(shape);
+        self.bits.push(bits);
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut n: usize) -> usize {
+        while self.parent[n] != n {
+            let p = self.parent[self.parent[n]];
+            self.parent[n] = p;
+            n = p;
+        }
+        n
+    }
+
+    fn unify(&mut self, a: usize, b: usize) {
+        let mut todo = vec![(a, b)];
+
+        while let Some((a, b)) = todo.pop() {
+            let (a, b) = (self.find(a), self.find(b));
+
+            if a == b {
+                continue;
+            }
+
+            let shape = match (self.shape[a].clone(), self.shape[b].clone()) {
+                (Shape::Unknown, s) | (s, Shape::Unknown) => s,
+                (Shape::Record(mut x), Shape::Record(y)) => {
+                    for (off, n) in y {
+                        match x.get(&off).cloned() {
+                            Some(m) => todo.push((m, n)),
+                            None => {
+                                x.insert(off, n);
+                            }
+                        }
+                    }
+                    Shape::Record(x)
+                }
+                (s @ Shape::Record(_), _) | (_, s @ Shape::Record(_)) => s,
+                (Shape::Pointer(x), Shape::Pointer(y)) => {
+                    todo.push((x, y));
+                    Shape::Pointer(x)
+                }
+                (Shape::Code, _) | (_, Shape::Code) => Shape::Code,
+                (s @ Shape::Pointer(_), _) | (_, s @ Shape::Pointer(_)) => s,
+                (Shape::Integer(x), Shape::Integer(y)) => Shape::Integer(if x == y || y.is_none() { x } else if x.is_none() { y } else { None }),
+                (s @ Shape::Integer(_), Shape::Bool) | (Shape::Bool, s @ Shape::Integer(_)) => s,
+                (Shape::Bool, Shape::Bool) => Shape::Bool,
+            };
+
+            self.parent[b] = a;
+            self.shape[a] = shape;
+            self.bits[a] = ::std::cmp::max(self.bits[a], self.bits[b]);
+        }
+    }
+
+    /// Requires `n` to have at least `shape`.
+    fn constrain(&mut self, n: usize, shape: Shape) {
+        let r = self.find(n);
+        let bits = self.bits[r];
+        let m = self.fresh(shape, bits);
+        self.unify(n, m);
+    }
+
+    /// Returns the record `n` points to, making `n` a pointer if it isn't one.
+    fn pointee(&mut self, n: usize) -> usize {
+        let r = self.find(n);
+
+        if let Shape::Pointer(rec) = self.shape[r] {
+            return rec;
+        }
+
+        let rec = self.fresh(Shape::Record(BTreeMap::new()), 0);
+        self.constrain(n, Shape::Pointer(rec));
+
+        let r = self.find(n);
+
+        match self.shape[r] {
+            Shape::Pointer(rec) => rec,
+            _ => rec,
+        }
+    }
+
+    /// Returns the node of the field at `offset` inside record `rec`.
+    fn field(&mut self, rec: usize, offset: i64, bits: usize) -> usize {
+        let r = self.find(rec);
+
+        if let Shape::Record(ref fields) = self.shape[r] {
+            if let Some(&n) = fields.get(&offset) {
+                return n;
+            }
+        }
+
+        let n = self.fresh(Shape::Unknown, bits);
+        if let Shape::Record(ref mut fields) = self.shape[r] {
+            fields.insert(offset, n);
+        }
+        n
+    }
+
+    fn resolve(&mut self, n: usize, visiting: &mut HashSet<usize>) -> Type {
+        let r = self.find(n);
+        let bits = self.bits[r];
+
+        match self.shape[r].clone() {
+            Shape::Unknown => Type::Unknown(bits),
+            Shape::Bool => Type::Bool,
+            Shape::Integer(signed) => Type::Integer { bits: bits, signed: signed },
+            Shape::Code => Type::Code,
+            Shape::Pointer(rec) => {
+                let rec = self.find(rec);
+
+                if !visiting.insert(rec) {
+                    return Type::Pointer(Box::new(Type::Unknown(0)));
+                }
+
+                let ty = match self.shape[rec].clone() {
+                    Shape::Record(ref fields) if fields.len() == 1 && fields.contains_key(&0) => self.resolve(fields[&0], visiting),
+                    Shape::Record(ref fields) if fields.is_empty() => Type::Unknown(0),
+                    Shape::Record(fields) => Type::Struct(fields.into_iter().map(|(off, n)| (off, self.resolve(n, visiting))).collect()),
+                    _ => self.resolve(rec, visiting),
+                };
+
+                visiting.remove(&rec);
+                Type::Pointer(Box::new(ty))
+            }
+            Shape::Record(fields) => Type::Struct(fields.into_iter().map(|(off, n)| (off, self.resolve(n, visiting))).collect()),
+        }
+    }
+}
+
+/// Address of a memory access relative to something known.
+#[derive(Clone,Debug,PartialEq)]
+enum Base {
+    /// Stack pointer at entry
+    Frame,
+    /// SSA variable
+    Variable(Cow<'static, str>, Option<usize>),
+}
+
+struct Context<'a> {
+    solver: Solver,
+    variables: HashMap<(Cow<'static, str>, Option<usize>), usize>,
+    frame: BTreeMap<i64, usize>,
+    /// Base and offset of every variable defined by adding a constant
+    offsets: HashMap<(Cow<'static, str>, Option<usize>), (Base, i64)>,
+    stack_pointer: Option<&'a str>,
+}
+
+impl<'a> Context<'a> {
+    fn variable(&mut self, name: &Cow<'static, str>, subscript: Option<usize>, bits: usize) -> usize {
+        let key = (name.clone(), subscript);
+
+        match self.variables.get(&key) {
+            Some(&n) => n,
+            None => {
+                let n = self.solver.fresh(Shape::Unknown, bits);
+                self.variables.insert(key, n);
+                n
+            }
+        }
+    }
+
+    /// Type variable of `rv`. Constants get a fresh one.
+    fn value(&mut self, rv: &Rvalue) -> usize {
+        match rv {
+            &Rvalue::Variable { ref name, subscript, size, .. } => self.variable(name, subscript, size),
+            &Rvalue::Constant { size, .. } => self.solver.fresh(Shape::Unknown, size),
+            &Rvalue::Undefined => self.solver.fresh(Shape::Unknown, 0),
+        }
+    }
+
+    /// Base and offset of the address `rv`.
+    fn address(&self, rv: &Rvalue) -> Option<(Base, i64)> {
+        match rv {
+            &Rvalue::Variable { ref name, subscript, offset: 0, .. } => {
+                match self.offsets.get(&(name.clone(), subscript)) {
+                    Some(x) => Some(x.clone()),
+                    None if subscript.is_none() && Some(name.as_ref()) == self.stack_pointer => Some((Base::Frame, 0)),
+                    None => Some((Base::Variable(name.clone(), subscript), 0)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Records base and offset of variables computed by adding constants.
+    fn offset(&mut self, stmt: &Statement) -> bool {
+        let (name, subscript) = match stmt.assignee {
+            Lvalue::Variable { ref name, subscript, .. } => (name, subscript),
+            Lvalue::Undefined => return false,
+        };
+        let new = match stmt.op {
+            Operation::Add(ref a, Rvalue::Constant { value, size }) | Operation::Add(Rvalue::Constant { value, size }, ref a) => {
+                self.address(a).map(|(b, o)| (b, o.wrapping_add(sign_extend(value, size))))
+            }
+            Operation::Subtract(ref a, Rvalue::Constant { value, size }) => self.address(a).map(|(b, o)| (b, o.wrapping_sub(sign_extend(value, size)))),
+            Operation::Move(ref a) => self.address(a),
+            _ => None,
+        };
+
+        match new {
+            Some(new) => {
+                let key = (name.clone(), subscript);
+                let changed = self.offsets.get(&key) != Some(&new);
+                self.offsets.insert(key, new);
+                changed
+            }
+            None => false,
+        }
+    }
+
+    /// Type variable of the memory at `addr`, `bits` wide.
+    fn memory(&mut self, addr: &Rvalue, bits: usize) -> Option<usize> {
+        match self.address(addr) {
+            Some((Base::Frame, off)) => {
+                match self.frame.get(&off) {
+                    Some(&n) => Some(n),
+                    None => {
+                        let n = self.solver.fresh(Shape::Unknown, bits);
+                        self.frame.insert(off, n);
+                        Some(n)
+                    }
+                }
+            }
+            Some((Base::Variable(name, subscript), off)) => {
+                let size = addr.size().unwrap_or(0);
+                let base = self.variable(&name, subscript, size);
+                let rec = self.solver.pointee(base);
+                Some(self.solver.field(rec, off, bits))
+            }
+            None => None,
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        let (assignee, bits) = match stmt.assignee {
+            Lvalue::Variable { ref name, subscript, size } => (Some(self.variable(name, subscript, size)), size),
+            Lvalue::Undefined => (None, 0),
+        };
+        let integer = |ctx: &mut Context, ops: &[&Rvalue], signed: Option<bool>| for rv in ops {
+            let n = ctx.value(rv);
+            ctx.solver.constrain(n, Shape::Integer(signed));
+        };
+
+        match stmt.op {
+            Operation::Move(ref a) => {
+                let n = self.value(a);
+                if let Some(x) = assignee {
+                    self.solver.unify(x, n);
+                }
+            }
+            Operation::Phi(ref ops) => {
+                for a in ops.iter() {
+                    let n = self.value(a);
+                    if let Some(x) = assignee {
+                        self.solver.unify(x, n);
+                    }
+                }
+            }
+            Operation::Add(ref a, ref b) | Operation::Subtract(ref a, ref b) => {
+                let (na, nb) = (self.value(a), self.value(b));
+
+                // Adding a constant to a pointer yields a pointer into the same object, which
+                // `offset` tracks. Anything else keeps its type.
+                let (sa, sb) = (self.solver.find(na), self.solver.find(nb));
+                let constant = if let &Rvalue::Constant { .. } = b { true } else { false };
+
+                match (assignee, self.is_pointer(sa), self.is_pointer(sb)) {
+                    (Some(x), false, _) if constant => self.solver.unify(x, na),
+                    (Some(x), false, false) if !constant => self.solver.constrain(x, Shape::Integer(None)),
+                    _ => {}
+                }
+            }
+            Operation::Multiply(ref a, ref b) |
+            Operation::ShiftLeft(ref a, ref b) |
+            Operation::Modulo(ref a, ref b) |
+            Operation::InclusiveOr(ref a, ref b) |
+            Operation::ExclusiveOr(ref a, ref b) => {
+                integer(self, &[a, b], None);
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Integer(None));
+                }
+            }
+            Operation::DivideUnsigned(ref a, ref b) | Operation::ShiftRightUnsigned(ref a, ref b) => {
+                integer(self, &[a, b], Some(false));
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Integer(Some(false)));
+                }
+            }
+            Operation::DivideSigned(ref a, ref b) | Operation::ShiftRightSigned(ref a, ref b) => {
+                integer(self, &[a, b], Some(true));
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Integer(Some(true)));
+                }
+            }
+            Operation::And(_, _) => {}
+            Operation::Equal(ref a, ref b) => {
+                let (na, nb) = (self.value(a), self.value(b));
+                if let (&Rvalue::Variable { .. }, &Rvalue::Variable { .. }) = (a, b) {
+                    self.solver.unify(na, nb);
+                }
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Bool);
+                }
+            }
+            Operation::LessUnsigned(ref a, ref b) | Operation::LessOrEqualUnsigned(ref a, ref b) => {
+                if let (&Rvalue::Variable { .. }, &Rvalue::Variable { .. }) = (a, b) {
+                    let (na, nb) = (self.value(a), self.value(b));
+                    self.solver.unify(na, nb);
+                }
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Bool);
+                }
+            }
+            Operation::LessSigned(ref a, ref b) | Operation::LessOrEqualSigned(ref a, ref b) => {
+                integer(self, &[a, b], Some(true));
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Bool);
+                }
+            }
+            Operation::ZeroExtend(_, ref a) => {
+                integer(self, &[a], Some(false));
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Integer(None));
+                }
+            }
+            Operation::SignExtend(_, ref a) => {
+                integer(self, &[a], Some(true));
+                if let Some(x) = assignee {
+                    self.solver.constrain(x, Shape::Integer(Some(true)));
+                }
+            }
+            Operation::Load(_, _, size, ref addr) => {
+                if let Some(m) = self.memory(addr, size) {
+                    if let Some(x) = assignee {
+                        self.solver.unify(x, m);
+                    }
+                }
+            }
+            Operation::Store(_, _, size, ref addr, ref val) => {
+                if let Some(m) = self.memory(addr, size) {
+                    if let &Rvalue::Variable { .. } = val {
+                        let n = self.value(val);
+                        self.solver.unify(m, n);
+                    }
+                }
+            }
+            Operation::Call(ref target @ Rvalue::Variable { .. }) => {
+                let n = self.value(target);
+                self.solver.constrain(n, Shape::Code);
+            }
+            Operation::Call(_) | Operation::Select(..) | Operation::Initialize(..) => {}
+        }
+
+        if let Some(x) = assignee {
+            let r = self.solver.find(x);
+            self.solver.bits[r] = ::std::cmp::max(self.solver.bits[r], bits);
+        }
+    }
+
+    fn is_pointer(&self, rep: usize) -> bool {
+        match self.solver.shape[rep] {
+            Shape::Pointer(_) | Shape::Code => true,
+            _ => false,
+        }
+    }
+}
+
+fn sign_extend(value: u64, size: usize) -> i64 {
+    if size == 0 || size >= 64 {
+        value as i64
+    } else {
+        let shift = 64 - size;
+        ((value << shift) as i64) >> shift
+    }
+}
+
+/// Infers types for all SSA variables and stack frame slots of `func`. Works best on functions in
+/// SSA form, outside SSA form all assignments to a variable share one type. The stack pointer is
+/// taken from the function's calling convention, if known.
+pub fn infer_types(func: &Function) -> Types {
+    let stack_pointer = func.calling_convention().map(|cc| cc.stack_pointer()).or_else(
+        || {
+            ["RSP", "ESP", "SP"].iter().cloned().find(
+                |sp| {
+                    func.statements().any(|s| s.op.operands().iter().any(|rv| if let &&Rvalue::Variable { ref name, .. } = rv { name == sp } else { false }))
+                }
+            )
+        }
+    );
+    let mut ctx = Context {
+        solver: Solver::default(),
+        variables: HashMap::new(),
+        frame: BTreeMap::new(),
+        offsets: HashMap::new(),
+        stack_pointer: stack_pointer,
+    };
+    let stmts = func.statements().collect::<Vec<_>>();
+
+    // Stack and field offsets flow through Phi-free chains of additions. Iterate until they
+    // stop changing, as basic blocks aren't in any particular order.
+    for _ in 0..stmts.len() + 1 {
+        let mut changed = false;
+        for stmt in stmts.iter() {
+            changed |= ctx.offset(stmt);
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Pointer constraints first, so that address arithmetic sees them.
+    for stmt in stmts.iter() {
+        if let Operation::Load(..) = stmt.op {
+            ctx.statement(stmt);
+        } else if let Operation::Store(..) = stmt.op {
+            ctx.statement(stmt);
+        }
+    }
+    for stmt in stmts.iter() {
+        match stmt.op {
+            Operation::Load(..) | Operation::Store(..) => {}
+            _ => ctx.statement(stmt),
+        }
+    }
+
+    let mut ret = Types::default();
+    let variables = ctx.variables.clone();
+    let frame = ctx.frame.clone();
+
+    for (key, n) in variables {
+        let ty = ctx.solver.resolve(n, &mut HashSet::new());
+        ret.variables.insert(key, ty);
+    }
+    for (off, n) in frame {
+        let ty = ctx.solver.resolve(n, &mut HashSet::new());
+        ret.frame.insert(off, ty);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::{BasicBlock, ControlFlowGraph, ControlFlowTarget, Endianess, Mnemonic, Region};
+    use panopticon_graph_algos::MutableGraphTrait;
+
+    fn var(name: &'static str, size: usize) -> Lvalue {
+        Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+    }
+
+    fn function(stmts: Vec<Statement>) -> Function {
+        let mne = Mnemonic::new(0..1, "test".to_string(), "".to_string(), vec![].iter(), stmts.iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let vx = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let mut func = Function::undefined(0, None, &Region::undefined("RAM".to_owned(), 1), None);
+
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(vx);
+        func
+    }
+
+    fn load(addr: &Lvalue, bits: usize, to: &Lvalue) -> Statement {
+        Statement { op: Operation::Load(Cow::Borrowed("RAM"), Endianess::Little, bits, addr.clone().into()), assignee: to.clone() }
+    }
+
+    fn ty(types: &Types, name: &'static str) -> Type {
+        types.variables[&(Cow::Borrowed(name), None)].clone()
+    }
+
+    /*
+     * a = p->x; b = p->y; c = a / b (signed)
+     */
+    #[test]
+    fn struct_fields() {
+        let p = var("p", 64);
+        let q = var("q", 64);
+        let a = var("a", 32);
+        let b = var("b", 32);
+        let c = var("c", 32);
+        let func = function(
+            vec![
+                load(&p, 32, &a),
+                Statement { op: Operation::Add(p.clone().into(), Rvalue::new_u64(8)), assignee: q.clone() },
+                load(&q, 32, &b),
+                Statement { op: Operation::DivideSigned(a.clone().into(), b.clone().into()), assignee: c.clone() },
+            ]
+        );
+        let types = infer_types(&func);
+        let int = Type::Integer { bits: 32, signed: Some(true) };
+        let mut fields = BTreeMap::new();
+
+        fields.insert(0, int.clone());
+        fields.insert(8, int.clone());
+
+        assert_eq!(ty(&types, "p"), Type::Pointer(Box::new(Type::Struct(fields))));
+        assert_eq!(ty(&types, "c"), int);
+    }
+
+    /*
+     * n = *(rsp - 8); n = n->next; call *(rsp)
+     */
+    #[test]
+    fn frame_slots() {
+        let rsp = var("RSP", 64);
+        let t = var("t", 64);
+        let n = var("n", 64);
+        let f = var("f", 64);
+        let next = var("next", 64);
+        let func = function(
+            vec![
+                Statement { op: Operation::Subtract(rsp.clone().into(), Rvalue::new_u64(8)), assignee: t.clone() },
+                load(&t, 64, &n),
+                load(&n, 64, &next),
+                Statement { op: Operation::Move(next.clone().into()), assignee: n.clone() },
+                load(&rsp, 64, &f),
+                Statement { op: Operation::Call(f.clone().into()), assignee: Lvalue::Undefined },
+            ]
+        );
+        let types = infer_types(&func);
+
+        assert_eq!(types.frame[&-8], Type::Pointer(Box::new(Type::Pointer(Box::new(Type::Unknown(0))))));
+        assert_eq!(types.frame[&0], Type::Code);
+        assert_eq!(ty(&types, "f"), Type::Code);
+    }
+}
@@ -0,0 +1,788 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Table-driven decoder generation.
+//!
+//! Turns machine-readable instruction set descriptions into token patterns for the
+//! `new_disassembler!` macro of `panopticon-core`. The generator is meant to be run from the
+//! `build.rs` of an architecture crate. The resulting file contains a single
+//! `new_disassembler!` invocation that is pulled into the decoder with `include!`.
+//!
+//! ```ignore
+//! // build.rs
+//! extern crate panopticon_isa_gen;
+//!
+//! fn main() {
+//!     let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("rv32i.rs");
+//!     panopticon_isa_gen::generate("isa/rv32i.toml".as_ref(), &out).unwrap();
+//!     println!("cargo:rerun-if-changed=isa");
+//! }
+//!
+//! // src/disassembler.rs
+//! let main = include!(concat!(env!("OUT_DIR"), "/rv32i.rs"));
+//! ```
+//!
+//! Specifications are TOML files. The top level names the `Architecture` implementation, the
+//! token size in bits, the decoded fields of the instruction words and a template for the
+//! semantic function called for each instruction. In the template `{}` is replaced with the
+//! instruction name, with all characters not allowed in Rust identifiers replaced by `_`.
+//!
+//! ```toml
+//! arch = "Riscv"
+//! token = 16
+//! width = 32
+//! action = "semantic::{}"
+//! default = "semantic::illegal"
+//!
+//! [fields]
+//! rd = "11..7"
+//! rs1 = "19..15"
+//! imm12 = "31..20"
+//! # Fields can be split over multiple bit ranges, most significant part first.
+//! bimm = ["31", "7", "30..25", "11..8"]
+//!
+//! [[instruction]]
+//! name = "addi"
+//! encoding = "rd rs1 imm12 14..12=0 6..2=0x04 1..0=3"
+//! ```
+//!
+//! Encodings use the syntax of the [riscv-opcodes](https://github.com/riscv/riscv-opcodes)
+//! files: a list of field names and fixed bit ranges of the form `hi..lo=value` or `bit=value`.
+//! A value of `ignore` leaves the range unconstrained. Bits neither covered by a field nor by a
+//! fixed range match anything. Instructions may override `width` and `action`.
+//!
+//! Complete riscv-opcodes files can be included with the `opcodes` key. Their paths are relative
+//! to the specification. Lines starting with `$` (pseudo instructions and imports) are skipped,
+//! pseudo instructions should be listed explicitly if wanted. The RISC-V field names are
+//! predefined and can be overridden in `[fields]`.
+//!
+//! ```toml
+//! opcodes = ["riscv-opcodes/rv_i", "riscv-opcodes/rv_m"]
+//! ```
+//!
+//! Instructions wider than a token are split into multiple tokens in memory order. Words are
+//! assumed to be little endian unless `big-endian = true` is set.
+//!
+//! The disassembler picks the first matching pattern, but patterns generated from the same
+//! specification must not be identical. Two instructions with the same fixed bits are rejected.
+//! The generated code expects `State` to be in scope at the `include!` site.
+
+#![allow(missing_docs)]
+
+extern crate toml;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+use std::cmp;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::result;
+
+/// Generator error.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Error {
+    fn from(s: String) -> Error {
+        Error(s)
+    }
+}
+
+impl<'a> From<&'a str> for Error {
+    fn from(s: &'a str) -> Error {
+        Error(s.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error(format!("I/O error: {}", e))
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Error {
+        Error(format!("malformed specification: {}", e))
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// Inclusive range of bits `hi..lo` in an instruction word.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct Range {
+    pub hi: usize,
+    pub lo: usize,
+}
+
+impl Range {
+    /// Parses `hi..lo` or a single bit index.
+    pub fn parse(s: &str) -> Result<Range> {
+        let bit = |s: &str| s.trim().parse::<usize>().map_err(|_| Error(format!("invalid bit index '{}'", s)));
+        let mut it = s.splitn(2, "..");
+        let hi = bit(it.next().unwrap())?;
+        let lo = match it.next() {
+            Some(lo) => bit(lo)?,
+            None => hi,
+        };
+
+        if lo > hi {
+            return Err(format!("bit range '{}' is not most significant bit first", s).into());
+        }
+
+        Ok(Range { hi: hi, lo: lo })
+    }
+
+    pub fn len(&self) -> usize {
+        self.hi - self.lo + 1
+    }
+}
+
+/// Single instruction of a specification.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Instruction {
+    pub name: String,
+    /// Size in bits
+    pub width: usize,
+    /// Fields decoded into capture groups
+    pub fields: Vec<String>,
+    /// Fixed bit ranges. `None` for ranges that are explicitly ignored.
+    pub fixed: Vec<(Range, Option<u64>)>,
+    /// Semantic function, overrides the specification wide template
+    pub action: Option<String>,
+}
+
+impl Instruction {
+    /// Parses an encoding in riscv-opcodes syntax: field names and `hi..lo=value` pairs
+    /// separated by white space.
+    pub fn new(name: &str, width: usize, encoding: &str) -> Result<Instruction> {
+        let mut ret = Instruction {
+            name: name.to_string(),
+            width: width,
+            fields: vec![],
+            fixed: vec![],
+            action: None,
+        };
+
+        for arg in encoding.split_whitespace() {
+            if let Some(p) = arg.find('=') {
+                let rgn = Range::parse(&arg[..p])?;
+                let val = parse_value(&arg[p + 1..]).map_err(|e| Error(format!("{}: {}", name, e)))?;
+
+                if rgn.hi >= width {
+                    return Err(format!("{}: bit range '{}' exceeds the {} bit instruction", name, arg, width).into());
+                }
+                if let Some(v) = val {
+                    if rgn.len() < 64 && v >> rgn.len() != 0 {
+                        return Err(format!("{}: value of '{}' does not fit its range", name, arg).into());
+                    }
+                }
+                ret.fixed.push((rgn, val));
+            } else {
+                ret.fields.push(arg.to_string());
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Semantic function name generated from `template`.
+    pub fn action(&self, template: &str) -> String {
+        match self.action {
+            Some(ref a) => a.clone(),
+            None => template.replace("{}", &identifier(&self.name)),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Bit {
+    Any,
+    Fixed(bool),
+    Field(usize),
+}
+
+struct Layout {
+    bits: Vec<Bit>,
+    /// Capture group names
+    groups: Vec<String>,
+    /// Fields captured piecewise. Groups and their lengths, most significant first.
+    joins: Vec<(String, Vec<(usize, usize)>)>,
+}
+
+#[derive(Deserialize,Debug)]
+#[serde(untagged)]
+enum FieldDef {
+    Single(String),
+    Split(Vec<String>),
+}
+
+#[derive(Deserialize,Debug)]
+struct InstructionDef {
+    name: String,
+    encoding: String,
+    width: Option<usize>,
+    action: Option<String>,
+}
+
+#[derive(Deserialize,Debug)]
+#[serde(rename_all = "kebab-case")]
+struct SpecDef {
+    arch: String,
+    token: usize,
+    width: Option<usize>,
+    action: Option<String>,
+    default: Option<String>,
+    #[serde(default)]
+    big_endian: bool,
+    #[serde(default)]
+    fields: HashMap<String, FieldDef>,
+    #[serde(default)]
+    opcodes: Vec<String>,
+    #[serde(default)]
+    instruction: Vec<InstructionDef>,
+}
+
+/// Instruction set specification.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Spec {
+    /// Type implementing `Architecture`
+    pub arch: String,
+    /// Token size in bits
+    pub token: usize,
+    /// Default instruction size in bits
+    pub width: usize,
+    pub big_endian: bool,
+    /// Semantic function template. `{}` is replaced by the instruction name.
+    pub action: String,
+    /// Function called if no pattern matches
+    pub default: Option<String>,
+    /// Bit ranges of all fields, most significant part first
+    pub fields: HashMap<String, Vec<Range>>,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Spec {
+    /// New, empty specification with the RISC-V fields predefined.
+    pub fn new(arch: &str, token: usize) -> Spec {
+        let fields = RISCV_FIELDS
+            .iter()
+            .map(|&(n, r)| (n.to_string(), r.split(',').map(|r| Range::parse(r).unwrap()).collect()))
+            .collect();
+
+        Spec {
+            arch: arch.to_string(),
+            token: token,
+            width: token,
+            big_endian: false,
+            action: "{}".to_string(),
+            default: None,
+            fields: fields,
+            instructions: vec![],
+        }
+    }
+
+    /// Parses a TOML specification. Files listed in `opcodes` are resolved relative to `base`.
+    pub fn from_toml(s: &str, base: &Path) -> Result<Spec> {
+        let def: SpecDef = toml::from_str(s)?;
+
+        if def.token == 0 || def.token % 8 != 0 || def.token > 64 {
+            return Err(format!("invalid token size {}", def.token).into());
+        }
+
+        let mut ret = Spec::new(&def.arch, def.token);
+
+        ret.width = def.width.unwrap_or(def.token);
+        ret.big_endian = def.big_endian;
+        ret.default = def.default;
+        if let Some(a) = def.action {
+            ret.action = a;
+        }
+
+        for (name, f) in def.fields.into_iter() {
+            let rgns = match f {
+                FieldDef::Single(r) => vec![r],
+                FieldDef::Split(v) => v,
+            };
+            let rgns = rgns.iter().map(|r| Range::parse(r)).collect::<Result<Vec<_>>>()?;
+
+            if rgns.is_empty() {
+                return Err(format!("field '{}' has no bits", name).into());
+            }
+            ret.fields.insert(name, rgns);
+        }
+
+        for path in def.opcodes.iter() {
+            let mut fd = File::open(base.join(path))?;
+            let mut buf = String::new();
+
+            fd.read_to_string(&mut buf)?;
+            ret.add_riscv_opcodes(&buf)?;
+        }
+
+        for i in def.instruction.into_iter() {
+            let mut insn = Instruction::new(&i.name, i.width.unwrap_or(ret.width), &i.encoding)?;
+
+            insn.action = i.action;
+            ret.instructions.push(insn);
+        }
+
+        Ok(ret)
+    }
+
+    /// Adds all instructions from a file in riscv-opcodes format. Instruction that don't end in
+    /// `11` are 16 bit compressed instructions, all others 32 bits wide.
+    pub fn add_riscv_opcodes(&mut self, s: &str) -> Result<()> {
+        for line in s.lines() {
+            let line = match line.find('#') {
+                Some(p) => &line[..p],
+                None => line,
+            };
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('$') {
+                continue;
+            }
+
+            let mut it = line.splitn(2, char::is_whitespace);
+            let name = it.next().unwrap();
+            let enc = it.next().unwrap_or("");
+            let compressed = enc.split_whitespace().any(|a| a.starts_with("1..0=") && a != "1..0=3");
+            let insn = Instruction::new(name, if compressed { 16 } else { 32 }, enc)?;
+
+            self.instructions.push(insn);
+        }
+
+        Ok(())
+    }
+
+    // Memory position of the token containing bit `i` of a `width` bit instruction.
+    fn token_index(&self, i: usize, width: usize) -> usize {
+        let t = i / self.token;
+
+        if self.big_endian { width / self.token - 1 - t } else { t }
+    }
+
+    // Assigns each bit of `insn` to a fixed value or a capture group. The disassembler
+    // concatenates the bits of a capture group in memory order. Fields whose bits are in a
+    // different order are captured piecewise and joined by the generated action.
+    fn layout(&self, insn: &Instruction) -> Result<Layout> {
+        let mut ret = Layout { bits: vec![Bit::Any; insn.width], groups: vec![], joins: vec![] };
+        let width = insn.width;
+        let set = |bits: &mut Vec<Bit>, i: usize, b: Bit| -> Result<()> {
+            if i >= width {
+                return Err(format!("{}: bit {} is outside of the instruction", insn.name, i).into());
+            }
+            if bits[i] != Bit::Any {
+                return Err(format!("{}: bit {} is used twice", insn.name, i).into());
+            }
+            bits[i] = b;
+            Ok(())
+        };
+
+        for f in insn.fields.iter() {
+            let rgns = self.fields.get(f).ok_or_else(|| Error(format!("{}: unknown field '{}'", insn.name, f)))?;
+
+            if !is_group_name(f) {
+                return Err(format!("{}: field '{}' is not a valid capture group name", insn.name, f).into());
+            }
+
+            let declared = rgns.iter().flat_map(|r| (r.lo..r.hi + 1).rev()).collect::<Vec<_>>();
+            let mut captured = declared.clone();
+
+            captured.sort_by_key(|&i| (self.token_index(i, width), width - i));
+
+            if captured == declared {
+                let g = ret.groups.len();
+
+                ret.groups.push(f.clone());
+                for i in declared.into_iter() {
+                    set(&mut ret.bits, i, Bit::Field(g))?;
+                }
+            } else {
+                let mut pieces = vec![];
+
+                for r in rgns.iter() {
+                    let mut hi = r.hi;
+
+                    loop {
+                        let lo = cmp::max(r.lo, hi - hi % self.token);
+                        let g = ret.groups.len();
+
+                        ret.groups.push(format!("{}_{}", f, pieces.len()));
+                        pieces.push((g, hi - lo + 1));
+                        for i in lo..hi + 1 {
+                            set(&mut ret.bits, i, Bit::Field(g))?;
+                        }
+
+                        if lo == r.lo {
+                            break;
+                        }
+                        hi = lo - 1;
+                    }
+                }
+
+                ret.joins.push((f.clone(), pieces));
+            }
+        }
+
+        for &(r, v) in insn.fixed.iter() {
+            if let Some(v) = v {
+                for i in r.lo..r.hi + 1 {
+                    set(&mut ret.bits, i, Bit::Fixed((v >> (i - r.lo)) & 1 == 1))?;
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Token patterns of `insn` in memory order.
+    pub fn patterns(&self, insn: &Instruction) -> Result<Vec<String>> {
+        if insn.width == 0 || insn.width % self.token != 0 {
+            return Err(format!("{}: width {} is not a multiple of the token size", insn.name, insn.width).into());
+        }
+
+        let layout = self.layout(insn)?;
+        let tokens = insn.width / self.token;
+        let mut ret = Vec::with_capacity(tokens);
+
+        for t in 0..tokens {
+            let t = if self.big_endian { tokens - 1 - t } else { t };
+            let mut pat = String::new();
+            let mut prev = None;
+
+            for i in (t * self.token..(t + 1) * self.token).rev() {
+                let b = layout.bits[i];
+
+                match b {
+                    Bit::Field(g) => {
+                        if prev != Some(b) {
+                            if !pat.is_empty() {
+                                pat.push(' ');
+                            }
+                            pat.push_str(&layout.groups[g]);
+                            pat.push('@');
+                        }
+                        pat.push('.');
+                    }
+                    Bit::Fixed(true) | Bit::Fixed(false) | Bit::Any => {
+                        if let Some(Bit::Field(_)) = prev {
+                            pat.push(' ');
+                        }
+                        pat.push(
+                            match b {
+                                Bit::Fixed(true) => '1',
+                                Bit::Fixed(false) => '0',
+                                _ => '.',
+                            }
+                        );
+                    }
+                }
+                prev = Some(b);
+            }
+
+            ret.push(pat);
+        }
+
+        Ok(ret)
+    }
+
+    // Semantic action of `insn`, wrapped in a closure reassembling piecewise captured fields.
+    fn action(&self, insn: &Instruction, layout: &Layout) -> String {
+        let act = insn.action(&self.action);
+
+        if layout.joins.is_empty() {
+            return act;
+        }
+
+        let mut ret = format!("|st: &mut State<{}>| {{\n", self.arch);
+
+        for &(ref f, ref pieces) in layout.joins.iter() {
+            let mut shift = pieces.iter().map(|&(_, len)| len).sum::<usize>();
+            let mut val = vec![];
+
+            for &(g, len) in pieces.iter() {
+                shift -= len;
+                if shift > 0 {
+                    val.push(format!("(st.get_group({:?}) << {})", layout.groups[g], shift));
+                } else {
+                    val.push(format!("st.get_group({:?})", layout.groups[g]));
+                }
+            }
+            ret.push_str(&format!("        let v = {};\n", val.join(" | ")));
+            ret.push_str(&format!("        st.groups.push(({:?}.to_string(), v));\n", f));
+        }
+        ret.push_str(&format!("        ({})(st)\n    }}", act));
+        ret
+    }
+
+    /// Generates the `new_disassembler!` invocation for all instructions.
+    pub fn generate(&self) -> Result<String> {
+        let mut seen = HashMap::<Vec<Option<bool>>, &str>::new();
+        let mut rules = vec![];
+
+        for insn in self.instructions.iter() {
+            let pats = self.patterns(insn)?;
+            let layout = self.layout(insn)?;
+            let fixed = layout
+                .bits
+                .iter()
+                .map(
+                    |b| match *b {
+                        Bit::Fixed(b) => Some(b),
+                        _ => None,
+                    }
+                )
+                .collect::<Vec<_>>();
+
+            if let Some(other) = seen.insert(fixed, &insn.name) {
+                return Err(format!("{} and {} have the same encoding", other, insn.name).into());
+            }
+
+            let pats = pats.iter().map(|p| format!("{:?}", p)).collect::<Vec<_>>();
+            rules.push(format!("    [ {} ] = {}", pats.join(", "), self.action(insn, &layout)));
+        }
+
+        if rules.is_empty() {
+            return Err("specification contains no instructions".into());
+        }
+
+        let mut ret = format!("// Generated by panopticon-isa-gen, do not edit.\nnew_disassembler!({} =>\n", self.arch);
+
+        ret.push_str(&rules.join(",\n"));
+        if let Some(ref def) = self.default {
+            ret.push_str(&format!(",\n    _ = {}", def));
+        }
+        ret.push_str("\n)\n");
+
+        Ok(ret)
+    }
+}
+
+/// Reads the specification at `input` and writes the generated table to `output`.
+pub fn generate(input: &Path, output: &Path) -> Result<()> {
+    let mut buf = String::new();
+
+    File::open(input)?.read_to_string(&mut buf)?;
+
+    let base = input.parent().unwrap_or(Path::new("."));
+    let spec = Spec::from_toml(&buf, base).map_err(|e| Error(format!("{}: {}", input.display(), e)))?;
+    let code = spec.generate()?;
+
+    File::create(output)?.write_all(code.as_bytes())?;
+    Ok(())
+}
+
+fn parse_value(s: &str) -> Result<Option<u64>> {
+    let ret = if s == "ignore" {
+        return Ok(None);
+    } else if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16)
+    } else if s.starts_with("0b") {
+        u64::from_str_radix(&s[2..], 2)
+    } else {
+        s.parse::<u64>()
+    };
+
+    ret.map(Some).map_err(|_| Error(format!("invalid value '{}'", s)))
+}
+
+fn is_group_name(s: &str) -> bool {
+    let mut it = s.chars();
+
+    match it.next() {
+        Some(c) if c.is_ascii_alphabetic() => it.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+fn identifier(s: &str) -> String {
+    s.chars().map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' }).collect()
+}
+
+/// Operand fields used by the riscv-opcodes files.
+static RISCV_FIELDS: &'static [(&'static str, &'static str)] = &[
+    ("rd", "11..7"),
+    ("rs1", "19..15"),
+    ("rs2", "24..20"),
+    ("rs3", "31..27"),
+    ("rm", "14..12"),
+    ("imm12", "31..20"),
+    ("imm12hi", "31..25"),
+    ("imm12lo", "11..7"),
+    ("bimm12hi", "31..25"),
+    ("bimm12lo", "11..7"),
+    ("imm20", "31..12"),
+    ("jimm20", "31..12"),
+    ("shamt", "25..20"),
+    ("shamtw", "24..20"),
+    ("csr", "31..20"),
+    ("zimm", "19..15"),
+    ("fm", "31..28"),
+    ("pred", "27..24"),
+    ("succ", "23..20"),
+    ("aq", "26"),
+    ("rl", "25"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(Range::parse("31..20"), Ok(Range { hi: 31, lo: 20 }));
+        assert_eq!(Range::parse("7"), Ok(Range { hi: 7, lo: 7 }));
+        assert!(Range::parse("3..7").is_err());
+        assert!(Range::parse("a..0").is_err());
+    }
+
+    #[test]
+    fn riscv_opcodes() {
+        let mut spec = Spec::new("Riscv", 16);
+
+        spec.action = "semantic::{}".to_string();
+        spec.add_riscv_opcodes(
+            "# comment\n\
+             addi    rd rs1 imm12 14..12=0 6..2=0x04 1..0=3\n\
+             $pseudo_op rv_i::addi nop 31..0=0x13\n\
+             fence.i imm12 rs1 14..12=1 rd 6..2=0x03 1..0=3\n\
+             c.nop   15..13=0 12=0 11..7=0 6..2=0 1..0=1\n",
+        ).unwrap();
+
+        assert_eq!(spec.instructions.len(), 3);
+        assert_eq!(spec.instructions[2].width, 16);
+        assert_eq!(
+            spec.patterns(&spec.instructions[0]).unwrap(),
+            vec!["rs1_1@. 000 rd@..... 0010011", "imm12@............ rs1_0@...."]
+        );
+        assert_eq!(
+            spec.generate().unwrap(),
+            "// Generated by panopticon-isa-gen, do not edit.\n\
+             new_disassembler!(Riscv =>\n    \
+             [ \"rs1_1@. 000 rd@..... 0010011\", \"imm12@............ rs1_0@....\" ] = |st: &mut State<Riscv>| {\n        \
+             let v = (st.get_group(\"rs1_0\") << 1) | st.get_group(\"rs1_1\");\n        \
+             st.groups.push((\"rs1\".to_string(), v));\n        \
+             (semantic::addi)(st)\n    },\n    \
+             [ \"rs1_1@. 001 rd@..... 0001111\", \"imm12@............ rs1_0@....\" ] = |st: &mut State<Riscv>| {\n        \
+             let v = (st.get_group(\"rs1_0\") << 1) | st.get_group(\"rs1_1\");\n        \
+             st.groups.push((\"rs1\".to_string(), v));\n        \
+             (semantic::fence_i)(st)\n    },\n    \
+             [ \"0000000000000001\" ] = semantic::c_nop\n)\n"
+        );
+    }
+
+    #[test]
+    fn scattered_fields() {
+        let mut spec = Spec::new("Riscv", 32);
+
+        spec.fields.insert("bimm".to_string(), ["31", "7", "30..25", "11..8"].iter().map(|r| Range::parse(r).unwrap()).collect());
+        spec.instructions.push(Instruction::new("beq", 32, "bimm rs1 rs2 14..12=0 6..0=0x63").unwrap());
+
+        assert_eq!(
+            spec.patterns(&spec.instructions[0]).unwrap(),
+            vec!["bimm_0@. bimm_2@...... rs2@..... rs1@..... 000 bimm_3@.... bimm_1@. 1100011"]
+        );
+        assert!(
+            spec.generate()
+                .unwrap()
+                .contains("let v = (st.get_group(\"bimm_0\") << 11) | (st.get_group(\"bimm_1\") << 10) | (st.get_group(\"bimm_2\") << 4) | st.get_group(\"bimm_3\");")
+        );
+    }
+
+    #[test]
+    fn toml_spec() {
+        let spec = Spec::from_toml(
+            r#"
+            arch = "Avr"
+            token = 16
+            default = "nonary(\"unk\", semantic::nop)"
+
+            [fields]
+            d = "8..4"
+            r = ["9", "3..0"]
+            k = "31..16"
+
+            [[instruction]]
+            name = "add"
+            encoding = "15..10=0b000011 d r"
+            action = "binary(\"add\", reg, reg, semantic::add)"
+
+            [[instruction]]
+            name = "sts"
+            width = 32
+            encoding = "15..9=0b1001001 d 3..0=0 k"
+            "#,
+            Path::new("."),
+        ).unwrap();
+
+        assert_eq!(spec.patterns(&spec.instructions[0]).unwrap(), vec!["000011 r@. d@..... r@...."]);
+        assert_eq!(
+            spec.patterns(&spec.instructions[1]).unwrap(),
+            vec!["1001001 d@..... 0000", "k@................"]
+        );
+        assert!(spec.generate().unwrap().ends_with("] = sts,\n    _ = nonary(\"unk\", semantic::nop)\n)\n"));
+    }
+
+    #[test]
+    fn big_endian() {
+        let mut spec = Spec::new("M68k", 16);
+
+        spec.big_endian = true;
+        spec.fields.insert("disp".to_string(), vec![Range::parse("15..0").unwrap()]);
+        spec.instructions.push(Instruction::new("bra", 32, "disp 31..16=0x6000").unwrap());
+
+        assert_eq!(spec.patterns(&spec.instructions[0]).unwrap(), vec!["0110000000000000", "disp@................"]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut spec = Spec::new("Riscv", 32);
+
+        assert!(Instruction::new("x", 32, "3..0=16").is_err());
+        assert!(Instruction::new("x", 32, "40..32=1").is_err());
+
+        spec.instructions.push(Instruction::new("a", 32, "foo 1..0=3").unwrap());
+        assert!(spec.generate().is_err());
+
+        spec.instructions[0] = Instruction::new("a", 32, "rd 11..7=3").unwrap();
+        assert!(spec.generate().is_err());
+
+        spec.instructions[0] = Instruction::new("a", 32, "rd 6..0=0x13").unwrap();
+        spec.instructions.push(Instruction::new("b", 32, "rs1 6..0=0x13").unwrap());
+        assert_eq!(spec.generate(), Err(Error("a and b have the same encoding".to_string())));
+    }
+}
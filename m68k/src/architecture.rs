@@ -0,0 +1,98 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use decode::{self, Flow};
+use panopticon_core::{Architecture, Guard, Match, Region, Result, Rvalue};
+
+#[derive(Clone,Debug)]
+pub enum M68k {}
+
+/// CPU model
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Model {
+    /// 68000 and 68008
+    M68000,
+}
+
+/// Exception vectors that are entry points. Vector 1 is the initial program counter.
+const VECTORS: [(usize, &'static str, &'static str); 6] = [
+    (1, "RESET", "Reset vector"),
+    (2, "BUS_ERROR", "Bus error handler"),
+    (3, "ADDRESS_ERROR", "Address error handler"),
+    (4, "ILLEGAL", "Illegal instruction handler"),
+    (5, "ZERO_DIVIDE", "Division by zero handler"),
+    (31, "NMI", "Level 7 interrupt autovector"),
+];
+
+impl Architecture for M68k {
+    type Token = u16;
+    type Configuration = Model;
+
+    fn prepare(reg: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        let mut ret = vec![];
+
+        for &(vec, name, comment) in VECTORS.iter() {
+            let mut i = reg.iter().seek(vec as u64 * 4);
+            let mut addr = 0u64;
+
+            for _ in 0..4 {
+                match i.next() {
+                    Some(Some(b)) => addr = (addr << 8) | b as u64,
+                    _ => return Ok(ret),
+                }
+            }
+
+            if addr != 0 && addr % 2 == 0 && addr < reg.size() {
+                ret.push((name, addr, comment));
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        if start % 2 != 0 {
+            return Err(format!("unaligned 68000 instruction at {:#x}", start).into());
+        }
+
+        let (insn, tokens) = decode::read(reg, start)?;
+        let len = 2 * tokens.len() as u64;
+        let next = Rvalue::new_u32((start + len) as u32);
+        let jumps = match insn.flow.clone() {
+            Flow::Next | Flow::Call(_) => vec![(start, next, Guard::always())],
+            Flow::Jump(tgt) => vec![(start, tgt, Guard::always())],
+            Flow::Branch(tgt, flag) => {
+                let guard = Guard::from_flag(&flag)?;
+                vec![(start, tgt, guard.clone()), (start, next, guard.negation())]
+            }
+            Flow::Return | Flow::Halt => vec![],
+        };
+
+        debug!("disass @ {:#x}: {} {:?}", start, insn.opcode, insn.flow);
+
+        Ok(
+            Match::<M68k> {
+                tokens: tokens,
+                mnemonics: vec![insn.mnemonic(start, len)?],
+                jumps: jumps,
+                configuration: cfg.clone(),
+            }
+        )
+    }
+}
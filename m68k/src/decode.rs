@@ -0,0 +1,1458 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! 68000 instruction decoder.
+
+use operand::{self, Ea};
+use panopticon_core::{Lvalue, Mnemonic, Operation, Region, Result, Rvalue, Statement};
+use semantic::*;
+use std::borrow::Cow;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Jumps to the target
+    Jump(Rvalue),
+    /// Jumps to the target if the flag is set, falls through otherwise
+    Branch(Rvalue, Rvalue),
+    /// Calls the target and falls through
+    Call(Rvalue),
+    /// Returns to the caller or from an exception
+    Return,
+    /// Raises an exception that doesn't return (`illegal`)
+    Halt,
+}
+
+/// A single decoded instruction.
+#[derive(Clone,Debug)]
+pub struct Insn {
+    pub opcode: String,
+    pub format: String,
+    pub operands: Vec<Rvalue>,
+    pub statements: Vec<Statement>,
+    pub flow: Flow,
+}
+
+impl Insn {
+    fn new(opcode: &str, operands: Vec<(String, Vec<Rvalue>)>, statements: Vec<Statement>) -> Insn {
+        let format = operands.iter().map(|x| x.0.clone()).collect::<Vec<_>>().join(", ");
+
+        Insn {
+            opcode: opcode.to_string(),
+            format: format,
+            operands: operands.into_iter().flat_map(|x| x.1).collect(),
+            statements: statements,
+            flow: Flow::Next,
+        }
+    }
+
+    fn flow(mut self, flow: Flow) -> Insn {
+        self.flow = flow;
+        self
+    }
+
+    pub fn mnemonic(&self, addr: u64, len: u64) -> Result<Mnemonic> {
+        Mnemonic::new(addr..addr + len, self.opcode.clone(), self.format.clone(), self.operands.iter(), self.statements.iter())
+    }
+}
+
+/// Reads the opcode and extension words of an instruction.
+pub struct Words<'a> {
+    region: &'a Region,
+    start: u64,
+    pub tokens: Vec<u16>,
+}
+
+impl<'a> Words<'a> {
+    pub fn new(region: &'a Region, start: u64) -> Words<'a> {
+        Words { region: region, start: start, tokens: vec![] }
+    }
+
+    /// Address of the next word.
+    pub fn address(&self) -> u64 {
+        self.start + 2 * self.tokens.len() as u64
+    }
+
+    /// Reads the next big endian word.
+    pub fn word(&mut self) -> Result<u16> {
+        let mut i = self.region.iter().seek(self.address());
+        let mut ret = 0u16;
+
+        for _ in 0..2 {
+            match i.next() {
+                Some(Some(b)) => ret = (ret << 8) | b as u16,
+                _ => return Err(format!("68000 instruction at {:#x} truncated", self.start).into()),
+            }
+        }
+
+        self.tokens.push(ret);
+        Ok(ret)
+    }
+
+    /// Reads the next two words as a 32-bit value.
+    pub fn long(&mut self) -> Result<u32> {
+        let hi = self.word()? as u32;
+        Ok((hi << 16) | self.word()? as u32)
+    }
+}
+
+type Operand = (String, Vec<Rvalue>);
+
+fn unknown(op: u16) -> Result<Insn> {
+    Err(format!("unknown 68000 instruction {:#06x}", op).into())
+}
+
+/// Operand size in the encoding most instructions use in bits 6 and 7.
+fn size(bits: u16) -> Option<usize> {
+    match bits & 3 {
+        0 => Some(8),
+        1 => Some(16),
+        2 => Some(32),
+        _ => None,
+    }
+}
+
+fn suffix(size: usize) -> &'static str {
+    match size {
+        8 => ".b",
+        16 => ".w",
+        _ => ".l",
+    }
+}
+
+fn show_reg(r: usize) -> Operand {
+    ("{u}".to_string(), vec![reg(r, 32)])
+}
+
+fn show_imm(v: u64, size: usize) -> Operand {
+    ("#{u}".to_string(), vec![imm(v, size)])
+}
+
+fn literal(s: &str) -> Operand {
+    (s.to_string(), vec![])
+}
+
+/// Decodes the instruction at `addr`. Returns the instruction and the words it consists of.
+pub fn read(region: &Region, addr: u64) -> Result<(Insn, Vec<u16>)> {
+    let mut w = Words::new(region, addr);
+    let op = w.word()?;
+    let insn = match op >> 12 {
+        0x0 => group0(op, &mut w)?,
+        0x1...0x3 => move_(op, &mut w)?,
+        0x4 => group4(op, &mut w)?,
+        0x5 => group5(op, &mut w)?,
+        0x6 => branch(op, &mut w, addr)?,
+        0x7 if op & 0x100 == 0 => {
+            let r = (op >> 9) as usize & 7;
+            let v = imm(op as u64 & 0xff, 8);
+            let mut stmts = rreil!{ sext/32 (reg_lv(r)), (v); }?;
+
+            stmts.extend(logic_flags(v.clone(), 8)?);
+            Insn::new("moveq", vec![("#{s}".to_string(), vec![v]), show_reg(r)], stmts)
+        }
+        0x8 | 0xc => logic_or_multiply(op, &mut w)?,
+        0x9 | 0xd => add_sub(op, &mut w)?,
+        // Line A emulator trap
+        0xa => Insn::new("linea", vec![show_imm(op as u64, 16)], vec![]),
+        0xb => compare(op, &mut w)?,
+        0xe => shift(op, &mut w)?,
+        _ => unknown(op)?,
+    };
+
+    Ok((insn, w.tokens))
+}
+
+// Immediate instructions, bit manipulation and movep.
+fn group0(op: u16, w: &mut Words) -> Result<Insn> {
+    let rx = (op >> 9) as usize & 7;
+    let mode = (op >> 3) & 7;
+
+    if op & 0x100 != 0 {
+        if mode == 1 {
+            return movep(op, w);
+        }
+
+        let dst = Ea::read(mode, op, 8, w)?;
+        return bit_op(op, Some(rx), 0, dst);
+    }
+
+    if rx == 4 {
+        let n = w.word()? as u64 & 0xff;
+        let dst = Ea::read(mode, op, 8, w)?;
+
+        if let Ea::Immediate(_) = dst {
+            return unknown(op);
+        }
+        return bit_op(op, None, n, dst);
+    }
+
+    let name = match rx {
+        0 => "ori",
+        1 => "andi",
+        2 => "subi",
+        3 => "addi",
+        5 => "eori",
+        6 => "cmpi",
+        _ => return unknown(op),
+    };
+
+    // ori, andi and eori to the condition codes or status register
+    if op & 0x3f == 0x3c && (rx == 0 || rx == 1 || rx == 5) {
+        let system = match (op >> 6) & 3 {
+            0 => false,
+            1 => true,
+            _ => return unknown(op),
+        };
+        let v = w.word()? as u64;
+        let v = if system { v } else { v & 0xff };
+        let mut stmts = vec![];
+
+        for (i, f) in ["C", "V", "Z", "N", "X"].iter().enumerate() {
+            let flag = Lvalue::Variable { name: Cow::Borrowed(*f), subscript: None, size: 1 };
+            let set = (v >> i) & 1 != 0;
+
+            match (rx, set) {
+                (0, true) => stmts.extend(rreil!{ mov (flag), [1]:1; }?),
+                (1, false) => stmts.extend(rreil!{ mov (flag), [0]:1; }?),
+                (5, true) => stmts.extend(rreil!{ xor (flag), (flag), [1]:1; }?),
+                _ => {}
+            }
+        }
+
+        if system {
+            let hi = v >> 8;
+            stmts.extend(
+                match rx {
+                    0 => rreil!{ or srh:8, srh:8, [hi]:8; }?,
+                    1 => rreil!{ and srh:8, srh:8, [hi]:8; }?,
+                    _ => rreil!{ xor srh:8, srh:8, [hi]:8; }?,
+                }
+            );
+        }
+
+        let ops = vec![show_imm(v, if system { 16 } else { 8 }), literal(if system { "sr" } else { "ccr" })];
+        return Ok(Insn::new(name, ops, stmts));
+    }
+
+    let size = match size(op >> 6) {
+        Some(s) => s,
+        None => return unknown(op),
+    };
+    let v = match size {
+        8 => w.word()? as u64 & 0xff,
+        16 => w.word()? as u64,
+        _ => w.long()? as u64,
+    };
+    let dst = Ea::read(mode, op, size, w)?;
+
+    if !dst.is_data() || !dst.is_alterable() {
+        return unknown(op);
+    }
+
+    let src = imm(v, size);
+    let (mut stmts, loc) = dst.locate(size)?;
+    let (load, d) = loc.load(size, "dst")?;
+
+    stmts.extend(load);
+    match rx {
+        0 => stmts.extend(logic(Operation::InclusiveOr, d, src, size)?),
+        1 => stmts.extend(logic(Operation::And, d, src, size)?),
+        2 => stmts.extend(arith(d, src, size, true, false, true)?),
+        3 => stmts.extend(arith(d, src, size, false, false, true)?),
+        5 => stmts.extend(logic(Operation::ExclusiveOr, d, src, size)?),
+        _ => stmts.extend(arith(d, src, size, true, false, false)?),
+    }
+    if rx != 6 {
+        stmts.extend(loc.store(result(size))?);
+    }
+
+    Ok(Insn::new(&format!("{}{}", name, suffix(size)), vec![show_imm(v, size), dst.format(size)], stmts))
+}
+
+// btst, bchg, bclr and bset. The bit number is either in data register `dynamic` or `n`.
+fn bit_op(op: u16, dynamic: Option<usize>, n: u64, dst: Ea) -> Result<Insn> {
+    let kind = (op >> 6) & 3;
+
+    if !dst.is_data() || (kind != 0 && !dst.is_alterable()) {
+        return unknown(op);
+    }
+
+    // bit numbers are modulo 32 for registers and modulo 8 for memory operands
+    let size = if let Ea::DataReg(_) = dst { 32 } else { 8 };
+    let top = size as u64 - 1;
+    let (mut stmts, loc) = dst.locate(size)?;
+    let (load, v) = loc.load(size, "val")?;
+
+    stmts.extend(load);
+    match dynamic {
+        Some(r) => {
+            stmts.extend(
+                rreil!{
+                    and n:(size), (reg(r, size)), [top]:(size);
+                    shl m:(size), [1]:(size), n:(size);
+                }?
+            )
+        }
+        None => {
+            let bit = 1u64 << (n & top);
+            stmts.extend(rreil!{ mov m:(size), [bit]:(size); }?);
+        }
+    }
+
+    stmts.extend(
+        rreil!{
+            and t:(size), (v), m:(size);
+            cmpeq Z:1, t:(size), [0]:(size);
+        }?
+    );
+
+    match kind {
+        0 => {}
+        1 => stmts.extend(rreil!{ xor res:(size), (v), m:(size); }?),
+        2 => {
+            let all = mask(!0, size);
+            stmts.extend(
+                rreil!{
+                    xor m:(size), m:(size), [all]:(size);
+                    and res:(size), (v), m:(size);
+                }?
+            );
+        }
+        _ => stmts.extend(rreil!{ or res:(size), (v), m:(size); }?),
+    }
+    if kind != 0 {
+        stmts.extend(loc.store(result(size))?);
+    }
+
+    let name = ["btst", "bchg", "bclr", "bset"][kind as usize];
+    let bit = match dynamic {
+        Some(r) => show_reg(r),
+        None => show_imm(n, 8),
+    };
+
+    Ok(Insn::new(name, vec![bit, dst.format(size)], stmts))
+}
+
+// Transfers every other byte between a data register and memory.
+fn movep(op: u16, w: &mut Words) -> Result<Insn> {
+    let dx = (op >> 9) as usize & 7;
+    let an = (op as usize & 7) + 8;
+    let d = w.word()? as i16 as i32 as u32;
+    let size = if op & 0x40 != 0 { 32 } else { 16 };
+    let to_memory = op & 0x80 != 0;
+    let bytes = size / 8;
+    let mut stmts = vec![];
+
+    for i in 0..bytes {
+        let off = d.wrapping_add(2 * i as u32);
+        let shift = 8 * (bytes - 1 - i);
+
+        stmts.extend(rreil!{ add ea:32, (reg(an, 32)), [off]:32; }?);
+        if to_memory {
+            stmts.push(store(rreil_rvalue!{ ea:32 }, reg(dx, 32).extract(8, shift)?, 8));
+        } else {
+            stmts.push(load(rreil_lvalue!{ b:8 }, rreil_rvalue!{ ea:32 }, 8));
+            stmts.extend(rreil!{ sel/(shift) val:(size), b:8; }?);
+        }
+    }
+
+    if !to_memory {
+        stmts.extend(write_reg(dx, rreil_rvalue!{ val:(size) })?);
+    }
+
+    let mem = ("({s},{u})".to_string(), vec![imm(d as u64, 16), reg(an, 32)]);
+    let ops = if to_memory { vec![show_reg(dx), mem] } else { vec![mem, show_reg(dx)] };
+
+    Ok(Insn::new(&format!("movep{}", suffix(size)), ops, stmts))
+}
+
+fn move_(op: u16, w: &mut Words) -> Result<Insn> {
+    let size = match op >> 12 {
+        1 => 8,
+        3 => 16,
+        _ => 32,
+    };
+    let rx = (op >> 9) as usize & 7;
+    let dmode = (op >> 6) & 7;
+    let src = Ea::read((op >> 3) & 7, op, size, w)?;
+
+    if size == 8 && !src.is_data() {
+        return unknown(op);
+    }
+
+    let (mut stmts, v) = operand::read(&src, size, "src")?;
+
+    if dmode == 1 {
+        if size == 8 {
+            return unknown(op);
+        }
+
+        stmts.extend(rreil!{ sext/32 (reg_lv(rx + 8)), (v); }?);
+        return Ok(Insn::new(&format!("movea{}", suffix(size)), vec![src.format(size), show_reg(rx + 8)], stmts));
+    }
+
+    let dst = Ea::read(dmode, rx as u16, size, w)?;
+
+    if !dst.is_data() || !dst.is_alterable() {
+        return unknown(op);
+    }
+
+    stmts.extend(logic_flags(v.clone(), size)?);
+    stmts.extend(operand::write(&dst, v)?);
+    Ok(Insn::new(&format!("move{}", suffix(size)), vec![src.format(size), dst.format(size)], stmts))
+}
+
+// Miscellaneous instructions.
+fn group4(op: u16, w: &mut Words) -> Result<Insn> {
+    let rx = (op >> 9) as usize & 7;
+    let mode = (op >> 3) & 7;
+    let ry = op as usize & 7;
+
+    match op {
+        0x4afc => return Ok(Insn::new("illegal", vec![], vec![]).flow(Flow::Halt)),
+        0x4e70 => return Ok(Insn::new("reset", vec![], vec![])),
+        0x4e71 => return Ok(Insn::new("nop", vec![], vec![])),
+        0x4e72 => {
+            let v = w.word()? as u64;
+            let stmts = set_status_register(imm(v, 16), true)?;
+            return Ok(Insn::new("stop", vec![show_imm(v, 16)], stmts));
+        }
+        0x4e73 | 0x4e77 => {
+            let mut stmts = vec![load(rreil_lvalue!{ sr:16 }, reg(SP, 32), 16)];
+
+            stmts.extend(set_status_register(rreil_rvalue!{ sr:16 }, op == 0x4e73)?);
+            stmts.extend(rreil!{ add ea:32, sp:32, [2]:32; }?);
+            stmts.push(load(rreil_lvalue!{ pc:32 }, rreil_rvalue!{ ea:32 }, 32));
+            stmts.extend(rreil!{ add sp:32, sp:32, [6]:32; }?);
+
+            let name = if op == 0x4e73 { "rte" } else { "rtr" };
+            return Ok(Insn::new(name, vec![], stmts).flow(Flow::Return));
+        }
+        0x4e75 => return Ok(Insn::new("rts", vec![], pop(rreil_lvalue!{ pc:32 })?).flow(Flow::Return)),
+        0x4e76 => return Ok(Insn::new("trapv", vec![], vec![])),
+        _ => {}
+    }
+
+    match op & 0xfff8 {
+        0x4e40 | 0x4e48 => return Ok(Insn::new("trap", vec![show_imm(op as u64 & 0xf, 8)], vec![])),
+        0x4e50 => {
+            let an = ry + 8;
+            let d = w.word()? as u64;
+            let mut stmts = rreil!{ mov fp:32, (reg(an, 32)); }?;
+
+            stmts.extend(push(rreil_rvalue!{ fp:32 })?);
+            stmts.extend(
+                rreil!{
+                    mov (reg_lv(an)), sp:32;
+                    add sp:32, sp:32, [(sign_extend(d, 16))]:32;
+                }?
+            );
+            return Ok(Insn::new("link", vec![show_reg(an), ("#{s}".to_string(), vec![imm(d, 16)])], stmts));
+        }
+        0x4e58 => {
+            let an = ry + 8;
+            let mut stmts = rreil!{ mov sp:32, (reg(an, 32)); }?;
+
+            stmts.extend(pop(reg_lv(an))?);
+            return Ok(Insn::new("unlk", vec![show_reg(an)], stmts));
+        }
+        0x4e60 => {
+            let stmts = rreil!{ mov usp:32, (reg(ry + 8, 32)); }?;
+            return Ok(Insn::new("move", vec![show_reg(ry + 8), literal("usp")], stmts));
+        }
+        0x4e68 => {
+            let stmts = rreil!{ mov (reg_lv(ry + 8)), usp:32; }?;
+            return Ok(Insn::new("move", vec![literal("usp"), show_reg(ry + 8)], stmts));
+        }
+        0x4840 => return swap(ry),
+        0x4880 | 0x48c0 => return ext(ry, op & 0x40 != 0),
+        _ => {}
+    }
+
+    match op & 0xffc0 {
+        0x4e80 | 0x4ec0 => return jump(op, w),
+        0x4840 => {
+            let ea = Ea::read(mode, op, 32, w)?;
+
+            if !ea.is_control() {
+                return unknown(op);
+            }
+
+            let (mut stmts, addr) = operand::address(&ea)?;
+
+            stmts.extend(rreil!{ mov ea:32, (addr); }?);
+            stmts.extend(push(rreil_rvalue!{ ea:32 })?);
+            return Ok(Insn::new("pea", vec![ea.format(32)], stmts));
+        }
+        0x4880 | 0x48c0 | 0x4c80 | 0x4cc0 => return movem(op, w),
+        0x40c0 | 0x44c0 | 0x46c0 => return move_status(op, w),
+        0x4800 => {
+            let dst = Ea::read(mode, op, 8, w)?;
+
+            if !dst.is_data() || !dst.is_alterable() {
+                return unknown(op);
+            }
+            return bcd("nbcd", None, dst);
+        }
+        0x4ac0 => {
+            let dst = Ea::read(mode, op, 8, w)?;
+
+            if !dst.is_data() || !dst.is_alterable() {
+                return unknown(op);
+            }
+
+            let (mut stmts, loc) = dst.locate(8)?;
+            let (load, v) = loc.load(8, "val")?;
+
+            stmts.extend(load);
+            stmts.extend(logic_flags(v.clone(), 8)?);
+            stmts.extend(rreil!{ or res:8, (v), [0x80]:8; }?);
+            stmts.extend(loc.store(result(8))?);
+            return Ok(Insn::new("tas", vec![dst.format(8)], stmts));
+        }
+        _ => {}
+    }
+
+    match op & 0xf1c0 {
+        0x41c0 => {
+            let ea = Ea::read(mode, op, 32, w)?;
+
+            if !ea.is_control() {
+                return unknown(op);
+            }
+
+            let (mut stmts, addr) = operand::address(&ea)?;
+
+            stmts.extend(rreil!{ mov (reg_lv(rx + 8)), (addr); }?);
+            return Ok(Insn::new("lea", vec![ea.format(32), show_reg(rx + 8)], stmts));
+        }
+        0x4180 => {
+            let src = Ea::read(mode, op, 16, w)?;
+
+            if !src.is_data() {
+                return unknown(op);
+            }
+
+            // traps if the register is negative or greater than the operand
+            let (mut stmts, _) = operand::read(&src, 16, "src")?;
+
+            stmts.extend(rreil!{ mov N:1, ?; }?);
+            return Ok(Insn::new("chk.w", vec![src.format(16), show_reg(rx)], stmts));
+        }
+        _ => {}
+    }
+
+    let size = match size(op >> 6) {
+        Some(s) => s,
+        None => return unknown(op),
+    };
+    let name = match op & 0xff00 {
+        0x4000 => "negx",
+        0x4200 => "clr",
+        0x4400 => "neg",
+        0x4600 => "not",
+        0x4a00 => "tst",
+        _ => return unknown(op),
+    };
+    let dst = Ea::read(mode, op, size, w)?;
+
+    if !dst.is_data() || !dst.is_alterable() {
+        return unknown(op);
+    }
+
+    let (mut stmts, loc) = dst.locate(size)?;
+
+    if name == "clr" {
+        stmts.extend(loc.store(imm(0, size))?);
+        stmts.extend(logic_flags(imm(0, size), size)?);
+    } else {
+        let (load, v) = loc.load(size, "val")?;
+
+        stmts.extend(load);
+        match name {
+            "negx" => stmts.extend(arith(imm(0, size), v, size, true, true, true)?),
+            "neg" => stmts.extend(arith(imm(0, size), v, size, true, false, true)?),
+            "not" => stmts.extend(logic(Operation::ExclusiveOr, v, imm(!0, size), size)?),
+            _ => stmts.extend(logic_flags(v, size)?),
+        }
+        if name != "tst" {
+            stmts.extend(loc.store(result(size))?);
+        }
+    }
+
+    Ok(Insn::new(&format!("{}{}", name, suffix(size)), vec![dst.format(size)], stmts))
+}
+
+fn swap(r: usize) -> Result<Insn> {
+    let mut stmts = rreil!{
+        mov hi:16, (reg(r, 32).extract(16, 16)?);
+        mov lo:16, (reg(r, 16));
+        zext/32 (reg_lv(r)), hi:16;
+        sel/16 (reg_lv(r)), lo:16;
+    }?;
+
+    stmts.extend(logic_flags(reg(r, 32), 32)?);
+    Ok(Insn::new("swap", vec![show_reg(r)], stmts))
+}
+
+fn ext(r: usize, long: bool) -> Result<Insn> {
+    let size = if long { 32 } else { 16 };
+    let mut stmts = rreil!{ sext/(size) val:(size), (reg(r, size / 2)); }?;
+
+    stmts.extend(write_reg(r, rreil_rvalue!{ val:(size) })?);
+    stmts.extend(logic_flags(rreil_rvalue!{ val:(size) }, size)?);
+    Ok(Insn::new(&format!("ext{}", suffix(size)), vec![show_reg(r)], stmts))
+}
+
+// jsr and jmp
+fn jump(op: u16, w: &mut Words) -> Result<Insn> {
+    let call = op & 0x40 == 0;
+    let ea = Ea::read((op >> 3) & 7, op, 32, w)?;
+
+    if !ea.is_control() {
+        return unknown(op);
+    }
+
+    let (mut stmts, addr) = operand::address(&ea)?;
+    let target = if let Rvalue::Constant { .. } = addr {
+        addr
+    } else {
+        stmts.extend(rreil!{ mov tgt:32, (addr); }?);
+        rreil_rvalue!{ tgt:32 }
+    };
+    let (fmt, ops) = ea.format(32);
+    let ops = vec![(fmt.replace("{p:ram}", "{c:ram}"), ops)];
+
+    if call {
+        stmts.extend(push(Rvalue::new_u32(w.address() as u32))?);
+        stmts.extend(rreil!{ call (target); }?);
+        Ok(Insn::new("jsr", ops, stmts).flow(Flow::Call(target)))
+    } else {
+        Ok(Insn::new("jmp", ops, stmts).flow(Flow::Jump(target)))
+    }
+}
+
+// Register list of movem. Consecutive registers of the same kind are combined into ranges.
+fn register_list(regs: &[usize]) -> Operand {
+    let mut fmt = vec![];
+    let mut ops = vec![];
+    let mut i = 0;
+
+    if regs.is_empty() {
+        return show_imm(0, 16);
+    }
+
+    while i < regs.len() {
+        let mut j = i;
+
+        while j + 1 < regs.len() && regs[j + 1] == regs[j] + 1 && regs[j + 1] / 8 == regs[i] / 8 {
+            j += 1;
+        }
+
+        if i == j {
+            fmt.push("{u}");
+            ops.push(reg(regs[i], 32));
+        } else {
+            fmt.push("{u}-{u}");
+            ops.push(reg(regs[i], 32));
+            ops.push(reg(regs[j], 32));
+        }
+        i = j + 1;
+    }
+
+    (fmt.join("/"), ops)
+}
+
+fn movem(op: u16, w: &mut Words) -> Result<Insn> {
+    let size = if op & 0x40 != 0 { 32 } else { 16 };
+    let step = size as u64 / 8;
+    let to_regs = op & 0x400 != 0;
+    let list = w.word()?;
+    let ea = Ea::read((op >> 3) & 7, op, size, w)?;
+    let valid = match ea {
+        Ea::PostInc(_) => to_regs,
+        Ea::PreDec(_) => !to_regs,
+        _ => ea.is_control() && (to_regs || ea.is_alterable()),
+    };
+
+    if !valid {
+        return unknown(op);
+    }
+
+    // the register mask is reversed for the pre-decrement mode
+    let regs = match ea {
+        Ea::PreDec(_) => (0..16).filter(|&i| list & (0x8000 >> i) != 0).collect::<Vec<_>>(),
+        _ => (0..16).filter(|&i| list & (1 << i) != 0).collect::<Vec<_>>(),
+    };
+    let mut stmts = match ea {
+        Ea::PostInc(r) | Ea::PreDec(r) => rreil!{ mov ptr:32, (reg(r, 32)); }?,
+        _ => {
+            let (mut stmts, addr) = operand::address(&ea)?;
+            stmts.extend(rreil!{ mov ptr:32, (addr); }?);
+            stmts
+        }
+    };
+
+    if let Ea::PreDec(_) = ea {
+        for &r in regs.iter().rev() {
+            stmts.extend(rreil!{ sub ptr:32, ptr:32, [step]:32; }?);
+            stmts.push(store(rreil_rvalue!{ ptr:32 }, reg(r, size), size));
+        }
+    } else {
+        for &r in regs.iter() {
+            if to_regs {
+                stmts.push(load(rreil_lvalue!{ val:(size) }, rreil_rvalue!{ ptr:32 }, size));
+                stmts.extend(rreil!{ sext/32 (reg_lv(r)), val:(size); }?);
+            } else {
+                stmts.push(store(rreil_rvalue!{ ptr:32 }, reg(r, size), size));
+            }
+            stmts.extend(rreil!{ add ptr:32, ptr:32, [step]:32; }?);
+        }
+    }
+
+    match ea {
+        Ea::PostInc(r) | Ea::PreDec(r) => stmts.extend(rreil!{ mov (reg_lv(r)), ptr:32; }?),
+        _ => {}
+    }
+
+    let ops = if to_regs { vec![ea.format(size), register_list(&regs)] } else { vec![register_list(&regs), ea.format(size)] };
+    Ok(Insn::new(&format!("movem{}", suffix(size)), ops, stmts))
+}
+
+// move from sr, move to ccr and move to sr
+fn move_status(op: u16, w: &mut Words) -> Result<Insn> {
+    let ea = Ea::read((op >> 3) & 7, op, 16, w)?;
+
+    if !ea.is_data() {
+        return unknown(op);
+    }
+
+    match op & 0xffc0 {
+        0x40c0 => {
+            if !ea.is_alterable() {
+                return unknown(op);
+            }
+
+            let mut stmts = status_register()?;
+
+            stmts.extend(operand::write(&ea, rreil_rvalue!{ sr:16 })?);
+            Ok(Insn::new("move", vec![literal("sr"), ea.format(16)], stmts))
+        }
+        x => {
+            let system = x == 0x46c0;
+            let (mut stmts, v) = operand::read(&ea, 16, "src")?;
+
+            stmts.extend(set_status_register(v, system)?);
+            Ok(Insn::new("move", vec![ea.format(16), literal(if system { "sr" } else { "ccr" })], stmts))
+        }
+    }
+}
+
+// Decimal arithmetic isn't modeled, the result and flags are undefined.
+fn bcd(name: &str, src: Option<Ea>, dst: Ea) -> Result<Insn> {
+    let mut stmts = vec![];
+    let mut ops = vec![];
+
+    if let Some(src) = src {
+        let (read, _) = operand::read(&src, 8, "src")?;
+
+        stmts.extend(read);
+        ops.push(src.format(8));
+    }
+
+    let (locate, loc) = dst.locate(8)?;
+
+    stmts.extend(locate);
+    stmts.extend(
+        rreil!{
+            mov res:8, ?;
+            mov X:1, ?;
+            mov N:1, ?;
+            mov Z:1, ?;
+            mov V:1, ?;
+            mov C:1, ?;
+        }?
+    );
+    stmts.extend(loc.store(result(8))?);
+    ops.push(dst.format(8));
+
+    Ok(Insn::new(name, ops, stmts))
+}
+
+// addq, subq, scc and dbcc
+fn group5(op: u16, w: &mut Words) -> Result<Insn> {
+    let mode = (op >> 3) & 7;
+    let ry = op as usize & 7;
+    let cc = (op >> 8) as usize & 0xf;
+
+    if (op >> 6) & 3 == 3 {
+        if mode == 1 {
+            let base = w.address() as u32;
+            let target = Rvalue::new_u32(base.wrapping_add(w.word()? as i16 as i32 as u32));
+            let (mut stmts, cond) = condition(cc)?;
+
+            // the counter is only decremented if the condition is false
+            stmts.extend(
+                rreil!{
+                    xor nc:1, (cond), [1]:1;
+                    zext/16 dec:16, nc:1;
+                    sub cnt:16, (reg(ry, 16)), dec:16;
+                }?
+            );
+            stmts.extend(write_reg(ry, rreil_rvalue!{ cnt:16 })?);
+            stmts.extend(
+                rreil!{
+                    cmpeq done:1, cnt:16, [0xffff]:16;
+                    xor again:1, done:1, [1]:1;
+                    and again:1, again:1, nc:1;
+                }?
+            );
+
+            let name = if cc == 1 { "dbra".to_string() } else { format!("db{}", CONDITIONS[cc]) };
+            let ops = vec![show_reg(ry), ("{c:ram}".to_string(), vec![target.clone()])];
+            return Ok(Insn::new(&name, ops, stmts).flow(Flow::Branch(target, rreil_rvalue!{ again:1 })));
+        }
+
+        let dst = Ea::read(mode, op, 8, w)?;
+
+        if !dst.is_data() || !dst.is_alterable() {
+            return unknown(op);
+        }
+
+        let (mut stmts, cond) = condition(cc)?;
+
+        stmts.extend(
+            rreil!{
+                zext/8 c:8, (cond);
+                sub res:8, [0]:8, c:8;
+            }?
+        );
+        stmts.extend(operand::write(&dst, result(8))?);
+        return Ok(Insn::new(&format!("s{}", CONDITIONS[cc]), vec![dst.format(8)], stmts));
+    }
+
+    let size = size(op >> 6).unwrap();
+    let sub = op & 0x100 != 0;
+    let data = match (op >> 9) & 7 {
+        0 => 8,
+        x => x as u64,
+    };
+    let dst = Ea::read(mode, op, size, w)?;
+    let name = format!("{}{}", if sub { "subq" } else { "addq" }, suffix(size));
+
+    if !dst.is_alterable() {
+        return unknown(op);
+    }
+
+    // address registers are changed as a whole and the flags are left alone
+    if let Ea::AddrReg(r) = dst {
+        if size == 8 {
+            return unknown(op);
+        }
+
+        let an = reg_lv(r);
+        let stmts = if sub {
+            rreil!{ sub (an), (an), [data]:32; }?
+        } else {
+            rreil!{ add (an), (an), [data]:32; }?
+        };
+
+        return Ok(Insn::new(&name, vec![show_imm(data, size), show_reg(r)], stmts));
+    }
+
+    let (mut stmts, loc) = dst.locate(size)?;
+    let (load, v) = loc.load(size, "dst")?;
+
+    stmts.extend(load);
+    stmts.extend(arith(v, imm(data, size), size, sub, false, true)?);
+    stmts.extend(loc.store(result(size))?);
+    Ok(Insn::new(&name, vec![show_imm(data, size), dst.format(size)], stmts))
+}
+
+// bra, bsr and bcc
+fn branch(op: u16, w: &mut Words, addr: u64) -> Result<Insn> {
+    let cc = (op >> 8) as usize & 0xf;
+    let base = addr as u32 + 2;
+    let (disp, sfx) = match op & 0xff {
+        0 => (w.word()? as i16 as i32, ".w"),
+        d => (d as u8 as i8 as i32, ".s"),
+    };
+    let target = Rvalue::new_u32(base.wrapping_add(disp as u32));
+    let ops = vec![("{c:ram}".to_string(), vec![target.clone()])];
+
+    match cc {
+        0 => Ok(Insn::new(&format!("bra{}", sfx), ops, vec![]).flow(Flow::Jump(target))),
+        1 => {
+            let mut stmts = push(Rvalue::new_u32(w.address() as u32))?;
+
+            stmts.extend(rreil!{ call (target); }?);
+            Ok(Insn::new(&format!("bsr{}", sfx), ops, stmts).flow(Flow::Call(target)))
+        }
+        _ => {
+            let (stmts, cond) = condition(cc)?;
+            Ok(Insn::new(&format!("b{}{}", CONDITIONS[cc], sfx), ops, stmts).flow(Flow::Branch(target, cond)))
+        }
+    }
+}
+
+// or, and, mulu, muls, divu, divs, sbcd, abcd and exg
+fn logic_or_multiply(op: u16, w: &mut Words) -> Result<Insn> {
+    let is_and = op >> 12 == 0xc;
+    let rx = (op >> 9) as usize & 7;
+    let mode = (op >> 3) & 7;
+    let ry = op as usize & 7;
+    let opmode = (op >> 6) & 7;
+
+    if opmode == 3 || opmode == 7 {
+        let signed = opmode == 7;
+        let src = Ea::read(mode, op, 16, w)?;
+
+        if !src.is_data() {
+            return unknown(op);
+        }
+
+        let (mut stmts, v) = operand::read(&src, 16, "src")?;
+        let name = match (is_and, signed) {
+            (true, false) => "mulu.w",
+            (true, true) => "muls.w",
+            (false, false) => "divu.w",
+            (false, true) => "divs.w",
+        };
+
+        if signed {
+            stmts.extend(rreil!{ sext/32 b:32, (v); }?);
+        } else {
+            stmts.extend(rreil!{ zext/32 b:32, (v); }?);
+        }
+
+        if is_and {
+            if signed {
+                stmts.extend(rreil!{ sext/32 a:32, (reg(rx, 16)); }?);
+            } else {
+                stmts.extend(rreil!{ zext/32 a:32, (reg(rx, 16)); }?);
+            }
+            stmts.extend(rreil!{ mul res:32, a:32, b:32; }?);
+            stmts.extend(write_reg(rx, result(32))?);
+            stmts.extend(logic_flags(result(32), 32)?);
+        } else {
+            // quotient in the lower, remainder in the upper word. Overflows and division by zero
+            // aren't modeled.
+            let d = reg(rx, 32);
+
+            if signed {
+                stmts.extend(rreil!{ divs q:32, (d), b:32; }?);
+            } else {
+                stmts.extend(rreil!{ div q:32, (d), b:32; }?);
+            }
+            stmts.extend(
+                rreil!{
+                    mul t:32, q:32, b:32;
+                    sub r:32, (d), t:32;
+                    zext/32 res:32, q:16;
+                    sel/16 res:32, r:16;
+                }?
+            );
+            stmts.extend(write_reg(rx, result(32))?);
+            stmts.extend(logic_flags(rreil_rvalue!{ q:16 }, 16)?);
+        }
+
+        return Ok(Insn::new(name, vec![src.format(16), show_reg(rx)], stmts));
+    }
+
+    if op & 0x1f0 == 0x100 {
+        let name = if is_and { "abcd" } else { "sbcd" };
+
+        if op & 8 != 0 {
+            return bcd(name, Some(Ea::PreDec(ry + 8)), Ea::PreDec(rx + 8));
+        } else {
+            return bcd(name, Some(Ea::DataReg(ry)), Ea::DataReg(rx));
+        }
+    }
+
+    if is_and {
+        let regs = match op & 0x1f8 {
+            0x140 => Some((rx, ry)),
+            0x148 => Some((rx + 8, ry + 8)),
+            0x188 => Some((rx, ry + 8)),
+            _ => None,
+        };
+
+        if let Some((a, b)) = regs {
+            let stmts = rreil!{
+                mov t:32, (reg(a, 32));
+                mov (reg_lv(a)), (reg(b, 32));
+                mov (reg_lv(b)), t:32;
+            }?;
+
+            return Ok(Insn::new("exg", vec![show_reg(a), show_reg(b)], stmts));
+        }
+    }
+
+    let size = size(opmode).unwrap();
+    let name = format!("{}{}", if is_and { "and" } else { "or" }, suffix(size));
+    let bin = if is_and { Operation::And } else { Operation::InclusiveOr };
+    let ea = Ea::read(mode, op, size, w)?;
+
+    if opmode < 4 {
+        if !ea.is_data() {
+            return unknown(op);
+        }
+
+        let (mut stmts, v) = operand::read(&ea, size, "src")?;
+
+        stmts.extend(logic(bin, reg(rx, size), v, size)?);
+        stmts.extend(write_reg(rx, result(size))?);
+        Ok(Insn::new(&name, vec![ea.format(size), show_reg(rx)], stmts))
+    } else {
+        if !ea.is_memory() || !ea.is_alterable() {
+            return unknown(op);
+        }
+
+        let (mut stmts, loc) = ea.locate(size)?;
+        let (load, v) = loc.load(size, "dst")?;
+
+        stmts.extend(load);
+        stmts.extend(logic(bin, v, reg(rx, size), size)?);
+        stmts.extend(loc.store(result(size))?);
+        Ok(Insn::new(&name, vec![show_reg(rx), ea.format(size)], stmts))
+    }
+}
+
+// add, adda, addx, sub, suba and subx
+fn add_sub(op: u16, w: &mut Words) -> Result<Insn> {
+    let sub = op >> 12 == 0x9;
+    let base = if sub { "sub" } else { "add" };
+    let rx = (op >> 9) as usize & 7;
+    let mode = (op >> 3) & 7;
+    let ry = op as usize & 7;
+    let opmode = (op >> 6) & 7;
+
+    if opmode == 3 || opmode == 7 {
+        let size = if opmode == 7 { 32 } else { 16 };
+        let an = reg_lv(rx + 8);
+        let src = Ea::read(mode, op, size, w)?;
+        let (mut stmts, v) = operand::read(&src, size, "src")?;
+
+        stmts.extend(rreil!{ sext/32 src:32, (v); }?);
+        if sub {
+            stmts.extend(rreil!{ sub (an), (an), src:32; }?);
+        } else {
+            stmts.extend(rreil!{ add (an), (an), src:32; }?);
+        }
+
+        return Ok(Insn::new(&format!("{}a{}", base, suffix(size)), vec![src.format(size), show_reg(rx + 8)], stmts));
+    }
+
+    let size = size(opmode).unwrap();
+
+    if op & 0x130 == 0x100 {
+        let (src, dst) = if op & 8 != 0 {
+            (Ea::PreDec(ry + 8), Ea::PreDec(rx + 8))
+        } else {
+            (Ea::DataReg(ry), Ea::DataReg(rx))
+        };
+        let (mut stmts, s) = operand::read(&src, size, "src")?;
+        let (locate, loc) = dst.locate(size)?;
+        let (load, d) = loc.load(size, "dst")?;
+
+        stmts.extend(locate);
+        stmts.extend(load);
+        stmts.extend(arith(d, s, size, sub, true, true)?);
+        stmts.extend(loc.store(result(size))?);
+        return Ok(Insn::new(&format!("{}x{}", base, suffix(size)), vec![src.format(size), dst.format(size)], stmts));
+    }
+
+    let name = format!("{}{}", base, suffix(size));
+    let ea = Ea::read(mode, op, size, w)?;
+
+    if opmode < 4 {
+        if size == 8 && !ea.is_data() {
+            return unknown(op);
+        }
+
+        let (mut stmts, v) = operand::read(&ea, size, "src")?;
+
+        stmts.extend(arith(reg(rx, size), v, size, sub, false, true)?);
+        stmts.extend(write_reg(rx, result(size))?);
+        Ok(Insn::new(&name, vec![ea.format(size), show_reg(rx)], stmts))
+    } else {
+        if !ea.is_memory() || !ea.is_alterable() {
+            return unknown(op);
+        }
+
+        let (mut stmts, loc) = ea.locate(size)?;
+        let (load, v) = loc.load(size, "dst")?;
+
+        stmts.extend(load);
+        stmts.extend(arith(v, reg(rx, size), size, sub, false, true)?);
+        stmts.extend(loc.store(result(size))?);
+        Ok(Insn::new(&name, vec![show_reg(rx), ea.format(size)], stmts))
+    }
+}
+
+// cmp, cmpa, cmpm and eor
+fn compare(op: u16, w: &mut Words) -> Result<Insn> {
+    let rx = (op >> 9) as usize & 7;
+    let mode = (op >> 3) & 7;
+    let ry = op as usize & 7;
+    let opmode = (op >> 6) & 7;
+
+    if opmode == 3 || opmode == 7 {
+        let size = if opmode == 7 { 32 } else { 16 };
+        let src = Ea::read(mode, op, size, w)?;
+        let (mut stmts, v) = operand::read(&src, size, "src")?;
+
+        stmts.extend(rreil!{ sext/32 src:32, (v); }?);
+        stmts.extend(arith(reg(rx + 8, 32), rreil_rvalue!{ src:32 }, 32, true, false, false)?);
+        return Ok(Insn::new(&format!("cmpa{}", suffix(size)), vec![src.format(size), show_reg(rx + 8)], stmts));
+    }
+
+    let size = size(opmode).unwrap();
+
+    if opmode >= 4 && mode == 1 {
+        let src = Ea::PostInc(ry + 8);
+        let dst = Ea::PostInc(rx + 8);
+        let (mut stmts, s) = operand::read(&src, size, "src")?;
+        let (read, d) = operand::read(&dst, size, "dst")?;
+
+        stmts.extend(read);
+        stmts.extend(arith(d, s, size, true, false, false)?);
+        return Ok(Insn::new(&format!("cmpm{}", suffix(size)), vec![src.format(size), dst.format(size)], stmts));
+    }
+
+    let ea = Ea::read(mode, op, size, w)?;
+
+    if opmode >= 4 {
+        if !ea.is_data() || !ea.is_alterable() {
+            return unknown(op);
+        }
+
+        let (mut stmts, loc) = ea.locate(size)?;
+        let (load, v) = loc.load(size, "dst")?;
+
+        stmts.extend(load);
+        stmts.extend(logic(Operation::ExclusiveOr, v, reg(rx, size), size)?);
+        stmts.extend(loc.store(result(size))?);
+        return Ok(Insn::new(&format!("eor{}", suffix(size)), vec![show_reg(rx), ea.format(size)], stmts));
+    }
+
+    if size == 8 && !ea.is_data() {
+        return unknown(op);
+    }
+
+    let (mut stmts, v) = operand::read(&ea, size, "src")?;
+
+    stmts.extend(arith(reg(rx, size), v, size, true, false, false)?);
+    Ok(Insn::new(&format!("cmp{}", suffix(size)), vec![ea.format(size), show_reg(rx)], stmts))
+}
+
+/// Shift count
+#[derive(Clone,Copy,Debug)]
+enum Count {
+    Immediate(u64),
+    Register(usize),
+}
+
+// Shifts and rotates. Memory operands are shifted by one bit, registers by an immediate or the
+// value of a data register.
+fn shift(op: u16, w: &mut Words) -> Result<Insn> {
+    let left = op & 0x100 != 0;
+    let rx = (op >> 9) as usize & 7;
+    let ry = op as usize & 7;
+
+    if (op >> 6) & 3 == 3 {
+        if op & 0x800 != 0 {
+            return unknown(op);
+        }
+
+        let kind = (op >> 9) & 3;
+        let dst = Ea::read((op >> 3) & 7, op, 16, w)?;
+
+        if !dst.is_memory() || !dst.is_alterable() {
+            return unknown(op);
+        }
+
+        let (mut stmts, loc) = dst.locate(16)?;
+        let (load, v) = loc.load(16, "dst")?;
+
+        stmts.extend(load);
+        stmts.extend(shift_rotate(kind, left, v, 16, Count::Immediate(1))?);
+        stmts.extend(loc.store(result(16))?);
+        return Ok(Insn::new(&format!("{}.w", shift_name(kind, left)), vec![dst.format(16)], stmts));
+    }
+
+    let size = size(op >> 6).unwrap();
+    let kind = (op >> 3) & 3;
+    let (count, shown) = if op & 0x20 != 0 {
+        (Count::Register(rx), show_reg(rx))
+    } else {
+        let n = if rx == 0 { 8 } else { rx as u64 };
+        (Count::Immediate(n), show_imm(n, 8))
+    };
+    let mut stmts = shift_rotate(kind, left, reg(ry, size), size, count)?;
+
+    stmts.extend(write_reg(ry, result(size))?);
+    Ok(Insn::new(&format!("{}{}", shift_name(kind, left), suffix(size)), vec![shown, show_reg(ry)], stmts))
+}
+
+fn shift_name(kind: u16, left: bool) -> String {
+    format!("{}{}", ["as", "ls", "rox", "ro"][kind as usize & 3], if left { "l" } else { "r" })
+}
+
+// Computes `res:size` and the flags.
+fn shift_rotate(kind: u16, left: bool, v: Rvalue, size: usize, count: Count) -> Result<Vec<Statement>> {
+    let s = size as u64;
+    let mut stmts = vec![];
+
+    match count {
+        Count::Immediate(n) => {
+            let n = n as usize;
+            let back = s - n as u64;
+
+            match (kind, left) {
+                (0, true) | (1, true) => {
+                    stmts.extend(
+                        rreil!{
+                            shl res:(size), (v), [n]:(size);
+                            mov C:1, (v.extract(1, size - n)?);
+                            mov X:1, C:1;
+                        }?
+                    )
+                }
+                (0, false) => {
+                    stmts.extend(
+                        rreil!{
+                            shrs res:(size), (v), [n]:(size);
+                            mov C:1, (v.extract(1, n - 1)?);
+                            mov X:1, C:1;
+                        }?
+                    )
+                }
+                (1, false) => {
+                    stmts.extend(
+                        rreil!{
+                            shr res:(size), (v), [n]:(size);
+                            mov C:1, (v.extract(1, n - 1)?);
+                            mov X:1, C:1;
+                        }?
+                    )
+                }
+                (2, _) => {
+                    // rotate through X as a size + 1 bit value
+                    let back = back + 1;
+
+                    stmts.extend(
+                        rreil!{
+                            zext/(size + 1) w:(size + 1), (v);
+                            sel/(size) w:(size + 1), X:1;
+                        }?
+                    );
+                    if left {
+                        stmts.extend(
+                            rreil!{
+                                shl a:(size + 1), w:(size + 1), [n]:(size + 1);
+                                shr b:(size + 1), w:(size + 1), [back]:(size + 1);
+                            }?
+                        );
+                    } else {
+                        stmts.extend(
+                            rreil!{
+                                shr a:(size + 1), w:(size + 1), [n]:(size + 1);
+                                shl b:(size + 1), w:(size + 1), [back]:(size + 1);
+                            }?
+                        );
+                    }
+                    stmts.extend(
+                        rreil!{
+                            or w:(size + 1), a:(size + 1), b:(size + 1);
+                            mov res:(size), w:(size);
+                            mov C:1, w:1/(size);
+                            mov X:1, C:1;
+                        }?
+                    );
+                }
+                (_, true) => {
+                    stmts.extend(
+                        rreil!{
+                            shl a:(size), (v), [n]:(size);
+                            shr b:(size), (v), [back]:(size);
+                            or res:(size), a:(size), b:(size);
+                            mov C:1, res:1;
+                        }?
+                    )
+                }
+                (_, false) => {
+                    stmts.extend(
+                        rreil!{
+                            shr a:(size), (v), [n]:(size);
+                            shl b:(size), (v), [back]:(size);
+                            or res:(size), a:(size), b:(size);
+                            mov C:1, res:1/(size - 1);
+                        }?
+                    )
+                }
+            }
+        }
+        Count::Register(r) => {
+            // counts are modulo 64. C and X depend on the count being zero or not.
+            stmts.extend(rreil!{ and n:(size), (reg(r, size)), [63]:(size); }?);
+
+            match (kind, left) {
+                (0, true) | (1, true) => stmts.extend(rreil!{ shl res:(size), (v), n:(size); }?),
+                (0, false) => stmts.extend(rreil!{ shrs res:(size), (v), n:(size); }?),
+                (1, false) => stmts.extend(rreil!{ shr res:(size), (v), n:(size); }?),
+                (2, _) => stmts.extend(rreil!{ mov res:(size), ?; }?),
+                (_, true) => {
+                    stmts.extend(
+                        rreil!{
+                            and m:(size), n:(size), [(s - 1)]:(size);
+                            sub k:(size), [s]:(size), m:(size);
+                            shl a:(size), (v), m:(size);
+                            shr b:(size), (v), k:(size);
+                            or res:(size), a:(size), b:(size);
+                        }?
+                    )
+                }
+                (_, false) => {
+                    stmts.extend(
+                        rreil!{
+                            and m:(size), n:(size), [(s - 1)]:(size);
+                            sub k:(size), [s]:(size), m:(size);
+                            shr a:(size), (v), m:(size);
+                            shl b:(size), (v), k:(size);
+                            or res:(size), a:(size), b:(size);
+                        }?
+                    )
+                }
+            }
+
+            stmts.extend(rreil!{ mov C:1, ?; }?);
+            if kind != 3 {
+                stmts.extend(rreil!{ mov X:1, ?; }?);
+            }
+        }
+    }
+
+    // asl sets V if the most significant bit changes at any time during the shift
+    if kind == 0 && left {
+        stmts.extend(rreil!{ mov V:1, ?; }?);
+    } else {
+        stmts.extend(rreil!{ mov V:1, [0]:1; }?);
+    }
+
+    stmts.extend(nz(result(size), size)?);
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_words(words: &[u16]) -> Insn {
+        let bytes = words.iter().flat_map(|w| vec![(w >> 8) as u8, *w as u8]).collect::<Vec<_>>();
+        let reg = Region::wrap("ram".to_string(), bytes);
+        let (ret, tokens) = read(&reg, 0).unwrap();
+
+        assert_eq!(tokens, words.to_vec());
+        for s in ret.statements.iter() {
+            assert!(s.sanity_check().is_ok(), "{} {:?}", ret.opcode, s);
+        }
+        assert!(ret.mnemonic(0, 2 * tokens.len() as u64).is_ok());
+        ret
+    }
+
+    #[test]
+    fn data_movement() {
+        assert_eq!(decode_words(&[0x7001]).opcode, "moveq");
+        assert_eq!(decode_words(&[0x2200]).opcode, "move.l");
+        assert_eq!(decode_words(&[0x3028, 0x0004]).format, "({s},{u}), {u}");
+        assert_eq!(decode_words(&[0x3040]).opcode, "movea.w");
+        assert_eq!(decode_words(&[0x23fc, 0x0000, 0x0001, 0x0000, 0x2000]).format, "#{u}, ({p:ram}).l");
+        assert_eq!(decode_words(&[0x41fa, 0x0010]).operands, vec![Rvalue::new_u32(0x12), reg(8, 32)]);
+        assert_eq!(decode_words(&[0x4cdf, 0x0303]).format, "({u})+, {u}-{u}/{u}-{u}");
+        assert_eq!(decode_words(&[0x48e7, 0xc0c0]).operands, vec![reg(0, 32), reg(1, 32), reg(8, 32), reg(9, 32), reg(SP, 32)]);
+        assert_eq!(decode_words(&[0x0188, 0x0000]).opcode, "movep.w");
+        assert_eq!(decode_words(&[0x4840]).opcode, "swap");
+        assert_eq!(decode_words(&[0xc141]).opcode, "exg");
+        assert_eq!(decode_words(&[0x4e56, 0xfff8]).opcode, "link");
+        assert_eq!(decode_words(&[0x3030, 0x1802]).format, "({s},{u},{u}.l), {u}");
+        assert_eq!(decode_words(&[0x303b, 0x0006]).format, "({s},pc,{u}.w), {u}");
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(decode_words(&[0xd081]).opcode, "add.l");
+        assert_eq!(decode_words(&[0xd1c8]).opcode, "adda.l");
+        assert_eq!(decode_words(&[0xd101]).opcode, "addx.b");
+        assert_eq!(decode_words(&[0x5280]).opcode, "addq.l");
+        assert_eq!(decode_words(&[0x0c40, 0x0010]).opcode, "cmpi.w");
+        assert_eq!(decode_words(&[0xb308]).opcode, "cmpm.b");
+        assert_eq!(decode_words(&[0xb141]).opcode, "eor.w");
+        assert_eq!(decode_words(&[0x80c1]).opcode, "divu.w");
+        assert_eq!(decode_words(&[0xc1c1]).opcode, "muls.w");
+        assert_eq!(decode_words(&[0xc107]).opcode, "abcd");
+        assert_eq!(decode_words(&[0x4480]).opcode, "neg.l");
+        assert_eq!(decode_words(&[0x4a50]).opcode, "tst.w");
+        assert_eq!(decode_words(&[0xe348]).opcode, "lsl.w");
+        assert_eq!(decode_words(&[0xe1b8]).opcode, "rol.l");
+        assert_eq!(decode_words(&[0xe310]).opcode, "roxl.b");
+        assert_eq!(decode_words(&[0xe0d0]).opcode, "asr.w");
+        assert_eq!(decode_words(&[0x0800, 0x0003]).opcode, "btst");
+        assert_eq!(decode_words(&[0x03d0]).opcode, "bset");
+        assert_eq!(decode_words(&[0x57c0]).opcode, "seq");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(decode_words(&[0x4e71]).flow, Flow::Next);
+        assert_eq!(decode_words(&[0x4e75]).flow, Flow::Return);
+        assert_eq!(decode_words(&[0x4e73]).flow, Flow::Return);
+        assert_eq!(decode_words(&[0x4afc]).flow, Flow::Halt);
+        assert_eq!(decode_words(&[0x60fe]).flow, Flow::Jump(Rvalue::new_u32(0)));
+        assert_eq!(decode_words(&[0x6604]).flow, Flow::Branch(Rvalue::new_u32(6), rreil_rvalue!{ cond:1 }));
+        assert_eq!(decode_words(&[0x51c8, 0xfffe]).flow, Flow::Branch(Rvalue::new_u32(0), rreil_rvalue!{ again:1 }));
+        assert_eq!(decode_words(&[0x4eb9, 0x0000, 0x1000]).flow, Flow::Call(Rvalue::new_u32(0x1000)));
+        assert_eq!(decode_words(&[0x4eb9, 0x0000, 0x1000]).format, "({c:ram}).l");
+        assert_eq!(decode_words(&[0x4ed0]).flow, Flow::Jump(rreil_rvalue!{ tgt:32 }));
+        assert_eq!(decode_words(&[0x6100, 0x0010]).opcode, "bsr.w");
+        assert_eq!(decode_words(&[0x4e4f]).opcode, "trap");
+        assert_eq!(decode_words(&[0xa9f0]).opcode, "linea");
+    }
+
+    #[test]
+    fn system() {
+        assert_eq!(decode_words(&[0x027c, 0x2700]).format, "#{u}, sr");
+        assert_eq!(decode_words(&[0x46fc, 0x2700]).opcode, "move");
+        assert_eq!(decode_words(&[0x40c0]).format, "sr, {u}");
+        assert_eq!(decode_words(&[0x4e68]).format, "usp, {u}");
+        assert_eq!(decode_words(&[0x4e72, 0x2000]).opcode, "stop");
+    }
+
+    #[test]
+    fn invalid() {
+        for words in [vec![0xf000], vec![0x4e7a], vec![0x1048], vec![0x0c3a, 0x0000], vec![0x4ec0], vec![0x7100]].iter() {
+            let bytes = words.iter().flat_map(|w| vec![(w >> 8) as u8, *w as u8]).collect::<Vec<_>>();
+            let reg = Region::wrap("ram".to_string(), bytes);
+
+            assert!(read(&reg, 0).is_err(), "{:?}", words);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! Motorola 68000 disassembler.
+//!
+//! This disassembler handles the instruction set of the original 68000 (and the 68008) with all
+//! twelve addressing modes. Instructions and their extension words are read big endian.
+//!
+//! The condition codes X, N, Z, V and C are modeled as separate flags. The system byte of the
+//! status register (trace, supervisor and interrupt mask bits) is kept in `srh`, the user stack
+//! pointer in `usp`. Subroutine calls push the return address onto the stack and `rts` pops it.
+//! BCD arithmetic sets its result to undefined. Line A and line F opcodes trap into the operating
+//! system, classic Mac OS uses the former for Toolbox calls. Line A opcodes are decoded as
+//! `linea` and fall through to the next instruction.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+
+pub mod semantic;
+mod operand;
+mod decode;
+
+mod architecture;
+pub use architecture::{M68k, Model};
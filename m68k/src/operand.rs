@@ -0,0 +1,298 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! Effective addresses.
+
+use decode::Words;
+use panopticon_core::{Lvalue, Result, Rvalue, Statement};
+use semantic::*;
+use std::borrow::Cow;
+
+/// Index register of the indexed addressing modes
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct Index {
+    /// Register number, 0 to 15
+    pub reg: usize,
+    /// Use all 32 bits instead of the sign extended lower word
+    pub long: bool,
+}
+
+/// Operand addressed by the mode and register fields of an instruction.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Ea {
+    /// `dn`
+    DataReg(usize),
+    /// `an`
+    AddrReg(usize),
+    /// `(an)`
+    Indirect(usize),
+    /// `(an)+`
+    PostInc(usize),
+    /// `-(an)`
+    PreDec(usize),
+    /// `(d16,an)`
+    Disp(usize, i16),
+    /// `(d8,an,xn)`
+    Index(usize, i8, Index),
+    /// `(xxx).w` and `(xxx).l`. The short form is sign extended
+    Absolute(u32, bool),
+    /// `(d16,pc)`, with the resulting address
+    PcDisp(u32),
+    /// `(d8,pc,xn)`, with the address of the extension word and the displacement
+    PcIndex(u32, i8, Index),
+    /// `#imm`
+    Immediate(u32),
+}
+
+impl Ea {
+    /// Decodes the effective address in the 3-bit `mode` and `reg` fields. Extension words are
+    /// read from `words`. `size` is the operand size in bits.
+    pub fn read(mode: u16, reg: u16, size: usize, words: &mut Words) -> Result<Ea> {
+        let r = reg as usize & 7;
+
+        match (mode & 7, r) {
+            (0, _) => Ok(Ea::DataReg(r)),
+            (1, _) => Ok(Ea::AddrReg(r + 8)),
+            (2, _) => Ok(Ea::Indirect(r + 8)),
+            (3, _) => Ok(Ea::PostInc(r + 8)),
+            (4, _) => Ok(Ea::PreDec(r + 8)),
+            (5, _) => Ok(Ea::Disp(r + 8, words.word()? as i16)),
+            (6, _) => {
+                let (d, idx) = index(words)?;
+                Ok(Ea::Index(r + 8, d, idx))
+            }
+            (7, 0) => Ok(Ea::Absolute(sign_extend(words.word()? as u64, 16) as u32, true)),
+            (7, 1) => Ok(Ea::Absolute(words.long()?, false)),
+            (7, 2) => {
+                let base = words.address() as u32;
+                let d = words.word()? as i16;
+                Ok(Ea::PcDisp(base.wrapping_add(d as i32 as u32)))
+            }
+            (7, 3) => {
+                let base = words.address() as u32;
+                let (d, idx) = index(words)?;
+                Ok(Ea::PcIndex(base, d, idx))
+            }
+            (7, 4) => {
+                match size {
+                    8 => Ok(Ea::Immediate(words.word()? as u32 & 0xff)),
+                    16 => Ok(Ea::Immediate(words.word()? as u32)),
+                    _ => Ok(Ea::Immediate(words.long()?)),
+                }
+            }
+            _ => Err(format!("invalid 68000 addressing mode {}/{}", mode, reg).into()),
+        }
+    }
+
+    /// All modes except `an`.
+    pub fn is_data(&self) -> bool {
+        match self {
+            &Ea::AddrReg(_) => false,
+            _ => true,
+        }
+    }
+
+    /// All modes except the register direct ones.
+    pub fn is_memory(&self) -> bool {
+        match self {
+            &Ea::DataReg(_) | &Ea::AddrReg(_) => false,
+            _ => true,
+        }
+    }
+
+    /// All modes that can be written to.
+    pub fn is_alterable(&self) -> bool {
+        match self {
+            &Ea::PcDisp(_) | &Ea::PcIndex(..) | &Ea::Immediate(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Memory operands without implicit register updates and size. Used by `jmp`, `lea` and co.
+    pub fn is_control(&self) -> bool {
+        match self {
+            &Ea::Indirect(_) | &Ea::Disp(..) | &Ea::Index(..) | &Ea::Absolute(..) | &Ea::PcDisp(_) | &Ea::PcIndex(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Format string and operands for displaying the address.
+    pub fn format(&self, size: usize) -> (String, Vec<Rvalue>) {
+        match self {
+            &Ea::DataReg(r) | &Ea::AddrReg(r) => ("{u}".to_string(), vec![reg(r, 32)]),
+            &Ea::Indirect(r) => ("({u})".to_string(), vec![reg(r, 32)]),
+            &Ea::PostInc(r) => ("({u})+".to_string(), vec![reg(r, 32)]),
+            &Ea::PreDec(r) => ("-({u})".to_string(), vec![reg(r, 32)]),
+            &Ea::Disp(r, d) => ("({s},{u})".to_string(), vec![imm(d as u64, 16), reg(r, 32)]),
+            &Ea::Index(r, d, idx) => {
+                let fmt = format!("({{s}},{{u}},{{u}}.{})", if idx.long { "l" } else { "w" });
+                (fmt, vec![imm(d as u64, 8), reg(r, 32), reg(idx.reg, 32)])
+            }
+            &Ea::Absolute(a, short) => (format!("({{p:ram}}).{}", if short { "w" } else { "l" }), vec![Rvalue::new_u32(a)]),
+            &Ea::PcDisp(a) => ("({p:ram},pc)".to_string(), vec![Rvalue::new_u32(a)]),
+            &Ea::PcIndex(_, d, idx) => {
+                let fmt = format!("({{s}},pc,{{u}}.{})", if idx.long { "l" } else { "w" });
+                (fmt, vec![imm(d as u64, 8), reg(idx.reg, 32)])
+            }
+            &Ea::Immediate(v) => ("#{u}".to_string(), vec![imm(v as u64, size)]),
+        }
+    }
+
+    /// Computes the location the effective address refers to. Pre-decrement and post-increment
+    /// modes update the address register here, the address itself is kept in `ea:32`.
+    pub fn locate(&self, size: usize) -> Result<(Vec<Statement>, Loc)> {
+        // the stack pointer is always kept word aligned
+        let step = |r: usize| if r == SP && size == 8 { 2 } else { size as u64 / 8 };
+        let ea = rreil_lvalue!{ ea:32 };
+
+        match self {
+            &Ea::DataReg(r) | &Ea::AddrReg(r) => Ok((vec![], Loc::Reg(r))),
+            &Ea::Indirect(r) => Ok((vec![], Loc::Mem(reg(r, 32)))),
+            &Ea::PostInc(r) => {
+                let an = reg_lv(r);
+                let stmts = rreil!{
+                    mov (ea), (an);
+                    add (an), (an), [(step(r))]:32;
+                }?;
+
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+            &Ea::PreDec(r) => {
+                let an = reg_lv(r);
+                let stmts = rreil!{
+                    sub (an), (an), [(step(r))]:32;
+                    mov (ea), (an);
+                }?;
+
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+            &Ea::Disp(r, d) => {
+                let an = reg(r, 32);
+                let stmts = rreil!{ add (ea), (an), [(d as i32 as u32)]:32; }?;
+
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+            &Ea::Index(r, d, idx) => {
+                let mut stmts = index_value(idx)?;
+                let an = reg(r, 32);
+
+                stmts.extend(
+                    rreil!{
+                        add (ea), (an), [(d as i32 as u32)]:32;
+                        add (ea), (ea), idx:32;
+                    }?
+                );
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+            &Ea::Absolute(a, _) | &Ea::PcDisp(a) => Ok((vec![], Loc::Mem(Rvalue::new_u32(a)))),
+            &Ea::PcIndex(base, d, idx) => {
+                let mut stmts = index_value(idx)?;
+
+                stmts.extend(rreil!{ add (ea), idx:32, [(base.wrapping_add(d as i32 as u32))]:32; }?);
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+            &Ea::Immediate(v) => Ok((vec![], Loc::Imm(imm(v as u64, size)))),
+        }
+    }
+}
+
+// Reads a brief extension word.
+fn index(words: &mut Words) -> Result<(i8, Index)> {
+    let w = words.word()?;
+
+    if w & 0x0700 != 0 {
+        return Err(format!("68020 index extension word {:#06x}", w).into());
+    }
+
+    let idx = Index { reg: (w >> 12) as usize & 0xf, long: w & 0x0800 != 0 };
+    Ok((w as u8 as i8, idx))
+}
+
+// Index register value in `idx:32`.
+fn index_value(idx: Index) -> Result<Vec<Statement>> {
+    if idx.long {
+        rreil!{ mov idx:32, (reg(idx.reg, 32)); }
+    } else {
+        rreil!{ sext/32 idx:32, (reg(idx.reg, 16)); }
+    }
+}
+
+/// Operand location once its address is known.
+#[derive(Clone,PartialEq,Debug)]
+pub enum Loc {
+    Reg(usize),
+    Mem(Rvalue),
+    Imm(Rvalue),
+}
+
+impl Loc {
+    /// Reads `size` bits. Memory operands are loaded into `name:size`.
+    pub fn load(&self, size: usize, name: &'static str) -> Result<(Vec<Statement>, Rvalue)> {
+        match self {
+            &Loc::Reg(r) => Ok((vec![], reg(r, size))),
+            &Loc::Mem(ref addr) => {
+                let lv = Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: size };
+                Ok((vec![load(lv.clone(), addr.clone(), size)], lv.into()))
+            }
+            &Loc::Imm(ref v) => Ok((vec![], v.clone())),
+        }
+    }
+
+    /// Writes `v`. Register writes only change the lower bits.
+    pub fn store(&self, v: Rvalue) -> Result<Vec<Statement>> {
+        match self {
+            &Loc::Reg(r) => write_reg(r, v),
+            &Loc::Mem(ref addr) => {
+                let size = v.size().ok_or("store of undefined value")?;
+                Ok(vec![store(addr.clone(), v, size)])
+            }
+            &Loc::Imm(_) => Err("can't write to an immediate".into()),
+        }
+    }
+}
+
+/// Reads the `size` bits wide operand at `ea`. Memory operands are loaded into `name:size`.
+pub fn read(ea: &Ea, size: usize, name: &'static str) -> Result<(Vec<Statement>, Rvalue)> {
+    let (mut stmts, loc) = ea.locate(size)?;
+    let (load, v) = loc.load(size, name)?;
+
+    stmts.extend(load);
+    Ok((stmts, v))
+}
+
+/// Writes `v` to `ea`.
+pub fn write(ea: &Ea, v: Rvalue) -> Result<Vec<Statement>> {
+    let size = v.size().unwrap_or(32);
+    let (mut stmts, loc) = ea.locate(size)?;
+
+    stmts.extend(loc.store(v)?);
+    Ok(stmts)
+}
+
+/// Computes the address of a control addressing mode.
+pub fn address(ea: &Ea) -> Result<(Vec<Statement>, Rvalue)> {
+    if !ea.is_control() {
+        return Err(format!("{:?} is not a control addressing mode", ea).into());
+    }
+
+    match ea.locate(32)? {
+        (stmts, Loc::Mem(addr)) => Ok((stmts, addr)),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! RREIL code generation helpers for the 68000.
+
+use panopticon_core::{Endianess, Lvalue, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+pub static REGISTERS: [&'static str; 16] = [
+    "d0", "d1", "d2", "d3", "d4", "d5", "d6", "d7",
+    "a0", "a1", "a2", "a3", "a4", "a5", "a6", "sp",
+];
+
+/// Condition code suffixes in encoding order.
+pub static CONDITIONS: [&'static str; 16] = [
+    "t", "f", "hi", "ls", "cc", "cs", "ne", "eq",
+    "vc", "vs", "pl", "mi", "ge", "lt", "gt", "le",
+];
+
+/// Stack pointer, `a7`.
+pub const SP: usize = 15;
+
+pub type BinOp = fn(Rvalue, Rvalue) -> Operation<Rvalue>;
+
+/// Register `r` as assignee. Registers 0 to 7 are `d0` to `d7`, 8 to 15 are `a0` to `a7`.
+pub fn reg_lv(r: usize) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(REGISTERS[r & 0xf]), subscript: None, size: 32 }
+}
+
+/// Lower `size` bits of register `r`.
+pub fn reg(r: usize, size: usize) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(REGISTERS[r & 0xf]),
+        subscript: None,
+        offset: 0,
+        size: size,
+    }
+}
+
+/// Truncates `v` to `size` bits.
+pub fn mask(v: u64, size: usize) -> u64 {
+    if size >= 64 { v } else { v & ((1 << size) - 1) }
+}
+
+/// Constant of `size` bits.
+pub fn imm(v: u64, size: usize) -> Rvalue {
+    Rvalue::Constant { value: mask(v, size), size: size }
+}
+
+/// Sign extends the `bits` wide value `v` to 64 bits.
+pub fn sign_extend(v: u64, bits: usize) -> u64 {
+    let shift = 64 - bits;
+    (((v << shift) as i64) >> shift) as u64
+}
+
+/// Writes `v` into the lower bits of register `r`, leaving the rest untouched.
+pub fn write_reg(r: usize, v: Rvalue) -> Result<Vec<Statement>> {
+    let lv = reg_lv(r);
+
+    match v.size() {
+        Some(32) | None => rreil!{ mov (lv), (v); },
+        Some(_) => rreil!{ sel/0 (lv), (v); },
+    }
+}
+
+/// Loads `size` bits from `addr` into `lv`.
+pub fn load(lv: Lvalue, addr: Rvalue, size: usize) -> Statement {
+    Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Big, size, addr), assignee: lv }
+}
+
+/// Stores the `size` bits wide value `v` at `addr`.
+pub fn store(addr: Rvalue, v: Rvalue, size: usize) -> Statement {
+    Statement { op: Operation::Store(Cow::Borrowed("ram"), Endianess::Big, size, addr, v), assignee: Lvalue::Undefined }
+}
+
+/// Decrements the stack pointer and writes the 32-bit value `v` onto the stack.
+pub fn push(v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{ sub sp:32, sp:32, [4]:32; }?;
+
+    stmts.push(store(reg(SP, 32), v, 32));
+    Ok(stmts)
+}
+
+/// Pops a 32-bit value off the stack into `lv`.
+pub fn pop(lv: Lvalue) -> Result<Vec<Statement>> {
+    let mut stmts = vec![load(lv, reg(SP, 32), 32)];
+
+    stmts.extend(rreil!{ add sp:32, sp:32, [4]:32; }?);
+    Ok(stmts)
+}
+
+/// Sets N and Z according to the `size` bits wide value `v`.
+pub fn nz(v: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    rreil!{
+        mov N:1, (v.extract(1, size - 1)?);
+        cmpeq Z:1, (v), [0]:(size);
+    }
+}
+
+/// Flags after moves and logic operations: N and Z are set according to `v`, V and C are cleared.
+pub fn logic_flags(v: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    let mut stmts = nz(v, size)?;
+
+    stmts.extend(
+        rreil!{
+            mov V:1, [0]:1;
+            mov C:1, [0]:1;
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Computes `a op b` into `res:size` and sets the flags like logic instructions do.
+pub fn logic(op: BinOp, a: Rvalue, b: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    let res = rreil_lvalue!{ res:(size) };
+    let mut stmts = vec![Statement { op: op(a, b), assignee: res.clone() }];
+
+    stmts.extend(logic_flags(res.into(), size)?);
+    Ok(stmts)
+}
+
+/// Computes `a + b` or `a - b` into `res:size` and sets N, Z, V and C. If `extend` is set X is
+/// added or subtracted as well and Z is only ever cleared, like `addx` and `subx` do. X is set
+/// to the carry if `set_x` is true.
+pub fn arith(a: Rvalue, b: Rvalue, size: usize, subtract: bool, extend: bool, set_x: bool) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        zext/(size + 1) x:(size + 1), (a);
+        zext/(size + 1) y:(size + 1), (b);
+        zext/(size + 1) cy:(size + 1), X:1;
+    }?;
+
+    if !extend {
+        stmts.extend(rreil!{ mov cy:(size + 1), [0]:(size + 1); }?);
+    }
+
+    if subtract {
+        stmts.extend(
+            rreil!{
+                sub wide:(size + 1), x:(size + 1), y:(size + 1);
+                sub wide:(size + 1), wide:(size + 1), cy:(size + 1);
+                mov res:(size), wide:(size);
+                xor ov:(size), (a), (b);
+                xor ov2:(size), (a), res:(size);
+            }?
+        );
+    } else {
+        stmts.extend(
+            rreil!{
+                add wide:(size + 1), x:(size + 1), y:(size + 1);
+                add wide:(size + 1), wide:(size + 1), cy:(size + 1);
+                mov res:(size), wide:(size);
+                xor ov:(size), (a), res:(size);
+                xor ov2:(size), (b), res:(size);
+            }?
+        );
+    }
+
+    stmts.extend(
+        rreil!{
+            and ov:(size), ov:(size), ov2:(size);
+            mov V:1, ov:1/(size - 1);
+            mov C:1, wide:1/(size);
+            mov N:1, res:1/(size - 1);
+        }?
+    );
+
+    if extend {
+        stmts.extend(
+            rreil!{
+                cmpeq zero:1, res:(size), [0]:(size);
+                and Z:1, Z:1, zero:1;
+            }?
+        );
+    } else {
+        stmts.extend(rreil!{ cmpeq Z:1, res:(size), [0]:(size); }?);
+    }
+
+    if set_x {
+        stmts.extend(rreil!{ mov X:1, C:1; }?);
+    }
+
+    Ok(stmts)
+}
+
+/// Result of `arith`.
+pub fn result(size: usize) -> Rvalue {
+    rreil_rvalue!{ res:(size) }
+}
+
+/// Evaluates condition `cc` into a 1-bit value. `t` and `f` are constants.
+pub fn condition(cc: usize) -> Result<(Vec<Statement>, Rvalue)> {
+    let stmts = match cc & 0xf {
+        0 => return Ok((vec![], Rvalue::new_bit(1))),
+        1 => return Ok((vec![], Rvalue::new_bit(0))),
+        2 => {
+            rreil!{
+                or cond:1, C:1, Z:1;
+                xor cond:1, cond:1, [1]:1;
+            }?
+        }
+        3 => rreil!{ or cond:1, C:1, Z:1; }?,
+        4 => rreil!{ xor cond:1, C:1, [1]:1; }?,
+        5 => rreil!{ mov cond:1, C:1; }?,
+        6 => rreil!{ xor cond:1, Z:1, [1]:1; }?,
+        7 => rreil!{ mov cond:1, Z:1; }?,
+        8 => rreil!{ xor cond:1, V:1, [1]:1; }?,
+        9 => rreil!{ mov cond:1, V:1; }?,
+        10 => rreil!{ xor cond:1, N:1, [1]:1; }?,
+        11 => rreil!{ mov cond:1, N:1; }?,
+        12 => {
+            rreil!{
+                xor cond:1, N:1, V:1;
+                xor cond:1, cond:1, [1]:1;
+            }?
+        }
+        13 => rreil!{ xor cond:1, N:1, V:1; }?,
+        14 => {
+            rreil!{
+                xor cond:1, N:1, V:1;
+                or cond:1, cond:1, Z:1;
+                xor cond:1, cond:1, [1]:1;
+            }?
+        }
+        _ => {
+            rreil!{
+                xor cond:1, N:1, V:1;
+                or cond:1, cond:1, Z:1;
+            }?
+        }
+    };
+
+    Ok((stmts, rreil_rvalue!{ cond:1 }))
+}
+
+/// Assembles the 16-bit status register from `srh` and the condition codes into `sr:16`.
+pub fn status_register() -> Result<Vec<Statement>> {
+    rreil!{
+        zext/16 sr:16, C:1;
+        sel/1 sr:16, V:1;
+        sel/2 sr:16, Z:1;
+        sel/3 sr:16, N:1;
+        sel/4 sr:16, X:1;
+        sel/8 sr:16, srh:8;
+    }
+}
+
+/// Sets the condition codes from the lower five bits of `v`. Sets `srh` from bits 8 to 15 as
+/// well if `system` is true.
+pub fn set_status_register(v: Rvalue, system: bool) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov C:1, (v.extract(1, 0)?);
+        mov V:1, (v.extract(1, 1)?);
+        mov Z:1, (v.extract(1, 2)?);
+        mov N:1, (v.extract(1, 3)?);
+        mov X:1, (v.extract(1, 4)?);
+    }?;
+
+    if system {
+        stmts.extend(rreil!{ mov srh:8, (v.extract(8, 8)?); }?);
+    }
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sane(stmts: Vec<Statement>) {
+        for s in stmts.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn statements_are_sane() {
+        for &sz in [8, 16, 32].iter() {
+            sane(arith(reg(0, sz), reg(1, sz), sz, false, false, true).unwrap());
+            sane(arith(reg(0, sz), imm(1, sz), sz, true, true, true).unwrap());
+            sane(logic_flags(reg(2, sz), sz).unwrap());
+            sane(logic(Operation::ExclusiveOr, reg(2, sz), imm(!0, sz), sz).unwrap());
+            sane(write_reg(3, reg(4, sz)).unwrap());
+        }
+        for cc in 0..16 {
+            sane(condition(cc).unwrap().0);
+        }
+        sane(push(reg(8, 32)).unwrap());
+        sane(pop(reg_lv(8)).unwrap());
+        sane(status_register().unwrap());
+        sane(set_status_register(rreil_rvalue!{ sr:16 }, true).unwrap());
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(reg_lv(SP), rreil_lvalue!{ sp:32 });
+        assert_eq!(reg(9, 16), rreil_rvalue!{ a1:16 });
+        assert_eq!(sign_extend(0xff80, 16), 0xffff_ffff_ffff_ff80);
+        assert_eq!(imm(0x1ff, 8), Rvalue::Constant { value: 0xff, size: 8 });
+    }
+}
@@ -0,0 +1,82 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_m68k;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Architecture, Function, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_m68k::{M68k, Model};
+
+fn region(words: &[u16]) -> Region {
+    Region::wrap("ram".to_string(), words.iter().flat_map(|w| vec![(w >> 8) as u8, *w as u8]).collect())
+}
+
+// Counts down the argument, calling a subroutine on each iteration
+fn function() -> Region {
+    region(
+        &[
+            0x4e56, 0x0000, // link a6,#0
+            0x202e, 0x0008, // move.l (8,a6),d0
+            0x6708, // beq.s 0x12
+            0x5380, // subq.l #1,d0
+            0x6100, 0x0008, // bsr.w 0x16
+            0x60f8, // bra.s 0xa
+            0x4e5e, // unlk a6
+            0x4e75, // rts
+            0x4e75, // rts
+        ],
+    )
+}
+
+#[test]
+fn loop_and_call() {
+    let reg = function();
+    let func = Function::new::<M68k>(0, &reg, None, Model::M68000).unwrap();
+    let mut starts = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    starts.sort();
+
+    assert_eq!(starts, vec![0x0, 0xa, 0x12]);
+    assert_eq!(func.cfg().num_edges(), 3);
+    assert_eq!(func.end(), 0x16);
+    assert_eq!(func.collect_call_addresses(), vec![0x16]);
+}
+
+#[test]
+fn reset_vector() {
+    let mut words = vec![0u16; 0x200];
+
+    // initial stack pointer and program counter
+    words[0..4].copy_from_slice(&[0x0001, 0x0000, 0x0000, 0x0100]);
+    words[0x80] = 0x4e75;
+
+    let reg = region(&words);
+    let entries = M68k::prepare(&reg, &Model::M68000).unwrap();
+
+    assert_eq!(entries.iter().map(|e| (e.0, e.1)).collect::<Vec<_>>(), vec![("RESET", 0x100)]);
+}
+
+#[test]
+fn unaligned() {
+    let reg = function();
+
+    assert!(M68k::decode(&reg, 1, &Model::M68000).is_err());
+    assert_eq!(M68k::decode(&reg, 0xa, &Model::M68000).unwrap().mnemonics[0].opcode, "subq.l");
+}
@@ -0,0 +1,113 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use decode::{self, Flow};
+use panopticon_core::{Architecture, Guard, Match, Region, Result, Rvalue};
+
+#[derive(Clone,Debug)]
+pub enum Mcs51 {}
+
+/// CPU model
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Model {
+    /// 8051 and 8031: 128 bytes of internal RAM, two timers
+    I8051,
+    /// 8052 and 8032: 256 bytes of internal RAM and a third timer
+    I8052,
+}
+
+/// CPU configuration.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct Cpu {
+    pub model: Model,
+    /// Active register bank, 0 to 3
+    pub bank: u8,
+}
+
+impl Cpu {
+    /// CPU state after reset, register bank 0 is active.
+    pub fn new(model: Model) -> Cpu {
+        Cpu { model: model, bank: 0 }
+    }
+}
+
+/// Interrupt vectors. The 8051 jumps to these addresses instead of reading a pointer from them.
+const VECTORS: [(u64, &'static str, &'static str); 7] = [
+    (0x00, "RESET", "Reset vector"),
+    (0x03, "IE0", "External interrupt 0"),
+    (0x0b, "TF0", "Timer 0 overflow"),
+    (0x13, "IE1", "External interrupt 1"),
+    (0x1b, "TF1", "Timer 1 overflow"),
+    (0x23, "SERIAL", "Serial port interrupt"),
+    (0x2b, "TF2", "Timer 2 overflow"),
+];
+
+impl Architecture for Mcs51 {
+    type Token = u8;
+    type Configuration = Cpu;
+
+    fn prepare(reg: &Region, cpu: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        let mut ret = vec![];
+
+        for &(addr, name, comment) in VECTORS.iter() {
+            if addr == 0x2b && cpu.model == Model::I8051 {
+                continue;
+            }
+
+            // unused vectors are usually left erased
+            match reg.iter().seek(addr).next() {
+                Some(Some(b)) if addr == 0 || b != 0xff => ret.push((name, addr, comment)),
+                _ => {}
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn decode(reg: &Region, start: u64, cpu: &Self::Configuration) -> Result<Match<Self>> {
+        let (insn, tokens) = decode::read(reg, start, cpu)?;
+        let len = tokens.len() as u64;
+        let next = Rvalue::new_u16((start + len) as u16);
+        let jumps = match insn.flow.clone() {
+            Flow::Next | Flow::Call(_) => vec![(start, next, Guard::always())],
+            Flow::Jump(tgt) => vec![(start, tgt, Guard::always())],
+            Flow::Branch(tgt, flag) => {
+                let guard = Guard::from_flag(&flag)?;
+                vec![(start, tgt, guard.clone()), (start, next, guard.negation())]
+            }
+            Flow::Return => vec![],
+        };
+        let mut cfg = cpu.clone();
+
+        if let Some(bank) = insn.bank {
+            cfg.bank = bank;
+        }
+
+        debug!("disass @ {:#x}: {} {:?}", start, insn.opcode, insn.flow);
+
+        Ok(
+            Match::<Mcs51> {
+                tokens: tokens,
+                mnemonics: vec![insn.mnemonic(start, len)?],
+                jumps: jumps,
+                configuration: cfg,
+            }
+        )
+    }
+}
@@ -0,0 +1,762 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! 8051 instruction decoder.
+
+use architecture::Cpu;
+use panopticon_core::{Lvalue, Mnemonic, Operation, Region, Result, Rvalue, Statement};
+use semantic::*;
+use std::borrow::Cow;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Jumps to the target
+    Jump(Rvalue),
+    /// Jumps to the target if the flag is set, falls through otherwise
+    Branch(Rvalue, Rvalue),
+    /// Calls the target and falls through
+    Call(Rvalue),
+    /// Returns from a subroutine or interrupt
+    Return,
+}
+
+/// A single decoded instruction.
+#[derive(Clone,Debug)]
+pub struct Insn {
+    pub opcode: String,
+    pub format: String,
+    pub operands: Vec<Rvalue>,
+    pub statements: Vec<Statement>,
+    pub flow: Flow,
+    /// Register bank selected by the instruction, if it sets RS0 and RS1 to known values
+    pub bank: Option<u8>,
+}
+
+impl Insn {
+    fn new(opcode: &str, operands: Vec<Operand>, statements: Vec<Statement>) -> Insn {
+        let format = operands.iter().map(|x| x.0.clone()).collect::<Vec<_>>().join(", ");
+
+        Insn {
+            opcode: opcode.to_string(),
+            format: format,
+            operands: operands.into_iter().flat_map(|x| x.1).collect(),
+            statements: statements,
+            flow: Flow::Next,
+            bank: None,
+        }
+    }
+
+    fn flow(mut self, flow: Flow) -> Insn {
+        self.flow = flow;
+        self
+    }
+
+    fn bank(mut self, bank: Option<u8>) -> Insn {
+        self.bank = bank;
+        self
+    }
+
+    pub fn mnemonic(&self, addr: u64, len: u64) -> Result<Mnemonic> {
+        Mnemonic::new(addr..addr + len, self.opcode.clone(), self.format.clone(), self.operands.iter(), self.statements.iter())
+    }
+}
+
+/// Reads the opcode and operand bytes of an instruction.
+pub struct Bytes<'a> {
+    region: &'a Region,
+    start: u64,
+    pub tokens: Vec<u8>,
+}
+
+impl<'a> Bytes<'a> {
+    pub fn new(region: &'a Region, start: u64) -> Bytes<'a> {
+        Bytes { region: region, start: start, tokens: vec![] }
+    }
+
+    /// Address of the next byte.
+    pub fn address(&self) -> u64 {
+        self.start + self.tokens.len() as u64
+    }
+
+    pub fn byte(&mut self) -> Result<u8> {
+        match self.region.iter().seek(self.address()).next() {
+            Some(Some(b)) => {
+                self.tokens.push(b);
+                Ok(b)
+            }
+            _ => Err(format!("8051 instruction at {:#x} truncated", self.start).into()),
+        }
+    }
+
+    /// Reads a relative jump offset and returns the target.
+    fn relative(&mut self) -> Result<u16> {
+        let rel = self.byte()? as i8;
+        Ok((self.address() as i64 + rel as i64) as u16)
+    }
+
+    /// Reads a big endian 16-bit address or immediate.
+    fn word(&mut self) -> Result<u16> {
+        let hi = self.byte()? as u16;
+        Ok((hi << 8) | self.byte()? as u16)
+    }
+}
+
+type Operand = (String, Vec<Rvalue>);
+
+fn text(s: &str) -> Operand {
+    (s.to_string(), vec![])
+}
+
+fn code(addr: u16) -> Operand {
+    ("{c:code}".to_string(), vec![Rvalue::new_u16(addr)])
+}
+
+/// Operand of most arithmetic and data transfer instructions.
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Loc {
+    A,
+    /// `Rn` of the current bank
+    Reg(u8),
+    /// Internal RAM below 0x80 or a special function register
+    Direct(u8),
+    /// Internal RAM addressed by `R0` or `R1`
+    Indirect(u8),
+    Imm(u8),
+}
+
+impl Loc {
+    fn operand(&self) -> Operand {
+        match *self {
+            Loc::A => text("A"),
+            Loc::Reg(r) => text(&format!("R{}", r)),
+            Loc::Direct(addr) => {
+                if let Some(name) = sfr_name(addr) {
+                    text(name)
+                } else if addr < 0x80 {
+                    ("{p:idata}".to_string(), vec![byte(addr)])
+                } else {
+                    ("{u}".to_string(), vec![byte(addr)])
+                }
+            }
+            Loc::Indirect(r) => text(&format!("@R{}", r)),
+            Loc::Imm(v) => ("#{u}".to_string(), vec![byte(v)]),
+        }
+    }
+
+    /// Reads the operand. Memory is loaded into `tmp`.
+    fn read(&self, bank: u8, tmp: &'static str) -> Result<(Vec<Statement>, Rvalue)> {
+        match *self {
+            Loc::A => Ok((vec![], rreil_rvalue!{ a:8 })),
+            Loc::Reg(r) => Ok((vec![], register(bank, r).into())),
+            Loc::Direct(addr) => read_direct(addr, tmp),
+            Loc::Indirect(r) => {
+                let lv = Lvalue::Variable { name: Cow::Borrowed(tmp), subscript: None, size: 8 };
+                Ok((vec![load(IDATA, register(bank, r).into(), lv.clone())], lv.into()))
+            }
+            Loc::Imm(v) => Ok((vec![], byte(v))),
+        }
+    }
+
+    fn write(&self, bank: u8, v: Rvalue) -> Result<Vec<Statement>> {
+        match *self {
+            Loc::A => rreil!{ mov a:8, (v); },
+            Loc::Reg(r) => rreil!{ mov (register(bank, r)), (v); },
+            Loc::Direct(addr) => write_direct(addr, v),
+            Loc::Indirect(r) => Ok(vec![store(IDATA, register(bank, r).into(), v)]),
+            Loc::Imm(_) => Err("8051 immediate operand written".into()),
+        }
+    }
+}
+
+/// Operand in the low nibble of opcodes `x4` to `xf`.
+fn source(op: u8, b: &mut Bytes) -> Result<Loc> {
+    match op & 0xf {
+        4 => Ok(Loc::Imm(b.byte()?)),
+        5 => Ok(Loc::Direct(b.byte()?)),
+        6 | 7 => Ok(Loc::Indirect(op & 1)),
+        _ => Ok(Loc::Reg(op & 7)),
+    }
+}
+
+fn bit_operand(bit: u8, complement: bool) -> Operand {
+    let prefix = if complement { "/" } else { "" };
+    let addr = bit_byte(bit);
+
+    match sfr_name(addr) {
+        Some(_) if addr == PSW => text(&format!("{}{}", prefix, PSW_BITS[(bit & 7) as usize])),
+        Some(name) if bit >= 0x80 => text(&format!("{}{}.{}", prefix, name, bit & 7)),
+        _ => (format!("{}{{u}}", prefix), vec![byte(bit)]),
+    }
+}
+
+/// Register bank after writing `v` to the PSW.
+fn bank_of(psw: u8) -> Option<u8> {
+    Some((psw >> 3) & 3)
+}
+
+fn unknown(op: u8) -> Result<Insn> {
+    Err(format!("unknown 8051 instruction {:#04x}", op).into())
+}
+
+/// Decodes the instruction at `addr`.
+pub fn read(region: &Region, addr: u64, cpu: &Cpu) -> Result<(Insn, Vec<u8>)> {
+    let mut b = Bytes::new(region, addr);
+    let op = b.byte()?;
+    let insn = decode(op, &mut b, cpu.bank)?;
+
+    Ok((insn, b.tokens))
+}
+
+fn decode(op: u8, b: &mut Bytes, bank: u8) -> Result<Insn> {
+    match op {
+        0x00 => Ok(Insn::new("nop", vec![], vec![])),
+        _ if op & 0x0f == 0x01 => {
+            let lo = b.byte()? as u16;
+            let target = (b.address() as u16 & 0xf800) | ((op as u16 & 0xe0) << 3) | lo;
+
+            if op & 0x10 == 0 {
+                Ok(Insn::new("ajmp", vec![code(target)], vec![]).flow(Flow::Jump(Rvalue::new_u16(target))))
+            } else {
+                call("acall", target, b.address() as u16)
+            }
+        }
+        0x02 => {
+            let target = b.word()?;
+            Ok(Insn::new("ljmp", vec![code(target)], vec![]).flow(Flow::Jump(Rvalue::new_u16(target))))
+        }
+        0x12 => {
+            let target = b.word()?;
+            call("lcall", target, b.address() as u16)
+        }
+        0x80 => {
+            let target = b.relative()?;
+            Ok(Insn::new("sjmp", vec![code(target)], vec![]).flow(Flow::Jump(Rvalue::new_u16(target))))
+        }
+        0x73 => {
+            let stmts = rreil!{
+                zext/16 tgt:16, a:8;
+                add tgt:16, tgt:16, dptr:16;
+            }?;
+            Ok(Insn::new("jmp", vec![text("@A+DPTR")], stmts).flow(Flow::Jump(rreil_rvalue!{ tgt:16 })))
+        }
+        0x22 | 0x32 => {
+            let name = if op == 0x22 { "ret" } else { "reti" };
+            Ok(Insn::new(name, vec![], pop_pc()?).flow(Flow::Return))
+        }
+        0x10 | 0x20 | 0x30 => {
+            let bit = b.byte()?;
+            let target = b.relative()?;
+            let (mut stmts, v) = read_bit(bit)?;
+
+            stmts.extend(rreil!{ mov cond:1, (v); }?);
+            let name = match op {
+                0x10 => {
+                    stmts.extend(write_bit(bit, rreil_rvalue!{ [0]:1 })?);
+                    "jbc"
+                }
+                0x20 => "jb",
+                _ => {
+                    stmts.extend(rreil!{ xor cond:1, cond:1, [1]:1; }?);
+                    "jnb"
+                }
+            };
+
+            branch(name, vec![bit_operand(bit, false), code(target)], stmts, target)
+        }
+        0x40 | 0x50 | 0x60 | 0x70 => {
+            let target = b.relative()?;
+            let (name, mut stmts) = match op {
+                0x40 | 0x50 => (if op == 0x40 { "jc" } else { "jnc" }, rreil!{ mov cond:1, CY:1; }?),
+                _ => (if op == 0x60 { "jz" } else { "jnz" }, rreil!{ cmpeq cond:1, a:8, [0]:8; }?),
+            };
+
+            if op == 0x50 || op == 0x70 {
+                stmts.extend(rreil!{ xor cond:1, cond:1, [1]:1; }?);
+            }
+            branch(name, vec![code(target)], stmts, target)
+        }
+        0xb4...0xbf => {
+            let (x, y) = if op & 0xf < 6 {
+                (Loc::A, source(op, b)?)
+            } else {
+                (source(op, b)?, Loc::Imm(b.byte()?))
+            };
+            let target = b.relative()?;
+            let (mut stmts, xv) = x.read(bank, "cj_a")?;
+            let (s, yv) = y.read(bank, "cj_b")?;
+
+            stmts.extend(s);
+            stmts.extend(
+                rreil!{
+                    cmpltu CY:1, (xv), (yv);
+                    cmpeq cond:1, (xv), (yv);
+                    xor cond:1, cond:1, [1]:1;
+                }?
+            );
+            branch("cjne", vec![x.operand(), y.operand(), code(target)], stmts, target)
+        }
+        0xd5 | 0xd8...0xdf => {
+            let dst = if op == 0xd5 { Loc::Direct(b.byte()?) } else { Loc::Reg(op & 7) };
+            let target = b.relative()?;
+            let (mut stmts, v) = dst.read(bank, "dj")?;
+
+            stmts.extend(rreil!{ sub dj:8, (v), [1]:8; }?);
+            stmts.extend(dst.write(bank, rreil_rvalue!{ dj:8 })?);
+            stmts.extend(
+                rreil!{
+                    cmpeq cond:1, dj:8, [0]:8;
+                    xor cond:1, cond:1, [1]:1;
+                }?
+            );
+            branch("djnz", vec![dst.operand(), code(target)], stmts, target)
+        }
+
+        // arithmetic
+        0x24...0x2f | 0x34...0x3f | 0x94...0x9f => {
+            let src = source(op, b)?;
+            let (mut stmts, v) = src.read(bank, "src")?;
+            let name = match op >> 4 {
+                2 => {
+                    stmts.extend(add(v, false)?);
+                    "add"
+                }
+                3 => {
+                    stmts.extend(add(v, true)?);
+                    "addc"
+                }
+                _ => {
+                    stmts.extend(subb(v)?);
+                    "subb"
+                }
+            };
+
+            Ok(Insn::new(name, vec![text("A"), src.operand()], stmts))
+        }
+        0x04...0x0f | 0x14...0x1f => {
+            let dst = if op & 0xf == 4 { Loc::A } else { source(op, b)? };
+            let (mut stmts, v) = dst.read(bank, "tmp")?;
+            let name = if op < 0x10 {
+                stmts.extend(rreil!{ add res:8, (v), [1]:8; }?);
+                "inc"
+            } else {
+                stmts.extend(rreil!{ sub res:8, (v), [1]:8; }?);
+                "dec"
+            };
+
+            stmts.extend(dst.write(bank, rreil_rvalue!{ res:8 })?);
+            Ok(Insn::new(name, vec![dst.operand()], stmts))
+        }
+        0xa3 => Ok(Insn::new("inc", vec![text("DPTR")], rreil!{ add dptr:16, dptr:16, [1]:16; }?)),
+        0xa4 => {
+            let stmts = rreil!{
+                zext/16 mul_a:16, a:8;
+                zext/16 mul_b:16, b:8;
+                mul mul_a:16, mul_a:16, mul_b:16;
+                mov a:8, mul_a:8;
+                mov b:8, mul_a:8/8;
+                cmpeq OV:1, b:8, [0]:8;
+                xor OV:1, OV:1, [1]:1;
+                mov CY:1, [0]:1;
+            }?;
+            Ok(Insn::new("mul", vec![text("AB")], stmts))
+        }
+        0x84 => {
+            let stmts = rreil!{
+                cmpeq OV:1, b:8, [0]:8;
+                div div_q:8, a:8, b:8;
+                mod div_r:8, a:8, b:8;
+                mov a:8, div_q:8;
+                mov b:8, div_r:8;
+                mov CY:1, [0]:1;
+            }?;
+            Ok(Insn::new("div", vec![text("AB")], stmts))
+        }
+        0xd4 => {
+            let stmts = rreil!{
+                mov a:8, ?;
+                mov CY:1, ?;
+            }?;
+            Ok(Insn::new("da", vec![text("A")], stmts))
+        }
+
+        // logic
+        0x42...0x4f | 0x52...0x5f | 0x62...0x6f => {
+            let (dst, src) = match op & 0xf {
+                2 => (Loc::Direct(b.byte()?), Loc::A),
+                3 => {
+                    let dst = Loc::Direct(b.byte()?);
+                    (dst, Loc::Imm(b.byte()?))
+                }
+                _ => (Loc::A, source(op, b)?),
+            };
+            let (mut stmts, x) = dst.read(bank, "dst")?;
+            let (s, y) = src.read(bank, "src")?;
+            let (name, op_fn, psw_fn): (&str, fn(Rvalue, Rvalue) -> Operation<Rvalue>, fn(u8, u8) -> u8) = match op >> 4 {
+                4 => ("orl", Operation::InclusiveOr, |a, b| a | b),
+                5 => ("anl", Operation::And, |a, b| a & b),
+                _ => ("xrl", Operation::ExclusiveOr, |a, b| a ^ b),
+            };
+            let new_bank = match (dst, src) {
+                (Loc::Direct(PSW), Loc::Imm(v)) => bank_of(psw_fn(bank << 3, v)),
+                _ => None,
+            };
+
+            stmts.extend(s);
+            stmts.push(Statement { op: op_fn(x, y), assignee: rreil_lvalue!{ res:8 } });
+            stmts.extend(dst.write(bank, rreil_rvalue!{ res:8 })?);
+            Ok(Insn::new(name, vec![dst.operand(), src.operand()], stmts).bank(new_bank))
+        }
+        0xe4 => Ok(Insn::new("clr", vec![text("A")], rreil!{ mov a:8, [0]:8; }?)),
+        0xf4 => Ok(Insn::new("cpl", vec![text("A")], rreil!{ xor a:8, a:8, [0xff]:8; }?)),
+        0x03 | 0x13 | 0x23 | 0x33 => {
+            let (name, stmts) = match op {
+                0x03 => {
+                    ("rr", rreil!{
+                        shl rot:8, a:8, [7]:8;
+                        shr a:8, a:8, [1]:8;
+                        or a:8, a:8, rot:8;
+                    }?)
+                }
+                0x13 => {
+                    ("rrc", rreil!{
+                        mov rot_c:1, a:1;
+                        shr a:8, a:8, [1]:8;
+                        sel/7 a:8, CY:1;
+                        mov CY:1, rot_c:1;
+                    }?)
+                }
+                0x23 => {
+                    ("rl", rreil!{
+                        shr rot:8, a:8, [7]:8;
+                        shl a:8, a:8, [1]:8;
+                        or a:8, a:8, rot:8;
+                    }?)
+                }
+                _ => {
+                    ("rlc", rreil!{
+                        mov rot_c:1, a:1/7;
+                        shl a:8, a:8, [1]:8;
+                        sel/0 a:8, CY:1;
+                        mov CY:1, rot_c:1;
+                    }?)
+                }
+            };
+            Ok(Insn::new(name, vec![text("A")], stmts))
+        }
+        0xc4 => {
+            let stmts = rreil!{
+                shl rot:8, a:8, [4]:8;
+                shr a:8, a:8, [4]:8;
+                or a:8, a:8, rot:8;
+            }?;
+            Ok(Insn::new("swap", vec![text("A")], stmts))
+        }
+
+        // boolean
+        0xc3 => Ok(Insn::new("clr", vec![text("C")], rreil!{ mov CY:1, [0]:1; }?)),
+        0xd3 => Ok(Insn::new("setb", vec![text("C")], rreil!{ mov CY:1, [1]:1; }?)),
+        0xb3 => Ok(Insn::new("cpl", vec![text("C")], rreil!{ xor CY:1, CY:1, [1]:1; }?)),
+        0xb2 | 0xc2 | 0xd2 => {
+            let bit = b.byte()?;
+            let (name, mut stmts, v) = match op {
+                0xc2 => ("clr", vec![], rreil_rvalue!{ [0]:1 }),
+                0xd2 => ("setb", vec![], rreil_rvalue!{ [1]:1 }),
+                _ => {
+                    let (mut stmts, v) = read_bit(bit)?;
+                    stmts.extend(rreil!{ xor cpl:1, (v), [1]:1; }?);
+                    ("cpl", stmts, rreil_rvalue!{ cpl:1 })
+                }
+            };
+            let new_bank = match (bit_byte(bit), bit & 7, &v) {
+                (PSW, pos @ 3...4, &Rvalue::Constant { value, .. }) => {
+                    let mask = 1 << (pos - 3);
+                    Some(if value == 1 { bank | mask } else { bank & !mask })
+                }
+                (PSW, pos @ 3...4, _) => Some(bank ^ (1 << (pos - 3))),
+                _ => None,
+            };
+
+            stmts.extend(write_bit(bit, v)?);
+            Ok(Insn::new(name, vec![bit_operand(bit, false)], stmts).bank(new_bank))
+        }
+        0xa2 => {
+            let bit = b.byte()?;
+            let (mut stmts, v) = read_bit(bit)?;
+
+            stmts.extend(rreil!{ mov CY:1, (v); }?);
+            Ok(Insn::new("mov", vec![text("C"), bit_operand(bit, false)], stmts))
+        }
+        0x92 => {
+            let bit = b.byte()?;
+            Ok(Insn::new("mov", vec![bit_operand(bit, false), text("C")], write_bit(bit, rreil_rvalue!{ CY:1 })?))
+        }
+        0x72 | 0x82 | 0xa0 | 0xb0 => {
+            let bit = b.byte()?;
+            let complement = op == 0xa0 || op == 0xb0;
+            let (mut stmts, v) = read_bit(bit)?;
+
+            stmts.extend(rreil!{ mov bit:1, (v); }?);
+            if complement {
+                stmts.extend(rreil!{ xor bit:1, bit:1, [1]:1; }?);
+            }
+
+            let name = if op == 0x72 || op == 0xa0 {
+                stmts.extend(rreil!{ or CY:1, CY:1, bit:1; }?);
+                "orl"
+            } else {
+                stmts.extend(rreil!{ and CY:1, CY:1, bit:1; }?);
+                "anl"
+            };
+            Ok(Insn::new(name, vec![text("C"), bit_operand(bit, complement)], stmts))
+        }
+
+        // data transfer
+        0x74...0x7f | 0xe5...0xef | 0xf5...0xff | 0x86...0x8f | 0xa6...0xaf => {
+            let (dst, src) = match op >> 4 {
+                7 => {
+                    let dst = if op == 0x74 { Loc::A } else { source(op, b)? };
+                    (dst, Loc::Imm(b.byte()?))
+                }
+                0xe => (Loc::A, source(op, b)?),
+                0xf => (source(op, b)?, Loc::A),
+                8 => {
+                    let src = source(op, b)?;
+                    (Loc::Direct(b.byte()?), src)
+                }
+                _ => {
+                    let dst = source(op, b)?;
+                    (dst, Loc::Direct(b.byte()?))
+                }
+            };
+            let (mut stmts, v) = src.read(bank, "src")?;
+            let new_bank = match (dst, src) {
+                (Loc::Direct(PSW), Loc::Imm(v)) => bank_of(v),
+                _ => None,
+            };
+
+            stmts.extend(dst.write(bank, v)?);
+            Ok(Insn::new("mov", vec![dst.operand(), src.operand()], stmts).bank(new_bank))
+        }
+        0x85 => {
+            let src = Loc::Direct(b.byte()?);
+            let dst = Loc::Direct(b.byte()?);
+            let (mut stmts, v) = src.read(bank, "src")?;
+
+            stmts.extend(dst.write(bank, v)?);
+            Ok(Insn::new("mov", vec![dst.operand(), src.operand()], stmts))
+        }
+        0x90 => {
+            let v = b.word()?;
+            let stmts = rreil!{ mov dptr:16, [v]:16; }?;
+            Ok(Insn::new("mov", vec![text("DPTR"), ("#{u}".to_string(), vec![Rvalue::new_u16(v)])], stmts))
+        }
+        0x83 | 0x93 => {
+            let (base, name) = if op == 0x83 {
+                (Rvalue::new_u16(b.address() as u16), "@A+PC")
+            } else {
+                (rreil_rvalue!{ dptr:16 }, "@A+DPTR")
+            };
+            let mut stmts = rreil!{
+                zext/16 addr:16, a:8;
+                add addr:16, addr:16, (base);
+            }?;
+
+            stmts.push(load(CODE, rreil_rvalue!{ addr:16 }, rreil_lvalue!{ a:8 }));
+            Ok(Insn::new("movc", vec![text("A"), text(name)], stmts))
+        }
+        0xe0 | 0xe2 | 0xe3 | 0xf0 | 0xf2 | 0xf3 => {
+            let (mut stmts, ptr) = if op & 0xf == 0 {
+                (vec![], "@DPTR".to_string())
+            } else {
+                (rreil!{ zext/16 addr:16, (register(bank, op & 1)); }?, format!("@R{}", op & 1))
+            };
+            let addr = if op & 0xf == 0 { rreil_rvalue!{ dptr:16 } } else { rreil_rvalue!{ addr:16 } };
+
+            if op < 0xf0 {
+                stmts.push(load(XDATA, addr, rreil_lvalue!{ a:8 }));
+                Ok(Insn::new("movx", vec![text("A"), text(&ptr)], stmts))
+            } else {
+                stmts.push(store(XDATA, addr, rreil_rvalue!{ a:8 }));
+                Ok(Insn::new("movx", vec![text(&ptr), text("A")], stmts))
+            }
+        }
+        0xc0 => {
+            let src = Loc::Direct(b.byte()?);
+            let (mut stmts, v) = src.read(bank, "src")?;
+
+            stmts.extend(push(v)?);
+            Ok(Insn::new("push", vec![src.operand()], stmts))
+        }
+        0xd0 => {
+            let dst = Loc::Direct(b.byte()?);
+            let mut stmts = pop(rreil_lvalue!{ pop:8 })?;
+
+            stmts.extend(dst.write(bank, rreil_rvalue!{ pop:8 })?);
+            Ok(Insn::new("pop", vec![dst.operand()], stmts))
+        }
+        0xc5...0xcf => {
+            let src = source(op, b)?;
+            let (mut stmts, v) = src.read(bank, "src")?;
+
+            stmts.extend(rreil!{ mov xch:8, (v); }?);
+            stmts.extend(src.write(bank, rreil_rvalue!{ a:8 })?);
+            stmts.extend(rreil!{ mov a:8, xch:8; }?);
+            Ok(Insn::new("xch", vec![text("A"), src.operand()], stmts))
+        }
+        0xd6 | 0xd7 => {
+            let src = Loc::Indirect(op & 1);
+            let (mut stmts, _) = src.read(bank, "src")?;
+
+            stmts.extend(
+                rreil!{
+                    mov xch:8, a:8;
+                    sel/0 a:8, src:4;
+                    sel/0 src:8, xch:4;
+                }?
+            );
+            stmts.extend(src.write(bank, rreil_rvalue!{ src:8 })?);
+            Ok(Insn::new("xchd", vec![text("A"), src.operand()], stmts))
+        }
+        _ => unknown(op),
+    }
+}
+
+fn call(name: &str, target: u16, ret: u16) -> Result<Insn> {
+    let mut stmts = push_pc(ret)?;
+    let tgt = Rvalue::new_u16(target);
+
+    stmts.extend(rreil!{ call (tgt); }?);
+    Ok(Insn::new(name, vec![code(target)], stmts).flow(Flow::Call(tgt)))
+}
+
+fn branch(name: &str, ops: Vec<Operand>, stmts: Vec<Statement>, target: u16) -> Result<Insn> {
+    Ok(Insn::new(name, ops, stmts).flow(Flow::Branch(Rvalue::new_u16(target), rreil_rvalue!{ cond:1 })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Model;
+
+    fn decode_at(bytes: &[u8], addr: u64, bank: u8) -> Insn {
+        let mut buf = vec![0; addr as usize];
+        buf.extend_from_slice(bytes);
+
+        let reg = Region::wrap("code".to_string(), buf);
+        let cpu = Cpu { model: Model::I8051, bank: bank };
+        let (ret, tokens) = read(&reg, addr, &cpu).unwrap();
+
+        assert_eq!(tokens, bytes.to_vec());
+        for s in ret.statements.iter() {
+            assert!(s.sanity_check().is_ok(), "{} {:?}", ret.opcode, s);
+        }
+        assert!(ret.mnemonic(addr, tokens.len() as u64).is_ok());
+        ret
+    }
+
+    fn decode_bytes(bytes: &[u8]) -> Insn {
+        decode_at(bytes, 0, 0)
+    }
+
+    #[test]
+    fn all_opcodes() {
+        for op in 0..0x100 {
+            let bytes = [op as u8, 0x35, 0x12];
+            let reg = Region::wrap("code".to_string(), bytes.to_vec());
+
+            match read(&reg, 0, &Cpu::new(Model::I8052)) {
+                Ok((insn, tokens)) => {
+                    assert!(bytes.starts_with(&tokens));
+                    for s in insn.statements.iter() {
+                        assert!(s.sanity_check().is_ok(), "{} {:?}", insn.opcode, s);
+                    }
+                }
+                Err(_) => assert_eq!(op, 0xa5),
+            }
+        }
+    }
+
+    #[test]
+    fn data_transfer() {
+        assert_eq!(decode_bytes(&[0x74, 0x12]).format, "A, #{u}");
+        assert_eq!(decode_bytes(&[0x85, 0x31, 0x30]).operands, vec![byte(0x30), byte(0x31)]);
+        assert_eq!(decode_bytes(&[0xf5, 0x90]).format, "P1, A");
+        assert_eq!(decode_bytes(&[0xe6]).format, "A, @R0");
+        assert_eq!(decode_bytes(&[0x90, 0x12, 0x34]).operands, vec![Rvalue::new_u16(0x1234)]);
+        assert_eq!(decode_bytes(&[0x93]).format, "A, @A+DPTR");
+        assert_eq!(decode_bytes(&[0xf0]).opcode, "movx");
+        assert_eq!(decode_bytes(&[0xd6]).opcode, "xchd");
+        assert_eq!(decode_bytes(&[0xc0, 0xe0]).format, "ACC");
+
+        let mov = decode_at(&[0xff], 0, 2);
+        assert_eq!(mov.format, "R7, A");
+        assert_eq!(mov.statements[0].assignee, rreil_lvalue!{ r23:8 });
+    }
+
+    #[test]
+    fn register_banks() {
+        assert_eq!(decode_bytes(&[0x75, 0xd0, 0x18]).bank, Some(3));
+        assert_eq!(decode_bytes(&[0xd2, 0xd3]).bank, Some(1));
+        assert_eq!(decode_bytes(&[0xd2, 0xd3]).format, "RS0");
+        assert_eq!(decode_at(&[0xc2, 0xd4], 0, 3).bank, Some(1));
+        assert_eq!(decode_at(&[0xb2, 0xd3], 0, 3).bank, Some(2));
+        assert_eq!(decode_at(&[0x43, 0xd0, 0x10], 0, 1).bank, Some(3));
+        assert_eq!(decode_at(&[0x53, 0xd0, 0xe7], 0, 3).bank, Some(0));
+        assert_eq!(decode_bytes(&[0xf5, 0xd0]).bank, None);
+        assert_eq!(decode_bytes(&[0xd0, 0xd0]).bank, None);
+    }
+
+    #[test]
+    fn bits() {
+        assert_eq!(decode_bytes(&[0xd2, 0x93]).format, "P1.3");
+        assert_eq!(decode_bytes(&[0xa2, 0x07]).format, "C, {u}");
+        assert_eq!(decode_bytes(&[0xb0, 0xe7]).format, "C, /ACC.7");
+        assert_eq!(decode_bytes(&[0x92, 0xd5]).format, "F0, C");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(decode_bytes(&[0x00]).flow, Flow::Next);
+        assert_eq!(decode_bytes(&[0x22]).flow, Flow::Return);
+        assert_eq!(decode_bytes(&[0x32]).flow, Flow::Return);
+        assert_eq!(decode_bytes(&[0x80, 0xfe]).flow, Flow::Jump(Rvalue::new_u16(0)));
+        assert_eq!(decode_bytes(&[0x02, 0x12, 0x34]).flow, Flow::Jump(Rvalue::new_u16(0x1234)));
+        assert_eq!(decode_at(&[0x01, 0x23], 0x7fe, 0).flow, Flow::Jump(Rvalue::new_u16(0x823)));
+        assert_eq!(decode_at(&[0xf1, 0x23], 0x100, 0).flow, Flow::Call(Rvalue::new_u16(0x723)));
+        assert_eq!(decode_bytes(&[0x12, 0x01, 0x00]).flow, Flow::Call(Rvalue::new_u16(0x100)));
+        assert_eq!(decode_bytes(&[0x73]).flow, Flow::Jump(rreil_rvalue!{ tgt:16 }));
+        assert_eq!(decode_bytes(&[0x60, 0x02]).flow, Flow::Branch(Rvalue::new_u16(4), rreil_rvalue!{ cond:1 }));
+        assert_eq!(decode_bytes(&[0xb4, 0x05, 0xfd]).format, "A, #{u}, {c:code}");
+        assert_eq!(decode_bytes(&[0xb6, 0x05, 0xfd]).flow, Flow::Branch(Rvalue::new_u16(0), rreil_rvalue!{ cond:1 }));
+        assert_eq!(decode_bytes(&[0xd5, 0x30, 0xfd]).format, "{p:idata}, {c:code}");
+        assert_eq!(decode_bytes(&[0x10, 0x00, 0x10]).opcode, "jbc");
+    }
+
+    #[test]
+    fn invalid() {
+        for bytes in [vec![0xa5], vec![0x02, 0x12], vec![0x75, 0x30]].iter() {
+            let reg = Region::wrap("code".to_string(), bytes.clone());
+
+            assert!(read(&reg, 0, &Cpu::new(Model::I8051)).is_err(), "{:?}", bytes);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! Intel MCS-51 (8051) disassembler.
+//!
+//! The 8051 has four address spaces. Code memory holds the program and constant tables read by
+//! `movc`. The internal data memory is 128 bytes on the 8051 and 256 on the 8052, its lower half
+//! can be accessed directly and the whole of it indirectly through `R0` and `R1`. Direct
+//! addresses above 0x7f select special function registers instead of RAM. External data memory
+//! is reached with `movx`. RREIL loads and stores name the space they access: `code`, `idata`,
+//! `sfr` or `xdata`.
+//!
+//! The first 32 bytes of internal RAM are four banks of eight registers, the RS0 and RS1 bits of
+//! the PSW select the bank `R0` to `R7` refer to. Register `Rn` of bank `b` is the variable
+//! `r(8b + n)`, direct accesses to these addresses use the same variables. The bank is part of
+//! the CPU configuration, instructions that set RS0 and RS1 to constants update it.
+//!
+//! The accumulator, `B`, `SP`, `DPTR` and the PSW flags are variables. Other special function
+//! registers are memory in the `sfr` space. The parity flag is computed from the accumulator when
+//! the PSW is read. Decimal adjust sets the accumulator and carry to undefined.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+
+pub mod semantic;
+mod decode;
+
+mod architecture;
+pub use architecture::{Cpu, Mcs51, Model};
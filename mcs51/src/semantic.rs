@@ -0,0 +1,352 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! RREIL code generation helpers for the 8051.
+
+use panopticon_core::{Endianess, Lvalue, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+/// Program memory.
+pub const CODE: &'static str = "code";
+/// Internal data memory.
+pub const IDATA: &'static str = "idata";
+/// Special function registers, addressed by their direct address.
+pub const SFR: &'static str = "sfr";
+/// External data memory.
+pub const XDATA: &'static str = "xdata";
+
+/// The 32 bytes of register banks 0 to 3.
+pub static REGISTERS: [&'static str; 32] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "r16", "r17", "r18", "r19", "r20", "r21", "r22", "r23",
+    "r24", "r25", "r26", "r27", "r28", "r29", "r30", "r31",
+];
+
+/// Special function registers of the 8051 and 8052.
+pub static SFR_NAMES: [(u8, &'static str); 26] = [
+    (0x80, "P0"), (0x81, "SP"), (0x82, "DPL"), (0x83, "DPH"), (0x87, "PCON"),
+    (0x88, "TCON"), (0x89, "TMOD"), (0x8a, "TL0"), (0x8b, "TL1"), (0x8c, "TH0"),
+    (0x8d, "TH1"), (0x90, "P1"), (0x98, "SCON"), (0x99, "SBUF"), (0xa0, "P2"),
+    (0xa8, "IE"), (0xb0, "P3"), (0xb8, "IP"), (0xc8, "T2CON"), (0xca, "RCAP2L"),
+    (0xcb, "RCAP2H"), (0xcc, "TL2"), (0xcd, "TH2"), (0xd0, "PSW"), (0xe0, "ACC"),
+    (0xf0, "B"),
+];
+
+/// PSW bits from bit 0 to 7.
+pub static PSW_BITS: [&'static str; 8] = ["P", "F1", "OV", "RS0", "RS1", "F0", "AC", "CY"];
+
+/// Direct address of the PSW.
+pub const PSW: u8 = 0xd0;
+
+/// Name of the special function register at `addr`, if it has one.
+pub fn sfr_name(addr: u8) -> Option<&'static str> {
+    SFR_NAMES.iter().find(|x| x.0 == addr).map(|x| x.1)
+}
+
+/// Register `n` of `bank`.
+pub fn register(bank: u8, n: u8) -> Lvalue {
+    byte_var(REGISTERS[((bank & 3) * 8 + (n & 7)) as usize])
+}
+
+fn byte_var(name: &'static str) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: 8 }
+}
+
+fn flag(name: &'static str) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: 1 }
+}
+
+/// Byte constant.
+pub fn byte(v: u8) -> Rvalue {
+    Rvalue::Constant { value: v as u64, size: 8 }
+}
+
+/// Reads a byte from `addr` in address space `space` into `lv`.
+pub fn load(space: &'static str, addr: Rvalue, lv: Lvalue) -> Statement {
+    Statement { op: Operation::Load(Cow::Borrowed(space), Endianess::Little, 8, addr), assignee: lv }
+}
+
+/// Writes the byte `v` to `addr` in address space `space`.
+pub fn store(space: &'static str, addr: Rvalue, v: Rvalue) -> Statement {
+    Statement { op: Operation::Store(Cow::Borrowed(space), Endianess::Little, 8, addr, v), assignee: Lvalue::Undefined }
+}
+
+// Byte at a direct address as seen by the IL.
+enum Direct {
+    Variable(Lvalue),
+    Dptr(usize),
+    Psw,
+    Memory(&'static str),
+}
+
+fn direct(addr: u8) -> Direct {
+    match addr {
+        0x00...0x1f => Direct::Variable(byte_var(REGISTERS[addr as usize])),
+        0x20...0x7f => Direct::Memory(IDATA),
+        0x81 => Direct::Variable(byte_var("sp")),
+        0x82 => Direct::Dptr(0),
+        0x83 => Direct::Dptr(8),
+        PSW => Direct::Psw,
+        0xe0 => Direct::Variable(byte_var("a")),
+        0xf0 => Direct::Variable(byte_var("b")),
+        _ => Direct::Memory(SFR),
+    }
+}
+
+/// Reads the byte at direct address `addr`. Memory is loaded into `tmp`.
+pub fn read_direct(addr: u8, tmp: &'static str) -> Result<(Vec<Statement>, Rvalue)> {
+    match direct(addr) {
+        Direct::Variable(lv) => Ok((vec![], lv.into())),
+        Direct::Dptr(off) => Ok((vec![], rreil_rvalue!{ dptr:16 }.extract(8, off)?)),
+        Direct::Psw => Ok((psw()?, rreil_rvalue!{ psw:8 })),
+        Direct::Memory(space) => {
+            let lv = byte_var(tmp);
+            Ok((vec![load(space, byte(addr), lv.clone())], lv.into()))
+        }
+    }
+}
+
+/// Writes `v` to direct address `addr`.
+pub fn write_direct(addr: u8, v: Rvalue) -> Result<Vec<Statement>> {
+    match direct(addr) {
+        Direct::Variable(lv) => rreil!{ mov (lv), (v); },
+        Direct::Dptr(off) => Ok(vec![Statement { op: Operation::Select(off, rreil_rvalue!{ dptr:16 }, v), assignee: rreil_lvalue!{ dptr:16 } }]),
+        Direct::Psw => set_psw(v),
+        Direct::Memory(space) => Ok(vec![store(space, byte(addr), v)]),
+    }
+}
+
+/// Byte holding bit address `bit`. Bits 0 to 0x7f are in internal RAM from 0x20 on, the rest in
+/// the special function registers whose address is a multiple of eight.
+pub fn bit_byte(bit: u8) -> u8 {
+    if bit < 0x80 { 0x20 + bit / 8 } else { bit & 0xf8 }
+}
+
+/// Reads the bit at bit address `bit`.
+pub fn read_bit(bit: u8) -> Result<(Vec<Statement>, Rvalue)> {
+    let addr = bit_byte(bit);
+    let pos = (bit & 7) as usize;
+
+    match direct(addr) {
+        Direct::Psw if pos == 0 => Ok((parity()?, rreil_rvalue!{ P:1 })),
+        Direct::Psw => Ok((vec![], flag(PSW_BITS[pos]).into())),
+        _ => {
+            let (stmts, v) = read_direct(addr, "bits")?;
+            Ok((stmts, v.extract(1, pos)?))
+        }
+    }
+}
+
+/// Writes the one bit value `v` to bit address `bit`. Writes to the parity flag are ignored.
+pub fn write_bit(bit: u8, v: Rvalue) -> Result<Vec<Statement>> {
+    let addr = bit_byte(bit);
+    let pos = (bit & 7) as usize;
+
+    match direct(addr) {
+        Direct::Psw if pos == 0 => Ok(vec![]),
+        Direct::Psw => rreil!{ mov (flag(PSW_BITS[pos])), (v); },
+        Direct::Variable(lv) => Ok(vec![Statement { op: Operation::Select(pos, lv.clone().into(), v), assignee: lv }]),
+        _ => {
+            let (mut stmts, old) = read_direct(addr, "bits")?;
+            let lv = rreil_lvalue!{ bits:8 };
+
+            stmts.push(Statement { op: Operation::Select(pos, old, v), assignee: lv.clone() });
+            stmts.extend(write_direct(addr, lv.into())?);
+            Ok(stmts)
+        }
+    }
+}
+
+/// Computes the parity flag from the accumulator. P is set if the number of ones is odd.
+pub fn parity() -> Result<Vec<Statement>> {
+    rreil!{
+        shr par:8, a:8, [4]:8;
+        xor par:8, par:8, a:8;
+        shr par_t:8, par:8, [2]:8;
+        xor par:8, par:8, par_t:8;
+        shr par_t:8, par:8, [1]:8;
+        xor par:8, par:8, par_t:8;
+        mov P:1, par:1;
+    }
+}
+
+/// Composes the PSW from the flags into `psw:8`.
+pub fn psw() -> Result<Vec<Statement>> {
+    let mut stmts = parity()?;
+
+    stmts.extend(rreil!{ zext/8 psw:8, P:1; }?);
+    for (i, &name) in PSW_BITS.iter().enumerate().skip(1) {
+        stmts.push(Statement { op: Operation::Select(i, rreil_rvalue!{ psw:8 }, flag(name).into()), assignee: rreil_lvalue!{ psw:8 } });
+    }
+
+    Ok(stmts)
+}
+
+/// Sets the PSW flags from the byte `v`. The parity flag is read only.
+pub fn set_psw(v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    for (i, &name) in PSW_BITS.iter().enumerate().skip(1) {
+        stmts.extend(rreil!{ mov (flag(name)), (v.extract(1, i)?); }?);
+    }
+
+    Ok(stmts)
+}
+
+/// Increments the stack pointer and writes `v` onto the stack.
+pub fn push(v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{ add sp:8, sp:8, [1]:8; }?;
+
+    stmts.push(store(IDATA, rreil_rvalue!{ sp:8 }, v));
+    Ok(stmts)
+}
+
+/// Pops a byte off the stack into `lv`.
+pub fn pop(lv: Lvalue) -> Result<Vec<Statement>> {
+    let mut stmts = vec![load(IDATA, rreil_rvalue!{ sp:8 }, lv)];
+
+    stmts.extend(rreil!{ sub sp:8, sp:8, [1]:8; }?);
+    Ok(stmts)
+}
+
+/// Pushes a return address, low byte first.
+pub fn push_pc(ret: u16) -> Result<Vec<Statement>> {
+    let mut stmts = push(byte(ret as u8))?;
+
+    stmts.extend(push(byte((ret >> 8) as u8))?);
+    Ok(stmts)
+}
+
+/// Pops a return address into `pc:16`.
+pub fn pop_pc() -> Result<Vec<Statement>> {
+    let mut stmts = pop(rreil_lvalue!{ pch:8 })?;
+
+    stmts.extend(pop(rreil_lvalue!{ pcl:8 })?);
+    stmts.extend(
+        rreil!{
+            zext/16 pc:16, pcl:8;
+            sel/8 pc:16, pch:8;
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Adds `v` and, for `addc`, the carry to the accumulator. Sets CY, AC and OV.
+pub fn add(v: Rvalue, carry: bool) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        zext/9 res:9, a:8;
+        zext/9 add_t:9, (v);
+        add res:9, res:9, add_t:9;
+        zext/5 nib:5, (rreil_rvalue!{ a:8 }.extract(4, 0)?);
+        zext/5 add_n:5, (v.extract(4, 0)?);
+        add nib:5, nib:5, add_n:5;
+    }?;
+
+    if carry {
+        stmts.extend(
+            rreil!{
+                zext/9 add_t:9, CY:1;
+                add res:9, res:9, add_t:9;
+                zext/5 add_n:5, CY:1;
+                add nib:5, nib:5, add_n:5;
+            }?
+        );
+    }
+
+    stmts.extend(
+        rreil!{
+            xor ov_a:8, a:8, res:8;
+            xor ov_b:8, (v), res:8;
+            and ov_a:8, ov_a:8, ov_b:8;
+        }?
+    );
+    stmts.extend(flags_and_result()?);
+    Ok(stmts)
+}
+
+/// Subtracts `v` and the carry from the accumulator. Sets CY, AC and OV.
+pub fn subb(v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        zext/9 res:9, a:8;
+        zext/9 sub_t:9, (v);
+        sub res:9, res:9, sub_t:9;
+        zext/9 sub_t:9, CY:1;
+        sub res:9, res:9, sub_t:9;
+        zext/5 nib:5, (rreil_rvalue!{ a:8 }.extract(4, 0)?);
+        zext/5 sub_n:5, (v.extract(4, 0)?);
+        sub nib:5, nib:5, sub_n:5;
+        zext/5 sub_n:5, CY:1;
+        sub nib:5, nib:5, sub_n:5;
+        xor ov_a:8, a:8, (v);
+        xor ov_b:8, a:8, res:8;
+        and ov_a:8, ov_a:8, ov_b:8;
+    }?;
+
+    stmts.extend(flags_and_result()?);
+    Ok(stmts)
+}
+
+// Carry out of bit 7 and 3, overflow from bit 7 of `ov_a` and the result.
+fn flags_and_result() -> Result<Vec<Statement>> {
+    rreil!{
+        mov CY:1, res:1/8;
+        mov AC:1, nib:1/4;
+        mov OV:1, ov_a:1/7;
+        mov a:8, res:8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statements_are_sane() {
+        let mut stmts = add(byte(3), true).unwrap();
+
+        stmts.extend(subb(rreil_rvalue!{ r7:8 }).unwrap());
+        stmts.extend(pop_pc().unwrap());
+        stmts.extend(push_pc(0x1234).unwrap());
+        for bit in [0x00, 0x7f, 0x80, 0xd0, 0xd7, 0xe3, 0xf7].iter() {
+            let (s, v) = read_bit(*bit).unwrap();
+            stmts.extend(s);
+            stmts.extend(write_bit(*bit, v).unwrap());
+        }
+        for addr in [0x05, 0x30, 0x81, 0x82, 0x83, 0x90, 0xd0, 0xe0].iter() {
+            let (s, v) = read_direct(*addr, "tmp").unwrap();
+            stmts.extend(s);
+            stmts.extend(write_direct(*addr, v).unwrap());
+        }
+
+        for s in stmts {
+            assert!(s.sanity_check().is_ok(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn banks() {
+        assert_eq!(register(0, 0), rreil_lvalue!{ r0:8 });
+        assert_eq!(register(2, 7), rreil_lvalue!{ r23:8 });
+        assert_eq!(read_direct(0x17, "tmp").unwrap().1, rreil_rvalue!{ r23:8 });
+        assert_eq!(bit_byte(0x0b), 0x21);
+        assert_eq!(bit_byte(0xd3), PSW);
+        assert_eq!(read_bit(0xd7).unwrap().1, rreil_rvalue!{ CY:1 });
+    }
+}
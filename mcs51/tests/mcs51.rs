@@ -0,0 +1,80 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_mcs51;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Architecture, Function, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_mcs51::{Cpu, Mcs51, Model};
+
+// Calls a subroutine five times, then spins
+fn function() -> Region {
+    Region::wrap(
+        "code".to_string(),
+        vec![
+            0x75, 0x81, 0x30, // mov SP, #0x30
+            0x78, 0x05, // mov R0, #5
+            0x12, 0x00, 0x0c, // lcall 0x0c
+            0xd8, 0xfb, // djnz R0, 0x05
+            0x80, 0xfe, // sjmp 0x0a
+            0x22, // ret
+        ],
+    )
+}
+
+#[test]
+fn loop_and_call() {
+    let reg = function();
+    let func = Function::new::<Mcs51>(0, &reg, None, Cpu::new(Model::I8051)).unwrap();
+    let mut starts = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    starts.sort();
+
+    assert_eq!(starts, vec![0x0, 0x5, 0xa]);
+    assert_eq!(func.cfg().num_edges(), 4);
+    assert_eq!(func.end(), 0xc);
+    assert_eq!(func.collect_call_addresses(), vec![0xc]);
+}
+
+#[test]
+fn interrupt_vectors() {
+    let mut bytes = vec![0xff; 0x40];
+
+    bytes[0..3].copy_from_slice(&[0x02, 0x00, 0x30]);
+    bytes[0x0b] = 0x32;
+    bytes[0x2b] = 0x32;
+
+    let reg = Region::wrap("code".to_string(), bytes);
+    let entries = Mcs51::prepare(&reg, &Cpu::new(Model::I8051)).unwrap();
+
+    assert_eq!(entries.iter().map(|e| (e.0, e.1)).collect::<Vec<_>>(), vec![("RESET", 0x00), ("TF0", 0x0b)]);
+    assert_eq!(Mcs51::prepare(&reg, &Cpu::new(Model::I8052)).unwrap().len(), 3);
+}
+
+#[test]
+fn bank_switch() {
+    let reg = Region::wrap("code".to_string(), vec![0x75, 0xd0, 0x08, 0xf8]);
+    let m = Mcs51::decode(&reg, 0, &Cpu::new(Model::I8051)).unwrap();
+
+    assert_eq!(m.configuration.bank, 1);
+
+    let mov = Mcs51::decode(&reg, 3, &m.configuration).unwrap();
+    assert_eq!(format!("{}", mov.mnemonics[0].instructions[0]), "mov r8:8, a:8");
+}
@@ -0,0 +1,216 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use decode::{self, Flow, Insn};
+use panopticon_core::{Architecture, Endianess, Guard, Match, Mnemonic, Region, Result, Rvalue};
+use std::sync::{Arc, RwLock};
+
+#[derive(Clone,Debug)]
+pub enum Mips {}
+
+/// Register width
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Mode {
+    /// 32-bit registers and addresses
+    Mips32,
+    /// 64-bit registers and addresses
+    Mips64,
+}
+
+/// CPU configuration.
+#[derive(Clone,Debug)]
+pub struct Cpu {
+    /// Register width
+    pub mode: Mode,
+    /// Byte order of instructions and data
+    pub endianess: Endianess,
+    global_pointer: Arc<RwLock<Option<u64>>>,
+}
+
+impl Cpu {
+    pub fn new(mode: Mode, endianess: Endianess) -> Cpu {
+        Cpu {
+            mode: mode,
+            endianess: endianess,
+            global_pointer: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Assumes `$gp` is `gp` in all functions, e.g. the value of the `_gp` symbol.
+    pub fn with_global_pointer(self, gp: u64) -> Cpu {
+        *self.global_pointer.write().unwrap() = Some(gp);
+        self
+    }
+
+    /// Value of `$gp` if known.
+    pub fn global_pointer(&self) -> Option<u64> {
+        self.global_pointer.read().ok().and_then(|gp| *gp)
+    }
+
+    /// Records the value of `$gp` computed by a function prologue. All clones of this `Cpu` share
+    /// it.
+    pub fn set_global_pointer(&self, gp: u64) -> Result<()> {
+        *self.global_pointer.write()? = Some(gp);
+        Ok(())
+    }
+
+    /// Size of registers and addresses in bits.
+    pub fn width(&self) -> usize {
+        match self.mode {
+            Mode::Mips32 => 32,
+            Mode::Mips64 => 64,
+        }
+    }
+
+    /// Truncates `v` to the register width.
+    pub fn mask(&self, v: u64) -> u64 {
+        match self.mode {
+            Mode::Mips32 => v & 0xffff_ffff,
+            Mode::Mips64 => v,
+        }
+    }
+
+    fn read(&self, reg: &Region, addr: u64, len: usize) -> Option<Vec<u8>> {
+        let mut buf = Vec::with_capacity(len);
+
+        for b in reg.iter().seek(addr).take(len) {
+            match b {
+                Some(b) => buf.push(b),
+                None => return None,
+            }
+        }
+
+        if buf.len() == len { Some(buf) } else { None }
+    }
+
+    /// Reads the 32-bit word at `addr`.
+    pub fn read_word(&self, reg: &Region, addr: u64) -> Option<u32> {
+        self.read(reg, addr, 4).map(
+            |buf| match self.endianess {
+                Endianess::Little => LittleEndian::read_u32(&buf),
+                Endianess::Big => BigEndian::read_u32(&buf),
+            }
+        )
+    }
+
+    /// Reads the register sized value at `addr`.
+    pub fn read_pointer(&self, reg: &Region, addr: u64) -> Option<u64> {
+        if self.mode == Mode::Mips32 {
+            return self.read_word(reg, addr).map(|x| x as u64);
+        }
+
+        self.read(reg, addr, 8).map(
+            |buf| match self.endianess {
+                Endianess::Little => LittleEndian::read_u64(&buf),
+                Endianess::Big => BigEndian::read_u64(&buf),
+            }
+        )
+    }
+
+    fn constant(&self, v: u64) -> Rvalue {
+        Rvalue::Constant { value: self.mask(v), size: self.width() }
+    }
+}
+
+fn mnemonic(insn: &Insn, addr: u64) -> Result<Mnemonic> {
+    Mnemonic::new(
+        addr..addr + 4,
+        insn.opcode.clone(),
+        insn.format.clone(),
+        insn.operands.iter(),
+        insn.statements.iter(),
+    )
+}
+
+impl Architecture for Mips {
+    type Token = u8;
+    type Configuration = Cpu;
+
+    fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        Ok(vec![])
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        if start & 3 != 0 {
+            return Err(format!("unaligned MIPS instruction at {:#x}", start).into());
+        }
+
+        let insn = decode::read(cfg, reg, start)?;
+        debug!("disass @ {:#x}: {} {:?}", start, insn.opcode, insn.flow);
+
+        let next = cfg.constant(start + 4);
+        let after_slot = cfg.constant(start + 8);
+        let (mnemonics, jumps) = match insn.flow.clone() {
+            Flow::Next => (vec![mnemonic(&insn, start)?], vec![(start, next, Guard::always())]),
+            flow => {
+                let mut slot = decode::read(cfg, reg, start + 4)?;
+
+                if slot.flow != Flow::Next {
+                    return Err(format!("branch in delay slot at {:#x}", start + 4).into());
+                }
+
+                let mut jumps = vec![];
+
+                match flow {
+                    Flow::Next => unreachable!(),
+                    Flow::Return => {
+                        jumps.push((start, next, Guard::always()));
+                    }
+                    Flow::Call { target } => {
+                        slot.statements.extend(rreil!{ call (target); }?);
+                        jumps.push((start, next, Guard::always()));
+                        jumps.push((start + 4, after_slot, Guard::always()));
+                    }
+                    Flow::Branch { target, taken, likely } => {
+                        let guard = match taken {
+                            Some(ref flag) => Guard::from_flag(flag)?,
+                            None => Guard::always(),
+                        };
+
+                        if likely && guard != Guard::always() {
+                            jumps.push((start, next, guard.clone()));
+                            jumps.push((start, after_slot, guard.negation()));
+                            jumps.push((start + 4, target, Guard::always()));
+                        } else {
+                            jumps.push((start, next, Guard::always()));
+                            if guard != Guard::always() {
+                                jumps.push((start + 4, after_slot, guard.negation()));
+                            }
+                            jumps.push((start + 4, target, guard));
+                        }
+                    }
+                }
+
+                (vec![mnemonic(&insn, start)?, mnemonic(&slot, start + 4)?], jumps)
+            }
+        };
+
+        let len = 4 * mnemonics.len();
+        let tokens = cfg.read(reg, start, len).unwrap_or_default();
+
+        Ok(
+            Match::<Mips> {
+                tokens: tokens,
+                mnemonics: mnemonics,
+                jumps: jumps,
+                configuration: cfg.clone(),
+            }
+        )
+    }
+}
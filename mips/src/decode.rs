@@ -0,0 +1,737 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! MIPS instruction decoder.
+
+use architecture::{Cpu, Mode};
+use panopticon_core::{Operation, Region, Result, Rvalue, Statement};
+use pic;
+use semantic::*;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Branches to `target` after the delay slot if the flag `taken` is set or `taken` is `None`.
+    /// Likely branches nullify the delay slot if not taken.
+    Branch { target: Rvalue, taken: Option<Rvalue>, likely: bool },
+    /// Calls `target` after the delay slot
+    Call { target: Rvalue },
+    /// Returns to the caller after the delay slot
+    Return,
+}
+
+/// A single decoded instruction.
+#[derive(Clone,Debug)]
+pub struct Insn {
+    pub opcode: String,
+    pub format: String,
+    pub operands: Vec<Rvalue>,
+    pub statements: Vec<Statement>,
+    pub flow: Flow,
+}
+
+impl Insn {
+    fn new(opcode: &str, format: &str, operands: Vec<Rvalue>, statements: Vec<Statement>) -> Insn {
+        Insn {
+            opcode: opcode.to_string(),
+            format: format.to_string(),
+            operands: operands,
+            statements: statements,
+            flow: Flow::Next,
+        }
+    }
+
+    fn flow(mut self, flow: Flow) -> Insn {
+        self.flow = flow;
+        self
+    }
+}
+
+struct Fields {
+    op: u32,
+    rs: u32,
+    rt: u32,
+    rd: u32,
+    sa: u32,
+    funct: u32,
+    imm: u64,
+    simm: u64,
+}
+
+impl Fields {
+    fn new(insn: u32) -> Fields {
+        Fields {
+            op: insn >> 26,
+            rs: (insn >> 21) & 0x1f,
+            rt: (insn >> 16) & 0x1f,
+            rd: (insn >> 11) & 0x1f,
+            sa: (insn >> 6) & 0x1f,
+            funct: insn & 0x3f,
+            imm: (insn & 0xffff) as u64,
+            simm: sign_extend((insn & 0xffff) as u64, 16),
+        }
+    }
+}
+
+fn require_64(cpu: &Cpu, insn: u32) -> Result<()> {
+    if cpu.mode == Mode::Mips64 {
+        Ok(())
+    } else {
+        Err(format!("64-bit instruction {:#010x} in 32-bit mode", insn).into())
+    }
+}
+
+/// Decodes the instruction at `addr`.
+pub fn read(cpu: &Cpu, region: &Region, addr: u64) -> Result<Insn> {
+    let insn = match cpu.read_word(region, addr) {
+        Some(insn) => insn,
+        None => return Err(format!("MIPS instruction at {:#x} truncated", addr).into()),
+    };
+    let f = Fields::new(insn);
+
+    let ret = match f.op {
+        0 => special(cpu, region, insn, &f, addr),
+        1 => regimm(cpu, insn, &f, addr),
+        2 | 3 => {
+            let target = cpu.mask(((addr + 4) & !0x0fff_ffff) | (((insn & 0x03ff_ffff) as u64) << 2));
+            let target = imm(cpu, target);
+
+            if f.op == 2 {
+                Ok(Insn::new("j", "{c:ram}", vec![target.clone()], vec![]).flow(Flow::Branch { target: target, taken: None, likely: false }))
+            } else {
+                let link = link(cpu, RA, addr)?;
+                Ok(Insn::new("jal", "{c:ram}", vec![target.clone()], link).flow(Flow::Call { target: target }))
+            }
+        }
+        4...7 | 20...23 => branch(cpu, &f, addr),
+        8...15 | 24 | 25 => immediate(cpu, insn, &f),
+        16 => cop0(cpu, insn, &f),
+        17 => cop1(cpu, insn, &f, addr),
+        28 => special2(cpu, insn, &f),
+        31 => special3(cpu, insn, &f),
+        32...39 | 40...46 | 48 | 55 | 56 | 63 => memory(cpu, insn, &f),
+        47 => Ok(Insn::new("cache", "{u}, {s}({u})", vec![Rvalue::new_u8(f.rt as u8), imm(cpu, f.simm), operand(cpu, f.rs)], vec![])),
+        51 => Ok(Insn::new("pref", "{u}, {s}({u})", vec![Rvalue::new_u8(f.rt as u8), imm(cpu, f.simm), operand(cpu, f.rs)], vec![])),
+        49 | 53 | 57 | 61 => {
+            // floating point loads and stores don't touch integer registers
+            let name = match f.op {
+                49 => "lwc1",
+                53 => "ldc1",
+                57 => "swc1",
+                _ => "sdc1",
+            };
+            Ok(Insn::new(name, "{u}, {s}({u})", vec![fpu_operand(f.rt), imm(cpu, f.simm), operand(cpu, f.rs)], vec![]))
+        }
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }?;
+
+    // $gp is set up by a short sequence at the start of PIC functions
+    if pic_writes_gp(&ret) {
+        if let Some(gp) = pic::global_pointer_setup(cpu, region, addr, insn) {
+            debug!("$gp = {:#x} set up at {:#x}", gp, addr);
+            cpu.set_global_pointer(gp)?;
+        }
+    }
+
+    Ok(ret)
+}
+
+fn pic_writes_gp(insn: &Insn) -> bool {
+    insn.statements.iter().any(
+        |s| match s.assignee {
+            ::panopticon_core::Lvalue::Variable { ref name, .. } => name == REGISTERS[GP as usize],
+            _ => false,
+        }
+    )
+}
+
+fn link(cpu: &Cpu, rd: u32, addr: u64) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+    let ret = imm(cpu, addr + 8);
+
+    rreil!{ mov (rd), (ret); }
+}
+
+fn three(cpu: &Cpu, name: &str, f: &Fields, stmts: Vec<Statement>) -> Result<Insn> {
+    Ok(Insn::new(name, "{u}, {u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs), operand(cpu, f.rt)], stmts))
+}
+
+fn special(cpu: &Cpu, region: &Region, insn: u32, f: &Fields, addr: u64) -> Result<Insn> {
+    let shift = |cpu: &Cpu, name: &str, op: BinOp, amount: u32| -> Result<Insn> {
+        let stmts = word_binop(cpu, op, f.rd, reg32(f.rt), Rvalue::new_u32(amount))?;
+        Ok(Insn::new(name, "{u}, {u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rt), Rvalue::new_u8(amount as u8)], stmts))
+    };
+    let dshift = |cpu: &Cpu, name: &str, op: BinOp, amount: u32| -> Result<Insn> {
+        require_64(cpu, insn)?;
+        let stmts = binop(cpu, op, f.rd, reg(cpu, f.rt), Rvalue::new_u64(amount as u64))?;
+        Ok(Insn::new(name, "{u}, {u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rt), Rvalue::new_u8(amount as u8)], stmts))
+    };
+    let vshift = |cpu: &Cpu, name: &str, op: BinOp, double: bool| -> Result<Insn> {
+        let mut stmts;
+        if double {
+            require_64(cpu, insn)?;
+            let rs = reg(cpu, f.rs);
+            stmts = rreil!{ and amount:64, (rs), [63]:64; }?;
+            stmts.extend(binop(cpu, op, f.rd, reg(cpu, f.rt), rreil_rvalue!{ amount:64 })?);
+        } else {
+            let rs = reg32(f.rs);
+            stmts = rreil!{ and amount:32, (rs), [31]:32; }?;
+            stmts.extend(word_binop(cpu, op, f.rd, reg32(f.rt), rreil_rvalue!{ amount:32 })?);
+        }
+        Ok(Insn::new(name, "{u}, {u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rt), operand(cpu, f.rs)], stmts))
+    };
+
+    match f.funct {
+        0x00 if insn == 0 => Ok(Insn::new("nop", "", vec![], vec![])),
+        0x00 => shift(cpu, "sll", Operation::ShiftLeft, f.sa),
+        0x02 => shift(cpu, "srl", Operation::ShiftRightUnsigned, f.sa),
+        0x03 => shift(cpu, "sra", Operation::ShiftRightSigned, f.sa),
+        0x04 => vshift(cpu, "sllv", Operation::ShiftLeft, false),
+        0x06 => vshift(cpu, "srlv", Operation::ShiftRightUnsigned, false),
+        0x07 => vshift(cpu, "srav", Operation::ShiftRightSigned, false),
+        0x08 => {
+            if f.rs == RA {
+                Ok(Insn::new("jr", "{u}", vec![operand(cpu, f.rs)], vec![]).flow(Flow::Return))
+            } else {
+                let (stmts, target) = indirect_target(cpu, region, addr, f.rs)?;
+                Ok(Insn::new("jr", "{u}", vec![operand(cpu, f.rs)], stmts).flow(Flow::Branch { target: target, taken: None, likely: false }))
+            }
+        }
+        0x09 => {
+            let (mut stmts, target) = indirect_target(cpu, region, addr, f.rs)?;
+            stmts.extend(link(cpu, f.rd, addr)?);
+            if f.rd == RA {
+                Ok(Insn::new("jalr", "{u}", vec![operand(cpu, f.rs)], stmts).flow(Flow::Call { target: target }))
+            } else {
+                Ok(Insn::new("jalr", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs)], stmts).flow(Flow::Call { target: target }))
+            }
+        }
+        0x0a => three(cpu, "movz", f, conditional_move(cpu, f.rd, f.rs, f.rt, true)?),
+        0x0b => three(cpu, "movn", f, conditional_move(cpu, f.rd, f.rs, f.rt, false)?),
+        0x0c => Ok(Insn::new("syscall", "", vec![], vec![])),
+        0x0d => Ok(Insn::new("break", "", vec![], vec![])),
+        0x0f => Ok(Insn::new("sync", "", vec![], vec![])),
+        0x10 | 0x12 => {
+            let (name, src) = if f.funct == 0x10 { ("mfhi", "hi") } else { ("mflo", "lo") };
+            let src: Rvalue = special_register(cpu, src).into();
+            let rd = reg_lv(cpu, f.rd);
+            Ok(Insn::new(name, "{u}", vec![operand(cpu, f.rd)], rreil!{ mov (rd), (src); }?))
+        }
+        0x11 | 0x13 => {
+            let (name, dst) = if f.funct == 0x11 { ("mthi", "hi") } else { ("mtlo", "lo") };
+            let dst = special_register(cpu, dst);
+            let rs = reg(cpu, f.rs);
+            Ok(Insn::new(name, "{u}", vec![operand(cpu, f.rs)], rreil!{ mov (dst), (rs); }?))
+        }
+        0x14 => vshift(cpu, "dsllv", Operation::ShiftLeft, true),
+        0x16 => vshift(cpu, "dsrlv", Operation::ShiftRightUnsigned, true),
+        0x17 => vshift(cpu, "dsrav", Operation::ShiftRightSigned, true),
+        0x18...0x1f => {
+            let double = f.funct >= 0x1c;
+            let signed = f.funct & 1 == 0;
+            let (a, b) = if double {
+                require_64(cpu, insn)?;
+                (reg(cpu, f.rs), reg(cpu, f.rt))
+            } else {
+                (reg32(f.rs), reg32(f.rt))
+            };
+            let (name, stmts) = match f.funct & 3 {
+                0 | 1 => (["mult", "multu", "dmult", "dmultu"][((f.funct >> 1) & 2 | (f.funct & 1)) as usize], multiply(cpu, a, b, signed, double)?),
+                _ => (["div", "divu", "ddiv", "ddivu"][((f.funct >> 1) & 2 | (f.funct & 1)) as usize], divide(cpu, a, b, signed, double)?),
+            };
+            Ok(Insn::new(name, "{u}, {u}", vec![operand(cpu, f.rs), operand(cpu, f.rt)], stmts))
+        }
+        0x20 | 0x21 | 0x22 | 0x23 => {
+            let op: BinOp = if f.funct < 0x22 { Operation::Add } else { Operation::Subtract };
+            let stmts = word_binop(cpu, op, f.rd, reg32(f.rs), reg32(f.rt))?;
+
+            if f.funct == 0x21 && f.rt == ZERO {
+                Ok(Insn::new("move", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs)], stmts))
+            } else {
+                three(cpu, ["add", "addu", "sub", "subu"][(f.funct & 3) as usize], f, stmts)
+            }
+        }
+        0x24...0x26 => {
+            let op: BinOp = match f.funct {
+                0x24 => Operation::And,
+                0x25 => Operation::InclusiveOr,
+                _ => Operation::ExclusiveOr,
+            };
+            let stmts = binop(cpu, op, f.rd, reg(cpu, f.rs), reg(cpu, f.rt))?;
+
+            if f.funct == 0x25 && f.rt == ZERO {
+                Ok(Insn::new("move", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs)], stmts))
+            } else {
+                three(cpu, ["and", "or", "xor"][(f.funct - 0x24) as usize], f, stmts)
+            }
+        }
+        0x27 => three(cpu, "nor", f, nor(cpu, f.rd, reg(cpu, f.rs), reg(cpu, f.rt))?),
+        0x2a => three(cpu, "slt", f, set_less(cpu, f.rd, reg(cpu, f.rs), reg(cpu, f.rt), true)?),
+        0x2b => three(cpu, "sltu", f, set_less(cpu, f.rd, reg(cpu, f.rs), reg(cpu, f.rt), false)?),
+        0x2c...0x2f => {
+            require_64(cpu, insn)?;
+            let op: BinOp = if f.funct < 0x2e { Operation::Add } else { Operation::Subtract };
+            let stmts = binop(cpu, op, f.rd, reg(cpu, f.rs), reg(cpu, f.rt))?;
+
+            if f.funct == 0x2d && f.rt == ZERO {
+                Ok(Insn::new("move", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs)], stmts))
+            } else {
+                three(cpu, ["dadd", "daddu", "dsub", "dsubu"][(f.funct & 3) as usize], f, stmts)
+            }
+        }
+        0x30...0x36 => Ok(Insn::new(["tge", "tgeu", "tlt", "tltu", "teq", "", "tne"][(f.funct - 0x30) as usize], "{u}, {u}", vec![operand(cpu, f.rs), operand(cpu, f.rt)], vec![])),
+        0x38 => dshift(cpu, "dsll", Operation::ShiftLeft, f.sa),
+        0x3a => dshift(cpu, "dsrl", Operation::ShiftRightUnsigned, f.sa),
+        0x3b => dshift(cpu, "dsra", Operation::ShiftRightSigned, f.sa),
+        0x3c => dshift(cpu, "dsll32", Operation::ShiftLeft, f.sa + 32),
+        0x3e => dshift(cpu, "dsrl32", Operation::ShiftRightUnsigned, f.sa + 32),
+        0x3f => dshift(cpu, "dsra32", Operation::ShiftRightSigned, f.sa + 32),
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+/// Returns the target of an indirect jump through register `r` at `addr`. If the value of `r`
+/// cannot be resolved statically it's copied into `target` before the delay slot executes.
+fn indirect_target(cpu: &Cpu, region: &Region, addr: u64, r: u32) -> Result<(Vec<Statement>, Rvalue)> {
+    if let Some(value) = pic::resolve_register(cpu, region, addr, r) {
+        debug!("resolved {} at {:#x} to {:#x}", REGISTERS[r as usize], addr, value);
+        return Ok((vec![], imm(cpu, value)));
+    }
+
+    let w = cpu.width();
+    let rs = reg(cpu, r);
+    let stmts = rreil!{ mov target:(w), (rs); }?;
+
+    Ok((stmts, rreil_rvalue!{ target:(w) }))
+}
+
+fn branch_target(cpu: &Cpu, f: &Fields, addr: u64) -> Rvalue {
+    imm(cpu, (addr + 4).wrapping_add(f.simm << 2))
+}
+
+fn branch(cpu: &Cpu, f: &Fields, addr: u64) -> Result<Insn> {
+    let target = branch_target(cpu, f, addr);
+    let likely = f.op >= 20;
+    let suffix = if likely { "l" } else { "" };
+    let (rs, rt, zero) = (reg(cpu, f.rs), reg(cpu, f.rt), imm(cpu, 0));
+
+    let (name, stmts) = match f.op & 3 {
+        0 => {
+            if f.rs == f.rt {
+                let flow = Flow::Branch { target: target.clone(), taken: None, likely: false };
+                return Ok(Insn::new("b", "{c:ram}", vec![target], vec![]).flow(flow));
+            }
+            ("beq", rreil!{ cmpeq taken:1, (rs), (rt); }?)
+        }
+        1 => {
+            ("bne",
+             rreil!{
+                cmpeq taken:1, (rs), (rt);
+                xor taken:1, taken:1, [1]:1;
+            }?)
+        }
+        2 => ("blez", rreil!{ cmples taken:1, (rs), (zero); }?),
+        _ => {
+            ("bgtz",
+             rreil!{
+                cmples taken:1, (rs), (zero);
+                xor taken:1, taken:1, [1]:1;
+            }?)
+        }
+    };
+    let flow = Flow::Branch { target: target.clone(), taken: Some(rreil_rvalue!{ taken:1 }), likely: likely };
+
+    if f.op & 3 < 2 {
+        if f.rt == ZERO {
+            let name = format!("{}z{}", name, suffix);
+            Ok(Insn::new(&name, "{u}, {c:ram}", vec![operand(cpu, f.rs), target], stmts).flow(flow))
+        } else {
+            let name = format!("{}{}", name, suffix);
+            Ok(Insn::new(&name, "{u}, {u}, {c:ram}", vec![operand(cpu, f.rs), operand(cpu, f.rt), target], stmts).flow(flow))
+        }
+    } else {
+        let name = format!("{}{}", name, suffix);
+        Ok(Insn::new(&name, "{u}, {c:ram}", vec![operand(cpu, f.rs), target], stmts).flow(flow))
+    }
+}
+
+fn regimm(cpu: &Cpu, insn: u32, f: &Fields, addr: u64) -> Result<Insn> {
+    let target = branch_target(cpu, f, addr);
+    let (rs, zero) = (reg(cpu, f.rs), imm(cpu, 0));
+
+    match f.rt {
+        0...3 | 16...19 => {
+            let less = f.rt & 1 == 0;
+            let likely = f.rt & 2 != 0;
+            let name = format!(
+                "{}{}{}",
+                if less { "bltz" } else { "bgez" },
+                if f.rt >= 16 { "al" } else { "" },
+                if likely { "l" } else { "" }
+            );
+
+            if f.rt >= 16 {
+                // conditional calls are treated as always taken
+                let stmts = link(cpu, RA, addr)?;
+                let name = if f.rt == 17 && f.rs == ZERO { "bal".to_string() } else { name };
+                return Ok(Insn::new(&name, "{u}, {c:ram}", vec![operand(cpu, f.rs), target.clone()], stmts).flow(Flow::Call { target: target }));
+            }
+
+            let mut stmts = rreil!{ cmplts taken:1, (rs), (zero); }?;
+            if !less {
+                stmts.extend(rreil!{ xor taken:1, taken:1, [1]:1; }?);
+            }
+            let flow = Flow::Branch { target: target.clone(), taken: Some(rreil_rvalue!{ taken:1 }), likely: likely };
+
+            Ok(Insn::new(&name, "{u}, {c:ram}", vec![operand(cpu, f.rs), target], stmts).flow(flow))
+        }
+        8...14 => {
+            let name = ["tgei", "tgeiu", "tlti", "tltiu", "teqi", "", "tnei"][(f.rt - 8) as usize];
+            Ok(Insn::new(name, "{u}, {s}", vec![operand(cpu, f.rs), imm(cpu, f.simm)], vec![]))
+        }
+        31 => Ok(Insn::new("synci", "{s}({u})", vec![imm(cpu, f.simm), operand(cpu, f.rs)], vec![])),
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+fn immediate(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let ops = |cpu: &Cpu, v: u64| vec![operand(cpu, f.rt), operand(cpu, f.rs), imm(cpu, v)];
+
+    match f.op {
+        8 | 9 => {
+            let stmts = word_binop(cpu, Operation::Add, f.rt, reg32(f.rs), Rvalue::new_u32(f.simm as u32))?;
+
+            if f.op == 9 && f.rs == ZERO {
+                Ok(Insn::new("li", "{u}, {s}", vec![operand(cpu, f.rt), imm(cpu, f.simm)], stmts))
+            } else {
+                Ok(Insn::new(if f.op == 8 { "addi" } else { "addiu" }, "{u}, {u}, {s}", ops(cpu, f.simm), stmts))
+            }
+        }
+        10 => Ok(Insn::new("slti", "{u}, {u}, {s}", ops(cpu, f.simm), set_less(cpu, f.rt, reg(cpu, f.rs), imm(cpu, f.simm), true)?)),
+        11 => Ok(Insn::new("sltiu", "{u}, {u}, {s}", ops(cpu, f.simm), set_less(cpu, f.rt, reg(cpu, f.rs), imm(cpu, f.simm), false)?)),
+        12...14 => {
+            let (name, op): (&str, BinOp) = match f.op {
+                12 => ("andi", Operation::And),
+                13 => ("ori", Operation::InclusiveOr),
+                _ => ("xori", Operation::ExclusiveOr),
+            };
+            let stmts = binop(cpu, op, f.rt, reg(cpu, f.rs), imm(cpu, f.imm))?;
+
+            if f.op == 13 && f.rs == ZERO {
+                Ok(Insn::new("li", "{u}, {u}", vec![operand(cpu, f.rt), imm(cpu, f.imm)], stmts))
+            } else {
+                Ok(Insn::new(name, "{u}, {u}, {u}", ops(cpu, f.imm), stmts))
+            }
+        }
+        15 => {
+            let stmts = write_word(cpu, f.rt, Rvalue::new_u32((f.imm << 16) as u32))?;
+            Ok(Insn::new("lui", "{u}, {u}", vec![operand(cpu, f.rt), imm(cpu, f.imm)], stmts))
+        }
+        24 | 25 => {
+            require_64(cpu, insn)?;
+            let stmts = binop(cpu, Operation::Add, f.rt, reg(cpu, f.rs), imm(cpu, f.simm))?;
+            Ok(Insn::new(if f.op == 24 { "daddi" } else { "daddiu" }, "{u}, {u}, {s}", ops(cpu, f.simm), stmts))
+        }
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+fn cop0(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let sel = Rvalue::new_u8(f.rd as u8);
+
+    match f.rs {
+        0 | 1 => {
+            let rt = reg_lv(cpu, f.rt);
+            let name = if f.rs == 0 { "mfc0" } else { "dmfc0" };
+            Ok(Insn::new(name, "{u}, ${u}", vec![operand(cpu, f.rt), sel], rreil!{ mov (rt), ?; }?))
+        }
+        4 | 5 => Ok(Insn::new(if f.rs == 4 { "mtc0" } else { "dmtc0" }, "{u}, ${u}", vec![operand(cpu, f.rt), sel], vec![])),
+        11 => Ok(Insn::new(if insn & 0x20 == 0 { "di" } else { "ei" }, "{u}", vec![operand(cpu, f.rt)], vec![])),
+        16...31 => {
+            let name = match f.funct {
+                0x01 => "tlbr",
+                0x02 => "tlbwi",
+                0x06 => "tlbwr",
+                0x08 => "tlbp",
+                0x18 => "eret",
+                0x20 => "wait",
+                _ => return Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+            };
+            Ok(Insn::new(name, "", vec![], vec![]))
+        }
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+fn cop1(cpu: &Cpu, insn: u32, f: &Fields, addr: u64) -> Result<Insn> {
+    let fs = fpu_operand(f.rd);
+
+    match f.rs {
+        0...3 => {
+            let rt = reg_lv(cpu, f.rt);
+            let name = ["mfc1", "dmfc1", "cfc1", "mfhc1"][f.rs as usize];
+            Ok(Insn::new(name, "{u}, {u}", vec![operand(cpu, f.rt), fs], rreil!{ mov (rt), ?; }?))
+        }
+        4...7 => {
+            let name = ["mtc1", "dmtc1", "ctc1", "mthc1"][(f.rs - 4) as usize];
+            Ok(Insn::new(name, "{u}, {u}", vec![operand(cpu, f.rt), fs], vec![]))
+        }
+        8 => {
+            let target = branch_target(cpu, f, addr);
+            let likely = f.rt & 2 != 0;
+            let name = format!("bc1{}{}", if f.rt & 1 == 0 { "f" } else { "t" }, if likely { "l" } else { "" });
+            let stmts = if f.rt & 1 == 0 {
+                rreil!{ xor taken:1, fcc:1, [1]:1; }?
+            } else {
+                rreil!{ mov taken:1, fcc:1; }?
+            };
+            let flow = Flow::Branch { target: target.clone(), taken: Some(rreil_rvalue!{ taken:1 }), likely: likely };
+
+            Ok(Insn::new(&name, "{c:ram}", vec![target], stmts).flow(flow))
+        }
+        // arithmetic on floating point registers doesn't affect integer state. Comparisons set
+        // the condition flag used by bc1f and bc1t.
+        16...22 => {
+            let stmts = if f.funct >= 0x30 { rreil!{ mov fcc:1, ?; }? } else { vec![] };
+            Ok(Insn::new("cop1", "{u}", vec![Rvalue::new_u32(insn & 0x03ff_ffff)], stmts))
+        }
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+fn special2(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    match f.funct {
+        0x00 | 0x01 | 0x04 | 0x05 => {
+            let name = ["madd", "maddu", "", "", "msub", "msubu"][f.funct as usize];
+            let (hi, lo) = (special_register(cpu, "hi"), special_register(cpu, "lo"));
+            let stmts = rreil!{
+                mov (hi), ?;
+                mov (lo), ?;
+            }?;
+            Ok(Insn::new(name, "{u}, {u}", vec![operand(cpu, f.rs), operand(cpu, f.rt)], stmts))
+        }
+        0x02 => three(cpu, "mul", f, word_binop(cpu, Operation::Multiply, f.rd, reg32(f.rs), reg32(f.rt))?),
+        0x20 | 0x21 => {
+            // RREIL has no count leading zeros operation
+            let rd = reg_lv(cpu, f.rd);
+            let name = if f.funct == 0x20 { "clz" } else { "clo" };
+            Ok(Insn::new(name, "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs)], rreil!{ mov (rd), ?; }?))
+        }
+        0x3f => Ok(Insn::new("sdbbp", "", vec![], vec![])),
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+fn special3(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    match f.funct {
+        0x00 => {
+            // ext rt, rs, pos, size
+            let (pos, size) = (f.sa, f.rd + 1);
+            let mask = if size >= 32 { 0xffff_ffff } else { (1u32 << size) - 1 };
+            let rs = reg32(f.rs);
+            let mut stmts = rreil!{
+                shr res:32, (rs), [pos]:32;
+                and res:32, res:32, [mask]:32;
+            }?;
+
+            stmts.extend(write_word(cpu, f.rt, rreil_rvalue!{ res:32 })?);
+            Ok(Insn::new("ext", "{u}, {u}, {u}, {u}", vec![operand(cpu, f.rt), operand(cpu, f.rs), Rvalue::new_u8(pos as u8), Rvalue::new_u8(size as u8)], stmts))
+        }
+        0x04 => {
+            // ins rt, rs, pos, size
+            let (pos, msb) = (f.sa, f.rd);
+            if msb < pos {
+                return Err(format!("invalid MIPS instruction {:#010x}", insn).into());
+            }
+            let size = msb - pos + 1;
+            let mask = if size >= 32 { 0xffff_ffff } else { ((1u32 << size) - 1) << pos };
+            let inv = !mask;
+            let (rs, rt) = (reg32(f.rs), reg32(f.rt));
+            let mut stmts = rreil!{
+                shl ins:32, (rs), [pos]:32;
+                and ins:32, ins:32, [mask]:32;
+                and res:32, (rt), [inv]:32;
+                or res:32, res:32, ins:32;
+            }?;
+
+            stmts.extend(write_word(cpu, f.rt, rreil_rvalue!{ res:32 })?);
+            Ok(Insn::new("ins", "{u}, {u}, {u}, {u}", vec![operand(cpu, f.rt), operand(cpu, f.rs), Rvalue::new_u8(pos as u8), Rvalue::new_u8(size as u8)], stmts))
+        }
+        0x20 => {
+            let rt = reg32(f.rt);
+            let (name, mut stmts) = match f.sa {
+                0x02 => ("wsbh", rreil!{ mov res:32, ?; }?),
+                0x10 => {
+                    ("seb",
+                     rreil!{
+                        mov byte:8, (rt);
+                        sext/32 res:32, byte:8;
+                    }?)
+                }
+                0x18 => {
+                    ("seh",
+                     rreil!{
+                        mov half:16, (rt);
+                        sext/32 res:32, half:16;
+                    }?)
+                }
+                _ => return Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+            };
+
+            stmts.extend(write_word(cpu, f.rd, rreil_rvalue!{ res:32 })?);
+            Ok(Insn::new(name, "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rt)], stmts))
+        }
+        0x3b => {
+            // hardware registers, e.g. the thread pointer in $29
+            let rt = reg_lv(cpu, f.rt);
+            Ok(Insn::new("rdhwr", "{u}, ${u}", vec![operand(cpu, f.rt), Rvalue::new_u8(f.rd as u8)], rreil!{ mov (rt), ?; }?))
+        }
+        _ => Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    }
+}
+
+fn memory(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let (name, size, signed, is_load) = match f.op {
+        26 => ("ldl", 0, false, true),
+        27 => ("ldr", 0, false, true),
+        32 => ("lb", 8, true, true),
+        33 => ("lh", 16, true, true),
+        34 => ("lwl", 0, false, true),
+        35 => ("lw", 32, true, true),
+        36 => ("lbu", 8, false, true),
+        37 => ("lhu", 16, false, true),
+        38 => ("lwr", 0, false, true),
+        39 => ("lwu", 32, false, true),
+        40 => ("sb", 8, false, false),
+        41 => ("sh", 16, false, false),
+        42 => ("swl", 0, false, false),
+        43 => ("sw", 32, false, false),
+        44 => ("sdl", 0, false, false),
+        45 => ("sdr", 0, false, false),
+        46 => ("swr", 0, false, false),
+        48 => ("ll", 32, true, true),
+        55 => ("ld", 64, false, true),
+        56 => ("sc", 32, false, false),
+        63 => ("sd", 64, false, false),
+        _ => return Err(format!("unknown MIPS instruction {:#010x}", insn).into()),
+    };
+
+    if [26, 27, 39, 44, 45, 55, 63].contains(&f.op) {
+        require_64(cpu, insn)?;
+    }
+
+    let ops = vec![operand(cpu, f.rt), imm(cpu, f.simm), operand(cpu, f.rs)];
+    let w = cpu.width();
+
+    // unaligned partial accesses are not modeled
+    if size == 0 {
+        let stmts = if is_load {
+            let rt = reg_lv(cpu, f.rt);
+            rreil!{ mov (rt), ?; }?
+        } else {
+            vec![]
+        };
+        return Ok(Insn::new(name, "{u}, {s}({u})", ops, stmts));
+    }
+
+    let (mut stmts, address) = match cpu.global_pointer() {
+        Some(gp) if f.rs == GP => (vec![], imm(cpu, gp.wrapping_add(f.simm))),
+        _ => {
+            let (base, off) = (reg(cpu, f.rs), imm(cpu, f.simm));
+            (rreil!{ add address:(w), (base), (off); }?, rreil_rvalue!{ address:(w) })
+        }
+    };
+
+    if is_load {
+        stmts.extend(load(cpu, f.rt, address, size, signed)?);
+    } else {
+        stmts.extend(store(cpu, f.rt, address, size)?);
+        if f.op == 56 {
+            let rt = reg_lv(cpu, f.rt);
+            let one = imm(cpu, 1);
+            stmts.extend(rreil!{ mov (rt), (one); }?);
+        }
+    }
+
+    Ok(Insn::new(name, "{u}, {s}({u})", ops, stmts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Mips;
+    use panopticon_core::{Endianess, golden};
+
+    fn decode(cpu: &Cpu, insn: u32) -> Insn {
+        let reg = Region::wrap("ram".to_string(), vec![(insn >> 24) as u8, (insn >> 16) as u8, (insn >> 8) as u8, insn as u8]);
+        let ret = read(cpu, &reg, 0).unwrap();
+
+        for s in ret.statements.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+        ret
+    }
+
+    #[test]
+    fn aliases() {
+        let cpu = Cpu::new(Mode::Mips32, Endianess::Big);
+
+        assert_eq!(decode(&cpu, 0x00000000).opcode, "nop");
+        assert_eq!(decode(&cpu, 0x00801021).opcode, "move");
+        assert_eq!(decode(&cpu, 0x2402002a).opcode, "li");
+        assert_eq!(decode(&cpu, 0x10000004).opcode, "b");
+        assert_eq!(decode(&cpu, 0x04110004).opcode, "bal");
+        assert_eq!(decode(&cpu, 0x14800004).opcode, "bnez");
+    }
+
+    #[test]
+    fn flow() {
+        let cpu = Cpu::new(Mode::Mips32, Endianess::Big);
+
+        assert_eq!(decode(&cpu, 0x03e00008).flow, Flow::Return);
+        assert_eq!(decode(&cpu, 0x0c000010).flow, Flow::Call { target: Rvalue::new_u32(0x40) });
+        assert_eq!(decode(&cpu, 0x00000000).flow, Flow::Next);
+        match decode(&cpu, 0x1485fffe).flow {
+            Flow::Branch { target, taken: Some(_), likely: false } => assert_eq!(target, Rvalue::new_u32(0xfffffffc)),
+            f => panic!("{:?}", f),
+        }
+    }
+
+    #[test]
+    fn word_operations_sign_extend() {
+        let cpu = Cpu::new(Mode::Mips64, Endianess::Big);
+        let addu = decode(&cpu, 0x00851021);
+
+        assert_eq!(addu.opcode, "addu");
+        assert!(addu.statements.iter().any(|s| if let Operation::SignExtend(64, _) = s.op { true } else { false }));
+        assert_eq!(decode(&cpu, 0xdfbf0008).opcode, "ld");
+    }
+
+    #[test]
+    fn fpu_register_names() {
+        let cpu = Cpu::new(Mode::Mips64, Endianess::Big);
+        let listing = |insn: u32| golden::render::<Mips>(&[(insn >> 24) as u8, (insn >> 16) as u8, (insn >> 8) as u8, insn as u8], 0, &cpu)[0].clone();
+
+        assert_eq!(listing(0xc4a70010), "lwc1 f7, 0x10(a1)");
+        assert_eq!(listing(0xf7ac0008), "sdc1 f12, 0x8(sp)");
+        assert_eq!(listing(0x44224000), "dmfc1 v0, f8");
+        assert_eq!(listing(0x44843000), "mtc1 a0, f6");
+    }
+}
@@ -0,0 +1,50 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! MIPS32 and MIPS64 disassembler.
+//!
+//! This disassembler handles the integer instructions of MIPS32 and MIPS64 Release 2 CPUs in
+//! both byte orders.
+//!
+//! Branches and jumps are followed by a delay slot. The decoder returns both instructions as one
+//! match. The branch condition and target are computed into temporaries first, so that the
+//! delay slot instruction may overwrite the registers they depend on. Branch likely instructions
+//! only execute the delay slot if the branch is taken, which is modeled with an additional edge
+//! from the branch to the instruction after the delay slot.
+//!
+//! Position independent code calls functions through `$t9`. Before a `jalr` or `jr` the decoder
+//! looks at the preceding instructions to find the value of the target register. Targets loaded
+//! from the global offset table (`lw $t9, off($gp)`) or built with `lui`/`addiu` pairs are
+//! resolved to constants. The value of `$gp` is either passed in by the caller or recovered from
+//! the `lui`/`addiu`/`addu` sequence at the start of PIC functions. Loads and stores relative to
+//! `$gp` use constant addresses once `$gp` is known.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+extern crate byteorder;
+
+pub mod semantic;
+mod decode;
+mod pic;
+
+mod architecture;
+pub use architecture::{Cpu, Mips, Mode};
@@ -0,0 +1,172 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Recognition of position independent code idioms.
+//!
+//! The decoder only sees one instruction at a time. To resolve indirect calls it walks back from
+//! the call over the preceding instructions and tracks the few ways compilers compute call
+//! targets and `$gp`.
+
+use architecture::Cpu;
+use panopticon_core::Region;
+use semantic::{GP, T9, ZERO, sign_extend};
+
+/// How far to look back for the definition of a register.
+const MAX_SCAN: u64 = 16;
+
+fn op(insn: u32) -> u32 {
+    insn >> 26
+}
+
+fn rs(insn: u32) -> u32 {
+    (insn >> 21) & 0x1f
+}
+
+fn rt(insn: u32) -> u32 {
+    (insn >> 16) & 0x1f
+}
+
+fn rd(insn: u32) -> u32 {
+    (insn >> 11) & 0x1f
+}
+
+fn simm(insn: u32) -> u64 {
+    sign_extend((insn & 0xffff) as u64, 16)
+}
+
+/// Returns true if `insn` may transfer control.
+fn is_branch(insn: u32) -> bool {
+    match op(insn) {
+        0 => insn & 0x3e == 0x08,
+        1...7 | 20...23 => true,
+        _ => false,
+    }
+}
+
+/// Returns the register `insn` writes to, if any.
+fn destination(insn: u32) -> Option<u32> {
+    match op(insn) {
+        0 => {
+            match insn & 0x3f {
+                0x08 | 0x0c | 0x0d | 0x0f | 0x11 | 0x13 | 0x18...0x1f => None,
+                _ => Some(rd(insn)),
+            }
+        }
+        3 => Some(31),
+        8...15 | 24 | 25 | 32...39 | 48 | 52 | 55 | 56 => Some(rt(insn)),
+        28 => Some(rd(insn)),
+        31 => if insn & 0x3f == 0x20 { Some(rd(insn)) } else { Some(rt(insn)) },
+        _ => None,
+    }
+}
+
+/// Tries to find the constant value register `r` has when the instruction at `addr` executes.
+pub fn resolve_register(cpu: &Cpu, reg: &Region, addr: u64, r: u32) -> Option<u64> {
+    resolve(cpu, reg, addr, r, MAX_SCAN)
+}
+
+fn resolve(cpu: &Cpu, reg: &Region, addr: u64, r: u32, budget: u64) -> Option<u64> {
+    if r == ZERO {
+        return Some(0);
+    }
+    if r == GP {
+        if let Some(gp) = cpu.global_pointer() {
+            return Some(gp);
+        }
+    }
+
+    for i in 1..budget + 1 {
+        let a = addr.checked_sub(4 * i)?;
+        let insn = cpu.read_word(reg, a)?;
+
+        // the instruction right before could have us in its delay slot
+        if is_branch(insn) && i > 1 {
+            return None;
+        }
+        if destination(insn) != Some(r) {
+            continue;
+        }
+
+        let rest = budget - i;
+        let value = match op(insn) {
+            // lui
+            15 => Some(sign_extend(((insn & 0xffff) as u64) << 16, 32)),
+            // addiu, daddiu
+            9 | 25 => resolve(cpu, reg, a, rs(insn), rest).map(|b| b.wrapping_add(simm(insn))),
+            // ori
+            13 => resolve(cpu, reg, a, rs(insn), rest).map(|b| b | (insn & 0xffff) as u64),
+            // lw, ld
+            35 | 55 => {
+                let base = resolve(cpu, reg, a, rs(insn), rest)?;
+                cpu.read_pointer(reg, cpu.mask(base.wrapping_add(simm(insn))))
+            }
+            // move: addu, daddu, or with $zero
+            0 if [0x21, 0x25, 0x2d].contains(&(insn & 0x3f)) && rt(insn) == ZERO => resolve(cpu, reg, a, rs(insn), rest),
+            _ => None,
+        };
+
+        return value.map(|v| cpu.mask(v));
+    }
+
+    None
+}
+
+/// Recognizes the instruction sequences that set `$gp` at the start of a function. `insn` at
+/// `addr` is the last instruction of the sequence. Returns the value of `$gp` afterwards.
+///
+/// Three forms are recognized:
+///
+/// ```text
+/// lui   $gp, %hi(_gp_disp)      lui    $gp, %hi(%neg(%gp_rel(f)))      lui   $gp, %hi(_gp)
+/// addiu $gp, $gp, %lo(_gp_disp) daddu  $gp, $gp, $t9                   addiu $gp, $gp, %lo(_gp)
+/// addu  $gp, $gp, $t9           daddiu $gp, $gp, %lo(%neg(%gp_rel(f)))
+/// ```
+///
+/// The first two are relative to `$t9`, which holds the address of the function (the `lui`).
+pub fn global_pointer_setup(cpu: &Cpu, reg: &Region, addr: u64, insn: u32) -> Option<u64> {
+    let prev = cpu.read_word(reg, addr.checked_sub(4)?)?;
+    let first = addr.checked_sub(8).and_then(|a| cpu.read_word(reg, a).map(|i| (a, i)));
+    let is_lui_gp = |i: u32| op(i) == 15 && rt(i) == GP;
+    let is_addiu_gp = |i: u32| (op(i) == 9 || op(i) == 25) && rt(i) == GP && rs(i) == GP;
+    let is_addu_gp_t9 = |i: u32| op(i) == 0 && (i & 0x3f == 0x21 || i & 0x3f == 0x2d) && rd(i) == GP && rs(i) == GP && rt(i) == T9;
+    let hi = |i: u32| sign_extend(((i & 0xffff) as u64) << 16, 32);
+
+    let gp = if is_addu_gp_t9(insn) && is_addiu_gp(prev) {
+        let (start, lui) = first?;
+        if !is_lui_gp(lui) {
+            return None;
+        }
+        start.wrapping_add(hi(lui)).wrapping_add(simm(prev))
+    } else if is_addiu_gp(insn) && is_addu_gp_t9(prev) {
+        let (start, lui) = first?;
+        if !is_lui_gp(lui) {
+            return None;
+        }
+        start.wrapping_add(hi(lui)).wrapping_add(simm(insn))
+    } else if is_addiu_gp(insn) && is_lui_gp(prev) {
+        let next = cpu.read_word(reg, addr + 4);
+        if next.map(&is_addu_gp_t9) == Some(true) {
+            return None;
+        }
+        hi(prev).wrapping_add(simm(insn))
+    } else {
+        return None;
+    };
+
+    Some(cpu.mask(gp))
+}
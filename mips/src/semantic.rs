@@ -0,0 +1,366 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! RREIL code generation helpers for MIPS.
+
+use architecture::Cpu;
+use panopticon_core::{Lvalue, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+pub static REGISTERS: [&'static str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+pub static FPU_REGISTERS: [&'static str; 32] = [
+    "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7",
+    "f8", "f9", "f10", "f11", "f12", "f13", "f14", "f15",
+    "f16", "f17", "f18", "f19", "f20", "f21", "f22", "f23",
+    "f24", "f25", "f26", "f27", "f28", "f29", "f30", "f31",
+];
+
+pub const ZERO: u32 = 0;
+pub const T9: u32 = 25;
+pub const GP: u32 = 28;
+pub const SP: u32 = 29;
+pub const RA: u32 = 31;
+
+pub type BinOp = fn(Rvalue, Rvalue) -> Operation<Rvalue>;
+
+/// Register `r` as mnemonic operand.
+pub fn operand(cpu: &Cpu, r: u32) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(REGISTERS[r as usize & 0x1f]),
+        subscript: None,
+        offset: 0,
+        size: cpu.width(),
+    }
+}
+
+/// Floating point register `r` as mnemonic operand.
+pub fn fpu_operand(r: u32) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(FPU_REGISTERS[r as usize & 0x1f]),
+        subscript: None,
+        offset: 0,
+        size: 64,
+    }
+}
+
+/// Reads register `r`. `$zero` is always zero.
+pub fn reg(cpu: &Cpu, r: u32) -> Rvalue {
+    if r == ZERO {
+        Rvalue::Constant { value: 0, size: cpu.width() }
+    } else {
+        operand(cpu, r)
+    }
+}
+
+/// Reads the lower 32 bits of register `r`.
+pub fn reg32(r: u32) -> Rvalue {
+    if r == ZERO {
+        Rvalue::new_u32(0)
+    } else {
+        Rvalue::Variable {
+            name: Cow::Borrowed(REGISTERS[r as usize & 0x1f]),
+            subscript: None,
+            offset: 0,
+            size: 32,
+        }
+    }
+}
+
+/// Register `r` as assignee. Writes to `$zero` are discarded.
+pub fn reg_lv(cpu: &Cpu, r: u32) -> Lvalue {
+    if r == ZERO {
+        Lvalue::Undefined
+    } else {
+        Lvalue::Variable { name: Cow::Borrowed(REGISTERS[r as usize & 0x1f]), subscript: None, size: cpu.width() }
+    }
+}
+
+/// Register `name` outside of the general purpose register file, e.g. `hi` or `lo`.
+pub fn special_register(cpu: &Cpu, name: &'static str) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: cpu.width() }
+}
+
+/// Constant of register width.
+pub fn imm(cpu: &Cpu, v: u64) -> Rvalue {
+    Rvalue::Constant { value: cpu.mask(v), size: cpu.width() }
+}
+
+/// Sign extends the `bits` wide value `v` to 64 bits.
+pub fn sign_extend(v: u64, bits: usize) -> u64 {
+    let shift = 64 - bits;
+    (((v << shift) as i64) >> shift) as u64
+}
+
+/// Writes the 32-bit value `v` into register `rd`, sign extending it in 64-bit mode.
+pub fn write_word(cpu: &Cpu, rd: u32, v: Rvalue) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+
+    if cpu.width() == 32 {
+        rreil!{ mov (rd), (v); }
+    } else {
+        rreil!{ sext/64 (rd), (v); }
+    }
+}
+
+/// `rd := a op b` on 32-bit operands.
+pub fn word_binop(cpu: &Cpu, op: BinOp, rd: u32, a: Rvalue, b: Rvalue) -> Result<Vec<Statement>> {
+    let res = rreil_lvalue!{ res:32 };
+    let mut stmts = vec![Statement { op: op(a, b), assignee: res.clone() }];
+
+    stmts.extend(write_word(cpu, rd, res.into())?);
+    Ok(stmts)
+}
+
+/// `rd := a op b` on register sized operands.
+pub fn binop(cpu: &Cpu, op: BinOp, rd: u32, a: Rvalue, b: Rvalue) -> Result<Vec<Statement>> {
+    Ok(vec![Statement { op: op(a, b), assignee: reg_lv(cpu, rd) }])
+}
+
+/// `rd := a < b`, signed or unsigned.
+pub fn set_less(cpu: &Cpu, rd: u32, a: Rvalue, b: Rvalue, signed: bool) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+    let mut stmts = if signed {
+        rreil!{ cmplts lt:1, (a), (b); }?
+    } else {
+        rreil!{ cmpltu lt:1, (a), (b); }?
+    };
+
+    stmts.extend(rreil!{ zext/(cpu.width()) (rd), lt:1; }?);
+    Ok(stmts)
+}
+
+/// `rd := ~(a | b)`
+pub fn nor(cpu: &Cpu, rd: u32, a: Rvalue, b: Rvalue) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+    let ones = imm(cpu, 0xffff_ffff_ffff_ffff);
+
+    rreil!{
+        or (rd), (a), (b);
+        xor (rd), (rd), (ones);
+    }
+}
+
+/// Moves `rs` into `rd` if `rt` is zero (`movz`) or not zero (`movn`).
+pub fn conditional_move(cpu: &Cpu, rd: u32, rs: u32, rt: u32, if_zero: bool) -> Result<Vec<Statement>> {
+    let w = cpu.width();
+    let zero = imm(cpu, 0);
+    let (rd_lv, rd_rv, rs, rt) = (reg_lv(cpu, rd), reg(cpu, rd), reg(cpu, rs), reg(cpu, rt));
+    let mut stmts = rreil!{ cmpeq c:1, (rt), (zero); }?;
+
+    if !if_zero {
+        stmts.extend(rreil!{ xor c:1, c:1, [1]:1; }?);
+    }
+
+    stmts.extend(
+        rreil!{
+            zext/(w) mask:(w), c:1;
+            sub diff:(w), (rs), (rd_rv);
+            mul diff:(w), diff:(w), mask:(w);
+            add (rd_lv), (rd_rv), diff:(w);
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Multiplies `a` and `b` into `hi` and `lo`.
+pub fn multiply(cpu: &Cpu, a: Rvalue, b: Rvalue, signed: bool, double: bool) -> Result<Vec<Statement>> {
+    let (hi, lo) = (special_register(cpu, "hi"), special_register(cpu, "lo"));
+
+    if double {
+        // RREIL has no 128-bit multiply, only the lower half is known
+        rreil!{
+            mul (lo), (a), (b);
+            mov (hi), ?;
+        }
+    } else {
+        let mut stmts = if signed {
+            rreil!{
+                sext/64 a64:64, (a);
+                sext/64 b64:64, (b);
+            }?
+        } else {
+            rreil!{
+                zext/64 a64:64, (a);
+                zext/64 b64:64, (b);
+            }?
+        };
+
+        stmts.extend(rreil!{ mul prod:64, a64:64, b64:64; }?);
+        if cpu.width() == 32 {
+            stmts.extend(
+                rreil!{
+                    mov (lo), prod:32;
+                    mov (hi), prod:32/32;
+                }?
+            );
+        } else {
+            stmts.extend(
+                rreil!{
+                    sext/64 (lo), prod:32;
+                    sext/64 (hi), prod:32/32;
+                }?
+            );
+        }
+        Ok(stmts)
+    }
+}
+
+/// Divides `a` by `b`. The quotient is written to `lo`, the remainder to `hi`.
+pub fn divide(cpu: &Cpu, a: Rvalue, b: Rvalue, signed: bool, double: bool) -> Result<Vec<Statement>> {
+    let (hi, lo) = (special_register(cpu, "hi"), special_register(cpu, "lo"));
+
+    if double {
+        if signed {
+            rreil!{
+                divs (lo), (a), (b);
+                mod (hi), (a), (b);
+            }
+        } else {
+            rreil!{
+                div (lo), (a), (b);
+                mod (hi), (a), (b);
+            }
+        }
+    } else {
+        let mut stmts = if signed {
+            rreil!{
+                divs quot:32, (a), (b);
+                mod rem:32, (a), (b);
+            }?
+        } else {
+            rreil!{
+                div quot:32, (a), (b);
+                mod rem:32, (a), (b);
+            }?
+        };
+
+        if cpu.width() == 32 {
+            stmts.extend(
+                rreil!{
+                    mov (lo), quot:32;
+                    mov (hi), rem:32;
+                }?
+            );
+        } else {
+            stmts.extend(
+                rreil!{
+                    sext/64 (lo), quot:32;
+                    sext/64 (hi), rem:32;
+                }?
+            );
+        }
+        Ok(stmts)
+    }
+}
+
+fn memory(cpu: &Cpu, size: usize, addr: Rvalue, load: Option<Lvalue>, store: Option<Rvalue>) -> Statement {
+    let endianess = cpu.endianess;
+
+    match (load, store) {
+        (Some(lv), _) => Statement { op: Operation::Load(Cow::Borrowed("ram"), endianess, size, addr), assignee: lv },
+        (None, Some(val)) => Statement { op: Operation::Store(Cow::Borrowed("ram"), endianess, size, addr, val), assignee: Lvalue::Undefined },
+        (None, None) => unreachable!(),
+    }
+}
+
+/// Loads `size` bits from `addr` into `rt`, zero or sign extending them to the register width.
+pub fn load(cpu: &Cpu, rt: u32, addr: Rvalue, size: usize, signed: bool) -> Result<Vec<Statement>> {
+    let w = cpu.width();
+    let rt = reg_lv(cpu, rt);
+
+    if size == w {
+        return Ok(vec![memory(cpu, size, addr, Some(rt), None)]);
+    }
+
+    let val = Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size };
+    let mut stmts = vec![memory(cpu, size, addr, Some(val.clone()), None)];
+
+    if signed {
+        stmts.extend(rreil!{ sext/(w) (rt), (val); }?);
+    } else {
+        stmts.extend(rreil!{ zext/(w) (rt), (val); }?);
+    }
+    Ok(stmts)
+}
+
+/// Stores the lower `size` bits of `rt` at `addr`.
+pub fn store(cpu: &Cpu, rt: u32, addr: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    let rt = reg(cpu, rt);
+
+    if size == cpu.width() {
+        return Ok(vec![memory(cpu, size, addr, None, Some(rt))]);
+    }
+
+    let val = Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size };
+    let mut stmts = rreil!{ mov (val), (rt); }?;
+
+    stmts.push(memory(cpu, size, addr, None, Some(val.into())));
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Mode;
+    use panopticon_core::Endianess;
+
+    fn sane(stmts: Vec<Statement>) {
+        for s in stmts.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn zero_register() {
+        let cpu = Cpu::new(Mode::Mips32, Endianess::Big);
+
+        assert_eq!(reg(&cpu, ZERO), Rvalue::new_u32(0));
+        assert_eq!(reg_lv(&cpu, ZERO), Lvalue::Undefined);
+        assert_eq!(reg32(RA).size(), Some(32));
+    }
+
+    #[test]
+    fn statements_are_sane() {
+        for &mode in [Mode::Mips32, Mode::Mips64].iter() {
+            let cpu = Cpu::new(mode, Endianess::Little);
+
+            sane(word_binop(&cpu, Operation::Add, 2, reg32(4), reg32(5)).unwrap());
+            sane(set_less(&cpu, 2, reg(&cpu, 4), imm(&cpu, 10), true).unwrap());
+            sane(nor(&cpu, 2, reg(&cpu, 4), reg(&cpu, 0)).unwrap());
+            sane(conditional_move(&cpu, 2, 4, 5, false).unwrap());
+            sane(multiply(&cpu, reg32(4), reg32(5), true, false).unwrap());
+            sane(divide(&cpu, reg32(4), reg32(5), false, false).unwrap());
+            for &sz in [8, 16, 32].iter() {
+                sane(load(&cpu, 2, reg(&cpu, SP), sz, true).unwrap());
+                sane(store(&cpu, 2, reg(&cpu, SP), sz).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(sign_extend(0xfffc, 16), 0xffff_ffff_ffff_fffc);
+        assert_eq!(sign_extend(0x7fff, 16), 0x7fff);
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+extern crate panopticon_core;
+extern crate panopticon_mips;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Endianess, Function, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_mips::{Cpu, Mips, Mode};
+
+fn wrap(words: &[u32]) -> Region {
+    let mut bytes = vec![];
+
+    for w in words {
+        bytes.extend_from_slice(&[(w >> 24) as u8, (w >> 16) as u8, (w >> 8) as u8, *w as u8]);
+    }
+
+    Region::wrap("ram".to_string(), bytes)
+}
+
+fn starts(func: &Function) -> Vec<u64> {
+    let mut ret = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    ret.sort();
+    ret
+}
+
+#[test]
+fn delay_slots() {
+    let reg = wrap(
+        &[
+            0x10800003, // 0x00: beqz a0, 0x10
+            0x24020001, // 0x04: li v0, 1
+            0x24420001, // 0x08: addiu v0, v0, 1
+            0x00000000, // 0x0c: nop
+            0x03e00008, // 0x10: jr ra
+            0x00000000, // 0x14: nop
+        ],
+    );
+    let func = Function::new::<Mips>(0, &reg, None, Cpu::new(Mode::Mips32, Endianess::Big)).unwrap();
+
+    assert_eq!(starts(&func), vec![0x0, 0x8, 0x10]);
+    assert_eq!(func.cfg().num_edges(), 3);
+    assert_eq!(func.end(), 0x18);
+}
+
+#[test]
+fn branch_likely() {
+    let reg = wrap(
+        &[
+            0x50800002, // 0x00: beqzl a0, 0xc
+            0x24020001, // 0x04: li v0, 1
+            0x24020002, // 0x08: li v0, 2
+            0x03e00008, // 0x0c: jr ra
+            0x00000000, // 0x10: nop
+        ],
+    );
+    let func = Function::new::<Mips>(0, &reg, None, Cpu::new(Mode::Mips32, Endianess::Big)).unwrap();
+
+    // the delay slot is only executed if the branch is taken
+    assert_eq!(starts(&func), vec![0x0, 0x4, 0x8, 0xc]);
+    assert_eq!(func.cfg().num_edges(), 4);
+}
+
+#[test]
+fn calls() {
+    let reg = wrap(
+        &[
+            0x0c000004, // 0x00: jal 0x10
+            0x00000000, // 0x04: nop
+            0x03e00008, // 0x08: jr ra
+            0x00000000, // 0x0c: nop
+            0x03e00008, // 0x10: jr ra
+            0x00000000, // 0x14: nop
+        ],
+    );
+    let func = Function::new::<Mips>(0, &reg, None, Cpu::new(Mode::Mips32, Endianess::Big)).unwrap();
+
+    assert_eq!(starts(&func), vec![0x0]);
+    assert_eq!(func.collect_call_addresses(), vec![0x10]);
+}
+
+#[test]
+fn pic_calls() {
+    let reg = wrap(
+        &[
+            0x3c1c0001, // 0x00: lui gp, 0x1
+            0x279c8020, // 0x04: addiu gp, gp, -0x7fe0
+            0x0399e021, // 0x08: addu gp, gp, t9
+            0x8f998018, // 0x0c: lw t9, -0x7fe8(gp)
+            0x0320f809, // 0x10: jalr t9
+            0x00000000, // 0x14: nop
+            0x3c190000, // 0x18: lui t9, 0x0
+            0x27390040, // 0x1c: addiu t9, t9, 0x40
+            0x0320f809, // 0x20: jalr t9
+            0x00000000, // 0x24: nop
+            0x03e00008, // 0x28: jr ra
+            0x00000000, // 0x2c: nop
+            0x00000000, // 0x30: GOT[0]
+            0x00000000, // 0x34: GOT[1]
+            0x00000080, // 0x38: GOT[2]
+        ],
+    );
+    let cpu = Cpu::new(Mode::Mips32, Endianess::Big);
+    let func = Function::new::<Mips>(0, &reg, None, cpu.clone()).unwrap();
+
+    assert_eq!(cpu.global_pointer(), Some(0x8020));
+    assert_eq!(func.collect_call_addresses(), vec![0x80, 0x40]);
+}
+
+#[test]
+fn little_endian_64() {
+    let reg = Region::wrap(
+        "ram".to_string(),
+        vec![
+            0x2d, 0x10, 0x85, 0x00, // daddu v0, a0, a1
+            0x08, 0x00, 0xe0, 0x03, // jr ra
+            0x00, 0x00, 0x00, 0x00, // nop
+        ],
+    );
+    let func = Function::new::<Mips>(0, &reg, None, Cpu::new(Mode::Mips64, Endianess::Little)).unwrap();
+
+    assert_eq!(starts(&func), vec![0x0]);
+    assert_eq!(func.end(), 0xc);
+    assert!(Function::new::<Mips>(0, &reg, None, Cpu::new(Mode::Mips32, Endianess::Little)).is_err());
+}
@@ -0,0 +1,143 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Bank switched address translation.
+//!
+//! Cartridge based systems like the NES or the C64 map ROM larger than the 64 KiB address space
+//! of the 6502 through windows whose contents are selected by writing to a mapper register. The
+//! `Region` holds the whole ROM, so CPU addresses in jump and call targets need to be translated
+//! to region offsets. Which bank is selected at run time is unknown to the disassembler.
+//! [`Banking`](struct.Banking.html) assumes that targets inside the window of the jumping
+//! instruction stay in the same bank, which is the common case for code that switches banks via
+//! a trampoline in a fixed window.
+
+use std::fmt::Debug;
+
+/// Translation between CPU addresses and region offsets.
+pub trait Mapper: Debug + Send + Sync {
+    /// Region offset of the CPU address `addr`. `from` is the region offset of the instruction
+    /// referencing `addr`, if there is one.
+    fn offset(&self, addr: u16, from: Option<u64>) -> u64;
+
+    /// CPU address the byte at region offset `offset` is visible at.
+    fn address(&self, offset: u64) -> u16;
+}
+
+/// Part of the CPU address space mapped to one of several banks.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Window {
+    /// First CPU address of the window
+    pub start: u16,
+    /// Size in bytes
+    pub size: u32,
+    /// Region offsets of the banks selectable into the window. The first is assumed if the bank
+    /// can't be inferred.
+    pub banks: Vec<u64>,
+}
+
+impl Window {
+    fn contains(&self, addr: u16) -> bool {
+        addr >= self.start && (addr as u32) < self.start as u32 + self.size
+    }
+
+    fn bank_of(&self, offset: u64) -> Option<u64> {
+        self.banks.iter().cloned().find(|&b| offset >= b && offset < b + self.size as u64)
+    }
+}
+
+/// Static bank layout. CPU addresses outside all windows are mapped to the region offset of the
+/// same value.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct Banking {
+    pub windows: Vec<Window>,
+}
+
+impl Banking {
+    /// Layout without any windows.
+    pub fn new() -> Banking {
+        Banking { windows: vec![] }
+    }
+
+    /// Adds a window of `size` bytes at `start` selecting one of `banks`.
+    pub fn window(mut self, start: u16, size: u32, banks: Vec<u64>) -> Banking {
+        self.windows.push(Window { start: start, size: size, banks: banks });
+        self
+    }
+
+    /// NES UxROM (iNES mapper 2) and compatible: `banks` 16 KiB PRG ROM banks starting at region
+    /// offset `prg`. All but the last are switchable at `$8000`, the last one is fixed at `$C000`.
+    pub fn uxrom(prg: u64, banks: usize) -> Banking {
+        let last = prg + (banks.saturating_sub(1) as u64) * 0x4000;
+        let switchable = (0..banks.saturating_sub(1)).map(|b| prg + b as u64 * 0x4000).collect();
+
+        Banking::new().window(0x8000, 0x4000, switchable).window(0xc000, 0x4000, vec![last])
+    }
+
+    /// C64 cartridges with `banks` 8 KiB banks at `$8000` (ROML), like the Ocean and Magic Desk
+    /// types. The banks start at region offset `rom`.
+    pub fn c64_roml(rom: u64, banks: usize) -> Banking {
+        let banks = (0..banks).map(|b| rom + b as u64 * 0x2000).collect();
+
+        Banking::new().window(0x8000, 0x2000, banks)
+    }
+}
+
+impl Mapper for Banking {
+    fn offset(&self, addr: u16, from: Option<u64>) -> u64 {
+        match self.windows.iter().find(|w| w.contains(addr) && !w.banks.is_empty()) {
+            Some(w) => from.and_then(|f| w.bank_of(f)).unwrap_or(w.banks[0]) + (addr - w.start) as u64,
+            None => addr as u64,
+        }
+    }
+
+    fn address(&self, offset: u64) -> u16 {
+        for w in self.windows.iter() {
+            if let Some(b) = w.bank_of(offset) {
+                return w.start.wrapping_add((offset - b) as u16);
+            }
+        }
+
+        offset as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uxrom() {
+        let m = Banking::uxrom(0x10, 4);
+
+        assert_eq!(m.offset(0x8123, None), 0x10 + 0x123);
+        assert_eq!(m.offset(0x8123, Some(0x10 + 0x8000 + 5)), 0x10 + 0x8123);
+        assert_eq!(m.offset(0xfffc, Some(0x10 + 0x4000)), 0x10 + 0xfffc);
+        assert_eq!(m.offset(0x0200, Some(0x10)), 0x200);
+        assert_eq!(m.address(0x10 + 0x4000 + 7), 0x8007);
+        assert_eq!(m.address(0x10 + 0xc000), 0xc000);
+    }
+
+    #[test]
+    fn c64() {
+        let m = Banking::c64_roml(0, 16);
+
+        assert_eq!(m.offset(0x8010, Some(0x6000)), 0x6010);
+        assert_eq!(m.offset(0xa000, Some(0x6000)), 0xa000);
+        assert_eq!(m.address(0x1ffff), 0x9fff);
+    }
+}
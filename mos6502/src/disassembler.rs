@@ -16,8 +16,11 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use banking::Mapper;
 use panopticon_core::{Architecture, Guard, Lvalue, Match, Region, Result, Rvalue, State, Statement};
 use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use syntax;
 
 #[derive(Clone,Debug)]
@@ -27,7 +30,7 @@ impl Architecture for Mos {
     type Token = u8;
     type Configuration = Variant;
 
-    fn prepare(reg: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+    fn prepare(reg: &Region, cfg: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
         let i = reg.iter();
         let iv = vec![
             ("NMI", 0xfffa, "NMI vector"),
@@ -37,13 +40,12 @@ impl Architecture for Mos {
         let mut ret = vec![];
 
         for v in iv {
-            let mut j = i.clone();
-
-            j.seek(v.1);
+            let vector = cfg.offset(v.1, None);
+            let mut j = i.seek(vector);
             let maybe_lo = j.next();
             let maybe_hi = j.next();
             if let (Some(Some(hi)), Some(Some(lo))) = (maybe_hi, maybe_lo) {
-                let addr = ((hi as u64) << 8) | (lo as u64);
+                let addr = cfg.offset(((hi as u16) << 8) | (lo as u16), Some(vector));
 
                 ret.push((v.0, addr, v.2))
             }
@@ -98,11 +100,60 @@ lazy_static! {
 pub struct Variant {
     pub arg: Option<Rvalue>,
     pub rel: Option<i16>,
+    /// Bank switching hardware. Without one region offsets are CPU addresses.
+    pub mapper: Option<Arc<Mapper>>,
+}
+
+// mappers are told apart by identity
+impl Hash for Variant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.arg.hash(state);
+        self.rel.hash(state);
+        self.mapper.as_ref().map(|m| &**m as *const Mapper as *const u8 as usize).hash(state);
+    }
 }
 
 impl Variant {
     pub fn mos6502() -> Variant {
-        Variant { arg: None, rel: None }
+        Variant { arg: None, rel: None, mapper: None }
+    }
+
+    /// Translates jump and call targets with `mapper`.
+    pub fn with_mapper(mut self, mapper: Arc<Mapper>) -> Variant {
+        self.mapper = Some(mapper);
+        self
+    }
+
+    /// Region offset of the CPU address `addr`, referenced from region offset `from`.
+    pub fn offset(&self, addr: u16, from: Option<u64>) -> u64 {
+        match self.mapper {
+            Some(ref m) => m.offset(addr, from),
+            None => addr as u64,
+        }
+    }
+
+    /// CPU address of the instruction at region offset `offset`.
+    pub fn address(&self, offset: u64) -> u16 {
+        match self.mapper {
+            Some(ref m) => m.address(offset),
+            None => offset as u16,
+        }
+    }
+
+    /// Jump target for the CPU address `addr` used by the instruction at region offset `from`.
+    pub fn target(&self, addr: u16, from: u64) -> Rvalue {
+        match self.mapper {
+            Some(ref m) => Rvalue::new_u32(m.offset(addr, Some(from)) as u32),
+            None => Rvalue::new_u16(addr),
+        }
+    }
+
+    /// Address of the instruction following the `len` bytes long one at region offset `from`.
+    pub fn fallthrough(&self, from: u64, len: usize) -> Rvalue {
+        match self.mapper {
+            Some(_) => Rvalue::new_u32((from + len as u64) as u32),
+            None => Rvalue::new_u16((from + len as u64) as u16),
+        }
     }
 }
 
@@ -111,7 +162,7 @@ pub fn nonary(opcode: &'static str, sem: fn(&mut Variant) -> Result<Vec<Statemen
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
 
             st.mnemonic_dynargs(
                     len,
@@ -120,7 +171,7 @@ pub fn nonary(opcode: &'static str, sem: fn(&mut Variant) -> Result<Vec<Statemen
                     &|c| -> Result<(Vec<Rvalue>, Vec<Statement>)> { Ok((vec![], sem(c)?)) },
                 )
                 .unwrap();
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -150,7 +201,7 @@ pub fn implied(opcode: &'static str, _arg0: &Lvalue, sem: fn(&mut Variant, Rvalu
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             st.mnemonic(
                     len,
                     &opcode,
@@ -159,7 +210,7 @@ pub fn implied(opcode: &'static str, _arg0: &Lvalue, sem: fn(&mut Variant, Rvalu
                     &|c| -> Result<Vec<Statement>> { sem(c, arg0.clone().into()) },
                 )
                 .unwrap();
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -171,7 +222,7 @@ pub fn immediate(opcode: &'static str, sem: fn(&mut Variant, Rvalue) -> Result<V
         move |st: &mut State<Mos>| -> bool {
             let _arg = st.configuration.arg.clone();
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             if let Some(arg) = _arg {
                 st.mnemonic_dynargs(
                         len,
@@ -180,7 +231,7 @@ pub fn immediate(opcode: &'static str, sem: fn(&mut Variant, Rvalue) -> Result<V
                         &|c| -> Result<(Vec<Rvalue>, Vec<Statement>)> { Ok((vec![arg.clone()], sem(c, arg.clone())?)) },
                     )
                     .unwrap();
-                st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+                st.jump(next, Guard::always()).unwrap();
                 true
             } else {
                 false
@@ -194,7 +245,7 @@ pub fn zpage(opcode: &'static str, sem: fn(&mut Variant, Rvalue) -> Result<Vec<S
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             let base = st.configuration.arg.clone().unwrap();
 
             st.mnemonic(
@@ -214,7 +265,7 @@ pub fn zpage(opcode: &'static str, sem: fn(&mut Variant, Rvalue) -> Result<Vec<S
                     },
                 )
                 .unwrap();
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -226,7 +277,7 @@ pub fn zpage_offset(opcode: &'static str, _arg1: &Lvalue, sem: fn(&mut Variant,
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             let base = st.configuration.arg.clone().unwrap();
             let base_val = if let Rvalue::Constant { ref value, .. } = base {
                 *value
@@ -268,7 +319,7 @@ pub fn zpage_offset(opcode: &'static str, _arg1: &Lvalue, sem: fn(&mut Variant,
                 )
                 .unwrap();
 
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -280,7 +331,7 @@ pub fn zpage_index(opcode: &'static str, _arg1: Lvalue, sem: fn(&mut Variant, Rv
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             let base = st.configuration.arg.clone().unwrap();
             let base_val = if let Rvalue::Constant { ref value, .. } = base {
                 *value
@@ -330,7 +381,7 @@ pub fn zpage_index(opcode: &'static str, _arg1: Lvalue, sem: fn(&mut Variant, Rv
                     &|c| -> Result<Vec<Statement>> { sem(c, rreil_rvalue!{ val:8 }) },
                 )
                 .unwrap();
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -340,7 +391,7 @@ pub fn absolute(opcode: &'static str, sem: fn(&mut Variant, Rvalue) -> Result<Ve
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             let base = st.configuration.arg.clone().unwrap();
 
             st.mnemonic(
@@ -359,7 +410,7 @@ pub fn absolute(opcode: &'static str, sem: fn(&mut Variant, Rvalue) -> Result<Ve
                     },
                 )
                 .unwrap();
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -370,7 +421,7 @@ pub fn absolute_offset(opcode: &'static str, _arg1: &Lvalue, sem: fn(&mut Varian
     Box::new(
         move |st: &mut State<Mos>| -> bool {
             let len = st.tokens.len();
-            let next = (st.address + len as u64) as u16;
+            let next = st.configuration.fallthrough(st.address, len);
             let base = st.configuration.arg.clone().unwrap();
             let base_val = if let Rvalue::Constant { ref value, .. } = base {
                 *value
@@ -411,7 +462,7 @@ pub fn absolute_offset(opcode: &'static str, _arg1: &Lvalue, sem: fn(&mut Varian
                     &|c| -> Result<Vec<Statement>> { sem(c, rreil_rvalue!{ val:8 }) },
                 )
                 .unwrap();
-            st.jump(Rvalue::new_u16(next), Guard::always()).unwrap();
+            st.jump(next, Guard::always()).unwrap();
             true
         }
     )
@@ -430,15 +481,16 @@ pub fn branch(opcode: &'static str, _flag: &Lvalue, _set: bool) -> Box<Fn(&mut S
         move |st: &mut State<Mos>| -> bool {
             let rel = st.configuration.rel.unwrap();
             let len = st.tokens.len();
-            let fallthru = (st.address + len as u64) as u16;
+            let fallthru = st.configuration.fallthrough(st.address, len);
             let g = Guard::from_flag(&flag.clone().into()).ok().unwrap();
-            let k = (st.address as i16).wrapping_add(rel) as u16;
+            let k = (st.configuration.address(st.address) as i16).wrapping_add(rel) as u16;
+            let k = st.configuration.target(k, st.address);
 
             st.mnemonic(
                     2,
                     opcode,
                     "{c:ram}",
-                    vec![rreil_rvalue!{ [k]:16 }],
+                    vec![k.clone()],
                     &|_c| -> Result<Vec<Statement>> {
                         rreil!{
                 cmpeq flag:1, (set), (flag);
@@ -447,8 +499,8 @@ pub fn branch(opcode: &'static str, _flag: &Lvalue, _set: bool) -> Box<Fn(&mut S
                 )
                 .unwrap();
 
-            st.jump(Rvalue::new_u16(fallthru), g.negation()).unwrap();
-            st.jump(Rvalue::new_u16(k), g).unwrap();
+            st.jump(fallthru, g.negation()).unwrap();
+            st.jump(k, g).unwrap();
             true
         }
     )
@@ -458,8 +510,10 @@ pub fn branch(opcode: &'static str, _flag: &Lvalue, _set: bool) -> Box<Fn(&mut S
 mod tests {
     use super::*;
     use super::syntax::disassembler;
+    use banking::Banking;
     use panopticon_core::{Region, Rvalue};
     use std::borrow::Cow;
+    use std::sync::Arc;
 
     #[test]
     fn all() {
@@ -1227,4 +1281,60 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn undocumented() {
+        let test_vectors = vec![
+            (vec![0xa7, 0x80], "lax!", "X"),
+            (vec![0x07, 0x80], "slo!", "A"),
+            (vec![0xc7, 0x80], "dcp!", "C"),
+            (vec![0x4b, 0x0f], "alr!", "A"),
+            (vec![0xcb, 0x0f], "axs!", "X"),
+            (vec![0x6b, 0x0f], "arr!", "V"),
+        ];
+        let main = disassembler();
+
+        for (bytes, opname, reg) in test_vectors {
+            let reg_ = Region::wrap("base".to_string(), bytes);
+            let st = main.next_match(&mut reg_.iter().seek(0), 0, Variant::mos6502()).unwrap();
+            let mne = st.mnemonics.last().unwrap();
+
+            assert_eq!(mne.opcode, opname);
+            assert!(
+                mne.instructions.iter().any(
+                    |s| match s.assignee {
+                        Lvalue::Variable { ref name, .. } => name == reg,
+                        _ => false,
+                    }
+                ),
+                "{} doesn't write {}",
+                opname,
+                reg
+            );
+        }
+    }
+
+    #[test]
+    fn banked_targets() {
+        // UxROM with two switchable banks and a fixed one.
+        let mut rom = vec![0xea; 3 * 0x4000];
+
+        // Bank 1 at $8000: jsr $8010, jmp $c000, beq $8000
+        rom[0x4000..0x4008].copy_from_slice(&[0x20, 0x10, 0x80, 0x4c, 0x00, 0xc0, 0xf0, 0xfa]);
+        // Reset vector in the fixed bank
+        rom[0xbffc] = 0x00;
+        rom[0xbffd] = 0xc0;
+
+        let reg = Region::wrap("prg".to_string(), rom);
+        let cfg = Variant::mos6502().with_mapper(Arc::new(Banking::uxrom(0, 3)));
+        let jsr = Mos::decode(&reg, 0x4000, &cfg).unwrap();
+        let jmp = Mos::decode(&reg, 0x4003, &cfg).unwrap();
+        let beq = Mos::decode(&reg, 0x4006, &cfg).unwrap();
+
+        assert_eq!(jsr.mnemonics[0].operands, vec![Rvalue::new_u32(0x4010)]);
+        assert_eq!(jsr.jumps[0].1, Rvalue::new_u32(0x4003));
+        assert_eq!(jmp.jumps[0].1, Rvalue::new_u32(0x8000));
+        assert_eq!(beq.jumps[1].1, Rvalue::new_u32(0x4000));
+        assert!(Mos::prepare(&reg, &cfg).unwrap().contains(&("RESET", 0x8000, "Reset routine")));
+    }
 }
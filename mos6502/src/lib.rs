@@ -18,7 +18,12 @@
 
 //! MOS 6502 disassembler.
 //!
-//! This disassembler handles all documented opcode of the MOS Technology 6502 microprocessor.
+//! This disassembler handles all documented opcode of the MOS Technology 6502 microprocessor
+//! and the stable undocumented ones. Undocumented opcodes are suffixed with `!`, the unstable
+//! ones with `?` or decoded without semantics.
+//!
+//! Bank switched cartridges are supported by configuring a [`Mapper`](banking/trait.Mapper.html)
+//! that translates jump and call targets to region offsets.
 
 #![allow(missing_docs)]
 
@@ -36,3 +41,6 @@ mod semantic;
 
 mod disassembler;
 pub use disassembler::{Mos, Variant};
+
+pub mod banking;
+pub use banking::{Banking, Mapper, Window};
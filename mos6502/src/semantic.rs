@@ -611,8 +611,138 @@ pub fn tya(_cg: &mut Variant) -> Result<Vec<Statement>> {
     trr(_cg, &Y, &A)
 }
 
+// Stable undocumented opcodes. Read-modify-write variants operate on the loaded value like
+// ROL and ROR do.
+
+// ASL + ORA
+pub fn slo(_cg: &mut Variant, _r: Rvalue) -> Result<Vec<Statement>> {
+    let r = Lvalue::from_rvalue(_r.clone()).unwrap();
+    let mut stmts = rreil!{
+        mov C:1, (r.extract(1,7).unwrap());
+        shl (r), (r), [1]:8;
+    }?;
+
+    stmts.append(&mut ora(_cg, _r)?);
+    Ok(stmts)
+}
+
+// ROL + AND
+pub fn rla(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rol(_cg, r.clone())?;
+
+    stmts.append(&mut and(_cg, r)?);
+    Ok(stmts)
+}
+
+// LSR + EOR
+pub fn sre(_cg: &mut Variant, _r: Rvalue) -> Result<Vec<Statement>> {
+    let r = Lvalue::from_rvalue(_r.clone()).unwrap();
+    let mut stmts = rreil!{
+        mov C:1, (r.extract(1,0).unwrap());
+        shr (r), (r), [1]:8;
+    }?;
+
+    stmts.append(&mut eor(_cg, _r)?);
+    Ok(stmts)
+}
+
+// ROR + ADC
+pub fn rra(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = ror(_cg, r.clone())?;
+
+    stmts.append(&mut adc(_cg, r)?);
+    Ok(stmts)
+}
+
+// Stores A & X
+pub fn sax(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        and ax:8, A:8, X:8;
+    }?;
+
+    stmts.append(&mut st(_cg, rreil_lvalue!{ ax:8 }, r)?);
+    Ok(stmts)
+}
+
+// LDA + TAX
+pub fn lax(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = lda(_cg, r)?;
+
+    stmts.append(&mut rreil!{
+        mov X:8, A:8;
+    }?);
+    Ok(stmts)
+}
+
+// DEC + CMP
+pub fn dcp(_cg: &mut Variant, _r: Rvalue) -> Result<Vec<Statement>> {
+    let r = Lvalue::from_rvalue(_r.clone()).unwrap();
+    let mut stmts = rreil!{
+        sub (r), (r), [1]:8;
+    }?;
+
+    stmts.append(&mut cpa(_cg, _r)?);
+    Ok(stmts)
+}
+
+// INC + SBC
+pub fn isc(_cg: &mut Variant, _r: Rvalue) -> Result<Vec<Statement>> {
+    let r = Lvalue::from_rvalue(_r.clone()).unwrap();
+    let mut stmts = rreil!{
+        add (r), (r), [1]:8;
+    }?;
+
+    stmts.append(&mut sbc(_cg, _r)?);
+    Ok(stmts)
+}
+
+// AND, bit 7 of the result is copied into C
+pub fn anc(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = and(_cg, r)?;
+
+    stmts.append(&mut rreil!{
+        mov C:1, N:1;
+    }?);
+    Ok(stmts)
+}
+
+// AND + LSR A
+pub fn alr(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        and A:8, A:8, (r);
+        mov C:1, A:1;
+        shr A:8, A:8, [1]:8;
+        cmpeq Z:1, A:8, [0]:8;
+        mov N:1, [0]:1;
+    }
+}
+
+// AND + ROR A. C is bit 6 of the result and V is bit 6 xor bit 5.
+pub fn arr(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        and A:8, A:8, (r);
+        shr A:8, A:8, [1]:8;
+        sel/7 A:8, C:1;
+        mov C:1, A:1/6;
+        xor V:1, A:1/6, A:1/5;
+        cmpeq Z:1, A:8, [0]:8;
+        cmplts N:1, A:8, [0]:8;
+    }
+}
+
+// X = (A & X) - imm, without borrow. C is set like CMP does.
+pub fn axs(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        and ax:8, A:8, X:8;
+        cmpleu C:1, (r), ax:8;
+        sub X:8, ax:8, (r);
+        cmpeq Z:1, X:8, [0]:8;
+        cmplts N:1, X:8, [0]:8;
+    }
+}
+
 pub fn jmp_direct(st: &mut State<Mos>) -> bool {
-    let next = Rvalue::new_u16(st.get_group("immlo") as u16 | ((st.get_group("immhi") as u16) << 8));
+    let next = st.configuration.target(st.get_group("immlo") as u16 | ((st.get_group("immhi") as u16) << 8), st.address);
 
     st.mnemonic(
             3,
@@ -659,8 +789,8 @@ pub fn jmp_indirect(st: &mut State<Mos>) -> bool {
 }
 
 pub fn jsr(st: &mut State<Mos>) -> bool {
-    let next = Rvalue::new_u16(st.address as u16 + 3);
-    let target = Rvalue::new_u16(st.get_group("immlo") as u16 | ((st.get_group("immhi") as u16) << 8));
+    let next = st.configuration.fallthrough(st.address, 3);
+    let target = st.configuration.target(st.get_group("immlo") as u16 | ((st.get_group("immhi") as u16) << 8), st.address);
 
     st.mnemonic(
             3,
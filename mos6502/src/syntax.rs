@@ -59,7 +59,6 @@ pub fn disassembler() -> Arc<Disassembler<Mos>> {
             true
         });
 
-    // FIXME: Add illegal opcodes.
     new_disassembler!(Mos =>
         // ADC
         [ 0x61, imm8 ] = zpage_index("adc", rreil_lvalue!{ X:8 }, adc),	// 011 000 01 xxxx xxxx
@@ -334,91 +333,91 @@ pub fn disassembler() -> Arc<Disassembler<Mos>> {
 
 
         // SLO (ASL + ORA), ANC (AND + [ASL]carry only)
-        [ 0x03, imm8 ] = zpage_index("slo!", rreil_lvalue!{ X:8 }, nop_r),	// 000 000 11
-        [ 0x07, imm8 ] = zpage("slo!", nop_r),		// 000 001 11
-        [ 0x0b, imm8 ] = immediate("anc!", nop_r),		// 000 010 11 ANC!
-        [ 0x0f, imm16 ] = absolute("slo!", nop_r),		// 000 011 11
-        [ 0x13, imm8 ] = zpage_index("slo!", rreil_lvalue!{ Y:8 }, nop_r),	// 000 100 11
-        [ 0x17, imm8 ] = zpage_offset("slo!", &*X, nop_r),	// 000 101 11
-        [ 0x1b, imm16 ] = absolute_offset("slo!", &*Y, nop_r),	// 000 110 11
-        [ 0x1f, imm16 ] = absolute_offset("slo!", &*X, nop_r),	// 000 111 11
+        [ 0x03, imm8 ] = zpage_index("slo!", rreil_lvalue!{ X:8 }, slo),	// 000 000 11
+        [ 0x07, imm8 ] = zpage("slo!", slo),		// 000 001 11
+        [ 0x0b, imm8 ] = immediate("anc!", anc),		// 000 010 11 ANC!
+        [ 0x0f, imm16 ] = absolute("slo!", slo),		// 000 011 11
+        [ 0x13, imm8 ] = zpage_index("slo!", rreil_lvalue!{ Y:8 }, slo),	// 000 100 11
+        [ 0x17, imm8 ] = zpage_offset("slo!", &*X, slo),	// 000 101 11
+        [ 0x1b, imm16 ] = absolute_offset("slo!", &*Y, slo),	// 000 110 11
+        [ 0x1f, imm16 ] = absolute_offset("slo!", &*X, slo),	// 000 111 11
 
         // RLA (ROL + AND), ANC (AND + [ROL]carry only)
-        [ 0x23, imm8 ] = zpage_index("rla!", rreil_lvalue!{ X:8 }, nop_r),	// 001 000 11
-        [ 0x27, imm8 ] = zpage("rla!", nop_r),		// 001 001 11
-        [ 0x2b, imm8 ] = immediate("anc!", nop_r),		// 001 010 11 ANC!
-        [ 0x2f, imm16 ] = absolute("rla!", nop_r),		// 001 011 11
-        [ 0x33, imm8 ] = zpage_index("rla!", rreil_lvalue!{ Y:8 }, nop_r),	// 001 100 11
-        [ 0x37, imm8 ] = zpage_offset("rla!", &*X, nop_r),	// 001 101 11
-        [ 0x3b, imm16 ] = absolute_offset("rla!", &*Y, nop_r),	// 001 110 11
-        [ 0x3f, imm16 ] = absolute_offset("rla!", &*X, nop_r),	// 001 111 11
+        [ 0x23, imm8 ] = zpage_index("rla!", rreil_lvalue!{ X:8 }, rla),	// 001 000 11
+        [ 0x27, imm8 ] = zpage("rla!", rla),		// 001 001 11
+        [ 0x2b, imm8 ] = immediate("anc!", anc),		// 001 010 11 ANC!
+        [ 0x2f, imm16 ] = absolute("rla!", rla),		// 001 011 11
+        [ 0x33, imm8 ] = zpage_index("rla!", rreil_lvalue!{ Y:8 }, rla),	// 001 100 11
+        [ 0x37, imm8 ] = zpage_offset("rla!", &*X, rla),	// 001 101 11
+        [ 0x3b, imm16 ] = absolute_offset("rla!", &*Y, rla),	// 001 110 11
+        [ 0x3f, imm16 ] = absolute_offset("rla!", &*X, rla),	// 001 111 11
 
         // SRE (ASR + EOR), ALR (AND + LSR)
-        [ 0x43, imm8 ] = zpage_index("sre!", rreil_lvalue!{ X:8 }, nop_r),	// 010 000 11
-        [ 0x47, imm8 ] = zpage("sre!", nop_r),		// 010 001 11
-        [ 0x4b, imm8 ] = immediate("alr!", nop_r),		// 010 010 11 ALR!
-        [ 0x4f, imm16 ] = absolute("sre!", nop_r),		// 010 011 11
-        [ 0x53, imm8 ] = zpage_index("sre!", rreil_lvalue!{ Y:8 }, nop_r),	// 010 100 11
-        [ 0x57, imm8 ] = zpage_offset("sre!", &*X, nop_r),	// 010 101 11
-        [ 0x5b, imm16 ] = absolute_offset("sre!", &*Y, nop_r),	// 010 110 11
-        [ 0x5f, imm16 ] = absolute_offset("sre!", &*X, nop_r),	// 010 111 11
+        [ 0x43, imm8 ] = zpage_index("sre!", rreil_lvalue!{ X:8 }, sre),	// 010 000 11
+        [ 0x47, imm8 ] = zpage("sre!", sre),		// 010 001 11
+        [ 0x4b, imm8 ] = immediate("alr!", alr),		// 010 010 11 ALR!
+        [ 0x4f, imm16 ] = absolute("sre!", sre),		// 010 011 11
+        [ 0x53, imm8 ] = zpage_index("sre!", rreil_lvalue!{ Y:8 }, sre),	// 010 100 11
+        [ 0x57, imm8 ] = zpage_offset("sre!", &*X, sre),	// 010 101 11
+        [ 0x5b, imm16 ] = absolute_offset("sre!", &*Y, sre),	// 010 110 11
+        [ 0x5f, imm16 ] = absolute_offset("sre!", &*X, sre),	// 010 111 11
 
         // RRA (ROR + ADC), ARR (AND + ROR)
         // note to ARR: part of this command are some ADC mechanisms.
         // following effects appear after AND but before ROR: the V-Flag
         // is set according to (A and #{imm})+#{imm}, bit 0 does NOT go
         // into carry, but bit 7 is exchanged with the carry.
-        [ 0x63, imm8 ] = zpage_index("rra!", rreil_lvalue!{ X:8 }, nop_r),	// 011 000 11
-        [ 0x67, imm8 ] = zpage("rra!", nop_r),		// 011 001 11
-        [ 0x6b, imm8 ] = immediate("arr!", nop_r),		// 011 010 11 ARR!
-        [ 0x6f, imm16 ] = absolute("rra!", nop_r),		// 011 011 11
-        [ 0x73, imm8 ] = zpage_index("rra!", rreil_lvalue!{ Y:8 }, nop_r),	// 011 100 11
-        [ 0x77, imm8 ] = zpage_offset("rra!", &*X, nop_r),	// 011 101 11
-        [ 0x7b, imm16 ] = absolute_offset("rra!", &*Y, nop_r),	// 011 110 11
-        [ 0x7f, imm16 ] = absolute_offset("rra!", &*X, nop_r),	// 011 111 11
+        [ 0x63, imm8 ] = zpage_index("rra!", rreil_lvalue!{ X:8 }, rra),	// 011 000 11
+        [ 0x67, imm8 ] = zpage("rra!", rra),		// 011 001 11
+        [ 0x6b, imm8 ] = immediate("arr!", arr),		// 011 010 11 ARR!
+        [ 0x6f, imm16 ] = absolute("rra!", rra),		// 011 011 11
+        [ 0x73, imm8 ] = zpage_index("rra!", rreil_lvalue!{ Y:8 }, rra),	// 011 100 11
+        [ 0x77, imm8 ] = zpage_offset("rra!", &*X, rra),	// 011 101 11
+        [ 0x7b, imm16 ] = absolute_offset("rra!", &*Y, rra),	// 011 110 11
+        [ 0x7f, imm16 ] = absolute_offset("rra!", &*X, rra),	// 011 111 11
 
         // SAX (store A&X into {adr})
         // AHX stores A&X&H into {adr}
         // XAA? TXA + AND #{imm}
         // TAS stores A&X into S and A&X&H into {adr}
-        [ 0x83, imm8 ] = zpage_index("sax!", rreil_lvalue!{ X:8 }, nop_r),	// 100 000 11
-        [ 0x87, imm8 ] = zpage("sax!", nop_r),		// 100 001 11
+        [ 0x83, imm8 ] = zpage_index("sax!", rreil_lvalue!{ X:8 }, sax),	// 100 000 11
+        [ 0x87, imm8 ] = zpage("sax!", sax),		// 100 001 11
         [ 0x8b, imm8 ] = immediate("xaa?", nop_r),		// 100 010 11 XAA!
-        [ 0x8f, imm16 ] = absolute("sax!", nop_r),		// 100 011 11
+        [ 0x8f, imm16 ] = absolute("sax!", sax),		// 100 011 11
         [ 0x93, imm8 ] = zpage_index("ahx!", rreil_lvalue!{ Y:8 }, nop_r),	// 100 100 11
-        [ 0x97, imm8 ] = zpage_offset("sax!", &*Y, nop_r),	// 100 101 11
+        [ 0x97, imm8 ] = zpage_offset("sax!", &*Y, sax),	// 100 101 11
         [ 0x9b, imm16 ] = absolute_offset("tas!", &*Y, nop_r),	// 100 110 11
         [ 0x9f, imm16 ] = absolute_offset("ahx!", &*Y, nop_r),	// 100 111 11
 
         // LAX (LDA + TAX), LAS (stores {adr}&S into A, X and S)
-        [ 0xa3, imm8 ] = zpage_index("lax!", rreil_lvalue!{ X:8 }, nop_r),	// 101 000 11
-        [ 0xa7, imm8 ] = zpage("lax!", nop_r),		// 101 001 11
+        [ 0xa3, imm8 ] = zpage_index("lax!", rreil_lvalue!{ X:8 }, lax),	// 101 000 11
+        [ 0xa7, imm8 ] = zpage("lax!", lax),		// 101 001 11
         [ 0xab, imm8 ] = immediate("lax?", nop_r),		// 101 010 11
-        [ 0xaf, imm16 ] = absolute("lax!", nop_r),		// 101 011 11
-        [ 0xb3, imm8 ] = zpage_index("lax!", rreil_lvalue!{ Y:8 }, nop_r),	// 101 100 11
-        [ 0xb7, imm8 ] = zpage_offset("lax!", &*Y, nop_r),	// 101 101 11
+        [ 0xaf, imm16 ] = absolute("lax!", lax),		// 101 011 11
+        [ 0xb3, imm8 ] = zpage_index("lax!", rreil_lvalue!{ Y:8 }, lax),	// 101 100 11
+        [ 0xb7, imm8 ] = zpage_offset("lax!", &*Y, lax),	// 101 101 11
         [ 0xbb, imm16 ] = absolute_offset("las!", &*Y, nop_r),	// 101 110 11 LAS
-        [ 0xbf, imm16 ] = absolute_offset("lax!", &*Y, nop_r),	// 101 111 11
+        [ 0xbf, imm16 ] = absolute_offset("lax!", &*Y, lax),	// 101 111 11
 
         // DCP, AXS
-        [ 0xc3, imm8 ] = zpage_index("dcp!", rreil_lvalue!{ X:8 }, nop_r),	// 110 000 11
-        [ 0xc7, imm8 ] = zpage("dcp!", nop_r),		// 110 001 11
-        [ 0xcb, imm8 ] = immediate("axs!", nop_r),		// 110 010 11 AXS!
-        [ 0xcf, imm16 ] = absolute("dcp!", nop_r),		// 110 011 11
-        [ 0xd3, imm8 ] = zpage_index("dcp!", rreil_lvalue!{ Y:8 }, nop_r),	// 110 100 11
-        [ 0xd7, imm8 ] = zpage_offset("dcp!", &*X, nop_r),	// 110 101 11
-        [ 0xdb, imm16 ] = absolute_offset("dcp!", &*Y, nop_r),	// 110 110 11
-        [ 0xdf, imm16 ] = absolute_offset("dcp!", &*X, nop_r),	// 110 111 11
+        [ 0xc3, imm8 ] = zpage_index("dcp!", rreil_lvalue!{ X:8 }, dcp),	// 110 000 11
+        [ 0xc7, imm8 ] = zpage("dcp!", dcp),		// 110 001 11
+        [ 0xcb, imm8 ] = immediate("axs!", axs),		// 110 010 11 AXS!
+        [ 0xcf, imm16 ] = absolute("dcp!", dcp),		// 110 011 11
+        [ 0xd3, imm8 ] = zpage_index("dcp!", rreil_lvalue!{ Y:8 }, dcp),	// 110 100 11
+        [ 0xd7, imm8 ] = zpage_offset("dcp!", &*X, dcp),	// 110 101 11
+        [ 0xdb, imm16 ] = absolute_offset("dcp!", &*Y, dcp),	// 110 110 11
+        [ 0xdf, imm16 ] = absolute_offset("dcp!", &*X, dcp),	// 110 111 11
 
         // ISC, SBC
-        [ 0xe3, imm8 ] = zpage_index("isc!", rreil_lvalue!{ X:8 }, nop_r),	// 111 000 11
-        [ 0xe7, imm8 ] = zpage("isc!", nop_r),		// 111 001 11
-        [ 0xeb, imm8 ] = immediate("sbc!", nop_r),		// 111 010 11 SBC!
-        [ 0xef, imm16 ] = absolute("isc!", nop_r),		// 111 011 11
-        [ 0xf3, imm8 ] = zpage_index("isc!", rreil_lvalue!{ Y:8 }, nop_r),	// 111 100 11
-        [ 0xf7, imm8 ] = zpage_offset("isc!", &*X, nop_r),	// 111 101 11
-        [ 0xfb, imm16 ] = absolute_offset("isc!", &*Y, nop_r),	// 111 110 11
-        [ 0xff, imm16 ] = absolute_offset("isc!", &*X, nop_r),	// 111 111 11
+        [ 0xe3, imm8 ] = zpage_index("isc!", rreil_lvalue!{ X:8 }, isc),	// 111 000 11
+        [ 0xe7, imm8 ] = zpage("isc!", isc),		// 111 001 11
+        [ 0xeb, imm8 ] = immediate("sbc!", sbc),		// 111 010 11 SBC!
+        [ 0xef, imm16 ] = absolute("isc!", isc),		// 111 011 11
+        [ 0xf3, imm8 ] = zpage_index("isc!", rreil_lvalue!{ Y:8 }, isc),	// 111 100 11
+        [ 0xf7, imm8 ] = zpage_offset("isc!", &*X, isc),	// 111 101 11
+        [ 0xfb, imm16 ] = absolute_offset("isc!", &*Y, isc),	// 111 110 11
+        [ 0xff, imm16 ] = absolute_offset("isc!", &*X, isc),	// 111 111 11
 
         // catch all, FIXME: Add at least the args for illegal opcodes.
         _ = nonary("unk", nop)
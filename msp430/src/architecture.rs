@@ -0,0 +1,128 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use decode::{self, Flow};
+use panopticon_core::{Architecture, Guard, Match, Region, Result};
+use semantic::imm;
+
+#[derive(Clone,Debug)]
+pub enum Msp430 {}
+
+/// CPU model
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Model {
+    /// Classic MSP430 with a 16-bit address space
+    Msp430,
+    /// MSP430X with 20-bit registers and addresses
+    Msp430X,
+}
+
+impl Model {
+    /// Width of registers and addresses in bits.
+    pub fn width(&self) -> usize {
+        match *self {
+            Model::Msp430 => 16,
+            Model::Msp430X => 20,
+        }
+    }
+
+    /// Picks the model from the `e_flags` field of an ELF header. GCC and the TI tool chain
+    /// store the machine number there, 45 denotes the 430X.
+    pub fn from_elf_flags(flags: u32) -> Model {
+        if flags & 0xff == 45 { Model::Msp430X } else { Model::Msp430 }
+    }
+}
+
+/// Interrupt vector table at the top of the 16-bit address space. The last entry is the reset
+/// vector.
+const VECTORS: [(&'static str, &'static str); 16] = [
+    ("IRQ0", "Interrupt vector 0"),
+    ("IRQ1", "Interrupt vector 1"),
+    ("IRQ2", "Interrupt vector 2"),
+    ("IRQ3", "Interrupt vector 3"),
+    ("IRQ4", "Interrupt vector 4"),
+    ("IRQ5", "Interrupt vector 5"),
+    ("IRQ6", "Interrupt vector 6"),
+    ("IRQ7", "Interrupt vector 7"),
+    ("IRQ8", "Interrupt vector 8"),
+    ("IRQ9", "Interrupt vector 9"),
+    ("IRQ10", "Interrupt vector 10"),
+    ("IRQ11", "Interrupt vector 11"),
+    ("IRQ12", "Interrupt vector 12"),
+    ("IRQ13", "Interrupt vector 13"),
+    ("NMI", "Non-maskable interrupt handler"),
+    ("RESET", "Reset vector"),
+];
+
+impl Architecture for Msp430 {
+    type Token = u16;
+    type Configuration = Model;
+
+    fn prepare(reg: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        let mut ret = vec![];
+
+        for (i, &(name, comment)) in VECTORS.iter().enumerate() {
+            let mut j = reg.iter().seek(0xffe0 + 2 * i as u64);
+            let mut addr = 0u64;
+
+            for sh in 0..2 {
+                match j.next() {
+                    Some(Some(b)) => addr |= (b as u64) << (8 * sh),
+                    _ => return Ok(ret),
+                }
+            }
+
+            if addr != 0 && addr != 0xffff && addr % 2 == 0 && addr < reg.size() {
+                ret.push((name, addr, comment));
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        if start % 2 != 0 {
+            return Err(format!("unaligned MSP430 instruction at {:#x}", start).into());
+        }
+
+        let (insn, tokens) = decode::read(reg, start, *cfg)?;
+        let len = 2 * tokens.len() as u64;
+        let next = imm(start + len, cfg.width());
+        let jumps = match insn.flow.clone() {
+            Flow::Next | Flow::Call(_) => vec![(start, next, Guard::always())],
+            Flow::Jump(tgt) => vec![(start, tgt, Guard::always())],
+            Flow::Branch(tgt, flag) => {
+                let guard = Guard::from_flag(&flag)?;
+                vec![(start, tgt, guard.clone()), (start, next, guard.negation())]
+            }
+            Flow::Return => vec![],
+        };
+
+        debug!("disass @ {:#x}: {} {:?}", start, insn.opcode, insn.flow);
+
+        Ok(
+            Match::<Msp430> {
+                tokens: tokens,
+                mnemonics: vec![insn.mnemonic(start, len)?],
+                jumps: jumps,
+                configuration: cfg.clone(),
+            }
+        )
+    }
+}
@@ -0,0 +1,879 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! MSP430 and MSP430X instruction decoder.
+
+use architecture::Model;
+use operand::Opnd;
+use panopticon_core::{Mnemonic, Region, Result, Rvalue, Statement};
+use semantic::*;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Jumps to the target
+    Jump(Rvalue),
+    /// Jumps to the target if the flag is set, falls through otherwise
+    Branch(Rvalue, Rvalue),
+    /// Calls the target and falls through
+    Call(Rvalue),
+    /// Returns from a subroutine or interrupt
+    Return,
+}
+
+/// A single decoded instruction.
+#[derive(Clone,Debug)]
+pub struct Insn {
+    pub opcode: String,
+    pub format: String,
+    pub operands: Vec<Rvalue>,
+    pub statements: Vec<Statement>,
+    pub flow: Flow,
+}
+
+impl Insn {
+    fn new(opcode: &str, operands: Vec<Operand>, statements: Vec<Statement>) -> Insn {
+        let format = operands.iter().map(|x| x.0.clone()).collect::<Vec<_>>().join(", ");
+
+        Insn {
+            opcode: opcode.to_string(),
+            format: format,
+            operands: operands.into_iter().flat_map(|x| x.1).collect(),
+            statements: statements,
+            flow: Flow::Next,
+        }
+    }
+
+    fn flow(mut self, flow: Flow) -> Insn {
+        self.flow = flow;
+        self
+    }
+
+    pub fn mnemonic(&self, addr: u64, len: u64) -> Result<Mnemonic> {
+        Mnemonic::new(addr..addr + len, self.opcode.clone(), self.format.clone(), self.operands.iter(), self.statements.iter())
+    }
+}
+
+/// Reads the instruction words.
+pub struct Words<'a> {
+    region: &'a Region,
+    start: u64,
+    /// Register and address width
+    pub width: usize,
+    pub tokens: Vec<u16>,
+}
+
+impl<'a> Words<'a> {
+    pub fn new(region: &'a Region, start: u64, width: usize) -> Words<'a> {
+        Words { region: region, start: start, width: width, tokens: vec![] }
+    }
+
+    /// Address of the next word.
+    pub fn address(&self) -> u64 {
+        self.start + 2 * self.tokens.len() as u64
+    }
+
+    /// Reads the next little endian word.
+    pub fn word(&mut self) -> Result<u16> {
+        let mut i = self.region.iter().seek(self.address());
+        let mut ret = 0u16;
+
+        for sh in 0..2 {
+            match i.next() {
+                Some(Some(b)) => ret |= (b as u16) << (8 * sh),
+                _ => return Err(format!("MSP430 instruction at {:#x} truncated", self.start).into()),
+            }
+        }
+
+        self.tokens.push(ret);
+        Ok(ret)
+    }
+}
+
+type Operand = (String, Vec<Rvalue>);
+
+fn unknown(op: u16) -> Result<Insn> {
+    Err(format!("unknown MSP430 instruction {:#06x}", op).into())
+}
+
+fn suffix(size: usize) -> &'static str {
+    match size {
+        8 => ".b",
+        20 => ".a",
+        _ => "",
+    }
+}
+
+// Shows immediate jump and call targets as code pointers.
+fn code_target(opnd: Operand) -> Operand {
+    (opnd.0.replace("#{u}", "#{c:ram}"), opnd.1)
+}
+
+/// MSP430X extension word, prefixed to format I and II instructions.
+#[derive(Clone,Copy,Debug)]
+struct Ext(u16);
+
+enum Repeat {
+    Count(usize),
+    Reg(usize),
+}
+
+impl Ext {
+    /// Bits 16 to 19 of the source index or immediate.
+    fn src(&self) -> u64 {
+        ((self.0 >> 7) & 0xf) as u64
+    }
+
+    /// Bits 16 to 19 of the destination index.
+    fn dst(&self) -> u64 {
+        (self.0 & 0xf) as u64
+    }
+
+    /// Operand size selected by the A/L bit together with the B/W bit of the instruction.
+    fn size(&self, bw: bool) -> Option<usize> {
+        match (self.0 & 0x40 != 0, bw) {
+            (true, false) => Some(16),
+            (true, true) => Some(8),
+            (false, true) => Some(20),
+            (false, false) => None,
+        }
+    }
+
+    // Only used by register mode instructions
+    fn zero_carry(&self) -> bool {
+        self.0 & 0x100 != 0
+    }
+
+    fn repeat(&self) -> Repeat {
+        if self.0 & 0x80 != 0 {
+            Repeat::Reg((self.0 & 0xf) as usize)
+        } else {
+            Repeat::Count((self.0 & 0xf) as usize + 1)
+        }
+    }
+}
+
+/// Decodes the instruction at `addr`.
+pub fn read(region: &Region, addr: u64, model: Model) -> Result<(Insn, Vec<u16>)> {
+    let mut w = Words::new(region, addr, model.width());
+    let op = w.word()?;
+    let x = model == Model::Msp430X;
+    let insn = match op >> 12 {
+        0 if x => address(op, &mut w)?,
+        1 if x && op & 0xf800 == 0x1800 => {
+            let ext = Ext(op);
+            let op = w.word()?;
+
+            match op >> 12 {
+                1 if op & 0xfc00 == 0x1000 => single(op, Some(ext), &mut w)?,
+                4...15 => double(op, Some(ext), &mut w)?,
+                _ => unknown(op)?,
+            }
+        }
+        1 if x && op & 0xff00 == 0x1300 && op != 0x1300 => call_address(op, &mut w)?,
+        1 if x && op & 0xfc00 == 0x1400 => push_pop_multiple(op, &mut w)?,
+        1 if op & 0xfc00 == 0x1000 => single(op, None, &mut w)?,
+        2 | 3 => jump(op, &mut w)?,
+        4...15 => double(op, None, &mut w)?,
+        _ => unknown(op)?,
+    };
+
+    Ok((insn, w.tokens))
+}
+
+static DOUBLE: [&'static str; 12] = ["mov", "add", "addc", "subc", "sub", "cmp", "dadd", "bit", "bic", "bis", "xor", "and"];
+
+const MOV: usize = 4;
+const CMP: usize = 9;
+const BIT: usize = 0xb;
+
+enum Shown {
+    Nothing,
+    Source,
+    Destination,
+}
+
+/// Emulated instruction TI assemblers show for a format I instruction.
+fn emulated(opc: usize, src: Opnd, dst: Opnd, size: usize) -> Option<(&'static str, Shown)> {
+    let same_reg = src == dst && src.is_register();
+    let flag = |clear: &'static str, set: &'static str| if opc == 0xc { clear } else { set };
+
+    match (opc, src, dst) {
+        (MOV, Opnd::Imm(0), Opnd::Reg(CG)) => Some(("nop", Shown::Nothing)),
+        (MOV, Opnd::PostInc(SP), Opnd::Reg(PC)) => Some(("ret", Shown::Nothing)),
+        (MOV, _, Opnd::Reg(PC)) => Some(("br", Shown::Source)),
+        (MOV, Opnd::PostInc(SP), _) => Some(("pop", Shown::Destination)),
+        (MOV, Opnd::Imm(0), _) => Some(("clr", Shown::Destination)),
+        (5, Opnd::Imm(1), _) => Some(("inc", Shown::Destination)),
+        (5, Opnd::Imm(2), _) => Some(("incd", Shown::Destination)),
+        (5, _, _) if same_reg => Some(("rla", Shown::Destination)),
+        (6, Opnd::Imm(0), _) => Some(("adc", Shown::Destination)),
+        (6, _, _) if same_reg => Some(("rlc", Shown::Destination)),
+        (7, Opnd::Imm(0), _) => Some(("sbc", Shown::Destination)),
+        (8, Opnd::Imm(1), _) => Some(("dec", Shown::Destination)),
+        (8, Opnd::Imm(2), _) => Some(("decd", Shown::Destination)),
+        (CMP, Opnd::Imm(0), _) => Some(("tst", Shown::Destination)),
+        (0xa, Opnd::Imm(0), _) => Some(("dadc", Shown::Destination)),
+        (0xe, Opnd::Imm(v), _) if v == mask(!0, size) => Some(("inv", Shown::Destination)),
+        (0xc...0xd, Opnd::Imm(v), Opnd::Reg(SR)) if size == 16 => {
+            match v {
+                1 => Some((flag("clrc", "setc"), Shown::Nothing)),
+                2 => Some((flag("clrz", "setz"), Shown::Nothing)),
+                4 => Some((flag("clrn", "setn"), Shown::Nothing)),
+                8 => Some((flag("dint", "eint"), Shown::Nothing)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Applies the repetition count and the zero carry bit of an extension word to one execution of
+// a register mode instruction. Returns the statements and the `rpt` prefix of the opcode.
+fn repeat(ext: Option<Ext>, uses_carry: bool, body: Vec<Statement>, dst: usize, width: usize) -> Result<(Vec<Statement>, String)> {
+    let ext = match ext {
+        Some(e) => e,
+        None => return Ok((body, String::new())),
+    };
+    let mut once = if ext.zero_carry() && uses_carry { rreil!{ mov C:1, [0]:1; }? } else { vec![] };
+
+    once.extend(body);
+    match ext.repeat() {
+        Repeat::Count(1) => Ok((once, String::new())),
+        Repeat::Count(n) => Ok(((0..n).flat_map(|_| once.clone()).collect(), format!("rpt #{} {{ ", n))),
+        Repeat::Reg(r) => {
+            // unknown number of iterations
+            once.extend(
+                rreil!{
+                    mov (reg_lv(dst, width)), ?;
+                    mov C:1, ?;
+                    mov Z:1, ?;
+                    mov N:1, ?;
+                    mov V:1, ?;
+                }?
+            );
+            Ok((once, format!("rpt {} {{ ", NAMES[r])))
+        }
+    }
+}
+
+/// Format I: two operand instructions.
+fn double(op: u16, ext: Option<Ext>, w: &mut Words) -> Result<Insn> {
+    let width = w.width;
+    let opc = (op >> 12) as usize;
+    let bw = op & 0x40 != 0;
+    let (src_mode, dst_mode) = ((op >> 4) & 3, (op >> 7) & 1);
+    let registers = src_mode == 0 && dst_mode == 0;
+    let size = match ext {
+        Some(e) => e.size(bw).ok_or(format!("reserved MSP430X operand size {:#06x}", op))?,
+        None => if bw { 8 } else { 16 },
+    };
+    let (src_ext, dst_ext) = match ext {
+        Some(e) if !registers => (Some(e.src()), Some(e.dst())),
+        _ => (None, None),
+    };
+    let pc = w.address();
+    let src = Opnd::source(((op >> 8) & 0xf) as usize, src_mode, size, src_ext, w)?;
+    let dst = Opnd::destination((op & 0xf) as usize, dst_mode, dst_ext, w)?;
+    let body = double_semantics(opc, src, dst, size, width, pc)?;
+    let uses_carry = opc == 6 || opc == 7 || opc == 0xa;
+    let (stmts, prefix) = repeat(if registers { ext } else { None }, uses_carry, body, (op & 0xf) as usize, width)?;
+    let x = if ext.is_some() { "x" } else { "" };
+    let mut src_op = src.format(size, width);
+    let dst_op = dst.format(size, width);
+
+    let flow = match (opc, dst) {
+        (CMP, _) | (BIT, _) => Flow::Next,
+        (MOV, Opnd::Reg(PC)) if src == Opnd::PostInc(SP) => Flow::Return,
+        (MOV, Opnd::Reg(PC)) => {
+            if let Opnd::Imm(v) = src {
+                src_op = code_target(src_op);
+                Flow::Jump(imm(v, width))
+            } else {
+                Flow::Jump(reg(PC, width))
+            }
+        }
+        (_, Opnd::Reg(PC)) => Flow::Jump(reg(PC, width)),
+        _ => Flow::Next,
+    };
+    let (name, ops) = match emulated(opc, src, dst, size) {
+        Some((name, Shown::Nothing)) => (name, vec![]),
+        Some((name, Shown::Source)) => (name, vec![src_op]),
+        Some((name, Shown::Destination)) => (name, vec![dst_op]),
+        None => (DOUBLE[opc - 4], vec![src_op, dst_op]),
+    };
+    let opcode = format!("{}{}{}{}", prefix, name, x, suffix(size));
+
+    Ok(Insn::new(&opcode, ops, stmts).flow(flow))
+}
+
+fn double_semantics(opc: usize, src: Opnd, dst: Opnd, size: usize, width: usize, pc: u64) -> Result<Vec<Statement>> {
+    let (mut stmts, src_loc) = src.locate(size, width, "src_ea")?;
+    let (s, sv) = src_loc.read(size, width, pc, "src")?;
+
+    stmts.extend(s);
+    let (s, dst_loc) = dst.locate(size, width, "dst_ea")?;
+    stmts.extend(s);
+
+    if opc == MOV {
+        stmts.extend(dst_loc.write(sv, size, width)?);
+        return Ok(stmts);
+    }
+
+    let (s, dv) = dst_loc.read(size, width, pc, "dst")?;
+    let ones = mask(!0, size);
+    let res = result(size);
+
+    stmts.extend(s);
+    let write = match opc {
+        5...8 | CMP => {
+            let subtract = opc == 7 || opc == 8 || opc == CMP;
+            stmts.extend(arith(dv, sv, size, subtract, opc == 6 || opc == 7)?);
+            opc != CMP
+        }
+        0xa => {
+            // decimal addition
+            stmts.extend(
+                rreil!{
+                    mov res:(size), ?;
+                    mov C:1, ?;
+                    mov Z:1, ?;
+                    mov N:1, ?;
+                    mov V:1, ?;
+                }?
+            );
+            true
+        }
+        BIT | 0xf => {
+            stmts.extend(rreil!{ and res:(size), (dv), (sv); }?);
+            stmts.extend(logic_flags(res.clone(), size)?);
+            opc != BIT
+        }
+        0xc => {
+            stmts.extend(
+                rreil!{
+                    xor inv:(size), (sv), [ones]:(size);
+                    and res:(size), (dv), inv:(size);
+                }?
+            );
+            true
+        }
+        0xd => {
+            stmts.extend(rreil!{ or res:(size), (dv), (sv); }?);
+            true
+        }
+        _ => {
+            stmts.extend(rreil!{ xor res:(size), (dv), (sv); }?);
+            stmts.extend(logic_flags(res.clone(), size)?);
+            stmts.extend(rreil!{ and V:1, (dv.extract(1, size - 1)?), (sv.extract(1, size - 1)?); }?);
+            true
+        }
+    };
+
+    if write {
+        stmts.extend(dst_loc.write(res, size, width)?);
+    }
+    Ok(stmts)
+}
+
+static SINGLE: [&'static str; 6] = ["rrc", "swpb", "rra", "sxt", "push", "call"];
+
+/// Format II: single operand instructions.
+fn single(op: u16, ext: Option<Ext>, w: &mut Words) -> Result<Insn> {
+    let width = w.width;
+    let opc = ((op >> 7) & 7) as usize;
+    let bw = op & 0x40 != 0;
+    let mode = (op >> 4) & 3;
+    let r = (op & 0xf) as usize;
+
+    if opc == 6 && op == 0x1300 && ext.is_none() {
+        return reti(width);
+    } else if opc >= 6 {
+        return unknown(op);
+    }
+
+    let size = match ext {
+        Some(e) => e.size(bw).ok_or(format!("reserved MSP430X operand size {:#06x}", op))?,
+        None => if bw { 8 } else { 16 },
+    };
+
+    if (size == 8 && (opc == 1 || opc == 3 || opc == 5)) || (opc == 5 && ext.is_some()) {
+        return unknown(op);
+    }
+
+    let pc = w.address();
+    let idx = match ext {
+        Some(e) if mode != 0 => Some(e.dst()),
+        _ => None,
+    };
+    let opnd = Opnd::source(r, mode, size, idx, w)?;
+    let (mut stmts, loc) = opnd.locate(size, width, "ea")?;
+    let (s, v) = loc.read(size, width, pc, "val")?;
+    let res = result(size);
+    let zero_carry = ext.map(|e| mode == 0 && e.zero_carry()).unwrap_or(false);
+    let mut name = SINGLE[opc];
+    let mut format = opnd.format(size, width);
+    let mut flow = Flow::Next;
+
+    stmts.extend(s);
+    match opc {
+        0 | 2 => {
+            if zero_carry && opc == 0 {
+                name = "rru";
+            }
+            stmts.extend(rotate(opc as u16 / 2, v, size, 1, zero_carry)?);
+            stmts.extend(loc.write(res, size, width)?);
+        }
+        1 => {
+            let lo = v.extract(16, 0)?;
+            stmts.extend(
+                rreil!{
+                    shl swap:16, (lo), [8]:16;
+                    shr swpb:16, (lo), [8]:16;
+                    or swpb:16, swpb:16, swap:16;
+                }?
+            );
+            if size == 20 {
+                stmts.extend(rreil!{ zext/20 res:20, swpb:16; }?);
+            } else {
+                stmts.extend(rreil!{ mov res:16, swpb:16; }?);
+            }
+            stmts.extend(loc.write(res, size, width)?);
+        }
+        3 => {
+            stmts.extend(rreil!{ sext/(size) res:(size), (v.extract(8, 0)?); }?);
+            stmts.extend(logic_flags(res.clone(), size)?);
+            stmts.extend(loc.write(res, size, width)?);
+        }
+        4 => stmts.extend(push(v, size, width)?),
+        _ => {
+            let target = if let Rvalue::Constant { .. } = v {
+                format = code_target(format);
+                v
+            } else {
+                stmts.extend(rreil!{ mov tgt:16, (v); }?);
+                rreil_rvalue!{ tgt:16 }
+            };
+
+            stmts.extend(push(imm(w.address(), 16), 16, width)?);
+            stmts.extend(rreil!{ call (target); }?);
+            flow = Flow::Call(target);
+        }
+    }
+
+    let (stmts, prefix) = repeat(if mode == 0 { ext } else { None }, opc == 0, stmts, r, width)?;
+    let x = if ext.is_some() { "x" } else { "" };
+    let opcode = format!("{}{}{}{}", prefix, name, x, suffix(size));
+
+    Ok(Insn::new(&opcode, vec![format], stmts).flow(flow))
+}
+
+/// Shifts `v` `n` times into `res`. `kind` is 0 for rotate right through carry, 1 for
+/// arithmetic right, 2 for left and 3 for logic right shifts. The last bit shifted out ends up
+/// in C, N and Z are set from the result and V is cleared.
+fn rotate(kind: u16, v: Rvalue, size: usize, n: usize, zero_carry: bool) -> Result<Vec<Statement>> {
+    let res = result(size);
+    let mut stmts = vec![];
+
+    match kind {
+        0 => {
+            stmts.extend(rreil!{ mov res:(size), (v); }?);
+            for _ in 0..n {
+                if zero_carry {
+                    stmts.extend(rreil!{ mov c_in:1, [0]:1; }?);
+                } else {
+                    stmts.extend(rreil!{ mov c_in:1, C:1; }?);
+                }
+                stmts.extend(
+                    rreil!{
+                        mov C:1, res:1;
+                        shr res:(size), res:(size), [1]:(size);
+                        sel/(size - 1) res:(size), c_in:1;
+                    }?
+                );
+            }
+        }
+        1 => {
+            stmts.extend(
+                rreil!{
+                    mov C:1, (v.extract(1, n - 1)?);
+                    shrs res:(size), (v), [n]:(size);
+                }?
+            );
+        }
+        2 => {
+            stmts.extend(
+                rreil!{
+                    mov C:1, (v.extract(1, size - n)?);
+                    shl res:(size), (v), [n]:(size);
+                }?
+            );
+        }
+        _ => {
+            stmts.extend(
+                rreil!{
+                    mov C:1, (v.extract(1, n - 1)?);
+                    shr res:(size), (v), [n]:(size);
+                }?
+            );
+        }
+    }
+
+    stmts.extend(nz(res, size)?);
+    stmts.extend(rreil!{ mov V:1, [0]:1; }?);
+    Ok(stmts)
+}
+
+fn reti(width: usize) -> Result<Insn> {
+    let mut stmts = pop(var("saved_sr", 16), 16, width)?;
+
+    if width == 20 {
+        // bits 12 to 15 of the saved status register hold bits 16 to 19 of the PC
+        stmts.extend(rreil!{ and status:16, saved_sr:16, [0xfff]:16; }?);
+        stmts.extend(set_status_register(rreil_rvalue!{ status:16 }, width)?);
+        stmts.extend(pop(var("saved_pc", 16), 16, width)?);
+        stmts.extend(
+            rreil!{
+                zext/20 pc:20, saved_pc:16;
+                sel/16 pc:20, saved_sr:4/12;
+            }?
+        );
+    } else {
+        stmts.extend(set_status_register(rreil_rvalue!{ saved_sr:16 }, width)?);
+        stmts.extend(pop(reg_lv(PC, 16), 16, width)?);
+    }
+
+    Ok(Insn::new("reti", vec![], stmts).flow(Flow::Return))
+}
+
+static JUMPS: [&'static str; 8] = ["jne", "jeq", "jnc", "jc", "jn", "jge", "jl", "jmp"];
+
+/// Conditional and unconditional jumps with a 10-bit word offset.
+fn jump(op: u16, w: &mut Words) -> Result<Insn> {
+    let cc = (op >> 10) & 7;
+    let off = sign_extend((op & 0x3ff) as u64, 10).wrapping_mul(2);
+    let tgt = imm(w.address().wrapping_add(off), w.width);
+    let ops = vec![("{c:ram}".to_string(), vec![tgt.clone()])];
+    let (stmts, cond) = condition(cc)?;
+    let flow = if cc == 7 { Flow::Jump(tgt) } else { Flow::Branch(tgt, cond) };
+
+    Ok(Insn::new(JUMPS[cc as usize], ops, stmts).flow(flow))
+}
+
+static ADDRESS: [&'static str; 4] = ["mova", "cmpa", "adda", "suba"];
+
+/// MSP430X address instructions operating on 20-bit values: `mova`, `cmpa`, `adda`, `suba` and
+/// the multiple bit shifts.
+fn address(op: u16, w: &mut Words) -> Result<Insn> {
+    let hi = ((op >> 8) & 0xf) as usize;
+    let lo = (op & 0xf) as usize;
+    let sub = (op >> 4) & 0xf;
+
+    if sub == 4 || sub == 5 {
+        return multiple_shift(op);
+    }
+
+    let pc = w.address();
+    let (src, dst) = match sub {
+        0 => (Opnd::Indirect(hi), Opnd::Reg(lo)),
+        1 => (Opnd::PostInc(hi), Opnd::Reg(lo)),
+        2 => (Opnd::Absolute(((hi as u64) << 16) | w.word()? as u64), Opnd::Reg(lo)),
+        3 => (index20(hi, w)?, Opnd::Reg(lo)),
+        6 => (Opnd::Reg(hi), Opnd::Absolute(((lo as u64) << 16) | w.word()? as u64)),
+        7 => (Opnd::Reg(hi), index20(lo, w)?),
+        8...11 => (Opnd::Imm(((hi as u64) << 16) | w.word()? as u64), Opnd::Reg(lo)),
+        _ => (Opnd::Reg(hi), Opnd::Reg(lo)),
+    };
+    let kind = if sub < 8 { 0 } else { (sub & 3) as usize };
+    let (mut stmts, src_loc) = src.locate(20, 20, "src_ea")?;
+    let (s, sv) = src_loc.read(20, 20, pc, "src")?;
+
+    stmts.extend(s);
+    let (s, dst_loc) = dst.locate(20, 20, "dst_ea")?;
+    stmts.extend(s);
+
+    if kind == 0 {
+        stmts.extend(dst_loc.write(sv, 20, 20)?);
+    } else {
+        let (s, dv) = dst_loc.read(20, 20, pc, "dst")?;
+        stmts.extend(s);
+        stmts.extend(arith(dv, sv, 20, kind != 2, false)?);
+        if kind != 1 {
+            stmts.extend(dst_loc.write(result(20), 20, 20)?);
+        }
+    }
+
+    let mut src_op = src.format(20, 20);
+    let (name, ops, flow) = match (kind, dst) {
+        (0, Opnd::Reg(PC)) if src == Opnd::PostInc(SP) => ("reta", vec![], Flow::Return),
+        (0, Opnd::Reg(PC)) => {
+            let flow = if let Opnd::Imm(v) = src {
+                src_op = code_target(src_op);
+                Flow::Jump(imm(v, 20))
+            } else {
+                Flow::Jump(reg(PC, 20))
+            };
+            ("bra", vec![src_op], flow)
+        }
+        (2, Opnd::Reg(PC)) | (3, Opnd::Reg(PC)) => (ADDRESS[kind], vec![src_op, dst.format(20, 20)], Flow::Jump(reg(PC, 20))),
+        _ => (ADDRESS[kind], vec![src_op, dst.format(20, 20)], Flow::Next),
+    };
+
+    Ok(Insn::new(name, ops, stmts).flow(flow))
+}
+
+// `X(Rn)` operand of `mova` with a 16-bit index.
+fn index20(r: usize, w: &mut Words) -> Result<Opnd> {
+    let pc = w.address();
+    let x = sign_extend(w.word()? as u64, 16);
+
+    if r == PC {
+        Ok(Opnd::Symbolic(mask(pc.wrapping_add(x), 20)))
+    } else {
+        Ok(Opnd::Indexed(r, mask(x, 20)))
+    }
+}
+
+static SHIFTS: [&'static str; 4] = ["rrcm", "rram", "rlam", "rrum"];
+
+/// `rrcm`, `rram`, `rlam` and `rrum` shift a register up to four times.
+fn multiple_shift(op: u16) -> Result<Insn> {
+    let n = ((op >> 10) & 3) as usize + 1;
+    let kind = (op >> 8) & 3;
+    let size = if op & 0x10 != 0 { 16 } else { 20 };
+    let r = (op & 0xf) as usize;
+    let mut stmts = rotate(kind, reg(r, size), size, n, false)?;
+
+    stmts.extend(write_reg(r, result(size), 20)?);
+    let name = format!("{}{}", SHIFTS[kind as usize], if size == 20 { ".a" } else { "" });
+    let ops = vec![("#{u}".to_string(), vec![imm(n as u64, 16)]), (NAMES[r].to_string(), vec![])];
+
+    Ok(Insn::new(&name, ops, stmts))
+}
+
+/// `calla` pushes a 20-bit return address.
+fn call_address(op: u16, w: &mut Words) -> Result<Insn> {
+    let r = (op & 0xf) as usize;
+    let pc = w.address();
+    let opnd = match (op >> 4) & 0xf {
+        4 => Opnd::Reg(r),
+        5 => index20(r, w)?,
+        6 => Opnd::Indirect(r),
+        7 => Opnd::PostInc(r),
+        8 => Opnd::Absolute(((r as u64) << 16) | w.word()? as u64),
+        9 => Opnd::Symbolic(mask(pc.wrapping_add(((r as u64) << 16) | w.word()? as u64), 20)),
+        0xb => Opnd::Imm(((r as u64) << 16) | w.word()? as u64),
+        _ => return unknown(op),
+    };
+    let (mut stmts, loc) = opnd.locate(20, 20, "ea")?;
+    let (s, v) = loc.read(20, 20, pc, "val")?;
+    let mut format = opnd.format(20, 20);
+
+    stmts.extend(s);
+    let target = if let Rvalue::Constant { .. } = v {
+        format = code_target(format);
+        v
+    } else {
+        stmts.extend(rreil!{ mov tgt:20, (v); }?);
+        rreil_rvalue!{ tgt:20 }
+    };
+
+    stmts.extend(push(imm(w.address(), 20), 20, 20)?);
+    stmts.extend(rreil!{ call (target); }?);
+    Ok(Insn::new("calla", vec![format], stmts).flow(Flow::Call(target)))
+}
+
+/// `pushm` and `popm` save and restore up to 16 consecutive registers.
+fn push_pop_multiple(op: u16, w: &mut Words) -> Result<Insn> {
+    let n = ((op >> 4) & 0xf) as usize + 1;
+    let r = (op & 0xf) as usize;
+    let size = if op & 0x100 != 0 { 16 } else { 20 };
+    let mut stmts = vec![];
+    let count = ("#{u}".to_string(), vec![imm(n as u64, 16)]);
+
+    if op & 0x200 != 0 {
+        // encodes the first register popped
+        let last = r + n - 1;
+
+        if last > 15 {
+            return unknown(op);
+        }
+
+        for i in r..last + 1 {
+            stmts.extend(pop(var("popped", size), size, w.width)?);
+            stmts.extend(write_reg(i, rreil_rvalue!{ popped:(size) }, w.width)?);
+        }
+
+        let name = format!("popm{}", if size == 20 { ".a" } else { "" });
+        Ok(Insn::new(&name, vec![count, (NAMES[last].to_string(), vec![])], stmts))
+    } else {
+        if n > r + 1 {
+            return unknown(op);
+        }
+
+        for i in (r + 1 - n..r + 1).rev() {
+            stmts.extend(push(reg(i, size), size, w.width)?);
+        }
+
+        let name = format!("pushm{}", if size == 20 { ".a" } else { "" });
+        Ok(Insn::new(&name, vec![count, (NAMES[r].to_string(), vec![])], stmts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::Region;
+
+    fn decode_model(words: &[u16], model: Model) -> Insn {
+        let bytes = words.iter().flat_map(|w| vec![*w as u8, (w >> 8) as u8]).collect::<Vec<_>>();
+        let reg = Region::wrap("ram".to_string(), bytes);
+        let (ret, tokens) = read(&reg, 0, model).unwrap();
+
+        assert_eq!(tokens, words.to_vec());
+        for s in ret.statements.iter() {
+            assert!(s.sanity_check().is_ok(), "{} {:?}", ret.opcode, s);
+        }
+        assert!(ret.mnemonic(0, 2 * tokens.len() as u64).is_ok());
+        ret
+    }
+
+    fn decode_words(words: &[u16]) -> Insn {
+        decode_model(words, Model::Msp430)
+    }
+
+    fn decode_x(words: &[u16]) -> Insn {
+        decode_model(words, Model::Msp430X)
+    }
+
+    #[test]
+    fn double_operand() {
+        assert_eq!(decode_words(&[0x4f0e]).opcode, "mov");
+        assert_eq!(decode_words(&[0x4f0e]).format, "R15, R14");
+        assert_eq!(decode_words(&[0x4fce, 0x0004]).opcode, "mov.b");
+        assert_eq!(decode_words(&[0x4fce, 0x0004]).format, "R15, {s}(R14)");
+        assert_eq!(decode_words(&[0x403f, 0x1234]).format, "#{u}, R15");
+        assert_eq!(decode_words(&[0x403f, 0x1234]).operands, vec![imm(0x1234, 16)]);
+        assert_eq!(decode_words(&[0x4290, 0x0200, 0x0010]).format, "&{p:ram}, {p:ram}");
+        assert_eq!(decode_words(&[0x4290, 0x0200, 0x0010]).operands, vec![imm(0x200, 16), imm(0x14, 16)]);
+        assert_eq!(decode_words(&[0x4f2e]).format, "@R15, R14");
+        assert_eq!(decode_words(&[0x4f3e]).format, "@R15+, R14");
+        assert_eq!(decode_words(&[0x5f0e]).opcode, "add");
+        assert_eq!(decode_words(&[0x6f0e]).opcode, "addc");
+        assert_eq!(decode_words(&[0x7f0e]).opcode, "subc");
+        assert_eq!(decode_words(&[0x8f0e]).opcode, "sub");
+        assert_eq!(decode_words(&[0x9f0e]).opcode, "cmp");
+        assert_eq!(decode_words(&[0xaf0e]).opcode, "dadd");
+        assert_eq!(decode_words(&[0xbf0e]).opcode, "bit");
+        assert_eq!(decode_words(&[0xcf0e]).opcode, "bic");
+        assert_eq!(decode_words(&[0xdf0e]).opcode, "bis");
+        assert_eq!(decode_words(&[0xef0e]).opcode, "xor");
+        assert_eq!(decode_words(&[0xff4e]).opcode, "and.b");
+    }
+
+    #[test]
+    fn emulated() {
+        assert_eq!(decode_words(&[0x4303]).opcode, "nop");
+        assert_eq!(decode_words(&[0x430f]).opcode, "clr");
+        assert_eq!(decode_words(&[0x431f]).format, "#{u}, R15");
+        assert_eq!(decode_words(&[0x531f]).opcode, "inc");
+        assert_eq!(decode_words(&[0x532f]).opcode, "incd");
+        assert_eq!(decode_words(&[0x833f]).opcode, "sub");
+        assert_eq!(decode_words(&[0x831f]).opcode, "dec");
+        assert_eq!(decode_words(&[0x930f]).opcode, "tst");
+        assert_eq!(decode_words(&[0xe33f]).opcode, "inv");
+        assert_eq!(decode_words(&[0xe37f]).opcode, "inv.b");
+        assert_eq!(decode_words(&[0x5f0f]).opcode, "rla");
+        assert_eq!(decode_words(&[0x6f0f]).opcode, "rlc");
+        assert_eq!(decode_words(&[0x630f]).opcode, "adc");
+        assert_eq!(decode_words(&[0x413f]).opcode, "pop");
+        assert_eq!(decode_words(&[0xc312]).opcode, "clrc");
+        assert_eq!(decode_words(&[0xd232]).opcode, "eint");
+        assert_eq!(decode_words(&[0xc232]).opcode, "dint");
+        assert_eq!(decode_words(&[0xd222]).opcode, "setn");
+    }
+
+    #[test]
+    fn single_operand() {
+        assert_eq!(decode_words(&[0x100f]).opcode, "rrc");
+        assert_eq!(decode_words(&[0x108f]).opcode, "swpb");
+        assert_eq!(decode_words(&[0x114f]).opcode, "rra.b");
+        assert_eq!(decode_words(&[0x118f]).opcode, "sxt");
+        assert_eq!(decode_words(&[0x120f]).opcode, "push");
+        assert_eq!(decode_words(&[0x1230, 0x0005]).format, "#{u}");
+        assert!(read(&Region::wrap("ram".to_string(), vec![0xcf, 0x10]), 0, Model::Msp430).is_err());
+        assert!(read(&Region::wrap("ram".to_string(), vec![0x00, 0x00]), 0, Model::Msp430).is_err());
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(decode_words(&[0x4130]).flow, Flow::Return);
+        assert_eq!(decode_words(&[0x4130]).opcode, "ret");
+        assert_eq!(decode_words(&[0x1300]).flow, Flow::Return);
+        assert_eq!(decode_words(&[0x3fff]).flow, Flow::Jump(imm(0, 16)));
+        assert_eq!(decode_words(&[0x2002]).flow, Flow::Branch(imm(6, 16), rreil_rvalue!{ cond:1 }));
+        assert_eq!(decode_words(&[0x2002]).opcode, "jne");
+        assert_eq!(decode_words(&[0x3402]).opcode, "jge");
+        assert_eq!(decode_words(&[0x12b0, 0x4400]).flow, Flow::Call(imm(0x4400, 16)));
+        assert_eq!(decode_words(&[0x12b0, 0x4400]).format, "#{c:ram}");
+        assert_eq!(decode_words(&[0x128f]).flow, Flow::Call(rreil_rvalue!{ tgt:16 }));
+        assert_eq!(decode_words(&[0x4030, 0x4400]).opcode, "br");
+        assert_eq!(decode_words(&[0x4030, 0x4400]).flow, Flow::Jump(imm(0x4400, 16)));
+        assert_eq!(decode_words(&[0x4f00]).flow, Flow::Jump(reg(PC, 16)));
+        assert_eq!(decode_words(&[0x5f00]).flow, Flow::Jump(reg(PC, 16)));
+        assert_eq!(decode_words(&[0x9f00]).flow, Flow::Next);
+    }
+
+    #[test]
+    fn extended() {
+        assert_eq!(decode_x(&[0x1800, 0x4f4e]).opcode, "movx.a");
+        assert_eq!(decode_x(&[0x1840, 0x4f4e]).opcode, "movx.b");
+        assert_eq!(decode_x(&[0x1840, 0x4f0e]).opcode, "movx");
+        assert_eq!(decode_x(&[0x1843, 0x4f8e, 0x0000]).operands, vec![imm(0x30000, 20)]);
+        assert_eq!(decode_x(&[0x1880, 0x407f, 0x1234]).operands, vec![imm(0x11234, 20)]);
+        assert_eq!(decode_x(&[0x1843, 0x5f0e]).opcode, "rpt #4 { addx");
+        assert_eq!(decode_x(&[0x18c5, 0x5f0e]).opcode, "rpt R5 { addx");
+        assert_eq!(decode_x(&[0x1940, 0x100f]).opcode, "rrux");
+        assert_eq!(decode_x(&[0x1800, 0x124f]).opcode, "pushx.a");
+        assert!(read(&Region::wrap("ram".to_string(), vec![0x00, 0x18, 0x0e, 0x4f]), 0, Model::Msp430).is_err());
+    }
+
+    #[test]
+    fn address() {
+        assert_eq!(decode_x(&[0x0f0e]).opcode, "mova");
+        assert_eq!(decode_x(&[0x0f0e]).format, "@R15, R14");
+        assert_eq!(decode_x(&[0x018e, 0x2345]).operands, vec![imm(0x12345, 20)]);
+        assert_eq!(decode_x(&[0x0fce]).opcode, "mova");
+        assert_eq!(decode_x(&[0x0fde]).opcode, "cmpa");
+        assert_eq!(decode_x(&[0x03ae, 0x0010]).opcode, "adda");
+        assert_eq!(decode_x(&[0x0fff]).opcode, "suba");
+        assert_eq!(decode_x(&[0x0110]).flow, Flow::Return);
+        assert_eq!(decode_x(&[0x0110]).opcode, "reta");
+        assert_eq!(decode_x(&[0x0180, 0x4400]).flow, Flow::Jump(imm(0x14400, 20)));
+        assert_eq!(decode_x(&[0x0c4f]).opcode, "rrcm.a");
+        assert_eq!(decode_x(&[0x0e5f]).opcode, "rlam");
+        assert_eq!(decode_x(&[0x13b4, 0x4400]).flow, Flow::Call(imm(0x44400, 20)));
+        assert_eq!(decode_x(&[0x134f]).opcode, "calla");
+        assert_eq!(decode_x(&[0x152a]).format, "#{u}, R10");
+        assert_eq!(decode_x(&[0x1728]).format, "#{u}, R10");
+        assert_eq!(decode_x(&[0x1300]).flow, Flow::Return);
+    }
+}
@@ -0,0 +1,45 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! TI MSP430 disassembler.
+//!
+//! Covers the 27 core instructions of the MSP430 together with the emulated instructions TI
+//! assemblers show in their place (`ret`, `br`, `pop`, `clr`, `inc`, `tst`, ...), and the
+//! extended instruction set of the MSP430X: extension words, address instructions, `calla`,
+//! `pushm`/`popm` and repeated register operations. The model is selected with `Model`, the 430X
+//! uses 20-bit registers and addresses.
+//!
+//! Instruction words are read little endian. Constant generators R2 and R3 are decoded as
+//! immediates, R3 (`cg`) always reads as zero. The flags C, Z, N and V are kept separately and
+//! combined into `sr` when the status register is accessed as a whole. Decimal addition sets its
+//! result to undefined.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+
+pub mod semantic;
+mod operand;
+mod decode;
+
+mod architecture;
+pub use architecture::{Model, Msp430};
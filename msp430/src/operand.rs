@@ -0,0 +1,175 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! MSP430 addressing modes.
+
+use decode::Words;
+use panopticon_core::{Result, Rvalue, Statement};
+use semantic::*;
+
+/// Source or destination operand.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Opnd {
+    /// `Rn`
+    Reg(usize),
+    /// `X(Rn)`, the index is truncated to the address width
+    Indexed(usize, u64),
+    /// `ADDR`, encoded relative to the PC. Holds the absolute address
+    Symbolic(u64),
+    /// `&ADDR`
+    Absolute(u64),
+    /// `@Rn`
+    Indirect(usize),
+    /// `@Rn+`
+    PostInc(usize),
+    /// `#N`, including the values of the constant generators
+    Imm(u64),
+}
+
+impl Opnd {
+    /// Decodes a source operand. `ext` holds bits 16 to 19 of index words if the instruction has
+    /// an MSP430X extension word.
+    pub fn source(r: usize, mode: u16, size: usize, ext: Option<u64>, w: &mut Words) -> Result<Opnd> {
+        match (mode & 3, r) {
+            (0, CG) => Ok(Opnd::Imm(0)),
+            (1, CG) => Ok(Opnd::Imm(1)),
+            (2, CG) => Ok(Opnd::Imm(2)),
+            (3, CG) => Ok(Opnd::Imm(mask(!0, size))),
+            (2, SR) => Ok(Opnd::Imm(4)),
+            (3, SR) => Ok(Opnd::Imm(8)),
+            (0, r) => Ok(Opnd::Reg(r)),
+            (2, r) => Ok(Opnd::Indirect(r)),
+            (3, PC) => Ok(Opnd::Imm(mask(index(w, ext, false)?, size))),
+            (3, r) => Ok(Opnd::PostInc(r)),
+            (_, r) => indexed(r, ext, w),
+        }
+    }
+
+    /// Decodes a destination operand, which is either a register or indexed.
+    pub fn destination(r: usize, mode: u16, ext: Option<u64>, w: &mut Words) -> Result<Opnd> {
+        if mode & 1 == 0 { Ok(Opnd::Reg(r)) } else { indexed(r, ext, w) }
+    }
+
+    pub fn is_register(&self) -> bool {
+        if let Opnd::Reg(_) = *self { true } else { false }
+    }
+
+    /// Format string and operands of a `size` bits wide operand.
+    pub fn format(&self, size: usize, width: usize) -> (String, Vec<Rvalue>) {
+        match *self {
+            Opnd::Reg(r) => (NAMES[r].to_string(), vec![]),
+            Opnd::Indexed(r, x) => (format!("{{s}}({})", NAMES[r]), vec![imm(x, width)]),
+            Opnd::Symbolic(a) => ("{p:ram}".to_string(), vec![imm(a, width)]),
+            Opnd::Absolute(a) => ("&{p:ram}".to_string(), vec![imm(a, width)]),
+            Opnd::Indirect(r) => (format!("@{}", NAMES[r]), vec![]),
+            Opnd::PostInc(r) => (format!("@{}+", NAMES[r]), vec![]),
+            Opnd::Imm(v) => ("#{u}".to_string(), vec![imm(v, size)]),
+        }
+    }
+
+    /// Computes the address of a memory operand into `name`. Post increments are done here.
+    pub fn locate(&self, size: usize, width: usize, name: &'static str) -> Result<(Vec<Statement>, Loc)> {
+        let ea = var(name, width);
+
+        match *self {
+            Opnd::Reg(r) => Ok((vec![], Loc::Reg(r))),
+            Opnd::Imm(v) => Ok((vec![], Loc::Imm(v))),
+            Opnd::Symbolic(a) | Opnd::Absolute(a) => Ok((vec![], Loc::Mem(imm(a, width)))),
+            Opnd::Indirect(r) => Ok((vec![], Loc::Mem(reg(r, width)))),
+            Opnd::Indexed(r, x) => {
+                let stmts = rreil!{ add (ea), (reg(r, width)), [x]:(width); }?;
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+            Opnd::PostInc(r) => {
+                // the stack pointer and the program counter are always word aligned
+                let inc = match size {
+                    8 if r > SR => 1,
+                    20 => 4,
+                    _ => 2,
+                };
+                let rv = reg(r, width);
+                let stmts = rreil!{
+                    mov (ea), (rv);
+                    add (reg_lv(r, width)), (rv), [inc]:(width);
+                }?;
+
+                Ok((stmts, Loc::Mem(ea.into())))
+            }
+        }
+    }
+}
+
+// Reads an index word. With an extension word it holds the lower 16 bits of a 20-bit value.
+fn index(w: &mut Words, ext: Option<u64>, signed: bool) -> Result<u64> {
+    let lo = w.word()? as u64;
+
+    match ext {
+        Some(hi) => Ok((hi << 16) | lo),
+        None if signed => Ok(sign_extend(lo, 16)),
+        None => Ok(lo),
+    }
+}
+
+fn indexed(r: usize, ext: Option<u64>, w: &mut Words) -> Result<Opnd> {
+    let pc = w.address();
+    let width = w.width;
+
+    match r {
+        PC => Ok(Opnd::Symbolic(mask(pc.wrapping_add(index(w, ext, true)?), width))),
+        SR => Ok(Opnd::Absolute(index(w, ext, false)?)),
+        r => Ok(Opnd::Indexed(r, mask(index(w, ext, true)?, width))),
+    }
+}
+
+/// Resolved operand.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Loc {
+    Reg(usize),
+    Mem(Rvalue),
+    Imm(u64),
+}
+
+impl Loc {
+    /// Reads `size` bits. `pc` is the value the program counter has when read as a register,
+    /// memory is loaded into `tmp`.
+    pub fn read(&self, size: usize, width: usize, pc: u64, tmp: &'static str) -> Result<(Vec<Statement>, Rvalue)> {
+        match *self {
+            Loc::Reg(PC) => Ok((vec![], imm(pc, size))),
+            Loc::Reg(SR) => status_register(size, width),
+            Loc::Reg(CG) => Ok((vec![], imm(0, size))),
+            Loc::Reg(r) => Ok((vec![], reg(r, size))),
+            Loc::Mem(ref addr) => {
+                let lv = var(tmp, size);
+                Ok((load(lv.clone(), addr.clone(), size)?, lv.into()))
+            }
+            Loc::Imm(v) => Ok((vec![], imm(v, size))),
+        }
+    }
+
+    /// Writes the `size` bits wide `v`.
+    pub fn write(&self, v: Rvalue, size: usize, width: usize) -> Result<Vec<Statement>> {
+        match *self {
+            Loc::Reg(SR) => set_status_register(v, width),
+            Loc::Reg(CG) => Ok(vec![]),
+            Loc::Reg(r) => write_reg(r, v, width),
+            Loc::Mem(ref addr) => store(addr.clone(), v, size),
+            Loc::Imm(_) => Err("MSP430 immediate operand written".into()),
+        }
+    }
+}
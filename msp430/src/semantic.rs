@@ -0,0 +1,305 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! RREIL code generation helpers for the MSP430.
+
+use panopticon_core::{Endianess, Lvalue, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+/// IL names of R0 to R15.
+pub static REGISTERS: [&'static str; 16] = [
+    "pc", "sp", "sr", "cg", "r4", "r5", "r6", "r7",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+/// Assembler names of R0 to R15.
+pub static NAMES: [&'static str; 16] = [
+    "PC", "SP", "SR", "R3", "R4", "R5", "R6", "R7",
+    "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15",
+];
+
+pub const PC: usize = 0;
+pub const SP: usize = 1;
+pub const SR: usize = 2;
+/// Constant generator, reads as zero in register mode
+pub const CG: usize = 3;
+
+pub type BinOp = fn(Rvalue, Rvalue) -> Operation<Rvalue>;
+
+/// Register `r` as assignee. `width` is 16 on the MSP430 and 20 on the MSP430X.
+pub fn reg_lv(r: usize, width: usize) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(REGISTERS[r & 0xf]), subscript: None, size: width }
+}
+
+/// Temporary variable `name`.
+pub fn var(name: &'static str, size: usize) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: size }
+}
+
+/// Lower `size` bits of register `r`.
+pub fn reg(r: usize, size: usize) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(REGISTERS[r & 0xf]),
+        subscript: None,
+        offset: 0,
+        size: size,
+    }
+}
+
+/// Truncates `v` to `size` bits.
+pub fn mask(v: u64, size: usize) -> u64 {
+    if size >= 64 { v } else { v & ((1 << size) - 1) }
+}
+
+/// Constant of `size` bits.
+pub fn imm(v: u64, size: usize) -> Rvalue {
+    Rvalue::Constant { value: mask(v, size), size: size }
+}
+
+/// Sign extends the `bits` wide value `v` to 64 bits.
+pub fn sign_extend(v: u64, bits: usize) -> u64 {
+    let shift = 64 - bits;
+    (((v << shift) as i64) >> shift) as u64
+}
+
+/// Loads `size` bits from `addr` into `lv`. 20-bit values occupy two words in memory, the upper
+/// twelve bits are ignored.
+pub fn load(lv: Lvalue, addr: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    if size == 20 {
+        let mut stmts = vec![Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Little, 32, addr), assignee: rreil_lvalue!{ long:32 } }];
+
+        stmts.extend(rreil!{ mov (lv), long:20; }?);
+        Ok(stmts)
+    } else {
+        Ok(vec![Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Little, size, addr), assignee: lv }])
+    }
+}
+
+/// Stores the `size` bits wide value `v` at `addr`. 20-bit values are zero extended to 32 bits.
+pub fn store(addr: Rvalue, v: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    if size == 20 {
+        let mut stmts = rreil!{ zext/32 long:32, (v); }?;
+
+        stmts.push(Statement { op: Operation::Store(Cow::Borrowed("ram"), Endianess::Little, 32, addr, rreil_rvalue!{ long:32 }), assignee: Lvalue::Undefined });
+        Ok(stmts)
+    } else {
+        Ok(vec![Statement { op: Operation::Store(Cow::Borrowed("ram"), Endianess::Little, size, addr, v), assignee: Lvalue::Undefined }])
+    }
+}
+
+/// Bytes a `size` bits wide value occupies on the stack.
+pub fn stack_size(size: usize) -> u64 {
+    if size == 20 { 4 } else { 2 }
+}
+
+/// Pushes the `size` bits wide `v`. Bytes are pushed as words.
+pub fn push(v: Rvalue, size: usize, width: usize) -> Result<Vec<Statement>> {
+    let sp = reg_lv(SP, width);
+    let mut stmts = rreil!{ sub (sp), (sp), [(stack_size(size))]:(width); }?;
+
+    if size == 8 {
+        stmts.extend(rreil!{ zext/16 pushed:16, (v); }?);
+        stmts.extend(store(sp.into(), rreil_rvalue!{ pushed:16 }, 16)?);
+    } else {
+        stmts.extend(store(sp.into(), v, size)?);
+    }
+    Ok(stmts)
+}
+
+/// Pops a `size` bits wide value into `lv`.
+pub fn pop(lv: Lvalue, size: usize, width: usize) -> Result<Vec<Statement>> {
+    let sp = reg_lv(SP, width);
+    let mut stmts = load(lv, sp.clone().into(), size)?;
+
+    stmts.extend(rreil!{ add (sp), (sp), [(stack_size(size))]:(width); }?);
+    Ok(stmts)
+}
+
+/// Merges the flags into the status register `sr` and returns its lower `size` bits.
+pub fn status_register(size: usize, width: usize) -> Result<(Vec<Statement>, Rvalue)> {
+    let sr = reg_lv(SR, width);
+    let stmts = rreil!{
+        sel/0 (sr), C:1;
+        sel/1 (sr), Z:1;
+        sel/2 (sr), N:1;
+        sel/8 (sr), V:1;
+    }?;
+
+    Ok((stmts, reg(SR, size)))
+}
+
+/// Writes the zero extended `v` to the status register and updates the flags.
+pub fn set_status_register(v: Rvalue, width: usize) -> Result<Vec<Statement>> {
+    let size = v.size().unwrap_or(width);
+    let mut stmts = write_reg(SR, v.clone(), width)?;
+
+    stmts.extend(
+        rreil!{
+            mov C:1, (v.extract(1, 0)?);
+            mov Z:1, (v.extract(1, 1)?);
+            mov N:1, (v.extract(1, 2)?);
+        }?
+    );
+    if size > 8 {
+        stmts.extend(rreil!{ mov V:1, (v.extract(1, 8)?); }?);
+    } else {
+        stmts.extend(rreil!{ mov V:1, [0]:1; }?);
+    }
+    Ok(stmts)
+}
+
+/// Writes `v` into register `r`, clearing the bits above it. Byte and word operations on
+/// registers clear the upper bits.
+pub fn write_reg(r: usize, v: Rvalue, width: usize) -> Result<Vec<Statement>> {
+    let lv = reg_lv(r, width);
+
+    match v.size() {
+        Some(sz) if sz < width => rreil!{ zext/(width) (lv), (v); },
+        _ => rreil!{ mov (lv), (v); },
+    }
+}
+
+/// Sets N and Z according to the `size` bits wide value `v`.
+pub fn nz(v: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    rreil!{
+        mov N:1, (v.extract(1, size - 1)?);
+        cmpeq Z:1, (v), [0]:(size);
+    }
+}
+
+/// Flags after `and`, `bit` and `sxt`: N and Z from `v`, C is the inverse of Z and V is cleared.
+pub fn logic_flags(v: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    let mut stmts = nz(v, size)?;
+
+    stmts.extend(
+        rreil!{
+            xor C:1, Z:1, [1]:1;
+            mov V:1, [0]:1;
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Computes `dst + src` or `dst - src` into `res:size` and sets all four flags. Subtraction adds
+/// the complement of `src` plus one, or plus the carry if `carry` is set, so C is the inverted
+/// borrow. Addition adds the carry if `carry` is set.
+pub fn arith(dst: Rvalue, src: Rvalue, size: usize, subtract: bool, carry: bool) -> Result<Vec<Statement>> {
+    let mut stmts = if subtract {
+        rreil!{ xor opnd:(size), (src), [(mask(!0, size))]:(size); }?
+    } else {
+        rreil!{ mov opnd:(size), (src); }?
+    };
+
+    stmts.extend(
+        rreil!{
+            zext/(size + 1) x:(size + 1), (dst);
+            zext/(size + 1) y:(size + 1), opnd:(size);
+            add wide:(size + 1), x:(size + 1), y:(size + 1);
+        }?
+    );
+
+    if carry {
+        stmts.extend(
+            rreil!{
+                zext/(size + 1) cy:(size + 1), C:1;
+                add wide:(size + 1), wide:(size + 1), cy:(size + 1);
+            }?
+        );
+    } else if subtract {
+        stmts.extend(rreil!{ add wide:(size + 1), wide:(size + 1), [1]:(size + 1); }?);
+    }
+
+    stmts.extend(
+        rreil!{
+            mov res:(size), wide:(size);
+            xor ov:(size), (dst), res:(size);
+            xor ov2:(size), opnd:(size), res:(size);
+            and ov:(size), ov:(size), ov2:(size);
+            mov V:1, ov:1/(size - 1);
+            mov C:1, wide:1/(size);
+        }?
+    );
+    stmts.extend(nz(result(size), size)?);
+    Ok(stmts)
+}
+
+/// Result of `arith`.
+pub fn result(size: usize) -> Rvalue {
+    rreil_rvalue!{ res:(size) }
+}
+
+/// Jump condition `cc` as a 1-bit value. Condition 7 is `jmp`.
+pub fn condition(cc: u16) -> Result<(Vec<Statement>, Rvalue)> {
+    let stmts = match cc & 7 {
+        0 => rreil!{ xor cond:1, Z:1, [1]:1; }?,
+        1 => rreil!{ mov cond:1, Z:1; }?,
+        2 => rreil!{ xor cond:1, C:1, [1]:1; }?,
+        3 => rreil!{ mov cond:1, C:1; }?,
+        4 => rreil!{ mov cond:1, N:1; }?,
+        5 => {
+            rreil!{
+                xor cond:1, N:1, V:1;
+                xor cond:1, cond:1, [1]:1;
+            }?
+        }
+        6 => rreil!{ xor cond:1, N:1, V:1; }?,
+        _ => return Ok((vec![], Rvalue::new_bit(1))),
+    };
+
+    Ok((stmts, rreil_rvalue!{ cond:1 }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sane(stmts: Vec<Statement>) {
+        for s in stmts.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn statements_are_sane() {
+        for &sz in [8, 16, 20].iter() {
+            sane(arith(reg(4, sz), reg(5, sz), sz, false, false).unwrap());
+            sane(arith(reg(4, sz), imm(1, sz), sz, true, true).unwrap());
+            sane(arith(reg(4, sz), imm(1, sz), sz, true, false).unwrap());
+            sane(logic_flags(reg(6, sz), sz).unwrap());
+            sane(write_reg(7, reg(8, sz), 20).unwrap());
+            sane(load(reg_lv(9, sz), reg(SP, 20), sz).unwrap());
+            sane(store(reg(SP, 20), reg(9, sz), sz).unwrap());
+            sane(push(reg(10, sz), sz, 20).unwrap());
+            sane(pop(reg_lv(10, sz), sz, 20).unwrap());
+            sane(set_status_register(reg(11, sz), 20).unwrap());
+        }
+        for cc in 0..8 {
+            sane(condition(cc).unwrap().0);
+        }
+        sane(status_register(16, 16).unwrap().0);
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(reg_lv(SP, 16), rreil_lvalue!{ sp:16 });
+        assert_eq!(reg(12, 8), rreil_rvalue!{ r12:8 });
+        assert_eq!(sign_extend(0x3fe, 10), 0xffff_ffff_ffff_fffe);
+        assert_eq!(imm(0x1_2345, 16), Rvalue::Constant { value: 0x2345, size: 16 });
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_msp430;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Architecture, Function, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_msp430::{Model, Msp430};
+
+fn region(size: usize, start: usize, words: &[u16]) -> Region {
+    let mut bytes = vec![0xffu8; size];
+
+    for (i, w) in words.iter().enumerate() {
+        bytes[start + 2 * i] = *w as u8;
+        bytes[start + 2 * i + 1] = (w >> 8) as u8;
+    }
+
+    Region::wrap("ram".to_string(), bytes)
+}
+
+// Counts down R15, calling a subroutine on each iteration
+fn function() -> Region {
+    region(
+        0x10000,
+        0x4400,
+        &[
+            0x403f, 0x0005, // mov #5, R15
+            0x930f, // tst R15
+            0x2404, // jeq 0x4410
+            0x12b0, 0x4412, // call #0x4412
+            0x831f, // dec R15
+            0x3ffa, // jmp 0x4404
+            0x4130, // ret
+            0x4130, // ret
+        ],
+    )
+}
+
+#[test]
+fn loop_and_call() {
+    let reg = function();
+    let func = Function::new::<Msp430>(0x4400, &reg, None, Model::Msp430).unwrap();
+    let mut starts = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    starts.sort();
+
+    assert_eq!(starts, vec![0x4400, 0x4404, 0x4408, 0x4410]);
+    assert_eq!(func.cfg().num_edges(), 4);
+    assert_eq!(func.end(), 0x4412);
+    assert_eq!(func.collect_call_addresses(), vec![0x4412]);
+}
+
+#[test]
+fn reset_vector() {
+    let mut reg = function();
+
+    assert_eq!(Msp430::prepare(&reg, &Model::Msp430).unwrap(), vec![]);
+
+    reg = region(0x10000, 0xfffc, &[0xffff, 0x4400]);
+    let entries = Msp430::prepare(&reg, &Model::Msp430).unwrap();
+
+    assert_eq!(entries.iter().map(|e| (e.0, e.1)).collect::<Vec<_>>(), vec![("RESET", 0x4400)]);
+}
+
+#[test]
+fn extended_address_space() {
+    let reg = region(
+        0x20000,
+        0x10000,
+        &[
+            0x13b1, 0x0008, // calla #0x10008
+            0x0110, // reta
+            0x4303, // nop
+            0x0110, // reta
+        ],
+    );
+    let func = Function::new::<Msp430>(0x10000, &reg, None, Model::Msp430X).unwrap();
+
+    assert_eq!(func.basic_blocks().count(), 1);
+    assert_eq!(func.end(), 0x10006);
+    assert_eq!(func.collect_call_addresses(), vec![0x10008]);
+    assert!(Msp430::decode(&reg, 0x10000, &Model::Msp430).is_err());
+    assert!(Msp430::decode(&reg, 0x10001, &Model::Msp430X).is_err());
+}
@@ -0,0 +1,370 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Python bindings.
+//!
+//! Build with `maturin develop` inside this directory. The `panopticon` module exposes projects,
+//! their functions, basic blocks, mnemonics and IL statements, the searches of `search` and the
+//! renaming of `rename`:
+//!
+//! ```python
+//! import panopticon
+//!
+//! proj = panopticon.Project.open("/bin/ls")
+//! for func in proj.functions():
+//!     for bb in func.basic_blocks():
+//!         for mne in bb.mnemonics():
+//!             print(hex(mne.start), mne.text)
+//!
+//! for hit in proj.search_bytes("48 8b ?? ??"):
+//!     print(hex(hit.address), hit.function)
+//!
+//! proj.function(0x4011b0).rename("parse_args")
+//! proj.save("ls.panop")
+//! ```
+//!
+//! Functions and search hits refer to the project they came from, so they see renames made
+//! later. Basic blocks, mnemonics and statements are copies.
+
+extern crate panopticon_core;
+extern crate panopticon_analysis;
+extern crate pyo3;
+extern crate uuid;
+
+use panopticon_analysis::{Options, driver};
+use panopticon_core::{Collision, Error, Function, Lvalue, Pattern, Project, Statement, listing, rename, search};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+fn error(e: Error) -> PyErr {
+    PyRuntimeError::new_err(e.0.into_owned())
+}
+
+fn policy(suffix: bool) -> Collision {
+    if suffix { Collision::Suffix } else { Collision::Fail }
+}
+
+/// A loaded executable and the code found in it.
+#[pyclass(name = "Project")]
+#[derive(Clone)]
+struct PyProject {
+    inner: Arc<RwLock<Project>>,
+}
+
+impl PyProject {
+    fn new(proj: Project) -> PyProject {
+        PyProject { inner: Arc::new(RwLock::new(proj)) }
+    }
+
+    fn function_ref(&self, func: &Function) -> PyFunction {
+        PyFunction { project: self.clone(), uuid: func.uuid().clone() }
+    }
+
+    fn hits<I: Iterator<Item = search::Hit>>(&self, hits: I) -> Vec<PyHit> {
+        hits.map(|h| PyHit { project: self.clone(), region: h.region, address: h.address, function: h.function }).collect()
+    }
+}
+
+#[pymethods]
+impl PyProject {
+    /// Loads the executable at `path` and disassembles it.
+    #[staticmethod]
+    fn open(py: Python, path: &str) -> PyResult<PyProject> {
+        py.allow_threads(|| driver::analyze(Path::new(path), &Options::new())).map(PyProject::new).map_err(error)
+    }
+
+    /// Loads a session saved by `save`.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<PyProject> {
+        Project::open(Path::new(path)).map(PyProject::new).map_err(error)
+    }
+
+    /// Saves the session to `path`.
+    fn save(&self, path: &str) -> PyResult<()> {
+        self.inner.read().unwrap().snapshot(Path::new(path)).map_err(error)
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.inner.read().unwrap().name.clone()
+    }
+
+    /// All functions, ordered by address.
+    fn functions(&self) -> Vec<PyFunction> {
+        let proj = self.inner.read().unwrap();
+        let mut funcs = proj.code.iter().flat_map(|p| p.functions()).collect::<Vec<_>>();
+
+        funcs.sort_by_key(|f| f.start());
+        funcs.into_iter().map(|f| self.function_ref(f)).collect()
+    }
+
+    /// The function starting at `address`, or None.
+    fn function(&self, address: u64) -> Option<PyFunction> {
+        let proj = self.inner.read().unwrap();
+        let ret = proj.code.iter().filter_map(|p| p.find_function_by(|f| f.start() == address)).next().map(|f| self.function_ref(f));
+
+        ret
+    }
+
+    /// Finds a pattern of hex bytes with `??` wildcards, like `48 8b ?? ??`.
+    fn search_bytes(&self, pattern: &str) -> PyResult<Vec<PyHit>> {
+        let pattern = Pattern::parse(pattern).map_err(error)?;
+        let proj = self.inner.read().unwrap();
+        let ret = self.hits(search::bytes(&proj, &pattern));
+
+        Ok(ret)
+    }
+
+    /// Finds an ASCII or UTF-16LE string.
+    #[pyo3(signature = (s, utf16 = false))]
+    fn search_string(&self, s: &str, utf16: bool) -> Vec<PyHit> {
+        let pattern = if utf16 { Pattern::utf16(s) } else { Pattern::ascii(s) };
+        let proj = self.inner.read().unwrap();
+        let ret = self.hits(search::bytes(&proj, &pattern));
+
+        ret
+    }
+
+    /// Finds mnemonics using `value` as operand or in their IL.
+    fn search_immediate(&self, value: u64) -> Vec<PyHit> {
+        let proj = self.inner.read().unwrap();
+        let ret = self.hits(search::immediates(&proj, value));
+
+        ret
+    }
+
+    /// Names the global variable or function at `address`, see `rename::rename_global`. If
+    /// `suffix` is true a taken name gets a numeric suffix instead of failing. Returns the new
+    /// name.
+    #[pyo3(signature = (address, name, suffix = false))]
+    fn rename(&self, address: u64, name: &str, suffix: bool) -> PyResult<String> {
+        rename::rename_global(&mut self.inner.write().unwrap(), address, name, policy(suffix)).map_err(error)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Project {}>", self.name())
+    }
+}
+
+/// Function of a project.
+#[pyclass(name = "Function")]
+#[derive(Clone)]
+struct PyFunction {
+    project: PyProject,
+    uuid: Uuid,
+}
+
+impl PyFunction {
+    /// Runs `f` with the function, failing if it has been removed from the project.
+    fn with<T, F: FnOnce(&Project, &Function) -> T>(&self, f: F) -> PyResult<T> {
+        let proj = self.project.inner.read().unwrap();
+
+        match proj.find_function_by_uuid(&self.uuid) {
+            Some(func) => Ok(f(&proj, func)),
+            None => Err(PyRuntimeError::new_err("function no longer exists")),
+        }
+    }
+}
+
+#[pymethods]
+impl PyFunction {
+    #[getter]
+    fn name(&self) -> PyResult<String> {
+        self.with(|_, f| f.display_name())
+    }
+
+    #[getter]
+    fn start(&self) -> PyResult<u64> {
+        self.with(|_, f| f.start())
+    }
+
+    #[getter]
+    fn uuid(&self) -> String {
+        self.uuid.to_string()
+    }
+
+    /// Basic blocks, ordered by address.
+    fn basic_blocks(&self) -> PyResult<Vec<PyBasicBlock>> {
+        self.with(
+            |proj, func| {
+                let prog = proj.code.iter().find(|p| p.find_function_by_uuid(&self.uuid).is_some()).unwrap();
+                let mut bbs = func.basic_blocks().collect::<Vec<_>>();
+
+                bbs.sort_by_key(|bb| bb.area.start);
+                bbs.into_iter()
+                    .map(
+                        |bb| {
+                            let mnes = bb.mnemonics
+                                .iter()
+                                .map(
+                                    |m| {
+                                        PyMnemonic {
+                                            start: m.area.start,
+                                            end: m.area.end,
+                                            opcode: m.opcode.clone(),
+                                            text: listing::instruction(proj, prog, m),
+                                            statements: m.instructions.clone(),
+                                        }
+                                    }
+                                )
+                                .collect();
+                            PyBasicBlock { start: bb.area.start, end: bb.area.end, mnemonics: mnes }
+                        }
+                    )
+                    .collect()
+            }
+        )
+    }
+
+    /// Renames the function, see `rename::rename_function`. Returns the new name.
+    #[pyo3(signature = (name, suffix = false))]
+    fn rename(&self, name: &str, suffix: bool) -> PyResult<String> {
+        rename::rename_function(&mut self.project.inner.write().unwrap(), &self.uuid, name, policy(suffix)).map_err(error)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        self.with(|_, f| format!("<Function {} at {:#x}>", f.display_name(), f.start()))
+    }
+}
+
+/// Basic block of a function.
+#[pyclass(name = "BasicBlock")]
+#[derive(Clone)]
+struct PyBasicBlock {
+    /// Address of the first byte
+    #[pyo3(get)]
+    start: u64,
+    /// Address after the last byte
+    #[pyo3(get)]
+    end: u64,
+    mnemonics: Vec<PyMnemonic>,
+}
+
+#[pymethods]
+impl PyBasicBlock {
+    fn mnemonics(&self) -> Vec<PyMnemonic> {
+        self.mnemonics.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<BasicBlock {:#x}-{:#x}>", self.start, self.end)
+    }
+}
+
+/// Decoded instruction.
+#[pyclass(name = "Mnemonic")]
+#[derive(Clone)]
+struct PyMnemonic {
+    /// Address of the first byte
+    #[pyo3(get)]
+    start: u64,
+    /// Address after the last byte
+    #[pyo3(get)]
+    end: u64,
+    #[pyo3(get)]
+    opcode: String,
+    /// Opcode and operands as printed by `listing`
+    #[pyo3(get)]
+    text: String,
+    statements: Vec<Statement>,
+}
+
+#[pymethods]
+impl PyMnemonic {
+    /// IL statements the mnemonic is translated into.
+    fn statements(&self) -> Vec<PyStatement> {
+        self.statements.iter().map(|s| PyStatement { inner: s.clone() }).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Mnemonic {:#x} {}>", self.start, self.text)
+    }
+}
+
+/// IL statement.
+#[pyclass(name = "Statement")]
+#[derive(Clone)]
+struct PyStatement {
+    inner: Statement,
+}
+
+#[pymethods]
+impl PyStatement {
+    /// Variable written, or None
+    #[getter]
+    fn assignee(&self) -> Option<String> {
+        match self.inner.assignee {
+            Lvalue::Variable { ref name, .. } => Some(name.to_string()),
+            Lvalue::Undefined => None,
+        }
+    }
+
+    /// Operands, in RREIL syntax
+    #[getter]
+    fn operands(&self) -> Vec<String> {
+        self.inner.op.operands().iter().map(|rv| rv.to_string()).collect()
+    }
+
+    fn __str__(&self) -> String {
+        self.inner.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Statement {}>", self.inner)
+    }
+}
+
+/// Search result.
+#[pyclass(name = "Hit")]
+struct PyHit {
+    project: PyProject,
+    /// Name of the region searched
+    #[pyo3(get)]
+    region: String,
+    /// Address of the match
+    #[pyo3(get)]
+    address: u64,
+    function: Option<Uuid>,
+}
+
+#[pymethods]
+impl PyHit {
+    /// Function containing the match, or None
+    #[getter]
+    fn function(&self) -> Option<PyFunction> {
+        self.function.as_ref().map(|uu| PyFunction { project: self.project.clone(), uuid: uu.clone() })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Hit {}:{:#x}>", self.region, self.address)
+    }
+}
+
+#[pymodule]
+fn panopticon(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyProject>()?;
+    m.add_class::<PyFunction>()?;
+    m.add_class::<PyBasicBlock>()?;
+    m.add_class::<PyMnemonic>()?;
+    m.add_class::<PyStatement>()?;
+    m.add_class::<PyHit>()?;
+    Ok(())
+}
@@ -22,13 +22,12 @@
 use errors::*;
 use futures::{Future, future};
 use panopticon_abstract_interp::Kset;
-use panopticon_core::{ControlFlowTarget, Function, Guard, Mnemonic, Rvalue};
+use panopticon_core::{ControlFlowTarget, Function, Guard, Mnemonic, Rvalue, layout};
 use panopticon_graph_algos::{EdgeListGraphTrait, GraphTrait, IncidenceGraphTrait, VertexListGraphTrait};
 use panopticon_graph_algos::adjacency_list::{AdjacencyListEdgeDescriptor, AdjacencyListVertexDescriptor};
 use singleton::{AbstractInterpretation, VarName};
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
-use sugiyama;
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -123,19 +122,19 @@ impl ControlFlowLayout {
             move || -> Result<_> {
                 let vx_vec = vx_vec;
                 let edges = edges2;
-                Ok(sugiyama::linear_layout_start(&vx_vec, &edges, None)?)
+                Ok(layout::linear_layout_start(&vx_vec, &edges, None)?)
             }
         )
-                .and_then(move |layout| future::result(sugiyama::linear_layout_rank(layout)))
-                .and_then(move |layout| future::result(sugiyama::linear_layout_initial_order(layout)))
+                .and_then(move |layout| future::result(layout::linear_layout_rank(layout)))
+                .and_then(move |layout| future::result(layout::linear_layout_initial_order(layout)))
                 .and_then(
                     move |layout| {
                         future::loop_fn(
                             layout,
-                            |layout| if let &sugiyama::LinearLayout::Ordering { iterations_left: 0, .. } = &layout {
+                            |layout| if let &layout::LinearLayout::Ordering { iterations_left: 0, .. } = &layout {
                                 Ok(future::Loop::Break(layout))
                             } else {
-                                sugiyama::linear_layout_order(layout).map(|x| future::Loop::Continue(x))
+                                layout::linear_layout_order(layout).map(|x| future::Loop::Continue(x))
                             },
                         )
                     }
@@ -150,7 +149,7 @@ impl ControlFlowLayout {
 
                         future::lazy(
                             move || {
-                                let mut placement = sugiyama::linear_layout_placement(
+                                let mut placement = layout::linear_layout_placement(
                                     &vertices.iter().map(|&vx| vx).collect::<Vec<_>>(),
                                     &edges,
                                     &layout,
@@ -26,10 +26,17 @@ extern crate panopticon_abstract_interp;
 extern crate panopticon_data_flow;
 extern crate panopticon_graph_algos;
 extern crate panopticon_amd64;
+extern crate panopticon_arm;
 extern crate panopticon_avr;
+extern crate panopticon_m68k;
+extern crate panopticon_mcs51;
+extern crate panopticon_msp430;
+extern crate panopticon_superh;
+extern crate panopticon_mips;
+extern crate panopticon_riscv;
+extern crate panopticon_wasm;
 extern crate libc;
 extern crate uuid;
-extern crate cassowary;
 extern crate tempdir;
 extern crate chrono;
 extern crate chrono_humanize;
@@ -49,7 +56,6 @@ extern crate error_chain;
 #[macro_use]
 extern crate lazy_static;
 
-mod sugiyama;
 mod singleton;
 mod control_flow_layout;
 mod paths;
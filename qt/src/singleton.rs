@@ -128,7 +128,15 @@ impl Panopticon {
         use std::path::Path;
         use panopticon_core::{CallTarget, Machine};
         use panopticon_amd64 as amd64;
+        use panopticon_arm as arm;
         use panopticon_avr as avr;
+        use panopticon_m68k as m68k;
+        use panopticon_mcs51 as mcs51;
+        use panopticon_msp430 as msp430;
+        use panopticon_superh as superh;
+        use panopticon_mips as mips;
+        use panopticon_riscv as riscv;
+        use panopticon_wasm as wasm;
         use panopticon_analysis::pipeline;
         use futures::Stream;
         use std::ffi::CString;
@@ -163,6 +171,16 @@ impl Panopticon {
                     Machine::Avr => pipeline::<avr::Avr>(prog, reg.clone(), avr::Mcu::atmega103()),
                     Machine::Ia32 => pipeline::<amd64::Amd64>(prog, reg.clone(), amd64::Mode::Protected),
                     Machine::Amd64 => pipeline::<amd64::Amd64>(prog, reg.clone(), amd64::Mode::Long),
+                    Machine::Arm => pipeline::<arm::Arm>(prog, reg.clone(), arm::Cpu::new(arm::Mode::Arm).with_mapping_symbols(&proj.mapping_symbols)),
+                    Machine::Mips(e) => pipeline::<mips::Mips>(prog, reg.clone(), mips::Cpu::new(mips::Mode::Mips32, e)),
+                    Machine::Mips64(e) => pipeline::<mips::Mips>(prog, reg.clone(), mips::Cpu::new(mips::Mode::Mips64, e)),
+                    Machine::RiscV32(flags) => pipeline::<riscv::Riscv>(prog, reg.clone(), riscv::Cpu::new(riscv::Xlen::Rv32, riscv::Extensions::from_elf_flags(flags))),
+                    Machine::RiscV64(flags) => pipeline::<riscv::Riscv>(prog, reg.clone(), riscv::Cpu::new(riscv::Xlen::Rv64, riscv::Extensions::from_elf_flags(flags))),
+                    Machine::M68k => pipeline::<m68k::M68k>(prog, reg.clone(), m68k::Model::M68000),
+                    Machine::Mcs51 => pipeline::<mcs51::Mcs51>(prog, reg.clone(), mcs51::Cpu::new(mcs51::Model::I8051)),
+                    Machine::Msp430(flags) => pipeline::<msp430::Msp430>(prog, reg.clone(), msp430::Model::from_elf_flags(flags)),
+                    Machine::SuperH(e, flags) => pipeline::<superh::SuperH>(prog, reg.clone(), superh::Cpu::new(superh::Model::from_elf_flags(flags), e)),
+                    Machine::Wasm => pipeline::<wasm::Wasm>(prog, reg.clone(), wasm::Cpu::from_region(&reg)?),
                 };
                 self.region = Some(reg);
 
@@ -0,0 +1,172 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use byteorder::{ByteOrder, LittleEndian};
+use decode::{self, Flow};
+use panopticon_core::{Architecture, Guard, Match, Region, Result, Rvalue};
+
+#[derive(Clone,Debug)]
+pub enum Riscv {}
+
+/// Width of the integer registers
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Xlen {
+    /// RV32
+    Rv32,
+    /// RV64
+    Rv64,
+}
+
+/// Enabled standard extensions
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct Extensions {
+    /// Integer multiplication and division
+    pub m: bool,
+    /// Atomic instructions
+    pub a: bool,
+    /// Single precision floating point
+    pub f: bool,
+    /// Double precision floating point
+    pub d: bool,
+    /// Compressed instructions
+    pub c: bool,
+}
+
+impl Extensions {
+    /// Base integer instruction set only.
+    pub fn none() -> Extensions {
+        Extensions { m: false, a: false, f: false, d: false, c: false }
+    }
+
+    /// IMAFD, the general purpose instruction set, plus compressed instructions.
+    pub fn gc() -> Extensions {
+        Extensions { m: true, a: true, f: true, d: true, c: true }
+    }
+
+    /// Guesses the extensions from the `e_flags` field of an ELF header. The flags only record
+    /// compressed instruction use and the floating point ABI, M and A are assumed to be present.
+    pub fn from_elf_flags(flags: u32) -> Extensions {
+        const EF_RISCV_RVC: u32 = 0x1;
+        const EF_RISCV_FLOAT_ABI: u32 = 0x6;
+
+        let float = flags & EF_RISCV_FLOAT_ABI;
+
+        Extensions {
+            m: true,
+            a: true,
+            f: float != 0,
+            d: float >= 0x4,
+            c: flags & EF_RISCV_RVC != 0,
+        }
+    }
+}
+
+/// CPU configuration.
+#[derive(Clone,Copy,Debug)]
+pub struct Cpu {
+    pub xlen: Xlen,
+    pub extensions: Extensions,
+}
+
+impl Cpu {
+    pub fn new(xlen: Xlen, extensions: Extensions) -> Cpu {
+        Cpu { xlen: xlen, extensions: extensions }
+    }
+
+    /// Size of integer registers and addresses in bits.
+    pub fn width(&self) -> usize {
+        match self.xlen {
+            Xlen::Rv32 => 32,
+            Xlen::Rv64 => 64,
+        }
+    }
+
+    /// Size of floating point registers in bits. Zero if no floating point extension is enabled.
+    pub fn flen(&self) -> usize {
+        if self.extensions.d {
+            64
+        } else if self.extensions.f {
+            32
+        } else {
+            0
+        }
+    }
+
+    /// Truncates `v` to the register width.
+    pub fn mask(&self, v: u64) -> u64 {
+        match self.xlen {
+            Xlen::Rv32 => v & 0xffff_ffff,
+            Xlen::Rv64 => v,
+        }
+    }
+
+    /// Reads the 16-bit instruction parcel at `addr`.
+    pub fn read_parcel(&self, reg: &Region, addr: u64) -> Option<u16> {
+        let mut buf = [0u8; 2];
+        let mut i = reg.iter().seek(addr);
+
+        for b in buf.iter_mut() {
+            match i.next() {
+                Some(Some(x)) => *b = x,
+                _ => return None,
+            }
+        }
+
+        Some(LittleEndian::read_u16(&buf))
+    }
+}
+
+impl Architecture for Riscv {
+    type Token = u16;
+    type Configuration = Cpu;
+
+    fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        Ok(vec![])
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        let align = if cfg.extensions.c { 2 } else { 4 };
+        if start % align != 0 {
+            return Err(format!("unaligned RISC-V instruction at {:#x}", start).into());
+        }
+
+        let (insn, tokens) = decode::read(cfg, reg, start)?;
+        let len = 2 * tokens.len() as u64;
+        let next = Rvalue::Constant { value: cfg.mask(start + len), size: cfg.width() };
+        let jumps = match insn.flow.clone() {
+            Flow::Next | Flow::Call(_) => vec![(start, next, Guard::always())],
+            Flow::Jump(tgt) => vec![(start, tgt, Guard::always())],
+            Flow::Branch(tgt, flag) => {
+                let guard = Guard::from_flag(&flag)?;
+                vec![(start, tgt, guard.clone()), (start, next, guard.negation())]
+            }
+            Flow::Return => vec![],
+        };
+
+        debug!("disass @ {:#x}: {} {:?}", start, insn.opcode, insn.flow);
+
+        Ok(
+            Match::<Riscv> {
+                tokens: tokens,
+                mnemonics: vec![insn.mnemonic(start, len)?],
+                jumps: jumps,
+                configuration: cfg.clone(),
+            }
+        )
+    }
+}
@@ -0,0 +1,263 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Expansion of compressed (RVC) instructions into their 32-bit equivalents.
+
+use architecture::{Cpu, Xlen};
+use panopticon_core::Result;
+use semantic::{RA, SP, ZERO};
+
+fn bits(x: u16, hi: u32, lo: u32) -> u32 {
+    ((x as u32) >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+fn bit(x: u16, n: u32) -> u32 {
+    bits(x, n, n)
+}
+
+/// Register from a 3-bit field. Compressed instructions can only address x8 to x15 this way.
+fn creg(x: u16, lo: u32) -> u32 {
+    bits(x, lo + 2, lo) + 8
+}
+
+fn sext(v: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    (((v << shift) as i32) >> shift) as u32
+}
+
+fn r_type(op: u32, rd: u32, f3: u32, rs1: u32, rs2: u32, f7: u32) -> u32 {
+    (f7 << 25) | (rs2 << 20) | (rs1 << 15) | (f3 << 12) | (rd << 7) | op
+}
+
+fn i_type(op: u32, rd: u32, f3: u32, rs1: u32, imm: u32) -> u32 {
+    ((imm & 0xfff) << 20) | (rs1 << 15) | (f3 << 12) | (rd << 7) | op
+}
+
+fn s_type(op: u32, f3: u32, rs1: u32, rs2: u32, imm: u32) -> u32 {
+    (((imm >> 5) & 0x7f) << 25) | (rs2 << 20) | (rs1 << 15) | (f3 << 12) | ((imm & 0x1f) << 7) | op
+}
+
+fn b_type(f3: u32, rs1: u32, rs2: u32, imm: u32) -> u32 {
+    (((imm >> 12) & 1) << 31) | (((imm >> 5) & 0x3f) << 25) | (rs2 << 20) | (rs1 << 15) | (f3 << 12) | (((imm >> 1) & 0xf) << 8) | (((imm >> 11) & 1) << 7) | 0x63
+}
+
+fn u_type(op: u32, rd: u32, imm: u32) -> u32 {
+    (imm & 0xffff_f000) | (rd << 7) | op
+}
+
+fn j_type(rd: u32, imm: u32) -> u32 {
+    (((imm >> 20) & 1) << 31) | (((imm >> 1) & 0x3ff) << 21) | (((imm >> 11) & 1) << 20) | (((imm >> 12) & 0xff) << 12) | (rd << 7) | 0x6f
+}
+
+/// Offset of `c.j` and `c.jal`.
+fn cj_offset(x: u16) -> u32 {
+    let imm = (bit(x, 12) << 11) | (bit(x, 11) << 4) | (bits(x, 10, 9) << 8) | (bit(x, 8) << 10) | (bit(x, 7) << 6) | (bit(x, 6) << 7) | (bits(x, 5, 3) << 1) | (bit(x, 2) << 5);
+    sext(imm, 12)
+}
+
+/// Offset of `c.beqz` and `c.bnez`.
+fn cb_offset(x: u16) -> u32 {
+    let imm = (bit(x, 12) << 8) | (bits(x, 11, 10) << 3) | (bits(x, 6, 5) << 6) | (bits(x, 4, 3) << 1) | (bit(x, 2) << 5);
+    sext(imm, 9)
+}
+
+/// 6-bit signed immediate of CI-format instructions.
+fn ci_imm(x: u16) -> u32 {
+    sext((bit(x, 12) << 5) | bits(x, 6, 2), 6)
+}
+
+/// Expands the compressed instruction `x` into the equivalent 32-bit instruction.
+pub fn expand(cpu: &Cpu, x: u16) -> Result<u32> {
+    let rv64 = cpu.xlen == Xlen::Rv64;
+    let illegal = || Err(format!("illegal compressed instruction {:#06x}", x).into());
+    let f3 = bits(x, 15, 13);
+
+    if x == 0 {
+        return illegal();
+    }
+
+    let insn = match (x & 3, f3) {
+        // c.addi4spn
+        (0, 0) => {
+            let imm = (bits(x, 12, 11) << 4) | (bits(x, 10, 7) << 6) | (bit(x, 6) << 2) | (bit(x, 5) << 3);
+            if imm == 0 {
+                return illegal();
+            }
+            i_type(0x13, creg(x, 2), 0, SP, imm)
+        }
+        // c.fld, c.lw, c.flw/c.ld
+        (0, 1) | (0, 3) if f3 == 1 || rv64 => {
+            let imm = (bits(x, 12, 10) << 3) | (bits(x, 6, 5) << 6);
+            if f3 == 1 { i_type(0x07, creg(x, 2), 3, creg(x, 7), imm) } else { i_type(0x03, creg(x, 2), 3, creg(x, 7), imm) }
+        }
+        (0, 2) | (0, 3) => {
+            let imm = (bits(x, 12, 10) << 3) | (bit(x, 6) << 2) | (bit(x, 5) << 6);
+            if f3 == 2 { i_type(0x03, creg(x, 2), 2, creg(x, 7), imm) } else { i_type(0x07, creg(x, 2), 2, creg(x, 7), imm) }
+        }
+        // c.fsd, c.sw, c.fsw/c.sd
+        (0, 5) | (0, 7) if f3 == 5 || rv64 => {
+            let imm = (bits(x, 12, 10) << 3) | (bits(x, 6, 5) << 6);
+            if f3 == 5 { s_type(0x27, 3, creg(x, 7), creg(x, 2), imm) } else { s_type(0x23, 3, creg(x, 7), creg(x, 2), imm) }
+        }
+        (0, 6) | (0, 7) => {
+            let imm = (bits(x, 12, 10) << 3) | (bit(x, 6) << 2) | (bit(x, 5) << 6);
+            if f3 == 6 { s_type(0x23, 2, creg(x, 7), creg(x, 2), imm) } else { s_type(0x27, 2, creg(x, 7), creg(x, 2), imm) }
+        }
+        // c.addi, c.nop
+        (1, 0) => {
+            let rd = bits(x, 11, 7);
+            i_type(0x13, rd, 0, rd, ci_imm(x))
+        }
+        // c.addiw
+        (1, 1) if rv64 => {
+            let rd = bits(x, 11, 7);
+            if rd == ZERO {
+                return illegal();
+            }
+            i_type(0x1b, rd, 0, rd, ci_imm(x))
+        }
+        // c.jal
+        (1, 1) => j_type(RA, cj_offset(x)),
+        // c.li
+        (1, 2) => i_type(0x13, bits(x, 11, 7), 0, ZERO, ci_imm(x)),
+        (1, 3) => {
+            let rd = bits(x, 11, 7);
+
+            if rd == SP {
+                // c.addi16sp
+                let imm = (bit(x, 12) << 9) | (bit(x, 6) << 4) | (bit(x, 5) << 6) | (bits(x, 4, 3) << 7) | (bit(x, 2) << 5);
+                if imm == 0 {
+                    return illegal();
+                }
+                i_type(0x13, SP, 0, SP, sext(imm, 10))
+            } else {
+                // c.lui
+                let imm = sext((bit(x, 12) << 17) | (bits(x, 6, 2) << 12), 18);
+                if imm == 0 {
+                    return illegal();
+                }
+                u_type(0x37, rd, imm)
+            }
+        }
+        (1, 4) => {
+            let rd = creg(x, 7);
+            let shamt = (bit(x, 12) << 5) | bits(x, 6, 2);
+
+            match bits(x, 11, 10) {
+                0 => i_type(0x13, rd, 5, rd, shamt),
+                1 => i_type(0x13, rd, 5, rd, shamt | 0x400),
+                2 => i_type(0x13, rd, 7, rd, ci_imm(x)),
+                _ => {
+                    let rs2 = creg(x, 2);
+
+                    match (bit(x, 12), bits(x, 6, 5)) {
+                        (0, 0) => r_type(0x33, rd, 0, rd, rs2, 0x20),
+                        (0, 1) => r_type(0x33, rd, 4, rd, rs2, 0),
+                        (0, 2) => r_type(0x33, rd, 6, rd, rs2, 0),
+                        (0, 3) => r_type(0x33, rd, 7, rd, rs2, 0),
+                        (1, 0) if rv64 => r_type(0x3b, rd, 0, rd, rs2, 0x20),
+                        (1, 1) if rv64 => r_type(0x3b, rd, 0, rd, rs2, 0),
+                        _ => return illegal(),
+                    }
+                }
+            }
+        }
+        // c.j
+        (1, 5) => j_type(ZERO, cj_offset(x)),
+        // c.beqz, c.bnez
+        (1, 6) => b_type(0, creg(x, 7), ZERO, cb_offset(x)),
+        (1, 7) => b_type(1, creg(x, 7), ZERO, cb_offset(x)),
+        // c.slli
+        (2, 0) => {
+            let rd = bits(x, 11, 7);
+            i_type(0x13, rd, 1, rd, (bit(x, 12) << 5) | bits(x, 6, 2))
+        }
+        // c.fldsp, c.ldsp
+        (2, 1) | (2, 3) if f3 == 1 || rv64 => {
+            let imm = (bit(x, 12) << 5) | (bits(x, 6, 5) << 3) | (bits(x, 4, 2) << 6);
+            if f3 == 1 { i_type(0x07, bits(x, 11, 7), 3, SP, imm) } else { i_type(0x03, bits(x, 11, 7), 3, SP, imm) }
+        }
+        // c.lwsp, c.flwsp
+        (2, 2) | (2, 3) => {
+            let imm = (bit(x, 12) << 5) | (bits(x, 6, 4) << 2) | (bits(x, 3, 2) << 6);
+            if f3 == 2 { i_type(0x03, bits(x, 11, 7), 2, SP, imm) } else { i_type(0x07, bits(x, 11, 7), 2, SP, imm) }
+        }
+        (2, 4) => {
+            let (rs1, rs2) = (bits(x, 11, 7), bits(x, 6, 2));
+
+            match (bit(x, 12), rs1, rs2) {
+                (0, 0, 0) => return illegal(),
+                // c.jr
+                (0, _, 0) => i_type(0x67, ZERO, 0, rs1, 0),
+                // c.mv
+                (0, _, _) => r_type(0x33, rs1, 0, ZERO, rs2, 0),
+                // c.ebreak
+                (_, 0, 0) => 0x0010_0073,
+                // c.jalr
+                (_, _, 0) => i_type(0x67, RA, 0, rs1, 0),
+                // c.add
+                _ => r_type(0x33, rs1, 0, rs1, rs2, 0),
+            }
+        }
+        // c.fsdsp, c.sdsp
+        (2, 5) | (2, 7) if f3 == 5 || rv64 => {
+            let imm = (bits(x, 12, 10) << 3) | (bits(x, 9, 7) << 6);
+            if f3 == 5 { s_type(0x27, 3, SP, bits(x, 6, 2), imm) } else { s_type(0x23, 3, SP, bits(x, 6, 2), imm) }
+        }
+        // c.swsp, c.fswsp
+        (2, 6) | (2, 7) => {
+            let imm = (bits(x, 12, 9) << 2) | (bits(x, 8, 7) << 6);
+            if f3 == 6 { s_type(0x23, 2, SP, bits(x, 6, 2), imm) } else { s_type(0x27, 2, SP, bits(x, 6, 2), imm) }
+        }
+        _ => return illegal(),
+    };
+
+    Ok(insn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Extensions;
+
+    #[test]
+    fn expansion() {
+        let rv32 = Cpu::new(Xlen::Rv32, Extensions::gc());
+        let rv64 = Cpu::new(Xlen::Rv64, Extensions::gc());
+
+        // c.addi sp, -16 => addi sp, sp, -16
+        assert_eq!(expand(&rv64, 0x1141).unwrap(), 0xff010113);
+        // c.sdsp ra, 8(sp) => sd ra, 8(sp)
+        assert_eq!(expand(&rv64, 0xe406).unwrap(), 0x00113423);
+        // c.ldsp ra, 8(sp) => ld ra, 8(sp)
+        assert_eq!(expand(&rv64, 0x60a2).unwrap(), 0x00813083);
+        // c.jr ra => jalr zero, 0(ra)
+        assert_eq!(expand(&rv64, 0x8082).unwrap(), 0x00008067);
+        // c.li a0, 1 => addi a0, zero, 1
+        assert_eq!(expand(&rv64, 0x4505).unwrap(), 0x00100513);
+        // c.beqz a0, +8 => beq a0, zero, 8
+        assert_eq!(expand(&rv64, 0xc501).unwrap(), 0x00050463);
+        // c.j -2 => jal zero, -2
+        assert_eq!(expand(&rv64, 0xbffd).unwrap(), 0xfffff06f);
+        // c.jal on RV32, c.addiw on RV64
+        assert_eq!(expand(&rv32, 0x2505).unwrap() & 0xfff, 0x0ef);
+        assert_eq!(expand(&rv64, 0x2505).unwrap(), 0x0015051b);
+        assert!(expand(&rv64, 0x2011).is_err());
+        assert!(expand(&rv64, 0x0000).is_err());
+    }
+}
@@ -0,0 +1,749 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! RISC-V instruction decoder.
+
+use architecture::{Cpu, Xlen};
+use compressed;
+use panopticon_core::{Lvalue, Mnemonic, Operation, Region, Result, Rvalue, Statement};
+use semantic::*;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Jumps to the target
+    Jump(Rvalue),
+    /// Jumps to the target if the flag is set, falls through otherwise
+    Branch(Rvalue, Rvalue),
+    /// Calls the target and falls through
+    Call(Rvalue),
+    /// Returns to the caller
+    Return,
+}
+
+/// A single decoded instruction.
+#[derive(Clone,Debug)]
+pub struct Insn {
+    pub opcode: String,
+    pub format: String,
+    pub operands: Vec<Rvalue>,
+    pub statements: Vec<Statement>,
+    pub flow: Flow,
+}
+
+impl Insn {
+    fn new(opcode: &str, format: &str, operands: Vec<Rvalue>, statements: Vec<Statement>) -> Insn {
+        Insn {
+            opcode: opcode.to_string(),
+            format: format.to_string(),
+            operands: operands,
+            statements: statements,
+            flow: Flow::Next,
+        }
+    }
+
+    fn flow(mut self, flow: Flow) -> Insn {
+        self.flow = flow;
+        self
+    }
+
+    pub fn mnemonic(&self, addr: u64, len: u64) -> Result<Mnemonic> {
+        Mnemonic::new(addr..addr + len, self.opcode.clone(), self.format.clone(), self.operands.iter(), self.statements.iter())
+    }
+}
+
+struct Fields {
+    op: u32,
+    rd: u32,
+    f3: u32,
+    rs1: u32,
+    rs2: u32,
+    f7: u32,
+    i_imm: u64,
+    s_imm: u64,
+    b_imm: u64,
+    u_imm: u64,
+    j_imm: u64,
+}
+
+impl Fields {
+    fn new(insn: u32) -> Fields {
+        let x = insn as u64;
+
+        Fields {
+            op: insn & 0x7f,
+            rd: (insn >> 7) & 0x1f,
+            f3: (insn >> 12) & 7,
+            rs1: (insn >> 15) & 0x1f,
+            rs2: (insn >> 20) & 0x1f,
+            f7: insn >> 25,
+            i_imm: sign_extend(x >> 20, 12),
+            s_imm: sign_extend(((x >> 20) & 0xfe0) | ((x >> 7) & 0x1f), 12),
+            b_imm: sign_extend(((x >> 19) & 0x1000) | ((x << 4) & 0x800) | ((x >> 20) & 0x7e0) | ((x >> 7) & 0x1e), 13),
+            u_imm: sign_extend(x & 0xffff_f000, 32),
+            j_imm: sign_extend(((x >> 11) & 0x10_0000) | (x & 0xf_f000) | ((x >> 9) & 0x800) | ((x >> 20) & 0x7fe), 21),
+        }
+    }
+}
+
+fn unknown(insn: u32) -> Result<Insn> {
+    Err(format!("unknown RISC-V instruction {:#010x}", insn).into())
+}
+
+/// Decodes the instruction at `addr`. Returns the instruction and the parcels it consists of.
+pub fn read(cpu: &Cpu, region: &Region, addr: u64) -> Result<(Insn, Vec<u16>)> {
+    let lo = match cpu.read_parcel(region, addr) {
+        Some(p) => p,
+        None => return Err(format!("RISC-V instruction at {:#x} truncated", addr).into()),
+    };
+
+    if lo & 3 != 3 {
+        if !cpu.extensions.c {
+            return Err(format!("compressed instruction at {:#x} but C extension disabled", addr).into());
+        }
+
+        let insn = compressed::expand(cpu, lo)?;
+        return Ok((decode(cpu, region, insn, addr, 2)?, vec![lo]));
+    }
+
+    if lo & 0x1f == 0x1f {
+        return Err(format!("RISC-V instruction longer than 32 bits at {:#x}", addr).into());
+    }
+
+    let hi = match cpu.read_parcel(region, addr + 2) {
+        Some(p) => p,
+        None => return Err(format!("RISC-V instruction at {:#x} truncated", addr).into()),
+    };
+    let insn = (lo as u32) | ((hi as u32) << 16);
+
+    Ok((decode(cpu, region, insn, addr, 4)?, vec![lo, hi]))
+}
+
+/// Lifts the 32-bit instruction `insn` at `addr`. Compressed instructions are passed in expanded
+/// form with `len` 2.
+pub fn decode(cpu: &Cpu, region: &Region, insn: u32, addr: u64, len: u64) -> Result<Insn> {
+    let f = Fields::new(insn);
+    let rv64 = cpu.xlen == Xlen::Rv64;
+
+    match f.op {
+        0x03 => load_store(cpu, insn, &f, true),
+        0x07 | 0x27 if cpu.flen() > 0 => fp_load_store(cpu, insn, &f),
+        0x0f if f.f3 <= 1 => Ok(Insn::new(if f.f3 == 1 { "fence.i" } else { "fence" }, "", vec![], vec![])),
+        0x13 => op_imm(cpu, insn, &f),
+        0x17 => {
+            let value = imm(cpu, addr.wrapping_add(f.u_imm));
+            let rd = reg_lv(cpu, f.rd);
+            Ok(Insn::new("auipc", "{u}, {u}", vec![operand(cpu, f.rd), imm(cpu, f.u_imm >> 12)], rreil!{ mov (rd), (value); }?))
+        }
+        0x1b if rv64 => op_imm_32(cpu, insn, &f),
+        0x23 => load_store(cpu, insn, &f, false),
+        0x2f if cpu.extensions.a => atomic(cpu, insn, &f),
+        0x33 => op(cpu, insn, &f, false),
+        0x37 => {
+            let rd = reg_lv(cpu, f.rd);
+            let value = imm(cpu, f.u_imm);
+            Ok(Insn::new("lui", "{u}, {u}", vec![operand(cpu, f.rd), imm(cpu, f.u_imm >> 12)], rreil!{ mov (rd), (value); }?))
+        }
+        0x3b if rv64 => op(cpu, insn, &f, true),
+        0x43 | 0x47 | 0x4b | 0x4f | 0x53 if cpu.flen() > 0 => fp_op(cpu, insn, &f),
+        0x63 => branch(cpu, insn, &f, addr),
+        0x67 if f.f3 == 0 => jalr(cpu, region, &f, addr, len),
+        0x6f => {
+            let target = imm(cpu, addr.wrapping_add(f.j_imm));
+            let mut stmts = vec![];
+
+            if f.rd != ZERO {
+                let rd = reg_lv(cpu, f.rd);
+                let ret = imm(cpu, addr + len);
+                stmts.extend(rreil!{ mov (rd), (ret); }?);
+            }
+
+            match f.rd {
+                ZERO => Ok(Insn::new("j", "{c:ram}", vec![target.clone()], stmts).flow(Flow::Jump(target))),
+                RA | T0 => {
+                    stmts.extend(rreil!{ call (target); }?);
+                    let ops = if f.rd == RA { vec![target.clone()] } else { vec![operand(cpu, f.rd), target.clone()] };
+                    let fmt = if f.rd == RA { "{c:ram}" } else { "{u}, {c:ram}" };
+                    Ok(Insn::new("jal", fmt, ops, stmts).flow(Flow::Call(target)))
+                }
+                _ => Ok(Insn::new("jal", "{u}, {c:ram}", vec![operand(cpu, f.rd), target.clone()], stmts).flow(Flow::Jump(target))),
+            }
+        }
+        0x73 => system(cpu, insn, &f),
+        _ => unknown(insn),
+    }
+}
+
+fn op_imm(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let w = cpu.width() as u64;
+    let rs1 = reg(cpu, f.rs1);
+    let ops = vec![operand(cpu, f.rd), operand(cpu, f.rs1), imm(cpu, f.i_imm)];
+    let shamt = f.i_imm & (w - 1);
+    let shift_ops = vec![operand(cpu, f.rd), operand(cpu, f.rs1), imm(cpu, shamt)];
+
+    match f.f3 {
+        0 => {
+            let stmts = binop(cpu, Operation::Add, f.rd, rs1, imm(cpu, f.i_imm))?;
+
+            if insn == 0x0000_0013 {
+                Ok(Insn::new("nop", "", vec![], stmts))
+            } else if f.rs1 == ZERO {
+                Ok(Insn::new("li", "{u}, {s}", vec![operand(cpu, f.rd), imm(cpu, f.i_imm)], stmts))
+            } else if f.i_imm == 0 {
+                Ok(Insn::new("mv", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs1)], stmts))
+            } else {
+                Ok(Insn::new("addi", "{u}, {u}, {s}", ops, stmts))
+            }
+        }
+        1 if f.i_imm >> 6 == 0 || (w == 32 && f.i_imm >> 5 == 0) => Ok(Insn::new("slli", "{u}, {u}, {u}", shift_ops, binop(cpu, Operation::ShiftLeft, f.rd, rs1, imm(cpu, shamt))?)),
+        2 => Ok(Insn::new("slti", "{u}, {u}, {s}", ops, set_less(cpu, f.rd, rs1, imm(cpu, f.i_imm), true)?)),
+        3 => {
+            let stmts = set_less(cpu, f.rd, rs1, imm(cpu, f.i_imm), false)?;
+            if f.i_imm == 1 {
+                Ok(Insn::new("seqz", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs1)], stmts))
+            } else {
+                Ok(Insn::new("sltiu", "{u}, {u}, {u}", ops, stmts))
+            }
+        }
+        4 => {
+            let stmts = binop(cpu, Operation::ExclusiveOr, f.rd, rs1, imm(cpu, f.i_imm))?;
+            if f.i_imm == cpu.mask(!0) || f.i_imm == !0 {
+                Ok(Insn::new("not", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs1)], stmts))
+            } else {
+                Ok(Insn::new("xori", "{u}, {u}, {s}", ops, stmts))
+            }
+        }
+        5 if (f.i_imm >> 6) & 0x3f == 0 => Ok(Insn::new("srli", "{u}, {u}, {u}", shift_ops, binop(cpu, Operation::ShiftRightUnsigned, f.rd, rs1, imm(cpu, shamt))?)),
+        5 if (f.i_imm >> 6) & 0x3f == 0x10 => Ok(Insn::new("srai", "{u}, {u}, {u}", shift_ops, binop(cpu, Operation::ShiftRightSigned, f.rd, rs1, imm(cpu, shamt))?)),
+        6 => Ok(Insn::new("ori", "{u}, {u}, {s}", ops, binop(cpu, Operation::InclusiveOr, f.rd, rs1, imm(cpu, f.i_imm))?)),
+        7 => Ok(Insn::new("andi", "{u}, {u}, {s}", ops, binop(cpu, Operation::And, f.rd, rs1, imm(cpu, f.i_imm))?)),
+        _ => unknown(insn),
+    }
+}
+
+fn op_imm_32(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let rs1 = reg32(f.rs1);
+    let shamt = Rvalue::new_u32((f.i_imm & 0x1f) as u32);
+    let shift_ops = vec![operand(cpu, f.rd), operand(cpu, f.rs1), Rvalue::new_u32((f.i_imm & 0x1f) as u32)];
+
+    match (f.f3, f.f7) {
+        (0, _) => {
+            let stmts = word_binop(cpu, Operation::Add, f.rd, rs1, Rvalue::new_u32(f.i_imm as u32))?;
+            if f.i_imm == 0 {
+                Ok(Insn::new("sext.w", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs1)], stmts))
+            } else {
+                Ok(Insn::new("addiw", "{u}, {u}, {s}", vec![operand(cpu, f.rd), operand(cpu, f.rs1), imm(cpu, f.i_imm)], stmts))
+            }
+        }
+        (1, 0) => Ok(Insn::new("slliw", "{u}, {u}, {u}", shift_ops, word_binop(cpu, Operation::ShiftLeft, f.rd, rs1, shamt)?)),
+        (5, 0) => Ok(Insn::new("srliw", "{u}, {u}, {u}", shift_ops, word_binop(cpu, Operation::ShiftRightUnsigned, f.rd, rs1, shamt)?)),
+        (5, 0x20) => Ok(Insn::new("sraiw", "{u}, {u}, {u}", shift_ops, word_binop(cpu, Operation::ShiftRightSigned, f.rd, rs1, shamt)?)),
+        _ => unknown(insn),
+    }
+}
+
+/// Register-register operations. `word` selects the 32-bit variants of RV64.
+fn op(cpu: &Cpu, insn: u32, f: &Fields, word: bool) -> Result<Insn> {
+    let ops = vec![operand(cpu, f.rd), operand(cpu, f.rs1), operand(cpu, f.rs2)];
+    let w = if word { 32 } else { cpu.width() };
+    let (a, b) = if word { (reg32(f.rs1), reg32(f.rs2)) } else { (reg(cpu, f.rs1), reg(cpu, f.rs2)) };
+    let apply = |op: BinOp, a: Rvalue, b: Rvalue| if word { word_binop(cpu, op, f.rd, a, b) } else { binop(cpu, op, f.rd, a, b) };
+    let suffix = if word { "w" } else { "" };
+
+    // only the lower 5 or 6 bits of the shift amount are used
+    let shift = |op: BinOp| -> Result<Vec<Statement>> {
+        let mask = Rvalue::Constant { value: w as u64 - 1, size: w };
+        let mut stmts = rreil!{ and amount:(w), (b), (mask); }?;
+        stmts.extend(apply(op, a.clone(), rreil_rvalue!{ amount:(w) })?);
+        Ok(stmts)
+    };
+
+    let (name, stmts) = match (f.f7, f.f3) {
+        (0, 0) => ("add", apply(Operation::Add, a.clone(), b.clone())?),
+        (0x20, 0) => {
+            if f.rs1 == ZERO {
+                let name = format!("neg{}", suffix);
+                return Ok(Insn::new(&name, "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs2)], apply(Operation::Subtract, a.clone(), b.clone())?));
+            }
+            ("sub", apply(Operation::Subtract, a.clone(), b.clone())?)
+        }
+        (0, 1) => ("sll", shift(Operation::ShiftLeft)?),
+        (0, 2) if !word => ("slt", set_less(cpu, f.rd, a.clone(), b.clone(), true)?),
+        (0, 3) if !word => {
+            let stmts = set_less(cpu, f.rd, a.clone(), b.clone(), false)?;
+            if f.rs1 == ZERO {
+                return Ok(Insn::new("snez", "{u}, {u}", vec![operand(cpu, f.rd), operand(cpu, f.rs2)], stmts));
+            }
+            ("sltu", stmts)
+        }
+        (0, 4) if !word => ("xor", apply(Operation::ExclusiveOr, a.clone(), b.clone())?),
+        (0, 5) => ("srl", shift(Operation::ShiftRightUnsigned)?),
+        (0x20, 5) => ("sra", shift(Operation::ShiftRightSigned)?),
+        (0, 6) if !word => ("or", apply(Operation::InclusiveOr, a.clone(), b.clone())?),
+        (0, 7) if !word => ("and", apply(Operation::And, a.clone(), b.clone())?),
+        (1, _) if cpu.extensions.m => {
+            match f.f3 {
+                0 => ("mul", apply(Operation::Multiply, a.clone(), b.clone())?),
+                1 if !word => ("mulh", multiply_high(cpu, f.rd, a.clone(), b.clone(), true, true)?),
+                2 if !word => ("mulhsu", multiply_high(cpu, f.rd, a.clone(), b.clone(), true, false)?),
+                3 if !word => ("mulhu", multiply_high(cpu, f.rd, a.clone(), b.clone(), false, false)?),
+                4 => ("div", apply(Operation::DivideSigned, a.clone(), b.clone())?),
+                5 => ("divu", apply(Operation::DivideUnsigned, a.clone(), b.clone())?),
+                // RREIL's modulo is unsigned
+                6 => {
+                    let rd = reg_lv(cpu, f.rd);
+                    ("rem", rreil!{ mov (rd), ?; }?)
+                }
+                7 => ("remu", apply(Operation::Modulo, a.clone(), b.clone())?),
+                _ => return unknown(insn),
+            }
+        }
+        _ => return unknown(insn),
+    };
+
+    let name = format!("{}{}", name, suffix);
+    Ok(Insn::new(&name, "{u}, {u}, {u}", ops, stmts))
+}
+
+/// Computes the address `rs1 + offset` into `address`.
+fn address(cpu: &Cpu, rs1: u32, offset: u64) -> Result<(Vec<Statement>, Rvalue)> {
+    let w = cpu.width();
+    let base = reg(cpu, rs1);
+    let off = imm(cpu, offset);
+
+    Ok((rreil!{ add address:(w), (base), (off); }?, rreil_rvalue!{ address:(w) }))
+}
+
+fn load_store(cpu: &Cpu, insn: u32, f: &Fields, is_load: bool) -> Result<Insn> {
+    let rv64 = cpu.xlen == Xlen::Rv64;
+    let (name, size, signed) = match (is_load, f.f3) {
+        (true, 0) => ("lb", 8, true),
+        (true, 1) => ("lh", 16, true),
+        (true, 2) => ("lw", 32, true),
+        (true, 3) if rv64 => ("ld", 64, true),
+        (true, 4) => ("lbu", 8, false),
+        (true, 5) => ("lhu", 16, false),
+        (true, 6) if rv64 => ("lwu", 32, false),
+        (false, 0) => ("sb", 8, false),
+        (false, 1) => ("sh", 16, false),
+        (false, 2) => ("sw", 32, false),
+        (false, 3) if rv64 => ("sd", 64, false),
+        _ => return unknown(insn),
+    };
+
+    if is_load {
+        let (mut stmts, addr) = address(cpu, f.rs1, f.i_imm)?;
+        stmts.extend(load(reg_lv(cpu, f.rd), addr, size, signed)?);
+        Ok(Insn::new(name, "{u}, {s}({u})", vec![operand(cpu, f.rd), imm(cpu, f.i_imm), operand(cpu, f.rs1)], stmts))
+    } else {
+        let (mut stmts, addr) = address(cpu, f.rs1, f.s_imm)?;
+        stmts.extend(store(reg(cpu, f.rs2), addr, size)?);
+        Ok(Insn::new(name, "{u}, {s}({u})", vec![operand(cpu, f.rs2), imm(cpu, f.s_imm), operand(cpu, f.rs1)], stmts))
+    }
+}
+
+fn fp_load_store(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let is_load = f.op == 0x07;
+    let (name, size) = match (is_load, f.f3) {
+        (true, 2) => ("flw", 32),
+        (true, 3) if cpu.extensions.d => ("fld", 64),
+        (false, 2) => ("fsw", 32),
+        (false, 3) if cpu.extensions.d => ("fsd", 64),
+        _ => return unknown(insn),
+    };
+
+    if is_load {
+        let (mut stmts, addr) = address(cpu, f.rs1, f.i_imm)?;
+        stmts.extend(load(freg_lv(cpu, f.rd), addr, size, false)?);
+        Ok(Insn::new(name, "{u}, {s}({u})", vec![freg(cpu, f.rd), imm(cpu, f.i_imm), operand(cpu, f.rs1)], stmts))
+    } else {
+        let (mut stmts, addr) = address(cpu, f.rs1, f.s_imm)?;
+        stmts.extend(store(freg(cpu, f.rs2), addr, size)?);
+        Ok(Insn::new(name, "{u}, {s}({u})", vec![freg(cpu, f.rs2), imm(cpu, f.s_imm), operand(cpu, f.rs1)], stmts))
+    }
+}
+
+fn fp_op(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let fmt = f.f7 & 3;
+    let suffix = match fmt {
+        0 => "s",
+        1 if cpu.extensions.d => "d",
+        _ => return unknown(insn),
+    };
+    // rounding modes 5 and 6 are reserved
+    let rounds = |op: u32| match op {
+        0...3 | 8 | 11 | 24 | 26 => true,
+        _ => false,
+    };
+
+    if (f.op != 0x53 || rounds(f.f7 >> 2)) && (f.f3 == 5 || f.f3 == 6) {
+        return unknown(insn);
+    }
+
+    // fused multiply-add
+    if f.op != 0x53 {
+        let name = ["fmadd", "fmsub", "fnmsub", "fnmadd"][((f.op >> 2) & 3) as usize];
+        let name = format!("{}.{}", name, suffix);
+        let fd = freg_lv(cpu, f.rd);
+        let rs3 = insn >> 27;
+        return Ok(Insn::new(&name, "{u}, {u}, {u}, {u}", vec![freg(cpu, f.rd), freg(cpu, f.rs1), freg(cpu, f.rs2), freg(cpu, rs3)], rreil!{ mov (fd), ?; }?));
+    }
+
+    let ops3 = vec![freg(cpu, f.rd), freg(cpu, f.rs1), freg(cpu, f.rs2)];
+    let fd = freg_lv(cpu, f.rd);
+    let rd = reg_lv(cpu, f.rd);
+
+    match f.f7 >> 2 {
+        0...3 => {
+            let name = match f.f7 >> 2 {
+                0 => "fadd",
+                1 => "fsub",
+                2 => "fmul",
+                _ => "fdiv",
+            };
+            Ok(Insn::new(&format!("{}.{}", name, suffix), "{u}, {u}, {u}", ops3, rreil!{ mov (fd), ?; }?))
+        }
+        5 if f.f3 <= 1 => {
+            let name = if f.f3 == 0 { "fmin" } else { "fmax" };
+            Ok(Insn::new(&format!("{}.{}", name, suffix), "{u}, {u}, {u}", ops3, rreil!{ mov (fd), ?; }?))
+        }
+        4 if f.f3 <= 2 => {
+            let name = ["fsgnj", "fsgnjn", "fsgnjx"][f.f3 as usize];
+            if f.rs1 == f.rs2 && f.f3 == 0 {
+                let src = freg(cpu, f.rs1);
+                return Ok(Insn::new(&format!("fmv.{}", suffix), "{u}, {u}", vec![freg(cpu, f.rd), src.clone()], rreil!{ mov (fd), (src); }?));
+            }
+            Ok(Insn::new(&format!("{}.{}", name, suffix), "{u}, {u}, {u}", ops3, rreil!{ mov (fd), ?; }?))
+        }
+        8 => Ok(Insn::new(if fmt == 0 { "fcvt.s.d" } else { "fcvt.d.s" }, "{u}, {u}", vec![freg(cpu, f.rd), freg(cpu, f.rs1)], rreil!{ mov (fd), ?; }?)),
+        11 => Ok(Insn::new(&format!("fsqrt.{}", suffix), "{u}, {u}", vec![freg(cpu, f.rd), freg(cpu, f.rs1)], rreil!{ mov (fd), ?; }?)),
+        20 if f.f3 <= 2 => {
+            let name = ["fle", "flt", "feq"][f.f3 as usize];
+            Ok(Insn::new(&format!("{}.{}", name, suffix), "{u}, {u}, {u}", vec![operand(cpu, f.rd), freg(cpu, f.rs1), freg(cpu, f.rs2)], rreil!{ mov (rd), ?; }?))
+        }
+        24 => Ok(Insn::new(&format!("fcvt.{}.{}", ["w", "wu", "l", "lu"][(f.rs2 & 3) as usize], suffix), "{u}, {u}", vec![operand(cpu, f.rd), freg(cpu, f.rs1)], rreil!{ mov (rd), ?; }?)),
+        26 => Ok(Insn::new(&format!("fcvt.{}.{}", suffix, ["w", "wu", "l", "lu"][(f.rs2 & 3) as usize]), "{u}, {u}", vec![freg(cpu, f.rd), operand(cpu, f.rs1)], rreil!{ mov (fd), ?; }?)),
+        28 if f.f3 == 0 => {
+            // fmv.x.w, fmv.x.d: move bits to an integer register
+            let src = freg(cpu, f.rs1);
+            let stmts = if fmt == 0 {
+                let mut stmts = rreil!{ mov bits:32, (src); }?;
+                stmts.extend(write_word(cpu, f.rd, rreil_rvalue!{ bits:32 })?);
+                stmts
+            } else if cpu.width() == 64 {
+                rreil!{ mov (rd), (src); }?
+            } else {
+                return unknown(insn);
+            };
+            Ok(Insn::new(if fmt == 0 { "fmv.x.w" } else { "fmv.x.d" }, "{u}, {u}", vec![operand(cpu, f.rd), src], stmts))
+        }
+        28 if f.f3 == 1 => Ok(Insn::new(&format!("fclass.{}", suffix), "{u}, {u}", vec![operand(cpu, f.rd), freg(cpu, f.rs1)], rreil!{ mov (rd), ?; }?)),
+        30 if f.f3 == 0 => {
+            // fmv.w.x, fmv.d.x: move bits from an integer register
+            let src = reg(cpu, f.rs1);
+            let flen = cpu.flen();
+            let stmts = if fmt == 0 {
+                let lo = reg32(f.rs1);
+                if flen == 32 {
+                    rreil!{ mov (fd), (lo); }?
+                } else {
+                    rreil!{ zext/(flen) (fd), (lo); }?
+                }
+            } else if cpu.width() == 64 {
+                rreil!{ mov (fd), (src); }?
+            } else {
+                return unknown(insn);
+            };
+            Ok(Insn::new(if fmt == 0 { "fmv.w.x" } else { "fmv.d.x" }, "{u}, {u}", vec![freg(cpu, f.rd), operand(cpu, f.rs1)], stmts))
+        }
+        _ => unknown(insn),
+    }
+}
+
+fn atomic(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    let size = match f.f3 {
+        2 => 32,
+        3 if cpu.xlen == Xlen::Rv64 => 64,
+        _ => return unknown(insn),
+    };
+    let suffix = if size == 32 { "w" } else { "d" };
+    let addr = reg(cpu, f.rs1);
+    let rd = reg_lv(cpu, f.rd);
+
+    match f.f7 >> 2 {
+        0x02 => {
+            let name = format!("lr.{}", suffix);
+            Ok(Insn::new(&name, "{u}, ({u})", vec![operand(cpu, f.rd), operand(cpu, f.rs1)], load(rd, addr, size, true)?))
+        }
+        0x03 => {
+            // the reservation may have been lost
+            let name = format!("sc.{}", suffix);
+            let mut stmts = store(reg(cpu, f.rs2), addr, size)?;
+            stmts.extend(rreil!{ mov (rd), ?; }?);
+            Ok(Insn::new(&name, "{u}, {u}, ({u})", vec![operand(cpu, f.rd), operand(cpu, f.rs2), operand(cpu, f.rs1)], stmts))
+        }
+        func => {
+            let (name, op): (&str, Option<BinOp>) = match func {
+                0x00 => ("amoadd", Some(Operation::Add)),
+                0x01 => ("amoswap", None),
+                0x04 => ("amoxor", Some(Operation::ExclusiveOr)),
+                0x08 => ("amoor", Some(Operation::InclusiveOr)),
+                0x0c => ("amoand", Some(Operation::And)),
+                0x10 => ("amomin", None),
+                0x14 => ("amomax", None),
+                0x18 => ("amominu", None),
+                0x1c => ("amomaxu", None),
+                _ => return unknown(insn),
+            };
+            let old = Lvalue::Variable { name: "old".into(), subscript: None, size: size };
+            let new = Lvalue::Variable { name: "new".into(), subscript: None, size: size };
+            let src = if size == 32 { reg32(f.rs2) } else { reg(cpu, f.rs2) };
+            let mut stmts = load(old.clone(), addr.clone(), size, false)?;
+
+            match (op, func) {
+                (Some(op), _) => stmts.push(Statement { op: op(old.clone().into(), src), assignee: new.clone() }),
+                (None, 0x01) => stmts.extend(rreil!{ mov (new), (src); }?),
+                (None, _) => stmts.extend(rreil!{ mov (new), ?; }?),
+            }
+            stmts.extend(store(new.into(), addr, size)?);
+            if size == 32 {
+                stmts.extend(write_word(cpu, f.rd, old.into())?);
+            } else {
+                stmts.extend(rreil!{ mov (rd), (old); }?);
+            }
+
+            let name = format!("{}.{}", name, suffix);
+            Ok(Insn::new(&name, "{u}, {u}, ({u})", vec![operand(cpu, f.rd), operand(cpu, f.rs2), operand(cpu, f.rs1)], stmts))
+        }
+    }
+}
+
+fn branch(cpu: &Cpu, insn: u32, f: &Fields, addr: u64) -> Result<Insn> {
+    let target = imm(cpu, addr.wrapping_add(f.b_imm));
+    let (a, b) = (reg(cpu, f.rs1), reg(cpu, f.rs2));
+    let (name, stmts) = match f.f3 {
+        0 => ("beq", rreil!{ cmpeq cond:1, (a), (b); }?),
+        1 => {
+            ("bne",
+             rreil!{
+                cmpeq cond:1, (a), (b);
+                xor cond:1, cond:1, [1]:1;
+            }?)
+        }
+        4 => ("blt", rreil!{ cmplts cond:1, (a), (b); }?),
+        5 => {
+            ("bge",
+             rreil!{
+                cmplts cond:1, (a), (b);
+                xor cond:1, cond:1, [1]:1;
+            }?)
+        }
+        6 => ("bltu", rreil!{ cmpltu cond:1, (a), (b); }?),
+        7 => {
+            ("bgeu",
+             rreil!{
+                cmpltu cond:1, (a), (b);
+                xor cond:1, cond:1, [1]:1;
+            }?)
+        }
+        _ => return unknown(insn),
+    };
+    let flow = Flow::Branch(target.clone(), rreil_rvalue!{ cond:1 });
+
+    if f.rs2 == ZERO && (f.f3 == 0 || f.f3 == 1) {
+        let name = format!("{}z", name);
+        Ok(Insn::new(&name, "{u}, {c:ram}", vec![operand(cpu, f.rs1), target], stmts).flow(flow))
+    } else {
+        Ok(Insn::new(name, "{u}, {u}, {c:ram}", vec![operand(cpu, f.rs1), operand(cpu, f.rs2), target], stmts).flow(flow))
+    }
+}
+
+/// Returns the value of `r` if the instruction before `addr` is an `auipc` writing it.
+fn auipc_before(cpu: &Cpu, region: &Region, addr: u64, r: u32) -> Option<u64> {
+    if addr < 4 || r == ZERO {
+        return None;
+    }
+
+    let lo = cpu.read_parcel(region, addr - 4)? as u32;
+    let hi = cpu.read_parcel(region, addr - 2)? as u32;
+    let prev = Fields::new(lo | (hi << 16));
+
+    if prev.op == 0x17 && prev.rd == r {
+        Some(cpu.mask((addr - 4).wrapping_add(prev.u_imm)))
+    } else {
+        None
+    }
+}
+
+fn jalr(cpu: &Cpu, region: &Region, f: &Fields, addr: u64, len: u64) -> Result<Insn> {
+    let w = cpu.width();
+    let ops = vec![operand(cpu, f.rd), imm(cpu, f.i_imm), operand(cpu, f.rs1)];
+
+    if f.rd == ZERO && f.rs1 == RA && f.i_imm == 0 {
+        return Ok(Insn::new("ret", "", vec![], vec![]).flow(Flow::Return));
+    }
+
+    // the target is read before the link register is written
+    let (mut stmts, target) = match auipc_before(cpu, region, addr, f.rs1) {
+        Some(base) => (vec![], imm(cpu, base.wrapping_add(f.i_imm))),
+        None => {
+            let base = reg(cpu, f.rs1);
+            let off = imm(cpu, f.i_imm);
+            let mask = imm(cpu, !1);
+            (rreil!{
+                add target:(w), (base), (off);
+                and target:(w), target:(w), (mask);
+            }?,
+             rreil_rvalue!{ target:(w) })
+        }
+    };
+
+    if f.rd != ZERO {
+        let rd = reg_lv(cpu, f.rd);
+        let ret = imm(cpu, addr + len);
+        stmts.extend(rreil!{ mov (rd), (ret); }?);
+    }
+
+    match f.rd {
+        ZERO if f.i_imm == 0 => Ok(Insn::new("jr", "{u}", vec![operand(cpu, f.rs1)], stmts).flow(Flow::Jump(target))),
+        ZERO => Ok(Insn::new("jr", "{s}({u})", vec![imm(cpu, f.i_imm), operand(cpu, f.rs1)], stmts).flow(Flow::Jump(target))),
+        RA | T0 => {
+            stmts.extend(rreil!{ call (target); }?);
+            Ok(Insn::new("jalr", "{u}, {s}({u})", ops, stmts).flow(Flow::Call(target)))
+        }
+        _ => Ok(Insn::new("jalr", "{u}, {s}({u})", ops, stmts).flow(Flow::Jump(target))),
+    }
+}
+
+fn system(cpu: &Cpu, insn: u32, f: &Fields) -> Result<Insn> {
+    if f.f3 == 0 {
+        let name = match insn {
+            0x0000_0073 => "ecall",
+            0x0010_0073 => "ebreak",
+            0x0020_0073 => "uret",
+            0x1020_0073 => "sret",
+            0x3020_0073 => "mret",
+            0x1050_0073 => "wfi",
+            _ if f.f7 == 0x09 => "sfence.vma",
+            _ => return unknown(insn),
+        };
+        return match name {
+            "uret" | "sret" | "mret" => Ok(Insn::new(name, "", vec![], vec![]).flow(Flow::Return)),
+            _ => Ok(Insn::new(name, "", vec![], vec![])),
+        };
+    }
+
+    // control and status registers are not modeled
+    let name = match f.f3 {
+        1 => "csrrw",
+        2 => "csrrs",
+        3 => "csrrc",
+        5 => "csrrwi",
+        6 => "csrrsi",
+        7 => "csrrci",
+        _ => return unknown(insn),
+    };
+    let csr = Rvalue::new_u16((insn >> 20) as u16);
+    let src = if f.f3 >= 5 { imm(cpu, f.rs1 as u64) } else { operand(cpu, f.rs1) };
+    let rd = reg_lv(cpu, f.rd);
+
+    Ok(Insn::new(name, "{u}, {u}, {u}", vec![operand(cpu, f.rd), csr, src], rreil!{ mov (rd), ?; }?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Extensions;
+
+    fn decode_word(cpu: &Cpu, insn: u32) -> Insn {
+        let reg = Region::wrap("ram".to_string(), vec![insn as u8, (insn >> 8) as u8, (insn >> 16) as u8, (insn >> 24) as u8]);
+        let (ret, _) = read(cpu, &reg, 0).unwrap();
+
+        for s in ret.statements.iter() {
+            assert!(s.sanity_check().is_ok(), "{} {:?}", ret.opcode, s);
+        }
+        ret
+    }
+
+    #[test]
+    fn integer() {
+        let cpu = Cpu::new(Xlen::Rv64, Extensions::gc());
+
+        assert_eq!(decode_word(&cpu, 0x00000013).opcode, "nop");
+        assert_eq!(decode_word(&cpu, 0x02a00513).opcode, "li");
+        assert_eq!(decode_word(&cpu, 0x00b50533).opcode, "add");
+        assert_eq!(decode_word(&cpu, 0x02b50533).opcode, "mul");
+        assert_eq!(decode_word(&cpu, 0x00b5053b).opcode, "addw");
+        assert_eq!(decode_word(&cpu, 0x00853583).opcode, "ld");
+        assert_eq!(decode_word(&cpu, 0x00b53423).opcode, "sd");
+        assert_eq!(decode_word(&cpu, 0x00008067).flow, Flow::Return);
+        assert_eq!(decode_word(&cpu, 0x00000073).opcode, "ecall");
+    }
+
+    #[test]
+    fn extensions() {
+        let cpu = Cpu::new(Xlen::Rv64, Extensions::gc());
+
+        assert_eq!(decode_word(&cpu, 0x00b5352f).opcode, "amoadd.d");
+        assert_eq!(decode_word(&cpu, 0x1005252f).opcode, "lr.w");
+        assert_eq!(decode_word(&cpu, 0x00853507).opcode, "fld");
+        assert_eq!(decode_word(&cpu, 0x02b57553).opcode, "fadd.d");
+        assert_eq!(decode_word(&cpu, 0xe2050553).opcode, "fmv.x.d");
+
+        let base = Cpu::new(Xlen::Rv32, Extensions::none());
+        let reg = Region::wrap("ram".to_string(), vec![0x33, 0x05, 0xb5, 0x02]);
+        assert!(read(&base, &reg, 0).is_err());
+    }
+
+    #[test]
+    fn reserved_encodings() {
+        let cpu = Cpu::new(Xlen::Rv64, Extensions::gc());
+        let illegal = |insn: u32| {
+            let reg = Region::wrap("ram".to_string(), vec![insn as u8, (insn >> 8) as u8, (insn >> 16) as u8, (insn >> 24) as u8]);
+            read(&cpu, &reg, 0).is_err()
+        };
+
+        assert_eq!(decode_word(&cpu, 0x0ff0000f).opcode, "fence");
+        assert_eq!(decode_word(&cpu, 0x0000100f).opcode, "fence.i");
+        assert!(illegal(0xf36d348f));
+
+        // fadd.d with rm 5 and 6, fmadd.s with rm 6
+        assert!(illegal(0x02b55553));
+        assert!(illegal(0x02b56553));
+        assert!(illegal(0x00b56543));
+        assert_eq!(decode_word(&cpu, 0x02b50553).opcode, "fadd.d");
+
+        // fsgnj, fmin and feq take a function code instead of a rounding mode
+        assert_eq!(decode_word(&cpu, 0x22b52553).opcode, "fsgnjx.d");
+        assert!(illegal(0x22b53553));
+        assert!(illegal(0x2ab52553));
+        assert!(illegal(0xa2b53553));
+    }
+
+    #[test]
+    fn rv32() {
+        let cpu = Cpu::new(Xlen::Rv32, Extensions::gc());
+        let reg = Region::wrap("ram".to_string(), vec![0x83, 0x35, 0x85, 0x00]);
+
+        // ld is RV64 only
+        assert!(read(&cpu, &reg, 0).is_err());
+        assert_eq!(decode_word(&cpu, 0x01f55513).opcode, "srli");
+    }
+}
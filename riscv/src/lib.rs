@@ -0,0 +1,46 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! RISC-V disassembler.
+//!
+//! This disassembler handles the RV32 and RV64 base integer instruction sets together with the
+//! M (multiply/divide), A (atomics), F and D (floating point) and C (compressed) extensions. The
+//! register width and the set of enabled extensions are selected by the `Cpu` configuration.
+//!
+//! Compressed instructions are expanded into their 32-bit equivalents before they are lifted, so
+//! both share the same semantics. RREIL has no floating point operations. Floating point loads,
+//! stores and moves between register files are lifted exactly, all other floating point
+//! instructions set their destination to undefined.
+//!
+//! Far calls are encoded as `auipc`/`jalr` pairs. The decoder resolves the target of a `jalr` if
+//! the instruction right before it is an `auipc` writing the base register.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+extern crate byteorder;
+
+pub mod semantic;
+mod decode;
+mod compressed;
+
+mod architecture;
+pub use architecture::{Cpu, Extensions, Riscv, Xlen};
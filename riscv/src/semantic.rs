@@ -0,0 +1,254 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! RREIL code generation helpers for RISC-V.
+
+use architecture::Cpu;
+use panopticon_core::{Endianess, Lvalue, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+pub static REGISTERS: [&'static str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+pub static FP_REGISTERS: [&'static str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+pub const ZERO: u32 = 0;
+pub const RA: u32 = 1;
+pub const SP: u32 = 2;
+pub const T0: u32 = 5;
+
+pub type BinOp = fn(Rvalue, Rvalue) -> Operation<Rvalue>;
+
+/// Integer register `r` as mnemonic operand.
+pub fn operand(cpu: &Cpu, r: u32) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(REGISTERS[r as usize & 0x1f]),
+        subscript: None,
+        offset: 0,
+        size: cpu.width(),
+    }
+}
+
+/// Reads integer register `r`. `zero` is hardwired to zero.
+pub fn reg(cpu: &Cpu, r: u32) -> Rvalue {
+    if r == ZERO {
+        Rvalue::Constant { value: 0, size: cpu.width() }
+    } else {
+        operand(cpu, r)
+    }
+}
+
+/// Reads the lower 32 bits of integer register `r`.
+pub fn reg32(r: u32) -> Rvalue {
+    if r == ZERO {
+        Rvalue::new_u32(0)
+    } else {
+        Rvalue::Variable {
+            name: Cow::Borrowed(REGISTERS[r as usize & 0x1f]),
+            subscript: None,
+            offset: 0,
+            size: 32,
+        }
+    }
+}
+
+/// Integer register `r` as assignee. Writes to `zero` are discarded.
+pub fn reg_lv(cpu: &Cpu, r: u32) -> Lvalue {
+    if r == ZERO {
+        Lvalue::Undefined
+    } else {
+        Lvalue::Variable { name: Cow::Borrowed(REGISTERS[r as usize & 0x1f]), subscript: None, size: cpu.width() }
+    }
+}
+
+/// Floating point register `r`.
+pub fn freg(cpu: &Cpu, r: u32) -> Rvalue {
+    Rvalue::Variable {
+        name: Cow::Borrowed(FP_REGISTERS[r as usize & 0x1f]),
+        subscript: None,
+        offset: 0,
+        size: cpu.flen(),
+    }
+}
+
+/// Floating point register `r` as assignee.
+pub fn freg_lv(cpu: &Cpu, r: u32) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(FP_REGISTERS[r as usize & 0x1f]), subscript: None, size: cpu.flen() }
+}
+
+/// Constant of register width.
+pub fn imm(cpu: &Cpu, v: u64) -> Rvalue {
+    Rvalue::Constant { value: cpu.mask(v), size: cpu.width() }
+}
+
+/// Sign extends the `bits` wide value `v` to 64 bits.
+pub fn sign_extend(v: u64, bits: usize) -> u64 {
+    let shift = 64 - bits;
+    (((v << shift) as i64) >> shift) as u64
+}
+
+/// Writes the 32-bit value `v` into `rd`, sign extending it on RV64.
+pub fn write_word(cpu: &Cpu, rd: u32, v: Rvalue) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+
+    if cpu.width() == 32 {
+        rreil!{ mov (rd), (v); }
+    } else {
+        rreil!{ sext/64 (rd), (v); }
+    }
+}
+
+/// `rd := a op b` on register sized operands.
+pub fn binop(cpu: &Cpu, op: BinOp, rd: u32, a: Rvalue, b: Rvalue) -> Result<Vec<Statement>> {
+    Ok(vec![Statement { op: op(a, b), assignee: reg_lv(cpu, rd) }])
+}
+
+/// `rd := sext(a op b)` on 32-bit operands. Used for the `*w` instructions of RV64.
+pub fn word_binop(cpu: &Cpu, op: BinOp, rd: u32, a: Rvalue, b: Rvalue) -> Result<Vec<Statement>> {
+    let res = rreil_lvalue!{ res:32 };
+    let mut stmts = vec![Statement { op: op(a, b), assignee: res.clone() }];
+
+    stmts.extend(write_word(cpu, rd, res.into())?);
+    Ok(stmts)
+}
+
+/// `rd := a < b`, signed or unsigned.
+pub fn set_less(cpu: &Cpu, rd: u32, a: Rvalue, b: Rvalue, signed: bool) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+    let mut stmts = if signed {
+        rreil!{ cmplts lt:1, (a), (b); }?
+    } else {
+        rreil!{ cmpltu lt:1, (a), (b); }?
+    };
+
+    stmts.extend(rreil!{ zext/(cpu.width()) (rd), lt:1; }?);
+    Ok(stmts)
+}
+
+/// Computes the upper half of the product of `a` and `b`. Each operand is treated as signed if
+/// the respective flag is set.
+pub fn multiply_high(cpu: &Cpu, rd: u32, a: Rvalue, b: Rvalue, a_signed: bool, b_signed: bool) -> Result<Vec<Statement>> {
+    let rd = reg_lv(cpu, rd);
+
+    if cpu.width() == 64 {
+        // RREIL has no 128-bit operations
+        return rreil!{ mov (rd), ?; };
+    }
+
+    let mut stmts = if a_signed {
+        rreil!{ sext/64 a64:64, (a); }?
+    } else {
+        rreil!{ zext/64 a64:64, (a); }?
+    };
+
+    if b_signed {
+        stmts.extend(rreil!{ sext/64 b64:64, (b); }?);
+    } else {
+        stmts.extend(rreil!{ zext/64 b64:64, (b); }?);
+    }
+
+    stmts.extend(
+        rreil!{
+            mul prod:64, a64:64, b64:64;
+            mov (rd), prod:32/32;
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Loads `size` bits from `addr` into `lv`, zero or sign extending the value.
+pub fn load(lv: Lvalue, addr: Rvalue, size: usize, signed: bool) -> Result<Vec<Statement>> {
+    let w = lv.size().unwrap_or(size);
+
+    if w == size || lv == Lvalue::Undefined {
+        let val = if lv == Lvalue::Undefined { Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size } } else { lv };
+        return Ok(vec![Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Little, size, addr), assignee: val }]);
+    }
+
+    let val = Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size };
+    let mut stmts = vec![Statement { op: Operation::Load(Cow::Borrowed("ram"), Endianess::Little, size, addr), assignee: val.clone() }];
+
+    if signed {
+        stmts.extend(rreil!{ sext/(w) (lv), (val); }?);
+    } else {
+        stmts.extend(rreil!{ zext/(w) (lv), (val); }?);
+    }
+    Ok(stmts)
+}
+
+/// Stores the lower `size` bits of `v` at `addr`.
+pub fn store(v: Rvalue, addr: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+    let val = if v.size() == Some(size) || v.size().is_none() {
+        v
+    } else {
+        let val = Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size };
+        stmts.extend(rreil!{ mov (val), (v); }?);
+        val.into()
+    };
+
+    stmts.push(Statement { op: Operation::Store(Cow::Borrowed("ram"), Endianess::Little, size, addr, val), assignee: Lvalue::Undefined });
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::{Extensions, Xlen};
+
+    fn sane(stmts: Vec<Statement>) {
+        for s in stmts.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn statements_are_sane() {
+        for &xlen in [Xlen::Rv32, Xlen::Rv64].iter() {
+            let cpu = Cpu::new(xlen, Extensions::gc());
+
+            sane(binop(&cpu, Operation::Add, 10, reg(&cpu, 11), imm(&cpu, 4)).unwrap());
+            sane(word_binop(&cpu, Operation::ShiftLeft, 10, reg32(11), Rvalue::new_u32(3)).unwrap());
+            sane(set_less(&cpu, 10, reg(&cpu, 11), reg(&cpu, ZERO), true).unwrap());
+            sane(multiply_high(&cpu, 10, reg(&cpu, 11), reg(&cpu, 12), true, false).unwrap());
+            for &sz in [8, 16, 32, 64].iter().filter(|&&sz| sz <= cpu.width()) {
+                sane(load(reg_lv(&cpu, 10), reg(&cpu, SP), sz, true).unwrap());
+                sane(store(reg(&cpu, 10), reg(&cpu, SP), sz).unwrap());
+            }
+            sane(load(freg_lv(&cpu, 10), reg(&cpu, SP), 32, false).unwrap());
+            sane(store(freg(&cpu, 10), reg(&cpu, SP), 32).unwrap());
+        }
+    }
+
+    #[test]
+    fn zero_register() {
+        let cpu = Cpu::new(Xlen::Rv64, Extensions::none());
+
+        assert_eq!(reg(&cpu, ZERO), Rvalue::new_u64(0));
+        assert_eq!(reg_lv(&cpu, ZERO), Lvalue::Undefined);
+    }
+}
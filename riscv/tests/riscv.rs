@@ -0,0 +1,73 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_riscv;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Architecture, Function, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_riscv::{Cpu, Extensions, Riscv, Xlen};
+
+// RV64GC function mixing compressed and full size instructions
+fn function() -> Region {
+    Region::wrap(
+        "ram".to_string(),
+        vec![
+            0x41, 0x11, // c.addi sp, -16
+            0x06, 0xe4, // c.sdsp ra, 8(sp)
+            0x63, 0x06, 0x05, 0x00, // beqz a0, 0x10
+            0x97, 0x00, 0x00, 0x00, // auipc ra, 0
+            0xe7, 0x80, 0x00, 0x02, // jalr ra, 32(ra)
+            0xa2, 0x60, // c.ldsp ra, 8(sp)
+            0x41, 0x01, // c.addi sp, 16
+            0x82, 0x80, // c.jr ra
+        ],
+    )
+}
+
+#[test]
+fn compressed() {
+    let reg = function();
+    let func = Function::new::<Riscv>(0, &reg, None, Cpu::new(Xlen::Rv64, Extensions::gc())).unwrap();
+    let mut starts = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    starts.sort();
+
+    assert_eq!(starts, vec![0x0, 0x8, 0x10]);
+    assert_eq!(func.cfg().num_edges(), 3);
+    assert_eq!(func.end(), 0x16);
+}
+
+#[test]
+fn auipc_jalr_call() {
+    let reg = function();
+    let func = Function::new::<Riscv>(0, &reg, None, Cpu::new(Xlen::Rv64, Extensions::gc())).unwrap();
+
+    assert_eq!(func.collect_call_addresses(), vec![0x28]);
+}
+
+#[test]
+fn without_compressed() {
+    let reg = function();
+    let mut ext = Extensions::gc();
+
+    ext.c = false;
+    assert!(Riscv::decode(&reg, 0, &Cpu::new(Xlen::Rv64, ext)).is_err());
+    assert!(Riscv::decode(&reg, 4, &Cpu::new(Xlen::Rv64, ext)).is_ok());
+}
@@ -0,0 +1,474 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Automation scripts running inside the analysis process.
+//!
+//! Scripts are written in [Rhai](https://rhai.rs), a small language with Rust-like syntax. They
+//! have no access to files, the network or other processes and only see the project they run
+//! on, exposed as the constant `project`. Everything a script prints is returned to the caller
+//! in [`Output`], together with the value of the last expression.
+//!
+//! ```text
+//! for f in project.functions() {
+//!     if f.name.starts_with("sub_") && f.callees().is_empty() {
+//!         f.rename("leaf_" + f.start.to_hex());
+//!     }
+//! }
+//! project.run_pass("xrefs");
+//! ```
+//!
+//! Addresses are integers. `project` has these methods:
+//!
+//! | Method | Returns |
+//! |--------|---------|
+//! | `name` | name of the project |
+//! | `functions()` | all functions, ordered by address |
+//! | `function(address)`, `function(name)` | a single function, or `()` |
+//! | `strings()` | string literals as `#{address, value}` |
+//! | `imports()` | imported symbols as `#{address, name}` |
+//! | `xrefs_to(address)` | references as `#{function, address, target, kind}` |
+//! | `read(address, length)` | bytes at `address`, ends early at undefined bytes |
+//! | `comment(address)`, `set_comment(address, text)` | user comment at `address` |
+//! | `rename(address, name)`, `rename(address, name, suffix)` | new name, see `rename::rename_global` |
+//! | `run_pass(name)` | `()`, runs one of the passes of `driver::Pass` by lower case name |
+//!
+//! Functions have the properties `name`, `start`, `uuid`, `aliases` and `size`, and the methods
+//! `basic_blocks()`, `mnemonics()`, `callees()` and `rename(name)`. Basic blocks are
+//! `#{start, end, mnemonics}`, mnemonics `#{address, size, opcode, text, il}`.
+//!
+//! [`Output`]: struct.Output.html
+
+#![warn(missing_docs)]
+
+extern crate rhai;
+extern crate uuid;
+extern crate panopticon_core;
+extern crate panopticon_analysis;
+extern crate panopticon_graph_algos;
+
+use panopticon_analysis::{Options, Pass, driver};
+use panopticon_core::{BasicBlock, CancellationToken, Collision, Function, Location, Program, Project, Region, Result, XrefKind, listing, rename};
+use rhai::{AST, Array, Blob, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::cell::RefCell;
+use std::fs::File;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+use uuid::Uuid;
+
+type ScriptResult<T> = ::std::result::Result<T, Box<EvalAltResult>>;
+
+/// Compiled script.
+#[derive(Clone,Debug)]
+pub struct Script {
+    ast: AST,
+    max_operations: u64,
+    cancel: CancellationToken,
+}
+
+/// What a script printed and returned.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct Output {
+    /// Lines printed with `print` and `debug`
+    pub printed: Vec<String>,
+    /// Value of the last expression, None if it has none
+    pub value: Option<String>,
+}
+
+#[derive(Clone)]
+struct ProjectHandle(Rc<RefCell<Project>>);
+
+#[derive(Clone)]
+struct FunctionHandle {
+    project: Rc<RefCell<Project>>,
+    uuid: Uuid,
+}
+
+fn integer(a: u64) -> Dynamic {
+    Dynamic::from(a as i64)
+}
+
+fn map(entries: Vec<(&str, Dynamic)>) -> Dynamic {
+    let mut ret = Map::new();
+
+    for (k, v) in entries {
+        ret.insert(k.into(), v);
+    }
+    Dynamic::from(ret)
+}
+
+fn mnemonics(proj: &Project, prog: &Program, func: &Function, bb: &BasicBlock) -> Array {
+    func.mnemonics(bb)
+        .map(
+            |(mne, stmts)| {
+                map(
+                    vec![
+                        ("address", integer(mne.area.start)),
+                        ("size", integer(mne.area.end - mne.area.start)),
+                        ("opcode", Dynamic::from(mne.opcode.clone())),
+                        ("text", Dynamic::from(listing::instruction(proj, prog, mne))),
+                        ("il", Dynamic::from(stmts.iter().map(|s| Dynamic::from(format!("{}", s))).collect::<Array>())),
+                    ]
+                )
+            }
+        )
+        .collect()
+}
+
+fn basic_blocks(func: &Function) -> Vec<&BasicBlock> {
+    let mut ret = func.basic_blocks().collect::<Vec<_>>();
+
+    ret.sort_by_key(|bb| bb.area.start);
+    ret
+}
+
+impl ProjectHandle {
+    fn functions(&mut self) -> Array {
+        let proj = self.0.borrow();
+        let mut funcs = proj.code.iter().flat_map(|p| p.functions()).map(|f| (f.start(), *f.uuid())).collect::<Vec<_>>();
+
+        funcs.sort();
+        funcs.into_iter().map(|(_, uu)| Dynamic::from(FunctionHandle { project: self.0.clone(), uuid: uu })).collect()
+    }
+
+    fn find<F: Fn(&Function) -> bool>(&self, filter: F) -> Dynamic {
+        let proj = self.0.borrow();
+        let func = proj.code.iter().filter_map(|p| p.find_function_by(&filter)).next();
+
+        match func {
+            Some(f) => Dynamic::from(FunctionHandle { project: self.0.clone(), uuid: *f.uuid() }),
+            None => Dynamic::UNIT,
+        }
+    }
+
+    fn rename(&mut self, address: i64, name: &str, suffix: bool) -> ScriptResult<String> {
+        let policy = if suffix { Collision::Suffix } else { Collision::Fail };
+
+        rename::rename_global(&mut self.0.borrow_mut(), address as u64, name, policy).map_err(|e| e.to_string().into())
+    }
+
+    fn run_pass(&mut self, name: &str) -> ScriptResult<()> {
+        let pass = match name {
+            "strings" => Pass::Strings,
+            "xrefs" => Pass::Xrefs,
+            "hardening" => Pass::Hardening,
+            "signatures" => Pass::Signatures,
+            "annotations" => Pass::Annotations,
+            "peripherals" => Pass::Peripherals,
+            _ => return Err(format!("unknown pass {}", name).into()),
+        };
+
+        driver::run_pass(&mut self.0.borrow_mut(), pass, &Options::new());
+        Ok(())
+    }
+}
+
+impl FunctionHandle {
+    fn with<T, F: FnOnce(&Project, &Program, &Function) -> T>(&self, f: F) -> ScriptResult<T> {
+        let proj = self.project.borrow();
+
+        for prog in proj.code.iter() {
+            if let Some(func) = prog.find_function_by_uuid(&self.uuid) {
+                return Ok(f(&proj, prog, func));
+            }
+        }
+
+        Err(format!("function {} no longer exists", self.uuid).into())
+    }
+}
+
+fn register(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ProjectHandle>("Project")
+        .register_get("name", |p: &mut ProjectHandle| p.0.borrow().name.clone())
+        .register_fn("functions", ProjectHandle::functions)
+        .register_fn("function", |p: &mut ProjectHandle, address: i64| p.find(|f| f.start() == address as u64))
+        .register_fn("function", |p: &mut ProjectHandle, name: &str| p.find(|f| f.name == name || f.aliases().iter().any(|a| a == name)))
+        .register_fn(
+            "strings",
+            |p: &mut ProjectHandle| -> Array {
+                p.0
+                    .borrow()
+                    .strings
+                    .iter()
+                    .map(|(&a, s)| map(vec![("address", integer(a)), ("value", Dynamic::from(s.value.clone()))]))
+                    .collect()
+            }
+        )
+        .register_fn(
+            "imports",
+            |p: &mut ProjectHandle| -> Array {
+                let proj = p.0.borrow();
+                let mut imports = proj.imports.iter().collect::<Vec<_>>();
+
+                imports.sort();
+                imports.into_iter().map(|(&a, n)| map(vec![("address", integer(a)), ("name", Dynamic::from(n.clone()))])).collect()
+            }
+        )
+        .register_fn(
+            "xrefs_to",
+            |p: &mut ProjectHandle, address: i64| -> Array {
+                p.0
+                    .borrow()
+                    .xrefs_to(address as u64)
+                    .iter()
+                    .map(
+                        |x| {
+                            let kind = match x.kind {
+                                XrefKind::Read => "read",
+                                XrefKind::Write => "write",
+                                XrefKind::Call => "call",
+                                XrefKind::Jump => "jump",
+                                XrefKind::Address => "address",
+                            };
+
+                            map(
+                                vec![
+                                    ("function", Dynamic::from(x.function.to_string())),
+                                    ("address", integer(x.address)),
+                                    ("target", integer(x.target)),
+                                    ("kind", Dynamic::from(kind.to_string())),
+                                ]
+                            )
+                        }
+                    )
+                    .collect()
+            }
+        )
+        .register_fn(
+            "read",
+            |p: &mut ProjectHandle, address: i64, len: i64| -> Blob {
+                let proj = p.0.borrow();
+                let ret = proj.region().iter().seek(address as u64).take(len.max(0) as usize).take_while(|b| b.is_some()).map(|b| b.unwrap()).collect();
+
+                ret
+            }
+        )
+        .register_fn(
+            "comment",
+            |p: &mut ProjectHandle, address: i64| match p.0.borrow().annotations.comment(&Location::Address(address as u64)) {
+                Some(c) => Dynamic::from(c.to_string()),
+                None => Dynamic::UNIT,
+            }
+        )
+        .register_fn(
+            "set_comment",
+            |p: &mut ProjectHandle, address: i64, text: &str| {
+                p.0.borrow_mut().annotations.set_comment(Location::Address(address as u64), text.to_string());
+            }
+        )
+        .register_fn("rename", |p: &mut ProjectHandle, address: i64, name: &str| p.rename(address, name, false))
+        .register_fn("rename", ProjectHandle::rename)
+        .register_fn("run_pass", ProjectHandle::run_pass);
+
+    engine
+        .register_type_with_name::<FunctionHandle>("Function")
+        .register_get("name", |f: &mut FunctionHandle| f.with(|_, _, func| func.name.clone()))
+        .register_get("start", |f: &mut FunctionHandle| f.with(|_, _, func| func.start() as i64))
+        .register_get("uuid", |f: &mut FunctionHandle| f.uuid.to_string())
+        .register_get("aliases", |f: &mut FunctionHandle| f.with(|_, _, func| func.aliases().iter().map(|a| Dynamic::from(a.clone())).collect::<Array>()))
+        .register_get("size", |f: &mut FunctionHandle| f.with(|_, _, func| func.basic_blocks().map(|bb| bb.area.end - bb.area.start).sum::<u64>() as i64))
+        .register_fn(
+            "basic_blocks",
+            |f: &mut FunctionHandle| {
+                f.with(
+                    |proj, prog, func| {
+                        basic_blocks(func)
+                            .into_iter()
+                            .map(
+                                |bb| {
+                                    map(
+                                        vec![
+                                            ("start", integer(bb.area.start)),
+                                            ("end", integer(bb.area.end)),
+                                            ("mnemonics", Dynamic::from(mnemonics(proj, prog, func, bb))),
+                                        ]
+                                    )
+                                }
+                            )
+                            .collect::<Array>()
+                    }
+                )
+            }
+        )
+        .register_fn("mnemonics", |f: &mut FunctionHandle| f.with(|proj, prog, func| basic_blocks(func).into_iter().flat_map(|bb| mnemonics(proj, prog, func, bb)).collect::<Array>()))
+        .register_fn(
+            "callees",
+            |f: &mut FunctionHandle| {
+                let uuid = f.uuid;
+                let proj = f.project.borrow();
+                let mut ret = proj.xrefs_from(&uuid).iter().filter(|x| x.kind == XrefKind::Call).map(|x| x.target).collect::<Vec<_>>();
+
+                ret.sort();
+                ret.dedup();
+                ret.into_iter().map(integer).collect::<Array>()
+            }
+        )
+        .register_fn(
+            "rename",
+            |f: &mut FunctionHandle, name: &str| -> ScriptResult<String> {
+                let start = f.with(|_, _, func| func.start())?;
+
+                ProjectHandle(f.project.clone()).rename(start as i64, name, false)
+            }
+        )
+        .register_fn("to_string", |f: &mut FunctionHandle| f.uuid.to_string());
+}
+
+impl Script {
+    /// Compiles `source`. Fails on syntax errors.
+    pub fn compile(source: &str) -> Result<Script> {
+        match Engine::new().compile(source) {
+            Ok(ast) => Ok(Script { ast: ast, max_operations: 0, cancel: CancellationToken::new() }),
+            Err(e) => Err(format!("script: {}", e).into()),
+        }
+    }
+
+    /// Reads and compiles the script at `path`.
+    pub fn open(path: &Path) -> Result<Script> {
+        use std::io::Read;
+
+        let mut source = String::new();
+
+        File::open(path)?.read_to_string(&mut source)?;
+        Script::compile(&source)
+    }
+
+    /// Aborts the script after `n` operations. Zero means no limit, the default.
+    pub fn with_max_operations(mut self, n: u64) -> Script {
+        self.max_operations = n;
+        self
+    }
+
+    /// Aborts the script once `token` is cancelled.
+    pub fn with_cancel(mut self, token: CancellationToken) -> Script {
+        self.cancel = token;
+        self
+    }
+
+    /// Runs the script on `proj`. Changes made before an error are kept.
+    pub fn run(&self, proj: &mut Project) -> Result<Output> {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+
+        register(&mut engine);
+        engine.set_max_operations(self.max_operations);
+
+        let p = printed.clone();
+        engine.on_print(move |s| p.borrow_mut().push(s.to_string()));
+
+        let p = printed.clone();
+        engine.on_debug(move |s, _, _| p.borrow_mut().push(s.to_string()));
+
+        let cancel = self.cancel.clone();
+        engine.on_progress(move |_| if cancel.is_cancelled() { Some(Dynamic::UNIT) } else { None });
+
+        // the bindings share the project, it's moved back afterwards
+        let shared = Rc::new(RefCell::new(mem::replace(proj, Project::new(String::new(), Region::undefined(String::new(), 0)))));
+        let mut scope = Scope::new();
+
+        scope.push_constant("project", ProjectHandle(shared.clone()));
+
+        let ret = engine.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast).map(|v| if v.is_unit() { None } else { Some(v.to_string()) });
+
+        drop(scope);
+        mem::swap(proj, &mut *shared.borrow_mut());
+
+        match ret {
+            Ok(value) => Ok(Output { printed: printed.borrow().clone(), value: value }),
+            Err(e) => Err(format!("script: {}", e).into()),
+        }
+    }
+}
+
+/// Compiles `source` and runs it on `proj`.
+pub fn run(proj: &mut Project, source: &str) -> Result<Output> {
+    Script::compile(source)?.run(proj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * 0x100: add rax, 1
+     * 0x104: ret
+     */
+    fn project() -> Project {
+        use panopticon_core::{ControlFlowGraph, ControlFlowTarget, Guard, Lvalue, Mnemonic, Operation, Rvalue, Statement};
+        use panopticon_graph_algos::MutableGraphTrait;
+
+        let mut proj = Project::new("test".to_string(), Region::undefined("RAM".to_string(), 0x1000));
+        let rax = Lvalue::Variable { name: "RAX".into(), size: 64, subscript: None };
+        let add = Statement { op: Operation::Add(rax.clone().into(), Rvalue::new_u64(1)), assignee: rax.clone() };
+        let ops = vec![rax.into(), Rvalue::new_u64(1)];
+        let mne = Mnemonic::new(0x100..0x104, "add".to_string(), "{u}, {u}".to_string(), ops.iter(), vec![add].iter()).ok().unwrap();
+        let ret = Mnemonic::new(0x104..0x105, "ret".to_string(), "".to_string(), vec![].iter(), vec![].iter()).ok().unwrap();
+        let mut cfg = ControlFlowGraph::new();
+        let b0 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![mne])));
+        let b1 = cfg.add_vertex(ControlFlowTarget::Resolved(BasicBlock::from_vec(vec![ret])));
+        let mut func = Function::undefined(0x100, None, proj.region(), Some("sub_100".to_string()));
+        let mut prog = Program::new("prog");
+
+        cfg.add_edge(Guard::True, b0, b1);
+        *func.cfg_mut() = cfg;
+        func.set_entry_point_ref(b0);
+        prog.insert(func);
+        proj.code.push(prog);
+        proj
+    }
+
+    #[test]
+    fn bulk_rename() {
+        let mut proj = project();
+        let source = r#"
+            for f in project.functions() {
+                print(f.name + " " + f.size + " " + f.mnemonics().len());
+                if f.name.starts_with("sub_") {
+                    f.rename("leaf_" + f.start.to_hex());
+                }
+            }
+            project.set_comment(0x104, "return");
+            project.function("leaf_100").basic_blocks()[0].mnemonics[0].opcode
+        "#;
+        let out = run(&mut proj, source).unwrap();
+
+        assert_eq!(out.printed, vec!["sub_100 5 2"]);
+        assert_eq!(out.value, Some("add".to_string()));
+        assert_eq!(proj.name, "test");
+        assert_eq!(proj.code[0].functions().next().unwrap().name, "leaf_100");
+        assert_eq!(proj.annotations.comment(&Location::Address(0x104)), Some("return"));
+    }
+
+    #[test]
+    fn errors() {
+        let mut proj = project();
+
+        assert!(Script::compile("let x = ;").is_err());
+        assert!(run(&mut proj, "project.run_pass(\"nonexistent\")").is_err());
+        assert!(run(&mut proj, "project.rename(0x100, \"\")").is_err());
+        assert!(Script::compile("loop {}").unwrap().with_max_operations(1000).run(&mut proj).is_err());
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(Script::compile("loop {}").unwrap().with_cancel(cancel).run(&mut proj).is_err());
+
+        assert_eq!(run(&mut proj, "project.run_pass(\"strings\"); project.function(0x104)").unwrap().value, None);
+        assert_eq!(proj.code[0].functions().count(), 1);
+    }
+}
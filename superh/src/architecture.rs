@@ -0,0 +1,198 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use decode::{self, Flow, Insn};
+use panopticon_core::{Architecture, Endianess, Guard, Match, Mnemonic, Region, Result};
+use semantic::imm;
+
+#[derive(Clone,Debug)]
+pub enum SuperH {}
+
+/// CPU model
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Model {
+    /// SH-1 and SH-2
+    Sh2,
+    /// SH-3 and SH-4, adds banked registers, cache control, dynamic shifts and, on the SH-4, the
+    /// floating point unit
+    Sh4,
+}
+
+impl Model {
+    /// Picks the model from the `e_flags` field of an ELF header. Unknown values select the SH-4.
+    pub fn from_elf_flags(flags: u32) -> Model {
+        // EF_SH1, EF_SH2, EF_SH_DSP, EF_SH2E and EF_SH2A
+        match flags & 0x1f {
+            1 | 2 | 4 | 0xb | 0xd => Model::Sh2,
+            _ => Model::Sh4,
+        }
+    }
+}
+
+/// CPU configuration.
+#[derive(Clone,Debug)]
+pub struct Cpu {
+    pub model: Model,
+    /// Byte order of instructions and data. The SH-2 is usually run big endian, the SH-4 little
+    /// endian.
+    pub endianess: Endianess,
+}
+
+impl Cpu {
+    pub fn new(model: Model, endianess: Endianess) -> Cpu {
+        Cpu { model: model, endianess: endianess }
+    }
+
+    fn read(&self, reg: &Region, addr: u64, len: usize) -> Option<u64> {
+        let mut ret = 0u64;
+
+        for (i, b) in reg.iter().seek(addr).take(len).enumerate() {
+            let b = match b {
+                Some(b) => b as u64,
+                None => return None,
+            };
+
+            match self.endianess {
+                Endianess::Little => ret |= b << (8 * i),
+                Endianess::Big => ret = (ret << 8) | b,
+            }
+
+            if i + 1 == len {
+                return Some(ret);
+            }
+        }
+
+        None
+    }
+
+    /// Reads the instruction word at `addr`.
+    pub fn read_word(&self, reg: &Region, addr: u64) -> Option<u16> {
+        self.read(reg, addr, 2).map(|x| x as u16)
+    }
+
+    /// Reads the 32-bit value at `addr`.
+    pub fn read_long(&self, reg: &Region, addr: u64) -> Option<u32> {
+        self.read(reg, addr, 4).map(|x| x as u32)
+    }
+}
+
+/// SH-2 exception vectors that are entry points. The SH-3 and SH-4 start at a fixed address
+/// instead.
+const VECTORS: [(u64, &'static str, &'static str); 3] = [
+    (0, "RESET", "Power-on reset vector"),
+    (2, "MANUAL_RESET", "Manual reset vector"),
+    (11, "NMI", "Non-maskable interrupt handler"),
+];
+
+fn mnemonic(insn: &Insn, addr: u64) -> Result<Mnemonic> {
+    Mnemonic::new(
+        addr..addr + 2,
+        insn.opcode.clone(),
+        insn.format.clone(),
+        insn.operands.iter(),
+        insn.statements.iter(),
+    )
+}
+
+impl Architecture for SuperH {
+    type Token = u16;
+    type Configuration = Cpu;
+
+    fn prepare(reg: &Region, cfg: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        let mut ret = vec![];
+
+        if cfg.model != Model::Sh2 {
+            return Ok(ret);
+        }
+
+        for &(vec, name, comment) in VECTORS.iter() {
+            match cfg.read_long(reg, vec * 4) {
+                Some(addr) if addr != 0 && addr % 2 == 0 && (addr as u64) < reg.size() => ret.push((name, addr as u64, comment)),
+                Some(_) => {}
+                None => break,
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        if start % 2 != 0 {
+            return Err(format!("unaligned SuperH instruction at {:#x}", start).into());
+        }
+
+        let insn = decode::read(cfg, reg, start)?;
+        debug!("disass @ {:#x}: {} {:?}", start, insn.opcode, insn.flow);
+
+        let next = imm(start + 2);
+        let after_slot = imm(start + 4);
+        let (mnemonics, jumps) = match insn.flow.clone() {
+            Flow::Next => (vec![mnemonic(&insn, start)?], vec![(start, next, Guard::always())]),
+            Flow::Branch { target, taken: Some(flag), delayed: false } => {
+                let guard = Guard::from_flag(&flag)?;
+                (vec![mnemonic(&insn, start)?], vec![(start, target, guard.clone()), (start, next, guard.negation())])
+            }
+            flow => {
+                let mut slot = decode::read(cfg, reg, start + 2)?;
+
+                if slot.flow != Flow::Next {
+                    return Err(format!("branch in delay slot at {:#x}", start + 2).into());
+                }
+
+                let mut jumps = vec![(start, next, Guard::always())];
+
+                match flow {
+                    Flow::Next => unreachable!(),
+                    Flow::Return => {}
+                    Flow::Call { target } => {
+                        slot.statements.extend(rreil!{ call (target); }?);
+                        jumps.push((start + 2, after_slot, Guard::always()));
+                    }
+                    Flow::Branch { target, taken, .. } => {
+                        let guard = match taken {
+                            Some(ref flag) => Guard::from_flag(flag)?,
+                            None => Guard::always(),
+                        };
+
+                        if guard != Guard::always() {
+                            jumps.push((start + 2, after_slot, guard.negation()));
+                        }
+                        jumps.push((start + 2, target, guard));
+                    }
+                }
+
+                (vec![mnemonic(&insn, start)?, mnemonic(&slot, start + 2)?], jumps)
+            }
+        };
+
+        let mut tokens = vec![];
+        for i in 0..mnemonics.len() as u64 {
+            tokens.extend(cfg.read_word(reg, start + 2 * i));
+        }
+
+        Ok(
+            Match::<SuperH> {
+                tokens: tokens,
+                mnemonics: mnemonics,
+                jumps: jumps,
+                configuration: cfg.clone(),
+            }
+        )
+    }
+}
@@ -0,0 +1,999 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! SuperH instruction decoder.
+
+use architecture::{Cpu, Model};
+use panopticon_core::{Operation, Region, Result, Rvalue, Statement};
+use semantic::*;
+
+/// How control flow continues after an instruction.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Branches to `target` if the flag `taken` is set or `taken` is `None`. Delayed branches
+    /// execute the instruction in their delay slot first.
+    Branch { target: Rvalue, taken: Option<Rvalue>, delayed: bool },
+    /// Calls `target` after the delay slot
+    Call { target: Rvalue },
+    /// Returns to the caller after the delay slot
+    Return,
+}
+
+/// A single decoded instruction.
+#[derive(Clone,Debug)]
+pub struct Insn {
+    pub opcode: String,
+    pub format: String,
+    pub operands: Vec<Rvalue>,
+    pub statements: Vec<Statement>,
+    pub flow: Flow,
+}
+
+impl Insn {
+    fn new(opcode: &str, format: &str, operands: Vec<Rvalue>, statements: Vec<Statement>) -> Insn {
+        Insn {
+            opcode: opcode.to_string(),
+            format: format.to_string(),
+            operands: operands,
+            statements: statements,
+            flow: Flow::Next,
+        }
+    }
+
+    fn flow(mut self, flow: Flow) -> Insn {
+        self.flow = flow;
+        self
+    }
+}
+
+fn unknown(op: u16) -> Result<Insn> {
+    Err(format!("unknown SuperH instruction {:#06x}", op).into())
+}
+
+fn require_sh4(cpu: &Cpu, op: u16) -> Result<()> {
+    if cpu.model == Model::Sh4 {
+        Ok(())
+    } else {
+        Err(format!("SH-4 instruction {:#06x} in SH-2 mode", op).into())
+    }
+}
+
+fn suffix(size: usize) -> &'static str {
+    match size {
+        8 => ".b",
+        16 => ".w",
+        _ => ".l",
+    }
+}
+
+/// Decodes the instruction at `addr`.
+pub fn read(cpu: &Cpu, region: &Region, addr: u64) -> Result<Insn> {
+    let op = match cpu.read_word(region, addr) {
+        Some(op) => op,
+        None => return Err(format!("SuperH instruction at {:#x} truncated", addr).into()),
+    };
+    let n = ((op >> 8) & 0xf) as usize;
+    let m = ((op >> 4) & 0xf) as usize;
+    let (rn, rm) = (REGISTERS[n], REGISTERS[m]);
+
+    match op >> 12 {
+        0x0 => group0(cpu, op, n, m, addr),
+        0x1 => {
+            let disp = (op & 0xf) as u64 * 4;
+            let mut stmts = rreil!{ add ea:32, (reg(n)), [disp]:32; }?;
+
+            stmts.extend(store(cpu, rreil_rvalue!{ ea:32 }, reg(m), 32)?);
+            Ok(Insn::new("mov.l", &format!("{}, @({{u}},{})", rm, rn), vec![imm(disp)], stmts))
+        }
+        0x2 => group2(cpu, op, n, m),
+        0x3 => group3(op, n, m),
+        0x4 => group4(cpu, op, n, m, addr),
+        0x5 => {
+            let disp = (op & 0xf) as u64 * 4;
+            let mut stmts = rreil!{ add ea:32, (reg(m)), [disp]:32; }?;
+
+            stmts.extend(load(cpu, reg_lv(n), rreil_rvalue!{ ea:32 }, 32)?);
+            Ok(Insn::new("mov.l", &format!("@({{u}},{}), {}", rm, rn), vec![imm(disp)], stmts))
+        }
+        0x6 => group6(cpu, op, n, m),
+        0x7 => {
+            let v = sign_extend((op & 0xff) as u64, 8);
+            let stmts = rreil!{ add (reg_lv(n)), (reg(n)), [v]:32; }?;
+            Ok(Insn::new("add", &format!("#{{s}}, {}", rn), vec![imm(v)], stmts))
+        }
+        0x8 => group8(cpu, op, m, addr),
+        0x9 | 0xd => {
+            // the word is relative to the branch target in a delay slot, which is ignored here
+            let (size, ea) = if op >> 12 == 0x9 {
+                (16, addr + 4 + (op & 0xff) as u64 * 2)
+            } else {
+                (32, (addr & !3) + 4 + (op & 0xff) as u64 * 4)
+            };
+            let stmts = load(cpu, reg_lv(n), imm(ea), size)?;
+            let name = format!("mov{}", suffix(size));
+
+            Ok(Insn::new(&name, &format!("{{p:ram}}, {}", rn), vec![imm(ea)], stmts))
+        }
+        0xa | 0xb => {
+            let target = imm((addr + 4).wrapping_add(sign_extend((op & 0xfff) as u64, 12).wrapping_mul(2)));
+
+            if op >> 12 == 0xa {
+                let flow = Flow::Branch { target: target.clone(), taken: None, delayed: true };
+                Ok(Insn::new("bra", "{c:ram}", vec![target], vec![]).flow(flow))
+            } else {
+                let stmts = rreil!{ mov pr:32, [(addr + 4)]:32; }?;
+                Ok(Insn::new("bsr", "{c:ram}", vec![target.clone()], stmts).flow(Flow::Call { target: target }))
+            }
+        }
+        0xc => group_c(cpu, op, addr),
+        0xe => {
+            let v = sign_extend((op & 0xff) as u64, 8);
+            let stmts = rreil!{ mov (reg_lv(n)), [v]:32; }?;
+            Ok(Insn::new("mov", &format!("#{{s}}, {}", rn), vec![imm(v)], stmts))
+        }
+        _ => float(cpu, op, n, m),
+    }
+}
+
+// `name Rm, Rn` computing `Rn = op(Rn, Rm)`
+fn binop(name: &str, op: BinOp, n: usize, m: usize) -> Result<Insn> {
+    let stmts = vec![Statement { op: op(reg(n), reg(m)), assignee: reg_lv(n) }];
+    Ok(Insn::new(name, &format!("{}, {}", REGISTERS[m], REGISTERS[n]), vec![], stmts))
+}
+
+fn read_control(name: &'static str) -> Result<(Vec<Statement>, Rvalue)> {
+    if name == "sr" {
+        Ok((status_register()?, rreil_rvalue!{ sr:32 }))
+    } else {
+        Ok((vec![], special_register(name).into()))
+    }
+}
+
+fn write_control(name: &'static str, v: Rvalue) -> Result<Vec<Statement>> {
+    if name == "sr" {
+        set_status_register(v)
+    } else {
+        rreil!{ mov (special_register(name)), (v); }
+    }
+}
+
+/// Register accessed by `lds`/`sts` (`system` set) or `ldc`/`stc`, selected by bits 4 to 7 of
+/// `op`. Returns whether the instruction is spelled `ldc`/`stc` and the register name.
+fn control_register(cpu: &Cpu, op: u16, system: bool, load: bool) -> Result<(bool, &'static str)> {
+    let sel = ((op >> 4) & 0xf) as usize;
+    let ret = match (system, sel) {
+        (true, 0) => (false, "mach"),
+        (true, 1) => (false, "macl"),
+        (true, 2) => (false, "pr"),
+        (false, 0) => (true, "sr"),
+        (false, 1) => (true, "gbr"),
+        (false, 2) => (true, "vbr"),
+        _ => {
+            require_sh4(cpu, op)?;
+            match (system, sel) {
+                (true, 3) if !load => (true, "sgr"),
+                (true, 5) => (false, "fpul"),
+                (true, 6) => (false, "fpscr"),
+                (true, 0xf) => (true, "dbr"),
+                (false, 3) => (true, "ssr"),
+                (false, 4) => (true, "spc"),
+                (false, 8...15) => (true, BANKED[sel & 7]),
+                _ => return Err(format!("unknown SuperH instruction {:#06x}", op).into()),
+            }
+        }
+    };
+
+    Ok(ret)
+}
+
+fn group0(cpu: &Cpu, op: u16, n: usize, m: usize, addr: u64) -> Result<Insn> {
+    let (rn, rm) = (REGISTERS[n], REGISTERS[m]);
+
+    match op & 0xf {
+        0x2 | 0xa => {
+            let (control, name) = control_register(cpu, op, op & 0xf == 0xa, false)?;
+            let (mut stmts, v) = read_control(name)?;
+
+            stmts.extend(rreil!{ mov (reg_lv(n)), (v); }?);
+            Ok(Insn::new(if control { "stc" } else { "sts" }, &format!("{}, {}", name, rn), vec![], stmts))
+        }
+        0x3 => {
+            match m {
+                0 | 2 => {
+                    let mut stmts = rreil!{ add target:32, (reg(n)), [(addr + 4)]:32; }?;
+                    let target = rreil_rvalue!{ target:32 };
+
+                    if m == 0 {
+                        stmts.extend(rreil!{ mov pr:32, [(addr + 4)]:32; }?);
+                        Ok(Insn::new("bsrf", rn, vec![], stmts).flow(Flow::Call { target: target }))
+                    } else {
+                        let flow = Flow::Branch { target: target, taken: None, delayed: true };
+                        Ok(Insn::new("braf", rn, vec![], stmts).flow(flow))
+                    }
+                }
+                8...11 => {
+                    require_sh4(cpu, op)?;
+                    let name = ["pref", "ocbi", "ocbp", "ocbwb"][m - 8];
+                    Ok(Insn::new(name, &format!("@{}", rn), vec![], vec![]))
+                }
+                12 => {
+                    require_sh4(cpu, op)?;
+                    let stmts = store(cpu, reg(n), reg(R0), 32)?;
+                    Ok(Insn::new("movca.l", &format!("r0, @{}", rn), vec![], stmts))
+                }
+                _ => unknown(op),
+            }
+        }
+        0x4...0x6 => {
+            let size = 8 << ((op & 0xf) - 4);
+            let mut stmts = rreil!{ add ea:32, (reg(R0)), (reg(n)); }?;
+
+            stmts.extend(store(cpu, rreil_rvalue!{ ea:32 }, reg(m), size)?);
+            Ok(Insn::new(&format!("mov{}", suffix(size)), &format!("{}, @(r0,{})", rm, rn), vec![], stmts))
+        }
+        0x7 => {
+            let stmts = rreil!{ mul macl:32, (reg(n)), (reg(m)); }?;
+            Ok(Insn::new("mul.l", &format!("{}, {}", rm, rn), vec![], stmts))
+        }
+        0x8 => {
+            match op {
+                0x0008 => Ok(Insn::new("clrt", "", vec![], rreil!{ mov T:1, [0]:1; }?)),
+                0x0018 => Ok(Insn::new("sett", "", vec![], rreil!{ mov T:1, [1]:1; }?)),
+                0x0028 => {
+                    let stmts = rreil!{
+                        mov mach:32, [0]:32;
+                        mov macl:32, [0]:32;
+                    }?;
+                    Ok(Insn::new("clrmac", "", vec![], stmts))
+                }
+                0x0038 => {
+                    require_sh4(cpu, op)?;
+                    Ok(Insn::new("ldtlb", "", vec![], vec![]))
+                }
+                0x0048 | 0x0058 => {
+                    require_sh4(cpu, op)?;
+                    let set = op == 0x0058;
+                    let stmts = rreil!{ mov S:1, [(set as u64)]:1; }?;
+                    Ok(Insn::new(if set { "sets" } else { "clrs" }, "", vec![], stmts))
+                }
+                _ => unknown(op),
+            }
+        }
+        0x9 => {
+            match (op & 0xff, n) {
+                (0x09, 0) => Ok(Insn::new("nop", "", vec![], vec![])),
+                (0x19, 0) => {
+                    let stmts = rreil!{
+                        mov M:1, [0]:1;
+                        mov Q:1, [0]:1;
+                        mov T:1, [0]:1;
+                    }?;
+                    Ok(Insn::new("div0u", "", vec![], stmts))
+                }
+                (0x29, _) => Ok(Insn::new("movt", rn, vec![], rreil!{ zext/32 (reg_lv(n)), T:1; }?)),
+                _ => unknown(op),
+            }
+        }
+        0xb => {
+            match op {
+                0x000b => Ok(Insn::new("rts", "", vec![], vec![]).flow(Flow::Return)),
+                0x001b => Ok(Insn::new("sleep", "", vec![], vec![])),
+                0x002b => {
+                    let mut stmts = vec![];
+
+                    if cpu.model == Model::Sh4 {
+                        stmts.extend(set_status_register(rreil_rvalue!{ ssr:32 })?);
+                    } else {
+                        // the program counter is popped first
+                        stmts.extend(rreil!{ add (reg_lv(SP)), (reg(SP)), [4]:32; }?);
+                        stmts.extend(load(cpu, special_register("saved_sr"), reg(SP), 32)?);
+                        stmts.extend(rreil!{ add (reg_lv(SP)), (reg(SP)), [4]:32; }?);
+                        stmts.extend(set_status_register(rreil_rvalue!{ saved_sr:32 })?);
+                    }
+                    Ok(Insn::new("rte", "", vec![], stmts).flow(Flow::Return))
+                }
+                _ => unknown(op),
+            }
+        }
+        0xc...0xe => {
+            let size = 8 << ((op & 0xf) - 0xc);
+            let mut stmts = rreil!{ add ea:32, (reg(R0)), (reg(m)); }?;
+
+            stmts.extend(load(cpu, reg_lv(n), rreil_rvalue!{ ea:32 }, size)?);
+            Ok(Insn::new(&format!("mov{}", suffix(size)), &format!("@(r0,{}), {}", rm, rn), vec![], stmts))
+        }
+        0xf => {
+            let mut stmts = rreil!{ add (reg_lv(m)), (reg(m)), [4]:32; }?;
+
+            stmts.extend(rreil!{ add (reg_lv(n)), (reg(n)), [4]:32; }?);
+            stmts.extend(
+                rreil!{
+                    mov mach:32, ?;
+                    mov macl:32, ?;
+                }?
+            );
+            Ok(Insn::new("mac.l", &format!("@{}+, @{}+", rm, rn), vec![], stmts))
+        }
+        _ => unknown(op),
+    }
+}
+
+fn group2(cpu: &Cpu, op: u16, n: usize, m: usize) -> Result<Insn> {
+    let (rn, rm) = (REGISTERS[n], REGISTERS[m]);
+    let regs = format!("{}, {}", rm, rn);
+
+    match op & 0xf {
+        0x0...0x2 => {
+            let size = 8 << (op & 0xf);
+            let stmts = store(cpu, reg(n), reg(m), size)?;
+            Ok(Insn::new(&format!("mov{}", suffix(size)), &format!("{}, @{}", rm, rn), vec![], stmts))
+        }
+        0x4...0x6 => {
+            let size = 8 << ((op & 0xf) - 4);
+            let mut stmts = rreil!{
+                mov stored:32, (reg(m));
+                sub (reg_lv(n)), (reg(n)), [(size as u64 / 8)]:32;
+            }?;
+
+            stmts.extend(store(cpu, reg(n), rreil_rvalue!{ stored:32 }, size)?);
+            Ok(Insn::new(&format!("mov{}", suffix(size)), &format!("{}, @-{}", rm, rn), vec![], stmts))
+        }
+        0x7 => {
+            let stmts = rreil!{
+                mov Q:1, (reg(n).extract(1, 31)?);
+                mov M:1, (reg(m).extract(1, 31)?);
+                xor T:1, Q:1, M:1;
+            }?;
+            Ok(Insn::new("div0s", &regs, vec![], stmts))
+        }
+        0x8 => {
+            let stmts = rreil!{
+                and tst:32, (reg(n)), (reg(m));
+                cmpeq T:1, tst:32, [0]:32;
+            }?;
+            Ok(Insn::new("tst", &regs, vec![], stmts))
+        }
+        0x9 => binop("and", Operation::And, n, m),
+        0xa => binop("xor", Operation::ExclusiveOr, n, m),
+        0xb => binop("or", Operation::InclusiveOr, n, m),
+        0xc => {
+            // set if any byte is equal
+            let stmts = rreil!{
+                xor eq:32, (reg(n)), (reg(m));
+                cmpeq T:1, eq:8/0, [0]:8;
+                cmpeq byte:1, eq:8/8, [0]:8;
+                or T:1, T:1, byte:1;
+                cmpeq byte:1, eq:8/16, [0]:8;
+                or T:1, T:1, byte:1;
+                cmpeq byte:1, eq:8/24, [0]:8;
+                or T:1, T:1, byte:1;
+            }?;
+            Ok(Insn::new("cmp/str", &regs, vec![], stmts))
+        }
+        0xd => {
+            let stmts = rreil!{
+                shl hi:32, (reg(m)), [16]:32;
+                shr lo:32, (reg(n)), [16]:32;
+                or (reg_lv(n)), hi:32, lo:32;
+            }?;
+            Ok(Insn::new("xtrct", &regs, vec![], stmts))
+        }
+        0xe => {
+            let stmts = rreil!{
+                zext/32 a:32, (reg(n).extract(16, 0)?);
+                zext/32 b:32, (reg(m).extract(16, 0)?);
+                mul macl:32, a:32, b:32;
+            }?;
+            Ok(Insn::new("mulu.w", &regs, vec![], stmts))
+        }
+        0xf => {
+            let stmts = rreil!{
+                sext/32 a:32, (reg(n).extract(16, 0)?);
+                sext/32 b:32, (reg(m).extract(16, 0)?);
+                mul macl:32, a:32, b:32;
+            }?;
+            Ok(Insn::new("muls.w", &regs, vec![], stmts))
+        }
+        _ => unknown(op),
+    }
+}
+
+fn group3(op: u16, n: usize, m: usize) -> Result<Insn> {
+    let (a, b) = (reg(n), reg(m));
+    let regs = format!("{}, {}", REGISTERS[m], REGISTERS[n]);
+    let compare = |name: &str, stmts: Vec<Statement>| -> Result<Insn> { Ok(Insn::new(name, &regs, vec![], stmts)) };
+
+    match op & 0xf {
+        0x0 => compare("cmp/eq", rreil!{ cmpeq T:1, (a), (b); }?),
+        0x2 => compare("cmp/hs", rreil!{ cmpleu T:1, (b), (a); }?),
+        0x3 => compare("cmp/ge", rreil!{ cmples T:1, (b), (a); }?),
+        0x6 => compare("cmp/hi", rreil!{ cmpltu T:1, (b), (a); }?),
+        0x7 => compare("cmp/gt", rreil!{ cmplts T:1, (b), (a); }?),
+        0x4 => {
+            // one step of a non-restoring division
+            let stmts = rreil!{
+                mov (reg_lv(n)), ?;
+                mov Q:1, ?;
+                mov T:1, ?;
+            }?;
+            compare("div1", stmts)
+        }
+        0x5 | 0xd => {
+            let mut stmts = if op & 0xf == 0x5 {
+                rreil!{
+                    zext/64 wide_a:64, (a);
+                    zext/64 wide_b:64, (b);
+                }?
+            } else {
+                rreil!{
+                    sext/64 wide_a:64, (a);
+                    sext/64 wide_b:64, (b);
+                }?
+            };
+
+            stmts.extend(
+                rreil!{
+                    mul prod:64, wide_a:64, wide_b:64;
+                    mov macl:32, prod:32/0;
+                    mov mach:32, prod:32/32;
+                }?
+            );
+            compare(if op & 0xf == 0x5 { "dmulu.l" } else { "dmuls.l" }, stmts)
+        }
+        0x8 => binop("sub", Operation::Subtract, n, m),
+        0xc => binop("add", Operation::Add, n, m),
+        0xa | 0xe => {
+            let mut stmts = with_carry(a, b, op & 0xf == 0xa)?;
+
+            stmts.extend(rreil!{ mov (reg_lv(n)), res:32; }?);
+            compare(if op & 0xf == 0xa { "subc" } else { "addc" }, stmts)
+        }
+        0xb | 0xf => {
+            let mut stmts = with_overflow(a, b, op & 0xf == 0xb)?;
+
+            stmts.extend(rreil!{ mov (reg_lv(n)), res:32; }?);
+            compare(if op & 0xf == 0xb { "subv" } else { "addv" }, stmts)
+        }
+        _ => unknown(op),
+    }
+}
+
+fn group4(cpu: &Cpu, op: u16, n: usize, m: usize, addr: u64) -> Result<Insn> {
+    let rn = REGISTERS[n];
+    let (v, lv) = (reg(n), reg_lv(n));
+    let single = |name: &str, stmts: Vec<Statement>| -> Result<Insn> { Ok(Insn::new(name, rn, vec![], stmts)) };
+
+    match op & 0xff {
+        0x00 | 0x20 => {
+            let stmts = rreil!{
+                mov T:1, (v.extract(1, 31)?);
+                shl (lv), (v), [1]:32;
+            }?;
+            single(if op & 0xff == 0 { "shll" } else { "shal" }, stmts)
+        }
+        0x01 => {
+            let stmts = rreil!{
+                mov T:1, (v.extract(1, 0)?);
+                shr (lv), (v), [1]:32;
+            }?;
+            single("shlr", stmts)
+        }
+        0x21 => {
+            let stmts = rreil!{
+                mov T:1, (v.extract(1, 0)?);
+                shrs (lv), (v), [1]:32;
+            }?;
+            single("shar", stmts)
+        }
+        0x04 => {
+            let stmts = rreil!{
+                mov T:1, (v.extract(1, 31)?);
+                shl rot:32, (v), [1]:32;
+                zext/32 low:32, T:1;
+                or (lv), rot:32, low:32;
+            }?;
+            single("rotl", stmts)
+        }
+        0x05 => {
+            let stmts = rreil!{
+                mov T:1, (v.extract(1, 0)?);
+                shr (lv), (v), [1]:32;
+                sel/31 (lv), T:1;
+            }?;
+            single("rotr", stmts)
+        }
+        0x24 => {
+            let stmts = rreil!{
+                mov carry:1, T:1;
+                mov T:1, (v.extract(1, 31)?);
+                shl (lv), (v), [1]:32;
+                sel/0 (lv), carry:1;
+            }?;
+            single("rotcl", stmts)
+        }
+        0x25 => {
+            let stmts = rreil!{
+                mov carry:1, T:1;
+                mov T:1, (v.extract(1, 0)?);
+                shr (lv), (v), [1]:32;
+                sel/31 (lv), carry:1;
+            }?;
+            single("rotcr", stmts)
+        }
+        0x08 | 0x18 | 0x28 => {
+            let by = [2, 8, 16][(op as usize >> 4) & 3];
+            single(&format!("shll{}", by), rreil!{ shl (lv), (v), [by]:32; }?)
+        }
+        0x09 | 0x19 | 0x29 => {
+            let by = [2, 8, 16][(op as usize >> 4) & 3];
+            single(&format!("shlr{}", by), rreil!{ shr (lv), (v), [by]:32; }?)
+        }
+        0x10 => {
+            let stmts = rreil!{
+                sub (lv), (v), [1]:32;
+                cmpeq T:1, (v), [0]:32;
+            }?;
+            single("dt", stmts)
+        }
+        0x11 => single("cmp/pz", rreil!{ cmples T:1, [0]:32, (v); }?),
+        0x15 => single("cmp/pl", rreil!{ cmplts T:1, [0]:32, (v); }?),
+        0x1b => {
+            let mut stmts = load(cpu, special_register("tas"), v.clone(), 8)?;
+
+            stmts.extend(
+                rreil!{
+                    cmpeq T:1, tas:8/0, [0]:8;
+                    or tas:32, tas:32, [0x80]:32;
+                }?
+            );
+            stmts.extend(store(cpu, v, rreil_rvalue!{ tas:32 }, 8)?);
+            Ok(Insn::new("tas.b", &format!("@{}", rn), vec![], stmts))
+        }
+        0x0b | 0x2b => {
+            let mut stmts = rreil!{ mov target:32, (v); }?;
+            let target = rreil_rvalue!{ target:32 };
+
+            if op & 0xff == 0x0b {
+                stmts.extend(rreil!{ mov pr:32, [(addr + 4)]:32; }?);
+                Ok(Insn::new("jsr", &format!("@{}", rn), vec![], stmts).flow(Flow::Call { target: target }))
+            } else {
+                let flow = Flow::Branch { target: target, taken: None, delayed: true };
+                Ok(Insn::new("jmp", &format!("@{}", rn), vec![], stmts).flow(flow))
+            }
+        }
+        _ => {
+            match op & 0xf {
+                0x2 | 0x3 => {
+                    let (control, name) = control_register(cpu, op, op & 0xf == 0x2, false)?;
+                    let (mut stmts, val) = read_control(name)?;
+
+                    stmts.extend(rreil!{ sub (lv), (v), [4]:32; }?);
+                    stmts.extend(store(cpu, v, val, 32)?);
+                    Ok(Insn::new(if control { "stc.l" } else { "sts.l" }, &format!("{}, @-{}", name, rn), vec![], stmts))
+                }
+                0x6 | 0x7 => {
+                    let (control, name) = control_register(cpu, op, op & 0xf == 0x6, true)?;
+                    let mut stmts = load(cpu, special_register("loaded"), v.clone(), 32)?;
+
+                    stmts.extend(rreil!{ add (lv), (v), [4]:32; }?);
+                    stmts.extend(write_control(name, rreil_rvalue!{ loaded:32 })?);
+                    Ok(Insn::new(if control { "ldc.l" } else { "lds.l" }, &format!("@{}+, {}", rn, name), vec![], stmts))
+                }
+                0xa | 0xe => {
+                    let (control, name) = control_register(cpu, op, op & 0xf == 0xa, true)?;
+                    let stmts = write_control(name, v)?;
+                    Ok(Insn::new(if control { "ldc" } else { "lds" }, &format!("{}, {}", rn, name), vec![], stmts))
+                }
+                0xc | 0xd => {
+                    require_sh4(cpu, op)?;
+                    let arithmetic = op & 0xf == 0xc;
+                    let mut stmts = dynamic_shift(v, reg(m), arithmetic)?;
+
+                    stmts.extend(rreil!{ mov (lv), res:32; }?);
+                    Ok(Insn::new(if arithmetic { "shad" } else { "shld" }, &format!("{}, {}", REGISTERS[m], rn), vec![], stmts))
+                }
+                0xf => {
+                    let mut stmts = rreil!{ add (reg_lv(m)), (reg(m)), [2]:32; }?;
+
+                    stmts.extend(rreil!{ add (lv), (v), [2]:32; }?);
+                    stmts.extend(
+                        rreil!{
+                            mov mach:32, ?;
+                            mov macl:32, ?;
+                        }?
+                    );
+                    Ok(Insn::new("mac.w", &format!("@{}+, @{}+", REGISTERS[m], rn), vec![], stmts))
+                }
+                _ => unknown(op),
+            }
+        }
+    }
+}
+
+fn group6(cpu: &Cpu, op: u16, n: usize, m: usize) -> Result<Insn> {
+    let (rn, rm) = (REGISTERS[n], REGISTERS[m]);
+    let regs = format!("{}, {}", rm, rn);
+    let (src, lv) = (reg(m), reg_lv(n));
+
+    match op & 0xf {
+        0x0...0x2 => {
+            let size = 8 << (op & 0xf);
+            let stmts = load(cpu, lv, src, size)?;
+            Ok(Insn::new(&format!("mov{}", suffix(size)), &format!("@{}, {}", rm, rn), vec![], stmts))
+        }
+        0x3 => Ok(Insn::new("mov", &regs, vec![], rreil!{ mov (lv), (src); }?)),
+        0x4...0x6 => {
+            let size = 8 << ((op & 0xf) - 4);
+            let mut stmts = load(cpu, lv, src.clone(), size)?;
+
+            if n != m {
+                stmts.extend(rreil!{ add (reg_lv(m)), (src), [(size as u64 / 8)]:32; }?);
+            }
+            Ok(Insn::new(&format!("mov{}", suffix(size)), &format!("@{}+, {}", rm, rn), vec![], stmts))
+        }
+        0x7 => Ok(Insn::new("not", &regs, vec![], rreil!{ xor (lv), (src), [0xffffffff]:32; }?)),
+        0x8 => {
+            let stmts = rreil!{
+                mov lo:8, (src.extract(8, 0)?);
+                mov hi:8, (src.extract(8, 8)?);
+                mov (lv), (src);
+                sel/0 (lv), hi:8;
+                sel/8 (lv), lo:8;
+            }?;
+            Ok(Insn::new("swap.b", &regs, vec![], stmts))
+        }
+        0x9 => {
+            let stmts = rreil!{
+                shl hi:32, (src), [16]:32;
+                shr lo:32, (src), [16]:32;
+                or (lv), hi:32, lo:32;
+            }?;
+            Ok(Insn::new("swap.w", &regs, vec![], stmts))
+        }
+        0xa => {
+            let mut stmts = with_carry(imm(0), src, true)?;
+
+            stmts.extend(rreil!{ mov (lv), res:32; }?);
+            Ok(Insn::new("negc", &regs, vec![], stmts))
+        }
+        0xb => Ok(Insn::new("neg", &regs, vec![], rreil!{ sub (lv), [0]:32, (src); }?)),
+        0xc => Ok(Insn::new("extu.b", &regs, vec![], rreil!{ zext/32 (lv), (src.extract(8, 0)?); }?)),
+        0xd => Ok(Insn::new("extu.w", &regs, vec![], rreil!{ zext/32 (lv), (src.extract(16, 0)?); }?)),
+        0xe => Ok(Insn::new("exts.b", &regs, vec![], rreil!{ sext/32 (lv), (src.extract(8, 0)?); }?)),
+        _ => Ok(Insn::new("exts.w", &regs, vec![], rreil!{ sext/32 (lv), (src.extract(16, 0)?); }?)),
+    }
+}
+
+fn group8(cpu: &Cpu, op: u16, m: usize, addr: u64) -> Result<Insn> {
+    let rm = REGISTERS[m];
+
+    match (op >> 8) & 0xf {
+        0x0 | 0x1 | 0x4 | 0x5 => {
+            let size = 8 << ((op >> 8) & 1);
+            let disp = (op & 0xf) as u64 * (size as u64 / 8);
+            let mut stmts = rreil!{ add ea:32, (reg(m)), [disp]:32; }?;
+            let name = format!("mov{}", suffix(size));
+
+            if op & 0x400 == 0 {
+                stmts.extend(store(cpu, rreil_rvalue!{ ea:32 }, reg(R0), size)?);
+                Ok(Insn::new(&name, &format!("r0, @({{u}},{})", rm), vec![imm(disp)], stmts))
+            } else {
+                stmts.extend(load(cpu, reg_lv(R0), rreil_rvalue!{ ea:32 }, size)?);
+                Ok(Insn::new(&name, &format!("@({{u}},{}), r0", rm), vec![imm(disp)], stmts))
+            }
+        }
+        0x8 => {
+            let v = sign_extend((op & 0xff) as u64, 8);
+            let stmts = rreil!{ cmpeq T:1, (reg(R0)), [v]:32; }?;
+            Ok(Insn::new("cmp/eq", "#{s}, r0", vec![imm(v)], stmts))
+        }
+        0x9 | 0xb | 0xd | 0xf => {
+            let target = imm((addr + 4).wrapping_add(sign_extend((op & 0xff) as u64, 8).wrapping_mul(2)));
+            let on_false = op & 0x200 != 0;
+            let delayed = op & 0x400 != 0;
+            let stmts = if on_false {
+                rreil!{ xor taken:1, T:1, [1]:1; }?
+            } else {
+                rreil!{ mov taken:1, T:1; }?
+            };
+            let name = format!("{}{}", if on_false { "bf" } else { "bt" }, if delayed { "/s" } else { "" });
+            let flow = Flow::Branch { target: target.clone(), taken: Some(rreil_rvalue!{ taken:1 }), delayed: delayed };
+
+            Ok(Insn::new(&name, "{c:ram}", vec![target], stmts).flow(flow))
+        }
+        _ => unknown(op),
+    }
+}
+
+fn group_c(cpu: &Cpu, op: u16, addr: u64) -> Result<Insn> {
+    let sel = (op >> 8) & 0xf;
+    let i = (op & 0xff) as u64;
+
+    match sel {
+        0x0...0x2 | 0x4...0x6 => {
+            let size = 8 << (sel & 3);
+            let disp = i * (size as u64 / 8);
+            let mut stmts = rreil!{ add ea:32, gbr:32, [disp]:32; }?;
+            let name = format!("mov{}", suffix(size));
+
+            if sel < 4 {
+                stmts.extend(store(cpu, rreil_rvalue!{ ea:32 }, reg(R0), size)?);
+                Ok(Insn::new(&name, "r0, @({u},gbr)", vec![imm(disp)], stmts))
+            } else {
+                stmts.extend(load(cpu, reg_lv(R0), rreil_rvalue!{ ea:32 }, size)?);
+                Ok(Insn::new(&name, "@({u},gbr), r0", vec![imm(disp)], stmts))
+            }
+        }
+        // the handler address comes from the vector table, execution resumes after it
+        0x3 => Ok(Insn::new("trapa", "#{u}", vec![imm(i)], vec![])),
+        0x7 => {
+            let ea = (addr & !3) + 4 + i * 4;
+            let stmts = rreil!{ mov (reg_lv(R0)), [ea]:32; }?;
+            Ok(Insn::new("mova", "{p:ram}, r0", vec![imm(ea)], stmts))
+        }
+        0x8 => {
+            let stmts = rreil!{
+                and tst:32, (reg(R0)), [i]:32;
+                cmpeq T:1, tst:32, [0]:32;
+            }?;
+            Ok(Insn::new("tst", "#{u}, r0", vec![imm(i)], stmts))
+        }
+        0x9...0xb => {
+            let name = ["and", "xor", "or"][sel as usize - 9];
+            let ops: [BinOp; 3] = [Operation::And, Operation::ExclusiveOr, Operation::InclusiveOr];
+            let op = ops[sel as usize - 9];
+            let stmts = vec![Statement { op: op(reg(R0), imm(i)), assignee: reg_lv(R0) }];
+            Ok(Insn::new(name, "#{u}, r0", vec![imm(i)], stmts))
+        }
+        _ => {
+            let mut stmts = rreil!{ add ea:32, gbr:32, (reg(R0)); }?;
+
+            stmts.extend(load(cpu, special_register("byte"), rreil_rvalue!{ ea:32 }, 8)?);
+            if sel == 0xc {
+                stmts.extend(
+                    rreil!{
+                        and byte:32, byte:32, [i]:32;
+                        cmpeq T:1, byte:32, [0]:32;
+                    }?
+                );
+                return Ok(Insn::new("tst.b", "#{u}, @(r0,gbr)", vec![imm(i)], stmts));
+            }
+
+            let name = ["and.b", "xor.b", "or.b"][sel as usize - 0xd];
+            let ops: [BinOp; 3] = [Operation::And, Operation::ExclusiveOr, Operation::InclusiveOr];
+            let op = ops[sel as usize - 0xd];
+
+            stmts.push(Statement { op: op(rreil_rvalue!{ byte:32 }, imm(i)), assignee: special_register("byte") });
+            stmts.extend(store(cpu, rreil_rvalue!{ ea:32 }, rreil_rvalue!{ byte:32 }, 8)?);
+            Ok(Insn::new(name, "#{u}, @(r0,gbr)", vec![imm(i)], stmts))
+        }
+    }
+}
+
+// Sets the floating point registers `first` to `first + count - 1` to undefined.
+fn undefined_float(first: usize, count: usize) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    for r in first..first + count {
+        stmts.extend(rreil!{ mov (special_register(FLOAT[r])), ?; }?);
+    }
+    Ok(stmts)
+}
+
+/// SH-4 floating point instructions.
+fn float(cpu: &Cpu, op: u16, n: usize, m: usize) -> Result<Insn> {
+    require_sh4(cpu, op)?;
+
+    let (frn, frm) = (FLOAT[n], FLOAT[m]);
+    let (rn, rm) = (REGISTERS[n], REGISTERS[m]);
+    let regs = format!("{}, {}", frm, frn);
+    let fr = |r: usize| -> Rvalue { special_register(FLOAT[r]).into() };
+
+    match op & 0xf {
+        0x0...0x3 => {
+            let name = ["fadd", "fsub", "fmul", "fdiv"][(op & 3) as usize];
+            Ok(Insn::new(name, &regs, vec![], undefined_float(n, 1)?))
+        }
+        0x4 | 0x5 => {
+            let name = if op & 1 == 0 { "fcmp/eq" } else { "fcmp/gt" };
+            Ok(Insn::new(name, &regs, vec![], rreil!{ mov T:1, ?; }?))
+        }
+        0x6 => {
+            let mut stmts = rreil!{ add ea:32, (reg(R0)), (reg(m)); }?;
+
+            stmts.extend(load(cpu, special_register(frn), rreil_rvalue!{ ea:32 }, 32)?);
+            Ok(Insn::new("fmov.s", &format!("@(r0,{}), {}", rm, frn), vec![], stmts))
+        }
+        0x7 => {
+            let mut stmts = rreil!{ add ea:32, (reg(R0)), (reg(n)); }?;
+
+            stmts.extend(store(cpu, rreil_rvalue!{ ea:32 }, fr(m), 32)?);
+            Ok(Insn::new("fmov.s", &format!("{}, @(r0,{})", frm, rn), vec![], stmts))
+        }
+        0x8 | 0x9 => {
+            let mut stmts = load(cpu, special_register(frn), reg(m), 32)?;
+
+            if op & 1 == 0 {
+                Ok(Insn::new("fmov.s", &format!("@{}, {}", rm, frn), vec![], stmts))
+            } else {
+                stmts.extend(rreil!{ add (reg_lv(m)), (reg(m)), [4]:32; }?);
+                Ok(Insn::new("fmov.s", &format!("@{}+, {}", rm, frn), vec![], stmts))
+            }
+        }
+        0xa => Ok(Insn::new("fmov.s", &format!("{}, @{}", frm, rn), vec![], store(cpu, reg(n), fr(m), 32)?)),
+        0xb => {
+            let mut stmts = rreil!{ sub (reg_lv(n)), (reg(n)), [4]:32; }?;
+
+            stmts.extend(store(cpu, reg(n), fr(m), 32)?);
+            Ok(Insn::new("fmov.s", &format!("{}, @-{}", frm, rn), vec![], stmts))
+        }
+        0xc => Ok(Insn::new("fmov", &regs, vec![], rreil!{ mov (special_register(frn)), (fr(m)); }?)),
+        0xd => float_single(op, n, m),
+        0xe => Ok(Insn::new("fmac", &format!("fr0, {}", regs), vec![], undefined_float(n, 1)?)),
+        _ => unknown(op),
+    }
+}
+
+// Floating point instructions with a single register operand, encoded as `1111nnnnxxxx1101`.
+fn float_single(op: u16, n: usize, m: usize) -> Result<Insn> {
+    let frn = FLOAT[n];
+    let (v, lv): (Rvalue, _) = (special_register(frn).into(), special_register(frn));
+    let single = |name: &str, stmts: Vec<Statement>| -> Result<Insn> { Ok(Insn::new(name, frn, vec![], stmts)) };
+
+    match m {
+        0x0 => Ok(Insn::new("fsts", &format!("fpul, {}", frn), vec![], rreil!{ mov (lv), fpul:32; }?)),
+        0x1 => Ok(Insn::new("flds", &format!("{}, fpul", frn), vec![], rreil!{ mov fpul:32, (v); }?)),
+        0x2 => Ok(Insn::new("float", &format!("fpul, {}", frn), vec![], undefined_float(n, 1)?)),
+        0x3 => Ok(Insn::new("ftrc", &format!("{}, fpul", frn), vec![], rreil!{ mov fpul:32, ?; }?)),
+        0x4 => single("fneg", rreil!{ xor (lv), (v), [0x80000000]:32; }?),
+        0x5 => single("fabs", rreil!{ and (lv), (v), [0x7fffffff]:32; }?),
+        0x6 => single("fsqrt", undefined_float(n, 1)?),
+        0x7 => single("fsrra", undefined_float(n, 1)?),
+        0x8 => single("fldi0", rreil!{ mov (lv), [0]:32; }?),
+        0x9 => single("fldi1", rreil!{ mov (lv), [0x3f800000]:32; }?),
+        0xa if n & 1 == 0 => Ok(Insn::new("fcnvsd", &format!("fpul, dr{}", n), vec![], undefined_float(n, 2)?)),
+        0xb if n & 1 == 0 => Ok(Insn::new("fcnvds", &format!("dr{}, fpul", n), vec![], rreil!{ mov fpul:32, ?; }?)),
+        0xe => {
+            let (vn, vm) = ((n >> 2) * 4, (n & 3) * 4);
+            Ok(Insn::new("fipr", &format!("fv{}, fv{}", vm, vn), vec![], undefined_float(vn + 3, 1)?))
+        }
+        0xf => {
+            match n {
+                0x3 | 0xb => {
+                    let bit = if n == 0x3 { 1u64 << 20 } else { 1u64 << 21 };
+                    let stmts = rreil!{ xor fpscr:32, fpscr:32, [bit]:32; }?;
+                    Ok(Insn::new(if n == 0x3 { "fschg" } else { "frchg" }, "", vec![], stmts))
+                }
+                _ if n & 3 == 1 => {
+                    let vn = (n >> 2) * 4;
+                    Ok(Insn::new("ftrv", &format!("xmtrx, fv{}", vn), vec![], undefined_float(vn, 4)?))
+                }
+                _ if n & 1 == 0 => Ok(Insn::new("fsca", &format!("fpul, dr{}", n), vec![], undefined_float(n, 2)?)),
+                _ => unknown(op),
+            }
+        }
+        _ => unknown(op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use panopticon_core::Endianess;
+
+    fn decode(cpu: &Cpu, op: u16) -> Insn {
+        let reg = Region::wrap("ram".to_string(), vec![(op >> 8) as u8, op as u8]);
+        let ret = read(cpu, &reg, 0).unwrap();
+
+        for s in ret.statements.iter() {
+            assert!(s.sanity_check().is_ok(), "{} {:?}", ret.opcode, s);
+        }
+        ret
+    }
+
+    #[test]
+    fn data_movement() {
+        let cpu = Cpu::new(Model::Sh2, Endianess::Big);
+
+        assert_eq!(decode(&cpu, 0x6013).format, "r1, r0");
+        assert_eq!(decode(&cpu, 0xe1ff).operands, vec![Rvalue::new_u32(0xffffffff)]);
+        assert_eq!(decode(&cpu, 0xd102).operands, vec![Rvalue::new_u32(12)]);
+        assert_eq!(decode(&cpu, 0xd102).format, "{p:ram}, r1");
+        assert_eq!(decode(&cpu, 0x9101).opcode, "mov.w");
+        assert_eq!(decode(&cpu, 0x2f16).format, "r1, @-r15");
+        assert_eq!(decode(&cpu, 0x6f16).format, "@r1+, r15");
+        assert_eq!(decode(&cpu, 0x1f12).format, "r1, @({u},r15)");
+        assert_eq!(decode(&cpu, 0x0f1e).format, "@(r0,r1), r15");
+        assert_eq!(decode(&cpu, 0x8512).opcode, "mov.w");
+        assert_eq!(decode(&cpu, 0xc601).format, "@({u},gbr), r0");
+        assert_eq!(decode(&cpu, 0xc701).operands, vec![Rvalue::new_u32(8)]);
+        assert_eq!(decode(&cpu, 0x6118).opcode, "swap.b");
+        assert_eq!(decode(&cpu, 0x4f22).opcode, "sts.l");
+        assert_eq!(decode(&cpu, 0x4f26).format, "@r15+, pr");
+        assert_eq!(decode(&cpu, 0x0002).format, "sr, r0");
+        assert_eq!(decode(&cpu, 0x400e).opcode, "ldc");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let cpu = Cpu::new(Model::Sh2, Endianess::Big);
+
+        assert_eq!(decode(&cpu, 0x3c1c).opcode, "add");
+        assert_eq!(decode(&cpu, 0x71fc).operands, vec![Rvalue::new_u32(0xfffffffc)]);
+        assert_eq!(decode(&cpu, 0x312e).opcode, "addc");
+        assert_eq!(decode(&cpu, 0x312b).opcode, "subv");
+        assert_eq!(decode(&cpu, 0x3120).opcode, "cmp/eq");
+        assert_eq!(decode(&cpu, 0x3126).opcode, "cmp/hi");
+        assert_eq!(decode(&cpu, 0x4115).opcode, "cmp/pl");
+        assert_eq!(decode(&cpu, 0x212c).opcode, "cmp/str");
+        assert_eq!(decode(&cpu, 0x4110).opcode, "dt");
+        assert_eq!(decode(&cpu, 0x2118).opcode, "tst");
+        assert_eq!(decode(&cpu, 0xc901).opcode, "and");
+        assert_eq!(decode(&cpu, 0xcd01).format, "#{u}, @(r0,gbr)");
+        assert_eq!(decode(&cpu, 0x611c).opcode, "extu.b");
+        assert_eq!(decode(&cpu, 0x4124).opcode, "rotcl");
+        assert_eq!(decode(&cpu, 0x4128).opcode, "shll16");
+        assert_eq!(decode(&cpu, 0x312d).opcode, "dmuls.l");
+        assert_eq!(decode(&cpu, 0x0117).opcode, "mul.l");
+        assert_eq!(decode(&cpu, 0x0019).opcode, "div0u");
+        assert_eq!(decode(&cpu, 0x411b).opcode, "tas.b");
+    }
+
+    #[test]
+    fn flow() {
+        let cpu = Cpu::new(Model::Sh2, Endianess::Big);
+        let taken = Some(rreil_rvalue!{ taken:1 });
+
+        assert_eq!(decode(&cpu, 0x000b).flow, Flow::Return);
+        assert_eq!(decode(&cpu, 0x002b).flow, Flow::Return);
+        assert_eq!(decode(&cpu, 0x0009).flow, Flow::Next);
+        assert_eq!(decode(&cpu, 0x8b02).flow, Flow::Branch { target: Rvalue::new_u32(8), taken: taken.clone(), delayed: false });
+        assert_eq!(decode(&cpu, 0x8f02).flow, Flow::Branch { target: Rvalue::new_u32(8), taken: taken.clone(), delayed: true });
+        assert_eq!(decode(&cpu, 0x8902).opcode, "bt");
+        assert_eq!(decode(&cpu, 0xaffe).flow, Flow::Branch { target: Rvalue::new_u32(0), taken: None, delayed: true });
+        assert_eq!(decode(&cpu, 0xb004).flow, Flow::Call { target: Rvalue::new_u32(12) });
+        assert_eq!(decode(&cpu, 0x410b).flow, Flow::Call { target: rreil_rvalue!{ target:32 } });
+        assert_eq!(decode(&cpu, 0x0123).opcode, "braf");
+        assert_eq!(decode(&cpu, 0xc320).flow, Flow::Next);
+    }
+
+    #[test]
+    fn models() {
+        let sh2 = Cpu::new(Model::Sh2, Endianess::Big);
+        let sh4 = Cpu::new(Model::Sh4, Endianess::Big);
+        let reg = |op: u16| Region::wrap("ram".to_string(), vec![(op >> 8) as u8, op as u8]);
+
+        for &op in [0x410c, 0xf00c, 0x0083, 0x4f32, 0x0048, 0x0182].iter() {
+            assert!(read(&sh2, &reg(op), 0).is_err(), "{:#x}", op);
+            assert!(read(&sh4, &reg(op), 0).is_ok(), "{:#x}", op);
+        }
+
+        assert_eq!(decode(&sh4, 0x410c).opcode, "shad");
+        assert_eq!(decode(&sh4, 0xf3fd).opcode, "fschg");
+        assert_eq!(decode(&sh4, 0xf08d).opcode, "fldi0");
+        assert_eq!(decode(&sh4, 0xf1fd).format, "xmtrx, fv0");
+        assert_eq!(decode(&sh4, 0xf169).format, "@r6+, fr1");
+        assert_eq!(decode(&sh4, 0x0182).format, "r0_bank, r1");
+        assert!(read(&sh4, &reg(0xfffd), 0).is_err());
+        assert!(read(&sh4, &reg(0x0000), 0).is_err());
+        assert!(read(&sh4, &reg(0x4f36), 0).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! SuperH disassembler.
+//!
+//! This disassembler handles the fixed 16-bit instruction format of the SH-1/SH-2 and the
+//! SH-3/SH-4 in both byte orders. The SH-4 model adds banked registers, cache control
+//! instructions, `shad`/`shld` and the floating point unit.
+//!
+//! Most branches are delayed: the instruction following them executes before the branch is
+//! taken. Like the MIPS disassembler, the decoder returns the branch and its delay slot as one
+//! match and copies the branch condition and indirect targets into temporaries first. `bt` and
+//! `bf` are the only branches without a delay slot.
+//!
+//! The T, S, Q and M bits of the status register are modeled as separate flags and merged into
+//! `sr` when it is accessed as a whole. Loads from the literal pool use constant addresses.
+//! Floating point moves assume single precision transfers (FPSCR.SZ clear), arithmetic sets its
+//! result to undefined, as do `div1` and the multiply-accumulate instructions.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+
+pub mod semantic;
+mod decode;
+
+mod architecture;
+pub use architecture::{Cpu, Model, SuperH};
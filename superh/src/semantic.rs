@@ -0,0 +1,263 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! RREIL code generation helpers for SuperH.
+
+use architecture::Cpu;
+use panopticon_core::{Lvalue, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+pub static REGISTERS: [&'static str; 16] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+/// Registers r0 to r7 of the inactive bank (SH-4).
+pub static BANKED: [&'static str; 8] = ["r0_bank", "r1_bank", "r2_bank", "r3_bank", "r4_bank", "r5_bank", "r6_bank", "r7_bank"];
+
+/// Single precision floating point registers (SH-4).
+pub static FLOAT: [&'static str; 16] = [
+    "fr0", "fr1", "fr2", "fr3", "fr4", "fr5", "fr6", "fr7",
+    "fr8", "fr9", "fr10", "fr11", "fr12", "fr13", "fr14", "fr15",
+];
+
+pub const R0: usize = 0;
+pub const SP: usize = 15;
+
+pub type BinOp = fn(Rvalue, Rvalue) -> Operation<Rvalue>;
+
+/// Reads general purpose register `r`.
+pub fn reg(r: usize) -> Rvalue {
+    Rvalue::Variable { name: Cow::Borrowed(REGISTERS[r & 0xf]), subscript: None, offset: 0, size: 32 }
+}
+
+/// General purpose register `r` as assignee.
+pub fn reg_lv(r: usize) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(REGISTERS[r & 0xf]), subscript: None, size: 32 }
+}
+
+/// 32-bit register `name` outside of the general purpose register file, e.g. `pr` or `fr3`.
+pub fn special_register(name: &'static str) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: 32 }
+}
+
+/// 32-bit constant.
+pub fn imm(v: u64) -> Rvalue {
+    Rvalue::new_u32(v as u32)
+}
+
+/// Sign extends the `bits` wide value `v` to 64 bits.
+pub fn sign_extend(v: u64, bits: usize) -> u64 {
+    let shift = 64 - bits;
+    (((v << shift) as i64) >> shift) as u64
+}
+
+fn memory(cpu: &Cpu, size: usize, addr: Rvalue, load: Option<Lvalue>, store: Option<Rvalue>) -> Statement {
+    let endianess = cpu.endianess;
+
+    match (load, store) {
+        (Some(lv), _) => Statement { op: Operation::Load(Cow::Borrowed("ram"), endianess, size, addr), assignee: lv },
+        (None, Some(val)) => Statement { op: Operation::Store(Cow::Borrowed("ram"), endianess, size, addr, val), assignee: Lvalue::Undefined },
+        (None, None) => unreachable!(),
+    }
+}
+
+/// Loads `size` bits from `addr` into `lv`. Bytes and words are sign extended to 32 bits.
+pub fn load(cpu: &Cpu, lv: Lvalue, addr: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    if size == 32 {
+        return Ok(vec![memory(cpu, size, addr, Some(lv), None)]);
+    }
+
+    let val = Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size };
+    let mut stmts = vec![memory(cpu, size, addr, Some(val.clone()), None)];
+
+    stmts.extend(rreil!{ sext/32 (lv), (val); }?);
+    Ok(stmts)
+}
+
+/// Stores the lower `size` bits of `v` at `addr`.
+pub fn store(cpu: &Cpu, addr: Rvalue, v: Rvalue, size: usize) -> Result<Vec<Statement>> {
+    if size == 32 {
+        return Ok(vec![memory(cpu, size, addr, None, Some(v))]);
+    }
+
+    let val = Lvalue::Variable { name: Cow::Borrowed("val"), subscript: None, size: size };
+    let mut stmts = rreil!{ mov (val), (v.extract(size, 0)?); }?;
+
+    stmts.push(memory(cpu, size, addr, None, Some(val.into())));
+    Ok(stmts)
+}
+
+/// Merges the T, S, Q and M bits into `sr`.
+pub fn status_register() -> Result<Vec<Statement>> {
+    rreil!{
+        sel/0 sr:32, T:1;
+        sel/1 sr:32, S:1;
+        sel/8 sr:32, Q:1;
+        sel/9 sr:32, M:1;
+    }
+}
+
+/// Writes `v` to `sr` and splits out the T, S, Q and M bits.
+pub fn set_status_register(v: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        mov sr:32, (v);
+        mov T:1, sr:1/0;
+        mov S:1, sr:1/1;
+        mov Q:1, sr:1/8;
+        mov M:1, sr:1/9;
+    }
+}
+
+/// Computes `a + b + T` or `a - b - T` into `res` and the carry or borrow into T.
+pub fn with_carry(a: Rvalue, b: Rvalue, subtract: bool) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        zext/33 wide_a:33, (a);
+        zext/33 wide_b:33, (b);
+        zext/33 wide_t:33, T:1;
+    }?;
+
+    if subtract {
+        stmts.extend(
+            rreil!{
+                sub wide:33, wide_a:33, wide_b:33;
+                sub wide:33, wide:33, wide_t:33;
+            }?
+        );
+    } else {
+        stmts.extend(
+            rreil!{
+                add wide:33, wide_a:33, wide_b:33;
+                add wide:33, wide:33, wide_t:33;
+            }?
+        );
+    }
+
+    stmts.extend(
+        rreil!{
+            mov res:32, wide:32;
+            mov T:1, wide:1/32;
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Computes `a + b` or `a - b` into `res` and the signed overflow into T.
+pub fn with_overflow(a: Rvalue, b: Rvalue, subtract: bool) -> Result<Vec<Statement>> {
+    let mut stmts = if subtract {
+        rreil!{
+            sub res:32, (a), (b);
+            xor ov_a:32, (a), (b);
+        }?
+    } else {
+        rreil!{
+            add res:32, (a), (b);
+            xor ov_a:32, (a), (b);
+            xor ov_a:32, ov_a:32, [0xffffffff]:32;
+        }?
+    };
+
+    stmts.extend(
+        rreil!{
+            xor ov_b:32, (a), res:32;
+            and ov_a:32, ov_a:32, ov_b:32;
+            mov T:1, ov_a:1/31;
+        }?
+    );
+    Ok(stmts)
+}
+
+/// Shifts `v` left if `amount` is positive and right by `32 - (amount & 31)` otherwise, as
+/// `shad` and `shld` do. The result ends up in `res`.
+pub fn dynamic_shift(v: Rvalue, amount: Rvalue, arithmetic: bool) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        and left_by:32, (amount), [31]:32;
+        shl left:32, (v), left_by:32;
+        xor right_by:32, (amount), [0xffffffff]:32;
+        and right_by:32, right_by:32, [31]:32;
+    }?;
+
+    // the second shift makes a right shift by 32 possible
+    if arithmetic {
+        stmts.extend(
+            rreil!{
+                shrs right:32, (v), right_by:32;
+                shrs right:32, right:32, [1]:32;
+            }?
+        );
+    } else {
+        stmts.extend(
+            rreil!{
+                shr right:32, (v), right_by:32;
+                shr right:32, right:32, [1]:32;
+            }?
+        );
+    }
+
+    stmts.extend(
+        rreil!{
+            sext/32 negative:32, (amount.extract(1, 31)?);
+            and right:32, right:32, negative:32;
+            xor negative:32, negative:32, [0xffffffff]:32;
+            and left:32, left:32, negative:32;
+            or res:32, left:32, right:32;
+        }?
+    );
+    Ok(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use architecture::Model;
+    use panopticon_core::Endianess;
+
+    fn sane(stmts: Vec<Statement>) {
+        for s in stmts.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn statements_are_sane() {
+        for &endianess in [Endianess::Little, Endianess::Big].iter() {
+            let cpu = Cpu::new(Model::Sh4, endianess);
+
+            for &sz in [8, 16, 32].iter() {
+                sane(load(&cpu, reg_lv(1), reg(SP), sz).unwrap());
+                sane(store(&cpu, reg(SP), reg(1), sz).unwrap());
+            }
+        }
+
+        sane(status_register().unwrap());
+        sane(set_status_register(reg(1)).unwrap());
+        sane(with_carry(reg(1), reg(2), true).unwrap());
+        sane(with_carry(reg(1), imm(1), false).unwrap());
+        sane(with_overflow(reg(1), reg(2), true).unwrap());
+        sane(with_overflow(reg(1), reg(2), false).unwrap());
+        sane(dynamic_shift(reg(1), reg(2), true).unwrap());
+        sane(dynamic_shift(reg(1), reg(2), false).unwrap());
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(sign_extend(0xfe, 8), 0xffff_ffff_ffff_fffe);
+        assert_eq!(sign_extend(0x7ff, 12), 0x7ff);
+    }
+}
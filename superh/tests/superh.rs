@@ -0,0 +1,112 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+extern crate panopticon_core;
+extern crate panopticon_superh;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Architecture, Endianess, Function, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_superh::{Cpu, Model, SuperH};
+
+fn wrap(words: &[u16], endianess: Endianess) -> Region {
+    let mut bytes = vec![];
+
+    for w in words {
+        match endianess {
+            Endianess::Big => bytes.extend_from_slice(&[(w >> 8) as u8, *w as u8]),
+            Endianess::Little => bytes.extend_from_slice(&[*w as u8, (w >> 8) as u8]),
+        }
+    }
+
+    Region::wrap("ram".to_string(), bytes)
+}
+
+fn starts(func: &Function) -> Vec<u64> {
+    let mut ret = func.basic_blocks().map(|bb| bb.area.start).collect::<Vec<_>>();
+    ret.sort();
+    ret
+}
+
+#[test]
+fn delay_slots() {
+    let reg = wrap(
+        &[
+            0x2118, // 0x00: tst r1, r1
+            0x8d02, // 0x02: bt/s 0x0a
+            0xe200, // 0x04: mov #0, r2
+            0x7201, // 0x06: add #1, r2
+            0x0009, // 0x08: nop
+            0x000b, // 0x0a: rts
+            0x0009, // 0x0c: nop
+        ],
+        Endianess::Big,
+    );
+    let func = Function::new::<SuperH>(0, &reg, None, Cpu::new(Model::Sh2, Endianess::Big)).unwrap();
+
+    assert_eq!(starts(&func), vec![0x0, 0x6, 0xa]);
+    assert_eq!(func.cfg().num_edges(), 3);
+    assert_eq!(func.end(), 0xe);
+}
+
+#[test]
+fn calls() {
+    let reg = wrap(
+        &[
+            0x4f22, // 0x00: sts.l pr, @-r15
+            0xb003, // 0x02: bsr 0x0c
+            0xe104, // 0x04: mov #4, r1
+            0x4f26, // 0x06: lds.l @r15+, pr
+            0x000b, // 0x08: rts
+            0x0009, // 0x0a: nop
+            0x000b, // 0x0c: rts
+            0x0009, // 0x0e: nop
+        ],
+        Endianess::Little,
+    );
+    let func = Function::new::<SuperH>(0, &reg, None, Cpu::new(Model::Sh4, Endianess::Little)).unwrap();
+
+    assert_eq!(starts(&func), vec![0x0]);
+    assert_eq!(func.end(), 0xc);
+    assert_eq!(func.collect_call_addresses(), vec![0xc]);
+}
+
+#[test]
+fn reset_vector() {
+    let mut words = vec![0u16; 0x100];
+
+    // power-on reset PC and SP, manual reset PC
+    words[0..6].copy_from_slice(&[0x0000, 0x0100, 0x0600, 0x0000, 0x0000, 0x0000]);
+
+    let reg = wrap(&words, Endianess::Big);
+    let entries = SuperH::prepare(&reg, &Cpu::new(Model::Sh2, Endianess::Big)).unwrap();
+
+    assert_eq!(entries.iter().map(|e| (e.0, e.1)).collect::<Vec<_>>(), vec![("RESET", 0x100)]);
+    assert!(SuperH::prepare(&reg, &Cpu::new(Model::Sh4, Endianess::Big)).unwrap().is_empty());
+}
+
+#[test]
+fn invalid_delay_slots() {
+    let cpu = Cpu::new(Model::Sh2, Endianess::Big);
+    let reg = wrap(&[0xa000, 0xa000, 0x0009], Endianess::Big);
+
+    assert!(SuperH::decode(&reg, 0, &cpu).is_err());
+    assert!(SuperH::decode(&reg, 1, &cpu).is_err());
+    assert_eq!(SuperH::decode(&reg, 2, &cpu).unwrap().mnemonics.len(), 2);
+}
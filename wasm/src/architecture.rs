@@ -0,0 +1,388 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use decode::{self, BlockType, Immediate, Instr};
+use panopticon_core::{Architecture, Guard, Match, Region, Result, Rvalue};
+use panopticon_core::wasm::{Body, Module};
+use semantic;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone,Debug)]
+pub enum Wasm {}
+
+/// Where a branch continues.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Target {
+    /// Jumps to `address`. The top `arity` values are moved down to stack slot `base`
+    Label { address: u64, base: usize, arity: usize },
+    /// Leaves the function, the top `arity` values are moved down to stack slot 0
+    Return { arity: usize },
+}
+
+/// How control flow continues after an instruction.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub enum Flow {
+    /// Falls through to the next instruction
+    Next,
+    /// Branches unconditionally (`br`, `else` and `return`)
+    Jump(Target),
+    /// Branches if the top of the stack is non-zero (`br_if`)
+    Branch(Target),
+    /// Falls through if the top of the stack is non-zero, jumps to the address otherwise (`if`)
+    If(u64),
+    /// Branches to one of the targets or the default depending on the top of the stack
+    Table(Vec<Target>, Target),
+    /// Traps
+    Trap,
+}
+
+/// A decoded instruction together with its position in the function.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct Step {
+    pub instr: Instr,
+    /// Number of values on the operand stack before the instruction is executed
+    pub height: usize,
+    pub flow: Flow,
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If,
+}
+
+#[derive(Clone,Debug)]
+struct Frame {
+    kind: FrameKind,
+    /// First instruction of the body
+    start: u64,
+    /// The `end` instruction
+    end: u64,
+    /// Stack height below the parameters
+    base: usize,
+    params: usize,
+    results: usize,
+}
+
+impl Frame {
+    fn label(&self) -> Target {
+        match self.kind {
+            FrameKind::Function => Target::Return { arity: self.results },
+            FrameKind::Loop => Target::Label { address: self.start, base: self.base, arity: self.params },
+            _ => Target::Label { address: self.end, base: self.base, arity: self.results },
+        }
+    }
+}
+
+/// CPU configuration.
+///
+/// WebAssembly code can't be decoded without knowing the module it's part of, so the
+/// configuration holds the parsed module and the result of a pass over all function bodies that
+/// computes the operand stack height and branch targets of every instruction.
+#[derive(Clone,Debug)]
+pub struct Cpu {
+    pub module: Arc<Module>,
+    steps: Arc<HashMap<u64, Step>>,
+}
+
+impl Cpu {
+    /// Parses the module in `bytes` and decodes all function bodies.
+    pub fn new(bytes: &[u8]) -> Result<Cpu> {
+        let module = Module::parse(bytes)?;
+        let mut steps = HashMap::new();
+
+        for body in module.bodies.iter() {
+            structure(&module, body, bytes, &mut steps)?;
+        }
+
+        Ok(Cpu { module: Arc::new(module), steps: Arc::new(steps) })
+    }
+
+    /// Decodes the module mapped into `reg` by the loader.
+    pub fn from_region(reg: &Region) -> Result<Cpu> {
+        let mut bytes = vec![];
+
+        for b in reg.iter() {
+            match b {
+                Some(b) => bytes.push(b),
+                None => break,
+            }
+        }
+
+        Cpu::new(&bytes)
+    }
+
+    /// Returns the instruction starting at `address`.
+    pub fn step(&self, address: u64) -> Result<&Step> {
+        self.steps.get(&address).ok_or_else(|| format!("no WebAssembly instruction starts at {:#x}", address).into())
+    }
+}
+
+fn block_arity(module: &Module, bt: BlockType) -> Result<(usize, usize)> {
+    match bt {
+        BlockType::Empty => Ok((0, 0)),
+        BlockType::Value(_) => Ok((0, 1)),
+        BlockType::Type(i) => {
+            match module.types.get(i as usize) {
+                Some(ty) => Ok((ty.params.len(), ty.results.len())),
+                None => Err(format!("unknown WebAssembly type {}", i).into()),
+            }
+        }
+    }
+}
+
+/// Decodes `body` and records a `Step` for each instruction in `steps`. This mirrors the
+/// validation algorithm of the specification without type checking.
+fn structure(module: &Module, body: &Body, bytes: &[u8], steps: &mut HashMap<u64, Step>) -> Result<()> {
+    let mut instrs = vec![];
+    let mut pos = body.start as usize;
+
+    while pos < body.end as usize {
+        let i = decode::read(&bytes[..body.end as usize], pos)?;
+
+        pos += i.len;
+        instrs.push((pos as u64 - i.len as u64, i));
+    }
+
+    // find the matching `else` and `end` of each block
+    let mut ends = HashMap::<u64, (Option<u64>, u64)>::new();
+    let mut open = vec![];
+
+    for &(addr, ref i) in instrs.iter() {
+        match i.opcode {
+            0x02...0x04 => open.push((addr, None)),
+            0x05 => {
+                match open.last_mut() {
+                    Some(&mut (_, ref mut otherwise)) => *otherwise = Some(addr),
+                    None => return Err(format!("unmatched else at {:#x}", addr).into()),
+                }
+            }
+            0x0b => {
+                if let Some((start, otherwise)) = open.pop() {
+                    ends.insert(start, (otherwise, addr));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let ty = module.types.get(body.type_index as usize).ok_or_else(|| format!("unknown WebAssembly type {}", body.type_index))?;
+    let mut frames = vec![
+        Frame {
+            kind: FrameKind::Function,
+            start: body.start,
+            end: body.end - 1,
+            base: 0,
+            params: 0,
+            results: ty.results.len(),
+        },
+    ];
+    let mut height = 0usize;
+
+    for (addr, instr) in instrs.into_iter() {
+        if frames.is_empty() {
+            return Err(format!("WebAssembly instruction after the end of the function at {:#x}", addr).into());
+        }
+
+        let next = addr + instr.len as u64;
+        let before = height;
+        let flow = {
+            let label = |frames: &Vec<Frame>, l: u32| -> Result<Target> {
+                match frames.len().checked_sub(l as usize + 1) {
+                    Some(i) => Ok(frames[i].label()),
+                    None => Err(format!("invalid branch depth {} at {:#x}", l, addr).into()),
+                }
+            };
+            let floor = frames.last().map(|f| f.base).unwrap_or(0);
+            let pop = |height: usize, n: usize| if height >= floor + n { height - n } else { floor };
+
+            match (instr.opcode, &instr.immediate) {
+                (0x00, _) => {
+                    height = floor;
+                    Flow::Trap
+                }
+                (0x02...0x04, &Immediate::Block(bt)) => {
+                    let (params, results) = block_arity(module, bt)?;
+                    let (otherwise, end) = match ends.get(&addr) {
+                        Some(&x) => x,
+                        None => return Err(format!("unterminated block at {:#x}", addr).into()),
+                    };
+                    let kind = match instr.opcode {
+                        0x02 => FrameKind::Block,
+                        0x03 => FrameKind::Loop,
+                        _ => FrameKind::If,
+                    };
+
+                    if kind == FrameKind::If {
+                        height = pop(height, 1);
+                    }
+                    frames.push(
+                        Frame {
+                            kind: kind,
+                            start: next,
+                            end: end,
+                            base: height.saturating_sub(params),
+                            params: params,
+                            results: results,
+                        }
+                    );
+
+                    if kind == FrameKind::If {
+                        Flow::If(otherwise.map(|e| e + 1).unwrap_or(end))
+                    } else {
+                        Flow::Next
+                    }
+                }
+                (0x05, _) => {
+                    let frame = frames.last().cloned().ok_or_else(|| format!("unmatched else at {:#x}", addr))?;
+
+                    height = frame.base + frame.params;
+                    Flow::Jump(Target::Label { address: frame.end, base: frame.base, arity: frame.results })
+                }
+                (0x0b, _) => {
+                    let frame = frames.pop().ok_or_else(|| format!("unmatched end at {:#x}", addr))?;
+
+                    height = frame.base + frame.results;
+                    if frame.kind == FrameKind::Function {
+                        Flow::Jump(frame.label())
+                    } else {
+                        Flow::Next
+                    }
+                }
+                (0x0c, &Immediate::Index(l)) => {
+                    let tgt = label(&frames, l)?;
+
+                    height = floor;
+                    Flow::Jump(tgt)
+                }
+                (0x0d, &Immediate::Index(l)) => {
+                    let tgt = label(&frames, l)?;
+
+                    height = pop(height, 1);
+                    Flow::Branch(tgt)
+                }
+                (0x0e, &Immediate::Table(ref tbl, default)) => {
+                    let tgts = tbl.iter().map(|&l| label(&frames, l)).collect::<Result<Vec<_>>>()?;
+                    let default = label(&frames, default)?;
+
+                    height = floor;
+                    Flow::Table(tgts, default)
+                }
+                (0x0f, _) => {
+                    height = floor;
+                    Flow::Jump(Target::Return { arity: ty.results.len() })
+                }
+                (0x10, &Immediate::Index(f)) => {
+                    let callee = module.function_type(f).ok_or_else(|| format!("unknown WebAssembly function {} at {:#x}", f, addr))?;
+
+                    height = pop(height, callee.params.len()) + callee.results.len();
+                    Flow::Next
+                }
+                (0x11, &Immediate::Indirect(t, _)) => {
+                    let callee = module.types.get(t as usize).ok_or_else(|| format!("unknown WebAssembly type {} at {:#x}", t, addr))?;
+
+                    height = pop(height, callee.params.len() + 1) + callee.results.len();
+                    Flow::Next
+                }
+                (op, _) => {
+                    match decode::effect(op) {
+                        Some((pops, pushes)) => {
+                            height = pop(height, pops) + pushes;
+                            Flow::Next
+                        }
+                        None => return Err(format!("unsupported WebAssembly instruction {:#x} at {:#x}", op, addr).into()),
+                    }
+                }
+            }
+        };
+
+        steps.insert(addr, Step { instr: instr, height: before, flow: flow });
+    }
+
+    Ok(())
+}
+
+impl Architecture for Wasm {
+    type Token = u8;
+    type Configuration = Cpu;
+
+    fn prepare(_: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        Ok(vec![])
+    }
+
+    fn decode(reg: &Region, start: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        let step = cfg.step(start)?;
+        let len = step.instr.len as u64;
+        let next = Rvalue::new_u64(start + len);
+        let (mne, flags) = semantic::lift(cfg, step, start)?;
+        let mut tokens = vec![];
+        let mut i = reg.iter().seek(start);
+
+        for _ in 0..len {
+            match i.next() {
+                Some(Some(b)) => tokens.push(b),
+                _ => return Err(format!("WebAssembly instruction at {:#x} truncated", start).into()),
+            }
+        }
+
+        let label = |tgt: &Target| match tgt {
+            &Target::Label { address, .. } => Some(Rvalue::new_u64(address)),
+            &Target::Return { .. } => None,
+        };
+        let mut jumps = vec![];
+
+        match step.flow {
+            Flow::Next => jumps.push((start, next, Guard::always())),
+            Flow::Jump(ref tgt) => jumps.extend(label(tgt).map(|t| (start, t, Guard::always()))),
+            Flow::Branch(ref tgt) => {
+                let guard = Guard::from_flag(&flags[0])?;
+
+                jumps.extend(label(tgt).map(|t| (start, t, guard.clone())));
+                jumps.push((start, next, guard.negation()));
+            }
+            Flow::If(otherwise) => {
+                let guard = Guard::from_flag(&flags[0])?;
+
+                jumps.push((start, next, guard.clone()));
+                jumps.push((start, Rvalue::new_u64(otherwise), guard.negation()));
+            }
+            Flow::Table(ref tgts, ref default) => {
+                for (tgt, flag) in tgts.iter().chain(Some(default)).zip(flags.iter()) {
+                    let guard = Guard::from_flag(flag)?;
+
+                    jumps.extend(label(tgt).map(|t| (start, t, guard)));
+                }
+            }
+            Flow::Trap => {}
+        }
+
+        debug!("disass @ {:#x}: {} {:?}", start, mne.opcode, step.flow);
+
+        Ok(
+            Match::<Wasm> {
+                tokens: tokens,
+                mnemonics: vec![mne],
+                jumps: jumps,
+                configuration: cfg.clone(),
+            }
+        )
+    }
+}
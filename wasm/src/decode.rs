@@ -0,0 +1,233 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! WebAssembly instruction decoder.
+
+use panopticon_core::Result;
+use panopticon_core::wasm::{Reader, ValueType};
+
+/// Opcode prefix of the saturating truncation instructions.
+pub const PREFIX_FC: u16 = 0xfc00;
+
+/// Type of a `block`, `loop` or `if`.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum BlockType {
+    /// No parameters and results
+    Empty,
+    /// No parameters, single result
+    Value(ValueType),
+    /// Parameters and results taken from the type section
+    Type(u32),
+}
+
+/// Immediate operands
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub enum Immediate {
+    None,
+    /// Block signature
+    Block(BlockType),
+    /// Label, function, local or global index
+    Index(u32),
+    /// Labels of a `br_table` and its default label
+    Table(Vec<u32>, u32),
+    /// Type and table index of `call_indirect`
+    Indirect(u32, u32),
+    /// Alignment exponent and offset of memory accesses
+    Memory(u32, u32),
+    /// Integer or float constant bits, sign extended for integers
+    Constant(u64),
+}
+
+/// A single decoded instruction.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct Instr {
+    /// Opcode. Prefixed opcodes are stored as `prefix << 8 | opcode`
+    pub opcode: u16,
+    pub immediate: Immediate,
+    /// Length in bytes
+    pub len: usize,
+}
+
+/// Decodes the instruction at `pos` in `bytes`.
+pub fn read(bytes: &[u8], pos: usize) -> Result<Instr> {
+    let mut rd = Reader::new(bytes, pos);
+    let op = rd.byte()?;
+    let opcode = if op == 0xfc { PREFIX_FC | rd.u32()? as u16 } else { op as u16 };
+    let imm = match opcode {
+        0x02...0x04 => {
+            let bt = match bytes.get(rd.pos).cloned() {
+                Some(0x40) => {
+                    rd.byte()?;
+                    BlockType::Empty
+                }
+                Some(b) if b & 0xc0 == 0x40 => BlockType::Value(rd.value_type()?),
+                _ => {
+                    let idx = rd.signed(33)?;
+
+                    if idx >> 32 != 0 {
+                        return Err(format!("invalid WebAssembly block type at {:#x}", pos).into());
+                    }
+                    BlockType::Type(idx as u32)
+                }
+            };
+            Immediate::Block(bt)
+        }
+        0x0c | 0x0d | 0x10 | 0x20...0x24 | 0xd2 => Immediate::Index(rd.u32()?),
+        0x0e => {
+            let len = rd.u32()?;
+            let mut tbl = vec![];
+
+            for _ in 0..len {
+                tbl.push(rd.u32()?);
+            }
+            Immediate::Table(tbl, rd.u32()?)
+        }
+        0x11 => {
+            let ty = rd.u32()?;
+            Immediate::Indirect(ty, rd.u32()?)
+        }
+        0x1c => {
+            for _ in 0..rd.u32()? {
+                rd.value_type()?;
+            }
+            Immediate::None
+        }
+        0x28...0x3e => {
+            let align = rd.u32()?;
+            Immediate::Memory(align, rd.u32()?)
+        }
+        0x3f | 0x40 | 0xd0 => {
+            rd.byte()?;
+            Immediate::None
+        }
+        0x41 => Immediate::Constant(rd.signed(32)? & 0xffff_ffff),
+        0x42 => Immediate::Constant(rd.signed(64)?),
+        0x43 => {
+            let b = rd.bytes(4)?;
+            Immediate::Constant(b.iter().rev().fold(0, |acc, &x| (acc << 8) | x as u64))
+        }
+        0x44 => {
+            let b = rd.bytes(8)?;
+            Immediate::Constant(b.iter().rev().fold(0, |acc, &x| (acc << 8) | x as u64))
+        }
+        _ => {
+            if name(opcode).is_none() {
+                return Err(format!("unknown WebAssembly opcode {:#x} at {:#x}", opcode, pos).into());
+            }
+            Immediate::None
+        }
+    };
+
+    Ok(Instr { opcode: opcode, immediate: imm, len: rd.pos - pos })
+}
+
+static NUMERIC: [&'static str; 0x80] = [
+    "i32.eqz", "i32.eq", "i32.ne", "i32.lt_s", "i32.lt_u", "i32.gt_s", "i32.gt_u", "i32.le_s",
+    "i32.le_u", "i32.ge_s", "i32.ge_u", "i64.eqz", "i64.eq", "i64.ne", "i64.lt_s", "i64.lt_u",
+    "i64.gt_s", "i64.gt_u", "i64.le_s", "i64.le_u", "i64.ge_s", "i64.ge_u", "f32.eq", "f32.ne",
+    "f32.lt", "f32.gt", "f32.le", "f32.ge", "f64.eq", "f64.ne", "f64.lt", "f64.gt",
+    "f64.le", "f64.ge", "i32.clz", "i32.ctz", "i32.popcnt", "i32.add", "i32.sub", "i32.mul",
+    "i32.div_s", "i32.div_u", "i32.rem_s", "i32.rem_u", "i32.and", "i32.or", "i32.xor", "i32.shl",
+    "i32.shr_s", "i32.shr_u", "i32.rotl", "i32.rotr", "i64.clz", "i64.ctz", "i64.popcnt", "i64.add",
+    "i64.sub", "i64.mul", "i64.div_s", "i64.div_u", "i64.rem_s", "i64.rem_u", "i64.and", "i64.or",
+    "i64.xor", "i64.shl", "i64.shr_s", "i64.shr_u", "i64.rotl", "i64.rotr", "f32.abs", "f32.neg",
+    "f32.ceil", "f32.floor", "f32.trunc", "f32.nearest", "f32.sqrt", "f32.add", "f32.sub", "f32.mul",
+    "f32.div", "f32.min", "f32.max", "f32.copysign", "f64.abs", "f64.neg", "f64.ceil", "f64.floor",
+    "f64.trunc", "f64.nearest", "f64.sqrt", "f64.add", "f64.sub", "f64.mul", "f64.div", "f64.min",
+    "f64.max", "f64.copysign", "i32.wrap_i64", "i32.trunc_f32_s", "i32.trunc_f32_u", "i32.trunc_f64_s", "i32.trunc_f64_u", "i64.extend_i32_s",
+    "i64.extend_i32_u", "i64.trunc_f32_s", "i64.trunc_f32_u", "i64.trunc_f64_s", "i64.trunc_f64_u", "f32.convert_i32_s", "f32.convert_i32_u", "f32.convert_i64_s",
+    "f32.convert_i64_u", "f32.demote_f64", "f64.convert_i32_s", "f64.convert_i32_u", "f64.convert_i64_s", "f64.convert_i64_u", "f64.promote_f32", "i32.reinterpret_f32",
+    "i64.reinterpret_f64", "f32.reinterpret_i32", "f64.reinterpret_i64", "i32.extend8_s", "i32.extend16_s", "i64.extend8_s", "i64.extend16_s", "i64.extend32_s",
+];
+
+static MEMORY: [&'static str; 23] = [
+    "i32.load", "i64.load", "f32.load", "f64.load", "i32.load8_s", "i32.load8_u", "i32.load16_s", "i32.load16_u",
+    "i64.load8_s", "i64.load8_u", "i64.load16_s", "i64.load16_u", "i64.load32_s", "i64.load32_u", "i32.store", "i64.store",
+    "f32.store", "f64.store", "i32.store8", "i32.store16", "i64.store8", "i64.store16", "i64.store32",
+];
+
+static SATURATING: [&'static str; 8] = [
+    "i32.trunc_sat_f32_s", "i32.trunc_sat_f32_u", "i32.trunc_sat_f64_s", "i32.trunc_sat_f64_u",
+    "i64.trunc_sat_f32_s", "i64.trunc_sat_f32_u", "i64.trunc_sat_f64_s", "i64.trunc_sat_f64_u",
+];
+
+/// Text format name of `opcode`.
+pub fn name(opcode: u16) -> Option<&'static str> {
+    match opcode {
+        0x00 => Some("unreachable"),
+        0x01 => Some("nop"),
+        0x02 => Some("block"),
+        0x03 => Some("loop"),
+        0x04 => Some("if"),
+        0x05 => Some("else"),
+        0x0b => Some("end"),
+        0x0c => Some("br"),
+        0x0d => Some("br_if"),
+        0x0e => Some("br_table"),
+        0x0f => Some("return"),
+        0x10 => Some("call"),
+        0x11 => Some("call_indirect"),
+        0x1a => Some("drop"),
+        0x1b | 0x1c => Some("select"),
+        0x20 => Some("local.get"),
+        0x21 => Some("local.set"),
+        0x22 => Some("local.tee"),
+        0x23 => Some("global.get"),
+        0x24 => Some("global.set"),
+        0x28...0x3e => Some(MEMORY[opcode as usize - 0x28]),
+        0x3f => Some("memory.size"),
+        0x40 => Some("memory.grow"),
+        0x41 => Some("i32.const"),
+        0x42 => Some("i64.const"),
+        0x43 => Some("f32.const"),
+        0x44 => Some("f64.const"),
+        0x45...0xc4 => Some(NUMERIC[opcode as usize - 0x45]),
+        0xd0 => Some("ref.null"),
+        0xd1 => Some("ref.is_null"),
+        0xd2 => Some("ref.func"),
+        0xfc00...0xfc07 => Some(SATURATING[opcode as usize - 0xfc00]),
+        _ => None,
+    }
+}
+
+/// Number of values popped and pushed by instructions that don't depend on the module or
+/// control structure.
+pub fn effect(opcode: u16) -> Option<(usize, usize)> {
+    match opcode {
+        0x01 => Some((0, 0)),
+        0x1a => Some((1, 0)),
+        0x1b | 0x1c => Some((3, 1)),
+        0x20 | 0x23 => Some((0, 1)),
+        0x21 | 0x24 => Some((1, 0)),
+        0x22 => Some((1, 1)),
+        0x28...0x35 => Some((1, 1)),
+        0x36...0x3e => Some((2, 0)),
+        0x3f => Some((0, 1)),
+        0x40 => Some((1, 1)),
+        0x41...0x44 => Some((0, 1)),
+        0x45 | 0x50 => Some((1, 1)),
+        0x46...0x66 => Some((2, 1)),
+        0x67...0x69 | 0x79...0x7b | 0x8b...0x91 | 0x99...0x9f => Some((1, 1)),
+        0x6a...0x78 | 0x7c...0x8a | 0x92...0x98 | 0xa0...0xa6 => Some((2, 1)),
+        0xa7...0xc4 => Some((1, 1)),
+        0xd0 | 0xd2 => Some((0, 1)),
+        0xd1 => Some((1, 1)),
+        0xfc00...0xfc07 => Some((1, 1)),
+        _ => None,
+    }
+}
@@ -0,0 +1,49 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! WebAssembly disassembler.
+//!
+//! This disassembler handles the MVP instruction set of WebAssembly together with the sign
+//! extension, saturating float-to-int and reference type instructions. Modules are loaded by
+//! `panopticon_core::loader` which maps the module file as is and starts a function at the first
+//! instruction of each body.
+//!
+//! WebAssembly is a stack machine with structured control flow. Before anything is decoded the
+//! `Cpu` configuration runs over all function bodies and records the operand stack height before
+//! each instruction as well as the targets of all branches. With the height known, each stack
+//! slot is lowered to a variable and instructions read and write these like registers. Branches
+//! that leave a block with results move them down to the block's slots. Calls only set their
+//! results to undefined, arguments are not copied into the callee's locals.
+//!
+//! RREIL has no floating point operations. Float constants, loads, stores, sign manipulation and
+//! reinterpretation are lifted exactly, the results of all other float instructions are
+//! undefined.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+
+pub mod semantic;
+mod decode;
+
+mod architecture;
+pub use architecture::{Cpu, Flow, Step, Target, Wasm};
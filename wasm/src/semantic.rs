@@ -0,0 +1,532 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! RREIL code generation for WebAssembly.
+//!
+//! Stack slots, locals and globals are 64-bit variables named `s<n>`, `l<n>` and `g<n>`. 32-bit
+//! values are kept zero extended.
+
+use architecture::{Cpu, Flow, Step, Target};
+use decode::{self, BlockType, Immediate};
+use panopticon_core::{Endianess, Lvalue, Mnemonic, Operation, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+pub type BinOp = fn(Rvalue, Rvalue) -> Operation<Rvalue>;
+
+fn variable(name: String, size: usize) -> Rvalue {
+    Rvalue::Variable { name: Cow::Owned(name), subscript: None, offset: 0, size: size }
+}
+
+fn variable_lv(name: String) -> Lvalue {
+    Lvalue::Variable { name: Cow::Owned(name), subscript: None, size: 64 }
+}
+
+/// Lower `size` bits of stack slot `n`.
+pub fn slot(n: usize, size: usize) -> Rvalue {
+    variable(format!("s{}", n), size)
+}
+
+/// Stack slot `n` as assignee.
+pub fn slot_lv(n: usize) -> Lvalue {
+    variable_lv(format!("s{}", n))
+}
+
+/// Local variable `n`. Parameters are the first locals.
+pub fn local(n: u32) -> Rvalue {
+    variable(format!("l{}", n), 64)
+}
+
+/// Local variable `n` as assignee.
+pub fn local_lv(n: u32) -> Lvalue {
+    variable_lv(format!("l{}", n))
+}
+
+/// Global variable `n`.
+pub fn global(n: u32) -> Rvalue {
+    variable(format!("g{}", n), 64)
+}
+
+/// Global variable `n` as assignee.
+pub fn global_lv(n: u32) -> Lvalue {
+    variable_lv(format!("g{}", n))
+}
+
+fn temp(name: &'static str, size: usize) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), subscript: None, size: size }
+}
+
+fn constant(value: u64, size: usize) -> Rvalue {
+    let mask = if size < 64 { (1u64 << size) - 1 } else { !0 };
+    Rvalue::Constant { value: value & mask, size: size }
+}
+
+/// Writes the `size` bit result of `op` into 64-bit `lv`, zero extending it.
+pub fn assign(lv: Lvalue, op: Operation<Rvalue>, size: usize) -> Result<Vec<Statement>> {
+    if size == 64 {
+        return Ok(vec![Statement { op: op, assignee: lv }]);
+    }
+
+    let res = temp("res", size);
+    let mut stmts = vec![Statement { op: op, assignee: res.clone() }];
+
+    stmts.extend(rreil!{ zext/64 (lv), (res); }?);
+    Ok(stmts)
+}
+
+/// Copies `arity` slots starting at `from` to `to`.
+pub fn shift(from: usize, to: usize, arity: usize) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    if from != to {
+        for k in 0..arity {
+            stmts.extend(rreil!{ mov (slot_lv(to + k)), (slot(from + k, 64)); }?);
+        }
+    }
+    Ok(stmts)
+}
+
+/// Copies `arity` slots starting at `from` to `to` if `flag` is set.
+pub fn shift_if(from: usize, to: usize, arity: usize, flag: &Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    if from != to {
+        for k in 0..arity {
+            let (dst, src) = (slot(to + k, 64), slot(from + k, 64));
+
+            stmts.extend(
+                rreil!{
+                    zext/64 cond:64, (flag);
+                    sub diff:64, (src), (dst);
+                    mul diff:64, diff:64, cond:64;
+                    add (slot_lv(to + k)), (dst), diff:64;
+                }?
+            );
+        }
+    }
+    Ok(stmts)
+}
+
+/// Moves the branch results from the top of the stack of height `height` to their place.
+fn branch(target: &Target, height: usize, flag: Option<&Rvalue>) -> Result<Vec<Statement>> {
+    let (to, arity) = match target {
+        &Target::Label { base, arity, .. } => (base, arity),
+        &Target::Return { arity } => (0, arity),
+    };
+    let from = height.checked_sub(arity).ok_or("WebAssembly operand stack underflow")?;
+
+    match flag {
+        Some(flag) => shift_if(from, to, arity, flag),
+        None => shift(from, to, arity),
+    }
+}
+
+/// `s[h-2] := s[h-2] op s[h-1]` on `size` bit operands.
+pub fn binop(h: usize, size: usize, op: BinOp) -> Result<Vec<Statement>> {
+    assign(slot_lv(h - 2), op(slot(h - 2, size), slot(h - 1, size)), size)
+}
+
+/// Compares the two topmost `size` bit values, swapping them if `swap` is set and inverting the
+/// result if `negate` is set.
+pub fn compare(h: usize, size: usize, op: BinOp, swap: bool, negate: bool) -> Result<Vec<Statement>> {
+    let (a, b) = (slot(h - 2, size), slot(h - 1, size));
+    let op = if swap { op(b, a) } else { op(a, b) };
+    let mut stmts = vec![Statement { op: op, assignee: temp("cmp", 1) }];
+
+    if negate {
+        stmts.extend(rreil!{ xor cmp:1, cmp:1, [1]:1; }?);
+    }
+    stmts.extend(rreil!{ zext/64 (slot_lv(h - 2)), cmp:1; }?);
+    Ok(stmts)
+}
+
+/// `s[h-1] := s[h-1] == 0`.
+pub fn eqz(h: usize, size: usize) -> Result<Vec<Statement>> {
+    rreil!{
+        cmpeq cmp:1, (slot(h - 1, size)), (constant(0, size));
+        zext/64 (slot_lv(h - 1)), cmp:1;
+    }
+}
+
+/// Shift with the count taken modulo `size`.
+pub fn shift_op(h: usize, size: usize, op: BinOp) -> Result<Vec<Statement>> {
+    let cnt = temp("cnt", size);
+    let mut stmts = rreil!{ and (cnt), (slot(h - 1, size)), (constant(size as u64 - 1, size)); }?;
+
+    stmts.extend(assign(slot_lv(h - 2), op(slot(h - 2, size), cnt.into()), size)?);
+    Ok(stmts)
+}
+
+/// Rotates the second topmost value left or right.
+pub fn rotate(h: usize, size: usize, left: bool) -> Result<Vec<Statement>> {
+    let (cnt, inv, hi, lo) = (temp("cnt", size), temp("inv", size), temp("hi", size), temp("lo", size));
+    let a = slot(h - 2, size);
+    let mask = constant(size as u64 - 1, size);
+    let mut stmts = rreil!{
+        and (cnt), (slot(h - 1, size)), (mask);
+        sub (inv), (constant(size as u64, size)), (cnt);
+        and (inv), (inv), (mask);
+    }?;
+
+    if left {
+        stmts.extend(rreil!{ shl (hi), (a), (cnt); shr (lo), (a), (inv); }?);
+    } else {
+        stmts.extend(rreil!{ shl (hi), (a), (inv); shr (lo), (a), (cnt); }?);
+    }
+    stmts.extend(assign(slot_lv(h - 2), Operation::InclusiveOr(hi.into(), lo.into()), size)?);
+    Ok(stmts)
+}
+
+/// Sign bit manipulation of `abs` and `copysign`. Clears the sign bit of slot `dst` and ors in
+/// the sign of `sign`.
+fn float_sign(dst: usize, size: usize, sign: Option<Rvalue>) -> Result<Vec<Statement>> {
+    let bit = 1u64 << (size - 1);
+    let mag = temp("mag", size);
+    let mut stmts = rreil!{ and (mag), (slot(dst, size)), (constant(!bit, size)); }?;
+
+    match sign {
+        Some(sign) => {
+            let sgn = temp("sgn", size);
+
+            stmts.extend(rreil!{ and (sgn), (sign), (constant(bit, size)); }?);
+            stmts.extend(assign(slot_lv(dst), Operation::InclusiveOr(mag.into(), sgn.into()), size)?);
+        }
+        None => stmts.extend(assign(slot_lv(dst), Operation::Move(mag.into()), size)?),
+    }
+    Ok(stmts)
+}
+
+/// Computes the effective address of a memory access with address operand in slot `n`.
+fn address(n: usize, offset: u32) -> Result<Vec<Statement>> {
+    rreil!{
+        zext/64 addr:64, (slot(n, 32));
+        add addr:64, addr:64, (constant(offset as u64, 64));
+    }
+}
+
+/// Loads `bits` from linear memory into a `size` bit value, zero or sign extending it.
+pub fn load(h: usize, offset: u32, bits: usize, size: usize, signed: bool) -> Result<Vec<Statement>> {
+    let val = temp("val", bits);
+    let mut stmts = address(h - 1, offset)?;
+
+    stmts.push(Statement { op: Operation::Load(Cow::Borrowed("memory"), Endianess::Little, bits, rreil_rvalue!{ addr:64 }), assignee: val.clone() });
+    if signed && bits < size {
+        stmts.extend(assign(slot_lv(h - 1), Operation::SignExtend(size, val.into()), size)?);
+    } else if bits < 64 {
+        stmts.extend(rreil!{ zext/64 (slot_lv(h - 1)), (val); }?);
+    } else {
+        stmts.extend(rreil!{ mov (slot_lv(h - 1)), (val); }?);
+    }
+    Ok(stmts)
+}
+
+/// Stores the lower `bits` of the topmost value in linear memory.
+pub fn store(h: usize, offset: u32, bits: usize) -> Result<Vec<Statement>> {
+    let mut stmts = address(h - 2, offset)?;
+
+    stmts.push(
+        Statement {
+            op: Operation::Store(Cow::Borrowed("memory"), Endianess::Little, bits, rreil_rvalue!{ addr:64 }, slot(h - 1, bits)),
+            assignee: Lvalue::Undefined,
+        }
+    );
+    Ok(stmts)
+}
+
+/// `s[h-1] := sext(s[h-1] & (2^bits - 1))` of width `size`.
+pub fn extend(h: usize, bits: usize, size: usize) -> Result<Vec<Statement>> {
+    assign(slot_lv(h - 1), Operation::SignExtend(size, slot(h - 1, bits)), size)
+}
+
+/// Sets `n` to undefined.
+pub fn undefined(n: usize) -> Result<Vec<Statement>> {
+    rreil!{ mov (slot_lv(n)), ?; }
+}
+
+/// Sets `flag` to `s[n] != 0`.
+fn nonzero(n: usize) -> Result<Vec<Statement>> {
+    rreil!{ cmpltu flag:1, [0]:32, (slot(n, 32)); }
+}
+
+/// Statements implementing the instruction, excluding control flow.
+fn statements(cpu: &Cpu, step: &Step) -> Result<Vec<Statement>> {
+    let h = step.height;
+    let (pops, _) = match (step.instr.opcode, &step.instr.immediate) {
+        (0x10, &Immediate::Index(f)) => cpu.module.function_type(f).map(|t| (t.params.len(), 0)).unwrap_or((0, 0)),
+        (0x11, &Immediate::Indirect(t, _)) => cpu.module.types.get(t as usize).map(|t| (t.params.len() + 1, 0)).unwrap_or((0, 0)),
+        (0x04, _) | (0x0d, _) | (0x0e, _) => (1, 0),
+        (op, _) => decode::effect(op).unwrap_or((0, 0)),
+    };
+
+    if h < pops {
+        return Err("WebAssembly operand stack underflow".into());
+    }
+
+    match (step.instr.opcode, &step.instr.immediate) {
+        // control
+        (0x00...0x0f, _) => Ok(vec![]),
+        (0x10, &Immediate::Index(f)) => {
+            let callee = cpu.module.function_type(f).ok_or("unknown WebAssembly function")?;
+            let target = match cpu.module.body(f) {
+                Some(body) => Rvalue::new_u64(body.start),
+                None => Rvalue::Undefined,
+            };
+            let mut stmts = rreil!{ call (target); }?;
+
+            for k in 0..callee.results.len() {
+                stmts.extend(undefined(h - pops + k)?);
+            }
+            Ok(stmts)
+        }
+        (0x11, &Immediate::Indirect(t, _)) => {
+            let callee = cpu.module.types.get(t as usize).ok_or("unknown WebAssembly type")?;
+            let mut stmts = rreil!{ call (Rvalue::Undefined); }?;
+
+            for k in 0..callee.results.len() {
+                stmts.extend(undefined(h - pops + k)?);
+            }
+            Ok(stmts)
+        }
+
+        // parametric
+        (0x1a, _) => Ok(vec![]),
+        (0x1b, _) | (0x1c, _) => {
+            rreil!{
+                cmpeq flag:1, (slot(h - 1, 32)), [0]:32;
+            }.and_then(
+                |mut stmts| {
+                    stmts.extend(shift_if(h - 2, h - 3, 1, &rreil_rvalue!{ flag:1 })?);
+                    Ok(stmts)
+                }
+            )
+        }
+
+        // variables
+        (0x20, &Immediate::Index(i)) => rreil!{ mov (slot_lv(h)), (local(i)); },
+        (0x21, &Immediate::Index(i)) | (0x22, &Immediate::Index(i)) => rreil!{ mov (local_lv(i)), (slot(h - 1, 64)); },
+        (0x23, &Immediate::Index(i)) => rreil!{ mov (slot_lv(h)), (global(i)); },
+        (0x24, &Immediate::Index(i)) => rreil!{ mov (global_lv(i)), (slot(h - 1, 64)); },
+
+        // memory
+        (op @ 0x28...0x35, &Immediate::Memory(_, off)) => {
+            let (bits, size, signed) = match op {
+                0x28 | 0x2a => (32, 32, false),
+                0x29 | 0x2b => (64, 64, false),
+                0x2c => (8, 32, true),
+                0x2d => (8, 32, false),
+                0x2e => (16, 32, true),
+                0x2f => (16, 32, false),
+                0x30 => (8, 64, true),
+                0x31 => (8, 64, false),
+                0x32 => (16, 64, true),
+                0x33 => (16, 64, false),
+                0x34 => (32, 64, true),
+                _ => (32, 64, false),
+            };
+            load(h, off, bits, size, signed)
+        }
+        (op @ 0x36...0x3e, &Immediate::Memory(_, off)) => {
+            let bits = match op {
+                0x36 | 0x38 | 0x3e => 32,
+                0x37 | 0x39 => 64,
+                0x3a | 0x3c => 8,
+                _ => 16,
+            };
+            store(h, off, bits)
+        }
+        (0x3f, _) => undefined(h),
+        (0x40, _) => undefined(h - 1),
+
+        // constants
+        (0x41...0x44, &Immediate::Constant(c)) => rreil!{ mov (slot_lv(h)), (constant(c, 64)); },
+
+        // integer comparison
+        (0x45, _) => eqz(h, 32),
+        (0x50, _) => eqz(h, 64),
+        (op @ 0x46...0x4f, _) | (op @ 0x51...0x5a, _) => {
+            let (size, op) = if op <= 0x4f { (32, op - 0x46) } else { (64, op - 0x51) };
+
+            match op {
+                0 => compare(h, size, Operation::Equal, false, false),
+                1 => compare(h, size, Operation::Equal, false, true),
+                2 => compare(h, size, Operation::LessSigned, false, false),
+                3 => compare(h, size, Operation::LessUnsigned, false, false),
+                4 => compare(h, size, Operation::LessSigned, true, false),
+                5 => compare(h, size, Operation::LessUnsigned, true, false),
+                6 => compare(h, size, Operation::LessOrEqualSigned, false, false),
+                7 => compare(h, size, Operation::LessOrEqualUnsigned, false, false),
+                8 => compare(h, size, Operation::LessOrEqualSigned, true, false),
+                _ => compare(h, size, Operation::LessOrEqualUnsigned, true, false),
+            }
+        }
+
+        // float comparison
+        (0x5b...0x66, _) => undefined(h - 2),
+
+        // integer arithmetic
+        (0x67...0x69, _) | (0x79...0x7b, _) => undefined(h - 1),
+        (op @ 0x6a...0x78, _) | (op @ 0x7c...0x8a, _) => {
+            let (size, op) = if op <= 0x78 { (32, op - 0x6a) } else { (64, op - 0x7c) };
+
+            match op {
+                0 => binop(h, size, Operation::Add),
+                1 => binop(h, size, Operation::Subtract),
+                2 => binop(h, size, Operation::Multiply),
+                3 => binop(h, size, Operation::DivideSigned),
+                4 => binop(h, size, Operation::DivideUnsigned),
+                5 => undefined(h - 2),
+                6 => binop(h, size, Operation::Modulo),
+                7 => binop(h, size, Operation::And),
+                8 => binop(h, size, Operation::InclusiveOr),
+                9 => binop(h, size, Operation::ExclusiveOr),
+                10 => shift_op(h, size, Operation::ShiftLeft),
+                11 => shift_op(h, size, Operation::ShiftRightSigned),
+                12 => shift_op(h, size, Operation::ShiftRightUnsigned),
+                13 => rotate(h, size, true),
+                _ => rotate(h, size, false),
+            }
+        }
+
+        // float arithmetic. RREIL has no floating point operations, only sign manipulation is
+        // lifted exactly.
+        (0x8b, _) => float_sign(h - 1, 32, None),
+        (0x99, _) => float_sign(h - 1, 64, None),
+        (0x8c, _) | (0x9a, _) => {
+            let size = if step.instr.opcode == 0x8c { 32 } else { 64 };
+            assign(slot_lv(h - 1), Operation::ExclusiveOr(slot(h - 1, size), constant(1 << (size - 1), size)), size)
+        }
+        (0x98, _) => float_sign(h - 2, 32, Some(slot(h - 1, 32))),
+        (0xa6, _) => float_sign(h - 2, 64, Some(slot(h - 1, 64))),
+        (0x8d...0x91, _) | (0x9b...0x9f, _) => undefined(h - 1),
+        (0x92...0x97, _) | (0xa0...0xa5, _) => undefined(h - 2),
+
+        // conversions
+        (0xa7, _) => rreil!{ zext/64 (slot_lv(h - 1)), (slot(h - 1, 32)); },
+        (0xac, _) => rreil!{ sext/64 (slot_lv(h - 1)), (slot(h - 1, 32)); },
+        (0xad, _) => rreil!{ zext/64 (slot_lv(h - 1)), (slot(h - 1, 32)); },
+        (0xbc...0xbf, _) => Ok(vec![]),
+        (0xa8...0xbb, _) => undefined(h - 1),
+        (0xc0, _) => extend(h, 8, 32),
+        (0xc1, _) => extend(h, 16, 32),
+        (0xc2, _) => extend(h, 8, 64),
+        (0xc3, _) => extend(h, 16, 64),
+        (0xc4, _) => extend(h, 32, 64),
+
+        // references
+        (0xd0, _) => rreil!{ mov (slot_lv(h)), [0]:64; },
+        (0xd1, _) => eqz(h, 64),
+        (0xd2, &Immediate::Index(f)) => rreil!{ mov (slot_lv(h)), (constant(f as u64, 64)); },
+
+        (0xfc00...0xfc07, _) => undefined(h - 1),
+
+        (op, _) => Err(format!("unsupported WebAssembly instruction {:#x}", op).into()),
+    }
+}
+
+/// Returns the disassembled instruction and, for conditional control flow, the flags its
+/// outgoing edges are guarded by.
+pub fn lift(cpu: &Cpu, step: &Step, address: u64) -> Result<(Mnemonic, Vec<Rvalue>)> {
+    let h = step.height;
+    let mut stmts = statements(cpu, step)?;
+    let mut flags = vec![];
+
+    match step.flow {
+        Flow::Next | Flow::Trap => {}
+        Flow::Jump(ref tgt) => stmts.extend(branch(tgt, h, None)?),
+        Flow::If(_) => {
+            stmts.extend(nonzero(h - 1)?);
+            flags.push(rreil_rvalue!{ flag:1 });
+        }
+        Flow::Branch(ref tgt) => {
+            let flag = rreil_rvalue!{ flag:1 };
+
+            stmts.extend(nonzero(h - 1)?);
+            stmts.extend(branch(tgt, h - 1, Some(&flag))?);
+            flags.push(flag);
+        }
+        Flow::Table(ref tgts, ref default) => {
+            let index = slot(h - 1, 32);
+
+            for (i, tgt) in tgts.iter().enumerate() {
+                let flag = variable(format!("case{}", i), 1);
+
+                stmts.push(
+                    Statement {
+                        op: Operation::Equal(index.clone(), constant(i as u64, 32)),
+                        assignee: Lvalue::from_rvalue(flag.clone()).unwrap(),
+                    }
+                );
+                stmts.extend(branch(tgt, h - 1, Some(&flag))?);
+                flags.push(flag);
+            }
+
+            let flag = rreil_rvalue!{ other:1 };
+
+            stmts.extend(rreil!{ cmpleu other:1, (constant(tgts.len() as u64, 32)), (index); }?);
+            stmts.extend(branch(default, h - 1, Some(&flag))?);
+            flags.push(flag);
+        }
+    }
+
+    let opcode = decode::name(step.instr.opcode).unwrap_or("unknown");
+    let (fmt, ops) = match step.instr.immediate {
+        Immediate::None => ("".to_string(), vec![]),
+        Immediate::Block(BlockType::Empty) => ("".to_string(), vec![]),
+        Immediate::Block(BlockType::Value(ty)) => (format!("(result {})", type_name(ty)), vec![]),
+        Immediate::Block(BlockType::Type(i)) => ("(type {u})".to_string(), vec![constant(i as u64, 32)]),
+        Immediate::Index(f) if step.instr.opcode == 0x10 => {
+            match cpu.module.body(f) {
+                Some(body) => ("{c:Module}".to_string(), vec![Rvalue::new_u64(body.start)]),
+                None => ("{u}".to_string(), vec![constant(f as u64, 32)]),
+            }
+        }
+        Immediate::Index(i) => ("{u}".to_string(), vec![constant(i as u64, 32)]),
+        Immediate::Table(ref tbl, default) => {
+            let ops = tbl.iter().chain(Some(&default)).map(|&l| constant(l as u64, 32)).collect::<Vec<_>>();
+            (vec!["{u}"; ops.len()].join(" "), ops)
+        }
+        Immediate::Indirect(t, _) => ("(type {u})".to_string(), vec![constant(t as u64, 32)]),
+        Immediate::Memory(_, 0) => ("".to_string(), vec![]),
+        Immediate::Memory(_, off) => ("offset={u}".to_string(), vec![constant(off as u64, 32)]),
+        Immediate::Constant(c) => {
+            match step.instr.opcode {
+                0x41 => ("{s}".to_string(), vec![constant(c, 32)]),
+                0x42 => ("{s}".to_string(), vec![constant(c, 64)]),
+                0x43 => ("{u}".to_string(), vec![constant(c, 32)]),
+                _ => ("{u}".to_string(), vec![constant(c, 64)]),
+            }
+        }
+    };
+    let len = step.instr.len as u64;
+    let mne = Mnemonic::from_parts(address..address + len, opcode.to_string(), &fmt, ops, stmts)?;
+
+    Ok((mne, flags))
+}
+
+fn type_name(ty: ::panopticon_core::wasm::ValueType) -> &'static str {
+    use panopticon_core::wasm::ValueType::*;
+
+    match ty {
+        I32 => "i32",
+        I64 => "i64",
+        F32 => "f32",
+        F64 => "f64",
+        V128 => "v128",
+        FuncRef => "funcref",
+        ExternRef => "externref",
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+extern crate panopticon_core;
+extern crate panopticon_wasm;
+extern crate panopticon_graph_algos;
+
+use panopticon_core::{Architecture, Function, Lvalue, Region};
+use panopticon_graph_algos::EdgeListGraphTrait;
+use panopticon_wasm::{Cpu, Wasm};
+use std::borrow::Cow;
+
+fn section(id: u8, content: Vec<u8>) -> Vec<u8> {
+    assert!(content.len() < 0x80);
+    let mut ret = vec![id, content.len() as u8];
+    ret.extend(content);
+    ret
+}
+
+// Module with the type `(i32) -> i32`, `() -> ()`, an import `env.f` of the latter and the
+// given functions of type 0 and 1.
+fn module(funcs: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut ret = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    let mut code = vec![funcs.len() as u8];
+    let mut types = vec![funcs.len() as u8];
+
+    for &(ty, body) in funcs.iter() {
+        types.push(ty);
+        code.push(body.len() as u8 + 1);
+        code.push(0);
+        code.extend_from_slice(body);
+    }
+
+    ret.extend(section(1, vec![0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00]));
+    ret.extend(section(2, vec![0x01, 0x03, b'e', b'n', b'v', 0x01, b'f', 0x00, 0x01]));
+    ret.extend(section(3, types));
+    ret.extend(section(10, code));
+    ret
+}
+
+fn function(bytes: &[u8], index: usize) -> (Function, u64) {
+    let cpu = Cpu::new(bytes).unwrap();
+    let start = cpu.module.bodies[index].start;
+    let reg = Region::wrap("Module".to_string(), bytes.to_vec());
+
+    (Function::new::<Wasm>(start, &reg, None, cpu).unwrap(), start)
+}
+
+fn starts(func: &Function, base: u64) -> Vec<u64> {
+    let mut ret = func.basic_blocks().map(|bb| bb.area.start - base).collect::<Vec<_>>();
+    ret.sort();
+    ret
+}
+
+#[test]
+fn if_else() {
+    let bytes = module(
+        &[
+            (
+                0,
+                &[
+                    0x20, 0x00, // local.get 0
+                    0x04, 0x7f, // if (result i32)
+                    0x41, 0x01, // i32.const 1
+                    0x05, // else
+                    0x41, 0x02, // i32.const 2
+                    0x0b, // end
+                    0x0b, // end
+                ],
+            ),
+        ],
+    );
+    let (func, start) = function(&bytes, 0);
+
+    assert_eq!(starts(&func, start), vec![0, 4, 7, 9]);
+    assert_eq!(func.cfg().num_edges(), 4);
+    assert_eq!(func.end() - start, 11);
+}
+
+#[test]
+fn loop_back_edge() {
+    let bytes = module(
+        &[
+            (
+                0,
+                &[
+                    0x03, 0x40, // loop
+                    0x20, 0x00, // local.get 0
+                    0x41, 0x01, // i32.const 1
+                    0x6b, // i32.sub
+                    0x22, 0x00, // local.tee 0
+                    0x0d, 0x00, // br_if 0
+                    0x0b, // end
+                    0x20, 0x00, // local.get 0
+                    0x0b, // end
+                ],
+            ),
+        ],
+    );
+    let (func, start) = function(&bytes, 0);
+
+    assert_eq!(starts(&func, start), vec![0, 2, 11]);
+    assert_eq!(func.cfg().num_edges(), 3);
+}
+
+#[test]
+fn branch_moves_results() {
+    let bytes = module(
+        &[
+            (
+                0,
+                &[
+                    0x02, 0x7f, // block (result i32)
+                    0x41, 0x07, // i32.const 7
+                    0x41, 0x01, // i32.const 1
+                    0x41, 0x00, // i32.const 0
+                    0x0d, 0x00, // br_if 0
+                    0x1a, // drop
+                    0x0b, // end
+                    0x0b, // end
+                ],
+            ),
+        ],
+    );
+    let cpu = Cpu::new(&bytes).unwrap();
+    let start = cpu.module.bodies[0].start;
+    let reg = Region::wrap("Module".to_string(), bytes.clone());
+    let m = Wasm::decode(&reg, start + 8, &cpu).unwrap();
+    let s0 = Lvalue::Variable { name: Cow::Borrowed("s0"), subscript: None, size: 64 };
+    assert_eq!(m.mnemonics[0].opcode, "br_if");
+    assert!(m.mnemonics[0].instructions.iter().any(|s| s.assignee == s0));
+    assert_eq!(m.jumps.len(), 2);
+
+    let (func, _) = function(&bytes, 0);
+    assert_eq!(starts(&func, start), vec![0, 10, 11]);
+}
+
+#[test]
+fn calls() {
+    let bytes = module(
+        &[
+            (
+                1,
+                &[
+                    0x10, 0x00, // call $env.f
+                    0x10, 0x02, // call 2
+                    0x0b, // end
+                ],
+            ),
+            (1, &[0x0b]),
+        ],
+    );
+    let cpu = Cpu::new(&bytes).unwrap();
+    let (func, _) = function(&bytes, 0);
+
+    assert_eq!(func.collect_call_addresses(), vec![cpu.module.bodies[1].start]);
+}
+
+#[test]
+fn statements() {
+    let bytes = module(
+        &[
+            (
+                0,
+                &[
+                    0x20, 0x00, // local.get 0
+                    0x41, 0x01, // i32.const 1
+                    0x6a, // i32.add
+                    0x0b, // end
+                ],
+            ),
+        ],
+    );
+    let cpu = Cpu::new(&bytes).unwrap();
+    let start = cpu.module.bodies[0].start;
+    let reg = Region::wrap("Module".to_string(), bytes.clone());
+
+    for off in [0, 2, 4, 5].iter() {
+        let m = Wasm::decode(&reg, start + off, &cpu).unwrap();
+
+        for s in m.mnemonics[0].instructions.iter() {
+            assert!(s.sanity_check().is_ok(), "{:?}", s);
+        }
+    }
+
+    let add = Wasm::decode(&reg, start + 4, &cpu).unwrap();
+    assert_eq!(add.mnemonics[0].opcode, "i32.add");
+    assert_eq!(format!("{}", add.mnemonics[0].instructions[0]), "add res:32, s0:32, s1:32");
+    assert!(Wasm::decode(&reg, start + 1, &cpu).is_err());
+}
@@ -0,0 +1,907 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use panopticon_core::{Architecture, Guard, Lvalue, Match, Region, Result, Rvalue, State, Statement};
+use semantic;
+use std::borrow::Cow;
+use syntax;
+
+#[derive(Clone,Debug)]
+pub enum Z80 {}
+
+impl Architecture for Z80 {
+    type Token = u8;
+    type Configuration = Variant;
+
+    fn prepare(reg: &Region, _: &Self::Configuration) -> Result<Vec<(&'static str, u64, &'static str)>> {
+        let iv = vec![
+            ("RESET", 0x0000, "Reset routine"),
+            ("INT", 0x0038, "Interrupt routine (mode 1)"),
+            ("NMI", 0x0066, "NMI routine"),
+        ];
+
+        Ok(iv.into_iter().filter(|v| v.1 < reg.size()).collect())
+    }
+
+    fn decode(reg: &Region, addr: u64, cfg: &Self::Configuration) -> Result<Match<Self>> {
+        info!("disass @ {:x}", addr);
+        let disass = syntax::disassembler();
+
+        if let Some(st) = disass.next_match(&mut reg.iter().seek(addr), addr, cfg.clone()) {
+            info!("    res: {:?}", st);
+            Ok(st.into())
+        } else {
+            Err("Unrecognized instruction".into())
+        }
+    }
+}
+
+// 8 bit registers
+lazy_static! {
+    pub static ref A: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("A"), size: 8, subscript: None };
+    pub static ref B: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("B"), size: 8, subscript: None };
+    pub static ref C: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("C"), size: 8, subscript: None };
+    pub static ref D: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("D"), size: 8, subscript: None };
+    pub static ref E: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("E"), size: 8, subscript: None };
+    pub static ref H: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("H"), size: 8, subscript: None };
+    pub static ref L: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("L"), size: 8, subscript: None };
+}
+
+// Interrupt vector and memory refresh registers
+lazy_static! {
+    pub static ref I: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("I"), size: 8, subscript: None };
+    pub static ref R: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("R"), size: 8, subscript: None };
+}
+
+// 16 bit index registers and stack pointer
+lazy_static! {
+    pub static ref IX: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("IX"), size: 16, subscript: None };
+    pub static ref IY: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("IY"), size: 16, subscript: None };
+    pub static ref SP: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("SP"), size: 16, subscript: None };
+}
+
+// flags. Bits 3 and 5 of F are not modeled.
+lazy_static! {
+    pub static ref SF: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("SF"), size: 1, subscript: None };
+    pub static ref ZF: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("ZF"), size: 1, subscript: None };
+    pub static ref HF: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("HF"), size: 1, subscript: None };
+    pub static ref PV: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("PV"), size: 1, subscript: None };
+    pub static ref NF: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("NF"), size: 1, subscript: None };
+    pub static ref CF: Lvalue = Lvalue::Variable{ name: Cow::Borrowed("CF"), size: 1, subscript: None };
+}
+
+/// Returns the register of the alternate set corresponding to `reg`.
+pub fn alternate(reg: &Lvalue) -> Lvalue {
+    if let &Lvalue::Variable { ref name, size, .. } = reg {
+        Lvalue::Variable { name: Cow::Owned(format!("{}'", name)), size: size, subscript: None }
+    } else {
+        unreachable!()
+    }
+}
+
+/// 16 bit register pairs.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Pair {
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    IX,
+    IY,
+}
+
+impl Pair {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pair::AF => "AF",
+            Pair::BC => "BC",
+            Pair::DE => "DE",
+            Pair::HL => "HL",
+            Pair::SP => "SP",
+            Pair::IX => "IX",
+            Pair::IY => "IY",
+        }
+    }
+
+    /// Register pair used for display.
+    pub fn rvalue(&self) -> Rvalue {
+        Rvalue::Variable { name: Cow::Borrowed(self.name()), size: 16, offset: 0, subscript: None }
+    }
+
+    /// High and low register of BC, DE and HL.
+    pub fn halves(&self) -> Option<(&'static Lvalue, &'static Lvalue)> {
+        match *self {
+            Pair::BC => Some((&*B, &*C)),
+            Pair::DE => Some((&*D, &*E)),
+            Pair::HL => Some((&*H, &*L)),
+            _ => None,
+        }
+    }
+
+    /// The 16 bit register of SP, IX and IY.
+    pub fn register(&self) -> Option<&'static Lvalue> {
+        match *self {
+            Pair::SP => Some(&*SP),
+            Pair::IX => Some(&*IX),
+            Pair::IY => Some(&*IY),
+            _ => None,
+        }
+    }
+}
+
+/// Memory or I/O address.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Address {
+    /// `(BC)`, `(DE)`, `(HL)` and `(SP)`
+    Pair(Pair),
+    /// `(IX+d)` and `(IY+d)`
+    Indexed(Pair, i8),
+    /// `(nn)`
+    Absolute(u16),
+    /// `(n)` of `IN` and `OUT`. The upper half of the port address is A
+    Port(u8),
+}
+
+/// Decoded operand.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Operand {
+    /// 8 bit register
+    Register(Lvalue),
+    /// 16 bit register pair
+    Pair(Pair),
+    Immediate(Rvalue),
+    /// Memory access of the given size in bits
+    Memory(Address, usize),
+    /// 8 bit I/O port access
+    Port(Address),
+}
+
+impl Operand {
+    /// Format string and mnemonic arguments of the operand.
+    pub fn format(&self) -> (String, Vec<Rvalue>) {
+        match self {
+            &Operand::Register(ref reg) => ("{u}".to_string(), vec![reg.clone().into()]),
+            &Operand::Pair(p) => ("{u}".to_string(), vec![p.rvalue()]),
+            &Operand::Immediate(ref v) => ("{u}".to_string(), vec![v.clone()]),
+            &Operand::Memory(Address::Absolute(a), _) => ("({p:ram})".to_string(), vec![Rvalue::new_u16(a)]),
+            &Operand::Memory(Address::Indexed(p, d), _) => {
+                let sign = if d < 0 { "-" } else { "+" };
+                (format!("({{u}}{}{:x})", sign, (d as i16).abs()), vec![p.rvalue()])
+            }
+            &Operand::Memory(Address::Pair(p), _) => ("({u})".to_string(), vec![p.rvalue()]),
+            &Operand::Port(Address::Port(n)) => ("({u})".to_string(), vec![Rvalue::new_u8(n)]),
+            &Operand::Port(_) => ("({u})".to_string(), vec![C.clone().into()]),
+            &Operand::Memory(Address::Port(_), _) => unreachable!(),
+        }
+    }
+}
+
+/// Operand encodings. Resolved into `Operand` after the instruction matched.
+#[derive(Clone,Copy,Debug)]
+pub enum Arg {
+    /// 8 bit register in the capture group. Index 6 is invalid.
+    Reg(&'static str),
+    /// Like `Reg` but index 6 is `(HL)` or `(IX+d)`/`(IY+d)` if prefixed.
+    RegMem(&'static str),
+    /// Fixed 8 bit register
+    Fixed(&'static Lvalue),
+    /// BC, DE, HL or SP in the capture group. HL is replaced by the index register if prefixed.
+    Pair(&'static str),
+    /// Like `Pair` but with AF instead of SP
+    Stack(&'static str),
+    /// Fixed register pair. HL is replaced by the index register if prefixed.
+    Fix(Pair),
+    /// Memory of the given size pointed to by a fixed register pair. Index registers replace HL.
+    Ind(Pair, usize),
+    Imm8,
+    Imm16,
+    /// Memory of the given size at a 16 bit immediate address
+    Abs(usize),
+    /// I/O port in the 8 bit immediate
+    Port,
+    /// I/O port in BC
+    PortC,
+}
+
+impl Arg {
+    pub fn resolve(&self, st: &State<Z80>) -> Option<Operand> {
+        let cfg = &st.configuration;
+        let hl = cfg.index.unwrap_or(Pair::HL);
+        let subst = |p: Pair| if p == Pair::HL { hl } else { p };
+
+        match *self {
+            Arg::Reg(grp) | Arg::RegMem(grp) => {
+                match st.get_group(grp) {
+                    0 => Some(Operand::Register(B.clone())),
+                    1 => Some(Operand::Register(C.clone())),
+                    2 => Some(Operand::Register(D.clone())),
+                    3 => Some(Operand::Register(E.clone())),
+                    4 => Some(Operand::Register(H.clone())),
+                    5 => Some(Operand::Register(L.clone())),
+                    6 => {
+                        match (*self, cfg.index, cfg.disp) {
+                            (Arg::Reg(_), _, _) => None,
+                            (_, Some(p), Some(d)) => Some(Operand::Memory(Address::Indexed(p, d), 8)),
+                            (_, None, _) => Some(Operand::Memory(Address::Pair(Pair::HL), 8)),
+                            _ => None,
+                        }
+                    }
+                    7 => Some(Operand::Register(A.clone())),
+                    _ => None,
+                }
+            }
+            Arg::Fixed(reg) => Some(Operand::Register(reg.clone())),
+            Arg::Pair(grp) => {
+                match st.get_group(grp) {
+                    0 => Some(Operand::Pair(Pair::BC)),
+                    1 => Some(Operand::Pair(Pair::DE)),
+                    2 => Some(Operand::Pair(hl)),
+                    3 => Some(Operand::Pair(Pair::SP)),
+                    _ => None,
+                }
+            }
+            Arg::Stack(grp) => {
+                match st.get_group(grp) {
+                    0 => Some(Operand::Pair(Pair::BC)),
+                    1 => Some(Operand::Pair(Pair::DE)),
+                    2 => Some(Operand::Pair(hl)),
+                    3 => Some(Operand::Pair(Pair::AF)),
+                    _ => None,
+                }
+            }
+            Arg::Fix(p) => Some(Operand::Pair(subst(p))),
+            Arg::Ind(p, sz) => Some(Operand::Memory(Address::Pair(subst(p)), sz)),
+            Arg::Imm8 => cfg.imm.map(|x| Operand::Immediate(Rvalue::new_u8(x as u8))),
+            Arg::Imm16 => cfg.imm.map(|x| Operand::Immediate(Rvalue::new_u16(x))),
+            Arg::Abs(sz) => cfg.imm.map(|x| Operand::Memory(Address::Absolute(x), sz)),
+            Arg::Port => cfg.imm.map(|x| Operand::Port(Address::Port(x as u8))),
+            Arg::PortC => Some(Operand::Port(Address::Pair(Pair::BC))),
+        }
+    }
+}
+
+#[derive(Clone,Debug,Hash)]
+pub struct Variant {
+    /// 8 or 16 bit immediate
+    pub imm: Option<u16>,
+    /// Displacement of `(IX+d)`/`(IY+d)` or offset of relative jumps
+    pub disp: Option<i8>,
+    /// Index register selected by a DD or FD prefix
+    pub index: Option<Pair>,
+}
+
+impl Variant {
+    pub fn z80() -> Variant {
+        Variant { imm: None, disp: None, index: None }
+    }
+}
+
+fn resolve(st: &State<Z80>, args: &[Arg]) -> Option<Vec<Operand>> {
+    args.iter().map(|a| a.resolve(st)).collect()
+}
+
+fn format(ops: &[Operand]) -> (String, Vec<Rvalue>) {
+    let mut fmt = vec![];
+    let mut args = vec![];
+
+    for op in ops.iter() {
+        let (f, mut a) = op.format();
+
+        fmt.push(f);
+        args.append(&mut a);
+    }
+
+    (fmt.join(", "), args)
+}
+
+fn condition(cc: u64) -> (&'static str, Guard) {
+    let (name, flag, set) = match cc {
+        0 => ("nz", &*ZF, false),
+        1 => ("z", &*ZF, true),
+        2 => ("nc", &*CF, false),
+        3 => ("c", &*CF, true),
+        4 => ("po", &*PV, false),
+        5 => ("pe", &*PV, true),
+        6 => ("p", &*SF, false),
+        _ => ("m", &*SF, true),
+    };
+    let g = Guard::from_flag(&flag.clone().into()).unwrap();
+
+    (name, if set { g } else { g.negation() })
+}
+
+fn fallthru(st: &State<Z80>) -> Rvalue {
+    Rvalue::new_u16((st.address + st.tokens.len() as u64) as u16)
+}
+
+// No arguments
+pub fn nonary(opcode: &'static str, sem: fn(&mut Variant) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    literal(opcode, "", sem)
+}
+
+// Fixed operand string
+pub fn literal(opcode: &'static str, fmt: &'static str, sem: fn(&mut Variant) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let len = st.tokens.len();
+            let next = fallthru(st);
+
+            st.mnemonic(len, opcode, fmt, vec![], &|c| sem(c)).unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// LD and friends. Copies `src` to `dst`
+pub fn load(opcode: &'static str, dst: Arg, src: Arg) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[dst, src]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+
+            if ops.iter().all(|o| if let &Operand::Memory(..) = o { true } else { false }) {
+                return false;
+            }
+
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = format(&ops);
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|_| -> Result<Vec<Statement>> {
+                        let (mut stmts, val) = semantic::read(&ops[1])?;
+                        stmts.append(&mut semantic::write(&ops[0], val)?);
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// Read-modify-write of a single operand
+pub fn unary(opcode: &'static str, arg: Arg, sem: fn(&mut Variant, Lvalue) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[arg]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = format(&ops);
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|c| -> Result<Vec<Statement>> {
+                        let (mut stmts, place, mut post) = semantic::place(&ops[0])?;
+                        stmts.append(&mut sem(c, place)?);
+                        stmts.append(&mut post);
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// Instructions only reading their operand. A is the implied destination of arithmetic ops
+pub fn source(opcode: &'static str, src: Arg, sem: fn(&mut Variant, Rvalue) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[src]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = match opcode {
+                "add" | "adc" | "sbc" => format(&[Operand::Register(A.clone()), ops[0].clone()]),
+                _ => format(&ops),
+            };
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|c| -> Result<Vec<Statement>> {
+                        let (mut stmts, val) = semantic::read(&ops[0])?;
+                        stmts.append(&mut sem(c, val)?);
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// Instructions only writing their operand
+pub fn sink(opcode: &'static str, dst: Arg, sem: fn(&mut Variant) -> Result<(Vec<Statement>, Rvalue)>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[dst]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = format(&ops);
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|c| -> Result<Vec<Statement>> {
+                        let (mut stmts, val) = sem(c)?;
+                        stmts.append(&mut semantic::write(&ops[0], val)?);
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// Modifies `dst` using `src`
+pub fn binary(opcode: &'static str, dst: Arg, src: Arg, sem: fn(&mut Variant, Lvalue, Rvalue) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[dst, src]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = format(&ops);
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|c| -> Result<Vec<Statement>> {
+                        let (mut stmts, val) = semantic::read(&ops[1])?;
+                        let (mut pre, place, mut post) = semantic::place(&ops[0])?;
+                        stmts.append(&mut pre);
+                        stmts.append(&mut sem(c, place, val)?);
+                        stmts.append(&mut post);
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// BIT, RES and SET. The bit index is in capture group "b"
+pub fn single_bit(opcode: &'static str, arg: Arg, sem: fn(&mut Variant, Lvalue, usize) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[arg]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+            let b = st.get_group("b") as usize;
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = format(&[Operand::Immediate(Rvalue::new_u8(b as u8)), ops[0].clone()]);
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|c| -> Result<Vec<Statement>> {
+                        let (mut stmts, place, mut post) = semantic::place(&ops[0])?;
+                        stmts.append(&mut sem(c, place, b)?);
+
+                        // BIT only tests
+                        if opcode != "bit" {
+                            stmts.append(&mut post);
+                        }
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// EX
+pub fn exchange(opcode: &'static str, a: Arg, b: Arg) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let ops = match resolve(st, &[a, b]) {
+                Some(ops) => ops,
+                None => return false,
+            };
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let (fmt, args) = format(&ops);
+
+            st.mnemonic(
+                    len,
+                    opcode,
+                    &fmt,
+                    args,
+                    &|_| -> Result<Vec<Statement>> {
+                        let (mut stmts, x) = semantic::read(&ops[0])?;
+                        let (mut y_stmts, y) = semantic::read(&ops[1])?;
+
+                        stmts.append(&mut y_stmts);
+                        stmts.append(&mut semantic::write(&ops[0], y)?);
+                        stmts.append(&mut semantic::write(&ops[1], x)?);
+                        Ok(stmts)
+                    },
+                )
+                .unwrap();
+            st.jump(next, Guard::always()).unwrap();
+            true
+        }
+    )
+}
+
+// Block instructions repeating while `flag` is equal to `set`
+pub fn repeat(opcode: &'static str, sem: fn(&mut Variant) -> Result<Vec<Statement>>, flag: Rvalue, set: bool) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let len = st.tokens.len();
+            let next = fallthru(st);
+            let this = Rvalue::new_u16(st.address as u16);
+            let g = Guard::from_flag(&flag).unwrap();
+            let g = if set { g } else { g.negation() };
+
+            st.mnemonic(len, opcode, "", vec![], &|c| sem(c)).unwrap();
+            st.jump(this, g.clone()).unwrap();
+            st.jump(next, g.negation()).unwrap();
+            true
+        }
+    )
+}
+
+// JP nn and JP cc,nn. The condition is in capture group "c"
+pub fn jump(st: &mut State<Z80>) -> bool {
+    let target = Rvalue::new_u16(st.configuration.imm.unwrap());
+    let len = st.tokens.len();
+    let next = fallthru(st);
+
+    if st.has_group("c") {
+        let (cc, g) = condition(st.get_group("c"));
+
+        st.mnemonic(len, "jp", &format!("{}, {{c:ram}}", cc), vec![target.clone()], &|_| Ok(vec![])).unwrap();
+        st.jump(next, g.negation()).unwrap();
+        st.jump(target, g).unwrap();
+    } else {
+        st.mnemonic(len, "jp", "{c:ram}", vec![target.clone()], &|_| Ok(vec![])).unwrap();
+        st.jump(target, Guard::always()).unwrap();
+    }
+    true
+}
+
+// JR e and JR cc,e. The condition is in the two bit capture group "c"
+pub fn jump_relative(st: &mut State<Z80>) -> bool {
+    let len = st.tokens.len();
+    let next = fallthru(st);
+    let k = (st.address as u16).wrapping_add(len as u16).wrapping_add(st.configuration.disp.unwrap() as i16 as u16);
+    let target = Rvalue::new_u16(k);
+
+    if st.has_group("c") {
+        let (cc, g) = condition(st.get_group("c"));
+
+        st.mnemonic(len, "jr", &format!("{}, {{c:ram}}", cc), vec![target.clone()], &|_| Ok(vec![])).unwrap();
+        st.jump(next, g.negation()).unwrap();
+        st.jump(target, g).unwrap();
+    } else {
+        st.mnemonic(len, "jr", "{c:ram}", vec![target.clone()], &|_| Ok(vec![])).unwrap();
+        st.jump(target, Guard::always()).unwrap();
+    }
+    true
+}
+
+// DJNZ e
+pub fn decrement_jump(st: &mut State<Z80>) -> bool {
+    let len = st.tokens.len();
+    let next = fallthru(st);
+    let k = (st.address as u16).wrapping_add(len as u16).wrapping_add(st.configuration.disp.unwrap() as i16 as u16);
+    let target = Rvalue::new_u16(k);
+    let g = Guard::from_flag(&rreil_rvalue!{ nz:1 }).unwrap();
+
+    st.mnemonic(len, "djnz", "{c:ram}", vec![target.clone()], &|c| semantic::djnz(c)).unwrap();
+    st.jump(next, g.negation()).unwrap();
+    st.jump(target, g).unwrap();
+    true
+}
+
+// JP (HL), JP (IX) and JP (IY)
+pub fn jump_indirect(st: &mut State<Z80>) -> bool {
+    let p = st.configuration.index.unwrap_or(Pair::HL);
+    let len = st.tokens.len();
+    let target = match p.register() {
+        Some(reg) => reg.clone().into(),
+        None => rreil_rvalue!{ hl:16 },
+    };
+
+    st.mnemonic(len, "jp", "({u})", vec![p.rvalue()], &|_| -> Result<Vec<Statement>> { Ok(semantic::read_pair(p)?.0) }).unwrap();
+    st.jump(target, Guard::always()).unwrap();
+    true
+}
+
+// CALL nn and CALL cc,nn. The condition is in capture group "c"
+pub fn call(st: &mut State<Z80>) -> bool {
+    let target = Rvalue::new_u16(st.configuration.imm.unwrap());
+    let len = st.tokens.len();
+    let next = fallthru(st);
+    let fmt = if st.has_group("c") {
+        format!("{}, {{c:ram}}", condition(st.get_group("c")).0)
+    } else {
+        "{c:ram}".to_string()
+    };
+
+    st.mnemonic(
+            len,
+            "call",
+            &fmt,
+            vec![target.clone()],
+            &|_| -> Result<Vec<Statement>> {
+                rreil!{
+            call (target);
+        }
+            },
+        )
+        .unwrap();
+    st.jump(next, Guard::always()).unwrap();
+    true
+}
+
+// RST p. The target is in capture group "t"
+pub fn rst(st: &mut State<Z80>) -> bool {
+    let target = Rvalue::new_u16(st.get_group("t") as u16 * 8);
+    let len = st.tokens.len();
+    let next = fallthru(st);
+
+    st.mnemonic(
+            len,
+            "rst",
+            "{c:ram}",
+            vec![target.clone()],
+            &|_| -> Result<Vec<Statement>> {
+                rreil!{
+            call (target);
+        }
+            },
+        )
+        .unwrap();
+    st.jump(next, Guard::always()).unwrap();
+    true
+}
+
+// RET, RETI, RETN and RET cc. The condition is in capture group "c"
+pub fn ret(opcode: &'static str, sem: fn(&mut Variant) -> Result<Vec<Statement>>) -> Box<Fn(&mut State<Z80>) -> bool> {
+    Box::new(
+        move |st: &mut State<Z80>| -> bool {
+            let len = st.tokens.len();
+
+            if st.has_group("c") {
+                let next = fallthru(st);
+                let (cc, g) = condition(st.get_group("c"));
+
+                st.mnemonic(len, opcode, cc, vec![], &|c| sem(c)).unwrap();
+                st.jump(next, g.negation()).unwrap();
+            } else {
+                st.mnemonic(len, opcode, "", vec![], &|c| sem(c)).unwrap();
+            }
+            true
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::syntax::disassembler;
+    use panopticon_core::{MnemonicFormatToken, Operation, Region, Rvalue, State};
+
+    fn decode(bytes: Vec<u8>) -> State<Z80> {
+        let reg = Region::wrap("base".to_string(), bytes);
+        let mut i = reg.iter().seek(0);
+
+        disassembler().next_match(&mut i, 0, Variant::z80()).unwrap()
+    }
+
+    fn reg(r: &Lvalue) -> Rvalue {
+        r.clone().into()
+    }
+
+    #[test]
+    fn all() {
+        let test_vectors = vec![
+            (vec![0x00], "nop", vec![]),
+            (vec![0x76], "halt", vec![]),
+
+            // LD
+            (vec![0x41], "ld", vec![reg(&*B), reg(&*C)]),
+            (vec![0x3e, 0x12], "ld", vec![reg(&*A), rreil_rvalue!{ [0x12]:8 }]),
+            (vec![0x7e], "ld", vec![reg(&*A), Pair::HL.rvalue()]),
+            (vec![0x21, 0x34, 0x12], "ld", vec![Pair::HL.rvalue(), rreil_rvalue!{ [0x1234]:16 }]),
+            (vec![0x3a, 0x00, 0x40], "ld", vec![reg(&*A), rreil_rvalue!{ [0x4000]:16 }]),
+            (vec![0xed, 0x43, 0x00, 0x40], "ld", vec![rreil_rvalue!{ [0x4000]:16 }, Pair::BC.rvalue()]),
+            (vec![0xed, 0x57], "ld", vec![reg(&*A), reg(&*I)]),
+            (vec![0xdd, 0x7e, 0xfe], "ld", vec![reg(&*A), Pair::IX.rvalue()]),
+            (vec![0xfd, 0x70, 0x05], "ld", vec![Pair::IY.rvalue(), reg(&*B)]),
+            (vec![0xfd, 0x36, 0x05, 0x42], "ld", vec![Pair::IY.rvalue(), rreil_rvalue!{ [0x42]:8 }]),
+            (vec![0xdd, 0x21, 0x00, 0x80], "ld", vec![Pair::IX.rvalue(), rreil_rvalue!{ [0x8000]:16 }]),
+            (vec![0xfd, 0xf9], "ld", vec![Pair::SP.rvalue(), Pair::IY.rvalue()]),
+
+            // PUSH, POP, EX
+            (vec![0xc5], "push", vec![Pair::BC.rvalue()]),
+            (vec![0xf1], "pop", vec![Pair::AF.rvalue()]),
+            (vec![0xdd, 0xe5], "push", vec![Pair::IX.rvalue()]),
+            (vec![0xeb], "ex", vec![Pair::DE.rvalue(), Pair::HL.rvalue()]),
+            (vec![0xfd, 0xe3], "ex", vec![Pair::SP.rvalue(), Pair::IY.rvalue()]),
+            (vec![0x08], "ex", vec![]),
+            (vec![0xd9], "exx", vec![]),
+
+            // Arithmetic
+            (vec![0x80], "add", vec![reg(&*A), reg(&*B)]),
+            (vec![0x96], "sub", vec![Pair::HL.rvalue()]),
+            (vec![0xfe, 0x10], "cp", vec![rreil_rvalue!{ [0x10]:8 }]),
+            (vec![0xdd, 0xae, 0x01], "xor", vec![Pair::IX.rvalue()]),
+            (vec![0x3c], "inc", vec![reg(&*A)]),
+            (vec![0xfd, 0x35, 0x00], "dec", vec![Pair::IY.rvalue()]),
+            (vec![0x09], "add", vec![Pair::HL.rvalue(), Pair::BC.rvalue()]),
+            (vec![0xdd, 0x29], "add", vec![Pair::IX.rvalue(), Pair::IX.rvalue()]),
+            (vec![0xed, 0x52], "sbc", vec![Pair::HL.rvalue(), Pair::DE.rvalue()]),
+            (vec![0x33], "inc", vec![Pair::SP.rvalue()]),
+            (vec![0xed, 0x44], "neg", vec![]),
+            (vec![0x27], "daa", vec![]),
+
+            // Rotates and bits
+            (vec![0x07], "rlca", vec![]),
+            (vec![0xcb, 0x11], "rl", vec![reg(&*C)]),
+            (vec![0xcb, 0x3e], "srl", vec![Pair::HL.rvalue()]),
+            (vec![0xdd, 0xcb, 0x03, 0x06], "rlc", vec![Pair::IX.rvalue()]),
+            (vec![0xcb, 0x7f], "bit", vec![rreil_rvalue!{ [7]:8 }, reg(&*A)]),
+            (vec![0xcb, 0x86], "res", vec![rreil_rvalue!{ [0]:8 }, Pair::HL.rvalue()]),
+            (vec![0xfd, 0xcb, 0x02, 0xce], "set", vec![rreil_rvalue!{ [1]:8 }, Pair::IY.rvalue()]),
+            (vec![0xed, 0x6f], "rld", vec![]),
+
+            // Block instructions
+            (vec![0xed, 0xb0], "ldir", vec![]),
+            (vec![0xed, 0xa1], "cpi", vec![]),
+            (vec![0xed, 0xb3], "otir", vec![]),
+
+            // I/O
+            (vec![0xdb, 0xfe], "in", vec![reg(&*A), rreil_rvalue!{ [0xfe]:8 }]),
+            (vec![0xed, 0x78], "in", vec![reg(&*A), reg(&*C)]),
+            (vec![0xed, 0x41], "out", vec![reg(&*C), reg(&*B)]),
+
+            // Control flow
+            (vec![0xc3, 0x00, 0x10], "jp", vec![rreil_rvalue!{ [0x1000]:16 }]),
+            (vec![0xca, 0x00, 0x10], "jp", vec![rreil_rvalue!{ [0x1000]:16 }]),
+            (vec![0xdd, 0xe9], "jp", vec![Pair::IX.rvalue()]),
+            (vec![0x18, 0x02], "jr", vec![rreil_rvalue!{ [4]:16 }]),
+            (vec![0x10, 0xfe], "djnz", vec![rreil_rvalue!{ [0]:16 }]),
+            (vec![0xcd, 0x34, 0x12], "call", vec![rreil_rvalue!{ [0x1234]:16 }]),
+            (vec![0xff], "rst", vec![rreil_rvalue!{ [0x38]:16 }]),
+            (vec![0xc9], "ret", vec![]),
+            (vec![0xed, 0x4d], "reti", vec![]),
+        ];
+
+        for (bytes, opname, args) in test_vectors {
+            println!("check '{}' {:?}", opname, bytes);
+
+            let l = bytes.len();
+            let st = decode(bytes);
+
+            assert_eq!(st.mnemonics.len(), 1);
+
+            let mne = &st.mnemonics[0];
+
+            assert_eq!(opname, mne.opcode);
+            assert_eq!(mne.area.start, 0);
+            assert_eq!(mne.area.end, l as u64);
+            assert_eq!(mne.operands, args);
+
+            for s in mne.instructions.iter() {
+                assert!(s.sanity_check().is_ok(), "{:?}", s);
+            }
+        }
+    }
+
+    #[test]
+    fn prefixes() {
+        // DD w/o a following IX instruction
+        let st = decode(vec![0xdd, 0x00]);
+        assert_eq!(st.mnemonics[0].opcode, "unk");
+        assert_eq!(st.mnemonics[0].area.end, 1);
+
+        // Undocumented IN (C)
+        let st = decode(vec![0xed, 0x70]);
+        assert_eq!(st.mnemonics[0].opcode, "unk");
+
+        let st = decode(vec![0xdd, 0x7e, 0xfe]);
+        let fmt = st.mnemonics[0].format_string.iter().filter_map(
+            |t| if let &MnemonicFormatToken::Literal(c) = t { Some(c) } else { None },
+        ).collect::<String>();
+        assert_eq!(fmt, ", (-2)");
+    }
+
+    #[test]
+    fn jumps() {
+        let st = decode(vec![0x20, 0xfe]);
+        assert_eq!(st.mnemonics[0].opcode, "jr");
+        assert_eq!(st.jumps.len(), 2);
+        assert_eq!(st.jumps[0].1, Rvalue::new_u16(2));
+        assert_eq!(st.jumps[1].1, Rvalue::new_u16(0));
+        assert_eq!(st.jumps[0].2, st.jumps[1].2.negation());
+
+        // LDIR jumps to itself until BC is zero
+        let st = decode(vec![0xed, 0xb0]);
+        assert_eq!(st.jumps.len(), 2);
+        assert_eq!(st.jumps[0].1, Rvalue::new_u16(0));
+        assert_eq!(st.jumps[1].1, Rvalue::new_u16(2));
+
+        let st = decode(vec![0xcd, 0x34, 0x12]);
+        assert_eq!(st.jumps, vec![(0, Rvalue::new_u16(3), Guard::always())]);
+        assert!(st.mnemonics[0].instructions.iter().any(|s| if let Operation::Call(_) = s.op { true } else { false }));
+
+        assert!(decode(vec![0xc9]).jumps.is_empty());
+        assert_eq!(decode(vec![0xc0]).jumps.len(), 1);
+        assert_eq!(decode(vec![0xe9]).jumps[0].1, rreil_rvalue!{ hl:16 });
+    }
+
+    #[test]
+    fn alternate_registers() {
+        let st = decode(vec![0xd9]);
+        let names = st.mnemonics[0]
+            .instructions
+            .iter()
+            .filter_map(|s| if let Lvalue::Variable { ref name, .. } = s.assignee { Some(name.to_string()) } else { None })
+            .collect::<Vec<_>>();
+
+        for r in ["B'", "C'", "D'", "E'", "H'", "L'"].iter() {
+            assert!(names.iter().any(|n| n == r), "{} not written", r);
+        }
+    }
+}
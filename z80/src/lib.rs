@@ -0,0 +1,40 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+//! Zilog Z80 disassembler.
+//!
+//! This disassembler handles the documented instruction set of the Zilog Z80 including the
+//! `CB`, `ED`, `DD`/`FD` (IX/IY) and `DDCB`/`FDCB` prefixed opcodes. The alternate register set
+//! is modeled as a second set of registers (`A'`, `B'`, ..., `CF'`) that `EX AF,AF'` and `EXX`
+//! swap with the primary one. The undocumented `SLL` is decoded as `sll!`.
+
+#![allow(missing_docs)]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate panopticon_core;
+#[macro_use]
+extern crate lazy_static;
+
+mod syntax;
+mod semantic;
+
+mod disassembler;
+pub use disassembler::{Z80, Variant};
@@ -0,0 +1,880 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use disassembler::*;
+use panopticon_core::{Lvalue, Result, Rvalue, Statement};
+use std::borrow::Cow;
+
+fn temp(name: &'static str, size: usize) -> Lvalue {
+    Lvalue::Variable { name: Cow::Borrowed(name), size: size, subscript: None }
+}
+
+/// Reads the register pair `p`. BC, DE, HL and AF are assembled into a temporary named after the
+/// pair.
+pub fn read_pair(p: Pair) -> Result<(Vec<Statement>, Rvalue)> {
+    if let Some(reg) = p.register() {
+        return Ok((vec![], reg.clone().into()));
+    }
+
+    let (name, mut stmts, high, low) = match p {
+        Pair::AF => {
+            let stmts = rreil!{
+                zext/8 f:8, CF:1;
+                sel/1 f:8, NF:1;
+                sel/2 f:8, PV:1;
+                sel/3 f:8, ?;
+                sel/4 f:8, HF:1;
+                sel/5 f:8, ?;
+                sel/6 f:8, ZF:1;
+                sel/7 f:8, SF:1;
+            }?;
+            ("af", stmts, A.clone(), rreil_lvalue!{ f:8 })
+        }
+        Pair::BC => ("bc", vec![], B.clone(), C.clone()),
+        Pair::DE => ("de", vec![], D.clone(), E.clone()),
+        Pair::HL => ("hl", vec![], H.clone(), L.clone()),
+        _ => unreachable!(),
+    };
+    let res = temp(name, 16);
+
+    stmts.append(
+        &mut rreil!{
+        zext/16 (res), (low);
+        zext/16 hi:16, (high);
+        shl hi:16, hi:16, [8]:16;
+        or (res), (res), hi:16;
+    }?
+    );
+
+    Ok((stmts, res.into()))
+}
+
+/// Writes the 16 bit value `v` to the register pair `p`.
+pub fn write_pair(p: Pair, v: Rvalue) -> Result<Vec<Statement>> {
+    if let Some(reg) = p.register() {
+        return rreil!{
+            mov (reg), (v);
+        };
+    }
+
+    match p {
+        Pair::AF => {
+            rreil!{
+                mov CF:1, (v.extract(1, 0)?);
+                mov NF:1, (v.extract(1, 1)?);
+                mov PV:1, (v.extract(1, 2)?);
+                mov HF:1, (v.extract(1, 4)?);
+                mov ZF:1, (v.extract(1, 6)?);
+                mov SF:1, (v.extract(1, 7)?);
+                mov A:8, (v.extract(8, 8)?);
+            }
+        }
+        _ => {
+            let (hi, lo) = p.halves().unwrap();
+
+            rreil!{
+                mov (lo), (v.extract(8, 0)?);
+                mov (hi), (v.extract(8, 8)?);
+            }
+        }
+    }
+}
+
+/// Computes the memory or I/O address `a`.
+pub fn address(a: Address) -> Result<(Vec<Statement>, Rvalue)> {
+    match a {
+        Address::Pair(p) => read_pair(p),
+        Address::Indexed(p, d) => {
+            let reg = p.register().unwrap();
+            let disp = Rvalue::new_u16(d as i16 as u16);
+            let stmts = rreil!{
+                add addr:16, (reg), (disp);
+            }?;
+
+            Ok((stmts, rreil_rvalue!{ addr:16 }))
+        }
+        Address::Absolute(n) => Ok((vec![], Rvalue::new_u16(n))),
+        Address::Port(n) => {
+            let stmts = rreil!{
+                zext/16 addr:16, A:8;
+                shl addr:16, addr:16, [8]:16;
+                or addr:16, addr:16, [n]:16;
+            }?;
+
+            Ok((stmts, rreil_rvalue!{ addr:16 }))
+        }
+    }
+}
+
+/// Returns the statements computing the operand, the operand value and the statements writing
+/// back a modified value. Memory operands are loaded into `val`.
+pub fn place(op: &Operand) -> Result<(Vec<Statement>, Lvalue, Vec<Statement>)> {
+    match op {
+        &Operand::Register(ref reg) => Ok((vec![], reg.clone(), vec![])),
+        &Operand::Pair(p) => {
+            let (stmts, val) = read_pair(p)?;
+            let val = Lvalue::from_rvalue(val).unwrap();
+            let post = write_pair(p, val.clone().into())?;
+
+            Ok((stmts, val, post))
+        }
+        &Operand::Memory(a, sz) => {
+            let (mut stmts, addr) = address(a)?;
+            let val = temp("val", sz);
+
+            stmts.append(
+                &mut rreil!{
+                load/ram/le/(sz) (val), (addr);
+            }?
+            );
+
+            let post = rreil!{
+                store/ram/le/(sz) (val), (addr);
+            }?;
+
+            Ok((stmts, val, post))
+        }
+        &Operand::Port(a) => {
+            let (mut stmts, addr) = address(a)?;
+
+            stmts.append(
+                &mut rreil!{
+                load/io/le/8 val:8, (addr);
+            }?
+            );
+
+            let post = rreil!{
+                store/io/le/8 val:8, (addr);
+            }?;
+
+            Ok((stmts, rreil_lvalue!{ val:8 }, post))
+        }
+        &Operand::Immediate(_) => Err("immediate isn't writable".into()),
+    }
+}
+
+/// Reads the operand.
+pub fn read(op: &Operand) -> Result<(Vec<Statement>, Rvalue)> {
+    match op {
+        &Operand::Immediate(ref v) => Ok((vec![], v.clone())),
+        _ => {
+            let (stmts, val, _) = place(op)?;
+            Ok((stmts, val.into()))
+        }
+    }
+}
+
+/// Writes `v` to the operand.
+pub fn write(op: &Operand, v: Rvalue) -> Result<Vec<Statement>> {
+    match op {
+        &Operand::Register(ref reg) => {
+            rreil!{
+                mov (reg), (v);
+            }
+        }
+        &Operand::Pair(p) => write_pair(p, v),
+        &Operand::Memory(a, sz) => {
+            let (mut stmts, addr) = address(a)?;
+
+            stmts.append(
+                &mut rreil!{
+                store/ram/le/(sz) (v), (addr);
+            }?
+            );
+            Ok(stmts)
+        }
+        &Operand::Port(a) => {
+            let (mut stmts, addr) = address(a)?;
+
+            stmts.append(
+                &mut rreil!{
+                store/io/le/8 (v), (addr);
+            }?
+            );
+            Ok(stmts)
+        }
+        &Operand::Immediate(_) => Err("immediate isn't writable".into()),
+    }
+}
+
+// Sign, zero and parity flag of an 8 bit result. Clears H and N.
+fn szp(v: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        mov SF:1, (v.extract(1, 7)?);
+        cmpeq ZF:1, (v), [0]:8;
+        mov par:8, (v);
+        shr t:8, par:8, [4]:8;
+        xor par:8, par:8, t:8;
+        shr t:8, par:8, [2]:8;
+        xor par:8, par:8, t:8;
+        shr t:8, par:8, [1]:8;
+        xor par:8, par:8, t:8;
+        xor PV:1, par:1, [1]:1;
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+// Computes `a + b` or `a - b`, optionally with carry, into `res` and sets all flags.
+fn arith(a: Rvalue, b: Rvalue, sz: usize, subtract: bool, with_carry: bool) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        zext/(sz + 1) x:(sz + 1), (a);
+        zext/(sz + 1) y:(sz + 1), (b);
+        zext/(sz + 1) cy:(sz + 1), CF:1;
+    }?;
+
+    if !with_carry {
+        stmts.append(
+            &mut rreil!{
+            mov cy:(sz + 1), [0]:(sz + 1);
+        }?
+        );
+    }
+
+    if subtract {
+        stmts.append(
+            &mut rreil!{
+            sub wide:(sz + 1), x:(sz + 1), y:(sz + 1);
+            sub wide:(sz + 1), wide:(sz + 1), cy:(sz + 1);
+            mov res:(sz), wide:(sz);
+            xor ov:(sz), (a), (b);
+            xor ov2:(sz), (a), res:(sz);
+            mov NF:1, [1]:1;
+        }?
+        );
+    } else {
+        stmts.append(
+            &mut rreil!{
+            add wide:(sz + 1), x:(sz + 1), y:(sz + 1);
+            add wide:(sz + 1), wide:(sz + 1), cy:(sz + 1);
+            mov res:(sz), wide:(sz);
+            xor ov:(sz), (a), res:(sz);
+            xor ov2:(sz), (b), res:(sz);
+            mov NF:1, [0]:1;
+        }?
+        );
+    }
+
+    stmts.append(
+        &mut rreil!{
+        and ov:(sz), ov:(sz), ov2:(sz);
+        mov PV:1, ov:1/(sz - 1);
+        mov CF:1, wide:1/(sz);
+        xor half:(sz), (a), (b);
+        xor half:(sz), half:(sz), res:(sz);
+        mov HF:1, half:1/(sz - 4);
+        mov SF:1, res:1/(sz - 1);
+        cmpeq ZF:1, res:(sz), [0]:(sz);
+    }?
+    );
+
+    Ok(stmts)
+}
+
+pub fn nop(_: &mut Variant) -> Result<Vec<Statement>> {
+    Ok(vec![])
+}
+
+pub fn add(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = arith(A.clone().into(), r, 8, false, false)?;
+    stmts.append(&mut rreil!{ mov A:8, res:8; }?);
+    Ok(stmts)
+}
+
+pub fn adc(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = arith(A.clone().into(), r, 8, false, true)?;
+    stmts.append(&mut rreil!{ mov A:8, res:8; }?);
+    Ok(stmts)
+}
+
+pub fn sub(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = arith(A.clone().into(), r, 8, true, false)?;
+    stmts.append(&mut rreil!{ mov A:8, res:8; }?);
+    Ok(stmts)
+}
+
+pub fn sbc(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = arith(A.clone().into(), r, 8, true, true)?;
+    stmts.append(&mut rreil!{ mov A:8, res:8; }?);
+    Ok(stmts)
+}
+
+pub fn cp(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    arith(A.clone().into(), r, 8, true, false)
+}
+
+pub fn and(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        and A:8, A:8, (r);
+        mov CF:1, [0]:1;
+    }?;
+    stmts.append(&mut szp(A.clone().into())?);
+    stmts.append(&mut rreil!{ mov HF:1, [1]:1; }?);
+    Ok(stmts)
+}
+
+pub fn or(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        or A:8, A:8, (r);
+        mov CF:1, [0]:1;
+    }?;
+    stmts.append(&mut szp(A.clone().into())?);
+    Ok(stmts)
+}
+
+pub fn xor(_cg: &mut Variant, r: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        xor A:8, A:8, (r);
+        mov CF:1, [0]:1;
+    }?;
+    stmts.append(&mut szp(A.clone().into())?);
+    Ok(stmts)
+}
+
+pub fn inc8(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        add res:8, (r), [1]:8;
+        cmpeq PV:1, (r), [0x7f]:8;
+        xor half:8, (r), res:8;
+        mov HF:1, half:1/4;
+        mov NF:1, [0]:1;
+        mov (r), res:8;
+        mov SF:1, res:1/7;
+        cmpeq ZF:1, res:8, [0]:8;
+    }
+}
+
+pub fn dec8(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        sub res:8, (r), [1]:8;
+        cmpeq PV:1, (r), [0x80]:8;
+        xor half:8, (r), res:8;
+        mov HF:1, half:1/4;
+        mov NF:1, [1]:1;
+        mov (r), res:8;
+        mov SF:1, res:1/7;
+        cmpeq ZF:1, res:8, [0]:8;
+    }
+}
+
+pub fn inc16(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        add (r), (r), [1]:16;
+    }
+}
+
+pub fn dec16(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        sub (r), (r), [1]:16;
+    }
+}
+
+// ADD HL,rr only changes H, N and C
+pub fn add16(_cg: &mut Variant, r: Lvalue, v: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        zext/17 x:17, (r);
+        zext/17 y:17, (v);
+        add wide:17, x:17, y:17;
+        xor half:16, (r), (v);
+        xor half:16, half:16, wide:16;
+        mov HF:1, half:1/12;
+        mov CF:1, wide:1/16;
+        mov NF:1, [0]:1;
+        mov (r), wide:16;
+    }
+}
+
+pub fn adc16(_cg: &mut Variant, r: Lvalue, v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = arith(r.clone().into(), v, 16, false, true)?;
+    stmts.append(&mut rreil!{ mov (r), res:16; }?);
+    Ok(stmts)
+}
+
+pub fn sbc16(_cg: &mut Variant, r: Lvalue, v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = arith(r.clone().into(), v, 16, true, true)?;
+    stmts.append(&mut rreil!{ mov (r), res:16; }?);
+    Ok(stmts)
+}
+
+pub fn neg(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    let mut stmts = arith(rreil_rvalue!{ [0]:8 }, A.clone().into(), 8, true, false)?;
+    stmts.append(&mut rreil!{ mov A:8, res:8; }?);
+    Ok(stmts)
+}
+
+pub fn cpl(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        xor A:8, A:8, [0xff]:8;
+        mov HF:1, [1]:1;
+        mov NF:1, [1]:1;
+    }
+}
+
+// Decimal adjust isn't modeled.
+pub fn daa(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov A:8, ?;
+        mov SF:1, ?;
+        mov ZF:1, ?;
+        mov HF:1, ?;
+        mov PV:1, ?;
+        mov CF:1, ?;
+    }
+}
+
+pub fn scf(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov CF:1, [1]:1;
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+pub fn ccf(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov HF:1, CF:1;
+        xor CF:1, CF:1, [1]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+pub fn di(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov IFF1:1, [0]:1;
+        mov IFF2:1, [0]:1;
+    }
+}
+
+pub fn ei(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov IFF1:1, [1]:1;
+        mov IFF2:1, [1]:1;
+    }
+}
+
+pub fn retn(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov IFF1:1, IFF2:1;
+    }
+}
+
+// LD A,I and LD A,R
+pub fn ld_ir(_cg: &mut Variant, r: Lvalue, v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov (r), (v);
+    }?;
+    stmts.append(&mut szp(v)?);
+    stmts.append(&mut rreil!{ mov PV:1, IFF2:1; }?);
+    Ok(stmts)
+}
+
+// IN r,(C)
+pub fn in_c(_cg: &mut Variant, r: Lvalue, v: Rvalue) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        mov (r), (v);
+    }?;
+    stmts.append(&mut szp(v)?);
+    Ok(stmts)
+}
+
+fn swap(a: &Lvalue) -> Result<Vec<Statement>> {
+    let b = alternate(a);
+    let t = temp("t", a.size().unwrap());
+
+    rreil!{
+        mov (t), (a);
+        mov (a), (b);
+        mov (b), (t);
+    }
+}
+
+pub fn ex_af(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    for r in [&*A, &*SF, &*ZF, &*HF, &*PV, &*NF, &*CF].iter() {
+        stmts.append(&mut swap(r)?);
+    }
+    Ok(stmts)
+}
+
+pub fn exx(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    let mut stmts = vec![];
+
+    for r in [&*B, &*C, &*D, &*E, &*H, &*L].iter() {
+        stmts.append(&mut swap(r)?);
+    }
+    Ok(stmts)
+}
+
+pub fn rlca(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov CF:1, A:1/7;
+        shl A:8, A:8, [1]:8;
+        sel/0 A:8, CF:1;
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+pub fn rrca(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov CF:1, A:1;
+        shr A:8, A:8, [1]:8;
+        sel/7 A:8, CF:1;
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+pub fn rla(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov hb:1, A:1/7;
+        shl A:8, A:8, [1]:8;
+        sel/0 A:8, CF:1;
+        mov CF:1, hb:1;
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+pub fn rra(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        mov lb:1, A:1;
+        shr A:8, A:8, [1]:8;
+        sel/7 A:8, CF:1;
+        mov CF:1, lb:1;
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+fn shift(r: Lvalue, mut stmts: Vec<Statement>) -> Result<Vec<Statement>> {
+    stmts.append(&mut szp(r.into())?);
+    Ok(stmts)
+}
+
+pub fn rlc(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov CF:1, (r.extract(1, 7)?);
+        shl (r), (r), [1]:8;
+        sel/0 (r), CF:1;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn rrc(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov CF:1, (r.extract(1, 0)?);
+        shr (r), (r), [1]:8;
+        sel/7 (r), CF:1;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn rl(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov hb:1, (r.extract(1, 7)?);
+        shl (r), (r), [1]:8;
+        sel/0 (r), CF:1;
+        mov CF:1, hb:1;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn rr(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov lb:1, (r.extract(1, 0)?);
+        shr (r), (r), [1]:8;
+        sel/7 (r), CF:1;
+        mov CF:1, lb:1;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn sla(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov CF:1, (r.extract(1, 7)?);
+        shl (r), (r), [1]:8;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn sra(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov CF:1, (r.extract(1, 0)?);
+        shrs (r), (r), [1]:8;
+    }?;
+    shift(r, stmts)
+}
+
+// Undocumented. Like SLA but shifts in a one.
+pub fn sll(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov CF:1, (r.extract(1, 7)?);
+        shl (r), (r), [1]:8;
+        sel/0 (r), [1]:1;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn srl(_cg: &mut Variant, r: Lvalue) -> Result<Vec<Statement>> {
+    let stmts = rreil!{
+        mov CF:1, (r.extract(1, 0)?);
+        shr (r), (r), [1]:8;
+    }?;
+    shift(r, stmts)
+}
+
+pub fn bit(_cg: &mut Variant, r: Lvalue, b: usize) -> Result<Vec<Statement>> {
+    let sign = if b == 7 { 1 } else { 0 };
+
+    rreil!{
+        xor ZF:1, (r.extract(1, b)?), [1]:1;
+        mov PV:1, ZF:1;
+        xor t:1, ZF:1, [1]:1;
+        and SF:1, t:1, [sign]:1;
+        mov HF:1, [1]:1;
+        mov NF:1, [0]:1;
+    }
+}
+
+pub fn res(_cg: &mut Variant, r: Lvalue, b: usize) -> Result<Vec<Statement>> {
+    rreil!{
+        sel/(b) (r), [0]:1;
+    }
+}
+
+pub fn set(_cg: &mut Variant, r: Lvalue, b: usize) -> Result<Vec<Statement>> {
+    rreil!{
+        sel/(b) (r), [1]:1;
+    }
+}
+
+// RLD and RRD rotate nibbles between A and (HL)
+pub fn rld(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    let (mut stmts, hl) = read_pair(Pair::HL)?;
+
+    stmts.append(
+        &mut rreil!{
+        load/ram/le/8 val:8, (hl);
+        shr t:8, val:8, [4]:8;
+        shl val:8, val:8, [4]:8;
+        and lo:8, A:8, [0xf]:8;
+        or val:8, val:8, lo:8;
+        store/ram/le/8 val:8, (hl);
+        and A:8, A:8, [0xf0]:8;
+        or A:8, A:8, t:8;
+    }?
+    );
+    stmts.append(&mut szp(A.clone().into())?);
+    Ok(stmts)
+}
+
+pub fn rrd(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    let (mut stmts, hl) = read_pair(Pair::HL)?;
+
+    stmts.append(
+        &mut rreil!{
+        load/ram/le/8 val:8, (hl);
+        and t:8, val:8, [0xf]:8;
+        shr val:8, val:8, [4]:8;
+        shl lo:8, A:8, [4]:8;
+        or val:8, val:8, lo:8;
+        store/ram/le/8 val:8, (hl);
+        and A:8, A:8, [0xf0]:8;
+        or A:8, A:8, t:8;
+    }?
+    );
+    stmts.append(&mut szp(A.clone().into())?);
+    Ok(stmts)
+}
+
+// Adds `delta` to the register pair
+fn step(p: Pair, delta: i16) -> Result<Vec<Statement>> {
+    let (mut stmts, v) = read_pair(p)?;
+    let d = Rvalue::new_u16(delta as u16);
+
+    stmts.append(
+        &mut rreil!{
+        add res:16, (v), (d);
+    }?
+    );
+    stmts.append(&mut write_pair(p, rreil_rvalue!{ res:16 })?);
+    Ok(stmts)
+}
+
+// Decrements BC and sets PV if it's not zero
+fn count(mut stmts: Vec<Statement>) -> Result<Vec<Statement>> {
+    stmts.append(&mut step(Pair::BC, -1)?);
+    stmts.append(
+        &mut rreil!{
+        cmpeq t:1, res:16, [0]:16;
+        xor PV:1, t:1, [1]:1;
+    }?
+    );
+    Ok(stmts)
+}
+
+fn ld_block(delta: i16) -> Result<Vec<Statement>> {
+    let (mut stmts, hl) = read_pair(Pair::HL)?;
+    let (mut de_stmts, de) = read_pair(Pair::DE)?;
+
+    stmts.append(&mut de_stmts);
+    stmts.append(
+        &mut rreil!{
+        load/ram/le/8 val:8, (hl);
+        store/ram/le/8 val:8, (de);
+        mov HF:1, [0]:1;
+        mov NF:1, [0]:1;
+    }?
+    );
+    stmts.append(&mut step(Pair::HL, delta)?);
+    stmts.append(&mut step(Pair::DE, delta)?);
+    count(stmts)
+}
+
+fn cp_block(delta: i16) -> Result<Vec<Statement>> {
+    let (mut stmts, hl) = read_pair(Pair::HL)?;
+
+    stmts.append(
+        &mut rreil!{
+        load/ram/le/8 val:8, (hl);
+        mov cf:1, CF:1;
+    }?
+    );
+    stmts.append(&mut arith(A.clone().into(), rreil_rvalue!{ val:8 }, 8, true, false)?);
+    stmts.append(&mut rreil!{ mov CF:1, cf:1; }?);
+    stmts.append(&mut step(Pair::HL, delta)?);
+    count(stmts)
+}
+
+fn in_block(delta: i16) -> Result<Vec<Statement>> {
+    let (mut stmts, bc) = read_pair(Pair::BC)?;
+    let (mut hl_stmts, hl) = read_pair(Pair::HL)?;
+
+    stmts.append(&mut hl_stmts);
+    stmts.append(
+        &mut rreil!{
+        load/io/le/8 val:8, (bc);
+        store/ram/le/8 val:8, (hl);
+        sub B:8, B:8, [1]:8;
+        cmpeq ZF:1, B:8, [0]:8;
+        mov NF:1, [1]:1;
+    }?
+    );
+    stmts.append(&mut step(Pair::HL, delta)?);
+    Ok(stmts)
+}
+
+// The port address uses the decremented B
+fn out_block(delta: i16) -> Result<Vec<Statement>> {
+    let mut stmts = rreil!{
+        sub B:8, B:8, [1]:8;
+        cmpeq ZF:1, B:8, [0]:8;
+        mov NF:1, [1]:1;
+    }?;
+    let (mut bc_stmts, bc) = read_pair(Pair::BC)?;
+    let (mut hl_stmts, hl) = read_pair(Pair::HL)?;
+
+    stmts.append(&mut bc_stmts);
+    stmts.append(&mut hl_stmts);
+    stmts.append(
+        &mut rreil!{
+        load/ram/le/8 val:8, (hl);
+        store/io/le/8 val:8, (bc);
+    }?
+    );
+    stmts.append(&mut step(Pair::HL, delta)?);
+    Ok(stmts)
+}
+
+pub fn ldi(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    ld_block(1)
+}
+
+pub fn ldd(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    ld_block(-1)
+}
+
+pub fn cpi(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    cp_block(1)
+}
+
+pub fn cpd(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    cp_block(-1)
+}
+
+// CPIR and CPDR stop if BC is zero or A was found
+fn cp_repeat(mut stmts: Vec<Statement>) -> Result<Vec<Statement>> {
+    stmts.append(
+        &mut rreil!{
+        xor nz:1, ZF:1, [1]:1;
+        and rep:1, PV:1, nz:1;
+    }?
+    );
+    Ok(stmts)
+}
+
+pub fn cpir(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    cp_repeat(cp_block(1)?)
+}
+
+pub fn cpdr(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    cp_repeat(cp_block(-1)?)
+}
+
+pub fn ini(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    in_block(1)
+}
+
+pub fn ind(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    in_block(-1)
+}
+
+pub fn outi(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    out_block(1)
+}
+
+pub fn outd(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    out_block(-1)
+}
+
+pub fn push(_cg: &mut Variant, v: Rvalue) -> Result<Vec<Statement>> {
+    rreil!{
+        sub SP:16, SP:16, [2]:16;
+        store/ram/le/16 (v), SP:16;
+    }
+}
+
+pub fn pop(_cg: &mut Variant) -> Result<(Vec<Statement>, Rvalue)> {
+    let stmts = rreil!{
+        load/ram/le/16 val:16, SP:16;
+        add SP:16, SP:16, [2]:16;
+    }?;
+
+    Ok((stmts, rreil_rvalue!{ val:16 }))
+}
+
+pub fn djnz(_cg: &mut Variant) -> Result<Vec<Statement>> {
+    rreil!{
+        sub B:8, B:8, [1]:8;
+        cmpeq z:1, B:8, [0]:8;
+        xor nz:1, z:1, [1]:1;
+    }
+}
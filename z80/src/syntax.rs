@@ -0,0 +1,233 @@
+/*
+ * Panopticon - A libre disassembler
+ * Copyright (C) 2017  Panopticon authors
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+
+use disassembler::*;
+
+use panopticon_core::{Disassembler, State};
+use semantic::*;
+
+use std::sync::Arc;
+
+pub fn disassembler() -> Arc<Disassembler<Z80>> {
+    let imm8 = new_disassembler!(Z80 =>
+        [ "imm@........" ] = |st: &mut State<Z80>| {
+            st.configuration.imm = Some(st.get_group("imm") as u16);
+            true
+        });
+
+    let imm16 = new_disassembler!(Z80 =>
+        [ "immlo@........", "immhi@........" ] = |st: &mut State<Z80>| {
+            st.configuration.imm = Some(st.get_group("immlo") as u16 | ((st.get_group("immhi") as u16) << 8));
+            true
+        });
+
+    let disp = new_disassembler!(Z80 =>
+        [ "disp@........" ] = |st: &mut State<Z80>| {
+            st.configuration.disp = Some(st.get_group("disp") as u8 as i8);
+            true
+        });
+
+    // DD and FD replace HL with IX and IY, (HL) with (IX+d) and (IY+d).
+    let index = new_disassembler!(Z80 =>
+        [ 0xdd ] = |st: &mut State<Z80>| {
+            st.configuration.index = Some(Pair::IX);
+            true
+        },
+        [ 0xfd ] = |st: &mut State<Z80>| {
+            st.configuration.index = Some(Pair::IY);
+            true
+        });
+
+    new_disassembler!(Z80 =>
+        // 8 bit loads
+        [ "01 d@... s@..." ] = load("ld", Arg::RegMem("d"), Arg::RegMem("s")),	// 01 ddd sss, 0x76 is halt
+        [ "00 r@... 110", imm8 ] = load("ld", Arg::RegMem("r"), Arg::Imm8),	// 00 rrr 110 nnnn nnnn
+        [ 0x02 ] = load("ld", Arg::Ind(Pair::BC, 8), Arg::Fixed(&*A)),
+        [ 0x12 ] = load("ld", Arg::Ind(Pair::DE, 8), Arg::Fixed(&*A)),
+        [ 0x0a ] = load("ld", Arg::Fixed(&*A), Arg::Ind(Pair::BC, 8)),
+        [ 0x1a ] = load("ld", Arg::Fixed(&*A), Arg::Ind(Pair::DE, 8)),
+        [ 0x32, imm16 ] = load("ld", Arg::Abs(8), Arg::Fixed(&*A)),
+        [ 0x3a, imm16 ] = load("ld", Arg::Fixed(&*A), Arg::Abs(8)),
+        [ 0xed, 0x47 ] = load("ld", Arg::Fixed(&*I), Arg::Fixed(&*A)),
+        [ 0xed, 0x4f ] = load("ld", Arg::Fixed(&*R), Arg::Fixed(&*A)),
+        [ 0xed, 0x57 ] = binary("ld", Arg::Fixed(&*A), Arg::Fixed(&*I), ld_ir),
+        [ 0xed, 0x5f ] = binary("ld", Arg::Fixed(&*A), Arg::Fixed(&*R), ld_ir),
+
+        // 16 bit loads
+        [ "00 p@.. 0001", imm16 ] = load("ld", Arg::Pair("p"), Arg::Imm16),	// 00 pp0 001 nnnn nnnn nnnn nnnn
+        [ 0x22, imm16 ] = load("ld", Arg::Abs(16), Arg::Fix(Pair::HL)),
+        [ 0x2a, imm16 ] = load("ld", Arg::Fix(Pair::HL), Arg::Abs(16)),
+        [ 0xed, "01 p@.. 0011", imm16 ] = load("ld", Arg::Abs(16), Arg::Pair("p")),
+        [ 0xed, "01 p@.. 1011", imm16 ] = load("ld", Arg::Pair("p"), Arg::Abs(16)),
+        [ 0xf9 ] = load("ld", Arg::Fix(Pair::SP), Arg::Fix(Pair::HL)),
+        [ "11 p@.. 0101" ] = source("push", Arg::Stack("p"), push),	// 11 pp0 101
+        [ "11 p@.. 0001" ] = sink("pop", Arg::Stack("p"), pop),	// 11 pp0 001
+
+        // Exchanges
+        [ 0x08 ] = literal("ex", "af, af'", ex_af),
+        [ 0xd9 ] = nonary("exx", exx),
+        [ 0xeb ] = exchange("ex", Arg::Fix(Pair::DE), Arg::Fix(Pair::HL)),
+        [ 0xe3 ] = exchange("ex", Arg::Ind(Pair::SP, 16), Arg::Fix(Pair::HL)),
+
+        // Block transfer and search
+        [ 0xed, 0xa0 ] = nonary("ldi", ldi),
+        [ 0xed, 0xa8 ] = nonary("ldd", ldd),
+        [ 0xed, 0xb0 ] = repeat("ldir", ldi, rreil_rvalue!{ PV:1 }, true),
+        [ 0xed, 0xb8 ] = repeat("lddr", ldd, rreil_rvalue!{ PV:1 }, true),
+        [ 0xed, 0xa1 ] = nonary("cpi", cpi),
+        [ 0xed, 0xa9 ] = nonary("cpd", cpd),
+        [ 0xed, 0xb1 ] = repeat("cpir", cpir, rreil_rvalue!{ rep:1 }, true),
+        [ 0xed, 0xb9 ] = repeat("cpdr", cpdr, rreil_rvalue!{ rep:1 }, true),
+
+        // 8 bit arithmetic
+        [ "10 000 r@..." ] = source("add", Arg::RegMem("r"), add),	// 10 000 rrr
+        [ "10 001 r@..." ] = source("adc", Arg::RegMem("r"), adc),	// 10 001 rrr
+        [ "10 010 r@..." ] = source("sub", Arg::RegMem("r"), sub),	// 10 010 rrr
+        [ "10 011 r@..." ] = source("sbc", Arg::RegMem("r"), sbc),	// 10 011 rrr
+        [ "10 100 r@..." ] = source("and", Arg::RegMem("r"), and),	// 10 100 rrr
+        [ "10 101 r@..." ] = source("xor", Arg::RegMem("r"), xor),	// 10 101 rrr
+        [ "10 110 r@..." ] = source("or", Arg::RegMem("r"), or),	// 10 110 rrr
+        [ "10 111 r@..." ] = source("cp", Arg::RegMem("r"), cp),	// 10 111 rrr
+        [ 0xc6, imm8 ] = source("add", Arg::Imm8, add),
+        [ 0xce, imm8 ] = source("adc", Arg::Imm8, adc),
+        [ 0xd6, imm8 ] = source("sub", Arg::Imm8, sub),
+        [ 0xde, imm8 ] = source("sbc", Arg::Imm8, sbc),
+        [ 0xe6, imm8 ] = source("and", Arg::Imm8, and),
+        [ 0xee, imm8 ] = source("xor", Arg::Imm8, xor),
+        [ 0xf6, imm8 ] = source("or", Arg::Imm8, or),
+        [ 0xfe, imm8 ] = source("cp", Arg::Imm8, cp),
+        [ "00 r@... 100" ] = unary("inc", Arg::RegMem("r"), inc8),	// 00 rrr 100
+        [ "00 r@... 101" ] = unary("dec", Arg::RegMem("r"), dec8),	// 00 rrr 101
+
+        // General purpose arithmetic and CPU control
+        [ 0x27 ] = nonary("daa", daa),
+        [ 0x2f ] = nonary("cpl", cpl),
+        [ 0xed, 0x44 ] = nonary("neg", neg),
+        [ 0x3f ] = nonary("ccf", ccf),
+        [ 0x37 ] = nonary("scf", scf),
+        [ 0x00 ] = nonary("nop", nop),
+        [ 0x76 ] = nonary("halt", nop),
+        [ 0xf3 ] = nonary("di", di),
+        [ 0xfb ] = nonary("ei", ei),
+        [ 0xed, 0x46 ] = literal("im", "0", nop),
+        [ 0xed, 0x56 ] = literal("im", "1", nop),
+        [ 0xed, 0x5e ] = literal("im", "2", nop),
+
+        // 16 bit arithmetic
+        [ "00 p@.. 1001" ] = binary("add", Arg::Fix(Pair::HL), Arg::Pair("p"), add16),	// 00 pp1 001
+        [ 0xed, "01 p@.. 1010" ] = binary("adc", Arg::Fix(Pair::HL), Arg::Pair("p"), adc16),	// 01 pp1 010
+        [ 0xed, "01 p@.. 0010" ] = binary("sbc", Arg::Fix(Pair::HL), Arg::Pair("p"), sbc16),	// 01 pp0 010
+        [ "00 p@.. 0011" ] = unary("inc", Arg::Pair("p"), inc16),	// 00 pp0 011
+        [ "00 p@.. 1011" ] = unary("dec", Arg::Pair("p"), dec16),	// 00 pp1 011
+
+        // Rotate and shift
+        [ 0x07 ] = nonary("rlca", rlca),
+        [ 0x17 ] = nonary("rla", rla),
+        [ 0x0f ] = nonary("rrca", rrca),
+        [ 0x1f ] = nonary("rra", rra),
+        [ 0xcb, "00 000 r@..." ] = unary("rlc", Arg::RegMem("r"), rlc),
+        [ 0xcb, "00 001 r@..." ] = unary("rrc", Arg::RegMem("r"), rrc),
+        [ 0xcb, "00 010 r@..." ] = unary("rl", Arg::RegMem("r"), rl),
+        [ 0xcb, "00 011 r@..." ] = unary("rr", Arg::RegMem("r"), rr),
+        [ 0xcb, "00 100 r@..." ] = unary("sla", Arg::RegMem("r"), sla),
+        [ 0xcb, "00 101 r@..." ] = unary("sra", Arg::RegMem("r"), sra),
+        [ 0xcb, "00 110 r@..." ] = unary("sll!", Arg::RegMem("r"), sll),
+        [ 0xcb, "00 111 r@..." ] = unary("srl", Arg::RegMem("r"), srl),
+        [ 0xed, 0x6f ] = nonary("rld", rld),
+        [ 0xed, 0x67 ] = nonary("rrd", rrd),
+
+        // Bit set, reset and test
+        [ 0xcb, "01 b@... r@..." ] = single_bit("bit", Arg::RegMem("r"), bit),	// 01 bbb rrr
+        [ 0xcb, "10 b@... r@..." ] = single_bit("res", Arg::RegMem("r"), res),	// 10 bbb rrr
+        [ 0xcb, "11 b@... r@..." ] = single_bit("set", Arg::RegMem("r"), set),	// 11 bbb rrr
+
+        // Jumps
+        [ 0xc3, imm16 ] = jump,
+        [ "11 c@... 010", imm16 ] = jump,	// 11 ccc 010 nnnn nnnn nnnn nnnn
+        [ 0x18, disp ] = jump_relative,
+        [ "001 c@.. 000", disp ] = jump_relative,	// 001 cc 000 eeee eeee
+        [ 0xe9 ] = jump_indirect,
+        [ 0x10, disp ] = decrement_jump,
+
+        // Call and return
+        [ 0xcd, imm16 ] = call,
+        [ "11 c@... 100", imm16 ] = call,	// 11 ccc 100 nnnn nnnn nnnn nnnn
+        [ 0xc9 ] = ret("ret", nop),
+        [ "11 c@... 000" ] = ret("ret", nop),	// 11 ccc 000
+        [ 0xed, 0x4d ] = ret("reti", nop),
+        [ 0xed, 0x45 ] = ret("retn", retn),
+        [ "11 t@... 111" ] = rst,	// 11 ttt 111
+
+        // Input and output
+        [ 0xdb, imm8 ] = load("in", Arg::Fixed(&*A), Arg::Port),
+        [ 0xed, "01 r@... 000" ] = binary("in", Arg::Reg("r"), Arg::PortC, in_c),	// 01 rrr 000
+        [ 0xed, 0xa2 ] = nonary("ini", ini),
+        [ 0xed, 0xaa ] = nonary("ind", ind),
+        [ 0xed, 0xb2 ] = repeat("inir", ini, rreil_rvalue!{ ZF:1 }, false),
+        [ 0xed, 0xba ] = repeat("indr", ind, rreil_rvalue!{ ZF:1 }, false),
+        [ 0xd3, imm8 ] = load("out", Arg::Port, Arg::Fixed(&*A)),
+        [ 0xed, "01 r@... 001" ] = load("out", Arg::PortC, Arg::Reg("r")),	// 01 rrr 001
+        [ 0xed, 0xa3 ] = nonary("outi", outi),
+        [ 0xed, 0xab ] = nonary("outd", outd),
+        [ 0xed, 0xb3 ] = repeat("otir", outi, rreil_rvalue!{ ZF:1 }, false),
+        [ 0xed, 0xbb ] = repeat("otdr", outd, rreil_rvalue!{ ZF:1 }, false),
+
+        // IX and IY
+        [ index, 0x21, imm16 ] = load("ld", Arg::Fix(Pair::HL), Arg::Imm16),
+        [ index, 0x22, imm16 ] = load("ld", Arg::Abs(16), Arg::Fix(Pair::HL)),
+        [ index, 0x2a, imm16 ] = load("ld", Arg::Fix(Pair::HL), Arg::Abs(16)),
+        [ index, 0xf9 ] = load("ld", Arg::Fix(Pair::SP), Arg::Fix(Pair::HL)),
+        [ index, 0xe5 ] = source("push", Arg::Fix(Pair::HL), push),
+        [ index, 0xe1 ] = sink("pop", Arg::Fix(Pair::HL), pop),
+        [ index, 0xe3 ] = exchange("ex", Arg::Ind(Pair::SP, 16), Arg::Fix(Pair::HL)),
+        [ index, "00 p@.. 1001" ] = binary("add", Arg::Fix(Pair::HL), Arg::Pair("p"), add16),
+        [ index, 0x23 ] = unary("inc", Arg::Fix(Pair::HL), inc16),
+        [ index, 0x2b ] = unary("dec", Arg::Fix(Pair::HL), dec16),
+        [ index, 0xe9 ] = jump_indirect,
+
+        // (IX+d) and (IY+d)
+        [ index, "01 d@... s@110", disp ] = load("ld", Arg::Reg("d"), Arg::RegMem("s")),	// 01 ddd 110 dddd dddd
+        [ index, "01 d@110 s@...", disp ] = load("ld", Arg::RegMem("d"), Arg::Reg("s")),	// 01 110 sss dddd dddd
+        [ index, "00 r@110 110", disp, imm8 ] = load("ld", Arg::RegMem("r"), Arg::Imm8),
+        [ index, "10 000 r@110", disp ] = source("add", Arg::RegMem("r"), add),
+        [ index, "10 001 r@110", disp ] = source("adc", Arg::RegMem("r"), adc),
+        [ index, "10 010 r@110", disp ] = source("sub", Arg::RegMem("r"), sub),
+        [ index, "10 011 r@110", disp ] = source("sbc", Arg::RegMem("r"), sbc),
+        [ index, "10 100 r@110", disp ] = source("and", Arg::RegMem("r"), and),
+        [ index, "10 101 r@110", disp ] = source("xor", Arg::RegMem("r"), xor),
+        [ index, "10 110 r@110", disp ] = source("or", Arg::RegMem("r"), or),
+        [ index, "10 111 r@110", disp ] = source("cp", Arg::RegMem("r"), cp),
+        [ index, "00 r@110 100", disp ] = unary("inc", Arg::RegMem("r"), inc8),
+        [ index, "00 r@110 101", disp ] = unary("dec", Arg::RegMem("r"), dec8),
+        [ index, 0xcb, disp, "00 000 r@110" ] = unary("rlc", Arg::RegMem("r"), rlc),
+        [ index, 0xcb, disp, "00 001 r@110" ] = unary("rrc", Arg::RegMem("r"), rrc),
+        [ index, 0xcb, disp, "00 010 r@110" ] = unary("rl", Arg::RegMem("r"), rl),
+        [ index, 0xcb, disp, "00 011 r@110" ] = unary("rr", Arg::RegMem("r"), rr),
+        [ index, 0xcb, disp, "00 100 r@110" ] = unary("sla", Arg::RegMem("r"), sla),
+        [ index, 0xcb, disp, "00 101 r@110" ] = unary("sra", Arg::RegMem("r"), sra),
+        [ index, 0xcb, disp, "00 110 r@110" ] = unary("sll!", Arg::RegMem("r"), sll),
+        [ index, 0xcb, disp, "00 111 r@110" ] = unary("srl", Arg::RegMem("r"), srl),
+        [ index, 0xcb, disp, "01 b@... r@110" ] = single_bit("bit", Arg::RegMem("r"), bit),
+        [ index, 0xcb, disp, "10 b@... r@110" ] = single_bit("res", Arg::RegMem("r"), res),
+        [ index, 0xcb, disp, "11 b@... r@110" ] = single_bit("set", Arg::RegMem("r"), set),
+
+        // Prefixes w/o a following IX/IY instruction act as NOP.
+        _ = nonary("unk", nop)
+    )
+}